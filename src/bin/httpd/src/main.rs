@@ -1,14 +1,15 @@
 /// A simple HTTP(s) server. Supports minimal HTTP 1.1 functionality,
-/// basically serving static html/png content. The main focus is on
-/// stability.
+/// basically serving static files (with ranges and conditional GETs)
+/// and, optionally, directory listings. The main focus is on stability.
 use clap::Parser;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{Read, Seek, SeekFrom};
 use std::net::TcpListener;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 #[derive(Parser)]
 struct Args {
@@ -16,6 +17,8 @@ struct Args {
     addr: std::net::SocketAddr,
     #[arg(short, long)]
     dir: String, // The directory to serve content from.
+    #[arg(long)]
+    dir_listing: bool, // List directories that don't have index.html.
 
     #[arg(long)]
     ssl_cert: Option<String>,
@@ -25,11 +28,12 @@ struct Args {
 
 // Intercept Ctrl+C ourselves if the OS does not do it for us.
 fn input_listener() {
-    use std::io::Read;
-
     loop {
         let mut input = [0_u8; 16];
         let sz = std::io::stdin().read(&mut input).unwrap();
+        if sz == 0 {
+            return; // No terminal, e.g. when run as a service.
+        }
         for b in &input[0..sz] {
             if *b == 3 {
                 println!("\ncaught ^C: exiting.");
@@ -40,113 +44,226 @@ fn input_listener() {
 }
 
 static ROOT_DIR: Mutex<String> = Mutex::new(String::new());
-static TXT_FILE_CACHE: Mutex<Option<HashMap<PathBuf, String>>> = Mutex::new(None);
-static IMG_FILE_CACHE: Mutex<Option<HashMap<PathBuf, Vec<u8>>>> = Mutex::new(None);
-static BAD_FILE_CACHE: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
-const MAX_BAD_CACHE_LEN: usize = 4096;
+static DIR_LISTING: AtomicBool = AtomicBool::new(false);
+static FILE_CACHE: Mutex<Option<FileCache>> = Mutex::new(None);
+const MAX_CACHED_FILE_LEN: u64 = 1 << 20; // Larger files are streamed from disk.
+const MAX_CACHE_LEN: usize = 64 << 20;
 const MAX_HEADER_LEN: usize = 4096 * 4;
 const MAX_HEADERS: usize = 64;
 
-fn get_txt_file(pb: PathBuf) -> Option<String> {
-    // Try cache hit.
-    {
-        let mut cache = TXT_FILE_CACHE.lock().unwrap();
-        if cache.is_none() {
-            *cache = Some(HashMap::new());
-        }
+struct CachedFile {
+    modified: SystemTime,
+    bytes: Arc<Vec<u8>>,
+}
 
-        let map = cache.as_mut().unwrap();
-        match map.get(&pb) {
-            Some(s) => return Some(s.clone()),
-            None => {}
-        }
+struct FileCache {
+    files: HashMap<PathBuf, CachedFile>,
+    total_len: usize,
+}
+
+// Returns the contents of a small file, from the cache if it is up to date.
+fn get_cached_file(pb: &Path, metadata: &std::fs::Metadata) -> Option<Arc<Vec<u8>>> {
+    if metadata.len() > MAX_CACHED_FILE_LEN {
+        return None;
     }
+    let modified = metadata.modified().ok()?;
 
-    let mut bad_cache_full = false;
-    // Try cache miss.
+    // Try cache hit.
     {
-        let bad_cache = BAD_FILE_CACHE.lock().unwrap();
-        if let Some(set) = &*bad_cache {
-            if set.len() >= MAX_BAD_CACHE_LEN {
-                bad_cache_full = true;
-            }
-            if set.contains(&pb) {
-                return None;
+        let cache = FILE_CACHE.lock().unwrap();
+        if let Some(cached) = cache.as_ref().and_then(|cache| cache.files.get(pb)) {
+            if cached.modified == modified && cached.bytes.len() as u64 == metadata.len() {
+                return Some(cached.bytes.clone());
             }
         }
     }
 
     // Try reading the file.
-    if let Ok(s) = std::fs::read_to_string(pb.clone()) {
-        TXT_FILE_CACHE
-            .lock()
-            .unwrap()
-            .as_mut()
-            .unwrap()
-            .insert(pb, s.clone());
-        Some(s)
-    } else {
-        if !bad_cache_full {
-            let mut bad_cache = BAD_FILE_CACHE.lock().unwrap();
-            if bad_cache.is_none() {
-                *bad_cache = Some(HashSet::new());
-            }
-            bad_cache.as_mut().unwrap().insert(pb);
-        }
-        None
+    let bytes = Arc::new(std::fs::read(pb).ok()?);
+    let mut cache = FILE_CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(|| FileCache {
+        files: HashMap::new(),
+        total_len: 0,
+    });
+    if let Some(stale) = cache.files.remove(pb) {
+        cache.total_len -= stale.bytes.len();
+    }
+    if cache.total_len + bytes.len() <= MAX_CACHE_LEN {
+        cache.total_len += bytes.len();
+        cache.files.insert(
+            pb.to_owned(),
+            CachedFile {
+                modified,
+                bytes: bytes.clone(),
+            },
+        );
     }
+    Some(bytes)
 }
 
-fn get_img_file(pb: PathBuf) -> Option<Vec<u8>> {
-    // Try cache hit.
-    {
-        let mut cache = IMG_FILE_CACHE.lock().unwrap();
-        if cache.is_none() {
-            *cache = Some(HashMap::new());
-        }
+fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "html" | "htm" => "text/html;charset=UTF-8",
+        "css" => "text/css;charset=UTF-8",
+        "js" | "mjs" => "text/javascript;charset=UTF-8",
+        "txt" | "md" | "cfg" | "log" | "rs" | "toml" => "text/plain;charset=UTF-8",
+        "csv" => "text/csv;charset=UTF-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
 
-        let map = cache.as_mut().unwrap();
-        match map.get(&pb) {
-            Some(v) => return Some(v.clone()),
-            None => {}
-        }
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Formats a timestamp as an IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT".
+fn http_date(unix_secs: u64) -> String {
+    let dt = time::OffsetDateTime::from_unix_timestamp(unix_secs as i64)
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[dt.weekday().number_days_from_monday() as usize],
+        dt.day(),
+        MONTHS[dt.month() as usize - 1],
+        dt.year(),
+        dt.hour(),
+        dt.minute(),
+        dt.second()
+    )
+}
+
+// Parses an IMF-fixdate. The obsolete RFC 850 and asctime formats are not
+// supported: conditional requests with them are served unconditionally.
+fn parse_http_date(s: &str) -> Option<u64> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    if words.len() != 6 || words[5] != "GMT" {
+        return None;
+    }
+    let day: u8 = words[1].parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == words[2])? as u8 + 1;
+    let year: i32 = words[3].parse().ok()?;
+    let hms: Vec<u8> = words[4]
+        .split(':')
+        .map(|num| num.parse().ok())
+        .collect::<Option<_>>()?;
+    if hms.len() != 3 {
+        return None;
     }
 
-    let mut bad_cache_full = false;
-    // Try cache miss.
-    {
-        let bad_cache = BAD_FILE_CACHE.lock().unwrap();
-        if let Some(set) = &*bad_cache {
-            if set.len() >= MAX_BAD_CACHE_LEN {
-                bad_cache_full = true;
-            }
-            if set.contains(&pb) {
-                return None;
-            }
+    let dt = time::Date::from_calendar_date(year, month.try_into().ok()?, day)
+        .ok()?
+        .with_hms(hms[0], hms[1], hms[2])
+        .ok()?
+        .assume_utc();
+    dt.unix_timestamp().try_into().ok()
+}
+
+// Parses a single "bytes=" range into inclusive (start, end) offsets.
+// Returns None if the header should be ignored (multiple or malformed ranges),
+// and Some(Err(())) if the range cannot be satisfied.
+fn parse_range(header: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let range = header.trim().strip_prefix("bytes=")?;
+    if range.contains(',') {
+        return None;
+    }
+    let (start, end) = range.trim().split_once('-')?;
+
+    if start.is_empty() {
+        // The last N bytes.
+        let suffix_len: u64 = end.parse().ok()?;
+        if suffix_len == 0 || len == 0 {
+            return Some(Err(()));
         }
+        return Some(Ok((len.saturating_sub(suffix_len), len - 1)));
     }
 
-    // Try reading the file.
-    if let Ok(bytes) = std::fs::read(pb.clone()) {
-        IMG_FILE_CACHE
-            .lock()
-            .unwrap()
-            .as_mut()
-            .unwrap()
-            .insert(pb, bytes.clone());
-        Some(bytes)
+    let start: u64 = start.parse().ok()?;
+    let end: u64 = if end.is_empty() {
+        u64::MAX
     } else {
-        if !bad_cache_full {
-            let mut bad_cache = BAD_FILE_CACHE.lock().unwrap();
-            if bad_cache.is_none() {
-                *bad_cache = Some(HashSet::new());
-            }
-            bad_cache.as_mut().unwrap().insert(pb);
+        end.parse().ok()?
+    };
+    if end < start {
+        return None;
+    }
+    if start >= len {
+        return Some(Err(()));
+    }
+    Some(Ok((start, end.min(len - 1))))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' {
+            let hex = std::str::from_utf8(bytes.get((idx + 1)..(idx + 3))?).ok()?;
+            res.push(u8::from_str_radix(hex, 16).ok()?);
+            idx += 3;
+        } else {
+            res.push(bytes[idx]);
+            idx += 1;
         }
+    }
+    let res = String::from_utf8(res).ok()?;
+    if res.contains('\0') {
         None
+    } else {
+        Some(res)
     }
 }
 
+fn percent_encode(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{:02X}", b));
+        }
+    }
+    res
+}
+
+fn html_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
 fn sanitize(s: &[u8]) -> String {
     let mut res = String::new();
     res.reserve(64);
@@ -176,13 +293,26 @@ fn log_request(status: u16, request: &[u8]) {
     );
 }
 
-// Only GET requests are currently supported.
+// Only GET and HEAD requests are currently supported.
 struct HttpRequest {
     url: String,
-    #[allow(unused)]
+    head: bool, // Send headers only.
     headers: Vec<String>,
 }
 
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find_map(|line| {
+            let (key, val) = line.split_once(':')?;
+            if key.trim().eq_ignore_ascii_case(name) {
+                Some(val.trim())
+            } else {
+                None
+            }
+        })
+    }
+}
+
 fn read_line(reader: &mut dyn std::io::Read) -> Result<String, u32> {
     let mut line = String::new();
 
@@ -205,6 +335,11 @@ fn read_line(reader: &mut dyn std::io::Read) -> Result<String, u32> {
         }
 
         if b == b'\r' {
+            // Consume the '\n' as well: closing a socket with unread
+            // bytes resets the connection and truncates the response.
+            if reader.read(&mut buf).map_err(|_| 400_u32)? != 1 || buf[0] != b'\n' {
+                return Err(400);
+            }
             break;
         }
 
@@ -237,9 +372,11 @@ fn read_request(reader: &mut dyn std::io::Read) -> Result<HttpRequest, u32> {
     let line = read_line(reader)?;
     let (method, url) = parse_status_line(line.as_str())?;
 
-    if method != "GET" {
-        return Err(421); // Misdirected.
-    }
+    let head = match method {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return Err(405),
+    };
 
     let mut headers = vec![];
     loop {
@@ -254,103 +391,256 @@ fn read_request(reader: &mut dyn std::io::Read) -> Result<HttpRequest, u32> {
         headers.push(line);
     }
 
-    Ok(HttpRequest { url, headers })
+    Ok(HttpRequest { url, head, headers })
+}
+
+fn write_headers(
+    status: &str,
+    headers: &[(&str, String)],
+    writer: &mut dyn std::io::Write,
+) -> Result<(), ()> {
+    let mut response = format!("HTTP/1.1 {status}\r\n");
+    for (name, val) in headers {
+        response.push_str(&format!("{name}: {val}\r\n"));
+    }
+    response.push_str("Connection: close\r\n\r\n");
+
+    writer.write_all(response.as_bytes()).map_err(|err| {
+        println!("write headers failed with err {:?}", err);
+    })
+}
+
+fn write_body(bytes: &[u8], writer: &mut dyn std::io::Write) -> Result<(), ()> {
+    writer.write_all(bytes).map_err(|err| {
+        println!("write bytes failed with err {:?}", err);
+    })?;
+    writer.flush().map_err(|err| {
+        println!("writer flush failed with err {:?}", err);
+    })
 }
 
 fn handle_request(request: HttpRequest, writer: &mut dyn std::io::Write) -> Result<(), ()> {
     let root = ROOT_DIR.lock().unwrap().clone();
 
-    let request_path = {
-        let mut url = request.url.as_str();
-        if url.find("..").is_some() {
-            // Somebody is naughty.
-            log_request(400, request.url.as_bytes());
-            return write_error(400, writer);
-        }
-
-        if url.find("?").is_some() {
-            // We don't accept/support dynamic content.
-            log_request(421, request.url.as_bytes());
-            return write_error(421, writer); // Misdirected request.
-        }
+    if request.url.contains('?') {
+        // We don't accept/support dynamic content.
+        log_request(421, request.url.as_bytes());
+        return write_error(421, writer); // Misdirected request.
+    }
 
-        if url == "/" {
-            Path::new(root.as_str()).join("index.html")
-        } else {
-            while url.starts_with('/') {
-                url = &url[1..];
-            }
-            if url.is_empty() {
-                log_request(404, url.as_bytes());
-                return write_error(404, writer);
-            }
-            Path::new(root.as_str()).join(url)
-        }
+    let Some(url) = percent_decode(request.url.as_str()) else {
+        log_request(400, request.url.as_bytes());
+        return write_error(400, writer);
     };
+    if url.contains("..") || !url.starts_with('/') {
+        // Somebody is naughty.
+        log_request(400, request.url.as_bytes());
+        return write_error(400, writer);
+    }
 
-    let path_str = request_path.clone().into_os_string().into_string().unwrap();
-    if path_str.len() > 256 {
+    let request_path = Path::new(root.as_str()).join(url.trim_start_matches('/'));
+    let path_str = request_path.to_string_lossy();
+    if path_str.len() > 256 || path_str.find("..").is_some() {
         log_request(404, request.url.as_bytes());
         return write_error(404, writer);
     }
 
-    if path_str.find("..").is_some() {
+    let Ok(metadata) = std::fs::metadata(&request_path) else {
         log_request(404, request.url.as_bytes());
         return write_error(404, writer);
+    };
+    if !metadata.is_dir() {
+        return serve_file(&request, &request_path, &metadata, writer);
+    }
+
+    if !url.ends_with('/') {
+        // Relative links in the index need the trailing slash.
+        log_request(301, request.url.as_bytes());
+        write_headers(
+            "301 Moved Permanently",
+            &[
+                ("Location", format!("{}/", request.url)),
+                ("Content-Length", "0".to_owned()),
+            ],
+            writer,
+        )?;
+        return write_body(&[], writer);
+    }
+
+    let index_path = request_path.join("index.html");
+    if let Ok(metadata) = std::fs::metadata(&index_path) {
+        if metadata.is_file() {
+            return serve_file(&request, &index_path, &metadata, writer);
+        }
     }
 
-    if path_str.as_str().ends_with(".png") {
-        if let Some(bytes) = get_img_file(request_path.clone()) {
+    if DIR_LISTING.load(Ordering::Relaxed) {
+        if let Ok(listing) = dir_listing(&url, &request_path) {
             log_request(200, request.url.as_bytes());
+            write_headers(
+                "200 OK",
+                &[
+                    ("Content-type", "text/html;charset=UTF-8".to_owned()),
+                    ("Content-Length", listing.len().to_string()),
+                ],
+                writer,
+            )?;
+            let body = if request.head { "" } else { listing.as_str() };
+            return write_body(body.as_bytes(), writer);
+        }
+    }
+
+    log_request(404, request.url.as_bytes());
+    write_error(404, writer)
+}
+
+fn serve_file(
+    request: &HttpRequest,
+    path: &Path,
+    metadata: &std::fs::Metadata,
+    writer: &mut dyn std::io::Write,
+) -> Result<(), ()> {
+    let len = metadata.len();
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let etag = format!("\"{:x}-{:x}\"", len, modified.as_nanos());
+    let last_modified = http_date(modified.as_secs());
+
+    // If-None-Match takes precedence over If-Modified-Since (RFC 9110, 13.2.2).
+    let not_modified = if let Some(tags) = request.header("If-None-Match") {
+        tags == "*"
+            || tags
+                .split(',')
+                .any(|tag| tag.trim().trim_start_matches("W/") == etag)
+    } else if let Some(since) = request.header("If-Modified-Since") {
+        parse_http_date(since).is_some_and(|since| modified.as_secs() <= since)
+    } else {
+        false
+    };
+    if not_modified {
+        log_request(304, request.url.as_bytes());
+        write_headers(
+            "304 Not Modified",
+            &[("ETag", etag), ("Last-Modified", last_modified)],
+            writer,
+        )?;
+        return write_body(&[], writer);
+    }
 
-            writer.write_all(format!("HTTP/1.1 200 OK\r\nContent-type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
-                        bytes.len()
-                    )
-                    .as_bytes(),
-                )
-                .map_err(|err| {
-                    println!("write headers failed with erro {:?}", err);
-                    ()
-                })?;
-            writer.write_all(&bytes).map_err(|err| {
-                println!("write bytes failed with err {:?}", err);
-                ()
-            })?;
-            return writer.flush().map_err(|err| {
-                println!("writer flush failed with err {:?}", err);
-                ()
-            });
+    // A Range with a stale If-Range validator gets the whole file.
+    let range = request.header("Range").filter(|_| {
+        request.header("If-Range").map_or(true, |validator| {
+            validator == etag || validator == last_modified
+        })
+    });
+    let mut headers = vec![
+        ("Content-type", mime_type(path).to_owned()),
+        ("Accept-Ranges", "bytes".to_owned()),
+        ("ETag", etag),
+        ("Last-Modified", last_modified),
+    ];
+    let (code, status, start, end) = match range.and_then(|range| parse_range(range, len)) {
+        None => (200, "200 OK", 0, len),
+        Some(Ok((start, end))) => {
+            headers.push(("Content-Range", format!("bytes {start}-{end}/{len}")));
+            (206, "206 Partial Content", start, end + 1)
         }
-        log_request(404, request.url.as_bytes());
-        return write_error(404, writer);
+        Some(Err(())) => {
+            log_request(416, request.url.as_bytes());
+            write_headers(
+                "416 Range Not Satisfiable",
+                &[
+                    ("Content-Range", format!("bytes */{len}")),
+                    ("Content-Length", "0".to_owned()),
+                ],
+                writer,
+            )?;
+            return write_body(&[], writer);
+        }
+    };
+    headers.push(("Content-Length", (end - start).to_string()));
+
+    let cached = get_cached_file(path, metadata);
+    let file = if cached.is_none() {
+        match File::open(path) {
+            Ok(file) => Some(file),
+            Err(_) => {
+                log_request(404, request.url.as_bytes());
+                return write_error(404, writer);
+            }
+        }
+    } else {
+        None
+    };
+
+    log_request(code, request.url.as_bytes());
+    write_headers(status, &headers, writer)?;
+    if request.head {
+        return write_body(&[], writer);
     }
 
-    if !path_str.as_str().ends_with(".html") {
-        log_request(404, request.url.as_bytes());
-        return write_error(404, writer);
+    if let Some(bytes) = cached {
+        // The file could have been truncated since metadata() was called.
+        let end = (end as usize).min(bytes.len());
+        let start = (start as usize).min(end);
+        return write_body(&bytes[start..end], writer);
     }
 
-    if let Some(text) = get_txt_file(request_path.clone()) {
-        log_request(200, request.url.as_bytes());
-        let bytes = text.as_bytes();
-
-        writer.write_all(format!("HTTP/1.1 200 OK\r\nContent-type: text/html;charset=UTF-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
-            bytes.len()).as_bytes()).map_err(|err| {
-                println!("write headers failed with erro {:?}", err);
-                ()
-            })?;
-        writer.write_all(bytes).map_err(|err| {
-            println!("write bytes failed with err {:?}", err);
-            ()
-        })?;
-        return writer.flush().map_err(|err| {
-            println!("writer flush failed with err {:?}", err);
-            ()
-        });
+    let mut file = file.unwrap();
+    file.seek(SeekFrom::Start(start)).map_err(|err| {
+        println!("seek failed with err {:?}", err);
+    })?;
+    std::io::copy(&mut file.take(end - start), writer).map_err(|err| {
+        println!("write bytes failed with err {:?}", err);
+    })?;
+    write_body(&[], writer)
+}
+
+fn dir_listing(url: &str, path: &Path) -> std::io::Result<String> {
+    let mut entries = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        entries.push((
+            !metadata.is_dir(), // Directories go first.
+            entry.file_name().to_string_lossy().into_owned(),
+            metadata.len(),
+            modified.as_secs(),
+        ));
     }
+    entries.sort();
 
-    log_request(404, request.url.as_bytes());
-    write_error(404, writer)
+    let title = html_escape(url);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Index of {title}</title>\n</head>\n<body>\n<h1>Index of {title}</h1>\n<table>\n"
+    );
+    if url != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for (is_file, name, len, modified) in entries {
+        let (slash, size) = if is_file {
+            ("", len.to_string())
+        } else {
+            ("/", "-".to_owned())
+        };
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{slash}\">{}{slash}</a></td><td align=\"right\">{size}</td><td>{}</td></tr>\n",
+            percent_encode(&name),
+            html_escape(&name),
+            http_date(modified)
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    Ok(html)
 }
 
 fn write_error(error: u32, writer: &mut dyn std::io::Write) -> Result<(), ()> {
@@ -358,6 +648,7 @@ fn write_error(error: u32, writer: &mut dyn std::io::Write) -> Result<(), ()> {
     let str_error = match error {
         400 => "400 Bad Request",
         404 => "404 Not Found",
+        405 => "405 Method Not Allowed",
         421 => "421 Misdirected Request",
         431 => "431 Request Header Fields Too Large",
         _ => {
@@ -536,6 +827,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     match std::fs::read_dir(Path::new(&args.dir)) {
         Ok(_) => {
             *ROOT_DIR.lock().unwrap() = args.dir.clone();
+            DIR_LISTING.store(args.dir_listing, Ordering::Relaxed);
         }
         Err(_) => {
             eprintln!("Directory '{}' not found.", &args.dir);
@@ -549,17 +841,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let cert_file = args.ssl_cert.as_ref().unwrap();
         let private_key_file = args.ssl_key.as_ref().unwrap();
 
-        let certs = rustls_pemfile::certs(&mut BufReader::new(&mut File::open(cert_file)?))
-            .collect::<Result<Vec<_>, _>>()?;
-        let private_key =
-            rustls_pemfile::private_key(&mut BufReader::new(&mut File::open(private_key_file)?))?
-                .unwrap();
-        let config = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, private_key)
-            .unwrap();
+        let config = moto_tls::server_config(cert_file, private_key_file)?;
         println!("Serving HTTPs on {:?}. Press Ctrl+C to exit.", args.addr);
-        Some(Arc::new(config))
+        Some(config)
     } else {
         println!("Serving HTTP on {:?}. Press Ctrl+C to exit.", args.addr);
         None
@@ -498,6 +498,37 @@ pub extern "C" fn irq_handler_inner(rsp: u64, irq_num: u64) {
     // have kernel page table also in there.
 
     match irq_num as u8 {
+        2 => {
+            if crate::xray::crash::in_progress() {
+                let (cr2, cr3) = super::read_cr2_cr3();
+                let regs = moto_sys::crash::CrashRegsV1 {
+                    rip: irq_stack.rip,
+                    rsp: irq_stack.rsp,
+                    rflags: irq_stack.flags,
+                    cs: irq_stack.cs,
+                    ss: irq_stack.ss,
+                    rax: irq_stack.rax,
+                    rbx: irq_stack.rbx,
+                    rcx: irq_stack.rcx,
+                    rdx: irq_stack.rdx,
+                    rsi: irq_stack.rsi,
+                    rdi: irq_stack.rdi,
+                    rbp: irq_stack.rbp,
+                    r8: irq_stack.r8,
+                    r9: irq_stack.r9,
+                    r10: irq_stack.r10,
+                    r11: irq_stack.r11,
+                    r12: irq_stack.r12,
+                    r13: irq_stack.r13,
+                    r14: irq_stack.r14,
+                    r15: irq_stack.r15,
+                    cr2,
+                    cr3,
+                };
+                crate::xray::crash::on_nmi(&regs, !uspace); // noreturn
+            }
+            crate::write_serial!("\n\nNMI on CPU {}\n\n", crate::arch::current_cpu());
+        }
         3 => {
             if uspace {
                 ThreadControlBlock::preempt_current_thread_irq(irq_stack); // noreturn
@@ -549,6 +580,10 @@ pub extern "C" fn irq_handler_inner(rsp: u64, irq_num: u64) {
         IRQ_WAKEUP => {
             crate::sched::local_wake(); // Wakeup.
             eoi();
+            if uspace && crate::sched::take_preempt_request() {
+                // A high priority (real-time) job is waiting.
+                ThreadControlBlock::preempt_current_thread_irq(irq_stack); // noreturn
+            }
         }
         IRQ_TLB_SHOOTDOWN => {
             eoi();
@@ -603,6 +638,16 @@ pub fn set_timer(when: super::time::Instant) {
     });
 }
 
+// Disarms the timer: writing zero to the TSC deadline disarms it.
+pub fn cancel_timer() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        use x86::apic::ApicControl;
+        x2apic().tsc_set(0);
+    });
+}
+
 /*
     from https://wiki.osdev.org/Exceptions:
 
@@ -846,6 +891,30 @@ pub fn wake_remote_cpu(cpu: uCpus) {
     }
 }
 
+// Used by crash dumps to stop other CPUs: NMIs are delivered even
+// if the CPU has interrupts disabled.
+pub fn nmi_other_cpus() {
+    use x86::apic::*;
+
+    let icr = x86::apic::Icr::for_x2apic(
+        0,
+        ApicId::X2Apic(0),
+        DestinationShorthand::AllExcludingSelf,
+        DeliveryMode::NMI,
+        DestinationMode::Physical,
+        DeliveryStatus::Idle,
+        Level::Assert,
+        TriggerMode::Edge,
+    );
+
+    // Don't use x2apic(): this may be called before it is initialized.
+    let mut x2apic = x86::apic::x2apic::X2APIC::new();
+    x2apic.attach();
+    unsafe {
+        x2apic.send_ipi(icr);
+    }
+}
+
 pub fn shoot_remote_tlb(cpu: uCpus) {
     use x86::apic::*;
 
@@ -2,6 +2,7 @@ pub(self) mod gdt;
 
 pub mod irq;
 pub mod paging;
+pub mod pmu;
 pub mod serial;
 pub mod syscall;
 pub mod time;
@@ -98,6 +99,54 @@ pub fn kernel_exit() -> ! {
     loop {} // The above did not work, so just loop
 }
 
+// Walks the frame pointer chain starting at @rbp, storing kernel return
+// addresses (relative to kernel_offset_virt()) into @buf. Used in crash dumps,
+// so stops at the first frame that doesn't look like a kernel one, including
+// frames with a non-canonical or unmapped @rbp (a corrupted stack must not
+// fault the crash path).
+pub fn backtrace_from(mut rbp: u64, buf: &mut [u64]) {
+    let kernel_offset = crate::mm::kernel_offset_virt();
+
+    for slot in buf.iter_mut() {
+        if rbp & 7 != 0 || rbp < crate::mm::virt::VMEM_KERNEL_DATA_START {
+            break;
+        }
+        // Kernel addresses are canonical iff bits 63..47 are all set.
+        if (rbp >> 47) != (u64::MAX >> 47) {
+            break;
+        }
+        // The frame is 16 bytes, so it may straddle a page boundary.
+        // paging::virt_to_phys() walks the page tables without locking.
+        let Some(ret_slot) = rbp.checked_add(8) else {
+            break;
+        };
+        if paging::virt_to_phys(rbp).is_none() || paging::virt_to_phys(ret_slot).is_none() {
+            break;
+        }
+        let ret_addr = unsafe { *(ret_slot as *const u64) };
+        if ret_addr <= kernel_offset {
+            break;
+        }
+        *slot = ret_addr - kernel_offset;
+        rbp = unsafe { *(rbp as *const u64) };
+    }
+}
+
+pub fn read_cr2_cr3() -> (u64, u64) {
+    let cr2: u64;
+    let cr3: u64;
+    unsafe {
+        core::arch::asm!(
+            "mov {}, cr2",
+            "mov {}, cr3",
+            out(reg) cr2,
+            out(reg) cr3,
+            options(nomem, nostack, preserves_flags)
+        )
+    };
+    (cr2, cr3)
+}
+
 #[cfg(debug_assertions)]
 fn get_backtrace() -> [u64; 256] {
     let mut backtrace: [u64; 256] = [0; 256];
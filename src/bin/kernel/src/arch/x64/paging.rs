@@ -62,6 +62,7 @@ impl PTE {
     const USER: u64 = 0b_00_000_100; // bit 2.
     const ACCESSED: u64 = 0b_00_100_000; // bit 5.
     const HUGE: u64 = 0b_10_000_000; // bit 7.
+    const NO_EXECUTE: u64 = 1 << 63; // Requires EFER.NXE.
     const MMIO_RW: u64 = 0b_00_111_011; // bit 0: present; bit 1: writable;
                                         // bit 3: write through; bit 4: no cache; bit 5: accessed.
 
@@ -380,6 +381,30 @@ impl PageTableImpl {
         self.flush_virt_addr(virt_addr);
     }
 
+    fn protect_page(&mut self, virt_addr: u64, kind: PageType, pte_flags: u64) {
+        assert_eq!(0, virt_addr & (kind.page_size() - 1));
+
+        let table_l3 = HwPageTable::from_pte(self.table_l4.get(PageTableImpl::idx_l4(virt_addr)));
+        let idx_l3 = PageTableImpl::idx_l3(virt_addr);
+        let (table, idx) = if kind == PageType::LargePage {
+            (table_l3, idx_l3)
+        } else {
+            let table_l2 = HwPageTable::from_pte(table_l3.get(idx_l3));
+            let idx_l2 = PageTableImpl::idx_l2(virt_addr);
+            if kind == PageType::MidPage {
+                (table_l2, idx_l2)
+            } else {
+                let table_l1 = HwPageTable::from_pte(table_l2.get(idx_l2));
+                (table_l1, PageTableImpl::idx_l1(virt_addr))
+            }
+        };
+
+        let pte = table.get(idx);
+        assert!(pte.is_present());
+        table.set(idx, PTE::from_u64(pte.to_addr() | pte_flags));
+        self.flush_virt_addr(virt_addr);
+    }
+
     fn is_readable(&self, virt_addr: u64) -> bool {
         let idx_l4 = PageTableImpl::idx_l4(virt_addr);
         let pte_l4 = self.table_l4.get(idx_l4);
@@ -553,24 +578,16 @@ impl PageTable {
         }
     }
 
-    pub fn map_page(
-        &self,
-        phys_addr: u64,
-        virt_addr: u64,
-        kind: PageType,
-        options: MappingOptions,
-    ) {
+    fn pte_flags(options: MappingOptions, kind: PageType, phys_addr: u64, virt_addr: u64) -> u64 {
         let mut options = options;
-        let dont_zero =
-            options.contains(MappingOptions::DONT_ZERO) || options.contains(MappingOptions::MMIO);
-        if dont_zero {
-            options.remove(MappingOptions::DONT_ZERO);
-        }
-
         let user = options.contains(MappingOptions::USER_ACCESSIBLE);
         if user {
             options.remove(MappingOptions::USER_ACCESSIBLE);
         }
+        let executable = options.contains(MappingOptions::EXECUTABLE);
+        if executable {
+            options.remove(MappingOptions::EXECUTABLE);
+        }
 
         // Can we use a match expression below? The complier treats "ORs" as matching ORs, not
         // matching to ORed bitflags (i.e. matches single bits only).
@@ -594,12 +611,49 @@ impl PageTable {
 
         if user {
             pte_flags |= PTE::USER;
+            // Kernel mappings are left executable, as they always were.
+            if !executable {
+                pte_flags |= PTE::NO_EXECUTE;
+            }
         }
 
         if kind != PageType::SmallPage {
             pte_flags |= PTE::HUGE;
         }
 
+        pte_flags
+    }
+
+    // Changes the protection of an already mapped page.
+    pub fn protect_page(&self, virt_addr: u64, kind: PageType, options: MappingOptions) {
+        let mut options = options;
+        options.remove(MappingOptions::DONT_ZERO);
+        let pte_flags = Self::pte_flags(options, kind, 0, virt_addr);
+
+        unsafe {
+            self.inst
+                .get()
+                .lock(line!())
+                .protect_page(virt_addr, kind, pte_flags);
+        }
+    }
+
+    pub fn map_page(
+        &self,
+        phys_addr: u64,
+        virt_addr: u64,
+        kind: PageType,
+        options: MappingOptions,
+    ) {
+        let mut options = options;
+        let dont_zero =
+            options.contains(MappingOptions::DONT_ZERO) || options.contains(MappingOptions::MMIO);
+        if dont_zero {
+            options.remove(MappingOptions::DONT_ZERO);
+        }
+
+        let pte_flags = Self::pte_flags(options, kind, phys_addr, virt_addr);
+
         unsafe {
             self.inst
                 .get()
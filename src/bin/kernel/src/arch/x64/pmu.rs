@@ -0,0 +1,183 @@
+// Architectural performance monitoring (Intel SDM Vol. 3B, ch. 20).
+//
+// We use two fixed-function counters (instructions retired, core cycles)
+// and two general-purpose counters (LLC misses, branch mispredictions).
+// The counters are programmed lazily, per CPU, the first time they are
+// needed on that CPU (see ensure_enabled()), and then count continuously,
+// both in kernel and user modes.
+
+use core::sync::atomic::*;
+
+use crate::config::MAX_CPUS;
+
+pub const NUM_COUNTERS: usize = 4;
+
+// Counter indices; also bits in the availability mask.
+pub const COUNTER_CYCLES: usize = 0;
+pub const COUNTER_INSTRUCTIONS: usize = 1;
+pub const COUNTER_CACHE_MISSES: usize = 2;
+pub const COUNTER_BRANCH_MISSES: usize = 3;
+
+const IA32_PMC0: u32 = 0xC1;
+const IA32_PMC1: u32 = 0xC2;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+const IA32_PERFEVTSEL1: u32 = 0x187;
+const IA32_FIXED_CTR0: u32 = 0x309; // Instructions retired.
+const IA32_FIXED_CTR1: u32 = 0x30A; // Unhalted core cycles.
+const IA32_FIXED_CTR_CTRL: u32 = 0x38D;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38F;
+
+const EVTSEL_USR: u64 = 1 << 16;
+const EVTSEL_OS: u64 = 1 << 17;
+const EVTSEL_EN: u64 = 1 << 22;
+const EVENT_LLC_MISSES: u64 = 0x2E | (0x41 << 8);
+const EVENT_BRANCH_MISSES: u64 = 0xC5;
+
+// Bits in CPUID.0AH:EBX indicating that an architectural event is NOT available.
+const CPUID_EBX_LLC_MISSES_NA: u32 = 1 << 4;
+const CPUID_EBX_BRANCH_MISSES_NA: u32 = 1 << 6;
+
+// Zero: not probed yet.
+static AVAILABLE_MASK: AtomicU32 = AtomicU32::new(0);
+const PROBED: u32 = 1 << 31;
+
+// Counter widths, in bits.
+static FIXED_WIDTH: AtomicU32 = AtomicU32::new(0);
+static GP_WIDTH: AtomicU32 = AtomicU32::new(0);
+
+// Set once somebody asks for the counters; until then, we don't touch the PMU.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+static ENABLED_CPUS: [AtomicBool; MAX_CPUS as usize] =
+    [const { AtomicBool::new(false) }; MAX_CPUS as usize];
+
+// Returns a mask of COUNTER_* bits that are supported by the CPU.
+pub fn available_mask() -> u32 {
+    let mask = AVAILABLE_MASK.load(Ordering::Relaxed);
+    if mask != 0 {
+        return mask & !PROBED;
+    }
+
+    let mut mask = 0;
+    let leaf = unsafe { core::arch::x86_64::__cpuid(0) };
+    if leaf.eax >= 0xA {
+        let leaf = unsafe { core::arch::x86_64::__cpuid(0xA) };
+        let version = leaf.eax & 0xff;
+        let num_gp = (leaf.eax >> 8) & 0xff;
+        let num_fixed = leaf.edx & 0x1f;
+        GP_WIDTH.store((leaf.eax >> 16) & 0xff, Ordering::Relaxed);
+        FIXED_WIDTH.store((leaf.edx >> 5) & 0xff, Ordering::Relaxed);
+
+        if version >= 2 {
+            if num_fixed >= 2 {
+                mask |= (1 << COUNTER_CYCLES) | (1 << COUNTER_INSTRUCTIONS);
+            }
+            if num_gp >= 2 {
+                if leaf.ebx & CPUID_EBX_LLC_MISSES_NA == 0 {
+                    mask |= 1 << COUNTER_CACHE_MISSES;
+                }
+                if leaf.ebx & CPUID_EBX_BRANCH_MISSES_NA == 0 {
+                    mask |= 1 << COUNTER_BRANCH_MISSES;
+                }
+            }
+        }
+    }
+
+    AVAILABLE_MASK.store(mask | PROBED, Ordering::Relaxed);
+    mask
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+// Called when the userspace first asks for the counters.
+pub fn request() {
+    REQUESTED.store(true, Ordering::Relaxed);
+    ensure_enabled();
+}
+
+pub fn enabled_on_this_cpu() -> bool {
+    ENABLED_CPUS[super::current_cpu() as usize].load(Ordering::Relaxed)
+}
+
+// Programs the counters on the current CPU, if not yet done.
+pub fn ensure_enabled() {
+    let cpu = super::current_cpu() as usize;
+    if ENABLED_CPUS[cpu].load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mask = available_mask();
+    if mask == 0 {
+        return;
+    }
+
+    let mut global_ctrl = 0;
+    if mask & (1 << COUNTER_INSTRUCTIONS) != 0 {
+        // Fixed counters 0 and 1: count in both rings (0b11 each).
+        super::wrmsr(IA32_FIXED_CTR_CTRL, 0x33);
+        global_ctrl |= (1 << 32) | (1 << 33);
+    }
+    if mask & (1 << COUNTER_CACHE_MISSES) != 0 {
+        super::wrmsr(
+            IA32_PERFEVTSEL0,
+            EVENT_LLC_MISSES | EVTSEL_USR | EVTSEL_OS | EVTSEL_EN,
+        );
+        global_ctrl |= 1 << 0;
+    }
+    if mask & (1 << COUNTER_BRANCH_MISSES) != 0 {
+        super::wrmsr(
+            IA32_PERFEVTSEL1,
+            EVENT_BRANCH_MISSES | EVTSEL_USR | EVTSEL_OS | EVTSEL_EN,
+        );
+        global_ctrl |= 1 << 1;
+    }
+    super::wrmsr(IA32_PERF_GLOBAL_CTRL, global_ctrl);
+
+    ENABLED_CPUS[cpu].store(true, Ordering::Relaxed);
+}
+
+// Reads the counters on the current CPU. Unavailable counters read as zero.
+pub fn read() -> [u64; NUM_COUNTERS] {
+    let mut result = [0; NUM_COUNTERS];
+    if !enabled_on_this_cpu() {
+        return result;
+    }
+
+    let mask = available_mask();
+    if mask & (1 << COUNTER_CYCLES) != 0 {
+        result[COUNTER_CYCLES] = super::rdmsr(IA32_FIXED_CTR1);
+        result[COUNTER_INSTRUCTIONS] = super::rdmsr(IA32_FIXED_CTR0);
+    }
+    if mask & (1 << COUNTER_CACHE_MISSES) != 0 {
+        result[COUNTER_CACHE_MISSES] = super::rdmsr(IA32_PMC0);
+    }
+    if mask & (1 << COUNTER_BRANCH_MISSES) != 0 {
+        result[COUNTER_BRANCH_MISSES] = super::rdmsr(IA32_PMC1);
+    }
+    result
+}
+
+// Counter increments between two reads, accounting for wraparound.
+pub fn delta(before: &[u64; NUM_COUNTERS], after: &[u64; NUM_COUNTERS]) -> [u64; NUM_COUNTERS] {
+    fn width_mask(width: u32) -> u64 {
+        if width == 0 || width >= 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        }
+    }
+    let fixed_mask = width_mask(FIXED_WIDTH.load(Ordering::Relaxed));
+    let gp_mask = width_mask(GP_WIDTH.load(Ordering::Relaxed));
+
+    let mut result = [0; NUM_COUNTERS];
+    for idx in 0..NUM_COUNTERS {
+        let mask = match idx {
+            COUNTER_CYCLES | COUNTER_INSTRUCTIONS => fixed_mask,
+            _ => gp_mask,
+        };
+        result[idx] = after[idx].wrapping_sub(before[idx]) & mask;
+    }
+    result
+}
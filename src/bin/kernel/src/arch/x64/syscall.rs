@@ -120,11 +120,11 @@ pub fn init() {
                 wrmsr
             ",
 
-            // Enable syscall/sysret. IA32_EFER.
+            // Enable syscall/sysret and no-execute pages. IA32_EFER.
             "
                 mov rcx, 0xc0000080
                 rdmsr
-                or eax, 1
+                or eax, 0x801
                 wrmsr
             ",
 
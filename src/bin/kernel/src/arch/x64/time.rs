@@ -282,6 +282,79 @@ pub fn system_start_time() -> super::time::Instant {
     }
 }
 
+// The wall clock slew in progress, as in KernelStaticPage: it started at
+// @tsc and moves the wall clock by @nsec in total. A new slew is @pending
+// until the next populate_kernel_static_page(), so that the kernel and
+// userspace switch to it at the same time.
+struct WallClockSlew {
+    tsc: u64,
+    nsec: i64,
+    pending: Option<i64>,
+}
+
+impl WallClockSlew {
+    // How far the slew has moved the wall clock by @tsc_val; the same as
+    // userspace computes from KernelStaticPage.
+    fn slewed(&self, tsc_val: u64) -> i64 {
+        if self.nsec == 0 || tsc_val <= self.tsc {
+            return 0;
+        }
+
+        let mut elapsed = tsc_val - self.tsc;
+        let tsc_shift = GLOBALS.tsc_shift.load(Ordering::Relaxed);
+        if tsc_shift >= 0 {
+            elapsed <<= tsc_shift;
+        } else {
+            elapsed >>= -tsc_shift;
+        }
+        let elapsed = ((elapsed as u128) * (GLOBALS.tsc_mul.load(Ordering::Relaxed) as u128)) >> 32;
+
+        let slewed = (elapsed / (moto_sys::SysCpu::WALL_CLOCK_SLEW_PERIOD_NSEC as u128))
+            .min(self.nsec.unsigned_abs() as u128) as i64;
+        slewed * self.nsec.signum()
+    }
+
+    // Moves what has been slewed by @tsc_val into the offset.
+    fn fold(&mut self, tsc_val: u64) {
+        let slewed = self.slewed(tsc_val);
+        GLOBALS
+            .wall_clock_offset_nsec
+            .fetch_add(slewed, Ordering::Relaxed);
+        self.nsec -= slewed;
+        self.tsc = tsc_val;
+    }
+}
+
+static WALL_CLOCK_SLEW: crate::util::SpinLock<WallClockSlew> =
+    crate::util::SpinLock::new(WallClockSlew {
+        tsc: 0,
+        nsec: 0,
+        pending: None,
+    });
+
+// Returns the offset and what is left to slew.
+pub fn wall_clock_offset() -> (i64, i64) {
+    let slew = WALL_CLOCK_SLEW.lock(line!());
+    let slewed = slew.slewed(rdtsc());
+    (
+        GLOBALS.wall_clock_offset_nsec.load(Ordering::Relaxed) + slewed,
+        slew.pending.unwrap_or(slew.nsec - slewed),
+    )
+}
+
+// Returns the new offset. Picked up by the next populate_kernel_static_page().
+pub fn adjust_wall_clock(delta_nsec: i64) -> i64 {
+    GLOBALS
+        .wall_clock_offset_nsec
+        .fetch_add(delta_nsec, Ordering::Relaxed)
+        .wrapping_add(delta_nsec)
+}
+
+// Replaces the slew in progress at the next populate_kernel_static_page().
+pub fn slew_wall_clock(delta_nsec: i64) {
+    WALL_CLOCK_SLEW.lock(line!()).pending = Some(delta_nsec);
+}
+
 pub fn populate_kernel_static_page(page: &mut moto_sys::KernelStaticPage) {
     update_globals();
 
@@ -290,7 +363,26 @@ pub fn populate_kernel_static_page(page: &mut moto_sys::KernelStaticPage) {
     page.tsc_shift = GLOBALS.tsc_shift.load(Ordering::Relaxed);
     page.tsc_ts = GLOBALS.tsc_ts.load(Ordering::Relaxed);
     page.system_time = GLOBALS.system_time.load(Ordering::Relaxed);
-    page.base_nsec = GLOBALS.base_nsec.load(Ordering::Relaxed);
+
+    // What has been slewed is folded into the offset when a new slew starts,
+    // and when the slew is over (so that computing the wall clock does not
+    // convert ever longer TSC intervals). Either way the wall clock does not
+    // jump, as the page is updated at the same time.
+    let mut slew = WALL_CLOCK_SLEW.lock(line!());
+    let now = rdtsc();
+    if let Some(nsec) = slew.pending.take() {
+        slew.fold(now);
+        slew.nsec = nsec;
+    } else if slew.nsec != 0 && slew.slewed(now) == slew.nsec {
+        slew.fold(now);
+    }
+    page.wall_clock_slew_tsc = slew.tsc;
+    page.wall_clock_slew_nsec = slew.nsec;
+    page.base_nsec = GLOBALS
+        .base_nsec
+        .load(Ordering::Relaxed)
+        .wrapping_add_signed(GLOBALS.wall_clock_offset_nsec.load(Ordering::Relaxed));
+    drop(slew);
 
     page.system_start_time_tsc = GLOBALS.system_start_time_tsc.load(Ordering::Relaxed);
 
@@ -309,6 +401,8 @@ pub struct Globals {
 
     // Wallclock base.
     pub base_nsec: AtomicU64,
+    // Set by userspace time sync (e.g. NTP); added to base_nsec.
+    pub wall_clock_offset_nsec: AtomicI64,
 
     wall_clock: PvClockWallClock,
     vcpu_time_info: super::time::PvClockVcpuTimeInfo,
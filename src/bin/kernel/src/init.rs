@@ -168,13 +168,15 @@ pub fn init_exited(init: &crate::uspace::Process) -> ! {
 pub struct KernelBootupInfo {
     pvh_addr: u64, // *const PvhStartInfo
     start_tsc: u64,
-    max_ram_offset: u64, // Max in use memory offset above 34M phys
+    max_ram_offset: u64, // Max in use memory offset above the kernel start.
     num_cpus: u32,
+    kernel_slide: u64, // The kernel is loaded at 34M phys + kernel_slide.
 }
 
 impl KernelBootupInfo {
     pub fn validate(&self) {
         assert!(self.num_cpus <= (crate::config::MAX_CPUS as u32));
+        assert_eq!(0, self.kernel_slide & (crate::mm::PAGE_SIZE_MID - 1));
         assert!(self
             .kernel_bytes_phys()
             .intersect(&self.initrd_bytes_phys())
@@ -195,7 +197,7 @@ impl KernelBootupInfo {
 
     pub fn kernel_bytes_phys(&self) -> crate::mm::MemorySegment {
         crate::mm::MemorySegment {
-            start: crate::mm::KERNEL_PHYS_START as u64,
+            start: crate::mm::KERNEL_PHYS_START as u64 + self.kernel_slide,
             size: self.max_ram_offset,
         }
     }
@@ -247,6 +249,7 @@ fn start_bsp(arg: u64) -> ! {
     let boot_info = *boot_info;
 
     boot_info.validate();
+    crate::mm::set_kernel_slide(boot_info.kernel_slide);
     crate::config::set_num_cpus(boot_info.num_cpus as uCpus);
 
     while AP_STARTED.load(Ordering::Relaxed) != (boot_info.num_cpus - 1) {
@@ -326,6 +329,7 @@ fn cpu_main(this_cpu: u64) -> ! {
 
         crate::mm::init_mm_bsp_stage2();
         crate::xray::stats::init();
+        crate::xray::crash_console::init();
         crate::uspace::init();
 
         // If we print the boot logo before init_clock(), KVM in the host misbehaves and
@@ -379,7 +383,13 @@ pub fn start_userspace_processes() {
         crate::xray::stats::kernel_stats(),
         address_space,
         entry_point,
+        // sys-io is loaded at its link address, so there is no slide.
+        moto_sys::stats::ModuleInfoV1::default(),
         0xffff_ffff_ffff_ffff, // All possible caps.
+        moto_sys::caps::SYSCALL_FILTER_ALLOW_ALL,
+        crate::uspace::namespace::root(),
+        moto_sys::caps::ROOT_UID,
+        0, // gid
         alloc::string::String::from("sys-io"),
     )
     .unwrap();
@@ -53,6 +53,8 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
     #[cfg(debug_assertions)]
     crate::arch::log_backtrace("panic");
 
+    crate::xray::crash::dump(info);
+
     crate::arch::kernel_exit()
 }
 
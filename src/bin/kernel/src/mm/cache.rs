@@ -79,6 +79,13 @@ impl CacheLine {
 
 const CACHE_LINES: usize = 30;
 
+// Pages in cached segments, across all caches.
+static CACHED_PAGES: AtomicU64 = AtomicU64::new(0);
+
+pub fn cached_pages() -> u64 {
+    CACHED_PAGES.load(Ordering::Relaxed)
+}
+
 pub(super) struct SegmentCache {
     caches: [SpinLock<*mut CacheLine>; CACHE_LINES],
     counts: [AtomicU64; CACHE_LINES],
@@ -161,6 +168,7 @@ impl SegmentCache {
         line.push(segment)?;
 
         self.counts[idx].fetch_add(1, Ordering::Relaxed);
+        CACHED_PAGES.fetch_add(segment.size >> PAGE_SIZE_SMALL_LOG2, Ordering::Relaxed);
         Ok(())
     }
 
@@ -176,6 +184,7 @@ impl SegmentCache {
         match line.pop() {
             Some(seg) => {
                 self.counts[idx].fetch_sub(1, Ordering::Relaxed);
+                CACHED_PAGES.fetch_sub(seg.size >> PAGE_SIZE_SMALL_LOG2, Ordering::Relaxed);
                 Some(seg)
             }
             None => None,
@@ -2,7 +2,7 @@
 // Note: only kheap and virt_intrusive provide memory allocation facilities,
 //       other than some corner cases that do frameless allocations.
 
-mod cache;
+pub mod cache;
 pub mod kheap;
 pub mod mmio;
 pub mod phys;
@@ -24,13 +24,25 @@ pub const ONE_MB: u64 = 1 << 20;
 // The full physical memory is mapped to [DIRECT_MAP_OFFSET, *)
 pub const PAGING_DIRECT_MAP_OFFSET: u64 = 1_u64 << 46;
 
-// We load the kernel at 34MB phys and PAGING_DIRECT_MAP_OFFSET + 34MB virt.
+// The kernel is loaded at 34MB phys + slide and PAGING_DIRECT_MAP_OFFSET + 34MB + slide virt,
+// where the slide is a random multiple of 2MB chosen by kloader (KASLR).
 pub const KERNEL_PHYS_START: u64 = ONE_MB * 34;
 
-// Note that we don't do kaslr, as it is mostly pointless:
-// https://grsecurity.net/kaslr_an_exercise_in_cargo_cult_security
-pub const fn kernel_offset_virt() -> u64 {
-    PAGING_DIRECT_MAP_OFFSET + KERNEL_PHYS_START
+static KERNEL_SLIDE: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+// Called once, on bootup, before anything looks at kernel addresses.
+pub fn set_kernel_slide(slide: u64) {
+    KERNEL_SLIDE.store(slide, Ordering::Relaxed);
+}
+
+pub fn kernel_slide() -> u64 {
+    KERNEL_SLIDE.load(Ordering::Relaxed)
+}
+
+// Where the kernel ELF image starts; subtract from kernel code addresses
+// to symbolize them.
+pub fn kernel_offset_virt() -> u64 {
+    PAGING_DIRECT_MAP_OFFSET + KERNEL_PHYS_START + kernel_slide()
 }
 
 // Slabs are currently only used for phys memory, and are thus never deallocated.
@@ -280,7 +292,7 @@ impl PageType {
 }
 
 bitflags! {
-    pub struct MappingOptions: u8 {
+    pub struct MappingOptions: u16 {
         const READABLE        = 1;
         const WRITABLE        = 2;
         const USER_ACCESSIBLE = 4;
@@ -289,9 +301,20 @@ bitflags! {
         const LAZY            = 32;
         const GUARD           = 64;
         const PRIVATE         = 128;  // Used by vmem_pages.
+        const EXECUTABLE      = 256;  // User mappings are no-execute otherwise.
+        const FREEABLE        = 512;  // Page hint; see VmemSegment::advise().
     }
 }
 
+// Memory usage hints from userspace (madvise).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemAdvice {
+    Free,     // Contents not needed; the pages may be reclaimed lazily.
+    WillNeed, // Map lazy pages now.
+    DontNeed, // Reclaim the pages now; they are zero-filled on next access.
+    Cold,     // Unlikely to be accessed soon.
+}
+
 impl Default for MappingOptions {
     fn default() -> Self {
         Self {
@@ -323,7 +346,8 @@ fn cpu_initialized() -> bool {
 // Returns the new stack.
 pub fn init_mm_bsp_stage1(boot_info: &crate::init::KernelBootupInfo) -> u64 {
     // [0..34M) - potentially used by the bootloader
-    // [34M..max_ram_offset) - the kernel binary
+    // [34M..34M + slide) - unused (see KASLR above)
+    // [34M + slide..34M + slide + max_ram_offset) - the kernel binary
     // initrd may be either below the kernel (if we loaded it) or above (if CHV)
 
     // Step 1. Give 2M+ to the kernel heap, permanently.
@@ -353,8 +377,8 @@ pub fn init_mm_bsp_stage1(boot_info: &crate::init::KernelBootupInfo) -> u64 {
 
     // Step 2. Init physical memory.
     let exclusion = MemorySegment {
-        start: KERNEL_PHYS_START as u64,
-        size: bootup_heap_phys.end() - (KERNEL_PHYS_START as u64),
+        start: boot_info.kernel_bytes_phys().start,
+        size: bootup_heap_phys.end() - boot_info.kernel_bytes_phys().start,
     };
     let pvh_mem_map = boot_info.pvh().mem_map();
     let mut available_memory: Vec<MemorySegment> = Vec::with_capacity(pvh_mem_map.len() + 2);
@@ -445,5 +469,13 @@ fn allocate_kernel_stack() -> u64 {
 
 pub fn oom_for_user(would_allocate: u64) -> bool {
     const RESERVED_MEMORY: u64 = 1 << 20; // Reserve 1M for the kernel & sys-io.
+    if (phys::PhysStats::get().available() + would_allocate) >= RESERVED_MEMORY {
+        return false;
+    }
+
+    // Under memory pressure: drop pages userspace marked as freeable, and retry.
+    if user::reclaim_freeable() == 0 {
+        return true;
+    }
     (phys::PhysStats::get().available() + would_allocate) < RESERVED_MEMORY
 }
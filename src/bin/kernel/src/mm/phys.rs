@@ -78,6 +78,12 @@ pub fn phys_allocate_contiguous_frames(
     PhysicalMemory::inst().allocate_contiguous_frames(kind, num_frames)
 }
 
+// Allocate a 2M frame from the huge page pool, used to back user memory.
+// Unlike allocate_frame(), does not panic on OOM: callers fall back to small pages.
+pub fn allocate_huge_frame() -> Result<SlabArc<Frame>, ErrorCode> {
+    PhysicalMemory::inst().allocate_huge_frame()
+}
+
 pub fn mark_unused(seg: &MemorySegment) {
     PhysicalMemory::inst().small_pages.mark_unused(seg)
 }
@@ -555,6 +561,9 @@ struct PhysicalMemory {
 
     small_pages: MemoryArea<PageSizeSmall>,
     mid_pages: DesignatedSegment<PageSizeMid>,
+
+    // 2M pages reserved at bootup to back user huge page allocations.
+    huge_pages: DesignatedSegment<PageSizeMid>,
 }
 
 // A pointer to the one and only instance of struct PhysicalMemory.
@@ -569,6 +578,11 @@ impl PhysicalMemory {
         size: (Self::MID_PAGES << PAGE_SIZE_MID_LOG2) as u64,
     };
 
+    // The huge page pool takes at most 1/HUGE_POOL_FRACTION of RAM;
+    // DesignatedSegment limits it to 64 pages (128M).
+    const HUGE_POOL_FRACTION: u64 = 8;
+    const HUGE_POOL_MAX_PAGES: u64 = 64;
+
     fn inst() -> &'static Self {
         let addr =
             unsafe { core::ptr::read_volatile(core::ptr::addr_of!(PHYS_MEM) as *const usize) };
@@ -591,6 +605,21 @@ impl PhysicalMemory {
         }
     }
 
+    fn allocate_huge_frame(&'static self) -> Result<SlabArc<Frame>, ErrorCode> {
+        let frame_start = self.huge_pages.allocate_frame()?;
+
+        let frame_result = self.slab.alloc_arc();
+
+        if let Ok(frame) = frame_result {
+            frame.get_mut().unwrap().start = frame_start;
+            frame.get_mut().unwrap().kind = PageType::MidPage;
+            Ok(frame)
+        } else {
+            self.huge_pages.deallocate_frame(frame_start);
+            frame_result
+        }
+    }
+
     fn allocate_frameless(&'static self, kind: PageType) -> Result<u64, ErrorCode> {
         let result = match kind {
             PageType::SmallPage => self.small_pages.allocate_frame(),
@@ -645,7 +674,10 @@ impl PhysicalMemory {
     fn deallocate_frame(&self, frame: &Frame) {
         match frame.kind {
             PageType::SmallPage => self.small_pages.deallocate_frame(frame.start),
-            // PageType::MidPage => self.mid_pages.deallocate_frame(frame.start),
+            // MID frames come only from the huge page pool.
+            PageType::MidPage => {
+                self.huge_pages.deallocate_frame(frame.start);
+            }
             _ => panic!(),
         };
 
@@ -723,12 +755,15 @@ impl PhysicalMemory {
         }
         assert!(mid_ok, "Physical RAM [2M; 10M) area not found.");
 
+        let huge_pool = Self::choose_huge_pool(available, in_use, total_size);
+
         use alloc::boxed::Box;
         let self_ = Box::leak(Box::new(PhysicalMemory {
             total_size,
             slab: MMSlab::<Frame>::new(true),
             small_pages: MemoryArea::new(),
             mid_pages: DesignatedSegment::new(&Self::MID_PAGES_SEGMENT),
+            huge_pages: DesignatedSegment::new(&huge_pool),
         }));
 
         let ptr = self_ as *mut PhysicalMemory;
@@ -740,6 +775,54 @@ impl PhysicalMemory {
         PhysicalMemory::assign_pages_to_area(available, &mut self_.small_pages);
         self_.small_pages.sort();
         self_.mark_used(in_use);
+
+        // The huge page pool is carved out of small pages permanently.
+        if !huge_pool.is_empty() {
+            self_.small_pages.mark_used(&huge_pool);
+            log::info!(
+                "Huge page pool: [0x{:x}..0x{:x}): {} pages.",
+                huge_pool.start,
+                huge_pool.end(),
+                huge_pool.size >> PAGE_SIZE_MID_LOG2
+            );
+        }
+    }
+
+    // Find a 2M-aligned physical range for the huge page pool, at the top
+    // of RAM, not in use at bootup. May return an empty segment.
+    fn choose_huge_pool(
+        available: &[MemorySegment],
+        in_use: &[MemorySegment],
+        total_size: u64,
+    ) -> MemorySegment {
+        let mut num_pages = ((total_size / Self::HUGE_POOL_FRACTION) >> PAGE_SIZE_MID_LOG2)
+            .min(Self::HUGE_POOL_MAX_PAGES);
+
+        while num_pages > 0 {
+            let size = num_pages << PAGE_SIZE_MID_LOG2;
+            for segment in available.iter().rev() {
+                if segment.size < size {
+                    continue;
+                }
+                let start = align_down(segment.end() - size, PAGE_SIZE_MID);
+                // Stay clear of the bootup area and the MID pages.
+                if start < segment.start || start < KERNEL_PHYS_START {
+                    continue;
+                }
+                let pool = MemorySegment { start, size };
+                if in_use.iter().any(|seg| !seg.intersect(&pool).is_empty()) {
+                    continue;
+                }
+                return pool;
+            }
+            num_pages >>= 1;
+        }
+
+        // Not at zero: DesignatedSegment reserves the zero page.
+        MemorySegment {
+            start: KERNEL_PHYS_START,
+            size: 0,
+        }
     }
 
     fn assign_pages_to_area<S: PageSize>(available: &[MemorySegment], area: &mut MemoryArea<S>) {
@@ -847,6 +930,9 @@ pub struct PhysStats {
 
     pub small_pages_used: u64,
     pub mid_pages_used: u64,
+
+    pub huge_pages: u64,
+    pub huge_pages_used: u64,
 }
 
 impl PhysStats {
@@ -864,6 +950,13 @@ impl PhysStats {
                 .used_bitmap
                 .load(Ordering::Relaxed)
                 .count_ones() as u64,
+
+            huge_pages: inst.huge_pages.num_pages as u64,
+            huge_pages_used: inst
+                .huge_pages
+                .used_bitmap
+                .load(Ordering::Relaxed)
+                .count_ones() as u64,
         }
     }
 
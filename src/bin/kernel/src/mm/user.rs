@@ -1,12 +1,41 @@
 use core::sync::atomic::*;
 
-use alloc::sync::Arc;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
 
 use super::{align_up, virt::*, PAGE_SIZE_SMALL, PAGE_SIZE_SMALL_LOG2};
-use crate::mm::{MappingOptions, MemorySegment, PAGE_SIZE_MID, PAGING_DIRECT_MAP_OFFSET};
+use crate::mm::{
+    MappingOptions, MemAdvice, MemorySegment, PAGE_SIZE_MID, PAGING_DIRECT_MAP_OFFSET,
+};
+use crate::util::SpinLock;
 use crate::xray::stats::MemStats;
 use moto_sys::ErrorCode;
 
+// Address spaces that may have pages marked as freeable (MemAdvice::Free).
+static FREEABLE: SpinLock<BTreeMap<usize, Weak<UserAddressSpace>>> = SpinLock::new(BTreeMap::new());
+
+fn address_space_key(address_space: &UserAddressSpace) -> usize {
+    address_space as *const UserAddressSpace as usize
+}
+
+// Drops all pages marked as freeable. Called under memory pressure.
+// Returns the number of pages reclaimed.
+pub fn reclaim_freeable() -> u64 {
+    let address_spaces = core::mem::take(&mut *FREEABLE.lock(line!()));
+
+    let mut reclaimed = 0;
+    for address_space in address_spaces.values().filter_map(|a| a.upgrade()) {
+        let pages = address_space.inner.reclaim_freeable();
+        address_space.user_mem_stats().add_reclaimed(pages);
+        reclaimed += pages;
+    }
+
+    if reclaimed > 0 {
+        log::debug!("reclaimed {} freeable pages", reclaimed);
+    }
+    reclaimed
+}
+
 #[derive(Debug)]
 pub struct UserStack {
     segment: super::MemorySegment, // Includes guard pages.
@@ -59,6 +88,8 @@ impl Drop for UserAddressSpace {
             self.do_drop_kernel_stack(segment);
         }
 
+        FREEABLE.lock(line!()).remove(&address_space_key(self));
+
         log::debug!("UserAddressSpace::drop()");
         // We don't check that total_usage is zero here, because
         // memory could still be mapped in self.inner.
@@ -412,6 +443,40 @@ impl UserAddressSpace {
         })
     }
 
+    // Allocates user heap backed by 2M pages from the huge page pool.
+    // Note: @num_pages are small pages.
+    pub fn alloc_user_huge(&self, num_pages: u64) -> Result<super::MemorySegment, ErrorCode> {
+        self.stats_user_add(num_pages << PAGE_SIZE_SMALL_LOG2)?;
+
+        self.inner
+            .vmem_allocate_huge_pages(
+                num_pages,
+                MappingOptions::READABLE
+                    | MappingOptions::WRITABLE
+                    | MappingOptions::USER_ACCESSIBLE,
+            )
+            .or_else(|err| {
+                self.stats_user_sub(num_pages << PAGE_SIZE_SMALL_LOG2);
+                Err(err)
+            })
+    }
+
+    // Same as alloc_user_unmapped(), but 2M-aligned, so that huge pages
+    // can be shared into the segment.
+    pub fn alloc_user_unmapped_huge(
+        &self,
+        num_pages: u64,
+    ) -> Result<super::MemorySegment, ErrorCode> {
+        self.stats_user_add(num_pages << PAGE_SIZE_SMALL_LOG2)?;
+
+        self.inner
+            .vmem_allocate_huge_pages(num_pages, MappingOptions::empty())
+            .or_else(|err| {
+                self.stats_user_sub(num_pages << PAGE_SIZE_SMALL_LOG2);
+                Err(err)
+            })
+    }
+
     pub fn alloc_user_lazy(&self, num_pages: u64) -> Result<super::MemorySegment, ErrorCode> {
         self.stats_user_add(num_pages << PAGE_SIZE_SMALL_LOG2)?;
 
@@ -505,6 +570,73 @@ impl UserAddressSpace {
         self.inner.fix_pagefault(pf_addr, error_code)
     }
 
+    // Maps a page filled with @bytes at @page_addr, which must be
+    // in an unmapped (reserved) region. See uspace/pager.rs.
+    pub fn supply_page(
+        &self,
+        page_addr: u64,
+        bytes: &[u8],
+        writable: bool,
+    ) -> Result<(), ErrorCode> {
+        if page_addr & (PAGE_SIZE_SMALL - 1) != 0 || bytes.len() as u64 != PAGE_SIZE_SMALL {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mapping_options = if writable {
+            MappingOptions::READABLE | MappingOptions::WRITABLE
+        } else {
+            MappingOptions::READABLE
+        };
+        self.inner.supply_page(page_addr, bytes, mapping_options)
+    }
+
+    // Applies @advice to [addr, addr + num_pages * PAGE_SIZE_SMALL), which must be
+    // within a single region allocated by this address space (see MemAdvice).
+    // Returns the number of bytes reclaimed.
+    pub fn advise(
+        self: &Arc<Self>,
+        addr: u64,
+        num_pages: u64,
+        advice: MemAdvice,
+    ) -> Result<u64, ErrorCode> {
+        if addr & (PAGE_SIZE_SMALL - 1) != 0 || num_pages == 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let end = num_pages
+            .checked_mul(PAGE_SIZE_SMALL)
+            .and_then(|size| addr.checked_add(size))
+            .ok_or(ErrorCode::InvalidArgument)?;
+
+        let reclaimed = self.inner.advise(addr, end, advice)?;
+        if advice == MemAdvice::Free {
+            FREEABLE
+                .lock(line!())
+                .insert(address_space_key(self), Arc::downgrade(self));
+        }
+
+        self.user_mem_stats().add_reclaimed(reclaimed);
+        Ok(reclaimed << PAGE_SIZE_SMALL_LOG2)
+    }
+
+    // Changes the protection of [addr, addr + num_pages * PAGE_SIZE_SMALL),
+    // which must be within a single region allocated by this address space.
+    pub fn protect(
+        &self,
+        addr: u64,
+        num_pages: u64,
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        if addr & (PAGE_SIZE_SMALL - 1) != 0 || num_pages == 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let end = num_pages
+            .checked_mul(PAGE_SIZE_SMALL)
+            .and_then(|size| addr.checked_add(size))
+            .ok_or(ErrorCode::InvalidArgument)?;
+
+        self.inner.protect(addr, end, mapping_options)
+    }
+
     pub fn copy_to_user(&self, bytes: &[u8], user_vaddr_start: u64) -> Result<(), ErrorCode> {
         let mut source_start = 0_u64;
         let mut dst_start = user_vaddr_start;
@@ -40,6 +40,7 @@ const ONE_GB: u64 = 1u64 << 30;
 // But we have reserved 515GB for the kernel to keep it in a single L3 PT,
 // so let's have these regions large enough to not think about it too much.
 
+// The lowest kernel code address; the actual one is randomized (see mm::kernel_slide()).
 pub const VMEM_KERNEL_CODE_START: u64 = super::PAGING_DIRECT_MAP_OFFSET + super::KERNEL_PHYS_START;
 pub const VMEM_KERNEL_DATA_START: u64 = super::PAGING_DIRECT_MAP_OFFSET - (512 * super::ONE_GB);
 
@@ -265,14 +266,28 @@ impl VmemRegion {
         &self,
         num_pages: u64,
         mapping_options: MappingOptions,
+    ) -> Result<MemorySegment, ErrorCode> {
+        self.allocate_pages_of_type(num_pages, mapping_options, PageType::SmallPage)
+    }
+
+    // Note: @num_pages are small pages; the segment is aligned to @page_type.
+    pub(super) fn allocate_pages_of_type(
+        &self,
+        num_pages: u64,
+        mapping_options: MappingOptions,
+        page_type: PageType,
     ) -> Result<MemorySegment, ErrorCode> {
         debug_assert!(!self.address_space.is_null());
         debug_assert_ne!(num_pages, 0);
+        let align = page_type.page_size();
         let size = num_pages << PAGE_SIZE_SMALL_LOG2;
-        let mut start = self.segment.start;
+        if (size & (align - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let mut start = align_up(self.segment.start, align);
 
         if start == 0 {
-            start = PAGE_SIZE_SMALL
+            start = align
         }
 
         let mut found_gap = false;
@@ -291,7 +306,7 @@ impl VmemRegion {
             found_gap = true;
         } else if let Some(last_seg) = segments.last_segment() {
             // Otherwise, try to add to the end, as this is the fastest.
-            let end = last_seg.segment().end();
+            let end = align_up(last_seg.segment().end(), align);
             if (end + size) <= self.segment.end() {
                 start = end;
                 found_gap = true;
@@ -307,7 +322,7 @@ impl VmemRegion {
                     found_gap = true;
                     break;
                 }
-                start = seg.vmem_segment().segment().end();
+                start = align_up(seg.vmem_segment().segment().end(), align);
             }
         }
 
@@ -321,7 +336,12 @@ impl VmemRegion {
             return Err(ErrorCode::OutOfMemory);
         }
 
-        let mut seg = VmemSegment::new(MemorySegment { start, size }, self, mapping_options);
+        let mut seg = VmemSegment::new(
+            MemorySegment { start, size },
+            self,
+            mapping_options,
+            page_type,
+        );
         seg.allocate_pages()?;
         self.bytes_used.fetch_add(size, Ordering::Relaxed);
 
@@ -426,7 +446,7 @@ impl VmemRegion {
             return Err(ErrorCode::InvalidArgument);
         }
 
-        let mut seg = VmemSegment::new(memory_segment, self, mapping_options);
+        let mut seg = VmemSegment::new(memory_segment, self, mapping_options, PageType::SmallPage);
         self.bytes_used.fetch_add(size, Ordering::Relaxed);
 
         seg.allocate_pages()?;
@@ -450,13 +470,68 @@ impl VmemRegion {
 
         Err(ErrorCode::InvalidArgument)
     }
+
+    fn supply_page(
+        &self,
+        page_addr: u64,
+        bytes: &[u8],
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        if !self.segment.contains(page_addr) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut segments = self.used_segments.lock(line!());
+        if let Some(seg) = segments.find_mut(page_addr) {
+            return seg.supply_page(page_addr, bytes, mapping_options);
+        }
+
+        Err(ErrorCode::InvalidArgument)
+    }
+
+    fn advise(&self, start: u64, end: u64, advice: MemAdvice) -> Result<u64, ErrorCode> {
+        if !self.segment.contains(start) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut segments = self.used_segments.lock(line!());
+        match segments.find_mut(start) {
+            Some(seg) if end <= seg.segment().end() => seg.advise(start, end, advice),
+            _ => Err(ErrorCode::InvalidArgument),
+        }
+    }
+
+    fn reclaim_freeable(&self) -> u64 {
+        let mut reclaimed = 0;
+        self.used_segments
+            .lock(line!())
+            .for_each_mut(|seg| reclaimed += seg.reclaim_freeable());
+        reclaimed
+    }
+
+    fn protect(
+        &self,
+        start: u64,
+        end: u64,
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        if !self.segment.contains(start) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut segments = self.used_segments.lock(line!());
+        match segments.find_mut(start) {
+            Some(seg) if end <= seg.segment().end() => seg.protect(start, end, mapping_options),
+            _ => Err(ErrorCode::InvalidArgument),
+        }
+    }
 }
 
 pub(super) struct AddressSpaceBase {
     pub(super) page_table: PageTable,
     pub(super) page_allocator: super::virt_intrusive::PageAllocator,
 
-    mem_stats: Arc<crate::xray::stats::MemStats>,
+    pub(super) mem_stats: Arc<crate::xray::stats::MemStats>,
 }
 
 impl AddressSpaceBase {
@@ -796,6 +871,18 @@ impl UserAddressSpaceBase {
         }
     }
 
+    // Allocates a segment backed by 2M pages. @num_pages are small pages.
+    // If @mapping_options are empty, the segment is reserved but not mapped.
+    pub(super) fn vmem_allocate_huge_pages(
+        &self,
+        num_pages: u64,
+        mapping_options: MappingOptions,
+    ) -> Result<MemorySegment, ErrorCode> {
+        debug_assert_ne!(num_pages, 0);
+        self.normal_memory
+            .allocate_pages_of_type(num_pages, mapping_options, PageType::MidPage)
+    }
+
     pub(super) fn vmem_allocate_contiguous_pages(
         &self,
         kind: VmemKind,
@@ -963,4 +1050,40 @@ impl UserAddressSpaceBase {
     pub(super) fn fix_pagefault(&self, pf_addr: u64, error_code: u64) -> Result<(), ErrorCode> {
         self.normal_memory.fix_pagefault(pf_addr, error_code)
     }
+
+    pub(super) fn supply_page(
+        &self,
+        page_addr: u64,
+        bytes: &[u8],
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        self.normal_memory.supply_page(
+            page_addr,
+            bytes,
+            mapping_options | MappingOptions::USER_ACCESSIBLE,
+        )
+    }
+
+    // Only normal memory is advisable: custom memory is not re-populated on page faults.
+    pub(super) fn advise(&self, start: u64, end: u64, advice: MemAdvice) -> Result<u64, ErrorCode> {
+        self.normal_memory.advise(start, end, advice)
+    }
+
+    pub(super) fn reclaim_freeable(&self) -> u64 {
+        self.normal_memory.reclaim_freeable()
+    }
+
+    pub(super) fn protect(
+        &self,
+        start: u64,
+        end: u64,
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        let mapping_options = mapping_options | MappingOptions::USER_ACCESSIBLE;
+        if self.normal_memory.segment.contains(start) {
+            self.normal_memory.protect(start, end, mapping_options)
+        } else {
+            self.custom_memory.protect(start, end, mapping_options)
+        }
+    }
 }
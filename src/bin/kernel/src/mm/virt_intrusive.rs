@@ -14,7 +14,7 @@ use crate::util::SpinLock;
 use super::phys::Frame;
 use super::slab::SlabArc;
 use super::virt::VaddrMapStatus;
-use super::{MappingOptions, MemorySegment, PAGE_SIZE_SMALL};
+use super::{MappingOptions, MemAdvice, MemorySegment, PAGE_SIZE_SMALL};
 
 #[derive(Default)]
 pub(super) struct Page {
@@ -223,6 +223,7 @@ pub(super) struct VmemSegment {
     pages: RBTree<PageTreeAdapter>,
     owner: crate::util::UnsafeRef<super::virt::VmemRegion>,
     mapping_options: MappingOptions,
+    page_type: PageType, // SmallPage, or MidPage for huge page segments.
 }
 
 impl Drop for VmemSegment {
@@ -236,11 +237,15 @@ impl VmemSegment {
         segment: MemorySegment,
         owner: &super::virt::VmemRegion,
         mapping_options: MappingOptions,
+        page_type: PageType,
     ) -> Self {
+        debug_assert_eq!(0, segment.start & (page_type.page_size() - 1));
+        debug_assert_eq!(0, segment.size & (page_type.page_size() - 1));
         Self {
             segment,
             owner: crate::util::UnsafeRef::from(owner),
             mapping_options,
+            page_type,
             pages: RBTree::new(PageTreeAdapter::new()),
         }
     }
@@ -253,13 +258,17 @@ impl VmemSegment {
         self.segment
     }
 
+    pub(super) fn page_type(&self) -> PageType {
+        self.page_type
+    }
+
     fn find_page(&self, vmem_addr: u64) -> Option<&Page> {
-        let page_addr = vmem_addr & !(PAGE_SIZE_SMALL - 1);
+        let page_addr = vmem_addr & !(self.page_type.page_size() - 1);
         self.pages.find(&page_addr).get()
     }
 
     fn find_page_mut(&mut self, vmem_addr: u64) -> Option<&mut Page> {
-        let page_addr = vmem_addr & !(PAGE_SIZE_SMALL - 1);
+        let page_addr = vmem_addr & !(self.page_type.page_size() - 1);
         self.pages
             .find(&page_addr)
             .clone_pointer()
@@ -297,11 +306,12 @@ impl VmemSegment {
         let page_mut = unsafe { page_ptr.as_mut().unwrap() };
         let frame = page_mut.frame.take();
         if let Some(frame) = frame.get() {
-            self.address_space().page_table.unmap_page(
-                frame.start(),
-                page_mut.start,
-                PageType::SmallPage,
-            );
+            self.address_space()
+                .page_table
+                .unmap_page(frame.start(), page_mut.start, frame.kind());
+            if frame.kind() == PageType::MidPage {
+                self.address_space().mem_stats.sub_huge(1);
+            }
         }
 
         page_mut.clear();
@@ -318,7 +328,7 @@ impl VmemSegment {
 
         while let Some(page) = cursor.remove() {
             self.clear_page(page);
-            sz += PAGE_SIZE_SMALL;
+            sz += self.page_type.page_size();
         }
 
         self.segment = MemorySegment::empty_segment();
@@ -341,7 +351,15 @@ impl VmemSegment {
         assert!(self.segment.size > 0);
         assert!(self.pages.is_empty());
 
-        let num_pages = self.segment.size >> PAGE_SIZE_SMALL_LOG2;
+        let page_type = self.page_type;
+        if page_type != PageType::SmallPage {
+            // Huge pages are never lazy, and stacks are never huge.
+            assert!(!self
+                .mapping_options
+                .intersects(MappingOptions::LAZY | MappingOptions::GUARD));
+        }
+
+        let num_pages = self.segment.size >> page_type.page_size_log2();
         let mut start = self.segment.start;
         for idx in 0..num_pages {
             // Allocate a vmem page.
@@ -374,7 +392,12 @@ impl VmemSegment {
             // Map, if needed.
             if !page_options.is_empty() && !self.mapping_options.contains(MappingOptions::LAZY) {
                 // Allocate a frame.
-                let frame = match super::phys::allocate_frame(PageType::SmallPage) {
+                let frame = if page_type == PageType::MidPage {
+                    super::phys::allocate_huge_frame()
+                } else {
+                    super::phys::allocate_frame(page_type)
+                };
+                let frame = match frame {
                     Ok(frame) => frame,
                     Err(err) => {
                         assert!(!page_mut.list_link.is_linked()); // TODO: remove.
@@ -389,9 +412,12 @@ impl VmemSegment {
                 self.address_space().page_table.map_page(
                     frame.get().unwrap().start(),
                     start,
-                    PageType::SmallPage,
+                    page_type,
                     page_options,
                 );
+                if page_type == PageType::MidPage {
+                    self.address_space().mem_stats.add_huge(1);
+                }
 
                 // Store the frame in the page.
                 page_mut.frame = frame;
@@ -400,7 +426,7 @@ impl VmemSegment {
             // Insert the page.
             self.pages.insert(unsafe { UnsafeRef::from_raw(page) });
 
-            start += PAGE_SIZE_SMALL;
+            start += page_type.page_size();
         }
 
         Ok(())
@@ -440,6 +466,11 @@ impl VmemSegment {
     pub(super) fn fix_pagefault(&mut self, pf_addr: u64, error_code: u64) -> Result<(), ErrorCode> {
         debug_assert!(self.segment.contains(pf_addr));
 
+        if self.page_type != PageType::SmallPage {
+            log::debug!("#PF: huge page segment");
+            return Err(ErrorCode::InvalidArgument);
+        }
+
         if ((pf_addr & !(PAGE_SIZE_SMALL - 1)) == self.segment.start)
             || (pf_addr >= (self.segment.end() - PAGE_SIZE_SMALL))
         {
@@ -456,6 +487,19 @@ impl VmemSegment {
         assert!(page.contains(pf_addr));
 
         if !page.frame.is_null() {
+            if error_code == 3 && page.mapping_options.contains(MappingOptions::FREEABLE) {
+                // A write to a page marked freeable: the page is needed again.
+                page.mapping_options.remove(MappingOptions::FREEABLE);
+                let page_start = page.start;
+                let mut mapping_options = page.mapping_options;
+                mapping_options.remove(MappingOptions::LAZY);
+                self.address_space().page_table.protect_page(
+                    page_start,
+                    PageType::SmallPage,
+                    mapping_options,
+                );
+                return Ok(());
+            }
             log::error!("#PF with a frame present.");
             return Err(ErrorCode::InvalidArgument);
         }
@@ -479,6 +523,16 @@ impl VmemSegment {
                 log::debug!("#PF: not writable");
                 return Err(ErrorCode::InvalidArgument);
             }
+        } else if error_code == 0x10 {
+            // Instruction fetch.
+            if !page
+                .mapping_options
+                .contains(MappingOptions::USER_ACCESSIBLE)
+                || !page.mapping_options.contains(MappingOptions::EXECUTABLE)
+            {
+                log::debug!("#PF: not executable");
+                return Err(ErrorCode::InvalidArgument);
+            }
         } else {
             log::debug!("Unsupported #PF error code: 0x{:x}", error_code + 4);
             return Err(ErrorCode::InvalidArgument);
@@ -499,6 +553,248 @@ impl VmemSegment {
         Ok(())
     }
 
+    // Maps a new page at @page_addr (which must not be mapped yet) filled
+    // with @bytes. Used to resolve page faults in regions backed by
+    // userspace pagers (e.g. memory-mapped files).
+    pub(super) fn supply_page(
+        &mut self,
+        page_addr: u64,
+        bytes: &[u8],
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        debug_assert!(self.segment.contains(page_addr));
+        debug_assert_eq!(bytes.len() as u64, PAGE_SIZE_SMALL);
+
+        if self.page_type != PageType::SmallPage {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let page = self.find_page_mut(page_addr).unwrap();
+        if page.start != page_addr {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if !page.frame.is_null() {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+
+        let frame = super::phys::allocate_frame(PageType::SmallPage)?;
+        let phys_addr = frame.get().unwrap().start();
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                (phys_addr + super::PAGING_DIRECT_MAP_OFFSET) as usize as *mut u8,
+                bytes.len(),
+            );
+        }
+        page.frame = frame;
+        page.mapping_options = mapping_options;
+
+        self.address_space().page_table.map_page(
+            phys_addr,
+            page_addr,
+            PageType::SmallPage,
+            mapping_options | MappingOptions::DONT_ZERO,
+        );
+
+        Ok(())
+    }
+
+    // Changes the protection of pages in [start, end); the pages must be
+    // owned by this segment (i.e. not shared or mapped from elsewhere).
+    pub(super) fn protect(
+        &mut self,
+        start: u64,
+        end: u64,
+        mapping_options: MappingOptions,
+    ) -> Result<(), ErrorCode> {
+        debug_assert!(self.segment.contains(start));
+        debug_assert!(end <= self.segment.end());
+
+        let page_size = self.page_type.page_size();
+        if (start & (page_size - 1)) != 0 || (end & (page_size - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        // Reserved/shared/paged segments have no mapping options;
+        // stacks and MMIO have their protection fixed.
+        if self.mapping_options.is_empty()
+            || self
+                .mapping_options
+                .intersects(MappingOptions::MMIO | MappingOptions::GUARD)
+        {
+            log::debug!("protect: segment 0x{:x} is fixed", self.segment.start);
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let executable = mapping_options.contains(MappingOptions::EXECUTABLE);
+        let mut cursor = self
+            .pages
+            .lower_bound(intrusive_collections::Bound::Included(&start));
+        while let Some(page) = cursor.get() {
+            if page.start >= end {
+                break;
+            }
+            // Code must not be writable via another mapping.
+            if executable && page.frame.refs() > 1 {
+                log::debug!("protect: page 0x{:x} is shared", page.start);
+                return Err(ErrorCode::NotAllowed);
+            }
+            cursor.move_next();
+        }
+
+        let mut cursor = self
+            .pages
+            .lower_bound(intrusive_collections::Bound::Included(&start));
+        while let Some(ptr) = cursor.clone_pointer() {
+            let page = unsafe { UnsafeRef::into_raw(ptr).as_mut().unwrap() };
+            if page.start >= end {
+                break;
+            }
+
+            let lazy = page.mapping_options & MappingOptions::LAZY;
+            page.mapping_options = mapping_options | lazy;
+            if !page.frame.is_null() {
+                self.address_space().page_table.protect_page(
+                    page.start,
+                    self.page_type,
+                    mapping_options,
+                );
+            }
+            cursor.move_next();
+        }
+
+        Ok(())
+    }
+
+    // Applies @advice to the pages in [start, end). Returns the number of
+    // pages reclaimed.
+    pub(super) fn advise(
+        &mut self,
+        start: u64,
+        end: u64,
+        advice: MemAdvice,
+    ) -> Result<u64, ErrorCode> {
+        debug_assert!(self.segment.contains(start));
+        debug_assert!(end <= self.segment.end());
+
+        // Only normal private memory can be reclaimed and then re-populated
+        // on page faults (see fix_pagefault()).
+        if self.page_type != PageType::SmallPage
+            || self.mapping_options.is_empty()
+            || self.mapping_options.contains(MappingOptions::MMIO)
+        {
+            log::debug!(
+                "advise: segment 0x{:x} is not advisable",
+                self.segment.start
+            );
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if (start & (PAGE_SIZE_SMALL - 1)) != 0 || (end & (PAGE_SIZE_SMALL - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut reclaimed = 0;
+        let mut cursor = self
+            .pages
+            .lower_bound(intrusive_collections::Bound::Included(&start));
+        while let Some(ptr) = cursor.clone_pointer() {
+            let page = unsafe { UnsafeRef::into_raw(ptr).as_mut().unwrap() };
+            if page.start >= end {
+                break;
+            }
+            cursor.move_next();
+
+            if page.mapping_options.is_empty() {
+                continue; // A guard page.
+            }
+
+            match advice {
+                MemAdvice::Cold => {} // We don't swap, so nothing to do.
+                MemAdvice::DontNeed => {
+                    if self.reclaim_page(page) {
+                        reclaimed += 1;
+                    }
+                }
+                MemAdvice::Free => {
+                    if page.frame.is_null()
+                        || page.frame.refs() > 1
+                        || page.mapping_options.contains(MappingOptions::FREEABLE)
+                    {
+                        continue;
+                    }
+                    page.mapping_options |= MappingOptions::FREEABLE;
+                    if page.mapping_options.contains(MappingOptions::WRITABLE) {
+                        // Write-protect the page to detect that it is needed again.
+                        let mut mapping_options = page.mapping_options;
+                        mapping_options.remove(MappingOptions::WRITABLE | MappingOptions::FREEABLE);
+                        mapping_options.remove(MappingOptions::LAZY);
+                        self.address_space().page_table.protect_page(
+                            page.start,
+                            PageType::SmallPage,
+                            mapping_options,
+                        );
+                    }
+                }
+                MemAdvice::WillNeed => {
+                    if !page.frame.is_null() {
+                        continue;
+                    }
+                    // This is a hint: stop quietly if there is no memory.
+                    let frame = match super::phys::allocate_frame(PageType::SmallPage) {
+                        Ok(frame) => frame,
+                        Err(_) => break,
+                    };
+                    let mut mapping_options = page.mapping_options;
+                    mapping_options.remove(MappingOptions::LAZY | MappingOptions::GUARD);
+                    self.address_space().page_table.map_page(
+                        frame.get().unwrap().start(),
+                        page.start,
+                        PageType::SmallPage,
+                        mapping_options,
+                    );
+                    page.frame = frame;
+                }
+            }
+        }
+
+        Ok(reclaimed)
+    }
+
+    // Drops the pages marked as freeable (see advise()).
+    // Returns the number of pages reclaimed.
+    pub(super) fn reclaim_freeable(&mut self) -> u64 {
+        if self.page_type != PageType::SmallPage || self.mapping_options.is_empty() {
+            return 0;
+        }
+
+        let mut reclaimed = 0;
+        let mut cursor = self.pages.front();
+        while let Some(ptr) = cursor.clone_pointer() {
+            let page = unsafe { UnsafeRef::into_raw(ptr).as_mut().unwrap() };
+            cursor.move_next();
+            if page.mapping_options.contains(MappingOptions::FREEABLE) && self.reclaim_page(page) {
+                reclaimed += 1;
+            }
+        }
+
+        reclaimed
+    }
+
+    // Unmaps a private page, so that it is zero-filled on next access.
+    fn reclaim_page(&self, page: &mut Page) -> bool {
+        page.mapping_options.remove(MappingOptions::FREEABLE);
+        if page.frame.is_null() || page.frame.refs() > 1 {
+            return false;
+        }
+
+        let frame = page.frame.take();
+        self.address_space().page_table.unmap_page(
+            frame.get().unwrap().start(),
+            page.start,
+            PageType::SmallPage,
+        );
+        true
+    }
+
     pub(super) fn share_with(
         &self,
         other: &mut Self,
@@ -509,6 +805,11 @@ impl VmemSegment {
         debug_assert!(!other.pages.is_empty());
         debug_assert_eq!(self.segment.size, other.segment.size);
 
+        if self.page_type != other.page_type {
+            other.relayout(self.page_type)?;
+        }
+        let page_type = self.page_type;
+
         let mut self_cursor = self.pages.front();
         let mut other_cursor = other.pages.front();
         while !self_cursor.is_null() {
@@ -526,17 +827,23 @@ impl VmemSegment {
                 other.address_space().page_table.unmap_page(
                     that_page.frame.get().unwrap().start(),
                     that_page.start,
-                    PageType::SmallPage,
+                    page_type,
                 );
+                if page_type == PageType::MidPage {
+                    other.address_space().mem_stats.sub_huge(1);
+                }
             }
 
             that_page.frame = this_page.frame.clone();
             other.address_space().page_table.map_page(
                 this_page.frame.get().unwrap().start(),
                 that_page.start,
-                PageType::SmallPage,
+                page_type,
                 mapping_options,
             );
+            if page_type == PageType::MidPage {
+                other.address_space().mem_stats.add_huge(1);
+            }
 
             self_cursor.move_next();
             other_cursor.move_next();
@@ -544,6 +851,34 @@ impl VmemSegment {
 
         Ok(())
     }
+
+    // Re-creates the pages of an unmapped segment with a different page type,
+    // so that a peer's segment backed by pages of that type can be shared into it.
+    fn relayout(&mut self, page_type: PageType) -> Result<(), ErrorCode> {
+        if !self.mapping_options.is_empty()
+            || (self.segment.start & (page_type.page_size() - 1)) != 0
+            || (self.segment.size & (page_type.page_size() - 1)) != 0
+        {
+            log::debug!(
+                "relayout: segment 0x{:x} can't change page type",
+                self.segment.start
+            );
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if self.pages.iter().any(|page| !page.frame.is_null()) {
+            log::debug!("relayout: segment 0x{:x} is mapped", self.segment.start);
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut pages = self.pages.take();
+        let mut cursor = pages.front_mut();
+        while let Some(page) = cursor.remove() {
+            self.clear_page(page);
+        }
+
+        self.page_type = page_type;
+        self.allocate_pages()
+    }
 }
 
 // ----------------------- Segment Map ----------------------------- //
@@ -668,6 +1003,14 @@ impl SegmentMap {
         None
     }
 
+    pub(super) fn for_each_mut<F: FnMut(&mut VmemSegment)>(&mut self, mut f: F) {
+        let mut cursor = self.segments.front_mut();
+        while let Some(seg) = cursor.get() {
+            f(unsafe { seg.vmem_segment_mut() });
+            cursor.move_next();
+        }
+    }
+
     pub(super) fn intersects(&self, segment: &MemorySegment) -> bool {
         if let Some(seg) = self
             .segments
@@ -10,6 +10,20 @@
 //
 // Priorities: see enum Priority
 // Cpu affinity: a single CPU hint
+// Load balancing: a CPU about to go idle steals a queued job from the busiest
+// CPU, if the job is migratable and is no longer cache-hot (has waited long
+// enough); busy CPUs periodically wake idle CPUs so that they can steal.
+// Real-time: threads with SchedPolicy::Fifo or SchedPolicy::RoundRobin run
+// at Priority::High; posting a high priority job preempts the userspace thread
+// running on the target CPU (via an IPI), so wakeup latency is bounded.
+// CPU hotplug: CPUs (other than the BSP) can be taken offline at runtime
+// (e.g. when the host shrinks the VM). An offline CPU migrates its queued
+// jobs to other CPUs and does not pick up new work; it keeps running only
+// the timers already posted on it (timers are per-CPU, see post_timer()),
+// and then sleeps with no deadline programmed.
+// Tick-less idle: the periodic scheduler tick (used to preempt userspace
+// threads) is stopped when a CPU goes idle; only the earliest timer
+// deadline is programmed, so idle CPUs don't wake up needlessly.
 // Sched groups: none: everything within a priority is round robin, so
 // a process with many threads will negatively affect a process with few threads.
 //
@@ -38,6 +52,11 @@ use super::Timer;
 
 const INITIAL_QUEUE_SIZE: usize = 64;
 
+const SCHED_TICK: core::time::Duration = core::time::Duration::from_millis(20);
+
+// CPU 0 updates the system time (see update_system_time()) this often (in TSC ticks).
+const SYSTEM_TIME_UPDATE_TSC: u64 = 1_000_000_000;
+
 static PERCPU_SCHEDULERS: StaticRef<StaticPerCpu<Scheduler>> = StaticRef::default_const();
 static USER_IRQ_WAITERS: StaticRef<alloc::vec::Vec<Arc<SysObject>>> = StaticRef::default_const();
 
@@ -47,14 +66,54 @@ static PERCPU_TIMERS: StaticRef<StaticPerCpu<Instant>> = StaticRef::default_cons
 static GLOBAL_READY_QUEUE_NORMAL: StaticRef<crate::util::SpinLock<VecDeque<Job>>> =
     StaticRef::default_const();
 
-#[derive(Clone)]
+// Note: higher priorities have lower values, so that min() gives the highest priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
 pub enum Priority {
-    High,   // Always picked; may starve lower-priority jobs.
-    Normal, // Normal jobs (most user jobs).
-    Low,    // Low priority jobs. Will run eventually (unless high priority jobs starve the rest).
-    Idle,   // Jobs for when there is nothing else to do. May never run.
+    High = 0,   // Always picked; may starve lower-priority jobs.
+    Normal = 1, // Normal jobs (most user jobs).
+    Low = 2, // Low priority jobs. Will run eventually (unless high priority jobs starve the rest).
+    Idle = 3, // Jobs for when there is nothing else to do. May never run.
+}
+
+impl Priority {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            0 => Some(Self::High),
+            1 => Some(Self::Normal),
+            2 => Some(Self::Low),
+            3 => Some(Self::Idle),
+            _ => None,
+        }
+    }
+}
+
+// Scheduling policies of user threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SchedPolicy {
+    Normal = 0,
+    Fifo = 1,       // Real-time: runs until it blocks.
+    RoundRobin = 2, // Real-time: yields to other real-time threads every RR_QUANTUM.
 }
 
+impl SchedPolicy {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Fifo),
+            2 => Some(Self::RoundRobin),
+            _ => None,
+        }
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        *self != Self::Normal
+    }
+}
+
+pub const RR_QUANTUM: core::time::Duration = core::time::Duration::from_millis(10);
+
 pub type SchedulerJobFn = fn(&Weak<Thread>, u64);
 
 fn empty_job(_: &Weak<Thread>, _: u64) {}
@@ -65,6 +124,11 @@ pub struct Job {
     pub arg: u64,
     pub prio: Priority,
     pub cpu: uCpus, // uCpus::MAX => not set.
+
+    // The job was put on a CPU for cache locality only (not affinity),
+    // so it can be stolen by another CPU.
+    migratable: bool,
+    queued_at: Instant,
 }
 
 unsafe impl Send for Job {}
@@ -78,6 +142,8 @@ impl Default for Job {
             arg: 0,
             prio: Priority::Idle,
             cpu: uCpus::MAX,
+            migratable: false,
+            queued_at: Instant::nan(),
         }
     }
 }
@@ -90,6 +156,8 @@ impl Job {
             arg: 0,
             prio: Priority::Normal,
             cpu,
+            migratable: false,
+            queued_at: Instant::nan(),
         }
     }
 
@@ -100,6 +168,8 @@ impl Job {
             arg,
             prio: Priority::Normal,
             cpu: uCpus::MAX,
+            migratable: false,
+            queued_at: Instant::nan(),
         }
     }
 
@@ -108,8 +178,10 @@ impl Job {
             job_fn,
             thread: thread.get_weak(),
             arg: 0,
-            prio: Priority::Normal,
+            prio: thread.priority(),
             cpu: thread.get_cpu_affinity(),
+            migratable: false,
+            queued_at: Instant::nan(),
         }
     }
 
@@ -122,8 +194,10 @@ impl Job {
                 job_fn,
                 thread: thread.get_weak(),
                 arg: 0,
-                prio: Priority::Normal,
+                prio: thread.priority(),
                 cpu: crate::arch::current_cpu(),
+                migratable: true,
+                queued_at: Instant::nan(),
             }
         }
     }
@@ -143,7 +217,13 @@ struct Scheduler {
 
     queue_length: AtomicU32,
     idle: AtomicBool,
+    online: AtomicBool,
 
+    // Set when a high priority job is posted: the userspace thread
+    // running on this CPU should be preempted.
+    preempt: AtomicBool,
+
+    high_queue: SpinLock<VecDeque<Job>>,
     normal_queue: SpinLock<VecDeque<Job>>,
 
     timers: Timers,
@@ -155,6 +235,11 @@ struct Scheduler {
 
     timer_irq_tick: AtomicBool,
 
+    // Stats.
+    ticks: AtomicU64,
+    idle_wakeups: AtomicU64,
+    spurious_wakeups: AtomicU64, // Woke up from idle, but had nothing to do.
+
     #[cfg(debug_assertions)]
     die_on_next_wake: AtomicBool,
 
@@ -165,12 +250,25 @@ struct Scheduler {
 impl Scheduler {
     const LOAD_PERIOD: u64 = 1u64 << 24; // Units are tscs on x64.
 
+    // A job that has waited in a queue for less than this is likely to still
+    // have its working set in the CPU's caches, so it is not stolen.
+    const CACHE_HOT: core::time::Duration = core::time::Duration::from_micros(500);
+
+    // Only steal from CPUs that have at least this many queued jobs.
+    const STEAL_MIN_QUEUE_DEPTH: u32 = 2;
+
+    // Busy CPUs wake idle CPUs (to steal work) every this many timer ticks.
+    const BALANCE_TICKS: u64 = 5;
+
     fn new() -> Self {
         Scheduler {
             cpu: crate::arch::current_cpu(),
             wake: AtomicBool::new(false),
+            online: AtomicBool::new(true),
             queue_length: AtomicU32::new(0),
             idle: AtomicBool::new(false),
+            preempt: AtomicBool::new(false),
+            high_queue: SpinLock::new(VecDeque::new()),
             normal_queue: SpinLock::new(VecDeque::with_capacity(INITIAL_QUEUE_SIZE)),
             timers: Timers::new(),
 
@@ -179,6 +277,9 @@ impl Scheduler {
             load_prev: AtomicU64::new(0),
             load_curr: 0,
             timer_irq_tick: AtomicBool::new(false),
+            ticks: AtomicU64::new(0),
+            idle_wakeups: AtomicU64::new(0),
+            spurious_wakeups: AtomicU64::new(0),
 
             #[cfg(debug_assertions)]
             die_on_next_wake: AtomicBool::new(false),
@@ -266,6 +367,131 @@ impl Scheduler {
         }
     }
 
+    fn is_online(&self) -> bool {
+        self.online.load(Ordering::Acquire)
+    }
+
+    // Called on an offline CPU: re-posts queued jobs, which post() will put on online CPUs.
+    fn migrate_jobs(&self) {
+        if self.queue_depth() == 0 {
+            return;
+        }
+
+        let mut jobs = core::mem::take(&mut *self.high_queue.lock(line!()));
+        jobs.append(&mut *self.normal_queue.lock(line!()));
+        self.queue_length
+            .fetch_sub(jobs.len() as u32, Ordering::Relaxed);
+
+        for job in jobs {
+            post(job);
+        }
+    }
+
+    fn queue_depth(&self) -> u32 {
+        self.queue_length.load(Ordering::Relaxed)
+    }
+
+    // Called when there is nothing to do: runs a job stolen from the busiest CPU.
+    fn try_steal(&self) -> bool {
+        let mut busiest: Option<(uCpus, u32)> = None;
+        let mut find_busiest = |cpu: uCpus, scheduler: &Scheduler| -> bool {
+            let depth = scheduler.queue_depth();
+            if cpu != self.cpu
+                && depth >= Self::STEAL_MIN_QUEUE_DEPTH
+                && busiest.map_or(true, |(_, max_depth)| max_depth < depth)
+            {
+                busiest = Some((cpu, depth));
+            }
+            false
+        };
+        PERCPU_SCHEDULERS.for_each_cpu(&mut find_busiest);
+
+        let victim = match busiest {
+            Some((cpu, _)) => PERCPU_SCHEDULERS.get_for_cpu(cpu),
+            None => return false,
+        };
+
+        // Steal the job that has waited the longest and is migratable.
+        let now = Instant::now();
+        let maybe_job = {
+            let mut queue = victim.normal_queue.lock(line!());
+            let pos = queue
+                .iter()
+                .position(|job| job.migratable && (job.queued_at + Self::CACHE_HOT) <= now);
+            pos.and_then(|pos| queue.remove(pos))
+        };
+
+        match maybe_job {
+            Some(mut job) => {
+                victim.queue_length.fetch_sub(1, Ordering::Relaxed);
+                job.cpu = self.cpu;
+                job.run();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Called on timer ticks: if this CPU has queued jobs, wake an idle CPU to steal them.
+    fn balance(&mut self) {
+        let ticks = self.ticks.fetch_add(1, Ordering::Relaxed) + 1;
+        if ticks % Self::BALANCE_TICKS != 0 || self.queue_depth() < Self::STEAL_MIN_QUEUE_DEPTH {
+            return;
+        }
+
+        let mut wake_idle = |_: uCpus, scheduler: &Scheduler| -> bool {
+            if scheduler.cpu != self.cpu
+                && scheduler.is_online()
+                && scheduler.idle.load(Ordering::Acquire)
+            {
+                scheduler.wake();
+                return true;
+            }
+            false
+        };
+        PERCPU_SCHEDULERS.for_each_cpu(&mut wake_idle);
+    }
+
+    // Tick-less idle: instead of the periodic tick, program the earliest deadline, if any.
+    fn stop_tick(&self, system_time_update: Instant) {
+        let (_, mut deadline) = self.timers.peek();
+        if self.cpu == 0 && (deadline.is_nan() || deadline > system_time_update) {
+            deadline = system_time_update;
+        }
+
+        if deadline.is_nan() {
+            crate::arch::irq::cancel_timer();
+        } else {
+            crate::arch::irq::set_timer(deadline);
+        }
+        *PERCPU_TIMERS.get_per_cpu() = deadline;
+    }
+
+    // Called when the CPU wakes from idle.
+    fn restart_tick(&self) {
+        let when = Instant::now() + SCHED_TICK;
+        let percpu_timer = PERCPU_TIMERS.get_per_cpu();
+        if percpu_timer.is_nan() || *percpu_timer > when {
+            crate::arch::irq::set_timer(when);
+            *percpu_timer = when;
+        }
+    }
+
+    fn timer_stats(&self) -> moto_sys::stats::CpuTimerStatsV1 {
+        let (pending_timers, next_deadline) = self.timers.peek();
+        moto_sys::stats::CpuTimerStatsV1 {
+            pending_timers: pending_timers as u64,
+            next_deadline: if next_deadline.is_nan() {
+                0
+            } else {
+                next_deadline.as_u64()
+            },
+            idle_wakeups: self.idle_wakeups.load(Ordering::Relaxed),
+            spurious_wakeups: self.spurious_wakeups.load(Ordering::Relaxed),
+            ticks: self.ticks.load(Ordering::Relaxed),
+        }
+    }
+
     fn wake(&self) {
         if self.cpu == crate::arch::current_cpu() {
             self.local_wake();
@@ -295,6 +521,9 @@ impl Scheduler {
         let now = crate::arch::time::Instant::now().as_u64();
         self.last_alive_check.store(now, Ordering::Relaxed);
         let mut check = |_: uCpus, scheduler: &Scheduler| -> bool {
+            if scheduler.idle.load(Ordering::Acquire) {
+                return false; // Idle CPUs may sleep for long (tick-less idle).
+            }
             let last_check = scheduler.last_alive_check.load(Ordering::Relaxed);
             if now > (last_check + 10_000_000_000) {
                 scheduler.die();
@@ -325,6 +554,7 @@ impl Scheduler {
         );
 
         let mut last_system_time_update = now_tsc;
+        let mut woke_from_idle = false;
 
         loop {
             #[cfg(debug_assertions)]
@@ -335,7 +565,7 @@ impl Scheduler {
             if self.cpu == 0 {
                 // TODO: should we do this more often? less often?
                 let now_tsc = crate::arch::time::Instant::now().as_u64();
-                if now_tsc - last_system_time_update > 1_000_000_000 {
+                if now_tsc - last_system_time_update > SYSTEM_TIME_UPDATE_TSC {
                     last_system_time_update = now_tsc;
                     update_system_time();
                 }
@@ -343,10 +573,26 @@ impl Scheduler {
 
             crate::uspace::process_wake_events(); // May add jobs to queues.
 
+            let online = self.is_online();
+            if !online {
+                self.migrate_jobs();
+            }
+
             curr_iteration += 1;
 
             if self.timer_irq_tick.swap(false, Ordering::Relaxed) {
                 self.update_load(true);
+                self.balance();
+            }
+
+            // High priority jobs are always picked first.
+            let maybe_job = self.high_queue.lock(line!()).pop_front();
+            if let Some(job) = maybe_job {
+                self.preempt.store(false, Ordering::Relaxed);
+                job.run();
+                self.queue_length.fetch_sub(1, Ordering::Relaxed);
+                last_job_iter = curr_iteration;
+                continue;
             }
 
             // Round robit between queues.
@@ -367,7 +613,7 @@ impl Scheduler {
                 }
             }
 
-            if curr_iteration % 3 == 1 {
+            if curr_iteration % 3 == 1 && online {
                 let maybe_job = { GLOBAL_READY_QUEUE_NORMAL.lock(102).pop_front() };
                 if let Some(job) = maybe_job {
                     job.run();
@@ -404,6 +650,15 @@ impl Scheduler {
                 continue;
             }
 
+            if online && self.try_steal() {
+                last_job_iter = curr_iteration;
+                continue;
+            }
+
+            if woke_from_idle && last_job_iter == 0 {
+                self.spurious_wakeups.fetch_add(1, Ordering::Relaxed);
+            }
+
             self.idle_start();
             if nosleep {
                 self.idle.store(true, Ordering::Release);
@@ -416,13 +671,24 @@ impl Scheduler {
                 } else {
                     crate::xray::tracing::trace("scheduler hlt", 0, 0, 0);
                     crate::xray::stats::system_stats_ref().start_cpu_usage_kernel();
+                    self.stop_tick(Instant::from_u64(
+                        last_system_time_update + SYSTEM_TIME_UPDATE_TSC,
+                    ));
                     self.idle.store(true, Ordering::Release);
                     interrupts::enable_and_hlt();
+                    #[cfg(debug_assertions)]
+                    self.last_alive_check.store(
+                        crate::arch::time::Instant::now().as_u64(),
+                        Ordering::Relaxed,
+                    );
                     self.idle.store(false, Ordering::Release);
+                    self.restart_tick();
+                    self.idle_wakeups.fetch_add(1, Ordering::Relaxed);
                     crate::xray::stats::system_stats_ref().stop_cpu_usage_kernel();
                     crate::xray::tracing::trace("scheduler hlt wake", 0, 0, 0);
                 }
             }
+            woke_from_idle = true;
             self.idle_stop();
             curr_iteration = 0; // Prevent overflows.
             last_job_iter = curr_iteration; // Reset the interval.
@@ -498,13 +764,25 @@ fn update_system_time() {
     crate::arch::time::populate_kernel_static_page(shared_page);
 }
 
-pub fn post(job: Job) {
+pub fn post(mut job: Job) {
+    job.queued_at = Instant::now();
+
+    if job.cpu != uCpus::MAX && !cpu_online(job.cpu) {
+        // Threads affined to an offline CPU run elsewhere until it is back online.
+        job.cpu = uCpus::MAX;
+    }
+
+    if job.prio == Priority::High {
+        post_high(job, false);
+        return;
+    }
+
     if job.cpu == uCpus::MAX {
         {
             GLOBAL_READY_QUEUE_NORMAL.lock(line!()).push_back(job)
         };
         let mut wake = |_: uCpus, scheduler: &Scheduler| -> bool {
-            if scheduler.idle.load(Ordering::Acquire) {
+            if scheduler.is_online() && scheduler.idle.load(Ordering::Acquire) {
                 scheduler.wake();
                 return true;
             }
@@ -520,6 +798,121 @@ pub fn post(job: Job) {
     }
 }
 
+// Posts a high priority job ahead of other queued high priority jobs:
+// used to keep a preempted real-time (FIFO) thread running.
+pub fn post_front(mut job: Job) {
+    debug_assert_eq!(job.prio, Priority::High);
+    job.queued_at = Instant::now();
+    post_high(job, true);
+}
+
+fn post_high(job: Job, front: bool) {
+    // High priority jobs are not put on the global queue, as it is
+    // polled round-robin with the local queue and timers.
+    let cpu = if job.cpu != uCpus::MAX && cpu_online(job.cpu) {
+        job.cpu
+    } else {
+        // Prefer an idle CPU, to not preempt anything.
+        let mut idle_cpu = uCpus::MAX;
+        let mut find_idle = |cpu: uCpus, scheduler: &Scheduler| -> bool {
+            if scheduler.is_online() && scheduler.idle.load(Ordering::Acquire) {
+                idle_cpu = cpu;
+                return true;
+            }
+            false
+        };
+        PERCPU_SCHEDULERS.for_each_cpu(&mut find_idle);
+        if idle_cpu != uCpus::MAX {
+            idle_cpu
+        } else if cpu_online(current_cpu()) {
+            current_cpu()
+        } else {
+            crate::arch::bsp()
+        }
+    };
+    assert!(cpu < crate::arch::num_cpus());
+
+    let scheduler = PERCPU_SCHEDULERS.get_for_cpu(cpu);
+    {
+        let mut queue = scheduler.high_queue.lock(line!());
+        if front {
+            queue.push_front(job);
+        } else {
+            queue.push_back(job);
+        }
+    }
+    scheduler.queue_length.fetch_add(1, Ordering::Relaxed);
+
+    if front {
+        // The job is being re-queued, nothing to preempt.
+        scheduler.wake();
+        return;
+    }
+
+    // Bounded wakeup latency: if the CPU is running a userspace thread,
+    // preempt it now instead of waiting for the next timer tick. Note that
+    // the current CPU may be in a syscall that will return to userspace,
+    // so we send the IPI to self as well.
+    scheduler.preempt.store(true, Ordering::Release);
+    if scheduler.idle.load(Ordering::Acquire) || cpu != current_cpu() {
+        scheduler.wake();
+    } else {
+        scheduler.wake.store(true, Ordering::Release);
+        crate::arch::irq::wake_remote_cpu(cpu);
+    }
+}
+
+// Re-queues the jobs of @thread waiting in normal queues as high priority
+// jobs: called when the thread's priority is raised to high (see
+// Thread::donate_priority()) while it is runnable.
+pub fn boost(thread: &Arc<Thread>) {
+    let weak = Arc::downgrade(thread);
+    let mut jobs = alloc::vec::Vec::new();
+
+    {
+        let mut queue = GLOBAL_READY_QUEUE_NORMAL.lock(line!());
+        queue.retain_mut(|job| {
+            if Weak::ptr_eq(&job.thread, &weak) {
+                jobs.push(core::mem::take(job));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    let mut take_jobs = |_: uCpus, scheduler: &Scheduler| -> bool {
+        let mut queue = scheduler.normal_queue.lock(line!());
+        let before = queue.len();
+        queue.retain_mut(|job| {
+            if Weak::ptr_eq(&job.thread, &weak) {
+                jobs.push(core::mem::take(job));
+                false
+            } else {
+                true
+            }
+        });
+        scheduler
+            .queue_length
+            .fetch_sub((before - queue.len()) as u32, Ordering::Relaxed);
+        false
+    };
+    PERCPU_SCHEDULERS.for_each_cpu(&mut take_jobs);
+
+    for mut job in jobs {
+        job.prio = Priority::High;
+        post_high(job, false);
+    }
+}
+
+// Called by IRQ (IRQ_WAKEUP) when in userspace: whether to preempt the running thread.
+pub fn take_preempt_request() -> bool {
+    PERCPU_SCHEDULERS
+        .get_per_cpu()
+        .preempt
+        .swap(false, Ordering::AcqRel)
+}
+
 pub fn post_timer(timer: Timer) {
     let when = timer.when();
 
@@ -582,9 +975,7 @@ pub fn on_timer_irq() {
     let scheduler = PERCPU_SCHEDULERS.get_per_cpu();
     scheduler.timer_irq_tick.store(true, Ordering::Relaxed);
 
-    const SCHED_TICK_MILLIS: u64 = 20;
-    let when =
-        crate::arch::time::Instant::now() + core::time::Duration::from_millis(SCHED_TICK_MILLIS);
+    let when = crate::arch::time::Instant::now() + SCHED_TICK;
 
     // Unlike the conditional vs curr_timer in maybe_program_timer() below, we set the timer
     // unconditionally here, because on_timer_irq() is called from the irq, that is the current timer
@@ -611,6 +1002,67 @@ fn maybe_program_timer(when: Instant) {
     }
 }
 
+// The number of jobs queued on each CPU (not including the global queue).
+pub fn get_queue_depths(buf: &mut [u32]) {
+    let num_cpus = crate::arch::num_cpus() as usize;
+    assert_eq!(buf.len(), num_cpus);
+
+    for cpu in 0..num_cpus {
+        buf[cpu] = PERCPU_SCHEDULERS.get_for_cpu(cpu as uCpus).queue_depth();
+    }
+}
+
+pub fn cpu_online(cpu: uCpus) -> bool {
+    PERCPU_SCHEDULERS.get_for_cpu(cpu).is_online()
+}
+
+// Returns the bitmask of online CPUs.
+pub fn online_cpus() -> u64 {
+    let mut mask = 0_u64;
+    let mut add = |cpu: uCpus, scheduler: &Scheduler| -> bool {
+        if scheduler.is_online() {
+            mask |= 1_u64 << cpu;
+        }
+        false
+    };
+    PERCPU_SCHEDULERS.for_each_cpu(&mut add);
+    mask
+}
+
+pub fn set_cpu_online(cpu: uCpus, online: bool) -> Result<(), ErrorCode> {
+    assert!(cpu < crate::arch::num_cpus());
+    if cpu == crate::arch::bsp() {
+        // The BSP updates system time and handles device IRQs.
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    let scheduler = PERCPU_SCHEDULERS.get_for_cpu(cpu);
+    if scheduler.online.swap(online, Ordering::AcqRel) == online {
+        return Ok(());
+    }
+    log::info!(
+        "CPU {} is now {}",
+        cpu,
+        if online { "online" } else { "offline" }
+    );
+
+    if !online {
+        // Move the running userspace thread (if any) off the CPU now.
+        scheduler.preempt.store(true, Ordering::Release);
+    }
+    scheduler.wake();
+    Ok(())
+}
+
+pub fn get_timer_stats(buf: &mut [moto_sys::stats::CpuTimerStatsV1]) {
+    let num_cpus = crate::arch::num_cpus() as usize;
+    assert_eq!(buf.len(), num_cpus);
+
+    for cpu in 0..num_cpus {
+        buf[cpu] = PERCPU_SCHEDULERS.get_for_cpu(cpu as uCpus).timer_stats();
+    }
+}
+
 pub fn get_usage(buf: &mut [f32]) {
     let num_cpus = crate::arch::num_cpus() as usize;
     assert_eq!(buf.len(), num_cpus);
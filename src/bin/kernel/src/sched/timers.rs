@@ -4,6 +4,8 @@ use super::{scheduler::Job, SchedulerJobFn};
 use crate::{arch::time::Instant, config::uCpus, uspace::process::Thread, util::SpinLock};
 use core::sync::atomic::*;
 
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
 pub struct Timer {
     when: Instant,
     id: u64, // Need unique ids for Eq trait and to cancel in thread.
@@ -16,8 +18,6 @@ unsafe impl Sync for Timer {}
 
 impl Timer {
     pub fn new(job_fn: SchedulerJobFn, thread: &Thread, when: Instant, cpu: uCpus) -> Self {
-        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
-
         debug_assert!(!when.is_nan());
 
         let mut job = Job::new(job_fn, thread);
@@ -27,6 +27,16 @@ impl Timer {
         Self { when, job, id, cpu }
     }
 
+    // A timer not associated with any thread: job_fn gets the timer ID as the arg.
+    pub fn new_detached(job_fn: SchedulerJobFn, when: Instant, cpu: uCpus) -> Self {
+        debug_assert!(!when.is_nan());
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let job = Job::new_with_arg(job_fn, id);
+
+        Self { when, job, id, cpu }
+    }
+
     pub fn id(&self) -> u64 {
         self.id
     }
@@ -152,4 +162,14 @@ impl Timers {
     pub fn pop(&self, cutoff: Instant) -> Result<Timer, Instant> {
         self.inner.lock(line!()).pop(cutoff)
     }
+
+    // Returns the number of pending timers and the earliest deadline (nan if none).
+    pub fn peek(&self) -> (usize, Instant) {
+        let inner = self.inner.lock(line!());
+        let next = match inner.time_queue.first_key_value() {
+            Some((when, _)) => *when,
+            None => Instant::nan(),
+        };
+        (inner.timers.len(), next)
+    }
 }
@@ -0,0 +1,166 @@
+// Event rings: see moto_sys::event_ring for the overview and the layout.
+//
+// The ring page is owned by a private (backing) address space, like named
+// shm segments, and is also mapped into the kernel via the direct map, so
+// that events can be produced from any context, including IRQs: producing
+// an event takes no blocking locks and does not allocate.
+//
+// The page is writable by the consumer, so the kernel never trusts
+// anything it reads from it: the head is tracked privately, and a bad
+// tail just makes the ring look full.
+
+use super::process::Process;
+use super::SysObject;
+use crate::mm::user::UserAddressSpace;
+use crate::mm::MappingOptions;
+use alloc::sync::Arc;
+use core::sync::atomic::*;
+use moto_sys::event_ring::*;
+use moto_sys::{ErrorCode, SysHandle};
+
+pub struct EventRing {
+    _backing: Arc<UserAddressSpace>, // Owns the ring page.
+    page: *mut EventRingPage,        // Kernel (direct map) address.
+
+    // Producers serialize on this lock; it is always taken with interrupts
+    // disabled, so a spinning producer never waits on itself.
+    producer_lock: AtomicBool,
+    head: AtomicU32,
+}
+
+unsafe impl Send for EventRing {}
+unsafe impl Sync for EventRing {}
+
+// What a wait object attached to a ring delivers its wakes to.
+pub struct Attachment {
+    pub ring_object: Arc<SysObject>,
+    pub ring: Arc<EventRing>,
+    pub cookie: u64,
+}
+
+impl EventRing {
+    // Adds an event to the ring. Returns true if the consumer must be woken.
+    // Must be called with interrupts disabled.
+    pub fn push(&self, cookie: u64, flags: u32) -> bool {
+        while self
+            .producer_lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        let page = unsafe { &*self.page };
+        let head = self.head.load(Ordering::Relaxed);
+        let next = (head + 1) % EVENT_RING_CAPACITY;
+        let tail = page.header.tail.load(Ordering::Acquire);
+
+        let wake = if tail >= EVENT_RING_CAPACITY || next == tail {
+            page.header.dropped.fetch_add(1, Ordering::Relaxed);
+            // Make sure the consumer drains the ring.
+            true
+        } else {
+            unsafe {
+                core::ptr::write_volatile(
+                    core::ptr::addr_of_mut!((*self.page).entries[head as usize]),
+                    EventRingEntry {
+                        cookie,
+                        flags,
+                        _reserved: 0,
+                    },
+                );
+            }
+            self.head.store(next, Ordering::Relaxed);
+            page.header.head.store(next, Ordering::Release);
+            (page.header.flags.load(Ordering::SeqCst) & EVENT_RING_F_NEED_WAKE) != 0
+        };
+
+        if wake {
+            page.header
+                .flags
+                .fetch_and(!EVENT_RING_F_NEED_WAKE, Ordering::Relaxed);
+        }
+
+        self.producer_lock.store(false, Ordering::Release);
+        wake
+    }
+}
+
+pub fn create(process: &Arc<Process>, url: &str) -> Result<SysHandle, ErrorCode> {
+    let args: alloc::vec::Vec<&str> = match url.strip_prefix("event_ring:") {
+        Some(args) => args.split(';').filter(|s| !s.is_empty()).collect(),
+        None => return Err(ErrorCode::InvalidArgument),
+    };
+    let address =
+        crate::util::decode_arg::<u64>(&args, "address").ok_or(ErrorCode::InvalidArgument)?;
+
+    if crate::mm::oom_for_user(EventRingPage::PAGE_SIZE) {
+        return Err(ErrorCode::OutOfMemory);
+    }
+
+    let backing = UserAddressSpace::new()?;
+    let backing_addr = backing.alloc_user_heap(1)?.start;
+    let page = backing.get_user_page_as_kernel(backing_addr)?;
+    UserAddressSpace::map_shared(
+        process.address_space(),
+        address,
+        &backing,
+        backing_addr,
+        MappingOptions::READABLE | MappingOptions::WRITABLE,
+    )?;
+
+    let ring = Arc::new(EventRing {
+        _backing: backing,
+        page: page as usize as *mut EventRingPage,
+        producer_lock: AtomicBool::new(false),
+        head: AtomicU32::new(0),
+    });
+
+    log::debug!(
+        "event_ring: process {} created a ring",
+        process.pid().as_u64()
+    );
+    let sys_object = SysObject::new_owned(
+        Arc::new(alloc::borrow::ToOwned::to_owned("event_ring")),
+        ring,
+        Arc::downgrade(process),
+    );
+    Ok(process.add_object(sys_object))
+}
+
+pub fn attach(
+    process: &Process,
+    ring: SysHandle,
+    source: SysHandle,
+    cookie: u64,
+) -> Result<(), ErrorCode> {
+    let ring_object = process
+        .get_object(&ring)
+        .ok_or(ErrorCode::InvalidArgument)?
+        .sys_object;
+    let ring = super::sysobject::object_from_sysobject::<EventRing>(&ring_object)
+        .ok_or(ErrorCode::InvalidArgument)?;
+    let source = process
+        .get_object(&source)
+        .ok_or(ErrorCode::InvalidArgument)?
+        .sys_object;
+
+    // Rings cannot feed rings: a ring's wakes must reach its waiters.
+    if super::sysobject::object_from_sysobject::<EventRing>(&source).is_some() {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    source.attach_event_ring(Attachment {
+        ring_object,
+        ring,
+        cookie,
+    })
+}
+
+pub fn detach(process: &Process, source: SysHandle) -> Result<(), ErrorCode> {
+    let source = process
+        .get_object(&source)
+        .ok_or(ErrorCode::InvalidArgument)?
+        .sys_object;
+    source.detach_event_ring()
+}
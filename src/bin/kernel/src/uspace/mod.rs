@@ -1,3 +1,4 @@
+pub mod namespace;
 pub mod process;
 pub mod syscall;
 
@@ -6,9 +7,15 @@ pub use process::Process;
 // Public because arch::irq interacts with serial_console.
 pub mod serial_console;
 
+mod event_ring;
+mod pager;
 mod shared;
+mod shm;
+mod syscall_trace;
 
 mod sysobject;
+mod timer;
+mod watchdog;
 pub use sysobject::SysObject;
 
 // Syscalls.
@@ -21,5 +28,6 @@ mod sys_ray_dbg;
 pub use sysobject::process_wake_events;
 
 pub fn init() {
+    namespace::init();
     shared::init();
 }
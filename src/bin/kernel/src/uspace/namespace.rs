@@ -0,0 +1,185 @@
+// Process namespaces (lightweight containers).
+//
+// A namespace gives the processes inside it:
+// - their own PID numbering (the first process in a namespace is PID 1);
+// - their own view of the filesystem root (enforced by sys-io, which
+//   queries the namespace of its peers via SysRay);
+// - a capability ceiling: no process in a namespace can have capabilities
+//   outside of the namespace's max_caps.
+//
+// Processes in the root namespace see global PIDs and the real FS root.
+
+use super::process::ProcessId;
+use crate::util::SpinLock;
+use crate::util::StaticRef;
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::sync::atomic::*;
+use moto_sys::ErrorCode;
+
+pub struct Namespace {
+    id: u64,
+    parent: Option<Arc<Namespace>>,
+
+    // The absolute path (in the root namespace) that is "/" for this namespace.
+    fs_root: String,
+    max_caps: u64,
+
+    next_local_pid: AtomicU64,
+    // local PID => global PID; global PID => local PID.
+    local_to_global: SpinLock<BTreeMap<u64, ProcessId>>,
+    global_to_local: SpinLock<BTreeMap<ProcessId, u64>>,
+}
+
+static ROOT: StaticRef<Arc<Namespace>> = StaticRef::default_const();
+
+pub fn init() {
+    use alloc::boxed::Box;
+    ROOT.set(Box::leak(Box::new(Arc::new(Namespace {
+        id: 0,
+        parent: None,
+        fs_root: "/".to_owned(),
+        max_caps: u64::MAX,
+        next_local_pid: AtomicU64::new(1),
+        local_to_global: SpinLock::new(BTreeMap::new()),
+        global_to_local: SpinLock::new(BTreeMap::new()),
+    }))));
+}
+
+pub fn root() -> Arc<Namespace> {
+    ROOT.clone()
+}
+
+impl Namespace {
+    const MAX_FS_ROOT_LEN: usize = 128;
+
+    // Creates a child namespace. @fs_root is relative to the parent's root.
+    pub fn new_child(
+        parent: &Arc<Namespace>,
+        fs_root: &str,
+        max_caps: u64,
+    ) -> Result<Arc<Self>, ErrorCode> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+        if !fs_root.starts_with('/') {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        for component in fs_root.split('/') {
+            if component == ".." || component == "." {
+                return Err(ErrorCode::InvalidArgument);
+            }
+        }
+
+        let fs_root = alloc::format!(
+            "{}{}",
+            parent.fs_root.trim_end_matches('/'),
+            fs_root.trim_end_matches('/')
+        );
+        let fs_root = if fs_root.is_empty() {
+            "/".to_owned()
+        } else {
+            fs_root
+        };
+        if fs_root.len() > Self::MAX_FS_ROOT_LEN {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        Ok(Arc::new(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            parent: Some(parent.clone()),
+            fs_root,
+            max_caps: max_caps & parent.max_caps,
+            next_local_pid: AtomicU64::new(1),
+            local_to_global: SpinLock::new(BTreeMap::new()),
+            global_to_local: SpinLock::new(BTreeMap::new()),
+        }))
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    pub fn fs_root(&self) -> &str {
+        self.fs_root.as_str()
+    }
+
+    pub fn max_caps(&self) -> u64 {
+        self.max_caps
+    }
+
+    // Returns true if @other is self or a descendant of self.
+    pub fn contains(&self, other: &Namespace) -> bool {
+        let mut curr = Some(other);
+        while let Some(ns) = curr {
+            if ns.id == self.id {
+                return true;
+            }
+            curr = ns.parent.as_deref();
+        }
+
+        false
+    }
+
+    // Registers a new process in this namespace and all its ancestors;
+    // returns the PID local to this namespace.
+    pub(super) fn register(&self, pid: ProcessId) -> u64 {
+        if let Some(parent) = self.parent.as_ref() {
+            parent.register(pid);
+        } else {
+            return pid.as_u64();
+        }
+
+        let local_pid = self.next_local_pid.fetch_add(1, Ordering::Relaxed);
+        self.local_to_global.lock(line!()).insert(local_pid, pid);
+        self.global_to_local.lock(line!()).insert(pid, local_pid);
+        local_pid
+    }
+
+    pub(super) fn unregister(&self, pid: ProcessId) {
+        if let Some(parent) = self.parent.as_ref() {
+            parent.unregister(pid);
+        } else {
+            return;
+        }
+
+        if let Some(local_pid) = self.global_to_local.lock(line!()).remove(&pid) {
+            self.local_to_global.lock(line!()).remove(&local_pid);
+        }
+    }
+
+    // Converts a local PID into the global PID.
+    pub fn to_global(&self, local_pid: u64) -> Option<ProcessId> {
+        if self.is_root() {
+            return Some(ProcessId::from_u64(local_pid));
+        }
+        self.local_to_global.lock(line!()).get(&local_pid).copied()
+    }
+
+    // Same as to_global, but returns the first process at or after @local_pid.
+    // Used to iterate over processes in the namespace.
+    pub fn to_global_at_or_after(&self, local_pid: u64) -> Option<ProcessId> {
+        if self.is_root() {
+            return Some(ProcessId::from_u64(local_pid));
+        }
+        self.local_to_global
+            .lock(line!())
+            .range(local_pid..)
+            .next()
+            .map(|(_, pid)| *pid)
+    }
+
+    // Converts a global PID into the local one, if the process is visible
+    // in this namespace.
+    pub fn to_local(&self, pid: ProcessId) -> Option<u64> {
+        if self.is_root() {
+            return Some(pid.as_u64());
+        }
+        self.global_to_local.lock(line!()).get(&pid).copied()
+    }
+}
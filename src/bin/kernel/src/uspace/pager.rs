@@ -0,0 +1,303 @@
+// Pagers: memory regions backed by userspace-provided data (e.g. mapped files).
+//
+// A pager is created via SysObj::create(url = "pager"); the creating process
+// then reserves paged regions in its address space via SysMem::OP_PAGER.
+// When a thread touches a page in a paged region that has not been supplied
+// yet, the thread is parked (it stays Live(Preempted), but is not scheduled),
+// the page is queued, and the pager handle is woken. A pager thread (usually
+// in the same process) then gets the queued page, reads its data (e.g. from
+// a file), and supplies it; the kernel copies the data into a new frame, maps
+// it, and resumes the parked threads.
+//
+// Pages are never evicted: a paged region costs as much RAM as the number of
+// pages touched, not as the size of the region.
+//
+// If the pager goes away (or the region is unmapped) while threads are parked,
+// they are resumed and will fault again, at which point they are killed.
+
+use super::process::{Process, Thread};
+use super::syscall::{ResultBuilder, SyscallArgs};
+use super::SysObject;
+use crate::mm::user::UserAddressSpace;
+use crate::mm::PAGE_SIZE_SMALL;
+use crate::util::SpinLock;
+use alloc::borrow::ToOwned;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use moto_sys::syscalls::SyscallResult;
+use moto_sys::{ErrorCode, SysHandle, SysMem};
+
+struct Region {
+    end: u64,
+    writable: bool,
+}
+
+struct PagerInner {
+    sys_object: Weak<SysObject>,
+    regions: BTreeMap<u64, Region>,           // By start address.
+    faults: VecDeque<u64>,                    // Pages to deliver to the userspace pager.
+    parked: BTreeMap<u64, Vec<Weak<Thread>>>, // Threads waiting for pages.
+}
+
+pub struct Pager {
+    inner: SpinLock<PagerInner>,
+    address_space: Arc<UserAddressSpace>,
+}
+
+// Paged regions by (address space, start address).
+static REGIONS: SpinLock<BTreeMap<(usize, u64), (u64, Weak<Pager>)>> =
+    SpinLock::new(BTreeMap::new());
+
+fn address_space_key(address_space: &UserAddressSpace) -> usize {
+    address_space as *const UserAddressSpace as usize
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        let parked = {
+            let mut inner = self.inner.lock(line!());
+            let key = address_space_key(&self.address_space);
+            let mut regions = REGIONS.lock(line!());
+            for start in inner.regions.keys() {
+                regions.remove(&(key, *start));
+            }
+            core::mem::take(&mut inner.parked)
+        };
+
+        for (_, threads) in parked {
+            resume_threads(threads);
+        }
+    }
+}
+
+pub fn create(process: &Process) -> SysHandle {
+    let pager = Arc::new(Pager {
+        inner: SpinLock::new(PagerInner {
+            sys_object: Weak::new(),
+            regions: BTreeMap::new(),
+            faults: VecDeque::new(),
+            parked: BTreeMap::new(),
+        }),
+        address_space: process.address_space().clone(),
+    });
+
+    let sys_object = SysObject::new_owned(
+        Arc::new("pager".to_owned()),
+        pager.clone(),
+        alloc::sync::Weak::new(),
+    );
+    pager.inner.lock(line!()).sys_object = Arc::downgrade(&sys_object);
+
+    process.add_object(sys_object)
+}
+
+fn resume_threads(threads: Vec<Weak<Thread>>) {
+    for thread in threads {
+        if let Some(thread) = thread.upgrade() {
+            thread.post_resume_paged();
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PagedFault {
+    NotPaged, // Not in a paged region: a real page fault.
+    Resume,   // The page has been supplied concurrently: resume the thread.
+    Parked,   // The thread has been parked until the page is supplied.
+}
+
+// Called with the thread's status locked. The thread must be parked
+// (i.e. set Live(Preempted)) by the caller if Parked is returned.
+pub fn on_pagefault(thread: &Thread, pf_addr: u64, error_code: u64) -> PagedFault {
+    if error_code & 1 != 0 {
+        // A protection violation (e.g. a write to a read-only mapped file).
+        return PagedFault::NotPaged;
+    }
+
+    let owner = thread.owner();
+    let address_space = owner.address_space();
+    let pager = {
+        let key = address_space_key(address_space);
+        let regions = REGIONS.lock(line!());
+        match regions.range(..=(key, pf_addr)).next_back() {
+            Some(((k, _), (end, pager))) if *k == key && pf_addr < *end => pager.upgrade(),
+            _ => None,
+        }
+    };
+    let pager = match pager {
+        Some(pager) => pager,
+        None => return PagedFault::NotPaged,
+    };
+
+    let page_addr = pf_addr & !(PAGE_SIZE_SMALL - 1);
+    let mut inner = pager.inner.lock(line!());
+
+    // The page may have been supplied after the fault but before
+    // the lock was taken above (supply() maps before locking).
+    if address_space.virt_to_phys(page_addr).is_some() {
+        return PagedFault::Resume;
+    }
+
+    let parked = inner.parked.entry(page_addr).or_default();
+    let first = parked.is_empty();
+    parked.push(thread.get_weak());
+
+    if first {
+        inner.faults.push_back(page_addr);
+        if let Some(sys_object) = inner.sys_object.upgrade() {
+            sys_object.wake(false);
+        }
+    }
+
+    PagedFault::Parked
+}
+
+// Called after @addr has been unmapped from @address_space.
+pub fn on_unmap(address_space: &UserAddressSpace, addr: u64) {
+    let pager = match REGIONS
+        .lock(line!())
+        .remove(&(address_space_key(address_space), addr))
+    {
+        Some((_, pager)) => pager.upgrade(),
+        None => return,
+    };
+    let pager = match pager {
+        Some(pager) => pager,
+        None => return,
+    };
+
+    let threads = {
+        let mut inner = pager.inner.lock(line!());
+        let end = match inner.regions.remove(&addr) {
+            Some(region) => region.end,
+            None => return,
+        };
+        inner.faults.retain(|page| *page < addr || *page >= end);
+
+        let pages: Vec<u64> = inner.parked.range(addr..end).map(|(k, _)| *k).collect();
+        let mut threads = Vec::new();
+        for page in pages {
+            threads.append(&mut inner.parked.remove(&page).unwrap());
+        }
+        threads
+    };
+
+    resume_threads(threads);
+}
+
+impl Pager {
+    fn map(self: &Arc<Self>, num_pages: u64, writable: bool) -> Result<u64, ErrorCode> {
+        if num_pages == 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let segment = self.address_space.alloc_user_unmapped(num_pages)?;
+
+        self.inner.lock(line!()).regions.insert(
+            segment.start,
+            Region {
+                end: segment.end(),
+                writable,
+            },
+        );
+        REGIONS.lock(line!()).insert(
+            (address_space_key(&self.address_space), segment.start),
+            (segment.end(), Arc::downgrade(self)),
+        );
+
+        Ok(segment.start)
+    }
+
+    fn next_fault(&self) -> Result<(u64, u64), ErrorCode> {
+        let mut inner = self.inner.lock(line!());
+        while let Some(page_addr) = inner.faults.pop_front() {
+            if let Some((start, _)) = inner
+                .regions
+                .range(..=page_addr)
+                .next_back()
+                .filter(|(_, region)| page_addr < region.end)
+            {
+                return Ok((page_addr, *start));
+            }
+        }
+
+        Err(ErrorCode::NotReady)
+    }
+
+    fn supply(&self, page_addr: u64, bytes: &[u8]) -> Result<(), ErrorCode> {
+        let writable = {
+            let inner = self.inner.lock(line!());
+            match inner
+                .regions
+                .range(..=page_addr)
+                .next_back()
+                .filter(|(_, region)| page_addr < region.end)
+            {
+                Some((_, region)) => region.writable,
+                None => return Err(ErrorCode::InvalidArgument),
+            }
+        };
+
+        match self.address_space.supply_page(page_addr, bytes, writable) {
+            Ok(()) | Err(ErrorCode::AlreadyInUse) => {}
+            Err(err) => return Err(err),
+        }
+
+        let threads = self
+            .inner
+            .lock(line!())
+            .parked
+            .remove(&page_addr)
+            .unwrap_or_default();
+        resume_threads(threads);
+
+        Ok(())
+    }
+}
+
+pub(super) fn sys_pager_impl(thread: &Thread, args: &SyscallArgs) -> SyscallResult {
+    let pager = match super::sysobject::object_from_handle::<Pager>(
+        &thread.owner(),
+        SysHandle::from_u64(args.args[0]),
+    ) {
+        Some(pager) => pager,
+        None => return ResultBuilder::result(ErrorCode::BadHandle),
+    };
+
+    match args.flags {
+        SysMem::F_PAGER_MAP => {
+            let writable = match args.args[2] as u32 {
+                SysMem::F_READABLE => false,
+                flags if flags == (SysMem::F_READABLE | SysMem::F_WRITABLE) => true,
+                _ => return ResultBuilder::invalid_argument(),
+            };
+            match pager.map(args.args[1], writable) {
+                Ok(addr) => ResultBuilder::ok_1(addr),
+                Err(err) => ResultBuilder::result(err),
+            }
+        }
+        SysMem::F_PAGER_NEXT_FAULT => match pager.next_fault() {
+            Ok((page_addr, region_start)) => ResultBuilder::ok_2(page_addr, region_start),
+            Err(err) => ResultBuilder::result(err),
+        },
+        SysMem::F_PAGER_SUPPLY => {
+            let page_addr = args.args[1];
+            if page_addr & (PAGE_SIZE_SMALL - 1) != 0 {
+                return ResultBuilder::invalid_argument();
+            }
+            let mut bytes = alloc::vec![0_u8; PAGE_SIZE_SMALL as usize];
+            if let Err(err) = thread
+                .owner()
+                .address_space()
+                .read_from_user_into(args.args[2], &mut bytes)
+            {
+                return ResultBuilder::result(err);
+            }
+            match pager.supply(page_addr, &bytes) {
+                Ok(()) => ResultBuilder::ok(),
+                Err(err) => ResultBuilder::result(err),
+            }
+        }
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
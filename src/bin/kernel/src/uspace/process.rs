@@ -1,5 +1,6 @@
 // Userspace process.
 
+use super::namespace::Namespace;
 use super::sys_ray_dbg::DebugSession;
 use super::sysobject::SysObject;
 use crate::arch::current_cpu;
@@ -8,8 +9,10 @@ use crate::arch::syscall::TOCR_KILLED_OTHER;
 use crate::arch::syscall::TOCR_KILLED_SF;
 use crate::arch::time::Instant;
 use crate::config::uCpus;
+use crate::config::MAX_CPUS;
 use crate::mm;
 use crate::mm::user::UserAddressSpace;
+use crate::sched::Priority;
 use crate::util::LockGuard;
 use crate::util::SpinLock;
 use crate::xray::stats::KProcessStats;
@@ -23,6 +26,7 @@ use core::assert_matches::assert_matches;
 use core::sync::atomic::*;
 use moto_sys::ErrorCode;
 use moto_sys::SysHandle;
+use moto_sys::SysObj;
 use moto_sys::UserThreadControlBlock;
 
 // Process ID.
@@ -114,9 +118,19 @@ pub struct Process {
     main_thread: Option<Arc<Thread>>,
     self_object: Option<Arc<SysObject>>, // Points at self.
     entry_point: u64,
+    image: moto_sys::stats::ModuleInfoV1, // The loaded executable.
 
     address_space: Arc<UserAddressSpace>,
     capabilities: AtomicU64,
+    syscall_filter: u128, // See moto_sys::caps::syscall_allowed().
+
+    namespace: Arc<Namespace>,
+    local_pid: u64, // The PID as seen from within self.namespace.
+
+    // Credentials. The kernel only tracks them: they are checked by
+    // userspace servers, e.g. by sys-io against file permissions.
+    uid: u32,
+    gid: u32,
 
     status: SpinLock<ProcessStatus>,
 
@@ -142,6 +156,17 @@ pub struct Process {
     //     Thread::status is locked is dangerous, as the normal pattern
     //     is to order the two locks from the larger (process) to the smaller (thread).
     paused_debuggee: AtomicBool,
+
+    // Graceful termination: if the process listens for termination requests
+    // (see SysCpu::terminate_pid()), a request wakes the listener object;
+    // otherwise the process is killed.
+    termination_listener: SpinLock<Option<Arc<SysObject>>>,
+    termination_requested: AtomicBool,
+
+    // If the process is traced, completed syscalls are recorded into
+    // syscall_trace. See syscall_trace.rs.
+    pub(super) syscall_trace: SpinLock<Weak<super::syscall_trace::SyscallTrace>>,
+    pub(super) syscall_traced: AtomicBool,
 }
 
 unsafe impl Send for Process {}
@@ -149,6 +174,7 @@ unsafe impl Sync for Process {}
 
 impl Drop for Process {
     fn drop(&mut self) {
+        self.namespace.unregister(self.pid());
         self.stats.process_dropped();
     }
 }
@@ -165,13 +191,22 @@ impl Process {
         parent: Arc<KProcessStats>,
         address_space: Arc<UserAddressSpace>,
         entry_point: u64,
+        image: moto_sys::stats::ModuleInfoV1,
         capabilities: u64,
+        syscall_filter: u128,
+        namespace: Arc<Namespace>,
+        uid: u32,
+        gid: u32,
         debug_name: String,
     ) -> Result<Arc<Self>, ErrorCode> {
         if !crate::mm::virt::is_user(entry_point) {
             return Err(ErrorCode::NotAllowed);
         }
 
+        if capabilities & !namespace.max_caps() != 0 {
+            return Err(ErrorCode::NotAllowed);
+        }
+
         if debug_name.trim().is_empty() {
             log::error!("Process:new(): empty debug_name.");
             return Err(ErrorCode::InvalidArgument);
@@ -185,7 +220,13 @@ impl Process {
         let self_ = Arc::new_cyclic(|me| Process {
             address_space,
             entry_point,
+            image,
             capabilities: AtomicU64::new(capabilities),
+            syscall_filter,
+            namespace,
+            local_pid: 0,
+            uid,
+            gid,
             status: SpinLock::new(ProcessStatus::Created),
             this: me.clone(),
             main_thread: None,
@@ -203,6 +244,10 @@ impl Process {
             ),
             debug_session: SpinLock::new(None),
             paused_debuggee: AtomicBool::new(false),
+            termination_listener: SpinLock::new(None),
+            termination_requested: AtomicBool::new(false),
+            syscall_trace: SpinLock::new(Weak::new()),
+            syscall_traced: AtomicBool::new(false),
         });
 
         // Safe because this is the "constructor" and no other references exit.
@@ -214,9 +259,12 @@ impl Process {
             ptr.as_mut().unwrap()
         };
 
+        self_mut.local_pid = self_mut.namespace.register(self_mut.pid());
+
         let process_page = self_mut.address_space.process_static_page_mut();
-        process_page.pid = self_mut.pid().as_u64();
+        process_page.pid = self_mut.local_pid;
         process_page.capabilities = capabilities;
+        process_page.image_slide = image.slide;
 
         self_mut.main_thread = Some(Thread::new(self_.clone(), user_stack, self_mut.entry_point));
 
@@ -246,14 +294,52 @@ impl Process {
         let args: alloc::vec::Vec<&str> = url_part.split(';').collect();
         let entry_point: Option<u64> = crate::util::decode_arg::<u64>(&args, "entry_point");
         let capabilities: u64 = crate::util::decode_arg::<u64>(&args, "capabilities").unwrap_or(0);
+        let fs_root: Option<String> = crate::util::decode_arg::<String>(&args, "fs_root");
+        let syscall_filter: u128 = crate::util::decode_arg::<u128>(&args, "syscall_filter")
+            .unwrap_or(moto_sys::caps::SYSCALL_FILTER_ALLOW_ALL);
+        let uid: Option<u32> = crate::util::decode_arg::<u32>(&args, "uid");
+        let gid: Option<u32> = crate::util::decode_arg::<u32>(&args, "gid");
 
         if entry_point.is_none() {
             log::debug!("missing entry_point");
             return Err(ErrorCode::InvalidArgument);
         }
 
+        // The loader (in the parent) reports where it has put the image, so that
+        // debuggers and backtraces can undo ASLR slides.
+        let image = moto_sys::stats::ModuleInfoV1 {
+            start: crate::util::decode_arg::<u64>(&args, "image_start").unwrap_or(0),
+            end: crate::util::decode_arg::<u64>(&args, "image_end").unwrap_or(0),
+            slide: crate::util::decode_arg::<u64>(&args, "image_slide").unwrap_or(0),
+        };
+        if image.end < image.start
+            || !crate::mm::virt::is_user(image.end)
+            || image.slide > image.start
+        {
+            log::debug!("bad image range");
+            return Err(ErrorCode::InvalidArgument);
+        }
+
         let parent = parent_thread.owner();
         let parent_caps = parent.capabilities();
+
+        // Children inherit credentials; only root can hand out different ones.
+        if (uid.is_some() || gid.is_some()) && parent.uid != moto_sys::caps::ROOT_UID {
+            return Err(ErrorCode::NotAllowed);
+        }
+        let uid = uid.unwrap_or(parent.uid);
+        let gid = gid.unwrap_or(parent.gid);
+
+        // A child with fs_root specified starts a new namespace; otherwise
+        // the child shares the namespace with its parent.
+        let namespace = if let Some(fs_root) = fs_root {
+            if capabilities & (moto_sys::caps::CAP_IO_MANAGER | moto_sys::caps::CAP_SYS) != 0 {
+                return Err(ErrorCode::NotAllowed);
+            }
+            Namespace::new_child(&parent.namespace, fs_root.as_str(), capabilities)?
+        } else {
+            parent.namespace.clone()
+        };
         if parent_caps & moto_sys::caps::CAP_SYS == 0 {
             if capabilities & (moto_sys::caps::CAP_IO_MANAGER | moto_sys::caps::CAP_SYS) != 0 {
                 return Err(ErrorCode::NotAllowed);
@@ -284,7 +370,13 @@ impl Process {
             parent.stats.clone(),
             address_space,
             entry_point.unwrap(),
+            image,
             capabilities,
+            // Children cannot make syscalls their parents cannot make.
+            syscall_filter & parent.syscall_filter,
+            namespace,
+            uid,
+            gid,
             url,
         )
         .map_err(|_| ErrorCode::InternalError)?;
@@ -300,6 +392,31 @@ impl Process {
         self.capabilities.load(Ordering::Relaxed)
     }
 
+    pub fn syscall_filter(&self) -> u128 {
+        self.syscall_filter
+    }
+
+    pub fn namespace(&self) -> &Arc<Namespace> {
+        &self.namespace
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn stats(&self) -> &Arc<KProcessStats> {
+        &self.stats
+    }
+
+    // The PID of @other as seen by self, if @other is visible to self.
+    pub fn pid_of(&self, other: &Process) -> Option<u64> {
+        self.namespace.to_local(other.pid())
+    }
+
     pub(super) fn add_object(&self, object: Arc<SysObject>) -> SysHandle {
         let wait_object = WaitObject::new(object);
         let object_id = self
@@ -311,6 +428,50 @@ impl Process {
         object_id
     }
 
+    // Like add_object(), but only before the process starts: once it runs,
+    // only the process itself adds objects to its handle table.
+    pub(super) fn add_object_before_start(
+        &self,
+        object: Arc<SysObject>,
+    ) -> Result<SysHandle, ErrorCode> {
+        let status = self.status.lock(line!());
+        if *status != ProcessStatus::Created {
+            return Err(ErrorCode::NotReady);
+        }
+        let handle = self.add_object(object);
+        drop(status);
+        Ok(handle)
+    }
+
+    // Inserts @object at a specific handle value chosen by the parent.
+    // Only allowed before the process starts, so that the handle values
+    // the child sees at startup are stable.
+    pub(super) fn add_object_at(
+        &self,
+        object: Arc<SysObject>,
+        handle: SysHandle,
+    ) -> Result<(), ErrorCode> {
+        if handle.as_u64() < SysObj::MIN_INHERITED_HANDLE
+            || handle.as_u64() >= Self::MIN_WAIT_OBJECT_ID
+        {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let status = self.status.lock(line!());
+        if *status != ProcessStatus::Created {
+            return Err(ErrorCode::NotReady);
+        }
+
+        let mut objects = self.wait_objects.lock(line!());
+        if objects.contains_key(&handle) {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+        objects.insert(handle, WaitObject::new(object));
+        drop(objects);
+        drop(status);
+        Ok(())
+    }
+
     pub(super) fn get_object(&self, handle: &SysHandle) -> Option<WaitObject> {
         let objects = self.wait_objects.lock(line!());
         if let Some(obj) = objects.get(handle) {
@@ -320,6 +481,20 @@ impl Process {
         }
     }
 
+    // Returns up to @max objects with handles >= @start, in handle order.
+    pub(super) fn list_objects(
+        &self,
+        start: SysHandle,
+        max: usize,
+    ) -> Vec<(SysHandle, Arc<SysObject>)> {
+        let objects = self.wait_objects.lock(line!());
+        objects
+            .range(start..)
+            .take(max)
+            .map(|(handle, obj)| (*handle, obj.sys_object.clone()))
+            .collect()
+    }
+
     // TODO: put_object should not remove live threads, as they can self-ref.
     pub(super) fn put_object(&self, handle: &SysHandle) -> Result<(), ()> {
         if let Some(obj) = {
@@ -398,6 +573,10 @@ impl Process {
         self.stats.debug_name()
     }
 
+    pub(super) fn image(&self) -> &moto_sys::stats::ModuleInfoV1 {
+        &self.image
+    }
+
     pub(super) fn list_tids(&self, start_tid: &ThreadId, buf: &mut [u64]) -> usize {
         let _ = self.status.lock(line!());
         let tids = self.threads.range(start_tid..);
@@ -412,6 +591,20 @@ impl Process {
 
         idx
     }
+
+    // Prints the backtraces of all threads of the process. Threads that are
+    // currently running print the state saved when they last left userspace.
+    pub(super) fn print_backtraces(&self) {
+        let threads: Vec<Arc<Thread>> = {
+            let _lock = self.status.lock(line!());
+            self.threads.values().cloned().collect()
+        };
+
+        for thread in threads {
+            thread.print_backtrace();
+        }
+    }
+
     pub(super) fn get_thread_data(&self, tid: u64) -> Option<moto_sys::stats::ThreadDataV1> {
         let thread: Arc<Thread> = {
             let _ = self.status.lock(line!());
@@ -421,6 +614,19 @@ impl Process {
         Some(thread.get_thread_data())
     }
 
+    // Returns the stats of up to @max threads, in TID order.
+    pub(super) fn thread_stats(&self, max: usize) -> Vec<moto_sys::stats::ThreadStatsV1> {
+        let threads: Vec<Arc<Thread>> = {
+            let _lock = self.status.lock(line!());
+            self.threads.values().take(max).cloned().collect()
+        };
+
+        threads
+            .iter()
+            .map(|thread| thread.get_thread_stats())
+            .collect()
+    }
+
     pub(super) fn self_object(&self) -> Option<Arc<SysObject>> {
         self.status.lock(line!()); // Must lock status because self.self_object is mutated on exit.
         compiler_fence(Ordering::AcqRel);
@@ -555,6 +761,44 @@ impl Process {
         }
     }
 
+    // Returns the object woken when the process is asked to terminate.
+    pub(super) fn termination_listener(&self) -> Arc<SysObject> {
+        let mut listener = self.termination_listener.lock(line!());
+        if let Some(obj) = listener.as_ref() {
+            return obj.clone();
+        }
+
+        let obj = SysObject::new_owned(
+            Arc::new("terminate_request".to_owned()),
+            Arc::new(()),
+            self.this.clone(),
+        );
+        *listener = Some(obj.clone());
+        drop(listener);
+
+        if self.termination_requested.load(Ordering::Acquire) {
+            obj.wake(false);
+        }
+        obj
+    }
+
+    // Returns true if the process listens for termination requests and has
+    // been notified; otherwise the process is killed.
+    pub(super) fn request_termination(&self) -> bool {
+        self.termination_requested.store(true, Ordering::Release);
+        let listener = self.termination_listener.lock(line!()).clone();
+        match listener {
+            Some(obj) => {
+                obj.wake(false);
+                true
+            }
+            None => {
+                self.die();
+                false
+            }
+        }
+    }
+
     unsafe fn get_mut(&self) -> (&mut Self, LockGuard<ProcessStatus>) {
         let lock = self.status.lock(line!());
         ((self as *const Self as *mut Self).as_mut().unwrap(), lock)
@@ -624,6 +868,9 @@ impl Process {
                     *status_lock = match thread_status {
                         ThreadStatus::Finished => ProcessStatus::Exiting(0),
                         ThreadStatus::Exited(val) => ProcessStatus::Exiting(val),
+                        ThreadStatus::Killed(ThreadKilledReason::StackOverflow) => {
+                            ProcessStatus::Exiting(moto_sys::stats::EXIT_CODE_STACK_OVERFLOW)
+                        }
                         ThreadStatus::Killed(_) | ThreadStatus::Error(_) => {
                             ProcessStatus::Exiting(u64::MAX)
                         }
@@ -681,7 +928,7 @@ impl Process {
         }
 
         if exited {
-            let self_obj = {
+            let (self_obj, status) = {
                 let (self_mut, mut status_lock) = unsafe { self.get_mut() };
                 match *status_lock {
                     ProcessStatus::Running => {
@@ -711,8 +958,9 @@ impl Process {
                 );
 
                 self_mut.main_thread = None;
-                self_mut.self_object.take().unwrap()
+                (self_mut.self_object.take().unwrap(), *status_lock)
             };
+            crate::xray::acct::on_process_exited(self, status);
             self_obj.mark_done();
             SysObject::wake(&self_obj, false);
 
@@ -787,6 +1035,7 @@ pub enum ThreadOffCpuReason {
 pub enum ThreadKilledReason {
     GPF,
     PageFault,
+    StackOverflow, // A page fault on the stack guard page.
     SegFault,
     MainThreadExited,
     ProcessKilled,
@@ -796,6 +1045,13 @@ pub enum ThreadKilledReason {
 // Note: Thread is never moved, but we don't use Pin<> because
 //       we use Arc and Weak, and Pin interaction with Arc and Weak
 //       is underdeveloped: see e.g. PinWeak.
+#[derive(Default)]
+struct ThreadPerfCounters {
+    counts: [u64; crate::arch::pmu::NUM_COUNTERS],
+    // PMU values when the thread was put on CPU, if measuring.
+    on_cpu_since: Option<[u64; crate::arch::pmu::NUM_COUNTERS]>,
+}
+
 pub struct Thread {
     // Read-only fields:
     tid: ThreadId, // Used in SysObject (waiting threads). Must be globally unique.
@@ -825,6 +1081,7 @@ pub struct Thread {
     user_tcb_kernel_addr: u64, // *mut UserThreadControlBlock in the kernel address space.
 
     capabilities: AtomicU64,
+    syscall_filter: u128, // Cached from the owner before the thread starts.
 
     timer_id: AtomicU64,
     timer_cpu: AtomicU32,
@@ -838,6 +1095,28 @@ pub struct Thread {
     last_cpu: AtomicU32,
     affined_to: AtomicU32,
 
+    // See Thread::priority().
+    base_priority: AtomicU8,
+    inherited_priority: AtomicU8, // NO_PRIORITY if none.
+
+    // Lock waits (SysCpu::F_DONATE_TARGET): the priorities lent to this thread,
+    // by donor TID, and their highest; and the thread this one lends its own to.
+    donations: SpinLock<Vec<(u64, Priority)>>,
+    donated_priority: AtomicU8, // NO_PRIORITY if none.
+    donating_to: SpinLock<Option<Weak<Thread>>>,
+
+    // See Thread::set_sched_policy().
+    sched_policy: AtomicU8,
+    rr_slice_start: AtomicU64, // Instant (as TSC); for SchedPolicy::RoundRobin.
+
+    // CPU time (as TSC) this thread has been on CPU, in kernel and userspace.
+    cpu_time: AtomicU64,
+    on_cpu_since: AtomicU64, // Zero if not on CPU.
+    perf: SpinLock<ThreadPerfCounters>,
+
+    // The last unresolved fault: ThreadDataV1::FAULT_*.
+    fault: AtomicU8,
+
     pub process_stats: Arc<KProcessStats>,
 }
 
@@ -883,6 +1162,7 @@ impl Thread {
             thread_entry_point,
             user_stack,
             capabilities: AtomicU64::new(0),
+            syscall_filter: 0,
             kernel_stack_segment: None,
             status: SpinLock::new(ThreadStatus::Created),
             timer_id: AtomicU64::new(0),
@@ -892,6 +1172,17 @@ impl Thread {
             wakers: SpinLock::new(alloc::vec![]),
             last_cpu: AtomicU32::new(u32::MAX),
             affined_to: AtomicU32::new(uCpus::MAX as u32),
+            base_priority: AtomicU8::new(Priority::Normal as u8),
+            inherited_priority: AtomicU8::new(NO_PRIORITY),
+            donations: SpinLock::new(Vec::new()),
+            donated_priority: AtomicU8::new(NO_PRIORITY),
+            donating_to: SpinLock::new(None),
+            sched_policy: AtomicU8::new(crate::sched::SchedPolicy::Normal as u8),
+            rr_slice_start: AtomicU64::new(0),
+            cpu_time: AtomicU64::new(0),
+            on_cpu_since: AtomicU64::new(0),
+            perf: SpinLock::new(ThreadPerfCounters::default()),
+            fault: AtomicU8::new(moto_sys::stats::ThreadDataV1::FAULT_NONE),
             process_stats: owner.stats.clone(),
         });
         unsafe {
@@ -1039,6 +1330,10 @@ impl Thread {
         self.capabilities.load(Ordering::Relaxed)
     }
 
+    pub fn syscall_filter(&self) -> u128 {
+        self.syscall_filter
+    }
+
     pub fn owner(&self) -> Arc<Process> {
         self.owner.upgrade().unwrap()
     }
@@ -1324,6 +1619,7 @@ impl Thread {
                 self_mut
                     .capabilities
                     .store(process.capabilities(), Ordering::Relaxed);
+                self_mut.syscall_filter = process.syscall_filter();
 
                 let upt = process.address_space.user_page_table();
 
@@ -1357,6 +1653,7 @@ impl Thread {
                 return;
             }
             core::mem::drop(cpu_usage_scope);
+            self.on_cpu_start();
             self.on_thread_descheduled(tcb.spawn_usermode_thread(arg));
         }
     }
@@ -1493,6 +1790,7 @@ impl Thread {
         self.cancel_timeout();
         self.clear_wait_objects_on_wake();
 
+        self.on_cpu_start();
         self.on_thread_descheduled(
             /*
              * Resumes the thread on this CPU:
@@ -1523,6 +1821,7 @@ impl Thread {
 
         if resume {
             log::debug!("resume_in_userspace: {}", self.debug_name());
+            self.on_cpu_start();
             self.on_thread_descheduled(self.tcb.resume_preempted_thread());
         }
     }
@@ -1592,6 +1891,7 @@ impl Thread {
         let mut thread_data = moto_sys::stats::ThreadDataV1::default();
 
         thread_data.tid = self.tid.as_u64();
+        thread_data.fault = self.fault.load(Ordering::Relaxed);
         {
             let status = self.status.lock(line!());
             match *status {
@@ -1618,6 +1918,20 @@ impl Thread {
         thread_data
     }
 
+    fn get_thread_stats(&self) -> moto_sys::stats::ThreadStatsV1 {
+        let thread_data = self.get_thread_data();
+
+        moto_sys::stats::ThreadStatsV1 {
+            tid: thread_data.tid,
+            cpu_usage: self.cpu_time(),
+            status: thread_data.status,
+            last_cpu: self.last_cpu().map_or(u16::MAX, |cpu| cpu as u16),
+            sched_policy: self.sched_policy() as u8,
+            paused_debuggee: thread_data.paused_debuggee,
+            _pad: [0; 2],
+        }
+    }
+
     pub fn wake_by_timeout(&self) {
         let mut status = self.status.lock(line!());
         match *status {
@@ -1644,7 +1958,16 @@ impl Thread {
     fn post_wake_locked(&self, this_cpu: bool) {
         self.trace("thread::post_wake_locked", 0, 0);
         self.tcb.validate_rsp();
+        // A new round-robin time slice; see keeps_cpu_on_preemption().
+        self.rr_slice_start
+            .store(Instant::now().as_u64(), Ordering::Relaxed);
         if this_cpu {
+            // The waker is swapping into self: see with_swap_donor().
+            let donor = SWAP_DONORS[current_cpu() as usize].load(Ordering::Relaxed);
+            if let Some(donor) = Priority::from_u8(donor) {
+                self.inherit_priority(donor);
+            }
+
             crate::sched::post(crate::sched::Job::new_on_current_cpu(
                 Self::job_fn_resume_in_kernel,
                 self,
@@ -1723,6 +2046,7 @@ impl Thread {
 
     fn on_thread_exited(&self) {
         self.trace("exited", 0, 0);
+        self.stop_donating();
         let thread_status = {
             let status = self.status.lock(line!());
             #[cfg(debug_assertions)]
@@ -1803,33 +2127,50 @@ impl Thread {
     }
 
     fn print_backtrace(&self) {
-        let backtrace = self
-            .owner()
+        let owner = self.owner();
+        let backtrace = owner
             .address_space
             .get_backtrace(self.tcb.rip(), self.tcb.rbp());
         crate::raw_log!("\n{}: backtrace:", self.debug_name());
         for val in backtrace {
-            crate::raw_log!("\t0x{:x} \\", val);
+            crate::raw_log!("\t0x{:x} \\", owner.image.unslide(val));
         }
     }
 
     fn on_pagefault(&self) {
         let (pf_addr, error_code) = self.tcb.pf_addr_error_code().unwrap();
         self.trace("thread pagefault", pf_addr, error_code);
+        crate::xray::tracepoints::tracepoint(
+            moto_sys::stats::TRACE_PAGE_FAULT,
+            self.owner().pid().as_u64(),
+            self.tid.as_u64(),
+            pf_addr,
+        );
         log::trace!("Thread #PF: 0x{:x}", pf_addr);
         let mut resume_in_userspace = false;
         let mut call_on_exited = false;
+        let mut pause_owner = false;
 
         {
             let mut status = self.status.lock(line!());
             match *status {
                 ThreadStatus::Live(LiveThreadStatus::Running) => {
-                    if self
+                    let fixed = self
                         .owner()
                         .address_space
                         .fix_pagefault(pf_addr, error_code)
-                        .is_ok()
-                    {
+                        .is_ok();
+                    let paged = if fixed {
+                        super::pager::PagedFault::NotPaged
+                    } else {
+                        super::pager::on_pagefault(self, pf_addr, error_code)
+                    };
+
+                    if paged == super::pager::PagedFault::Parked {
+                        // The pager will resume the thread; see post_resume_paged().
+                        log::trace!("#PF parked: 0x{:x}", pf_addr);
+                        *status = ThreadStatus::Live(LiveThreadStatus::Preempted);
+                    } else if fixed || paged == super::pager::PagedFault::Resume {
                         log::trace!("#PF fixed!");
                         if self.owner().paused_debuggee.load(Ordering::Relaxed) {
                             *status = ThreadStatus::PausedDebuggee(LiveThreadStatus::Preempted);
@@ -1838,16 +2179,52 @@ impl Thread {
                             resume_in_userspace = true;
                         }
                     } else {
-                        log::info!(
-                            "#PF: thread {} killed: pf_addr: 0x{:x}\n\trip: 0x{:x} stack: 0x{:x?}",
-                            self.debug_name(),
-                            pf_addr,
-                            self.tcb.rip(),
-                            self.user_stack
-                        );
-                        self.print_backtrace();
-                        *status = ThreadStatus::Killed(ThreadKilledReason::PageFault);
-                        call_on_exited = true;
+                        let (fault, reason) = if self.user_stack.is_overflow(pf_addr) {
+                            (
+                                moto_sys::stats::ThreadDataV1::FAULT_STACK_OVERFLOW,
+                                ThreadKilledReason::StackOverflow,
+                            )
+                        } else {
+                            (
+                                moto_sys::stats::ThreadDataV1::FAULT_PAGE,
+                                ThreadKilledReason::PageFault,
+                            )
+                        };
+
+                        // If a debugger is attached, stop the thread at the faulting
+                        // instruction (once), so that the debugger can inspect it.
+                        let first_fault = self.fault.swap(fault, Ordering::Relaxed)
+                            == moto_sys::stats::ThreadDataV1::FAULT_NONE;
+                        if first_fault && self.owner().debug_session.lock(line!()).is_some() {
+                            log::info!(
+                                "#PF: thread {} paused for the debugger: pf_addr: 0x{:x} rip: 0x{:x}",
+                                self.debug_name(),
+                                pf_addr,
+                                self.tcb.rip()
+                            );
+                            *status = ThreadStatus::PausedDebuggee(LiveThreadStatus::Preempted);
+                            pause_owner = true;
+                        } else {
+                            if reason == ThreadKilledReason::StackOverflow {
+                                log::error!(
+                                    "thread {} overflowed its stack: pf_addr: 0x{:x} rip: 0x{:x}",
+                                    self.debug_name(),
+                                    pf_addr,
+                                    self.owner().image.unslide(self.tcb.rip())
+                                );
+                            } else {
+                                log::info!(
+                                    "#PF: thread {} killed: pf_addr: 0x{:x}\n\trip: 0x{:x} stack: 0x{:x?}",
+                                    self.debug_name(),
+                                    pf_addr,
+                                    self.tcb.rip(),
+                                    self.user_stack
+                                );
+                            }
+                            self.print_backtrace();
+                            *status = ThreadStatus::Killed(reason);
+                            call_on_exited = true;
+                        }
                     }
                 }
                 ThreadStatus::Killed(_) => {
@@ -1856,7 +2233,10 @@ impl Thread {
                 _ => panic!("Unexpected thread status {:?}", *status),
             }
         }
-        if resume_in_userspace {
+        if pause_owner {
+            // Other threads will pause when they are next preempted.
+            let _ = self.owner().dbg_pause();
+        } else if resume_in_userspace {
             // Page faults should not lead to CPU migrations.
             crate::sched::post(crate::sched::Job::new_on_current_cpu(
                 Self::job_fn_resume_in_userspace,
@@ -1867,7 +2247,195 @@ impl Thread {
         }
     }
 
+    // The effective priority of the thread: the highest of its base priority
+    // and the priorities it has inherited.
+    //
+    // Priority inheritance: a thread that swaps into another thread
+    // (i.e. synchronously waits for it, e.g. in an IPC call) lends its
+    // priority to the other thread until the other thread blocks again;
+    // a thread that waits for a lock held by another thread (see
+    // SysCpu::F_DONATE_TARGET) lends its priority to the lock owner for as
+    // long as it waits. This prevents unbounded priority inversion when a high
+    // priority thread waits on a lower priority one (e.g. a server, or a lock owner).
+    pub fn priority(&self) -> Priority {
+        let base = self.base_priority.load(Ordering::Relaxed);
+        let inherited = self.inherited_priority.load(Ordering::Relaxed);
+        let donated = self.donated_priority.load(Ordering::Relaxed);
+        Priority::from_u8(base.min(inherited).min(donated)).unwrap()
+    }
+
+    pub fn base_priority(&self) -> Priority {
+        Priority::from_u8(self.base_priority.load(Ordering::Relaxed)).unwrap()
+    }
+
+    pub fn set_base_priority(&self, priority: Priority) {
+        self.base_priority.store(priority as u8, Ordering::Relaxed);
+    }
+
+    pub fn sched_policy(&self) -> crate::sched::SchedPolicy {
+        crate::sched::SchedPolicy::from_u8(self.sched_policy.load(Ordering::Relaxed)).unwrap()
+    }
+
+    // Real-time threads run at Priority::High; see sched/scheduler.rs.
+    pub fn set_sched_policy(&self, policy: crate::sched::SchedPolicy) {
+        self.sched_policy.store(policy as u8, Ordering::Relaxed);
+        self.rr_slice_start
+            .store(Instant::now().as_u64(), Ordering::Relaxed);
+        self.set_base_priority(if policy.is_realtime() {
+            Priority::High
+        } else {
+            Priority::Normal
+        });
+    }
+
+    // Whether the thread, being preempted, should continue running
+    // ahead of other real-time threads.
+    fn keeps_cpu_on_preemption(&self) -> bool {
+        match self.sched_policy() {
+            crate::sched::SchedPolicy::Normal => false,
+            crate::sched::SchedPolicy::Fifo => true,
+            crate::sched::SchedPolicy::RoundRobin => {
+                let now = Instant::now();
+                let slice_start = Instant::from_u64(self.rr_slice_start.load(Ordering::Relaxed));
+                if slice_start + crate::sched::RR_QUANTUM > now {
+                    true
+                } else {
+                    self.rr_slice_start.store(now.as_u64(), Ordering::Relaxed);
+                    false
+                }
+            }
+        }
+    }
+
+    pub fn inherit_priority(&self, priority: Priority) {
+        self.inherited_priority
+            .fetch_min(priority as u8, Ordering::Relaxed);
+    }
+
+    // Called when the thread is about to block: the work it has done on behalf
+    // of its swap donor(s) is done. Lock donations stay: see donate_priority().
+    pub fn drop_inherited_priority(&self) {
+        self.inherited_priority
+            .store(NO_PRIORITY, Ordering::Relaxed);
+    }
+
+    // Lends @priority (self's, before it blocks) to @target (the owner of
+    // the lock self is about to wait for) until stop_donating(). A queued
+    // target that becomes high priority is moved to a high priority queue,
+    // so that it doesn't wait behind normal priority jobs while self waits for it.
+    pub fn donate_priority(&self, target: &Arc<Thread>, priority: Priority) {
+        self.stop_donating();
+        let prev = target.priority();
+        {
+            let mut donations = target.donations.lock(line!());
+            donations.push((self.tid.as_u64(), priority));
+            target
+                .donated_priority
+                .fetch_min(priority as u8, Ordering::Relaxed);
+        }
+        *self.donating_to.lock(line!()) = Some(Arc::downgrade(target));
+
+        if prev != Priority::High && target.priority() == Priority::High {
+            crate::sched::boost(target);
+        }
+    }
+
+    // Called when self stops waiting (or exits).
+    pub fn stop_donating(&self) {
+        let Some(target) = self.donating_to.lock(line!()).take() else {
+            return;
+        };
+        let Some(target) = target.upgrade() else {
+            return;
+        };
+        let mut donations = target.donations.lock(line!());
+        donations.retain(|(tid, _)| *tid != self.tid.as_u64());
+        let donated = donations
+            .iter()
+            .map(|(_, priority)| *priority as u8)
+            .min()
+            .unwrap_or(NO_PRIORITY);
+        target.donated_priority.store(donated, Ordering::Relaxed);
+    }
+
+    fn on_cpu_start(&self) {
+        let now = Instant::now().as_u64();
+        self.on_cpu_since.store(now, Ordering::Relaxed);
+        if crate::arch::pmu::requested() {
+            crate::arch::pmu::ensure_enabled();
+            if crate::arch::pmu::enabled_on_this_cpu() {
+                self.perf.lock(line!()).on_cpu_since = Some(crate::arch::pmu::read());
+            }
+        }
+        crate::xray::tracepoints::tracepoint(
+            moto_sys::stats::TRACE_SCHED_IN,
+            self.owner().pid().as_u64(),
+            self.tid.as_u64(),
+            0,
+        );
+    }
+
+    // Returns the performance counters accumulated by this thread since
+    // the counters were first requested. Must be called on the thread's CPU.
+    pub fn perf_counters(&self) -> [u64; crate::arch::pmu::NUM_COUNTERS] {
+        let mut perf = self.perf.lock(line!());
+        let now = crate::arch::pmu::read();
+        let mut result = perf.counts;
+        match perf.on_cpu_since.as_ref() {
+            Some(start) => {
+                let delta = crate::arch::pmu::delta(start, &now);
+                for idx in 0..delta.len() {
+                    result[idx] += delta[idx];
+                }
+            }
+            None => {
+                // The first request: start measuring now.
+                if crate::arch::pmu::enabled_on_this_cpu() {
+                    perf.on_cpu_since = Some(now);
+                }
+            }
+        }
+        result
+    }
+
+    // Returns the CPU time (as TSC) the thread has spent on CPU.
+    pub fn cpu_time(&self) -> u64 {
+        let since = self.on_cpu_since.load(Ordering::Relaxed);
+        let cpu_time = self.cpu_time.load(Ordering::Relaxed);
+        if since == 0 {
+            return cpu_time;
+        }
+
+        let now = Instant::now().as_u64();
+        if now > since {
+            cpu_time + now - since
+        } else {
+            cpu_time
+        }
+    }
+
     fn on_thread_descheduled(&self, tocr: ThreadOffCpuReason) {
+        let since = self.on_cpu_since.swap(0, Ordering::Relaxed);
+        let now = Instant::now().as_u64();
+        if since != 0 && now > since {
+            self.cpu_time.fetch_add(now - since, Ordering::Relaxed);
+        }
+        {
+            let mut perf = self.perf.lock(line!());
+            if let Some(start) = perf.on_cpu_since.take() {
+                let delta = crate::arch::pmu::delta(&start, &crate::arch::pmu::read());
+                for idx in 0..delta.len() {
+                    perf.counts[idx] += delta[idx];
+                }
+            }
+        }
+        crate::xray::tracepoints::tracepoint(
+            moto_sys::stats::TRACE_SCHED_OUT,
+            self.owner().pid().as_u64(),
+            self.tid.as_u64(),
+            0,
+        );
+
         crate::util::full_fence();
         match tocr {
             ThreadOffCpuReason::Exited => self.on_thread_exited(),
@@ -1900,10 +2468,17 @@ impl Thread {
                         }
                     }
                     if resume_in_userspace {
-                        crate::sched::post(crate::sched::Job::new(
-                            Self::job_fn_resume_in_userspace,
-                            self,
-                        ));
+                        if self.keeps_cpu_on_preemption() {
+                            crate::sched::post_front(crate::sched::Job::new_on_current_cpu(
+                                Self::job_fn_resume_in_userspace,
+                                self,
+                            ));
+                        } else {
+                            crate::sched::post(crate::sched::Job::new(
+                                Self::job_fn_resume_in_userspace,
+                                self,
+                            ));
+                        }
                     } else if call_on_exited {
                         self.on_thread_exited();
                     }
@@ -1913,9 +2488,9 @@ impl Thread {
                 {
                     let mut status = self.status.lock(line!());
                     if self.user_stack.is_overflow(addr) {
-                        log::debug!(
-                            "Thread killed: {:?}: #PF(0x{:x}): stack overflow.",
-                            *status,
+                        log::error!(
+                            "thread {} overflowed its stack: #PF(0x{:x})",
+                            self.debug_name(),
                             addr
                         );
                     } else if self.user_stack.is_underflow(addr) {
@@ -1930,7 +2505,19 @@ impl Thread {
 
                     match *status {
                         ThreadStatus::Live(LiveThreadStatus::Running) => {
-                            *status = ThreadStatus::Killed(ThreadKilledReason::PageFault)
+                            *status = if self.user_stack.is_overflow(addr) {
+                                self.fault.store(
+                                    moto_sys::stats::ThreadDataV1::FAULT_STACK_OVERFLOW,
+                                    Ordering::Relaxed,
+                                );
+                                ThreadStatus::Killed(ThreadKilledReason::StackOverflow)
+                            } else {
+                                self.fault.store(
+                                    moto_sys::stats::ThreadDataV1::FAULT_PAGE,
+                                    Ordering::Relaxed,
+                                );
+                                ThreadStatus::Killed(ThreadKilledReason::PageFault)
+                            }
                         }
 
                         ThreadStatus::Killed(ThreadKilledReason::ProcessKilled) => {}
@@ -1977,6 +2564,14 @@ impl Thread {
         }
     }
 
+    // Resumes a thread parked in on_pagefault() waiting for a pager.
+    pub(super) fn post_resume_paged(&self) {
+        crate::sched::post(crate::sched::Job::new(
+            Self::job_fn_resume_in_userspace,
+            self,
+        ));
+    }
+
     fn job_fn_resume_in_userspace(thread: &Weak<Self>, _: u64) {
         if let Some(thread) = thread.upgrade() {
             thread.resume_in_userspace();
@@ -2014,6 +2609,22 @@ impl Thread {
     }
 }
 
+const NO_PRIORITY: u8 = u8::MAX;
+
+// The priority of the thread currently swapping into another thread, per CPU.
+static SWAP_DONORS: [AtomicU8; MAX_CPUS as usize] =
+    [const { AtomicU8::new(NO_PRIORITY) }; MAX_CPUS as usize];
+
+// Runs @f (that wakes a thread with this_cpu == true, i.e. swaps into it)
+// so that the wakee inherits @donor priority.
+pub fn with_swap_donor<F: FnOnce() -> R, R>(donor: Priority, f: F) -> R {
+    let cpu = current_cpu() as usize;
+    SWAP_DONORS[cpu].store(donor as u8, Ordering::Relaxed);
+    let result = f();
+    SWAP_DONORS[cpu].store(NO_PRIORITY, Ordering::Relaxed);
+    result
+}
+
 pub fn post_kill_by_pid(pid: u64) {
     crate::sched::post(crate::sched::Job::new_with_arg(
         Process::job_fn_kill_by_pid,
@@ -232,6 +232,11 @@ pub(super) fn try_wake(
     }
 }
 
+// Whether @obj is an IPC channel (an endpoint of a shared URL or of an ipc pair).
+pub(super) fn is_channel(obj: &Arc<SysObject>) -> bool {
+    super::sysobject::object_from_sysobject::<Shared>(obj).is_some()
+}
+
 pub(super) fn peer_owner(
     this: super::process::ProcessId,
     maybe_shared: &Arc<SysObject>,
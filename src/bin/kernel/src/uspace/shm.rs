@@ -0,0 +1,227 @@
+// Named shared memory segments.
+//
+// Unlike "shared:" objects, which connect exactly two processes (an IPC
+// channel), named segments can be mapped by any number of processes:
+//   - SysObj::create(url = "shm:name=N;address=A;page_num=P;others=[none|ro|rw]")
+//     creates segment N of P small pages and maps it read-write at A;
+//   - SysObj::get(url = "shm:name=N;address=A;page_num=P;access=[ro|rw]")
+//     maps an existing segment at A.
+// A must point to an unmapped (reserved) region of exactly P small pages.
+//
+// Access control: the creator process can always map the segment read-write;
+// other processes are limited by the "others" argument given at creation.
+//
+// The segment's memory is owned by a private (backing) address space, so
+// the segment does not depend on the creator keeping it mapped. The name
+// is unregistered when the creator's handle is dropped; existing mappings
+// remain valid until they are unmapped.
+
+use super::process::{Process, ProcessId};
+use super::SysObject;
+use crate::mm::user::UserAddressSpace;
+use crate::mm::MappingOptions;
+use crate::util::SpinLock;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use moto_sys::{ErrorCode, SysHandle};
+
+// Segments larger than this are probably a user error.
+const MAX_PAGES: u64 = 1 << 18; // 1 GiB.
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Access {
+    None,
+    ReadOnly,
+    ReadWrite,
+}
+
+impl Access {
+    fn parse(arg: &str) -> Option<Self> {
+        match arg {
+            "none" => Some(Access::None),
+            "ro" => Some(Access::ReadOnly),
+            "rw" => Some(Access::ReadWrite),
+            _ => None,
+        }
+    }
+}
+
+// The memory of a segment; kept alive by all handles to the segment.
+struct Segment {
+    backing: Arc<UserAddressSpace>,
+    backing_addr: u64,
+    page_num: u64,
+}
+
+// The creator's handle: keeps the name registered.
+struct NamedSegment {
+    name: Arc<String>,
+    segment: Arc<Segment>,
+    creator: ProcessId,
+    others: Access,
+}
+
+impl Drop for NamedSegment {
+    fn drop(&mut self) {
+        let mut segments = SEGMENTS.lock(line!());
+        if let Some(named) = segments.get(&self.name) {
+            if named.strong_count() == 0 {
+                segments.remove(&self.name);
+            }
+        }
+    }
+}
+
+// Whether @obj is a handle to a shared memory segment (see SysObj URLs).
+pub(super) fn is_shm(obj: &Arc<SysObject>) -> bool {
+    super::sysobject::object_from_sysobject::<NamedSegment>(obj).is_some()
+        || super::sysobject::object_from_sysobject::<Segment>(obj).is_some()
+}
+
+static SEGMENTS: SpinLock<BTreeMap<Arc<String>, Weak<NamedSegment>>> =
+    SpinLock::new(BTreeMap::new());
+
+struct ShmArgs {
+    name: String,
+    address: u64,
+    page_num: u64,
+    access: Access,
+}
+
+fn parse_args(args: &str, access_key: &str, default_access: Access) -> Result<ShmArgs, ErrorCode> {
+    let args: alloc::vec::Vec<&str> = args.split(';').filter(|s| !s.is_empty()).collect();
+
+    let name = match crate::util::decode_arg::<String>(&args, "name") {
+        Some(name) if !name.is_empty() => name,
+        _ => return Err(ErrorCode::InvalidArgument),
+    };
+    let address =
+        crate::util::decode_arg::<u64>(&args, "address").ok_or(ErrorCode::InvalidArgument)?;
+    let page_num = match crate::util::decode_arg::<u64>(&args, "page_num") {
+        Some(num) if num > 0 && num <= MAX_PAGES => num,
+        _ => return Err(ErrorCode::InvalidArgument),
+    };
+    let access = match crate::util::decode_arg::<String>(&args, access_key) {
+        None => default_access,
+        Some(arg) => Access::parse(arg.as_str()).ok_or(ErrorCode::InvalidArgument)?,
+    };
+
+    Ok(ShmArgs {
+        name,
+        address,
+        page_num,
+        access,
+    })
+}
+
+fn map(
+    segment: &Segment,
+    process: &Process,
+    address: u64,
+    access: Access,
+) -> Result<(), ErrorCode> {
+    let mapping_options = match access {
+        Access::ReadWrite => MappingOptions::READABLE | MappingOptions::WRITABLE,
+        Access::ReadOnly => MappingOptions::READABLE,
+        Access::None => return Err(ErrorCode::NotAllowed),
+    };
+
+    UserAddressSpace::map_shared(
+        process.address_space(),
+        address,
+        &segment.backing,
+        segment.backing_addr,
+        mapping_options,
+    )
+}
+
+pub(super) fn create(process: &Arc<Process>, args: &str) -> Result<SysHandle, ErrorCode> {
+    let args = parse_args(args, "others", Access::None)?;
+
+    if crate::mm::oom_for_user(args.page_num << moto_sys::sys_mem::PAGE_SIZE_SMALL_LOG2) {
+        return Err(ErrorCode::OutOfMemory);
+    }
+
+    let backing = UserAddressSpace::new()?;
+    let backing_addr = backing.alloc_user_heap(args.page_num)?.start;
+    let segment = Arc::new(Segment {
+        backing,
+        backing_addr,
+        page_num: args.page_num,
+    });
+
+    let name = Arc::new(args.name);
+    let named = Arc::new(NamedSegment {
+        name: name.clone(),
+        segment: segment.clone(),
+        creator: process.pid(),
+        others: args.access,
+    });
+
+    {
+        let mut segments = SEGMENTS.lock(line!());
+        if let Some(existing) = segments.get(&name) {
+            if existing.strong_count() > 0 {
+                log::debug!("shm: '{}' already exists", name);
+                // Note: dropping @named will not unregister the existing segment.
+                drop(segments);
+                return Err(ErrorCode::AlreadyInUse);
+            }
+        }
+        segments.insert(name.clone(), Arc::downgrade(&named));
+    }
+
+    if let Err(err) = map(&segment, process, args.address, Access::ReadWrite) {
+        drop(named); // Unregisters the name.
+        return Err(err);
+    }
+
+    log::debug!(
+        "shm: process {} created '{}' ({} pages)",
+        process.pid().as_u64(),
+        name,
+        args.page_num
+    );
+    let sys_object = SysObject::new_owned(name, named, Arc::downgrade(process));
+    Ok(process.add_object(sys_object))
+}
+
+pub(super) fn open(process: &Arc<Process>, args: &str) -> Result<SysHandle, ErrorCode> {
+    let args = parse_args(args, "access", Access::ReadOnly)?;
+    if args.access == Access::None {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let named = SEGMENTS
+        .lock(line!())
+        .get(&args.name)
+        .and_then(|named| named.upgrade())
+        .ok_or(ErrorCode::NotFound)?;
+
+    let allowed = if named.creator == process.pid() {
+        Access::ReadWrite
+    } else {
+        named.others
+    };
+    if args.access > allowed {
+        log::debug!(
+            "shm: process {} denied access to '{}'",
+            process.pid().as_u64(),
+            named.name
+        );
+        return Err(ErrorCode::NotAllowed);
+    }
+    if args.page_num != named.segment.page_num {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    map(&named.segment, process, args.address, args.access)?;
+
+    let sys_object = SysObject::new_owned(
+        named.name.clone(),
+        named.segment.clone(),
+        Arc::downgrade(process),
+    );
+    Ok(process.add_object(sys_object))
+}
@@ -231,13 +231,40 @@ pub(super) fn sys_wait_impl(curr: &super::process::Thread, args: &SyscallArgs) -
         next_arg += 1;
     }
 
+    let mut donate_target = None;
+    if flags & SysCpu::F_DONATE_TARGET != 0 {
+        flags ^= SysCpu::F_DONATE_TARGET;
+        let handle = SysHandle::from_u64(args.args[next_arg]);
+        next_arg += 1;
+        donate_target =
+            super::sysobject::object_from_handle::<super::process::Thread>(&curr.owner(), handle);
+        if donate_target.is_none() {
+            return ResultBuilder::bad_handle(handle);
+        }
+    }
+
     if (flags & !SysCpu::F_HANDLE_ARRAY) != 0 {
         log::debug!("sys_wait_impl: bad flags: 0x{:x}", args.flags);
         return ResultBuilder::invalid_argument();
     }
 
+    // If the thread is about to block, it no longer needs the priority
+    // it has inherited from swaps (but it passes it further via the swap
+    // target, or to the thread it waits for).
+    let priority = curr.priority();
+    if timeout != 0 {
+        curr.drop_inherited_priority();
+    }
+
     if wake_target != SysHandle::NONE {
-        match do_wake(curr, wake_target, SysHandle::NONE, wake_this_cpu) {
+        let wake_result = if wake_this_cpu {
+            super::process::with_swap_donor(priority, || {
+                do_wake(curr, wake_target, SysHandle::NONE, wake_this_cpu)
+            })
+        } else {
+            do_wake(curr, wake_target, SysHandle::NONE, wake_this_cpu)
+        };
+        match wake_result {
             Err(err) => match err {
                 ErrorCode::BadHandle => return ResultBuilder::bad_handle(wake_target),
                 _ => return ResultBuilder::result(err),
@@ -262,7 +289,20 @@ pub(super) fn sys_wait_impl(curr: &super::process::Thread, args: &SyscallArgs) -
     let (timed_out, wakers) = if (timeout == 0) && (curr.capabilities() & CAP_IO_MANAGER != 0) {
         (false, curr.take_wakers())
     } else {
-        curr.wait()
+        // The lock owner keeps the donated priority for as long as
+        // the thread waits (see Thread::donate_priority()).
+        let donating = match &donate_target {
+            Some(thread) if timeout != 0 && thread.tid() != curr.tid() => {
+                curr.donate_priority(thread, priority);
+                true
+            }
+            _ => false,
+        };
+        let result = curr.wait();
+        if donating {
+            curr.stop_donating();
+        }
+        result
     };
 
     process_wake_handles(curr, args, next_arg, wakers, timed_out)
@@ -274,6 +314,13 @@ pub(super) fn do_wake(
     wakee_thread: SysHandle,
     this_cpu: bool,
 ) -> Result<(), ErrorCode> {
+    crate::xray::tracepoints::tracepoint(
+        moto_sys::stats::TRACE_IPC_WAKE,
+        waker.owner().pid().as_u64(),
+        waker.tid().as_u64(),
+        wake_target.as_u64(),
+    );
+
     if wake_target == SysHandle::SELF && wakee_thread != SysHandle::NONE {
         if let Some(thread) = super::sysobject::object_from_handle::<super::process::Thread>(
             &waker.owner(),
@@ -358,12 +405,27 @@ fn sys_kill_impl(killer: &super::process::Thread, args: &SyscallArgs) -> Syscall
         return ResultBuilder::result(ErrorCode::BadHandle);
     }
 
-    if args.flags == SysCpu::F_KILL_PID {
-        let target_pid = args.args[0];
+    if args.flags == SysCpu::F_KILL_PID
+        || args.flags == SysCpu::F_KILL_PID | SysCpu::F_KILL_GRACEFUL
+    {
+        let graceful = args.flags & SysCpu::F_KILL_GRACEFUL != 0;
+        // The PID is local to the killer's namespace.
+        let target_pid = match killer.owner().namespace().to_global(args.args[0]) {
+            Some(pid) => pid.as_u64(),
+            None => return ResultBuilder::result(ErrorCode::InvalidArgument),
+        };
         if let Some(target_stats) = crate::xray::stats::stats_from_pid(target_pid) {
             if let Some(target) = target_stats.owner.upgrade() {
                 if target.capabilities() & moto_sys::caps::CAP_SYS != 0 {
                     return ResultBuilder::result(ErrorCode::NotAllowed);
+                } else if graceful {
+                    log::debug!(
+                        "process {} asked to terminate by {}",
+                        target.debug_name(),
+                        killer.owner().debug_name()
+                    );
+                    let notified = target.request_termination();
+                    return ResultBuilder::ok_1(notified as u64);
                 } else {
                     log::debug!(
                         "process {} killed by {}",
@@ -446,7 +508,10 @@ fn sys_cpu_usage_impl(curr: &super::process::Thread, args: &mut SyscallArgs) ->
     if args.version > 0 {
         return ResultBuilder::version_too_high();
     }
-    if args.flags != 0 {
+    if args.flags != 0
+        && args.flags != SysCpu::F_USAGE_QUEUE_DEPTHS
+        && args.flags != SysCpu::F_USAGE_TIMERS
+    {
         return ResultBuilder::invalid_argument();
     }
 
@@ -465,6 +530,49 @@ fn sys_cpu_usage_impl(curr: &super::process::Thread, args: &mut SyscallArgs) ->
         return ResultBuilder::invalid_argument();
     }
 
+    if args.flags == SysCpu::F_USAGE_QUEUE_DEPTHS {
+        let mut depths: Vec<u32> = alloc::vec![0; crate::arch::num_cpus() as usize];
+        crate::sched::get_queue_depths(depths.as_mut());
+
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                depths.as_ptr() as *const u8,
+                depths.len() * core::mem::size_of::<u32>(),
+            )
+        };
+        if curr
+            .owner()
+            .address_space()
+            .copy_to_user(bytes, addr)
+            .is_err()
+        {
+            return ResultBuilder::invalid_argument();
+        }
+        return ResultBuilder::ok();
+    }
+
+    if args.flags == SysCpu::F_USAGE_TIMERS {
+        let mut stats: Vec<moto_sys::stats::CpuTimerStatsV1> =
+            alloc::vec![Default::default(); crate::arch::num_cpus() as usize];
+        crate::sched::get_timer_stats(stats.as_mut());
+
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                stats.as_ptr() as *const u8,
+                stats.len() * core::mem::size_of::<moto_sys::stats::CpuTimerStatsV1>(),
+            )
+        };
+        if curr
+            .owner()
+            .address_space()
+            .copy_to_user(bytes, addr)
+            .is_err()
+        {
+            return ResultBuilder::invalid_argument();
+        }
+        return ResultBuilder::ok();
+    }
+
     let mut usage: Vec<f32> = Vec::with_capacity(crate::arch::num_cpus() as usize);
     for _ in 0..crate::arch::num_cpus() {
         usage.push(0.0);
@@ -504,6 +612,8 @@ fn sys_affine_cpu(curr: &super::process::Thread, args: &mut SyscallArgs) -> Sysc
     } else {
         if arg0 >= (crate::arch::num_cpus() as u64) {
             return ResultBuilder::invalid_argument();
+        } else if !crate::sched::cpu_online(arg0 as uCpus) {
+            return ResultBuilder::result(ErrorCode::NotReady);
         } else {
             Some(arg0 as uCpus)
         }
@@ -520,6 +630,148 @@ fn sys_affine_cpu(curr: &super::process::Thread, args: &mut SyscallArgs) -> Sysc
     ResultBuilder::ok()
 }
 
+fn sys_sched_policy(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+    if args.flags != 0
+        || args.args[1] != 0
+        || args.args[2] != 0
+        || args.args[3] != 0
+        || args.args[4] != 0
+        || args.args[5] != 0
+    {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let policy = match u8::try_from(args.args[0])
+        .ok()
+        .and_then(crate::sched::SchedPolicy::from_u8)
+    {
+        Some(policy) => policy,
+        None => return ResultBuilder::invalid_argument(),
+    };
+
+    // Real-time threads can starve everything else.
+    if policy.is_realtime() && (curr.capabilities() & moto_sys::caps::CAP_REALTIME) == 0 {
+        return ResultBuilder::result(ErrorCode::NotAllowed);
+    }
+
+    curr.set_sched_policy(policy);
+    ResultBuilder::ok()
+}
+
+fn sys_priority(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+    if args.flags != 0
+        || args.args[1] != 0
+        || args.args[2] != 0
+        || args.args[3] != 0
+        || args.args[4] != 0
+        || args.args[5] != 0
+    {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let priority = match args.args[0] {
+        SysCpu::PRIORITY_HIGH => crate::sched::Priority::High,
+        SysCpu::PRIORITY_NORMAL => crate::sched::Priority::Normal,
+        _ => return ResultBuilder::invalid_argument(),
+    };
+
+    // High priority threads can starve everything else.
+    if priority == crate::sched::Priority::High
+        && (curr.capabilities() & moto_sys::caps::CAP_REALTIME) == 0
+    {
+        return ResultBuilder::result(ErrorCode::NotAllowed);
+    }
+
+    curr.set_base_priority(priority);
+    ResultBuilder::ok()
+}
+
+fn sys_hotplug(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    if args.flags == SysCpu::F_HOTPLUG_QUERY {
+        return ResultBuilder::ok_1(crate::sched::online_cpus());
+    }
+
+    let online = match args.flags {
+        SysCpu::F_HOTPLUG_OFFLINE => false,
+        SysCpu::F_HOTPLUG_ONLINE => true,
+        _ => return ResultBuilder::invalid_argument(),
+    };
+    if (curr.capabilities() & moto_sys::caps::CAP_SYS) == 0 {
+        return ResultBuilder::result(ErrorCode::NotAllowed);
+    }
+    if args.args[0] >= (crate::arch::num_cpus() as u64) {
+        return ResultBuilder::invalid_argument();
+    }
+
+    match crate::sched::set_cpu_online(args.args[0] as uCpus, online) {
+        Ok(()) => ResultBuilder::ok(),
+        Err(err) => ResultBuilder::result(err),
+    }
+}
+
+fn sys_event_ring(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let process = curr.owner();
+    let result = match args.flags {
+        SysCpu::F_EVENT_RING_ATTACH => super::event_ring::attach(
+            &process,
+            SysHandle::from_u64(args.args[0]),
+            SysHandle::from_u64(args.args[1]),
+            args.args[2],
+        ),
+        SysCpu::F_EVENT_RING_DETACH => {
+            super::event_ring::detach(&process, SysHandle::from_u64(args.args[0]))
+        }
+        _ => return ResultBuilder::invalid_argument(),
+    };
+
+    match result {
+        Ok(()) => ResultBuilder::ok(),
+        Err(err) => ResultBuilder::result(err),
+    }
+}
+
+fn sys_wall_clock(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    match args.flags {
+        SysCpu::F_WALL_CLOCK_QUERY => {
+            let (offset, slew_remaining) = crate::arch::time::wall_clock_offset();
+            ResultBuilder::ok_2(offset as u64, slew_remaining as u64)
+        }
+        SysCpu::F_WALL_CLOCK_ADJUST => {
+            if (curr.capabilities() & moto_sys::caps::CAP_SYS) == 0 {
+                return ResultBuilder::result(ErrorCode::NotAllowed);
+            }
+            let offset = crate::arch::time::adjust_wall_clock(args.args[0] as i64);
+            ResultBuilder::ok_1(offset as u64)
+        }
+        SysCpu::F_WALL_CLOCK_SLEW => {
+            if (curr.capabilities() & moto_sys::caps::CAP_SYS) == 0 {
+                return ResultBuilder::result(ErrorCode::NotAllowed);
+            }
+            crate::arch::time::slew_wall_clock(args.args[0] as i64);
+            ResultBuilder::ok()
+        }
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
+
 fn sys_query_percpu_stats(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
     if args.version > 0 {
         return ResultBuilder::version_too_high();
@@ -543,6 +795,125 @@ fn sys_query_percpu_stats(curr: &super::process::Thread, args: &mut SyscallArgs)
     ResultBuilder::ok_1(num_entries as u64)
 }
 
+fn sys_timer_impl(curr: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    if args.args[3] != 0 || args.args[4] != 0 || args.args[5] != 0 {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let handle = SysHandle::from_u64(args.args[0]);
+    let timer = match super::sysobject::object_from_handle::<super::timer::UserTimer>(
+        &curr.owner(),
+        handle,
+    ) {
+        Some(timer) => timer,
+        None => return ResultBuilder::bad_handle(handle),
+    };
+
+    match args.flags {
+        SysCpu::F_TIMER_SET => {
+            let when = match args.args[1] {
+                0 => None,
+                when => Some(crate::arch::time::Instant::from_u64(when)),
+            };
+            let period = match args.args[2] {
+                0 => None,
+                nanos => {
+                    let period = core::time::Duration::from_nanos(nanos);
+                    if period < super::timer::MIN_PERIOD {
+                        return ResultBuilder::invalid_argument();
+                    }
+                    Some(period)
+                }
+            };
+
+            ResultBuilder::ok_1(timer.set(when, period))
+        }
+        SysCpu::F_TIMER_TAKE => {
+            if args.args[1] != 0 || args.args[2] != 0 {
+                return ResultBuilder::invalid_argument();
+            }
+            ResultBuilder::ok_1(timer.take_expirations())
+        }
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
+
+fn sys_cpu_time_impl(curr: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    if args.args.iter().any(|arg| *arg != 0) {
+        return ResultBuilder::invalid_argument();
+    }
+
+    match args.flags {
+        SysCpu::F_CPU_TIME_PROCESS => {
+            let now = crate::arch::time::Instant::now().as_u64();
+            ResultBuilder::ok_1(curr.process_stats.cpu_usage(now))
+        }
+        SysCpu::F_CPU_TIME_THREAD => ResultBuilder::ok_1(curr.cpu_time()),
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
+
+fn sys_perf_impl(curr: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    if args.args.iter().any(|arg| *arg != 0) {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let available = crate::arch::pmu::available_mask();
+    if available == 0 {
+        return ResultBuilder::result(ErrorCode::NotImplemented);
+    }
+    crate::arch::pmu::request();
+
+    let counters = match args.flags {
+        SysCpu::F_PERF_THREAD => curr.perf_counters(),
+        SysCpu::F_PERF_CPU => crate::arch::pmu::read(),
+        _ => return ResultBuilder::invalid_argument(),
+    };
+
+    let mut data = [0_u64; 6];
+    data[0..counters.len()].copy_from_slice(&counters);
+    data[counters.len()] = available as u64;
+    SyscallResult { result: 0, data }
+}
+
+fn sys_watchdog_impl(curr: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    if args.args[1..].iter().any(|arg| *arg != 0) {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let handle = SysHandle::from_u64(args.args[0]);
+    let watchdog = match super::sysobject::object_from_handle::<super::watchdog::Watchdog>(
+        &curr.owner(),
+        handle,
+    ) {
+        Some(watchdog) => watchdog,
+        None => return ResultBuilder::bad_handle(handle),
+    };
+
+    match args.flags {
+        SysCpu::F_WATCHDOG_PET => ResultBuilder::ok_1(watchdog.pet(&curr.owner())),
+        SysCpu::F_WATCHDOG_DISARM => ResultBuilder::ok_1(watchdog.disarm()),
+        SysCpu::F_WATCHDOG_QUERY => ResultBuilder::ok_1(watchdog.misses()),
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
+
 pub(super) fn sys_cpu_impl(curr: &super::process::Thread, args: &mut SyscallArgs) -> SyscallResult {
     match args.operation {
         SysCpu::OP_WAIT => sys_wait_impl(curr, args),
@@ -552,6 +923,15 @@ pub(super) fn sys_cpu_impl(curr: &super::process::Thread, args: &mut SyscallArgs
         SysCpu::OP_USAGE => sys_cpu_usage_impl(curr, args),
         SysCpu::OP_AFFINE_CPU => sys_affine_cpu(curr, args),
         SysCpu::OP_QUERY_PERCPU_STATS => sys_query_percpu_stats(curr, args),
+        SysCpu::OP_TIMER => sys_timer_impl(curr, args),
+        SysCpu::OP_CPU_TIME => sys_cpu_time_impl(curr, args),
+        SysCpu::OP_PERF => sys_perf_impl(curr, args),
+        SysCpu::OP_WATCHDOG => sys_watchdog_impl(curr, args),
+        SysCpu::OP_SCHED_POLICY => sys_sched_policy(curr, args),
+        SysCpu::OP_HOTPLUG => sys_hotplug(curr, args),
+        SysCpu::OP_EVENT_RING => sys_event_ring(curr, args),
+        SysCpu::OP_WALL_CLOCK => sys_wall_clock(curr, args),
+        SysCpu::OP_PRIORITY => sys_priority(curr, args),
         // Note: curr.exit() below does not return, and the compiler puts ud2 after call,
         //       so if we get INVALID_OPCODE interrupt, we screwed up in syscall_exit_asm().
         SysCpu::OP_EXIT => curr.exit(args.args[0]),
@@ -3,7 +3,8 @@ use moto_sys::*;
 use syscalls::SyscallResult;
 
 use crate::mm::user::UserAddressSpace;
-use crate::mm::MappingOptions;
+use crate::mm::{MappingOptions, MemAdvice};
+use alloc::sync::Arc;
 
 use super::syscall::*;
 
@@ -89,6 +90,10 @@ fn sys_map(
         return ResultBuilder::invalid_argument();
     }
 
+    if (flags & SysMem::F_HUGE) != 0 {
+        return sys_map_huge(address_space, flags, phys_addr, virt_addr, num_pages);
+    }
+
     if flags == (SysMem::F_READABLE | SysMem::F_WRITABLE | SysMem::F_CONTIGUOUS) {
         // This is used for MMIO at arbitrary addresses, e.g. to create VirtIO virtqueues.
         if !io_manager {
@@ -118,6 +123,14 @@ fn sys_map(
             return ResultBuilder::invalid_argument();
         }
 
+        // Transparently promote large allocations to huge pages.
+        let bytes = num_pages << sys_mem::PAGE_SIZE_SMALL_LOG2;
+        if bytes >= HUGE_PAGE_PROMOTION_MIN_SIZE && (bytes & (sys_mem::PAGE_SIZE_MID - 1)) == 0 {
+            if let Ok(segment) = address_space.alloc_user_huge(num_pages) {
+                return ResultBuilder::ok_2(segment.start, segment.size);
+            }
+        }
+
         return match address_space.alloc_user_heap(num_pages) {
             Ok(segment) => ResultBuilder::ok_2(segment.start, segment.size),
             Err(_) => ResultBuilder::result(ErrorCode::OutOfMemory),
@@ -171,6 +184,13 @@ fn sys_map(
             opts |= MappingOptions::WRITABLE;
             flags &= !SysMem::F_WRITABLE;
         }
+        if (flags & SysMem::F_EXECUTABLE) != 0 {
+            if let Err(err) = check_exec_policy(curr_thread, address_space, opts) {
+                return ResultBuilder::result(err);
+            }
+            opts |= MappingOptions::EXECUTABLE;
+            flags &= !SysMem::F_EXECUTABLE;
+        }
         if flags != 0 {
             log::debug!("sys_mem_impl: bad map flags: 0x{:x}", flags);
             return ResultBuilder::invalid_argument();
@@ -191,6 +211,78 @@ fn sys_map(
     return ResultBuilder::invalid_argument();
 }
 
+// W^X: executable memory is never writable in the address space it is mapped
+// into. Code loaded into another address space (i.e. when spawning a process)
+// is always allowed; making memory in one's own address space executable
+// (i.e. JIT) requires CAP_EXEC_MEM.
+fn check_exec_policy(
+    curr_thread: &super::process::Thread,
+    address_space: &UserAddressSpace,
+    opts: MappingOptions,
+) -> Result<(), ErrorCode> {
+    if opts.contains(MappingOptions::WRITABLE) {
+        log::debug!("sys_mem: W^X violation");
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    let owner = curr_thread.owner();
+    if core::ptr::eq(address_space, owner.address_space().as_ref())
+        && owner.capabilities() & moto_sys::caps::CAP_EXEC_MEM == 0
+    {
+        log::debug!(
+            "sys_mem: process {} has no CAP_EXEC_MEM",
+            owner.pid().as_u64()
+        );
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    Ok(())
+}
+
+// Normal heap allocations of at least this size are backed by huge pages,
+// if the size is a multiple of PAGE_SIZE_MID and huge pages are available.
+const HUGE_PAGE_PROMOTION_MIN_SIZE: u64 = 4 * sys_mem::PAGE_SIZE_MID;
+
+fn sys_map_huge(
+    address_space: &UserAddressSpace,
+    flags: u32,
+    phys_addr: u64,
+    virt_addr: u64,
+    num_pages: u64,
+) -> SyscallResult {
+    if phys_addr != u64::MAX || virt_addr != u64::MAX {
+        log::debug!("sys_map_huge: bad map addresses");
+        return ResultBuilder::invalid_argument();
+    }
+    if ((num_pages << sys_mem::PAGE_SIZE_SMALL_LOG2) & (sys_mem::PAGE_SIZE_MID - 1)) != 0 {
+        log::debug!("sys_map_huge: bad num_pages: {}", num_pages);
+        return ResultBuilder::invalid_argument();
+    }
+
+    if flags == SysMem::F_HUGE {
+        // Reserve an unmapped region to share huge pages into.
+        return match address_space.alloc_user_unmapped_huge(num_pages) {
+            Ok(segment) => ResultBuilder::ok_2(segment.start, segment.size),
+            Err(_) => ResultBuilder::result(ErrorCode::OutOfMemory),
+        };
+    }
+
+    if flags != (SysMem::F_READABLE | SysMem::F_WRITABLE | SysMem::F_HUGE) {
+        log::debug!("sys_map_huge: bad map flags: 0x{:x}", flags);
+        return ResultBuilder::invalid_argument();
+    }
+
+    // F_HUGE is a hint: fall back to small pages if the huge page pool is exhausted.
+    let segment = match address_space.alloc_user_huge(num_pages) {
+        Ok(segment) => Ok(segment),
+        Err(_) => address_space.alloc_user_heap(num_pages),
+    };
+    match segment {
+        Ok(segment) => ResultBuilder::ok_2(segment.start, segment.size),
+        Err(_) => ResultBuilder::result(ErrorCode::OutOfMemory),
+    }
+}
+
 fn sys_unmap(
     _curr_thread: &super::process::Thread,
     address_space: &UserAddressSpace,
@@ -212,7 +304,70 @@ fn sys_unmap(
             log::debug!("sys_unmap: 0x{:x} failed: {:?}", virt_addr, err);
             ResultBuilder::invalid_argument()
         }
+        Ok(()) => {
+            super::pager::on_unmap(address_space, virt_addr);
+            ResultBuilder::ok()
+        }
+    }
+}
+
+fn sys_protect(
+    curr_thread: &super::process::Thread,
+    address_space: &UserAddressSpace,
+    flags: u32,
+    virt_addr: u64,
+    num_pages: u64,
+) -> SyscallResult {
+    let opts = if flags == SysMem::F_READABLE {
+        MappingOptions::READABLE
+    } else if flags == (SysMem::F_READABLE | SysMem::F_WRITABLE) {
+        MappingOptions::READABLE | MappingOptions::WRITABLE
+    } else if flags == (SysMem::F_READABLE | SysMem::F_EXECUTABLE) {
+        if let Err(err) = check_exec_policy(curr_thread, address_space, MappingOptions::READABLE) {
+            return ResultBuilder::result(err);
+        }
+        MappingOptions::READABLE | MappingOptions::EXECUTABLE
+    } else {
+        log::debug!("sys_protect: bad flags: 0x{:x}", flags);
+        return ResultBuilder::result(if (flags & SysMem::F_EXECUTABLE) != 0 {
+            ErrorCode::NotAllowed // W^X.
+        } else {
+            ErrorCode::InvalidArgument
+        });
+    };
+
+    match address_space.protect(virt_addr, num_pages, opts) {
         Ok(()) => ResultBuilder::ok(),
+        Err(err) => {
+            log::debug!("sys_protect: 0x{:x} failed: {:?}", virt_addr, err);
+            ResultBuilder::result(err)
+        }
+    }
+}
+
+fn sys_advise(
+    address_space: &Arc<UserAddressSpace>,
+    flags: u32,
+    virt_addr: u64,
+    num_pages: u64,
+) -> SyscallResult {
+    let advice = match flags {
+        SysMem::F_ADVISE_FREE => MemAdvice::Free,
+        SysMem::F_ADVISE_WILLNEED => MemAdvice::WillNeed,
+        SysMem::F_ADVISE_DONTNEED => MemAdvice::DontNeed,
+        SysMem::F_ADVISE_COLD => MemAdvice::Cold,
+        _ => {
+            log::debug!("sys_advise: bad advice: {}", flags);
+            return ResultBuilder::invalid_argument();
+        }
+    };
+
+    match address_space.advise(virt_addr, num_pages, advice) {
+        Ok(reclaimed) => ResultBuilder::ok_1(reclaimed),
+        Err(err) => {
+            log::debug!("sys_advise: 0x{:x} failed: {:?}", virt_addr, err);
+            ResultBuilder::result(err)
+        }
     }
 }
 
@@ -232,6 +387,16 @@ fn sys_mem_global_stats(
     stats.available = phys_stats.total_size;
     stats.used_pages = phys_stats.small_pages_used;
     stats.heap_total = heap_stats.total_in_heap as u64;
+    stats.huge_pages_total = phys_stats.huge_pages;
+    stats.huge_pages_used = phys_stats.huge_pages_used;
+    stats.small_pages_total = phys_stats.small_pages;
+    stats.mid_pages_total = phys_stats.mid_pages;
+    stats.mid_pages_used = phys_stats.mid_pages_used;
+
+    let system_stats = crate::xray::stats::system_stats_ref();
+    stats.user_pages = system_stats.pages_user();
+    stats.kernel_pages = system_stats.pages_kernel();
+    stats.cached_pages = crate::mm::cache::cached_pages();
 
     unsafe {
         let src: &[u8] = core::slice::from_raw_parts(
@@ -282,6 +447,7 @@ fn sys_reclaim() -> SyscallResult {
     log::warn!("SysMem::reclaim(): do CAPs check.");
 
     crate::mm::kheap::reclaim();
+    crate::mm::user::reclaim_freeable();
     ResultBuilder::ok()
 }
 
@@ -292,6 +458,11 @@ pub fn sys_mem_impl(thread: &super::process::Thread, args: &SyscallArgs) -> Sysc
         return ResultBuilder::version_too_high();
     }
 
+    if args.operation == SysMem::OP_PAGER {
+        // args[0] is the pager handle, not an address space.
+        return super::pager::sys_pager_impl(thread, args);
+    }
+
     let address_space_handle = SysHandle::from_u64(args.args[0]);
 
     if address_space_handle == SysHandle::NONE {
@@ -360,6 +531,24 @@ pub fn sys_mem_impl(thread: &super::process::Thread, args: &SyscallArgs) -> Sysc
                 args.args[2],
             );
         }
+        SysMem::OP_PROTECT => {
+            if args.args[3] != 0 || args.args[4] != 0 || args.args[5] != 0 {
+                return ResultBuilder::invalid_argument();
+            }
+            return sys_protect(
+                thread,
+                &address_space,
+                args.flags,
+                args.args[1],
+                args.args[2],
+            );
+        }
+        SysMem::OP_ADVISE => {
+            if args.args[3] != 0 || args.args[4] != 0 || args.args[5] != 0 {
+                return ResultBuilder::invalid_argument();
+            }
+            return sys_advise(&address_space, args.flags, args.args[1], args.args[2]);
+        }
         SysMem::OP_QUERY => {
             if args.args[5] != 0 {
                 return ResultBuilder::invalid_argument();
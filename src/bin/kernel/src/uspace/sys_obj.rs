@@ -82,6 +82,12 @@ fn sys_handle_create(
             "shared" => {
                 return sys_handle_shared(SysObj::OP_CREATE, thread, parent, suffix);
             }
+            "shm" => {
+                if parent != SysHandle::NONE {
+                    return Err(ErrorCode::InvalidArgument);
+                }
+                return super::shm::create(&thread.owner(), suffix);
+            }
             _ => {}
         }
     }
@@ -207,6 +213,22 @@ fn sys_handle_get(
                 Err(ErrorCode::InvalidArgument)
             }
         }
+        "terminate_request" => {
+            if parent != SysHandle::SELF {
+                return Err(ErrorCode::InvalidArgument);
+            }
+            let process = thread.owner();
+            Ok(process.add_object(process.termination_listener()))
+        }
+        "klog" => {
+            if parent != SysHandle::KERNEL {
+                return Err(ErrorCode::InvalidArgument);
+            }
+            if thread.capabilities() & moto_sys::caps::CAP_LOG == 0 {
+                return Err(ErrorCode::NotAllowed);
+            }
+            Ok(thread.owner().add_object(crate::xray::logger::log_waiter()))
+        }
         "serial_console" => {
             if parent != SysHandle::KERNEL {
                 return Err(ErrorCode::InvalidArgument);
@@ -239,6 +261,12 @@ fn sys_handle_get(
                     "shared" => {
                         return sys_handle_shared(SysObj::OP_GET, thread, parent, suffix);
                     }
+                    "shm" => {
+                        if parent != SysHandle::NONE {
+                            return Err(ErrorCode::InvalidArgument);
+                        }
+                        return super::shm::open(&thread.owner(), suffix);
+                    }
                     _ => {}
                 }
             }
@@ -281,6 +309,81 @@ fn sys_handle_put(
     }
 }
 
+fn sys_handle_dup(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let move_handle = match args.flags {
+        0 => false,
+        SysObj::F_DUP_MOVE => true,
+        _ => return ResultBuilder::invalid_argument(),
+    };
+
+    let this_process = thread.owner();
+    let process_from_handle = |handle: SysHandle| -> Option<Arc<super::Process>> {
+        if handle == SysHandle::SELF {
+            this_process.self_pinned()
+        } else {
+            super::sysobject::object_from_handle::<super::Process>(&this_process, handle)
+        }
+    };
+
+    let (src, dst) = match (
+        process_from_handle(SysHandle::from_u64(args.args[0])),
+        process_from_handle(SysHandle::from_u64(args.args[2])),
+    ) {
+        (Some(src), Some(dst)) => (src, dst),
+        _ => {
+            log::debug!("sys_handle_dup: bad process handle");
+            return ResultBuilder::invalid_argument();
+        }
+    };
+
+    let handle = SysHandle::from_u64(args.args[1]);
+    let obj = match src.get_object(&handle) {
+        Some(obj) => obj.sys_object,
+        None => return ResultBuilder::bad_handle(handle),
+    };
+
+    // Objects bound to a process (e.g. IPC endpoints) resolve thread handles
+    // in that process, so they can only be placed there.
+    if let Some(owner) = obj.process_owner().upgrade() {
+        if owner.pid() != dst.pid() {
+            log::debug!(
+                "sys_handle_dup: object {} is bound to pid {}",
+                obj.id(),
+                owner.pid().as_u64()
+            );
+            return ResultBuilder::invalid_argument();
+        }
+    }
+
+    // Handles are placed into other processes only before they start.
+    let dst_handle = SysHandle::from_u64(args.args[3]);
+    let dst_handle = if dst_handle == SysHandle::NONE {
+        if dst.pid() == this_process.pid() {
+            dst.add_object(obj)
+        } else {
+            match dst.add_object_before_start(obj) {
+                Ok(dst_handle) => dst_handle,
+                Err(err) => return ResultBuilder::result(err),
+            }
+        }
+    } else {
+        if let Err(err) = dst.add_object_at(obj, dst_handle) {
+            return ResultBuilder::result(err);
+        }
+        dst_handle
+    };
+
+    if move_handle && !(src.pid() == dst.pid() && handle == dst_handle) {
+        let _ = src.put_object(&handle);
+    }
+
+    ResultBuilder::ok_1(dst_handle.as_u64())
+}
+
 fn sys_query_handle(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
     if args.version > 0 {
         return ResultBuilder::version_too_high();
@@ -308,7 +411,10 @@ fn sys_query_handle(thread: &super::process::Thread, args: &SyscallArgs) -> Sysc
 
         if return_pid {
             if let Some(proc) = super::shared::peer_owner(thread.owner().pid(), &obj.sys_object) {
-                return ResultBuilder::ok_1(proc.pid().as_u64());
+                if let Some(pid) = process.pid_of(&proc) {
+                    return ResultBuilder::ok_1(pid);
+                }
+                return ResultBuilder::result(ErrorCode::NotFound);
             } else {
                 return ResultBuilder::result(ErrorCode::NotFound);
             }
@@ -364,6 +470,40 @@ pub(super) fn sys_ctl_impl(thread: &super::process::Thread, args: &SyscallArgs)
                 }
             }
 
+            if url == "timer" {
+                if parent != SysHandle::NONE {
+                    return ResultBuilder::invalid_argument();
+                }
+                return ResultBuilder::ok_1(super::timer::create(&thread.owner()).as_u64());
+            }
+
+            if url == "pager" {
+                if parent != SysHandle::NONE {
+                    return ResultBuilder::invalid_argument();
+                }
+                return ResultBuilder::ok_1(super::pager::create(&thread.owner()).as_u64());
+            }
+
+            if url.starts_with("event_ring:") {
+                if parent != SysHandle::NONE {
+                    return ResultBuilder::invalid_argument();
+                }
+                return match super::event_ring::create(&thread.owner(), &url) {
+                    Ok(handle) => ResultBuilder::ok_1(handle.as_u64()),
+                    Err(err) => ResultBuilder::result(err),
+                };
+            }
+
+            if url.starts_with("watchdog:") {
+                if parent != SysHandle::NONE {
+                    return ResultBuilder::invalid_argument();
+                }
+                return match super::watchdog::create(&thread.owner(), &url) {
+                    Ok(handle) => ResultBuilder::ok_1(handle.as_u64()),
+                    Err(err) => ResultBuilder::result(err),
+                };
+            }
+
             match sys_handle_create(thread, parent, &url) {
                 Ok(handle) => ResultBuilder::ok_1(handle.as_u64()),
                 Err(err) => ResultBuilder::result(err),
@@ -430,6 +570,7 @@ pub(super) fn sys_ctl_impl(thread: &super::process::Thread, args: &SyscallArgs)
             }
         }
         SysObj::OP_QUERY_HANDLE => sys_query_handle(thread, args),
+        SysObj::OP_DUP => sys_handle_dup(thread, args),
 
         SysObj::OP_SET_LOG_LEVEL => {
             if args.version > 0 {
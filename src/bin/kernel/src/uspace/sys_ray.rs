@@ -1,4 +1,11 @@
-use moto_sys::{stats::ProcessStatsV1, syscalls::SyscallResult, ErrorCode, SysHandle, SysRay};
+use moto_sys::{
+    stats::{
+        HandleInfoV1, KLogEntryV1, NamespaceInfoV1, ProcessAcctV1, ProcessStatsV1,
+        SyscallTraceEventV1, ThreadStatsV1, TraceEventV1,
+    },
+    syscalls::SyscallResult,
+    ErrorCode, SysHandle, SysRay,
+};
 
 use crate::xray::stats::KProcessStats;
 
@@ -45,7 +52,18 @@ fn sys_query_process_list(thread: &super::process::Thread, args: &SyscallArgs) -
         _ => return ResultBuilder::invalid_argument(),
     };
 
-    let pid = super::process::ProcessId::from_u64(args.args[0]);
+    // Processes in a non-root namespace see only processes in that namespace
+    // (and its descendants), with PIDs local to the namespace.
+    let namespace = thread.owner().namespace().clone();
+    let pid = if flat_list {
+        namespace.to_global_at_or_after(args.args[0])
+    } else {
+        namespace.to_global(args.args[0])
+    };
+    let pid = match pid {
+        Some(pid) => pid,
+        None => return ResultBuilder::ok_1(0),
+    };
     let dest_addr = args.args[1] as usize;
     let dest_num = args.args[2] as usize; // Number of structs, not number of bytes.
     if dest_num < 1 {
@@ -66,6 +84,17 @@ fn sys_query_process_list(thread: &super::process::Thread, args: &SyscallArgs) -
         let mut stats = ProcessStatsV1::default();
         val.into_v1(&mut stats, now);
 
+        if !namespace.is_root() {
+            match namespace.to_local(val.pid()) {
+                Some(local_pid) => stats.pid = local_pid,
+                None => return true, // Not visible: skip.
+            }
+            stats.parent_pid = val
+                .parent()
+                .and_then(|parent| namespace.to_local(parent.pid()))
+                .unwrap_or(0);
+        }
+
         unsafe {
             let dest_ptr = dest_addr + *counter_ref * core::mem::size_of::<ProcessStatsV1>();
             let buf: &[u8] = core::slice::from_raw_parts(
@@ -90,6 +119,237 @@ fn sys_query_process_list(thread: &super::process::Thread, args: &SyscallArgs) -
     ResultBuilder::ok_1(counter as u64)
 }
 
+fn sys_query_namespace(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let caller = thread.owner();
+    let pid = match caller.namespace().to_global(args.args[0]) {
+        Some(pid) => pid,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    let target = match super::process::Process::from_pid(pid.as_u64()) {
+        Some(proc) => proc,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+
+    let namespace = target.namespace();
+    if !caller.namespace().contains(namespace) {
+        return ResultBuilder::result(ErrorCode::NotFound);
+    }
+
+    let mut info = NamespaceInfoV1::default();
+    info.id = namespace.id();
+    info.local_pid = namespace.to_local(pid).unwrap_or(0);
+    info.max_caps = namespace.max_caps();
+    let fs_root = namespace.fs_root().as_bytes();
+    info.fs_root_bytes[0..fs_root.len()].copy_from_slice(fs_root);
+    info.fs_root_len = fs_root.len() as u8;
+
+    let buf: &[u8] = unsafe {
+        core::slice::from_raw_parts(
+            &info as *const _ as *const u8,
+            core::mem::size_of::<NamespaceInfoV1>(),
+        )
+    };
+    if let Err(err) = caller.address_space().copy_to_user(buf, args.args[1]) {
+        return ResultBuilder::result(err);
+    }
+
+    ResultBuilder::ok()
+}
+
+fn sys_query_credentials(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let caller = thread.owner();
+    let pid = match caller.namespace().to_global(args.args[0]) {
+        Some(pid) => pid,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    let target = match super::process::Process::from_pid(pid.as_u64()) {
+        Some(proc) => proc,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    if !caller.namespace().contains(target.namespace()) {
+        return ResultBuilder::result(ErrorCode::NotFound);
+    }
+
+    ResultBuilder::ok_2(target.uid() as u64, target.gid() as u64)
+}
+
+fn sys_query_threads(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let caller = thread.owner();
+    let pid = match caller.namespace().to_global(args.args[0]) {
+        Some(pid) => pid,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    let target = match super::process::Process::from_pid(pid.as_u64()) {
+        Some(proc) => proc,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    if !caller.namespace().contains(target.namespace()) {
+        return ResultBuilder::result(ErrorCode::NotFound);
+    }
+
+    let dest_num = args.args[2] as usize; // Number of structs, not number of bytes.
+    if dest_num < 1 {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let stats = target.thread_stats(dest_num);
+    let buf: &[u8] = unsafe {
+        core::slice::from_raw_parts(
+            stats.as_ptr() as *const u8,
+            stats.len() * core::mem::size_of::<ThreadStatsV1>(),
+        )
+    };
+    if let Err(err) = caller.address_space().copy_to_user(buf, args.args[1]) {
+        return ResultBuilder::result(err);
+    }
+
+    ResultBuilder::ok_1(stats.len() as u64)
+}
+
+fn handle_info(
+    viewer: &super::process::Process,
+    owner: &super::process::Process,
+    handle: SysHandle,
+    obj: &alloc::sync::Arc<super::SysObject>,
+) -> HandleInfoV1 {
+    use super::sysobject::object_from_sysobject;
+    use moto_sys::stats::*;
+
+    let mut info = HandleInfoV1::default();
+    info.handle = handle.as_u64();
+    info.wake_count = obj.wake_count();
+
+    let mut peer = None;
+    info.kind = if let Some(process) = object_from_sysobject::<super::process::Process>(obj) {
+        peer = Some(process);
+        HANDLE_KIND_PROCESS
+    } else if let Some(thread) = object_from_sysobject::<super::process::Thread>(obj) {
+        peer = Some(thread.owner());
+        HANDLE_KIND_THREAD
+    } else if super::shared::is_channel(obj) {
+        peer = super::shared::peer_owner(owner.pid(), obj);
+        info.peer_dropped = obj.sibling_dropped() as u8;
+        HANDLE_KIND_CHANNEL
+    } else if super::shm::is_shm(obj) {
+        HANDLE_KIND_SHM
+    } else if object_from_sysobject::<super::timer::UserTimer>(obj).is_some() {
+        HANDLE_KIND_TIMER
+    } else if object_from_sysobject::<super::event_ring::EventRing>(obj).is_some() {
+        HANDLE_KIND_EVENT_RING
+    } else if object_from_sysobject::<crate::mm::user::UserAddressSpace>(obj).is_some() {
+        HANDLE_KIND_ADDRESS_SPACE
+    } else if object_from_sysobject::<super::pager::Pager>(obj).is_some() {
+        HANDLE_KIND_PAGER
+    } else if object_from_sysobject::<super::watchdog::Watchdog>(obj).is_some() {
+        HANDLE_KIND_WATCHDOG
+    } else if object_from_sysobject::<super::sys_ray_dbg::DebugSession>(obj).is_some()
+        || object_from_sysobject::<super::syscall_trace::SyscallTrace>(obj).is_some()
+    {
+        HANDLE_KIND_DEBUG
+    } else {
+        HANDLE_KIND_OTHER
+    };
+
+    // PIDs are reported as seen by the viewer; invisible peers are reported as 0.
+    if let Some(peer) = peer {
+        info.peer_pid = viewer.pid_of(&peer).unwrap_or(0);
+    }
+
+    let url = crate::xray::stats::truncate_str(obj.url(), MAX_HANDLE_URL_LEN);
+    info.url_bytes[0..url.len()].copy_from_slice(url.as_bytes());
+    info.url_len = url.len() as u8;
+
+    info
+}
+
+fn sys_query_handles(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let caller = thread.owner();
+    let pid = match caller.namespace().to_global(args.args[0]) {
+        Some(pid) => pid,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    let target = match super::process::Process::from_pid(pid.as_u64()) {
+        Some(proc) => proc,
+        None => return ResultBuilder::result(ErrorCode::NotFound),
+    };
+    if !caller.namespace().contains(target.namespace()) {
+        return ResultBuilder::result(ErrorCode::NotFound);
+    }
+
+    let dest_num = (args.args[2] as usize).min(256); // Number of structs, not number of bytes.
+    if dest_num < 1 {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let infos: alloc::vec::Vec<HandleInfoV1> = target
+        .list_objects(SysHandle::from_u64(args.args[3]), dest_num)
+        .iter()
+        .map(|(handle, obj)| handle_info(&caller, &target, *handle, obj))
+        .collect();
+    let buf: &[u8] = unsafe {
+        core::slice::from_raw_parts(
+            infos.as_ptr() as *const u8,
+            infos.len() * core::mem::size_of::<HandleInfoV1>(),
+        )
+    };
+    if let Err(err) = caller.address_space().copy_to_user(buf, args.args[1]) {
+        return ResultBuilder::result(err);
+    }
+
+    ResultBuilder::ok_1(infos.len() as u64)
+}
+
+fn sys_query_acct(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let start_seq = args.args[0];
+    let dest_addr = args.args[1];
+    let dest_num = args.args[2] as usize; // Number of structs, not number of bytes.
+    if dest_num < 1 {
+        return ResultBuilder::invalid_argument();
+    }
+
+    // Copy the records out first: copying to user memory while holding
+    // the accounting lock can fault and block, with the lock held.
+    let caller = thread.owner();
+    let mut records = alloc::vec::Vec::new();
+    crate::xray::acct::read(&caller, start_seq, |record| {
+        records.push(record.clone());
+        records.len() < dest_num
+    });
+
+    let address_space = caller.address_space().clone();
+    let buf: &[u8] = unsafe {
+        core::slice::from_raw_parts(
+            records.as_ptr() as *const u8,
+            records.len() * core::mem::size_of::<ProcessAcctV1>(),
+        )
+    };
+    if let Err(err) = address_space.copy_to_user(buf, dest_addr) {
+        return ResultBuilder::result(err);
+    }
+
+    ResultBuilder::ok_1(records.len() as u64)
+}
+
 fn sys_log(
     curr_thread: &super::process::Thread,
     flags: u32,
@@ -125,6 +385,136 @@ fn sys_log(
     ResultBuilder::ok()
 }
 
+fn sys_log_read(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    if (thread.owner().capabilities() & moto_sys::caps::CAP_LOG) == 0 {
+        return ResultBuilder::result(ErrorCode::NotAllowed);
+    }
+
+    let start_seq = args.args[0];
+    let dest_addr = args.args[1];
+    let dest_num = args.args[2] as usize; // Number of structs, not number of bytes.
+    if dest_num < 1 {
+        return ResultBuilder::invalid_argument();
+    }
+
+    // Copy the entries out first: copying to user memory while holding
+    // the log lock is not a good idea, as it can log.
+    let mut entries = alloc::vec::Vec::new();
+    crate::xray::logger::read(start_seq, |entry| {
+        entries.push(entry.clone());
+        entries.len() < dest_num
+    });
+
+    let address_space = thread.owner().address_space().clone();
+    let buf: &[u8] = unsafe {
+        core::slice::from_raw_parts(
+            entries.as_ptr() as *const u8,
+            entries.len() * core::mem::size_of::<KLogEntryV1>(),
+        )
+    };
+    if let Err(err) = address_space.copy_to_user(buf, dest_addr) {
+        return ResultBuilder::result(err);
+    }
+
+    ResultBuilder::ok_1(entries.len() as u64)
+}
+
+fn sys_trace(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    // Tracepoints see the whole system.
+    let owner = thread.owner();
+    if (owner.capabilities() & moto_sys::caps::CAP_LOG) == 0 || !owner.namespace().is_root() {
+        return ResultBuilder::result(ErrorCode::NotAllowed);
+    }
+
+    match args.flags {
+        SysRay::F_TRACE_ENABLE => match crate::xray::tracepoints::set_enabled_mask(args.args[0]) {
+            Ok(prev) => ResultBuilder::ok_1(prev),
+            Err(err) => ResultBuilder::result(err),
+        },
+        SysRay::F_TRACE_DRAIN => {
+            let dest_num = (args.args[2] as usize).min(256);
+            if dest_num < 1 {
+                return ResultBuilder::invalid_argument();
+            }
+
+            let mut events = alloc::vec![TraceEventV1::default(); dest_num];
+            let count = match crate::xray::tracepoints::drain(args.args[0] as u32, &mut events) {
+                Ok(count) => count,
+                Err(err) => return ResultBuilder::result(err),
+            };
+
+            let buf: &[u8] = unsafe {
+                core::slice::from_raw_parts(
+                    events.as_ptr() as *const u8,
+                    count * core::mem::size_of::<TraceEventV1>(),
+                )
+            };
+            if let Err(err) = owner.address_space().copy_to_user(buf, args.args[1]) {
+                return ResultBuilder::result(err);
+            }
+
+            ResultBuilder::ok_1(count as u64)
+        }
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
+
+fn sys_syscall_trace(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
+    if args.version > 0 {
+        return ResultBuilder::version_too_high();
+    }
+
+    let owner = thread.owner();
+    match args.flags {
+        SysRay::F_SYSCALL_TRACE_ATTACH => {
+            if args.args[1..] != [0; 5] {
+                return ResultBuilder::invalid_argument();
+            }
+            match super::syscall_trace::attach(&owner, args.args[0]) {
+                Ok(handle) => ResultBuilder::ok_1(handle.as_u64()),
+                Err(err) => ResultBuilder::result(err),
+            }
+        }
+        SysRay::F_SYSCALL_TRACE_READ => {
+            let dest_num = (args.args[2] as usize).min(256);
+            if dest_num < 1 {
+                return ResultBuilder::invalid_argument();
+            }
+
+            let mut events = alloc::vec![SyscallTraceEventV1::default(); dest_num];
+            let count = match super::syscall_trace::read(
+                &owner,
+                SysHandle::from_u64(args.args[0]),
+                &mut events,
+            ) {
+                Ok(count) => count,
+                Err(err) => return ResultBuilder::result(err),
+            };
+
+            let buf: &[u8] = unsafe {
+                core::slice::from_raw_parts(
+                    events.as_ptr() as *const u8,
+                    count * core::mem::size_of::<SyscallTraceEventV1>(),
+                )
+            };
+            if let Err(err) = owner.address_space().copy_to_user(buf, args.args[1]) {
+                return ResultBuilder::result(err);
+            }
+
+            ResultBuilder::ok_1(count as u64)
+        }
+        _ => ResultBuilder::invalid_argument(),
+    }
+}
+
 pub(super) fn sys_ray_impl(thread: &super::process::Thread, args: &SyscallArgs) -> SyscallResult {
     match args.operation {
         SysRay::OP_DBG => super::sys_ray_dbg::sys_ray_dbg_impl(thread, args),
@@ -134,8 +524,16 @@ pub(super) fn sys_ray_impl(thread: &super::process::Thread, args: &SyscallArgs)
             SysRay::F_QUERY_LIST | SysRay::F_QUERY_LIST_CHILDREN => {
                 sys_query_process_list(thread, args)
             }
+            SysRay::F_QUERY_NAMESPACE => sys_query_namespace(thread, args),
+            SysRay::F_QUERY_ACCT => sys_query_acct(thread, args),
+            SysRay::F_QUERY_THREADS => sys_query_threads(thread, args),
+            SysRay::F_QUERY_HANDLES => sys_query_handles(thread, args),
+            SysRay::F_QUERY_CREDENTIALS => sys_query_credentials(thread, args),
             _ => ResultBuilder::invalid_argument(),
         },
+        SysRay::OP_TRACE => sys_trace(thread, args),
+        SysRay::OP_SYSCALL_TRACE => sys_syscall_trace(thread, args),
+        SysRay::OP_LOG if args.flags == SysRay::F_LOG_READ => sys_log_read(thread, args),
         SysRay::OP_LOG => {
             if args.args[2] != 0 || args.args[3] != 0 || args.args[4] != 0 || args.args[5] != 0 {
                 return ResultBuilder::invalid_argument();
@@ -231,6 +231,49 @@ fn sys_dbg_get_thread_data(
     }
 }
 
+fn sys_dbg_list_modules(
+    debugger: Arc<super::process::Process>,
+    args: &SyscallArgs,
+) -> SyscallResult {
+    use moto_sys::stats::ModuleInfoV1;
+
+    if args.version < 1 {
+        return ResultBuilder::version_too_low();
+    }
+    if args.version > 1 {
+        return ResultBuilder::version_too_high();
+    }
+    if args.args[3..] != [0; 3] {
+        return ResultBuilder::invalid_argument();
+    }
+
+    let dbg_handle = SysHandle::from_u64(args.args[0]);
+    let session = match get_session(&debugger, dbg_handle) {
+        Ok(s) => s,
+        Err(err) => return ResultBuilder::result(err),
+    };
+
+    let buf_start = args.args[1];
+    let buf_len = args.args[2];
+    if buf_len == 0 {
+        return ResultBuilder::ok_1(0);
+    }
+
+    // Processes are statically linked, so there is only the executable.
+    let module = *session.debuggee.image();
+    unsafe {
+        let bytes = core::slice::from_raw_parts(
+            &module as *const _ as usize as *const u8,
+            core::mem::size_of::<ModuleInfoV1>(),
+        );
+        if let Err(err) = debugger.address_space().copy_to_user(bytes, buf_start) {
+            return ResultBuilder::result(err);
+        }
+    }
+
+    ResultBuilder::ok_1(1)
+}
+
 fn sys_dbg_resume_process(
     debugger: Arc<super::process::Process>,
     args: &SyscallArgs,
@@ -362,6 +405,7 @@ pub fn sys_ray_dbg_impl(
         SysRay::F_DBG_RESUME_PROCESS => sys_dbg_resume_process(thread.owner(), args),
         SysRay::F_DBG_RESUME_THREAD => sys_dbg_resume_thread(thread.owner(), args),
         SysRay::F_DBG_DETACH => sys_dbg_detach(thread.owner(), args),
+        SysRay::F_DBG_LIST_MODULES => sys_dbg_list_modules(thread.owner(), args),
         _ => ResultBuilder::invalid_argument(),
     }
 }
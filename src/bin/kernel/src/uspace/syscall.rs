@@ -105,6 +105,29 @@ pub fn do_syscall(curr: &super::process::Thread, args: &mut SyscallArgs) -> Sysc
     }
 
     curr.on_syscall_enter(args.syscall_nr, args.operation);
+    let trace_event = super::syscall_trace::on_syscall_enter(&curr.owner(), args);
+    let trace_arg = ((args.syscall_nr as u64) << 8) | (args.operation as u64);
+    crate::xray::tracepoints::tracepoint(
+        moto_sys::stats::TRACE_SYSCALL_ENTER,
+        curr.owner().pid().as_u64(),
+        curr.tid().as_u64(),
+        trace_arg,
+    );
+
+    if !moto_sys::caps::syscall_allowed(curr.syscall_filter(), args.syscall_nr, args.operation) {
+        log::debug!(
+            "do_syscall: thread 0x{:x}: syscall {}:{} filtered out",
+            curr.tid().as_u64(),
+            args.syscall_nr,
+            args.operation
+        );
+        let result = ResultBuilder::result(ErrorCode::NotAllowed);
+        if let Some(event) = trace_event {
+            super::syscall_trace::on_syscall_exit(curr, event, &result);
+        }
+        curr.on_syscall_exit();
+        return result;
+    }
 
     let result = match args.syscall_nr {
         syscalls::SYS_CPU => super::sys_cpu::sys_cpu_impl(curr, args),
@@ -114,6 +137,15 @@ pub fn do_syscall(curr: &super::process::Thread, args: &mut SyscallArgs) -> Sysc
         _ => ResultBuilder::not_implemented(),
     };
 
+    crate::xray::tracepoints::tracepoint(
+        moto_sys::stats::TRACE_SYSCALL_EXIT,
+        curr.owner().pid().as_u64(),
+        curr.tid().as_u64(),
+        trace_arg,
+    );
+    if let Some(event) = trace_event {
+        super::syscall_trace::on_syscall_exit(curr, event, &result);
+    }
     curr.on_syscall_exit();
     result
 }
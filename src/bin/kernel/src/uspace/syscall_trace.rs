@@ -0,0 +1,182 @@
+// Syscall tracing (strace): a tracer subscribes to syscalls of a process
+// via SysRay::OP_SYSCALL_TRACE. Syscalls completed by the tracee are
+// recorded, with their arguments and results, into a bounded queue owned
+// by the subscription, and the tracer's handle is woken. Unlike tracepoints,
+// which see all processes but only syscall numbers, a syscall trace is
+// per process: a process can trace processes in its namespace (or nested
+// ones) that run under its user ID; tracing other users' processes needs
+// CAP_LOG.
+//
+// The tracee references the subscription weakly, so putting the tracer's
+// handle (or the tracer exiting) stops the trace.
+
+use super::process::{Process, Thread};
+use super::syscall::SyscallArgs;
+use super::SysObject;
+use crate::arch::time::Instant;
+use crate::util::SpinLock;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::*;
+use moto_sys::stats::SyscallTraceEventV1;
+use moto_sys::syscalls::SyscallResult;
+use moto_sys::{ErrorCode, SysHandle};
+
+// When the tracer does not keep up, the oldest events are dropped.
+const MAX_QUEUED_EVENTS: usize = 1024;
+
+pub struct SyscallTrace {
+    tracee: Weak<Process>,
+    events: SpinLock<VecDeque<SyscallTraceEventV1>>,
+    next_seq: AtomicU64, // Protected by the events lock.
+    waiter: SpinLock<Weak<SysObject>>,
+}
+
+impl Drop for SyscallTrace {
+    fn drop(&mut self) {
+        let tracee = match self.tracee.upgrade() {
+            Some(tracee) => tracee,
+            None => return,
+        };
+
+        // A new trace may have been attached after the last strong ref
+        // to this one was dropped.
+        let mut current = tracee.syscall_trace.lock(line!());
+        if core::ptr::eq(current.as_ptr(), self) {
+            tracee.syscall_traced.store(false, Ordering::Relaxed);
+            *current = Weak::new();
+        }
+    }
+}
+
+// @pid is as seen in the tracer's namespace.
+pub fn attach(tracer: &Arc<Process>, pid: u64) -> Result<SysHandle, ErrorCode> {
+    let pid = tracer
+        .namespace()
+        .to_global(pid)
+        .ok_or(ErrorCode::NotFound)?
+        .as_u64();
+    if pid < 2 {
+        // Cannot trace system processes.
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    // Cannot trace self or ancestors: e.g. the tracer printing a traced
+    // syscall would generate more traced syscalls.
+    let mut stats = crate::xray::stats::stats_from_pid(tracer.pid().as_u64());
+    while let Some(parent) = stats {
+        if parent.pid().as_u64() == pid {
+            return Err(ErrorCode::NotAllowed);
+        }
+        stats = parent.parent();
+    }
+
+    let tracee = Process::from_pid(pid).ok_or(ErrorCode::NotFound)?;
+    if !tracer.namespace().contains(tracee.namespace()) {
+        return Err(ErrorCode::NotFound);
+    }
+    if tracee.uid() != tracer.uid() && (tracer.capabilities() & moto_sys::caps::CAP_LOG) == 0 {
+        return Err(ErrorCode::NotAllowed);
+    }
+
+    let trace = Arc::new(SyscallTrace {
+        tracee: Arc::downgrade(&tracee),
+        events: SpinLock::new(VecDeque::new()),
+        next_seq: AtomicU64::new(0),
+        waiter: SpinLock::new(Weak::new()),
+    });
+
+    let sys_object = SysObject::new_owned(
+        Arc::new(alloc::format!(
+            "syscall trace {} -> {}",
+            tracer.pid().as_u64(),
+            pid
+        )),
+        trace.clone(),
+        Arc::downgrade(tracer),
+    );
+    *trace.waiter.lock(line!()) = Arc::downgrade(&sys_object);
+
+    {
+        let mut current = tracee.syscall_trace.lock(line!());
+        if current.upgrade().is_some() {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+        *current = Arc::downgrade(&trace);
+        tracee.syscall_traced.store(true, Ordering::Relaxed);
+    }
+
+    log::debug!(
+        "process {} started tracing syscalls of process {}",
+        tracer.pid().as_u64(),
+        pid
+    );
+    Ok(tracer.add_object(sys_object))
+}
+
+// Moves the oldest recorded events into @buf; returns the number of events moved.
+pub fn read(
+    tracer: &Process,
+    handle: SysHandle,
+    buf: &mut [SyscallTraceEventV1],
+) -> Result<usize, ErrorCode> {
+    let trace = super::sysobject::object_from_handle::<SyscallTrace>(tracer, handle)
+        .ok_or(ErrorCode::BadHandle)?;
+
+    let mut events = trace.events.lock(line!());
+    let mut count = 0;
+    while count < buf.len() {
+        match events.pop_front() {
+            Some(event) => buf[count] = event,
+            None => break,
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+// Returns the partially filled event to record on syscall exit, if the
+// process is traced.
+#[inline(always)]
+pub fn on_syscall_enter(process: &Process, args: &SyscallArgs) -> Option<SyscallTraceEventV1> {
+    if !process.syscall_traced.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(SyscallTraceEventV1 {
+        started: Instant::now().as_u64(),
+        syscall_nr: args.syscall_nr,
+        operation: args.operation,
+        version: args.version,
+        flags: args.flags,
+        args: args.args,
+        ..Default::default()
+    })
+}
+
+pub fn on_syscall_exit(thread: &Thread, mut event: SyscallTraceEventV1, result: &SyscallResult) {
+    let trace = match thread.owner().syscall_trace.lock(line!()).upgrade() {
+        Some(trace) => trace,
+        None => return,
+    };
+
+    event.tid = thread.tid().as_u64();
+    event.finished = Instant::now().as_u64();
+    event.result = result.result;
+    event.data = result.data;
+
+    {
+        let mut events = trace.events.lock(line!());
+        event.seq = trace.next_seq.fetch_add(1, Ordering::Relaxed);
+        if events.len() == MAX_QUEUED_EVENTS {
+            events.pop_front(); // Drop the oldest event.
+        }
+        events.push_back(event);
+    }
+
+    let waiter = trace.waiter.lock(line!()).upgrade();
+    if let Some(waiter) = waiter {
+        waiter.wake(false);
+    }
+}
@@ -5,6 +5,7 @@ use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::{collections::BTreeMap, sync::Weak};
 use moto_sys::syscalls::*;
+use moto_sys::ErrorCode;
 
 use crate::util::SpinLock;
 
@@ -41,6 +42,12 @@ pub struct SysObject {
 
     // Some objects wake once and then always stay "woken", e.g. process completions.
     done: AtomicBool,
+
+    // If attached to an event ring, wakes are delivered into the ring instead
+    // of to waiting threads. See event_ring.rs. The lock is taken in IRQs, so
+    // it must be taken with interrupts disabled elsewhere.
+    has_event_ring: AtomicBool,
+    event_ring: SpinLock<Option<super::event_ring::Attachment>>,
 }
 
 impl PartialEq for SysObject {
@@ -80,6 +87,8 @@ impl SysObject {
             wake_counter: AtomicU64::new(0),
             sibling_dropped: AtomicBool::new(false),
             done: AtomicBool::new(false),
+            has_event_ring: AtomicBool::new(false),
+            event_ring: SpinLock::new(None),
         })
     }
 
@@ -120,6 +129,58 @@ impl SysObject {
         self.done.load(Ordering::Acquire)
     }
 
+    pub fn attach_event_ring(
+        &self,
+        attachment: super::event_ring::Attachment,
+    ) -> Result<(), ErrorCode> {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut event_ring = self.event_ring.lock(line!());
+            if event_ring.is_some() {
+                return Err(ErrorCode::AlreadyInUse);
+            }
+            *event_ring = Some(attachment);
+            self.has_event_ring.store(true, Ordering::Release);
+            Ok(())
+        })
+    }
+
+    pub fn detach_event_ring(&self) -> Result<(), ErrorCode> {
+        let attachment = x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut event_ring = self.event_ring.lock(line!());
+            self.has_event_ring.store(false, Ordering::Release);
+            event_ring.take()
+        });
+        // Drop the attachment with interrupts enabled.
+        match attachment {
+            Some(_) => Ok(()),
+            None => Err(ErrorCode::InvalidArgument),
+        }
+    }
+
+    // Returns true if the wake has been delivered into an event ring.
+    // Must be called with interrupts disabled.
+    fn deliver_to_event_ring(&self) -> bool {
+        if !self.has_event_ring.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let event_ring = self.event_ring.lock(line!());
+        let attachment = match event_ring.as_ref() {
+            Some(attachment) => attachment,
+            None => return false,
+        };
+
+        let flags = if self.sibling_dropped() {
+            moto_sys::event_ring::EVENT_F_PEER_DROPPED
+        } else {
+            0
+        };
+        if attachment.ring.push(attachment.cookie, flags) {
+            Self::wake_irq(&attachment.ring_object);
+        }
+        true
+    }
+
     #[inline(always)]
     pub fn is_woken(&self) -> bool {
         self.next_woken.load(Ordering::Relaxed) != 0
@@ -171,6 +232,10 @@ impl SysObject {
     #[inline]
     pub fn wake_irq(self_: &Arc<Self>) {
         self_.wake_counter.fetch_add(1, Ordering::Release);
+        if self_.deliver_to_event_ring() {
+            return;
+        }
+
         // Protect against concurrent attempts to add this object to the woken list.
         let prev = self_.wake_event_lock.swap(true, Ordering::Acquire);
         if prev {
@@ -201,6 +266,12 @@ impl SysObject {
     // NOT called from IRQ.
     pub fn wake(&self, this_cpu: bool) {
         self.wake_counter.fetch_add(1, Ordering::Release);
+        if self.has_event_ring.load(Ordering::Acquire)
+            && x86_64::instructions::interrupts::without_interrupts(|| self.deliver_to_event_ring())
+        {
+            return;
+        }
+
         // Protect against concurrent attempts to add this object to the woken list.
         let prev = self.wake_event_lock.swap(true, Ordering::Acquire);
         if prev {
@@ -0,0 +1,155 @@
+// Timer objects: waitable handles that are woken when the timer expires.
+//
+// A timer is created via SysObj::create(url = "timer") and armed/disarmed
+// via SysCpu::OP_TIMER. A one-shot timer wakes its handle once; a periodic
+// timer wakes its handle every period until disarmed. Expirations are counted,
+// so that the user can figure out how many periods have passed since
+// the last check (wakes are coalesced by sys_wait()).
+
+use super::process::Process;
+use super::SysObject;
+use crate::arch::current_cpu;
+use crate::arch::time::Instant;
+use crate::config::uCpus;
+use crate::util::SpinLock;
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::*;
+use core::time::Duration;
+use moto_sys::SysHandle;
+
+// Periodic timers with shorter periods are not allowed, as they will just
+// burn CPU cycles in the scheduler.
+pub const MIN_PERIOD: Duration = Duration::from_micros(100);
+
+struct UserTimerInner {
+    sys_object: Weak<SysObject>,
+    timer_id: u64, // Zero if not armed.
+    timer_cpu: uCpus,
+    when: Instant,
+    period: Option<Duration>,
+}
+
+pub struct UserTimer {
+    inner: SpinLock<UserTimerInner>,
+    expirations: AtomicU64,
+}
+
+// Armed timers by (scheduler) timer ID.
+static ARMED_TIMERS: SpinLock<BTreeMap<u64, Weak<UserTimer>>> = SpinLock::new(BTreeMap::new());
+
+impl Drop for UserTimer {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock(line!());
+        Self::disarm_locked(&mut inner);
+    }
+}
+
+pub fn create(process: &Process) -> SysHandle {
+    let timer = Arc::new(UserTimer {
+        inner: SpinLock::new(UserTimerInner {
+            sys_object: Weak::new(),
+            timer_id: 0,
+            timer_cpu: 0,
+            when: Instant::nan(),
+            period: None,
+        }),
+        expirations: AtomicU64::new(0),
+    });
+
+    let sys_object = SysObject::new_owned(
+        Arc::new("timer".to_owned()),
+        timer.clone(),
+        alloc::sync::Weak::new(),
+    );
+    timer.inner.lock(line!()).sys_object = Arc::downgrade(&sys_object);
+
+    process.add_object(sys_object)
+}
+
+impl UserTimer {
+    // Arms (if @when is some) or disarms (if @when is none) the timer.
+    // Returns the number of expirations since the last set/take.
+    pub fn set(self: &Arc<Self>, when: Option<Instant>, period: Option<Duration>) -> u64 {
+        let mut inner = self.inner.lock(line!());
+        Self::disarm_locked(&mut inner);
+
+        if let Some(when) = when {
+            inner.period = period;
+            self.arm_locked(&mut inner, when);
+        }
+
+        self.expirations.swap(0, Ordering::Relaxed)
+    }
+
+    // Returns the number of expirations since the last set/take.
+    pub fn take_expirations(&self) -> u64 {
+        self.expirations.swap(0, Ordering::Relaxed)
+    }
+
+    fn arm_locked(self: &Arc<Self>, inner: &mut UserTimerInner, when: Instant) {
+        debug_assert_eq!(inner.timer_id, 0);
+
+        // Note: timers must be posted on the current CPU; see sched::post_timer().
+        let cpu = current_cpu();
+        let timer = crate::sched::Timer::new_detached(Self::job_fn_on_timer, when, cpu);
+        inner.timer_id = timer.id();
+        inner.timer_cpu = cpu;
+        inner.when = when;
+
+        ARMED_TIMERS
+            .lock(line!())
+            .insert(timer.id(), Arc::downgrade(self));
+        crate::sched::post_timer(timer);
+    }
+
+    fn disarm_locked(inner: &mut UserTimerInner) {
+        let timer_id = inner.timer_id;
+        if timer_id == 0 {
+            return;
+        }
+
+        inner.timer_id = 0;
+        crate::sched::cancel_timer(timer_id, inner.timer_cpu);
+        ARMED_TIMERS.lock(line!()).remove(&timer_id);
+    }
+
+    fn job_fn_on_timer(_: &Weak<super::process::Thread>, timer_id: u64) {
+        let timer = {
+            let armed = ARMED_TIMERS.lock(line!());
+            match armed.get(&timer_id).and_then(|t| t.upgrade()) {
+                Some(timer) => timer,
+                None => return,
+            }
+        };
+
+        let mut inner = timer.inner.lock(line!());
+        if inner.timer_id != timer_id {
+            return; // Re-armed or disarmed concurrently.
+        }
+        inner.timer_id = 0;
+        ARMED_TIMERS.lock(line!()).remove(&timer_id);
+
+        let mut expirations = 1;
+        if let Some(period) = inner.period {
+            // Skip (but count) the periods we have missed.
+            let now = Instant::now();
+            let mut next = inner.when + period;
+            if next <= now {
+                let period_nanos = period.as_nanos() as u64;
+                let missed = (now.duration_since(next).as_nanos() as u64) / period_nanos + 1;
+                expirations += missed;
+                next = inner.when + Duration::from_nanos(period_nanos.saturating_mul(missed + 1));
+            }
+            timer.arm_locked(&mut inner, next);
+        }
+        timer.expirations.fetch_add(expirations, Ordering::Relaxed);
+
+        let sys_object = inner.sys_object.upgrade();
+        drop(inner);
+        if let Some(sys_object) = sys_object {
+            sys_object.wake(false);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Watchdog objects: detect silent hangs in (system) services.
+//
+// A watchdog is created via SysObj::create(url = "watchdog:timeout_ms=N;action=A")
+// and is petted via SysCpu::OP_WATCHDOG. The process that pets the watchdog
+// is the watched process: usually a supervisor creates the watchdog and passes
+// it to the child it spawns (see SysObj::dup()), and the child pets it.
+//
+// The watchdog is armed on the first pet. If the watched process does not pet
+// the watchdog within the timeout, the kernel logs an error, counts the miss,
+// and wakes the watchdog handle (so that the supervisor waiting on it can
+// react, e.g. restart the service). Depending on the action, the kernel also:
+//   - "dump": prints backtraces of all threads of the watched process;
+//   - "pause": pauses the watched process as a debuggee, so that a debugger
+//     can be attached to inspect the hang.
+// Only the first miss after a pet triggers the action; subsequent misses
+// are just logged and counted.
+
+use super::process::Process;
+use super::SysObject;
+use crate::arch::current_cpu;
+use crate::arch::time::Instant;
+use crate::config::uCpus;
+use crate::util::SpinLock;
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::*;
+use core::time::Duration;
+use moto_sys::{ErrorCode, SysHandle};
+
+// Shorter timeouts are not useful for detecting hangs, and will just
+// burn CPU cycles in the scheduler.
+pub const MIN_TIMEOUT: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Log,
+    Dump,
+    Pause,
+}
+
+struct WatchdogInner {
+    sys_object: Weak<SysObject>,
+    watched: Weak<Process>,
+    timer_id: u64, // Zero if not armed.
+    timer_cpu: uCpus,
+    missed_since_pet: bool,
+}
+
+pub struct Watchdog {
+    inner: SpinLock<WatchdogInner>,
+    timeout: Duration,
+    action: Action,
+    misses: AtomicU64,
+}
+
+// Armed watchdogs by (scheduler) timer ID.
+static ARMED_WATCHDOGS: SpinLock<BTreeMap<u64, Weak<Watchdog>>> = SpinLock::new(BTreeMap::new());
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock(line!());
+        Self::disarm_locked(&mut inner);
+    }
+}
+
+pub fn create(process: &Process, url: &str) -> Result<SysHandle, ErrorCode> {
+    let args: alloc::vec::Vec<&str> = match url.strip_prefix("watchdog:") {
+        Some(args) => args.split(';').filter(|s| !s.is_empty()).collect(),
+        None => return Err(ErrorCode::InvalidArgument),
+    };
+
+    let timeout = match crate::util::decode_arg::<u64>(&args, "timeout_ms") {
+        Some(ms) => Duration::from_millis(ms),
+        None => return Err(ErrorCode::InvalidArgument),
+    };
+    if timeout < MIN_TIMEOUT {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let action = match crate::util::decode_arg::<alloc::string::String>(&args, "action") {
+        None => Action::Log,
+        Some(action) => match action.as_str() {
+            "log" => Action::Log,
+            "dump" => Action::Dump,
+            "pause" => Action::Pause,
+            _ => return Err(ErrorCode::InvalidArgument),
+        },
+    };
+
+    let watchdog = Arc::new(Watchdog {
+        inner: SpinLock::new(WatchdogInner {
+            sys_object: Weak::new(),
+            watched: Weak::new(),
+            timer_id: 0,
+            timer_cpu: 0,
+            missed_since_pet: false,
+        }),
+        timeout,
+        action,
+        misses: AtomicU64::new(0),
+    });
+
+    let sys_object = SysObject::new_owned(
+        Arc::new("watchdog".to_owned()),
+        watchdog.clone(),
+        alloc::sync::Weak::new(),
+    );
+    watchdog.inner.lock(line!()).sys_object = Arc::downgrade(&sys_object);
+
+    Ok(process.add_object(sys_object))
+}
+
+impl Watchdog {
+    // Re-arms the watchdog, making @process the watched process.
+    // Returns the number of misses since the last pet.
+    pub fn pet(self: &Arc<Self>, process: &Arc<Process>) -> u64 {
+        let mut inner = self.inner.lock(line!());
+        Self::disarm_locked(&mut inner);
+
+        inner.watched = Arc::downgrade(process);
+        inner.missed_since_pet = false;
+        self.arm_locked(&mut inner);
+
+        self.misses.swap(0, Ordering::Relaxed)
+    }
+
+    // Stops watching; a subsequent pet re-arms the watchdog.
+    // Returns the number of misses since the last pet.
+    pub fn disarm(&self) -> u64 {
+        let mut inner = self.inner.lock(line!());
+        Self::disarm_locked(&mut inner);
+        inner.watched = Weak::new();
+
+        self.misses.swap(0, Ordering::Relaxed)
+    }
+
+    // Returns the number of misses since the last pet, without petting.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn arm_locked(self: &Arc<Self>, inner: &mut WatchdogInner) {
+        debug_assert_eq!(inner.timer_id, 0);
+
+        // Note: timers must be posted on the current CPU; see sched::post_timer().
+        let cpu = current_cpu();
+        let timer = crate::sched::Timer::new_detached(
+            Self::job_fn_on_timer,
+            Instant::now() + self.timeout,
+            cpu,
+        );
+        inner.timer_id = timer.id();
+        inner.timer_cpu = cpu;
+
+        ARMED_WATCHDOGS
+            .lock(line!())
+            .insert(timer.id(), Arc::downgrade(self));
+        crate::sched::post_timer(timer);
+    }
+
+    fn disarm_locked(inner: &mut WatchdogInner) {
+        let timer_id = inner.timer_id;
+        if timer_id == 0 {
+            return;
+        }
+
+        inner.timer_id = 0;
+        crate::sched::cancel_timer(timer_id, inner.timer_cpu);
+        ARMED_WATCHDOGS.lock(line!()).remove(&timer_id);
+    }
+
+    fn job_fn_on_timer(_: &Weak<super::process::Thread>, timer_id: u64) {
+        let watchdog = {
+            let armed = ARMED_WATCHDOGS.lock(line!());
+            match armed.get(&timer_id).and_then(|w| w.upgrade()) {
+                Some(watchdog) => watchdog,
+                None => return,
+            }
+        };
+
+        let mut inner = watchdog.inner.lock(line!());
+        if inner.timer_id != timer_id {
+            return; // Petted or disarmed concurrently.
+        }
+        inner.timer_id = 0;
+        ARMED_WATCHDOGS.lock(line!()).remove(&timer_id);
+
+        let process = match inner.watched.upgrade() {
+            Some(process) => process,
+            None => return, // The watched process is gone: nothing to watch.
+        };
+
+        let misses = watchdog.misses.fetch_add(1, Ordering::Relaxed) + 1;
+        let first_miss = !inner.missed_since_pet;
+        inner.missed_since_pet = true;
+
+        // Keep watching, so that a long hang is reported every timeout.
+        watchdog.arm_locked(&mut inner);
+
+        let sys_object = inner.sys_object.upgrade();
+        drop(inner);
+
+        log::error!(
+            "watchdog: process {} '{}' missed its deadline ({} ms); misses: {}",
+            process.pid().as_u64(),
+            process.debug_name(),
+            watchdog.timeout.as_millis(),
+            misses
+        );
+
+        if first_miss {
+            match watchdog.action {
+                Action::Log => {}
+                Action::Dump => process.print_backtraces(),
+                Action::Pause => {
+                    if let Err(err) = process.dbg_pause() {
+                        log::warn!(
+                            "watchdog: failed to pause process {}: {:?}",
+                            process.pid().as_u64(),
+                            err
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(sys_object) = sys_object {
+            sys_object.wake(false);
+        }
+    }
+}
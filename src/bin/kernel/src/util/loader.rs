@@ -31,6 +31,9 @@ impl<'a> elfloader::ElfLoader for Loader<'a> {
             if header.flags().is_write() {
                 mapping_options |= MappingOptions::WRITABLE;
             }
+            if header.flags().is_execute() {
+                mapping_options |= MappingOptions::EXECUTABLE;
+            }
 
             let num_pages = (vaddr_end - vaddr_start) >> PAGE_SIZE_SMALL_LOG2;
             self.address_space
@@ -93,6 +93,27 @@ impl<T: ?Sized> SpinLock<T> {
             data: unsafe { &mut *self.data.get() },
         }
     }
+
+    // Does not spin: returns None if the lock is held.
+    pub fn try_lock(&self, lockword: u32) -> Option<LockGuard<'_, T>> {
+        assert_ne!(0, lockword);
+        if self
+            .lock_word
+            .compare_exchange(0, lockword, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+
+        #[cfg(debug_assertions)]
+        self.lock_cpu
+            .store(crate::arch::current_cpu() as u32, Ordering::Release);
+
+        Some(LockGuard {
+            lock_word: &self.lock_word,
+            data: unsafe { &mut *self.data.get() },
+        })
+    }
 }
 
 impl<T: ?Sized + Default> Default for SpinLock<T> {
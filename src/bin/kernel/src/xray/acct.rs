@@ -0,0 +1,106 @@
+// Process accounting: a record is added here for every userspace process
+// that exits. The log is a fixed-size ring, so old records are dropped
+// if nobody reads them in time; readers detect gaps via record sequence
+// numbers.
+
+use crate::uspace::process::{Process, ProcessStatus};
+use crate::util::SpinLock;
+use alloc::collections::VecDeque;
+use moto_sys::stats::ProcessAcctV1;
+
+pub const ACCT_LOG_SIZE: usize = 256;
+
+struct AcctEntry {
+    record: ProcessAcctV1,
+    namespace_id: u64,
+    local_pid: u64,
+    local_parent_pid: u64,
+}
+
+struct AcctLog {
+    entries: VecDeque<AcctEntry>,
+    next_seq: u64,
+}
+
+static ACCT_LOG: SpinLock<AcctLog> = SpinLock::new(AcctLog {
+    entries: VecDeque::new(),
+    next_seq: 1,
+});
+
+// Called once, when the last thread of @process has exited.
+pub fn on_process_exited(process: &Process, status: ProcessStatus) {
+    let stats = process.stats();
+    let (exit_status, exit_code) = match status {
+        ProcessStatus::Exited(code) => (ProcessAcctV1::EXITED, code),
+        ProcessStatus::Killed => (ProcessAcctV1::KILLED, u64::MAX),
+        _ => (ProcessAcctV1::ERROR, u64::MAX),
+    };
+
+    let mut record = ProcessAcctV1::default();
+    record.pid = process.pid().as_u64();
+    record.parent_pid = stats.parent().map_or(0, |p| p.pid().as_u64());
+    record.started = stats.started();
+    record.exited = crate::arch::time::Instant::now().as_u64();
+    record.cpu_usage = stats.cpu_usage(record.exited);
+    record.cpu_usage_kernel = stats.cpu_usage_kernel(record.exited);
+    record.peak_pages_user = stats.peak_pages_user();
+    record.exit_code = exit_code;
+    record.exit_status = exit_status;
+
+    let debug_name = process.debug_name().as_bytes();
+    let name_len = debug_name.len().min(record.debug_name_bytes.len());
+    record.debug_name_bytes[0..name_len].copy_from_slice(&debug_name[0..name_len]);
+    record.debug_name_len = name_len as u8;
+
+    let namespace = process.namespace();
+    let mut entry = AcctEntry {
+        namespace_id: namespace.id(),
+        local_pid: namespace.to_local(process.pid()).unwrap_or(0),
+        local_parent_pid: stats
+            .parent()
+            .and_then(|p| namespace.to_local(p.pid()))
+            .unwrap_or(0),
+        record,
+    };
+
+    let mut log = ACCT_LOG.lock(line!());
+    entry.record.seq = log.next_seq;
+    log.next_seq += 1;
+    if log.entries.len() == ACCT_LOG_SIZE {
+        log.entries.pop_front();
+    }
+    log.entries.push_back(entry);
+}
+
+// Calls @func for records with seq >= @start_seq, in order, until @func
+// returns false. Processes in a non-root namespace see only records of
+// processes from that namespace, with local PIDs.
+pub fn read<F>(caller: &Process, start_seq: u64, mut func: F)
+where
+    F: FnMut(&ProcessAcctV1) -> bool,
+{
+    let namespace = caller.namespace();
+    let log = ACCT_LOG.lock(line!());
+    for entry in log.entries.iter() {
+        if entry.record.seq < start_seq {
+            continue;
+        }
+
+        if namespace.is_root() {
+            if !func(&entry.record) {
+                break;
+            }
+            continue;
+        }
+
+        if entry.namespace_id != namespace.id() {
+            continue;
+        }
+        let mut record = entry.record.clone();
+        record.pid = entry.local_pid;
+        record.parent_pid = entry.local_parent_pid;
+        if !func(&record) {
+            break;
+        }
+    }
+}
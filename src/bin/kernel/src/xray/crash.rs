@@ -0,0 +1,206 @@
+// Kernel crash dumps: see moto_sys::crash for the format.
+//
+// On a panic, the panicking CPU stops the other CPUs with an NMI; each stopped
+// CPU saves its registers and backtrace into its slot and halts. The panicking
+// CPU then writes the crash image to the serial console and, if the VM has one,
+// to the crash console port (see crash_console.rs). As the kernel state is
+// suspect, this path neither allocates nor takes (blocking) locks.
+
+use crate::config::MAX_CPUS;
+use core::cell::UnsafeCell;
+use core::sync::atomic::*;
+use moto_sys::crash::*;
+use moto_sys::stats::KLogEntryV1;
+
+// How long to wait for other CPUs to respond to the NMI.
+const NMI_WAIT_SPINS: u64 = 100_000_000;
+
+struct CpuSlot {
+    saved: AtomicBool,
+    data: UnsafeCell<CrashCpuV1>,
+}
+
+unsafe impl Sync for CpuSlot {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: CpuSlot = CpuSlot {
+    saved: AtomicBool::new(false),
+    data: UnsafeCell::new(unsafe { core::mem::zeroed() }),
+};
+
+static SLOTS: [CpuSlot; MAX_CPUS as usize] = [EMPTY_SLOT; MAX_CPUS as usize];
+static IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+pub fn in_progress() -> bool {
+    IN_PROGRESS.load(Ordering::Acquire)
+}
+
+// Called on a CPU stopped by the crash NMI. @in_kernel: whether the CPU
+// was running the kernel (otherwise there is no kernel backtrace).
+pub fn on_nmi(regs: &CrashRegsV1, in_kernel: bool) -> ! {
+    let cpu = crate::arch::apic_cpu_id_32();
+    if let Some(slot) = SLOTS.get(cpu as usize) {
+        let data = unsafe { &mut *slot.data.get() };
+        data.cpu = cpu;
+        data.state = CRASH_CPU_STOPPED;
+        data.regs = *regs;
+        if in_kernel {
+            crate::arch::backtrace_from(regs.rbp, &mut data.backtrace);
+        }
+        slot.saved.store(true, Ordering::Release);
+    }
+
+    loop {
+        x86_64::instructions::interrupts::disable();
+        x86_64::instructions::hlt();
+    }
+}
+
+// Called once, from the panic handler.
+pub fn dump(info: &core::panic::PanicInfo) {
+    let this_cpu = crate::arch::apic_cpu_id_32();
+    let num_cpus = (crate::config::num_cpus() as u32).clamp(1, MAX_CPUS as u32);
+
+    IN_PROGRESS.store(true, Ordering::Release);
+    if num_cpus > 1 {
+        crate::arch::irq::nmi_other_cpus();
+        for _ in 0..NMI_WAIT_SPINS {
+            let waiting = (0..num_cpus)
+                .filter(|cpu| *cpu != this_cpu)
+                .any(|cpu| !SLOTS[cpu as usize].saved.load(Ordering::Acquire));
+            if !waiting {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    if let Some(slot) = SLOTS.get(this_cpu as usize) {
+        let data = unsafe { &mut *slot.data.get() };
+        data.cpu = this_cpu;
+        data.state = CRASH_CPU_PANIC;
+
+        let rsp: u64;
+        let rbp: u64;
+        unsafe {
+            core::arch::asm!(
+                "mov {}, rsp",
+                "mov {}, rbp",
+                out(reg) rsp,
+                out(reg) rbp,
+                options(nomem, nostack, preserves_flags)
+            )
+        };
+        let (cr2, cr3) = crate::arch::read_cr2_cr3();
+        data.regs.rsp = rsp;
+        data.regs.rbp = rbp;
+        data.regs.cr2 = cr2;
+        data.regs.cr3 = cr3;
+        crate::arch::backtrace_from(rbp, &mut data.backtrace);
+        slot.saved.store(true, Ordering::Release);
+    }
+
+    let mut header = CrashHeaderV1 {
+        magic: CRASH_MAGIC,
+        version: CRASH_VERSION,
+        panic_cpu: this_cpu,
+        num_cpus,
+        num_log_entries: 0,
+        timestamp: crate::arch::time::Instant::now().as_u64(),
+        tsc_in_sec: (crate::arch::time::Instant::from_u64(0) + core::time::Duration::from_secs(1))
+            .as_u64(),
+        uptime_nanos: crate::arch::time::system_start_time().elapsed().as_nanos() as u64,
+        kernel_offset: crate::mm::kernel_offset_virt(),
+        msg_len: 0,
+        _reserved: 0,
+        msg_bytes: [0; CRASH_MAX_MSG_LEN],
+    };
+    let mut writer = super::logger::TruncatingWriter::new(&mut header.msg_bytes);
+    let _ = core::fmt::write(&mut writer, format_args!("{}", info));
+    header.msg_len = writer.len() as u32;
+
+    let written = super::logger::try_read_recent(CRASH_MAX_LOG_ENTRIES, |count, entries| {
+        header.num_log_entries = count as u32;
+        write_image(&header, entries);
+    });
+    if !written {
+        // A stopped CPU holds the log ring lock.
+        write_image(&header, &mut core::iter::empty());
+    }
+}
+
+fn write_image(header: &CrashHeaderV1, log_entries: &mut dyn Iterator<Item = &KLogEntryV1>) {
+    let mut writer = HexWriter {
+        line: [0; CRASH_BYTES_PER_LINE],
+        len: 0,
+        checksum: CRASH_CHECKSUM_INIT,
+    };
+
+    emit(format_args!("\n{}\n", CRASH_BEGIN_MARKER));
+    writer.write(as_bytes(header));
+    for cpu in 0..header.num_cpus {
+        let slot = &SLOTS[cpu as usize];
+        if slot.saved.load(Ordering::Acquire) {
+            writer.write(as_bytes(unsafe { &*slot.data.get() }));
+        } else {
+            let mut data = CrashCpuV1::default();
+            data.cpu = cpu;
+            writer.write(as_bytes(&data));
+        }
+    }
+    for entry in log_entries {
+        writer.write(as_bytes(entry));
+    }
+    let checksum = writer.checksum;
+    writer.write(&checksum.to_le_bytes());
+    writer.flush();
+    emit(format_args!("{}\n", CRASH_END_MARKER));
+}
+
+// Writes to the serial console and to the crash console port. Both get the same
+// text, so the crashdump tool can parse either.
+fn emit(args: core::fmt::Arguments) {
+    crate::write_serial!("{}", args);
+    let _ = core::fmt::write(&mut super::crash_console::Writer, args);
+}
+
+fn as_bytes<T: Sized>(val: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(val as *const T as *const u8, core::mem::size_of::<T>()) }
+}
+
+struct HexWriter {
+    line: [u8; CRASH_BYTES_PER_LINE],
+    len: usize,
+    checksum: u64,
+}
+
+impl HexWriter {
+    fn write(&mut self, bytes: &[u8]) {
+        self.checksum = crash_checksum(self.checksum, bytes);
+        for byte in bytes {
+            self.line[self.len] = *byte;
+            self.len += 1;
+            if self.len == CRASH_BYTES_PER_LINE {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        if self.len == 0 {
+            return;
+        }
+        let mut out = [0_u8; CRASH_BYTES_PER_LINE * 2];
+        for (idx, byte) in self.line[..self.len].iter().enumerate() {
+            out[idx * 2] = HEX[(byte >> 4) as usize];
+            out[idx * 2 + 1] = HEX[(byte & 0xf) as usize];
+        }
+        emit(format_args!(
+            "{}\n",
+            core::str::from_utf8(&out[..(self.len * 2)]).unwrap()
+        ));
+        self.len = 0;
+    }
+}
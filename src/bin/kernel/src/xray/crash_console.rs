@@ -0,0 +1,384 @@
+// A virtio-console port that kernel crash dumps are written to (see crash.rs).
+//
+// Unlike the serial console, whatever the host connects to this port (e.g. a file,
+// see docs/build.md) keeps the dump after the VM is gone, without anything having
+// to capture the console. The kernel owns the device (sys-io skips virtio-console
+// devices): it is set up at bootup and then only polled, so writing to it on a
+// panic does not allocate, take locks, or depend on interrupts.
+//
+// Only the minimum is implemented: a modern (virtio 1.0+) virtio-console device
+// on PCI bus 0, without VIRTIO_CONSOLE_F_MULTIPORT, so that port 0 is the console
+// and its transmitq is virtqueue 1, with a single in-flight buffer.
+
+use crate::mm::{PAGE_SIZE_SMALL, PAGING_DIRECT_MAP_OFFSET};
+use crate::util::StaticRef;
+use core::sync::atomic::*;
+use x86_64::instructions::port::Port;
+
+const VIRTIO_VENDOR_ID: u16 = 0x1af4;
+const VIRTIO_CONSOLE_DEVICE_ID: u16 = 0x1043;
+
+const PCI_CFG_COMMAND: u8 = 0x04;
+const PCI_COMMAND_BUS_MEM: u16 = 0x02;
+const PCI_COMMAND_BUS_MASTER: u16 = 0x04;
+const PCI_COMMAND_INTX_DISABLE: u16 = 0x400;
+const PCI_STATUS_CAP_LIST: u16 = 0x10;
+const PCI_CAPABILITY_LIST: u8 = 0x34;
+const PCI_CAP_VENDOR: u8 = 0x09;
+
+const VIRTIO_PCI_CAP_COMMON_CFG: u8 = 1;
+const VIRTIO_PCI_CAP_NOTIFY_CFG: u8 = 2;
+
+// Offsets in struct virtio_pci_common_cfg (section 4.1.4.3 in virtio 1.1 spec).
+const COMMON_DRIVER_FEATURE_SELECT: u64 = 0x08;
+const COMMON_DRIVER_FEATURE: u64 = 0x0c;
+const COMMON_DEVICE_STATUS: u64 = 0x14;
+const COMMON_QUEUE_SELECT: u64 = 0x16;
+const COMMON_QUEUE_SIZE: u64 = 0x18;
+const COMMON_QUEUE_ENABLE: u64 = 0x1c;
+const COMMON_QUEUE_NOTIFY_OFF: u64 = 0x1e;
+const COMMON_QUEUE_DESC: u64 = 0x20;
+const COMMON_QUEUE_DRIVER: u64 = 0x28;
+const COMMON_QUEUE_DEVICE: u64 = 0x30;
+const COMMON_CFG_LEN: u64 = 0x38;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FEATURES_OK: u8 = 8;
+
+const VIRTQ_AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+// Port 0 transmitq.
+const TX_QUEUE: u16 = 1;
+const QUEUE_SIZE: u16 = 2;
+
+// The virtqueue and the data buffer share a single page.
+const DESC_OFFSET: u64 = 0;
+const AVAIL_OFFSET: u64 = DESC_OFFSET + 16 * (QUEUE_SIZE as u64);
+const USED_OFFSET: u64 = crate::mm::align_up(AVAIL_OFFSET + 6 + 2 * (QUEUE_SIZE as u64), 4);
+const DATA_OFFSET: u64 = 512;
+const DATA_SIZE: usize = (PAGE_SIZE_SMALL - DATA_OFFSET) as usize;
+
+// How long to wait for the device to consume a buffer.
+const USED_WAIT_SPINS: u64 = 100_000_000;
+
+struct CrashConsole {
+    page_phys: u64,
+    notify_addr: u64,
+    next_avail: AtomicU16,
+    broken: AtomicBool,
+}
+
+static CONSOLE: StaticRef<CrashConsole> = StaticRef::default_const();
+
+#[derive(Clone, Copy)]
+struct PciFunction {
+    bus: u8,
+    slot: u8,
+    func: u8,
+}
+
+impl PciFunction {
+    fn select(&self, offset: u8) {
+        let address = 0x8000_0000_u32
+            | ((self.bus as u32) << 16)
+            | ((self.slot as u32) << 11)
+            | ((self.func as u32) << 8)
+            | ((offset as u32) & 0xfc);
+        unsafe { Port::<u32>::new(0xcf8).write(address) };
+    }
+
+    fn read_u32(&self, offset: u8) -> u32 {
+        self.select(offset);
+        unsafe { Port::<u32>::new(0xcfc).read() }
+    }
+
+    fn write_u32(&self, offset: u8, val: u32) {
+        self.select(offset);
+        unsafe { Port::<u32>::new(0xcfc).write(val) };
+    }
+
+    fn read_u16(&self, offset: u8) -> u16 {
+        (self.read_u32(offset) >> ((offset & 2) * 8)) as u16
+    }
+
+    fn read_u8(&self, offset: u8) -> u8 {
+        (self.read_u32(offset) >> ((offset & 3) * 8)) as u8
+    }
+
+    fn write_u16(&self, offset: u8, val: u16) {
+        let shift = (offset & 2) * 8;
+        let prev = self.read_u32(offset) & !(0xffff_u32 << shift);
+        self.write_u32(offset, prev | ((val as u32) << shift));
+    }
+
+    fn bar_phys_addr(&self, bar: u8) -> Option<u64> {
+        if bar > 5 {
+            return None;
+        }
+        let offset = 0x10 + bar * 4;
+        let lo = self.read_u32(offset);
+        if lo & 1 != 0 {
+            return None; // An I/O space BAR.
+        }
+        let hi = if lo & 6 == 4 {
+            self.read_u32(offset + 4)
+        } else {
+            0
+        };
+        Some(((hi as u64) << 32) | ((lo & 0xffff_fff0) as u64))
+    }
+}
+
+// A virtio PCI capability: a region in one of the BARs.
+struct VirtioCap {
+    bar: u8,
+    offset: u64,
+    length: u64,
+    notify_off_multiplier: u32,
+}
+
+fn find_virtio_cap(dev: PciFunction, cfg_type: u8) -> Option<VirtioCap> {
+    if dev.read_u16(0x06) & PCI_STATUS_CAP_LIST == 0 {
+        return None;
+    }
+
+    let mut pos = dev.read_u8(PCI_CAPABILITY_LIST);
+    for _ in 0..48 {
+        if pos < 0x40 {
+            break;
+        }
+        pos &= !3;
+        let cap = dev.read_u16(pos);
+        if (cap & 0xff) as u8 == PCI_CAP_VENDOR && dev.read_u8(pos + 3) == cfg_type {
+            return Some(VirtioCap {
+                bar: dev.read_u8(pos + 4),
+                offset: dev.read_u32(pos + 8) as u64,
+                length: dev.read_u32(pos + 12) as u64,
+                notify_off_multiplier: if cfg_type == VIRTIO_PCI_CAP_NOTIFY_CFG {
+                    dev.read_u32(pos + 16)
+                } else {
+                    0
+                },
+            });
+        }
+        pos = (cap >> 8) as u8;
+    }
+
+    None
+}
+
+fn find_device() -> Option<PciFunction> {
+    // Scanning all 256 buses takes too long for the bootup path; VMs we run in
+    // put virtio devices on bus 0.
+    for slot in 0..32 {
+        for func in 0..8 {
+            let dev = PciFunction { bus: 0, slot, func };
+            let id = dev.read_u32(0);
+            if (id & 0xffff) as u16 == VIRTIO_VENDOR_ID
+                && (id >> 16) as u16 == VIRTIO_CONSOLE_DEVICE_ID
+            {
+                return Some(dev);
+            }
+            if func == 0 && (id == 0xffff_ffff || dev.read_u8(0x0e) & 0x80 == 0) {
+                break; // No device, or not a multi-function device.
+            }
+        }
+    }
+
+    None
+}
+
+fn mmio_read_u8(addr: u64) -> u8 {
+    unsafe { core::ptr::read_volatile(addr as *const u8) }
+}
+
+fn mmio_write_u8(addr: u64, val: u8) {
+    unsafe { core::ptr::write_volatile(addr as *mut u8, val) }
+}
+
+fn mmio_read_u16(addr: u64) -> u16 {
+    unsafe { core::ptr::read_volatile(addr as *const u16) }
+}
+
+fn mmio_write_u16(addr: u64, val: u16) {
+    unsafe { core::ptr::write_volatile(addr as *mut u16, val) }
+}
+
+fn mmio_write_u32(addr: u64, val: u32) {
+    unsafe { core::ptr::write_volatile(addr as *mut u32, val) }
+}
+
+fn mmio_write_u64(addr: u64, val: u64) {
+    mmio_write_u32(addr, val as u32);
+    mmio_write_u32(addr + 4, (val >> 32) as u32);
+}
+
+// Probes for the crash console and sets it up. Called once, on bootup.
+pub fn init() {
+    let Some(dev) = find_device() else {
+        return;
+    };
+
+    let (Some(common), Some(notify)) = (
+        find_virtio_cap(dev, VIRTIO_PCI_CAP_COMMON_CFG),
+        find_virtio_cap(dev, VIRTIO_PCI_CAP_NOTIFY_CFG),
+    ) else {
+        log::warn!("crash console: virtio capabilities not found");
+        return;
+    };
+    if common.bar != notify.bar || common.length < COMMON_CFG_LEN {
+        log::warn!("crash console: unsupported BAR layout");
+        return;
+    }
+    let Some(bar_phys) = dev.bar_phys_addr(common.bar) else {
+        log::warn!("crash console: unsupported BAR {}", common.bar);
+        return;
+    };
+
+    let mut command = dev.read_u16(PCI_CFG_COMMAND);
+    command |= PCI_COMMAND_BUS_MEM | PCI_COMMAND_BUS_MASTER | PCI_COMMAND_INTX_DISABLE;
+    dev.write_u16(PCI_CFG_COMMAND, command);
+
+    // Map the part of the BAR that covers both capabilities.
+    let region_start = crate::mm::align_down(common.offset.min(notify.offset), PAGE_SIZE_SMALL);
+    let region_end = (common.offset + common.length).max(notify.offset + notify.length);
+    let mapping = match crate::mm::mmio::mmio_map_region(
+        bar_phys + region_start,
+        region_end - region_start,
+    ) {
+        Ok(mapping) => mapping,
+        Err(err) => {
+            log::warn!("crash console: failed to map the BAR: {:?}", err);
+            return;
+        }
+    };
+    let cfg = mapping.virt_addr + common.offset - region_start;
+
+    // Device initialization: see section 3.1.1 in virtio 1.1 spec.
+    mmio_write_u8(cfg + COMMON_DEVICE_STATUS, 0);
+    mmio_write_u8(cfg + COMMON_DEVICE_STATUS, STATUS_ACKNOWLEDGE);
+    mmio_write_u8(
+        cfg + COMMON_DEVICE_STATUS,
+        STATUS_ACKNOWLEDGE | STATUS_DRIVER,
+    );
+
+    // Only VIRTIO_F_VERSION_1 (bit 32).
+    mmio_write_u32(cfg + COMMON_DRIVER_FEATURE_SELECT, 0);
+    mmio_write_u32(cfg + COMMON_DRIVER_FEATURE, 0);
+    mmio_write_u32(cfg + COMMON_DRIVER_FEATURE_SELECT, 1);
+    mmio_write_u32(cfg + COMMON_DRIVER_FEATURE, 1);
+
+    let status = STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK;
+    mmio_write_u8(cfg + COMMON_DEVICE_STATUS, status);
+    if mmio_read_u8(cfg + COMMON_DEVICE_STATUS) != status {
+        log::warn!("crash console: features not accepted");
+        return;
+    }
+
+    mmio_write_u16(cfg + COMMON_QUEUE_SELECT, TX_QUEUE);
+    if mmio_read_u16(cfg + COMMON_QUEUE_SIZE) < QUEUE_SIZE {
+        log::warn!("crash console: no transmit queue");
+        return;
+    }
+    mmio_write_u16(cfg + COMMON_QUEUE_SIZE, QUEUE_SIZE);
+    let notify_off = mmio_read_u16(cfg + COMMON_QUEUE_NOTIFY_OFF) as u64;
+
+    let page_phys = match crate::mm::phys::phys_allocate_frameless(crate::mm::PageType::SmallPage) {
+        Ok(addr) => addr,
+        Err(_) => return,
+    };
+    crate::mm::zero_page(
+        PAGING_DIRECT_MAP_OFFSET + page_phys,
+        crate::mm::PageType::SmallPage,
+    );
+    mmio_write_u16(
+        PAGING_DIRECT_MAP_OFFSET + page_phys + AVAIL_OFFSET,
+        VIRTQ_AVAIL_F_NO_INTERRUPT,
+    );
+
+    mmio_write_u64(cfg + COMMON_QUEUE_DESC, page_phys + DESC_OFFSET);
+    mmio_write_u64(cfg + COMMON_QUEUE_DRIVER, page_phys + AVAIL_OFFSET);
+    mmio_write_u64(cfg + COMMON_QUEUE_DEVICE, page_phys + USED_OFFSET);
+    mmio_write_u16(cfg + COMMON_QUEUE_ENABLE, 1);
+
+    mmio_write_u8(cfg + COMMON_DEVICE_STATUS, status | STATUS_DRIVER_OK);
+
+    let console = alloc::boxed::Box::leak(alloc::boxed::Box::new(CrashConsole {
+        page_phys,
+        notify_addr: mapping.virt_addr + notify.offset - region_start
+            + notify_off * (notify.notify_off_multiplier as u64),
+        next_avail: AtomicU16::new(0),
+        broken: AtomicBool::new(false),
+    }));
+    CONSOLE.set(console);
+    log::info!("crash console at {}:{}.{}", dev.bus, dev.slot, dev.func);
+}
+
+// Writes @bytes to the crash console, if there is one. Only called on a panic,
+// by the single CPU that writes the crash dump.
+pub fn write(bytes: &[u8]) {
+    let Some(console) = CONSOLE.get() else {
+        return;
+    };
+
+    for chunk in bytes.chunks(DATA_SIZE) {
+        if console.broken.load(Ordering::Relaxed) {
+            return;
+        }
+        console.send(chunk);
+    }
+}
+
+impl CrashConsole {
+    fn send(&self, chunk: &[u8]) {
+        let page = PAGING_DIRECT_MAP_OFFSET + self.page_phys;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                chunk.as_ptr(),
+                (page + DATA_OFFSET) as usize as *mut u8,
+                chunk.len(),
+            );
+        }
+
+        // There is at most one buffer in flight, so descriptor 0 is always free.
+        let desc = page + DESC_OFFSET;
+        mmio_write_u64(desc, self.page_phys + DATA_OFFSET);
+        mmio_write_u32(desc + 8, chunk.len() as u32);
+        mmio_write_u16(desc + 12, 0); // flags
+        mmio_write_u16(desc + 14, 0); // next
+
+        let idx = self.next_avail.load(Ordering::Relaxed);
+        let slot = (idx % QUEUE_SIZE) as u64;
+        mmio_write_u16(page + AVAIL_OFFSET + 4 + 2 * slot, 0);
+        fence(Ordering::SeqCst);
+        let idx = idx.wrapping_add(1);
+        mmio_write_u16(page + AVAIL_OFFSET + 2, idx);
+        self.next_avail.store(idx, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+
+        mmio_write_u16(self.notify_addr, TX_QUEUE);
+
+        for _ in 0..USED_WAIT_SPINS {
+            fence(Ordering::SeqCst);
+            if mmio_read_u16(page + USED_OFFSET + 2) == idx {
+                return;
+            }
+            core::hint::spin_loop();
+        }
+
+        // Don't stall the rest of the crash dump on a stuck device.
+        self.broken.store(true, Ordering::Relaxed);
+    }
+}
+
+// Formats into the crash console.
+pub struct Writer;
+
+impl core::fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        write(s.as_bytes());
+        Ok(())
+    }
+}
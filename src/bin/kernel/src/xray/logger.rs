@@ -1,3 +1,134 @@
+use crate::uspace::SysObject;
+use crate::util::{SpinLock, StaticRef};
+use alloc::borrow::ToOwned;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use moto_sys::stats::KLogEntryV1;
+
+// In addition to the serial console, log messages are kept in a ring,
+// so that the userspace can read them (see SysRay::read_log_v1()).
+const LOG_RING_SIZE: usize = 512;
+
+struct LogRing {
+    entries: Vec<KLogEntryV1>, // Preallocated in init_logging().
+    next_seq: u64,
+}
+
+static LOG_RING: SpinLock<LogRing> = SpinLock::new(LogRing {
+    entries: Vec::new(),
+    next_seq: 1,
+});
+
+// Woken when new entries are added to LOG_RING.
+static LOG_WAITER: StaticRef<Arc<SysObject>> = StaticRef::default_const();
+
+// Copies as much of a string as fits, never splitting a UTF-8 char.
+pub(super) struct TruncatingWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> TruncatingWriter<'a> {
+    pub(super) fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a> core::fmt::Write for TruncatingWriter<'a> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            let c_len = c.len_utf8();
+            if self.len + c_len > self.buf.len() {
+                break;
+            }
+            c.encode_utf8(&mut self.buf[self.len..]);
+            self.len += c_len;
+        }
+        Ok(())
+    }
+}
+
+fn write_truncated(buf: &mut [u8], args: core::fmt::Arguments) -> u8 {
+    let mut writer = TruncatingWriter { buf, len: 0 };
+    let _ = core::fmt::write(&mut writer, args);
+    writer.len as u8
+}
+
+fn log_to_ring(level: u8, user: bool, subsystem: &str, args: core::fmt::Arguments) {
+    {
+        let mut ring = LOG_RING.lock(line!());
+        if ring.entries.capacity() == 0 {
+            return; // Not initialized yet.
+        }
+
+        let seq = ring.next_seq;
+        ring.next_seq += 1;
+        let idx = ((seq - 1) as usize) % LOG_RING_SIZE;
+        if ring.entries.len() <= idx {
+            ring.entries.push(KLogEntryV1::default());
+        }
+
+        let entry = &mut ring.entries[idx];
+        entry.seq = seq;
+        entry.timestamp = crate::arch::time::Instant::now().as_u64();
+        entry.level = level;
+        entry.cpu = crate::arch::current_cpu() as u8;
+        entry.user = user as u8;
+        entry.subsystem_len =
+            write_truncated(&mut entry.subsystem_bytes, format_args!("{}", subsystem));
+        entry.msg_len = write_truncated(&mut entry.msg_bytes, args);
+    }
+
+    // Note: log calls can come from anywhere, so use the IRQ-safe wake.
+    if LOG_WAITER.is_set() {
+        SysObject::wake_irq(&LOG_WAITER);
+    }
+}
+
+// Calls @func for entries with seq >= @start_seq, in order, until @func
+// returns false.
+pub fn read<F>(start_seq: u64, mut func: F)
+where
+    F: FnMut(&KLogEntryV1) -> bool,
+{
+    let ring = LOG_RING.lock(line!());
+    let first_seq = ring.next_seq.saturating_sub(ring.entries.len() as u64);
+    for seq in start_seq.max(first_seq)..ring.next_seq {
+        if !func(&ring.entries[((seq - 1) as usize) % LOG_RING_SIZE]) {
+            break;
+        }
+    }
+}
+
+// Calls @func with the number of (up to @max) most recent entries and
+// an iterator over them, oldest first. Does not block, as it is used
+// when crashing: returns false if the ring is locked.
+pub(super) fn try_read_recent<F>(max: usize, func: F) -> bool
+where
+    F: FnOnce(usize, &mut dyn Iterator<Item = &KLogEntryV1>),
+{
+    let ring = match LOG_RING.try_lock(line!()) {
+        Some(ring) => ring,
+        None => return false,
+    };
+
+    let first_seq = ring
+        .next_seq
+        .saturating_sub(ring.entries.len().min(max) as u64);
+    let mut entries =
+        (first_seq..ring.next_seq).map(|seq| &ring.entries[((seq - 1) as usize) % LOG_RING_SIZE]);
+    func((ring.next_seq - first_seq) as usize, &mut entries);
+    true
+}
+
+pub fn log_waiter() -> Arc<SysObject> {
+    LOG_WAITER.clone()
+}
+
 struct Logger {
     lock: crate::util::SpinLock<()>, // To unscramble concurrent log messages.
 }
@@ -36,6 +167,8 @@ impl log::Log for Logger {
                 line,
                 record.args()
             );
+
+            log_to_ring(record.level() as u8, false, target, *record.args());
         }
     }
 
@@ -62,11 +195,17 @@ pub fn log_user(thread: &crate::uspace::process::Thread, msg: &str) {
         thr,
         msg
     );
+
+    log_to_ring(log::Level::Info as u8, true, &thr, format_args!("{}", msg));
 }
 
 // Initializes the logger from crate log.
 // Must be called after the global allocator has been set up.
 pub fn init_logging() {
+    LOG_RING.lock(line!()).entries.reserve_exact(LOG_RING_SIZE);
+    LOG_WAITER.set(alloc::boxed::Box::leak(alloc::boxed::Box::new(
+        SysObject::new(Arc::new("klog".to_owned())),
+    )));
     assert!(log::set_logger(&LOGGER).is_ok());
 
     #[cfg(debug_assertions)]
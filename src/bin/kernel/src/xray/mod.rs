@@ -1,3 +1,7 @@
+pub mod acct;
+pub mod crash;
+pub mod crash_console;
 pub mod logger;
 pub mod stats;
+pub mod tracepoints;
 pub mod tracing;
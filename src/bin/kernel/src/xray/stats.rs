@@ -23,6 +23,9 @@ use moto_sys::stats::*;
 #[derive(Debug)]
 pub struct MemStats {
     pages_used: AtomicU64,
+    peak_pages: AtomicU64,
+    huge_pages: AtomicU64,      // 2M pages mapped; also counted in pages_used.
+    reclaimed_pages: AtomicU64, // Dropped via memory hints (madvise).
     user_stats: bool,
 }
 
@@ -39,6 +42,9 @@ impl MemStats {
     const fn new(user_stats: bool) -> Self {
         Self {
             pages_used: AtomicU64::new(0),
+            peak_pages: AtomicU64::new(0),
+            huge_pages: AtomicU64::new(0),
+            reclaimed_pages: AtomicU64::new(0),
             user_stats,
         }
     }
@@ -67,9 +73,16 @@ impl MemStats {
         }
     }
 
+    // The high watermark of pages used, in pages.
+    pub fn peak_pages(&self) -> u64 {
+        self.peak_pages.load(Ordering::Relaxed)
+    }
+
     #[inline]
     fn add_simple(&self, num_pages: u64) {
-        self.pages_used.fetch_add(num_pages, Ordering::Relaxed);
+        let prev = self.pages_used.fetch_add(num_pages, Ordering::Relaxed);
+        self.peak_pages
+            .fetch_max(prev + num_pages, Ordering::Relaxed);
     }
 
     pub fn sub(&self, num_pages: u64) {
@@ -88,6 +101,26 @@ impl MemStats {
     fn sub_simple(&self, num_pages: u64) {
         self.pages_used.fetch_sub(num_pages, Ordering::Relaxed);
     }
+
+    pub fn huge_pages(&self) -> u64 {
+        self.huge_pages.load(Ordering::Relaxed)
+    }
+
+    pub fn add_huge(&self, num_huge_pages: u64) {
+        self.huge_pages.fetch_add(num_huge_pages, Ordering::Relaxed);
+    }
+
+    pub fn sub_huge(&self, num_huge_pages: u64) {
+        self.huge_pages.fetch_sub(num_huge_pages, Ordering::Relaxed);
+    }
+
+    pub fn reclaimed_pages(&self) -> u64 {
+        self.reclaimed_pages.load(Ordering::Relaxed)
+    }
+
+    pub fn add_reclaimed(&self, num_pages: u64) {
+        self.reclaimed_pages.fetch_add(num_pages, Ordering::Relaxed);
+    }
 }
 
 #[repr(C, align(64))]
@@ -170,6 +203,7 @@ pub struct KProcessStats {
     active_children: AtomicU64,
     children: SpinLock<BTreeMap<ProcessId, Weak<KProcessStats>>>,
     active: AtomicBool,
+    started: u64, // Instant as u64.
 
     mem_stats_user: Arc<MemStats>,
     mem_stats_kernel: Arc<MemStats>,
@@ -230,6 +264,7 @@ impl KProcessStats {
             active_children: AtomicU64::new(0),
             children: SpinLock::new(BTreeMap::new()),
             active: AtomicBool::new(true),
+            started: crate::arch::time::Instant::now().as_u64(),
             mem_stats_user,
             mem_stats_kernel,
             owner,
@@ -303,6 +338,22 @@ impl KProcessStats {
         self.pid
     }
 
+    pub fn started(&self) -> u64 {
+        self.started
+    }
+
+    pub fn peak_pages_user(&self) -> u64 {
+        self.mem_stats_user.peak_pages()
+    }
+
+    pub fn pages_user(&self) -> u64 {
+        self.mem_stats_user.pages_used.load(Ordering::Relaxed)
+    }
+
+    pub fn pages_kernel(&self) -> u64 {
+        self.mem_stats_kernel.pages_used.load(Ordering::Relaxed)
+    }
+
     pub fn on_thread_added(&self) {
         self.active_threads.fetch_add(1, Ordering::Relaxed);
         self.total_threads.fetch_add(1, Ordering::Relaxed);
@@ -324,6 +375,8 @@ impl KProcessStats {
         dest.active_children = self.active_children.load(Ordering::Relaxed);
         dest.pages_user = self.mem_stats_user.pages_used.load(Ordering::Relaxed);
         dest.pages_kernel = self.mem_stats_kernel.pages_used.load(Ordering::Relaxed);
+        dest.pages_user_huge = self.mem_stats_user.huge_pages();
+        dest.bytes_reclaimed = self.mem_stats_user.reclaimed_pages() << PAGE_SIZE_SMALL_LOG2;
         dest.cpu_usage = self.cpu_usage(now);
 
         dest.system_process = 0;
@@ -333,20 +386,16 @@ impl KProcessStats {
             }
         };
 
-        let debug_name = if self.debug_name.as_bytes().len() > 32 {
-            &self.debug_name.as_bytes()[0..32]
-        } else {
-            self.debug_name.as_bytes()
-        };
-        unsafe {
-            core::intrinsics::copy_nonoverlapping(
-                debug_name.as_ptr(),
-                dest.debug_name_bytes.as_mut_ptr(),
-                debug_name.len(),
-            );
-        }
+        // The debug name is the command line the process was started with:
+        // see moto-runtime's process.rs.
+        let debug_name = truncate_str(self.debug_name.as_str(), dest.debug_name_bytes.len());
+        dest.debug_name_bytes[0..debug_name.len()].copy_from_slice(debug_name.as_bytes());
         dest.debug_name_len = debug_name.len() as u8;
 
+        let cmdline = truncate_str(self.debug_name.as_str(), dest.cmdline_bytes.len());
+        dest.cmdline_bytes[0..cmdline.len()].copy_from_slice(cmdline.as_bytes());
+        dest.cmdline_len = cmdline.len() as u8;
+
         dest.active = if self.active.load(Ordering::Relaxed) {
             1
         } else {
@@ -395,7 +444,8 @@ impl KProcessStats {
         }
     }
 
-    fn cpu_usage(&self, now: u64) -> u64 {
+    // Total CPU usage (kernel + userspace) as TSC.
+    pub fn cpu_usage(&self, now: u64) -> u64 {
         let mut res = 0;
         for entry in &self.per_cpu_stats.data {
             res += entry.usage_kernel(now) + entry.usage_uspace(now);
@@ -404,6 +454,16 @@ impl KProcessStats {
         res
     }
 
+    // Kernel CPU usage (syscalls, page faults, etc.) as TSC.
+    pub fn cpu_usage_kernel(&self, now: u64) -> u64 {
+        let mut res = 0;
+        for entry in &self.per_cpu_stats.data {
+            res += entry.usage_kernel(now);
+        }
+
+        res
+    }
+
     pub fn get_percpu_stats_entry(&self, cpu: uCpus) -> &PerCpuStatsEntry {
         &self.per_cpu_stats.data[cpu as usize]
     }
@@ -539,6 +599,18 @@ pub fn init() {
     );
 }
 
+// Truncates @s to at most @max_len bytes, at a char boundary.
+pub fn truncate_str(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut len = max_len;
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    &s[0..len]
+}
+
 fn system_stats() -> Arc<KProcessStats> {
     SYSTEM_STATS.clone()
 }
@@ -0,0 +1,113 @@
+// Static tracepoints: unlike xray::tracing, which is a debugging aid that
+// dumps to the serial console, tracepoints are always compiled in, can be
+// enabled per event, and are drained from the userspace.
+//
+// Each CPU has its own ring, so recording an event only contends with
+// a concurrent drain of the same CPU. When a ring is full, the oldest
+// events are overwritten.
+
+use core::sync::atomic::*;
+
+use crate::arch::time::Instant;
+use crate::config::MAX_CPUS;
+use crate::util::SpinLock;
+use alloc::vec::Vec;
+use moto_sys::stats::{TraceEventV1, TRACE_NUM_EVENTS};
+use moto_sys::ErrorCode;
+
+const RING_SIZE: usize = 1024; // Per CPU.
+
+struct Ring {
+    events: Vec<TraceEventV1>, // Allocated when tracing is first enabled.
+    next_seq: u64,             // The seq of the next event to record.
+    read_seq: u64,             // The seq of the next event to drain.
+}
+
+const EMPTY_RING: SpinLock<Ring> = SpinLock::new(Ring {
+    events: Vec::new(),
+    next_seq: 0,
+    read_seq: 0,
+});
+
+static RINGS: [SpinLock<Ring>; MAX_CPUS as usize] = [EMPTY_RING; MAX_CPUS as usize];
+
+static ENABLED_MASK: AtomicU64 = AtomicU64::new(0);
+
+#[inline(always)]
+pub fn enabled(event: u32) -> bool {
+    ENABLED_MASK.load(Ordering::Relaxed) & (1 << event) != 0
+}
+
+#[inline(always)]
+pub fn tracepoint(event: u32, pid: u64, tid: u64, arg: u64) {
+    if enabled(event) {
+        record(event, pid, tid, arg);
+    }
+}
+
+#[inline(never)]
+fn record(event: u32, pid: u64, tid: u64, arg: u64) {
+    let cpu = crate::arch::current_cpu();
+    let mut ring = RINGS[cpu as usize].lock(line!());
+    if ring.events.capacity() == 0 {
+        return;
+    }
+
+    let seq = ring.next_seq;
+    ring.next_seq += 1;
+    if ring.next_seq - ring.read_seq > RING_SIZE as u64 {
+        ring.read_seq = ring.next_seq - RING_SIZE as u64; // Drop the oldest event.
+    }
+
+    let idx = (seq as usize) % RING_SIZE;
+    let trace_event = TraceEventV1 {
+        seq,
+        timestamp: Instant::now().as_u64(),
+        event,
+        cpu: cpu as u32,
+        pid,
+        tid,
+        arg,
+    };
+    if ring.events.len() == idx {
+        ring.events.push(trace_event);
+    } else {
+        ring.events[idx] = trace_event;
+    }
+}
+
+// Returns the previous mask.
+pub fn set_enabled_mask(mask: u64) -> Result<u64, ErrorCode> {
+    if mask >> TRACE_NUM_EVENTS != 0 {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    if mask != 0 {
+        for cpu in 0..crate::arch::num_cpus() {
+            let mut ring = RINGS[cpu as usize].lock(line!());
+            if ring.events.capacity() == 0 {
+                ring.events.reserve_exact(RING_SIZE);
+            }
+        }
+    }
+
+    Ok(ENABLED_MASK.swap(mask, Ordering::Relaxed))
+}
+
+// Moves the oldest recorded events of @cpu into @buf; returns
+// the number of events moved.
+pub fn drain(cpu: u32, buf: &mut [TraceEventV1]) -> Result<usize, ErrorCode> {
+    if cpu >= crate::arch::num_cpus() as u32 {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let mut ring = RINGS[cpu as usize].lock(line!());
+    let mut count = 0;
+    while count < buf.len() && ring.read_seq < ring.next_seq {
+        buf[count] = ring.events[(ring.read_seq as usize) % RING_SIZE];
+        ring.read_seq += 1;
+        count += 1;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,146 @@
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Result;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::Cmd;
+
+pub fn run(host: &str, cmd: &Cmd) -> ! {
+    match do_run(host, cmd) {
+        Ok(_) => std::process::exit(0),
+        Err(err) => {
+            eprintln!("{} error: {}", crate::binary_name(), err);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn do_run(host: &str, cmd: &Cmd) -> Result<()> {
+    use std::net::ToSocketAddrs;
+    let addrs = host.to_socket_addrs()?;
+
+    for addr in addrs {
+        if let Ok(stream) = handshake(addr) {
+            return match cmd {
+                Cmd::Push { local, remote } => push(stream, local, remote),
+                Cmd::Pull { remote, local } => pull(stream, remote, local),
+            };
+        }
+    }
+
+    Err(std::io::Error::other(format!("{host}: unreachable")))
+}
+
+fn handshake(addr: SocketAddr) -> Result<TcpStream> {
+    let mut buf = [0_u8; 64];
+    let mut tcp_stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))?;
+    tcp_stream.set_nodelay(true).unwrap();
+
+    tcp_stream.write_all(crate::MAGIC_BYTES_CLIENT)?;
+    tcp_stream.read_exact(&mut buf[0..crate::MAGIC_BYTES_SERVER.len()])?;
+
+    if crate::MAGIC_BYTES_SERVER != &buf[0..crate::MAGIC_BYTES_SERVER.len()] {
+        eprintln!("{} error: bad remote reply.", crate::binary_name());
+        std::process::exit(1);
+    }
+
+    Ok(tcp_stream)
+}
+
+/// Prints transfer progress at most every 100ms, and once when done.
+struct Progress {
+    name: String,
+    size: u64,
+    start: Instant,
+    first: u64, // Resumed from.
+    last_print: Option<Instant>,
+}
+
+impl Progress {
+    fn new(name: &str, size: u64) -> Self {
+        Self {
+            name: name.to_owned(),
+            size,
+            start: Instant::now(),
+            first: u64::MAX,
+            last_print: None,
+        }
+    }
+
+    fn update(&mut self, done: u64) {
+        if self.first == u64::MAX {
+            self.first = done;
+        }
+        let now = Instant::now();
+        if done < self.size
+            && self
+                .last_print
+                .is_some_and(|last| now.duration_since(last) < Duration::from_millis(100))
+        {
+            return;
+        }
+        self.last_print = Some(now);
+
+        let percent = (done * 100).checked_div(self.size).unwrap_or(100);
+        let secs = self.start.elapsed().as_secs_f64().max(0.001);
+        let rate = ((done - self.first) as f64 / secs / (1024.0 * 1024.0)) as u64;
+        print!(
+            "\r{}: {percent:>3}% {done}/{} bytes {rate} MiB/s",
+            self.name, self.size
+        );
+        if done == self.size {
+            if self.first > 0 {
+                print!(" (resumed at {})", self.first);
+            }
+            println!();
+        }
+        let _ = std::io::stdout().flush();
+    }
+}
+
+fn push(mut stream: TcpStream, local: &str, remote: &str) -> Result<()> {
+    let mut file = std::fs::File::open(local)?;
+    let size = file.metadata()?.len();
+
+    // Pushing into a directory keeps the local file name.
+    let remote = match std::path::Path::new(local).file_name() {
+        Some(name) if remote.ends_with('/') => format!("{remote}{}", name.to_string_lossy()),
+        _ => remote.to_owned(),
+    };
+
+    crate::write_u64(&mut stream, crate::CMD_PUSH)?;
+    crate::write_str(&mut stream, &remote)?;
+    crate::write_u64(&mut stream, size)?;
+    crate::read_status(&mut stream)?;
+
+    let mut progress = Progress::new(local, size);
+    crate::send_file(&mut stream, &mut file, size, |done| progress.update(done))?;
+
+    // The server verifies the checksum.
+    crate::read_status(&mut stream)
+}
+
+fn pull(mut stream: TcpStream, remote: &str, local: &str) -> Result<()> {
+    crate::write_u64(&mut stream, crate::CMD_PULL)?;
+    crate::write_str(&mut stream, remote)?;
+    crate::read_status(&mut stream)?;
+    let size = crate::read_u64(&mut stream)?;
+
+    // Pulling into a directory keeps the remote file name.
+    let mut dest = std::path::PathBuf::from(local);
+    if dest.is_dir() {
+        match std::path::Path::new(remote).file_name() {
+            Some(name) => dest.push(name),
+            None => return Err(ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    let mut receiver = crate::Receiver::new(&dest)?;
+    receiver.send_offset(&mut stream)?;
+
+    let mut progress = Progress::new(remote, size);
+    receiver.receive(&mut stream, size, |done| progress.update(done))
+}
@@ -0,0 +1,278 @@
+// Motor copy: push/pull files between a dev host and a Motor OS VM.
+//
+// Files are transferred over plain TCP, with CRC64 verification.
+// Interrupted transfers leave a "*.mcp-part" file next to the destination,
+// and a later transfer of the same file resumes from where it stopped.
+
+use std::fs::File;
+use std::io::{Read, Result, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+
+mod client;
+mod server;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(short, long, default_value_t = false)]
+    server: bool,
+
+    #[arg(short, long, default_value_t = 5542, requires = "server")]
+    port: u16,
+
+    #[arg(short, long, default_value = "/", requires = "server")]
+    dir: String, // The directory remote paths are relative to.
+
+    #[arg(short, long, conflicts_with = "server")]
+    client: Option<String>, // The host to connect to.
+
+    #[command(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Copy a local file to the server.
+    Push { local: String, remote: String },
+    /// Copy a file from the server.
+    Pull { remote: String, local: String },
+}
+
+static MAGIC_BYTES_CLIENT: &[u8] = b"mcp_magic_client_v1";
+static MAGIC_BYTES_SERVER: &[u8] = b"mcp_magic_server_v1";
+const CMD_PUSH: u64 = 1;
+const CMD_PULL: u64 = 2;
+
+const MAX_PATH_LEN: usize = 4096;
+const PART_SUFFIX: &str = ".mcp-part";
+
+static CRC64: crc::Crc<u64> = crc::Crc::<u64>::new(&crc::CRC_64_XZ);
+
+fn binary_name() -> String {
+    std::path::Path::new(std::env::args().next().unwrap().as_str())
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+// Intercept Ctrl+C ourselves if the OS does not do it for us.
+fn input_listener(prog: String) {
+    loop {
+        let mut input = [0_u8; 16];
+        let sz = match std::io::stdin().read(&mut input) {
+            Ok(0) | Err(_) => return, // No terminal.
+            Ok(sz) => sz,
+        };
+        for b in &input[0..sz] {
+            if *b == 3 {
+                println!("\n{prog}: caught ^C: exiting.");
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+fn main() {
+    std::thread::spawn(move || input_listener(binary_name()));
+
+    let args = Args::parse();
+
+    if args.server {
+        server::run(args.port, &args.dir);
+    } else if let (Some(host), Some(cmd)) = (&args.client, &args.cmd) {
+        client::run(host, cmd);
+    } else if args.client.is_some() {
+        eprintln!("error: --client requires a push or pull command");
+        std::process::exit(1);
+    } else {
+        eprintln!("error: either --server or --client argument is required");
+        std::process::exit(1);
+    }
+}
+
+fn write_u64(stream: &mut TcpStream, val: u64) -> Result<()> {
+    stream.write_all(&val.to_le_bytes())
+}
+
+fn read_u64(stream: &mut TcpStream) -> Result<u64> {
+    let mut buf = [0_u8; 8];
+    stream.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn write_str(stream: &mut TcpStream, s: &str) -> Result<()> {
+    write_u64(stream, s.len() as u64)?;
+    stream.write_all(s.as_bytes())
+}
+
+fn read_str(stream: &mut TcpStream) -> Result<String> {
+    let len = read_u64(stream)? as usize;
+    if len > MAX_PATH_LEN {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+    let mut buf = vec![0_u8; len];
+    stream.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| std::io::ErrorKind::InvalidData.into())
+}
+
+// Errors are sent to the peer as a non-empty message; success as an empty one.
+fn write_status(stream: &mut TcpStream, err: Option<&std::io::Error>) -> Result<()> {
+    match err {
+        None => write_str(stream, ""),
+        Some(err) => write_str(stream, format!("{err}").as_str()),
+    }
+}
+
+fn read_status(stream: &mut TcpStream) -> Result<()> {
+    let msg = read_str(stream)?;
+    if msg.is_empty() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("remote: {msg}")))
+    }
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut part = dest.as_os_str().to_owned();
+    part.push(PART_SUFFIX);
+    PathBuf::from(part)
+}
+
+// Hashes the first `len` bytes of the file.
+fn hash_prefix(file: &mut File, len: u64) -> Result<crc::Digest<'static, u64>> {
+    let mut digest = CRC64.digest();
+    let mut buf = vec![0_u8; 64 * 1024];
+    let mut remaining = len;
+    file.seek(SeekFrom::Start(0))?;
+    while remaining > 0 {
+        let chunk = remaining.min(buf.len() as u64) as usize;
+        file.read_exact(&mut buf[0..chunk])?;
+        digest.update(&buf[0..chunk]);
+        remaining -= chunk as u64;
+    }
+    Ok(digest)
+}
+
+/// The receiving side of a transfer: a partially received file.
+struct Receiver {
+    dest: PathBuf,
+    part: File,
+    offset: u64, // How much has been received before.
+    digest: crc::Digest<'static, u64>,
+}
+
+impl Receiver {
+    fn new(dest: &Path) -> Result<Self> {
+        let part_path = part_path(dest);
+        let mut part = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&part_path)?;
+        let offset = part.metadata()?.len();
+        let digest = hash_prefix(&mut part, offset)?;
+
+        Ok(Self {
+            dest: dest.to_owned(),
+            part,
+            offset,
+            digest,
+        })
+    }
+
+    // Tells the sender what we have.
+    fn send_offset(&mut self, stream: &mut TcpStream) -> Result<()> {
+        write_u64(stream, self.offset)?;
+        write_u64(stream, self.digest.clone().finalize())
+    }
+
+    // Receives the rest of the file, verifies it, and moves it into place.
+    fn receive(
+        mut self,
+        stream: &mut TcpStream,
+        size: u64,
+        mut progress: impl FnMut(u64),
+    ) -> Result<()> {
+        let start = read_u64(stream)?;
+        if start != self.offset {
+            // The sender could not resume: the partial file does not match.
+            self.part.set_len(0)?;
+            self.digest = CRC64.digest();
+        }
+        self.part.seek(SeekFrom::Start(start))?;
+
+        let mut buf = vec![0_u8; 64 * 1024];
+        let mut received = start;
+        progress(received);
+        while received < size {
+            let chunk = (size - received).min(buf.len() as u64) as usize;
+            let sz = stream.read(&mut buf[0..chunk])?;
+            if sz == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            self.part.write_all(&buf[0..sz])?;
+            self.digest.update(&buf[0..sz]);
+            received += sz as u64;
+            progress(received);
+        }
+        self.part.flush()?;
+
+        let crc = read_u64(stream)?;
+        let part_path = part_path(&self.dest);
+        if crc != self.digest.finalize() {
+            // Don't resume from corrupted data.
+            let _ = std::fs::remove_file(&part_path);
+            return Err(std::io::Error::other("checksum mismatch"));
+        }
+
+        drop(self.part);
+        std::fs::rename(&part_path, &self.dest)
+    }
+}
+
+// The sending side of a transfer: skips what the receiver already has,
+// if its checksum matches, and sends the rest followed by the checksum
+// of the whole file.
+fn send_file(
+    stream: &mut TcpStream,
+    file: &mut File,
+    size: u64,
+    mut progress: impl FnMut(u64),
+) -> Result<()> {
+    let offset = read_u64(stream)?;
+    let crc = read_u64(stream)?;
+
+    let (start, mut digest) = if offset > 0 && offset <= size {
+        let digest = hash_prefix(file, offset)?;
+        if digest.clone().finalize() == crc {
+            (offset, digest)
+        } else {
+            (0, CRC64.digest())
+        }
+    } else {
+        (0, CRC64.digest())
+    };
+    write_u64(stream, start)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut buf = vec![0_u8; 64 * 1024];
+    let mut sent = start;
+    progress(sent);
+    while sent < size {
+        let chunk = (size - sent).min(buf.len() as u64) as usize;
+        // The file could have been truncated while being sent:
+        // read_exact() will fail then.
+        file.read_exact(&mut buf[0..chunk])?;
+        stream.write_all(&buf[0..chunk])?;
+        digest.update(&buf[0..chunk]);
+        sent += chunk as u64;
+        progress(sent);
+    }
+
+    write_u64(stream, digest.finalize())
+}
@@ -0,0 +1,120 @@
+use std::io::Read;
+use std::io::Result;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::path::{Component, Path, PathBuf};
+
+pub fn run(port: u16, dir: &str) -> ! {
+    match do_run(port, dir) {
+        Ok(_) => std::process::exit(0),
+        Err(err) => {
+            eprintln!("{} error: {:?}", crate::binary_name(), err);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn do_run(port: u16, dir: &str) -> Result<()> {
+    let root = std::fs::canonicalize(dir)?;
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
+    let listener = std::net::TcpListener::bind(addr)?;
+
+    println!(
+        "{} server: serving {} on 0.0.0.0:{}\n",
+        crate::binary_name(),
+        root.display(),
+        port
+    );
+
+    for stream in listener.incoming().flatten() {
+        let root = root.clone();
+        let _ = std::thread::spawn(move || {
+            let peer = stream.peer_addr();
+            if let Err(err) = handle_connection(stream, &root) {
+                eprintln!("{}: {:?}: {:?}", crate::binary_name(), peer, err);
+            }
+        });
+    }
+
+    unreachable!()
+}
+
+// Remote paths are relative to the root, even if they start with '/'.
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+    let mut resolved = root.to_owned();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::RootDir | Component::CurDir => {}
+            _ => return Err(std::io::ErrorKind::InvalidInput.into()),
+        }
+    }
+    if resolved == root {
+        return Err(std::io::ErrorKind::InvalidInput.into());
+    }
+    Ok(resolved)
+}
+
+fn handle_connection(mut tcp_stream: TcpStream, root: &Path) -> Result<()> {
+    tcp_stream.set_nodelay(true).unwrap();
+    let mut buf = [0_u8; 64];
+
+    // "Authenticate" the client.
+    tcp_stream.read_exact(&mut buf[0..crate::MAGIC_BYTES_CLIENT.len()])?;
+
+    if crate::MAGIC_BYTES_CLIENT != &buf[0..crate::MAGIC_BYTES_CLIENT.len()] {
+        return Ok(()); // Doesn't matter if we return Ok or Err.
+    }
+    tcp_stream.write_all(crate::MAGIC_BYTES_SERVER)?;
+
+    let cmd = crate::read_u64(&mut tcp_stream)?;
+    let path = crate::read_str(&mut tcp_stream)?;
+    match cmd {
+        crate::CMD_PUSH => handle_push(tcp_stream, root, &path),
+        crate::CMD_PULL => handle_pull(tcp_stream, root, &path),
+        _ => {
+            eprintln!("unrecognized command: {}", cmd);
+            Ok(())
+        }
+    }
+}
+
+fn handle_push(mut stream: TcpStream, root: &Path, path: &str) -> Result<()> {
+    let size = crate::read_u64(&mut stream)?;
+
+    let receiver = resolve(root, path).and_then(|dest| crate::Receiver::new(&dest));
+    crate::write_status(&mut stream, receiver.as_ref().err())?;
+    let mut receiver = receiver?;
+    receiver.send_offset(&mut stream)?;
+
+    let result = receiver.receive(&mut stream, size, |_| {});
+    crate::write_status(&mut stream, result.as_ref().err())?;
+    if result.is_ok() {
+        println!("{}: received {path} ({size} bytes)", crate::binary_name());
+    }
+    result
+}
+
+fn handle_pull(mut stream: TcpStream, root: &Path, path: &str) -> Result<()> {
+    let file = resolve(root, path).and_then(|src| {
+        let file = std::fs::File::open(src)?;
+        if file.metadata()?.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "is a directory",
+            ));
+        }
+        Ok(file)
+    });
+    crate::write_status(&mut stream, file.as_ref().err())?;
+    let mut file = file?;
+
+    let size = file.metadata()?.len();
+    crate::write_u64(&mut stream, size)?;
+    crate::send_file(&mut stream, &mut file, size, |_| {})?;
+    println!("{}: sent {path} ({size} bytes)", crate::binary_name());
+    Ok(())
+}
@@ -141,7 +141,22 @@ fn get_thread_trace(
     backtrace
 }
 
-fn print_stack_trace(dbg_handle: moto_sys::SysHandle, tid: u64) {
+// Converts a runtime address into the link-time one (undoes ASLR),
+// so that printed addresses can be symbolized with the binary.
+fn unslide(modules: &[moto_sys::stats::ModuleInfoV1], addr: u64) -> u64 {
+    for module in modules {
+        if module.contains(addr) {
+            return module.unslide(addr);
+        }
+    }
+    addr
+}
+
+fn print_stack_trace(
+    dbg_handle: moto_sys::SysHandle,
+    modules: &[moto_sys::stats::ModuleInfoV1],
+    tid: u64,
+) {
     let thread_data = SysRay::dbg_get_thread_data_v1(dbg_handle, tid).unwrap();
     println!("print_stack_trace {:?}", thread_data);
 
@@ -155,7 +170,21 @@ fn print_stack_trace(dbg_handle: moto_sys::SysHandle, tid: u64) {
         thread_data.tid, thread_data.status, thread_data.syscall_num, thread_data.syscall_op
     )
     .ok();
-    write!(&mut writer, " \\\n  0x{:x}", thread_data.ip).ok();
+    match thread_data.fault {
+        moto_sys::stats::ThreadDataV1::FAULT_STACK_OVERFLOW => {
+            write!(&mut writer, " overflowed its stack at").ok();
+        }
+        moto_sys::stats::ThreadDataV1::FAULT_PAGE => {
+            write!(&mut writer, " page fault at").ok();
+        }
+        _ => {}
+    }
+    write!(
+        &mut writer,
+        " \\\n  0x{:x}",
+        unslide(modules, thread_data.ip)
+    )
+    .ok();
     for addr in backtrace {
         if addr == 0 {
             break;
@@ -165,7 +194,7 @@ fn print_stack_trace(dbg_handle: moto_sys::SysHandle, tid: u64) {
             break;
         }
 
-        write!(&mut writer, " \\\n  0x{:x}", addr).ok();
+        write!(&mut writer, " \\\n  0x{:x}", unslide(modules, addr)).ok();
     }
 
     let _ = write!(&mut writer, "\n\n");
@@ -194,6 +223,16 @@ fn cmd_print_stacks(pid: u64) -> Result<(), moto_sys::ErrorCode> {
     // Sleep a bit to let all running threads to get paused.
     std::thread::sleep(std::time::Duration::from_millis(50));
 
+    let mut modules = [moto_sys::stats::ModuleInfoV1::default(); 8];
+    let num_modules = SysRay::dbg_list_modules_v1(dbg_handle, &mut modules).unwrap();
+    let modules = &modules[0..num_modules];
+    for module in modules {
+        println!(
+            "Module: [0x{:x}..0x{:x}) slide: 0x{:x}",
+            module.start, module.end, module.slide
+        );
+    }
+
     let mut all_tids = VecDeque::new();
 
     let mut tids = [0_u64; 64];
@@ -206,7 +245,7 @@ fn cmd_print_stacks(pid: u64) -> Result<(), moto_sys::ErrorCode> {
 
         for idx in 0..sz {
             all_tids.push_back(tids[idx]);
-            print_stack_trace(dbg_handle, tids[idx]);
+            print_stack_trace(dbg_handle, modules, tids[idx]);
         }
         start_tid = tids[sz - 1] + 1;
     }
@@ -0,0 +1,190 @@
+//! Public key user authentication (RFC 4252) against authorized_keys.
+
+use std::io::Result;
+
+use crate::transport::Transport;
+use crate::wire::*;
+
+const KEY_ALG: &str = "ssh-ed25519";
+const MAX_AUTH_ATTEMPTS: usize = 20;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut res = Vec::with_capacity(s.len() * 3 / 4);
+    let mut acc = 0_u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        acc = (acc << 6) | BASE64.iter().position(|b| *b == c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((acc >> bits) as u8);
+        }
+    }
+    Some(res)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let val = chunk
+            .iter()
+            .enumerate()
+            .fold(0_u32, |acc, (idx, b)| acc | (*b as u32) << (16 - 8 * idx));
+        for idx in 0..=chunk.len() {
+            res.push(BASE64[(val >> (18 - 6 * idx)) as usize & 63] as char);
+        }
+    }
+    res // Unpadded, as in OpenSSH fingerprints.
+}
+
+/// The OpenSSH-style fingerprint of a public key blob.
+pub fn fingerprint(key_blob: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, key_blob);
+    format!("SHA256:{}", base64_encode(digest.as_ref()))
+}
+
+// Returns the raw ed25519 public key from an ssh-ed25519 key blob.
+fn parse_key_blob(blob: &[u8]) -> Option<&[u8]> {
+    let mut decoder = Decoder::new(blob);
+    if decoder.str().ok()? != KEY_ALG {
+        return None;
+    }
+    let key = decoder.string().ok()?;
+    if key.len() == 32 {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+/// Reads ssh-ed25519 keys (as blobs) from an OpenSSH authorized_keys file.
+/// Other key types, and options, are ignored.
+pub fn read_authorized_keys(path: &str) -> Result<Vec<Vec<u8>>> {
+    let mut keys = vec![];
+    for line in std::fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace().skip_while(|word| *word != KEY_ALG);
+        if let Some(blob) = words.nth(1).and_then(base64_decode) {
+            if parse_key_blob(&blob).is_some() {
+                keys.push(blob);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+fn verify_signature(
+    session_id: &[u8],
+    user: &str,
+    service: &str,
+    key_blob: &[u8],
+    signature: &[u8],
+) -> bool {
+    let Some(key) = parse_key_blob(key_blob) else {
+        return false;
+    };
+    let mut decoder = Decoder::new(signature);
+    if decoder.str().ok() != Some(KEY_ALG) {
+        return false;
+    }
+    let Ok(signature) = decoder.string() else {
+        return false;
+    };
+
+    let signed = Encoder::default()
+        .string(session_id)
+        .u8(MSG_USERAUTH_REQUEST)
+        .str(user)
+        .str(service)
+        .str("publickey")
+        .bool(true)
+        .str(KEY_ALG)
+        .string(key_blob)
+        .finish();
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+        .verify(&signed, signature)
+        .is_ok()
+}
+
+/// Runs the "ssh-userauth" service until the client authenticates with
+/// one of the keys. Returns the user name and the key fingerprint.
+pub fn authenticate(transport: &mut Transport, keys: &[Vec<u8>]) -> Result<(String, String)> {
+    let request = transport.reader.read_packet()?;
+    let mut decoder = Decoder::new(&request);
+    if decoder.u8()? != MSG_SERVICE_REQUEST || decoder.str()? != "ssh-userauth" {
+        transport
+            .writer
+            .disconnect(DISCONNECT_SERVICE_NOT_AVAILABLE, "expected ssh-userauth");
+        return Err(bad_data("expected ssh-userauth"));
+    }
+    transport.writer.write_packet(
+        &Encoder::new(MSG_SERVICE_ACCEPT)
+            .str("ssh-userauth")
+            .finish(),
+    )?;
+
+    let failure = Encoder::new(MSG_USERAUTH_FAILURE)
+        .name_list(&["publickey"])
+        .bool(false)
+        .finish();
+
+    for _ in 0..MAX_AUTH_ATTEMPTS {
+        let request = transport.reader.read_packet()?;
+        let mut decoder = Decoder::new(&request);
+        match decoder.u8()? {
+            MSG_USERAUTH_REQUEST => {}
+            MSG_IGNORE | MSG_DEBUG => continue,
+            _ => {
+                transport
+                    .writer
+                    .disconnect(DISCONNECT_PROTOCOL_ERROR, "expected USERAUTH_REQUEST");
+                return Err(bad_data("expected USERAUTH_REQUEST"));
+            }
+        }
+        let user = decoder.str()?;
+        let service = decoder.str()?;
+        let method = decoder.str()?;
+        if method != "publickey" || service != "ssh-connection" {
+            transport.writer.write_packet(&failure)?;
+            continue;
+        }
+
+        let has_signature = decoder.bool()?;
+        let alg = decoder.str()?;
+        let key_blob = decoder.string()?;
+        if alg != KEY_ALG || !keys.iter().any(|key| key == key_blob) {
+            transport.writer.write_packet(&failure)?;
+            continue;
+        }
+
+        if !has_signature {
+            // The client asks whether the key is acceptable.
+            transport.writer.write_packet(
+                &Encoder::new(MSG_USERAUTH_PK_OK)
+                    .str(alg)
+                    .string(key_blob)
+                    .finish(),
+            )?;
+            continue;
+        }
+
+        let signature = decoder.string()?;
+        if verify_signature(&transport.session_id, user, service, key_blob, signature) {
+            transport.writer.write_packet(&[MSG_USERAUTH_SUCCESS])?;
+            return Ok((user.to_owned(), fingerprint(key_blob)));
+        }
+        transport.writer.write_packet(&failure)?;
+    }
+
+    transport.writer.disconnect(
+        DISCONNECT_NO_MORE_AUTH_METHODS_AVAILABLE,
+        "too many authentication failures",
+    );
+    Err(bad_data("too many authentication failures"))
+}
@@ -0,0 +1,143 @@
+/// A minimal SSH server: ssh-ed25519 public key authentication against
+/// an authorized_keys file, and a single session channel per connection
+/// running rush (or a command) behind an emulated terminal.
+use clap::Parser;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ring::signature::{Ed25519KeyPair, KeyPair};
+
+mod auth;
+mod pty;
+mod session;
+mod transport;
+mod wire;
+
+// Unauthenticated clients are disconnected after this.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Parser)]
+struct Args {
+    #[arg(short, long, default_value = "0.0.0.0:22")]
+    addr: std::net::SocketAddr,
+
+    // Generated on first use.
+    #[arg(long, default_value = "/sys/cfg/ssh/host_key")]
+    host_key: String,
+
+    // OpenSSH format; only ssh-ed25519 keys are supported.
+    #[arg(long, default_value = "/sys/cfg/ssh/authorized_keys")]
+    authorized_keys: String,
+
+    #[arg(long, default_value = "/bin/rush -t /sys/cfg/rush.cfg")]
+    shell: String,
+}
+
+// Intercept Ctrl+C ourselves if the OS does not do it for us.
+fn input_listener() {
+    use std::io::Read;
+
+    loop {
+        let mut input = [0_u8; 16];
+        let sz = std::io::stdin().read(&mut input).unwrap_or(0);
+        if sz == 0 {
+            return; // No terminal, e.g. when run as a service.
+        }
+        for b in &input[0..sz] {
+            if *b == 3 {
+                println!("\ncaught ^C: exiting.");
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+fn load_host_key(path: &str) -> Result<Ed25519KeyPair, Box<dyn std::error::Error>> {
+    let pkcs8 = match std::fs::read(path) {
+        Ok(pkcs8) => pkcs8,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let rng = ring::rand::SystemRandom::new();
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng)
+                .map_err(|_| "host key generation failed")?
+                .as_ref()
+                .to_vec();
+            if let Some(dir) = std::path::Path::new(path).parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, &pkcs8)?;
+            println!("Generated a new host key in {path}.");
+            pkcs8
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|_| "bad host key")?)
+}
+
+fn handle_connection(stream: TcpStream, host_key: &Ed25519KeyPair, args: &Args) {
+    let peer = match stream.peer_addr() {
+        Ok(peer) => peer.to_string(),
+        Err(_) => "?".to_owned(),
+    };
+
+    // The file is read for every connection, so that changes take effect
+    // without restarting the server.
+    let keys = match auth::read_authorized_keys(&args.authorized_keys) {
+        Ok(keys) => keys,
+        Err(err) => {
+            println!("{}: {:?}", args.authorized_keys, err.kind());
+            vec![]
+        }
+    };
+
+    let _ = stream.set_nodelay(true);
+    let _ = stream.set_read_timeout(Some(LOGIN_TIMEOUT));
+    let mut transport = match transport::handshake(stream, host_key) {
+        Ok(transport) => transport,
+        Err(err) => {
+            println!("{peer}: handshake failed: {err}");
+            return;
+        }
+    };
+    let (user, fingerprint) = match auth::authenticate(&mut transport, &keys) {
+        Ok(res) => res,
+        Err(err) => {
+            println!("{peer}: authentication failed: {err}");
+            return;
+        }
+    };
+    println!("{peer}: accepted publickey for {user}: {fingerprint}");
+    let _ = transport.reader.set_read_timeout(None);
+
+    match session::run(transport, host_key, &args.shell) {
+        Ok(()) => println!("{peer}: disconnected"),
+        Err(err) => println!("{peer}: disconnected: {err}"),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::thread::spawn(input_listener);
+
+    let args = Arc::new(Args::parse());
+    let host_key = Arc::new(load_host_key(&args.host_key)?);
+    let host_key_blob = wire::Encoder::default()
+        .str("ssh-ed25519")
+        .string(host_key.public_key().as_ref())
+        .finish();
+
+    let tcp_listener = TcpListener::bind(args.addr)?;
+    println!(
+        "Serving SSH on {:?}; host key {}. Press Ctrl+C to exit.",
+        args.addr,
+        auth::fingerprint(&host_key_blob)
+    );
+
+    for stream in tcp_listener.incoming().flatten() {
+        let host_key = host_key.clone();
+        let args = args.clone();
+        std::thread::spawn(move || handle_connection(stream, &host_key, &args));
+    }
+
+    Ok(())
+}
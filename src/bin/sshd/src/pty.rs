@@ -0,0 +1,76 @@
+//! Motor OS has no pseudo-terminals: programs read raw bytes from stdin
+//! and write to stdout, as they do on the serial console (see sys-tty).
+//! `Pty` emulates the parts of a terminal line discipline that SSH clients,
+//! which put the local terminal into raw mode, rely on.
+
+pub struct Pty {
+    term: String,
+    cols: u32,
+    rows: u32,
+}
+
+impl Pty {
+    pub fn new(term: &str, cols: u32, rows: u32) -> Self {
+        Self {
+            term: term.to_owned(),
+            cols,
+            rows,
+        }
+    }
+
+    /// Programs can't be notified of the new size (there are no signals),
+    /// but programs started later will see it.
+    pub fn resize(&mut self, cols: u32, rows: u32) {
+        self.cols = cols;
+        self.rows = rows;
+    }
+
+    /// The environment of programs attached to the terminal.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut env = vec![];
+        if !self.term.is_empty() {
+            env.push(("TERM".to_owned(), self.term.clone()));
+        }
+        if self.cols > 0 && self.rows > 0 {
+            env.push(("COLUMNS".to_owned(), self.cols.to_string()));
+            env.push(("LINES".to_owned(), self.rows.to_string()));
+        }
+        env
+    }
+
+    /// Keyboard input: Enter sends CR; follow it with LF, as sys-tty does.
+    pub fn input(&self, data: &[u8]) -> Vec<u8> {
+        let mut res = Vec::with_capacity(data.len() + 1);
+        for b in data {
+            res.push(*b);
+            if *b == b'\r' {
+                res.push(b'\n');
+            }
+        }
+        res
+    }
+
+    pub fn output_filter(&self) -> OutputFilter {
+        OutputFilter { last: 0 }
+    }
+}
+
+/// Translates program output for the client terminal: LF moves to the
+/// start of the next line (like ONLCR), unless it already follows CR.
+pub struct OutputFilter {
+    last: u8,
+}
+
+impl OutputFilter {
+    pub fn filter(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut res = Vec::with_capacity(data.len() + 16);
+        for b in data {
+            if *b == b'\n' && self.last != b'\r' {
+                res.push(b'\r');
+            }
+            res.push(*b);
+            self.last = *b;
+        }
+        res
+    }
+}
@@ -0,0 +1,430 @@
+//! The SSH connection protocol (RFC 4254): a single "session" channel
+//! running a shell or a command.
+
+use std::io::{Read, Result, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::pty::Pty;
+use crate::transport::{KexState, PacketReader, PacketWriter, Transport};
+use crate::wire::*;
+
+const LOCAL_CHANNEL: u32 = 0; // Only one channel is supported.
+const LOCAL_WINDOW: u32 = 1 << 20;
+const LOCAL_MAX_PACKET: u32 = 32 * 1024;
+const EXTENDED_DATA_STDERR: u32 = 1;
+const MAX_ENV_VARS: usize = 64;
+
+// Channel open failure reasons (RFC 4254, section 5.1).
+const OPEN_ADMINISTRATIVELY_PROHIBITED: u32 = 1;
+const OPEN_RESOURCE_SHORTAGE: u32 = 4;
+
+/// State shared with the threads forwarding program output.
+struct Shared {
+    writer: Mutex<PacketWriter>,
+    window: Mutex<u32>, // How much the client can receive.
+    window_changed: Condvar,
+    closed: AtomicBool, // Nothing can be sent on the channel anymore.
+    close_sent: AtomicBool,
+}
+
+impl Shared {
+    fn send(&self, payload: &[u8]) -> Result<()> {
+        self.writer.lock().unwrap().write_packet(payload)
+    }
+
+    fn close(&self) {
+        let _window = self.window.lock().unwrap();
+        self.closed.store(true, Ordering::Release);
+        self.window_changed.notify_all();
+    }
+
+    fn adjust_window(&self, bytes: u32) {
+        let mut window = self.window.lock().unwrap();
+        *window = window.saturating_add(bytes);
+        self.window_changed.notify_all();
+    }
+
+    // Sends data, waiting for the client to open its window as needed.
+    fn send_data(
+        &self,
+        remote_id: u32,
+        max_packet: u32,
+        stream: Option<u32>,
+        mut data: &[u8],
+    ) -> Result<()> {
+        while !data.is_empty() {
+            let len = {
+                let mut window = self.window.lock().unwrap();
+                while *window == 0 && !self.closed.load(Ordering::Acquire) {
+                    window = self.window_changed.wait(window).unwrap();
+                }
+                if self.closed.load(Ordering::Acquire) {
+                    return Err(std::io::ErrorKind::BrokenPipe.into());
+                }
+                let len = data.len().min(*window as usize).min(max_packet as usize);
+                *window -= len as u32;
+                len
+            };
+
+            let msg = match stream {
+                None => Encoder::new(MSG_CHANNEL_DATA).u32(remote_id),
+                Some(code) => Encoder::new(MSG_CHANNEL_EXTENDED_DATA)
+                    .u32(remote_id)
+                    .u32(code),
+            };
+            self.send(&msg.string(&data[0..len]).finish())?;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+}
+
+struct Channel {
+    remote_id: u32,
+    max_packet: u32,
+    pty: Option<Pty>,
+    env: Vec<(String, String)>,
+    // Input is written by a separate thread, so that a program that does
+    // not read its input does not stop packets (e.g. window adjustments)
+    // from being processed. Carries the data and its size on the wire.
+    stdin: Option<mpsc::Sender<(Vec<u8>, u32)>>,
+    child: Option<Arc<Mutex<Child>>>,
+}
+
+impl Channel {
+    fn spawn(&mut self, shared: &Arc<Shared>, cmdline: &str) -> Result<()> {
+        let words: Vec<&str> = cmdline.split_whitespace().collect();
+        if words.is_empty() {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        }
+
+        let mut command = Command::new(words[0]);
+        command.args(&words[1..]);
+        command.env_clear();
+        if let Some(pty) = &self.pty {
+            command.envs(pty.env());
+        }
+        command.envs(self.env.iter().map(|(key, val)| (key, val)));
+        command.current_dir("/");
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        let mut child = command.spawn()?;
+
+        let mut stdin = child.stdin.take();
+        let (stdin_sender, stdin_receiver) = mpsc::channel::<(Vec<u8>, u32)>();
+        self.stdin = Some(stdin_sender);
+        let input_shared = shared.clone();
+        let remote_id = self.remote_id;
+        std::thread::spawn(move || {
+            let mut consumed = 0; // Received since the last window adjustment.
+            for (data, len) in stdin_receiver {
+                if let Some(pipe) = &mut stdin {
+                    if pipe.write_all(&data).is_err() {
+                        stdin = None; // Keep draining, so that the client is not stuck.
+                    }
+                }
+                // Open the window only after the data has been consumed,
+                // so that the queue stays bounded.
+                consumed += len;
+                if consumed >= LOCAL_WINDOW / 2 {
+                    let adjust = Encoder::new(MSG_CHANNEL_WINDOW_ADJUST)
+                        .u32(remote_id)
+                        .u32(consumed)
+                        .finish();
+                    if input_shared.send(&adjust).is_err() {
+                        break;
+                    }
+                    consumed = 0;
+                }
+            }
+        });
+
+        let outputs: [(Box<dyn Read + Send>, _); 2] = [
+            (Box::new(child.stdout.take().unwrap()), None),
+            (
+                Box::new(child.stderr.take().unwrap()),
+                // Terminals show both stdout and stderr.
+                self.pty
+                    .as_ref()
+                    .map_or(Some(EXTENDED_DATA_STDERR), |_| None),
+            ),
+        ];
+        let mut threads = vec![];
+        for (mut output, stream) in outputs {
+            let shared = shared.clone();
+            let mut filter = self.pty.as_ref().map(|pty| pty.output_filter());
+            let (remote_id, max_packet) = (self.remote_id, self.max_packet);
+            threads.push(std::thread::spawn(move || {
+                let mut buf = vec![0_u8; LOCAL_MAX_PACKET as usize];
+                loop {
+                    let sz = match output.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(sz) => sz,
+                    };
+                    let data = match &mut filter {
+                        Some(filter) => filter.filter(&buf[0..sz]),
+                        None => buf[0..sz].to_vec(),
+                    };
+                    if shared
+                        .send_data(remote_id, max_packet, stream, &data)
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        let child = Arc::new(Mutex::new(child));
+        self.child = Some(child.clone());
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            let code = loop {
+                match child.lock().unwrap().try_wait() {
+                    Ok(Some(status)) => break status.code().unwrap_or(-1),
+                    Ok(None) => {}
+                    Err(_) => break -1,
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            };
+            for thread in threads {
+                let _ = thread.join();
+            }
+            if shared.closed.load(Ordering::Acquire) {
+                return;
+            }
+
+            let _ = shared.send(
+                &Encoder::new(MSG_CHANNEL_REQUEST)
+                    .u32(remote_id)
+                    .str("exit-status")
+                    .bool(false)
+                    .u32(code as u32)
+                    .finish(),
+            );
+            let _ = shared.send(&Encoder::new(MSG_CHANNEL_EOF).u32(remote_id).finish());
+            if !shared.close_sent.swap(true, Ordering::AcqRel) {
+                let _ = shared.send(&Encoder::new(MSG_CHANNEL_CLOSE).u32(remote_id).finish());
+            }
+            shared.close();
+        });
+
+        Ok(())
+    }
+
+    // Handles a channel request; returns whether it succeeded.
+    fn request(
+        &mut self,
+        shared: &Arc<Shared>,
+        kind: &str,
+        decoder: &mut Decoder,
+        shell: &str,
+    ) -> Result<bool> {
+        match kind {
+            "pty-req" if self.child.is_none() => {
+                let term = decoder.str()?;
+                let cols = decoder.u32()?;
+                let rows = decoder.u32()?;
+                self.pty = Some(Pty::new(term, cols, rows));
+                Ok(true)
+            }
+            "window-change" => {
+                let cols = decoder.u32()?;
+                let rows = decoder.u32()?;
+                if let Some(pty) = &mut self.pty {
+                    pty.resize(cols, rows);
+                }
+                Ok(true)
+            }
+            "env" if self.env.len() < MAX_ENV_VARS => {
+                let name = decoder.str()?;
+                let val = decoder.str()?;
+                self.env.push((name.to_owned(), val.to_owned()));
+                Ok(true)
+            }
+            "shell" if self.child.is_none() => Ok(self.spawn(shared, shell).is_ok()),
+            // Commands are run directly, without a shell.
+            "exec" if self.child.is_none() => {
+                let cmdline = decoder.str()?.to_owned();
+                Ok(self.spawn(shared, &cmdline).is_ok())
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn kill(&mut self) {
+        self.stdin = None;
+        if let Some(child) = &self.child {
+            let mut child = child.lock().unwrap();
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+            }
+        }
+    }
+}
+
+// Handles the next packet from the client; returns false when done.
+fn handle_packet(
+    reader: &mut PacketReader,
+    kex: &mut KexState,
+    host_key: &ring::signature::Ed25519KeyPair,
+    shared: &Arc<Shared>,
+    channel: &mut Option<Channel>,
+    shell: &str,
+) -> Result<bool> {
+    let packet = reader.read_packet()?;
+    let mut decoder = Decoder::new(&packet);
+    let msg = decoder.u8()?;
+
+    // Channel messages start with the recipient channel.
+    if (MSG_CHANNEL_WINDOW_ADJUST..=MSG_CHANNEL_FAILURE).contains(&msg)
+        && (decoder.u32()? != LOCAL_CHANNEL || channel.is_none())
+    {
+        return Ok(true);
+    }
+
+    match msg {
+        MSG_DISCONNECT => return Ok(false),
+        MSG_IGNORE | MSG_DEBUG | MSG_UNIMPLEMENTED => {}
+        MSG_KEXINIT => {
+            // Holding the writer keeps the output threads from sending
+            // channel data in the middle of the key exchange.
+            let mut writer = shared.writer.lock().unwrap();
+            kex.rekey(reader, &mut writer, host_key, &packet)?;
+        }
+        MSG_GLOBAL_REQUEST => {
+            decoder.str()?;
+            if decoder.bool()? {
+                shared.send(&[MSG_REQUEST_FAILURE])?;
+            }
+        }
+        MSG_CHANNEL_OPEN => {
+            let kind = decoder.str()?;
+            let remote_id = decoder.u32()?;
+            let window = decoder.u32()?;
+            let max_packet = decoder.u32()?;
+            if kind == "session" && channel.is_none() {
+                shared.adjust_window(window);
+                *channel = Some(Channel {
+                    remote_id,
+                    max_packet: max_packet.max(1),
+                    pty: None,
+                    env: vec![],
+                    stdin: None,
+                    child: None,
+                });
+                shared.send(
+                    &Encoder::new(MSG_CHANNEL_OPEN_CONFIRMATION)
+                        .u32(remote_id)
+                        .u32(LOCAL_CHANNEL)
+                        .u32(LOCAL_WINDOW)
+                        .u32(LOCAL_MAX_PACKET)
+                        .finish(),
+                )?;
+                return Ok(true);
+            }
+
+            let reason = if kind == "session" {
+                OPEN_RESOURCE_SHORTAGE
+            } else {
+                OPEN_ADMINISTRATIVELY_PROHIBITED
+            };
+            shared.send(
+                &Encoder::new(MSG_CHANNEL_OPEN_FAILURE)
+                    .u32(remote_id)
+                    .u32(reason)
+                    .str("only one session channel is supported")
+                    .str("")
+                    .finish(),
+            )?;
+        }
+        MSG_CHANNEL_WINDOW_ADJUST => shared.adjust_window(decoder.u32()?),
+        MSG_CHANNEL_DATA => {
+            let channel = channel.as_mut().unwrap();
+            let data = decoder.string()?;
+            if let Some(stdin) = &channel.stdin {
+                let input = match &channel.pty {
+                    Some(pty) => pty.input(data),
+                    None => data.to_vec(),
+                };
+                let _ = stdin.send((input, data.len() as u32));
+            }
+        }
+        MSG_CHANNEL_EOF => channel.as_mut().unwrap().stdin = None,
+        MSG_CHANNEL_CLOSE => {
+            let remote_id = channel.as_ref().unwrap().remote_id;
+            if !shared.close_sent.swap(true, Ordering::AcqRel) {
+                shared.send(&Encoder::new(MSG_CHANNEL_CLOSE).u32(remote_id).finish())?;
+            }
+            return Ok(false);
+        }
+        MSG_CHANNEL_REQUEST => {
+            let channel = channel.as_mut().unwrap();
+            let kind = decoder.str()?;
+            let want_reply = decoder.bool()?;
+            let ok = channel.request(shared, kind, &mut decoder, shell)?;
+            if want_reply {
+                let reply = if ok {
+                    MSG_CHANNEL_SUCCESS
+                } else {
+                    MSG_CHANNEL_FAILURE
+                };
+                shared.send(&Encoder::new(reply).u32(channel.remote_id).finish())?;
+            }
+        }
+        MSG_CHANNEL_EXTENDED_DATA | MSG_CHANNEL_SUCCESS | MSG_CHANNEL_FAILURE => {}
+        _ => shared.send(
+            &Encoder::new(MSG_UNIMPLEMENTED)
+                .u32(reader.last_seq())
+                .finish(),
+        )?,
+    }
+    Ok(true)
+}
+
+/// Serves the connection until the client closes it.
+pub fn run(
+    transport: Transport,
+    host_key: &ring::signature::Ed25519KeyPair,
+    shell: &str,
+) -> Result<()> {
+    let Transport {
+        mut reader,
+        writer,
+        mut kex,
+        ..
+    } = transport;
+    let shared = Arc::new(Shared {
+        writer: Mutex::new(writer),
+        window: Mutex::new(0),
+        window_changed: Condvar::new(),
+        closed: AtomicBool::new(false),
+        close_sent: AtomicBool::new(false),
+    });
+    let mut channel: Option<Channel> = None;
+
+    let result = loop {
+        match handle_packet(
+            &mut reader,
+            &mut kex,
+            host_key,
+            &shared,
+            &mut channel,
+            shell,
+        ) {
+            Ok(true) => {}
+            Ok(false) => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+
+    shared.close();
+    if let Some(channel) = &mut channel {
+        channel.kill();
+    }
+    result
+}
@@ -0,0 +1,434 @@
+//! The SSH transport layer (RFC 4253): version exchange, binary packets,
+//! and curve25519-sha256 key exchange (RFC 8731) with an ssh-ed25519 host
+//! key and the chacha20-poly1305@openssh.com cipher. Clients may rekey at
+//! any time; the server never initiates it.
+
+use std::io::{Read, Result, Write};
+use std::net::TcpStream;
+
+use ring::aead::chacha20_poly1305_openssh::{OpeningKey, SealingKey, KEY_LEN, TAG_LEN};
+use ring::rand::SecureRandom;
+use ring::signature::KeyPair;
+
+use crate::wire::*;
+
+const SERVER_VERSION: &str = "SSH-2.0-MotorSSH_0.1";
+const MAX_VERSION_LEN: usize = 255;
+const MAX_PACKET_LEN: usize = 256 * 1024;
+const BLOCK_SIZE: usize = 8;
+const MIN_PADDING: usize = 4;
+
+const KEX_ALGS: [&str; 2] = ["curve25519-sha256", "curve25519-sha256@libssh.org"];
+// Strict key exchange mitigates the "Terrapin" prefix truncation attack.
+const KEX_STRICT_CLIENT: &str = "kex-strict-c-v00@openssh.com";
+const KEX_STRICT_SERVER: &str = "kex-strict-s-v00@openssh.com";
+const HOST_KEY_ALG: &str = "ssh-ed25519";
+const CIPHER: &str = "chacha20-poly1305@openssh.com";
+const MAC: &str = "hmac-sha2-256"; // Not used: the cipher is an AEAD.
+const COMPRESSION: &str = "none";
+
+/// Reads packets sent by the client.
+pub struct PacketReader {
+    stream: TcpStream,
+    seq: u32,
+    key: Option<OpeningKey>,
+}
+
+impl PacketReader {
+    /// Reads the next packet and returns its payload.
+    pub fn read_packet(&mut self) -> Result<Vec<u8>> {
+        let mut len_bytes = [0_u8; 4];
+        self.stream.read_exact(&mut len_bytes)?;
+        let len = match &self.key {
+            Some(key) => key.decrypt_packet_length(self.seq, len_bytes),
+            None => len_bytes,
+        };
+        let len = u32::from_be_bytes(len) as usize;
+        if !(BLOCK_SIZE..=MAX_PACKET_LEN).contains(&len) {
+            return Err(bad_data("bad packet length"));
+        }
+
+        let tag_len = if self.key.is_some() { TAG_LEN } else { 0 };
+        let mut packet = vec![0_u8; 4 + len + tag_len];
+        packet[0..4].copy_from_slice(&len_bytes);
+        self.stream.read_exact(&mut packet[4..])?;
+
+        if let Some(key) = &self.key {
+            let tag: [u8; TAG_LEN] = packet[(4 + len)..].try_into().unwrap();
+            key.open_in_place(self.seq, &mut packet[0..(4 + len)], &tag)
+                .map_err(|_| bad_data("bad packet MAC"))?;
+        }
+        self.seq = self.seq.wrapping_add(1);
+
+        let padding = packet[4] as usize;
+        if padding < MIN_PADDING || padding + 1 > len {
+            return Err(bad_data("bad packet padding"));
+        }
+        Ok(packet[5..(4 + len - padding)].to_vec())
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    /// The sequence number of the last packet read.
+    pub fn last_seq(&self) -> u32 {
+        self.seq.wrapping_sub(1)
+    }
+}
+
+/// Sends packets to the client.
+pub struct PacketWriter {
+    stream: TcpStream,
+    seq: u32,
+    key: Option<SealingKey>,
+    rng: ring::rand::SystemRandom,
+}
+
+impl PacketWriter {
+    pub fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
+        // The packet length is not padded if it is encrypted separately.
+        let unpadded = if self.key.is_some() { 1 } else { 5 } + payload.len();
+        let mut padding = BLOCK_SIZE - unpadded % BLOCK_SIZE;
+        if padding < MIN_PADDING {
+            padding += BLOCK_SIZE;
+        }
+        let len = 1 + payload.len() + padding;
+
+        let mut packet = Vec::with_capacity(4 + len + TAG_LEN);
+        packet.extend_from_slice(&(len as u32).to_be_bytes());
+        packet.push(padding as u8);
+        packet.extend_from_slice(payload);
+        let mut padding_bytes = [0_u8; MIN_PADDING + BLOCK_SIZE];
+        self.rng
+            .fill(&mut padding_bytes[0..padding])
+            .map_err(|_| std::io::Error::other("rng failure"))?;
+        packet.extend_from_slice(&padding_bytes[0..padding]);
+
+        if let Some(key) = &self.key {
+            let mut tag = [0_u8; TAG_LEN];
+            key.seal_in_place(self.seq, &mut packet, &mut tag);
+            packet.extend_from_slice(&tag);
+        }
+        self.seq = self.seq.wrapping_add(1);
+
+        self.stream.write_all(&packet)
+    }
+
+    pub fn disconnect(&mut self, reason: u32, description: &str) {
+        let msg = Encoder::new(MSG_DISCONNECT)
+            .u32(reason)
+            .str(description)
+            .str("")
+            .finish();
+        let _ = self.write_packet(&msg);
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// An established (encrypted) connection.
+pub struct Transport {
+    pub reader: PacketReader,
+    pub writer: PacketWriter,
+    pub session_id: Vec<u8>,
+    pub kex: KexState,
+}
+
+/// What key exchanges after the first one (rekeying) need.
+pub struct KexState {
+    client_version: String,
+    session_id: Vec<u8>, // The exchange hash of the first key exchange.
+    strict: bool,        // Sequence numbers restart with every NEWKEYS.
+}
+
+fn read_version(stream: &mut TcpStream) -> Result<String> {
+    // Servers may send other lines before the version, but clients don't.
+    let mut line = Vec::new();
+    loop {
+        let mut byte = [0_u8; 1];
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        line.push(byte[0]);
+        if line.len() > MAX_VERSION_LEN {
+            return Err(bad_data("version line too long"));
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    let version = String::from_utf8(line).map_err(|_| bad_data("bad version"))?;
+    if !version.starts_with("SSH-2.0-") && !version.starts_with("SSH-1.99-") {
+        return Err(bad_data("unsupported protocol version"));
+    }
+    Ok(version)
+}
+
+fn sha256(parts: &[&[u8]]) -> Vec<u8> {
+    let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+    for part in parts {
+        ctx.update(part);
+    }
+    ctx.finish().as_ref().to_vec()
+}
+
+// Derives a cipher key from the shared secret (RFC 4253, section 7.2).
+fn derive_key(k: &[u8], h: &[u8], letter: u8, session_id: &[u8]) -> [u8; KEY_LEN] {
+    let mut key = sha256(&[k, h, &[letter], session_id]);
+    while key.len() < KEY_LEN {
+        let more = sha256(&[k, h, &key]);
+        key.extend_from_slice(&more);
+    }
+    key[0..KEY_LEN].try_into().unwrap()
+}
+
+// Returns the first client algorithm that the server supports.
+fn negotiate<'a>(client: &[&'a str], server: &[&str]) -> Option<&'a str> {
+    client.iter().find(|alg| server.contains(alg)).copied()
+}
+
+struct ClientKexInit<'a> {
+    kex: Vec<&'a str>,
+    host_key: Vec<&'a str>,
+    cipher_c2s: Vec<&'a str>,
+    cipher_s2c: Vec<&'a str>,
+    compression_c2s: Vec<&'a str>,
+    compression_s2c: Vec<&'a str>,
+    first_kex_packet_follows: bool,
+}
+
+impl<'a> ClientKexInit<'a> {
+    fn parse(payload: &'a [u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(payload);
+        if decoder.u8()? != MSG_KEXINIT {
+            return Err(bad_data("expected KEXINIT"));
+        }
+        decoder.raw(16)?; // Cookie.
+        let kex = decoder.name_list()?;
+        let host_key = decoder.name_list()?;
+        let cipher_c2s = decoder.name_list()?;
+        let cipher_s2c = decoder.name_list()?;
+        decoder.name_list()?; // MAC c2s.
+        decoder.name_list()?; // MAC s2c.
+        let compression_c2s = decoder.name_list()?;
+        let compression_s2c = decoder.name_list()?;
+        decoder.name_list()?; // Languages c2s.
+        decoder.name_list()?; // Languages s2c.
+        let first_kex_packet_follows = decoder.bool()?;
+
+        Ok(Self {
+            kex,
+            host_key,
+            cipher_c2s,
+            cipher_s2c,
+            compression_c2s,
+            compression_s2c,
+            first_kex_packet_follows,
+        })
+    }
+}
+
+fn kexinit(rng: &ring::rand::SystemRandom) -> Result<Vec<u8>> {
+    let mut cookie = [0_u8; 16];
+    rng.fill(&mut cookie)
+        .map_err(|_| std::io::Error::other("rng failure"))?;
+    let mut server_kex_algs = KEX_ALGS.to_vec();
+    server_kex_algs.push(KEX_STRICT_SERVER);
+    Ok(Encoder::new(MSG_KEXINIT)
+        .raw(&cookie)
+        .name_list(&server_kex_algs)
+        .name_list(&[HOST_KEY_ALG])
+        .name_list(&[CIPHER])
+        .name_list(&[CIPHER])
+        .name_list(&[MAC])
+        .name_list(&[MAC])
+        .name_list(&[COMPRESSION])
+        .name_list(&[COMPRESSION])
+        .name_list(&[])
+        .name_list(&[])
+        .bool(false)
+        .u32(0)
+        .finish())
+}
+
+/// Runs the version exchange and the initial key exchange.
+pub fn handshake(
+    mut stream: TcpStream,
+    host_key: &ring::signature::Ed25519KeyPair,
+) -> Result<Transport> {
+    let rng = ring::rand::SystemRandom::new();
+
+    stream.write_all(format!("{SERVER_VERSION}\r\n").as_bytes())?;
+    let client_version = read_version(&mut stream)?;
+
+    let mut reader = PacketReader {
+        stream: stream.try_clone()?,
+        seq: 0,
+        key: None,
+    };
+    let mut writer = PacketWriter {
+        stream,
+        seq: 0,
+        key: None,
+        rng,
+    };
+
+    let server_kexinit = kexinit(&writer.rng)?;
+    writer.write_packet(&server_kexinit)?;
+    let client_kexinit = reader.read_packet()?;
+
+    let mut kex = KexState {
+        client_version,
+        session_id: vec![],
+        strict: ClientKexInit::parse(&client_kexinit)?
+            .kex
+            .contains(&KEX_STRICT_CLIENT),
+    };
+    kex.exchange(
+        &mut reader,
+        &mut writer,
+        host_key,
+        &client_kexinit,
+        &server_kexinit,
+    )?;
+
+    Ok(Transport {
+        reader,
+        writer,
+        session_id: kex.session_id.clone(),
+        kex,
+    })
+}
+
+impl KexState {
+    /// Answers a KEXINIT the client sent on an established connection
+    /// (RFC 4253, section 9) with a new key exchange; the session id stays.
+    /// Only key exchange packets may be sent until it is done, so the caller
+    /// must not let anything else use @writer meanwhile.
+    pub fn rekey(
+        &mut self,
+        reader: &mut PacketReader,
+        writer: &mut PacketWriter,
+        host_key: &ring::signature::Ed25519KeyPair,
+        client_kexinit: &[u8],
+    ) -> Result<()> {
+        let server_kexinit = kexinit(&writer.rng)?;
+        writer.write_packet(&server_kexinit)?;
+        self.exchange(reader, writer, host_key, client_kexinit, &server_kexinit)
+    }
+
+    // The key exchange after both KEXINIT packets: ends with both
+    // directions switched to the new keys.
+    fn exchange(
+        &mut self,
+        reader: &mut PacketReader,
+        writer: &mut PacketWriter,
+        host_key: &ring::signature::Ed25519KeyPair,
+        client_kexinit: &[u8],
+        server_kexinit: &[u8],
+    ) -> Result<()> {
+        let client = ClientKexInit::parse(client_kexinit)?;
+        let kex = negotiate(&client.kex, &KEX_ALGS);
+        let negotiated = kex.is_some()
+            && negotiate(&client.host_key, &[HOST_KEY_ALG]).is_some()
+            && negotiate(&client.cipher_c2s, &[CIPHER]).is_some()
+            && negotiate(&client.cipher_s2c, &[CIPHER]).is_some()
+            && negotiate(&client.compression_c2s, &[COMPRESSION]).is_some()
+            && negotiate(&client.compression_s2c, &[COMPRESSION]).is_some();
+        if !negotiated {
+            writer.disconnect(DISCONNECT_KEY_EXCHANGE_FAILED, "no matching algorithms");
+            return Err(bad_data("no matching algorithms"));
+        }
+
+        // A wrong guess of the key exchange algorithm is ignored (RFC 4253, 7).
+        let wrong_guess = client.first_kex_packet_follows
+            && (client.kex.first() != kex.as_ref()
+                || client.host_key.first() != Some(&HOST_KEY_ALG));
+        if wrong_guess {
+            reader.read_packet()?;
+        }
+
+        let ecdh_init = reader.read_packet()?;
+        let mut decoder = Decoder::new(&ecdh_init);
+        if decoder.u8()? != MSG_KEX_ECDH_INIT {
+            writer.disconnect(DISCONNECT_PROTOCOL_ERROR, "expected KEX_ECDH_INIT");
+            return Err(bad_data("expected KEX_ECDH_INIT"));
+        }
+        let client_public = decoder.string()?;
+
+        let private =
+            ring::agreement::EphemeralPrivateKey::generate(&ring::agreement::X25519, &writer.rng)
+                .map_err(|_| std::io::Error::other("key generation failed"))?;
+        let server_public = private
+            .compute_public_key()
+            .map_err(|_| std::io::Error::other("key generation failed"))?;
+        let shared_secret = ring::agreement::agree_ephemeral(
+            private,
+            &ring::agreement::UnparsedPublicKey::new(&ring::agreement::X25519, client_public),
+            |secret| secret.to_vec(),
+        )
+        .map_err(|_| bad_data("bad client public key"))?;
+        let k = Encoder::default().mpint(&shared_secret).finish();
+
+        let host_key_blob = Encoder::default()
+            .str(HOST_KEY_ALG)
+            .string(host_key.public_key().as_ref())
+            .finish();
+        let exchange_hash = sha256(&[
+            &Encoder::default()
+                .str(&self.client_version)
+                .str(SERVER_VERSION)
+                .string(client_kexinit)
+                .string(server_kexinit)
+                .string(&host_key_blob)
+                .string(client_public)
+                .string(server_public.as_ref())
+                .finish(),
+            &k,
+        ]);
+        let signature = Encoder::default()
+            .str(HOST_KEY_ALG)
+            .string(host_key.sign(&exchange_hash).as_ref())
+            .finish();
+
+        let ecdh_reply = Encoder::new(MSG_KEX_ECDH_REPLY)
+            .string(&host_key_blob)
+            .string(server_public.as_ref())
+            .string(&signature)
+            .finish();
+        writer.write_packet(&ecdh_reply)?;
+        writer.write_packet(&[MSG_NEWKEYS])?;
+
+        if self.session_id.is_empty() {
+            self.session_id = exchange_hash.clone();
+        }
+        writer.key = Some(SealingKey::new(&derive_key(
+            &k,
+            &exchange_hash,
+            b'D',
+            &self.session_id,
+        )));
+        if self.strict {
+            writer.seq = 0;
+        }
+
+        let newkeys = reader.read_packet()?;
+        if newkeys != [MSG_NEWKEYS] {
+            writer.disconnect(DISCONNECT_PROTOCOL_ERROR, "expected NEWKEYS");
+            return Err(bad_data("expected NEWKEYS"));
+        }
+        reader.key = Some(OpeningKey::new(&derive_key(
+            &k,
+            &exchange_hash,
+            b'C',
+            &self.session_id,
+        )));
+        if self.strict {
+            reader.seq = 0;
+        }
+
+        Ok(())
+    }
+}
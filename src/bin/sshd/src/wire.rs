@@ -0,0 +1,149 @@
+//! SSH data types (RFC 4251, section 5) and message numbers.
+
+use std::io::{Error, ErrorKind, Result};
+
+pub const MSG_DISCONNECT: u8 = 1;
+pub const MSG_IGNORE: u8 = 2;
+pub const MSG_UNIMPLEMENTED: u8 = 3;
+pub const MSG_DEBUG: u8 = 4;
+pub const MSG_SERVICE_REQUEST: u8 = 5;
+pub const MSG_SERVICE_ACCEPT: u8 = 6;
+pub const MSG_KEXINIT: u8 = 20;
+pub const MSG_NEWKEYS: u8 = 21;
+pub const MSG_KEX_ECDH_INIT: u8 = 30;
+pub const MSG_KEX_ECDH_REPLY: u8 = 31;
+pub const MSG_USERAUTH_REQUEST: u8 = 50;
+pub const MSG_USERAUTH_FAILURE: u8 = 51;
+pub const MSG_USERAUTH_SUCCESS: u8 = 52;
+pub const MSG_USERAUTH_PK_OK: u8 = 60;
+pub const MSG_GLOBAL_REQUEST: u8 = 80;
+pub const MSG_REQUEST_FAILURE: u8 = 82;
+pub const MSG_CHANNEL_OPEN: u8 = 90;
+pub const MSG_CHANNEL_OPEN_CONFIRMATION: u8 = 91;
+pub const MSG_CHANNEL_OPEN_FAILURE: u8 = 92;
+pub const MSG_CHANNEL_WINDOW_ADJUST: u8 = 93;
+pub const MSG_CHANNEL_DATA: u8 = 94;
+pub const MSG_CHANNEL_EXTENDED_DATA: u8 = 95;
+pub const MSG_CHANNEL_EOF: u8 = 96;
+pub const MSG_CHANNEL_CLOSE: u8 = 97;
+pub const MSG_CHANNEL_REQUEST: u8 = 98;
+pub const MSG_CHANNEL_SUCCESS: u8 = 99;
+pub const MSG_CHANNEL_FAILURE: u8 = 100;
+
+// Disconnect reason codes (RFC 4253, section 11.1).
+pub const DISCONNECT_PROTOCOL_ERROR: u32 = 2;
+pub const DISCONNECT_KEY_EXCHANGE_FAILED: u32 = 3;
+pub const DISCONNECT_SERVICE_NOT_AVAILABLE: u32 = 7;
+pub const DISCONNECT_NO_MORE_AUTH_METHODS_AVAILABLE: u32 = 14;
+
+pub fn bad_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Builds a message payload.
+#[derive(Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(msg: u8) -> Self {
+        Self { buf: vec![msg] }
+    }
+
+    pub fn u8(mut self, val: u8) -> Self {
+        self.buf.push(val);
+        self
+    }
+
+    pub fn bool(self, val: bool) -> Self {
+        self.u8(val as u8)
+    }
+
+    pub fn u32(mut self, val: u32) -> Self {
+        self.buf.extend_from_slice(&val.to_be_bytes());
+        self
+    }
+
+    pub fn raw(mut self, bytes: &[u8]) -> Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn string(self, bytes: &[u8]) -> Self {
+        self.u32(bytes.len() as u32).raw(bytes)
+    }
+
+    pub fn str(self, s: &str) -> Self {
+        self.string(s.as_bytes())
+    }
+
+    pub fn name_list(self, names: &[&str]) -> Self {
+        self.str(names.join(",").as_str())
+    }
+
+    /// Encodes an unsigned big-endian number.
+    pub fn mpint(self, bytes: &[u8]) -> Self {
+        let first = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        let bytes = &bytes[first..];
+        if bytes.first().is_some_and(|b| *b & 0x80 != 0) {
+            self.u32(bytes.len() as u32 + 1).u8(0).raw(bytes)
+        } else {
+            self.string(bytes)
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Parses a message payload.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn raw(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() - self.pos < len {
+            return Err(bad_data("truncated message"));
+        }
+        let bytes = &self.buf[self.pos..(self.pos + len)];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.raw(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.raw(4)?.try_into().unwrap()))
+    }
+
+    pub fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.raw(len)
+    }
+
+    pub fn str(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.string()?).map_err(|_| bad_data("bad UTF-8 string"))
+    }
+
+    pub fn name_list(&mut self) -> Result<Vec<&'a str>> {
+        let names = self.str()?;
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(names.split(',').collect())
+    }
+}
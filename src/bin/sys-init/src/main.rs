@@ -1,5 +1,7 @@
 use moto_runtime::moturus_log;
 
+mod services;
+
 // use moto_sys::caps::{CAP_IO_MANAGER, CAP_LOG, CAP_SHARE, CAP_SPAWN};
 use moto_sys::*;
 
@@ -98,6 +100,8 @@ fn main() {
         log::set_max_level(log::LevelFilter::Info);
     }
 
+    services::start();
+
     let mut tty = std::process::Command::new(config.tty.as_str())
         .env(moto_sys::caps::MOTURUS_CAPS_ENV_KEY, "0xffffffffffffffff")
         .stdin(std::process::Stdio::null())
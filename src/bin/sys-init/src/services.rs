@@ -0,0 +1,573 @@
+// Service supervision: starts the services declared in /sys/cfg/services.cfg
+// in dependency order, restarts them with exponential backoff when they exit,
+// and serves start/stop/restart/status/logs requests (see moto-svc).
+//
+// Config format (one section per service):
+//
+//   [name]
+//   command = /path/to/binary ARGS...
+//   caps = spawn log        # Capabilities (default: none).
+//   restart = on-failure    # always | on-failure | never (default: on-failure).
+//   after = other another   # Services that must be running first.
+//   autostart = yes         # yes | no (default: yes).
+
+use moto_ipc::sync::{ChannelSize, LocalServer, ResponseHeader};
+use moto_svc::implementation::*;
+use moto_svc::{ServiceState, ServiceStatus, MAX_NAME_LEN};
+use moto_sys::{ErrorCode, SysHandle};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const CONFIG_PATH: &str = "/sys/cfg/services.cfg";
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// A service that ran at least this long before exiting is restarted
+// with MIN_BACKOFF.
+const STABLE_UPTIME: Duration = Duration::from_secs(30);
+const TICK: Duration = Duration::from_millis(100);
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RestartPolicy {
+    Always,
+    OnFailure,
+    Never,
+}
+
+#[derive(Debug)]
+struct Config {
+    name: String,
+    command: Vec<String>,
+    caps: u64,
+    restart: RestartPolicy,
+    after: Vec<String>,
+    autostart: bool,
+}
+
+impl Config {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            command: Vec::new(),
+            caps: 0,
+            restart: RestartPolicy::OnFailure,
+            after: Vec::new(),
+            autostart: true,
+        }
+    }
+
+    fn set(&mut self, key: &str, val: &str) -> Result<(), String> {
+        match key {
+            "command" => self.command = val.split_whitespace().map(|s| s.to_owned()).collect(),
+            "caps" => {
+                for cap in val.split_whitespace() {
+                    self.caps |= match cap {
+                        "sys" => moto_sys::caps::CAP_SYS,
+                        "io_manager" => moto_sys::caps::CAP_IO_MANAGER,
+                        "spawn" => moto_sys::caps::CAP_SPAWN,
+                        "log" => moto_sys::caps::CAP_LOG,
+                        "exec_mem" => moto_sys::caps::CAP_EXEC_MEM,
+                        "realtime" => moto_sys::caps::CAP_REALTIME,
+                        _ => return Err(format!("unknown cap '{}'", cap)),
+                    };
+                }
+            }
+            "restart" => {
+                self.restart = match val {
+                    "always" => RestartPolicy::Always,
+                    "on-failure" => RestartPolicy::OnFailure,
+                    "never" => RestartPolicy::Never,
+                    _ => return Err(format!("bad restart policy '{}'", val)),
+                }
+            }
+            "after" => self.after = val.split_whitespace().map(|s| s.to_owned()).collect(),
+            "autostart" => {
+                self.autostart = match val {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(format!("bad autostart value '{}'", val)),
+                }
+            }
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+// Returns the services ordered so that dependencies come first.
+fn parse_config(contents: &str) -> Result<Vec<Config>, String> {
+    let mut configs: Vec<Config> = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = match line.split_once('#') {
+            Some((line, _comment)) => line.trim(),
+            None => line.trim(),
+        };
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            let name = name.trim();
+            if name.is_empty()
+                || name.len() > MAX_NAME_LEN
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(format!("line {}: bad service name '{}'", line_no, name));
+            }
+            if configs.iter().any(|cfg| cfg.name == name) {
+                return Err(format!("line {}: duplicate service '{}'", line_no, name));
+            }
+            configs.push(Config::new(name));
+            continue;
+        }
+
+        let Some(config) = configs.last_mut() else {
+            return Err(format!("line {}: expected '[service name]'", line_no));
+        };
+        let Some((key, val)) = line.split_once('=') else {
+            return Err(format!("line {}: expected 'key = value'", line_no));
+        };
+        config
+            .set(key.trim(), val.trim())
+            .map_err(|err| format!("line {}: {}", line_no, err))?;
+    }
+
+    for config in &configs {
+        if config.command.is_empty() {
+            return Err(format!("service '{}': missing command", config.name));
+        }
+        for dep in &config.after {
+            if !configs.iter().any(|cfg| &cfg.name == dep) {
+                return Err(format!(
+                    "service '{}': unknown dependency '{}'",
+                    config.name, dep
+                ));
+            }
+        }
+    }
+
+    // Sort topologically, keeping the config order where possible.
+    let mut sorted: Vec<Config> = Vec::with_capacity(configs.len());
+    while !configs.is_empty() {
+        let Some(pos) = configs.iter().position(|cfg| {
+            cfg.after
+                .iter()
+                .all(|dep| sorted.iter().any(|done| &done.name == dep))
+        }) else {
+            let names: Vec<&str> = configs.iter().map(|cfg| cfg.name.as_str()).collect();
+            return Err(format!("dependency cycle among {:?}", names));
+        };
+        sorted.push(configs.remove(pos));
+    }
+
+    Ok(sorted)
+}
+
+// The most recent output of a service.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<VecDeque<u8>>>);
+
+impl Output {
+    fn append(&self, bytes: &[u8]) {
+        let mut buf = self.0.lock().unwrap();
+        buf.extend(bytes);
+        if buf.len() > MAX_OUTPUT_BYTES {
+            let excess = buf.len() - MAX_OUTPUT_BYTES;
+            buf.drain(0..excess);
+        }
+    }
+
+    // The tail of the output, starting at a line boundary, not longer than @max_len.
+    fn tail(&self, max_len: usize) -> Vec<u8> {
+        let buf = self.0.lock().unwrap();
+        if buf.len() <= max_len {
+            return buf.iter().copied().collect();
+        }
+
+        let mut tail = buf.range((buf.len() - max_len)..);
+        let _ = tail.by_ref().position(|b| *b == b'\n');
+        tail.copied().collect()
+    }
+
+    fn collect_from(&self, mut src: impl Read + Send + 'static) {
+        let output = self.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0_u8; 256];
+            loop {
+                match src.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(sz) => output.append(&buf[0..sz]),
+                }
+            }
+        });
+    }
+}
+
+struct Service {
+    config: Config,
+    deps: Vec<usize>, // Indices of config.after services.
+    wanted: bool,
+    exited: bool, // Exited and not restarted because of the restart policy.
+    child: Option<Child>,
+    started: Instant,
+    next_start: Instant,
+    backoff: Duration,
+    restarts: u32,
+    last_exit: Option<i32>,
+    output: Output,
+}
+
+impl Service {
+    fn state(&self, now: Instant) -> ServiceState {
+        if self.child.is_some() {
+            ServiceState::Running
+        } else if !self.wanted {
+            if self.exited {
+                ServiceState::Exited
+            } else {
+                ServiceState::Stopped
+            }
+        } else if now < self.next_start {
+            ServiceState::Backoff
+        } else {
+            ServiceState::Waiting
+        }
+    }
+
+    fn note(&self, msg: &str) {
+        self.output
+            .append(format!("[sys-init] {}\n", msg).as_bytes());
+    }
+
+    fn schedule_restart(&mut self, now: Instant) {
+        self.next_start = now + self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+
+    fn spawn(&mut self, now: Instant) {
+        let result = Command::new(self.config.command[0].as_str())
+            .args(&self.config.command[1..])
+            .env(
+                moto_sys::caps::MOTURUS_CAPS_ENV_KEY,
+                format!("0x{:x}", self.config.caps),
+            )
+            .current_dir("/")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        match result {
+            Ok(mut child) => {
+                if let Some(stdout) = child.stdout.take() {
+                    self.output.collect_from(stdout);
+                }
+                if let Some(stderr) = child.stderr.take() {
+                    self.output.collect_from(stderr);
+                }
+                self.child = Some(child);
+                self.started = now;
+                self.note("started");
+            }
+            Err(err) => {
+                let msg = format!("failed to start: {:?}", err.kind());
+                log::warn!("service '{}': {}", self.config.name, msg);
+                self.note(msg.as_str());
+                self.schedule_restart(now);
+            }
+        }
+    }
+
+    // Kills the service if it is running; does not change whether it is wanted.
+    fn kill(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let code = child.wait().ok().and_then(|status| status.code());
+            self.last_exit = Some(code.unwrap_or(-1));
+            self.note("killed");
+        }
+    }
+
+    fn reap(&mut self, now: Instant) {
+        let Some(child) = self.child.as_mut() else {
+            return;
+        };
+        let code = match child.try_wait() {
+            Ok(None) => return,
+            Ok(Some(status)) => status.code().unwrap_or(-1),
+            Err(_) => -1,
+        };
+        self.child = None;
+        self.last_exit = Some(code);
+        self.note(format!("exited with code {}", code).as_str());
+
+        let restart = match self.config.restart {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => code != 0,
+            RestartPolicy::Never => false,
+        };
+        if !restart {
+            self.wanted = false;
+            self.exited = true;
+            return;
+        }
+
+        if now.duration_since(self.started) >= STABLE_UPTIME {
+            self.backoff = MIN_BACKOFF;
+        }
+        self.restarts += 1;
+        log::info!(
+            "service '{}' exited with code {}; restarting in {:?}",
+            self.config.name,
+            code,
+            self.backoff
+        );
+        self.schedule_restart(now);
+    }
+
+    fn status(&self, now: Instant) -> ServiceStatus {
+        ServiceStatus {
+            name: self.config.name.clone(),
+            state: self.state(now),
+            uptime: self
+                .child
+                .as_ref()
+                .map(|_| now.duration_since(self.started)),
+            restarts: self.restarts,
+            last_exit: self.last_exit,
+        }
+    }
+}
+
+pub struct Supervisor {
+    services: Vec<Service>, // Dependencies first.
+}
+
+impl Supervisor {
+    fn new(configs: Vec<Config>) -> Self {
+        let now = Instant::now();
+        let mut services: Vec<Service> = Vec::with_capacity(configs.len());
+        for config in configs {
+            let deps = config
+                .after
+                .iter()
+                .map(|dep| {
+                    services
+                        .iter()
+                        .position(|svc| &svc.config.name == dep)
+                        .unwrap()
+                })
+                .collect();
+            services.push(Service {
+                wanted: config.autostart,
+                config,
+                deps,
+                exited: false,
+                child: None,
+                started: now,
+                next_start: now,
+                backoff: MIN_BACKOFF,
+                restarts: 0,
+                last_exit: None,
+                output: Output::default(),
+            });
+        }
+
+        Self { services }
+    }
+
+    fn find(&self, name: &str) -> Result<usize, ErrorCode> {
+        self.services
+            .iter()
+            .position(|svc| svc.config.name == name)
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+        for svc in &mut self.services {
+            svc.reap(now);
+        }
+
+        // Dependencies come first, so a whole chain can start in one tick.
+        for idx in 0..self.services.len() {
+            let svc = &self.services[idx];
+            if !svc.wanted || svc.child.is_some() || now < svc.next_start {
+                continue;
+            }
+            if svc
+                .deps
+                .iter()
+                .all(|dep| self.services[*dep].child.is_some())
+            {
+                self.services[idx].spawn(now);
+            }
+        }
+    }
+
+    // Starts the service and, transitively, its dependencies.
+    fn start(&mut self, idx: usize) {
+        let now = Instant::now();
+        let mut starting = vec![false; self.services.len()];
+        starting[idx] = true;
+        for pos in (0..=idx).rev() {
+            if !starting[pos] {
+                continue;
+            }
+            let svc = &mut self.services[pos];
+            svc.wanted = true;
+            svc.exited = false;
+            if svc.child.is_none() {
+                // Skip the backoff, if any.
+                svc.next_start = now;
+                svc.backoff = MIN_BACKOFF;
+            }
+            for dep in &svc.deps {
+                starting[*dep] = true;
+            }
+        }
+    }
+
+    // Stops the service and, transitively, the services depending on it.
+    fn stop(&mut self, idx: usize) {
+        let mut stopping = vec![false; self.services.len()];
+        stopping[idx] = true;
+        for pos in idx..self.services.len() {
+            let svc = &mut self.services[pos];
+            if !stopping[pos] && !svc.deps.iter().any(|dep| stopping[*dep]) {
+                continue;
+            }
+            stopping[pos] = true;
+            svc.wanted = false;
+            svc.exited = false;
+            svc.kill();
+        }
+    }
+
+    fn restart(&mut self, idx: usize) {
+        let svc = &mut self.services[idx];
+        svc.kill();
+        svc.wanted = false;
+        self.start(idx);
+    }
+
+    fn handle_request(&mut self, cmd: u16, name: &str, buf: &mut [u8]) -> Result<(), ErrorCode> {
+        match cmd {
+            CMD_START | CMD_STOP | CMD_RESTART => {
+                let idx = self.find(name)?;
+                match cmd {
+                    CMD_START => self.start(idx),
+                    CMD_STOP => self.stop(idx),
+                    _ => self.restart(idx),
+                }
+                self.tick();
+                Response::prepare(buf, 0);
+            }
+            CMD_STATUS => {
+                let now = Instant::now();
+                let entries: Vec<StatusEntry> = self
+                    .services
+                    .iter()
+                    .filter(|svc| name.is_empty() || svc.config.name == name)
+                    .take(StatusEntry::max_entries(buf.len()))
+                    .map(|svc| StatusEntry::new(&svc.status(now)))
+                    .collect();
+                if !name.is_empty() && entries.is_empty() {
+                    return Err(ErrorCode::NotFound);
+                }
+                let payload = Response::prepare(buf, entries.len() as u32);
+                StatusEntry::write_all(payload, &entries);
+            }
+            CMD_LOGS => {
+                let idx = self.find(name)?;
+                let max_len = buf.len() - core::mem::size_of::<Response>();
+                let tail = self.services[idx].output.tail(max_len);
+                let payload = Response::prepare(buf, tail.len() as u32);
+                payload[0..tail.len()].copy_from_slice(&tail);
+            }
+            _ => return Err(ErrorCode::InvalidArgument),
+        }
+
+        Ok(())
+    }
+}
+
+fn serve(supervisor: Arc<Mutex<Supervisor>>) {
+    let mut server = match LocalServer::new(moto_svc::SERVER_URL, ChannelSize::Small, 8, 2) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("sys-init: failed to start the service server: {:?}", err);
+            return;
+        }
+    };
+
+    loop {
+        let Ok(wakers) = server.wait(SysHandle::NONE, &[]) else {
+            continue;
+        };
+
+        for waker in &wakers {
+            let Some(conn) = server.get_connection(*waker) else {
+                continue;
+            };
+            if !conn.connected() || !conn.have_req() {
+                continue;
+            }
+
+            let result = match Request::parse(conn.data()) {
+                Ok((cmd, name)) => {
+                    let name = name.to_owned();
+                    supervisor
+                        .lock()
+                        .unwrap()
+                        .handle_request(cmd, &name, conn.data_mut())
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                unsafe {
+                    conn.raw_channel().get_mut::<ResponseHeader>().result = err.into();
+                }
+            }
+            let _ = conn.finish_rpc();
+        }
+    }
+}
+
+// Loads the config and starts supervising services in the background.
+pub fn start() {
+    let configs = match std::fs::read_to_string(CONFIG_PATH) {
+        Ok(contents) => match parse_config(&contents) {
+            Ok(configs) => configs,
+            Err(err) => {
+                log::error!("sys-init: {}: {}", CONFIG_PATH, err);
+                moto_runtime::moturus_log!("sys-init: {}: {}", CONFIG_PATH, err);
+                Vec::new()
+            }
+        },
+        // No services.
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            moto_runtime::moturus_log!("sys-init: {}: {:?}", CONFIG_PATH, err.kind());
+            Vec::new()
+        }
+    };
+
+    let supervisor = Arc::new(Mutex::new(Supervisor::new(configs)));
+
+    let server_supervisor = supervisor.clone();
+    std::thread::spawn(move || serve(server_supervisor));
+
+    std::thread::spawn(move || loop {
+        supervisor.lock().unwrap().tick();
+        std::thread::sleep(TICK);
+    });
+}
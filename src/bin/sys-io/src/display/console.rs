@@ -0,0 +1,291 @@
+// A text console on the framebuffer: a grid of 8x16 cells (the 8x8 font
+// with its rows doubled) and a history of lines to scroll back through.
+// Input is treated as by a dumb terminal: CR, LF, TAB and BS are handled,
+// other control characters and escape sequences are dropped.
+
+use std::collections::VecDeque;
+
+use super::font;
+
+const CELL_WIDTH: usize = font::GLYPH_WIDTH;
+const CELL_HEIGHT: usize = font::GLYPH_HEIGHT * 2;
+
+// Lines kept above the screen.
+const SCROLLBACK_LINES: usize = 2000;
+
+const FG_COLOR: u32 = 0x00_c0_c0_c0;
+const BG_COLOR: u32 = 0x00_00_00_00;
+
+const TAB_WIDTH: usize = 8;
+
+enum Escape {
+    None,
+    Esc,
+    Csi, // Until a final byte (0x40 to 0x7e).
+}
+
+pub struct Console {
+    width: usize, // In pixels.
+    height: usize,
+    cols: usize,
+    rows: usize,
+
+    // History and the screen: the cursor is on the last line.
+    lines: VecDeque<Vec<u8>>,
+    cursor_col: usize,
+    view_offset: usize, // How many lines the view is scrolled back.
+
+    // What render() needs to do.
+    dirty: Vec<bool>,  // Screen rows.
+    shift_rows: usize, // Screen rows the drawn text has moved up by.
+    full_redraw: bool,
+
+    escape: Escape,
+    utf8_continuation_bytes: u8, // Left to skip.
+}
+
+impl Console {
+    pub fn new(width: u32, height: u32) -> Self {
+        let width = width as usize;
+        let height = height as usize;
+        let cols = (width / CELL_WIDTH).max(1);
+        let rows = (height / CELL_HEIGHT).max(1);
+
+        let mut lines = VecDeque::new();
+        lines.push_back(Vec::with_capacity(cols));
+
+        Self {
+            width,
+            height,
+            cols,
+            rows,
+            lines,
+            cursor_col: 0,
+            view_offset: 0,
+            dirty: vec![false; rows],
+            shift_rows: 0,
+            full_redraw: true,
+            escape: Escape::None,
+            utf8_continuation_bytes: 0,
+        }
+    }
+
+    // The index of the line shown at the top of the screen.
+    fn first_visible_line(&self) -> usize {
+        self.lines.len().saturating_sub(self.rows) - self.view_offset
+    }
+
+    fn mark_line_dirty(&mut self, line_idx: usize) {
+        let first = self.first_visible_line();
+        if line_idx >= first && line_idx - first < self.rows {
+            self.dirty[line_idx - first] = true;
+        }
+    }
+
+    // Redraw everything, e.g. after a client has drawn over the console.
+    pub fn invalidate(&mut self) {
+        self.full_redraw = true;
+    }
+
+    pub fn scroll(&mut self, lines: i32) {
+        let max_offset = self.lines.len().saturating_sub(self.rows);
+        let view_offset = if lines == i32::MIN {
+            0
+        } else if lines >= 0 {
+            self.view_offset
+                .saturating_add(lines as usize)
+                .min(max_offset)
+        } else {
+            self.view_offset
+                .saturating_sub(lines.unsigned_abs() as usize)
+        };
+
+        if view_offset != self.view_offset {
+            self.view_offset = view_offset;
+            self.full_redraw = true;
+        }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        // Output brings the view back to the bottom.
+        self.scroll(i32::MIN);
+
+        let cursor_line = self.lines.len() - 1;
+        self.mark_line_dirty(cursor_line);
+
+        for &byte in bytes {
+            match self.escape {
+                Escape::None => {}
+                Escape::Esc => {
+                    self.escape = if byte == b'[' {
+                        Escape::Csi
+                    } else {
+                        Escape::None
+                    };
+                    continue;
+                }
+                Escape::Csi => {
+                    if (0x40..=0x7e).contains(&byte) {
+                        self.escape = Escape::None;
+                    }
+                    continue;
+                }
+            }
+
+            if self.utf8_continuation_bytes > 0 {
+                self.utf8_continuation_bytes -= 1;
+                if byte & 0xc0 == 0x80 {
+                    continue;
+                }
+                self.utf8_continuation_bytes = 0; // Malformed: not skipped.
+            }
+
+            match byte {
+                0x1b => self.escape = Escape::Esc,
+                b'\n' => self.new_line(),
+                b'\r' => self.cursor_col = 0,
+                b'\t' => {
+                    let spaces = TAB_WIDTH - (self.cursor_col % TAB_WIDTH);
+                    for _ in 0..spaces {
+                        self.put(b' ');
+                    }
+                }
+                8 | 0x7f => {
+                    // Erases, as sys-tty does on the serial console.
+                    if self.cursor_col > 0 {
+                        self.cursor_col -= 1;
+                        let line = self.lines.back_mut().unwrap();
+                        if self.cursor_col < line.len() {
+                            line[self.cursor_col] = b' ';
+                        }
+                    }
+                }
+                0..=0x1f => {}
+                0x20..=0x7e => self.put(byte),
+                _ => {
+                    // One cell per UTF-8 encoded character.
+                    self.utf8_continuation_bytes = match byte {
+                        0xc0..=0xdf => 1,
+                        0xe0..=0xef => 2,
+                        0xf0..=0xf7 => 3,
+                        _ => 0,
+                    };
+                    self.put(0x7f);
+                }
+            }
+        }
+
+        let cursor_line = self.lines.len() - 1;
+        self.mark_line_dirty(cursor_line);
+    }
+
+    fn put(&mut self, byte: u8) {
+        if self.cursor_col >= self.cols {
+            self.new_line();
+        }
+        let line = self.lines.back_mut().unwrap();
+        if line.len() <= self.cursor_col {
+            line.resize(self.cursor_col + 1, b' ');
+        }
+        line[self.cursor_col] = byte;
+        self.cursor_col += 1;
+    }
+
+    fn new_line(&mut self) {
+        // The cursor leaves the line.
+        let cursor_line = self.lines.len() - 1;
+        self.mark_line_dirty(cursor_line);
+
+        self.lines.push_back(Vec::with_capacity(self.cols));
+        self.cursor_col = 0;
+        if self.lines.len() > self.rows + SCROLLBACK_LINES {
+            self.lines.pop_front();
+        }
+
+        if self.lines.len() > self.rows {
+            // The screen scrolls up by one row.
+            self.dirty.rotate_left(1);
+            *self.dirty.last_mut().unwrap() = true;
+            self.shift_rows += 1;
+        } else {
+            let cursor_line = self.lines.len() - 1;
+            self.mark_line_dirty(cursor_line);
+        }
+    }
+
+    // Draws what has changed since the last call into @fb (rows of self.width
+    // pixels); returns the band of pixel rows drawn into, as (y, height).
+    pub fn render(&mut self, fb: &mut [u32]) -> Option<(u32, u32)> {
+        let text_height = self.rows * CELL_HEIGHT;
+
+        if self.shift_rows >= self.rows {
+            self.full_redraw = true;
+        }
+        if self.full_redraw {
+            fb[0..(self.width * self.height)].fill(BG_COLOR);
+            self.dirty.fill(true);
+        } else if self.shift_rows > 0 {
+            let shift = self.shift_rows * CELL_HEIGHT * self.width;
+            fb.copy_within(shift..(text_height * self.width), 0);
+        }
+
+        let mut first_dirty = None;
+        let mut last_dirty = 0;
+        for row in 0..self.rows {
+            if self.dirty[row] {
+                self.draw_row(fb, row);
+                self.dirty[row] = false;
+                first_dirty.get_or_insert(row);
+                last_dirty = row;
+            }
+        }
+
+        let result = if self.full_redraw {
+            Some((0, self.height as u32))
+        } else if self.shift_rows > 0 {
+            Some((0, text_height as u32))
+        } else {
+            first_dirty.map(|first| {
+                (
+                    (first * CELL_HEIGHT) as u32,
+                    ((last_dirty + 1 - first) * CELL_HEIGHT) as u32,
+                )
+            })
+        };
+
+        self.full_redraw = false;
+        self.shift_rows = 0;
+        result
+    }
+
+    fn draw_row(&self, fb: &mut [u32], row: usize) {
+        let line_idx = self.first_visible_line() + row;
+        let line = self
+            .lines
+            .get(line_idx)
+            .map(|l| l.as_slice())
+            .unwrap_or(&[]);
+        let cursor_col = if self.view_offset == 0 && line_idx == self.lines.len() - 1 {
+            Some(self.cursor_col.min(self.cols - 1))
+        } else {
+            None
+        };
+
+        for col in 0..self.cols {
+            let glyph = font::glyph(line.get(col).copied().unwrap_or(b' '));
+            let (fg, bg) = if cursor_col == Some(col) {
+                (BG_COLOR, FG_COLOR)
+            } else {
+                (FG_COLOR, BG_COLOR)
+            };
+
+            for y in 0..CELL_HEIGHT {
+                let bits = glyph[y / 2];
+                let start = (row * CELL_HEIGHT + y) * self.width + col * CELL_WIDTH;
+                for (x, pixel) in fb[start..(start + CELL_WIDTH)].iter_mut().enumerate() {
+                    *pixel = if bits & (1 << x) != 0 { fg } else { bg };
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// The display service: see moto_sys_io::display.
+
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use moto_ipc::sync::{LocalServer, LocalServerConnection, RequestHeader};
+use moto_sys::{ErrorCode, SysHandle};
+use moto_sys_io::display::*;
+use moto_virtio::GpuDevice;
+
+mod console;
+mod font;
+
+struct Display {
+    gpu: Arc<dyn GpuDevice>,
+    info: DisplayInfoV1,
+    pixels: &'static mut [u32],
+    console: console::Console,
+    owner: Option<SysHandle>, // The connection that acquired the display.
+}
+
+impl Display {
+    fn new() -> Option<Self> {
+        let gpu = moto_virtio::lsgpu().into_iter().next()?;
+        let (width, height) = gpu.display_size();
+        let stride = width * 4;
+        let framebuffer_size = moto_sys::align_up(
+            (stride as u64) * (height as u64),
+            moto_sys::sys_mem::PAGE_SIZE_SMALL,
+        );
+
+        // Clients are trusted with the pixels: the console has nothing to hide.
+        let (_handle, addr) =
+            match moto_sys::SysMem::shm_create(FRAMEBUFFER_SHM_NAME, framebuffer_size, "rw") {
+                Ok(res) => res,
+                Err(err) => {
+                    crate::moto_log!("display: failed to allocate the framebuffer: {:?}.", err);
+                    return None;
+                }
+            };
+        let pixels = unsafe {
+            core::slice::from_raw_parts_mut(
+                addr as usize as *mut u32,
+                (width as usize) * (height as usize),
+            )
+        };
+        pixels.fill(0);
+
+        if gpu.attach_framebuffer(addr, width, height).is_err() {
+            crate::moto_log!("display: failed to set up the framebuffer.");
+            return None;
+        }
+
+        Some(Self {
+            gpu,
+            info: DisplayInfoV1 {
+                width,
+                height,
+                stride,
+                pixel_format: PIXEL_FORMAT_XRGB8888,
+                framebuffer_size,
+                owner_pid: 0,
+            },
+            pixels,
+            console: console::Console::new(width, height),
+            owner: None,
+        })
+    }
+
+    fn render_console(&mut self) {
+        if self.owner.is_some() {
+            return;
+        }
+        if let Some((y, height)) = self.console.render(self.pixels) {
+            let _ = self.gpu.flush(0, y, self.info.width, height);
+        }
+    }
+
+    fn release(&mut self) {
+        self.owner = None;
+        self.info.owner_pid = 0;
+        self.console.invalidate();
+        self.render_console();
+    }
+
+    fn process_ipc(&mut self, conn: &mut LocalServerConnection) {
+        assert!(conn.connected());
+        if !conn.have_req() {
+            return;
+        }
+
+        let cmd = conn.req::<RequestHeader>().cmd;
+        let result = match cmd {
+            CMD_GET_INFO => {
+                conn.resp::<DisplayResponse>().info = self.info;
+                Ok(())
+            }
+            CMD_ACQUIRE => match self.owner {
+                Some(owner) if owner != conn.handle() => Err(ErrorCode::AlreadyInUse),
+                _ => {
+                    self.owner = Some(conn.handle());
+                    self.info.owner_pid = moto_sys::SysObj::get_pid(conn.handle()).unwrap_or(0);
+                    Ok(())
+                }
+            },
+            CMD_RELEASE => {
+                if self.owner == Some(conn.handle()) {
+                    self.release();
+                    Ok(())
+                } else {
+                    Err(ErrorCode::InvalidArgument)
+                }
+            }
+            CMD_FLUSH => {
+                if self.owner == Some(conn.handle()) {
+                    let req = conn.req::<DisplayRequest>();
+                    self.gpu
+                        .flush(req.x, req.y, req.width, req.height)
+                        .map_err(|_| ErrorCode::InternalError)
+                } else {
+                    Err(ErrorCode::NotAllowed)
+                }
+            }
+            CMD_CONSOLE_WRITE => {
+                let req = conn.req::<ConsoleWriteRequest>();
+                let len = req.len as usize;
+                if len > CONSOLE_WRITE_MAX {
+                    Err(ErrorCode::InvalidArgument)
+                } else {
+                    self.console.write(&req.data[0..len]);
+                    self.render_console();
+                    Ok(())
+                }
+            }
+            CMD_CONSOLE_SCROLL => {
+                self.console.scroll(conn.req::<DisplayRequest>().lines);
+                self.render_console();
+                Ok(())
+            }
+            _ => {
+                conn.disconnect();
+                return;
+            }
+        };
+
+        conn.resp::<DisplayResponse>().header.result = match result {
+            Ok(()) => ErrorCode::Ok.into(),
+            Err(err) => err.into(),
+        };
+        let _ = conn.finish_rpc();
+    }
+}
+
+static STARTED: AtomicU32 = AtomicU32::new(0);
+
+// Does nothing if there is no display. Returns once the service accepts
+// connections, so that sys-tty, which starts later, can find it.
+pub fn start() {
+    if moto_virtio::lsgpu().is_empty() {
+        return;
+    }
+
+    let _ = std::thread::spawn(display_service_thread);
+    while STARTED.load(Ordering::Acquire) == 0 {
+        moto_runtime::futex_wait(&STARTED, 0, None);
+    }
+}
+
+fn display_service_thread() {
+    let display = Display::new();
+    let service = display.as_ref().and_then(|_| {
+        LocalServer::new(URL_DISPLAY, moto_ipc::sync::ChannelSize::Small, 8, 2)
+            .inspect_err(|err| {
+                crate::moto_log!("display: error starting the service: {:?}.", err);
+            })
+            .ok()
+    });
+
+    STARTED.store(1, Ordering::Release);
+    moto_runtime::futex_wake(&STARTED);
+
+    let (Some(mut display), Some(mut service)) = (display, service) else {
+        return;
+    };
+
+    display.render_console();
+    loop {
+        match service.wait(SysHandle::NONE, &[]) {
+            Ok(wakers) => {
+                for waker in &wakers {
+                    if let Some(conn) = service.get_connection(*waker) {
+                        display.process_ipc(conn);
+                    }
+                }
+            }
+            Err(wakers) => assert_eq!(wakers.len(), 0),
+        }
+
+        // The owner may have gone away without releasing the display.
+        if let Some(owner) = display.owner {
+            if service
+                .get_connection(owner)
+                .map_or(true, |conn| !conn.connected())
+            {
+                display.release();
+            }
+        }
+    }
+}
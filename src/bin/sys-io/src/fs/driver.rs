@@ -5,51 +5,428 @@ use moto_ipc::sync::*;
 use moto_runtime::rt_api::fs::*;
 use moto_sys::SysHandle;
 
-use super::filesystem::fs;
+use super::filesystem::{fs, FileSystem};
+use super::path_walk::{self, PathWalk};
+use super::watch;
+
+// Unix-style access bits, as in each rwx triplet of a file mode.
+const ACCESS_READ: u32 = 4;
+const ACCESS_WRITE: u32 = 2;
+const ACCESS_EXEC: u32 = 1; // Directories: look up entries.
+
+// The user and the group of peers whose credentials can't be queried.
+const NOBODY: u32 = 65534;
+
+// An advisory lock (see FlockRequest) on a file.
+struct FileLock {
+    exclusive: bool,
+    owners: Vec<(u64, u64)>, // (conn_id, fd).
+}
+
+// Locks are shared by all connections, so they are keyed by File::unique_id().
+static FILE_LOCKS: std::sync::Mutex<std::collections::BTreeMap<u64, FileLock>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(1);
+
+fn lock_file(file_id: u64, owner: (u64, u64), exclusive: bool) -> Result<(), ErrorCode> {
+    let mut locks = FILE_LOCKS.lock().unwrap();
+    let lock = locks.entry(file_id).or_insert_with(|| FileLock {
+        exclusive,
+        owners: Vec::new(),
+    });
+
+    // Converting between shared and exclusive is fine if nobody else holds the lock.
+    let others = lock.owners.iter().any(|o| *o != owner);
+    if others && (exclusive || lock.exclusive) {
+        return Err(ErrorCode::NotReady);
+    }
+    lock.exclusive = exclusive;
+    if !lock.owners.contains(&owner) {
+        lock.owners.push(owner);
+    }
+    Ok(())
+}
+
+fn unlock_file(file_id: u64, owner: (u64, u64)) {
+    let mut locks = FILE_LOCKS.lock().unwrap();
+    if let Some(lock) = locks.get_mut(&file_id) {
+        lock.owners.retain(|o| *o != owner);
+        if lock.owners.is_empty() {
+            locks.remove(&file_id);
+        }
+    }
+}
+
+// An open file shared by fds on different connections of a process
+// (see FileDupRequest). The driver is single-threaded.
+#[derive(Clone)]
+struct SharedFile(std::rc::Rc<core::cell::RefCell<Box<dyn super::filesystem::File>>>);
+
+impl super::filesystem::File for SharedFile {
+    fn unique_id(&self) -> u64 {
+        self.0.borrow().unique_id()
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        self.0.borrow_mut().size()
+    }
+
+    fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        self.0.borrow_mut().write_offset(offset, buf)
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.0.borrow_mut().read_offset(offset, buf)
+    }
+
+    fn sync(&mut self, data_only: bool) -> Result<(), ErrorCode> {
+        self.0.borrow_mut().sync(data_only)
+    }
+
+    fn allocate(&mut self, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode> {
+        self.0.borrow_mut().allocate(offset, len, punch_hole)
+    }
+
+    fn set_direct(&mut self, direct: bool) -> Result<(), ErrorCode> {
+        self.0.borrow_mut().set_direct(direct)
+    }
+}
+
+// An exported fd (FileDupRequest::F_EXPORT) waiting to be imported.
+struct PendingDup {
+    pid: u64,
+    conn_id: u64,
+    file: SharedFile,
+    access: u32,
+    path: String,
+}
+
+static NEXT_DUP_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // Token => exported file.
+    static PENDING_DUPS: core::cell::RefCell<std::collections::HashMap<u64, PendingDup>> =
+        core::cell::RefCell::new(std::collections::HashMap::new());
+}
 
 struct PerConnectionData {
     next_fd: u64,
-    readdirs: std::collections::HashMap<u64, Box<dyn super::filesystem::DirectoryIter>>,
+    readdirs: std::collections::HashMap<u64, String>, // fd => dir path.
     files: std::collections::HashMap<u64, Box<dyn super::filesystem::File>>,
+    file_access: std::collections::HashMap<u64, u32>, // ACCESS_* granted at open time.
+    file_paths: std::collections::HashMap<u64, String>, // Resolved, for change notification.
+    direct_files: std::collections::HashSet<u64>,     // Opened with F_DIRECT.
+    shared_files: std::collections::HashMap<u64, SharedFile>, // Exported via CMD_FILE_DUP.
+
+    // The FS root of the peer's namespace; "/" for processes in the root
+    // namespace. None if the namespace could not be queried: such a peer
+    // can't resolve any path.
+    fs_root: Option<String>,
+    ns_max_caps: u64, // The caps processes in the peer's namespace are limited to.
+
+    conn_id: u64, // Identifies lock owners; unlike the handle, never re-used.
+    pid: u64,     // Zero if unknown.
+
+    // The credentials of the peer, checked against file modes.
+    uid: u32,
+    gid: u32,
+}
+
+// Nodes are (path in the root namespace, FILE_TYPE_*); the type is zero
+// for a last component that does not exist, as it may be created.
+impl PathWalk for PerConnectionData {
+    type Node = (String, u8);
+
+    fn root(&self) -> Result<Self::Node, ErrorCode> {
+        let fs_root = self.fs_root()?.trim_end_matches('/');
+        Ok((fs_root.to_owned(), FILE_TYPE_DIR))
+    }
+
+    fn is_dir(&self, node: &Self::Node) -> bool {
+        node.1 == FILE_TYPE_DIR
+    }
+
+    fn is_symlink(&self, node: &Self::Node) -> bool {
+        node.1 == FILE_TYPE_SYMLINK
+    }
+
+    fn find(&self, dir: &Self::Node, name: &str) -> Result<Self::Node, ErrorCode> {
+        let path = format!("{}/{}", dir.0, name);
+        let file_type = fs().lstat(path.as_str())?.file_type;
+        Ok((path, file_type))
+    }
+
+    fn symlink_target(&self, node: &Self::Node) -> Result<String, ErrorCode> {
+        fs().readlink(node.0.as_str())
+    }
+
+    fn not_found(&self, dir: &Self::Node, name: &str) -> Result<Self::Node, ErrorCode> {
+        Ok((format!("{}/{}", dir.0, name), 0))
+    }
 }
 
 impl PerConnectionData {
-    fn new() -> Self {
+    fn new(conn: &LocalServerConnection) -> Self {
+        let pid = moto_sys::SysObj::get_pid(conn.handle());
+        let (fs_root, ns_max_caps) = pid
+            .and_then(moto_sys::SysRay::query_namespace_v1)
+            .map(|ns| (Some(ns.fs_root().to_owned()), ns.max_caps))
+            .unwrap_or((None, 0));
+        let (uid, gid) = pid
+            .and_then(moto_sys::SysRay::query_credentials)
+            .unwrap_or((NOBODY, NOBODY));
+
         PerConnectionData {
             next_fd: 1,
             readdirs: std::collections::HashMap::new(),
             files: std::collections::HashMap::new(),
+            file_access: std::collections::HashMap::new(),
+            file_paths: std::collections::HashMap::new(),
+            direct_files: std::collections::HashSet::new(),
+            shared_files: std::collections::HashMap::new(),
+            fs_root,
+            ns_max_caps,
+            conn_id: NEXT_CONN_ID.fetch_add(1, Ordering::Relaxed),
+            pid: pid.unwrap_or(0),
+            uid,
+            gid,
+        }
+    }
+
+    fn get(conn: &mut LocalServerConnection) -> &mut Self {
+        if conn.extension_mut::<PerConnectionData>().is_none() {
+            let pcon = Box::new(PerConnectionData::new(conn));
+            conn.set_extension(pcon);
+        }
+        conn.extension_mut::<PerConnectionData>().unwrap()
+    }
+
+    // Converts a path as seen by the peer into the path in the root namespace.
+    fn resolve_path(&self, fname: &str) -> Result<String, ErrorCode> {
+        self.resolve(fname, true)
+    }
+
+    // Like resolve_path(), but a symlink at the last component is not
+    // followed (e.g. to unlink or rename the link itself).
+    fn resolve_path_nofollow(&self, fname: &str) -> Result<String, ErrorCode> {
+        self.resolve(fname, false)
+    }
+
+    // FS drivers follow symlinks in the root namespace, so for a namespaced
+    // peer the symlinks in @fname are followed here, against its FS root:
+    // absolute targets start over at the FS root, and ".." stops there.
+    // The resolved path has no symlinks, except at the last component if
+    // !follow_last, so the drivers don't follow any.
+    fn resolve(&self, fname: &str, follow_last: bool) -> Result<String, ErrorCode> {
+        let fs_root = self.fs_root()?;
+        if fs_root == "/" {
+            return Ok(fname.to_owned());
+        }
+
+        // Paths from the runtime are absolute and normalized, but we
+        // don't trust the peer to not escape its root.
+        if !fname.starts_with('/') || fname.split('/').any(|c| c == "..") {
+            return Err(ErrorCode::InvalidFilename);
+        }
+
+        path_walk::walk(self, fname, follow_last).map(|(path, _)| path)
+    }
+
+    fn is_root(&self) -> bool {
+        self.uid == moto_sys::caps::ROOT_UID
+    }
+
+    // Mounts and snapshots affect every namespace, so uid 0 (which
+    // namespaced processes inherit) is not enough: the peer must also be
+    // in the root namespace, or in one that keeps CAP_SYS.
+    fn may_mount(&self) -> bool {
+        self.is_root()
+            && (self.fs_root.as_deref() == Some("/")
+                || (self.ns_max_caps & moto_sys::caps::CAP_SYS) != 0)
+    }
+
+    fn fs_root(&self) -> Result<&str, ErrorCode> {
+        self.fs_root.as_deref().ok_or(ErrorCode::NotAllowed)
+    }
+
+    // Drive sources are paths, so they are resolved like any other path;
+    // other sources must be well-formed.
+    fn resolve_mount_source(&self, source: &str) -> Result<String, ErrorCode> {
+        if source.starts_with('/') {
+            return self.resolve_path(source);
+        }
+
+        let valid = if source == "tmpfs" {
+            true
+        } else if let Some(name) = source.strip_prefix("snapshot:") {
+            !name.is_empty() && !name.contains('/')
+        } else if let Some(tag) = source.strip_prefix("virtiofs:") {
+            !tag.is_empty()
+        } else {
+            false
+        };
+
+        if valid {
+            Ok(source.to_owned())
+        } else {
+            Err(ErrorCode::InvalidArgument)
+        }
+    }
+
+    // Whether the peer has @access (ACCESS_*) to a file with @attr: the owner,
+    // the group, or the other bits of the mode apply, as in Unix.
+    fn may_access(&self, attr: &FileAttrData, access: u32) -> bool {
+        if self.is_root() {
+            return true;
+        }
+
+        let bits = if attr.uid == self.uid {
+            attr.mode >> 6
+        } else if attr.gid == self.gid {
+            attr.mode >> 3
+        } else {
+            attr.mode
+        };
+        (bits & access) == access
+    }
+
+    // @path is resolved (see resolve_path()). As in Unix, reaching it needs
+    // search (exec) access to every directory above it, from the peer's FS root.
+    fn check_access(&self, path: &str, access: u32) -> Result<(), ErrorCode> {
+        if self.is_root() {
+            return Ok(());
+        }
+
+        let path = path.trim_end_matches('/');
+        let fs_root = self.fs_root()?.trim_end_matches('/');
+        if let Some(relative) = path.strip_prefix(fs_root) {
+            for (idx, _) in relative.match_indices('/') {
+                let dir = &path[..(fs_root.len() + idx)];
+                let dir = if dir.is_empty() { "/" } else { dir };
+                if !self.may_access(&fs().stat(dir)?, ACCESS_EXEC) {
+                    return Err(ErrorCode::NotAllowed);
+                }
+            }
+        }
+
+        let path = if path.is_empty() { "/" } else { path };
+        if self.may_access(&fs().stat(path)?, access) {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotAllowed)
+        }
+    }
+
+    // Adding or removing an entry needs write access to its directory.
+    fn check_parent_access(&self, path: &str) -> Result<(), ErrorCode> {
+        let parent = match path.trim_end_matches('/').rsplit_once('/') {
+            Some(("", _)) | None => "/",
+            Some((parent, _)) => parent,
+        };
+        self.check_access(parent, ACCESS_WRITE | ACCESS_EXEC)
+    }
+
+    // New files and directories belong to the peer that created them; if
+    // they can't (e.g. the peer is over its quota), they are removed.
+    fn set_owner(&self, path: &str) -> Result<(), ErrorCode> {
+        if self.is_root() {
+            return Ok(()); // Entries belong to root by default.
+        }
+        fs().chown(path, self.uid, self.gid).inspect_err(|_| {
+            let is_dir = fs()
+                .lstat(path)
+                .is_ok_and(|attr| attr.file_type == FILE_TYPE_DIR);
+            let _ = if is_dir {
+                fs().delete_dir(path)
+            } else {
+                fs().unlink(path)
+            };
+        })
+    }
+
+    // Limits the FILE_PERM_* bits of @attr to what the peer may do.
+    fn restrict_file_perm(&self, attr: &mut FileAttrData) {
+        if !self.may_access(attr, ACCESS_READ) {
+            attr.file_perm &= !FILE_PERM_READ;
+        }
+        if !self.may_access(attr, ACCESS_WRITE) {
+            attr.file_perm &= !FILE_PERM_WRITE;
         }
     }
 
-    fn add_readdir(&mut self, ptr: Box<dyn super::filesystem::DirectoryIter>) -> u64 {
+    fn add_readdir(&mut self, path: String) -> u64 {
         let fd = self.next_fd;
         self.next_fd += 1;
-        self.readdirs.insert(fd, ptr);
+        self.readdirs.insert(fd, path);
         fd
     }
 
-    fn get_readdir(&mut self, fd: u64) -> Option<&mut Box<dyn super::filesystem::DirectoryIter>> {
-        self.readdirs.get_mut(&fd)
+    fn get_readdir(&self, fd: u64) -> Option<&str> {
+        self.readdirs.get(&fd).map(|path| path.as_str())
     }
 
     fn remove_readdir(&mut self, fd: u64) {
         self.readdirs.remove(&fd);
     }
 
-    fn add_file(&mut self, ptr: Box<dyn super::filesystem::File>) -> u64 {
+    fn add_file(&mut self, ptr: Box<dyn super::filesystem::File>, access: u32, path: &str) -> u64 {
         let fd = self.next_fd;
         self.next_fd += 1;
         self.files.insert(fd, ptr);
+        self.file_access.insert(fd, access);
+        self.file_paths.insert(fd, path.to_owned());
         fd
     }
 
+    fn file_access(&self, fd: u64) -> u32 {
+        self.file_access.get(&fd).copied().unwrap_or(0)
+    }
+
+    // Reads and writes on direct fds must be aligned (see FileOpenRequest::F_DIRECT).
+    fn check_direct_io(&self, fd: u64, offset: u64, size: u64) -> Result<(), ErrorCode> {
+        const MASK: u64 = FileOpenRequest::DIRECT_IO_ALIGN - 1;
+        if self.direct_files.contains(&fd) && ((offset | size) & MASK) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        Ok(())
+    }
+
     fn get_file(&mut self, fd: u64) -> Option<&mut Box<dyn super::filesystem::File>> {
         self.files.get_mut(&fd)
     }
 
     fn remove_file(&mut self, fd: u64) {
-        self.files.remove(&fd);
+        if let Some(file) = self.files.remove(&fd) {
+            unlock_file(file.unique_id(), (self.conn_id, fd));
+        }
+        self.file_access.remove(&fd);
+        self.file_paths.remove(&fd);
+        self.direct_files.remove(&fd);
+        self.shared_files.remove(&fd);
+    }
+
+    // The file behind @fd, made shareable with other connections.
+    fn share_file(&mut self, fd: u64) -> Option<SharedFile> {
+        if let Some(shared) = self.shared_files.get(&fd) {
+            return Some(shared.clone());
+        }
+        let file = self.files.remove(&fd)?;
+        let shared = SharedFile(std::rc::Rc::new(core::cell::RefCell::new(file)));
+        self.files.insert(fd, Box::new(shared.clone()));
+        self.shared_files.insert(fd, shared.clone());
+        Some(shared)
+    }
+}
+
+// Disconnected (e.g. exited) peers don't leave their locks behind.
+impl Drop for PerConnectionData {
+    fn drop(&mut self) {
+        for (fd, file) in &self.files {
+            unlock_file(file.unique_id(), (self.conn_id, *fd));
+        }
+        watch::remove_watcher(self.conn_id);
+        PENDING_DUPS.with_borrow_mut(|dups| dups.retain(|_, dup| dup.conn_id != self.conn_id));
     }
 }
 
@@ -113,28 +490,53 @@ impl Driver {
                     continue;
                 }
 
+                // Page cache usage is accounted to the peer.
+                super::page_cache::set_context(PerConnectionData::get(conn).pid, false);
+
                 let raw_channel = conn.raw_channel();
                 unsafe {
                     let cmd = raw_channel.get::<RequestHeader>().cmd;
 
                     let result = match cmd {
-                        CMD_STAT => Self::on_stat(raw_channel),
+                        CMD_STAT => Self::on_stat(conn, raw_channel),
+                        CMD_STATFS => Self::on_statfs(conn, raw_channel),
                         CMD_FILE_OPEN => Self::on_file_open(conn, raw_channel),
                         CMD_FILE_READ => Self::on_file_read(conn, raw_channel),
                         CMD_FILE_WRITE => Self::on_file_write(conn, raw_channel),
                         CMD_READDIR => Self::on_readdir(conn, raw_channel),
                         CMD_READDIR_NEXT => Self::on_readdir_next(conn, raw_channel),
                         CMD_CLOSE_FD => Self::on_close_fd(conn, raw_channel),
-                        CMD_MKDIR => Self::on_mkdir(raw_channel),
-                        CMD_UNLINK => Self::on_unlink(raw_channel),
-                        CMD_RENAME => Self::on_rename(raw_channel),
+                        CMD_MKDIR => Self::on_mkdir(conn, raw_channel),
+                        CMD_UNLINK => Self::on_unlink(conn, raw_channel),
+                        CMD_RENAME => Self::on_rename(conn, raw_channel),
+                        CMD_SYMLINK => Self::on_symlink(conn, raw_channel),
+                        CMD_READLINK => Self::on_readlink(conn, raw_channel),
+                        CMD_LINK => Self::on_link(conn, raw_channel),
+                        CMD_CHMOD => Self::on_chmod(conn, raw_channel),
+                        CMD_CHOWN => Self::on_chown(conn, raw_channel),
+                        CMD_FLOCK => Self::on_flock(conn, raw_channel),
+                        CMD_FILE_SYNC => Self::on_file_sync(conn, raw_channel),
+                        CMD_FILE_ALLOCATE => Self::on_file_allocate(conn, raw_channel),
+                        CMD_FILE_DUP => Self::on_file_dup(conn, raw_channel),
+                        CMD_XATTR_GET | CMD_XATTR_SET | CMD_XATTR_LIST | CMD_XATTR_REMOVE => {
+                            Self::on_xattr(conn, raw_channel)
+                        }
+                        CMD_QUOTA_GET | CMD_QUOTA_SET => Self::on_quota(conn, raw_channel),
+                        CMD_MOUNT | CMD_UNMOUNT => Self::on_mount(conn, raw_channel),
+                        CMD_SNAPSHOT_CREATE | CMD_SNAPSHOT_DELETE | CMD_SNAPSHOT_LIST => {
+                            Self::on_snapshot(conn, raw_channel)
+                        }
+                        CMD_WATCH_ADD => Self::on_watch_add(conn, raw_channel),
+                        CMD_WATCH_REMOVE => Self::on_watch_remove(conn, raw_channel),
+                        CMD_WATCH_NEXT => Self::on_watch_next(conn, raw_channel),
                         _ => Err(ErrorCode::InvalidArgument),
                     };
 
                     if let Err(err) = result {
                         #[cfg(debug_assertions)]
-                        if cmd != CMD_STAT && cmd != 0 {
-                            // CMD_STAT is often used to probe, so don't spam the log.
+                        if cmd != CMD_STAT && cmd != 0 && err != ErrorCode::NotReady {
+                            // CMD_STAT is often used to probe, so don't spam the log;
+                            // NotReady is how locks and watches are polled.
                             crate::moto_log!("fs::driver: command {} failed with {:?}", cmd, err);
                         }
 
@@ -157,7 +559,10 @@ impl Driver {
         }
     }
 
-    unsafe fn on_mkdir(raw_channel: RawChannel) -> Result<(), ErrorCode> {
+    unsafe fn on_mkdir(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
         let req = raw_channel.get::<MkdirRequest>();
         assert_eq!(req.header.cmd, CMD_MKDIR);
 
@@ -179,14 +584,22 @@ impl Driver {
             }
         };
 
-        super::filesystem::fs().mkdir(fname)?;
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path_nofollow(fname)?;
+        pcon.check_parent_access(fname.as_str())?;
+        super::filesystem::fs().mkdir(fname.as_str())?;
+        pcon.set_owner(fname.as_str())?;
+        watch::notify(fname.as_str(), WATCH_EVENT_CREATE);
 
         let resp = raw_channel.get_mut::<CloseFdResponse>();
         resp.header.result = 0;
         Ok(())
     }
 
-    unsafe fn on_unlink(raw_channel: RawChannel) -> Result<(), ErrorCode> {
+    unsafe fn on_unlink(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
         let req = raw_channel.get::<UnlinkRequest>();
         assert_eq!(req.header.cmd, CMD_UNLINK);
 
@@ -208,19 +621,27 @@ impl Driver {
             }
         };
 
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path_nofollow(fname)?;
+        let fname = fname.as_str();
+        pcon.check_parent_access(fname)?;
         match req.header.flags {
             F_UNLINK_FILE => super::filesystem::fs().unlink(fname)?,
             F_UNLINK_DIR => super::filesystem::fs().delete_dir(fname)?,
             F_UNLINK_DIR_ALL => super::filesystem::fs().delete_dir_all(fname)?,
             _ => return Err(ErrorCode::InvalidArgument),
         }
+        watch::notify(fname, WATCH_EVENT_DELETE);
 
         let resp = raw_channel.get_mut::<UnlinkResponse>();
         resp.header.result = 0;
         Ok(())
     }
 
-    unsafe fn on_rename(raw_channel: RawChannel) -> Result<(), ErrorCode> {
+    unsafe fn on_rename(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
         let req = raw_channel.get::<RenameRequest>();
         assert_eq!(req.header.cmd, CMD_RENAME);
 
@@ -228,17 +649,180 @@ impl Driver {
             return Err(ErrorCode::InvalidArgument);
         }
 
-        let old = req.old(&raw_channel)?;
-        let new = req.new(&raw_channel)?;
+        let pcon = PerConnectionData::get(conn);
+        let old = pcon.resolve_path_nofollow(req.old(&raw_channel)?)?;
+        let new = pcon.resolve_path_nofollow(req.new(&raw_channel)?)?;
+        pcon.check_parent_access(old.as_str())?;
+        pcon.check_parent_access(new.as_str())?;
 
         log::debug!("driver: rename: {} -> {}", old, new);
 
-        super::filesystem::fs().rename(old, new)?;
+        super::filesystem::fs().rename(old.as_str(), new.as_str())?;
+        watch::notify(old.as_str(), WATCH_EVENT_RENAME_FROM);
+        watch::notify(new.as_str(), WATCH_EVENT_RENAME_TO);
         let resp = raw_channel.get_mut::<RenameResponse>();
         resp.header.result = 0;
         Ok(())
     }
 
+    unsafe fn on_symlink(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<SymlinkRequest>();
+        assert_eq!(req.header.cmd, CMD_SYMLINK);
+
+        if (req.header.ver != 0) || (req.parent_fd != 0) || (req.header.flags != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        // The target is stored as is: it is resolved when the link is followed,
+        // against the FS root of the follower (see PerConnectionData::resolve()).
+        let pcon = PerConnectionData::get(conn);
+        let target = req.old(&raw_channel)?;
+        let link = pcon.resolve_path_nofollow(req.new(&raw_channel)?)?;
+        pcon.check_parent_access(link.as_str())?;
+
+        log::debug!("driver: symlink: {} -> {}", link, target);
+
+        super::filesystem::fs().symlink(target, link.as_str())?;
+        watch::notify(link.as_str(), WATCH_EVENT_CREATE);
+        let resp = raw_channel.get_mut::<SymlinkResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_link(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<LinkRequest>();
+        assert_eq!(req.header.cmd, CMD_LINK);
+
+        if (req.header.ver != 0) || (req.parent_fd != 0) || (req.header.flags != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        let existing = pcon.resolve_path_nofollow(req.old(&raw_channel)?)?;
+        let link = pcon.resolve_path_nofollow(req.new(&raw_channel)?)?;
+        pcon.check_parent_access(link.as_str())?;
+
+        log::debug!("driver: link: {} -> {}", link, existing);
+
+        super::filesystem::fs().link(existing.as_str(), link.as_str())?;
+        watch::notify(link.as_str(), WATCH_EVENT_CREATE);
+        let resp = raw_channel.get_mut::<LinkResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_chmod(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<ChmodRequest>();
+        assert_eq!(req.header.cmd, CMD_CHMOD);
+
+        let mode = req.header.flags;
+        if (req.header.ver != 0) || (req.parent_fd != 0) || (mode & !FILE_MODE_MASK != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let fname = raw_channel.get_bytes(&req.fname, req.fname_size as usize)?;
+        let fname = core::str::from_utf8(fname).map_err(|_| ErrorCode::InvalidFilename)?;
+
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path(fname)?;
+        // Only the owner (or root) can change the mode.
+        if !pcon.is_root() && fs().stat(fname.as_str())?.uid != pcon.uid {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        log::debug!("driver: chmod: {} {:o}", fname, mode);
+
+        fs().chmod(fname.as_str(), mode as u16)?;
+        watch::notify(fname.as_str(), WATCH_EVENT_ATTRIB);
+        let resp = raw_channel.get_mut::<ChmodResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_chown(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<ChownRequest>();
+        assert_eq!(req.header.cmd, CMD_CHOWN);
+
+        if (req.header.ver != 0) || (req.parent_fd != 0) || (req.header.flags != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let fname = raw_channel.get_bytes(&req.fname, req.fname_size as usize)?;
+        let fname = core::str::from_utf8(fname).map_err(|_| ErrorCode::InvalidFilename)?;
+
+        let pcon = PerConnectionData::get(conn);
+        if !pcon.is_root() {
+            return Err(ErrorCode::NotAllowed);
+        }
+        let fname = pcon.resolve_path(fname)?;
+
+        let attr = fs().stat(fname.as_str())?;
+        let uid = match req.uid {
+            ChownRequest::CHOWN_UNCHANGED => attr.uid,
+            uid => uid,
+        };
+        let gid = match req.gid {
+            ChownRequest::CHOWN_UNCHANGED => attr.gid,
+            gid => gid,
+        };
+
+        log::debug!("driver: chown: {} {}:{}", fname, uid, gid);
+
+        fs().chown(fname.as_str(), uid, gid)?;
+        watch::notify(fname.as_str(), WATCH_EVENT_ATTRIB);
+        let resp = raw_channel.get_mut::<ChownResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_readlink(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<ReadLinkRequest>();
+        assert_eq!(req.header.cmd, CMD_READLINK);
+
+        if (req.header.ver != 0) || (req.header.flags != 0) || (req.parent_fd != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let fname_bytes = match raw_channel.get_bytes(&req.fname, req.fname_size as usize) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return Err(ErrorCode::InvalidFilename);
+            }
+        };
+
+        let fname = match core::str::from_utf8(fname_bytes) {
+            Ok(fname) => fname,
+            Err(_) => {
+                return Err(ErrorCode::InvalidFilename);
+            }
+        };
+
+        // As stored: absolute targets are paths in the peer's namespace.
+        let fname = PerConnectionData::get(conn).resolve_path_nofollow(fname)?;
+        let target = fs().readlink(fname.as_str())?;
+
+        let resp = raw_channel.get_mut::<ReadLinkResponse>();
+        resp.header.result = 0;
+        resp.target_size = target.len() as u16;
+        raw_channel.put_bytes(target.as_bytes(), &mut resp.target)?;
+        Ok(())
+    }
+
     unsafe fn on_readdir(
         conn: &mut LocalServerConnection,
         raw_channel: RawChannel,
@@ -264,19 +848,14 @@ impl Driver {
             }
         };
 
-        let iter = fs().iter(fname)?;
-        let pcon = {
-            match conn.extension_mut::<PerConnectionData>() {
-                Some(pcon) => pcon,
-                None => {
-                    let pcon = Box::new(PerConnectionData::new());
-                    conn.set_extension(pcon);
-                    conn.extension_mut::<PerConnectionData>().unwrap()
-                }
-            }
-        };
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path(fname)?;
+        pcon.check_access(fname.as_str(), ACCESS_READ)?;
+        // Listings are read in batches by on_readdir_next(), each from its
+        // own iterator; this only validates the directory.
+        drop(fs().iter(fname.as_str(), 0)?);
 
-        let readdir_fd = pcon.add_readdir(iter);
+        let readdir_fd = pcon.add_readdir(fname);
 
         let resp = raw_channel.get_mut::<ReadDirResponse>();
         resp.header.result = 0;
@@ -304,52 +883,68 @@ impl Driver {
             }
         };
 
-        let iter = pcon.get_readdir(req.readdir_fd);
-        if iter.is_none() {
-            return Err(ErrorCode::InternalError);
-        }
-        let iter = iter.unwrap();
-
-        let item = iter.next();
+        let path = match pcon.get_readdir(req.readdir_fd) {
+            Some(path) => path,
+            None => return Err(ErrorCode::InternalError),
+        };
+        let cursor = req.cursor;
+        let iter = fs().iter(path, cursor)?;
 
         let resp = raw_channel.get_mut::<ReadDirNextResponse>();
         resp.header.result = 0;
         resp.header.ver = 0;
+        resp.entries = 0;
+        resp.cursor = cursor;
+
+        // Pack as many entries as fit; the rest go into the next batch.
+        let channel_end = (raw_channel.get::<u8>() as *const u8 as usize) + raw_channel.size();
+        let mut addr = resp.dir_entries.as_mut_ptr() as usize;
+        for item in iter {
+            let fname = item.filename().as_bytes();
+            let packed_size = DirEntryData::packed_size(fname.len());
+            if addr + packed_size > channel_end {
+                break;
+            }
 
-        if item.is_none() {
-            resp.entries = 0;
-            return Ok(());
-        }
+            let (file_type, size) = {
+                if item.is_directory() {
+                    (moto_runtime::rt_api::fs::FILE_TYPE_DIR, 0)
+                } else if item.is_symlink() {
+                    (moto_runtime::rt_api::fs::FILE_TYPE_SYMLINK, item.size()?)
+                } else {
+                    (moto_runtime::rt_api::fs::FILE_TYPE_FILE, item.size()?)
+                }
+            };
+            let attr = moto_runtime::rt_api::fs::FileAttrData {
+                version: 0,
+                self_size: core::mem::size_of::<moto_runtime::rt_api::fs::FileAttrData>() as u16,
+                file_perm: 0,
+                file_type,
+                reserved: 0,
+                size,
+                created: 0,
+                accessed: 0,
+                modified: 0,
+                uid: 0,
+                gid: 0,
+                mode: 0,
+                reserved_2: 0,
+            };
 
-        let item = item.unwrap_unchecked();
-        let (file_type, size) = {
-            if item.is_directory() {
-                (moto_runtime::rt_api::fs::FILE_TYPE_DIR, 0)
-            } else {
-                (moto_runtime::rt_api::fs::FILE_TYPE_FILE, item.size()?)
-            }
-        };
-        let attr = moto_runtime::rt_api::fs::FileAttrData {
-            version: 0,
-            self_size: core::mem::size_of::<moto_runtime::rt_api::fs::FileAttrData>() as u16,
-            file_perm: 0,
-            file_type: file_type,
-            reserved: 0,
-            size: size,
-            created: 0,
-            accessed: 0,
-            modified: 0,
-        };
-
-        resp.entries = 1;
-        let dir_entry = &mut raw_channel.get_at_mut(&mut resp.dir_entries, 1)?[0];
-        dir_entry.version = 0;
-        dir_entry.self_size = core::mem::size_of::<moto_runtime::rt_api::fs::DirEntryData>() as u16;
-        dir_entry.reserved = 0;
-        dir_entry.attr = attr;
-        dir_entry.fd = 0;
-        dir_entry.fname_size = item.filename().as_bytes().len() as u16;
-        raw_channel.put_bytes(item.filename().as_bytes(), &mut dir_entry.fname)?;
+            let dir_entry =
+                &mut raw_channel.get_at_mut(&mut *(addr as *mut [DirEntryData; 0]), 1)?[0];
+            dir_entry.version = 0;
+            dir_entry.self_size = core::mem::size_of::<DirEntryData>() as u16;
+            dir_entry.reserved = 0;
+            dir_entry.attr = attr;
+            dir_entry.fd = 0;
+            dir_entry.fname_size = fname.len() as u16;
+            raw_channel.put_bytes(fname, &mut dir_entry.fname)?;
+
+            resp.entries += 1;
+            resp.cursor = item.cursor();
+            addr += packed_size;
+        }
 
         Ok(())
     }
@@ -379,17 +974,44 @@ impl Driver {
             }
         };
 
-        let mut flags = req.header.flags;
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path(fname)?;
+        let fname = fname.as_str();
+
+        let mut flags = req.header.flags & !FileOpenRequest::F_DIRECT;
         if flags & FileOpenRequest::F_CREATE_NEW == FileOpenRequest::F_CREATE_NEW {
+            pcon.check_parent_access(fname)?;
             fs().create_file(fname)?;
+            pcon.set_owner(fname)?;
+            watch::notify(fname, WATCH_EVENT_CREATE);
             flags ^= FileOpenRequest::F_CREATE_NEW;
         }
 
         if flags
             == (FileOpenRequest::F_CREATE | FileOpenRequest::F_TRUNCATE | FileOpenRequest::F_WRITE)
         {
+            // Truncation re-creates the file, so its owner and mode are restored.
+            let prev = fs().stat(fname).ok();
+            match prev.as_ref() {
+                Some(attr) if !pcon.may_access(attr, ACCESS_WRITE) => {
+                    return Err(ErrorCode::NotAllowed)
+                }
+                Some(_) => {}
+                None => pcon.check_parent_access(fname)?,
+            }
             fs().unlink(fname).ok();
             fs().create_file(fname)?;
+            match prev {
+                Some(attr) => {
+                    fs().chown(fname, attr.uid, attr.gid)?;
+                    fs().chmod(fname, attr.mode as u16)?;
+                    watch::notify(fname, WATCH_EVENT_MODIFY);
+                }
+                None => {
+                    pcon.set_owner(fname)?;
+                    watch::notify(fname, WATCH_EVENT_CREATE);
+                }
+            }
             flags = FileOpenRequest::F_WRITE;
         }
 
@@ -408,20 +1030,26 @@ impl Driver {
             return Err(ErrorCode::NotImplemented);
         }
 
-        let mut file = fs().open_file(fname)?;
-        let pcon = {
-            match conn.extension_mut::<PerConnectionData>() {
-                Some(pcon) => pcon,
-                None => {
-                    let pcon = Box::new(PerConnectionData::new());
-                    conn.set_extension(pcon);
-                    conn.extension_mut::<PerConnectionData>().unwrap()
-                }
-            }
+        // Root may do anything with its fds, as before credentials existed.
+        let access = if pcon.is_root() {
+            ACCESS_READ | ACCESS_WRITE
+        } else if flags == FileOpenRequest::F_READ {
+            ACCESS_READ
+        } else {
+            ACCESS_WRITE
         };
+        pcon.check_access(fname, access)?;
+        let mut file = fs().open_file(fname)?;
+        let direct = req.header.flags & FileOpenRequest::F_DIRECT != 0;
+        if direct {
+            file.set_direct(true)?;
+        }
 
         let file_sz = file.size()?;
-        let fd = pcon.add_file(file);
+        let fd = pcon.add_file(file, access, fname);
+        if direct {
+            pcon.direct_files.insert(fd);
+        }
 
         let resp = raw_channel.get_mut::<FileOpenResponse>();
         resp.header.result = 0;
@@ -449,12 +1077,21 @@ impl Driver {
             }
         };
 
+        if pcon.file_access(req.fd) & ACCESS_READ == 0 {
+            return Err(ErrorCode::NotAllowed);
+        }
+        pcon.check_direct_io(req.fd, req.offset, req.max_bytes as u64)?;
+        let direct = pcon.direct_files.contains(&req.fd);
+        super::page_cache::set_context(pcon.pid, direct);
         if let Some(file) = pcon.get_file(req.fd) {
             let resp = raw_channel.get_mut::<FileReadResponse>();
             resp.header.result = 0;
 
-            let buf_size = (req.max_bytes as usize)
+            let mut buf_size = (req.max_bytes as usize)
                 .min(raw_channel.size() - core::mem::size_of::<FileReadResponse>());
+            if direct {
+                buf_size &= !(FileOpenRequest::DIRECT_IO_ALIGN as usize - 1);
+            }
 
             let buf = raw_channel.get_bytes_mut(&mut &mut resp.data, buf_size)?;
             let bytes_read = file.read_offset(req.offset, buf)?;
@@ -489,6 +1126,11 @@ impl Driver {
             }
         };
 
+        if pcon.file_access(req.fd) & ACCESS_WRITE == 0 {
+            return Err(ErrorCode::NotAllowed);
+        }
+        pcon.check_direct_io(req.fd, req.offset, req.size as u64)?;
+        super::page_cache::set_context(pcon.pid, pcon.direct_files.contains(&req.fd));
         let p_file = pcon.get_file(req.fd);
         if p_file.is_none() {
             return Err(ErrorCode::InternalError);
@@ -497,6 +1139,9 @@ impl Driver {
         let file = p_file.unwrap();
         let buf = unsafe { core::slice::from_raw_parts(&req.data as *const u8, req.size as usize) };
         let written = file.write_offset(req.offset, buf)?;
+        if let Some(path) = pcon.file_paths.get(&req.fd) {
+            watch::notify(path.as_str(), WATCH_EVENT_MODIFY);
+        }
 
         let resp = raw_channel.get_mut::<FileWriteResponse>();
         resp.header.result = 0;
@@ -524,12 +1169,10 @@ impl Driver {
         };
 
         if req.header.flags == CloseFdRequest::F_READDIR {
-            let iter = pcon.get_readdir(req.fd);
-            if iter.is_none() {
+            if pcon.get_readdir(req.fd).is_none() {
                 return Err(ErrorCode::InternalError);
             }
 
-            drop(iter);
             pcon.remove_readdir(req.fd);
         } else if req.header.flags == CloseFdRequest::F_FILE {
             let file = pcon.get_file(req.fd);
@@ -548,11 +1191,449 @@ impl Driver {
         Ok(())
     }
 
-    unsafe fn on_stat(raw_channel: RawChannel) -> Result<(), ErrorCode> {
+    unsafe fn on_flock(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<FlockRequest>();
+        assert_eq!(req.header.cmd, CMD_FLOCK);
+
+        if req.header.ver != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        let owner = (pcon.conn_id, req.fd);
+        let file_id = match pcon.get_file(req.fd) {
+            Some(file) => file.unique_id(),
+            None => return Err(ErrorCode::BadHandle),
+        };
+
+        match req.header.flags {
+            FlockRequest::F_LOCK_SHARED => lock_file(file_id, owner, false)?,
+            FlockRequest::F_LOCK_EXCLUSIVE => lock_file(file_id, owner, true)?,
+            FlockRequest::F_UNLOCK => unlock_file(file_id, owner),
+            _ => return Err(ErrorCode::InvalidArgument),
+        }
+
+        let resp = raw_channel.get_mut::<FlockResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_file_sync(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<FileSyncRequest>();
+        assert_eq!(req.header.cmd, CMD_FILE_SYNC);
+
+        if (req.header.ver != 0) || (req.header.flags & !FileSyncRequest::F_DATA_ONLY != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        // As in Unix, read-only fds can be synced too.
+        let pcon = PerConnectionData::get(conn);
+        let file = pcon.get_file(req.fd).ok_or(ErrorCode::BadHandle)?;
+        file.sync(req.header.flags == FileSyncRequest::F_DATA_ONLY)?;
+
+        let resp = raw_channel.get_mut::<FileSyncResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_file_allocate(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<FileAllocateRequest>();
+        assert_eq!(req.header.cmd, CMD_FILE_ALLOCATE);
+
+        if (req.header.ver != 0) || (req.header.flags & !FileAllocateRequest::F_PUNCH_HOLE != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if req.offset.checked_add(req.len).is_none() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        if pcon.file_access(req.fd) & ACCESS_WRITE == 0 {
+            return Err(ErrorCode::NotAllowed);
+        }
+        let file = pcon.get_file(req.fd).ok_or(ErrorCode::BadHandle)?;
+        file.allocate(
+            req.offset,
+            req.len,
+            req.header.flags == FileAllocateRequest::F_PUNCH_HOLE,
+        )?;
+        if let Some(path) = pcon.file_paths.get(&req.fd) {
+            watch::notify(path.as_str(), WATCH_EVENT_MODIFY);
+        }
+
+        let resp = raw_channel.get_mut::<FileAllocateResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_file_dup(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<FileDupRequest>();
+        assert_eq!(req.header.cmd, CMD_FILE_DUP);
+
+        if req.header.ver != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        // Both connections must be known to belong to the same process.
+        if pcon.pid == 0 {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        let (fd, size) = match req.header.flags {
+            FileDupRequest::F_EXPORT => {
+                let file = pcon.share_file(req.fd).ok_or(ErrorCode::BadHandle)?;
+                let token = NEXT_DUP_TOKEN.fetch_add(1, Ordering::Relaxed);
+                let dup = PendingDup {
+                    pid: pcon.pid,
+                    conn_id: pcon.conn_id,
+                    file,
+                    access: pcon.file_access(req.fd),
+                    path: pcon.file_paths.get(&req.fd).cloned().unwrap_or_default(),
+                };
+                PENDING_DUPS.with_borrow_mut(|dups| dups.insert(token, dup));
+                (token, 0)
+            }
+            FileDupRequest::F_IMPORT => {
+                let mut dup = PENDING_DUPS
+                    .with_borrow_mut(|dups| match dups.get(&req.fd) {
+                        Some(dup) if dup.pid == pcon.pid => dups.remove(&req.fd),
+                        _ => None,
+                    })
+                    .ok_or(ErrorCode::BadHandle)?;
+                let size = super::filesystem::File::size(&mut dup.file)?;
+                let fd = pcon.add_file(Box::new(dup.file), dup.access, dup.path.as_str());
+                (fd, size)
+            }
+            _ => return Err(ErrorCode::InvalidArgument),
+        };
+
+        let resp = raw_channel.get_mut::<FileDupResponse>();
+        resp.header.result = 0;
+        resp.fd = fd;
+        resp.size = size;
+
+        Ok(())
+    }
+
+    unsafe fn on_xattr(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<XattrRequest>();
+        let cmd = req.header.cmd;
+
+        let flags_ok = match cmd {
+            CMD_XATTR_SET => {
+                req.header.flags & !(XattrRequest::F_CREATE | XattrRequest::F_REPLACE) == 0
+                    && req.header.flags != (XattrRequest::F_CREATE | XattrRequest::F_REPLACE)
+            }
+            _ => req.header.flags == 0,
+        };
+        if (req.header.ver != 0) || (req.parent_fd != 0) || !flags_ok {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if (cmd == CMD_XATTR_LIST && req.name_size != 0)
+            || (cmd != CMD_XATTR_SET && req.value_size != 0)
+            || (req.name_size as usize > MAX_XATTR_NAME)
+            || (req.value_size as usize > MAX_XATTR_VALUE)
+        {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let name = req.name(&raw_channel)?;
+        let namespace = XATTR_NAMESPACES.iter().find(|ns| name.starts_with(*ns));
+        if cmd != CMD_XATTR_LIST && namespace.map_or(true, |ns| name.len() == ns.len()) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path(req.fname(&raw_channel)?)?;
+        let is_root = pcon.is_root();
+
+        // Reading needs read access; writing "user." attributes needs write
+        // access; "security." attributes are written, and "trusted." ones
+        // are accessed at all, by root only.
+        let writes = cmd == CMD_XATTR_SET || cmd == CMD_XATTR_REMOVE;
+        pcon.check_access(fname.as_str(), ACCESS_READ)?;
+        match namespace.copied() {
+            Some("trusted.") | Some("security.") if writes && !is_root => {
+                return Err(ErrorCode::NotAllowed)
+            }
+            Some("trusted.") if !is_root => return Err(ErrorCode::NotAllowed),
+            Some("user.") if writes => pcon.check_access(fname.as_str(), ACCESS_WRITE)?,
+            _ => {}
+        }
+
+        log::debug!("driver: xattr {}: {} {}", cmd, fname, name);
+
+        let data = match cmd {
+            CMD_XATTR_GET => fs().get_xattr(fname.as_str(), name)?,
+            CMD_XATTR_LIST => {
+                let mut data = Vec::new();
+                for name in fs().list_xattrs(fname.as_str())? {
+                    if name.starts_with("trusted.") && !is_root {
+                        continue;
+                    }
+                    data.extend_from_slice(name.as_bytes());
+                    data.push(0);
+                }
+                data
+            }
+            CMD_XATTR_SET => {
+                let value = req.value(&raw_channel)?;
+                if req.header.flags != 0 {
+                    let exists = match fs().get_xattr(fname.as_str(), name) {
+                        Ok(_) => true,
+                        Err(ErrorCode::NotFound) => false,
+                        Err(err) => return Err(err),
+                    };
+                    if exists && req.header.flags == XattrRequest::F_CREATE {
+                        return Err(ErrorCode::AlreadyInUse);
+                    }
+                    if !exists && req.header.flags == XattrRequest::F_REPLACE {
+                        return Err(ErrorCode::NotFound);
+                    }
+                }
+                fs().set_xattr(fname.as_str(), name, value)?;
+                watch::notify(fname.as_str(), WATCH_EVENT_ATTRIB);
+                Vec::new()
+            }
+            CMD_XATTR_REMOVE => {
+                fs().remove_xattr(fname.as_str(), name)?;
+                watch::notify(fname.as_str(), WATCH_EVENT_ATTRIB);
+                Vec::new()
+            }
+            _ => unreachable!(),
+        };
+
+        if data.len() > u16::MAX as usize {
+            return Err(ErrorCode::BufferFull);
+        }
+        let resp = raw_channel.get_mut::<XattrResponse>();
+        raw_channel
+            .put_bytes(data.as_slice(), &mut resp.data)
+            .map_err(|_| ErrorCode::BufferFull)?;
+        resp.data_size = data.len() as u16;
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_quota(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<QuotaRequest>();
+        let cmd = req.header.cmd;
+
+        if (req.header.ver != 0)
+            || (req.parent_fd != 0)
+            || (req.header.flags & !QuotaRequest::F_USER != 0)
+        {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let uid = if req.header.flags == QuotaRequest::F_USER {
+            Some(req.uid)
+        } else {
+            None
+        };
+
+        let fname = raw_channel.get_bytes(&req.fname, req.fname_size as usize)?;
+        let fname = core::str::from_utf8(fname).map_err(|_| ErrorCode::InvalidFilename)?;
+
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path(fname)?;
+
+        // Root sets quotas; users may see their own, and those of the
+        // directories they can read.
+        match (cmd, uid) {
+            _ if pcon.is_root() => {}
+            (CMD_QUOTA_GET, Some(uid)) if uid == pcon.uid => {}
+            (CMD_QUOTA_GET, None) => pcon.check_access(fname.as_str(), ACCESS_READ)?,
+            _ => return Err(ErrorCode::NotAllowed),
+        }
+
+        let quota = if cmd == CMD_QUOTA_SET {
+            log::debug!(
+                "driver: quota: {} {:?} {} {}",
+                fname,
+                uid,
+                req.max_bytes,
+                req.max_inodes
+            );
+            fs().set_quota(fname.as_str(), uid, req.max_bytes, req.max_inodes)?;
+            QuotaData::default()
+        } else {
+            fs().get_quota(fname.as_str(), uid)?
+        };
+
+        let resp = raw_channel.get_mut::<QuotaResponse>();
+        resp.quota = quota;
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_mount(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<MountRequest>();
+        let cmd = req.header.cmd;
+
+        if (req.header.ver != 0) || (req.header.flags != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let (source, target) = req.paths(&raw_channel)?;
+
+        let pcon = PerConnectionData::get(conn);
+        if !pcon.may_mount() {
+            return Err(ErrorCode::NotAllowed);
+        }
+        let target = pcon.resolve_path(target)?;
+
+        log::debug!("driver: mount: {} {} {}", cmd, source, target);
+        if cmd == CMD_MOUNT {
+            let source = pcon.resolve_mount_source(source)?;
+            super::filesystem::mount_source(source.as_str(), target.as_str(), req.max_bytes)?;
+        } else {
+            super::filesystem::unmount(target.as_str())?;
+        }
+
+        let resp = raw_channel.get_mut::<MountResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_snapshot(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<SnapshotRequest>();
+        let cmd = req.header.cmd;
+
+        if (req.header.ver != 0) || (req.header.flags != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let (name, path) = req.paths(&raw_channel)?;
+
+        let pcon = PerConnectionData::get(conn);
+        if !pcon.may_mount() {
+            return Err(ErrorCode::NotAllowed);
+        }
+        let path = pcon.resolve_path(path)?;
+
+        log::debug!("driver: snapshot: {} {} {}", cmd, name, path);
+        let snapshots = match cmd {
+            CMD_SNAPSHOT_CREATE => {
+                super::filesystem::create_snapshot(path.as_str(), name, req.max_bytes)?;
+                Vec::new()
+            }
+            CMD_SNAPSHOT_DELETE => {
+                super::filesystem::delete_snapshot(path.as_str(), name)?;
+                Vec::new()
+            }
+            _ => super::filesystem::list_snapshots(path.as_str())?,
+        };
+
+        let resp = raw_channel.get_mut::<SnapshotResponse>();
+        raw_channel
+            .get_at_mut(&mut resp.snapshots, snapshots.len())?
+            .copy_from_slice(&snapshots);
+        resp.num_snapshots = snapshots.len() as u16;
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_watch_add(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<WatchAddRequest>();
+        assert_eq!(req.header.cmd, CMD_WATCH_ADD);
+
+        if (req.header.ver != 0) || (req.parent_fd != 0) || (req.header.flags != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let fname = raw_channel.get_bytes(&req.fname, req.fname_size as usize)?;
+        let fname = core::str::from_utf8(fname).map_err(|_| ErrorCode::InvalidFilename)?;
+
+        let handle = conn.handle();
+        let pcon = PerConnectionData::get(conn);
+        let fname = pcon.resolve_path(fname)?;
+        // Watching reveals names and changes, like reading does.
+        pcon.check_access(fname.as_str(), ACCESS_READ)?;
+
+        let wd = watch::add_watch(pcon.conn_id, handle, fname.as_str());
+        let resp = raw_channel.get_mut::<WatchAddResponse>();
+        resp.header.result = 0;
+        resp.wd = wd;
+        Ok(())
+    }
+
+    unsafe fn on_watch_remove(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<WatchRemoveRequest>();
+        assert_eq!(req.header.cmd, CMD_WATCH_REMOVE);
+
+        if req.header.ver != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        watch::remove_watch(pcon.conn_id, req.wd)?;
+
+        let resp = raw_channel.get_mut::<WatchRemoveResponse>();
+        resp.header.result = 0;
+        Ok(())
+    }
+
+    unsafe fn on_watch_next(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<WatchNextRequest>();
+        assert_eq!(req.header.cmd, CMD_WATCH_NEXT);
+
+        if req.header.ver != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let pcon = PerConnectionData::get(conn);
+        let event = watch::next_event(pcon.conn_id).ok_or(ErrorCode::NotReady)?;
+
+        let resp = raw_channel.get_mut::<WatchNextResponse>();
+        resp.header.result = 0;
+        resp.wd = event.wd;
+        resp.event = event.event;
+        resp.name_size = event.name.len() as u16;
+        raw_channel.put_bytes(event.name.as_bytes(), &mut resp.name)?;
+        Ok(())
+    }
+
+    unsafe fn on_stat(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
         let req = raw_channel.get::<StatRequest>();
         assert_eq!(req.header.cmd, CMD_STAT);
 
-        if (req.header.ver != 0) || (req.header.flags != 0) || (req.parent_fd != 0) {
+        let nofollow = req.header.flags == F_STAT_NOFOLLOW;
+        if (req.header.ver != 0) || (req.header.flags != 0 && !nofollow) || (req.parent_fd != 0) {
             return Err(ErrorCode::InternalError);
         }
 
@@ -570,7 +1651,13 @@ impl Driver {
             }
         };
 
-        let attr = fs().stat(fname)?;
+        let pcon = PerConnectionData::get(conn);
+        let mut attr = if nofollow {
+            fs().lstat(pcon.resolve_path_nofollow(fname)?.as_str())?
+        } else {
+            fs().stat(pcon.resolve_path(fname)?.as_str())?
+        };
+        pcon.restrict_file_perm(&mut attr);
 
         let resp = raw_channel.get_mut::<StatResponse>();
         resp.header.result = 0; // Ok.
@@ -579,6 +1666,43 @@ impl Driver {
 
         Ok(())
     }
+
+    unsafe fn on_statfs(
+        conn: &mut LocalServerConnection,
+        raw_channel: RawChannel,
+    ) -> Result<(), ErrorCode> {
+        let req = raw_channel.get::<StatFsRequest>();
+        assert_eq!(req.header.cmd, CMD_STATFS);
+
+        if (req.header.ver != 0) || (req.header.flags != 0) || (req.parent_fd != 0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let fname_bytes = match raw_channel.get_bytes(&req.fname, req.fname_size as usize) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return Err(ErrorCode::InvalidFilename);
+            }
+        };
+
+        let fname = match core::str::from_utf8(fname_bytes) {
+            Ok(fname) => fname,
+            Err(_) => {
+                return Err(ErrorCode::InvalidFilename);
+            }
+        };
+
+        // The path has to exist; the filesystem it is on is reported.
+        let fname = PerConnectionData::get(conn).resolve_path(fname)?;
+        let _ = fs().stat(fname.as_str())?;
+        let stats = fs().statfs(fname.as_str())?;
+
+        let resp = raw_channel.get_mut::<StatFsResponse>();
+        resp.header.result = 0; // Ok.
+        resp.stats = stats;
+
+        Ok(())
+    }
 }
 
 pub fn start() -> Result<(), ErrorCode> {
@@ -1,21 +1,32 @@
 // FileSystem. The API is synchronous because we don't have asynchronous
 // FS drivers for now.
 
+use alloc::sync::Arc;
 use moto_runtime::rt_api;
 use moto_sys::ErrorCode;
 
 pub trait File {
+    fn unique_id(&self) -> u64; // Identifies the file, not the fd (e.g. for locks).
     fn size(&mut self) -> Result<u64, ErrorCode>;
     fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode>;
     fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode>;
+    fn sync(&mut self, data_only: bool) -> Result<(), ErrorCode>; // Make writes durable.
+
+    // Allocate [offset, offset + len), growing the file if needed, or
+    // free it, keeping the size, if punch_hole.
+    fn allocate(&mut self, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode>;
+
+    // Bypass the FS caches for this handle (see FileOpenRequest::F_DIRECT).
+    fn set_direct(&mut self, direct: bool) -> Result<(), ErrorCode>;
 }
 
 #[allow(unused)]
 pub trait DirectoryEntry {
     fn is_directory(&self) -> bool;
+    fn is_symlink(&self) -> bool;
     fn filename(&self) -> &str; // The filename without ancestors.
-    fn path(&self) -> &str; // The full path.
     fn size(&self) -> Result<u64, ErrorCode>;
+    fn cursor(&self) -> u64; // Where listing resumes after this entry; never zero.
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
@@ -24,23 +35,451 @@ pub trait DirectoryIter: Iterator<Item = Box<dyn DirectoryEntry>> {}
 pub trait FileSystem {
     fn open_file(&'static mut self, path: &str) -> Result<Box<dyn File>, ErrorCode>;
     fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode>;
-    fn iter(&'static mut self, path: &str) -> Result<Box<dyn DirectoryIter>, ErrorCode>;
+    // Lists the directory, after the entry with @cursor (zero: from the start),
+    // in the order of the cursors (see rt_api::fs::ReadDirNextRequest). Entries
+    // are produced as they are iterated, so listing a few is cheap.
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn DirectoryIter>, ErrorCode>;
     fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode>;
+    // Like stat, but doesn't follow the symlink at path.
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode>;
+    // Of the filesystem path belongs to.
+    fn statfs(&'static mut self, path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode>;
     fn mkdir(&'static mut self, path: &str) -> Result<(), ErrorCode>;
     fn unlink(&'static mut self, path: &str) -> Result<(), ErrorCode>;
     fn rename(&'static mut self, old: &str, new: &str) -> Result<(), ErrorCode>;
+    fn symlink(&'static mut self, target: &str, link: &str) -> Result<(), ErrorCode>;
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode>;
+    // A hard link to existing, which must not be a directory.
+    fn link(&'static mut self, existing: &str, link: &str) -> Result<(), ErrorCode>;
+    // Set the permission bits (rt_api::fs::FILE_MODE_MASK).
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode>;
+    fn chown(&'static mut self, path: &str, uid: u32, gid: u32) -> Result<(), ErrorCode>;
+    // Extended attributes (see rt_api::fs::XattrRequest); all follow symlinks.
+    fn get_xattr(&'static mut self, path: &str, name: &str) -> Result<Vec<u8>, ErrorCode>;
+    fn list_xattrs(&'static mut self, path: &str) -> Result<Vec<String>, ErrorCode>;
+    fn set_xattr(&'static mut self, path: &str, name: &str, value: &[u8]) -> Result<(), ErrorCode>;
+    fn remove_xattr(&'static mut self, path: &str, name: &str) -> Result<(), ErrorCode>;
+    // Quotas: with @uid, of the user on the filesystem of @path; otherwise
+    // of the directory at @path. Limits of zero remove the quota.
+    fn get_quota(
+        &'static mut self,
+        path: &str,
+        uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode>;
+    fn set_quota(
+        &'static mut self,
+        path: &str,
+        uid: Option<u32>,
+        max_bytes: u64,
+        max_inodes: u64,
+    ) -> Result<(), ErrorCode>;
     fn delete_dir(&'static mut self, path: &str) -> Result<(), ErrorCode>;
     fn delete_dir_all(&'static mut self, path: &str) -> Result<(), ErrorCode>;
+
+    // Snapshots of the volume (see rt_api::fs::SnapshotRequest); only srfs has them.
+    fn create_snapshot(&'static mut self, _name: &str, _max_bytes: u64) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+    fn delete_snapshot(&'static mut self, _name: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+    fn list_snapshots(&'static mut self) -> Result<Vec<rt_api::fs::SnapshotData>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+    // The snapshot as a read-only filesystem, to mount.
+    fn open_snapshot(&'static mut self, _name: &str) -> Result<Box<dyn FileSystem>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+}
+
+// The root filesystem, with filesystems mounted over it (e.g. tmpfs at /tmp).
+// Paths are routed to the filesystem with the longest matching mount point,
+// which sees them relative to its own root.
+pub struct Mounts {
+    root: Box<dyn FileSystem>,
+    mounts: Vec<(String, Box<dyn FileSystem>)>,
+}
+
+impl Mounts {
+    // The index and the mount point length of the mount @path is under.
+    fn find(&self, path: &str) -> Option<(usize, usize)> {
+        self.mounts
+            .iter()
+            .enumerate()
+            .filter(
+                |(_, (mount_point, _))| match path.strip_prefix(mount_point.as_str()) {
+                    Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                    None => false,
+                },
+            )
+            .max_by_key(|(_, (mount_point, _))| mount_point.len())
+            .map(|(idx, (mount_point, _))| (idx, mount_point.len()))
+    }
+
+    fn get(&'static mut self, mount: Option<(usize, usize)>) -> &'static mut dyn FileSystem {
+        match mount {
+            Some((idx, _)) => self.mounts[idx].1.as_mut(),
+            None => self.root.as_mut(),
+        }
+    }
+
+    fn route<'a>(&'static mut self, path: &'a str) -> (&'static mut dyn FileSystem, &'a str) {
+        let mount = self.find(path);
+        (self.get(mount), local_path(path, mount))
+    }
+
+    // Both paths must be on the same filesystem.
+    fn route_pair<'a>(
+        &'static mut self,
+        first: &'a str,
+        second: &'a str,
+    ) -> Result<(&'static mut dyn FileSystem, &'a str, &'a str), ErrorCode> {
+        let mount = self.find(first);
+        if mount.map(|(idx, _)| idx) != self.find(second).map(|(idx, _)| idx) {
+            return Err(ErrorCode::CrossesDevices);
+        }
+        Ok((
+            self.get(mount),
+            local_path(first, mount),
+            local_path(second, mount),
+        ))
+    }
+}
+
+fn local_path(path: &str, mount: Option<(usize, usize)>) -> &str {
+    match mount {
+        Some((_, len)) if path.len() == len => "/",
+        Some((_, len)) => &path[len..],
+        None => path,
+    }
+}
+
+impl FileSystem for Mounts {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn File>, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.open_file(path)
+    }
+
+    fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.create_file(path)
+    }
+
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn DirectoryIter>, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.iter(path, cursor)
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.stat(path)
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.lstat(path)
+    }
+
+    fn statfs(&'static mut self, path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.statfs(path)
+    }
+
+    fn mkdir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.mkdir(path)
+    }
+
+    fn unlink(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.unlink(path)
+    }
+
+    fn rename(&'static mut self, old: &str, new: &str) -> Result<(), ErrorCode> {
+        let (fs, old, new) = self.route_pair(old, new)?;
+        fs.rename(old, new)
+    }
+
+    fn symlink(&'static mut self, target: &str, link: &str) -> Result<(), ErrorCode> {
+        let (fs, link) = self.route(link);
+        fs.symlink(target, link)
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.readlink(path)
+    }
+
+    fn link(&'static mut self, existing: &str, link: &str) -> Result<(), ErrorCode> {
+        let (fs, existing, link) = self.route_pair(existing, link)?;
+        fs.link(existing, link)
+    }
+
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.chmod(path, mode)
+    }
+
+    fn chown(&'static mut self, path: &str, uid: u32, gid: u32) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.chown(path, uid, gid)
+    }
+
+    fn get_xattr(&'static mut self, path: &str, name: &str) -> Result<Vec<u8>, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.get_xattr(path, name)
+    }
+
+    fn list_xattrs(&'static mut self, path: &str) -> Result<Vec<String>, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.list_xattrs(path)
+    }
+
+    fn set_xattr(&'static mut self, path: &str, name: &str, value: &[u8]) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.set_xattr(path, name, value)
+    }
+
+    fn remove_xattr(&'static mut self, path: &str, name: &str) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.remove_xattr(path, name)
+    }
+
+    fn get_quota(
+        &'static mut self,
+        path: &str,
+        uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.get_quota(path, uid)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        path: &str,
+        uid: Option<u32>,
+        max_bytes: u64,
+        max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.set_quota(path, uid, max_bytes, max_inodes)
+    }
+
+    fn delete_dir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.delete_dir(path)
+    }
+
+    fn delete_dir_all(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (fs, path) = self.route(path);
+        fs.delete_dir_all(path)
+    }
 }
 
-static mut FS: Option<Box<dyn FileSystem>> = None;
+static mut FS: Option<Mounts> = None;
 
-pub fn fs() -> &'static mut Box<dyn FileSystem> {
+pub fn fs() -> &'static mut Mounts {
     unsafe { FS.as_mut().unwrap() }
 }
 
+// A row of the mount table, as listed in /proc/mounts.
+#[derive(Clone)]
+pub struct MountInfo {
+    pub source: String, // As given to mount_source(), e.g. "/dev/vdb1".
+    pub mount_point: String,
+    pub fs_type: &'static str,
+}
+
+// Kept apart from Mounts, which is borrowed while procfs lists the table.
+static MOUNT_TABLE: std::sync::Mutex<Vec<MountInfo>> = std::sync::Mutex::new(Vec::new());
+
+// The root filesystem first, then the others in the order they were mounted.
+pub fn mount_table() -> Vec<MountInfo> {
+    MOUNT_TABLE.lock().unwrap().clone()
+}
+
+fn add_to_mount_table(source: &str, fs_type: &'static str, mount_point: &str) {
+    MOUNT_TABLE.lock().unwrap().push(MountInfo {
+        source: source.to_owned(),
+        mount_point: mount_point.to_owned(),
+        fs_type,
+    });
+}
+
+// The name of the @idx-th virtio drive in /dev.
+pub fn drive_name(idx: usize) -> Option<String> {
+    if idx < 26 {
+        Some(format!("vd{}", (b'a' + idx as u8) as char))
+    } else {
+        None
+    }
+}
+
+// "/dev/vdb" => (1, 0); "/dev/vdb2" => (1, 2): drive indices and partition
+// numbers, which are one-based, with zero for the whole drive.
+fn parse_drive_name(name: &str) -> Option<(usize, usize)> {
+    let rest = name.strip_prefix("/dev/vd")?;
+    let letter = *rest.as_bytes().first()?;
+    if !letter.is_ascii_lowercase() {
+        return None;
+    }
+    let partition = match &rest[1..] {
+        "" => 0,
+        num => num
+            .parse::<usize>()
+            .ok()
+            .filter(|num| (1..=4).contains(num))?,
+    };
+    Some(((letter - b'a') as usize, partition))
+}
+
+// Mounts @fs at @mount_point, which is an absolute path without a trailing '/'.
+pub fn mount(source: &str, fs_type: &'static str, mount_point: &str, fs: Box<dyn FileSystem>) {
+    assert!(mount_point.starts_with('/') && !mount_point.ends_with('/'));
+    self::fs().mounts.push((mount_point.to_owned(), fs));
+    add_to_mount_table(source, fs_type, mount_point);
+}
+
+// Mounts @source (see rt_api::fs::MountRequest) at @mount_point, which must
+// be a directory that nothing is mounted at yet.
+pub fn mount_source(source: &str, mount_point: &str, max_bytes: u64) -> Result<(), ErrorCode> {
+    if !mount_point.starts_with('/') || mount_point.ends_with('/') {
+        return Err(ErrorCode::InvalidFilename);
+    }
+    if self::fs().stat(mount_point)?.file_type != rt_api::fs::FILE_TYPE_DIR {
+        return Err(ErrorCode::NotADirectory);
+    }
+    let table = mount_table();
+    if table.iter().any(|m| m.mount_point == mount_point) {
+        return Err(ErrorCode::AlreadyInUse);
+    }
+
+    let (fs, fs_type) = if source == "tmpfs" {
+        let max_bytes = if max_bytes == 0 {
+            moto_sys::stats::MemoryStats::get()?.available / super::TMPFS_MEMORY_FRACTION
+        } else {
+            max_bytes
+        };
+        (super::fs_tmpfs::init(mount_point, max_bytes), "tmpfs")
+    } else if let Some(name) = source.strip_prefix("snapshot:") {
+        let (fs, _) = self::fs().route(mount_point);
+        (fs.open_snapshot(name)?, "srfs")
+    } else if let Some(tag) = source.strip_prefix("virtiofs:") {
+        if table.iter().any(|m| m.source == source) {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+        let dev = moto_virtio::lsfs()
+            .into_iter()
+            .find(|dev| dev.tag() == tag)
+            .ok_or(ErrorCode::NotFound)?;
+        (super::fs_virtiofs::init(dev, mount_point)?, "virtiofs")
+    } else {
+        let (drive_idx, partition) = parse_drive_name(source).ok_or(ErrorCode::NotFound)?;
+        // A volume must not be mounted twice, including as a part of its drive.
+        let overlaps = table
+            .iter()
+            .filter_map(|m| parse_drive_name(&m.source))
+            .any(|(idx, part)| {
+                idx == drive_idx && (part == partition || part == 0 || partition == 0)
+            });
+        if overlaps {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+
+        let (drive, lba, sectors) = find_volume(drive_idx, partition)?;
+        match super::fs_fat::init(drive.clone(), lba, sectors, mount_point) {
+            Ok(fs) => (fs, "fat"),
+            Err(_) => (
+                super::fs_ext2::init(drive, lba, sectors, mount_point)?,
+                "ext2",
+            ),
+        }
+    };
+
+    mount(source, fs_type, mount_point, fs);
+    log::info!("Mounted {} at {}.", source, mount_point);
+    Ok(())
+}
+
+// Snapshots of the volume @path is on (see rt_api::fs::SnapshotRequest).
+pub fn create_snapshot(path: &str, name: &str, max_bytes: u64) -> Result<(), ErrorCode> {
+    let (fs, _) = self::fs().route(path);
+    fs.create_snapshot(name, max_bytes)?;
+    log::info!("Created snapshot {}.", name);
+    Ok(())
+}
+
+pub fn delete_snapshot(path: &str, name: &str) -> Result<(), ErrorCode> {
+    let (fs, _) = self::fs().route(path);
+    fs.delete_snapshot(name)?;
+    log::info!("Deleted snapshot {}.", name);
+    Ok(())
+}
+
+pub fn list_snapshots(path: &str) -> Result<Vec<rt_api::fs::SnapshotData>, ErrorCode> {
+    let (fs, _) = self::fs().route(path);
+    fs.list_snapshots()
+}
+
+// (drive, lba, sectors) of a partition (see parse_drive_name()).
+fn find_volume(
+    drive_idx: usize,
+    partition: usize,
+) -> Result<(Arc<dyn moto_virtio::BlockDevice>, u64, u64), ErrorCode> {
+    let drive = super::page_cache::drives()
+        .into_iter()
+        .nth(drive_idx)
+        .ok_or(ErrorCode::NotFound)?;
+    let capacity = drive.capacity();
+    if partition == 0 {
+        return Ok((drive, 0, capacity));
+    }
+
+    let mut sector = [0_u8; moto_virtio::BLOCK_SIZE];
+    super::partition::Partition::new(drive.clone(), 0, capacity).read(0, &mut sector)?;
+    let mbr = super::mbr::Mbr::parse(&sector).map_err(|_| ErrorCode::NotFound)?;
+    let pte = mbr.entries[partition - 1];
+    if pte.partition_type == super::mbr::PartitionType::Unused {
+        return Err(ErrorCode::NotFound);
+    }
+    Ok((drive, pte.lba as u64, pte.sectors as u64))
+}
+
+// Detaches the filesystem mounted at @mount_point. Files open on it stay
+// usable until closed; filesystems mounted under it must be unmounted first.
+pub fn unmount(mount_point: &str) -> Result<(), ErrorCode> {
+    let mounts = &mut self::fs().mounts;
+    let idx = mounts
+        .iter()
+        .position(|(mp, _)| mp == mount_point)
+        .ok_or(ErrorCode::InvalidArgument)?;
+    let nested = mounts.iter().any(|(mp, _)| {
+        mp.strip_prefix(mount_point)
+            .is_some_and(|rest| rest.starts_with('/'))
+    });
+    if nested {
+        return Err(ErrorCode::AlreadyInUse);
+    }
+
+    mounts.remove(idx);
+    MOUNT_TABLE
+        .lock()
+        .unwrap()
+        .retain(|m| m.mount_point != mount_point);
+    log::info!("Unmounted {}.", mount_point);
+    Ok(())
+}
+
 pub fn init() {
-    let mut drives = moto_virtio::lsblk();
+    let mut drives = super::page_cache::drives();
     if drives.len() == 0 {
         log::error!("No drives found");
         panic!("No drives found");
@@ -55,13 +494,27 @@ pub fn init() {
     let mut block = alloc::vec::Vec::<u8>::with_capacity(BLOCK_SIZE);
     unsafe { block.set_len(BLOCK_SIZE) }; // Safe because we just allocated with the same len.
 
-    let mut fs: Option<Box<dyn FileSystem>> = None;
-    for drive in &mut drives {
+    // The root filesystem, its source, and its type.
+    let mut fs: Option<(Box<dyn FileSystem>, String, &'static str)> = None;
+    // Volumes that are mounted under /mnt: (source, drive, lba, sectors).
+    let mut fat_volumes = Vec::new();
+    let mut ext2_volumes = Vec::new();
+    for (drive_idx, drive) in drives.iter_mut().enumerate() {
+        let drive_name = drive_name(drive_idx)
+            .map(|name| format!("/dev/{}", name))
+            .unwrap_or_default();
         if let Ok(()) = drive.read(block.as_mut_slice(), 0, 1) {
+            // Drives formatted on the host often have no partition table.
+            if super::fs_fat::probe(block.as_slice()) {
+                fat_volumes.push((drive_name, drive.clone(), 0, drive.capacity()));
+                continue;
+            }
+
             match super::mbr::Mbr::parse(block.as_slice()) {
                 Ok(mbr) => {
-                    for pte in &mbr.entries {
+                    for (pte_idx, pte) in mbr.entries.iter().enumerate() {
                         log::trace!("MBR PTE: {:?}", pte);
+                        let source = format!("{}{}", drive_name, pte_idx + 1);
                         match pte.partition_type {
                             super::mbr::PartitionType::FlatFs => {
                                 if fs.is_some() {
@@ -69,10 +522,14 @@ pub fn init() {
                                     panic!();
                                 }
 
-                                fs = Some(super::fs_flatfs::init(
-                                    drive.clone(),
-                                    pte.lba as u64,
-                                    pte.sectors as u64,
+                                fs = Some((
+                                    super::fs_flatfs::init(
+                                        drive.clone(),
+                                        pte.lba as u64,
+                                        pte.sectors as u64,
+                                    ),
+                                    source,
+                                    "flatfs",
                                 ));
                             }
                             super::mbr::PartitionType::SrFs => {
@@ -81,17 +538,45 @@ pub fn init() {
                                     panic!();
                                 }
 
-                                fs = Some(super::fs_srfs::init(
-                                    drive.clone(),
-                                    pte.lba as u64,
-                                    pte.sectors as u64,
+                                fs = Some((
+                                    super::fs_srfs::init(
+                                        drive.clone(),
+                                        pte.lba as u64,
+                                        pte.sectors as u64,
+                                    ),
+                                    source,
+                                    "srfs",
                                 ));
                             }
+                            super::mbr::PartitionType::Fat32(_) => fat_volumes.push((
+                                source,
+                                drive.clone(),
+                                pte.lba as u64,
+                                pte.sectors as u64,
+                            )),
+                            super::mbr::PartitionType::LinuxExt(_) => ext2_volumes.push((
+                                source,
+                                drive.clone(),
+                                pte.lba as u64,
+                                pte.sectors as u64,
+                            )),
                             _ => continue,
                         }
                     }
                 }
                 Err(err) => {
+                    // The error borrows block, which is reused below.
+                    let err = err.to_owned();
+
+                    // Linux data disks often have no partition table.
+                    if let Ok(()) =
+                        drive.read(block.as_mut_slice(), super::fs_ext2::PROBE_OFFSET, 1)
+                    {
+                        if super::fs_ext2::probe(block.as_slice()) {
+                            ext2_volumes.push((drive_name, drive.clone(), 0, drive.capacity()));
+                            continue;
+                        }
+                    }
                     crate::moto_log!("Failed to read MBR: {}", err);
                     log::warn!("Failed to read MBR: {}", err);
                 }
@@ -102,11 +587,16 @@ pub fn init() {
         }
     }
 
-    if fs.is_none() {
+    let Some((root, root_source, root_type)) = fs else {
         log::error!("Couldn't find a data partion.");
         panic!("Couldn't find a data partition.");
-    }
+    };
+    add_to_mount_table(&root_source, root_type, "/");
 
+    let mut fs = Some(Mounts {
+        root,
+        mounts: Vec::new(),
+    });
     unsafe {
         core::mem::swap(
             std::ptr::addr_of_mut!(FS).as_mut().unwrap_unchecked(),
@@ -114,4 +604,62 @@ pub fn init() {
         )
     };
     assert!(fs.is_none());
+
+    mount_volumes("fat", "fat", fat_volumes, super::fs_fat::init);
+    mount_volumes("ext", "ext2", ext2_volumes, super::fs_ext2::init);
+    mount_shared_dirs();
+}
+
+type VolumeInit =
+    fn(Arc<dyn moto_virtio::BlockDevice>, u64, u64, &str) -> Result<Box<dyn FileSystem>, ErrorCode>;
+
+// Mounts @volumes, (source, drive, lba, sectors), at /mnt/{prefix}N.
+fn mount_volumes(
+    prefix: &str,
+    fs_type: &'static str,
+    volumes: Vec<(String, Arc<dyn moto_virtio::BlockDevice>, u64, u64)>,
+    init: VolumeInit,
+) {
+    for (idx, (source, drive, lba, sectors)) in volumes.into_iter().enumerate() {
+        let mount_point = format!("/mnt/{}{}", prefix, idx);
+        let fs = init(drive, lba, sectors, &mount_point);
+        mount_under_mnt(&source, fs_type, &mount_point, fs);
+    }
+}
+
+// Host directories shared via virtio-fs are mounted at /mnt/{tag}.
+fn mount_shared_dirs() {
+    for (idx, dev) in moto_virtio::lsfs().into_iter().enumerate() {
+        let tag = dev.tag();
+        let mount_point = format!("/mnt/{}", tag);
+        let mount_point = if tag.contains('/')
+            || tag == "."
+            || tag == ".."
+            || self::fs().mounts.iter().any(|(mp, _)| *mp == mount_point)
+        {
+            format!("/mnt/virtiofs{}", idx)
+        } else {
+            mount_point
+        };
+        let fs = super::fs_virtiofs::init(dev, &mount_point);
+        mount_under_mnt(&format!("virtiofs:{}", tag), "virtiofs", &mount_point, fs);
+    }
+}
+
+fn mount_under_mnt(
+    source: &str,
+    fs_type: &'static str,
+    mount_point: &str,
+    fs: Result<Box<dyn FileSystem>, ErrorCode>,
+) {
+    match fs {
+        Ok(fs) => {
+            // See set_temp_dir() in mod.rs.
+            let _ = self::fs().mkdir("/mnt");
+            let _ = self::fs().mkdir(mount_point);
+            mount(source, fs_type, mount_point, fs);
+            log::info!("Mounted a volume at {}.", mount_point);
+        }
+        Err(err) => log::warn!("Failed to mount {}: {:?}", mount_point, err),
+    }
 }
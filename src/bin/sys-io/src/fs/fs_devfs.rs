@@ -0,0 +1,425 @@
+// devfs: device nodes at /dev.
+//
+// console      Writes go to the kernel log; reads return EOF.
+// null         Reads return EOF; writes are discarded.
+// zero         Reads return zeroes; writes are discarded.
+// random       Reads return RDRAND output; writes are discarded.
+// urandom      Same as random.
+// vda, vdb...  Virtio block devices, byte-addressable; root only.
+//
+// The set of nodes is fixed at boot. Nodes can't be created, removed, or
+// changed, but opening them with create/truncate works, so that shell
+// redirections like "> /dev/null" do.
+
+use super::filesystem::FileSystem;
+use super::partition::Partition;
+use alloc::sync::Arc;
+use moto_runtime::rt_api;
+use moto_sys::ErrorCode;
+
+const SECTOR_SIZE: u64 = moto_virtio::BLOCK_SIZE as u64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharDevice {
+    Console,
+    Null,
+    Random,
+    URandom,
+    Zero,
+}
+
+// Sorted by name, which is also the listing order.
+const CHAR_DEVICES: [(&str, CharDevice); 5] = [
+    ("console", CharDevice::Console),
+    ("null", CharDevice::Null),
+    ("random", CharDevice::Random),
+    ("urandom", CharDevice::URandom),
+    ("zero", CharDevice::Zero),
+];
+
+#[derive(Clone, Copy)]
+enum Node {
+    Root,
+    Char(CharDevice),
+    Block(usize), // The index of the drive.
+}
+
+struct CharDeviceFile {
+    device: CharDevice,
+}
+
+impl super::File for CharDeviceFile {
+    fn unique_id(&self) -> u64 {
+        self.device as u64
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(0)
+    }
+
+    fn write_offset(&mut self, _offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        if self.device == CharDevice::Console {
+            let msg = String::from_utf8_lossy(buf);
+            let msg = msg.trim_end_matches('\n');
+            if !msg.is_empty() {
+                moto_sys::SysRay::log(msg)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn read_offset(&mut self, _offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        match self.device {
+            CharDevice::Console | CharDevice::Null => Ok(0),
+            CharDevice::Zero => {
+                buf.fill(0);
+                Ok(buf.len())
+            }
+            CharDevice::Random | CharDevice::URandom => {
+                moto_sys::fill_random(buf)?;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+
+    fn allocate(&mut self, _offset: u64, _len: u64, _punch_hole: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+struct BlockDeviceFile {
+    idx: usize,
+    partition: Partition, // The whole drive.
+}
+
+impl super::File for BlockDeviceFile {
+    fn unique_id(&self) -> u64 {
+        (1 << 32) + self.idx as u64
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self.partition.size())
+    }
+
+    // Writes past the end of the device are truncated.
+    fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let size = self.partition.size();
+        if offset >= size {
+            return Err(ErrorCode::StorageFull);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        self.partition.write(offset, &buf[0..len])?;
+        Ok(len)
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let size = self.partition.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        self.partition.read(offset, &mut buf[0..len])?;
+        Ok(len)
+    }
+
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        self.partition.flush()
+    }
+
+    fn allocate(&mut self, _offset: u64, _len: u64, _punch_hole: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(()) // The driver tells the page cache about direct I/O.
+    }
+}
+
+struct DirectoryEntryDevFs {
+    name: String,
+    size: u64,
+    cursor: u64,
+}
+
+impl super::filesystem::DirectoryEntry for DirectoryEntryDevFs {
+    fn is_directory(&self) -> bool {
+        false
+    }
+
+    fn is_symlink(&self) -> bool {
+        false
+    }
+
+    fn filename(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> Result<u64, ErrorCode> {
+        Ok(self.size)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+// Character devices, then block devices; the cursor is the number of
+// entries listed so far.
+struct DirectoryIterDevFs {
+    fs: &'static FileSystemDevFs,
+    cursor: u64,
+}
+
+impl Iterator for DirectoryIterDevFs {
+    type Item = Box<dyn super::filesystem::DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.cursor as usize;
+        let (name, size) = if pos < CHAR_DEVICES.len() {
+            (CHAR_DEVICES[pos].0.to_owned(), 0)
+        } else {
+            let idx = pos - CHAR_DEVICES.len();
+            let drive = self.fs.drives.get(idx)?;
+            (
+                super::filesystem::drive_name(idx)?,
+                drive.capacity() * SECTOR_SIZE,
+            )
+        };
+        self.cursor += 1;
+
+        Some(Box::new(DirectoryEntryDevFs {
+            name,
+            size,
+            cursor: self.cursor,
+        }))
+    }
+}
+
+impl super::DirectoryIter for DirectoryIterDevFs {}
+
+struct FileSystemDevFs {
+    mount_point: String,
+    drives: Vec<Arc<dyn moto_virtio::BlockDevice>>,
+}
+
+impl FileSystemDevFs {
+    fn lookup(&self, path: &str) -> Result<Node, ErrorCode> {
+        let name = path.trim_matches('/');
+        if name.is_empty() {
+            return Ok(Node::Root);
+        }
+        if let Some((_, device)) = CHAR_DEVICES.iter().find(|(n, _)| *n == name) {
+            return Ok(Node::Char(*device));
+        }
+        (0..self.drives.len())
+            .find(|idx| super::filesystem::drive_name(*idx).as_deref() == Some(name))
+            .map(Node::Block)
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    fn attr(&self, node: Node) -> rt_api::fs::FileAttrData {
+        let (file_perm, file_type, size, mode) = match node {
+            Node::Root => (
+                rt_api::fs::FILE_PERM_READ,
+                rt_api::fs::FILE_TYPE_DIR,
+                0,
+                0o755,
+            ),
+            Node::Char(_) => (
+                rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE,
+                rt_api::fs::FILE_TYPE_FILE,
+                0,
+                0o666,
+            ),
+            Node::Block(idx) => (
+                rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE,
+                rt_api::fs::FILE_TYPE_FILE,
+                self.drives[idx].capacity() * SECTOR_SIZE,
+                0o600,
+            ),
+        };
+        rt_api::fs::FileAttrData {
+            version: 0,
+            self_size: core::mem::size_of::<rt_api::fs::FileAttrData>() as u16,
+            file_perm,
+            file_type,
+            reserved: 0,
+            size,
+            created: 0,
+            accessed: 0,
+            modified: 0,
+            uid: moto_sys::caps::ROOT_UID,
+            gid: 0,
+            mode,
+            reserved_2: 0,
+        }
+    }
+}
+
+impl FileSystem for FileSystemDevFs {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
+        match self.lookup(path)? {
+            Node::Root => Err(ErrorCode::NotFound),
+            Node::Char(device) => Ok(Box::new(CharDeviceFile { device })),
+            Node::Block(idx) => {
+                let drive = self.drives[idx].clone();
+                let sectors = drive.capacity();
+                Ok(Box::new(BlockDeviceFile {
+                    idx,
+                    partition: Partition::new(drive, 0, sectors),
+                }))
+            }
+        }
+    }
+
+    // Devices exist already: see the comment at the top.
+    fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        match self.lookup(path) {
+            Ok(Node::Char(_)) | Ok(Node::Block(_)) => Ok(()),
+            _ => Err(ErrorCode::NotAllowed),
+        }
+    }
+
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        match self.lookup(path)? {
+            Node::Root => Ok(Box::new(DirectoryIterDevFs { fs: self, cursor })),
+            _ => Err(ErrorCode::NotADirectory),
+        }
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let node = self.lookup(path)?;
+        Ok(self.attr(node))
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        self.stat(path) // No symlinks.
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        Ok(rt_api::fs::FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<rt_api::fs::FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_DEVFS,
+            read_only: 0,
+            reserved: 0,
+            block_size: SECTOR_SIZE,
+            blocks_total: 0,
+            blocks_free: 0,
+        })
+    }
+
+    fn mkdir(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn unlink(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn rename(&'static mut self, _old: &str, _new: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn symlink(&'static mut self, _target: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        self.lookup(path)?;
+        Err(ErrorCode::InvalidArgument) // Not a symlink.
+    }
+
+    fn link(&'static mut self, _existing: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    // Truncating re-applies the mode and the owner, which must then succeed.
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode> {
+        let node = self.lookup(path)?;
+        if self.attr(node).mode == mode as u32 {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotAllowed)
+        }
+    }
+
+    fn chown(&'static mut self, path: &str, uid: u32, gid: u32) -> Result<(), ErrorCode> {
+        let attr = self.attr(self.lookup(path)?);
+        if attr.uid == uid && attr.gid == gid {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotAllowed)
+        }
+    }
+
+    fn get_xattr(&'static mut self, _path: &str, _name: &str) -> Result<Vec<u8>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn list_xattrs(&'static mut self, _path: &str) -> Result<Vec<String>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_xattr(
+        &'static mut self,
+        _path: &str,
+        _name: &str,
+        _value: &[u8],
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn remove_xattr(&'static mut self, _path: &str, _name: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn delete_dir(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn delete_dir_all(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+}
+
+pub(super) fn init(mount_point: &str) -> Box<dyn FileSystem> {
+    Box::new(FileSystemDevFs {
+        mount_point: mount_point.trim_end_matches('/').to_owned(),
+        drives: super::page_cache::drives(),
+    })
+}
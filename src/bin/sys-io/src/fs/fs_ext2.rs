@@ -0,0 +1,679 @@
+// Read-only ext2 volumes, e.g. data disks built on Linux, mounted at
+// /mnt/extN (see super::filesystem::init()). Cleanly unmounted ext3 volumes
+// can be read as well, as their journal is not needed then; ext4 extents
+// and 64-bit volumes are not supported.
+//
+// Owners and permission bits are reported as they are on the volume.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::filesystem::FileSystem;
+use super::partition::Partition;
+use super::path_walk::{self, PathWalk};
+use alloc::sync::Arc;
+use moto_runtime::rt_api;
+use moto_sys::ErrorCode;
+
+// The superblock is at byte 1024 of the volume; see probe().
+pub(super) const PROBE_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const MAGIC: u16 = 0xEF53;
+
+const ROOT_INO: u32 = 2;
+const GOOD_OLD_INODE_SIZE: u32 = 128;
+const GROUP_DESC_SIZE: u64 = 32;
+const DIRECT_BLOCKS: u64 = 12;
+const FAST_SYMLINK_SIZE: u64 = 60;
+
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const INCOMPAT_FLEX_BG: u32 = 0x0200;
+const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE | INCOMPAT_FLEX_BG;
+const EXTENTS_FL: u32 = 0x0008_0000;
+
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFREG: u16 = 0x8000;
+const S_IFLNK: u16 = 0xA000;
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..(offset + 2)].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..(offset + 4)].try_into().unwrap())
+}
+
+// Whether @sector, read at PROBE_OFFSET, is the start of an ext2 superblock.
+pub(super) fn probe(sector: &[u8]) -> bool {
+    sector.len() >= 58 && read_u16(sector, 56) == MAGIC
+}
+
+struct Inode {
+    ino: u32,
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    size: u64,
+    atime: u32,
+    mtime: u32,
+    blocks_512: u32, // i_blocks: the 512-byte sectors used, including the xattr block.
+    file_acl: u32,
+    block: [u32; 15],
+}
+
+impl Inode {
+    fn file_type(&self) -> u16 {
+        self.mode & S_IFMT
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type() == S_IFDIR
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.file_type() == S_IFLNK
+    }
+
+    fn to_file_attr(&self) -> rt_api::fs::FileAttrData {
+        use rt_api::fs::FileAttrData;
+
+        FileAttrData {
+            version: 1,
+            self_size: core::mem::size_of::<FileAttrData>() as u16,
+            file_perm: rt_api::fs::FILE_PERM_READ,
+            file_type: match self.file_type() {
+                S_IFDIR => rt_api::fs::FILE_TYPE_DIR,
+                S_IFLNK => rt_api::fs::FILE_TYPE_SYMLINK,
+                _ => rt_api::fs::FILE_TYPE_FILE,
+            },
+            reserved: 0,
+            size: match self.file_type() {
+                S_IFREG | S_IFLNK => self.size,
+                _ => 0,
+            },
+            created: 0, // ext2 doesn't keep it.
+            accessed: self.atime as u64 * 1_000_000_000,
+            modified: self.mtime as u64 * 1_000_000_000,
+            uid: self.uid,
+            gid: self.gid,
+            mode: (self.mode as u32) & rt_api::fs::FILE_MODE_MASK,
+            reserved_2: 0,
+        }
+    }
+}
+
+struct Volume {
+    part: Partition,
+    block_size: u64,
+    blocks_count: u64,
+    free_blocks: u64,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    inode_size: u32,
+    inode_tables: Vec<u64>, // The first block of each group's inode table.
+}
+
+impl Volume {
+    fn read_block_u32(&mut self, block: u32, idx: u64) -> Result<u32, ErrorCode> {
+        let mut bytes = [0_u8; 4];
+        self.part
+            .read(self.block_offset(block)? + idx * 4, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn block_offset(&self, block: u32) -> Result<u64, ErrorCode> {
+        if block as u64 >= self.blocks_count {
+            log::warn!("ext2: bad block number {}", block);
+            return Err(ErrorCode::InternalError);
+        }
+        Ok(block as u64 * self.block_size)
+    }
+
+    fn read_inode(&mut self, ino: u32) -> Result<Inode, ErrorCode> {
+        if ino == 0 || ino > self.inodes_count {
+            log::warn!("ext2: bad inode number {}", ino);
+            return Err(ErrorCode::InternalError);
+        }
+        let group = (ino - 1) / self.inodes_per_group;
+        let idx = (ino - 1) % self.inodes_per_group;
+        let offset = self.inode_tables[group as usize] * self.block_size
+            + idx as u64 * self.inode_size as u64;
+
+        let mut raw = [0_u8; GOOD_OLD_INODE_SIZE as usize];
+        self.part.read(offset, &mut raw)?;
+        if read_u32(&raw, 32) & EXTENTS_FL != 0 {
+            log::warn!("ext2: inode {} uses extents", ino);
+            return Err(ErrorCode::NotImplemented);
+        }
+
+        let mode = read_u16(&raw, 0);
+        let mut size = read_u32(&raw, 4) as u64;
+        if mode & S_IFMT == S_IFREG {
+            size |= (read_u32(&raw, 108) as u64) << 32;
+        }
+        let mut block = [0_u32; 15];
+        for (idx, block) in block.iter_mut().enumerate() {
+            *block = read_u32(&raw, 40 + idx * 4);
+        }
+        Ok(Inode {
+            ino,
+            mode,
+            uid: read_u16(&raw, 2) as u32 | (read_u16(&raw, 120) as u32) << 16,
+            gid: read_u16(&raw, 24) as u32 | (read_u16(&raw, 122) as u32) << 16,
+            size,
+            atime: read_u32(&raw, 8),
+            mtime: read_u32(&raw, 16),
+            blocks_512: read_u32(&raw, 28),
+            file_acl: read_u32(&raw, 104),
+            block,
+        })
+    }
+
+    // The block of @inode that has the bytes at @logical * block_size;
+    // zero if it is a hole.
+    fn map_block(&mut self, inode: &Inode, logical: u64) -> Result<u32, ErrorCode> {
+        let per_block = self.block_size / 4;
+        let mut idx = logical;
+        if idx < DIRECT_BLOCKS {
+            return Ok(inode.block[idx as usize]);
+        }
+
+        // Indirect blocks: single, double, triple.
+        idx -= DIRECT_BLOCKS;
+        let mut span = per_block;
+        for level in 0..3 {
+            if idx < span {
+                let mut block = inode.block[DIRECT_BLOCKS as usize + level];
+                while span > 1 {
+                    if block == 0 {
+                        return Ok(0);
+                    }
+                    span /= per_block;
+                    block = self.read_block_u32(block, idx / span)?;
+                    idx %= span;
+                }
+                return Ok(block);
+            }
+            idx -= span;
+            span *= per_block;
+        }
+        Err(ErrorCode::FileTooLarge)
+    }
+
+    fn read_data(&mut self, inode: &Inode, offset: u64, buf: &mut [u8]) -> Result<(), ErrorCode> {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let skip = pos % self.block_size;
+            let piece = (buf.len() - done).min((self.block_size - skip) as usize);
+            let dst = &mut buf[done..(done + piece)];
+            match self.map_block(inode, pos / self.block_size)? {
+                0 => dst.fill(0),
+                block => {
+                    let block_offset = self.block_offset(block)?;
+                    self.part.read(block_offset + skip, dst)?;
+                }
+            }
+            done += piece;
+        }
+        Ok(())
+    }
+
+    fn read_symlink(&mut self, inode: &Inode) -> Result<String, ErrorCode> {
+        let ea_blocks = if inode.file_acl != 0 {
+            (self.block_size / 512) as u32
+        } else {
+            0
+        };
+        // Short targets are kept in place of the block numbers.
+        let target = if inode.size < FAST_SYMLINK_SIZE && inode.blocks_512 == ea_blocks {
+            inode
+                .block
+                .iter()
+                .flat_map(|block| block.to_le_bytes())
+                .take(inode.size as usize)
+                .collect()
+        } else {
+            let mut target = vec![0_u8; inode.size as usize];
+            self.read_data(inode, 0, &mut target)?;
+            target
+        };
+        String::from_utf8(target).map_err(|_| ErrorCode::InvalidFilename)
+    }
+
+    // The entries of @dir, other than "." and "..": (name, ino).
+    fn read_dir(&mut self, dir: &Inode) -> Result<Vec<(String, u32)>, ErrorCode> {
+        let mut entries = vec![];
+        let mut offset = 0;
+        loop {
+            let (block_entries, next) = self.read_dir_block(dir, offset)?;
+            entries.extend(block_entries.into_iter().map(|(name, ino, _)| (name, ino)));
+            if next >= dir.size {
+                return Ok(entries);
+            }
+            offset = next;
+        }
+    }
+
+    // The entries of @dir from the record at @offset to the end of its block,
+    // where records end: (name, ino, the offset of the next record), and the
+    // offset of the next block.
+    fn read_dir_block(
+        &mut self,
+        dir: &Inode,
+        offset: u64,
+    ) -> Result<(Vec<(String, u32, u64)>, u64), ErrorCode> {
+        if !dir.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        let block_end = (offset - offset % self.block_size + self.block_size).min(dir.size);
+        let mut bytes = vec![0_u8; block_end.saturating_sub(offset) as usize];
+        self.read_data(dir, offset, &mut bytes)?;
+
+        let mut entries = vec![];
+        let mut pos = 0;
+        while pos + 8 <= bytes.len() {
+            let ino = read_u32(&bytes, pos);
+            let rec_len = read_u16(&bytes, pos + 4) as usize;
+            let name_len = bytes[pos + 6] as usize;
+            if rec_len < 8 || pos + rec_len > bytes.len() || 8 + name_len > rec_len {
+                log::warn!("ext2: bad directory entry in inode {}", dir.ino);
+                return Err(ErrorCode::InternalError);
+            }
+
+            let name = &bytes[(pos + 8)..(pos + 8 + name_len)];
+            pos += rec_len;
+            if ino != 0 && name != b"." && name != b".." {
+                let name = String::from_utf8_lossy(name).into_owned();
+                entries.push((name, ino, offset + pos as u64));
+            }
+        }
+        Ok((entries, block_end))
+    }
+
+    fn find(&mut self, dir: &Inode, name: &str) -> Result<Inode, ErrorCode> {
+        if name == ".." {
+            // The root's ".." is the root itself.
+            let mut bytes = [0_u8; 24];
+            self.read_data(dir, 0, &mut bytes)?;
+            let rec_len = read_u16(&bytes, 4) as usize;
+            if rec_len + 8 > bytes.len() {
+                return Err(ErrorCode::InternalError);
+            }
+            return self.read_inode(read_u32(&bytes, rec_len));
+        }
+
+        let (_, ino) = self
+            .read_dir(dir)?
+            .into_iter()
+            .find(|(entry, _)| entry == name)
+            .ok_or(ErrorCode::NotFound)?;
+        self.read_inode(ino)
+    }
+}
+
+struct FileSystemExt2 {
+    mount_point: String,
+    vol: Rc<RefCell<Volume>>,
+}
+
+struct File {
+    vol: Rc<RefCell<Volume>>,
+    inode: Inode,
+}
+
+impl super::File for File {
+    fn unique_id(&self) -> u64 {
+        (Rc::as_ptr(&self.vol) as usize as u64).wrapping_add(self.inode.ino as u64)
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self.inode.size)
+    }
+
+    fn write_offset(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        if offset == self.inode.size {
+            return Ok(0);
+        }
+        if offset > self.inode.size {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let len = (self.inode.size - offset).min(buf.len() as u64) as usize;
+        self.vol
+            .borrow_mut()
+            .read_data(&self.inode, offset, &mut buf[0..len])?;
+        Ok(len)
+    }
+
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        Ok(()) // Read-only.
+    }
+
+    fn allocate(&mut self, _offset: u64, _len: u64, _punch_hole: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed) // Read-only.
+    }
+
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+struct DirectoryEntryExt2 {
+    name: String,
+    inode: Inode,
+    cursor: u64, // The offset of the next record in the directory.
+}
+
+impl super::filesystem::DirectoryEntry for DirectoryEntryExt2 {
+    fn is_directory(&self) -> bool {
+        self.inode.is_dir()
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.inode.is_symlink()
+    }
+
+    fn filename(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn size(&self) -> Result<u64, ErrorCode> {
+        Ok(self.inode.to_file_attr().size)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
+}
+
+// Directories are read a block at a time.
+struct DirectoryIterExt2 {
+    vol: Rc<RefCell<Volume>>,
+    dir: Inode,
+    offset: u64,                                     // Of the next block to read.
+    entries: std::vec::IntoIter<(String, u32, u64)>, // Read, not yet listed.
+}
+
+impl Iterator for DirectoryIterExt2 {
+    type Item = Box<dyn super::filesystem::DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut vol = self.vol.borrow_mut();
+        loop {
+            if let Some((name, ino, cursor)) = self.entries.next() {
+                return Some(Box::new(DirectoryEntryExt2 {
+                    name,
+                    inode: vol.read_inode(ino).ok()?,
+                    cursor,
+                }));
+            }
+            if self.offset >= self.dir.size {
+                return None;
+            }
+            let (entries, next) = vol.read_dir_block(&self.dir, self.offset).ok()?;
+            self.entries = entries.into_iter();
+            self.offset = next;
+        }
+    }
+}
+
+impl super::DirectoryIter for DirectoryIterExt2 {}
+
+impl FileSystemExt2 {
+    // Symlinks are followed, except for the last path component if !follow_last.
+    fn lookup(&self, path: &str, follow_last: bool) -> Result<Inode, ErrorCode> {
+        path_walk::walk(self, path, follow_last)
+    }
+}
+
+impl PathWalk for FileSystemExt2 {
+    type Node = Inode;
+
+    fn root(&self) -> Result<Inode, ErrorCode> {
+        self.vol.borrow_mut().read_inode(ROOT_INO)
+    }
+
+    fn is_dir(&self, inode: &Inode) -> bool {
+        inode.is_dir()
+    }
+
+    fn is_symlink(&self, inode: &Inode) -> bool {
+        inode.is_symlink()
+    }
+
+    fn find(&self, dir: &Inode, name: &str) -> Result<Inode, ErrorCode> {
+        self.vol.borrow_mut().find(dir, name)
+    }
+
+    fn symlink_target(&self, inode: &Inode) -> Result<String, ErrorCode> {
+        let target = self.vol.borrow_mut().read_symlink(inode)?;
+        path_walk::local_target(&self.mount_point, target)
+    }
+}
+
+impl super::filesystem::FileSystem for FileSystemExt2 {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
+        let inode = self.lookup(path, true)?;
+        match inode.file_type() {
+            S_IFREG => Ok(Box::new(File {
+                vol: self.vol.clone(),
+                inode,
+            })),
+            S_IFDIR => Err(ErrorCode::NotFound),
+            _ => Err(ErrorCode::NotAllowed), // Devices, pipes, sockets.
+        }
+    }
+
+    fn create_file(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    // The volume is read-only, so the offsets of records are stable cursors.
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        let dir = self.lookup(path, true)?;
+        if !dir.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+
+        Ok(Box::new(DirectoryIterExt2 {
+            vol: self.vol.clone(),
+            dir,
+            offset: cursor,
+            entries: Vec::new().into_iter(),
+        }))
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        Ok(self.lookup(path, true)?.to_file_attr())
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        Ok(self.lookup(path, false)?.to_file_attr())
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        use rt_api::fs::FsStatsData;
+
+        let vol = self.vol.borrow();
+        Ok(FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_EXT2,
+            read_only: 1,
+            reserved: 0,
+            block_size: vol.block_size,
+            blocks_total: vol.blocks_count,
+            blocks_free: vol.free_blocks,
+        })
+    }
+
+    fn mkdir(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn unlink(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn delete_dir(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn delete_dir_all(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn rename(&'static mut self, _old: &str, _new: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn symlink(&'static mut self, _target: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        let inode = self.lookup(path, false)?;
+        if !inode.is_symlink() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.vol.borrow_mut().read_symlink(&inode)
+    }
+
+    fn link(&'static mut self, _existing: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn chmod(&'static mut self, _path: &str, _mode: u16) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn chown(&'static mut self, _path: &str, _uid: u32, _gid: u32) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    // Extended attributes of ext2 are not read (yet).
+    fn get_xattr(&'static mut self, _path: &str, _name: &str) -> Result<Vec<u8>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn list_xattrs(&'static mut self, _path: &str) -> Result<Vec<String>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_xattr(
+        &'static mut self,
+        _path: &str,
+        _name: &str,
+        _value: &[u8],
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn remove_xattr(&'static mut self, _path: &str, _name: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    // ext2 quota files are not read.
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+}
+
+pub(super) fn init(
+    virtio_drive: Arc<dyn moto_virtio::BlockDevice>,
+    lba: u64,
+    sectors: u64,
+    mount_point: &str,
+) -> Result<Box<dyn FileSystem>, ErrorCode> {
+    let mut part = Partition::new(virtio_drive, lba, sectors);
+    let mut sb = [0_u8; SUPERBLOCK_SIZE];
+    part.read(PROBE_OFFSET, &mut sb)?;
+    if !probe(&sb) {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let incompat = read_u32(&sb, 96);
+    if incompat & !SUPPORTED_INCOMPAT != 0 {
+        log::warn!(
+            "ext2: unsupported features 0x{:x}",
+            incompat & !SUPPORTED_INCOMPAT
+        );
+        return Err(ErrorCode::NotImplemented);
+    }
+
+    let inodes_count = read_u32(&sb, 0);
+    let blocks_count = read_u32(&sb, 4) as u64;
+    let free_blocks = read_u32(&sb, 12) as u64;
+    let first_data_block = read_u32(&sb, 20) as u64;
+    let log_block_size = read_u32(&sb, 24);
+    let blocks_per_group = read_u32(&sb, 32) as u64;
+    let inodes_per_group = read_u32(&sb, 40);
+    let inode_size = if read_u32(&sb, 76) == 0 {
+        GOOD_OLD_INODE_SIZE
+    } else {
+        read_u16(&sb, 88) as u32
+    };
+    if log_block_size > 6
+        || blocks_per_group == 0
+        || inodes_per_group == 0
+        || inode_size < GOOD_OLD_INODE_SIZE
+    {
+        return Err(ErrorCode::InvalidArgument);
+    }
+    let block_size = 1024_u64 << log_block_size;
+    if blocks_count * block_size > part.size() {
+        log::warn!("ext2: the volume is larger than its partition");
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    // The group descriptors follow the superblock.
+    let groups = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+    let mut descs = vec![0_u8; (groups * GROUP_DESC_SIZE) as usize];
+    part.read((first_data_block + 1) * block_size, &mut descs)?;
+    let inode_tables = descs
+        .chunks_exact(GROUP_DESC_SIZE as usize)
+        .map(|desc| read_u32(desc, 8) as u64)
+        .collect();
+
+    Ok(Box::new(FileSystemExt2 {
+        mount_point: mount_point.to_owned(),
+        vol: Rc::new(RefCell::new(Volume {
+            part,
+            block_size,
+            blocks_count,
+            free_blocks,
+            inodes_count,
+            inodes_per_group,
+            inode_size,
+            inode_tables,
+        })),
+    }))
+}
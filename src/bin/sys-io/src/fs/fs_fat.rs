@@ -0,0 +1,1444 @@
+// FAT32 volumes, e.g. a second drive formatted on the host, for exchanging
+// files with other systems. They are mounted at /mnt/fatN (see
+// super::filesystem::init()).
+//
+// Long file names (VFAT) are supported; FAT12/16 and exFAT are not. FAT has
+// no owners, permissions, or links: entries belong to root and are writable
+// by everyone unless they have the read-only attribute, which chmod sets.
+//
+// The whole FAT is kept in memory; directories are read from the drive.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::filesystem::FileSystem;
+use super::partition::Partition;
+use alloc::sync::Arc;
+use moto_runtime::rt_api;
+use moto_sys::ErrorCode;
+
+const SECTOR_SIZE: u32 = 512;
+const DIR_ENTRY_SIZE: u32 = 32;
+const MIN_FAT32_CLUSTERS: u32 = 65525;
+const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+
+const FAT_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+const FAT_FREE: u32 = 0;
+const FAT_EOC: u32 = 0x0FFF_FFF8; // And above: the end of a chain.
+const FIRST_CLUSTER: u32 = 2;
+
+const FSINFO_LEAD_SIG: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIG: u32 = 0x6141_7272;
+const FSINFO_FREE_COUNT: usize = 488;
+const FSINFO_NEXT_FREE: usize = 492;
+
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LFN: u8 = 0x0F;
+
+const ENTRY_FREE: u8 = 0xE5;
+const ENTRY_END: u8 = 0x00;
+const ENTRY_KANJI_E5: u8 = 0x05; // A first byte of 0xE5 is stored as 0x05.
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXT: u8 = 0x10;
+
+const LFN_LAST: u8 = 0x40;
+const LFN_CHARS: usize = 13;
+const LFN_CHAR_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const MAX_NAME_LEN: usize = 255; // In UTF-16 units.
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..(offset + 2)].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..(offset + 4)].try_into().unwrap())
+}
+
+fn write_u16(bytes: &mut [u8], offset: usize, val: u16) {
+    bytes[offset..(offset + 2)].copy_from_slice(&val.to_le_bytes());
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, val: u32) {
+    bytes[offset..(offset + 4)].copy_from_slice(&val.to_le_bytes());
+}
+
+// The layout of the volume, from the BIOS Parameter Block in its first sector.
+struct Geometry {
+    cluster_size: u32, // In bytes.
+    fat_start: u64,    // In bytes.
+    fat_size: u64,     // In bytes, of one copy.
+    num_fats: u32,
+    active_fat: Option<u32>, // Some if the copies are not mirrored.
+    root_cluster: u32,
+    fsinfo: Option<u64>, // In bytes.
+    data_start: u64,     // In bytes.
+    cluster_count: u32,
+}
+
+impl Geometry {
+    fn parse(sector: &[u8]) -> Option<Self> {
+        if sector.len() < SECTOR_SIZE as usize || sector[510..512] != [0x55, 0xaa] {
+            return None;
+        }
+        if &sector[82..90] != b"FAT32   " {
+            return None;
+        }
+
+        let bytes_per_sector = read_u16(sector, 11) as u32;
+        let sectors_per_cluster = sector[13] as u32;
+        let reserved_sectors = read_u16(sector, 14) as u32;
+        let num_fats = sector[16] as u32;
+        let root_entry_count = read_u16(sector, 17);
+        let total_sectors_16 = read_u16(sector, 19) as u32;
+        let fat_size_16 = read_u16(sector, 22);
+        let total_sectors_32 = read_u32(sector, 32);
+        let fat_sectors = read_u32(sector, 36);
+        let ext_flags = read_u16(sector, 40);
+        let root_cluster = read_u32(sector, 44);
+        let fsinfo_sector = read_u16(sector, 48) as u32;
+
+        if bytes_per_sector != SECTOR_SIZE
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved_sectors == 0
+            || num_fats == 0
+            || root_entry_count != 0
+            || fat_size_16 != 0
+            || fat_sectors == 0
+        {
+            return None;
+        }
+
+        let total_sectors = if total_sectors_16 != 0 {
+            total_sectors_16
+        } else {
+            total_sectors_32
+        };
+        let data_sector = reserved_sectors + num_fats * fat_sectors;
+        if data_sector >= total_sectors {
+            return None;
+        }
+        let cluster_count = ((total_sectors - data_sector) / sectors_per_cluster)
+            .min(fat_sectors * SECTOR_SIZE / 4 - FIRST_CLUSTER);
+        if cluster_count < MIN_FAT32_CLUSTERS || root_cluster < FIRST_CLUSTER {
+            return None;
+        }
+
+        Some(Self {
+            cluster_size: sectors_per_cluster * SECTOR_SIZE,
+            fat_start: reserved_sectors as u64 * SECTOR_SIZE as u64,
+            fat_size: fat_sectors as u64 * SECTOR_SIZE as u64,
+            num_fats,
+            active_fat: if ext_flags & 0x80 != 0 {
+                Some((ext_flags & 0xF) as u32)
+            } else {
+                None
+            },
+            root_cluster,
+            fsinfo: if fsinfo_sector != 0 && fsinfo_sector < reserved_sectors {
+                Some(fsinfo_sector as u64 * SECTOR_SIZE as u64)
+            } else {
+                None
+            },
+            data_start: data_sector as u64 * SECTOR_SIZE as u64,
+            cluster_count,
+        })
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= FIRST_CLUSTER && cluster < self.cluster_count + FIRST_CLUSTER
+    }
+
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - FIRST_CLUSTER) as u64 * self.cluster_size as u64
+    }
+}
+
+// Whether @sector is the boot sector of a FAT32 volume.
+pub(super) fn probe(sector: &[u8]) -> bool {
+    Geometry::parse(sector).is_some()
+}
+
+// A directory entry, as read from the drive.
+#[derive(Clone)]
+struct Entry {
+    name: String,  // The long name, if there is one.
+    raw: [u8; 32], // The short entry.
+    dir: u32,      // The first cluster of the containing directory; 0 for the root.
+    offset: u32,   // Of the short entry in the directory.
+    slots: u32,    // The short entry and its long name entries.
+}
+
+impl Entry {
+    fn root(root_cluster: u32) -> Self {
+        let mut raw = [0_u8; 32];
+        raw[11] = ATTR_DIRECTORY;
+        set_first_cluster(&mut raw, root_cluster);
+        Self {
+            name: String::new(),
+            raw,
+            dir: 0,
+            offset: 0,
+            slots: 0,
+        }
+    }
+
+    fn is_root(&self) -> bool {
+        self.dir == 0
+    }
+
+    fn attr(&self) -> u8 {
+        self.raw[11]
+    }
+
+    fn is_dir(&self) -> bool {
+        self.attr() & ATTR_DIRECTORY != 0
+    }
+
+    fn first_cluster(&self) -> u32 {
+        ((read_u16(&self.raw, 20) as u32) << 16) | read_u16(&self.raw, 26) as u32
+    }
+
+    fn size(&self) -> u32 {
+        read_u32(&self.raw, 28)
+    }
+
+    fn first_slot(&self) -> u32 {
+        self.offset - (self.slots - 1) * DIR_ENTRY_SIZE
+    }
+
+    fn key(&self) -> (u32, u32) {
+        (self.dir, self.offset)
+    }
+
+    fn to_file_attr(&self) -> rt_api::fs::FileAttrData {
+        use rt_api::fs::FileAttrData;
+
+        let read_only = self.attr() & ATTR_READ_ONLY != 0;
+        let mode = match (self.is_dir(), read_only) {
+            (true, false) => 0o777,
+            (true, true) => 0o555,
+            (false, false) => 0o666,
+            (false, true) => 0o444,
+        };
+        FileAttrData {
+            version: 1,
+            self_size: core::mem::size_of::<FileAttrData>() as u16,
+            file_perm: if read_only {
+                rt_api::fs::FILE_PERM_READ
+            } else {
+                rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE
+            },
+            file_type: if self.is_dir() {
+                rt_api::fs::FILE_TYPE_DIR
+            } else {
+                rt_api::fs::FILE_TYPE_FILE
+            },
+            reserved: 0,
+            size: if self.is_dir() { 0 } else { self.size() as u64 },
+            created: from_dos_time(read_u16(&self.raw, 16), read_u16(&self.raw, 14)),
+            accessed: 0,
+            modified: from_dos_time(read_u16(&self.raw, 24), read_u16(&self.raw, 22)),
+            uid: moto_sys::caps::ROOT_UID,
+            gid: 0,
+            mode,
+            reserved_2: 0,
+        }
+    }
+}
+
+fn set_first_cluster(raw: &mut [u8], cluster: u32) {
+    write_u16(raw, 20, (cluster >> 16) as u16);
+    write_u16(raw, 26, cluster as u16);
+}
+
+fn set_modified(raw: &mut [u8]) {
+    let (date, time) = dos_time_now();
+    write_u16(raw, 24, date);
+    write_u16(raw, 22, time);
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date, and back.
+// See http://howardhinnant.github.io/date_algorithms.html.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// FAT timestamps are in local time; we treat them as UTC.
+fn from_dos_time(date: u16, time: u16) -> u64 {
+    if date == 0 {
+        return 0;
+    }
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xF).clamp(1, 12) as u32;
+    let day = (date & 0x1F).max(1) as u32;
+    let secs = days_from_civil(year, month, day) * 86400
+        + (time >> 11) as i64 * 3600
+        + ((time >> 5) & 0x3F) as i64 * 60
+        + (time & 0x1F) as i64 * 2;
+    (secs as u64) * 1_000_000_000
+}
+
+fn dos_time_now() -> (u16, u16) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs_of_day = (secs % 86400) as u16;
+    let date = (((year - 1980).clamp(0, 127) as u16) << 9) | ((month as u16) << 5) | (day as u16);
+    let time =
+        ((secs_of_day / 3600) << 11) | (((secs_of_day / 60) % 60) << 5) | ((secs_of_day % 60) / 2);
+    (date, time)
+}
+
+fn short_name_checksum(short: &[u8]) -> u8 {
+    short[0..11].iter().fold(0_u8, |sum, c| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(*c)
+    })
+}
+
+fn decode_short_name(raw: &[u8]) -> String {
+    let nt = raw[12];
+    let mut base: Vec<u8> = raw[0..8].to_vec();
+    if base[0] == ENTRY_KANJI_E5 {
+        base[0] = ENTRY_FREE;
+    }
+    let decode = |bytes: &[u8], lower: bool| -> String {
+        let s: String = bytes
+            .iter()
+            .map(|b| *b as char)
+            .collect::<String>()
+            .trim_end_matches(' ')
+            .to_owned();
+        if lower {
+            s.to_lowercase()
+        } else {
+            s
+        }
+    };
+    let base = decode(&base, nt & NT_LOWER_BASE != 0);
+    let ext = decode(&raw[8..11], nt & NT_LOWER_EXT != 0);
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.ends_with('.')
+        && !name.ends_with(' ')
+        && name.encode_utf16().count() <= MAX_NAME_LEN
+        && !name
+            .chars()
+            .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+}
+
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || b"$%'-_@~`!(){}^#&".contains(&c)
+}
+
+// The 8.3 form of @name, if it is an upper case 8.3 name that needs no long name.
+fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    if base.is_empty()
+        || base.len() > 8
+        || ext.len() > 3
+        || !base.bytes().chain(ext.bytes()).all(is_short_name_char)
+    {
+        return None;
+    }
+
+    let mut short = [b' '; 11];
+    short[0..base.len()].copy_from_slice(base.as_bytes());
+    short[8..(8 + ext.len())].copy_from_slice(ext.as_bytes());
+    Some(short)
+}
+
+// A unique "BASE~N.EXT" short name for a long name.
+fn generate_short_name(name: &str, taken: &[[u8; 11]]) -> Result<[u8; 11], ErrorCode> {
+    let to_short = |s: &str| -> Vec<u8> {
+        s.chars()
+            .filter(|c| *c != ' ' && *c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if c.is_ascii() && is_short_name_char(c as u8) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .collect()
+    };
+    let (base, ext) = match name.trim_start_matches('.').rsplit_once('.') {
+        Some((base, ext)) => (to_short(base), to_short(ext)),
+        None => (to_short(name), vec![]),
+    };
+
+    let mut short = [b' '; 11];
+    for (idx, c) in ext.iter().take(3).enumerate() {
+        short[8 + idx] = *c;
+    }
+    for num in 1..1_000_000_u32 {
+        let tail = format!("~{}", num);
+        let keep = base.len().min(8 - tail.len());
+        short[0..8].fill(b' ');
+        short[0..keep].copy_from_slice(&base[0..keep]);
+        short[keep..(keep + tail.len())].copy_from_slice(tail.as_bytes());
+        if !taken.contains(&short) {
+            return Ok(short);
+        }
+    }
+    Err(ErrorCode::StorageFull)
+}
+
+// The long name entries for @name, in the order they are stored.
+fn lfn_entries(name: &str, checksum: u8) -> Vec<[u8; 32]> {
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    if units.len() % LFN_CHARS != 0 {
+        units.push(0);
+        while units.len() % LFN_CHARS != 0 {
+            units.push(0xFFFF);
+        }
+    }
+
+    let count = units.len() / LFN_CHARS;
+    let mut entries = vec![];
+    for idx in (0..count).rev() {
+        let mut entry = [0_u8; 32];
+        entry[0] = (idx + 1) as u8 | if idx == count - 1 { LFN_LAST } else { 0 };
+        entry[11] = ATTR_LFN;
+        entry[13] = checksum;
+        for (pos, offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+            write_u16(&mut entry, *offset, units[idx * LFN_CHARS + pos]);
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+// Parses the entries of a directory; "." and ".." are skipped.
+fn parse_dir(dir: u32, bytes: &[u8]) -> Vec<Entry> {
+    let mut entries = vec![];
+    // The long name being assembled: its UTF-16 units, the next expected
+    // ordinal, the checksum, and the number of slots so far.
+    let mut lfn: Option<(Vec<u16>, u8, u8, u32)> = None;
+
+    for (idx, slot) in bytes.chunks_exact(DIR_ENTRY_SIZE as usize).enumerate() {
+        match slot[0] {
+            ENTRY_END => break,
+            ENTRY_FREE => {
+                lfn = None;
+                continue;
+            }
+            _ => {}
+        }
+
+        if slot[11] & 0x3F == ATTR_LFN {
+            let ord = slot[0] & 0x1F;
+            let units = LFN_CHAR_OFFSETS
+                .iter()
+                .map(|offset| read_u16(slot, *offset));
+            if slot[0] & LFN_LAST != 0 && ord > 0 {
+                let mut name = vec![0_u16; ord as usize * LFN_CHARS];
+                name[((ord - 1) as usize * LFN_CHARS)..(ord as usize * LFN_CHARS)]
+                    .iter_mut()
+                    .zip(units)
+                    .for_each(|(dst, src)| *dst = src);
+                lfn = Some((name, ord - 1, slot[13], 1));
+            } else if let Some((name, next, checksum, slots)) = &mut lfn {
+                if ord == *next && ord > 0 && slot[13] == *checksum {
+                    name[((ord - 1) as usize * LFN_CHARS)..(ord as usize * LFN_CHARS)]
+                        .iter_mut()
+                        .zip(units)
+                        .for_each(|(dst, src)| *dst = src);
+                    *next -= 1;
+                    *slots += 1;
+                } else {
+                    lfn = None;
+                }
+            }
+            continue;
+        }
+
+        let lfn = lfn.take();
+        if slot[11] & ATTR_VOLUME_ID != 0 || slot[0] == b'.' {
+            continue;
+        }
+
+        let raw: [u8; 32] = slot.try_into().unwrap();
+        let (name, slots) = match lfn {
+            Some((units, 0, checksum, slots)) if checksum == short_name_checksum(&raw) => {
+                let len = units.iter().position(|c| *c == 0).unwrap_or(units.len());
+                (String::from_utf16_lossy(&units[0..len]), slots + 1)
+            }
+            _ => (decode_short_name(&raw), 1),
+        };
+        entries.push(Entry {
+            name,
+            raw,
+            dir,
+            offset: idx as u32 * DIR_ENTRY_SIZE,
+            slots,
+        });
+    }
+    entries
+}
+
+fn names_match(a: &str, b: &str) -> bool {
+    a == b || a.to_uppercase() == b.to_uppercase()
+}
+
+// Splits @path into its parent directory and the last component.
+fn split_parent_child(path: &str) -> Result<(&str, &str), ErrorCode> {
+    let (parent, child) = path
+        .trim_end_matches('/')
+        .rsplit_once('/')
+        .ok_or(ErrorCode::InvalidFilename)?;
+    if child.is_empty() || child == "." || child == ".." {
+        return Err(ErrorCode::InvalidFilename);
+    }
+    Ok((if parent.is_empty() { "/" } else { parent }, child))
+}
+
+// The state of an open file, shared by its handles.
+struct Node {
+    dir: u32,    // Of the entry, see Entry; updated on rename.
+    offset: u32, // Of the entry, see Entry; updated on rename.
+    clusters: Vec<u32>,
+    size: u32,
+    unlinked: bool, // The clusters are freed when the last handle is closed.
+}
+
+struct Volume {
+    part: Partition,
+    geo: Geometry,
+    fat: Vec<u32>, // Indexed by cluster.
+    free_clusters: u32,
+    next_free: u32, // Where to start looking for a free cluster.
+    open_files: HashMap<(u32, u32), Weak<RefCell<Node>>>, // By Entry::key().
+}
+
+impl Volume {
+    fn write_fat_entry(&mut self, cluster: u32, val: u32) -> Result<(), ErrorCode> {
+        let cluster = cluster as usize;
+        self.fat[cluster] = (self.fat[cluster] & !FAT_ENTRY_MASK) | (val & FAT_ENTRY_MASK);
+
+        // Write the sector the entry is in to every copy of the FAT.
+        const PER_SECTOR: usize = SECTOR_SIZE as usize / 4;
+        let first = cluster / PER_SECTOR * PER_SECTOR;
+        let mut sector = [0_u8; SECTOR_SIZE as usize];
+        for (idx, val) in self.fat[first..(first + PER_SECTOR)].iter().enumerate() {
+            write_u32(&mut sector, idx * 4, *val);
+        }
+        let offset = (first * 4) as u64;
+        for fat in 0..self.geo.num_fats {
+            if self.geo.active_fat.is_some_and(|active| active != fat) {
+                continue;
+            }
+            let start = self.geo.fat_start + fat as u64 * self.geo.fat_size;
+            self.part.write(start + offset, &sector)?;
+        }
+        Ok(())
+    }
+
+    fn next_cluster(&self, cluster: u32) -> u32 {
+        self.fat[cluster as usize] & FAT_ENTRY_MASK
+    }
+
+    fn chain(&self, first: u32) -> Result<Vec<u32>, ErrorCode> {
+        let mut chain = vec![];
+        if first == FAT_FREE {
+            return Ok(chain); // An empty file.
+        }
+
+        let mut cluster = first;
+        loop {
+            if !self.geo.is_valid_cluster(cluster) || chain.len() > self.geo.cluster_count as usize
+            {
+                log::warn!("FAT: bad cluster chain at {}", first);
+                return Err(ErrorCode::InternalError);
+            }
+            chain.push(cluster);
+            cluster = self.next_cluster(cluster);
+            if cluster >= FAT_EOC {
+                return Ok(chain);
+            }
+        }
+    }
+
+    // Allocates a cluster and appends it to the chain ending with @last.
+    fn alloc_cluster(&mut self, last: Option<u32>) -> Result<u32, ErrorCode> {
+        if self.free_clusters == 0 {
+            return Err(ErrorCode::StorageFull);
+        }
+
+        let count = self.geo.cluster_count;
+        let start = self.next_free.max(FIRST_CLUSTER) - FIRST_CLUSTER;
+        let cluster = (0..count)
+            .map(|idx| (start + idx) % count + FIRST_CLUSTER)
+            .find(|cluster| self.next_cluster(*cluster) == FAT_FREE)
+            .ok_or(ErrorCode::StorageFull)?;
+
+        self.write_fat_entry(cluster, FAT_ENTRY_MASK)?;
+        if let Some(last) = last {
+            self.write_fat_entry(last, cluster)?;
+        }
+        self.free_clusters -= 1;
+        self.next_free = cluster + 1;
+        Ok(cluster)
+    }
+
+    fn free_chain(&mut self, first: u32) -> Result<(), ErrorCode> {
+        for cluster in self.chain(first)? {
+            self.write_fat_entry(cluster, FAT_FREE)?;
+            self.free_clusters += 1;
+        }
+        Ok(())
+    }
+
+    fn zero_cluster(&mut self, cluster: u32) -> Result<(), ErrorCode> {
+        let zeroes = vec![0_u8; self.geo.cluster_size as usize];
+        self.part.write(self.geo.cluster_offset(cluster), &zeroes)
+    }
+
+    // Reads (@write == None) or writes the bytes of the cluster chain @chain
+    // at @offset.
+    fn chain_io(
+        &mut self,
+        chain: &[u32],
+        offset: u64,
+        mut read: Option<&mut [u8]>,
+        write: Option<&[u8]>,
+    ) -> Result<(), ErrorCode> {
+        let cluster_size = self.geo.cluster_size as u64;
+        let len = read
+            .as_ref()
+            .map(|b| b.len())
+            .or(write.map(|b| b.len()))
+            .unwrap();
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let cluster = *chain
+                .get((pos / cluster_size) as usize)
+                .ok_or(ErrorCode::InternalError)?;
+            let skip = pos % cluster_size;
+            let piece = (len - done).min((cluster_size - skip) as usize);
+            let disk_offset = self.geo.cluster_offset(cluster) + skip;
+            match (&mut read, write) {
+                (Some(buf), _) => self
+                    .part
+                    .read(disk_offset, &mut buf[done..(done + piece)])?,
+                (None, Some(buf)) => self.part.write(disk_offset, &buf[done..(done + piece)])?,
+                (None, None) => unreachable!(),
+            }
+            done += piece;
+        }
+        Ok(())
+    }
+
+    fn read_dir_bytes(&mut self, dir: u32) -> Result<(Vec<u32>, Vec<u8>), ErrorCode> {
+        let chain = self.chain(dir)?;
+        let mut bytes = vec![0_u8; chain.len() * self.geo.cluster_size as usize];
+        self.chain_io(&chain, 0, Some(&mut bytes), None)?;
+        Ok((chain, bytes))
+    }
+
+    // The entries of the directory at cluster @first in the window of slots
+    // at @offset, and where the next window starts (None: at the end). A long
+    // name cut off by the end of the window starts the next one; windows are
+    // large enough to hold the longest one.
+    fn read_dir_window(
+        &mut self,
+        first: u32,
+        offset: u32,
+    ) -> Result<(Vec<Entry>, Option<u32>), ErrorCode> {
+        const MIN_WINDOW: u32 = 32 * DIR_ENTRY_SIZE; // > 20 long name slots + 1.
+
+        let chain = self.chain(first)?;
+        let dir_size = chain.len() as u32 * self.geo.cluster_size;
+        let end = dir_size.min(offset + self.geo.cluster_size.max(MIN_WINDOW));
+        let mut bytes = vec![0_u8; end.saturating_sub(offset) as usize];
+        self.chain_io(&chain, offset as u64, Some(&mut bytes), None)?;
+
+        let mut entries = parse_dir(first, &bytes);
+        for entry in &mut entries {
+            entry.offset += offset;
+        }
+        let slots = bytes.chunks_exact(DIR_ENTRY_SIZE as usize);
+        if end == dir_size || slots.clone().any(|slot| slot[0] == ENTRY_END) {
+            return Ok((entries, None));
+        }
+
+        let trailing_lfn = slots
+            .rev()
+            .take_while(|slot| slot[0] != ENTRY_FREE && slot[11] & 0x3F == ATTR_LFN)
+            .count() as u32;
+        match end - trailing_lfn * DIR_ENTRY_SIZE {
+            next if next > offset => Ok((entries, Some(next))),
+            _ => Ok((entries, Some(end))), // Not a valid long name: skip it.
+        }
+    }
+
+    fn read_dir(&mut self, dir: &Entry) -> Result<Vec<Entry>, ErrorCode> {
+        if !dir.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        let first = dir.first_cluster();
+        let (_, bytes) = self.read_dir_bytes(first)?;
+        Ok(parse_dir(first, &bytes))
+    }
+
+    fn lookup(&mut self, path: &str) -> Result<Entry, ErrorCode> {
+        let mut dirs = vec![Entry::root(self.geo.root_cluster)];
+        for component in path.split('/') {
+            if component.is_empty() || component == "." {
+                continue;
+            }
+            let last = dirs.last().unwrap();
+            if !last.is_dir() {
+                return Err(ErrorCode::NotADirectory);
+            }
+            if component == ".." {
+                if dirs.len() > 1 {
+                    dirs.pop();
+                }
+                continue;
+            }
+
+            let entry = self
+                .read_dir(last)?
+                .into_iter()
+                .find(|entry| names_match(&entry.name, component))
+                .ok_or(ErrorCode::NotFound)?;
+            dirs.push(entry);
+        }
+        Ok(dirs.pop().unwrap())
+    }
+
+    fn write_entry(&mut self, entry: &Entry) -> Result<(), ErrorCode> {
+        assert!(!entry.is_root());
+        let chain = self.chain(entry.dir)?;
+        self.chain_io(&chain, entry.offset as u64, None, Some(&entry.raw))
+    }
+
+    fn delete_entry(&mut self, entry: &Entry) -> Result<(), ErrorCode> {
+        let chain = self.chain(entry.dir)?;
+        for slot in 0..entry.slots {
+            let offset = entry.first_slot() + slot * DIR_ENTRY_SIZE;
+            self.chain_io(&chain, offset as u64, None, Some(&[ENTRY_FREE]))?;
+        }
+        Ok(())
+    }
+
+    // Adds an entry named @name to @dir, with the attributes, times, size
+    // and first cluster of @template.
+    fn add_entry(
+        &mut self,
+        dir: &Entry,
+        name: &str,
+        template: &[u8; 32],
+    ) -> Result<Entry, ErrorCode> {
+        if !is_valid_name(name) {
+            return Err(ErrorCode::InvalidFilename);
+        }
+        let existing = self.read_dir(dir)?;
+        if existing.iter().any(|entry| names_match(&entry.name, name)) {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+
+        let taken: Vec<[u8; 11]> = existing
+            .iter()
+            .map(|entry| entry.raw[0..11].try_into().unwrap())
+            .collect();
+        let mut raw = *template;
+        raw[12] = 0;
+        let slots = match exact_short_name(name).filter(|short| !taken.contains(short)) {
+            Some(short) => {
+                raw[0..11].copy_from_slice(&short);
+                vec![]
+            }
+            None => {
+                raw[0..11].copy_from_slice(&generate_short_name(name, &taken)?);
+                lfn_entries(name, short_name_checksum(&raw))
+            }
+        };
+        let count = slots.len() as u32 + 1;
+
+        // Find a run of free slots, growing the directory if needed.
+        let first = dir.first_cluster();
+        let (mut chain, bytes) = self.read_dir_bytes(first)?;
+        let mut run = 0;
+        let mut start = None;
+        for (idx, slot) in bytes.chunks_exact(DIR_ENTRY_SIZE as usize).enumerate() {
+            if slot[0] == ENTRY_FREE || slot[0] == ENTRY_END {
+                run += 1;
+                if run == count {
+                    start = Some((idx as u32 + 1 - count) * DIR_ENTRY_SIZE);
+                    break;
+                }
+            } else {
+                run = 0;
+            }
+        }
+        let start = match start {
+            Some(start) => start,
+            None => {
+                // The free slots at the end are the start of the run.
+                let start = bytes.len() as u32 - run * DIR_ENTRY_SIZE;
+                while chain.len() as u32 * self.geo.cluster_size < start + count * DIR_ENTRY_SIZE {
+                    let cluster = self.alloc_cluster(chain.last().copied())?;
+                    self.zero_cluster(cluster)?;
+                    chain.push(cluster);
+                }
+                start
+            }
+        };
+
+        let mut offset = start;
+        for slot in slots.iter().chain(core::iter::once(&raw)) {
+            self.chain_io(&chain, offset as u64, None, Some(slot))?;
+            offset += DIR_ENTRY_SIZE;
+        }
+
+        Ok(Entry {
+            name: name.to_owned(),
+            raw,
+            dir: first,
+            offset: offset - DIR_ENTRY_SIZE,
+            slots: count,
+        })
+    }
+
+    fn write_fsinfo(&mut self) -> Result<(), ErrorCode> {
+        let Some(offset) = self.geo.fsinfo else {
+            return Ok(());
+        };
+        let mut sector = [0_u8; SECTOR_SIZE as usize];
+        self.part.read(offset, &mut sector)?;
+        if read_u32(&sector, 0) != FSINFO_LEAD_SIG || read_u32(&sector, 484) != FSINFO_STRUCT_SIG {
+            return Ok(());
+        }
+        write_u32(&mut sector, FSINFO_FREE_COUNT, self.free_clusters);
+        write_u32(&mut sector, FSINFO_NEXT_FREE, self.next_free);
+        self.part.write(offset, &sector)
+    }
+
+    // Removes @entry; the clusters of open files are freed when they are closed.
+    fn remove(&mut self, entry: &Entry) -> Result<(), ErrorCode> {
+        self.delete_entry(entry)?;
+        if let Some(node) = self
+            .open_files
+            .remove(&entry.key())
+            .and_then(|n| n.upgrade())
+        {
+            node.borrow_mut().unlinked = true;
+            return Ok(());
+        }
+        self.free_chain(entry.first_cluster())
+    }
+}
+
+struct FileSystemFat {
+    mount_point: String,
+    vol: Rc<RefCell<Volume>>,
+}
+
+struct File {
+    vol: Rc<RefCell<Volume>>,
+    node: Rc<RefCell<Node>>,
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.node) > 1 {
+            return;
+        }
+        let node = self.node.borrow();
+        let mut vol = self.vol.borrow_mut();
+        if node.unlinked {
+            if let Some(first) = node.clusters.first() {
+                let _ = vol.free_chain(*first);
+            }
+        } else {
+            vol.open_files.remove(&(node.dir, node.offset));
+        }
+    }
+}
+
+impl File {
+    fn grow(vol: &mut Volume, node: &mut Node, size: u64) -> Result<(), ErrorCode> {
+        let cluster_size = vol.geo.cluster_size as u64;
+        while (node.clusters.len() as u64) * cluster_size < size {
+            let cluster = vol.alloc_cluster(node.clusters.last().copied())?;
+            node.clusters.push(cluster);
+        }
+        Ok(())
+    }
+
+    // Writes the size and the first cluster to the directory entry.
+    fn update_entry(&self, vol: &mut Volume, node: &Node) -> Result<(), ErrorCode> {
+        let chain = vol.chain(node.dir)?;
+        let mut raw = [0_u8; 32];
+        vol.chain_io(&chain, node.offset as u64, Some(&mut raw), None)?;
+        set_first_cluster(&mut raw, node.clusters.first().copied().unwrap_or(FAT_FREE));
+        write_u32(&mut raw, 28, node.size);
+        set_modified(&mut raw);
+        raw[11] |= ATTR_ARCHIVE;
+        vol.chain_io(&chain, node.offset as u64, None, Some(&raw))
+    }
+}
+
+impl super::File for File {
+    fn unique_id(&self) -> u64 {
+        Rc::as_ptr(&self.node) as usize as u64
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self.node.borrow().size as u64)
+    }
+
+    fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let end = offset + buf.len() as u64;
+        if end > MAX_FILE_SIZE {
+            return Err(ErrorCode::FileTooLarge);
+        }
+
+        let mut vol = self.vol.borrow_mut();
+        let mut node = self.node.borrow_mut();
+        if let Err(err) = Self::grow(&mut vol, &mut node, end) {
+            // Keep the clusters that were allocated in the file.
+            if !node.unlinked {
+                let _ = self.update_entry(&mut vol, &node);
+            }
+            return Err(err);
+        }
+
+        // Writing past the end leaves a zero-filled gap.
+        if offset > node.size as u64 {
+            let gap = vec![0_u8; (offset - node.size as u64) as usize];
+            vol.chain_io(&node.clusters, node.size as u64, None, Some(&gap))?;
+        }
+        vol.chain_io(&node.clusters, offset, None, Some(buf))?;
+
+        node.size = node.size.max(end as u32);
+        if !node.unlinked {
+            self.update_entry(&mut vol, &node)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let node = self.node.borrow();
+        let size = node.size as u64;
+        if offset == size {
+            return Ok(0);
+        }
+        if offset > size {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let len = (size - offset).min(buf.len() as u64) as usize;
+        self.vol
+            .borrow_mut()
+            .chain_io(&node.clusters, offset, Some(&mut buf[0..len]), None)?;
+        Ok(len)
+    }
+
+    // FAT has no ordering guarantees to keep, so data_only doesn't matter.
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        let mut vol = self.vol.borrow_mut();
+        vol.write_fsinfo()?;
+        vol.part.flush()
+    }
+
+    // FAT has no holes: both allocating and punching write zeroes.
+    fn allocate(&mut self, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode> {
+        let end = offset.checked_add(len).ok_or(ErrorCode::FileTooLarge)?;
+        let size = self.node.borrow().size as u64;
+        let (start, end) = if punch_hole {
+            (offset, end.min(size))
+        } else {
+            (size, end)
+        };
+
+        let zeroes = vec![0_u8; 64 * 1024];
+        let mut pos = start;
+        while pos < end {
+            let chunk = (end - pos).min(zeroes.len() as u64) as usize;
+            pos += self.write_offset(pos, &zeroes[0..chunk])? as u64;
+        }
+        Ok(())
+    }
+
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+struct DirectoryEntryFat {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    cursor: u64, // Just past the short entry in the directory.
+}
+
+impl super::filesystem::DirectoryEntry for DirectoryEntryFat {
+    fn is_directory(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_symlink(&self) -> bool {
+        false
+    }
+
+    fn filename(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn size(&self) -> Result<u64, ErrorCode> {
+        Ok(self.size)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
+}
+
+// Directories are read a window of slots at a time (see read_dir_window).
+struct DirectoryIterFat {
+    vol: Rc<RefCell<Volume>>,
+    dir: u32,            // The first cluster.
+    offset: Option<u32>, // Of the next window; None at the end.
+    entries: std::vec::IntoIter<Entry>,
+}
+
+impl Iterator for DirectoryIterFat {
+    type Item = Box<dyn super::filesystem::DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.next() {
+                return Some(Box::new(DirectoryEntryFat {
+                    is_dir: entry.is_dir(),
+                    size: if entry.is_dir() {
+                        0
+                    } else {
+                        entry.size() as u64
+                    },
+                    cursor: (entry.offset + DIR_ENTRY_SIZE) as u64,
+                    name: entry.name,
+                }));
+            }
+            let (entries, next) = self
+                .vol
+                .borrow_mut()
+                .read_dir_window(self.dir, self.offset?)
+                .ok()?;
+            self.entries = entries.into_iter();
+            self.offset = next;
+        }
+    }
+}
+
+impl super::DirectoryIter for DirectoryIterFat {}
+
+impl FileSystemFat {
+    fn lookup_parent<'a>(&self, path: &'a str) -> Result<(Entry, &'a str), ErrorCode> {
+        let (parent, child) = split_parent_child(path)?;
+        let parent = self.vol.borrow_mut().lookup(parent)?;
+        if !parent.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        Ok((parent, child))
+    }
+
+    fn new_entry_template(attr: u8) -> [u8; 32] {
+        let mut raw = [0_u8; 32];
+        raw[11] = attr;
+        let (date, time) = dos_time_now();
+        write_u16(&mut raw, 16, date);
+        write_u16(&mut raw, 14, time);
+        write_u16(&mut raw, 18, date);
+        write_u16(&mut raw, 24, date);
+        write_u16(&mut raw, 22, time);
+        raw
+    }
+
+    fn is_empty_dir(&self, entry: &Entry) -> Result<bool, ErrorCode> {
+        Ok(self.vol.borrow_mut().read_dir(entry)?.is_empty())
+    }
+
+    fn remove_all(&self, dir: &Entry) -> Result<(), ErrorCode> {
+        let entries = self.vol.borrow_mut().read_dir(dir)?;
+        for entry in entries {
+            if entry.is_dir() {
+                self.remove_all(&entry)?;
+            }
+            self.vol.borrow_mut().remove(&entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl super::filesystem::FileSystem for FileSystemFat {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
+        let entry = self.vol.borrow_mut().lookup(path)?;
+        if entry.is_dir() {
+            return Err(ErrorCode::NotFound);
+        }
+
+        let mut vol = self.vol.borrow_mut();
+        if let Some(node) = vol.open_files.get(&entry.key()).and_then(|n| n.upgrade()) {
+            return Ok(Box::new(File {
+                vol: self.vol.clone(),
+                node,
+            }));
+        }
+
+        let node = Rc::new(RefCell::new(Node {
+            dir: entry.dir,
+            offset: entry.offset,
+            clusters: vol.chain(entry.first_cluster())?,
+            size: entry.size(),
+            unlinked: false,
+        }));
+        vol.open_files.insert(entry.key(), Rc::downgrade(&node));
+        Ok(Box::new(File {
+            vol: self.vol.clone(),
+            node,
+        }))
+    }
+
+    fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (parent, child) = self.lookup_parent(path)?;
+        let template = Self::new_entry_template(ATTR_ARCHIVE);
+        self.vol.borrow_mut().add_entry(&parent, child, &template)?;
+        Ok(())
+    }
+
+    // Entries don't move within their directory, so their offsets are
+    // stable cursors.
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        let dir = self.vol.borrow_mut().lookup(path)?;
+        if !dir.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+
+        Ok(Box::new(DirectoryIterFat {
+            vol: self.vol.clone(),
+            dir: dir.first_cluster(),
+            offset: Some(cursor as u32),
+            entries: Vec::new().into_iter(),
+        }))
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        Ok(self.vol.borrow_mut().lookup(path)?.to_file_attr())
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        self.stat(path) // No symlinks in FAT.
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        use rt_api::fs::FsStatsData;
+
+        let vol = self.vol.borrow();
+        Ok(FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_FAT,
+            read_only: 0,
+            reserved: 0,
+            block_size: vol.geo.cluster_size as u64,
+            blocks_total: vol.geo.cluster_count as u64,
+            blocks_free: vol.free_clusters as u64,
+        })
+    }
+
+    fn mkdir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (parent, child) = self.lookup_parent(path)?;
+        let mut vol = self.vol.borrow_mut();
+        let cluster = vol.alloc_cluster(None)?;
+        let result = (|| {
+            vol.zero_cluster(cluster)?;
+
+            // "." and "..", which refers to the root as cluster 0.
+            let mut dot = Self::new_entry_template(ATTR_DIRECTORY);
+            dot[0..11].copy_from_slice(b".          ");
+            set_first_cluster(&mut dot, cluster);
+            let mut dotdot = Self::new_entry_template(ATTR_DIRECTORY);
+            dotdot[0..11].copy_from_slice(b"..         ");
+            if !parent.is_root() {
+                set_first_cluster(&mut dotdot, parent.first_cluster());
+            }
+            let offset = vol.geo.cluster_offset(cluster);
+            vol.part.write(offset, &dot)?;
+            vol.part.write(offset + DIR_ENTRY_SIZE as u64, &dotdot)?;
+
+            let mut template = Self::new_entry_template(ATTR_DIRECTORY);
+            set_first_cluster(&mut template, cluster);
+            vol.add_entry(&parent, child, &template)
+        })();
+
+        if let Err(err) = result {
+            vol.free_chain(cluster)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn unlink(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let entry = self.vol.borrow_mut().lookup(path)?;
+        if entry.is_root() {
+            return Err(ErrorCode::InvalidFilename);
+        }
+        if entry.is_dir() && !self.is_empty_dir(&entry)? {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.vol.borrow_mut().remove(&entry)
+    }
+
+    fn delete_dir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        if !self.vol.borrow_mut().lookup(path)?.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        self.unlink(path)
+    }
+
+    fn delete_dir_all(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let entry = self.vol.borrow_mut().lookup(path)?;
+        if !entry.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        self.remove_all(&entry)?;
+        self.unlink(path)
+    }
+
+    fn rename(&'static mut self, old: &str, new: &str) -> Result<(), ErrorCode> {
+        let entry = self.vol.borrow_mut().lookup(old)?;
+        if entry.is_root() {
+            return Err(ErrorCode::InvalidFilename);
+        }
+        let (new_parent, new_child) = self.lookup_parent(new)?;
+
+        // A directory can't be moved into itself; there are no links in
+        // FAT, so comparing paths is enough.
+        let old_upper = old.trim_end_matches('/').to_uppercase();
+        if entry.is_dir() && new.to_uppercase().starts_with(&format!("{}/", old_upper)) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        // An existing destination is replaced.
+        let mut case_change = false;
+        let existing = self.vol.borrow_mut().read_dir(&new_parent)?;
+        if let Some(target) = existing
+            .iter()
+            .find(|target| names_match(&target.name, new_child))
+        {
+            if target.key() != entry.key() {
+                match (entry.is_dir(), target.is_dir()) {
+                    (true, false) => return Err(ErrorCode::NotADirectory),
+                    (false, true) => return Err(ErrorCode::InvalidArgument),
+                    (true, true) if !self.is_empty_dir(target)? => {
+                        return Err(ErrorCode::InvalidArgument)
+                    }
+                    _ => {}
+                }
+                self.vol.borrow_mut().remove(target)?;
+            } else if target.name == new_child {
+                return Ok(());
+            } else {
+                case_change = true;
+            }
+        }
+
+        // The new entry is added first: a crash in between leaves the file
+        // with two names, rather than none. Only a change of case has to
+        // free the old name first.
+        let mut vol = self.vol.borrow_mut();
+        let added = if case_change {
+            vol.delete_entry(&entry)?;
+            vol.add_entry(&new_parent, new_child, &entry.raw)?
+        } else {
+            let added = vol.add_entry(&new_parent, new_child, &entry.raw)?;
+            vol.delete_entry(&entry)?;
+            added
+        };
+
+        if entry.is_dir() && new_parent.first_cluster() != entry.dir {
+            // Update "..".
+            let first = entry.first_cluster();
+            let chain = vol.chain(first)?;
+            let mut dotdot = [0_u8; 32];
+            vol.chain_io(&chain, DIR_ENTRY_SIZE as u64, Some(&mut dotdot), None)?;
+            let parent_cluster = if new_parent.is_root() {
+                0
+            } else {
+                new_parent.first_cluster()
+            };
+            set_first_cluster(&mut dotdot, parent_cluster);
+            vol.chain_io(&chain, DIR_ENTRY_SIZE as u64, None, Some(&dotdot))?;
+        }
+
+        if let Some(node) = vol.open_files.remove(&entry.key()) {
+            if let Some(node) = node.upgrade() {
+                let mut node = node.borrow_mut();
+                node.dir = added.dir;
+                node.offset = added.offset;
+            }
+            vol.open_files.insert(added.key(), node);
+        }
+        Ok(())
+    }
+
+    fn symlink(&'static mut self, _target: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed) // No symlinks in FAT.
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        let _ = self.stat(path)?;
+        Err(ErrorCode::InvalidArgument) // Not a symlink.
+    }
+
+    fn link(&'static mut self, _existing: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed) // No hard links in FAT.
+    }
+
+    // Only the owner's write bit is kept, as the read-only attribute.
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode> {
+        let mut entry = self.vol.borrow_mut().lookup(path)?;
+        if entry.is_root() {
+            return Err(ErrorCode::NotAllowed);
+        }
+        if mode & 0o200 == 0 {
+            entry.raw[11] |= ATTR_READ_ONLY;
+        } else {
+            entry.raw[11] &= !ATTR_READ_ONLY;
+        }
+        self.vol.borrow_mut().write_entry(&entry)
+    }
+
+    // FAT has no owners; like Linux's vfat with "quiet", this is not an
+    // error, so that files can be created by anyone.
+    fn chown(&'static mut self, path: &str, _uid: u32, _gid: u32) -> Result<(), ErrorCode> {
+        self.vol.borrow_mut().lookup(path).map(|_| ())
+    }
+
+    // FAT has no extended attributes.
+    fn get_xattr(&'static mut self, _path: &str, _name: &str) -> Result<Vec<u8>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn list_xattrs(&'static mut self, _path: &str) -> Result<Vec<String>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_xattr(
+        &'static mut self,
+        _path: &str,
+        _name: &str,
+        _value: &[u8],
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn remove_xattr(&'static mut self, _path: &str, _name: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    // FAT has no owners to charge.
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+}
+
+pub(super) fn init(
+    virtio_drive: Arc<dyn moto_virtio::BlockDevice>,
+    lba: u64,
+    sectors: u64,
+    mount_point: &str,
+) -> Result<Box<dyn FileSystem>, ErrorCode> {
+    let mut part = Partition::new(virtio_drive, lba, sectors);
+    let mut sector = [0_u8; SECTOR_SIZE as usize];
+    part.read(0, &mut sector)?;
+    let geo = Geometry::parse(&sector).ok_or(ErrorCode::InvalidArgument)?;
+    if geo.data_start + geo.cluster_count as u64 * geo.cluster_size as u64 > part.size() {
+        log::warn!("FAT: the volume is larger than its partition");
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let fat_offset = geo.fat_start + geo.active_fat.unwrap_or(0) as u64 * geo.fat_size;
+    let mut fat_bytes = vec![0_u8; (geo.cluster_count + FIRST_CLUSTER) as usize * 4];
+    part.read(fat_offset, &mut fat_bytes)?;
+    let fat: Vec<u32> = fat_bytes
+        .chunks_exact(4)
+        .map(|entry| u32::from_le_bytes(entry.try_into().unwrap()))
+        .collect();
+    let free_clusters = fat[(FIRST_CLUSTER as usize)..]
+        .iter()
+        .filter(|entry| **entry & FAT_ENTRY_MASK == FAT_FREE)
+        .count() as u32;
+
+    Ok(Box::new(FileSystemFat {
+        mount_point: mount_point.to_owned(),
+        vol: Rc::new(RefCell::new(Volume {
+            part,
+            geo,
+            fat,
+            free_clusters,
+            next_free: FIRST_CLUSTER,
+            open_files: HashMap::new(),
+        })),
+    }))
+}
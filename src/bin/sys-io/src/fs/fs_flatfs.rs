@@ -11,6 +11,7 @@ const _: () = assert!(BLOCK_SIZE <= PAGE_SIZE_SMALL);
 
 struct FileSystemFlatFS {
     root_dir: flatfs::Dir<'static>,
+    num_blocks: u64, // The size of the partition, in BLOCK_SIZE blocks.
 }
 
 #[derive(Clone, Copy)]
@@ -19,6 +20,10 @@ struct FileFlatFs {
 }
 
 impl super::File for FileFlatFs {
+    fn unique_id(&self) -> u64 {
+        self.bytes.as_ptr() as usize as u64
+    }
+
     fn size(&mut self) -> Result<u64, ErrorCode> {
         Ok(self.bytes.len() as u64)
     }
@@ -27,6 +32,18 @@ impl super::File for FileFlatFs {
         Err(ErrorCode::NotAllowed)
     }
 
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        Ok(()) // Read-only.
+    }
+
+    fn allocate(&mut self, _offset: u64, _len: u64, _punch_hole: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed) // Read-only.
+    }
+
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+
     fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
         let offset = offset as usize;
         if offset == self.bytes.len() {
@@ -55,6 +72,7 @@ struct DirectoryEntryFlatFs {
     path: String,
     file: Option<FileFlatFs>,
     dir: Option<&'static flatfs::Dir<'static>>,
+    cursor: u64,
 }
 
 impl super::filesystem::DirectoryEntry for DirectoryEntryFlatFs {
@@ -62,6 +80,10 @@ impl super::filesystem::DirectoryEntry for DirectoryEntryFlatFs {
         self.dir.is_some()
     }
 
+    fn is_symlink(&self) -> bool {
+        false
+    }
+
     fn filename(&self) -> &str {
         self.name
     }
@@ -70,10 +92,6 @@ impl super::filesystem::DirectoryEntry for DirectoryEntryFlatFs {
         self
     }
 
-    fn path(&self) -> &str {
-        &self.path
-    }
-
     fn size(&self) -> Result<u64, ErrorCode> {
         if let Some(file) = self.file {
             Ok(file.bytes.len() as u64)
@@ -81,13 +99,25 @@ impl super::filesystem::DirectoryEntry for DirectoryEntryFlatFs {
             panic!("not a file")
         }
     }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
 }
 
+// FlatFS is read-only: the cursor is the number of entries listed so far,
+// subdirectories first.
 struct DirectoryIterFlatFs {
     path: String,
-    iter_dirs:
-        Option<std::collections::btree_map::Iter<'static, &'static str, flatfs::Dir<'static>>>,
-    iter_files: Option<std::collections::btree_map::Iter<'static, &'static str, &'static [u8]>>,
+    iter_dirs: Option<
+        std::iter::Skip<
+            std::collections::btree_map::Iter<'static, &'static str, flatfs::Dir<'static>>,
+        >,
+    >,
+    iter_files: Option<
+        std::iter::Skip<std::collections::btree_map::Iter<'static, &'static str, &'static [u8]>>,
+    >,
+    cursor: u64,
 }
 
 impl Iterator for DirectoryIterFlatFs {
@@ -99,11 +129,13 @@ impl Iterator for DirectoryIterFlatFs {
                 let mut path = self.path.clone();
                 path.push('/');
                 path.push_str(name);
+                self.cursor += 1;
                 return Some(Box::new(DirectoryEntryFlatFs {
                     name: *name,
                     path,
                     file: None,
                     dir: Some(dir),
+                    cursor: self.cursor,
                 }));
             }
             self.iter_dirs = None;
@@ -114,11 +146,13 @@ impl Iterator for DirectoryIterFlatFs {
                 let mut path = self.path.clone();
                 path.push('/');
                 path.push_str(name);
+                self.cursor += 1;
                 return Some(Box::new(DirectoryEntryFlatFs {
                     name: *name,
                     path,
                     file: Some(FileFlatFs { bytes: *file }),
                     dir: None,
+                    cursor: self.cursor,
                 }));
             }
             self.iter_files = None;
@@ -168,6 +202,7 @@ impl super::filesystem::FileSystem for FileSystemFlatFS {
     fn iter(
         &'static mut self,
         path: &str,
+        cursor: u64,
     ) -> Result<Box<dyn super::DirectoryIter>, moto_sys::ErrorCode> {
         if !path.starts_with('/') {
             return Err(ErrorCode::InvalidFilename);
@@ -187,10 +222,12 @@ impl super::filesystem::FileSystem for FileSystemFlatFS {
             return Err(ErrorCode::InvalidFilename);
         }
 
+        let skip_files = (cursor as usize).saturating_sub(dir.subdirs.len());
         Ok(Box::new(DirectoryIterFlatFs {
             path: dir.path.clone(),
-            iter_dirs: Some(dir.subdirs.iter()),
-            iter_files: Some(dir.files.iter()),
+            iter_dirs: Some(dir.subdirs.iter().skip(cursor as usize)),
+            iter_files: Some(dir.files.iter().skip(skip_files)),
+            cursor,
         }))
     }
 
@@ -237,6 +274,10 @@ impl super::filesystem::FileSystem for FileSystemFlatFS {
                 created: 0,
                 accessed: 0,
                 modified: 0,
+                uid: moto_sys::caps::ROOT_UID,
+                gid: 0,
+                mode: 0o444, // Read-only.
+                reserved_2: 0,
             }),
             None => Ok(rt_api::fs::FileAttrData {
                 version: 0,
@@ -248,10 +289,32 @@ impl super::filesystem::FileSystem for FileSystemFlatFS {
                 created: 0,
                 accessed: 0,
                 modified: 0,
+                uid: moto_sys::caps::ROOT_UID,
+                gid: 0,
+                mode: 0o555, // Read-only.
+                reserved_2: 0,
             }),
         }
     }
 
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        self.stat(path) // No symlinks in FlatFS.
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        // FlatFS is a read-only image: there is no free space.
+        Ok(rt_api::fs::FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<rt_api::fs::FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_FLATFS,
+            read_only: 1,
+            reserved: 0,
+            block_size: BLOCK_SIZE,
+            blocks_total: self.num_blocks,
+            blocks_free: 0,
+        })
+    }
+
     fn mkdir(&'static mut self, _path: &str) -> Result<(), moto_sys::ErrorCode> {
         Err(ErrorCode::NotAllowed)
     }
@@ -271,6 +334,68 @@ impl super::filesystem::FileSystem for FileSystemFlatFS {
     fn rename(&'static mut self, _old: &str, _new: &str) -> Result<(), ErrorCode> {
         Err(ErrorCode::NotAllowed)
     }
+
+    fn symlink(&'static mut self, _target: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        let _ = self.stat(path)?;
+        Err(ErrorCode::InvalidArgument) // Not a symlink.
+    }
+
+    fn link(&'static mut self, _existing: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn chmod(&'static mut self, _path: &str, _mode: u16) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn chown(&'static mut self, _path: &str, _uid: u32, _gid: u32) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    // FlatFS has no extended attributes.
+    fn get_xattr(&'static mut self, _path: &str, _name: &str) -> Result<Vec<u8>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn list_xattrs(&'static mut self, _path: &str) -> Result<Vec<String>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_xattr(
+        &'static mut self,
+        _path: &str,
+        _name: &str,
+        _value: &[u8],
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn remove_xattr(&'static mut self, _path: &str, _name: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    // FlatFS is read-only.
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
 }
 
 pub(super) fn init(
@@ -310,5 +435,6 @@ pub(super) fn init(
 
     Box::new(FileSystemFlatFS {
         root_dir: root_dir.unwrap(),
+        num_blocks: blocks,
     })
 }
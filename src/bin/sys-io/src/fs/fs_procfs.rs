@@ -0,0 +1,621 @@
+// procfs: a read-only view of the system as files, generated on open.
+//
+// /proc/cache          Page cache state and totals, in pages.
+// /proc/meminfo        Physical memory usage.
+// /proc/cpuinfo        One block per CPU.
+// /proc/mounts         One line per mount: source mount_point fs_type.
+// /proc/{pid}/cache    Page cache usage by the process, in pages.
+// /proc/{pid}/status   "Key:\tvalue" lines.
+// /proc/{pid}/stats    One line: pid (name) state ppid cpu_usage_ns pages_user
+//                      pages_kernel active_threads total_threads
+//                      active_children total_children bytes_reclaimed.
+// /proc/{pid}/cmdline  The command line, as recorded by the kernel.
+// /proc/{pid}/maps     The loaded images: start-end slide name.
+//
+// Contents are snapshots taken when the file is opened; files report a size
+// of zero in stat() and listings, as their size is not known until then.
+
+use super::filesystem::FileSystem;
+use moto_runtime::rt_api;
+use moto_sys::stats::{MemoryStats, ProcessStatsV1};
+use moto_sys::ErrorCode;
+use std::fmt::Write;
+
+// Files in the root directory.
+const SYSTEM_FILES: [&str; 4] = ["cache", "cpuinfo", "meminfo", "mounts"];
+// Files in each /proc/{pid} directory.
+const PROCESS_FILES: [&str; 5] = ["cache", "cmdline", "maps", "stats", "status"];
+
+#[derive(Clone, Copy)]
+enum Node {
+    Root,
+    SystemFile(usize), // SYSTEM_FILES index.
+    ProcessDir(u64),
+    ProcessFile(u64, usize), // PID, PROCESS_FILES index.
+}
+
+impl Node {
+    fn is_dir(&self) -> bool {
+        matches!(self, Node::Root | Node::ProcessDir(_))
+    }
+
+    fn unique_id(&self) -> u64 {
+        match self {
+            Node::Root => 0,
+            Node::SystemFile(idx) => 1 + *idx as u64,
+            Node::ProcessDir(pid) => (pid + 1) << 8,
+            Node::ProcessFile(pid, idx) => ((pid + 1) << 8) + 1 + *idx as u64,
+        }
+    }
+}
+
+fn process_stats(pid: u64) -> Result<ProcessStatsV1, ErrorCode> {
+    let mut buf = [ProcessStatsV1::default()];
+    match ProcessStatsV1::list(pid, &mut buf) {
+        Ok(1) if buf[0].pid == pid => {
+            let [stats] = buf;
+            Ok(stats)
+        }
+        _ => Err(ErrorCode::NotFound),
+    }
+}
+
+fn lookup(path: &str) -> Result<Node, ErrorCode> {
+    let mut components = path.split('/').filter(|c| !c.is_empty());
+    let node = match components.next() {
+        None => return Ok(Node::Root),
+        Some(name) => match SYSTEM_FILES.iter().position(|f| *f == name) {
+            Some(idx) => Node::SystemFile(idx),
+            None => {
+                // PID_SYSTEM is the aggregate, not a process.
+                let pid = name.parse::<u64>().map_err(|_| ErrorCode::NotFound)?;
+                if pid == moto_sys::stats::PID_SYSTEM || name != pid.to_string() {
+                    return Err(ErrorCode::NotFound);
+                }
+                process_stats(pid)?;
+                Node::ProcessDir(pid)
+            }
+        },
+    };
+
+    let node = match (node, components.next()) {
+        (node, None) => node,
+        (Node::ProcessDir(pid), Some(name)) => {
+            match PROCESS_FILES.iter().position(|f| *f == name) {
+                Some(idx) => Node::ProcessFile(pid, idx),
+                None => return Err(ErrorCode::NotFound),
+            }
+        }
+        _ => return Err(ErrorCode::NotFound),
+    };
+
+    match components.next() {
+        None => Ok(node),
+        Some(_) => Err(ErrorCode::NotFound),
+    }
+}
+
+fn render(node: Node) -> Result<String, ErrorCode> {
+    let mut out = String::new();
+    match node {
+        Node::Root | Node::ProcessDir(_) => return Err(ErrorCode::NotFound),
+        Node::SystemFile(idx) => match SYSTEM_FILES[idx] {
+            "cache" => render_cache(&mut out),
+            "cpuinfo" => render_cpuinfo(&mut out)?,
+            "meminfo" => render_meminfo(&mut out)?,
+            "mounts" => render_mounts(&mut out),
+            _ => unreachable!(),
+        },
+        Node::ProcessFile(pid, idx) => {
+            let stats = process_stats(pid)?;
+            match PROCESS_FILES[idx] {
+                "cache" => render_process_cache(&mut out, pid),
+                "cmdline" => writeln!(out, "{}", stats.cmdline()).unwrap(),
+                "maps" => render_maps(&mut out, &stats),
+                "stats" => render_stats(&mut out, &stats),
+                "status" => render_status(&mut out, &stats),
+                _ => unreachable!(),
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn render_meminfo(out: &mut String) -> Result<(), ErrorCode> {
+    let stats = MemoryStats::get()?;
+    let huge_page_kb = moto_sys::sys_mem::PAGE_SIZE_MID >> 10;
+    for (key, kb) in [
+        ("MemTotal", stats.available >> 10),
+        ("MemFree", stats.free() >> 10),
+        ("MemUsed", stats.used() >> 10),
+        ("Cached", stats.cached() >> 10),
+        ("Kernel", stats.kernel() >> 10),
+        ("KernelHeap", stats.heap_total >> 10),
+        ("HugePagesTotal", stats.huge_pages_total * huge_page_kb),
+        ("HugePagesUsed", stats.huge_pages_used * huge_page_kb),
+    ] {
+        writeln!(out, "{:<16}{:>12} kB", format!("{}:", key), kb).unwrap();
+    }
+    Ok(())
+}
+
+fn render_cache_lines(out: &mut String, lines: &[(&str, u64)]) {
+    for (key, val) in lines {
+        writeln!(out, "{:<16}{:>12}", format!("{}:", key), val).unwrap();
+    }
+}
+
+fn render_cache(out: &mut String) {
+    let info = super::page_cache::info();
+    render_cache_lines(
+        out,
+        &[
+            ("Pages", info.pages),
+            ("Capacity", info.capacity),
+            ("Dirty", info.dirty),
+            ("Hits", info.totals.hits),
+            ("Misses", info.totals.misses),
+            ("Readahead", info.totals.readahead),
+            ("Dirtied", info.totals.dirtied),
+            ("Writebacks", info.writebacks),
+        ],
+    );
+}
+
+fn render_process_cache(out: &mut String, pid: u64) {
+    let stats = super::page_cache::process_stats(pid);
+    render_cache_lines(
+        out,
+        &[
+            ("Hits", stats.hits),
+            ("Misses", stats.misses),
+            ("Readahead", stats.readahead),
+            ("Dirtied", stats.dirtied),
+        ],
+    );
+}
+
+fn render_mounts(out: &mut String) {
+    for mount in super::filesystem::mount_table() {
+        writeln!(
+            out,
+            "{} {} {}",
+            mount.source, mount.mount_point, mount.fs_type
+        )
+        .unwrap();
+    }
+}
+
+// The CPU vendor and the brand string, from CPUID.
+#[allow(unused_unsafe)] // __cpuid() is safe in newer toolchains.
+fn cpu_model() -> (String, String) {
+    use core::arch::x86_64::__cpuid;
+
+    let regs_to_string = |regs: &[u32]| {
+        let bytes: Vec<u8> = regs.iter().flat_map(|reg| reg.to_le_bytes()).collect();
+        String::from_utf8_lossy(&bytes)
+            .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+            .to_owned()
+    };
+
+    let leaf = unsafe { __cpuid(0) };
+    let vendor = regs_to_string(&[leaf.ebx, leaf.edx, leaf.ecx]);
+
+    let max_extended = unsafe { __cpuid(0x8000_0000) }.eax;
+    let brand = if max_extended >= 0x8000_0004 {
+        let mut regs = vec![];
+        for leaf in 0x8000_0002..=0x8000_0004 {
+            let leaf = unsafe { __cpuid(leaf) };
+            regs.extend_from_slice(&[leaf.eax, leaf.ebx, leaf.ecx, leaf.edx]);
+        }
+        regs_to_string(&regs)
+    } else {
+        "unknown".to_owned()
+    };
+
+    (vendor, brand)
+}
+
+fn render_cpuinfo(out: &mut String) -> Result<(), ErrorCode> {
+    let num_cpus = moto_sys::num_cpus() as usize;
+    let mut usage = vec![0.0_f32; num_cpus];
+    moto_sys::stats::get_cpu_usage(&mut usage)?;
+    let mut queue_depths = vec![0_u32; num_cpus];
+    moto_sys::stats::get_cpu_queue_depths(&mut queue_depths)?;
+
+    let (vendor, brand) = cpu_model();
+    let tsc_mhz = moto_sys::KernelStaticPage::get().tsc_in_sec / 1_000_000;
+    for cpu in 0..num_cpus {
+        writeln!(out, "processor\t: {}", cpu).unwrap();
+        writeln!(out, "vendor_id\t: {}", vendor).unwrap();
+        writeln!(out, "model name\t: {}", brand).unwrap();
+        writeln!(out, "tsc MHz\t\t: {}", tsc_mhz).unwrap();
+        writeln!(out, "usage\t\t: {:.1}%", usage[cpu] * 100.0).unwrap();
+        writeln!(out, "queue depth\t: {}", queue_depths[cpu]).unwrap();
+        writeln!(out).unwrap();
+    }
+    Ok(())
+}
+
+fn process_state(stats: &ProcessStatsV1) -> &'static str {
+    if stats.active == 1 {
+        "running"
+    } else {
+        "zombie"
+    }
+}
+
+fn render_status(out: &mut String, stats: &ProcessStatsV1) {
+    let kb = |pages: u64| (pages << moto_sys::sys_mem::PAGE_SIZE_SMALL_LOG2) >> 10;
+
+    writeln!(out, "Name:\t{}", stats.debug_name()).unwrap();
+    writeln!(out, "State:\t{}", process_state(stats)).unwrap();
+    writeln!(out, "Pid:\t{}", stats.pid).unwrap();
+    writeln!(out, "PPid:\t{}", stats.parent_pid).unwrap();
+    if let Ok((uid, gid)) = moto_sys::SysRay::query_credentials(stats.pid) {
+        writeln!(out, "Uid:\t{}", uid).unwrap();
+        writeln!(out, "Gid:\t{}", gid).unwrap();
+    }
+    let system = if stats.system_process == 1 {
+        "yes"
+    } else {
+        "no"
+    };
+    writeln!(out, "System:\t{}", system).unwrap();
+    writeln!(out, "Threads:\t{}", stats.active_threads).unwrap();
+    writeln!(out, "ThreadsTotal:\t{}", stats.total_threads).unwrap();
+    writeln!(out, "Children:\t{}", stats.active_children).unwrap();
+    writeln!(out, "ChildrenTotal:\t{}", stats.total_children).unwrap();
+    writeln!(out, "VmUser:\t{} kB", kb(stats.pages_user)).unwrap();
+    writeln!(out, "VmHuge:\t{} kB", kb(stats.pages_user_huge)).unwrap();
+    writeln!(out, "VmKernel:\t{} kB", kb(stats.pages_kernel)).unwrap();
+    writeln!(out, "VmReclaimed:\t{} kB", stats.bytes_reclaimed >> 10).unwrap();
+    let cpu = moto_sys::time::tsc_to_duration(stats.cpu_usage);
+    writeln!(
+        out,
+        "CpuTime:\t{}.{:03}s",
+        cpu.as_secs(),
+        cpu.subsec_millis()
+    )
+    .unwrap();
+}
+
+fn render_stats(out: &mut String, stats: &ProcessStatsV1) {
+    writeln!(
+        out,
+        "{} ({}) {} {} {} {} {} {} {} {} {} {}",
+        stats.pid,
+        stats.debug_name(),
+        process_state(stats),
+        stats.parent_pid,
+        moto_sys::time::tsc_to_duration(stats.cpu_usage).as_nanos(),
+        stats.pages_user,
+        stats.pages_kernel,
+        stats.active_threads,
+        stats.total_threads,
+        stats.active_children,
+        stats.total_children,
+        stats.bytes_reclaimed,
+    )
+    .unwrap();
+}
+
+// Module info is only available to debuggers: the file is empty for
+// processes that can't be attached to (system processes, sys-io's
+// ancestors, and processes being debugged).
+fn render_maps(out: &mut String, stats: &ProcessStatsV1) {
+    let Ok(dbg_handle) = moto_sys::SysRay::dbg_attach(stats.pid) else {
+        return;
+    };
+    let mut modules = [moto_sys::stats::ModuleInfoV1::default(); 8];
+    let num_modules = moto_sys::SysRay::dbg_list_modules_v1(dbg_handle, &mut modules);
+    let _ = moto_sys::SysRay::dbg_detach(dbg_handle);
+
+    for module in &modules[0..num_modules.unwrap_or(0)] {
+        writeln!(
+            out,
+            "{:016x}-{:016x} {:x} {}",
+            module.start,
+            module.end,
+            module.slide,
+            stats.debug_name()
+        )
+        .unwrap();
+    }
+}
+
+struct FileProcFs {
+    id: u64,
+    bytes: Vec<u8>,
+}
+
+impl super::File for FileProcFs {
+    fn unique_id(&self) -> u64 {
+        self.id
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self.bytes.len() as u64)
+    }
+
+    fn write_offset(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let offset = (offset as usize).min(self.bytes.len());
+        let len = buf.len().min(self.bytes.len() - offset);
+        buf[0..len].copy_from_slice(&self.bytes[offset..(offset + len)]);
+        Ok(len)
+    }
+
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        Ok(()) // Read-only.
+    }
+
+    fn allocate(&mut self, _offset: u64, _len: u64, _punch_hole: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed) // Read-only.
+    }
+
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(()) // Nothing is cached.
+    }
+}
+
+struct DirectoryEntryProcFs {
+    name: String,
+    is_dir: bool,
+    cursor: u64,
+}
+
+impl super::filesystem::DirectoryEntry for DirectoryEntryProcFs {
+    fn is_directory(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_symlink(&self) -> bool {
+        false
+    }
+
+    fn filename(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> Result<u64, ErrorCode> {
+        Ok(0)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+// Lists the fixed files (cursors from one), then, in the root directory,
+// the processes (the cursor is the PID plus the number of fixed files).
+struct DirectoryIterProcFs {
+    files: &'static [&'static str],
+    cursor: u64,
+    list_processes: bool,
+    processes: std::vec::IntoIter<u64>, // Fetched, not yet listed.
+}
+
+impl DirectoryIterProcFs {
+    const BATCH_SIZE: usize = 32;
+
+    fn next_process(&mut self) -> Option<u64> {
+        if let Some(pid) = self.processes.next() {
+            return Some(pid);
+        }
+
+        let first_pid = self.cursor + 1 - self.files.len() as u64;
+        let mut buf: Vec<ProcessStatsV1> = (0..Self::BATCH_SIZE)
+            .map(|_| ProcessStatsV1::default())
+            .collect();
+        let count = ProcessStatsV1::list(first_pid, &mut buf).ok()?;
+        buf.truncate(count);
+        self.processes = buf
+            .iter()
+            .map(|stats| stats.pid)
+            .filter(|pid| *pid != moto_sys::stats::PID_SYSTEM)
+            .collect::<Vec<_>>()
+            .into_iter();
+        self.processes.next()
+    }
+}
+
+impl Iterator for DirectoryIterProcFs {
+    type Item = Box<dyn super::filesystem::DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, is_dir) = if (self.cursor as usize) < self.files.len() {
+            self.cursor += 1;
+            (self.files[self.cursor as usize - 1].to_owned(), false)
+        } else if self.list_processes {
+            let pid = self.next_process()?;
+            self.cursor = pid + self.files.len() as u64;
+            (pid.to_string(), true)
+        } else {
+            return None;
+        };
+
+        Some(Box::new(DirectoryEntryProcFs {
+            name,
+            is_dir,
+            cursor: self.cursor,
+        }))
+    }
+}
+
+impl super::DirectoryIter for DirectoryIterProcFs {}
+
+struct FileSystemProcFs {
+    mount_point: String,
+}
+
+impl FileSystem for FileSystemProcFs {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
+        let node = lookup(path)?;
+        Ok(Box::new(FileProcFs {
+            id: node.unique_id(),
+            bytes: render(node)?.into_bytes(),
+        }))
+    }
+
+    fn create_file(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        let (files, list_processes): (&'static [&'static str], bool) = match lookup(path)? {
+            Node::Root => (&SYSTEM_FILES, true),
+            Node::ProcessDir(_) => (&PROCESS_FILES, false),
+            _ => return Err(ErrorCode::NotADirectory),
+        };
+
+        Ok(Box::new(DirectoryIterProcFs {
+            files,
+            cursor,
+            list_processes,
+            processes: Vec::new().into_iter(),
+        }))
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let node = lookup(path)?;
+        let (file_perm, file_type, mode) = if node.is_dir() {
+            (rt_api::fs::FILE_PERM_READ, rt_api::fs::FILE_TYPE_DIR, 0o555)
+        } else {
+            (
+                rt_api::fs::FILE_PERM_READ,
+                rt_api::fs::FILE_TYPE_FILE,
+                0o444,
+            )
+        };
+        Ok(rt_api::fs::FileAttrData {
+            version: 0,
+            self_size: core::mem::size_of::<rt_api::fs::FileAttrData>() as u16,
+            file_perm,
+            file_type,
+            reserved: 0,
+            size: 0,
+            created: 0,
+            accessed: 0,
+            modified: 0,
+            uid: moto_sys::caps::ROOT_UID,
+            gid: 0,
+            mode,
+            reserved_2: 0,
+        })
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        self.stat(path) // No symlinks.
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        Ok(rt_api::fs::FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<rt_api::fs::FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_PROCFS,
+            read_only: 1,
+            reserved: 0,
+            block_size: moto_sys::sys_mem::PAGE_SIZE_SMALL,
+            blocks_total: 0,
+            blocks_free: 0,
+        })
+    }
+
+    fn mkdir(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn unlink(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn rename(&'static mut self, _old: &str, _new: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn symlink(&'static mut self, _target: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        lookup(path)?;
+        Err(ErrorCode::InvalidArgument) // Not a symlink.
+    }
+
+    fn link(&'static mut self, _existing: &str, _link: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn chmod(&'static mut self, _path: &str, _mode: u16) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn chown(&'static mut self, _path: &str, _uid: u32, _gid: u32) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn get_xattr(&'static mut self, _path: &str, _name: &str) -> Result<Vec<u8>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn list_xattrs(&'static mut self, _path: &str) -> Result<Vec<String>, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_xattr(
+        &'static mut self,
+        _path: &str,
+        _name: &str,
+        _value: &[u8],
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn remove_xattr(&'static mut self, _path: &str, _name: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn delete_dir(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+
+    fn delete_dir_all(&'static mut self, _path: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotAllowed)
+    }
+}
+
+pub(super) fn init(mount_point: &str) -> Box<dyn FileSystem> {
+    Box::new(FileSystemProcFs {
+        mount_point: mount_point.trim_end_matches('/').to_owned(),
+    })
+}
@@ -10,24 +10,65 @@ const BLOCK_512: usize = 512;
 
 struct FileSystemSrFS {
     inner: srfs::FileSystem,
+    device: DeviceAdapter, // To open snapshots with.
+    read_only: bool,       // Snapshots are read-only.
+}
+
+impl FileSystemSrFS {
+    fn check_writable(&self) -> Result<(), ErrorCode> {
+        if self.read_only {
+            Err(ErrorCode::NotAllowed)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 struct File {
     inner: srfs::File,
+    read_only: bool,
 }
 
 impl super::File for File {
+    fn unique_id(&self) -> u64 {
+        self.inner.unique_id()
+    }
+
     fn size(&mut self) -> Result<u64, ErrorCode> {
         self.inner.size().map_err(to_error_code)
     }
 
     fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        if self.read_only {
+            return Err(ErrorCode::NotAllowed);
+        }
         self.inner.write_offset(offset, buf).map_err(to_error_code)
     }
 
     fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
         self.inner.read_offset(offset, buf).map_err(to_error_code)
     }
+
+    // srfs writes metadata with the data, so there is nothing to save by data_only.
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        self.inner.sync().map_err(to_error_code)
+    }
+
+    fn allocate(&mut self, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode> {
+        if self.read_only {
+            return Err(ErrorCode::NotAllowed);
+        }
+        if punch_hole {
+            self.inner.punch_hole(offset, len).map_err(to_error_code)
+        } else {
+            self.inner.allocate(offset, len).map_err(to_error_code)
+        }
+    }
+
+    fn set_direct(&mut self, direct: bool) -> Result<(), ErrorCode> {
+        self.inner.set_direct(direct);
+        Ok(())
+    }
 }
 
 struct DirectoryEntrySrFs {
@@ -39,6 +80,11 @@ impl super::filesystem::DirectoryEntry for DirectoryEntrySrFs {
         self.inner.file_type() == srfs::EntryKind::Directory
     }
 
+    fn is_symlink(&self) -> bool {
+        self.inner.file_type() == srfs::EntryKind::File
+            && self.inner.stat().is_ok_and(|attr| attr.is_symlink)
+    }
+
     fn filename(&self) -> &str {
         self.inner.file_name()
     }
@@ -47,14 +93,14 @@ impl super::filesystem::DirectoryEntry for DirectoryEntrySrFs {
         self
     }
 
-    fn path(&self) -> &str {
-        self.inner.path()
-    }
-
     fn size(&self) -> Result<u64, ErrorCode> {
         let attr = self.inner.stat().map_err(to_error_code)?;
         Ok(attr.size)
     }
+
+    fn cursor(&self) -> u64 {
+        self.inner.cursor()
+    }
 }
 
 struct DirectoryIterSrFs {
@@ -80,44 +126,62 @@ impl super::DirectoryIter for DirectoryIterSrFs {}
 impl super::filesystem::FileSystem for FileSystemSrFS {
     fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
         let inner = self.inner.open_file(path).map_err(to_error_code)?;
-        Ok(Box::new(File { inner }))
+        Ok(Box::new(File {
+            inner,
+            read_only: self.read_only,
+        }))
     }
 
     fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
         let _ = self.inner.create_file(path).map_err(to_error_code)?;
         Ok(())
     }
 
-    fn iter(&'static mut self, path: &str) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
-        let inner = self.inner.read_dir(path).map_err(to_error_code)?;
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        let inner = self
+            .inner
+            .read_dir_from(path, cursor)
+            .map_err(to_error_code)?;
         Ok(Box::new(DirectoryIterSrFs { inner }))
     }
 
     fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
-        use rt_api::fs::FileAttrData;
         let attr = self.inner.stat(path).map_err(to_error_code)?;
+        Ok(to_file_attr(&attr))
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let attr = self.inner.lstat(path).map_err(to_error_code)?;
+        Ok(to_file_attr(&attr))
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        use rt_api::fs::FsStatsData;
 
-        Ok(FileAttrData {
-            version: 1,
-            self_size: core::mem::size_of::<FileAttrData>() as u16,
-            file_perm: rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE,
-            file_type: match attr.kind {
-                srfs::EntryKind::Directory => rt_api::fs::FILE_TYPE_DIR,
-                srfs::EntryKind::File => rt_api::fs::FILE_TYPE_FILE,
-            },
+        Ok(FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_SRFS,
+            read_only: self.read_only as u8,
             reserved: 0,
-            size: attr.size,
-            created: to_moto_timestamp(attr.created),
-            accessed: 0,
-            modified: to_moto_timestamp(attr.modified),
+            block_size: srfs::BLOCK_SIZE,
+            blocks_total: self.inner.num_blocks(),
+            blocks_free: self.inner.empty_blocks(),
         })
     }
 
     fn mkdir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
         self.inner.create_dir(path).map_err(to_error_code)
     }
 
     fn unlink(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
         self.inner.unlink(path).map_err(to_error_code)
     }
 
@@ -130,8 +194,168 @@ impl super::filesystem::FileSystem for FileSystemSrFS {
     }
 
     fn rename(&'static mut self, old: &str, new: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
         self.inner.rename(old, new).map_err(to_error_code)
     }
+
+    fn symlink(&'static mut self, target: &str, link: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner
+            .create_symlink(link, target)
+            .map_err(to_error_code)
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        self.inner.read_link(path).map_err(to_error_code)
+    }
+
+    fn link(&'static mut self, existing: &str, link: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner.hard_link(existing, link).map_err(to_error_code)
+    }
+
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner.set_mode(path, mode).map_err(to_error_code)
+    }
+
+    fn chown(&'static mut self, path: &str, uid: u32, gid: u32) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner.set_owner(path, uid, gid).map_err(to_error_code)
+    }
+
+    fn get_xattr(&'static mut self, path: &str, name: &str) -> Result<Vec<u8>, ErrorCode> {
+        self.inner.get_xattr(path, name).map_err(to_error_code)
+    }
+
+    fn list_xattrs(&'static mut self, path: &str) -> Result<Vec<String>, ErrorCode> {
+        self.inner.list_xattrs(path).map_err(to_error_code)
+    }
+
+    fn set_xattr(&'static mut self, path: &str, name: &str, value: &[u8]) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner
+            .set_xattr(path, name, value)
+            .map_err(to_error_code)
+    }
+
+    fn remove_xattr(&'static mut self, path: &str, name: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner.remove_xattr(path, name).map_err(to_error_code)
+    }
+
+    fn get_quota(
+        &'static mut self,
+        path: &str,
+        uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        let quota = match uid {
+            Some(uid) => self.inner.user_quota(uid),
+            None => self.inner.dir_quota(path),
+        }
+        .map_err(to_error_code)?;
+
+        Ok(rt_api::fs::QuotaData {
+            max_bytes: quota.limits.max_bytes,
+            max_inodes: quota.limits.max_inodes,
+            used_bytes: quota.usage.bytes,
+            used_inodes: quota.usage.inodes,
+        })
+    }
+
+    fn set_quota(
+        &'static mut self,
+        path: &str,
+        uid: Option<u32>,
+        max_bytes: u64,
+        max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        let limits = srfs::QuotaLimits {
+            max_bytes,
+            max_inodes,
+        };
+        match uid {
+            Some(uid) => self.inner.set_user_quota(uid, limits),
+            None => self.inner.set_dir_quota(path, limits),
+        }
+        .map_err(to_error_code)
+    }
+
+    fn create_snapshot(&'static mut self, name: &str, max_bytes: u64) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        let blocks = max_bytes.div_ceil(srfs::BLOCK_SIZE);
+        self.inner
+            .create_snapshot(name, blocks)
+            .map_err(to_error_code)
+    }
+
+    fn delete_snapshot(&'static mut self, name: &str) -> Result<(), ErrorCode> {
+        self.check_writable()?;
+        self.inner.delete_snapshot(name).map_err(to_error_code)
+    }
+
+    fn list_snapshots(&'static mut self) -> Result<Vec<rt_api::fs::SnapshotData>, ErrorCode> {
+        use rt_api::fs::{SnapshotData, MAX_SNAPSHOT_NAME};
+
+        Ok(self
+            .inner
+            .snapshots()
+            .into_iter()
+            .map(|snapshot| {
+                let mut name = [0_u8; MAX_SNAPSHOT_NAME];
+                let name_size = snapshot.name.len().min(MAX_SNAPSHOT_NAME);
+                name[0..name_size].copy_from_slice(&snapshot.name.as_bytes()[0..name_size]);
+                SnapshotData {
+                    created: to_moto_timestamp(snapshot.created.into()),
+                    max_bytes: snapshot.blocks * srfs::BLOCK_SIZE,
+                    used_bytes: snapshot.used_blocks * srfs::BLOCK_SIZE,
+                    valid: snapshot.valid as u8,
+                    reserved: 0,
+                    name_size: name_size as u16,
+                    reserved_2: 0,
+                    name,
+                }
+            })
+            .collect())
+    }
+
+    fn open_snapshot(&'static mut self, name: &str) -> Result<Box<dyn FileSystem>, ErrorCode> {
+        let device = Box::new(self.device.clone());
+        let inner = self
+            .inner
+            .open_snapshot(name, device)
+            .map_err(to_error_code)?;
+        Ok(Box::new(FileSystemSrFS {
+            inner,
+            device: self.device.clone(),
+            read_only: true,
+        }))
+    }
+}
+
+fn to_file_attr(attr: &srfs::Attr) -> rt_api::fs::FileAttrData {
+    use rt_api::fs::FileAttrData;
+
+    FileAttrData {
+        version: 1,
+        self_size: core::mem::size_of::<FileAttrData>() as u16,
+        file_perm: rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE,
+        file_type: match attr.kind {
+            srfs::EntryKind::Directory => rt_api::fs::FILE_TYPE_DIR,
+            srfs::EntryKind::File if attr.is_symlink => rt_api::fs::FILE_TYPE_SYMLINK,
+            srfs::EntryKind::File => rt_api::fs::FILE_TYPE_FILE,
+        },
+        reserved: 0,
+        size: attr.size,
+        created: to_moto_timestamp(attr.created),
+        accessed: 0,
+        modified: to_moto_timestamp(attr.modified),
+        uid: attr.uid,
+        gid: attr.gid,
+        mode: attr.mode as u32,
+        reserved_2: 0,
+    }
 }
 
 pub(super) fn init(
@@ -141,16 +365,21 @@ pub(super) fn init(
 ) -> Box<dyn FileSystem> {
     assert_eq!(0, blocks & 3); // here blocks are in 512 bytes; we need in 4k.
 
-    let adapter = Box::new(DeviceAdapter {
+    let device = DeviceAdapter {
         virtio_drive,
         blocks4k: blocks >> 2,
         lba_offset: lba << BLOCK_512.ilog2(),
-    });
-
-    let inner = srfs::FileSystem::open_device(adapter).unwrap();
-    Box::new(FileSystemSrFS { inner })
+    };
+
+    let inner = srfs::FileSystem::open_device(Box::new(device.clone())).unwrap();
+    Box::new(FileSystemSrFS {
+        inner,
+        device,
+        read_only: false,
+    })
 }
 
+#[derive(Clone)]
 struct DeviceAdapter {
     virtio_drive: Arc<dyn moto_virtio::BlockDevice>,
     blocks4k: u64,
@@ -189,15 +418,29 @@ impl srfs::SyncBlockDevice for DeviceAdapter {
             )
             .map_err(|_| srfs::FsError::IoError)
     }
+
+    fn flush(&mut self) -> Result<(), srfs::FsError> {
+        self.virtio_drive
+            .flush()
+            .map_err(|_| srfs::FsError::IoError)
+    }
 }
 
 fn to_error_code(error: std::io::Error) -> ErrorCode {
+    if srfs::is_quota_exceeded(&error) {
+        return ErrorCode::QuotaExceeded;
+    }
     match error.kind() {
         std::io::ErrorKind::NotFound => ErrorCode::NotFound,
         std::io::ErrorKind::PermissionDenied => ErrorCode::NotAllowed,
         std::io::ErrorKind::AlreadyExists => ErrorCode::AlreadyInUse,
+        std::io::ErrorKind::ResourceBusy => ErrorCode::AlreadyInUse,
         std::io::ErrorKind::WouldBlock => todo!(),
-        std::io::ErrorKind::InvalidInput => todo!(),
+        std::io::ErrorKind::InvalidInput => ErrorCode::InvalidArgument,
+        std::io::ErrorKind::InvalidFilename => ErrorCode::InvalidFilename,
+        std::io::ErrorKind::NotADirectory => ErrorCode::NotADirectory,
+        std::io::ErrorKind::IsADirectory => ErrorCode::InvalidArgument,
+        std::io::ErrorKind::FilesystemLoop => ErrorCode::FilesystemLoop,
         std::io::ErrorKind::InvalidData => ErrorCode::UnknownError,
         std::io::ErrorKind::TimedOut => todo!(),
         std::io::ErrorKind::WriteZero => todo!(),
@@ -206,6 +449,7 @@ fn to_error_code(error: std::io::Error) -> ErrorCode {
         std::io::ErrorKind::UnexpectedEof => todo!(),
         std::io::ErrorKind::OutOfMemory => ErrorCode::OutOfMemory,
         std::io::ErrorKind::FileTooLarge => ErrorCode::FileTooLarge,
+        std::io::ErrorKind::StorageFull => ErrorCode::StorageFull,
         // std::io::ErrorKind::Other => todo!(),
         _ => ErrorCode::UnknownError,
     }
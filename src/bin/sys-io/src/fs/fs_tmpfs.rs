@@ -0,0 +1,714 @@
+// A memory-backed filesystem, mounted at /tmp. Nothing survives a restart,
+// and nothing touches the block device.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::filesystem::FileSystem;
+use super::path_walk::{self, PathWalk};
+use moto_runtime::rt_api;
+use moto_sys::ErrorCode;
+
+type NodeId = u64;
+
+const ROOT_ID: NodeId = 1;
+const MAX_NODES: usize = 1 << 16;
+// Data is not stored in blocks; statfs reports usage in these.
+const BLOCK_SIZE: u64 = 4096;
+
+const DEFAULT_DIR_MODE: u16 = 0o755;
+const DEFAULT_FILE_MODE: u16 = 0o644;
+const SYMLINK_MODE: u16 = 0o777;
+
+// Names and values of the extended attributes of a node, as in srfs.
+const MAX_XATTR_BYTES: usize = 4096;
+
+// Bytes of file data, shared by the filesystem and its open files.
+struct Usage {
+    used: Cell<u64>,
+    max: u64,
+}
+
+struct FileData {
+    bytes: Vec<u8>,
+    modified: SystemTime,
+    usage: Rc<Usage>,
+}
+
+// Unlinked files stay readable while open, so their bytes are
+// accounted for until the last reference is gone.
+impl Drop for FileData {
+    fn drop(&mut self) {
+        let used = self.usage.used.get();
+        self.usage.used.set(used - self.bytes.len() as u64);
+    }
+}
+
+// The entries of a directory, by name, and by cursor for listing: cursors
+// are handed out in the order entries are added, and never reused, so a
+// listing resumed by cursor is not thrown off by changes in between.
+#[derive(Default)]
+struct Dir {
+    entries: BTreeMap<String, (NodeId, u64)>, // Name => (node, cursor).
+    by_cursor: BTreeMap<u64, String>,
+    next_cursor: u64,
+}
+
+impl Dir {
+    fn get(&self, name: &str) -> Option<&NodeId> {
+        self.entries.get(name).map(|(id, _)| id)
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    fn insert(&mut self, name: String, id: NodeId) {
+        if let Some(entry) = self.entries.get_mut(&name) {
+            entry.0 = id; // Replaced: keeps its place in listings.
+            return;
+        }
+        self.next_cursor += 1;
+        self.by_cursor.insert(self.next_cursor, name.clone());
+        self.entries.insert(name, (id, self.next_cursor));
+    }
+
+    fn remove(&mut self, name: &str) -> Option<NodeId> {
+        let (id, cursor) = self.entries.remove(name)?;
+        self.by_cursor.remove(&cursor);
+        Some(id)
+    }
+
+    // The first entry after @cursor: (cursor, name, node).
+    fn next_after(&self, cursor: u64) -> Option<(u64, &String, NodeId)> {
+        let (cursor, name) = self.by_cursor.range(cursor.saturating_add(1)..).next()?;
+        Some((*cursor, name, self.entries.get(name)?.0))
+    }
+}
+
+enum Content {
+    Dir(Dir),
+    File(Rc<RefCell<FileData>>),
+    Symlink(String),
+}
+
+struct Node {
+    content: Content,
+    parent: NodeId, // Of directories; the root is its own parent.
+    links: u32,
+    uid: u32,
+    gid: u32,
+    mode: u16,
+    created: SystemTime,
+    modified: SystemTime, // Of files, see FileData.
+    xattrs: BTreeMap<String, Vec<u8>>,
+}
+
+impl Node {
+    fn new(content: Content, parent: NodeId) -> Self {
+        let mode = match &content {
+            Content::Dir(_) => DEFAULT_DIR_MODE,
+            Content::File(_) => DEFAULT_FILE_MODE,
+            Content::Symlink(_) => SYMLINK_MODE,
+        };
+        let now = SystemTime::now();
+        Self {
+            content,
+            parent,
+            links: 1,
+            uid: moto_sys::caps::ROOT_UID,
+            gid: moto_sys::caps::ROOT_UID,
+            mode,
+            created: now,
+            modified: now,
+            xattrs: BTreeMap::new(),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.content, Content::Dir(_))
+    }
+
+    fn size(&self) -> u64 {
+        match &self.content {
+            Content::Dir(_) => 0,
+            Content::File(data) => data.borrow().bytes.len() as u64,
+            Content::Symlink(target) => target.len() as u64,
+        }
+    }
+}
+
+struct FileSystemTmpFs {
+    mount_point: String,
+    nodes: HashMap<NodeId, Node>,
+    next_id: NodeId,
+    usage: Rc<Usage>,
+}
+
+struct File {
+    data: Rc<RefCell<FileData>>,
+}
+
+impl super::File for File {
+    fn unique_id(&self) -> u64 {
+        Rc::as_ptr(&self.data) as usize as u64
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self.data.borrow().bytes.len() as u64)
+    }
+
+    fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let mut data = self.data.borrow_mut();
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(ErrorCode::FileTooLarge)?;
+        let len = data.bytes.len() as u64;
+        if end > len {
+            let used = data.usage.used.get();
+            if used + (end - len) > data.usage.max {
+                return Err(ErrorCode::StorageFull);
+            }
+            data.usage.used.set(used + (end - len));
+            // Writing past the end leaves a zero-filled gap.
+            data.bytes.resize(end as usize, 0);
+        }
+
+        data.bytes[(offset as usize)..(end as usize)].copy_from_slice(buf);
+        data.modified = SystemTime::now();
+        Ok(buf.len())
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let data = self.data.borrow();
+        let offset = offset as usize;
+        if offset == data.bytes.len() {
+            return Ok(0);
+        }
+        if offset > data.bytes.len() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let end = data.bytes.len().min(offset + buf.len());
+        buf[0..(end - offset)].copy_from_slice(&data.bytes[offset..end]);
+        Ok(end - offset)
+    }
+
+    fn sync(&mut self, _data_only: bool) -> Result<(), ErrorCode> {
+        Ok(()) // Nothing to make durable.
+    }
+
+    // There are no holes here: punched ranges are zero-filled.
+    fn allocate(&mut self, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode> {
+        let mut data = self.data.borrow_mut();
+        let end = offset.checked_add(len).ok_or(ErrorCode::FileTooLarge)?;
+        let size = data.bytes.len() as u64;
+        if punch_hole {
+            let end = end.min(size);
+            if offset < end {
+                data.bytes[(offset as usize)..(end as usize)].fill(0);
+                data.modified = SystemTime::now();
+            }
+            return Ok(());
+        }
+
+        if end > size {
+            let used = data.usage.used.get();
+            if used + (end - size) > data.usage.max {
+                return Err(ErrorCode::StorageFull);
+            }
+            data.usage.used.set(used + (end - size));
+            data.bytes.resize(end as usize, 0);
+            data.modified = SystemTime::now();
+        }
+        Ok(())
+    }
+
+    // Files live in memory: there is no cache to bypass.
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+struct DirectoryEntryTmpFs {
+    name: String,
+    is_dir: bool,
+    is_symlink: bool,
+    size: u64,
+    cursor: u64,
+}
+
+impl super::filesystem::DirectoryEntry for DirectoryEntryTmpFs {
+    fn is_directory(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    fn filename(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn size(&self) -> Result<u64, ErrorCode> {
+        Ok(self.size)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
+}
+
+struct DirectoryIterTmpFs {
+    fs: &'static FileSystemTmpFs,
+    dir: NodeId,
+    cursor: u64,
+}
+
+impl Iterator for DirectoryIterTmpFs {
+    type Item = Box<dyn super::filesystem::DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (cursor, name, id) = self.fs.entries(self.dir).ok()?.next_after(self.cursor)?;
+        self.cursor = cursor;
+        let node = self.fs.node(id);
+        Some(Box::new(DirectoryEntryTmpFs {
+            name: name.clone(),
+            is_dir: node.is_dir(),
+            is_symlink: matches!(node.content, Content::Symlink(_)),
+            size: node.size(),
+            cursor,
+        }))
+    }
+}
+
+impl super::DirectoryIter for DirectoryIterTmpFs {}
+
+// Splits @path into its parent directory and the last component.
+fn split_parent_child(path: &str) -> Result<(&str, &str), ErrorCode> {
+    let (parent, child) = path
+        .trim_end_matches('/')
+        .rsplit_once('/')
+        .ok_or(ErrorCode::InvalidFilename)?;
+    if child.is_empty() || child == "." || child == ".." {
+        return Err(ErrorCode::InvalidFilename);
+    }
+    Ok((if parent.is_empty() { "/" } else { parent }, child))
+}
+
+impl FileSystemTmpFs {
+    fn node(&self, id: NodeId) -> &Node {
+        self.nodes.get(&id).unwrap()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes.get_mut(&id).unwrap()
+    }
+
+    fn entries(&self, id: NodeId) -> Result<&Dir, ErrorCode> {
+        match &self.node(id).content {
+            Content::Dir(entries) => Ok(entries),
+            _ => Err(ErrorCode::NotADirectory),
+        }
+    }
+
+    fn entries_mut(&mut self, id: NodeId) -> Result<&mut Dir, ErrorCode> {
+        match &mut self.node_mut(id).content {
+            Content::Dir(entries) => Ok(entries),
+            _ => Err(ErrorCode::NotADirectory),
+        }
+    }
+
+    // Symlinks are followed, except for the last path component if !follow_last.
+    fn lookup(&self, path: &str, follow_last: bool) -> Result<NodeId, ErrorCode> {
+        path_walk::walk(self, path, follow_last)
+    }
+
+    fn add_node(&mut self, path: &str, content: Content) -> Result<NodeId, ErrorCode> {
+        if self.nodes.len() >= MAX_NODES {
+            return Err(ErrorCode::StorageFull);
+        }
+        let (parent, child) = split_parent_child(path)?;
+        let parent = self.lookup(parent, true)?;
+        if self.entries(parent)?.contains_key(child) {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.nodes.insert(id, Node::new(content, parent));
+        self.entries_mut(parent)?.insert(child.to_owned(), id);
+        self.node_mut(parent).modified = SystemTime::now();
+        Ok(id)
+    }
+
+    // Removes the entry @child of @parent, and its node, if it was the last link.
+    fn remove_entry(&mut self, parent: NodeId, child: &str) {
+        let id = self.entries_mut(parent).unwrap().remove(child).unwrap();
+        self.node_mut(parent).modified = SystemTime::now();
+
+        let node = self.node_mut(id);
+        node.links -= 1;
+        if node.links == 0 {
+            self.nodes.remove(&id);
+        }
+    }
+
+    fn remove_all(&mut self, dir: NodeId) {
+        let children: Vec<String> = self.entries(dir).unwrap().keys().cloned().collect();
+        for child in children {
+            let id = *self.entries(dir).unwrap().get(&child).unwrap();
+            if self.node(id).is_dir() {
+                self.remove_all(id);
+            }
+            self.remove_entry(dir, &child);
+        }
+    }
+
+    fn to_file_attr(&self, id: NodeId) -> rt_api::fs::FileAttrData {
+        use rt_api::fs::FileAttrData;
+
+        let node = self.node(id);
+        let modified = match &node.content {
+            Content::File(data) => data.borrow().modified,
+            _ => node.modified,
+        };
+        FileAttrData {
+            version: 1,
+            self_size: core::mem::size_of::<FileAttrData>() as u16,
+            file_perm: rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE,
+            file_type: match &node.content {
+                Content::Dir(_) => rt_api::fs::FILE_TYPE_DIR,
+                Content::File(_) => rt_api::fs::FILE_TYPE_FILE,
+                Content::Symlink(_) => rt_api::fs::FILE_TYPE_SYMLINK,
+            },
+            reserved: 0,
+            size: node.size(),
+            created: to_moto_timestamp(node.created),
+            accessed: 0,
+            modified: to_moto_timestamp(modified),
+            uid: node.uid,
+            gid: node.gid,
+            mode: node.mode as u32,
+            reserved_2: 0,
+        }
+    }
+}
+
+impl PathWalk for FileSystemTmpFs {
+    type Node = NodeId;
+
+    fn root(&self) -> Result<NodeId, ErrorCode> {
+        Ok(ROOT_ID)
+    }
+
+    fn is_dir(&self, id: &NodeId) -> bool {
+        self.node(*id).is_dir()
+    }
+
+    fn is_symlink(&self, id: &NodeId) -> bool {
+        matches!(self.node(*id).content, Content::Symlink(_))
+    }
+
+    fn find(&self, dir: &NodeId, name: &str) -> Result<NodeId, ErrorCode> {
+        self.entries(*dir)?
+            .get(name)
+            .copied()
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    fn symlink_target(&self, id: &NodeId) -> Result<String, ErrorCode> {
+        match &self.node(*id).content {
+            Content::Symlink(target) => path_walk::local_target(&self.mount_point, target.clone()),
+            _ => Err(ErrorCode::InvalidArgument),
+        }
+    }
+}
+
+impl super::filesystem::FileSystem for FileSystemTmpFs {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
+        let id = self.lookup(path, true)?;
+        match &self.node(id).content {
+            Content::File(data) => Ok(Box::new(File { data: data.clone() })),
+            _ => Err(ErrorCode::NotFound),
+        }
+    }
+
+    fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let data = FileData {
+            bytes: Vec::new(),
+            modified: SystemTime::now(),
+            usage: self.usage.clone(),
+        };
+        self.add_node(path, Content::File(Rc::new(RefCell::new(data))))
+            .map(|_| ())
+    }
+
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        let dir = self.lookup(path, true)?;
+        self.entries(dir)?;
+
+        Ok(Box::new(DirectoryIterTmpFs {
+            fs: self,
+            dir,
+            cursor,
+        }))
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let id = self.lookup(path, true)?;
+        Ok(self.to_file_attr(id))
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        let id = self.lookup(path, false)?;
+        Ok(self.to_file_attr(id))
+    }
+
+    fn statfs(&'static mut self, _path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        use rt_api::fs::FsStatsData;
+
+        let max = self.usage.max / BLOCK_SIZE;
+        let used = self.usage.used.get().div_ceil(BLOCK_SIZE);
+        Ok(FsStatsData {
+            version: 0,
+            self_size: core::mem::size_of::<FsStatsData>() as u16,
+            fs_type: rt_api::fs::FS_TYPE_TMPFS,
+            read_only: 0,
+            reserved: 0,
+            block_size: BLOCK_SIZE,
+            blocks_total: max,
+            blocks_free: max.saturating_sub(used),
+        })
+    }
+
+    fn mkdir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.add_node(path, Content::Dir(Dir::default()))
+            .map(|_| ())
+    }
+
+    // Removes the symlink itself, not its target.
+    fn unlink(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let (parent, child) = split_parent_child(path)?;
+        let parent = self.lookup(parent, true)?;
+        let id = *self
+            .entries(parent)?
+            .get(child)
+            .ok_or(ErrorCode::NotFound)?;
+        if self.entries(id).is_ok_and(|entries| !entries.is_empty()) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        self.remove_entry(parent, child);
+        Ok(())
+    }
+
+    fn delete_dir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        if !self.node(self.lookup(path, false)?).is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        self.unlink(path)
+    }
+
+    fn delete_dir_all(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        let id = self.lookup(path, false)?;
+        if !self.node(id).is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        self.remove_all(id);
+        self.unlink(path)
+    }
+
+    fn rename(&'static mut self, old: &str, new: &str) -> Result<(), ErrorCode> {
+        let (old_parent, old_child) = split_parent_child(old)?;
+        let (new_parent, new_child) = split_parent_child(new)?;
+        let old_parent = self.lookup(old_parent, true)?;
+        let new_parent = self.lookup(new_parent, true)?;
+        let id = *self
+            .entries(old_parent)?
+            .get(old_child)
+            .ok_or(ErrorCode::NotFound)?;
+        self.entries(new_parent)?;
+
+        if self.node(id).is_dir() {
+            // A directory can't be moved into itself.
+            let mut ancestor = new_parent;
+            loop {
+                if ancestor == id {
+                    return Err(ErrorCode::InvalidArgument);
+                }
+                if ancestor == ROOT_ID {
+                    break;
+                }
+                ancestor = self.node(ancestor).parent;
+            }
+        }
+
+        // An existing destination is replaced atomically.
+        if let Some(target) = self.entries(new_parent)?.get(new_child).copied() {
+            if target == id {
+                return Ok(());
+            }
+            match (self.node(id).is_dir(), self.node(target).is_dir()) {
+                (true, false) => return Err(ErrorCode::NotADirectory),
+                (false, true) => return Err(ErrorCode::InvalidArgument),
+                (true, true) if !self.entries(target)?.is_empty() => {
+                    return Err(ErrorCode::InvalidArgument)
+                }
+                _ => {}
+            }
+            self.remove_entry(new_parent, new_child);
+        }
+
+        self.entries_mut(old_parent)?.remove(old_child);
+        self.entries_mut(new_parent)?
+            .insert(new_child.to_owned(), id);
+        self.node_mut(id).parent = new_parent;
+        let now = SystemTime::now();
+        self.node_mut(old_parent).modified = now;
+        self.node_mut(new_parent).modified = now;
+        Ok(())
+    }
+
+    fn symlink(&'static mut self, target: &str, link: &str) -> Result<(), ErrorCode> {
+        self.add_node(link, Content::Symlink(target.to_owned()))
+            .map(|_| ())
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        let id = self.lookup(path, false)?;
+        match &self.node(id).content {
+            Content::Symlink(target) => Ok(target.clone()),
+            _ => Err(ErrorCode::InvalidArgument),
+        }
+    }
+
+    // A symlink is linked itself, rather than its target.
+    fn link(&'static mut self, existing: &str, link: &str) -> Result<(), ErrorCode> {
+        let id = self.lookup(existing, false)?;
+        if self.node(id).is_dir() {
+            return Err(ErrorCode::NotAllowed);
+        }
+
+        let (parent, child) = split_parent_child(link)?;
+        let parent = self.lookup(parent, true)?;
+        if self.entries(parent)?.contains_key(child) {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+        self.entries_mut(parent)?.insert(child.to_owned(), id);
+        self.node_mut(parent).modified = SystemTime::now();
+        self.node_mut(id).links += 1;
+        Ok(())
+    }
+
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode> {
+        if (mode as u32 & !rt_api::fs::FILE_MODE_MASK) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let id = self.lookup(path, true)?;
+        self.node_mut(id).mode = mode;
+        Ok(())
+    }
+
+    fn chown(&'static mut self, path: &str, uid: u32, gid: u32) -> Result<(), ErrorCode> {
+        let id = self.lookup(path, true)?;
+        let node = self.node_mut(id);
+        node.uid = uid;
+        node.gid = gid;
+        Ok(())
+    }
+
+    fn get_xattr(&'static mut self, path: &str, name: &str) -> Result<Vec<u8>, ErrorCode> {
+        let id = self.lookup(path, true)?;
+        let value = self.node(id).xattrs.get(name);
+        value.cloned().ok_or(ErrorCode::NotFound)
+    }
+
+    fn list_xattrs(&'static mut self, path: &str) -> Result<Vec<String>, ErrorCode> {
+        let id = self.lookup(path, true)?;
+        Ok(self.node(id).xattrs.keys().cloned().collect())
+    }
+
+    fn set_xattr(&'static mut self, path: &str, name: &str, value: &[u8]) -> Result<(), ErrorCode> {
+        let id = self.lookup(path, true)?;
+        let xattrs = &mut self.node_mut(id).xattrs;
+        let used: usize = xattrs
+            .iter()
+            .filter(|(n, _)| n.as_str() != name)
+            .map(|(n, v)| n.len() + v.len())
+            .sum();
+        if used + name.len() + value.len() > MAX_XATTR_BYTES {
+            return Err(ErrorCode::FileTooLarge);
+        }
+        xattrs.insert(name.to_owned(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove_xattr(&'static mut self, path: &str, name: &str) -> Result<(), ErrorCode> {
+        let id = self.lookup(path, true)?;
+        let xattrs = &mut self.node_mut(id).xattrs;
+        xattrs.remove(name).map(|_| ()).ok_or(ErrorCode::NotFound)
+    }
+
+    // tmpfs has a size limit (see statfs), but no quotas.
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+}
+
+fn to_moto_timestamp(ts: SystemTime) -> u64 {
+    let dur = ts.duration_since(UNIX_EPOCH).unwrap();
+    dur.as_nanos() as u64
+}
+
+// File data is limited to @max_bytes; @mount_point is where the
+// filesystem is mounted, for absolute symlinks.
+pub(super) fn init(mount_point: &str, max_bytes: u64) -> Box<dyn FileSystem> {
+    let mut root = Node::new(Content::Dir(Dir::default()), ROOT_ID);
+    root.mode = 0o777; // Everyone can create scratch files.
+
+    let mut nodes = HashMap::new();
+    nodes.insert(ROOT_ID, root);
+    Box::new(FileSystemTmpFs {
+        mount_point: mount_point.trim_end_matches('/').to_owned(),
+        nodes,
+        next_id: ROOT_ID + 1,
+        usage: Rc::new(Usage {
+            used: Cell::new(0),
+            max: max_bytes,
+        }),
+    })
+}
@@ -0,0 +1,970 @@
+// Host directories shared via virtio-fs, mounted at /mnt/<tag> (see
+// super::filesystem::init()). Requests are FUSE messages, served by
+// a daemon on the host (e.g. virtiofsd).
+//
+// Paths are resolved here, one FUSE_LOOKUP per component, so that symlinks
+// are followed in the guest's namespace; looked up nodes are forgotten once
+// they are no longer used. Host owners don't mean anything in the guest:
+// entries belong to root, with the permission bits they have on the host.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::filesystem::FileSystem;
+use super::path_walk::{self, PathWalk};
+use alloc::sync::Arc;
+use moto_runtime::rt_api;
+use moto_sys::ErrorCode;
+
+const FUSE_KERNEL_VERSION: u32 = 7;
+const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
+const FUSE_ROOT_ID: u64 = 1;
+
+const FUSE_LOOKUP: u32 = 1;
+const FUSE_GETATTR: u32 = 3;
+const FUSE_SETATTR: u32 = 4;
+const FUSE_READLINK: u32 = 5;
+const FUSE_SYMLINK: u32 = 6;
+const FUSE_MKNOD: u32 = 8;
+const FUSE_MKDIR: u32 = 9;
+const FUSE_UNLINK: u32 = 10;
+const FUSE_RMDIR: u32 = 11;
+const FUSE_RENAME: u32 = 12;
+const FUSE_LINK: u32 = 13;
+const FUSE_OPEN: u32 = 14;
+const FUSE_READ: u32 = 15;
+const FUSE_WRITE: u32 = 16;
+const FUSE_STATFS: u32 = 17;
+const FUSE_RELEASE: u32 = 18;
+const FUSE_FSYNC: u32 = 20;
+const FUSE_SETXATTR: u32 = 21;
+const FUSE_GETXATTR: u32 = 22;
+const FUSE_LISTXATTR: u32 = 23;
+const FUSE_REMOVEXATTR: u32 = 24;
+const FUSE_INIT: u32 = 26;
+const FUSE_OPENDIR: u32 = 27;
+const FUSE_READDIR: u32 = 28;
+const FUSE_RELEASEDIR: u32 = 29;
+const FUSE_BATCH_FORGET: u32 = 42;
+const FUSE_FALLOCATE: u32 = 43;
+
+const IN_HEADER_SIZE: usize = 40;
+const OUT_HEADER_SIZE: usize = 16;
+const ATTR_SIZE: usize = 88;
+const ENTRY_OUT_SIZE: usize = 40 + ATTR_SIZE;
+const ATTR_OUT_SIZE: usize = 16 + ATTR_SIZE;
+const DIRENT_SIZE: usize = 24; // Without the name.
+
+const FUSE_GETATTR_FH: u32 = 1;
+const FATTR_MODE: u32 = 1;
+const FUSE_FSYNC_FDATASYNC: u32 = 1;
+const FALLOC_FL_KEEP_SIZE: u32 = 1;
+const FALLOC_FL_PUNCH_HOLE: u32 = 2; // Must be used with FALLOC_FL_KEEP_SIZE.
+
+const O_RDONLY: u32 = 0;
+const O_RDWR: u32 = 2;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+const DT_DIR: u32 = 4;
+
+const DEFAULT_DIR_MODE: u32 = 0o755;
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+// Reads and writes are split into requests of at most this many bytes.
+const MAX_IO_SIZE: usize = 128 * 1024;
+const READDIR_SIZE: u32 = 16 * 1024;
+// XATTR_SIZE_MAX on Linux hosts: the size of values and name lists.
+const MAX_XATTR_SIZE: u32 = 64 * 1024;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..(offset + 4)].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..(offset + 8)].try_into().unwrap())
+}
+
+fn to_error(errno: i32) -> ErrorCode {
+    match errno {
+        2 | 61 => ErrorCode::NotFound,              // ENOENT, ENODATA
+        1 | 13 | 30 => ErrorCode::NotAllowed,       // EPERM, EACCES, EROFS
+        16 | 17 => ErrorCode::AlreadyInUse,         // EBUSY, EEXIST
+        18 => ErrorCode::CrossesDevices,            // EXDEV
+        20 => ErrorCode::NotADirectory,             // ENOTDIR
+        21 | 22 | 39 => ErrorCode::InvalidArgument, // EISDIR, EINVAL, ENOTEMPTY
+        7 | 27 | 34 => ErrorCode::FileTooLarge,     // E2BIG, EFBIG, ERANGE
+        28 | 122 => ErrorCode::StorageFull,         // ENOSPC, EDQUOT
+        36 => ErrorCode::InvalidFilename,           // ENAMETOOLONG
+        38 | 95 => ErrorCode::NotImplemented,       // ENOSYS, EOPNOTSUPP
+        40 => ErrorCode::FilesystemLoop,            // ELOOP
+        _ => ErrorCode::InternalError,
+    }
+}
+
+fn split_parent_child(path: &str) -> Result<(&str, &str), ErrorCode> {
+    let (parent, child) = path
+        .trim_end_matches('/')
+        .rsplit_once('/')
+        .ok_or(ErrorCode::InvalidFilename)?;
+    if child.is_empty() || child == "." || child == ".." {
+        return Err(ErrorCode::InvalidFilename);
+    }
+    Ok((if parent.is_empty() { "/" } else { parent }, child))
+}
+
+// A FUSE request: the header is filled in by Session::encode().
+struct Request {
+    opcode: u32,
+    nodeid: u64,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn new(opcode: u32, nodeid: u64) -> Self {
+        Self {
+            opcode,
+            nodeid,
+            body: Vec::new(),
+        }
+    }
+
+    fn u32(mut self, val: u32) -> Self {
+        self.body.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, val: u64) -> Self {
+        self.body.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    // Names are NUL-terminated.
+    fn name(mut self, name: &str) -> Self {
+        self.body.extend_from_slice(name.as_bytes());
+        self.body.push(0);
+        self
+    }
+}
+
+// An entry of a FUSE_READDIR reply: (name, is_dir, the host's offset of
+// the next entry).
+type DirPageEntry = (String, bool, u64);
+
+#[derive(Clone, Copy)]
+struct Attr {
+    size: u64,
+    atime: u64, // In nanoseconds.
+    mtime: u64,
+    mode: u32,
+}
+
+impl Attr {
+    fn parse(bytes: &[u8]) -> Self {
+        Self {
+            size: read_u64(bytes, 8),
+            atime: read_u64(bytes, 24) * 1_000_000_000 + read_u32(bytes, 48) as u64,
+            mtime: read_u64(bytes, 32) * 1_000_000_000 + read_u32(bytes, 52) as u64,
+            mode: read_u32(bytes, 60),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    fn to_file_attr(self) -> rt_api::fs::FileAttrData {
+        use rt_api::fs::FileAttrData;
+
+        FileAttrData {
+            version: 1,
+            self_size: core::mem::size_of::<FileAttrData>() as u16,
+            file_perm: rt_api::fs::FILE_PERM_READ | rt_api::fs::FILE_PERM_WRITE,
+            file_type: match self.mode & S_IFMT {
+                S_IFDIR => rt_api::fs::FILE_TYPE_DIR,
+                S_IFLNK => rt_api::fs::FILE_TYPE_SYMLINK,
+                _ => rt_api::fs::FILE_TYPE_FILE,
+            },
+            reserved: 0,
+            size: match self.mode & S_IFMT {
+                S_IFREG | S_IFLNK => self.size,
+                _ => 0,
+            },
+            created: 0, // FUSE doesn't report it.
+            accessed: self.atime,
+            modified: self.mtime,
+            uid: moto_sys::caps::ROOT_UID,
+            gid: 0,
+            mode: self.mode & rt_api::fs::FILE_MODE_MASK,
+            reserved_2: 0,
+        }
+    }
+}
+
+struct Session {
+    dev: Arc<dyn moto_virtio::FuseDevice>,
+    next_unique: u64,
+    max_write: usize,
+    request: Vec<u8>,
+    reply: Vec<u8>,
+}
+
+impl Session {
+    // Puts @req, with its header, into self.request; returns its unique id.
+    fn encode(&mut self, req: &Request) -> u64 {
+        let unique = self.next_unique;
+        self.next_unique += 1;
+
+        let len = IN_HEADER_SIZE + req.body.len();
+        self.request.clear();
+        self.request.extend_from_slice(&(len as u32).to_le_bytes());
+        self.request.extend_from_slice(&req.opcode.to_le_bytes());
+        self.request.extend_from_slice(&unique.to_le_bytes());
+        self.request.extend_from_slice(&req.nodeid.to_le_bytes());
+        // uid, gid, pid, total_extlen, padding: requests are made as root.
+        self.request.extend_from_slice(&[0; 16]);
+        self.request.extend_from_slice(&req.body);
+        unique
+    }
+
+    // Returns the reply without its header.
+    fn call(&mut self, req: Request) -> Result<&[u8], ErrorCode> {
+        let unique = self.encode(&req);
+        let reply_len = self
+            .dev
+            .request(&self.request, &mut self.reply)
+            .map_err(|_| ErrorCode::InternalError)?;
+        if reply_len < OUT_HEADER_SIZE
+            || read_u32(&self.reply, 0) as usize != reply_len
+            || read_u64(&self.reply, 8) != unique
+        {
+            log::warn!("virtio-fs: bad reply to opcode {}", req.opcode);
+            return Err(ErrorCode::InternalError);
+        }
+
+        match read_u32(&self.reply, 4) as i32 {
+            0 => Ok(&self.reply[OUT_HEADER_SIZE..reply_len]),
+            err => Err(to_error(-err)),
+        }
+    }
+
+    // Calls that reply with fuse_entry_out look the new node up.
+    fn call_entry(&mut self, req: Request) -> Result<(u64, Attr), ErrorCode> {
+        let reply = self.call(req)?;
+        if reply.len() < ENTRY_OUT_SIZE {
+            return Err(ErrorCode::InternalError);
+        }
+        match read_u64(reply, 0) {
+            0 => Err(ErrorCode::NotFound), // A negative entry.
+            nodeid => Ok((nodeid, Attr::parse(&reply[40..]))),
+        }
+    }
+
+    // Each lookup of a node has to be forgotten, once.
+    fn forget(&mut self, nodeids: &[u64]) {
+        let mut counts = HashMap::new();
+        for nodeid in nodeids.iter().filter(|id| **id != FUSE_ROOT_ID) {
+            *counts.entry(*nodeid).or_insert(0_u64) += 1;
+        }
+        if counts.is_empty() {
+            return;
+        }
+
+        let mut req = Request::new(FUSE_BATCH_FORGET, 0)
+            .u32(counts.len() as u32)
+            .u32(0);
+        for (nodeid, nlookup) in counts {
+            req = req.u64(nodeid).u64(nlookup);
+        }
+
+        self.encode(&req);
+        // There is no reply; the host just keeps the nodes longer on errors.
+        if self.dev.send(&self.request).is_err() {
+            log::warn!("virtio-fs: FUSE_BATCH_FORGET failed");
+        }
+    }
+
+    fn lookup(&mut self, dir: u64, name: &str) -> Result<(u64, Attr), ErrorCode> {
+        self.call_entry(Request::new(FUSE_LOOKUP, dir).name(name))
+    }
+
+    fn getattr(&mut self, nodeid: u64, fh: Option<u64>) -> Result<Attr, ErrorCode> {
+        let req = Request::new(FUSE_GETATTR, nodeid)
+            .u32(if fh.is_some() { FUSE_GETATTR_FH } else { 0 })
+            .u32(0)
+            .u64(fh.unwrap_or(0));
+        let reply = self.call(req)?;
+        if reply.len() < ATTR_OUT_SIZE {
+            return Err(ErrorCode::InternalError);
+        }
+        Ok(Attr::parse(&reply[16..]))
+    }
+
+    fn readlink(&mut self, nodeid: u64) -> Result<String, ErrorCode> {
+        let reply = self.call(Request::new(FUSE_READLINK, nodeid))?;
+        String::from_utf8(reply.to_vec()).map_err(|_| ErrorCode::InvalidFilename)
+    }
+
+    fn open(&mut self, opcode: u32, nodeid: u64, flags: u32) -> Result<u64, ErrorCode> {
+        let reply = self.call(Request::new(opcode, nodeid).u32(flags).u32(0))?;
+        if reply.len() < 16 {
+            return Err(ErrorCode::InternalError);
+        }
+        Ok(read_u64(reply, 0))
+    }
+
+    fn release(&mut self, opcode: u32, nodeid: u64, fh: u64) {
+        let req = Request::new(opcode, nodeid).u64(fh).u32(0).u32(0).u64(0);
+        if let Err(err) = self.call(req) {
+            log::warn!("virtio-fs: release failed: {:?}", err);
+        }
+    }
+
+    // The entries of @dir, other than "." and "..": (name, is_dir).
+    fn read_dir(&mut self, dir: u64) -> Result<Vec<(String, bool)>, ErrorCode> {
+        let fh = self.open(FUSE_OPENDIR, dir, O_RDONLY)?;
+        let mut entries = vec![];
+        let mut offset = Some(0);
+        let result = loop {
+            let Some(from) = offset else {
+                break Ok(());
+            };
+            match self.read_dir_page(dir, fh, from) {
+                Ok((page, next)) => {
+                    entries.extend(page.into_iter().map(|(name, is_dir, _)| (name, is_dir)));
+                    offset = next;
+                }
+                Err(err) => break Err(err),
+            }
+        };
+        self.release(FUSE_RELEASEDIR, dir, fh);
+        result.map(|_| entries)
+    }
+
+    // One FUSE_READDIR reply of the entries of @dir, opened as @fh, from the
+    // host's @offset, and the offset to continue at (None: at the end).
+    fn read_dir_page(
+        &mut self,
+        dir: u64,
+        fh: u64,
+        mut offset: u64,
+    ) -> Result<(Vec<DirPageEntry>, Option<u64>), ErrorCode> {
+        let req = Request::new(FUSE_READDIR, dir)
+            .u64(fh)
+            .u64(offset)
+            .u32(READDIR_SIZE)
+            .u32(0)
+            .u64(0)
+            .u32(0)
+            .u32(0);
+        let reply = self.call(req)?;
+        if reply.is_empty() {
+            return Ok((vec![], None));
+        }
+
+        let mut entries = vec![];
+        let mut pos = 0;
+        while pos + DIRENT_SIZE <= reply.len() {
+            let name_len = read_u32(reply, pos + 16) as usize;
+            let end = pos + DIRENT_SIZE + name_len;
+            if end > reply.len() {
+                break;
+            }
+            offset = read_u64(reply, pos + 8);
+            let name = String::from_utf8_lossy(&reply[(pos + DIRENT_SIZE)..end]);
+            if name != "." && name != ".." {
+                let is_dir = read_u32(reply, pos + 20) == DT_DIR;
+                entries.push((name.into_owned(), is_dir, offset));
+            }
+            pos = end.next_multiple_of(8);
+        }
+        Ok((entries, Some(offset)))
+    }
+
+    fn remove_all(&mut self, dir: u64) -> Result<(), ErrorCode> {
+        for (name, is_dir) in self.read_dir(dir)? {
+            if is_dir {
+                let (child, _) = self.lookup(dir, &name)?;
+                let result = self.remove_all(child);
+                self.forget(&[child]);
+                result?;
+                self.call(Request::new(FUSE_RMDIR, dir).name(&name))?;
+            } else {
+                self.call(Request::new(FUSE_UNLINK, dir).name(&name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct FileSystemVirtioFs {
+    mount_point: String,
+    session: Rc<RefCell<Session>>,
+}
+
+struct File {
+    session: Rc<RefCell<Session>>,
+    nodeid: u64,
+    fh: u64,
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let mut session = self.session.borrow_mut();
+        session.release(FUSE_RELEASE, self.nodeid, self.fh);
+        session.forget(&[self.nodeid]);
+    }
+}
+
+impl super::File for File {
+    fn unique_id(&self) -> u64 {
+        (Rc::as_ptr(&self.session) as usize as u64).wrapping_add(self.nodeid)
+    }
+
+    fn size(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self
+            .session
+            .borrow_mut()
+            .getattr(self.nodeid, Some(self.fh))?
+            .size)
+    }
+
+    fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let mut session = self.session.borrow_mut();
+        let len = buf.len().min(session.max_write);
+        let mut req = Request::new(FUSE_WRITE, self.nodeid)
+            .u64(self.fh)
+            .u64(offset)
+            .u32(len as u32)
+            .u32(0)
+            .u64(0)
+            .u32(0)
+            .u32(0);
+        req.body.extend_from_slice(&buf[0..len]);
+
+        let reply = session.call(req)?;
+        if reply.len() < 8 {
+            return Err(ErrorCode::InternalError);
+        }
+        Ok(read_u32(reply, 0) as usize)
+    }
+
+    fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let len = buf.len().min(MAX_IO_SIZE);
+        let req = Request::new(FUSE_READ, self.nodeid)
+            .u64(self.fh)
+            .u64(offset)
+            .u32(len as u32)
+            .u32(0)
+            .u64(0)
+            .u32(0)
+            .u32(0);
+
+        let mut session = self.session.borrow_mut();
+        let reply = session.call(req)?;
+        let read = reply.len().min(len);
+        buf[0..read].copy_from_slice(&reply[0..read]);
+        Ok(read)
+    }
+
+    fn sync(&mut self, data_only: bool) -> Result<(), ErrorCode> {
+        let flags = if data_only { FUSE_FSYNC_FDATASYNC } else { 0 };
+        let req = Request::new(FUSE_FSYNC, self.nodeid)
+            .u64(self.fh)
+            .u32(flags)
+            .u32(0);
+        self.session.borrow_mut().call(req).map(|_| ())
+    }
+
+    fn allocate(&mut self, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode> {
+        let mode = if punch_hole {
+            FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE
+        } else {
+            0
+        };
+        let req = Request::new(FUSE_FALLOCATE, self.nodeid)
+            .u64(self.fh)
+            .u64(offset)
+            .u64(len)
+            .u32(mode)
+            .u32(0);
+        self.session.borrow_mut().call(req).map(|_| ())
+    }
+
+    // sys-io caches nothing; caching on the host is up to virtiofsd.
+    fn set_direct(&mut self, _direct: bool) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+struct DirectoryEntryVirtioFs {
+    name: String,
+    attr: Attr,
+    cursor: u64, // The host's offset of the next entry.
+}
+
+impl super::filesystem::DirectoryEntry for DirectoryEntryVirtioFs {
+    fn is_directory(&self) -> bool {
+        self.attr.is_dir()
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.attr.is_symlink()
+    }
+
+    fn filename(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn size(&self) -> Result<u64, ErrorCode> {
+        Ok(self.attr.to_file_attr().size)
+    }
+
+    fn cursor(&self) -> u64 {
+        self.cursor
+    }
+}
+
+// Directories are read a FUSE_READDIR reply at a time; the host's offsets
+// are the cursors.
+struct DirectoryIterVirtioFs {
+    session: Rc<RefCell<Session>>,
+    dir: u64, // Looked up: forgotten on drop.
+    fh: u64,
+    offset: Option<u64>, // Of the next reply; None at the end.
+    entries: std::vec::IntoIter<DirPageEntry>,
+}
+
+impl Drop for DirectoryIterVirtioFs {
+    fn drop(&mut self) {
+        let mut session = self.session.borrow_mut();
+        session.release(FUSE_RELEASEDIR, self.dir, self.fh);
+        session.forget(&[self.dir]);
+    }
+}
+
+impl Iterator for DirectoryIterVirtioFs {
+    type Item = Box<dyn super::filesystem::DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut session = self.session.borrow_mut();
+        loop {
+            if let Some((name, _, cursor)) = self.entries.next() {
+                // Entries removed since they were read are skipped.
+                let (nodeid, attr) = match session.lookup(self.dir, &name) {
+                    Ok(entry) => entry,
+                    Err(ErrorCode::NotFound) => continue,
+                    Err(_) => return None,
+                };
+                session.forget(&[nodeid]);
+                return Some(Box::new(DirectoryEntryVirtioFs { name, attr, cursor }));
+            }
+            let (entries, next) = session
+                .read_dir_page(self.dir, self.fh, self.offset?)
+                .ok()?;
+            self.entries = entries.into_iter();
+            self.offset = next;
+        }
+    }
+}
+
+impl super::DirectoryIter for DirectoryIterVirtioFs {}
+
+impl FileSystemVirtioFs {
+    // Looks up @path; the node has to be forgotten by the caller.
+    // Symlinks are followed, except for the last path component if !follow_last.
+    fn lookup(&self, path: &str, follow_last: bool) -> Result<(u64, Attr), ErrorCode> {
+        path_walk::walk(self, path, follow_last)
+    }
+
+    // Calls @f with the looked up node at @path.
+    fn with_node<T>(
+        &self,
+        path: &str,
+        follow_last: bool,
+        f: impl FnOnce(&mut Session, u64, &Attr) -> Result<T, ErrorCode>,
+    ) -> Result<T, ErrorCode> {
+        let (nodeid, attr) = self.lookup(path, follow_last)?;
+        let mut session = self.session.borrow_mut();
+        let result = f(&mut session, nodeid, &attr);
+        session.forget(&[nodeid]);
+        result
+    }
+
+    // Calls @f with the parent directory of @path and the last component.
+    fn with_parent<T>(
+        &self,
+        path: &str,
+        f: impl FnOnce(&mut Session, u64, &str) -> Result<T, ErrorCode>,
+    ) -> Result<T, ErrorCode> {
+        let (parent, child) = split_parent_child(path)?;
+        self.with_node(parent, true, |session, dir, attr| {
+            if !attr.is_dir() {
+                return Err(ErrorCode::NotADirectory);
+            }
+            f(session, dir, child)
+        })
+    }
+
+    // Doesn't follow the symlink at @path.
+    fn lookup_attr(&self, path: &str) -> Result<Attr, ErrorCode> {
+        self.with_node(path, false, |_, _, attr| Ok(*attr))
+    }
+
+    // For requests that create a node: it is not kept.
+    fn create_entry(
+        &self,
+        path: &str,
+        req: impl FnOnce(u64, &str) -> Request,
+    ) -> Result<(), ErrorCode> {
+        self.with_parent(path, |session, dir, child| {
+            let (nodeid, _) = session.call_entry(req(dir, child))?;
+            session.forget(&[nodeid]);
+            Ok(())
+        })
+    }
+}
+
+// Nodes are (nodeid, attr), and are forgotten once released.
+impl PathWalk for FileSystemVirtioFs {
+    type Node = (u64, Attr);
+
+    fn root(&self) -> Result<Self::Node, ErrorCode> {
+        let attr = self.session.borrow_mut().getattr(FUSE_ROOT_ID, None)?;
+        Ok((FUSE_ROOT_ID, attr))
+    }
+
+    fn is_dir(&self, node: &Self::Node) -> bool {
+        node.1.is_dir()
+    }
+
+    fn is_symlink(&self, node: &Self::Node) -> bool {
+        node.1.is_symlink()
+    }
+
+    fn find(&self, dir: &Self::Node, name: &str) -> Result<Self::Node, ErrorCode> {
+        self.session.borrow_mut().lookup(dir.0, name)
+    }
+
+    fn symlink_target(&self, node: &Self::Node) -> Result<String, ErrorCode> {
+        let target = self.session.borrow_mut().readlink(node.0)?;
+        path_walk::local_target(&self.mount_point, target)
+    }
+
+    fn release(&self, nodes: Vec<Self::Node>) {
+        let nodeids: Vec<u64> = nodes.iter().map(|(nodeid, _)| *nodeid).collect();
+        self.session.borrow_mut().forget(&nodeids);
+    }
+}
+
+impl super::filesystem::FileSystem for FileSystemVirtioFs {
+    fn open_file(&'static mut self, path: &str) -> Result<Box<dyn super::File>, ErrorCode> {
+        let (nodeid, attr) = self.lookup(path, true)?;
+        let mut session = self.session.borrow_mut();
+        let fh = match attr.mode & S_IFMT {
+            // Files that are read-only on the host can still be read.
+            S_IFREG => match session.open(FUSE_OPEN, nodeid, O_RDWR) {
+                Err(ErrorCode::NotAllowed) => session.open(FUSE_OPEN, nodeid, O_RDONLY),
+                result => result,
+            },
+            S_IFDIR => Err(ErrorCode::NotFound),
+            _ => Err(ErrorCode::NotAllowed), // Devices, pipes, sockets.
+        };
+        match fh {
+            Ok(fh) => Ok(Box::new(File {
+                session: self.session.clone(),
+                nodeid,
+                fh,
+            })),
+            Err(err) => {
+                session.forget(&[nodeid]);
+                Err(err)
+            }
+        }
+    }
+
+    fn create_file(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.create_entry(path, |dir, child| {
+            Request::new(FUSE_MKNOD, dir)
+                .u32(S_IFREG | DEFAULT_FILE_MODE)
+                .u32(0)
+                .u32(0)
+                .u32(0)
+                .name(child)
+        })
+    }
+
+    fn iter(
+        &'static mut self,
+        path: &str,
+        cursor: u64,
+    ) -> Result<Box<dyn super::DirectoryIter>, ErrorCode> {
+        let (dir, attr) = self.lookup(path, true)?;
+        let mut session = self.session.borrow_mut();
+        let fh = if attr.is_dir() {
+            session.open(FUSE_OPENDIR, dir, O_RDONLY)
+        } else {
+            Err(ErrorCode::NotADirectory)
+        };
+        let fh = match fh {
+            Ok(fh) => fh,
+            Err(err) => {
+                session.forget(&[dir]);
+                return Err(err);
+            }
+        };
+
+        Ok(Box::new(DirectoryIterVirtioFs {
+            session: self.session.clone(),
+            dir,
+            fh,
+            offset: Some(cursor),
+            entries: Vec::new().into_iter(),
+        }))
+    }
+
+    fn stat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        self.with_node(path, true, |_, _, attr| Ok(attr.to_file_attr()))
+    }
+
+    fn lstat(&'static mut self, path: &str) -> Result<rt_api::fs::FileAttrData, ErrorCode> {
+        self.with_node(path, false, |_, _, attr| Ok(attr.to_file_attr()))
+    }
+
+    fn statfs(&'static mut self, path: &str) -> Result<rt_api::fs::FsStatsData, ErrorCode> {
+        use rt_api::fs::FsStatsData;
+
+        self.with_node(path, true, |session, nodeid, _| {
+            let reply = session.call(Request::new(FUSE_STATFS, nodeid))?;
+            if reply.len() < 80 {
+                return Err(ErrorCode::InternalError);
+            }
+            Ok(FsStatsData {
+                version: 0,
+                self_size: core::mem::size_of::<FsStatsData>() as u16,
+                fs_type: rt_api::fs::FS_TYPE_VIRTIOFS,
+                read_only: 0,
+                reserved: 0,
+                block_size: read_u32(reply, 40) as u64,
+                blocks_total: read_u64(reply, 0),
+                blocks_free: read_u64(reply, 16), // Available to unprivileged users.
+            })
+        })
+    }
+
+    fn mkdir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.create_entry(path, |dir, child| {
+            Request::new(FUSE_MKDIR, dir)
+                .u32(DEFAULT_DIR_MODE)
+                .u32(0)
+                .name(child)
+        })
+    }
+
+    // Removes the symlink itself, not its target.
+    fn unlink(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.with_parent(path, |session, dir, child| {
+            let (nodeid, attr) = session.lookup(dir, child)?;
+            session.forget(&[nodeid]);
+            let opcode = if attr.is_dir() {
+                FUSE_RMDIR
+            } else {
+                FUSE_UNLINK
+            };
+            session
+                .call(Request::new(opcode, dir).name(child))
+                .map(|_| ())
+        })
+    }
+
+    fn delete_dir(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        if !self.lookup_attr(path)?.is_dir() {
+            return Err(ErrorCode::NotADirectory);
+        }
+        self.unlink(path)
+    }
+
+    fn delete_dir_all(&'static mut self, path: &str) -> Result<(), ErrorCode> {
+        self.with_node(path, false, |session, dir, attr| {
+            if !attr.is_dir() {
+                return Err(ErrorCode::NotADirectory);
+            }
+            session.remove_all(dir)
+        })?;
+        self.unlink(path)
+    }
+
+    fn rename(&'static mut self, old: &str, new: &str) -> Result<(), ErrorCode> {
+        let (old_parent, old_child) = split_parent_child(old)?;
+        let (new_parent, new_child) = split_parent_child(new)?;
+        let (new_dir, new_dir_attr) = self.lookup(new_parent, true)?;
+
+        let result = self.with_node(old_parent, true, |session, old_dir, old_dir_attr| {
+            if !old_dir_attr.is_dir() || !new_dir_attr.is_dir() {
+                return Err(ErrorCode::NotADirectory);
+            }
+            let req = Request::new(FUSE_RENAME, old_dir)
+                .u64(new_dir)
+                .name(old_child)
+                .name(new_child);
+            session.call(req).map(|_| ())
+        });
+        self.session.borrow_mut().forget(&[new_dir]);
+        result
+    }
+
+    fn symlink(&'static mut self, target: &str, link: &str) -> Result<(), ErrorCode> {
+        self.create_entry(link, |dir, child| {
+            Request::new(FUSE_SYMLINK, dir).name(child).name(target)
+        })
+    }
+
+    fn readlink(&'static mut self, path: &str) -> Result<String, ErrorCode> {
+        self.with_node(path, false, |session, nodeid, attr| {
+            if !attr.is_symlink() {
+                return Err(ErrorCode::InvalidArgument);
+            }
+            session.readlink(nodeid)
+        })
+    }
+
+    fn link(&'static mut self, existing: &str, link: &str) -> Result<(), ErrorCode> {
+        let (nodeid, attr) = self.lookup(existing, false)?;
+        let result = if attr.is_dir() {
+            Err(ErrorCode::NotAllowed)
+        } else {
+            self.create_entry(link, |dir, child| {
+                Request::new(FUSE_LINK, dir).u64(nodeid).name(child)
+            })
+        };
+        self.session.borrow_mut().forget(&[nodeid]);
+        result
+    }
+
+    fn chmod(&'static mut self, path: &str, mode: u16) -> Result<(), ErrorCode> {
+        if (mode as u32 & !rt_api::fs::FILE_MODE_MASK) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.with_node(path, true, |session, nodeid, _| {
+            // fuse_setattr_in: only valid and mode are used.
+            let mut req = Request::new(FUSE_SETATTR, nodeid).u32(FATTR_MODE);
+            req.body.resize(68, 0);
+            req = req.u32(mode as u32);
+            req.body.resize(88, 0);
+            session.call(req).map(|_| ())
+        })
+    }
+
+    // Host owners are not mapped (see the top of the file); like on FAT,
+    // this is not an error, so that files can be created by anyone.
+    fn chown(&'static mut self, path: &str, _uid: u32, _gid: u32) -> Result<(), ErrorCode> {
+        self.lookup_attr(path).map(|_| ())
+    }
+
+    fn get_xattr(&'static mut self, path: &str, name: &str) -> Result<Vec<u8>, ErrorCode> {
+        self.with_node(path, true, |session, nodeid, _| {
+            let req = Request::new(FUSE_GETXATTR, nodeid)
+                .u32(MAX_XATTR_SIZE)
+                .u32(0) // padding
+                .name(name);
+            session.call(req).map(|value| value.to_vec())
+        })
+    }
+
+    fn list_xattrs(&'static mut self, path: &str) -> Result<Vec<String>, ErrorCode> {
+        self.with_node(path, true, |session, nodeid, _| {
+            let req = Request::new(FUSE_LISTXATTR, nodeid)
+                .u32(MAX_XATTR_SIZE)
+                .u32(0); // padding
+            let reply = session.call(req)?;
+
+            // Names are NUL-terminated.
+            let mut names = Vec::new();
+            for name in reply.split(|b| *b == 0).filter(|n| !n.is_empty()) {
+                let name = core::str::from_utf8(name).map_err(|_| ErrorCode::InternalError)?;
+                names.push(name.to_owned());
+            }
+            Ok(names)
+        })
+    }
+
+    fn set_xattr(&'static mut self, path: &str, name: &str, value: &[u8]) -> Result<(), ErrorCode> {
+        self.with_node(path, true, |session, nodeid, _| {
+            // fuse_setxattr_in: size, flags (XATTR_CREATE/REPLACE are checked
+            // by the caller).
+            let mut req = Request::new(FUSE_SETXATTR, nodeid)
+                .u32(value.len() as u32)
+                .u32(0)
+                .name(name);
+            req.body.extend_from_slice(value);
+            session.call(req).map(|_| ())
+        })
+    }
+
+    fn remove_xattr(&'static mut self, path: &str, name: &str) -> Result<(), ErrorCode> {
+        self.with_node(path, true, |session, nodeid, _| {
+            let req = Request::new(FUSE_REMOVEXATTR, nodeid).name(name);
+            session.call(req).map(|_| ())
+        })
+    }
+
+    // Quotas of the host are not visible through FUSE.
+    fn get_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+    ) -> Result<rt_api::fs::QuotaData, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_quota(
+        &'static mut self,
+        _path: &str,
+        _uid: Option<u32>,
+        _max_bytes: u64,
+        _max_inodes: u64,
+    ) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+}
+
+pub(super) fn init(
+    dev: Arc<dyn moto_virtio::FuseDevice>,
+    mount_point: &str,
+) -> Result<Box<dyn FileSystem>, ErrorCode> {
+    let mut session = Session {
+        dev,
+        next_unique: 1,
+        max_write: 0,
+        request: Vec::with_capacity(moto_virtio::FUSE_MAX_MESSAGE),
+        reply: vec![0; moto_virtio::FUSE_MAX_MESSAGE],
+    };
+
+    let req = Request::new(FUSE_INIT, 0)
+        .u32(FUSE_KERNEL_VERSION)
+        .u32(FUSE_KERNEL_MINOR_VERSION)
+        .u32(0) // max_readahead
+        .u32(0); // flags
+    let reply = session.call(req)?;
+    if reply.len() < 24 || read_u32(reply, 0) != FUSE_KERNEL_VERSION {
+        log::warn!("virtio-fs: unsupported FUSE version");
+        return Err(ErrorCode::NotImplemented);
+    }
+    session.max_write = (read_u32(reply, 20) as usize).clamp(4096, MAX_IO_SIZE);
+
+    Ok(Box::new(FileSystemVirtioFs {
+        mount_point: mount_point.to_owned(),
+        session: Rc::new(RefCell::new(session)),
+    }))
+}
@@ -1,18 +1,34 @@
 mod dispatcher;
 mod driver;
 mod filesystem;
+mod fs_devfs;
+mod fs_ext2;
+mod fs_fat;
 mod fs_flatfs;
+mod fs_procfs;
 mod fs_srfs;
+mod fs_tmpfs;
+mod fs_virtiofs;
 mod mbr;
+mod page_cache;
+mod partition;
+mod path_walk;
+mod watch;
 
 pub use filesystem::*;
 const DRIVER_URL: &str = "moturus-fs-driver";
 
+// tmpfs may use up to this fraction of the physical memory.
+const TMPFS_MEMORY_FRACTION: u64 = 4;
+
 pub static STARTED: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
 
 pub fn init() {
+    page_cache::init();
     filesystem::init();
     set_temp_dir();
+    mount_virtual("proc", "procfs", "/proc", fs_procfs::init);
+    mount_virtual("dev", "devfs", "/dev", fs_devfs::init);
     dispatcher::start().unwrap();
     while STARTED.load(std::sync::atomic::Ordering::Relaxed) == 0 {
         moto_runtime::futex_wait(&STARTED, 0, None);
@@ -21,14 +37,32 @@ pub fn init() {
     crate::moto_log!("FS initialized");
 }
 
+// The temp dir is a tmpfs, so that scratch files don't go to the drive.
 fn set_temp_dir() {
     let dir = std::env::temp_dir();
     let dirname = dir.to_str().unwrap();
 
-    if let Ok(_attr) = filesystem::fs().stat(dirname) {
-        filesystem::fs().delete_dir(dirname).unwrap();
-    }
-
-    // Read-only FS can't create dirs, so we ignore errors.
+    // The mount point shows up in its parent's listing only if it exists on
+    // the root FS. Read-only FS can't create dirs, so we ignore errors.
     let _ = filesystem::fs().mkdir(dirname);
+
+    let max_bytes = moto_sys::stats::MemoryStats::get().unwrap().available / TMPFS_MEMORY_FRACTION;
+    filesystem::mount(
+        "tmpfs",
+        "tmpfs",
+        dirname,
+        fs_tmpfs::init(dirname, max_bytes),
+    );
+}
+
+// Filesystems without storage, e.g. procfs.
+fn mount_virtual(
+    source: &str,
+    fs_type: &'static str,
+    mount_point: &str,
+    init: fn(&str) -> Box<dyn FileSystem>,
+) {
+    // See set_temp_dir() above.
+    let _ = filesystem::fs().mkdir(mount_point);
+    filesystem::mount(source, fs_type, mount_point, init(mount_point));
 }
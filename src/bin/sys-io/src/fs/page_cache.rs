@@ -0,0 +1,477 @@
+// The page cache: the contents of the drives, in 4K pages, shared by all the
+// filesystems on them (see drives()).
+//
+// A read that misses reads ahead if it continues where the previous read on
+// the drive stopped; the readahead window doubles with each such read, up to
+// MAX_READAHEAD_PAGES, and reads that hit the window extend it. Writes only
+// dirty the cached pages, which are written back once they expire (by the
+// writeback thread), when too many are dirty, when they are evicted, or when
+// the drive is flushed. A flush writes back the dirty pages of the drive
+// before flushing it, so flushes order writes as they do without the cache.
+//
+// Direct I/O (F_DIRECT, see set_context()) bypasses the cache: reads don't
+// populate it, and writes go to the drive, updating the pages already cached.
+//
+// The cache stays locked during drive I/O: the only other user of the lock
+// is the writeback thread.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use moto_virtio::{BlockDevice, BLOCK_SIZE};
+
+const PAGE_SIZE: usize = 4096;
+const SECTORS_PER_PAGE: u64 = (PAGE_SIZE / BLOCK_SIZE) as u64;
+
+// The cache may use up to this fraction of the physical memory.
+const CACHE_MEMORY_FRACTION: u64 = 8;
+const MIN_CACHE_PAGES: usize = 1024;
+
+const MAX_READAHEAD_PAGES: u64 = 32;
+
+// Dirty pages are written back once they are DIRTY_EXPIRE old, by a thread
+// waking up every WRITEBACK_INTERVAL, or right away if more than
+// 1/MAX_DIRTY_FRACTION of the cache is dirty.
+const DIRTY_EXPIRE: Duration = Duration::from_secs(5);
+const WRITEBACK_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_DIRTY_FRACTION: usize = 4;
+
+#[repr(C, align(4096))]
+struct Page([u8; PAGE_SIZE]);
+
+struct CachedPage {
+    page: Box<Page>,
+    sectors: u64,           // Fewer than SECTORS_PER_PAGE at the end of a drive.
+    stamp: u64,             // The key in Cache::lru; zero until cached.
+    dirty: Option<Instant>, // Since when.
+}
+
+impl CachedPage {
+    fn bytes(&self) -> &[u8] {
+        &self.page.0[..(self.sectors as usize * BLOCK_SIZE)]
+    }
+}
+
+#[derive(Default)]
+struct Readahead {
+    next_page: u64, // Where the previous read stopped.
+    window: u64,
+}
+
+/// Page cache usage, of a process or in total.
+#[derive(Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub readahead: u64, // Pages read ahead.
+    pub dirtied: u64,   // Pages dirtied by writes.
+}
+
+/// The state of the page cache, in pages.
+pub struct CacheInfo {
+    pub pages: u64,
+    pub dirty: u64,
+    pub capacity: u64,
+    pub writebacks: u64,
+    pub totals: CacheStats,
+}
+
+type PageKey = (usize, u64); // (drive idx, page_no).
+
+struct Cache {
+    pages: BTreeMap<PageKey, CachedPage>,
+    lru: BTreeMap<u64, PageKey>, // stamp => page; least recently used first.
+    dirty: BTreeSet<PageKey>,
+    next_stamp: u64,
+    capacity: usize, // In pages.
+    readahead: BTreeMap<usize, Readahead>,
+    stats: BTreeMap<u64, CacheStats>, // pid => usage.
+    totals: CacheStats,
+    writebacks: u64,
+}
+
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    pages: BTreeMap::new(),
+    lru: BTreeMap::new(),
+    dirty: BTreeSet::new(),
+    next_stamp: 1,
+    capacity: MIN_CACHE_PAGES,
+    readahead: BTreeMap::new(),
+    stats: BTreeMap::new(),
+    totals: CacheStats {
+        hits: 0,
+        misses: 0,
+        readahead: 0,
+        dirtied: 0,
+    },
+    writebacks: 0,
+});
+
+thread_local! {
+    // The process the FS driver works for, and whether its I/O is direct.
+    static CONTEXT: Cell<(u64, bool)> = const { Cell::new((0, false)) };
+
+    // The drives themselves, indexed as in PageKey: any I/O may evict any
+    // page, so the cache needs all of them at hand.
+    static DRIVES: Vec<Arc<dyn BlockDevice>> = moto_virtio::lsblk();
+}
+
+// Called by the FS driver for each request.
+pub(super) fn set_context(pid: u64, direct: bool) {
+    CONTEXT.with(|ctx| ctx.set((pid, direct)));
+}
+
+fn is_direct() -> bool {
+    CONTEXT.with(|ctx| ctx.get().1)
+}
+
+// The number of sectors in the page; fewer at the end of the drive.
+fn page_sectors(drive: &dyn BlockDevice, page_no: u64) -> u64 {
+    drive
+        .capacity()
+        .saturating_sub(page_no * SECTORS_PER_PAGE)
+        .min(SECTORS_PER_PAGE)
+}
+
+impl Cache {
+    fn count(&mut self, update: impl Fn(&mut CacheStats)) {
+        update(&mut self.totals);
+        let pid = CONTEXT.with(|ctx| ctx.get().0);
+        if pid != 0 {
+            // Zero is sys-io itself, e.g. mounting filesystems.
+            update(self.stats.entry(pid).or_default());
+        }
+    }
+
+    fn touch(&mut self, key: PageKey) {
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+        let page = self.pages.get_mut(&key).unwrap();
+        self.lru.remove(&page.stamp);
+        page.stamp = stamp;
+        self.lru.insert(stamp, key);
+    }
+
+    fn mark_dirty(&mut self, key: PageKey) {
+        let page = self.pages.get_mut(&key).unwrap();
+        if page.dirty.is_none() {
+            page.dirty = Some(Instant::now());
+            self.dirty.insert(key);
+            self.count(|stats| stats.dirtied += 1);
+        }
+    }
+
+    fn insert(&mut self, drives: &[Arc<dyn BlockDevice>], key: PageKey, page: CachedPage) {
+        self.pages.insert(key, page);
+        self.touch(key);
+        self.evict(drives);
+    }
+
+    fn evict(&mut self, drives: &[Arc<dyn BlockDevice>]) {
+        while self.pages.len() > self.capacity {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            if self.dirty.contains(&key) && self.write_back(drives, key).is_err() {
+                // Dropping the page would lose the write: keep it for now.
+                log::error!("page cache: failed to write back page {:?}", key);
+                self.touch(key);
+                break;
+            }
+            self.pages.remove(&key);
+        }
+    }
+
+    fn write_back(&mut self, drives: &[Arc<dyn BlockDevice>], key: PageKey) -> Result<(), ()> {
+        let page = self.pages.get_mut(&key).unwrap();
+        drives[key.0].write(
+            page.bytes(),
+            key.1 * PAGE_SIZE as u64,
+            page.sectors as usize,
+        )?;
+        page.dirty = None;
+        self.dirty.remove(&key);
+        self.writebacks += 1;
+        Ok(())
+    }
+
+    // Writes back the dirty pages of the drive (of all drives if None) that
+    // were dirtied before the deadline (all of them if None), in order.
+    fn write_back_all(
+        &mut self,
+        drives: &[Arc<dyn BlockDevice>],
+        drive_idx: Option<usize>,
+        dirtied_before: Option<Instant>,
+    ) -> Result<(), ()> {
+        let keys: Vec<PageKey> = self
+            .dirty
+            .iter()
+            .filter(|key| drive_idx.map_or(true, |idx| key.0 == idx))
+            .filter(|key| {
+                dirtied_before.map_or(true, |deadline| self.pages[key].dirty.unwrap() < deadline)
+            })
+            .copied()
+            .collect();
+        for key in keys {
+            self.write_back(drives, key)?;
+        }
+        Ok(())
+    }
+
+    // Reads up to @count pages starting at @page_no into the cache, stopping
+    // at the first page already cached. Returns the number of pages read.
+    fn fetch(
+        &mut self,
+        drives: &[Arc<dyn BlockDevice>],
+        drive_idx: usize,
+        page_no: u64,
+        count: u64,
+    ) -> Result<u64, ()> {
+        let drive = drives[drive_idx].as_ref();
+        let end = drive.capacity().div_ceil(SECTORS_PER_PAGE);
+        let mut count = count.min(end.saturating_sub(page_no));
+        if let Some(((_, cached), _)) = self
+            .pages
+            .range((drive_idx, page_no)..(drive_idx, page_no + count))
+            .next()
+        {
+            count = cached - page_no;
+        }
+        if count == 0 {
+            return Ok(0);
+        }
+
+        // One request for all the pages.
+        let mut pages: Vec<Page> = (0..count).map(|_| Page([0; PAGE_SIZE])).collect();
+        let sectors = drive
+            .capacity()
+            .saturating_sub(page_no * SECTORS_PER_PAGE)
+            .min(count * SECTORS_PER_PAGE);
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(
+                pages.as_mut_ptr() as *mut u8,
+                sectors as usize * BLOCK_SIZE,
+            )
+        };
+        drive.read(bytes, page_no * PAGE_SIZE as u64, sectors as usize)?;
+
+        for (idx, page) in pages.into_iter().enumerate() {
+            let page_no = page_no + idx as u64;
+            let page = CachedPage {
+                page: Box::new(page),
+                sectors: page_sectors(drive, page_no),
+                stamp: 0,
+                dirty: None,
+            };
+            self.insert(drives, (drive_idx, page_no), page);
+        }
+        Ok(count)
+    }
+
+    // The readahead window for a (non-direct) read of the page.
+    fn readahead_window(&mut self, drive_idx: usize, page_no: u64) -> u64 {
+        let readahead = self.readahead.entry(drive_idx).or_default();
+        readahead.window = if page_no == readahead.next_page {
+            (readahead.window * 2).clamp(1, MAX_READAHEAD_PAGES)
+        } else {
+            0
+        };
+        readahead.next_page = page_no + 1;
+        readahead.window
+    }
+
+    fn read(
+        &mut self,
+        drives: &[Arc<dyn BlockDevice>],
+        drive_idx: usize,
+        buf: &mut [u8],
+        address: u64,
+    ) -> Result<(), ()> {
+        let direct = is_direct();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let addr = address + pos as u64;
+            let page_no = addr / PAGE_SIZE as u64;
+            let offset = (addr % PAGE_SIZE as u64) as usize;
+            let len = (PAGE_SIZE - offset).min(buf.len() - pos);
+            let key = (drive_idx, page_no);
+
+            if self.pages.contains_key(&key) {
+                self.count(|stats| stats.hits += 1);
+                if !direct {
+                    let window = self.readahead_window(drive_idx, page_no);
+                    // A failed readahead is not the reader's problem.
+                    if let Ok(fetched) = self.fetch(drives, drive_idx, page_no + 1, window) {
+                        self.count(|stats| stats.readahead += fetched);
+                    }
+                }
+            } else if direct {
+                self.count(|stats| stats.misses += 1);
+                drives[drive_idx].read(&mut buf[pos..(pos + len)], addr, len / BLOCK_SIZE)?;
+                pos += len;
+                continue;
+            } else {
+                self.count(|stats| stats.misses += 1);
+                let window = self.readahead_window(drive_idx, page_no);
+                let fetched = self.fetch(drives, drive_idx, page_no, 1 + window)?;
+                self.count(|stats| stats.readahead += fetched - 1);
+            }
+
+            self.touch(key);
+            let page = &self.pages[&key];
+            buf[pos..(pos + len)].copy_from_slice(&page.page.0[offset..(offset + len)]);
+            pos += len;
+        }
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        drives: &[Arc<dyn BlockDevice>],
+        drive_idx: usize,
+        buf: &[u8],
+        address: u64,
+    ) -> Result<(), ()> {
+        let direct = is_direct();
+        if direct {
+            drives[drive_idx].write(buf, address, buf.len() / BLOCK_SIZE)?;
+        }
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let addr = address + pos as u64;
+            let page_no = addr / PAGE_SIZE as u64;
+            let offset = (addr % PAGE_SIZE as u64) as usize;
+            let len = (PAGE_SIZE - offset).min(buf.len() - pos);
+            let key = (drive_idx, page_no);
+
+            if !self.pages.contains_key(&key) {
+                if direct {
+                    pos += len;
+                    continue;
+                }
+                let sectors = page_sectors(drives[drive_idx].as_ref(), page_no);
+                if offset == 0 && len == sectors as usize * BLOCK_SIZE {
+                    // The whole page is overwritten: no need to read it.
+                    let page = CachedPage {
+                        page: Box::new(Page([0; PAGE_SIZE])),
+                        sectors,
+                        stamp: 0,
+                        dirty: None,
+                    };
+                    self.insert(drives, key, page);
+                } else {
+                    self.fetch(drives, drive_idx, page_no, 1)?;
+                }
+            }
+
+            self.touch(key);
+            let page = self.pages.get_mut(&key).unwrap();
+            page.page.0[offset..(offset + len)].copy_from_slice(&buf[pos..(pos + len)]);
+            if !direct {
+                self.mark_dirty(key);
+            }
+            pos += len;
+        }
+
+        if self.dirty.len() > self.capacity / MAX_DIRTY_FRACTION {
+            self.write_back_all(drives, None, None)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, drives: &[Arc<dyn BlockDevice>], drive_idx: usize) -> Result<(), ()> {
+        self.write_back_all(drives, Some(drive_idx), None)?;
+        drives[drive_idx].flush()
+    }
+
+    // Forget the usage of processes that have exited.
+    fn prune_stats(&mut self) {
+        self.stats.retain(|pid, _| {
+            let mut buf = [moto_sys::stats::ProcessStatsV1::default()];
+            matches!(
+                moto_sys::stats::ProcessStatsV1::list(*pid, &mut buf),
+                Ok(1) if buf[0].pid == *pid
+            )
+        });
+    }
+}
+
+// A drive behind the page cache.
+struct CachedDrive {
+    idx: usize,
+}
+
+impl BlockDevice for CachedDrive {
+    fn read(&self, buf: &mut [u8], address: u64, number_of_blocks: usize) -> Result<(), ()> {
+        let buf = &mut buf[..(number_of_blocks * BLOCK_SIZE)];
+        DRIVES.with(|drives| CACHE.lock().unwrap().read(drives, self.idx, buf, address))
+    }
+
+    fn write(&self, buf: &[u8], address: u64, number_of_blocks: usize) -> Result<(), ()> {
+        let buf = &buf[..(number_of_blocks * BLOCK_SIZE)];
+        DRIVES.with(|drives| CACHE.lock().unwrap().write(drives, self.idx, buf, address))
+    }
+
+    fn flush(&self) -> Result<(), ()> {
+        DRIVES.with(|drives| CACHE.lock().unwrap().flush(drives, self.idx))
+    }
+
+    fn capacity(&self) -> u64 {
+        DRIVES.with(|drives| drives[self.idx].capacity())
+    }
+}
+
+/// The drives, as listed by moto_virtio::lsblk(), behind the page cache.
+pub fn drives() -> Vec<Arc<dyn BlockDevice>> {
+    let count = DRIVES.with(|drives| drives.len());
+    (0..count)
+        .map(|idx| Arc::new(CachedDrive { idx }) as Arc<dyn BlockDevice>)
+        .collect()
+}
+
+pub fn info() -> CacheInfo {
+    let cache = CACHE.lock().unwrap();
+    CacheInfo {
+        pages: cache.pages.len() as u64,
+        dirty: cache.dirty.len() as u64,
+        capacity: cache.capacity as u64,
+        writebacks: cache.writebacks,
+        totals: cache.totals,
+    }
+}
+
+pub fn process_stats(pid: u64) -> CacheStats {
+    CACHE
+        .lock()
+        .unwrap()
+        .stats
+        .get(&pid)
+        .copied()
+        .unwrap_or_default()
+}
+
+pub(super) fn init() {
+    let max_bytes = moto_sys::stats::MemoryStats::get().unwrap().available / CACHE_MEMORY_FRACTION;
+    CACHE.lock().unwrap().capacity = ((max_bytes / PAGE_SIZE as u64) as usize).max(MIN_CACHE_PAGES);
+    std::thread::spawn(writeback);
+}
+
+fn writeback() {
+    // VirtIO interrupts are affined to CPU 0.
+    moto_sys::SysCpu::affine_to_cpu(Some(0)).unwrap();
+
+    loop {
+        std::thread::sleep(WRITEBACK_INTERVAL);
+        let mut cache = CACHE.lock().unwrap();
+        if let Some(deadline) = Instant::now().checked_sub(DIRTY_EXPIRE) {
+            let result = DRIVES.with(|drives| cache.write_back_all(drives, None, Some(deadline)));
+            if result.is_err() {
+                log::error!("page cache: writeback failed");
+            }
+        }
+        cache.prune_stats();
+    }
+}
@@ -0,0 +1,122 @@
+// Byte-granular reads and writes of a partition, for filesystem drivers
+// that don't have their own block layer (e.g. FAT).
+
+use alloc::sync::Arc;
+use moto_sys::ErrorCode;
+
+const SECTOR_SIZE: u64 = moto_virtio::BLOCK_SIZE as u64;
+const BOUNCE_SIZE: usize = 64 * 1024;
+
+pub struct Partition {
+    drive: Arc<dyn moto_virtio::BlockDevice>,
+    start: u64, // In bytes.
+    size: u64,  // In bytes.
+    // Virtio needs sector-aligned buffers, and writes need whole sectors.
+    bounce: *mut u8,
+}
+
+impl Drop for Partition {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.bounce, Self::bounce_layout()) }
+    }
+}
+
+impl Partition {
+    pub fn new(drive: Arc<dyn moto_virtio::BlockDevice>, lba: u64, sectors: u64) -> Self {
+        let bounce = unsafe { std::alloc::alloc(Self::bounce_layout()) };
+        assert!(!bounce.is_null());
+        Self {
+            drive,
+            start: lba * SECTOR_SIZE,
+            size: sectors * SECTOR_SIZE,
+            bounce,
+        }
+    }
+
+    fn bounce_layout() -> std::alloc::Layout {
+        std::alloc::Layout::from_size_align(BOUNCE_SIZE, SECTOR_SIZE as usize).unwrap()
+    }
+
+    fn bounce(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.bounce, BOUNCE_SIZE) }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // Calls @f for each piece of [offset, offset + len) that fits into the bounce
+    // buffer, with the first sector of the piece, the number of sectors, and
+    // the piece's offset in the first sector and length.
+    fn for_each_piece(
+        &mut self,
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(&mut Self, u64, usize, usize, usize, usize) -> Result<(), ErrorCode>,
+    ) -> Result<(), ErrorCode> {
+        if offset
+            .checked_add(len as u64)
+            .map_or(true, |end| end > self.size)
+        {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let sector = pos & !(SECTOR_SIZE - 1);
+            let skip = (pos - sector) as usize;
+            let piece = (len - done).min(BOUNCE_SIZE - skip);
+            let sectors = (skip + piece).div_ceil(SECTOR_SIZE as usize);
+            f(self, sector, sectors, skip, piece, done)?;
+            done += piece;
+        }
+        Ok(())
+    }
+
+    fn read_sectors(&mut self, sector: u64, sectors: usize) -> Result<(), ErrorCode> {
+        let address = self.start + sector;
+        let bytes = sectors * SECTOR_SIZE as usize;
+        let buf = unsafe { core::slice::from_raw_parts_mut(self.bounce, bytes) };
+        self.drive
+            .read(buf, address, sectors)
+            .map_err(|_| ErrorCode::InternalError)
+    }
+
+    pub fn read(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), ErrorCode> {
+        self.for_each_piece(
+            offset,
+            buf.len(),
+            |this, sector, sectors, skip, piece, done| {
+                this.read_sectors(sector, sectors)?;
+                buf[done..(done + piece)].copy_from_slice(&this.bounce()[skip..(skip + piece)]);
+                Ok(())
+            },
+        )
+    }
+
+    pub fn write(&mut self, offset: u64, buf: &[u8]) -> Result<(), ErrorCode> {
+        self.for_each_piece(
+            offset,
+            buf.len(),
+            |this, sector, sectors, skip, piece, done| {
+                // Partial sectors keep the rest of their bytes.
+                if skip != 0 || piece % (SECTOR_SIZE as usize) != 0 {
+                    this.read_sectors(sector, sectors)?;
+                }
+                this.bounce()[skip..(skip + piece)].copy_from_slice(&buf[done..(done + piece)]);
+
+                let address = this.start + sector;
+                let bytes = sectors * SECTOR_SIZE as usize;
+                let data = unsafe { core::slice::from_raw_parts(this.bounce, bytes) };
+                this.drive
+                    .write(data, address, sectors)
+                    .map_err(|_| ErrorCode::InternalError)
+            },
+        )
+    }
+
+    pub fn flush(&mut self) -> Result<(), ErrorCode> {
+        self.drive.flush().map_err(|_| ErrorCode::InternalError)
+    }
+}
@@ -0,0 +1,125 @@
+// Path resolution for filesystems with symlinks, and for the driver, which
+// resolves the paths of namespaced peers against their FS root. Components
+// are looked up one at a time; symlink targets are spliced in as they are
+// encountered, absolute targets start over at the root, and ".." stops there.
+
+use moto_sys::ErrorCode;
+
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+// What walk() needs from a filesystem.
+pub trait PathWalk {
+    type Node;
+
+    fn root(&self) -> Result<Self::Node, ErrorCode>;
+    fn is_dir(&self, node: &Self::Node) -> bool;
+    fn is_symlink(&self, node: &Self::Node) -> bool;
+
+    // Looks up @name in the directory @dir.
+    fn find(&self, dir: &Self::Node, name: &str) -> Result<Self::Node, ErrorCode>;
+
+    // The target of the symlink @node, as a path on this filesystem.
+    fn symlink_target(&self, node: &Self::Node) -> Result<String, ErrorCode>;
+
+    // Called if the last component, @name, is not in @dir: callers that
+    // resolve paths to be created may return a node for it.
+    fn not_found(&self, _dir: &Self::Node, _name: &str) -> Result<Self::Node, ErrorCode> {
+        Err(ErrorCode::NotFound)
+    }
+
+    // The nodes walk() looked up, but did not return.
+    fn release(&self, _nodes: Vec<Self::Node>) {}
+}
+
+// Symlinks are followed, except for the last path component if !follow_last.
+pub fn walk<W: PathWalk>(fs: &W, path: &str, follow_last: bool) -> Result<W::Node, ErrorCode> {
+    // The directories walked through, for "..", starting with the root.
+    let mut dirs = vec![fs.root()?];
+    let mut released = vec![];
+
+    let result = walk_components(fs, path, follow_last, &mut dirs, &mut released);
+    let last = dirs.pop().unwrap();
+    released.extend(dirs);
+    let result = match result {
+        Ok(()) => Ok(last),
+        Err(err) => {
+            released.push(last);
+            Err(err)
+        }
+    };
+
+    if !released.is_empty() {
+        fs.release(released);
+    }
+    result
+}
+
+// Pushes the nodes @path leads through onto @dirs; the last one is the result.
+fn walk_components<W: PathWalk>(
+    fs: &W,
+    path: &str,
+    follow_last: bool,
+    dirs: &mut Vec<W::Node>,
+    released: &mut Vec<W::Node>,
+) -> Result<(), ErrorCode> {
+    // Components yet to be resolved, in reverse order.
+    let mut components: Vec<String> = path.split('/').rev().map(|c| c.to_owned()).collect();
+    let mut follows = 0;
+
+    while let Some(component) = components.pop() {
+        if component.is_empty() || component == "." {
+            continue;
+        }
+        let dir = dirs.last().unwrap();
+        if !fs.is_dir(dir) {
+            return Err(ErrorCode::NotADirectory);
+        }
+        if component == ".." {
+            if dirs.len() > 1 {
+                released.push(dirs.pop().unwrap());
+            }
+            continue;
+        }
+
+        let is_last = components.iter().all(|c| c.is_empty() || c == ".");
+        let node = match fs.find(dir, &component) {
+            Ok(node) => node,
+            Err(ErrorCode::NotFound) if is_last => fs.not_found(dir, &component)?,
+            Err(err) => return Err(err),
+        };
+
+        if fs.is_symlink(&node) && (!is_last || follow_last) {
+            follows += 1;
+            let target = if follows > MAX_SYMLINK_FOLLOWS {
+                Err(ErrorCode::FilesystemLoop)
+            } else {
+                fs.symlink_target(&node)
+            };
+            released.push(node);
+
+            let target = target?;
+            if target.starts_with('/') {
+                released.extend(dirs.drain(1..));
+            }
+            components.extend(target.split('/').rev().map(|c| c.to_owned()));
+            continue;
+        }
+
+        dirs.push(node);
+    }
+
+    Ok(())
+}
+
+// Absolute symlink targets are paths in the global namespace; only those
+// under @mount_point can be followed by the filesystem mounted there.
+pub fn local_target(mount_point: &str, target: String) -> Result<String, ErrorCode> {
+    if !target.starts_with('/') {
+        return Ok(target);
+    }
+    match target.strip_prefix(mount_point) {
+        Some("") => Ok("/".to_owned()),
+        Some(rest) if rest.starts_with('/') => Ok(rest.to_owned()),
+        _ => Err(ErrorCode::NotFound),
+    }
+}
@@ -0,0 +1,125 @@
+// Change notification (see WatchAddRequest): the driver reports changes
+// by path here, and the events are queued for the watching connections.
+
+use moto_runtime::rt_api::fs::*;
+use moto_sys::SysHandle;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+// Events beyond this are dropped, and WATCH_EVENT_OVERFLOW is queued instead.
+const MAX_QUEUED_EVENTS: usize = 256;
+
+#[derive(PartialEq, Eq)]
+pub struct Event {
+    pub wd: u32,
+    pub event: u16,
+    pub name: String,
+}
+
+struct Watcher {
+    handle: SysHandle, // Woken when events are queued.
+    watches: HashMap<u32, String>,
+    next_wd: u32,
+    events: VecDeque<Event>,
+}
+
+impl Watcher {
+    fn queue(&mut self, event: Event) {
+        // Writes come in bursts; one MODIFY for them is enough.
+        if self.events.back() == Some(&event) {
+            return;
+        }
+
+        if self.events.len() >= MAX_QUEUED_EVENTS {
+            if self.events.back().map(|e| e.event) != Some(WATCH_EVENT_OVERFLOW) {
+                self.events.push_back(Event {
+                    wd: 0,
+                    event: WATCH_EVENT_OVERFLOW,
+                    name: String::new(),
+                });
+            }
+            return;
+        }
+
+        self.events.push_back(event);
+        // The peer may have exited; its watcher is removed on disconnect.
+        let _ = moto_sys::SysCpu::wake(self.handle);
+    }
+}
+
+// Keyed by conn_id.
+static WATCHERS: std::sync::Mutex<BTreeMap<u64, Watcher>> = std::sync::Mutex::new(BTreeMap::new());
+
+pub fn add_watch(conn_id: u64, handle: SysHandle, path: &str) -> u32 {
+    let mut watchers = WATCHERS.lock().unwrap();
+    let watcher = watchers.entry(conn_id).or_insert_with(|| Watcher {
+        handle,
+        watches: HashMap::new(),
+        next_wd: 1,
+        events: VecDeque::new(),
+    });
+
+    let wd = watcher.next_wd;
+    watcher.next_wd += 1;
+    watcher.watches.insert(wd, path.to_owned());
+    wd
+}
+
+pub fn remove_watch(conn_id: u64, wd: u32) -> Result<(), moto_sys::ErrorCode> {
+    let mut watchers = WATCHERS.lock().unwrap();
+    let watcher = watchers
+        .get_mut(&conn_id)
+        .ok_or(moto_sys::ErrorCode::BadHandle)?;
+    watcher
+        .watches
+        .remove(&wd)
+        .ok_or(moto_sys::ErrorCode::BadHandle)?;
+    watcher.events.retain(|e| e.wd != wd);
+    Ok(())
+}
+
+pub fn remove_watcher(conn_id: u64) {
+    WATCHERS.lock().unwrap().remove(&conn_id);
+}
+
+pub fn next_event(conn_id: u64) -> Option<Event> {
+    WATCHERS
+        .lock()
+        .unwrap()
+        .get_mut(&conn_id)
+        .and_then(|watcher| watcher.events.pop_front())
+}
+
+// Reports @event to the watches on @path and on its parent directory.
+pub fn notify(path: &str, event: u16) {
+    let mut watchers = WATCHERS.lock().unwrap();
+    if watchers.is_empty() {
+        return;
+    }
+
+    let path = path.trim_end_matches('/');
+    let (parent, name) = match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => return,
+    };
+
+    for watcher in watchers.values_mut() {
+        let mut matches = vec![];
+        for (wd, watched) in &watcher.watches {
+            let watched = match watched.trim_end_matches('/') {
+                "" => "/",
+                watched => watched,
+            };
+            if watched == path {
+                matches.push((*wd, String::new()));
+            } else if watched == parent {
+                matches.push((*wd, name.to_owned()));
+            }
+        }
+        // Keep the order of events stable for clients.
+        matches.sort_by_key(|(wd, _)| *wd);
+        for (wd, name) in matches {
+            watcher.queue(Event { wd, event, name });
+        }
+    }
+}
@@ -3,6 +3,7 @@
 #![feature(core_intrinsics)]
 #![feature(io_error_more)]
 
+mod display;
 mod fs;
 mod logger;
 mod net;
@@ -53,6 +54,7 @@ pub extern "C" fn moturus_log_panics_to_kernel() -> bool {
 
 fn main() {
     runtime::start();
+    display::start();
 
     let mut cmd = std::process::Command::new("/sys/sys-init");
 
@@ -76,6 +76,9 @@ impl<'de> Deserialize<'de> for MacAddress {
 pub(super) struct IpRoute {
     pub ip_network: IpNetwork,
     pub gateway: IpAddr,
+    // Lower is preferred, among routes with equally long prefixes.
+    #[serde(default)]
+    pub metric: u32,
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -83,6 +86,19 @@ pub(super) struct DeviceCfg {
     pub mac: MacAddress,
     pub cidrs: Vec<IpNetwork>,
     pub routes: Vec<IpRoute>,
+    // Whether to autoconfigure IPv6 addresses and the default route (SLAAC).
+    #[serde(default = "default_true")]
+    pub slaac: bool,
+    // The IP MTU; the device's own if not set.
+    #[serde(default)]
+    pub mtu: Option<u16>,
+    // Whether the link is up at startup; `ifconfig` can change this at runtime.
+    #[serde(default = "default_true")]
+    pub up: bool,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl DeviceCfg {
@@ -92,15 +108,51 @@ impl DeviceCfg {
             mac: MacAddress::from_str(mac).unwrap(),
             cidrs: vec![],
             routes: vec![],
+            slaac: false,
+            mtu: None,
+            up: true,
         }
     }
 }
 
+// TCP tunables (the [tcp] section); unset values keep sys-io's defaults.
+#[derive(Clone, Default, Deserialize, Debug)]
+pub(super) struct TcpCfg {
+    // Default socket buffer sizes, in bytes; listeners may override them.
+    // smoltcp negotiates window scaling when the receive buffer exceeds 64K.
+    pub rx_buffer: Option<usize>,
+    pub tx_buffer: Option<usize>,
+    pub congestion_control: Option<CongestionControl>,
+}
+
+#[derive(Clone, Copy, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum CongestionControl {
+    None,
+    Reno,
+    Cubic,
+}
+
+// NTP time sync (the [ntp] section); off unless servers are listed.
+#[derive(Clone, Default, Deserialize, Debug)]
+pub(super) struct NtpCfg {
+    pub servers: Vec<IpAddr>,
+    // Seconds between queries; at least 16.
+    pub poll_interval: Option<u64>,
+}
+
 #[derive(Deserialize, Debug)]
 pub(super) struct NetConfig {
-    #[allow(unused)]
+    // Whether to create the loopback device (127.0.0.1/8 and ::1).
+    #[serde(default = "default_true")]
     pub loopback: bool,
     pub devices: BTreeMap<String, DeviceCfg>,
+    #[serde(default)]
+    pub nameservers: Vec<IpAddr>, // Upstream DNS servers.
+    #[serde(default)]
+    pub tcp: TcpCfg,
+    #[serde(default)]
+    pub ntp: NtpCfg,
 }
 
 pub(super) fn load() -> Result<NetConfig, ErrorCode> {
@@ -123,22 +175,3 @@ pub(super) fn load() -> Result<NetConfig, ErrorCode> {
         ErrorCode::InvalidArgument
     })
 }
-
-// Find the device name and the local IP address to route to dst.
-impl NetConfig {
-    pub(super) fn find_route(&self, dst: &IpAddr) -> Option<(String, IpAddr)> {
-        for (dev_name, dev_cfg) in &self.devices {
-            for route in &dev_cfg.routes {
-                if route.ip_network.contains(*dst) {
-                    for cidr in &dev_cfg.cidrs {
-                        if cidr.contains(*dst) && route.ip_network.contains(cidr.ip()) {
-                            return Some((dev_name.to_owned(), cidr.ip()));
-                        }
-                    }
-                }
-            }
-        }
-
-        None
-    }
-}
@@ -0,0 +1,419 @@
+// A stub DNS resolver: lookups send A and AAAA queries to the configured
+// nameservers, retransmitting with backoff and moving on to the next server
+// on failure; answers (including NXDOMAIN) are cached for their TTL.
+//
+// The resolver does no I/O itself: NetSys sends the queries it produces
+// through a UDP socket and feeds it the responses.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use moto_ipc::io_channel;
+use moto_runtime::rt_api::net::{DNS_MAX_ADDRS, DNS_MAX_NAME_LEN};
+use moto_sys::ErrorCode;
+
+pub(super) const DNS_PORT: u16 = 53;
+
+const RETRANSMIT_DELAY: Duration = Duration::from_secs(1);
+const ATTEMPTS_PER_SERVER: u32 = 2;
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MIN_TTL: Duration = Duration::from_secs(5);
+const MAX_TTL: Duration = Duration::from_secs(3600);
+const NEGATIVE_TTL: Duration = Duration::from_secs(30);
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NXDOMAIN: u16 = 3;
+
+// A CMD_DNS_LOOKUP request waiting for its lookup to complete.
+pub(super) struct Waiter {
+    pub msg: io_channel::Msg,
+    pub conn: Rc<io_channel::ServerConnection>,
+}
+
+pub(super) type LookupResult = Result<Vec<IpAddr>, ErrorCode>;
+
+struct CacheEntry {
+    result: LookupResult,
+    expires: Instant,
+}
+
+struct Lookup {
+    waiters: Vec<Waiter>,
+
+    // Transaction IDs of the A and AAAA queries that haven't been answered yet.
+    pending: Vec<u16>,
+    addrs_v4: Vec<Ipv4Addr>,
+    addrs_v6: Vec<Ipv6Addr>,
+    ttl: u32,
+    not_found: bool,
+
+    server_idx: usize,
+    attempt: u32,
+    retransmit_at: Instant,
+    deadline: Instant,
+}
+
+enum Answer {
+    Addrs(Vec<IpAddr>, u32),
+    NotFound,
+    ServerFailure,
+}
+
+pub(super) struct Resolver {
+    servers: Vec<IpAddr>,
+
+    cache: HashMap<String, CacheEntry>,
+    lookups: HashMap<String, Lookup>,
+    queries: HashMap<u16, (String, u16)>, // Transaction ID -> (name, type).
+    next_txid: u16,
+
+    outgoing: Vec<(IpAddr, Vec<u8>)>,
+    completed: Vec<(Vec<Waiter>, LookupResult)>,
+}
+
+impl Resolver {
+    pub fn new(servers: Vec<IpAddr>) -> Self {
+        Self {
+            servers,
+            cache: HashMap::new(),
+            lookups: HashMap::new(),
+            queries: HashMap::new(),
+            // Not security, but avoids reusing IDs across sys-io restarts.
+            next_txid: moto_sys::time::Instant::now().as_u64() as u16,
+            outgoing: Vec::new(),
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn servers(&self) -> &[IpAddr] {
+        &self.servers
+    }
+
+    // Returns the waiter back if the lookup completed immediately, e.g. from the cache.
+    pub fn lookup(&mut self, name: &str, waiter: Waiter) -> Option<(Waiter, LookupResult)> {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        if !is_valid_name(&name) {
+            return Some((waiter, Err(ErrorCode::InvalidArgument)));
+        }
+
+        let now = Instant::now();
+        if let Some(entry) = self.cache.get(&name) {
+            if entry.expires > now {
+                return Some((waiter, entry.result.clone()));
+            }
+            self.cache.remove(&name);
+        }
+
+        if let Some(lookup) = self.lookups.get_mut(&name) {
+            lookup.waiters.push(waiter);
+            return None;
+        }
+
+        if self.servers.is_empty() {
+            return Some((waiter, Err(ErrorCode::NotFound)));
+        }
+
+        let mut lookup = Lookup {
+            waiters: vec![waiter],
+            pending: Vec::with_capacity(2),
+            addrs_v4: Vec::new(),
+            addrs_v6: Vec::new(),
+            ttl: u32::MAX,
+            not_found: false,
+            server_idx: 0,
+            attempt: 0,
+            retransmit_at: now,
+            deadline: now + LOOKUP_TIMEOUT,
+        };
+        for qtype in [TYPE_A, TYPE_AAAA] {
+            let txid = self.new_txid();
+            self.queries.insert(txid, (name.clone(), qtype));
+            lookup.pending.push(txid);
+        }
+        self.send_queries(&name, &mut lookup, now);
+        self.lookups.insert(name, lookup);
+
+        None
+    }
+
+    pub fn on_response(&mut self, src: IpAddr, packet: &[u8]) {
+        if !self.servers.contains(&src) {
+            return;
+        }
+        let Some((txid, answer)) = decode_response(packet) else {
+            return;
+        };
+        let Some((name, _)) = self.queries.get(&txid) else {
+            return; // A late answer to an already completed query.
+        };
+        let name = name.clone();
+        let lookup = self.lookups.get_mut(&name).unwrap();
+
+        match answer {
+            Answer::Addrs(addrs, ttl) => {
+                for addr in addrs {
+                    match addr {
+                        IpAddr::V4(addr) => lookup.addrs_v4.push(addr),
+                        IpAddr::V6(addr) => lookup.addrs_v6.push(addr),
+                    }
+                }
+                lookup.ttl = lookup.ttl.min(ttl);
+            }
+            Answer::NotFound => lookup.not_found = true,
+            Answer::ServerFailure => {
+                // Ask the next server right away.
+                lookup.attempt = ATTEMPTS_PER_SERVER;
+                lookup.retransmit_at = Instant::now();
+                return;
+            }
+        }
+
+        self.queries.remove(&txid);
+        lookup.pending.retain(|id| *id != txid);
+        if lookup.pending.is_empty() {
+            self.complete(&name);
+        }
+    }
+
+    // Retransmits queries and times lookups out.
+    pub fn poll(&mut self) {
+        let now = Instant::now();
+        let names: Vec<String> = self
+            .lookups
+            .iter()
+            .filter(|(_, lookup)| lookup.retransmit_at <= now || lookup.deadline <= now)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in names {
+            if self.lookups.get(&name).unwrap().deadline <= now {
+                self.complete(&name);
+                continue;
+            }
+
+            let mut lookup = self.lookups.remove(&name).unwrap();
+            if lookup.attempt >= ATTEMPTS_PER_SERVER {
+                lookup.attempt = 0;
+                lookup.server_idx = (lookup.server_idx + 1) % self.servers.len();
+            }
+            self.send_queries(&name, &mut lookup, now);
+            self.lookups.insert(name, lookup);
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.lookups
+            .values()
+            .map(|lookup| lookup.retransmit_at.min(lookup.deadline))
+            .min()
+    }
+
+    // Queries to send, as (server, packet).
+    pub fn take_outgoing(&mut self) -> Vec<(IpAddr, Vec<u8>)> {
+        core::mem::take(&mut self.outgoing)
+    }
+
+    pub fn take_completed(&mut self) -> Vec<(Vec<Waiter>, LookupResult)> {
+        core::mem::take(&mut self.completed)
+    }
+
+    fn new_txid(&mut self) -> u16 {
+        loop {
+            self.next_txid = self.next_txid.wrapping_add(1);
+            if !self.queries.contains_key(&self.next_txid) {
+                return self.next_txid;
+            }
+        }
+    }
+
+    fn send_queries(&mut self, name: &str, lookup: &mut Lookup, now: Instant) {
+        let server = self.servers[lookup.server_idx];
+        for txid in &lookup.pending {
+            let (_, qtype) = self.queries.get(txid).unwrap();
+            self.outgoing
+                .push((server, encode_query(*txid, name, *qtype)));
+        }
+
+        lookup.retransmit_at = now + RETRANSMIT_DELAY * (1 << lookup.attempt);
+        lookup.attempt += 1;
+    }
+
+    fn complete(&mut self, name: &str) {
+        let lookup = self.lookups.remove(name).unwrap();
+        for txid in &lookup.pending {
+            self.queries.remove(txid);
+        }
+
+        let mut addrs: Vec<IpAddr> = lookup
+            .addrs_v4
+            .iter()
+            .map(|addr| IpAddr::V4(*addr))
+            .chain(lookup.addrs_v6.iter().map(|addr| IpAddr::V6(*addr)))
+            .collect();
+        addrs.dedup();
+        addrs.truncate(DNS_MAX_ADDRS);
+
+        let (result, ttl) = if !addrs.is_empty() {
+            let ttl = Duration::from_secs(lookup.ttl as u64).clamp(MIN_TTL, MAX_TTL);
+            (Ok(addrs), Some(ttl))
+        } else if lookup.not_found {
+            (Err(ErrorCode::NotFound), Some(NEGATIVE_TTL))
+        } else if lookup.pending.is_empty() {
+            // The name exists, but has no addresses.
+            (Err(ErrorCode::NotFound), Some(NEGATIVE_TTL))
+        } else {
+            // Timed out: don't cache, the servers may come back.
+            (Err(ErrorCode::TimedOut), None)
+        };
+
+        if let Some(ttl) = ttl {
+            self.insert_into_cache(name, result.clone(), ttl);
+        }
+        self.completed.push((lookup.waiters, result));
+    }
+
+    fn insert_into_cache(&mut self, name: &str, result: LookupResult, ttl: Duration) {
+        let now = Instant::now();
+        if self.cache.len() >= MAX_CACHE_ENTRIES {
+            self.cache.retain(|_, entry| entry.expires > now);
+        }
+        if self.cache.len() >= MAX_CACHE_ENTRIES {
+            // Still full: drop the entry closest to expiring.
+            let victim = self
+                .cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires)
+                .map(|(name, _)| name.clone())
+                .unwrap();
+            self.cache.remove(&victim);
+        }
+
+        self.cache.insert(
+            name.to_owned(),
+            CacheEntry {
+                result,
+                expires: now + ttl,
+            },
+        );
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= DNS_MAX_NAME_LEN
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+}
+
+// The name must be valid (see is_valid_name()).
+fn encode_query(txid: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(12 + name.len() + 2 + 4);
+
+    packet.extend_from_slice(&txid.to_be_bytes());
+    packet.extend_from_slice(&0x0100_u16.to_be_bytes()); // Recursion desired.
+    packet.extend_from_slice(&1_u16.to_be_bytes()); // One question.
+    packet.extend_from_slice(&[0; 6]); // No answer, authority, or additional records.
+
+    for label in name.split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    packet
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        packet.get(pos..pos + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(packet: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        packet.get(pos..pos + 4)?.try_into().unwrap(),
+    ))
+}
+
+// Returns the position after the (possibly compressed) name at pos.
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            _ if len & 0xC0 == 0xC0 => return Some(pos + 2),
+            _ if len & 0xC0 == 0 => pos += 1 + len as usize,
+            _ => return None,
+        }
+    }
+}
+
+fn decode_response(packet: &[u8]) -> Option<(u16, Answer)> {
+    let txid = read_u16(packet, 0)?;
+    let flags = read_u16(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return None; // Not a response.
+    }
+
+    match flags & 0xF {
+        0 => {}
+        RCODE_NXDOMAIN => return Some((txid, Answer::NotFound)),
+        _ => return Some((txid, Answer::ServerFailure)),
+    }
+
+    let questions = read_u16(packet, 4)?;
+    let answers = read_u16(packet, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(packet, pos)? + 4;
+    }
+
+    // CNAME records are skipped: recursive servers include the records
+    // the alias chain ends with.
+    let mut addrs = Vec::new();
+    let mut ttl = u32::MAX;
+    for _ in 0..answers {
+        pos = skip_name(packet, pos)?;
+        let rtype = read_u16(packet, pos)?;
+        let class = read_u16(packet, pos + 2)?;
+        let rttl = read_u32(packet, pos + 4)?;
+        let len = read_u16(packet, pos + 8)? as usize;
+        let data = packet.get(pos + 10..pos + 10 + len)?;
+        pos += 10 + len;
+
+        if class != CLASS_IN {
+            continue;
+        }
+        match (rtype, len) {
+            (TYPE_A, 4) => {
+                let octets: [u8; 4] = data.try_into().unwrap();
+                addrs.push(IpAddr::V4(octets.into()));
+            }
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().unwrap();
+                addrs.push(IpAddr::V6(octets.into()));
+            }
+            _ => continue,
+        }
+        ttl = ttl.min(rttl);
+    }
+
+    Some((txid, Answer::Addrs(addrs, ttl)))
+}
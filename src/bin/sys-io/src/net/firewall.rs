@@ -0,0 +1,268 @@
+// The packet filter: two stateless rule chains, one checked as packets arrive
+// on a device (before smoltcp sees them), the other as smoltcp sends them.
+// The admin manages the rules at runtime (see moto_sys_io::netcfg and `mfw`);
+// until then, everything passes without even being parsed.
+//
+// Devices and NetSys share the Firewall via Rc<RefCell<>>: all networking
+// happens on the IO thread.
+
+use std::cell::RefCell;
+use std::net::IpAddr;
+use std::rc::Rc;
+
+use ipnetwork::IpNetwork;
+use moto_sys::ErrorCode;
+use moto_sys_io::netcfg::*;
+use smoltcp::wire::{EthernetFrame, EthernetProtocol, Ipv4Packet, Ipv6Packet};
+
+pub(super) const MAX_RULES_PER_CHAIN: usize = 256;
+
+const NUM_CHAINS: usize = 2;
+
+// The parts of a packet rules look at.
+struct PacketInfo {
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: u8,
+    ports: Option<(u16, u16)>, // (src, dst), for TCP and UDP; not in non-first fragments.
+}
+
+impl PacketInfo {
+    // None if this is not an IP packet (e.g. ARP), or a malformed one, which
+    // smoltcp drops anyway.
+    fn parse(packet: &[u8], ethernet: bool) -> Option<Self> {
+        let (is_ipv6, ip_packet) = if ethernet {
+            let frame = EthernetFrame::new_checked(packet).ok()?;
+            match frame.ethertype() {
+                EthernetProtocol::Ipv4 => (false, &packet[EthernetFrame::<&[u8]>::header_len()..]),
+                EthernetProtocol::Ipv6 => (true, &packet[EthernetFrame::<&[u8]>::header_len()..]),
+                _ => return None,
+            }
+        } else {
+            (packet.first()? >> 4 == 6, packet)
+        };
+
+        let (src, dst, protocol, first_fragment, payload) = if is_ipv6 {
+            let ip = Ipv6Packet::new_checked(ip_packet).ok()?;
+            (
+                IpAddr::V6(ip.src_addr().into()),
+                IpAddr::V6(ip.dst_addr().into()),
+                u8::from(ip.next_header()),
+                true, // Extension headers, incl. fragment ones, are not looked into.
+                ip.payload(),
+            )
+        } else {
+            let ip = Ipv4Packet::new_checked(ip_packet).ok()?;
+            (
+                IpAddr::V4(ip.src_addr().into()),
+                IpAddr::V4(ip.dst_addr().into()),
+                u8::from(ip.next_header()),
+                ip.frag_offset() == 0,
+                ip.payload(),
+            )
+        };
+
+        // TCP and UDP headers both start with the source and destination ports.
+        let ports = match protocol {
+            FW_PROTO_TCP | FW_PROTO_UDP if first_fragment && payload.len() >= 4 => Some((
+                u16::from_be_bytes([payload[0], payload[1]]),
+                u16::from_be_bytes([payload[2], payload[3]]),
+            )),
+            _ => None,
+        };
+
+        Some(Self {
+            src,
+            dst,
+            protocol,
+            ports,
+        })
+    }
+}
+
+struct Rule {
+    info: FwRuleV1,
+    src: Option<IpNetwork>,
+    dst: Option<IpNetwork>,
+}
+
+impl Rule {
+    fn new(info: FwRuleV1) -> Result<Self, ErrorCode> {
+        if info.action != FW_ALLOW && info.action != FW_DENY {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if (info.match_flags & FW_MATCH_IFACE) != 0 && info.ifname().is_none() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let network = |cidr: Option<IpCidrV1>| -> Result<Option<IpNetwork>, ErrorCode> {
+            cidr.map(|cidr| IpNetwork::new(cidr.addr(), cidr.prefix_len))
+                .transpose()
+                .map_err(|_| ErrorCode::InvalidArgument)
+        };
+        let src = network(info.src())?;
+        let dst = network(info.dst())?;
+        if let (Some(src), Some(dst)) = (src, dst) {
+            if src.is_ipv4() != dst.is_ipv4() {
+                return Err(ErrorCode::InvalidArgument);
+            }
+        }
+        for (first, last) in [info.src_ports(), info.dst_ports()].into_iter().flatten() {
+            if first > last {
+                return Err(ErrorCode::InvalidArgument);
+            }
+        }
+
+        let mut info = info;
+        info.packets = 0;
+        info.bytes = 0;
+        Ok(Self { info, src, dst })
+    }
+
+    fn matches(&self, ifname: &str, packet: &PacketInfo) -> bool {
+        let in_range = |range: Option<(u16, u16)>, port: Option<u16>| match range {
+            None => true,
+            Some((first, last)) => port.is_some_and(|port| (first..=last).contains(&port)),
+        };
+
+        self.info.ifname().map_or(true, |name| name == ifname)
+            && match self.info.protocol {
+                FW_PROTO_ANY => true,
+                FW_PROTO_ICMP => {
+                    packet.protocol == FW_PROTO_ICMP || packet.protocol == FW_PROTO_ICMPV6
+                }
+                protocol => packet.protocol == protocol,
+            }
+            && self.src.map_or(true, |net| net.contains(packet.src))
+            && self.dst.map_or(true, |net| net.contains(packet.dst))
+            && in_range(self.info.src_ports(), packet.ports.map(|p| p.0))
+            && in_range(self.info.dst_ports(), packet.ports.map(|p| p.1))
+    }
+}
+
+pub(super) struct Firewall {
+    chains: [Vec<Rule>; NUM_CHAINS], // Indexed by FW_CHAIN_*.
+    policies: [u8; NUM_CHAINS],
+}
+
+impl Firewall {
+    pub fn new() -> Self {
+        Self {
+            chains: [Vec::new(), Vec::new()],
+            policies: [FW_ALLOW; NUM_CHAINS],
+        }
+    }
+
+    fn chain_idx(chain: u8) -> Result<usize, ErrorCode> {
+        if (chain as usize) < NUM_CHAINS {
+            Ok(chain as usize)
+        } else {
+            Err(ErrorCode::InvalidArgument)
+        }
+    }
+
+    // Whether anything in the chain can be denied.
+    pub fn filters(&self, chain: u8) -> bool {
+        let idx = chain as usize;
+        !self.chains[idx].is_empty() || self.policies[idx] != FW_ALLOW
+    }
+
+    // `ethernet` is false for devices that carry bare IP packets.
+    pub fn allows(&mut self, chain: u8, ifname: &str, packet: &[u8], ethernet: bool) -> bool {
+        if !self.filters(chain) {
+            return true;
+        }
+        let Some(info) = PacketInfo::parse(packet, ethernet) else {
+            return true;
+        };
+
+        let idx = chain as usize;
+        for rule in &mut self.chains[idx] {
+            if rule.matches(ifname, &info) {
+                rule.info.packets += 1;
+                rule.info.bytes += packet.len() as u64;
+                return rule.info.action == FW_ALLOW;
+            }
+        }
+        self.policies[idx] == FW_ALLOW
+    }
+
+    // Up to max_rules rules, starting with the start_idx-th one (input rules first).
+    pub fn list(&self, start_idx: usize, max_rules: usize) -> FwRules {
+        FwRules {
+            input_policy: self.policies[FW_CHAIN_INPUT as usize],
+            output_policy: self.policies[FW_CHAIN_OUTPUT as usize],
+            rules: self
+                .chains
+                .iter()
+                .flatten()
+                .skip(start_idx)
+                .take(max_rules)
+                .map(|rule| rule.info)
+                .collect(),
+        }
+    }
+
+    // Inserts the rule at position in its chain, or appends it if position is past the end.
+    pub fn add(&mut self, rule: FwRuleV1, position: usize) -> Result<(), ErrorCode> {
+        let idx = Self::chain_idx(rule.chain)?;
+        let rule = Rule::new(rule)?;
+        let chain = &mut self.chains[idx];
+        if chain.len() >= MAX_RULES_PER_CHAIN {
+            return Err(ErrorCode::OutOfMemory);
+        }
+        log::info!("firewall: added {:?}", rule.info);
+        chain.insert(position.min(chain.len()), rule);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, chain: u8, position: usize) -> Result<(), ErrorCode> {
+        let chain = &mut self.chains[Self::chain_idx(chain)?];
+        if position >= chain.len() {
+            return Err(ErrorCode::NotFound);
+        }
+        let rule = chain.remove(position);
+        log::info!("firewall: removed {:?}", rule.info);
+        Ok(())
+    }
+
+    pub fn set_policy(&mut self, chain: u8, action: u8) -> Result<(), ErrorCode> {
+        let idx = Self::chain_idx(chain)?;
+        if action != FW_ALLOW && action != FW_DENY {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.policies[idx] = action;
+        Ok(())
+    }
+
+    pub fn flush(&mut self, chain: u8) -> Result<(), ErrorCode> {
+        self.chains[Self::chain_idx(chain)?].clear();
+        Ok(())
+    }
+}
+
+// What a device checks its packets with.
+pub(super) struct PacketFilter {
+    firewall: Rc<RefCell<Firewall>>,
+    ifname: String,
+    ethernet: bool,
+}
+
+impl PacketFilter {
+    pub fn new(firewall: &Rc<RefCell<Firewall>>, ifname: &str, ethernet: bool) -> Self {
+        Self {
+            firewall: firewall.clone(),
+            ifname: ifname.to_owned(),
+            ethernet,
+        }
+    }
+
+    pub fn filters(&self, chain: u8) -> bool {
+        self.firewall.borrow().filters(chain)
+    }
+
+    pub fn allows(&self, chain: u8, packet: &[u8]) -> bool {
+        self.firewall
+            .borrow_mut()
+            .allows(chain, &self.ifname, packet, self.ethernet)
+    }
+}
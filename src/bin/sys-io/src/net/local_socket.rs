@@ -0,0 +1,699 @@
+// Local (Unix-domain) sockets: stream and datagram sockets between processes
+// on this machine, named by absolute paths or by abstract names (starting with
+// a zero byte). Data never touches smoltcp: it is copied from the sender's IO
+// pages into the receiver's, and a sender's TX completes when its data reaches
+// the receiver, so a slow reader blocks writers.
+//
+// Path names live here, not in the filesystem, but they are resolved against
+// the process's filesystem root, so processes in different namespaces don't see
+// each other's sockets. Abstract names are global.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+use moto_ipc::io_channel;
+use moto_runtime::rt_api::{self, net::LocalCreds};
+use moto_sys::{ErrorCode, SysHandle};
+
+use crate::runtime::PendingCompletion;
+
+// Connections not yet accepted, per listener.
+const MAX_BACKLOG: usize = 128;
+
+const NOBODY: u32 = u32::MAX;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Stream,
+    Datagram,
+}
+
+// A TX waiting for the receiver to have room.
+struct Delivery {
+    // The TX to complete once delivered.
+    tx: Option<(io_channel::Msg, Rc<io_channel::ServerConnection>)>,
+    // None marks the end of stream.
+    data: Option<(io_channel::IoPage, usize)>,
+    src_name: Vec<u8>, // Empty for streams.
+    creds: LocalCreds,
+}
+
+struct LocalListener {
+    conn: Rc<io_channel::ServerConnection>,
+    pid: u64,
+    key: Vec<u8>,
+    name: Vec<u8>,
+    // Connect requests waiting for accept(), and the other way around.
+    backlog: VecDeque<(io_channel::Msg, Rc<io_channel::ServerConnection>)>,
+    accepts: VecDeque<(io_channel::Msg, Rc<io_channel::ServerConnection>)>,
+}
+
+struct LocalSocket {
+    kind: Kind,
+    conn: Rc<io_channel::ServerConnection>,
+    subchannel_mask: u64,
+    creds: LocalCreds,
+
+    key: Option<Vec<u8>>, // Bound datagram sockets only.
+    name: Vec<u8>,
+
+    // The other end of a stream; None once it is gone.
+    peer: Option<u64>,
+    write_shut: bool,
+    read_shut: bool,
+
+    rx_queue: VecDeque<Delivery>,
+    rx_in_flight: usize, // Not yet acked by the client.
+}
+
+pub(super) struct LocalSockets {
+    next_id: u64,
+    names: HashMap<Vec<u8>, u64>, // Listeners and bound datagram sockets.
+    listeners: HashMap<u64, LocalListener>,
+    sockets: HashMap<u64, LocalSocket>,
+    conn_ids: HashMap<SysHandle, HashSet<u64>>, // Listeners and sockets.
+
+    // Sockets that could not get an RX page.
+    pending_rx: VecDeque<u64>,
+    pending_completions: VecDeque<PendingCompletion>,
+}
+
+fn creds_of(conn: &io_channel::ServerConnection) -> LocalCreds {
+    let pid = moto_sys::SysObj::get_pid(conn.wait_handle()).unwrap_or(0);
+    let (uid, gid) = moto_sys::SysRay::query_credentials(pid).unwrap_or((NOBODY, NOBODY));
+    LocalCreds { pid, uid, gid }
+}
+
+// The key names are registered under: abstract names as they are, paths
+// prefixed with the process's filesystem root. Paths are refused if the
+// root can't be queried, rather than treated as in the root namespace.
+fn name_key(creds: &LocalCreds, name: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+    match name.first() {
+        Some(0) => Ok(name.to_vec()),
+        Some(b'/') => {
+            let fs_root = moto_sys::SysRay::query_namespace_v1(creds.pid)
+                .map(|ns| ns.fs_root().trim_end_matches('/').to_owned())
+                .map_err(|_| ErrorCode::NotAllowed)?;
+            let mut key = fs_root.into_bytes();
+            key.extend_from_slice(name);
+            Ok(key)
+        }
+        _ => Err(ErrorCode::InvalidFilename),
+    }
+}
+
+// Reads the name that follows `offset` bytes of data in the request's page.
+fn name_from_page(
+    conn: &io_channel::ServerConnection,
+    msg: &io_channel::Msg,
+    offset: usize,
+) -> Result<(io_channel::IoPage, Vec<u8>), ErrorCode> {
+    let page = conn.get_page(msg.payload.shared_pages()[0])?;
+    let name_len = msg.payload.args_16()[1] as usize;
+    if name_len > rt_api::net::LOCAL_NAME_MAX || offset + name_len > io_channel::PAGE_SIZE {
+        return Err(ErrorCode::InvalidArgument);
+    }
+    let name = page.bytes()[offset..(offset + name_len)].to_vec();
+    Ok((page, name))
+}
+
+fn error_msg(mut msg: io_channel::Msg, err: ErrorCode) -> Option<io_channel::Msg> {
+    msg.status = err.into();
+    Some(msg)
+}
+
+impl LocalSockets {
+    pub fn new() -> Self {
+        Self {
+            next_id: 1,
+            names: HashMap::new(),
+            listeners: HashMap::new(),
+            sockets: HashMap::new(),
+            conn_ids: HashMap::new(),
+            pending_rx: VecDeque::new(),
+            pending_completions: VecDeque::new(),
+        }
+    }
+
+    pub fn pop_completion(&mut self) -> Option<PendingCompletion> {
+        self.pending_completions.pop_front()
+    }
+
+    pub fn has_completions(&self) -> bool {
+        !self.pending_completions.is_empty()
+    }
+
+    fn new_id(&mut self, conn: &io_channel::ServerConnection) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.conn_ids
+            .entry(conn.wait_handle())
+            .or_default()
+            .insert(id);
+        id
+    }
+
+    fn complete(&mut self, msg: io_channel::Msg, conn: &io_channel::ServerConnection) {
+        self.pending_completions.push_back(PendingCompletion {
+            msg,
+            endpoint_handle: conn.wait_handle(),
+        });
+    }
+
+    // The socket `msg` refers to, if it belongs to `conn`.
+    fn socket_from_msg(
+        &mut self,
+        conn: &io_channel::ServerConnection,
+        msg: &io_channel::Msg,
+    ) -> Option<&mut LocalSocket> {
+        self.sockets
+            .get_mut(&msg.handle)
+            .filter(|socket| socket.conn.wait_handle() == conn.wait_handle())
+    }
+
+    // For CMD_SOCKET_TX_WAIT.
+    pub fn subchannel_mask(
+        &mut self,
+        conn: &io_channel::ServerConnection,
+        msg: &io_channel::Msg,
+    ) -> Option<u64> {
+        self.socket_from_msg(conn, msg)
+            .map(|socket| socket.subchannel_mask)
+    }
+
+    fn new_socket(
+        &mut self,
+        kind: Kind,
+        conn: &Rc<io_channel::ServerConnection>,
+        subchannel_idx: usize,
+        creds: LocalCreds,
+    ) -> u64 {
+        let id = self.new_id(conn);
+        self.sockets.insert(
+            id,
+            LocalSocket {
+                kind,
+                conn: conn.clone(),
+                subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+                creds,
+                key: None,
+                name: Vec::new(),
+                peer: None,
+                write_shut: false,
+                read_shut: false,
+                rx_queue: VecDeque::new(),
+                // Nothing is sent until the client has registered the socket and acked.
+                rx_in_flight: rt_api::net::LOCAL_RX_MAX_INFLIGHT,
+            },
+        );
+        id
+    }
+
+    pub fn bind(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        let subchannel_idx = sqe.flags as usize;
+        if subchannel_idx >= rt_api::net::IO_SUBCHANNELS {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+        let kind = sqe.payload.args_8()[4];
+        let name = match name_from_page(conn, &sqe, 0) {
+            Ok((_, name)) => name,
+            Err(err) => return error_msg(sqe, err),
+        };
+
+        let creds = creds_of(conn);
+        let key = if name.is_empty() && kind == rt_api::net::LOCAL_KIND_DATAGRAM {
+            None // Unbound.
+        } else {
+            match name_key(&creds, &name) {
+                Ok(key) => Some(key),
+                Err(err) => return error_msg(sqe, err),
+            }
+        };
+        if let Some(key) = &key {
+            if self.names.contains_key(key) {
+                return error_msg(sqe, ErrorCode::AlreadyInUse);
+            }
+        }
+
+        let id = match kind {
+            rt_api::net::LOCAL_KIND_LISTENER => {
+                let id = self.new_id(conn);
+                self.listeners.insert(
+                    id,
+                    LocalListener {
+                        conn: conn.clone(),
+                        pid: creds.pid,
+                        key: key.clone().unwrap(),
+                        name: name.clone(),
+                        backlog: VecDeque::new(),
+                        accepts: VecDeque::new(),
+                    },
+                );
+                id
+            }
+            rt_api::net::LOCAL_KIND_DATAGRAM => {
+                let id = self.new_socket(Kind::Datagram, conn, subchannel_idx, creds);
+                let socket = self.sockets.get_mut(&id).unwrap();
+                socket.key = key.clone();
+                socket.name = name.clone();
+                id
+            }
+            _ => return error_msg(sqe, ErrorCode::InvalidArgument),
+        };
+        if let Some(key) = key {
+            self.names.insert(key, id);
+        }
+
+        log::debug!(
+            "{}:{} local socket 0x{:x} bound to {:?}",
+            file!(),
+            line!(),
+            id,
+            String::from_utf8_lossy(&name)
+        );
+
+        sqe.handle = id;
+        sqe.status = ErrorCode::Ok.into();
+        Some(sqe)
+    }
+
+    pub fn pair(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        let subchannel_1 = sqe.flags as usize;
+        let subchannel_2 = sqe.payload.args_32()[0] as usize;
+        if subchannel_1 >= rt_api::net::IO_SUBCHANNELS
+            || subchannel_2 >= rt_api::net::IO_SUBCHANNELS
+        {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+        let kind = match sqe.payload.args_8()[4] {
+            rt_api::net::LOCAL_KIND_STREAM => Kind::Stream,
+            rt_api::net::LOCAL_KIND_DATAGRAM => Kind::Datagram,
+            _ => return error_msg(sqe, ErrorCode::InvalidArgument),
+        };
+
+        let creds = creds_of(conn);
+        let id_1 = self.new_socket(kind, conn, subchannel_1, creds);
+        let id_2 = self.new_socket(kind, conn, subchannel_2, creds);
+        self.sockets.get_mut(&id_1).unwrap().peer = Some(id_2);
+        self.sockets.get_mut(&id_2).unwrap().peer = Some(id_1);
+
+        sqe.handle = id_1;
+        sqe.payload.args_64_mut()[0] = id_2;
+        rt_api::net::put_local_creds(&mut sqe.payload, &creds);
+        sqe.status = ErrorCode::Ok.into();
+        Some(sqe)
+    }
+
+    pub fn connect(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        if sqe.flags as usize >= rt_api::net::IO_SUBCHANNELS {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+        let key = match name_from_page(conn, &sqe, 0)
+            .and_then(|(_, name)| name_key(&creds_of(conn), &name))
+        {
+            Ok(key) => key,
+            Err(err) => return error_msg(sqe, err),
+        };
+
+        let Some(listener_id) = self.names.get(&key).copied() else {
+            return error_msg(sqe, ErrorCode::NotFound);
+        };
+        let Some(listener) = self.listeners.get_mut(&listener_id) else {
+            // A datagram socket.
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        };
+
+        if let Some(accept) = listener.accepts.pop_front() {
+            self.connect_streams(listener_id, (sqe, conn.clone()), accept);
+            return None;
+        }
+        if listener.backlog.len() >= MAX_BACKLOG {
+            return error_msg(sqe, ErrorCode::NotReady);
+        }
+        listener.backlog.push_back((sqe, conn.clone()));
+        None
+    }
+
+    pub fn accept(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        if sqe.flags as usize >= rt_api::net::IO_SUBCHANNELS {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+        let listener_id = sqe.handle;
+        let Some(listener) = self.listeners.get_mut(&listener_id) else {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        };
+        // As with TCP, accepted streams may come through other channels of the process.
+        if listener.conn.wait_handle() != conn.wait_handle()
+            && moto_sys::SysObj::get_pid(conn.wait_handle()).ok() != Some(listener.pid)
+        {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+
+        if let Some(connect) = listener.backlog.pop_front() {
+            self.connect_streams(listener_id, connect, (sqe, conn.clone()));
+            return None;
+        }
+        listener.accepts.push_back((sqe, conn.clone()));
+        None
+    }
+
+    fn connect_streams(
+        &mut self,
+        listener_id: u64,
+        connect: (io_channel::Msg, Rc<io_channel::ServerConnection>),
+        accept: (io_channel::Msg, Rc<io_channel::ServerConnection>),
+    ) {
+        let (mut connect_sqe, connect_conn) = connect;
+        let (mut accept_sqe, accept_conn) = accept;
+        let client_creds = creds_of(&connect_conn);
+        let server_creds = creds_of(&accept_conn);
+
+        let client_id = self.new_socket(
+            Kind::Stream,
+            &connect_conn,
+            connect_sqe.flags as usize,
+            client_creds,
+        );
+        let server_id = self.new_socket(
+            Kind::Stream,
+            &accept_conn,
+            accept_sqe.flags as usize,
+            server_creds,
+        );
+        self.sockets.get_mut(&client_id).unwrap().peer = Some(server_id);
+        let server = self.sockets.get_mut(&server_id).unwrap();
+        server.peer = Some(client_id);
+        server.name = self.listeners.get(&listener_id).unwrap().name.clone();
+
+        connect_sqe.handle = client_id;
+        rt_api::net::put_local_creds(&mut connect_sqe.payload, &server_creds);
+        connect_sqe.status = ErrorCode::Ok.into();
+        self.complete(connect_sqe, &connect_conn);
+
+        accept_sqe.handle = server_id;
+        rt_api::net::put_local_creds(&mut accept_sqe.payload, &client_creds);
+        accept_sqe.status = ErrorCode::Ok.into();
+        self.complete(accept_sqe, &accept_conn);
+    }
+
+    pub fn tx(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        let len = sqe.flags as usize;
+        let (page, dest_name) = match name_from_page(conn, &sqe, len) {
+            Ok(res) => res,
+            Err(err) => return error_msg(sqe, err),
+        };
+        let Some(socket) = self.socket_from_msg(conn, &sqe) else {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        };
+        if socket.write_shut {
+            return error_msg(sqe, ErrorCode::UnexpectedEof);
+        }
+        let (kind, peer, creds) = (socket.kind, socket.peer, socket.creds);
+
+        // Don't trust the client's runtime to have checked the length.
+        let max_len = match kind {
+            Kind::Stream => io_channel::PAGE_SIZE,
+            Kind::Datagram => rt_api::net::LOCAL_MAX_DATAGRAM,
+        };
+        if len > max_len {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+
+        let (dest_id, src_name) = match kind {
+            Kind::Stream => match peer {
+                Some(peer) => (peer, Vec::new()),
+                None => return error_msg(sqe, ErrorCode::UnexpectedEof), // Broken pipe.
+            },
+            Kind::Datagram => {
+                let dest_id = if dest_name.is_empty() {
+                    peer // A socket pair.
+                } else {
+                    name_key(&creds, &dest_name)
+                        .ok()
+                        .and_then(|key| self.names.get(&key).copied())
+                };
+                match dest_id.filter(|id| {
+                    self.sockets
+                        .get(id)
+                        .is_some_and(|dest| dest.kind == Kind::Datagram)
+                }) {
+                    Some(id) => (id, self.sockets.get(&sqe.handle).unwrap().name.clone()),
+                    None => return error_msg(sqe, ErrorCode::NotFound),
+                }
+            }
+        };
+        // do_rx() puts the sender's name after the data.
+        if len + src_name.len() > io_channel::PAGE_SIZE {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+
+        let dest = self.sockets.get_mut(&dest_id).unwrap();
+        if dest.read_shut {
+            // Nobody will read this.
+            let mut sqe = sqe;
+            sqe.status = ErrorCode::Ok.into();
+            return Some(sqe);
+        }
+        dest.rx_queue.push_back(Delivery {
+            tx: Some((sqe, conn.clone())),
+            data: Some((page, len)),
+            src_name,
+            creds,
+        });
+        self.do_rx(dest_id);
+        None
+    }
+
+    // Moves deliveries into the socket's client, while there is room.
+    fn do_rx(&mut self, socket_id: u64) {
+        let Some(socket) = self.sockets.get_mut(&socket_id) else {
+            return;
+        };
+
+        while socket.rx_in_flight < rt_api::net::LOCAL_RX_MAX_INFLIGHT {
+            let Some(delivery) = socket.rx_queue.front() else {
+                break;
+            };
+
+            let msg = match &delivery.data {
+                Some((tx_page, len)) => {
+                    let page = match socket.conn.alloc_page(socket.subchannel_mask) {
+                        Ok(page) => page,
+                        Err(_) => {
+                            if !self.pending_rx.contains(&socket_id) {
+                                self.pending_rx.push_back(socket_id);
+                            }
+                            break;
+                        }
+                    };
+                    let name_len = delivery.src_name.len();
+                    page.bytes_mut()[..*len].copy_from_slice(&tx_page.bytes()[..*len]);
+                    page.bytes_mut()[*len..(*len + name_len)].copy_from_slice(&delivery.src_name);
+                    rt_api::net::local_socket_data_msg(
+                        rt_api::net::CMD_LOCAL_SOCKET_RX,
+                        socket_id,
+                        Some(page),
+                        *len,
+                        name_len,
+                    )
+                }
+                None => rt_api::net::local_socket_data_msg(
+                    rt_api::net::CMD_LOCAL_SOCKET_RX,
+                    socket_id,
+                    None,
+                    0,
+                    0,
+                ),
+            };
+            let mut msg = msg;
+            rt_api::net::put_local_creds(&mut msg.payload, &delivery.creds);
+            msg.status = ErrorCode::Ok.into();
+
+            let delivery = socket.rx_queue.pop_front().unwrap();
+            socket.rx_in_flight += 1;
+            self.pending_completions.push_back(PendingCompletion {
+                msg,
+                endpoint_handle: socket.conn.wait_handle(),
+            });
+            if let Some((mut tx_sqe, tx_conn)) = delivery.tx {
+                tx_sqe.status = ErrorCode::Ok.into();
+                self.pending_completions.push_back(PendingCompletion {
+                    msg: tx_sqe,
+                    endpoint_handle: tx_conn.wait_handle(),
+                });
+            }
+        }
+    }
+
+    pub fn rx_ack(&mut self, conn: &Rc<io_channel::ServerConnection>, msg: io_channel::Msg) {
+        let acked = msg.payload.args_32()[0] as usize;
+        let Some(socket) = self.socket_from_msg(conn, &msg) else {
+            return;
+        };
+        socket.rx_in_flight = socket.rx_in_flight.saturating_sub(acked);
+        self.do_rx(msg.handle);
+    }
+
+    pub fn shutdown(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        let how = sqe.payload.args_64()[0];
+        let Some(socket) = self.socket_from_msg(conn, &sqe) else {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        };
+
+        if how & rt_api::net::LOCAL_SHUT_RD != 0 {
+            socket.read_shut = true;
+            // Let blocked writers go.
+            let dropped: Vec<Delivery> = socket.rx_queue.drain(..).collect();
+            self.complete_dropped(dropped);
+        }
+
+        let socket = self.sockets.get_mut(&sqe.handle).unwrap();
+        if how & rt_api::net::LOCAL_SHUT_WR != 0 && !socket.write_shut {
+            socket.write_shut = true;
+            if let (Kind::Stream, Some(peer)) = (socket.kind, socket.peer) {
+                self.send_eof(peer);
+            }
+        }
+
+        sqe.status = ErrorCode::Ok.into();
+        Some(sqe)
+    }
+
+    fn send_eof(&mut self, socket_id: u64) {
+        let Some(socket) = self.sockets.get_mut(&socket_id) else {
+            return;
+        };
+        socket.rx_queue.push_back(Delivery {
+            tx: None,
+            data: None,
+            src_name: Vec::new(),
+            creds: socket.creds,
+        });
+        self.do_rx(socket_id);
+    }
+
+    // Completes TXs that will never be delivered; as far as writers
+    // are concerned, the reader got the data and dropped it.
+    fn complete_dropped(&mut self, deliveries: Vec<Delivery>) {
+        for delivery in deliveries {
+            if let Some((mut tx_sqe, tx_conn)) = delivery.tx {
+                tx_sqe.status = ErrorCode::Ok.into();
+                self.complete(tx_sqe, &tx_conn);
+            }
+        }
+    }
+
+    pub fn drop_req(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        let id = sqe.handle;
+        let owned = self
+            .conn_ids
+            .get_mut(&conn.wait_handle())
+            .is_some_and(|ids| ids.remove(&id));
+        if !owned {
+            return error_msg(sqe, ErrorCode::InvalidArgument);
+        }
+
+        self.drop_id(id);
+        sqe.status = ErrorCode::Ok.into();
+        Some(sqe)
+    }
+
+    fn drop_id(&mut self, id: u64) {
+        if let Some(listener) = self.listeners.remove(&id) {
+            self.names.remove(&listener.key);
+            for (sqe, conn) in listener.backlog {
+                let mut sqe = sqe;
+                sqe.status = ErrorCode::NotFound.into(); // Connection refused.
+                self.complete(sqe, &conn);
+            }
+            for (sqe, conn) in listener.accepts {
+                let mut sqe = sqe;
+                sqe.status = ErrorCode::BadHandle.into();
+                self.complete(sqe, &conn);
+            }
+            return;
+        }
+
+        let Some(socket) = self.sockets.remove(&id) else {
+            return;
+        };
+        log::debug!("{}:{} dropping local socket 0x{:x}", file!(), line!(), id);
+        if let Some(key) = &socket.key {
+            self.names.remove(key);
+        }
+        if let Some(peer_id) = socket.peer {
+            if let Some(peer) = self.sockets.get_mut(&peer_id) {
+                peer.peer = None;
+                if peer.kind == Kind::Stream {
+                    self.send_eof(peer_id);
+                }
+            }
+        }
+        self.complete_dropped(socket.rx_queue.into_iter().collect());
+    }
+
+    pub fn on_connection_drop(&mut self, conn: SysHandle) {
+        let Some(ids) = self.conn_ids.remove(&conn) else {
+            return;
+        };
+        for id in ids {
+            self.drop_id(id);
+        }
+
+        // Requests from the connection that still wait for something.
+        for listener in self.listeners.values_mut() {
+            listener.backlog.retain(|(_, c)| c.wait_handle() != conn);
+            listener.accepts.retain(|(_, c)| c.wait_handle() != conn);
+        }
+        for socket in self.sockets.values_mut() {
+            for delivery in &mut socket.rx_queue {
+                if delivery
+                    .tx
+                    .as_ref()
+                    .is_some_and(|(_, c)| c.wait_handle() == conn)
+                {
+                    delivery.tx = None;
+                }
+            }
+        }
+        self.pending_completions
+            .retain(|pc| pc.endpoint_handle != conn);
+    }
+
+    // Retries RX for sockets that could not get a page.
+    pub fn poll(&mut self) {
+        let mut pending_rx = VecDeque::new();
+        core::mem::swap(&mut pending_rx, &mut self.pending_rx);
+        while let Some(socket_id) = pending_rx.pop_front() {
+            self.do_rx(socket_id); // May insert socket_id back into self.pending_rx.
+        }
+    }
+}
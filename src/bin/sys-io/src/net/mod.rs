@@ -1,11 +1,20 @@
 use moto_ipc::io_channel;
 
 mod config;
+mod dns;
+mod firewall;
+mod local_socket;
 mod netdev;
 mod netsys;
+mod ntp;
+mod ping_socket;
+mod rate_limit;
+mod route;
+mod slaac;
 mod smoltcp_helpers;
 mod socket;
 mod tcp_listener;
+mod udp_socket;
 
 pub fn init() -> Box<dyn crate::runtime::IoSubsystem> {
     let config = match config::load() {
@@ -1,14 +1,26 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::net::SocketAddr;
+use std::rc::Rc;
 
-use moto_sys::SysHandle;
+use moto_sys::{ErrorCode, SysHandle};
 use smoltcp::iface::{SocketHandle, SocketSet};
-use smoltcp::phy::Loopback;
-use smoltcp::phy::{RxToken, TxToken};
+use smoltcp::phy::{Device, RxToken, TxToken};
 
 use super::config::DeviceCfg;
+use super::firewall::{Firewall, PacketFilter};
+use moto_sys_io::netcfg::{FW_CHAIN_INPUT, FW_CHAIN_OUTPUT};
+
+const ETHERNET_HEADER_LEN: usize = 14;
+
+// The smallest link MTU IPv6 works over (RFC 8200).
+const MIN_MTU: u16 = 1280;
+
+// The largest IP packet; loopback segments are sized by this.
+const LOOPBACK_MTU: usize = 65535;
 
 struct VirtioRxToken {
     dev: *mut VirtioSmoltcpDevice,
@@ -28,7 +40,10 @@ impl RxToken for VirtioRxToken {
         if let Some(rx_packet) = self.dev().rx_packet.as_ref() {
             let buf = rx_packet.bytes_mut();
             // log::debug!("consuming {} RX bytes", buf.len());
+            let len = buf.len();
             let res = f(buf);
+            self.dev().stats.rx_packets += 1;
+            self.dev().stats.rx_bytes += len as u64;
             self.dev().rx_packet = None;
 
             self.dev().poll_virtio_rx();
@@ -60,7 +75,23 @@ impl TxToken for VirtioTxToken {
             return f(&mut buf[..]);
         }
 
+        // The packet has to be built before it can be checked, so it can't go
+        // straight into the NIC.
+        if self.dev().filter.filters(FW_CHAIN_OUTPUT) {
+            let mut buffer = vec![0u8; len];
+            let result = f(&mut buffer);
+            if self.dev().filter.allows(FW_CHAIN_OUTPUT, &buffer) {
+                self.dev().stats.tx_packets += 1;
+                self.dev().stats.tx_bytes += len as u64;
+                self.dev().pending_tx.push_back(buffer);
+                self.dev().send_pending_tx();
+            }
+            return result;
+        }
+
         // log::debug!("Tx consume {} bytes", len);
+        self.dev().stats.tx_packets += 1;
+        self.dev().stats.tx_bytes += len as u64;
 
         if !self.dev().pending_tx.is_empty() {
             self.dev().send_pending_tx();
@@ -93,10 +124,13 @@ struct VirtioSmoltcpDevice {
     pending_tx: VecDeque<Vec<u8>>,
     virtio_dev: moto_virtio::virtio_net::NetDev,
     rx_packet: Option<moto_virtio::virtio_net::RxPacket>,
+    mtu: Option<u16>, // Set by the config or by the admin; lower than the device's own.
+    stats: moto_sys_io::netcfg::InterfaceStatsV1,
+    filter: PacketFilter,
 }
 
 impl VirtioSmoltcpDevice {
-    fn new(dev_cfg: &super::config::DeviceCfg) -> Option<Self> {
+    fn new(dev_cfg: &super::config::DeviceCfg, filter: PacketFilter) -> Option<Self> {
         let mac = dev_cfg.mac.raw();
 
         let virtio_dev = moto_virtio::virtio_net::take_by_mac(&mac)?;
@@ -105,6 +139,9 @@ impl VirtioSmoltcpDevice {
             pending_tx: VecDeque::new(),
             virtio_dev,
             rx_packet: None,
+            mtu: None,
+            stats: Default::default(),
+            filter,
         };
         self_.virtio_dev.start_receiving();
 
@@ -130,6 +167,25 @@ impl VirtioSmoltcpDevice {
         }
     }
 
+    // The largest Ethernet frame (sans FCS) the device can send.
+    fn max_frame_size(&self) -> usize {
+        if let Some(mtu) = self.virtio_dev.mtu() {
+            mtu as usize
+        } else {
+            1536
+        }
+    }
+
+    // Drops whatever was received while the link was down.
+    fn discard_rx(&mut self) {
+        if self.rx_packet.take().is_some() {
+            self.stats.rx_dropped += 1;
+        }
+        while self.virtio_dev.rx_get().is_some() {
+            self.stats.rx_dropped += 1;
+        }
+    }
+
     fn send_pending_tx(&mut self) {
         if self.pending_tx.is_empty() {
             return;
@@ -173,6 +229,13 @@ impl smoltcp::phy::Device for VirtioSmoltcpDevice {
         self.poll_virtio_rx();
         core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
         core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        while let Some(rx_packet) = self.rx_packet.as_ref() {
+            if self.filter.allows(FW_CHAIN_INPUT, rx_packet.bytes_mut()) {
+                break;
+            }
+            self.rx_packet = None;
+            self.poll_virtio_rx();
+        }
         if self.rx_packet.is_none() {
             // No bytes to read.
             return None;
@@ -200,28 +263,124 @@ impl smoltcp::phy::Device for VirtioSmoltcpDevice {
     fn capabilities(&self) -> smoltcp::phy::DeviceCapabilities {
         let mut caps = smoltcp::phy::DeviceCapabilities::default();
         caps.medium = smoltcp::phy::Medium::Ethernet;
-        caps.max_transmission_unit = if let Some(mtu) = self.virtio_dev.mtu() {
-            mtu as usize
-        } else {
-            1536
+        caps.max_transmission_unit = match self.mtu {
+            Some(mtu) => mtu as usize + ETHERNET_HEADER_LEN,
+            None => self.max_frame_size(),
         };
 
         caps
     }
 }
 
+struct LoopbackRxToken {
+    packet: Vec<u8>,
+}
+
+impl RxToken for LoopbackRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.packet)
+    }
+}
+
+struct LoopbackTxToken<'a> {
+    dev: &'a mut LoopbackDevice,
+}
+
+impl TxToken for LoopbackTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut packet = vec![0; len];
+        let res = f(&mut packet);
+        if !self.dev.filter.allows(FW_CHAIN_OUTPUT, &packet) {
+            return res;
+        }
+        self.dev.stats.tx_packets += 1;
+        self.dev.stats.tx_bytes += len as u64;
+        self.dev.queue.push_back(packet);
+        res
+    }
+}
+
+// Packets sent through the loopback device are received back, in order, without
+// leaving sys-io. It carries bare IP packets, so there is no ARP/NDP either.
+struct LoopbackDevice {
+    queue: VecDeque<Vec<u8>>,
+    stats: moto_sys_io::netcfg::InterfaceStatsV1,
+    filter: PacketFilter,
+}
+
+impl LoopbackDevice {
+    fn new(filter: PacketFilter) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            stats: Default::default(),
+            filter,
+        }
+    }
+
+    fn discard_rx(&mut self) {
+        self.stats.rx_dropped += self.queue.len() as u64;
+        self.queue.clear();
+    }
+}
+
+impl smoltcp::phy::Device for LoopbackDevice {
+    type RxToken<'a> = LoopbackRxToken;
+    type TxToken<'a> = LoopbackTxToken<'a>;
+
+    fn receive(
+        &mut self,
+        _timestamp: smoltcp::time::Instant,
+    ) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let packet = loop {
+            let packet = self.queue.pop_front()?;
+            if self.filter.allows(FW_CHAIN_INPUT, &packet) {
+                break packet;
+            }
+        };
+        self.stats.rx_packets += 1;
+        self.stats.rx_bytes += packet.len() as u64;
+        Some((LoopbackRxToken { packet }, LoopbackTxToken { dev: self }))
+    }
+
+    fn transmit(&mut self, _timestamp: smoltcp::time::Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken { dev: self })
+    }
+
+    fn capabilities(&self) -> smoltcp::phy::DeviceCapabilities {
+        let mut caps = smoltcp::phy::DeviceCapabilities::default();
+        caps.medium = smoltcp::phy::Medium::Ip;
+        caps.max_transmission_unit = LOOPBACK_MTU;
+
+        caps
+    }
+}
+
 enum SmoltcpDevice {
     VirtIo(VirtioSmoltcpDevice),
-    Loopback(smoltcp::phy::Loopback),
+    Loopback(LoopbackDevice),
 }
 
 impl SmoltcpDevice {
-    fn ethernet_address(&self) -> smoltcp::wire::EthernetAddress {
+    fn hardware_address(&self) -> smoltcp::wire::HardwareAddress {
         match self {
-            Self::VirtIo(dev) => smoltcp::wire::EthernetAddress::from_bytes(dev.virtio_dev.mac()),
-            Self::Loopback(_) => {
-                smoltcp::wire::EthernetAddress([0x02, 0x00, 0x00, 0x00, 0x00, 0x01])
+            Self::VirtIo(dev) => {
+                smoltcp::wire::EthernetAddress::from_bytes(dev.virtio_dev.mac()).into()
             }
+            Self::Loopback(_) => smoltcp::wire::HardwareAddress::Ip,
+        }
+    }
+
+    // The IP MTU.
+    fn ip_mtu(&self) -> usize {
+        match self {
+            Self::VirtIo(dev) => dev.capabilities().max_transmission_unit - ETHERNET_HEADER_LEN,
+            Self::Loopback(_) => LOOPBACK_MTU,
         }
     }
 }
@@ -235,6 +394,9 @@ pub(super) struct NetDev {
     pub sockets: SocketSet<'static>,
 
     ports_in_use: std::collections::HashSet<u16>,
+
+    // A link that is down neither sends nor receives anything, and is not routed through.
+    up: bool,
 }
 
 impl NetDev {
@@ -255,7 +417,7 @@ impl NetDev {
     }
 
     fn new(name: &str, dev_cfg: &super::config::DeviceCfg, mut device: SmoltcpDevice) -> Self {
-        let mut config = smoltcp::iface::Config::new(device.ethernet_address().into());
+        let mut config = smoltcp::iface::Config::new(device.hardware_address());
         config.random_seed = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map(|dur| dur.as_nanos() as u64)
@@ -288,17 +450,20 @@ impl NetDev {
             }
         });
 
-        iface.routes_mut().update(|storage| {
-            for route in &dev_cfg.routes {
-                let rt = smoltcp::iface::Route {
-                    cidr: super::smoltcp_helpers::ip_network_to_cidr(&route.ip_network),
-                    via_router: route.gateway.into(),
-                    preferred_until: None,
-                    expires_at: None,
-                };
-                storage.push(rt).unwrap();
+        if let (Some(mtu), SmoltcpDevice::VirtIo(dev)) = (dev_cfg.mtu, &mut device) {
+            let max_mtu = (dev.max_frame_size() - ETHERNET_HEADER_LEN) as u16;
+            if (MIN_MTU..=max_mtu).contains(&mtu) {
+                dev.mtu = Some(mtu);
+            } else {
+                log::warn!(
+                    "{}: MTU {} is outside of [{}, {}]; ignored.",
+                    name,
+                    mtu,
+                    MIN_MTU,
+                    max_mtu
+                );
             }
-        });
+        }
         Self {
             name: name.to_owned(),
             config: dev_cfg.clone(),
@@ -306,10 +471,14 @@ impl NetDev {
             iface,
             sockets: SocketSet::new(vec![]),
             ports_in_use: std::collections::HashSet::new(),
+            up: dev_cfg.up,
         }
     }
 
     pub fn wait_timeout(&mut self) -> Option<core::time::Duration> {
+        if !self.up {
+            return None;
+        }
         let Self { iface, sockets, .. } = self;
         iface
             .poll_delay(smoltcp::time::Instant::now(), sockets)
@@ -320,6 +489,126 @@ impl NetDev {
         &self.config
     }
 
+    pub fn mac(&self) -> [u8; 6] {
+        match self.device.hardware_address() {
+            smoltcp::wire::HardwareAddress::Ethernet(addr) => addr.0,
+            _ => [0; 6],
+        }
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        matches!(self.device, SmoltcpDevice::Loopback(_))
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+
+    pub fn stats(&self) -> moto_sys_io::netcfg::InterfaceStatsV1 {
+        match &self.device {
+            SmoltcpDevice::VirtIo(dev) => dev.stats,
+            SmoltcpDevice::Loopback(dev) => dev.stats,
+        }
+    }
+
+    pub fn set_up(&mut self, up: bool) {
+        if self.up != up {
+            log::info!("{}: link {}", self.name, if up { "up" } else { "down" });
+        }
+        self.up = up;
+    }
+
+    // The IP MTU.
+    pub fn mtu(&self) -> u16 {
+        self.device.ip_mtu().min(u16::MAX as usize) as u16
+    }
+
+    pub fn set_mtu(&mut self, mtu: u16) -> Result<(), ErrorCode> {
+        let SmoltcpDevice::VirtIo(dev) = &mut self.device else {
+            return Err(ErrorCode::NotImplemented);
+        };
+        if mtu < MIN_MTU || (mtu as usize + ETHERNET_HEADER_LEN) > dev.max_frame_size() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        // Established TCP connections keep their MSS; new ones pick up the MTU.
+        dev.mtu = Some(mtu);
+        log::info!("{}: MTU set to {}", self.name, mtu);
+        Ok(())
+    }
+
+    pub fn ip_cidrs(&self) -> Vec<(IpAddr, u8)> {
+        self.iface
+            .ip_addrs()
+            .iter()
+            .map(|cidr| (cidr.address().into(), cidr.prefix_len()))
+            .collect()
+    }
+
+    // Adds an address not in the config (i.e. autoconfigured or added at runtime).
+    pub fn add_ip_addr(&mut self, addr: IpAddr, prefix_len: u8) -> bool {
+        let cidr = smoltcp::wire::IpCidr::new(addr.into(), prefix_len);
+        let mut added = false;
+        self.iface.update_ip_addrs(|ip_addrs| {
+            if ip_addrs.contains(&cidr) {
+                added = true;
+            } else {
+                added = ip_addrs.push(cidr).is_ok();
+            }
+        });
+
+        if added {
+            log::debug!(
+                "{}:{} added IP {:?} to {}",
+                file!(),
+                line!(),
+                addr,
+                self.name
+            );
+        }
+        added
+    }
+
+    pub fn remove_ip_addr(&mut self, addr: IpAddr) -> bool {
+        let addr: smoltcp::wire::IpAddress = addr.into();
+        let mut removed = false;
+        self.iface.update_ip_addrs(|ip_addrs| {
+            let len = ip_addrs.len();
+            ip_addrs.retain(|cidr| cidr.address() != addr);
+            removed = ip_addrs.len() != len;
+        });
+
+        if removed {
+            log::debug!(
+                "{}:{} removed IP {:?} from {}",
+                file!(),
+                line!(),
+                addr,
+                self.name
+            );
+        }
+        removed
+    }
+
+    // Replaces the routes smoltcp sends through gateways with (destination, gateway).
+    pub fn set_gateways(&mut self, gateways: &[(ipnetwork::IpNetwork, IpAddr)]) {
+        let name = &self.name;
+        self.iface.routes_mut().update(|storage| {
+            storage.clear();
+            for (destination, gateway) in gateways {
+                let route = smoltcp::iface::Route {
+                    cidr: super::smoltcp_helpers::ip_network_to_cidr(destination),
+                    via_router: (*gateway).into(),
+                    preferred_until: None,
+                    expires_at: None,
+                };
+                if storage.push(route).is_err() {
+                    log::warn!("{}: too many routes; {:?} ignored", name, destination);
+                }
+            }
+        });
+    }
+
     pub fn get_ephemeral_port(
         &mut self,
         _local_ip_addr: &IpAddr,
@@ -357,7 +646,45 @@ impl NetDev {
             .unwrap();
     }
 
+    // Smoltcp answers IGMP queries for the groups joined here.
+    pub fn join_multicast_group(&mut self, group: Ipv4Addr) -> Result<(), ErrorCode> {
+        use smoltcp::iface::MulticastError;
+
+        let Self { iface, device, .. } = self;
+        let group = smoltcp::wire::Ipv4Address::from(group);
+        let now = smoltcp::time::Instant::now();
+        let result = match device {
+            SmoltcpDevice::VirtIo(dev) => iface.join_multicast_group(dev, group, now),
+            SmoltcpDevice::Loopback(dev) => iface.join_multicast_group(dev, group, now),
+        };
+        match result {
+            // Exhausted: joined, but the initial report was not sent; the next query gets one.
+            Ok(_) | Err(MulticastError::Exhausted) => Ok(()),
+            Err(MulticastError::GroupTableFull) => Err(ErrorCode::OutOfMemory),
+            Err(MulticastError::Ipv6NotSupported) => Err(ErrorCode::NotImplemented),
+        }
+    }
+
+    pub fn leave_multicast_group(&mut self, group: Ipv4Addr) {
+        let Self { iface, device, .. } = self;
+        let group = smoltcp::wire::Ipv4Address::from(group);
+        let now = smoltcp::time::Instant::now();
+        // The group is gone even if the leave message could not be sent.
+        let _ = match device {
+            SmoltcpDevice::VirtIo(dev) => iface.leave_multicast_group(dev, group, now),
+            SmoltcpDevice::Loopback(dev) => iface.leave_multicast_group(dev, group, now),
+        };
+    }
+
     pub fn poll(&mut self) -> bool {
+        if !self.up {
+            match &mut self.device {
+                SmoltcpDevice::VirtIo(dev) => dev.discard_rx(),
+                SmoltcpDevice::Loopback(dev) => dev.discard_rx(),
+            }
+            return false;
+        }
+
         if let SmoltcpDevice::VirtIo(dev) = &mut self.device {
             if !dev.pending_tx.is_empty() {
                 dev.send_pending_tx();
@@ -378,25 +705,33 @@ impl NetDev {
     }
 }
 
-pub(super) fn init(config: &super::config::NetConfig) -> Vec<NetDev> {
+pub(super) fn init(
+    config: &super::config::NetConfig,
+    firewall: &Rc<RefCell<Firewall>>,
+) -> Vec<NetDev> {
     let mut result = vec![];
 
     if config.loopback {
-        let mut loopback_cfg = DeviceCfg::new("02:00:00:00:00:01");
+        let mut loopback_cfg = DeviceCfg::new("00:00:00:00:00:00");
         loopback_cfg
             .cidrs
             .push(ipnetwork::IpNetwork::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8).unwrap());
-        let loopback_dev = Loopback::new(smoltcp::phy::Medium::Ethernet);
+        loopback_cfg
+            .cidrs
+            .push(ipnetwork::IpNetwork::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 128).unwrap());
         let dev = NetDev::new(
             "loopback",
             &loopback_cfg,
-            SmoltcpDevice::Loopback(loopback_dev),
+            SmoltcpDevice::Loopback(LoopbackDevice::new(PacketFilter::new(
+                firewall, "loopback", false,
+            ))),
         );
         result.push(dev);
     }
 
     for (dev_name, dev_cfg) in &config.devices {
-        if let Some(dev_inner) = VirtioSmoltcpDevice::new(dev_cfg) {
+        let filter = PacketFilter::new(firewall, dev_name, true);
+        if let Some(dev_inner) = VirtioSmoltcpDevice::new(dev_cfg, filter) {
             let dev = NetDev::new(dev_name, dev_cfg, SmoltcpDevice::VirtIo(dev_inner));
             result.push(dev);
         } else {
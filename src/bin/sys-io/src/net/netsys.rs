@@ -1,7 +1,7 @@
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet, VecDeque},
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     rc::Rc,
 };
 
@@ -11,10 +11,22 @@ use moto_ipc::io_channel;
 use moto_runtime::rt_api::{self, net::TcpState};
 use moto_sys::{ErrorCode, SysHandle};
 
+use super::dns::Resolver;
+use super::firewall::Firewall;
+use super::local_socket::LocalSockets;
+use super::ntp::TimeSync;
+use super::ping_socket::PingSocket;
+use super::ping_socket::PingTx;
+use super::rate_limit::TokenBucket;
+use super::route::{Route, RouteOrigin, RoutingTable};
+use super::slaac::SlaacDevice;
 use super::socket::MotoSocket;
 use super::socket::SocketId;
+use super::socket::TcpKeepalive;
 use super::tcp_listener::TcpListener;
 use super::tcp_listener::TcpListenerId;
+use super::udp_socket::UdpSocket;
+use super::udp_socket::UdpTx;
 use super::RxBuf;
 use super::{netdev::NetDev, TxBuf};
 
@@ -24,6 +36,24 @@ const MAX_NUM_LISTENING_SOCKETS: usize = 32;
 // How many concurrent connections per listener (any SocketAddr) to allow.
 const _DEFAULT_MAX_CONNECTIONS_PER_LISTENER: usize = 16;
 
+// Smoltcp buffer sizes of TCP sockets, unless sys-net.toml or their listener
+// says otherwise; requested sizes are clamped to MIN..=MAX, as Linux does.
+const TCP_RX_BUF_SIZE: usize = io_channel::PAGE_SIZE * (rt_api::net::TCP_RX_MAX_INFLIGHT as usize);
+const TCP_TX_BUF_SIZE: usize = 16384 * 2;
+const TCP_MIN_BUF_SIZE: usize = 2048;
+const TCP_MAX_BUF_SIZE: usize = 1 << 20;
+
+// Without keepalives, remotely dropped sockets may hang around indefinitely,
+// so connected sockets start with these.
+const TCP_DEFAULT_KEEPALIVE: TcpKeepalive = TcpKeepalive {
+    time_ms: 10_000,
+    interval_ms: 5_000,
+    retries: 1,
+};
+
+// The longest a socket closed by the client keeps sending queued bytes.
+const TCP_MAX_LINGER: std::time::Duration = std::time::Duration::from_secs(60);
+
 pub(super) struct NetSys {
     devices: Vec<NetDev>, // Never changes, as device_idx references inside here.
     wait_handles: HashMap<SysHandle, usize>, // Handle -> idx in self.devices.
@@ -37,12 +67,26 @@ pub(super) struct NetSys {
     tcp_listeners: HashMap<TcpListenerId, TcpListener>,
     tcp_sockets: HashMap<SocketId, MotoSocket>, // Active connections.
 
+    // Sockets the client has closed that are still sending queued bytes (see
+    // MotoSocket::linger), with the time they are aborted at.
+    closing_tcp_sockets: HashMap<SocketId, std::time::Instant>,
+
+    // TCP and UDP sockets that ran out of their TX allowance, with the time
+    // they can send again.
+    throttled_sockets: HashMap<SocketId, std::time::Instant>,
+
     // An ordered list of all sockets in the system, to be used for stats reporting.
     socket_ids: std::collections::BTreeSet<SocketId>,
 
     // If we can't allocate an IO buffer (page) for RX for a socket, the socket is placed here.
     pending_tcp_rx: VecDeque<SocketId>,
 
+    udp_sockets: HashMap<SocketId, UdpSocket>,
+    pending_udp_rx: VecDeque<SocketId>, // Same as pending_tcp_rx above.
+
+    ping_sockets: HashMap<SocketId, PingSocket>,
+    pending_ping_rx: VecDeque<SocketId>,
+
     // "Empty" sockets cached here.
     tcp_socket_cache: Vec<smoltcp::socket::tcp::Socket<'static>>,
 
@@ -50,17 +94,62 @@ pub(super) struct NetSys {
     // accessing another process's sockets, and to drop/clear when the process dies.
     conn_tcp_listeners: HashMap<SysHandle, HashSet<TcpListenerId>>,
     conn_tcp_sockets: HashMap<SysHandle, HashSet<SocketId>>,
+    conn_udp_sockets: HashMap<SysHandle, HashSet<SocketId>>,
+    conn_ping_sockets: HashMap<SysHandle, HashSet<SocketId>>,
 
     woken_sockets: Rc<RefCell<VecDeque<SocketId>>>,
     wakers: std::collections::HashMap<SocketId, std::task::Waker>,
 
-    // config: config::NetConfig,
-    config: super::config::NetConfig,
+    resolver: Resolver,
+    // UDP sockets the resolver talks to nameservers through, as (device_idx, handle):
+    // one on each device nameservers are routed through. They share an ID, a waker,
+    // and a local port.
+    dns_sockets: Vec<(usize, smoltcp::iface::SocketHandle)>,
+    dns_socket_id: SocketId,
+    dns_waker: Option<std::task::Waker>,
+    dns_port: u16, // Zero if there are no nameservers.
+
+    // NTP client; its sockets are set up like DNS sockets above.
+    time_sync: TimeSync,
+    ntp_sockets: Vec<(usize, smoltcp::iface::SocketHandle)>,
+    ntp_socket_id: SocketId,
+    ntp_waker: Option<std::task::Waker>,
+    ntp_port: u16, // Zero if there are no NTP servers.
+
+    // IPv6 autoconfiguration on devices that have it enabled. Like DNS sockets above,
+    // the raw ICMPv6 sockets share an ID and a waker.
+    slaac_devices: Vec<SlaacDevice>,
+    slaac_socket_id: SocketId,
+    slaac_waker: Option<std::task::Waker>,
+
+    local_sockets: LocalSockets,
+
+    // Clients to tell when a socket's subchannel has a free TX page again (see
+    // CMD_SOCKET_TX_WAIT), with the subchannel mask.
+    tx_waiters: Vec<(Rc<io_channel::ServerConnection>, io_channel::Msg, u64)>,
+
+    routes: RoutingTable,
+
+    // Protocol counters reported by `netstat -s`.
+    net_stats: moto_sys_io::stats::NetStatsV1,
+
+    // Defaults for new TCP sockets; see TCP_RX_BUF_SIZE above.
+    tcp_rx_buf_size: usize,
+    tcp_tx_buf_size: usize,
+    tcp_congestion_control: smoltcp::socket::tcp::CongestionControl,
+
+    // Shared with the devices, which filter packets with it.
+    firewall: Rc<RefCell<Firewall>>,
+
+    // IPv4 multicast groups joined on devices: (device_idx, group) -> the number of
+    // UDP sockets that joined it. The device leaves the group when the last one does.
+    multicast_groups: HashMap<(usize, Ipv4Addr), usize>,
 }
 
 impl NetSys {
     pub fn new(config: super::config::NetConfig) -> Box<Self> {
-        let devices = super::netdev::init(&config);
+        let firewall = Rc::new(RefCell::new(Firewall::new()));
+        let devices = super::netdev::init(&config, &firewall);
         let mut self_ref = Box::new(Self {
             devices,
             wait_handles: HashMap::new(),
@@ -68,15 +157,66 @@ impl NetSys {
             next_id: 1,
             tcp_listeners: HashMap::new(),
             tcp_sockets: HashMap::new(),
+            closing_tcp_sockets: HashMap::new(),
+            throttled_sockets: HashMap::new(),
             socket_ids: std::collections::BTreeSet::new(),
             pending_tcp_rx: VecDeque::new(),
+            udp_sockets: HashMap::new(),
+            pending_udp_rx: VecDeque::new(),
+            ping_sockets: HashMap::new(),
+            pending_ping_rx: VecDeque::new(),
             tcp_socket_cache: Vec::new(),
             pending_completions: VecDeque::new(),
             conn_tcp_listeners: HashMap::new(),
             conn_tcp_sockets: HashMap::new(),
+            conn_udp_sockets: HashMap::new(),
+            conn_ping_sockets: HashMap::new(),
             woken_sockets: Rc::new(std::cell::RefCell::new(VecDeque::new())),
             wakers: HashMap::new(),
-            config,
+            resolver: Resolver::new(config.nameservers.clone()),
+            dns_sockets: Vec::new(),
+            dns_socket_id: SocketId::from(0),
+            dns_waker: None,
+            dns_port: 0,
+            time_sync: TimeSync::new(
+                config.ntp.servers.clone(),
+                config.ntp.poll_interval.map_or(
+                    super::ntp::DEFAULT_POLL_INTERVAL,
+                    std::time::Duration::from_secs,
+                ),
+            ),
+            ntp_sockets: Vec::new(),
+            ntp_socket_id: SocketId::from(0),
+            ntp_waker: None,
+            ntp_port: 0,
+            slaac_devices: Vec::new(),
+            slaac_socket_id: SocketId::from(0),
+            slaac_waker: None,
+            local_sockets: LocalSockets::new(),
+            tx_waiters: Vec::new(),
+            routes: RoutingTable::new(),
+            net_stats: moto_sys_io::stats::NetStatsV1::default(),
+            tcp_rx_buf_size: config
+                .tcp
+                .rx_buffer
+                .map_or(TCP_RX_BUF_SIZE, |sz| sz.clamp(TCP_MIN_BUF_SIZE, TCP_MAX_BUF_SIZE)),
+            tcp_tx_buf_size: config
+                .tcp
+                .tx_buffer
+                .map_or(TCP_TX_BUF_SIZE, |sz| sz.clamp(TCP_MIN_BUF_SIZE, TCP_MAX_BUF_SIZE)),
+            tcp_congestion_control: match config.tcp.congestion_control {
+                Some(super::config::CongestionControl::None) => {
+                    smoltcp::socket::tcp::CongestionControl::None
+                }
+                Some(super::config::CongestionControl::Reno) => {
+                    smoltcp::socket::tcp::CongestionControl::Reno
+                }
+                Some(super::config::CongestionControl::Cubic) | None => {
+                    smoltcp::socket::tcp::CongestionControl::Cubic
+                }
+            },
+            firewall,
+            multicast_groups: HashMap::new(),
         });
 
         for idx in 0..self_ref.devices.len() {
@@ -87,14 +227,303 @@ impl NetSys {
             for cidr in &device.dev_cfg().cidrs {
                 self_ref.ip_addresses.insert(cidr.ip(), idx);
             }
+            for route in &device.dev_cfg().routes {
+                let route = Route::new(
+                    route.ip_network,
+                    Some(route.gateway),
+                    idx,
+                    route.metric,
+                    RouteOrigin::Config,
+                );
+                if let Err(err) = self_ref.routes.add(route.clone()) {
+                    log::warn!("sys-io: route {:?} not added: {:?}", route, err);
+                }
+            }
 
             #[cfg(debug_assertions)]
             log::debug!("sys-io: initialized net device {}", device.name());
         }
 
+        for idx in 0..self_ref.devices.len() {
+            self_ref.sync_gateways(idx);
+        }
+        self_ref.init_slaac();
+        self_ref.init_dns_sockets();
+        self_ref.init_ntp_sockets();
+
         self_ref
     }
 
+    fn init_slaac(&mut self) {
+        let device_ids: Vec<usize> = (0..self.devices.len())
+            .filter(|idx| self.devices[*idx].dev_cfg().slaac)
+            .collect();
+        if device_ids.is_empty() {
+            return;
+        }
+
+        self.slaac_socket_id = self.next_id().into();
+        let socket_waker =
+            super::socket::SocketWaker::new(self.slaac_socket_id, self.woken_sockets.clone());
+        let waker = unsafe { std::task::Waker::from_raw(socket_waker.into_raw_waker()) };
+
+        for device_idx in device_ids {
+            let device = &mut self.devices[device_idx];
+            let mac = device.mac();
+            let link_local = super::slaac::link_local_address(&mac);
+            if !device.add_ip_addr(IpAddr::V6(link_local), 64) {
+                log::warn!("sys-io: {}: failed to add {:?}", device.name(), link_local);
+                continue;
+            }
+            self.ip_addresses.insert(IpAddr::V6(link_local), device_idx);
+
+            let mut smol_socket = super::slaac::new_smoltcp_socket();
+            smol_socket.register_recv_waker(&waker);
+            self.slaac_devices.push(SlaacDevice {
+                device_idx,
+                handle: device.sockets.add(smol_socket),
+                mac,
+                link_local,
+                global_addr: None,
+                router: None,
+                solicitations_sent: 0,
+                next_solicitation: Some(std::time::Instant::now()),
+            });
+        }
+        self.slaac_waker = Some(waker);
+    }
+
+    // Sends router solicitations that are due.
+    fn poll_slaac(&mut self) {
+        let now = std::time::Instant::now();
+        for slaac_dev in &mut self.slaac_devices {
+            match slaac_dev.next_solicitation {
+                Some(when) if when <= now => {}
+                _ => continue,
+            }
+
+            let packet = super::slaac::router_solicitation(&slaac_dev.link_local, &slaac_dev.mac);
+            let smol_socket = self.devices[slaac_dev.device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::raw::Socket>(slaac_dev.handle);
+            if let Err(err) = smol_socket.send_slice(&packet) {
+                log::debug!("{}:{} router solicitation: {:?}", file!(), line!(), err);
+            }
+
+            slaac_dev.solicitations_sent += 1;
+            slaac_dev.next_solicitation =
+                if slaac_dev.solicitations_sent < super::slaac::MAX_RTR_SOLICITATIONS {
+                    Some(now + super::slaac::RTR_SOLICITATION_INTERVAL)
+                } else {
+                    None
+                };
+        }
+    }
+
+    fn on_slaac_socket_poll(&mut self) {
+        let waker = self.slaac_waker.as_ref().unwrap();
+        let mut adverts = Vec::new();
+        for (idx, slaac_dev) in self.slaac_devices.iter().enumerate() {
+            let smol_socket = self.devices[slaac_dev.device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::raw::Socket>(slaac_dev.handle);
+            smol_socket.register_recv_waker(waker);
+
+            // Other ICMPv6 messages (e.g. neighbor discovery) are handled by smoltcp.
+            while let Ok(packet) = smol_socket.recv() {
+                if let Some(advert) = super::slaac::parse_router_advert(packet) {
+                    adverts.push((idx, advert));
+                }
+            }
+        }
+
+        for (idx, advert) in adverts {
+            self.on_router_advert(idx, advert);
+        }
+    }
+
+    // TODO: expire addresses and routes when their lifetimes run out, and re-solicit.
+    fn on_router_advert(&mut self, slaac_idx: usize, advert: super::slaac::RouterAdvert) {
+        let slaac_dev = &mut self.slaac_devices[slaac_idx];
+        let device_idx = slaac_dev.device_idx;
+        let device = &mut self.devices[device_idx];
+        slaac_dev.next_solicitation = None;
+
+        if let Some((prefix, _valid_lifetime)) = advert.prefix {
+            let addr = super::slaac::address_in_prefix(&prefix, &slaac_dev.mac);
+            if slaac_dev.global_addr != Some(addr) {
+                if device.add_ip_addr(IpAddr::V6(addr), 64) {
+                    log::info!("sys-io: {}: autoconfigured {:?}", device.name(), addr);
+                    slaac_dev.global_addr = Some(addr);
+                    self.ip_addresses.insert(IpAddr::V6(addr), device_idx);
+                    self.routes.invalidate();
+                    self.on_ip_addr_added(device_idx, IpAddr::V6(addr));
+                } else {
+                    log::warn!("sys-io: {}: failed to add {:?}", device.name(), addr);
+                }
+            }
+        }
+
+        let slaac_dev = &mut self.slaac_devices[slaac_idx];
+        let prev_router = slaac_dev.router;
+        let router = if advert.router_lifetime.is_zero() {
+            prev_router.filter(|router| *router != advert.router)
+        } else {
+            Some(advert.router)
+        };
+        if router == prev_router {
+            return;
+        }
+        slaac_dev.router = router;
+
+        if let Some(prev_router) = prev_router {
+            self.routes.remove(|route| {
+                route.origin == RouteOrigin::Autoconf
+                    && route.device_idx == device_idx
+                    && route.gateway == Some(IpAddr::V6(prev_router))
+            });
+        }
+        if let Some(router) = router {
+            log::info!(
+                "sys-io: {}: IPv6 default router {:?}",
+                self.devices[device_idx].name(),
+                router
+            );
+            let route = Route::new(
+                "::/0".parse().unwrap(),
+                Some(IpAddr::V6(router)),
+                device_idx,
+                super::route::AUTOCONF_METRIC,
+                RouteOrigin::Autoconf,
+            );
+            if let Err(err) = self.routes.add(route) {
+                log::warn!("sys-io: IPv6 default route not added: {:?}", err);
+            }
+        }
+        self.sync_gateways(device_idx);
+    }
+
+    // Mirrors the routing table's gateways on the device into smoltcp.
+    fn sync_gateways(&mut self, device_idx: usize) {
+        let gateways = self.routes.device_gateways(device_idx);
+        self.devices[device_idx].set_gateways(&gateways);
+    }
+
+    // Listeners bound to [::] listen on all addresses, including autoconfigured ones.
+    fn on_ip_addr_added(&mut self, device_idx: usize, ip_addr: IpAddr) {
+        let listeners: Vec<(TcpListenerId, u16)> = self
+            .tcp_listeners
+            .iter()
+            .filter(|(_, listener)| Self::listens_on(listener.socket_addr(), &ip_addr))
+            .map(|(id, listener)| (*id, listener.socket_addr().port()))
+            .collect();
+
+        for (listener_id, port) in listeners {
+            if let Err(err) = self.start_listening_on_device(
+                listener_id,
+                device_idx,
+                SocketAddr::new(ip_addr, port),
+                DEFAULT_NUM_LISTENING_SOCKETS,
+            ) {
+                log::warn!(
+                    "{}:{} failed to listen on {:?}: {:?}",
+                    file!(),
+                    line!(),
+                    ip_addr,
+                    err
+                );
+            }
+        }
+    }
+
+    // Whether a listener bound to `bound` should listen on `ip_addr`: 0.0.0.0
+    // means all IPv4 addresses, and [::] means all addresses (dual stack).
+    fn listens_on(bound: &SocketAddr, ip_addr: &IpAddr) -> bool {
+        match bound.ip() {
+            IpAddr::V4(ip) => ip.is_unspecified() && ip_addr.is_ipv4(),
+            IpAddr::V6(ip) => ip.is_unspecified(),
+        }
+    }
+
+    fn local_ip_addrs(&self, device_idx: usize) -> Vec<IpAddr> {
+        self.ip_addresses
+            .iter()
+            .filter(|(_, idx)| **idx == device_idx)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    fn init_dns_sockets(&mut self) {
+        let servers = self.resolver.servers().to_vec();
+        if let Some((port, socket_id, waker, sockets)) =
+            self.open_client_sockets(&servers, "nameserver")
+        {
+            self.dns_port = port;
+            self.dns_socket_id = socket_id;
+            self.dns_waker = Some(waker);
+            self.dns_sockets = sockets;
+        }
+    }
+
+    fn init_ntp_sockets(&mut self) {
+        let servers = self.time_sync.servers().to_vec();
+        if let Some((port, socket_id, waker, sockets)) =
+            self.open_client_sockets(&servers, "NTP server")
+        {
+            self.ntp_port = port;
+            self.ntp_socket_id = socket_id;
+            self.ntp_waker = Some(waker);
+            self.ntp_sockets = sockets;
+        }
+    }
+
+    // Opens UDP sockets for sys-io's own use (DNS, NTP) on each device the servers
+    // are routed through. The sockets share a local port, an ID, and a waker.
+    #[allow(clippy::type_complexity)]
+    fn open_client_sockets(
+        &mut self,
+        servers: &[IpAddr],
+        what: &str,
+    ) -> Option<(
+        u16,
+        SocketId,
+        std::task::Waker,
+        Vec<(usize, smoltcp::iface::SocketHandle)>,
+    )> {
+        let mut device_ids = Vec::new();
+        for server in servers {
+            match self.find_route(server) {
+                Some((device_idx, _)) => {
+                    if !device_ids.contains(&device_idx) {
+                        device_ids.push(device_idx);
+                    }
+                }
+                None => log::warn!("sys-io: no route to {} {:?}", what, server),
+            }
+        }
+        if device_ids.is_empty() {
+            return None;
+        }
+
+        let port = self.get_ephemeral_udp_port().unwrap();
+        let socket_id: SocketId = self.next_id().into();
+        let socket_waker = super::socket::SocketWaker::new(socket_id, self.woken_sockets.clone());
+        let waker = unsafe { std::task::Waker::from_raw(socket_waker.into_raw_waker()) };
+
+        let mut sockets = Vec::with_capacity(device_ids.len());
+        for device_idx in device_ids {
+            let mut smol_socket = super::udp_socket::new_smoltcp_socket();
+            smol_socket.bind(port).unwrap();
+            smol_socket.register_recv_waker(&waker);
+            sockets.push((
+                device_idx,
+                self.devices[device_idx].sockets.add(smol_socket),
+            ));
+        }
+
+        Some((port, socket_id, waker, sockets))
+    }
+
     fn tcp_listener_bind(
         &mut self,
         conn: &Rc<io_channel::ServerConnection>,
@@ -150,7 +579,12 @@ impl NetSys {
         }
 
         let listener_id: TcpListenerId = self.next_id().into();
-        let listener = TcpListener::new(conn.clone(), socket_addr);
+        let listener = TcpListener::new(
+            conn.clone(),
+            socket_addr,
+            self.tcp_rx_buf_size,
+            self.tcp_tx_buf_size,
+        );
         self.tcp_listeners.insert(listener_id, listener);
 
         let conn_listeners = match self.conn_tcp_listeners.get_mut(&conn.wait_handle()) {
@@ -175,9 +609,11 @@ impl NetSys {
         match device_idx {
             None => {
                 for idx in 0..self.devices.len() {
-                    let cidrs = self.devices[idx].dev_cfg().cidrs.clone();
-                    for cidr in &cidrs {
-                        let local_addr = SocketAddr::new(cidr.ip(), socket_addr.port());
+                    for ip_addr in self.local_ip_addrs(idx) {
+                        if !Self::listens_on(&socket_addr, &ip_addr) {
+                            continue;
+                        }
+                        let local_addr = SocketAddr::new(ip_addr, socket_addr.port());
                         if let Err(err) = self.start_listening_on_device(
                             listener_id,
                             idx,
@@ -217,14 +653,12 @@ impl NetSys {
         assert!(!socket_addr.ip().is_unspecified());
 
         for _ in 0..num_listeners {
-            let conn = self
-                .tcp_listeners
-                .get_mut(&listener_id)
-                .unwrap()
-                .conn()
-                .clone();
+            let listener = self.tcp_listeners.get(&listener_id).unwrap();
+            let conn = listener.conn().clone();
+            let (rx_buf_size, tx_buf_size) = (listener.rx_buf_size(), listener.tx_buf_size());
             let conn_handle = conn.wait_handle();
-            let mut moto_socket = self.new_socket_for_device(device_idx, conn)?;
+            let mut moto_socket =
+                self.new_socket_for_device(device_idx, conn, rx_buf_size, tx_buf_size)?;
             let socket_id = moto_socket.id;
             moto_socket.listener_id = Some(listener_id);
             self.tcp_listeners
@@ -267,8 +701,10 @@ impl NetSys {
         &mut self,
         device_idx: usize,
         conn: Rc<io_channel::ServerConnection>,
+        rx_buf_size: usize,
+        tx_buf_size: usize,
     ) -> Result<MotoSocket, ErrorCode> {
-        let mut smol_socket = self.get_unused_tcp_socket()?;
+        let mut smol_socket = self.get_unused_tcp_socket(rx_buf_size, tx_buf_size)?;
         let socket_id = self.next_id().into();
 
         let socket_waker = super::socket::SocketWaker::new(socket_id, self.woken_sockets.clone());
@@ -306,6 +742,9 @@ impl NetSys {
             subchannel_mask: u64::MAX,
             listening_on: None,
             replacement_listener_created: false,
+            keepalive: None,
+            linger: Some(std::time::Duration::ZERO),
+            tx_limiter: None,
             stats_rx_bytes: 0,
             stats_tx_bytes: 0,
         })
@@ -336,6 +775,7 @@ impl NetSys {
     }
 
     fn drop_tcp_socket(&mut self, socket_id: SocketId) {
+        let lingering = self.closing_tcp_sockets.remove(&socket_id).is_some();
         self.cancel_tcp_tx(socket_id);
 
         // First, abort the connection without removing the socket from
@@ -381,7 +821,7 @@ impl NetSys {
             .conn_tcp_sockets
             .get_mut(&moto_socket.conn.wait_handle())
         {
-            assert!(conn_sockets.remove(&socket_id));
+            assert!(conn_sockets.remove(&socket_id) || lingering);
         }
 
         if let Some(listener_id) = moto_socket.listener_id.take() {
@@ -525,24 +965,40 @@ impl NetSys {
         Ok(())
     }
 
+    // Only sockets with the default buffer sizes are cached.
     fn get_unused_tcp_socket(
         &mut self,
+        rx_buf_size: usize,
+        tx_buf_size: usize,
     ) -> Result<smoltcp::socket::tcp::Socket<'static>, ErrorCode> {
-        if let Some(socket) = self.tcp_socket_cache.pop() {
-            Ok(socket)
-        } else {
-            const RX_BUF_SZ: usize =
-                io_channel::PAGE_SIZE * (rt_api::net::TCP_RX_MAX_INFLIGHT as usize);
-            let rx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0; RX_BUF_SZ]);
-            let tx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0; 16384 * 2]);
-
-            log::debug!("{}:{} new TCP socket", file!(), line!());
-            Ok(smoltcp::socket::tcp::Socket::new(rx_buffer, tx_buffer))
+        if rx_buf_size == self.tcp_rx_buf_size && tx_buf_size == self.tcp_tx_buf_size {
+            if let Some(socket) = self.tcp_socket_cache.pop() {
+                return Ok(socket);
+            }
         }
+
+        let rx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0; rx_buf_size]);
+        let tx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0; tx_buf_size]);
+
+        log::debug!("{}:{} new TCP socket", file!(), line!());
+        let mut socket = smoltcp::socket::tcp::Socket::new(rx_buffer, tx_buffer);
+        socket.set_congestion_control(self.tcp_congestion_control);
+        Ok(socket)
     }
 
-    fn put_unused_tcp_socket(&mut self, socket: smoltcp::socket::tcp::Socket<'static>) {
+    fn put_unused_tcp_socket(&mut self, mut socket: smoltcp::socket::tcp::Socket<'static>) {
         debug_assert_eq!(socket.state(), smoltcp::socket::tcp::State::Closed);
+        if socket.recv_capacity() != self.tcp_rx_buf_size
+            || socket.send_capacity() != self.tcp_tx_buf_size
+        {
+            return;
+        }
+
+        // Socket options survive smoltcp's reset; the next user should not see ours.
+        socket.set_timeout(None);
+        socket.set_keep_alive(None);
+        socket.set_hop_limit(None);
+
         // TODO: limit the size of the cache (i.e. drop socket if the cache is too large).
         self.tcp_socket_cache.push(socket);
     }
@@ -599,14 +1055,19 @@ impl NetSys {
                 }
             };
 
-        let mut moto_socket = match self.new_socket_for_device(device_idx, conn.clone()) {
+        let mut moto_socket = match self.new_socket_for_device(
+            device_idx,
+            conn.clone(),
+            self.tcp_rx_buf_size,
+            self.tcp_tx_buf_size,
+        ) {
             Ok(s) => s,
             Err(err) => {
                 sqe.status = err.into();
                 return Some(sqe);
             }
         };
-        moto_socket.subchannel_mask = sqe.payload.args_64()[0];
+        moto_socket.subchannel_mask = sqe.handle;
 
         let conn_handle = conn.wait_handle();
         if let Some(conn_sockets) = self.conn_tcp_sockets.get_mut(&conn_handle) {
@@ -647,6 +1108,7 @@ impl NetSys {
 
         // 3. call connect
         self.devices[device_idx].connect_socket(smol_handle, &local_addr, &remote_addr);
+        self.net_stats.tcp_active_opens += 1;
 
         None
     }
@@ -722,6 +1184,7 @@ impl NetSys {
         }
 
         moto_socket.stats_tx_bytes += sz as u64;
+        self.net_stats.tcp_tx_bytes += sz as u64;
         moto_socket.tx_queue.push_back(TxBuf {
             page,
             len: sz,
@@ -829,6 +1292,67 @@ impl NetSys {
             return sqe;
         }
 
+        if options == rt_api::net::TCP_OPTION_KEEPALIVE {
+            let args = sqe.payload.args_32();
+            let keepalive = if args[2] == 0 {
+                None
+            } else if args[3] == 0 || args[4] == 0 {
+                sqe.status = ErrorCode::InvalidArgument.into();
+                return sqe;
+            } else {
+                Some(TcpKeepalive {
+                    time_ms: args[2],
+                    interval_ms: args[3],
+                    retries: args[4],
+                })
+            };
+
+            let smol_socket = self.devices[moto_socket.device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
+            Self::set_smol_keepalive(smol_socket, keepalive);
+            moto_socket.keepalive = keepalive;
+            sqe.status = ErrorCode::Ok.into();
+            return sqe;
+        }
+
+        if options == rt_api::net::TCP_OPTION_LINGER {
+            let args = sqe.payload.args_32();
+            moto_socket.linger = match args[2] {
+                0 => None,
+                1 => Some(std::time::Duration::from_secs(args[3] as u64)),
+                _ => {
+                    sqe.status = ErrorCode::InvalidArgument.into();
+                    return sqe;
+                }
+            };
+            sqe.status = ErrorCode::Ok.into();
+            return sqe;
+        }
+
+        if options == rt_api::net::TCP_OPTION_TX_RATE_LIMIT {
+            let rate = sqe.payload.args_64()[1];
+            moto_socket.tx_limiter = if rate == 0 {
+                None
+            } else {
+                Some(TokenBucket::new(rate))
+            };
+            // Let queued bytes go out at the new rate.
+            self.throttled_sockets.remove(&socket_id);
+            self.woken_sockets.borrow_mut().push_back(socket_id);
+            sqe.status = ErrorCode::Ok.into();
+            return sqe;
+        }
+
+        if options == rt_api::net::TCP_OPTION_SEND_BUFFER
+            || options == rt_api::net::TCP_OPTION_RECV_BUFFER
+        {
+            // Smoltcp cannot resize buffers of a live socket; listeners can
+            // configure the sockets they accept.
+            sqe.status = ErrorCode::NotImplemented.into();
+            return sqe;
+        }
+
         let shut_rd =
             (options & rt_api::net::TCP_OPTION_SHUT_RD != 0) && moto_socket.state.can_read();
         options ^= rt_api::net::TCP_OPTION_SHUT_RD;
@@ -942,6 +1466,49 @@ impl NetSys {
                 sqe.payload.args_32_mut()[0] = ttl;
                 sqe.status = ErrorCode::Ok.into();
             }
+            rt_api::net::TCP_OPTION_KEEPALIVE => {
+                let keepalive = moto_socket.keepalive.unwrap_or(TcpKeepalive {
+                    time_ms: 0,
+                    interval_ms: 0,
+                    retries: 0,
+                });
+                let args = sqe.payload.args_32_mut();
+                args[0] = keepalive.time_ms;
+                args[1] = keepalive.interval_ms;
+                args[2] = keepalive.retries;
+                sqe.status = ErrorCode::Ok.into();
+            }
+            rt_api::net::TCP_OPTION_LINGER => {
+                let args = sqe.payload.args_32_mut();
+                match moto_socket.linger {
+                    Some(linger) => {
+                        args[0] = 1;
+                        args[1] = linger.as_secs() as u32;
+                    }
+                    None => {
+                        args[0] = 0;
+                        args[1] = 0;
+                    }
+                }
+                sqe.status = ErrorCode::Ok.into();
+            }
+            rt_api::net::TCP_OPTION_SEND_BUFFER | rt_api::net::TCP_OPTION_RECV_BUFFER => {
+                let smol_socket = self.devices[moto_socket.device_idx]
+                    .sockets
+                    .get::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
+                let size = if options == rt_api::net::TCP_OPTION_SEND_BUFFER {
+                    smol_socket.send_capacity()
+                } else {
+                    smol_socket.recv_capacity()
+                };
+                sqe.payload.args_32_mut()[0] = size as u32;
+                sqe.status = ErrorCode::Ok.into();
+            }
+            rt_api::net::TCP_OPTION_TX_RATE_LIMIT => {
+                sqe.payload.args_64_mut()[0] =
+                    moto_socket.tx_limiter.as_ref().map_or(0, |l| l.rate());
+                sqe.status = ErrorCode::Ok.into();
+            }
             _ => {
                 log::debug!("Invalid option 0x{}", options);
                 sqe.status = ErrorCode::InvalidArgument.into();
@@ -968,82 +1535,1549 @@ impl NetSys {
             line!(),
             u64::from(socket_id)
         );
-        // While there can still be outgoing writes that need completion,
-        // we drop everything here: let the user-side worry about
-        // not dropping connections before writes are complete.
-        // TODO: implement SO_LINGER (Rust: TcpStream::set_linger()).
-        self.drop_tcp_socket(socket_id);
+        // By default (SO_LINGER(0)), we drop everything here, including
+        // outgoing writes: let the user-side worry about not dropping
+        // connections before writes are complete.
+        let moto_socket = self.tcp_sockets.get(&socket_id).unwrap();
+        let linger = match moto_socket.linger {
+            None => TCP_MAX_LINGER,
+            Some(linger) => linger.min(TCP_MAX_LINGER),
+        };
+        if linger.is_zero() || !moto_socket.state.can_write() {
+            self.drop_tcp_socket(socket_id);
+        } else {
+            self.linger_tcp_socket(socket_id, std::time::Instant::now() + linger);
+        }
         sqe.status = ErrorCode::Ok.into();
         Some(sqe)
     }
 
-    fn next_id(&mut self) -> u64 {
-        let res = self.next_id;
-        self.next_id += 1;
-        res
+    // Keeps the socket around, without its client, until the queued bytes
+    // are sent and the connection is closed, or until the deadline.
+    fn linger_tcp_socket(&mut self, socket_id: SocketId, deadline: std::time::Instant) {
+        let moto_socket = self.tcp_sockets.get_mut(&socket_id).unwrap();
+        if let Some(conn_sockets) = self
+            .conn_tcp_sockets
+            .get_mut(&moto_socket.conn.wait_handle())
+        {
+            assert!(conn_sockets.remove(&socket_id));
+        }
+
+        // Nothing is delivered to the client anymore.
+        moto_socket.state = TcpState::Closed;
+        moto_socket.rx_ack = u64::MAX;
+        moto_socket.rx_closed_notified = true;
+
+        self.closing_tcp_sockets.insert(socket_id, deadline);
+        self.on_closing_tcp_socket_poll(socket_id);
     }
 
-    fn device_idx_from_name(&self, name: &str) -> usize {
-        for idx in 0..self.devices.len() {
-            if self.devices[idx].name() == name {
-                return idx;
+    fn on_closing_tcp_socket_poll(&mut self, socket_id: SocketId) {
+        let waker = if let Some(waker) = self.wakers.get(&socket_id) {
+            waker.clone()
+        } else {
+            return;
+        };
+        let moto_socket = self.tcp_sockets.get(&socket_id).unwrap();
+        let smol_socket = self.devices[moto_socket.device_idx]
+            .sockets
+            .get_mut::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
+        smol_socket.register_recv_waker(&waker);
+        smol_socket.register_send_waker(&waker);
+
+        while smol_socket.recv_queue() > 0 {
+            let _ = smol_socket.recv(|bytes| (bytes.len(), ()));
+        }
+
+        let can_send = smol_socket.can_send();
+        match smol_socket.state() {
+            smoltcp::socket::tcp::State::Closed | smoltcp::socket::tcp::State::TimeWait => {
+                self.drop_tcp_socket(socket_id);
+                return;
             }
+            _ => {}
         }
-        panic!("{}:{} bad name '{}'", file!(), line!(), name)
-    }
 
-    // Find the device to route through.
-    fn find_route(&self, ip_addr: &IpAddr) -> Option<(usize, IpAddr)> {
-        // First, look through local addresses.
-        match self.ip_addresses.get(ip_addr) {
-            Some(device_idx) => return Some((*device_idx, *ip_addr)),
-            None => {}
+        if can_send {
+            self.do_tcp_tx(socket_id);
         }
 
-        // If not found, look through routes.
-        self.config
-            .find_route(ip_addr)
-            .map(|(dev_name, addr)| (self.device_idx_from_name(dev_name.as_str()), addr))
+        let moto_socket = self.tcp_sockets.get(&socket_id).unwrap();
+        if moto_socket.tx_queue.is_empty() {
+            self.devices[moto_socket.device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::tcp::Socket>(moto_socket.handle)
+                .close();
+        }
     }
 
-    fn rx_buf_to_pc(
-        socket_id: SocketId,
-        endpoint_handle: SysHandle,
-        x_buf: RxBuf,
-        rx_seq: u64,
-    ) -> PendingCompletion {
-        let (io_page, sz) = (x_buf.page, x_buf.consumed);
-        let mut msg =
-            moto_runtime::rt_api::net::tcp_stream_rx_msg(socket_id.into(), io_page, sz, rx_seq);
-        msg.status = ErrorCode::Ok.into();
-
-        PendingCompletion {
-            msg,
-            endpoint_handle,
+    // Aborts lingering sockets that are past their deadline.
+    fn expire_closing_tcp_sockets(&mut self) {
+        let now = std::time::Instant::now();
+        let expired: Vec<SocketId> = self
+            .closing_tcp_sockets
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(socket_id, _)| *socket_id)
+            .collect();
+        for socket_id in expired {
+            self.drop_tcp_socket(socket_id);
         }
     }
 
-    // Returns 'true' if the socket can do tx/rx.
-    fn on_tcp_listener_connected(&mut self, socket_id: SocketId) -> bool {
-        let moto_socket = self.tcp_sockets.get_mut(&socket_id).unwrap();
-        let device_idx = moto_socket.device_idx;
-        let smol_socket = self.devices[device_idx]
-            .sockets
-            .get_mut::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
-
-        smol_socket.set_nagle_enabled(false); // A good idea, generally.
-        smol_socket.set_ack_delay(None);
+    // Throttled sockets are polled again once they can send; dropped ones are ignored there.
+    fn wake_throttled_sockets(&mut self) {
+        let now = std::time::Instant::now();
+        self.throttled_sockets.retain(|socket_id, ready_at| {
+            if *ready_at > now {
+                return true;
+            }
+            self.woken_sockets.borrow_mut().push_back(*socket_id);
+            false
+        });
+    }
 
-        let local_addr = super::smoltcp_helpers::socket_addr_from_endpoint(
-            smol_socket.local_endpoint().unwrap(),
-        );
-        assert_eq!(local_addr, moto_socket.listening_on.unwrap());
-        let remote_addr = super::smoltcp_helpers::socket_addr_from_endpoint(
-            smol_socket.remote_endpoint().unwrap(),
-        );
+    // Smoltcp sends a keepalive once the connection has been idle for `time`, and
+    // aborts it if the peer does not answer within `interval * retries`. Unlike
+    // Linux, it does not repeat the probe in between.
+    fn set_smol_keepalive(
+        smol_socket: &mut smoltcp::socket::tcp::Socket,
+        keepalive: Option<TcpKeepalive>,
+    ) {
+        match keepalive {
+            Some(keepalive) => {
+                smol_socket.set_keep_alive(Some(smoltcp::time::Duration::from_millis(
+                    keepalive.time_ms as u64,
+                )));
+                smol_socket.set_timeout(Some(smoltcp::time::Duration::from_millis(
+                    keepalive.interval_ms as u64 * keepalive.retries as u64,
+                )));
+            }
+            None => {
+                smol_socket.set_keep_alive(None);
+                smol_socket.set_timeout(None);
+            }
+        }
+    }
 
-        let listener_id = *moto_socket.listener_id.as_ref().unwrap();
-        let listener = self.tcp_listeners.get_mut(&listener_id).unwrap();
+    fn tcp_listener_set_option(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        let listener_id: TcpListenerId = sqe.handle.into();
+        let listener = match self.tcp_listeners.get_mut(&listener_id) {
+            Some(l) if l.conn_handle() == conn.wait_handle() => l,
+            _ => {
+                sqe.status = ErrorCode::InvalidArgument.into();
+                return sqe;
+            }
+        };
+
+        let size = (sqe.payload.args_32()[2] as usize).clamp(TCP_MIN_BUF_SIZE, TCP_MAX_BUF_SIZE);
+        match sqe.payload.args_64()[0] {
+            rt_api::net::TCP_OPTION_SEND_BUFFER => listener.set_tx_buf_size(size),
+            rt_api::net::TCP_OPTION_RECV_BUFFER => listener.set_rx_buf_size(size),
+            _ => {
+                sqe.status = ErrorCode::InvalidArgument.into();
+                return sqe;
+            }
+        }
+
+        // Sockets still waiting for a connection are recreated with the new buffers.
+        let listening_sockets = listener.listening_sockets();
+        for socket_id in listening_sockets {
+            let moto_socket = self.tcp_sockets.get(&socket_id).unwrap();
+            let smol_socket = self.devices[moto_socket.device_idx]
+                .sockets
+                .get::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
+            if smol_socket.state() != smoltcp::socket::tcp::State::Listen {
+                continue;
+            }
+
+            let device_idx = moto_socket.device_idx;
+            let addr = moto_socket.listening_on.unwrap();
+            self.drop_tcp_socket(socket_id);
+            if let Err(err) = self.start_listening_on_device(listener_id, device_idx, addr, 1) {
+                sqe.status = err.into();
+                return sqe;
+            }
+        }
+
+        sqe.status = ErrorCode::Ok.into();
+        sqe
+    }
+
+    fn tcp_listener_get_option(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        let listener_id: TcpListenerId = sqe.handle.into();
+        let listener = match self.tcp_listeners.get(&listener_id) {
+            Some(l) if l.conn_handle() == conn.wait_handle() => l,
+            _ => {
+                sqe.status = ErrorCode::InvalidArgument.into();
+                return sqe;
+            }
+        };
+
+        let size = match sqe.payload.args_64()[0] {
+            rt_api::net::TCP_OPTION_SEND_BUFFER => listener.tx_buf_size(),
+            rt_api::net::TCP_OPTION_RECV_BUFFER => listener.rx_buf_size(),
+            _ => {
+                sqe.status = ErrorCode::InvalidArgument.into();
+                return sqe;
+            }
+        };
+        sqe.payload.args_32_mut()[0] = size as u32;
+        sqe.status = ErrorCode::Ok.into();
+        sqe
+    }
+
+    fn udp_socket_bind(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        if self.devices.is_empty() {
+            sqe.status = ErrorCode::NotFound.into();
+            return sqe;
+        }
+
+        let mut socket_addr = match rt_api::net::get_socket_addr(&sqe.payload) {
+            Ok(addr) => addr,
+            Err(err) => {
+                sqe.status = err.into();
+                return sqe;
+            }
+        };
+
+        let subchannel_idx = sqe.flags as usize;
+        if subchannel_idx >= rt_api::net::IO_SUBCHANNELS {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return sqe;
+        }
+
+        let ip_addr = socket_addr.ip();
+        let device_ids: Vec<usize> = if ip_addr.is_unspecified() {
+            (0..self.devices.len()).collect()
+        } else {
+            match self.ip_addresses.get(&ip_addr) {
+                Some(idx) => vec![*idx],
+                None => {
+                    sqe.status = ErrorCode::InvalidArgument.into();
+                    return sqe;
+                }
+            }
+        };
+
+        if socket_addr.port() == 0 {
+            match self.get_ephemeral_udp_port() {
+                Some(port) => socket_addr.set_port(port),
+                None => {
+                    log::info!("{}:{} out of UDP ports", file!(), line!());
+                    sqe.status = ErrorCode::OutOfMemory.into();
+                    return sqe;
+                }
+            }
+        } else if socket_addr.port() == self.dns_port
+            || socket_addr.port() == self.ntp_port
+            || self
+                .udp_sockets
+                .values()
+                .any(|s| s.conflicts_with(&socket_addr))
+        {
+            sqe.status = ErrorCode::AlreadyInUse.into();
+            return sqe;
+        }
+
+        let socket_id: SocketId = self.next_id().into();
+        let socket_waker = super::socket::SocketWaker::new(socket_id, self.woken_sockets.clone());
+        let waker = unsafe { std::task::Waker::from_raw(socket_waker.into_raw_waker()) };
+
+        let endpoint = smoltcp::wire::IpListenEndpoint {
+            addr: if ip_addr.is_unspecified() {
+                None
+            } else {
+                Some(ip_addr.into())
+            },
+            port: socket_addr.port(),
+        };
+
+        let mut handles = Vec::with_capacity(device_ids.len());
+        for device_idx in device_ids {
+            let mut smol_socket = super::udp_socket::new_smoltcp_socket();
+            // Cannot fail: the endpoint has a non-zero port and the socket is new.
+            smol_socket.bind(endpoint).unwrap();
+            smol_socket.register_recv_waker(&waker);
+            smol_socket.register_send_waker(&waker);
+            handles.push((
+                device_idx,
+                self.devices[device_idx].sockets.add(smol_socket),
+            ));
+        }
+
+        self.udp_sockets.insert(
+            socket_id,
+            UdpSocket {
+                id: socket_id,
+                conn: conn.clone(),
+                local_addr: socket_addr,
+                handles,
+                waker,
+                tx_queue: VecDeque::new(),
+                subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+                multicast_groups: Vec::new(),
+                tx_limiter: None,
+            },
+        );
+        self.conn_udp_sockets
+            .entry(conn.wait_handle())
+            .or_default()
+            .insert(socket_id);
+
+        log::debug!(
+            "{}:{} new UDP socket 0x{:x} bound to {:?}",
+            file!(),
+            line!(),
+            u64::from(socket_id),
+            socket_addr
+        );
+
+        sqe.handle = socket_id.into();
+        rt_api::net::put_socket_addr(&mut sqe.payload, &socket_addr);
+        sqe.status = ErrorCode::Ok.into();
+        sqe
+    }
+
+    // TODO: do better than a linear search.
+    fn get_ephemeral_udp_port(&self) -> Option<u16> {
+        (49152..=65535_u16).find(|port| {
+            *port != self.dns_port
+                && *port != self.ntp_port
+                && !self
+                    .udp_sockets
+                    .values()
+                    .any(|s| s.local_addr.port() == *port)
+        })
+    }
+
+    fn udp_socket_from_msg(
+        &self,
+        conn_handle: SysHandle,
+        sqe: &io_channel::Msg,
+    ) -> Option<SocketId> {
+        let socket_id: SocketId = sqe.handle.into();
+
+        // Validate that the socket belongs to the connection.
+        match self.conn_udp_sockets.get(&conn_handle) {
+            Some(socks) if socks.contains(&socket_id) => Some(socket_id),
+            _ => {
+                log::debug!("{}:{} bad socket", file!(), line!());
+                None
+            }
+        }
+    }
+
+    // TX is one-way: only a send that fails gets a response, a CMD_UDP_SOCKET_TX
+    // message with the error, which the client reports on its next send.
+    fn udp_socket_tx(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut msg: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        // Note: we need to get the page so that it is freed.
+        let page = if let Ok(page) = conn.get_page(rt_api::net::udp_socket_datagram_page(&msg)) {
+            page
+        } else {
+            msg.status = ErrorCode::InvalidArgument.into();
+            return Some(msg);
+        };
+        let Some(socket_id) = self.udp_socket_from_msg(conn.wait_handle(), &msg) else {
+            msg.status = ErrorCode::BadHandle.into();
+            return Some(msg);
+        };
+
+        let len = msg.flags as usize;
+        let Ok(dest) = rt_api::net::get_socket_addr(&msg.payload) else {
+            msg.status = ErrorCode::InvalidArgument.into();
+            return Some(msg);
+        };
+        if len > rt_api::net::UDP_MAX_DATAGRAM || dest.port() == 0 || dest.ip().is_unspecified() {
+            msg.status = ErrorCode::InvalidArgument.into();
+            return Some(msg);
+        }
+
+        // Datagrams to a group the socket joined go out where it joined it.
+        let joined_on = match dest.ip() {
+            IpAddr::V4(group) => self.udp_sockets[&socket_id]
+                .multicast_groups
+                .iter()
+                .find(|(_, joined)| *joined == group)
+                .map(|(device_idx, _)| *device_idx),
+            IpAddr::V6(_) => None,
+        };
+        let device_idx = match joined_on.or_else(|| self.find_route(&dest.ip()).map(|r| r.0)) {
+            Some(device_idx) => device_idx,
+            None => {
+                log::debug!("{}:{} no route to {:?}", file!(), line!(), dest);
+                msg.status = ErrorCode::NotFound.into();
+                return Some(msg);
+            }
+        };
+
+        let udp_socket = self.udp_sockets.get_mut(&socket_id).unwrap();
+        if udp_socket.handle_on(device_idx).is_none() {
+            log::debug!(
+                "{}:{} UDP socket 0x{:x} is not bound on the route to {:?}",
+                file!(),
+                line!(),
+                u64::from(socket_id),
+                dest
+            );
+            msg.status = ErrorCode::InvalidArgument.into();
+            return Some(msg);
+        }
+
+        udp_socket.tx_queue.push_back(UdpTx {
+            page,
+            len,
+            dest,
+            device_idx,
+        });
+        self.do_udp_tx(socket_id);
+        None
+    }
+
+    fn do_udp_tx(&mut self, socket_id: SocketId) {
+        let udp_socket = self.udp_sockets.get_mut(&socket_id).unwrap();
+
+        while let Some(tx) = udp_socket.tx_queue.pop_front() {
+            if let Some(limiter) = &mut udp_socket.tx_limiter {
+                if limiter.available() == 0 {
+                    self.throttled_sockets.insert(socket_id, limiter.ready_at());
+                    udp_socket.tx_queue.push_front(tx);
+                    break;
+                }
+            }
+
+            let handle = udp_socket.handle_on(tx.device_idx).unwrap();
+            let smol_socket = self.devices[tx.device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::udp::Socket>(handle);
+
+            let endpoint = smoltcp::wire::IpEndpoint::new(tx.dest.ip().into(), tx.dest.port());
+            match smol_socket.send_slice(&tx.page.bytes()[..tx.len], endpoint) {
+                Ok(()) => {
+                    self.net_stats.udp_tx_datagrams += 1;
+                    if let Some(limiter) = &mut udp_socket.tx_limiter {
+                        limiter.consume(tx.len);
+                    }
+                }
+                Err(smoltcp::socket::udp::SendError::BufferFull) => {
+                    // Will retry when the socket is woken.
+                    udp_socket.tx_queue.push_front(tx);
+                    break;
+                }
+                Err(err) => {
+                    self.net_stats.udp_tx_errors += 1;
+                    log::debug!(
+                        "{}:{} UDP send to {:?}: {:?}",
+                        file!(),
+                        line!(),
+                        tx.dest,
+                        err
+                    );
+                    let mut msg = io_channel::Msg::new();
+                    msg.command = rt_api::net::CMD_UDP_SOCKET_TX;
+                    msg.handle = socket_id.into();
+                    msg.status = ErrorCode::InvalidArgument.into();
+                    self.pending_completions.push_back(PendingCompletion {
+                        msg,
+                        endpoint_handle: udp_socket.conn.wait_handle(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn do_udp_rx(&mut self, socket_id: SocketId) {
+        let udp_socket = if let Some(socket) = self.udp_sockets.get_mut(&socket_id) {
+            socket
+        } else {
+            // The socket may have been dropped while sitting on self.pending_udp_rx.
+            return;
+        };
+
+        for (device_idx, handle) in &udp_socket.handles {
+            let smol_socket = self.devices[*device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::udp::Socket>(*handle);
+
+            while smol_socket.can_recv() {
+                let page = match udp_socket.conn.alloc_page(udp_socket.subchannel_mask) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        assert_eq!(err, ErrorCode::NotReady);
+                        if !self.pending_udp_rx.contains(&socket_id) {
+                            self.pending_udp_rx.push_back(socket_id);
+                        }
+                        return;
+                    }
+                };
+
+                let (data, meta) = smol_socket.recv().unwrap();
+                // Datagrams larger than a page are truncated, like with recv_from()
+                // into a small buffer elsewhere.
+                let len = data.len().min(rt_api::net::UDP_MAX_DATAGRAM);
+                page.bytes_mut()[..len].copy_from_slice(&data[..len]);
+                let src = super::smoltcp_helpers::socket_addr_from_endpoint(meta.endpoint);
+
+                let mut msg = rt_api::net::udp_socket_datagram_msg(
+                    rt_api::net::CMD_UDP_SOCKET_RX,
+                    socket_id.into(),
+                    page,
+                    len,
+                    &src,
+                );
+                msg.status = ErrorCode::Ok.into();
+                self.pending_completions.push_back(PendingCompletion {
+                    msg,
+                    endpoint_handle: udp_socket.conn.wait_handle(),
+                });
+                self.net_stats.udp_rx_datagrams += 1;
+            }
+        }
+    }
+
+    fn on_udp_socket_poll(&mut self, socket_id: SocketId) {
+        let Some(udp_socket) = self.udp_sockets.get(&socket_id) else {
+            return;
+        };
+
+        // Smoltcp wakers are one-shot.
+        for (device_idx, handle) in &udp_socket.handles {
+            let smol_socket = self.devices[*device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::udp::Socket>(*handle);
+            smol_socket.register_recv_waker(&udp_socket.waker);
+            smol_socket.register_send_waker(&udp_socket.waker);
+        }
+
+        self.do_udp_tx(socket_id);
+        self.do_udp_rx(socket_id);
+    }
+
+    fn udp_socket_set_option(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        let Some(socket_id) = self.udp_socket_from_msg(conn.wait_handle(), &sqe) else {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return sqe;
+        };
+        let udp_socket = self.udp_sockets.get(&socket_id).unwrap();
+
+        match sqe.payload.args_64()[0] {
+            rt_api::net::UDP_OPTION_TTL => {
+                let ttl = sqe.payload.args_32()[2];
+                if ttl == 0 || ttl > 255 {
+                    sqe.status = ErrorCode::InvalidArgument.into();
+                    return sqe;
+                };
+
+                for (device_idx, handle) in &udp_socket.handles {
+                    self.devices[*device_idx]
+                        .sockets
+                        .get_mut::<smoltcp::socket::udp::Socket>(*handle)
+                        .set_hop_limit(Some(ttl as u8));
+                }
+                sqe.status = ErrorCode::Ok.into();
+            }
+            rt_api::net::UDP_OPTION_TX_RATE_LIMIT => {
+                let rate = sqe.payload.args_64()[1];
+                self.udp_sockets.get_mut(&socket_id).unwrap().tx_limiter = if rate == 0 {
+                    None
+                } else {
+                    Some(TokenBucket::new(rate))
+                };
+                self.throttled_sockets.remove(&socket_id);
+                self.woken_sockets.borrow_mut().push_back(socket_id);
+                sqe.status = ErrorCode::Ok.into();
+            }
+            rt_api::net::UDP_OPTION_JOIN_MULTICAST_V4
+            | rt_api::net::UDP_OPTION_LEAVE_MULTICAST_V4 => {
+                let join = sqe.payload.args_64()[0] == rt_api::net::UDP_OPTION_JOIN_MULTICAST_V4;
+                let group = Ipv4Addr::from(sqe.payload.args_32()[2]);
+                let local_addr = Ipv4Addr::from(sqe.payload.args_32()[3]);
+                let result = if join {
+                    self.udp_join_multicast(socket_id, group, local_addr)
+                } else {
+                    self.udp_leave_multicast(socket_id, group, local_addr)
+                };
+                sqe.status = match result {
+                    Ok(()) => ErrorCode::Ok.into(),
+                    Err(err) => err.into(),
+                };
+            }
+            options => {
+                log::debug!("Invalid option 0x{}", options);
+                sqe.status = ErrorCode::InvalidArgument.into();
+            }
+        }
+
+        sqe
+    }
+
+    fn udp_socket_get_option(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        let Some(socket_id) = self.udp_socket_from_msg(conn.wait_handle(), &sqe) else {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return sqe;
+        };
+        let udp_socket = self.udp_sockets.get(&socket_id).unwrap();
+
+        match sqe.payload.args_64()[0] {
+            rt_api::net::UDP_OPTION_TTL => {
+                // All smoltcp sockets of a UDP socket have the same hop limit.
+                let (device_idx, handle) = udp_socket.handles[0];
+                let smol_socket = self.devices[device_idx]
+                    .sockets
+                    .get::<smoltcp::socket::udp::Socket>(handle);
+                let ttl = if let Some(hl) = smol_socket.hop_limit() {
+                    hl as u32
+                } else {
+                    64 // This is what smoltcp documentation implies.
+                };
+                sqe.payload.args_32_mut()[0] = ttl;
+                sqe.status = ErrorCode::Ok.into();
+            }
+            rt_api::net::UDP_OPTION_TX_RATE_LIMIT => {
+                sqe.payload.args_64_mut()[0] =
+                    udp_socket.tx_limiter.as_ref().map_or(0, |l| l.rate());
+                sqe.status = ErrorCode::Ok.into();
+            }
+            options => {
+                log::debug!("Invalid option 0x{}", options);
+                sqe.status = ErrorCode::InvalidArgument.into();
+            }
+        }
+
+        sqe
+    }
+
+    fn udp_socket_drop(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        let Some(socket_id) = self.udp_socket_from_msg(conn.wait_handle(), &sqe) else {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return sqe;
+        };
+
+        self.conn_udp_sockets
+            .get_mut(&conn.wait_handle())
+            .unwrap()
+            .remove(&socket_id);
+        self.drop_udp_socket(socket_id);
+
+        sqe.status = ErrorCode::Ok.into();
+        sqe
+    }
+
+    fn drop_udp_socket(&mut self, socket_id: SocketId) {
+        let udp_socket = self.udp_sockets.remove(&socket_id).unwrap();
+        log::debug!(
+            "{}:{} dropping UDP socket 0x{:x} bound to {:?}",
+            file!(),
+            line!(),
+            u64::from(udp_socket.id),
+            udp_socket.local_addr
+        );
+
+        for (device_idx, group) in udp_socket.multicast_groups {
+            self.release_multicast_group(device_idx, group);
+        }
+        for (device_idx, handle) in udp_socket.handles {
+            self.devices[device_idx].sockets.remove(handle);
+        }
+    }
+
+    // The device a multicast group is joined on: the one with local_addr, or, if
+    // local_addr is unspecified, the one the group routes through.
+    fn multicast_device(&self, group: Ipv4Addr, local_addr: Ipv4Addr) -> Result<usize, ErrorCode> {
+        if !group.is_multicast() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let device_idx = if local_addr.is_unspecified() {
+            self.find_route(&IpAddr::V4(group))
+                .map(|(device_idx, _)| device_idx)
+        } else {
+            self.ip_addresses.get(&IpAddr::V4(local_addr)).copied()
+        };
+        device_idx.ok_or(ErrorCode::NotFound)
+    }
+
+    fn udp_join_multicast(
+        &mut self,
+        socket_id: SocketId,
+        group: Ipv4Addr,
+        local_addr: Ipv4Addr,
+    ) -> Result<(), ErrorCode> {
+        let device_idx = self.multicast_device(group, local_addr)?;
+        let udp_socket = self.udp_sockets.get(&socket_id).unwrap();
+        if udp_socket.handle_on(device_idx).is_none() {
+            // Bound to an address on another device: datagrams to the group won't reach it.
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if udp_socket.multicast_groups.contains(&(device_idx, group)) {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+
+        let members = self.multicast_groups.get(&(device_idx, group)).copied();
+        if members.is_none() {
+            self.devices[device_idx].join_multicast_group(group)?;
+            log::debug!("joined {} on {}", group, self.devices[device_idx].name());
+        }
+        self.multicast_groups
+            .insert((device_idx, group), members.unwrap_or(0) + 1);
+
+        self.udp_sockets
+            .get_mut(&socket_id)
+            .unwrap()
+            .multicast_groups
+            .push((device_idx, group));
+        Ok(())
+    }
+
+    fn udp_leave_multicast(
+        &mut self,
+        socket_id: SocketId,
+        group: Ipv4Addr,
+        local_addr: Ipv4Addr,
+    ) -> Result<(), ErrorCode> {
+        let device_idx = self.multicast_device(group, local_addr)?;
+        let memberships = &mut self
+            .udp_sockets
+            .get_mut(&socket_id)
+            .unwrap()
+            .multicast_groups;
+        let Some(pos) = memberships.iter().position(|m| *m == (device_idx, group)) else {
+            return Err(ErrorCode::NotFound);
+        };
+        memberships.swap_remove(pos);
+        self.release_multicast_group(device_idx, group);
+        Ok(())
+    }
+
+    fn release_multicast_group(&mut self, device_idx: usize, group: Ipv4Addr) {
+        let members = self.multicast_groups.get_mut(&(device_idx, group)).unwrap();
+        *members -= 1;
+        if *members == 0 {
+            self.multicast_groups.remove(&(device_idx, group));
+            self.devices[device_idx].leave_multicast_group(group);
+            log::debug!("left {} on {}", group, self.devices[device_idx].name());
+        }
+    }
+
+    fn ping_socket_open(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        if self.devices.is_empty() {
+            sqe.status = ErrorCode::NotFound.into();
+            return sqe;
+        }
+
+        let subchannel_idx = sqe.flags as usize;
+        if subchannel_idx >= rt_api::net::IO_SUBCHANNELS {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return sqe;
+        }
+
+        // TODO: do better than a linear search.
+        let Some(ident) =
+            (1..=u16::MAX).find(|ident| !self.ping_sockets.values().any(|s| s.ident == *ident))
+        else {
+            sqe.status = ErrorCode::OutOfMemory.into();
+            return sqe;
+        };
+
+        let socket_id: SocketId = self.next_id().into();
+        let socket_waker = super::socket::SocketWaker::new(socket_id, self.woken_sockets.clone());
+        let waker = unsafe { std::task::Waker::from_raw(socket_waker.into_raw_waker()) };
+
+        let mut handles = Vec::with_capacity(self.devices.len());
+        for device_idx in 0..self.devices.len() {
+            let mut smol_socket = super::ping_socket::new_smoltcp_socket();
+            // Cannot fail: the identifier is non-zero and the socket is new.
+            smol_socket
+                .bind(smoltcp::socket::icmp::Endpoint::Ident(ident))
+                .unwrap();
+            smol_socket.register_recv_waker(&waker);
+            smol_socket.register_send_waker(&waker);
+            handles.push((
+                device_idx,
+                self.devices[device_idx].sockets.add(smol_socket),
+            ));
+        }
+
+        self.ping_sockets.insert(
+            socket_id,
+            PingSocket {
+                id: socket_id,
+                conn: conn.clone(),
+                ident,
+                handles,
+                waker,
+                tx_queue: VecDeque::new(),
+                subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+            },
+        );
+        self.conn_ping_sockets
+            .entry(conn.wait_handle())
+            .or_default()
+            .insert(socket_id);
+
+        log::debug!(
+            "{}:{} new ping socket 0x{:x} ident {}",
+            file!(),
+            line!(),
+            u64::from(socket_id),
+            ident
+        );
+
+        sqe.handle = socket_id.into();
+        sqe.payload.args_16_mut()[0] = ident;
+        sqe.status = ErrorCode::Ok.into();
+        sqe
+    }
+
+    fn ping_socket_from_msg(
+        &self,
+        conn_handle: SysHandle,
+        sqe: &io_channel::Msg,
+    ) -> Option<SocketId> {
+        let socket_id: SocketId = sqe.handle.into();
+
+        // Validate that the socket belongs to the connection.
+        match self.conn_ping_sockets.get(&conn_handle) {
+            Some(socks) if socks.contains(&socket_id) => Some(socket_id),
+            _ => {
+                log::debug!("{}:{} bad socket", file!(), line!());
+                None
+            }
+        }
+    }
+
+    // As with UDP, TX is one-way: requests that can't be routed are dropped,
+    // and the application sees no reply.
+    fn ping_socket_tx(&mut self, conn: &Rc<io_channel::ServerConnection>, msg: io_channel::Msg) {
+        // Note: we need to get the page so that it is freed.
+        let Ok(page) = conn.get_page(rt_api::net::ping_socket_page(&msg)) else {
+            return;
+        };
+        let Some(socket_id) = self.ping_socket_from_msg(conn.wait_handle(), &msg) else {
+            return;
+        };
+
+        let len = msg.flags as usize;
+        let Ok(dest) = rt_api::net::ping_socket_addr(&msg) else {
+            return;
+        };
+        if len > rt_api::net::PING_MAX_PAYLOAD {
+            return;
+        }
+
+        let Some((device_idx, _)) = self.find_route(&dest) else {
+            log::debug!("{}:{} no route to {:?}", file!(), line!(), dest);
+            return;
+        };
+
+        self.ping_sockets
+            .get_mut(&socket_id)
+            .unwrap()
+            .tx_queue
+            .push_back(PingTx {
+                page,
+                len,
+                dest,
+                seq: rt_api::net::ping_socket_seq(&msg),
+                device_idx,
+            });
+        self.do_ping_tx(socket_id);
+    }
+
+    fn do_ping_tx(&mut self, socket_id: SocketId) {
+        let ping_socket = self.ping_sockets.get_mut(&socket_id).unwrap();
+
+        while let Some(tx) = ping_socket.tx_queue.pop_front() {
+            let handle = ping_socket.handle_on(tx.device_idx).unwrap();
+            let smol_socket = self.devices[tx.device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::icmp::Socket>(handle);
+
+            let packet = super::ping_socket::echo_request(
+                ping_socket.ident,
+                tx.seq,
+                &tx.page.bytes()[..tx.len],
+                &tx.dest,
+            );
+            match smol_socket.send_slice(&packet, tx.dest.into()) {
+                Ok(()) => {}
+                Err(smoltcp::socket::icmp::SendError::BufferFull) => {
+                    // Will retry when the socket is woken.
+                    ping_socket.tx_queue.push_front(tx);
+                    break;
+                }
+                Err(err) => {
+                    log::debug!("{}:{} ping to {:?}: {:?}", file!(), line!(), tx.dest, err);
+                }
+            }
+        }
+    }
+
+    fn do_ping_rx(&mut self, socket_id: SocketId) {
+        let Some(ping_socket) = self.ping_sockets.get_mut(&socket_id) else {
+            // The socket may have been dropped while sitting on self.pending_ping_rx.
+            return;
+        };
+
+        for (device_idx, handle) in &ping_socket.handles {
+            let smol_socket = self.devices[*device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::icmp::Socket>(*handle);
+
+            while smol_socket.can_recv() {
+                let page = match ping_socket.conn.alloc_page(ping_socket.subchannel_mask) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        assert_eq!(err, ErrorCode::NotReady);
+                        if !self.pending_ping_rx.contains(&socket_id) {
+                            self.pending_ping_rx.push_back(socket_id);
+                        }
+                        return;
+                    }
+                };
+
+                let (packet, src) = smol_socket.recv().unwrap();
+                // Echo requests with our identifier land here too.
+                let Some((seq, data)) =
+                    super::ping_socket::parse_echo_reply(ping_socket.ident, packet, &src)
+                else {
+                    continue;
+                };
+                let len = data.len().min(rt_api::net::PING_MAX_PAYLOAD);
+                page.bytes_mut()[..len].copy_from_slice(&data[..len]);
+
+                let mut msg = rt_api::net::ping_socket_msg(
+                    rt_api::net::CMD_PING_SOCKET_RX,
+                    socket_id.into(),
+                    page,
+                    len,
+                    &src.into(),
+                    seq,
+                );
+                msg.status = ErrorCode::Ok.into();
+                self.pending_completions.push_back(PendingCompletion {
+                    msg,
+                    endpoint_handle: ping_socket.conn.wait_handle(),
+                });
+            }
+        }
+    }
+
+    fn on_ping_socket_poll(&mut self, socket_id: SocketId) {
+        let Some(ping_socket) = self.ping_sockets.get(&socket_id) else {
+            return;
+        };
+
+        // Smoltcp wakers are one-shot.
+        for (device_idx, handle) in &ping_socket.handles {
+            let smol_socket = self.devices[*device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::icmp::Socket>(*handle);
+            smol_socket.register_recv_waker(&ping_socket.waker);
+            smol_socket.register_send_waker(&ping_socket.waker);
+        }
+
+        self.do_ping_tx(socket_id);
+        self.do_ping_rx(socket_id);
+    }
+
+    fn ping_socket_drop(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> io_channel::Msg {
+        let Some(socket_id) = self.ping_socket_from_msg(conn.wait_handle(), &sqe) else {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return sqe;
+        };
+
+        self.conn_ping_sockets
+            .get_mut(&conn.wait_handle())
+            .unwrap()
+            .remove(&socket_id);
+        self.drop_ping_socket(socket_id);
+
+        sqe.status = ErrorCode::Ok.into();
+        sqe
+    }
+
+    fn drop_ping_socket(&mut self, socket_id: SocketId) {
+        let ping_socket = self.ping_sockets.remove(&socket_id).unwrap();
+        log::debug!(
+            "{}:{} dropping ping socket 0x{:x} ident {}",
+            file!(),
+            line!(),
+            u64::from(ping_socket.id),
+            ping_socket.ident
+        );
+
+        for (device_idx, handle) in ping_socket.handles {
+            self.devices[device_idx].sockets.remove(handle);
+        }
+    }
+
+    fn dns_lookup(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut sqe: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        // Note: we need to get the page so that it is freed.
+        let page = match conn.get_page(sqe.payload.shared_pages()[0]) {
+            Ok(page) => page,
+            Err(err) => {
+                sqe.status = err.into();
+                return Some(sqe);
+            }
+        };
+
+        let name_len = sqe.payload.args_16()[1] as usize;
+        if name_len > rt_api::net::DNS_MAX_NAME_LEN
+            || sqe.flags as usize >= rt_api::net::IO_SUBCHANNELS
+        {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return Some(sqe);
+        }
+        let Ok(name) = core::str::from_utf8(&page.bytes()[..name_len]) else {
+            sqe.status = ErrorCode::InvalidArgument.into();
+            return Some(sqe);
+        };
+
+        let waiter = super::dns::Waiter {
+            msg: sqe,
+            conn: conn.clone(),
+        };
+        if let Some((waiter, result)) = self.resolver.lookup(name, waiter) {
+            return Some(Self::dns_lookup_response(waiter, result));
+        }
+
+        self.flush_dns();
+        None
+    }
+
+    fn dns_lookup_response(
+        waiter: super::dns::Waiter,
+        result: super::dns::LookupResult,
+    ) -> io_channel::Msg {
+        let mut msg = waiter.msg;
+        let addrs = match result {
+            Ok(addrs) => addrs,
+            Err(err) => {
+                msg.status = err.into();
+                return msg;
+            }
+        };
+
+        let subchannel_mask = rt_api::net::io_subchannel_mask(msg.flags as usize);
+        let page = match waiter.conn.alloc_page(subchannel_mask) {
+            Ok(page) => page,
+            Err(err) => {
+                log::debug!("{}:{} alloc_page failed: {:?}", file!(), line!(), err);
+                msg.status = ErrorCode::OutOfMemory.into();
+                return msg;
+            }
+        };
+        for (idx, addr) in addrs.iter().enumerate() {
+            rt_api::net::put_dns_addr(page.bytes_mut(), idx, addr);
+        }
+
+        msg.payload.shared_pages_mut()[0] = io_channel::IoPage::into_u16(page);
+        msg.payload.args_16_mut()[1] = addrs.len() as u16;
+        msg.status = ErrorCode::Ok.into();
+        msg
+    }
+
+    // Sends out the resolver's queries and completes finished lookups.
+    fn flush_dns(&mut self) {
+        for (server, packet) in self.resolver.take_outgoing() {
+            if let Err(err) = self.send_from_client_socket(
+                &self.dns_sockets.clone(),
+                server,
+                super::dns::DNS_PORT,
+                &packet,
+            ) {
+                // The query will be retransmitted.
+                log::debug!(
+                    "{}:{} DNS query to {:?}: {:?}",
+                    file!(),
+                    line!(),
+                    server,
+                    err
+                );
+            }
+        }
+
+        for (waiters, result) in self.resolver.take_completed() {
+            for waiter in waiters {
+                let endpoint_handle = waiter.conn.wait_handle();
+                self.pending_completions.push_back(PendingCompletion {
+                    msg: Self::dns_lookup_response(waiter, result.clone()),
+                    endpoint_handle,
+                });
+            }
+        }
+    }
+
+    fn on_dns_socket_poll(&mut self) {
+        let waker = self.dns_waker.as_ref().unwrap();
+        for (device_idx, handle) in &self.dns_sockets {
+            let smol_socket = self.devices[*device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::udp::Socket>(*handle);
+            smol_socket.register_recv_waker(waker);
+
+            while let Ok((packet, meta)) = smol_socket.recv() {
+                self.resolver.on_response(meta.endpoint.addr.into(), packet);
+            }
+        }
+
+        self.flush_dns();
+    }
+
+    fn flush_ntp(&mut self) {
+        for (server, packet) in self.time_sync.take_outgoing() {
+            if let Err(err) = self.send_from_client_socket(
+                &self.ntp_sockets.clone(),
+                server,
+                super::ntp::NTP_PORT,
+                &packet,
+            ) {
+                log::debug!(
+                    "{}:{} NTP query to {:?}: {:?}",
+                    file!(),
+                    line!(),
+                    server,
+                    err
+                );
+            }
+        }
+    }
+
+    fn on_ntp_socket_poll(&mut self) {
+        let waker = self.ntp_waker.as_ref().unwrap();
+        for (device_idx, handle) in &self.ntp_sockets {
+            let smol_socket = self.devices[*device_idx]
+                .sockets
+                .get_mut::<smoltcp::socket::udp::Socket>(*handle);
+            smol_socket.register_recv_waker(waker);
+
+            while let Ok((packet, meta)) = smol_socket.recv() {
+                self.time_sync
+                    .on_response(meta.endpoint.addr.into(), packet);
+            }
+        }
+
+        self.flush_ntp();
+    }
+
+    // Sends a packet through the socket (from open_client_sockets()) on the
+    // device the server is routed through.
+    fn send_from_client_socket(
+        &mut self,
+        sockets: &[(usize, smoltcp::iface::SocketHandle)],
+        server: IpAddr,
+        port: u16,
+        packet: &[u8],
+    ) -> Result<(), ErrorCode> {
+        let (device_idx, handle) = self
+            .find_route(&server)
+            .and_then(|(device_idx, _)| sockets.iter().find(|(idx, _)| *idx == device_idx))
+            .copied()
+            .ok_or(ErrorCode::NotFound)?;
+
+        let smol_socket = self.devices[device_idx]
+            .sockets
+            .get_mut::<smoltcp::socket::udp::Socket>(handle);
+        let endpoint = smoltcp::wire::IpEndpoint::new(server.into(), port);
+        smol_socket.send_slice(packet, endpoint).map_err(|err| {
+            log::debug!("{}:{} {:?}", file!(), line!(), err);
+            ErrorCode::NotReady
+        })
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let res = self.next_id;
+        self.next_id += 1;
+        res
+    }
+
+    // A nonblocking write in the client found no free page in the socket's
+    // subchannel: the message goes back when there is one.
+    fn socket_tx_wait(
+        &mut self,
+        conn: &Rc<io_channel::ServerConnection>,
+        mut msg: io_channel::Msg,
+    ) -> Option<io_channel::Msg> {
+        let subchannel_mask = match msg.flags {
+            rt_api::net::TX_WAIT_TCP => self
+                .tcp_socket_from_msg(conn.wait_handle(), &msg)
+                .ok()
+                .and_then(|socket_id| self.tcp_sockets.get(&socket_id))
+                .map(|moto_socket| moto_socket.subchannel_mask),
+            rt_api::net::TX_WAIT_UDP => self
+                .udp_socket_from_msg(conn.wait_handle(), &msg)
+                .and_then(|socket_id| self.udp_sockets.get(&socket_id))
+                .map(|udp_socket| udp_socket.subchannel_mask),
+            rt_api::net::TX_WAIT_LOCAL => self.local_sockets.subchannel_mask(conn, &msg),
+            _ => None,
+        };
+        // Nobody waits for a bad socket.
+        let subchannel_mask = subchannel_mask?;
+
+        msg.status = ErrorCode::Ok.into();
+        if conn.has_free_client_page(subchannel_mask) {
+            return Some(msg);
+        }
+        if !self.tx_waiters.iter().any(|(waiter_conn, waiter, _)| {
+            waiter_conn.wait_handle() == conn.wait_handle()
+                && waiter.handle == msg.handle
+                && waiter.flags == msg.flags
+        }) {
+            self.tx_waiters.push((conn.clone(), msg, subchannel_mask));
+        }
+        None
+    }
+
+    // Client TX pages are freed as sys-io is done with them, all over the place,
+    // so waiters are checked once per poll().
+    fn wake_tx_waiters(&mut self) {
+        self.tx_waiters.retain(|(conn, msg, subchannel_mask)| {
+            if !conn.has_free_client_page(*subchannel_mask) {
+                return true;
+            }
+            self.pending_completions.push_back(PendingCompletion {
+                msg: *msg,
+                endpoint_handle: conn.wait_handle(),
+            });
+            false
+        });
+    }
+
+    // Find the device to route through.
+    fn find_route(&self, ip_addr: &IpAddr) -> Option<(usize, IpAddr)> {
+        // First, look through local addresses.
+        if let Some(device_idx) = self.ip_addresses.get(ip_addr) {
+            if self.devices[*device_idx].is_up() {
+                return Some((*device_idx, *ip_addr));
+            }
+        }
+
+        self.routes.lookup(ip_addr, &self.devices)
+    }
+
+    fn device_idx_by_name(&self, name: &str) -> Result<usize, ErrorCode> {
+        self.devices
+            .iter()
+            .position(|dev| dev.name() == name)
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    fn route_info(&self, route: &Route) -> moto_sys_io::netcfg::RouteInfoV1 {
+        use moto_sys_io::netcfg::*;
+
+        let mut info = RouteInfoV1::new(
+            route.destination.ip(),
+            route.destination.prefix(),
+            route.gateway,
+            Some(self.devices[route.device_idx].name()),
+            route.metric,
+        );
+        info.flags |= match route.origin {
+            RouteOrigin::Config => RTF_CONFIG,
+            RouteOrigin::Admin => 0,
+            RouteOrigin::Autoconf => RTF_AUTOCONF,
+            RouteOrigin::Connected => RTF_CONNECTED,
+        };
+        info
+    }
+
+    // The destination of a route from the admin: a network (host bits are ignored).
+    fn route_destination(
+        cidr: &moto_sys_io::netcfg::IpCidrV1,
+    ) -> Result<ipnetwork::IpNetwork, ErrorCode> {
+        ipnetwork::IpNetwork::new(cidr.addr(), cidr.prefix_len)
+            .and_then(|net| ipnetwork::IpNetwork::new(net.network(), net.prefix()))
+            .map_err(|_| ErrorCode::InvalidArgument)
+    }
+
+    fn configure_firewall(
+        &mut self,
+        cmd: u16,
+        payload: &crate::runtime::net_cfg::FwPayload,
+    ) -> Result<moto_sys_io::netcfg::FwRules, ErrorCode> {
+        use moto_sys_io::netcfg::*;
+
+        let rule = &payload.rule;
+        let mut firewall = self.firewall.borrow_mut();
+        match cmd {
+            CMD_FW_LIST => return Ok(firewall.list(payload.idx, MAX_FW_RULES)),
+            CMD_FW_ADD => {
+                if let Some(ifname) = rule.ifname() {
+                    self.device_idx_by_name(ifname)?;
+                }
+                firewall.add(*rule, payload.idx)?;
+            }
+            CMD_FW_DEL => firewall.remove(rule.chain, payload.idx)?,
+            CMD_FW_SET_POLICY => firewall.set_policy(rule.chain, rule.action)?,
+            CMD_FW_FLUSH => firewall.flush(rule.chain)?,
+            _ => return Err(ErrorCode::InvalidArgument),
+        }
+        Ok(FwRules {
+            input_policy: FW_ALLOW,
+            output_policy: FW_ALLOW,
+            rules: Vec::new(),
+        })
+    }
+
+    fn configure_routes(
+        &mut self,
+        cmd: u16,
+        payload: &crate::runtime::net_cfg::RoutePayload,
+    ) -> Result<Vec<moto_sys_io::netcfg::RouteInfoV1>, ErrorCode> {
+        use moto_sys_io::netcfg::*;
+
+        let req = &payload.route;
+        let ifname = if req.ifname_len == 0 {
+            None
+        } else {
+            Some(req.ifname())
+        };
+
+        match cmd {
+            CMD_LIST_ROUTES => Ok(self
+                .routes
+                .all_routes(&self.devices)
+                .iter()
+                .skip(payload.start_idx)
+                .take(MAX_ROUTE_INFOS)
+                .map(|route| self.route_info(route))
+                .collect()),
+
+            CMD_GET_ROUTE => {
+                let dst = req.destination.addr();
+                let prefix_len = if dst.is_ipv4() { 32 } else { 128 };
+                if let Some(device_idx) = self.ip_addresses.get(&dst) {
+                    let device = &self.devices[*device_idx];
+                    if device.is_up() {
+                        let mut info =
+                            RouteInfoV1::new(dst, prefix_len, None, Some(device.name()), 0);
+                        info.source = IpCidrV1::new(dst, prefix_len);
+                        info.flags |= RTF_LOCAL;
+                        return Ok(vec![info]);
+                    }
+                }
+
+                let (route, local_addr) = self
+                    .routes
+                    .lookup_route(&dst, &self.devices)
+                    .ok_or(ErrorCode::NotFound)?;
+                let mut info = self.route_info(&route);
+                info.source = IpCidrV1::new(local_addr, prefix_len);
+                Ok(vec![info])
+            }
+
+            CMD_ADD_ROUTE => {
+                let destination = Self::route_destination(&req.destination)?;
+                let gateway = req.gateway();
+                if gateway.is_some_and(|gw| gw.is_ipv4() != destination.is_ipv4()) {
+                    return Err(ErrorCode::InvalidArgument);
+                }
+
+                // The gateway must be on-link; it also tells the device if none is given.
+                let on_link = |device: &NetDev, gw: &IpAddr| {
+                    device.ip_cidrs().iter().any(|(addr, prefix_len)| {
+                        ipnetwork::IpNetwork::new(*addr, *prefix_len)
+                            .is_ok_and(|net| net.contains(*gw))
+                    })
+                };
+                let device_idx = match (ifname, gateway) {
+                    (Some(ifname), _) => self.device_idx_by_name(ifname)?,
+                    (None, Some(gw)) => self
+                        .devices
+                        .iter()
+                        .position(|device| on_link(device, &gw))
+                        .ok_or(ErrorCode::InvalidArgument)?,
+                    (None, None) => return Err(ErrorCode::InvalidArgument),
+                };
+                if gateway.is_some_and(|gw| !on_link(&self.devices[device_idx], &gw)) {
+                    return Err(ErrorCode::InvalidArgument);
+                }
+
+                self.routes.add(Route::new(
+                    destination,
+                    gateway,
+                    device_idx,
+                    req.metric,
+                    RouteOrigin::Admin,
+                ))?;
+                self.sync_gateways(device_idx);
+                Ok(Vec::new())
+            }
+
+            CMD_DEL_ROUTE => {
+                let destination = Self::route_destination(&req.destination)?;
+                let gateway = req.gateway();
+                let device_idx = match ifname {
+                    Some(ifname) => Some(self.device_idx_by_name(ifname)?),
+                    None => None,
+                };
+
+                let removed = self
+                    .routes
+                    .remove(|route| {
+                        route.destination == destination
+                            && device_idx.map_or(true, |idx| idx == route.device_idx)
+                            && gateway.map_or(true, |gw| route.gateway == Some(gw))
+                    })
+                    .ok_or(ErrorCode::NotFound)?;
+                self.sync_gateways(removed.device_idx);
+                Ok(Vec::new())
+            }
+
+            _ => Err(ErrorCode::InvalidArgument),
+        }
+    }
+
+    fn list_interfaces(&self) -> Vec<moto_sys_io::netcfg::InterfaceInfoV1> {
+        use moto_sys_io::netcfg::*;
+
+        let mut result = Vec::with_capacity(self.devices.len());
+        for device in self.devices.iter().take(MAX_INTERFACES) {
+            let mut info = InterfaceInfoV1::default();
+            info.set_name(device.name());
+            info.mac = device.mac();
+            info.mtu = device.mtu();
+            info.stats = device.stats();
+            if device.is_up() {
+                info.flags |= IFF_UP;
+            }
+            if device.is_loopback() {
+                info.flags |= IFF_LOOPBACK;
+            }
+            for (addr, prefix_len) in device.ip_cidrs().iter().take(MAX_INTERFACE_ADDRS) {
+                info.addrs[info.num_addrs as usize] = IpCidrV1::new(*addr, *prefix_len);
+                info.num_addrs += 1;
+            }
+            result.push(info);
+        }
+
+        result
+    }
+
+    fn configure_interface(
+        &mut self,
+        cmd: u16,
+        payload: &crate::runtime::net_cfg::NetCfgPayload,
+    ) -> Result<(), ErrorCode> {
+        use moto_sys_io::netcfg::*;
+
+        let device_idx = self.device_idx_by_name(&payload.ifname)?;
+        let addr = payload.cidr.addr();
+
+        match cmd {
+            CMD_ADD_ADDR => {
+                let prefix_len = payload.cidr.prefix_len;
+                if ipnetwork::IpNetwork::new(addr, prefix_len).is_err()
+                    || addr.is_unspecified()
+                    || addr.is_multicast()
+                {
+                    return Err(ErrorCode::InvalidArgument);
+                }
+                if self.ip_addresses.contains_key(&addr) {
+                    return Err(ErrorCode::AlreadyInUse);
+                }
+                if !self.devices[device_idx].add_ip_addr(addr, prefix_len) {
+                    return Err(ErrorCode::OutOfMemory); // No room for more addresses.
+                }
+                self.ip_addresses.insert(addr, device_idx);
+            }
+            CMD_DEL_ADDR => {
+                if self.ip_addresses.get(&addr) != Some(&device_idx) {
+                    return Err(ErrorCode::NotFound);
+                }
+                // Sockets bound to the address stay, but won't get anything through.
+                self.devices[device_idx].remove_ip_addr(addr);
+                self.ip_addresses.remove(&addr);
+            }
+            CMD_SET_MTU => self.devices[device_idx].set_mtu(payload.mtu)?,
+            CMD_SET_LINK => self.devices[device_idx].set_up(payload.up),
+            _ => return Err(ErrorCode::InvalidArgument),
+        }
+
+        // Addresses and links are what routes resolve to.
+        self.routes.invalidate();
+        Ok(())
+    }
+
+    fn rx_buf_to_pc(
+        socket_id: SocketId,
+        endpoint_handle: SysHandle,
+        x_buf: RxBuf,
+        rx_seq: u64,
+    ) -> PendingCompletion {
+        let (io_page, sz) = (x_buf.page, x_buf.consumed);
+        let mut msg =
+            moto_runtime::rt_api::net::tcp_stream_rx_msg(socket_id.into(), io_page, sz, rx_seq);
+        msg.status = ErrorCode::Ok.into();
+
+        PendingCompletion {
+            msg,
+            endpoint_handle,
+        }
+    }
+
+    // Returns 'true' if the socket can do tx/rx.
+    fn on_tcp_listener_connected(&mut self, socket_id: SocketId) -> bool {
+        let moto_socket = self.tcp_sockets.get_mut(&socket_id).unwrap();
+        let device_idx = moto_socket.device_idx;
+        let smol_socket = self.devices[device_idx]
+            .sockets
+            .get_mut::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
+
+        smol_socket.set_nagle_enabled(false); // A good idea, generally.
+        smol_socket.set_ack_delay(None);
+        self.net_stats.tcp_passive_opens += 1;
+
+        let local_addr = super::smoltcp_helpers::socket_addr_from_endpoint(
+            smol_socket.local_endpoint().unwrap(),
+        );
+        assert_eq!(local_addr, moto_socket.listening_on.unwrap());
+        let remote_addr = super::smoltcp_helpers::socket_addr_from_endpoint(
+            smol_socket.remote_endpoint().unwrap(),
+        );
+
+        let listener_id = *moto_socket.listener_id.as_ref().unwrap();
+        let listener = self.tcp_listeners.get_mut(&listener_id).unwrap();
         let may_do_io = if let Some((mut msg, conn)) = listener.get_pending_accept() {
             assert!(listener.remove_listening_socket(socket_id));
             moto_socket.state = TcpState::ReadWrite;
@@ -1113,9 +3147,8 @@ impl NetSys {
             .sockets
             .get_mut::<smoltcp::socket::tcp::Socket>(moto_socket.handle);
 
-        // Without these, remotely dropped sockets may hang around indefinitely.
-        smol_socket.set_timeout(Some(smoltcp::time::Duration::from_millis(5_000)));
-        smol_socket.set_keep_alive(Some(smoltcp::time::Duration::from_millis(10_000)));
+        Self::set_smol_keepalive(smol_socket, Some(TCP_DEFAULT_KEEPALIVE));
+        moto_socket.keepalive = Some(TCP_DEFAULT_KEEPALIVE);
         smol_socket.set_nagle_enabled(false); // A good idea, generally.
         smol_socket.set_ack_delay(None);
 
@@ -1140,6 +3173,7 @@ impl NetSys {
     fn on_connect_failed(&mut self, socket_id: SocketId) {
         let moto_socket = self.tcp_sockets.get_mut(&socket_id).unwrap();
 
+        self.net_stats.tcp_failed_connects += 1;
         let mut cqe = moto_socket.connect_req.take().unwrap();
         // Note: we don't generate the state change event because of the explicit PC below.
         moto_socket.state = TcpState::Closed;
@@ -1404,7 +3438,21 @@ impl NetSys {
             } else {
                 break;
             };
-            self.on_tcp_socket_poll(socket_id);
+            if socket_id == self.dns_socket_id {
+                self.on_dns_socket_poll();
+            } else if socket_id == self.ntp_socket_id {
+                self.on_ntp_socket_poll();
+            } else if socket_id == self.slaac_socket_id {
+                self.on_slaac_socket_poll();
+            } else if self.udp_sockets.contains_key(&socket_id) {
+                self.on_udp_socket_poll(socket_id);
+            } else if self.ping_sockets.contains_key(&socket_id) {
+                self.on_ping_socket_poll(socket_id);
+            } else if self.closing_tcp_sockets.contains_key(&socket_id) {
+                self.on_closing_tcp_socket_poll(socket_id);
+            } else {
+                self.on_tcp_socket_poll(socket_id);
+            }
         }
         assert!(self.woken_sockets.borrow().is_empty());
     }
@@ -1502,6 +3550,7 @@ impl NetSys {
 
             moto_socket.rx_seq += 1;
             moto_socket.stats_rx_bytes += rx_buf.consumed as u64;
+            self.net_stats.tcp_rx_bytes += rx_buf.consumed as u64;
             self.pending_completions.push_back(Self::rx_buf_to_pc(
                 socket_id,
                 moto_socket.conn.wait_handle(),
@@ -1564,8 +3613,22 @@ impl NetSys {
                 break;
             };
 
-            match smol_socket.send_slice(tx_buf.bytes()) {
+            let mut bytes = tx_buf.bytes();
+            if let Some(limiter) = &mut moto_socket.tx_limiter {
+                let allowed = limiter.available();
+                if allowed == 0 {
+                    self.throttled_sockets.insert(socket_id, limiter.ready_at());
+                    moto_socket.tx_queue.push_front(tx_buf);
+                    break;
+                }
+                bytes = &bytes[..allowed.min(bytes.len())];
+            }
+
+            match smol_socket.send_slice(bytes) {
                 Ok(usize) => {
+                    if let Some(limiter) = &mut moto_socket.tx_limiter {
+                        limiter.consume(usize);
+                    }
                     tx_buf.consume(usize);
                     if tx_buf.is_consumed() {
                         // Client writes are completed in tcp_stream_write,
@@ -1627,6 +3690,12 @@ impl IoSubsystem for NetSys {
             rt_api::net::CMD_TCP_LISTENER_BIND => Ok(Some(self.tcp_listener_bind(conn, msg))),
             rt_api::net::CMD_TCP_LISTENER_ACCEPT => self.tcp_listener_accept(conn, msg),
             rt_api::net::CMD_TCP_LISTENER_DROP => self.tcp_listener_drop(conn, msg).map(|_| None),
+            rt_api::net::CMD_TCP_LISTENER_SET_OPTION => {
+                Ok(Some(self.tcp_listener_set_option(conn, msg)))
+            }
+            rt_api::net::CMD_TCP_LISTENER_GET_OPTION => {
+                Ok(Some(self.tcp_listener_get_option(conn, msg)))
+            }
             rt_api::net::CMD_TCP_STREAM_CONNECT => Ok(self.tcp_stream_connect(conn, msg)),
             rt_api::net::CMD_TCP_STREAM_TX => {
                 self.tcp_stream_write(conn, msg);
@@ -1640,6 +3709,34 @@ impl IoSubsystem for NetSys {
                 Ok(Some(self.tcp_stream_get_option(conn, msg)))
             }
             rt_api::net::CMD_TCP_STREAM_CLOSE => Ok(self.tcp_stream_close(conn, msg)),
+            rt_api::net::CMD_UDP_SOCKET_BIND => Ok(Some(self.udp_socket_bind(conn, msg))),
+            rt_api::net::CMD_UDP_SOCKET_TX => Ok(self.udp_socket_tx(conn, msg)),
+            rt_api::net::CMD_UDP_SOCKET_SET_OPTION => {
+                Ok(Some(self.udp_socket_set_option(conn, msg)))
+            }
+            rt_api::net::CMD_UDP_SOCKET_GET_OPTION => {
+                Ok(Some(self.udp_socket_get_option(conn, msg)))
+            }
+            rt_api::net::CMD_UDP_SOCKET_DROP => Ok(Some(self.udp_socket_drop(conn, msg))),
+            rt_api::net::CMD_DNS_LOOKUP => Ok(self.dns_lookup(conn, msg)),
+            rt_api::net::CMD_LOCAL_SOCKET_BIND => Ok(self.local_sockets.bind(conn, msg)),
+            rt_api::net::CMD_LOCAL_SOCKET_PAIR => Ok(self.local_sockets.pair(conn, msg)),
+            rt_api::net::CMD_LOCAL_LISTENER_ACCEPT => Ok(self.local_sockets.accept(conn, msg)),
+            rt_api::net::CMD_LOCAL_STREAM_CONNECT => Ok(self.local_sockets.connect(conn, msg)),
+            rt_api::net::CMD_LOCAL_SOCKET_TX => Ok(self.local_sockets.tx(conn, msg)),
+            rt_api::net::CMD_LOCAL_SOCKET_RX_ACK => {
+                self.local_sockets.rx_ack(conn, msg);
+                Ok(None)
+            }
+            rt_api::net::CMD_LOCAL_SOCKET_SHUTDOWN => Ok(self.local_sockets.shutdown(conn, msg)),
+            rt_api::net::CMD_LOCAL_SOCKET_DROP => Ok(self.local_sockets.drop_req(conn, msg)),
+            rt_api::net::CMD_PING_SOCKET_OPEN => Ok(Some(self.ping_socket_open(conn, msg))),
+            rt_api::net::CMD_PING_SOCKET_TX => {
+                self.ping_socket_tx(conn, msg);
+                Ok(None)
+            }
+            rt_api::net::CMD_PING_SOCKET_DROP => Ok(Some(self.ping_socket_drop(conn, msg))),
+            rt_api::net::CMD_SOCKET_TX_WAIT => Ok(self.socket_tx_wait(conn, msg)),
             _ => {
                 #[cfg(debug_assertions)]
                 log::debug!(
@@ -1662,12 +3759,39 @@ impl IoSubsystem for NetSys {
             }
         }
 
+        // Lingering sockets send from the client's pages, so they go too.
+        let lingering: Vec<SocketId> = self
+            .closing_tcp_sockets
+            .keys()
+            .filter(|socket_id| self.tcp_sockets[*socket_id].conn.wait_handle() == conn)
+            .copied()
+            .collect();
+        for socket_id in lingering {
+            self.drop_tcp_socket(socket_id);
+        }
+
         if let Some(listeners) = self.conn_tcp_listeners.remove(&conn) {
             for listener_id in listeners {
                 self.drop_tcp_listener(listener_id);
             }
         }
 
+        if let Some(udp_sockets) = self.conn_udp_sockets.remove(&conn) {
+            for socket_id in udp_sockets {
+                self.drop_udp_socket(socket_id);
+            }
+        }
+
+        if let Some(ping_sockets) = self.conn_ping_sockets.remove(&conn) {
+            for socket_id in ping_sockets {
+                self.drop_ping_socket(socket_id);
+            }
+        }
+
+        self.local_sockets.on_connection_drop(conn);
+        self.tx_waiters
+            .retain(|(waiter_conn, _, _)| waiter_conn.wait_handle() != conn);
+
         log::debug!("conn 0x{:x} dropped", conn.as_u64());
     }
 
@@ -1678,10 +3802,36 @@ impl IoSubsystem for NetSys {
             self.do_tcp_rx(socket_id); // May insert socket_id back into self.pending_tcp_rx.
         }
 
+        let mut pending_udp_rx: VecDeque<SocketId> = VecDeque::new();
+        core::mem::swap(&mut pending_udp_rx, &mut self.pending_udp_rx);
+        while let Some(socket_id) = pending_udp_rx.pop_front() {
+            self.do_udp_rx(socket_id); // May insert socket_id back into self.pending_udp_rx.
+        }
+
+        let mut pending_ping_rx: VecDeque<SocketId> = VecDeque::new();
+        core::mem::swap(&mut pending_ping_rx, &mut self.pending_ping_rx);
+        while let Some(socket_id) = pending_ping_rx.pop_front() {
+            self.do_ping_rx(socket_id); // May insert socket_id back into self.pending_ping_rx.
+        }
+
+        self.wake_throttled_sockets();
+
         // client writes (tcp_stream_write) wake sockets; make sure we
         // process them before polling devices.
         self.process_polled_sockets();
 
+        self.resolver.poll();
+        self.flush_dns();
+        self.time_sync.poll();
+        self.flush_ntp();
+        self.poll_slaac();
+        self.expire_closing_tcp_sockets();
+
+        self.local_sockets.poll();
+        if let Some(prev) = self.local_sockets.pop_completion() {
+            return Some(prev);
+        }
+
         if let Some(prev) = self.pending_completions.pop_front() {
             return Some(prev);
         }
@@ -1708,11 +3858,29 @@ impl IoSubsystem for NetSys {
             }
         }
 
+        self.wake_tx_waiters();
         self.pending_completions.pop_front()
     }
 
     fn wait_timeout(&mut self) -> Option<core::time::Duration> {
-        let mut timeout = None;
+        if self.local_sockets.has_completions() {
+            return Some(core::time::Duration::ZERO);
+        }
+
+        let mut timeout = self
+            .resolver
+            .next_deadline()
+            .into_iter()
+            .chain(self.time_sync.next_deadline())
+            .chain(
+                self.slaac_devices
+                    .iter()
+                    .filter_map(|slaac_dev| slaac_dev.next_solicitation),
+            )
+            .chain(self.closing_tcp_sockets.values().copied())
+            .chain(self.throttled_sockets.values().copied())
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(std::time::Instant::now()));
 
         for device_idx in 0..self.devices.len() {
             let dev = self.devices.get_mut(device_idx).unwrap();
@@ -1732,6 +3900,35 @@ impl IoSubsystem for NetSys {
     }
 
     fn get_stats(&mut self, msg: &crate::runtime::internal_queue::Msg) {
+        if let Ok(payload) = msg
+            .payload
+            .clone()
+            .downcast::<crate::runtime::io_stats::GetTimeSyncStatusPayload>()
+        {
+            *payload.result.lock(line!()) = self.time_sync.status();
+            return;
+        }
+
+        if let Ok(payload) = msg
+            .payload
+            .clone()
+            .downcast::<crate::runtime::io_stats::GetNetStatsPayload>()
+        {
+            let mut stats = self.net_stats;
+            stats.tcp_established = self
+                .tcp_sockets
+                .values()
+                .filter(|s| {
+                    matches!(
+                        s.state,
+                        TcpState::ReadWrite | TcpState::ReadOnly | TcpState::WriteOnly
+                    )
+                })
+                .count() as u64;
+            *payload.result.lock(line!()) = stats;
+            return;
+        }
+
         let num_results = moto_sys_io::stats::MAX_TCP_SOCKET_STATS.min(self.socket_ids.len());
 
         let payload = msg
@@ -1778,6 +3975,14 @@ impl IoSubsystem for NetSys {
 
             stats.tcp_state = moto_socket.state.try_into().unwrap();
             stats.smoltcp_state = smol_socket.state();
+            stats.rx_queue = smol_socket.recv_queue() as u32;
+            stats.tx_queue = smol_socket.send_queue() as u32;
+            stats.rx_bytes = moto_socket.stats_rx_bytes;
+            stats.tx_bytes = moto_socket.stats_tx_bytes;
+            stats.rtt_ms = smol_socket.rtt().total_millis() as u32;
+            stats.rtt_var_ms = smol_socket.rtt_deviation().total_millis() as u32;
+            stats.cwnd = smol_socket.congestion_window().min(u32::MAX as usize) as u32;
+            stats.retransmits = smol_socket.retransmitted_segments();
 
             results.push(stats);
             if results.len() == num_results {
@@ -1785,4 +3990,40 @@ impl IoSubsystem for NetSys {
             }
         }
     }
+
+    fn net_config(&mut self, msg: &crate::runtime::internal_queue::Msg) {
+        use moto_sys_io::netcfg::*;
+
+        if let Ok(payload) = msg
+            .payload
+            .clone()
+            .downcast::<crate::runtime::net_cfg::FwPayload>()
+        {
+            let result = self.configure_firewall(msg.cmd, &payload);
+            *payload.result.lock(line!()) = result;
+            return;
+        }
+
+        if let Ok(payload) = msg
+            .payload
+            .clone()
+            .downcast::<crate::runtime::net_cfg::RoutePayload>()
+        {
+            let result = self.configure_routes(msg.cmd, &payload);
+            *payload.result.lock(line!()) = result;
+            return;
+        }
+
+        let payload = msg
+            .payload
+            .clone()
+            .downcast::<crate::runtime::net_cfg::NetCfgPayload>()
+            .unwrap();
+
+        let result = match msg.cmd {
+            CMD_LIST_INTERFACES => Ok(self.list_interfaces()),
+            cmd => self.configure_interface(cmd, &payload).map(|_| Vec::new()),
+        };
+        *payload.result.lock(line!()) = result;
+    }
 }
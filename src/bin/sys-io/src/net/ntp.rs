@@ -0,0 +1,310 @@
+// An SNTP (RFC 4330) client that keeps the wall clock in sync with the configured
+// NTP servers. Large errors (e.g. at startup, or after the VM was paused) are
+// stepped; small ones are slewed away by the kernel (at 500 ppm, see
+// SysCpu::F_WALL_CLOCK_SLEW), so that the clock never jumps once synced.
+//
+// Like the DNS resolver, TimeSync sends no packets itself: NetSys sends the
+// queries it produces through a UDP socket and feeds it the responses. The clock
+// is adjusted via SysCpu::adjust_wall_clock() and SysCpu::slew_wall_clock(),
+// so sys-io needs CAP_SYS.
+
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant, SystemTime},
+};
+
+use moto_sys::SysCpu;
+use moto_sys_io::stats::TimeSyncStatusV1;
+
+pub(super) const NTP_PORT: u16 = 123;
+
+pub(super) const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(64);
+pub(super) const MIN_POLL_INTERVAL: Duration = Duration::from_secs(16);
+
+const RETRANSMIT_DELAY: Duration = Duration::from_secs(2);
+const ATTEMPTS_PER_SERVER: u32 = 2;
+
+// Offsets above this are stepped rather than slewed.
+const STEP_THRESHOLD: Duration = Duration::from_millis(128);
+
+// The clock is reported as unsynchronized after this many polls without an answer.
+const MAX_MISSED_POLLS: u32 = 4;
+
+const PACKET_LEN: usize = 48;
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+const MAX_STRATUM: u8 = 15;
+
+// Seconds from the NTP epoch (1900) to the UNIX epoch (1970).
+const UNIX_EPOCH_NTP_SECS: u64 = 2_208_988_800;
+
+struct Query {
+    server_idx: usize,
+    // Our transmit timestamp; the server echoes it back as the originate timestamp.
+    transmit_ts: u64,
+    sent_at: Instant,
+    attempt: u32,
+    retransmit_at: Instant,
+}
+
+struct Sample {
+    server: IpAddr,
+    stratum: u8,
+    offset_nsec: i64,
+    delay_nsec: u64,
+    received_at: Instant,
+    received_wall: SystemTime,
+}
+
+pub(super) struct TimeSync {
+    servers: Vec<IpAddr>,
+    poll_interval: Duration,
+
+    server_idx: usize, // The server to ask first in the next round.
+    query: Option<Query>,
+    servers_tried: usize, // In the current round.
+    next_poll: Instant,
+
+    last_sample: Option<Sample>,
+    outgoing: Vec<(IpAddr, Vec<u8>)>,
+}
+
+impl TimeSync {
+    pub fn new(servers: Vec<IpAddr>, poll_interval: Duration) -> Self {
+        Self {
+            servers,
+            poll_interval: poll_interval.max(MIN_POLL_INTERVAL),
+            server_idx: 0,
+            query: None,
+            servers_tried: 0,
+            next_poll: Instant::now(),
+            last_sample: None,
+            outgoing: Vec::new(),
+        }
+    }
+
+    pub fn servers(&self) -> &[IpAddr] {
+        &self.servers
+    }
+
+    pub fn on_response(&mut self, src: IpAddr, packet: &[u8]) {
+        let received_at = Instant::now();
+        let Some(query) = self.query.as_mut() else {
+            return;
+        };
+        if src != self.servers[query.server_idx] {
+            return;
+        }
+        let Some(response) = decode_response(packet) else {
+            return;
+        };
+        if response.originate_ts != query.transmit_ts {
+            return; // A late answer to a retransmitted query, or a spoofed one.
+        }
+
+        if response.stratum == 0 || response.stratum > MAX_STRATUM {
+            // A kiss-o'-death or an unsynchronized server: try the next one.
+            log::debug!("sys-io: NTP server {:?} refused the query", src);
+            query.attempt = ATTEMPTS_PER_SERVER;
+            query.retransmit_at = received_at;
+            return;
+        }
+
+        // Measure the round trip with the monotonic clock, so that adjustments
+        // made while the query was in flight do not skew the result.
+        let t1 = ntp_to_nanos(query.transmit_ts);
+        let t2 = ntp_to_nanos(response.receive_ts);
+        let t3 = ntp_to_nanos(response.transmit_ts);
+        let t4 = t1 + received_at.duration_since(query.sent_at).as_nanos() as i128;
+
+        let offset = ((t2 - t1) + (t3 - t4)) / 2;
+        let delay = ((t4 - t1) - (t3 - t2)).max(0);
+        let Ok(offset_nsec) = i64::try_from(offset) else {
+            log::warn!(
+                "sys-io: NTP server {:?} is off by {}ns; ignored",
+                src,
+                offset
+            );
+            return;
+        };
+
+        self.query = None;
+        self.servers_tried = 0;
+        self.next_poll = received_at + self.poll_interval;
+        self.last_sample = Some(Sample {
+            server: src,
+            stratum: response.stratum,
+            offset_nsec,
+            delay_nsec: delay as u64,
+            received_at,
+            received_wall: SystemTime::now(),
+        });
+
+        correct(offset_nsec);
+    }
+
+    // Sends and retransmits queries.
+    pub fn poll(&mut self) {
+        if self.servers.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+
+        if let Some(query) = &self.query {
+            if query.retransmit_at <= now {
+                let (server_idx, attempt) = (query.server_idx, query.attempt);
+                if attempt < ATTEMPTS_PER_SERVER {
+                    self.send_query(server_idx, attempt + 1, now);
+                } else {
+                    self.servers_tried += 1;
+                    self.server_idx = (server_idx + 1) % self.servers.len();
+                    if self.servers_tried < self.servers.len() {
+                        self.send_query(self.server_idx, 1, now);
+                    } else {
+                        log::warn!("sys-io: no NTP server answered");
+                        self.query = None;
+                        self.servers_tried = 0;
+                        self.next_poll = now + self.poll_interval;
+                    }
+                }
+            }
+        } else if self.next_poll <= now {
+            self.send_query(self.server_idx, 1, now);
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.servers.is_empty() {
+            return None;
+        }
+        Some(match &self.query {
+            Some(query) => query.retransmit_at,
+            None => self.next_poll,
+        })
+    }
+
+    // Queries to send, as (server, packet).
+    pub fn take_outgoing(&mut self) -> Vec<(IpAddr, Vec<u8>)> {
+        core::mem::take(&mut self.outgoing)
+    }
+
+    pub fn status(&self) -> TimeSyncStatusV1 {
+        let mut status = TimeSyncStatusV1 {
+            num_servers: self.servers.len() as u32,
+            poll_interval_secs: self.poll_interval.as_secs() as u32,
+            slew_remaining_nsec: SysCpu::wall_clock_slew_remaining().unwrap_or(0),
+            ..Default::default()
+        };
+
+        if let Some(sample) = &self.last_sample {
+            status.synced = sample.received_at.elapsed() < self.poll_interval * MAX_MISSED_POLLS;
+            status.stratum = sample.stratum;
+            status.server_addr = match sample.server {
+                IpAddr::V4(addr) => addr.to_ipv6_mapped().octets(),
+                IpAddr::V6(addr) => addr.octets(),
+            };
+            status.last_sync_nsec = sample
+                .received_wall
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_nanos() as u64);
+            status.last_offset_nsec = sample.offset_nsec;
+            status.round_trip_nsec = sample.delay_nsec;
+        }
+
+        status
+    }
+
+    fn send_query(&mut self, server_idx: usize, attempt: u32, now: Instant) {
+        let transmit_ts = nanos_to_ntp(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_nanos()),
+        );
+
+        let mut packet = vec![0_u8; PACKET_LEN];
+        packet[0] = (VERSION << 3) | MODE_CLIENT;
+        packet[40..48].copy_from_slice(&transmit_ts.to_be_bytes());
+        self.outgoing.push((self.servers[server_idx], packet));
+
+        self.query = Some(Query {
+            server_idx,
+            transmit_ts,
+            sent_at: now,
+            attempt,
+            retransmit_at: now + RETRANSMIT_DELAY,
+        });
+    }
+}
+
+fn correct(offset_nsec: i64) {
+    if offset_nsec.unsigned_abs() > STEP_THRESHOLD.as_nanos() as u64 {
+        log::info!("sys-io: NTP: stepping the clock by {}ns", offset_nsec);
+        if let Err(err) = SysCpu::slew_wall_clock(0) {
+            log::error!("sys-io: failed to stop slewing the wall clock: {:?}", err);
+        }
+        if let Err(err) = SysCpu::adjust_wall_clock(offset_nsec) {
+            log::error!("sys-io: failed to adjust the wall clock: {:?}", err);
+        }
+        return;
+    }
+
+    // The new offset was measured against the clock as already
+    // slewed, so it replaces whatever remained to be slewed.
+    if let Err(err) = SysCpu::slew_wall_clock(offset_nsec) {
+        log::error!("sys-io: failed to slew the wall clock: {:?}", err);
+    }
+}
+
+// NTP timestamps are 32.32 fixed point seconds since 1900.
+fn nanos_to_ntp(unix_nanos: u128) -> u64 {
+    let secs = (unix_nanos / 1_000_000_000) as u64 + UNIX_EPOCH_NTP_SECS;
+    let frac = ((unix_nanos % 1_000_000_000) << 32) / 1_000_000_000;
+    (secs << 32) | (frac as u64)
+}
+
+// Nanoseconds since 1900; the 2036 era rollover is not handled.
+fn ntp_to_nanos(ts: u64) -> i128 {
+    let secs = (ts >> 32) as i128;
+    let frac = (ts & 0xffff_ffff) as i128;
+    secs * 1_000_000_000 + ((frac * 1_000_000_000) >> 32)
+}
+
+struct Response {
+    stratum: u8,
+    originate_ts: u64,
+    receive_ts: u64,
+    transmit_ts: u64,
+}
+
+fn read_u64(packet: &[u8], pos: usize) -> u64 {
+    u64::from_be_bytes(packet[pos..(pos + 8)].try_into().unwrap())
+}
+
+fn decode_response(packet: &[u8]) -> Option<Response> {
+    if packet.len() < PACKET_LEN {
+        return None;
+    }
+    let leap = packet[0] >> 6;
+    let version = (packet[0] >> 3) & 7;
+    let mode = packet[0] & 7;
+    if mode != MODE_SERVER || !(1..=VERSION).contains(&version) {
+        return None;
+    }
+
+    let response = Response {
+        stratum: packet[1],
+        originate_ts: read_u64(packet, 24),
+        receive_ts: read_u64(packet, 32),
+        transmit_ts: read_u64(packet, 40),
+    };
+    if response.transmit_ts == 0 {
+        return None;
+    }
+    if leap == LEAP_UNSYNCHRONIZED && response.stratum != 0 {
+        return None; // Kiss-o'-death packets also have LI == 3.
+    }
+
+    Some(response)
+}
@@ -0,0 +1,136 @@
+// Unprivileged ICMP echo ("ping") sockets, as in Linux: applications send and
+// receive echo payloads, and sys-io owns the identifiers and builds the packets,
+// so one process can't see or spoof another's pings.
+
+use std::{collections::VecDeque, net::IpAddr, rc::Rc};
+
+use moto_ipc::io_channel;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr, IpAddress};
+
+use super::socket::SocketId;
+
+// Smoltcp ICMP buffers, per device.
+const RX_PACKETS: usize = 16;
+const RX_BYTES: usize = 16 * 1024;
+const TX_PACKETS: usize = 8;
+const TX_BYTES: usize = 8 * 1024;
+
+// An echo request from the application waiting for room in the smoltcp socket.
+pub(super) struct PingTx {
+    pub page: io_channel::IoPage,
+    pub len: usize,
+    pub dest: IpAddr,
+    pub seq: u16,
+    pub device_idx: usize,
+}
+
+pub(super) struct PingSocket {
+    pub id: SocketId,
+    pub conn: Rc<io_channel::ServerConnection>,
+    pub ident: u16,
+
+    // Smoltcp sockets, as (device_idx, handle): one on each device.
+    pub handles: Vec<(usize, smoltcp::iface::SocketHandle)>,
+
+    pub waker: std::task::Waker,
+
+    pub tx_queue: VecDeque<PingTx>,
+
+    // See moto_ipc::io_channel::ServerConnection::alloc_page().
+    pub subchannel_mask: u64,
+}
+
+impl PingSocket {
+    pub fn handle_on(&self, device_idx: usize) -> Option<smoltcp::iface::SocketHandle> {
+        self.handles
+            .iter()
+            .find(|(idx, _)| *idx == device_idx)
+            .map(|(_, handle)| *handle)
+    }
+}
+
+// The ICMP message, as smoltcp ICMP sockets send them. Smoltcp re-parses it
+// and emits it with the source address of the route, so checksums are its job.
+pub(super) fn echo_request(ident: u16, seq_no: u16, data: &[u8], dest: &IpAddr) -> Vec<u8> {
+    match dest {
+        IpAddr::V4(_) => {
+            let repr = Icmpv4Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            };
+            let mut buf = vec![0_u8; repr.buffer_len()];
+            repr.emit(
+                &mut Icmpv4Packet::new_unchecked(&mut buf[..]),
+                &ChecksumCapabilities::ignored(),
+            );
+            buf
+        }
+        IpAddr::V6(addr) => {
+            let repr = Icmpv6Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            };
+            let mut buf = vec![0_u8; repr.buffer_len()];
+            // The source address only matters for the checksum.
+            let addr = IpAddress::Ipv6((*addr).into());
+            repr.emit(
+                &addr,
+                &addr,
+                &mut Icmpv6Packet::new_unchecked(&mut buf[..]),
+                &ChecksumCapabilities::ignored(),
+            );
+            buf
+        }
+    }
+}
+
+// Returns the sequence number and the payload of an echo reply to `ident`.
+// Smoltcp has verified the checksum by now.
+pub(super) fn parse_echo_reply<'a>(
+    ident: u16,
+    packet: &'a [u8],
+    src: &IpAddress,
+) -> Option<(u16, &'a [u8])> {
+    match src {
+        IpAddress::Ipv4(_) => {
+            let packet = Icmpv4Packet::new_checked(packet).ok()?;
+            match Icmpv4Repr::parse(&packet, &ChecksumCapabilities::ignored()).ok()? {
+                Icmpv4Repr::EchoReply {
+                    ident: reply_ident,
+                    seq_no,
+                    data,
+                } if reply_ident == ident => Some((seq_no, data)),
+                _ => None,
+            }
+        }
+        IpAddress::Ipv6(_) => {
+            let packet = Icmpv6Packet::new_checked(packet).ok()?;
+            match Icmpv6Repr::parse(src, src, &packet, &ChecksumCapabilities::ignored()).ok()? {
+                Icmpv6Repr::EchoReply {
+                    ident: reply_ident,
+                    seq_no,
+                    data,
+                } if reply_ident == ident => Some((seq_no, data)),
+                _ => None,
+            }
+        }
+    }
+}
+
+pub(super) fn new_smoltcp_socket() -> smoltcp::socket::icmp::Socket<'static> {
+    use smoltcp::socket::icmp;
+
+    let rx_buffer = icmp::PacketBuffer::new(
+        vec![icmp::PacketMetadata::EMPTY; RX_PACKETS],
+        vec![0; RX_BYTES],
+    );
+    let tx_buffer = icmp::PacketBuffer::new(
+        vec![icmp::PacketMetadata::EMPTY; TX_PACKETS],
+        vec![0; TX_BYTES],
+    );
+
+    icmp::Socket::new(rx_buffer, tx_buffer)
+}
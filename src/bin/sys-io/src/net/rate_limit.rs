@@ -0,0 +1,68 @@
+// Egress rate limiting: sockets with a TX rate limit (see TCP_OPTION_TX_RATE_LIMIT
+// and UDP_OPTION_TX_RATE_LIMIT) send through a token bucket. A UDP datagram cannot
+// be split, so a send may overdraw the bucket; the socket then waits until the
+// debt is paid off, which keeps the average rate right.
+
+use std::time::{Duration, Instant};
+
+// How much unused allowance a socket can save up for a burst.
+const BURST_TIME: Duration = Duration::from_millis(100);
+
+// ...but at least a full-sized packet.
+const MIN_BURST: u64 = 1500;
+
+pub(super) struct TokenBucket {
+    rate: u64, // Bytes per second.
+    burst: i64,
+    tokens: i64, // Negative if overdrawn.
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        assert!(rate > 0);
+        let burst = (rate as u128 * BURST_TIME.as_nanos() / 1_000_000_000)
+            .clamp(MIN_BURST as u128, i64::MAX as u128) as i64;
+
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            updated: Instant::now(),
+        }
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        let new_tokens = elapsed.as_nanos() * self.rate as u128 / 1_000_000_000;
+        if new_tokens == 0 {
+            return; // Don't lose the fraction.
+        }
+        self.tokens = (self.tokens as i128 + new_tokens as i128).min(self.burst as i128) as i64;
+        self.updated = now;
+    }
+
+    // How many bytes can be sent now.
+    pub fn available(&mut self) -> usize {
+        self.refill(Instant::now());
+        self.tokens.max(0) as usize
+    }
+
+    pub fn consume(&mut self, bytes: usize) {
+        self.tokens = self.tokens.saturating_sub(bytes as i64);
+    }
+
+    // When available() becomes non-zero.
+    pub fn ready_at(&self) -> Instant {
+        if self.tokens > 0 {
+            return self.updated;
+        }
+        let deficit = (1 - self.tokens as i128) as u128;
+        let nanos = (deficit * 1_000_000_000).div_ceil(self.rate as u128);
+        self.updated + Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+    }
+}
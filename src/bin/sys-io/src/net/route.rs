@@ -0,0 +1,229 @@
+// The routing table: picks the device, and the local address, to reach a
+// destination through. Routes come from sys-net.toml, from the admin (see
+// moto_sys_io::netcfg), and from IPv6 router advertisements; every address
+// on a device also makes its network reachable directly ("connected" routes).
+//
+// Smoltcp picks the gateway once the device is chosen, from its own per-device
+// route table; NetSys mirrors the routes of each device there.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+
+use super::netdev::NetDev;
+
+pub(super) const MAX_ROUTES: usize = 64;
+
+// Linux uses the same metric for routes from router advertisements, so that
+// routes configured statically win.
+pub(super) const AUTOCONF_METRIC: u32 = 1024;
+
+const CACHE_CAPACITY: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum RouteOrigin {
+    Config,
+    Admin,
+    Autoconf,
+    Connected, // Never stored: derived from device addresses.
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Route {
+    pub destination: IpNetwork,
+    pub gateway: Option<IpAddr>, // None: on-link.
+    pub device_idx: usize,
+    pub metric: u32,
+    pub origin: RouteOrigin,
+}
+
+impl Route {
+    // The destination with the host bits cleared, so that e.g. 10.0.0.1/8 and
+    // 10.0.0.0/8 are the same route.
+    pub fn new(
+        destination: IpNetwork,
+        gateway: Option<IpAddr>,
+        device_idx: usize,
+        metric: u32,
+        origin: RouteOrigin,
+    ) -> Self {
+        let destination = IpNetwork::new(destination.network(), destination.prefix()).unwrap();
+        Self {
+            destination,
+            gateway,
+            device_idx,
+            metric,
+            origin,
+        }
+    }
+
+    fn same_as(&self, other: &Route) -> bool {
+        self.destination == other.destination
+            && self.gateway == other.gateway
+            && self.device_idx == other.device_idx
+            && self.metric == other.metric
+    }
+}
+
+pub(super) struct RoutingTable {
+    routes: Vec<Route>,
+
+    // Destination -> (device_idx, local address). Any change to routes, addresses,
+    // or links makes cached lookups stale, so whoever changes them clears it.
+    cache: RefCell<HashMap<IpAddr, Option<(usize, IpAddr)>>>,
+}
+
+impl RoutingTable {
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn invalidate(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    pub fn add(&mut self, route: Route) -> Result<(), moto_sys::ErrorCode> {
+        if self.routes.iter().any(|r| r.same_as(&route)) {
+            return Err(moto_sys::ErrorCode::AlreadyInUse);
+        }
+        if self.routes.len() >= MAX_ROUTES {
+            return Err(moto_sys::ErrorCode::OutOfMemory);
+        }
+        self.routes.push(route);
+        self.invalidate();
+        Ok(())
+    }
+
+    // Removes the first route matching `pred`.
+    pub fn remove<F: Fn(&Route) -> bool>(&mut self, pred: F) -> Option<Route> {
+        let idx = self.routes.iter().position(pred)?;
+        self.invalidate();
+        Some(self.routes.remove(idx))
+    }
+
+    // All routes, including connected ones.
+    pub fn all_routes(&self, devices: &[NetDev]) -> Vec<Route> {
+        let mut result = Self::connected_routes(devices);
+        result.extend(self.routes.iter().cloned());
+        result
+    }
+
+    fn connected_routes(devices: &[NetDev]) -> Vec<Route> {
+        let mut result = Vec::new();
+        for (device_idx, device) in devices.iter().enumerate() {
+            for (addr, prefix_len) in device.ip_cidrs() {
+                let Ok(network) = IpNetwork::new(addr, prefix_len) else {
+                    continue;
+                };
+                let route = Route::new(network, None, device_idx, 0, RouteOrigin::Connected);
+                if !result.iter().any(|r: &Route| r.same_as(&route)) {
+                    result.push(route);
+                }
+            }
+        }
+        result
+    }
+
+    // The gateways smoltcp should know about on a device: for each destination,
+    // the best one.
+    pub fn device_gateways(&self, device_idx: usize) -> Vec<(IpNetwork, IpAddr)> {
+        let mut best: Vec<&Route> = Vec::new();
+        for route in &self.routes {
+            if route.device_idx != device_idx || route.gateway.is_none() {
+                continue;
+            }
+            match best.iter_mut().find(|r| r.destination == route.destination) {
+                Some(prev) if prev.metric > route.metric => *prev = route,
+                Some(_) => {}
+                None => best.push(route),
+            }
+        }
+        best.iter()
+            .map(|r| (r.destination, r.gateway.unwrap()))
+            .collect()
+    }
+
+    pub fn lookup(&self, dst: &IpAddr, devices: &[NetDev]) -> Option<(usize, IpAddr)> {
+        if let Some(cached) = self.cache.borrow().get(dst) {
+            return *cached;
+        }
+
+        let result = self
+            .lookup_route(dst, devices)
+            .map(|(route, local_addr)| (route.device_idx, local_addr));
+
+        let mut cache = self.cache.borrow_mut();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(*dst, result);
+        result
+    }
+
+    // Longest prefix first, then the lowest metric; on-link routes win ties.
+    // Uncached: lookup() is for the data path.
+    pub fn lookup_route(&self, dst: &IpAddr, devices: &[NetDev]) -> Option<(Route, IpAddr)> {
+        let connected = Self::connected_routes(devices);
+        let mut best: Option<(&Route, IpAddr)> = None;
+        for route in connected.iter().chain(self.routes.iter()) {
+            if !route.destination.contains(*dst) || !devices[route.device_idx].is_up() {
+                continue;
+            }
+            if let Some((prev, _)) = best {
+                let better = (
+                    route.destination.prefix(),
+                    std::cmp::Reverse(route.metric),
+                    route.gateway.is_none(),
+                ) > (
+                    prev.destination.prefix(),
+                    std::cmp::Reverse(prev.metric),
+                    prev.gateway.is_none(),
+                );
+                if !better {
+                    continue;
+                }
+            }
+            let next_hop = route.gateway.unwrap_or(*dst);
+            if let Some(local_addr) = source_addr(&devices[route.device_idx], dst, &next_hop) {
+                best = Some((route, local_addr));
+            }
+        }
+
+        best.map(|(route, local_addr)| (route.clone(), local_addr))
+    }
+}
+
+fn is_link_local(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => addr.is_link_local(),
+        IpAddr::V6(addr) => super::slaac::is_link_local(addr),
+    }
+}
+
+// The local address to send from: one on the same network as the next hop if
+// there is one; otherwise one of the same family and scope as the destination.
+fn source_addr(device: &NetDev, dst: &IpAddr, next_hop: &IpAddr) -> Option<IpAddr> {
+    let cidrs: Vec<(IpAddr, u8)> = device
+        .ip_cidrs()
+        .into_iter()
+        .filter(|(addr, _)| addr.is_ipv4() == dst.is_ipv4())
+        .collect();
+
+    cidrs
+        .iter()
+        .find(|(addr, prefix_len)| {
+            IpNetwork::new(*addr, *prefix_len).is_ok_and(|net| net.contains(*next_hop))
+                && is_link_local(addr) == is_link_local(dst)
+        })
+        .or_else(|| {
+            cidrs
+                .iter()
+                .find(|(addr, _)| is_link_local(addr) == is_link_local(dst))
+        })
+        .map(|(addr, _)| *addr)
+}
@@ -0,0 +1,177 @@
+// IPv6 stateless address autoconfiguration (RFC 4862), the parts smoltcp
+// doesn't do: link-local addresses, router solicitations, and turning router
+// advertisements into addresses and default routes. Neighbor discovery proper
+// is done by smoltcp.
+//
+// Duplicate address detection is not done: interface IDs are derived from MACs,
+// which are unique in the setups we run in.
+
+use std::net::Ipv6Addr;
+use std::time::{Duration, Instant};
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::wire::{
+    Icmpv6Packet, Icmpv6Repr, IpAddress, IpProtocol, Ipv6Address, Ipv6Packet, Ipv6Repr,
+    NdiscPrefixInfoFlags, NdiscRepr, RawHardwareAddress,
+};
+
+// RFC 4861, section 10.
+pub(super) const MAX_RTR_SOLICITATIONS: u32 = 3;
+pub(super) const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+
+const ALL_ROUTERS: Ipv6Address = Ipv6Address::LINK_LOCAL_ALL_ROUTERS;
+const NDISC_HOP_LIMIT: u8 = 255;
+
+// Raw socket buffers: only ICMPv6 lands here.
+const RX_PACKETS: usize = 8;
+const RX_BYTES: usize = 4096;
+const TX_PACKETS: usize = 2;
+const TX_BYTES: usize = 256;
+
+// Per-device autoconfiguration state.
+pub(super) struct SlaacDevice {
+    pub device_idx: usize,
+    pub handle: SocketHandle, // The raw ICMPv6 socket.
+    pub mac: [u8; 6],
+    pub link_local: Ipv6Addr,
+    pub global_addr: Option<Ipv6Addr>,
+    pub router: Option<Ipv6Addr>, // The default router.
+
+    // Router solicitations are sent until a router advertises itself.
+    pub solicitations_sent: u32,
+    pub next_solicitation: Option<Instant>,
+}
+
+// What a router advertisement tells us.
+pub(super) struct RouterAdvert {
+    pub router: Ipv6Addr,
+    pub router_lifetime: Duration, // Zero: not a default router.
+    // An on-link /64 prefix to autoconfigure an address in, with its valid lifetime.
+    pub prefix: Option<(Ipv6Addr, Duration)>,
+}
+
+// Modified EUI-64 (RFC 4291, appendix A).
+fn interface_id(mac: &[u8; 6]) -> [u8; 8] {
+    [
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]
+}
+
+// The address in the /64 prefix with the interface's ID.
+pub(super) fn address_in_prefix(prefix: &Ipv6Addr, mac: &[u8; 6]) -> Ipv6Addr {
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(&interface_id(mac));
+    Ipv6Addr::from(octets)
+}
+
+pub(super) fn link_local_address(mac: &[u8; 6]) -> Ipv6Addr {
+    address_in_prefix(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), mac)
+}
+
+pub(super) fn is_link_local(addr: &Ipv6Addr) -> bool {
+    (addr.segments()[0] & 0xffc0) == 0xfe80
+}
+
+// A complete IPv6 packet, as raw sockets send them.
+pub(super) fn router_solicitation(src: &Ipv6Addr, mac: &[u8; 6]) -> Vec<u8> {
+    let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit {
+        lladdr: Some(RawHardwareAddress::from_bytes(mac)),
+    });
+    let ip_repr = Ipv6Repr {
+        src_addr: Ipv6Address::from(*src),
+        dst_addr: ALL_ROUTERS,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: NDISC_HOP_LIMIT,
+    };
+
+    let mut buf = vec![0_u8; ip_repr.buffer_len() + icmp_repr.buffer_len()];
+    let mut ip_packet = Ipv6Packet::new_unchecked(&mut buf[..]);
+    ip_repr.emit(&mut ip_packet);
+    icmp_repr.emit(
+        &IpAddress::Ipv6(ip_repr.src_addr),
+        &IpAddress::Ipv6(ip_repr.dst_addr),
+        &mut Icmpv6Packet::new_unchecked(ip_packet.payload_mut()),
+        &ChecksumCapabilities::default(),
+    );
+
+    buf
+}
+
+// Parses a complete IPv6 packet, as raw sockets receive them.
+pub(super) fn parse_router_advert(packet: &[u8]) -> Option<RouterAdvert> {
+    let ip_packet = Ipv6Packet::new_checked(packet).ok()?;
+    let ip_repr = Ipv6Repr::parse(&ip_packet).ok()?;
+    // Routers advertise from link-local addresses with the maximum hop limit,
+    // so that advertisements can't come from off-link.
+    if ip_repr.next_header != IpProtocol::Icmpv6 || ip_repr.hop_limit != NDISC_HOP_LIMIT {
+        return None;
+    }
+    let router: Ipv6Addr = ip_repr.src_addr.into();
+    if !is_link_local(&router) {
+        return None;
+    }
+
+    let icmp_packet = Icmpv6Packet::new_checked(ip_packet.payload()).ok()?;
+    let icmp_repr = Icmpv6Repr::parse(
+        &IpAddress::Ipv6(ip_repr.src_addr),
+        &IpAddress::Ipv6(ip_repr.dst_addr),
+        &icmp_packet,
+        &ChecksumCapabilities::default(),
+    )
+    .ok()?;
+
+    let Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+        router_lifetime,
+        prefix_info,
+        ..
+    }) = icmp_repr
+    else {
+        return None;
+    };
+
+    let prefix = prefix_info.and_then(|info| {
+        if info.prefix_len == 64
+            && info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+            && info.valid_lifetime > smoltcp::time::Duration::ZERO
+        {
+            Some((info.prefix.into(), info.valid_lifetime.into()))
+        } else {
+            None
+        }
+    });
+
+    Some(RouterAdvert {
+        router,
+        router_lifetime: router_lifetime.into(),
+        prefix,
+    })
+}
+
+pub(super) fn new_smoltcp_socket() -> smoltcp::socket::raw::Socket<'static> {
+    use smoltcp::socket::raw;
+
+    let rx_buffer = raw::PacketBuffer::new(
+        vec![raw::PacketMetadata::EMPTY; RX_PACKETS],
+        vec![0; RX_BYTES],
+    );
+    let tx_buffer = raw::PacketBuffer::new(
+        vec![raw::PacketMetadata::EMPTY; TX_PACKETS],
+        vec![0; TX_BYTES],
+    );
+
+    raw::Socket::new(
+        smoltcp::wire::IpVersion::Ipv6,
+        IpProtocol::Icmpv6,
+        rx_buffer,
+        tx_buffer,
+    )
+}
@@ -55,11 +55,29 @@ pub(super) struct MotoSocket {
     // we create a replacement listening socket, and set this flag to true.
     pub replacement_listener_created: bool,
 
+    // Smoltcp knows only the idle time and the overall timeout, so we keep
+    // the parameters the client gave us.
+    pub keepalive: Option<TcpKeepalive>,
+
+    // SO_LINGER, as in Rust's TcpStream::set_linger(): None means the socket
+    // closes gracefully in the background when the client drops it.
+    pub linger: Option<std::time::Duration>,
+
+    // See TCP_OPTION_TX_RATE_LIMIT.
+    pub tx_limiter: Option<super::rate_limit::TokenBucket>,
+
     // stats
     pub stats_rx_bytes: u64, // Bytes sent to the application.
     pub stats_tx_bytes: u64, // Bytes received from the application.
 }
 
+#[derive(Clone, Copy)]
+pub(super) struct TcpKeepalive {
+    pub time_ms: u32,     // Idle time before the probe.
+    pub interval_ms: u32, // How long to wait for an answer, per retry.
+    pub retries: u32,
+}
+
 impl Drop for MotoSocket {
     fn drop(&mut self) {
         assert!(self.listener_id.is_none());
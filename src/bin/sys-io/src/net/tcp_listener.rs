@@ -38,6 +38,10 @@ pub struct TcpListener {
 
     // Pure listening sockets. We need to track them to drop when the listener is dropped.
     listening_sockets: HashSet<SocketId>,
+
+    // Buffer sizes of the sockets the listener accepts connections on.
+    rx_buf_size: usize,
+    tx_buf_size: usize,
 }
 
 impl Drop for TcpListener {
@@ -51,13 +55,20 @@ impl Drop for TcpListener {
 }
 
 impl TcpListener {
-    pub fn new(conn: std::rc::Rc<io_channel::ServerConnection>, socket_addr: SocketAddr) -> Self {
+    pub fn new(
+        conn: std::rc::Rc<io_channel::ServerConnection>,
+        socket_addr: SocketAddr,
+        rx_buf_size: usize,
+        tx_buf_size: usize,
+    ) -> Self {
         Self {
             conn,
             socket_addr,
             pending_accepts: VecDeque::new(),
             pending_sockets: VecDeque::new(),
             listening_sockets: HashSet::new(),
+            rx_buf_size,
+            tx_buf_size,
         }
     }
 
@@ -73,6 +84,22 @@ impl TcpListener {
         &self.socket_addr
     }
 
+    pub fn rx_buf_size(&self) -> usize {
+        self.rx_buf_size
+    }
+
+    pub fn tx_buf_size(&self) -> usize {
+        self.tx_buf_size
+    }
+
+    pub fn set_rx_buf_size(&mut self, size: usize) {
+        self.rx_buf_size = size;
+    }
+
+    pub fn set_tx_buf_size(&mut self, size: usize) {
+        self.tx_buf_size = size;
+    }
+
     pub fn add_pending_socket(&mut self, id: SocketId, addr: SocketAddr) {
         assert!(self.listening_sockets.remove(&id));
         self.pending_sockets.push_back((id, addr));
@@ -114,6 +141,10 @@ impl TcpListener {
         self.listening_sockets.remove(&id)
     }
 
+    pub fn listening_sockets(&self) -> Vec<SocketId> {
+        self.listening_sockets.iter().copied().collect()
+    }
+
     pub fn take_listening_sockets(&mut self) -> HashSet<SocketId> {
         let mut res = HashSet::new();
         core::mem::swap(&mut res, &mut self.listening_sockets);
@@ -0,0 +1,77 @@
+use std::{collections::VecDeque, net::SocketAddr, rc::Rc};
+
+use moto_ipc::io_channel;
+
+use super::socket::SocketId;
+
+// Smoltcp UDP buffers, per device.
+const RX_PACKETS: usize = 32;
+const RX_BYTES: usize = 32 * 1024;
+const TX_PACKETS: usize = 16;
+const TX_BYTES: usize = 16 * 1024;
+
+// A datagram from the application waiting for room in the smoltcp socket.
+pub(super) struct UdpTx {
+    pub page: io_channel::IoPage,
+    pub len: usize,
+    pub dest: SocketAddr,
+    pub device_idx: usize,
+}
+
+pub(super) struct UdpSocket {
+    pub id: SocketId,
+    pub conn: Rc<io_channel::ServerConnection>,
+
+    // What the socket is bound to: the IP is what the user gave us (can be 0.0.0.0),
+    // the port is the actual one (never zero).
+    pub local_addr: SocketAddr,
+
+    // Smoltcp sockets, as (device_idx, handle). A socket bound to a specific IP lives
+    // on the device with the IP; a socket bound to an unspecified IP lives on all devices.
+    pub handles: Vec<(usize, smoltcp::iface::SocketHandle)>,
+
+    pub waker: std::task::Waker,
+
+    pub tx_queue: VecDeque<UdpTx>,
+
+    // See moto_ipc::io_channel::ServerConnection::alloc_page().
+    pub subchannel_mask: u64,
+
+    // IPv4 multicast groups the socket joined, as (device_idx, group).
+    pub multicast_groups: Vec<(usize, std::net::Ipv4Addr)>,
+
+    // See UDP_OPTION_TX_RATE_LIMIT.
+    pub tx_limiter: Option<super::rate_limit::TokenBucket>,
+}
+
+impl UdpSocket {
+    pub fn handle_on(&self, device_idx: usize) -> Option<smoltcp::iface::SocketHandle> {
+        self.handles
+            .iter()
+            .find(|(idx, _)| *idx == device_idx)
+            .map(|(_, handle)| *handle)
+    }
+
+    // Whether a socket bound to `addr` would receive datagrams meant for this one.
+    pub fn conflicts_with(&self, addr: &SocketAddr) -> bool {
+        self.local_addr.port() == addr.port()
+            && (self.local_addr.ip().is_unspecified()
+                || addr.ip().is_unspecified()
+                || self.local_addr.ip() == addr.ip())
+    }
+}
+
+pub(super) fn new_smoltcp_socket() -> smoltcp::socket::udp::Socket<'static> {
+    use smoltcp::socket::udp;
+
+    let rx_buffer = udp::PacketBuffer::new(
+        vec![udp::PacketMetadata::EMPTY; RX_PACKETS],
+        vec![0; RX_BYTES],
+    );
+    let tx_buffer = udp::PacketBuffer::new(
+        vec![udp::PacketMetadata::EMPTY; TX_PACKETS],
+        vec![0; TX_BYTES],
+    );
+
+    udp::Socket::new(rx_buffer, tx_buffer)
+}
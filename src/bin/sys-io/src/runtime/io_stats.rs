@@ -53,6 +53,8 @@ fn process_ipc(service: &mut moto_ipc::sync::LocalServer, waker: moto_sys::SysHa
     let cmd = conn.req::<RequestHeader>().cmd;
     match cmd {
         CMD_TCP_STATS => get_tcp_stats(conn),
+        CMD_NET_STATS => get_net_stats(conn),
+        CMD_TIME_SYNC_STATUS => get_time_sync_status(conn),
         _ => {
             conn.disconnect();
         }
@@ -90,3 +92,37 @@ fn get_tcp_stats(conn: &mut LocalServerConnection) {
     resp.header.result = moto_sys::ErrorCode::Ok.into();
     let _ = conn.finish_rpc();
 }
+
+pub struct GetNetStatsPayload {
+    pub result: moto_runtime::util::SpinLock<NetStatsV1>,
+}
+
+fn get_net_stats(conn: &mut LocalServerConnection) {
+    let payload = Arc::new(GetNetStatsPayload {
+        result: moto_runtime::util::SpinLock::new(NetStatsV1::default()),
+    });
+
+    super::internal_queue::call(CMD_NET_STATS, payload.clone());
+
+    let resp = conn.resp::<GetNetStatsResponse>();
+    resp.stats = *payload.result.lock(line!());
+    resp.header.result = moto_sys::ErrorCode::Ok.into();
+    let _ = conn.finish_rpc();
+}
+
+pub struct GetTimeSyncStatusPayload {
+    pub result: moto_runtime::util::SpinLock<TimeSyncStatusV1>,
+}
+
+fn get_time_sync_status(conn: &mut LocalServerConnection) {
+    let payload = Arc::new(GetTimeSyncStatusPayload {
+        result: moto_runtime::util::SpinLock::new(TimeSyncStatusV1::default()),
+    });
+
+    super::internal_queue::call(CMD_TIME_SYNC_STATUS, payload.clone());
+
+    let resp = conn.resp::<GetTimeSyncStatusResponse>();
+    resp.status = *payload.result.lock(line!());
+    resp.header.result = moto_sys::ErrorCode::Ok.into();
+    let _ = conn.finish_rpc();
+}
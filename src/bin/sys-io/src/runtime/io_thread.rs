@@ -332,7 +332,23 @@ impl IoRuntime {
         };
 
         match msg.cmd {
-            moto_sys_io::stats::CMD_TCP_STATS => self.net.get_stats(&msg),
+            moto_sys_io::stats::CMD_TCP_STATS
+            | moto_sys_io::stats::CMD_NET_STATS
+            | moto_sys_io::stats::CMD_TIME_SYNC_STATUS => self.net.get_stats(&msg),
+            moto_sys_io::netcfg::CMD_LIST_INTERFACES
+            | moto_sys_io::netcfg::CMD_ADD_ADDR
+            | moto_sys_io::netcfg::CMD_DEL_ADDR
+            | moto_sys_io::netcfg::CMD_SET_MTU
+            | moto_sys_io::netcfg::CMD_SET_LINK
+            | moto_sys_io::netcfg::CMD_LIST_ROUTES
+            | moto_sys_io::netcfg::CMD_ADD_ROUTE
+            | moto_sys_io::netcfg::CMD_DEL_ROUTE
+            | moto_sys_io::netcfg::CMD_FW_LIST
+            | moto_sys_io::netcfg::CMD_FW_ADD
+            | moto_sys_io::netcfg::CMD_FW_DEL
+            | moto_sys_io::netcfg::CMD_FW_SET_POLICY
+            | moto_sys_io::netcfg::CMD_FW_FLUSH
+            | moto_sys_io::netcfg::CMD_GET_ROUTE => self.net.net_config(&msg),
             _ => panic!(),
         }
         msg.mark_done();
@@ -6,6 +6,7 @@ use moto_sys::SysHandle;
 pub mod internal_queue;
 pub mod io_stats;
 mod io_thread;
+pub mod net_cfg;
 
 pub struct PendingCompletion {
     pub msg: io_channel::Msg,
@@ -33,6 +34,9 @@ pub trait IoSubsystem {
     fn wait_timeout(&mut self) -> Option<core::time::Duration>;
 
     fn get_stats(&mut self, msg: &internal_queue::Msg);
+
+    // Lists or reconfigures network interfaces and routes (see moto_sys_io::netcfg).
+    fn net_config(&mut self, msg: &internal_queue::Msg);
 }
 
 pub static STARTED: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
@@ -44,6 +48,7 @@ pub fn start() {
         moto_runtime::futex_wait(&STARTED, 0, None);
     }
     io_stats::spawn_stats_service();
+    net_cfg::spawn_net_cfg_service();
 }
 
 // A single 2M page used for VirtIO/MMIO.
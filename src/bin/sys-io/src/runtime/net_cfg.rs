@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use moto_ipc::sync::{LocalServerConnection, RequestHeader};
+use moto_sys::ErrorCode;
+use moto_sys_io::netcfg::*;
+
+pub fn spawn_net_cfg_service() {
+    let _ = std::thread::spawn(|| net_cfg_service_thread());
+}
+
+fn net_cfg_service_thread() -> ! {
+    let mut service = match moto_ipc::sync::LocalServer::new(
+        URL_NET_CFG,
+        moto_ipc::sync::ChannelSize::Small,
+        2,
+        2,
+    ) {
+        Ok(s) => s,
+        Err(err) => {
+            crate::moto_log!(
+                "{}:{} error starting net config service: {:?}.",
+                file!(),
+                line!(),
+                err
+            );
+            std::process::exit(-1)
+        }
+    };
+
+    loop {
+        match service.wait(moto_sys::SysHandle::NONE, &[]) {
+            Ok(wakers) => {
+                for waker in &wakers {
+                    process_ipc(&mut service, *waker);
+                }
+            }
+            Err(wakers) => assert_eq!(wakers.len(), 0),
+        }
+    }
+}
+
+fn process_ipc(service: &mut moto_ipc::sync::LocalServer, waker: moto_sys::SysHandle) {
+    let conn = if let Some(conn) = service.get_connection(waker) {
+        conn
+    } else {
+        // A spurious wakeup by a dropped connection.
+        return;
+    };
+    assert!(conn.connected());
+    if !conn.have_req() {
+        return;
+    }
+
+    let cmd = conn.req::<RequestHeader>().cmd;
+    match cmd {
+        CMD_LIST_INTERFACES => list_interfaces(conn),
+        CMD_ADD_ADDR | CMD_DEL_ADDR | CMD_SET_MTU | CMD_SET_LINK => configure_interface(conn, cmd),
+        CMD_LIST_ROUTES | CMD_GET_ROUTE => route_rpc(conn, cmd),
+        CMD_ADD_ROUTE | CMD_DEL_ROUTE => {
+            if is_root(conn) {
+                route_rpc(conn, cmd)
+            } else {
+                let resp = conn.resp::<ListRoutesResponse<MAX_ROUTE_INFOS>>();
+                resp.num_results = 0;
+                resp.header.result = ErrorCode::NotAllowed.into();
+                let _ = conn.finish_rpc();
+            }
+        }
+        CMD_FW_LIST => fw_rpc(conn, cmd),
+        CMD_FW_ADD | CMD_FW_DEL | CMD_FW_SET_POLICY | CMD_FW_FLUSH => {
+            if is_root(conn) {
+                fw_rpc(conn, cmd)
+            } else {
+                let resp = conn.resp::<ListFwRulesResponse<MAX_FW_RULES>>();
+                resp.num_results = 0;
+                resp.header.result = ErrorCode::NotAllowed.into();
+                let _ = conn.finish_rpc();
+            }
+        }
+        _ => {
+            conn.disconnect();
+        }
+    }
+}
+
+pub struct NetCfgPayload {
+    pub ifname: String,
+    pub cidr: IpCidrV1,
+    pub mtu: u16,
+    pub up: bool,
+
+    // For CMD_LIST_INTERFACES, the interfaces; empty for other commands.
+    pub result: moto_runtime::util::SpinLock<Result<Vec<InterfaceInfoV1>, ErrorCode>>,
+}
+
+fn is_root(conn: &LocalServerConnection) -> bool {
+    moto_sys::SysObj::get_pid(conn.handle())
+        .and_then(moto_sys::SysRay::query_credentials)
+        .is_ok_and(|(uid, _)| uid == moto_sys::caps::ROOT_UID)
+}
+
+fn list_interfaces(conn: &mut LocalServerConnection) {
+    let payload = Arc::new(NetCfgPayload {
+        ifname: String::new(),
+        cidr: IpCidrV1::default(),
+        mtu: 0,
+        up: false,
+        result: moto_runtime::util::SpinLock::new(Ok(Vec::new())),
+    });
+
+    super::internal_queue::call(CMD_LIST_INTERFACES, payload.clone());
+
+    let resp = conn.resp::<ListInterfacesResponse<MAX_INTERFACES>>();
+    let mut result = Ok(Vec::new());
+    core::mem::swap(&mut *payload.result.lock(line!()), &mut result);
+    match result {
+        Ok(interfaces) => {
+            let num_results = interfaces.len().min(MAX_INTERFACES);
+            resp.interfaces[0..num_results].copy_from_slice(&interfaces[0..num_results]);
+            resp.num_results = num_results as u64;
+            resp.header.result = ErrorCode::Ok.into();
+        }
+        Err(err) => {
+            resp.num_results = 0;
+            resp.header.result = err.into();
+        }
+    }
+    let _ = conn.finish_rpc();
+}
+
+fn configure_interface(conn: &mut LocalServerConnection, cmd: u16) {
+    let result = if !is_root(conn) {
+        Err(ErrorCode::NotAllowed)
+    } else {
+        let req = conn.req::<InterfaceRequest>();
+        match req.name() {
+            None => Err(ErrorCode::InvalidArgument),
+            Some(ifname) => {
+                let payload = Arc::new(NetCfgPayload {
+                    ifname: ifname.to_owned(),
+                    cidr: req.cidr,
+                    mtu: req.mtu,
+                    up: req.up != 0,
+                    result: moto_runtime::util::SpinLock::new(Ok(Vec::new())),
+                });
+
+                super::internal_queue::call(cmd, payload.clone());
+
+                let result = payload.result.lock(line!()).clone();
+                result.map(|_| ())
+            }
+        }
+    };
+
+    let resp = conn.resp::<InterfaceResponse>();
+    resp.header.result = match result {
+        Ok(()) => ErrorCode::Ok.into(),
+        Err(err) => err.into(),
+    };
+    let _ = conn.finish_rpc();
+}
+
+pub struct RoutePayload {
+    pub route: RouteInfoV1,
+    pub start_idx: usize,
+
+    // Routes listed or looked up; empty for other commands.
+    pub result: moto_runtime::util::SpinLock<Result<Vec<RouteInfoV1>, ErrorCode>>,
+}
+
+fn route_rpc(conn: &mut LocalServerConnection, cmd: u16) {
+    let req = conn.req::<RouteRequest>();
+    let payload = Arc::new(RoutePayload {
+        route: req.route,
+        start_idx: req.start_idx as usize,
+        result: moto_runtime::util::SpinLock::new(Ok(Vec::new())),
+    });
+
+    super::internal_queue::call(cmd, payload.clone());
+
+    let resp = conn.resp::<ListRoutesResponse<MAX_ROUTE_INFOS>>();
+    let mut result = Ok(Vec::new());
+    core::mem::swap(&mut *payload.result.lock(line!()), &mut result);
+    match result {
+        Ok(routes) => {
+            let num_results = routes.len().min(MAX_ROUTE_INFOS);
+            resp.routes[0..num_results].copy_from_slice(&routes[0..num_results]);
+            resp.num_results = num_results as u64;
+            resp.header.result = ErrorCode::Ok.into();
+        }
+        Err(err) => {
+            resp.num_results = 0;
+            resp.header.result = err.into();
+        }
+    }
+    let _ = conn.finish_rpc();
+}
+
+pub struct FwPayload {
+    pub rule: FwRuleV1,
+    pub idx: usize,
+
+    // Rules listed; empty for other commands.
+    pub result: moto_runtime::util::SpinLock<Result<FwRules, ErrorCode>>,
+}
+
+fn fw_rpc(conn: &mut LocalServerConnection, cmd: u16) {
+    let req = conn.req::<FwRequest>();
+    let payload = Arc::new(FwPayload {
+        rule: req.rule,
+        idx: req.idx as usize,
+        result: moto_runtime::util::SpinLock::new(Err(ErrorCode::InternalError)),
+    });
+
+    super::internal_queue::call(cmd, payload.clone());
+
+    let resp = conn.resp::<ListFwRulesResponse<MAX_FW_RULES>>();
+    let mut result = Err(ErrorCode::InternalError);
+    core::mem::swap(&mut *payload.result.lock(line!()), &mut result);
+    match result {
+        Ok(listing) => {
+            let num_results = listing.rules.len().min(MAX_FW_RULES);
+            resp.rules[0..num_results].copy_from_slice(&listing.rules[0..num_results]);
+            resp.num_results = num_results as u64;
+            resp.input_policy = listing.input_policy;
+            resp.output_policy = listing.output_policy;
+            resp.header.result = ErrorCode::Ok.into();
+        }
+        Err(err) => {
+            resp.num_results = 0;
+            resp.header.result = err.into();
+        }
+    }
+    let _ = conn.finish_rpc();
+}
@@ -0,0 +1,21 @@
+// Mirrors what goes to the serial console onto the display, if there
+// is one: see moto_sys_io::display.
+
+use moto_sys_io::display::DisplayService;
+
+static DISPLAY: std::sync::Mutex<Option<DisplayService>> = std::sync::Mutex::new(None);
+
+pub fn init() {
+    if let Ok(display) = DisplayService::connect() {
+        *DISPLAY.lock().unwrap() = Some(display);
+    }
+}
+
+pub fn write(data: &[u8]) {
+    let mut display = DISPLAY.lock().unwrap();
+    if let Some(service) = display.as_mut() {
+        if service.console_write(data).is_err() {
+            *display = None; // Don't keep trying.
+        }
+    }
+}
@@ -9,6 +9,7 @@ use moto_sys::SysObj;
 
 use crate::serial::write_serial_raw;
 
+mod display;
 mod serial;
 
 #[no_mangle]
@@ -52,6 +53,7 @@ fn main() {
     }
 
     let fname = words[0];
+    display::init();
     let millis = moto_sys::time::since_system_start().as_millis();
     crate::serial::write_serial!(
         "   ... all services up at {:03}ms. Starting {}.\n\n",
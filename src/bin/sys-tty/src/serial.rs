@@ -90,6 +90,7 @@ pub fn read_serial() -> Option<u8> {
 #[doc(hidden)]
 pub fn write_serial_raw(data: &[u8]) {
     SERIAL1.lock().unwrap().write(data);
+    crate::display::write(data);
 }
 
 #[doc(hidden)]
@@ -100,6 +101,7 @@ pub fn write_serial_args(args: ::core::fmt::Arguments) {
         .unwrap()
         .write_fmt(args)
         .expect("Printing to serial failed");
+    crate::display::write(std::fmt::format(args).as_bytes());
 }
 
 #[macro_export]
@@ -0,0 +1,123 @@
+use moto_sys::stats::ProcessAcctV1;
+use moto_sys::time::{tsc_to_duration, Instant};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print accounting records of exited processes, oldest first.");
+    eprintln!("Note: the kernel keeps a limited number of records; older ones are dropped.\n");
+    eprintln!("usage:\n\tacct [-s]\n");
+    eprintln!("\t-s: print a per-program summary instead of individual records.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const ACCT_BUF_SIZE: usize = 64;
+
+pub fn do_command(args: &[String]) {
+    let mut summary = false;
+    if args.len() > 2 {
+        print_usage_and_exit(1)
+    }
+
+    if args.len() == 2 {
+        match args[1].as_str() {
+            "-s" => summary = true,
+            "--help" => print_usage_and_exit(0),
+            _ => print_usage_and_exit(1),
+        }
+    }
+
+    let mut records: Vec<ProcessAcctV1> = Vec::new();
+    let mut buf: Vec<ProcessAcctV1> = Vec::with_capacity(ACCT_BUF_SIZE);
+    for _ in 0..ACCT_BUF_SIZE {
+        buf.push(ProcessAcctV1::default());
+    }
+
+    let mut next_seq = 0;
+    loop {
+        let cnt = match ProcessAcctV1::read(next_seq, &mut buf[..]) {
+            Ok(cnt) => cnt,
+            Err(err) => {
+                eprintln!("acct failed.");
+                std::process::exit(err as u16 as i32);
+            }
+        };
+        if cnt == 0 {
+            break;
+        }
+        next_seq = buf[cnt - 1].seq + 1;
+        records.extend(buf.drain(0..cnt));
+        buf.resize_with(ACCT_BUF_SIZE, ProcessAcctV1::default);
+    }
+
+    if summary {
+        print_summary(&records);
+    } else {
+        print_records(&records);
+    }
+}
+
+fn runtime(record: &ProcessAcctV1) -> std::time::Duration {
+    Instant::from_u64(record.exited).duration_since(Instant::from_u64(record.started))
+}
+
+fn print_records(records: &[ProcessAcctV1]) {
+    println!(
+        "{:>8} {:>8} {:>10} {:>10} {:>10} {:>6}  Name",
+        "PID", "PPID", "REAL", "CPU", "PEAK_KB", "EXIT"
+    );
+
+    for record in records {
+        let exit = match record.exit_status {
+            ProcessAcctV1::EXITED
+                if record.exit_code == moto_sys::stats::EXIT_CODE_STACK_OVERFLOW =>
+            {
+                "STACK".to_owned()
+            }
+            ProcessAcctV1::EXITED => record.exit_code.to_string(),
+            ProcessAcctV1::KILLED => "KILL".to_owned(),
+            _ => "ERR".to_owned(),
+        };
+        println!(
+            "{:>8} {:>8} {:>10.3} {:>10.3} {:>10} {:>6}  {}",
+            record.pid,
+            record.parent_pid,
+            runtime(record).as_secs_f64(),
+            tsc_to_duration(record.cpu_usage).as_secs_f64(),
+            record.peak_bytes_user() >> 10,
+            exit,
+            record.debug_name()
+        );
+    }
+}
+
+fn print_summary(records: &[ProcessAcctV1]) {
+    // Program name => (runs, failures, real secs, cpu secs, max peak bytes).
+    let mut programs: std::collections::BTreeMap<&str, (u64, u64, f64, f64, u64)> =
+        std::collections::BTreeMap::new();
+    for record in records {
+        let entry = programs.entry(record.debug_name()).or_default();
+        entry.0 += 1;
+        if record.exit_status != ProcessAcctV1::EXITED || record.exit_code != 0 {
+            entry.1 += 1;
+        }
+        entry.2 += runtime(record).as_secs_f64();
+        entry.3 += tsc_to_duration(record.cpu_usage).as_secs_f64();
+        entry.4 = entry.4.max(record.peak_bytes_user());
+    }
+
+    println!(
+        "{:>6} {:>6} {:>10} {:>10} {:>10}  Name",
+        "RUNS", "FAILS", "REAL", "CPU", "PEAK_KB"
+    );
+    for (name, (runs, failures, real, cpu, peak)) in &programs {
+        println!(
+            "{:>6} {:>6} {:>10.3} {:>10.3} {:>10}  {}",
+            runs,
+            failures,
+            real,
+            cpu,
+            peak >> 10,
+            name
+        );
+    }
+}
@@ -0,0 +1,45 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Change the mode (permission bits) of files.");
+    eprintln!("usage:\n\tchmod MODE PATH...\n");
+    eprintln!("\tMODE: octal, e.g. 644 or 0755.");
+    eprintln!("\nOnly the owner of a file or root can change its mode.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "chmod");
+
+    if args.len() == 2 && (args[1] == "-h" || args[1] == "--help") {
+        print_usage_and_exit(0);
+    }
+    if args.len() < 3 {
+        print_usage_and_exit(1);
+    }
+
+    let mode = match u32::from_str_radix(args[1].as_str(), 8) {
+        Ok(mode) if mode & !moto_runtime::rt_api::fs::FILE_MODE_MASK == 0 => mode,
+        _ => {
+            eprintln!("chmod: invalid mode '{}'", args[1]);
+            std::process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for path in &args[2..] {
+        let result = std::fs::canonicalize(path)
+            .map_err(|err| format!("{:?}", err.kind()))
+            .and_then(|abs_path| {
+                moto_sys_io::fs::chmod(abs_path.to_str().unwrap_or(""), mode)
+                    .map_err(|err| format!("{:?}", err))
+            });
+        if let Err(err) = result {
+            eprintln!("chmod: {}: {}", path, err);
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Change the owner and/or the group of files.");
+    eprintln!("usage:\n\tchown UID[:GID] PATH...\n\tchown :GID PATH...\n");
+    eprintln!("\tUID, GID: numeric user and group IDs.");
+    eprintln!("\nOnly root can change the owner or the group of a file.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn parse_id(id: &str) -> Option<Option<u32>> {
+    if id.is_empty() {
+        return Some(None);
+    }
+    id.parse().ok().map(Some)
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "chown");
+
+    if args.len() == 2 && (args[1] == "-h" || args[1] == "--help") {
+        print_usage_and_exit(0);
+    }
+    if args.len() < 3 {
+        print_usage_and_exit(1);
+    }
+
+    let (uid, gid) = match args[1].split_once(':') {
+        Some((uid, gid)) => (parse_id(uid), parse_id(gid)),
+        None => (parse_id(args[1].as_str()), Some(None)),
+    };
+    let (uid, gid) = match (uid, gid) {
+        (Some(uid), Some(gid)) if uid.is_some() || gid.is_some() => (uid, gid),
+        _ => {
+            eprintln!("chown: invalid owner '{}'", args[1]);
+            std::process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for path in &args[2..] {
+        let result = std::fs::canonicalize(path)
+            .map_err(|err| format!("{:?}", err.kind()))
+            .and_then(|abs_path| {
+                moto_sys_io::fs::chown(abs_path.to_str().unwrap_or(""), uid, gid)
+                    .map_err(|err| format!("{:?}", err))
+            });
+        if let Err(err) = result {
+            eprintln!("chown: {}: {}", path, err);
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,156 @@
+use super::crontab::{Job, Schedule, CRONTAB_PATH};
+use std::collections::BTreeMap;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!(
+        "Run jobs from the system crontab ({}); see `crontab --help`.",
+        CRONTAB_PATH
+    );
+    eprintln!("Run as a service by sys-init (see /sys/cfg/services.cfg); logs to the kernel log.");
+    eprintln!("usage:\n\tcrond [-f FILE]\n");
+    eprintln!("\t-f: read jobs from FILE instead of {}.", CRONTAB_PATH);
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn log(msg: String) {
+    let _ = moto_sys::SysRay::log(format!("crond: {}", msg).as_str());
+}
+
+struct Crond {
+    path: String,
+    contents: Option<String>, // What `jobs` were parsed from.
+    jobs: Vec<Job>,
+    running: BTreeMap<usize, Child>, // Line => the job's process.
+}
+
+impl Crond {
+    // Re-reads the crontab if it has changed. A bad crontab is reported
+    // and the previous jobs are kept.
+    fn reload(&mut self) {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                log(format!("{}: {:?}", self.path, err.kind()));
+                return;
+            }
+        };
+        if self.contents.as_ref() == Some(&contents) {
+            return;
+        }
+
+        match super::crontab::parse(&contents) {
+            Ok(jobs) => {
+                if self.contents.is_some() {
+                    log(format!("reloaded {}: {} jobs", self.path, jobs.len()));
+                }
+                self.jobs = jobs;
+            }
+            Err(err) => log(format!("{}: {}; keeping the previous jobs", self.path, err)),
+        }
+        self.contents = Some(contents);
+    }
+
+    fn reap(&mut self) {
+        self.running.retain(|line, child| match child.try_wait() {
+            Ok(None) => true,
+            Ok(Some(status)) => {
+                if !status.success() {
+                    log(format!(
+                        "job at line {} exited with {:?}",
+                        line,
+                        status.code()
+                    ));
+                }
+                false
+            }
+            Err(_) => false,
+        });
+    }
+
+    fn run(&mut self, job: &Job) {
+        // Do not pile up instances of slow jobs.
+        if self.running.contains_key(&job.line) {
+            log(format!(
+                "job at line {} is still running; skipped",
+                job.line
+            ));
+            return;
+        }
+
+        let result = Command::new(&job.command[0])
+            .args(&job.command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match result {
+            Ok(child) => {
+                self.running.insert(job.line, child);
+            }
+            Err(err) => log(format!(
+                "job at line {}: failed to spawn {}: {:?}",
+                job.line,
+                job.command[0],
+                err.kind()
+            )),
+        }
+    }
+
+    fn run_matching(&mut self, filter: impl Fn(&Schedule) -> bool) {
+        let jobs: Vec<Job> = self
+            .jobs
+            .iter()
+            .filter(|job| filter(&job.schedule))
+            .cloned()
+            .collect();
+        for job in &jobs {
+            self.run(job);
+        }
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "crond");
+
+    let mut path = CRONTAB_PATH.to_owned();
+    match args.len() {
+        1 => {}
+        2 if args[1] == "--help" => print_usage_and_exit(0),
+        3 if args[1] == "-f" => path = args[2].clone(),
+        _ => print_usage_and_exit(1),
+    }
+
+    let mut crond = Crond {
+        path,
+        contents: None,
+        jobs: Vec::new(),
+        running: BTreeMap::new(),
+    };
+    crond.reload();
+    log(format!("started with {} jobs", crond.jobs.len()));
+    crond.run_matching(|schedule| *schedule == Schedule::Reboot);
+
+    let mut last_minute = time::OffsetDateTime::now_utc().unix_timestamp() / 60;
+    loop {
+        // Wake up at the start of each minute.
+        let now = time::OffsetDateTime::now_utc();
+        std::thread::sleep(Duration::from_secs(60 - (now.second() as u64)));
+
+        let now = time::OffsetDateTime::now_utc();
+        let minute = now.unix_timestamp() / 60;
+        if minute == last_minute {
+            continue; // Woke up early.
+        }
+        // If the clock jumped back, jobs for the repeated minutes run again;
+        // if it jumped forward, skipped minutes are not caught up.
+        last_minute = minute;
+
+        crond.reap();
+        crond.reload();
+        crond.run_matching(|schedule| schedule.matches(&now));
+    }
+}
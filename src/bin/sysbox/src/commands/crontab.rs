@@ -0,0 +1,279 @@
+use std::io::Write;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!(
+        "Install, list, edit, or remove the system crontab ({}).",
+        CRONTAB_PATH
+    );
+    eprintln!("usage:\n\tcrontab -l | -e | -r | FILE\n");
+    eprintln!("\t-l: print the installed crontab.");
+    eprintln!("\t-e: edit the installed crontab with kibim, then install it.");
+    eprintln!("\t-r: remove the installed crontab.");
+    eprintln!("\tFILE: check and install FILE as the crontab.");
+    eprintln!("\nFormat: one job per line; '#' starts a comment.");
+    eprintln!("\tMIN HOUR DAY MONTH WEEKDAY COMMAND [ARGS...]");
+    eprintln!("\t@reboot|@hourly|@daily|@weekly|@monthly|@yearly COMMAND [ARGS...]");
+    eprintln!("Fields are '*', N, A-B, or lists of these, each optionally followed by /STEP;");
+    eprintln!("WEEKDAY is 0-7 (0 and 7 are Sunday). Times are UTC.");
+    eprintln!("COMMAND is an executable (not a shell line), e.g. /sys/sysbox free.");
+    eprintln!("Changes are picked up by crond within a minute.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+pub const CRONTAB_PATH: &str = "/sys/cfg/crontab";
+const EDITOR: &str = "/bin/kibim";
+
+// Allowed values of a time field, as a bitmask.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Field(u64);
+
+impl Field {
+    fn parse(spec: &str, min: u32, max: u32) -> Result<Self, String> {
+        let mut mask = 0_u64;
+        for item in spec.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => match step.parse::<u32>() {
+                    Ok(step) if step > 0 => (range, step),
+                    _ => return Err(format!("bad step in '{}'", item)),
+                },
+                None => (item, 1),
+            };
+
+            let (from, to) = if range == "*" {
+                (min, max)
+            } else if let Some((from, to)) = range.split_once('-') {
+                (parse_num(from, min, max)?, parse_num(to, min, max)?)
+            } else {
+                let val = parse_num(range, min, max)?;
+                // "N/STEP" means "from N to max, every STEP".
+                (val, if step > 1 { max } else { val })
+            };
+            if from > to {
+                return Err(format!("bad range '{}'", range));
+            }
+
+            for val in (from..=to).step_by(step as usize) {
+                mask |= 1 << val;
+            }
+        }
+        Ok(Self(mask))
+    }
+
+    fn contains(&self, val: u32) -> bool {
+        self.0 & (1 << val) != 0
+    }
+}
+
+fn parse_num(s: &str, min: u32, max: u32) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(val) if val >= min && val <= max => Ok(val),
+        _ => Err(format!("'{}' is not in {}-{}", s, min, max)),
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Schedule {
+    Reboot,
+    Time {
+        minutes: Field,
+        hours: Field,
+        days: Field,
+        months: Field,
+        weekdays: Field,
+        // As in other crons, if both days and weekdays are restricted,
+        // a job runs when either matches.
+        any_day: bool,
+        any_weekday: bool,
+    },
+}
+
+impl Schedule {
+    fn parse(fields: &[&str]) -> Result<Self, String> {
+        let days = Field::parse(fields[2], 1, 31)?;
+        let mut weekdays = Field::parse(fields[4], 0, 7)?;
+        if weekdays.contains(7) {
+            weekdays.0 |= 1; // Sunday.
+        }
+
+        Ok(Schedule::Time {
+            minutes: Field::parse(fields[0], 0, 59)?,
+            hours: Field::parse(fields[1], 0, 23)?,
+            days,
+            months: Field::parse(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    pub fn matches(&self, now: &time::OffsetDateTime) -> bool {
+        match self {
+            Schedule::Reboot => false,
+            Schedule::Time {
+                minutes,
+                hours,
+                days,
+                months,
+                weekdays,
+                any_day,
+                any_weekday,
+            } => {
+                let day_matches = days.contains(now.day() as u32);
+                let weekday_matches =
+                    weekdays.contains(now.weekday().number_days_from_sunday() as u32);
+                let day_ok = match (any_day, any_weekday) {
+                    (false, false) => day_matches || weekday_matches,
+                    _ => day_matches && weekday_matches,
+                };
+
+                day_ok
+                    && minutes.contains(now.minute() as u32)
+                    && hours.contains(now.hour() as u32)
+                    && months.contains(now.month() as u32)
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Job {
+    pub line: usize,
+    pub schedule: Schedule,
+    pub command: Vec<String>,
+}
+
+pub fn parse(contents: &str) -> Result<Vec<Job>, String> {
+    let mut jobs = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (schedule, command) = if let Some(name) = words[0].strip_prefix('@') {
+            let schedule = match name {
+                "reboot" => Ok(Schedule::Reboot),
+                "hourly" => Schedule::parse(&["0", "*", "*", "*", "*"]),
+                "daily" | "midnight" => Schedule::parse(&["0", "0", "*", "*", "*"]),
+                "weekly" => Schedule::parse(&["0", "0", "*", "*", "0"]),
+                "monthly" => Schedule::parse(&["0", "0", "1", "*", "*"]),
+                "yearly" | "annually" => Schedule::parse(&["0", "0", "1", "1", "*"]),
+                _ => Err(format!("unknown schedule '{}'", words[0])),
+            };
+            (schedule, &words[1..])
+        } else if words.len() > 5 {
+            (Schedule::parse(&words[0..5]), &words[5..])
+        } else {
+            (
+                Err("expected 5 time fields and a command".to_owned()),
+                &words[0..0],
+            )
+        };
+
+        let schedule = schedule.map_err(|err| format!("line {}: {}", line_no, err))?;
+        if command.is_empty() {
+            return Err(format!("line {}: missing command", line_no));
+        }
+        jobs.push(Job {
+            line: line_no,
+            schedule,
+            command: command.iter().map(|s| s.to_string()).collect(),
+        });
+    }
+    Ok(jobs)
+}
+
+// Checks @contents and installs them, so that crond never sees a partially
+// written crontab.
+fn install(contents: &str) -> Result<(), String> {
+    parse(contents)?;
+
+    let tmp_path = format!("{}.new", CRONTAB_PATH);
+    let mut file = std::fs::File::create(&tmp_path).map_err(|e| format!("{:?}", e.kind()))?;
+    file.write_all(contents.as_bytes())
+        .map_err(|e| format!("{:?}", e.kind()))?;
+    drop(file);
+
+    std::fs::rename(&tmp_path, CRONTAB_PATH).map_err(|e| format!("{:?}", e.kind()))
+}
+
+fn read_installed() -> String {
+    match std::fs::read_to_string(CRONTAB_PATH) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            eprintln!("crontab: {}: {:?}", CRONTAB_PATH, err.kind());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn edit() -> Result<(), String> {
+    let tmp_path = format!(
+        "{}/crontab.{}",
+        moto_runtime::rt_api::TEMP_DIR,
+        moto_sys::ProcessStaticPage::get().pid
+    );
+    std::fs::write(&tmp_path, read_installed()).map_err(|e| format!("{:?}", e.kind()))?;
+
+    let result = loop {
+        let status = std::process::Command::new(EDITOR)
+            .arg(&tmp_path)
+            .status()
+            .map_err(|e| format!("{}: {:?}", EDITOR, e.kind()))?;
+        if !status.success() {
+            break Err(format!("{} failed; crontab not changed", EDITOR));
+        }
+
+        let contents = std::fs::read_to_string(&tmp_path).map_err(|e| format!("{:?}", e.kind()))?;
+        match parse(&contents) {
+            Ok(_) => break install(&contents),
+            Err(err) => {
+                eprintln!("crontab: {}", err);
+                eprint!("Edit again? [Y/n] ");
+                let mut answer = String::new();
+                let _ = std::io::stdin().read_line(&mut answer);
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    break Err("crontab not changed".to_owned());
+                }
+            }
+        }
+    };
+
+    let _ = std::fs::remove_file(&tmp_path);
+    result
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "crontab");
+
+    if args.len() != 2 {
+        print_usage_and_exit(1);
+    }
+
+    let result = match args[1].as_str() {
+        "--help" => print_usage_and_exit(0),
+        "-l" => {
+            print!("{}", read_installed());
+            Ok(())
+        }
+        "-e" => edit(),
+        "-r" => match std::fs::remove_file(CRONTAB_PATH) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("{:?}", err.kind())),
+        },
+        arg if arg.starts_with('-') => print_usage_and_exit(1),
+        file => std::fs::read_to_string(file)
+            .map_err(|e| format!("{}: {:?}", file, e.kind()))
+            .and_then(|contents| install(&contents)),
+    };
+
+    if let Err(err) = result {
+        eprintln!("crontab: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,115 @@
+use moto_runtime::rt_api::fs::{
+    FsStatsData, FS_TYPE_DEVFS, FS_TYPE_EXT2, FS_TYPE_FAT, FS_TYPE_FLATFS, FS_TYPE_PROCFS,
+    FS_TYPE_SRFS, FS_TYPE_TMPFS, FS_TYPE_VIRTIOFS,
+};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Report filesystem capacity and usage.");
+    eprintln!("usage:\n\tdf [-h] [PATH...]\n");
+    eprintln!("\t-h: print sizes in human-readable units (default: 1K blocks).");
+    eprintln!("\tPATH: report the filesystem PATH belongs to (default: /).");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn fmt_size(bytes: u64, human: bool) -> String {
+    if !human {
+        return bytes.div_ceil(1024).to_string();
+    }
+
+    const SUFFIXES: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut val = bytes as f64;
+    let mut idx = 0;
+    while val >= 1024.0 && idx < SUFFIXES.len() - 1 {
+        val /= 1024.0;
+        idx += 1;
+    }
+    if idx == 0 {
+        format!("{}{}", bytes, SUFFIXES[0])
+    } else {
+        format!("{:.1}{}", val, SUFFIXES[idx])
+    }
+}
+
+fn fs_type_str(stats: &FsStatsData) -> &'static str {
+    match stats.fs_type {
+        FS_TYPE_FLATFS => "flatfs",
+        FS_TYPE_SRFS => "srfs",
+        FS_TYPE_TMPFS => "tmpfs",
+        FS_TYPE_FAT => "fat32",
+        FS_TYPE_EXT2 => "ext2",
+        FS_TYPE_VIRTIOFS => "virtiofs",
+        FS_TYPE_PROCFS => "procfs",
+        FS_TYPE_DEVFS => "devfs",
+        _ => "unknown",
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "df");
+
+    let mut human = false;
+    let mut paths = Vec::new();
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-h" => human = true,
+            arg if arg.starts_with('-') => print_usage_and_exit(1),
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("/");
+    }
+
+    println!(
+        "{:8} {:>12} {:>12} {:>12} {:>5} {:>3} PATH",
+        "TYPE",
+        if human { "SIZE" } else { "1K-BLOCKS" },
+        "USED",
+        "AVAIL",
+        "USE%",
+        "RO"
+    );
+
+    let mut failed = false;
+    for path in paths {
+        let stats = std::fs::canonicalize(path)
+            .map_err(|err| format!("{:?}", err.kind()))
+            .and_then(|abs_path| {
+                moto_sys_io::fs::statfs(abs_path.to_str().unwrap_or(""))
+                    .map_err(|err| format!("{:?}", err))
+            });
+        let stats = match stats {
+            Ok(stats) => stats,
+            Err(err) => {
+                eprintln!("df: {}: {}", path, err);
+                failed = true;
+                continue;
+            }
+        };
+
+        let total = stats.blocks_total * stats.block_size;
+        let free = stats.blocks_free * stats.block_size;
+        let used = total - free;
+        let use_pct = if total == 0 {
+            0
+        } else {
+            (used * 100).div_ceil(total)
+        };
+        println!(
+            "{:8} {:>12} {:>12} {:>12} {:>4}% {:>3} {}",
+            fs_type_str(&stats),
+            fmt_size(total, human),
+            fmt_size(used, human),
+            fmt_size(free, human),
+            use_pct,
+            if stats.read_only != 0 { "yes" } else { "no" },
+            path
+        );
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
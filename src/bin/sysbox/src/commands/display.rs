@@ -0,0 +1,101 @@
+use moto_sys::ErrorCode;
+use moto_sys_io::display::DisplayService;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Show or use the display (virtio-gpu).");
+    eprintln!("usage:");
+    eprintln!("\tdisplay                 show the display's size and who has it");
+    eprintln!("\tdisplay scroll LINES    scroll the console back (or forward, if negative)");
+    eprintln!("\tdisplay scroll bottom   scroll the console to the bottom");
+    eprintln!("\tdisplay test [SECONDS]  draw a test pattern (default: for 5 seconds)\n");
+    std::process::exit(exit_code);
+}
+
+fn exit_with_error(err: ErrorCode) -> ! {
+    let reason = match err {
+        ErrorCode::NotFound => "no display".to_owned(),
+        ErrorCode::AlreadyInUse => "the display is in use".to_owned(),
+        err => format!("{:?}", err),
+    };
+    eprintln!("display: {}", reason);
+    std::process::exit(1);
+}
+
+fn connect() -> DisplayService {
+    DisplayService::connect().unwrap_or_else(|_| exit_with_error(ErrorCode::NotFound))
+}
+
+fn show_info() {
+    let info = connect().info().unwrap_or_else(|err| exit_with_error(err));
+    println!("Size:    {}x{}", info.width, info.height);
+    println!("Stride:  {} bytes", info.stride);
+    if info.owner_pid == 0 {
+        println!("In use:  by the console");
+    } else {
+        println!("In use:  by process {}", info.owner_pid);
+    }
+}
+
+fn draw_test_pattern(seconds: u64) {
+    let mut display = connect();
+    display.acquire().unwrap_or_else(|err| exit_with_error(err));
+    let mut fb = display
+        .map_framebuffer()
+        .unwrap_or_else(|err| exit_with_error(err));
+
+    let width = fb.width();
+    let height = fb.height();
+    let row_len = (fb.info().stride >> 2) as usize;
+    let pixels = fb.pixels_mut();
+    for y in 0..height {
+        for x in 0..width {
+            // Red grows to the right, green downwards; a white frame around.
+            let pixel = if x < 4 || y < 4 || x + 4 >= width || y + 4 >= height {
+                0x00_ff_ff_ff
+            } else {
+                let red = x * 255 / width;
+                let green = y * 255 / height;
+                (red << 16) | (green << 8) | 0x80
+            };
+            pixels[(y as usize) * row_len + (x as usize)] = pixel;
+        }
+    }
+
+    display
+        .flush(0, 0, width, height)
+        .unwrap_or_else(|err| exit_with_error(err));
+    std::thread::sleep(std::time::Duration::from_secs(seconds));
+    // Dropping the connection would also give the display back.
+    display.release().unwrap_or_else(|err| exit_with_error(err));
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "display");
+
+    match args.get(1).map(|s| s.as_str()) {
+        None | Some("info") if args.len() <= 2 => show_info(),
+        Some("scroll") if args.len() == 3 => {
+            let mut display = connect();
+            let result = if args[2] == "bottom" {
+                display.console_scroll_to_bottom()
+            } else {
+                match args[2].parse::<i32>() {
+                    Ok(lines) if lines != i32::MIN => display.console_scroll(lines),
+                    _ => print_usage_and_exit(1),
+                }
+            };
+            result.unwrap_or_else(|err| exit_with_error(err));
+        }
+        Some("test") if args.len() <= 3 => {
+            let seconds = match args.get(2) {
+                None => 5,
+                Some(arg) => arg
+                    .parse::<u64>()
+                    .unwrap_or_else(|_| print_usage_and_exit(1)),
+            };
+            draw_test_pattern(seconds);
+        }
+        Some("-h") | Some("--help") => print_usage_and_exit(0),
+        _ => print_usage_and_exit(1),
+    }
+}
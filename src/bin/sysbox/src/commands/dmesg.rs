@@ -0,0 +1,169 @@
+use moto_sys::stats::KLogEntryV1;
+use moto_sys::time::{Instant, SystemTime, UtcDateTime};
+use moto_sys::{SysCpu, SysHandle, SysObj};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print the kernel log, oldest entries first.");
+    eprintln!("Note: the kernel keeps a limited number of entries; older ones are dropped.\n");
+    eprintln!("usage:\n\tdmesg [-w] [-T] [-l LEVEL[,LEVEL...]] [-s SUBSYSTEM[,SUBSYSTEM...]]\n");
+    eprintln!("\t-w: wait for new entries and print them as they arrive.");
+    eprintln!("\t-T: print wall clock (UTC) timestamps instead of seconds since boot.");
+    eprintln!("\t-l: print only entries of the given levels:");
+    eprintln!("\t    error, warn, info, debug, trace, user (logged by userspace).");
+    eprintln!("\t-s: print only entries whose subsystem starts with one of the prefixes.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const KLOG_BUF_SIZE: usize = 64;
+
+struct Filter {
+    levels: Option<Vec<&'static str>>, // As in KLogEntryV1::level_str().
+    subsystems: Option<Vec<String>>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &KLogEntryV1) -> bool {
+        if let Some(levels) = &self.levels {
+            if !levels.contains(&entry.level_str()) {
+                return false;
+            }
+        }
+        if let Some(subsystems) = &self.subsystems {
+            let subsystem = entry.subsystem();
+            if !subsystems.iter().any(|s| subsystem.starts_with(s.as_str())) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn parse_levels(arg: &str) -> Vec<&'static str> {
+    arg.split(',')
+        .filter(|s| !s.is_empty())
+        .map(|level| match level.to_ascii_lowercase().as_str() {
+            "error" | "err" => "ERROR",
+            "warn" | "warning" => "WARN",
+            "info" => "INFO",
+            "debug" => "DEBUG",
+            "trace" => "TRACE",
+            "user" => "USER",
+            _ => {
+                eprintln!("dmesg: unknown level '{}'", level);
+                print_usage_and_exit(1)
+            }
+        })
+        .collect()
+}
+
+fn format_timestamp(timestamp: u64, wall_clock: bool) -> String {
+    let instant = Instant::from_u64(timestamp);
+    if wall_clock {
+        let ago = Instant::now().duration_since(instant);
+        let time = SystemTime::now()
+            .checked_sub_duration(&ago)
+            .unwrap_or(SystemTime::from_unix_ts(0));
+        UtcDateTime::from_unix_nanos(time.as_unix_ts() as u128).to_string()
+    } else {
+        let since_boot = instant.duration_since(moto_sys::time::system_start_time());
+        format!("{:12.6}", since_boot.as_secs_f64())
+    }
+}
+
+fn print_entry(entry: &KLogEntryV1, wall_clock: bool) {
+    println!(
+        "[{}] {:2} {:5} {}: {}",
+        format_timestamp(entry.timestamp, wall_clock),
+        entry.cpu,
+        entry.level_str(),
+        entry.subsystem(),
+        entry.msg()
+    );
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "dmesg");
+
+    let mut follow = false;
+    let mut wall_clock = false;
+    let mut filter = Filter {
+        levels: None,
+        subsystems: None,
+    };
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-w" => follow = true,
+            "-T" => wall_clock = true,
+            "-l" if filter.levels.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                filter.levels = Some(parse_levels(args[idx].as_str()));
+            }
+            "-s" if filter.subsystems.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                filter.subsystems = Some(
+                    args[idx]
+                        .split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_owned())
+                        .collect(),
+                );
+            }
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    // Get the wait handle before reading, so that no entries are missed
+    // between reading the log and waiting.
+    let waiter = if follow {
+        match SysObj::get(SysHandle::KERNEL, 0, "klog") {
+            Ok(handle) => Some(handle),
+            Err(err) => {
+                eprintln!("dmesg: failed to get the klog handle: {:?}", err);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut buf: Vec<KLogEntryV1> = Vec::with_capacity(KLOG_BUF_SIZE);
+    buf.resize_with(KLOG_BUF_SIZE, KLogEntryV1::default);
+
+    let mut next_seq = 0;
+    loop {
+        let cnt = match KLogEntryV1::read(next_seq, &mut buf[..]) {
+            Ok(cnt) => cnt,
+            Err(err) => {
+                eprintln!("dmesg: failed to read the kernel log: {:?}", err);
+                std::process::exit(1);
+            }
+        };
+
+        for entry in &buf[0..cnt] {
+            // next_seq == 0 means we started from whatever the ring has.
+            if next_seq != 0 && entry.seq > next_seq {
+                println!("[...] {} entries dropped", entry.seq - next_seq);
+            }
+            next_seq = entry.seq + 1;
+            if filter.matches(entry) {
+                print_entry(entry, wall_clock);
+            }
+        }
+
+        if cnt == KLOG_BUF_SIZE {
+            continue;
+        }
+        match waiter {
+            Some(handle) => {
+                // Spurious wakeups are fine: we just read nothing.
+                let _ = SysCpu::wait(&mut [handle], SysHandle::NONE, SysHandle::NONE, None);
+            }
+            None => break,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Summarize disk usage of files and directories, recursively.");
+    eprintln!("Note: sizes are file sizes (the FS does not report blocks used per file).\n");
+    eprintln!("usage:\n\tdu [-h] [-s | -d DEPTH] [-a] [PATH...]\n");
+    eprintln!("\t-h: print sizes in human-readable units (default: 1K blocks).");
+    eprintln!("\t-s: print only the total of each PATH.");
+    eprintln!("\t-d: print totals of directories at most DEPTH levels below PATH.");
+    eprintln!("\t-a: print files as well as directories.");
+    eprintln!("\tPATH: default: the current directory.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+struct Options {
+    human: bool,
+    all: bool,
+    max_depth: Option<usize>,
+}
+
+fn fmt_size(bytes: u64, human: bool) -> String {
+    if !human {
+        return bytes.div_ceil(1024).to_string();
+    }
+
+    const SUFFIXES: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut val = bytes as f64;
+    let mut idx = 0;
+    while val >= 1024.0 && idx < SUFFIXES.len() - 1 {
+        val /= 1024.0;
+        idx += 1;
+    }
+    if idx == 0 {
+        format!("{}{}", bytes, SUFFIXES[0])
+    } else {
+        format!("{:.1}{}", val, SUFFIXES[idx])
+    }
+}
+
+fn print_entry(size: u64, path: &Path, opts: &Options) {
+    println!("{:<10} {}", fmt_size(size, opts.human), path.display());
+}
+
+// Returns the total size of @path; prints entries not deeper than opts.max_depth.
+fn walk(path: &Path, depth: usize, opts: &Options, failed: &mut bool) -> u64 {
+    let print = opts.max_depth.map_or(true, |max_depth| depth <= max_depth);
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            eprintln!("du: {}: {:?}", path.display(), err.kind());
+            *failed = true;
+            return 0;
+        }
+    };
+    if !metadata.is_dir() {
+        if print && (opts.all || depth == 0) {
+            print_entry(metadata.len(), path, opts);
+        }
+        return metadata.len();
+    }
+
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("du: {}: {:?}", path.display(), err.kind());
+            *failed = true;
+            return 0;
+        }
+    };
+
+    let mut total = 0;
+    for entry in entries {
+        match entry {
+            Ok(entry) => total += walk(entry.path().as_path(), depth + 1, opts, failed),
+            Err(err) => {
+                eprintln!("du: {}: {:?}", path.display(), err.kind());
+                *failed = true;
+            }
+        }
+    }
+
+    if print {
+        print_entry(total, path, opts);
+    }
+    total
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "du");
+
+    let mut opts = Options {
+        human: false,
+        all: false,
+        max_depth: None,
+    };
+    let mut paths = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-h" => opts.human = true,
+            "-a" => opts.all = true,
+            "-s" if opts.max_depth.is_none() => opts.max_depth = Some(0),
+            "-d" if opts.max_depth.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                match args[idx].parse::<usize>() {
+                    Ok(depth) => opts.max_depth = Some(depth),
+                    Err(_) => print_usage_and_exit(1),
+                }
+            }
+            arg if arg.starts_with('-') => print_usage_and_exit(1),
+            arg => paths.push(arg),
+        }
+        idx += 1;
+    }
+    if paths.is_empty() {
+        paths.push(".");
+    }
+
+    let mut failed = false;
+    for path in paths {
+        walk(Path::new(path), 0, &opts, &mut failed);
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
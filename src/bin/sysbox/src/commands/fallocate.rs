@@ -0,0 +1,80 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Preallocate or deallocate space of a file.");
+    eprintln!("usage:\n\tfallocate [-p] [-o OFFSET] -l LENGTH FILE\n");
+    eprintln!("\t-p: punch a hole: free the range, keeping the file size;");
+    eprintln!("\t-o: the start of the range (default: 0);");
+    eprintln!("\t-l: the length of the range.");
+    eprintln!("\nOFFSET and LENGTH are in bytes, or in K, M, or G (1024-based).");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn parse_size(arg: &str) -> Option<u64> {
+    let (num, shift) = match arg.chars().last()? {
+        'K' | 'k' => (&arg[0..(arg.len() - 1)], 10),
+        'M' | 'm' => (&arg[0..(arg.len() - 1)], 20),
+        'G' | 'g' => (&arg[0..(arg.len() - 1)], 30),
+        _ => (arg, 0),
+    };
+    num.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "fallocate");
+
+    let mut punch_hole = false;
+    let mut offset = 0;
+    let mut len = None;
+    let mut path = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-p" => punch_hole = true,
+            "-o" | "-l" => {
+                idx += 1;
+                let Some(size) = args.get(idx).and_then(|s| parse_size(s.as_str())) else {
+                    print_usage_and_exit(1);
+                };
+                if arg == "-o" {
+                    offset = size;
+                } else {
+                    len = Some(size);
+                }
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let (Some(len), Some(path)) = (len, path) else {
+        print_usage_and_exit(1);
+    };
+
+    // As in Linux, allocating a missing file creates it.
+    if !punch_hole {
+        if let Err(err) = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+        {
+            eprintln!("fallocate: {}: {:?}", path, err.kind());
+            std::process::exit(1);
+        }
+    }
+
+    let result = std::fs::canonicalize(path)
+        .map_err(|err| format!("{:?}", err.kind()))
+        .and_then(|abs_path| {
+            moto_sys_io::fs::fallocate(abs_path.to_str().unwrap_or(""), offset, len, punch_hole)
+                .map_err(|err| format!("{:?}", err))
+        });
+    if let Err(err) = result {
+        eprintln!("fallocate: {}: {}", path, err);
+        std::process::exit(1);
+    }
+}
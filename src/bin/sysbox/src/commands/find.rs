@@ -0,0 +1,324 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Walk directory trees and print (or run commands on) entries matching all tests.");
+    eprintln!("usage:\n\tfind [PATH...] [OPTION...] [TEST...] [ACTION...]\n");
+    eprintln!("Options:");
+    eprintln!("\t-maxdepth N, -mindepth N: limit the depth of entries (PATH is at depth 0).");
+    eprintln!("Tests (prefix with ! to negate):");
+    eprintln!("\t-name GLOB, -iname GLOB: the file name matches GLOB (*, ?, [a-z], [!a]).");
+    eprintln!("\t-type f|d: a file or a directory.");
+    eprintln!("\t-size [+|-]N[c|k|M|G]: the size is more than, less than, or exactly N units");
+    eprintln!("\t                       (c: bytes, k/M/G: KiB/MiB/GiB, default: 512 bytes).");
+    eprintln!("\t-mtime [+|-]N, -mmin [+|-]N: modified more than, less than, or exactly");
+    eprintln!("\t                             N days/minutes ago.");
+    eprintln!("Actions (default: -print):");
+    eprintln!("\t-print: print the path.");
+    eprintln!("\t-exec CMD [ARG...] ';': run CMD, with {{}} replaced by the path;");
+    eprintln!("\t                        a test that passes if CMD succeeds.");
+    eprintln!("\tPATH: default: the current directory.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+// A glob matcher for file names: '*', '?', and '[...]' classes.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return name.is_empty();
+    };
+
+    match first {
+        '*' => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        '?' => !name.is_empty() && glob_match(rest, &name[1..]),
+        '[' => {
+            let Some((&c, name_rest)) = name.split_first() else {
+                return false;
+            };
+            let Some(end) = rest
+                .iter()
+                .skip(1)
+                .position(|c| *c == ']')
+                .map(|pos| pos + 1)
+            else {
+                // No closing bracket: a literal '['.
+                return c == '[' && glob_match(rest, name_rest);
+            };
+            let (negated, class) = match rest[0] {
+                '!' | '^' => (true, &rest[1..end]),
+                _ => (false, &rest[0..end]),
+            };
+
+            let mut found = false;
+            let mut idx = 0;
+            while idx < class.len() {
+                if idx + 2 < class.len() && class[idx + 1] == '-' {
+                    found |= class[idx] <= c && c <= class[idx + 2];
+                    idx += 3;
+                } else {
+                    found |= class[idx] == c;
+                    idx += 1;
+                }
+            }
+            found != negated && glob_match(&rest[(end + 1)..], name_rest)
+        }
+        _ => name.first() == Some(&first) && glob_match(rest, &name[1..]),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Cmp {
+    More(u64),
+    Less(u64),
+    Exactly(u64),
+}
+
+impl Cmp {
+    fn parse(arg: &str) -> Option<Self> {
+        if let Some(num) = arg.strip_prefix('+') {
+            num.parse().ok().map(Cmp::More)
+        } else if let Some(num) = arg.strip_prefix('-') {
+            num.parse().ok().map(Cmp::Less)
+        } else {
+            arg.parse().ok().map(Cmp::Exactly)
+        }
+    }
+
+    fn matches(&self, val: u64) -> bool {
+        match self {
+            Cmp::More(n) => val > *n,
+            Cmp::Less(n) => val < *n,
+            Cmp::Exactly(n) => val == *n,
+        }
+    }
+}
+
+enum Test {
+    Name(Vec<char>),
+    IName(Vec<char>),
+    IsDir(bool),
+    Size(Cmp, u64), // Units of the second value, in bytes.
+    Age(Cmp, u64),  // Units of the second value, in seconds.
+    Print,
+    Exec(Vec<String>),
+}
+
+struct Find {
+    tests: Vec<(bool, Test)>, // Negated, test.
+    min_depth: usize,
+    max_depth: Option<usize>,
+    now: SystemTime,
+    failed: bool,
+}
+
+impl Find {
+    fn eval(&self, test: &Test, path: &Path, metadata: &std::fs::Metadata) -> bool {
+        let name = || -> Vec<char> {
+            path.file_name()
+                .map(|name| name.to_string_lossy().chars().collect())
+                .unwrap_or_else(|| path.to_string_lossy().chars().collect())
+        };
+
+        match test {
+            Test::Name(glob) => glob_match(glob, &name()),
+            Test::IName(glob) => {
+                let name: Vec<char> = name().iter().flat_map(|c| c.to_lowercase()).collect();
+                glob_match(glob, &name)
+            }
+            Test::IsDir(is_dir) => metadata.is_dir() == *is_dir,
+            Test::Size(cmp, unit) => cmp.matches(metadata.len().div_ceil(*unit)),
+            Test::Age(cmp, unit) => {
+                let age = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|mtime| self.now.duration_since(mtime).ok())
+                    .unwrap_or(Duration::ZERO);
+                cmp.matches(age.as_secs() / unit)
+            }
+            Test::Print => {
+                println!("{}", path.display());
+                true
+            }
+            Test::Exec(cmd) => {
+                let path = path.to_string_lossy();
+                let args: Vec<String> = cmd.iter().map(|arg| arg.replace("{}", &path)).collect();
+                match std::process::Command::new(&args[0])
+                    .args(&args[1..])
+                    .status()
+                {
+                    Ok(status) => status.success(),
+                    Err(err) => {
+                        eprintln!("find: {}: {:?}", args[0], err.kind());
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, path: &Path, metadata: &std::fs::Metadata, depth: usize) {
+        if depth >= self.min_depth {
+            // Tests are ANDed: stop at the first one that fails.
+            for (negated, test) in &self.tests {
+                if self.eval(test, path, metadata) == *negated {
+                    break;
+                }
+            }
+        }
+
+        if !metadata.is_dir() || self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return;
+        }
+
+        // Entries are processed as they are read, so that large trees are
+        // not held in memory.
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("find: {}: {:?}", path.display(), err.kind());
+                self.failed = true;
+                return;
+            }
+        };
+        for entry in entries {
+            let result = entry.and_then(|entry| Ok((entry.path(), entry.metadata()?)));
+            match result {
+                Ok((path, metadata)) => self.visit(&path, &metadata, depth + 1),
+                Err(err) => {
+                    eprintln!("find: {}: {:?}", path.display(), err.kind());
+                    self.failed = true;
+                }
+            }
+        }
+    }
+}
+
+fn next_arg<'a>(args: &'a [String], idx: &mut usize) -> &'a str {
+    *idx += 1;
+    match args.get(*idx) {
+        Some(arg) => arg.as_str(),
+        None => print_usage_and_exit(1),
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "find");
+
+    let mut paths = Vec::new();
+    let mut idx = 1;
+    while idx < args.len() && !args[idx].starts_with('-') && args[idx] != "!" {
+        paths.push(args[idx].as_str());
+        idx += 1;
+    }
+    if paths.is_empty() {
+        paths.push(".");
+    }
+
+    let mut find = Find {
+        tests: Vec::new(),
+        min_depth: 0,
+        max_depth: None,
+        now: SystemTime::now(),
+        failed: false,
+    };
+    let mut has_action = false;
+    let mut negated = false;
+    while idx < args.len() {
+        let test = match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "!" | "-not" if !negated => {
+                negated = true;
+                idx += 1;
+                continue;
+            }
+            "-maxdepth" | "-mindepth" if !negated => {
+                let opt = args[idx].as_str();
+                let Ok(depth) = next_arg(args, &mut idx).parse::<usize>() else {
+                    print_usage_and_exit(1);
+                };
+                if opt == "-maxdepth" {
+                    find.max_depth = Some(depth);
+                } else {
+                    find.min_depth = depth;
+                }
+                idx += 1;
+                continue;
+            }
+            "-name" => Test::Name(next_arg(args, &mut idx).chars().collect()),
+            "-iname" => Test::IName(
+                next_arg(args, &mut idx)
+                    .chars()
+                    .flat_map(|c| c.to_lowercase())
+                    .collect(),
+            ),
+            "-type" => match next_arg(args, &mut idx) {
+                "f" => Test::IsDir(false),
+                "d" => Test::IsDir(true),
+                _ => print_usage_and_exit(1),
+            },
+            "-size" => {
+                let arg = next_arg(args, &mut idx);
+                let (num, unit) = match arg.char_indices().last() {
+                    Some((pos, 'c')) => (&arg[0..pos], 1),
+                    Some((pos, 'k')) => (&arg[0..pos], 1 << 10),
+                    Some((pos, 'M')) => (&arg[0..pos], 1 << 20),
+                    Some((pos, 'G')) => (&arg[0..pos], 1 << 30),
+                    _ => (arg, 512),
+                };
+                match Cmp::parse(num) {
+                    Some(cmp) => Test::Size(cmp, unit),
+                    None => print_usage_and_exit(1),
+                }
+            }
+            "-mtime" | "-mmin" => {
+                let unit = if args[idx] == "-mtime" { 86400 } else { 60 };
+                match Cmp::parse(next_arg(args, &mut idx)) {
+                    Some(cmp) => Test::Age(cmp, unit),
+                    None => print_usage_and_exit(1),
+                }
+            }
+            "-print" => {
+                has_action = true;
+                Test::Print
+            }
+            "-exec" => {
+                has_action = true;
+                let start = idx + 1;
+                let Some(len) = args[start..].iter().position(|arg| arg == ";") else {
+                    eprintln!("find: -exec: missing ';'");
+                    print_usage_and_exit(1);
+                };
+                if len == 0 {
+                    print_usage_and_exit(1);
+                }
+                idx = start + len;
+                Test::Exec(args[start..idx].to_vec())
+            }
+            _ => print_usage_and_exit(1),
+        };
+        find.tests.push((negated, test));
+        negated = false;
+        idx += 1;
+    }
+    if negated {
+        print_usage_and_exit(1);
+    }
+    if !has_action {
+        find.tests.push((false, Test::Print));
+    }
+
+    for path in paths {
+        let path = Path::new(path);
+        match std::fs::metadata(path) {
+            Ok(metadata) => find.visit(path, &metadata, 0),
+            Err(err) => {
+                eprintln!("find: {}: {:?}", path.display(), err.kind());
+                find.failed = true;
+            }
+        }
+    }
+
+    if find.failed {
+        std::process::exit(1);
+    }
+}
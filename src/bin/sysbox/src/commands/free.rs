@@ -1,58 +1,145 @@
+use moto_sys::stats::MemoryStats;
+use moto_sys::sys_mem::{PAGE_SIZE_MID_LOG2, PAGE_SIZE_SMALL_LOG2};
+
 fn print_usage_and_exit(exit_code: i32) -> ! {
     eprintln!("Report PHYSICAL RAM available and used.");
     eprintln!("This is different from `ps`, which reports VIRTUAL RAM usage.");
-    eprintln!("usage:\n\tfree [-(b|k|m|g)] [reclaim]\n");
+    eprintln!("usage:\n\tfree [-(b|k|m|g|h)] [-z] [reclaim]\n");
+    eprintln!("\t-b|-k|-m|-g: print sizes in bytes/KiB/MiB/GiB (default: bytes).");
+    eprintln!("\t-h: print sizes in human-readable units.");
+    eprintln!("\t-z: also print per-zone (page size) stats.");
+    eprintln!("\treclaim: ask the kernel to reclaim memory first.");
+    eprintln!("\nColumns:");
+    eprintln!("\tused:   physical 4K pages allocated (incl. the kernel and the huge page pool).");
+    eprintln!("\tcached: stacks of exited threads kept for reuse (included in used).");
+    eprintln!("\tkernel: the kernel heap plus kernel stacks, page tables, etc.");
     std::thread::sleep(std::time::Duration::new(0, 1_000_000));
     std::process::exit(exit_code);
 }
 
-pub fn do_command(args: &[String]) {
-    if args.len() > 3 {
-        print_usage_and_exit(-1)
-    }
+#[derive(Clone, Copy)]
+enum Units {
+    Shift(u32),
+    Human,
+}
 
-    let mut reclaim = false;
-    if args.len() == 3 {
-        if args[2].as_str() == "reclaim" {
-            reclaim = true;
-        } else {
-            print_usage_and_exit(-1);
+fn fmt_size(bytes: u64, units: Units) -> String {
+    match units {
+        Units::Shift(bits) => (bytes >> bits).to_string(),
+        Units::Human => {
+            const SUFFIXES: [&str; 5] = ["B", "K", "M", "G", "T"];
+            let mut val = bytes as f64;
+            let mut idx = 0;
+            while val >= 1024.0 && idx < SUFFIXES.len() - 1 {
+                val /= 1024.0;
+                idx += 1;
+            }
+            if idx == 0 {
+                format!("{}{}", bytes, SUFFIXES[0])
+            } else {
+                format!("{:.1}{}", val, SUFFIXES[idx])
+            }
         }
     }
+}
+
+pub fn do_command(args: &[String]) {
+    let mut reclaim = false;
+    let mut zones = false;
+    let mut units = None;
 
-    let mut shift_bits = 0;
-    if args.len() > 1 {
-        match args[1].as_str() {
-            "-b" => {}
+    for arg in &args[1..] {
+        match arg.as_str() {
             "--help" => print_usage_and_exit(0),
-            "-k" => shift_bits = 10,
-            "-m" => shift_bits = 20,
-            "-g" => shift_bits = 30,
-            "reclaim" => {
-                if reclaim {
-                    print_usage_and_exit(-1);
-                } else {
-                    reclaim = true;
-                }
-            }
+            "reclaim" if !reclaim => reclaim = true,
+            "-z" if !zones => zones = true,
+            "-b" if units.is_none() => units = Some(Units::Shift(0)),
+            "-k" if units.is_none() => units = Some(Units::Shift(10)),
+            "-m" if units.is_none() => units = Some(Units::Shift(20)),
+            "-g" if units.is_none() => units = Some(Units::Shift(30)),
+            "-h" if units.is_none() => units = Some(Units::Human),
             _ => print_usage_and_exit(1),
         }
     }
+    let units = units.unwrap_or(Units::Shift(0));
+
     if reclaim {
         moto_sys::SysMem::reclaim(moto_sys::syscalls::SysHandle::KERNEL).unwrap();
     }
 
-    let stats = moto_sys::stats::MemoryStats::get().unwrap();
-    let total = stats.available >> shift_bits;
-    let used = stats.used() >> shift_bits;
+    let stats = match MemoryStats::get() {
+        Ok(stats) => stats,
+        Err(err) => {
+            eprintln!("free: failed to get memory stats: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "        {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "total", "used", "free", "cached", "kernel"
+    );
+    println!(
+        "Mem:    {:>12} {:>12} {:>12} {:>12} {:>12}",
+        fmt_size(stats.available, units),
+        fmt_size(stats.used(), units),
+        fmt_size(stats.free(), units),
+        fmt_size(stats.cached(), units),
+        fmt_size(stats.kernel(), units),
+    );
+    if stats.huge_pages_total > 0 {
+        // The huge page pool is reserved at bootup, so it is counted as used above.
+        let huge_total = stats.huge_pages_total << PAGE_SIZE_MID_LOG2;
+        let huge_used = stats.huge_pages_used << PAGE_SIZE_MID_LOG2;
+        println!(
+            "Huge:   {:>12} {:>12} {:>12}",
+            fmt_size(huge_total, units),
+            fmt_size(huge_used, units),
+            fmt_size(huge_total - huge_used, units),
+        );
+    }
+
+    if !zones {
+        return;
+    }
 
-    println!("               total         used         free        kheap    pages");
     println!(
-        "Mem:    {:12} {:12} {:12} {:12}    {}",
-        total,
-        used,
-        total - used,
-        stats.heap_total >> shift_bits,
+        "\nZone     {:>10} {:>10} {:>10} {:>12}",
+        "pages", "used", "free", "size"
+    );
+    let zone_line = |name: &str, total: u64, used: u64, page_size_log2: u64| {
+        println!(
+            "{:8} {:>10} {:>10} {:>10} {:>12}",
+            name,
+            total,
+            used,
+            total.saturating_sub(used),
+            fmt_size(total << page_size_log2, units),
+        );
+    };
+    zone_line(
+        "4K",
+        stats.small_pages_total,
         stats.used_pages,
+        PAGE_SIZE_SMALL_LOG2,
+    );
+    zone_line(
+        "2M",
+        stats.mid_pages_total,
+        stats.mid_pages_used,
+        PAGE_SIZE_MID_LOG2,
+    );
+    zone_line(
+        "2M-huge",
+        stats.huge_pages_total,
+        stats.huge_pages_used,
+        PAGE_SIZE_MID_LOG2,
+    );
+
+    println!(
+        "\nMapped: user {}, kernel {}; kernel heap {}",
+        fmt_size(stats.user_pages << PAGE_SIZE_SMALL_LOG2, units),
+        fmt_size(stats.kernel_pages << PAGE_SIZE_SMALL_LOG2, units),
+        fmt_size(stats.heap_total, units),
     );
 }
@@ -0,0 +1,87 @@
+use moto_runtime::rt_api::fs::*;
+use moto_sys_io::fs::Watcher;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print changes of files and directories as they happen.");
+    eprintln!("usage:\n\tfswatch [-1] PATH...\n");
+    eprintln!("\t-1: exit after the first event.");
+    eprintln!("\nA watched directory reports changes of its entries, but not of");
+    eprintln!("entries of its subdirectories.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn event_name(event: u16) -> &'static str {
+    match event {
+        WATCH_EVENT_CREATE => "CREATE",
+        WATCH_EVENT_MODIFY => "MODIFY",
+        WATCH_EVENT_DELETE => "DELETE",
+        WATCH_EVENT_RENAME_FROM => "RENAME_FROM",
+        WATCH_EVENT_RENAME_TO => "RENAME_TO",
+        WATCH_EVENT_ATTRIB => "ATTRIB",
+        WATCH_EVENT_OVERFLOW => "OVERFLOW",
+        _ => "?",
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "fswatch");
+
+    let mut once = false;
+    let mut paths = vec![];
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-1" => once = true,
+            _ if arg.starts_with('-') => print_usage_and_exit(1),
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    if paths.is_empty() {
+        print_usage_and_exit(1);
+    }
+
+    let mut watcher = match Watcher::new() {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            eprintln!("fswatch: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // Watch descriptors are allocated in order, so wd N is paths[N - 1].
+    for path in &paths {
+        let result = std::fs::canonicalize(path)
+            .map_err(|err| format!("{:?}", err.kind()))
+            .and_then(|abs_path| {
+                watcher
+                    .add(abs_path.to_str().unwrap_or(""))
+                    .map_err(|err| format!("{:?}", err))
+            });
+        if let Err(err) = result {
+            eprintln!("fswatch: {}: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+
+    loop {
+        let event = match watcher.wait_event(None) {
+            Ok(event) => event,
+            Err(err) => {
+                eprintln!("fswatch: {:?}", err);
+                std::process::exit(1);
+            }
+        };
+
+        let path = match paths.get((event.wd as usize).wrapping_sub(1)) {
+            Some(path) if event.name.is_empty() => path.to_string(),
+            Some(path) => format!("{}/{}", path.trim_end_matches('/'), event.name),
+            None => String::new(),
+        };
+        println!("{} {}", event_name(event.event), path);
+
+        if once {
+            break;
+        }
+    }
+}
@@ -0,0 +1,83 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print extended attributes of files.");
+    eprintln!("usage:\n\tgetfattr [-n NAME] FILE...\n");
+    eprintln!("\t-n: print only the attribute NAME (default: all).");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+// Printable values are quoted, others are in hex, like getfattr -e text/hex.
+fn format_value(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(s) if !s.chars().any(|c| c.is_control()) => format!("\"{}\"", s.escape_default()),
+        _ => {
+            let mut hex = "0x".to_owned();
+            for b in value {
+                hex.push_str(format!("{:02x}", b).as_str());
+            }
+            hex
+        }
+    }
+}
+
+fn print_xattrs(path: &str, name: Option<&str>) -> Result<(), String> {
+    let abs_path = std::fs::canonicalize(path).map_err(|err| format!("{:?}", err.kind()))?;
+    let abs_path = abs_path.to_str().unwrap_or("");
+
+    let names = match name {
+        Some(name) => vec![name.to_owned()],
+        None => moto_sys_io::fs::list_xattrs(abs_path).map_err(|err| format!("{:?}", err))?,
+    };
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    println!("# file: {}", path);
+    for name in &names {
+        let value = moto_sys_io::fs::get_xattr(abs_path, name.as_str())
+            .map_err(|err| format!("{}: {:?}", name, err))?;
+        println!("{}={}", name, format_value(value.as_slice()));
+    }
+    println!();
+    Ok(())
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "getfattr");
+
+    let mut name = None;
+    let mut paths = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-n" => {
+                idx += 1;
+                let Some(arg) = args.get(idx) else {
+                    print_usage_and_exit(1);
+                };
+                name = Some(arg.as_str());
+            }
+            _ if !arg.starts_with('-') => paths.push(arg),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    if paths.is_empty() {
+        print_usage_and_exit(1);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        if let Err(err) = print_xattrs(path, name) {
+            eprintln!("getfattr: {}: {}", path, err);
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
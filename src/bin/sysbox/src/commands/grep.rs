@@ -0,0 +1,289 @@
+use regex::bytes::{Regex, RegexBuilder};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print lines matching a regular expression.");
+    eprintln!("usage:\n\tgrep [-r] [-n] [-i] [-v] [-l | -c] [-F] [-a] PATTERN [PATH...]\n");
+    eprintln!("\t-r: search directories recursively (default PATH: the current directory).");
+    eprintln!("\t-n: print line numbers.");
+    eprintln!("\t-i: ignore case.");
+    eprintln!("\t-v: print lines that do not match.");
+    eprintln!("\t-l: print only the names of files with matches (overrides -c).");
+    eprintln!("\t-c: print only the number of matching lines per file.");
+    eprintln!("\t-F: PATTERN is a fixed string, not a regular expression.");
+    eprintln!(
+        "\t-a: search binary files as text (by default, only whether they match is printed)."
+    );
+    eprintln!("\tPATH: default: stdin (without -r); '-' is stdin.");
+    eprintln!("\nPATTERN syntax: https://docs.rs/regex/latest/regex/#syntax");
+    eprintln!("Exit code: 0 if a line matched, 1 if none did, 2 on errors.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+// Gives stderr output time to reach the console, as print_usage_and_exit() does.
+fn exit(exit_code: i32) -> ! {
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+// Like GNU grep, files with NUL bytes at the beginning are considered binary.
+const BINARY_CHECK_BYTES: usize = 8 * 1024;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Lines,
+    FileNames,
+    Counts,
+}
+
+struct Options {
+    recursive: bool,
+    line_numbers: bool,
+    invert: bool,
+    text: bool,
+    output: Output,
+    with_file_names: bool,
+}
+
+struct Grep {
+    regex: Regex,
+    opts: Options,
+    matched: bool,
+    failed: bool,
+}
+
+impl Grep {
+    fn error(&mut self, path: &Path, err: std::io::Error) {
+        eprintln!("grep: {}: {:?}", path.display(), err.kind());
+        self.failed = true;
+    }
+
+    fn search(&mut self, reader: &mut dyn BufRead, name: &str) -> std::io::Result<()> {
+        let is_binary = if self.opts.text {
+            false
+        } else {
+            let buf = reader.fill_buf()?;
+            buf[0..buf.len().min(BINARY_CHECK_BYTES)].contains(&0)
+        };
+
+        let stdout = std::io::stdout();
+        let mut out = std::io::BufWriter::new(stdout.lock());
+        let mut line = Vec::new();
+        let mut line_no = 0_u64;
+        let mut count = 0_u64;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            line_no += 1;
+
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if self.regex.is_match(content) == self.opts.invert {
+                continue;
+            }
+            count += 1;
+            self.matched = true;
+
+            if self.opts.output == Output::FileNames {
+                writeln!(out, "{}", name)?;
+                return Ok(());
+            }
+            if self.opts.output == Output::Counts {
+                continue;
+            }
+            if is_binary {
+                writeln!(out, "Binary file {} matches", name)?;
+                return Ok(());
+            }
+
+            if self.opts.with_file_names {
+                write!(out, "{}:", name)?;
+            }
+            if self.opts.line_numbers {
+                write!(out, "{}:", line_no)?;
+            }
+            out.write_all(content)?;
+            out.write_all(b"\n")?;
+        }
+
+        if self.opts.output == Output::Counts {
+            if self.opts.with_file_names {
+                write!(out, "{}:", name)?;
+            }
+            writeln!(out, "{}", count)?;
+        }
+        out.flush()
+    }
+
+    fn search_file(&mut self, path: &Path) {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) => return self.error(path, err),
+        };
+        let mut reader = std::io::BufReader::new(file);
+        if let Err(err) = self.search(&mut reader, path.to_string_lossy().as_ref()) {
+            self.error(path, err);
+        }
+    }
+
+    // Symlinks are followed in PATHs given on the command line; inside
+    // directories, as with GNU grep -r, only those to files are.
+    fn search_path(&mut self, path: &Path, follow_symlinks: bool) {
+        if path == Path::new("-") {
+            let stdin = std::io::stdin();
+            if let Err(err) = self.search(&mut stdin.lock(), "(standard input)") {
+                self.error(path, err);
+            }
+            return;
+        }
+
+        let metadata = if follow_symlinks {
+            std::fs::metadata(path)
+        } else {
+            std::fs::symlink_metadata(path)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(err) => return self.error(path, err),
+        };
+        if metadata.is_symlink() {
+            // Links to directories may loop.
+            if !path.is_dir() {
+                self.search_file(path);
+            }
+            return;
+        }
+        if !metadata.is_dir() {
+            return self.search_file(path);
+        }
+        if !self.opts.recursive {
+            eprintln!("grep: {}: is a directory", path.display());
+            self.failed = true;
+            return;
+        }
+
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => return self.error(path, err),
+        };
+        let mut paths = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => paths.push(entry.path()),
+                Err(err) => self.error(path, err),
+            }
+        }
+        paths.sort();
+        for path in paths {
+            self.search_path(&path, false);
+        }
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "grep");
+
+    let mut opts = Options {
+        recursive: false,
+        line_numbers: false,
+        invert: false,
+        text: false,
+        output: Output::Lines,
+        with_file_names: false,
+    };
+    let mut ignore_case = false;
+    let mut fixed = false;
+    let mut pattern = None;
+    let mut paths = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        idx += 1;
+        if pattern.is_some() || !arg.starts_with('-') || arg == "-" {
+            if pattern.is_none() {
+                pattern = Some(arg);
+            } else {
+                paths.push(arg);
+            }
+            continue;
+        }
+        if arg == "--help" {
+            print_usage_and_exit(0);
+        }
+        if arg == "--" {
+            match args.get(idx) {
+                Some(arg) => pattern = Some(arg.as_str()),
+                None => print_usage_and_exit(2),
+            }
+            idx += 1;
+            continue;
+        }
+
+        // Flags can be combined, e.g. -rn.
+        for flag in arg[1..].chars() {
+            match flag {
+                'r' | 'R' => opts.recursive = true,
+                'n' => opts.line_numbers = true,
+                'i' => ignore_case = true,
+                'v' => opts.invert = true,
+                'a' => opts.text = true,
+                'l' => opts.output = Output::FileNames,
+                'c' if opts.output != Output::FileNames => opts.output = Output::Counts,
+                'c' => {}
+                'F' => fixed = true,
+                'E' => {} // Extended syntax is the default.
+                _ => print_usage_and_exit(2),
+            }
+        }
+    }
+
+    let Some(pattern) = pattern else {
+        print_usage_and_exit(2);
+    };
+    let pattern = if fixed {
+        regex::escape(pattern)
+    } else {
+        pattern.to_owned()
+    };
+    let regex = match RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(err) => {
+            eprintln!("grep: bad pattern: {}", err);
+            exit(2);
+        }
+    };
+
+    if paths.is_empty() && opts.recursive {
+        paths.push(".");
+    }
+    opts.with_file_names = paths.len() > 1 || opts.recursive;
+
+    let mut grep = Grep {
+        regex,
+        opts,
+        matched: false,
+        failed: false,
+    };
+    if paths.is_empty() {
+        paths.push("-");
+    }
+    for path in paths {
+        grep.search_path(Path::new(path), true);
+    }
+
+    exit(if grep.failed {
+        2
+    } else if grep.matched {
+        0
+    } else {
+        1
+    });
+}
@@ -0,0 +1,256 @@
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print a file as hex (like xxd), or convert such a dump back into binary.");
+    eprintln!("usage:\n\thexdump [-s [-]OFFSET] [-n LEN] [-c COLS] [FILE]");
+    eprintln!("\thexdump -r [FILE [OUTFILE]]\n");
+    eprintln!("\t-s: start at OFFSET (from the end of FILE if negative).");
+    eprintln!("\t-n: stop after LEN bytes.");
+    eprintln!("\t-c: print COLS bytes per line (default: 16).");
+    eprintln!("\t-r: reverse: read a dump and write the binary to OUTFILE (default: stdout).");
+    eprintln!("\tFILE: default: stdin; '-' is stdin.");
+    eprintln!("\nNumbers are decimal, or hex with a 0x prefix. Also available as 'xxd'.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const DEFAULT_COLS: usize = 16;
+const MAX_COLS: usize = 256;
+
+fn parse_number(arg: &str) -> Option<u64> {
+    match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => arg.parse().ok(),
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert!(args[0] == "hexdump" || args[0] == "xxd");
+
+    let mut seek: Option<(bool, u64)> = None; // (from the end, offset).
+    let mut len: Option<u64> = None;
+    let mut cols = DEFAULT_COLS;
+    let mut reverse = false;
+    let mut paths = vec![];
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-r" => reverse = true,
+            "-s" | "-n" | "-c" => {
+                idx += 1;
+                if idx >= args.len() {
+                    print_usage_and_exit(1);
+                }
+                let val = args[idx].as_str();
+                let (from_end, val) = match val.strip_prefix('-') {
+                    Some(val) if arg == "-s" => (true, val),
+                    _ => (false, val),
+                };
+                let Some(num) = parse_number(val) else {
+                    eprintln!("hexdump: bad number '{}'", args[idx]);
+                    std::process::exit(1);
+                };
+                match arg {
+                    "-s" => seek = Some((from_end, num)),
+                    "-n" => len = Some(num),
+                    _ => {
+                        if num == 0 || num as usize > MAX_COLS {
+                            eprintln!("hexdump: COLS must be between 1 and {}", MAX_COLS);
+                            std::process::exit(1);
+                        }
+                        cols = num as usize;
+                    }
+                }
+            }
+            "-" => paths.push(arg),
+            _ if arg.starts_with('-') => print_usage_and_exit(1),
+            _ => paths.push(arg),
+        }
+        idx += 1;
+    }
+
+    let result = if reverse {
+        if seek.is_some() || len.is_some() || paths.len() > 2 {
+            print_usage_and_exit(1);
+        }
+        reverse_dump(paths.first().copied(), paths.get(1).copied())
+    } else {
+        if paths.len() > 1 {
+            print_usage_and_exit(1);
+        }
+        dump(paths.first().copied(), seek, len, cols)
+    };
+
+    if let Err(err) = result {
+        eprintln!("hexdump: {:?}", err.kind());
+        std::process::exit(1);
+    }
+}
+
+fn open_input(path: Option<&str>) -> std::io::Result<Box<dyn BufRead>> {
+    match path {
+        None | Some("-") => Ok(Box::new(std::io::BufReader::new(std::io::stdin()))),
+        Some(path) => match std::fs::File::open(path) {
+            Ok(file) => Ok(Box::new(std::io::BufReader::new(file))),
+            Err(err) => {
+                eprintln!("hexdump: {}: {:?}", path, err.kind());
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+fn dump(
+    path: Option<&str>,
+    seek: Option<(bool, u64)>,
+    len: Option<u64>,
+    cols: usize,
+) -> std::io::Result<()> {
+    let mut offset = 0;
+    let reader = match (path, seek) {
+        // Files are seekable; stdin is skipped over.
+        (Some(path), Some((from_end, seek))) if path != "-" => {
+            let mut file = match std::fs::File::open(path) {
+                Ok(file) => file,
+                Err(err) => {
+                    eprintln!("hexdump: {}: {:?}", path, err.kind());
+                    std::process::exit(1);
+                }
+            };
+            offset = if from_end {
+                let size = file.metadata()?.len();
+                file.seek(SeekFrom::Start(size.saturating_sub(seek)))?
+            } else {
+                file.seek(SeekFrom::Start(seek))?
+            };
+            Box::new(std::io::BufReader::new(file))
+        }
+        (_, Some((true, _))) => {
+            eprintln!("hexdump: can't seek from the end of stdin");
+            std::process::exit(1);
+        }
+        (_, Some((false, seek))) => {
+            let mut reader = open_input(path)?;
+            offset = std::io::copy(&mut (&mut reader).take(seek), &mut std::io::sink())?;
+            reader
+        }
+        (_, None) => open_input(path)?,
+    };
+    let mut reader: Box<dyn Read> = match len {
+        Some(len) => Box::new(reader.take(len)),
+        None => Box::new(reader),
+    };
+
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    let mut buf = vec![0_u8; cols];
+    loop {
+        // Fill whole lines, so that short reads (e.g. from pipes) don't
+        // produce short lines.
+        let mut sz = 0;
+        while sz < cols {
+            match reader.read(&mut buf[sz..]) {
+                Ok(0) => break,
+                Ok(n) => sz += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if sz == 0 {
+            break;
+        }
+        write_line(&mut out, offset, &buf[0..sz], cols)?;
+        offset += sz as u64;
+    }
+    out.flush()
+}
+
+// 00000010: 6865 6c6c 6f0a                           hello.
+fn write_line(out: &mut impl Write, offset: u64, bytes: &[u8], cols: usize) -> std::io::Result<()> {
+    let mut line = format!("{:08x}: ", offset);
+    for idx in 0..cols {
+        match bytes.get(idx) {
+            Some(byte) => line.push_str(&format!("{:02x}", byte)),
+            None => line.push_str("  "),
+        }
+        if idx % 2 == 1 || idx == cols - 1 {
+            line.push(' ');
+        }
+    }
+    line.push(' ');
+    for byte in bytes {
+        line.push(if byte.is_ascii_graphic() || *byte == b' ' {
+            *byte as char
+        } else {
+            '.'
+        });
+    }
+    writeln!(out, "{}", line)
+}
+
+// Parses a dump line into its offset and bytes; the ASCII column, which
+// starts after two spaces, is ignored.
+fn parse_line(line: &str) -> Option<(u64, Vec<u8>)> {
+    let (offset, rest) = line.split_once(':')?;
+    let offset = u64::from_str_radix(offset.trim(), 16).ok()?;
+
+    let mut bytes = vec![];
+    let mut rest = rest.as_bytes();
+    loop {
+        match rest {
+            [b' ', b' ', ..] | [] => break,
+            [b' ', tail @ ..] => rest = tail,
+            [hi, lo, tail @ ..] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                let digits = [*hi, *lo];
+                let hex = std::str::from_utf8(&digits).unwrap();
+                bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                rest = tail;
+            }
+            _ => break,
+        }
+    }
+    Some((offset, bytes))
+}
+
+fn reverse_dump(path: Option<&str>, out_path: Option<&str>) -> std::io::Result<()> {
+    let reader = open_input(path)?;
+    let mut out: Box<dyn Write> = match out_path {
+        None | Some("-") => Box::new(std::io::BufWriter::new(std::io::stdout())),
+        Some(out_path) => match std::fs::File::create(out_path) {
+            Ok(file) => Box::new(std::io::BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("hexdump: {}: {:?}", out_path, err.kind());
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let mut pos = 0_u64; // Bytes written so far.
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((offset, bytes)) = parse_line(&line) else {
+            eprintln!("hexdump: line {}: not a hex dump line", line_no + 1);
+            std::process::exit(1);
+        };
+        if offset < pos {
+            eprintln!(
+                "hexdump: line {}: offset {:x} goes backwards",
+                line_no + 1,
+                offset
+            );
+            std::process::exit(1);
+        }
+
+        // Gaps between lines (e.g. from a dump with -s) are zero-filled.
+        std::io::copy(&mut std::io::repeat(0).take(offset - pos), &mut out)?;
+        out.write_all(&bytes)?;
+        pos = offset + bytes.len() as u64;
+    }
+    out.flush()
+}
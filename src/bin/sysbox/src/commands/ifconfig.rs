@@ -0,0 +1,138 @@
+use std::net::IpAddr;
+
+use moto_sys::ErrorCode;
+use moto_sys_io::netcfg::{InterfaceInfoV1, NetCfgService};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Show or change network interfaces. Changes last until sys-io restarts;");
+    eprintln!("/sys/cfg/sys-net.toml has the startup configuration.");
+    eprintln!("usage:");
+    eprintln!("\tifconfig [IFACE]                show interfaces");
+    eprintln!("\tifconfig IFACE up|down          bring the link up or down");
+    eprintln!("\tifconfig IFACE mtu MTU          set the MTU");
+    eprintln!("\tifconfig IFACE add ADDR/PREFIX  add an address");
+    eprintln!("\tifconfig IFACE del ADDR         remove an address\n");
+    std::process::exit(exit_code);
+}
+
+fn exit_with_error(ifname: &str, err: ErrorCode) -> ! {
+    let reason = match err {
+        ErrorCode::NotAllowed => "permission denied".to_owned(),
+        ErrorCode::NotFound => "no such interface or address".to_owned(),
+        ErrorCode::AlreadyInUse => "address already in use".to_owned(),
+        ErrorCode::OutOfMemory => "too many addresses".to_owned(),
+        ErrorCode::InvalidArgument => "invalid argument".to_owned(),
+        ErrorCode::NotImplemented => "not supported on this interface".to_owned(),
+        err => format!("{:?}", err),
+    };
+    eprintln!("ifconfig: {}: {}", ifname, reason);
+    std::process::exit(1);
+}
+
+fn print_interface(info: &InterfaceInfoV1) {
+    let mut flags = vec![];
+    if info.is_up() {
+        flags.push("UP");
+    }
+    if info.is_loopback() {
+        flags.push("LOOPBACK");
+    }
+    println!(
+        "{}: flags=<{}> mtu {}",
+        info.name(),
+        flags.join(","),
+        info.mtu
+    );
+
+    if !info.is_loopback() {
+        let mac = &info.mac;
+        println!(
+            "        ether {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        );
+    }
+    for cidr in info.addrs() {
+        let family = if cidr.addr().is_ipv4() {
+            "inet "
+        } else {
+            "inet6"
+        };
+        println!("        {} {:?}", family, cidr);
+    }
+
+    let stats = &info.stats;
+    println!(
+        "        RX packets {}  bytes {}  dropped {}",
+        stats.rx_packets, stats.rx_bytes, stats.rx_dropped
+    );
+    println!(
+        "        TX packets {}  bytes {}",
+        stats.tx_packets, stats.tx_bytes
+    );
+}
+
+fn parse_cidr(arg: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix_len) = arg.split_once('/')?;
+    Some((addr.parse().ok()?, prefix_len.parse().ok()?))
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "ifconfig");
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print_usage_and_exit(0);
+    }
+
+    let mut svc = match NetCfgService::connect() {
+        Ok(svc) => svc,
+        Err(err) => {
+            eprintln!("ifconfig: cannot connect to sys-io: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.len() <= 2 {
+        let interfaces = svc
+            .list_interfaces()
+            .unwrap_or_else(|err| exit_with_error("list", err));
+        let mut found = false;
+        for info in &interfaces {
+            if args.len() == 2 && info.name() != args[1] {
+                continue;
+            }
+            if found {
+                println!();
+            }
+            print_interface(info);
+            found = true;
+        }
+        if args.len() == 2 && !found {
+            exit_with_error(&args[1], ErrorCode::NotFound);
+        }
+        return;
+    }
+
+    let ifname = args[1].as_str();
+    let cmd: Vec<&str> = args[2..].iter().map(String::as_str).collect();
+    let result = match cmd[..] {
+        ["up"] => svc.set_link(ifname, true),
+        ["down"] => svc.set_link(ifname, false),
+        ["mtu", mtu] => match mtu.parse::<u16>() {
+            Ok(mtu) => svc.set_mtu(ifname, mtu),
+            Err(_) => print_usage_and_exit(1),
+        },
+        ["add", cidr] => match parse_cidr(cidr) {
+            Some((addr, prefix_len)) => svc.add_addr(ifname, addr, prefix_len),
+            None => print_usage_and_exit(1),
+        },
+        ["del", addr] => match addr.split('/').next().unwrap().parse::<IpAddr>() {
+            Ok(addr) => svc.del_addr(ifname, addr),
+            Err(_) => print_usage_and_exit(1),
+        },
+        _ => print_usage_and_exit(1),
+    };
+
+    if let Err(err) = result {
+        exit_with_error(ifname, err);
+    }
+}
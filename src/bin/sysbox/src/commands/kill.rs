@@ -1,26 +1,173 @@
+use moto_sys::stats::ProcessStatsV1;
+use std::time::{Duration, Instant};
+
 fn print_usage_and_exit(exit_code: i32) -> ! {
-    eprintln!("usage:\n\tkill $PID\n");
+    eprintln!("usage:\n\tkill [-f | -t SECS] [-n] [-w SECS] $PID|$NAME...\n");
+    eprintln!("By default, processes are asked to terminate; processes that do not");
+    eprintln!("listen for termination requests are killed right away.");
+    eprintln!("\t-f: kill (do not ask to terminate).");
+    eprintln!("\t-t: kill processes still running SECS seconds after asking them to terminate.");
+    eprintln!("\t-n: the arguments are process names (executables), not PIDs.");
+    eprintln!("\t-w: wait up to SECS seconds for processes to exit (default: 5).");
     std::process::exit(exit_code);
 }
 
+const DEFAULT_WAIT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+fn parse_secs(arg: Option<&String>) -> Duration {
+    match arg.and_then(|arg| arg.parse::<f64>().ok()) {
+        Some(secs) if secs >= 0.0 => Duration::from_secs_f64(secs),
+        _ => print_usage_and_exit(1),
+    }
+}
+
+fn is_running(pid: u64) -> bool {
+    let mut stats = [ProcessStatsV1::default()];
+    match ProcessStatsV1::list(pid, &mut stats) {
+        Ok(1) => stats[0].pid == pid && stats[0].active == 1,
+        _ => false,
+    }
+}
+
+// Waits until all @pids exit or @timeout expires; returns PIDs still running.
+fn wait_for_exit(mut pids: Vec<u64>, timeout: Duration) -> Vec<u64> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        pids.retain(|pid| is_running(*pid));
+        if pids.is_empty() || Instant::now() >= deadline {
+            return pids;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// The executable name of the process: the first word of its command line,
+// without the directory.
+fn exe_name(stats: &ProcessStatsV1) -> &str {
+    let exe = stats.cmdline().split(' ').next().unwrap_or("");
+    exe.rsplit('/').next().unwrap_or(exe)
+}
+
+fn pids_by_name(names: &[&String]) -> Vec<u64> {
+    const MAX_PROCS: usize = 1024;
+    let mut processes = Vec::with_capacity(MAX_PROCS);
+    for _ in 0..MAX_PROCS {
+        processes.push(ProcessStatsV1::default());
+    }
+    let cnt = match ProcessStatsV1::list(moto_sys::stats::PID_SYSTEM, &mut processes[..]) {
+        Ok(cnt) => cnt,
+        Err(err) => {
+            eprintln!("kill: failed to list processes: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let self_pid = moto_sys::ProcessStaticPage::get().pid;
+    let mut pids = Vec::new();
+    for name in names {
+        let mut found = false;
+        for stats in &processes[0..cnt] {
+            if stats.active == 1
+                && stats.pid != self_pid
+                && stats.pid > moto_sys::stats::PID_KERNEL
+                && exe_name(stats) == name.as_str()
+            {
+                pids.push(stats.pid);
+                found = true;
+            }
+        }
+        if !found {
+            eprintln!("kill: no process named '{}'", name);
+        }
+    }
+    pids
+}
+
 pub fn do_command(args: &[String]) {
     assert_eq!(args[0], "kill");
 
-    if args.len() != 2 {
+    let mut force = false;
+    let mut force_after = None;
+    let mut by_name = false;
+    let mut wait = DEFAULT_WAIT;
+    let mut targets = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-f" | "-9" => force = true,
+            "-n" => by_name = true,
+            "-t" => {
+                idx += 1;
+                force_after = Some(parse_secs(args.get(idx)));
+            }
+            "-w" => {
+                idx += 1;
+                wait = parse_secs(args.get(idx));
+            }
+            arg if arg.starts_with('-') => print_usage_and_exit(1),
+            _ => targets.push(&args[idx]),
+        }
+        idx += 1;
+    }
+    if targets.is_empty() || (force && force_after.is_some()) {
         print_usage_and_exit(1);
     }
 
-    let arg_str = args[1].as_str();
-    if arg_str == "--help" {
-        print_usage_and_exit(0);
+    let pids = if by_name {
+        pids_by_name(&targets)
+    } else {
+        targets
+            .iter()
+            .map(|arg| match arg.parse::<u64>() {
+                Ok(pid) => pid,
+                Err(_) => print_usage_and_exit(1),
+            })
+            .collect()
+    };
+
+    let mut signalled = Vec::new();
+    let mut failed = false;
+    for pid in pids {
+        let result = if force {
+            moto_sys::SysCpu::kill_pid(pid)
+        } else {
+            moto_sys::SysCpu::terminate_pid(pid).map(|_| ())
+        };
+        match result {
+            Ok(()) => signalled.push(pid),
+            Err(err) => {
+                eprintln!("kill: {}: failed: {:?}", pid, err);
+                failed = true;
+            }
+        }
     }
 
-    let pid = match arg_str.parse::<u64>() {
-        Ok(pid) => pid,
-        Err(_) => print_usage_and_exit(1),
-    };
+    let mut running = signalled.clone();
+    if let Some(timeout) = force_after {
+        running = wait_for_exit(running, timeout);
+        for pid in &running {
+            eprintln!("kill: {}: did not terminate; killing", pid);
+            if let Err(err) = moto_sys::SysCpu::kill_pid(*pid) {
+                eprintln!("kill: {}: failed: {:?}", pid, err);
+                failed = true;
+            }
+        }
+    }
+
+    let running = wait_for_exit(running, wait);
+    for pid in &signalled {
+        if running.contains(pid) {
+            eprintln!("kill: {}: still running", pid);
+            failed = true;
+        } else {
+            println!("{}: exited", pid);
+        }
+    }
 
-    if let Err(err) = moto_sys::SysCpu::kill_pid(pid) {
-        eprintln!("kill failed: {:?}", err);
+    if failed {
+        std::process::exit(1);
     }
 }
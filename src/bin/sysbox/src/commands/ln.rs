@@ -0,0 +1,31 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Create a hard or a symbolic link.");
+    eprintln!("usage:\n\tln TARGET LINK\n\tln -s TARGET LINK\n");
+    eprintln!("\t-s: create a symbolic link; otherwise LINK is a hard link to the");
+    eprintln!("\t    existing file TARGET (directories cannot be hard-linked).");
+    eprintln!("\nA symbolic link's TARGET is stored as is and does not have to exist;");
+    eprintln!("a relative TARGET is resolved against the directory containing LINK.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "ln");
+
+    if args.len() == 2 && (args[1] == "-h" || args[1] == "--help") {
+        print_usage_and_exit(0);
+    }
+
+    let result = match args.len() {
+        3 if !args[1].starts_with('-') => std::fs::hard_link(&args[1], &args[2]),
+        // std::fs::soft_link is the platform-independent way to create symlinks.
+        #[allow(deprecated)]
+        4 if args[1] == "-s" => std::fs::soft_link(&args[2], &args[3]),
+        _ => print_usage_and_exit(1),
+    };
+
+    if let Err(err) = result {
+        eprintln!("ln: {}: {:?}", args[args.len() - 1], err.kind());
+        std::process::exit(1);
+    }
+}
@@ -96,6 +96,12 @@ fn list_detailed(dir: &str, list_dots: bool) {
         ("", "")
     };
 
+    let (link_in, link_out) = if std::io::stdout().is_terminal() {
+        ("\x1b[1m\x1b[36m", "\x1b[0m\x1b[0m")
+    } else {
+        ("", "")
+    };
+
     for e in &entries {
         match e {
             Ok(e) => {
@@ -122,6 +128,20 @@ fn list_detailed(dir: &str, list_dots: bool) {
                         file_out,
                         width = size_len,
                     );
+                } else if ft.is_symlink() {
+                    let target = match std::fs::read_link(e.path()) {
+                        Ok(target) => target.display().to_string(),
+                        Err(_) => "?".to_owned(),
+                    };
+                    println!(
+                        "l {:width$} {}{}{} -> {}",
+                        e.metadata().unwrap().len(),
+                        link_in,
+                        fname,
+                        link_out,
+                        target,
+                        width = size_len,
+                    );
                 } else {
                     println!("? {}", fname);
                 }
@@ -173,6 +193,12 @@ fn list_plain(dir: &str, list_dots: bool) {
         ("", "")
     };
 
+    let (link_in, link_out) = if std::io::stdout().is_terminal() {
+        ("\x1b[36m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+
     for e in &entries {
         let ft = e.file_type().unwrap();
         let fname = e.file_name().to_str().unwrap().to_owned();
@@ -186,6 +212,8 @@ fn list_plain(dir: &str, list_dots: bool) {
             print!("{}{}{} ", dir_in, fname, dir_out);
         } else if ft.is_file() {
             print!("{}{}{} ", file_in, fname, file_out);
+        } else if ft.is_symlink() {
+            print!("{}{}{} ", link_in, fname, link_out);
         } else {
             print!("? {}", fname);
         }
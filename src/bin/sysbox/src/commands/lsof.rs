@@ -0,0 +1,161 @@
+use moto_sys::stats::{HandleInfoV1, ProcessStatsV1, PID_KERNEL, PID_SYSTEM};
+use std::collections::BTreeMap;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("List open handles of processes.");
+    eprintln!("Note: files and sockets are served by sys-io over the process's IO channel,");
+    eprintln!("      so they show up as a channel with sys-io as the peer.\n");
+    eprintln!("usage:\n\tlsof [-p PID] [-s]\n");
+    eprintln!("\t-p: list handles of the process PID only.");
+    eprintln!("\t-s: print the number of handles of each type per process.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const PS_BUF_SIZE: usize = 1024;
+const HANDLES_BUF_SIZE: usize = 64;
+
+fn list_processes() -> Vec<ProcessStatsV1> {
+    let mut processes = Vec::with_capacity(PS_BUF_SIZE);
+    processes.resize_with(PS_BUF_SIZE, ProcessStatsV1::default);
+    let cnt = match ProcessStatsV1::list(PID_SYSTEM, &mut processes[..]) {
+        Ok(cnt) => cnt,
+        Err(err) => {
+            eprintln!("lsof: failed to list processes: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+    processes.truncate(cnt);
+    processes.retain(|proc| proc.pid > PID_KERNEL && proc.active == 1);
+    processes
+}
+
+fn list_handles(pid: u64) -> Result<Vec<HandleInfoV1>, moto_sys::ErrorCode> {
+    let mut buf = Vec::with_capacity(HANDLES_BUF_SIZE);
+    buf.resize_with(HANDLES_BUF_SIZE, HandleInfoV1::default);
+
+    let mut handles = Vec::new();
+    let mut start = 0;
+    loop {
+        let cnt = HandleInfoV1::list(pid, start, &mut buf[..])?;
+        handles.extend_from_slice(&buf[0..cnt]);
+        if cnt < HANDLES_BUF_SIZE {
+            return Ok(handles);
+        }
+        start = buf[cnt - 1].handle + 1;
+    }
+}
+
+// The executable name of the process, without the directory.
+fn exe_name(stats: &ProcessStatsV1) -> &str {
+    let exe = stats.cmdline().split(' ').next().unwrap_or("");
+    exe.rsplit('/').next().unwrap_or(exe)
+}
+
+fn format_peer(info: &HandleInfoV1, names: &BTreeMap<u64, &str>) -> String {
+    let mut peer = if info.peer_pid == 0 {
+        "-".to_owned()
+    } else {
+        match names.get(&info.peer_pid) {
+            Some(name) => format!("{}/{}", info.peer_pid, name),
+            None => info.peer_pid.to_string(),
+        }
+    };
+    if info.peer_dropped != 0 {
+        peer.push_str(" (dropped)");
+    }
+    peer
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "lsof");
+
+    let mut pid = None;
+    let mut summary = false;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-s" => summary = true,
+            "-p" if pid.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                match args[idx].parse::<u64>() {
+                    Ok(val) => pid = Some(val),
+                    Err(_) => print_usage_and_exit(1),
+                }
+            }
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let processes = list_processes();
+    let names: BTreeMap<u64, &str> = processes
+        .iter()
+        .map(|proc| (proc.pid, exe_name(proc)))
+        .collect();
+
+    let targets: Vec<&ProcessStatsV1> = match pid {
+        Some(pid) => match processes.iter().find(|proc| proc.pid == pid) {
+            Some(proc) => vec![proc],
+            None => {
+                eprintln!("lsof: {}: no such process", pid);
+                std::process::exit(1);
+            }
+        },
+        None => processes.iter().collect(),
+    };
+
+    if summary {
+        println!("{:>6} {:16} {:>7}  TYPES", "PID", "COMMAND", "HANDLES");
+    } else {
+        println!(
+            "{:>6} {:16} {:>8} {:10} {:20} URL",
+            "PID", "COMMAND", "HANDLE", "TYPE", "PEER"
+        );
+    }
+
+    for proc in targets {
+        let handles = match list_handles(proc.pid) {
+            Ok(handles) => handles,
+            // The process may have exited after it was listed.
+            Err(moto_sys::ErrorCode::NotFound) => continue,
+            Err(err) => {
+                eprintln!("lsof: {}: failed to list handles: {:?}", proc.pid, err);
+                continue;
+            }
+        };
+
+        if summary {
+            let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+            for info in &handles {
+                *kinds.entry(info.kind_str()).or_default() += 1;
+            }
+            let kinds: Vec<String> = kinds
+                .iter()
+                .map(|(kind, cnt)| format!("{}:{}", kind, cnt))
+                .collect();
+            println!(
+                "{:>6} {:16} {:>7}  {}",
+                proc.pid,
+                exe_name(proc),
+                handles.len(),
+                kinds.join(" ")
+            );
+            continue;
+        }
+
+        for info in &handles {
+            println!(
+                "{:>6} {:16} {:>8} {:10} {:20} {}",
+                proc.pid,
+                exe_name(proc),
+                info.handle,
+                info.kind_str(),
+                format_peer(info, &names),
+                info.url()
+            );
+        }
+    }
+}
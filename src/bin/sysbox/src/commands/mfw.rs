@@ -0,0 +1,212 @@
+use std::net::IpAddr;
+
+use moto_sys::ErrorCode;
+use moto_sys_io::netcfg::*;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Show or change the packet filter (firewall). Changes last until sys-io restarts.");
+    eprintln!("usage:");
+    eprintln!("\tmfw                                    list rules, with packet counters");
+    eprintln!("\tmfw add CHAIN ACTION [MATCH...] [at N] add a rule (at position N: insert)");
+    eprintln!("\tmfw del CHAIN N                        remove rule N");
+    eprintln!("\tmfw policy CHAIN ACTION                what packets no rule matches get");
+    eprintln!("\tmfw flush [CHAIN]                      remove all rules\n");
+    eprintln!("CHAIN is `input` or `output`, ACTION is `allow` or `deny`. MATCH is one of:");
+    eprintln!("\tdev IFACE  proto tcp|udp|icmp|NUM  from ADDR[/PREFIX]  to ADDR[/PREFIX]");
+    eprintln!("\tsport PORT[-PORT]  dport PORT[-PORT]\n");
+    eprintln!("Rules are checked in order and the first match wins. The filter is stateless:");
+    eprintln!("with an input policy of `deny`, replies to outgoing connections need rules too,");
+    eprintln!("e.g. `mfw add input allow proto tcp sport 443`.\n");
+    std::process::exit(exit_code);
+}
+
+fn exit_with_error(err: ErrorCode) -> ! {
+    let reason = match err {
+        ErrorCode::NotAllowed => "permission denied".to_owned(),
+        ErrorCode::NotFound => "no such rule or interface".to_owned(),
+        ErrorCode::OutOfMemory => "too many rules".to_owned(),
+        ErrorCode::InvalidArgument => "invalid rule".to_owned(),
+        err => format!("{:?}", err),
+    };
+    eprintln!("mfw: {}", reason);
+    std::process::exit(1);
+}
+
+fn parse_chain(arg: &str) -> u8 {
+    match arg {
+        "input" => FW_CHAIN_INPUT,
+        "output" => FW_CHAIN_OUTPUT,
+        _ => print_usage_and_exit(1),
+    }
+}
+
+fn parse_action(arg: &str) -> u8 {
+    match arg {
+        "allow" => FW_ALLOW,
+        "deny" => FW_DENY,
+        _ => print_usage_and_exit(1),
+    }
+}
+
+fn parse_cidr(arg: &str) -> (IpAddr, u8) {
+    let (addr, prefix_len) = match arg.split_once('/') {
+        Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+        None => (arg, None),
+    };
+    let Ok(addr) = addr.parse::<IpAddr>() else {
+        print_usage_and_exit(1);
+    };
+    let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+    match prefix_len.map(|p| p.parse::<u8>()) {
+        None => (addr, max_prefix_len),
+        Some(Ok(prefix_len)) if prefix_len <= max_prefix_len => (addr, prefix_len),
+        _ => print_usage_and_exit(1),
+    }
+}
+
+fn parse_ports(arg: &str) -> (u16, u16) {
+    let (first, last) = arg.split_once('-').unwrap_or((arg, arg));
+    match (first.parse::<u16>(), last.parse::<u16>()) {
+        (Ok(first), Ok(last)) if first <= last => (first, last),
+        _ => print_usage_and_exit(1),
+    }
+}
+
+// Rule numbers are 1-based.
+fn parse_rule_number(arg: &str) -> usize {
+    match arg.parse::<usize>() {
+        Ok(num) if num > 0 => num - 1,
+        _ => print_usage_and_exit(1),
+    }
+}
+
+// MATCH... [at N] -> (rule, position).
+fn parse_rule(chain: u8, action: u8, args: &[String]) -> (FwRuleV1, Option<usize>) {
+    let mut rule = FwRuleV1::new(chain, action);
+    let mut position = None;
+
+    let mut idx = 0;
+    while idx < args.len() {
+        let Some(value) = args.get(idx + 1) else {
+            print_usage_and_exit(1);
+        };
+        match args[idx].as_str() {
+            "dev" if value.len() <= IFNAME_MAX => rule.set_ifname(value),
+            "proto" => {
+                rule.protocol = match value.as_str() {
+                    "tcp" => FW_PROTO_TCP,
+                    "udp" => FW_PROTO_UDP,
+                    "icmp" => FW_PROTO_ICMP,
+                    num => match num.parse::<u8>() {
+                        Ok(proto) if proto != FW_PROTO_ANY => proto,
+                        _ => print_usage_and_exit(1),
+                    },
+                }
+            }
+            "from" => {
+                let (addr, prefix_len) = parse_cidr(value);
+                rule.set_src(addr, prefix_len);
+            }
+            "to" => {
+                let (addr, prefix_len) = parse_cidr(value);
+                rule.set_dst(addr, prefix_len);
+            }
+            "sport" => {
+                let (first, last) = parse_ports(value);
+                rule.set_src_ports(first, last);
+            }
+            "dport" => {
+                let (first, last) = parse_ports(value);
+                rule.set_dst_ports(first, last);
+            }
+            "at" => position = Some(parse_rule_number(value)),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 2;
+    }
+
+    // Only TCP and UDP have ports.
+    let has_ports = rule.src_ports().is_some() || rule.dst_ports().is_some();
+    if has_ports && rule.protocol != FW_PROTO_TCP && rule.protocol != FW_PROTO_UDP {
+        eprintln!("mfw: ports need `proto tcp` or `proto udp`");
+        std::process::exit(1);
+    }
+
+    (rule, position)
+}
+
+fn policy_name(action: u8) -> &'static str {
+    if action == FW_ALLOW {
+        "allow"
+    } else {
+        "deny"
+    }
+}
+
+fn print_rules(rules: &FwRules) {
+    for (chain, name, policy) in [
+        (FW_CHAIN_INPUT, "input", rules.input_policy),
+        (FW_CHAIN_OUTPUT, "output", rules.output_policy),
+    ] {
+        println!("Chain {} (policy {})", name, policy_name(policy));
+        println!("{:>5} {:>10} {:>12}  rule", "num", "packets", "bytes");
+        for (idx, rule) in rules
+            .rules
+            .iter()
+            .filter(|rule| rule.chain == chain)
+            .enumerate()
+        {
+            println!(
+                "{:>5} {:>10} {:>12}  {:?}",
+                idx + 1,
+                rule.packets,
+                rule.bytes,
+                rule
+            );
+        }
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "mfw");
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print_usage_and_exit(0);
+    }
+
+    let mut svc = match NetCfgService::connect() {
+        Ok(svc) => svc,
+        Err(err) => {
+            eprintln!("mfw: cannot connect to sys-io: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.len() == 1 {
+        let rules = svc
+            .list_fw_rules()
+            .unwrap_or_else(|err| exit_with_error(err));
+        print_rules(&rules);
+        return;
+    }
+
+    let result = match args[1].as_str() {
+        "add" if args.len() >= 4 => {
+            let (rule, position) =
+                parse_rule(parse_chain(&args[2]), parse_action(&args[3]), &args[4..]);
+            svc.add_fw_rule(&rule, position)
+        }
+        "del" if args.len() == 4 => {
+            svc.del_fw_rule(parse_chain(&args[2]), parse_rule_number(&args[3]))
+        }
+        "policy" if args.len() == 4 => {
+            svc.set_fw_policy(parse_chain(&args[2]), parse_action(&args[3]))
+        }
+        "flush" if args.len() == 3 => svc.flush_fw_rules(parse_chain(&args[2])),
+        "flush" if args.len() == 2 => svc
+            .flush_fw_rules(FW_CHAIN_INPUT)
+            .and_then(|_| svc.flush_fw_rules(FW_CHAIN_OUTPUT)),
+        _ => print_usage_and_exit(1),
+    };
+    result.unwrap_or_else(|err| exit_with_error(err));
+}
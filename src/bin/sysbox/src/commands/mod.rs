@@ -1,18 +1,51 @@
+pub mod acct;
 pub mod cat;
+pub mod chmod;
+pub mod chown;
+pub mod crond;
+pub mod crontab;
 pub mod date;
+pub mod display;
+pub mod df;
+pub mod dmesg;
+pub mod du;
 pub mod echo;
+pub mod fallocate;
+pub mod find;
 pub mod free;
+pub mod fswatch;
+pub mod getfattr;
+pub mod grep;
+pub mod hexdump;
+pub mod ifconfig;
 pub mod kill;
+pub mod ln;
 pub mod loop_cmd; // Can't be just 'loop', as it is a keyword.
 pub mod ls;
+pub mod lsof;
+pub mod mfw;
 pub mod mkdir;
+pub mod mount;
 pub mod mv;
+pub mod netstat;
+pub mod ping;
+pub mod pkg;
 pub mod ps;
 pub mod pwd;
+pub mod quota;
+pub mod readlink;
 pub mod rm;
 pub mod rmdir;
+pub mod route;
+pub mod setfattr;
 pub mod sleep;
+pub mod snapshot;
 pub mod ss;
+pub mod strace;
+pub mod svc;
+pub mod tar;
 pub mod time;
+pub mod timesync;
 pub mod top;
 pub mod uptime;
+pub mod watch;
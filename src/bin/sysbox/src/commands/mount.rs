@@ -0,0 +1,98 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("List, add, or remove mounts.");
+    eprintln!("usage:\n\tmount");
+    eprintln!("\tmount [-s SIZE] SOURCE DIR");
+    eprintln!("\tmount -u DIR\n");
+    eprintln!("\tSOURCE: tmpfs, /dev/vdX[N] (FAT or ext2), virtiofs:TAG, or snapshot:NAME");
+    eprintln!("\t        (read-only; of the volume DIR is on);");
+    eprintln!("\t-s: the size limit of a tmpfs, in bytes, or in K, M, or G (1024-based);");
+    eprintln!("\t-u: unmount DIR.");
+    eprintln!("\nMounting and unmounting is for root only.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn parse_size(arg: &str) -> Option<u64> {
+    let (num, shift) = match arg.chars().last()? {
+        'K' | 'k' => (&arg[0..(arg.len() - 1)], 10),
+        'M' | 'm' => (&arg[0..(arg.len() - 1)], 20),
+        'G' | 'g' => (&arg[0..(arg.len() - 1)], 30),
+        _ => (arg, 0),
+    };
+    num.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn list_mounts() {
+    let table = match std::fs::read_to_string("/proc/mounts") {
+        Ok(table) => table,
+        Err(err) => {
+            eprintln!("mount: /proc/mounts: {:?}", err.kind());
+            std::process::exit(1);
+        }
+    };
+
+    for line in table.lines() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        if let [source, mount_point, fs_type] = fields.as_slice() {
+            println!("{} on {} type {}", source, mount_point, fs_type);
+        }
+    }
+}
+
+fn abs_path(path: &str) -> String {
+    match std::fs::canonicalize(path) {
+        Ok(abs_path) => abs_path.to_str().unwrap_or("").to_owned(),
+        Err(err) => {
+            eprintln!("mount: {}: {:?}", path, err.kind());
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "mount");
+
+    let mut max_bytes = 0;
+    let mut unmount = false;
+    let mut paths = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-s" => {
+                idx += 1;
+                match args.get(idx).and_then(|val| parse_size(val)) {
+                    Some(val) => max_bytes = val,
+                    None => print_usage_and_exit(1),
+                }
+            }
+            "-u" => unmount = true,
+            _ if !arg.starts_with('-') => paths.push(arg),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let result = match (unmount, paths.as_slice()) {
+        (false, []) => {
+            list_mounts();
+            return;
+        }
+        (true, [dir]) => {
+            let dir = abs_path(dir);
+            moto_sys_io::fs::unmount(&dir).map_err(|err| (dir, err))
+        }
+        (false, [source, dir]) => {
+            let dir = abs_path(dir);
+            moto_sys_io::fs::mount(source, &dir, max_bytes).map_err(|err| (dir, err))
+        }
+        _ => print_usage_and_exit(1),
+    };
+
+    if let Err((dir, err)) = result {
+        eprintln!("mount: {}: {:?}", dir, err);
+        std::process::exit(1);
+    }
+}
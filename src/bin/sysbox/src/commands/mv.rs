@@ -39,7 +39,9 @@ pub fn do_command(args: &[String]) {
         }
     }
 
-    if new_dir {
+    // As in Unix, an existing directory is moved into; other existing
+    // destinations are replaced (atomically).
+    if new_dir || std::path::Path::new(new).is_dir() {
         // Need to add the filename, otherwise the last slash is lost,
         // but it is meaningful.
         let fname = old_path.file_name().unwrap();
@@ -0,0 +1,252 @@
+use moto_sys::stats::{ProcessStatsV1, PID_SYSTEM};
+use moto_sys_io::netcfg::{InterfaceInfoV1, NetCfgService};
+use moto_sys_io::stats::{IoStatsService, TcpSocketStatsV1, MAX_TCP_SOCKET_STATS};
+use std::collections::BTreeMap;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("List network sockets, as reported by sys-io.");
+    eprintln!("Note: sys-io does not support UDP yet, so only TCP sockets are listed.\n");
+    eprintln!("usage:\n\tnetstat [-a | -l] [-e] [-p PID]\n\tnetstat -i\n\tnetstat -s\n");
+    eprintln!("\t-a: list all sockets (default: all but listening sockets).");
+    eprintln!("\t-l: list listening sockets only.");
+    eprintln!("\t-e: also show bytes, round-trip times and retransmits of each socket.");
+    eprintln!("\t-p: list sockets owned by the process PID only.");
+    eprintln!("\t-i: list network interfaces and their packet counters.");
+    eprintln!("\t-s: show per-protocol and per-interface statistics.");
+    eprintln!("\nColumns:");
+    eprintln!("\tRecv-Q: bytes received but not yet consumed by the owner.");
+    eprintln!("\tSend-Q: bytes sent by the owner but not yet acknowledged by the peer.");
+    eprintln!("\tRecv-Bytes, Send-Bytes (-e): bytes delivered to/accepted from the owner.");
+    eprintln!("\tRTT (-e): smoothed round-trip time and its mean deviation, in ms.");
+    eprintln!("\tCwnd (-e): congestion window in bytes; '-' without congestion control.");
+    eprintln!("\tRetrans (-e): segments sent again.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const PS_BUF_SIZE: usize = 1024;
+
+#[derive(PartialEq)]
+enum Selection {
+    Connected,
+    Listening,
+    All,
+}
+
+fn list_interfaces() -> Vec<InterfaceInfoV1> {
+    NetCfgService::connect()
+        .and_then(|mut svc| svc.list_interfaces())
+        .unwrap_or_else(|err| {
+            eprintln!("netstat: failed to list interfaces: {:?}", err);
+            std::process::exit(1);
+        })
+}
+
+fn print_interfaces() {
+    println!(
+        "{:15} {:>5} {:>10} {:>8} {:>10} Flg",
+        "Iface", "MTU", "RX-OK", "RX-DRP", "TX-OK"
+    );
+    for info in &list_interfaces() {
+        let mut flags = String::new();
+        if info.is_loopback() {
+            flags.push('L');
+        }
+        if info.is_up() {
+            flags.push('U');
+        }
+        println!(
+            "{:15} {:>5} {:>10} {:>8} {:>10} {}",
+            info.name(),
+            info.mtu,
+            info.stats.rx_packets,
+            info.stats.rx_dropped,
+            info.stats.tx_packets,
+            flags
+        );
+    }
+}
+
+fn print_statistics() {
+    let stats = IoStatsService::connect()
+        .and_then(|mut svc| svc.get_net_stats())
+        .unwrap_or_else(|err| {
+            eprintln!("netstat: failed to get network stats: {:?}", err);
+            std::process::exit(1);
+        });
+
+    println!("Tcp:");
+    println!("    {} active connection openings", stats.tcp_active_opens);
+    println!(
+        "    {} passive connection openings",
+        stats.tcp_passive_opens
+    );
+    println!(
+        "    {} failed connection attempts",
+        stats.tcp_failed_connects
+    );
+    println!("    {} connections established", stats.tcp_established);
+    println!("    {} bytes received", stats.tcp_rx_bytes);
+    println!("    {} bytes sent", stats.tcp_tx_bytes);
+    println!("Udp:");
+    println!("    {} datagrams received", stats.udp_rx_datagrams);
+    println!("    {} datagrams sent", stats.udp_tx_datagrams);
+    println!("    {} send errors", stats.udp_tx_errors);
+
+    for info in &list_interfaces() {
+        println!("{}:", info.name());
+        println!(
+            "    RX: {} packets, {} bytes, {} dropped",
+            info.stats.rx_packets, info.stats.rx_bytes, info.stats.rx_dropped
+        );
+        println!(
+            "    TX: {} packets, {} bytes",
+            info.stats.tx_packets, info.stats.tx_bytes
+        );
+    }
+}
+
+fn list_tcp_sockets() -> Vec<TcpSocketStatsV1> {
+    let mut svc = match IoStatsService::connect() {
+        Ok(svc) => svc,
+        Err(err) => {
+            eprintln!("netstat: failed to connect to sys-io: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut sockets = Vec::new();
+    let mut start_id = 0;
+    loop {
+        let stats = match svc.get_tcp_socket_stats(start_id) {
+            Ok(stats) => stats,
+            Err(err) => {
+                eprintln!("netstat: failed to get TCP socket stats: {:?}", err);
+                std::process::exit(1);
+            }
+        };
+        sockets.extend_from_slice(stats);
+        if stats.len() < MAX_TCP_SOCKET_STATS {
+            return sockets;
+        }
+        start_id = stats[stats.len() - 1].id + 1;
+    }
+}
+
+// PID -> the executable name of the process, without the directory.
+fn process_names() -> BTreeMap<u64, String> {
+    let mut processes = Vec::with_capacity(PS_BUF_SIZE);
+    processes.resize_with(PS_BUF_SIZE, ProcessStatsV1::default);
+    let cnt = ProcessStatsV1::list(PID_SYSTEM, &mut processes[..]).unwrap_or(0);
+
+    processes[0..cnt]
+        .iter()
+        .map(|proc| {
+            let exe = proc.cmdline().split(' ').next().unwrap_or("");
+            (proc.pid, exe.rsplit('/').next().unwrap_or(exe).to_owned())
+        })
+        .collect()
+}
+
+fn format_addr(addr: Option<std::net::SocketAddr>) -> String {
+    match addr {
+        Some(addr) => addr.to_string(),
+        None => "*:*".to_owned(),
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "netstat");
+
+    if args.len() == 2 && args[1] == "-i" {
+        print_interfaces();
+        return;
+    }
+    if args.len() == 2 && args[1] == "-s" {
+        print_statistics();
+        return;
+    }
+
+    let mut selection = Selection::Connected;
+    let mut pid = None;
+    let mut extended = false;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-a" if selection == Selection::Connected => selection = Selection::All,
+            "-l" if selection == Selection::Connected => selection = Selection::Listening,
+            "-e" if !extended => extended = true,
+            "-p" if pid.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                match args[idx].parse::<u64>() {
+                    Ok(val) => pid = Some(val),
+                    Err(_) => print_usage_and_exit(1),
+                }
+            }
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let sockets = list_tcp_sockets();
+    let names = process_names();
+
+    let byte_columns = if extended {
+        format!(
+            "{:>12} {:>12} {:>11} {:>10} {:>7} ",
+            "Recv-Bytes", "Send-Bytes", "RTT", "Cwnd", "Retrans"
+        )
+    } else {
+        String::new()
+    };
+    println!(
+        "{:5} {:>7} {:>7} {:24} {:24} {:12} {}PID/Program",
+        "Proto", "Recv-Q", "Send-Q", "Local Address", "Foreign Address", "State", byte_columns
+    );
+    for socket in &sockets {
+        let listening = socket.tcp_state == moto_runtime::rt_api::net::TcpState::Listening;
+        match selection {
+            Selection::Connected if listening => continue,
+            Selection::Listening if !listening => continue,
+            _ => {}
+        }
+        if pid.is_some_and(|pid| pid != socket.pid) {
+            continue;
+        }
+
+        let owner = match names.get(&socket.pid) {
+            Some(name) => format!("{}/{}", socket.pid, name),
+            None => socket.pid.to_string(),
+        };
+        let byte_columns = if extended {
+            let cwnd = if socket.cwnd == u32::MAX {
+                "-".to_owned()
+            } else {
+                socket.cwnd.to_string()
+            };
+            format!(
+                "{:>12} {:>12} {:>11} {:>10} {:>7} ",
+                socket.rx_bytes,
+                socket.tx_bytes,
+                format!("{}/{}", socket.rtt_ms, socket.rtt_var_ms),
+                cwnd,
+                socket.retransmits
+            )
+        } else {
+            String::new()
+        };
+        println!(
+            "{:5} {:>7} {:>7} {:24} {:24} {:12} {}{}",
+            "tcp",
+            socket.rx_queue,
+            socket.tx_queue,
+            format_addr(socket.local_addr()),
+            format_addr(socket.remote_addr()),
+            socket.smoltcp_state.to_string(),
+            byte_columns,
+            owner
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use moto_runtime::net::PingSocket;
+use moto_sys::ErrorCode;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Send ICMP echo requests to a host and report round-trip times.");
+    eprintln!("usage:\n\tping [-c COUNT] [-i SECS] [-s SIZE] [-W SECS] HOST\n");
+    eprintln!("\t-c: stop after sending COUNT requests (default: until Ctrl-C).");
+    eprintln!("\t-i: seconds between requests (default: 1; may be fractional).");
+    eprintln!("\t-s: payload bytes per request (default: 56).");
+    eprintln!("\t-W: seconds to wait for replies after the last request (default: 2).");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const DEFAULT_PAYLOAD_SIZE: usize = 56;
+const ICMP_HEADER_SIZE: usize = 8;
+
+// How often to check for Ctrl-C while waiting for replies.
+const QUIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+static QUIT: AtomicBool = AtomicBool::new(false);
+
+fn input_listener() {
+    loop {
+        let mut input = [0_u8; 16];
+        let sz = match std::io::stdin().read(&mut input) {
+            Ok(0) | Err(_) => return, // No console.
+            Ok(sz) => sz,
+        };
+        if input[0..sz].contains(&3 /* ^C */) {
+            QUIT.store(true, Ordering::Release);
+        }
+    }
+}
+
+fn resolve(host: &str) -> IpAddr {
+    if let Ok(addr) = host.parse::<IpAddr>() {
+        return addr;
+    }
+    match (host, 0).to_socket_addrs() {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => addr.ip(),
+            None => {
+                eprintln!("ping: {}: no addresses", host);
+                std::process::exit(2);
+            }
+        },
+        Err(err) => {
+            eprintln!("ping: {}: {}", host, err);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    transmitted: u32,
+    received: u32,
+    duplicates: u32,
+    rtts_ms: Vec<f64>,
+}
+
+impl Stats {
+    fn print(&self, host: &str, elapsed: Duration) {
+        println!("\n--- {} ping statistics ---", host);
+        let loss = if self.transmitted == 0 {
+            0.0
+        } else {
+            100.0 * (self.transmitted - self.received) as f64 / self.transmitted as f64
+        };
+        print!(
+            "{} packets transmitted, {} received, ",
+            self.transmitted, self.received
+        );
+        if self.duplicates > 0 {
+            print!("+{} duplicates, ", self.duplicates);
+        }
+        println!("{:.0}% packet loss, time {}ms", loss, elapsed.as_millis());
+
+        if self.rtts_ms.is_empty() {
+            return;
+        }
+        let count = self.rtts_ms.len() as f64;
+        let min = self.rtts_ms.iter().copied().fold(f64::MAX, f64::min);
+        let max = self.rtts_ms.iter().copied().fold(0.0, f64::max);
+        let avg = self.rtts_ms.iter().sum::<f64>() / count;
+        let mdev = (self.rtts_ms.iter().map(|rtt| rtt * rtt).sum::<f64>() / count - avg * avg)
+            .max(0.0)
+            .sqrt();
+        println!(
+            "rtt min/avg/max/mdev = {:.3}/{:.3}/{:.3}/{:.3} ms",
+            min, avg, max, mdev
+        );
+    }
+}
+
+// Prints the replies that arrive before the deadline (or Ctrl-C). After the last
+// request, there is no need to wait once every request has been replied to.
+fn receive_until(
+    socket: &PingSocket,
+    deadline: Instant,
+    last: bool,
+    sent: &mut HashMap<u16, (Instant, bool)>,
+    stats: &mut Stats,
+    buf: &mut [u8],
+) {
+    loop {
+        let now = Instant::now();
+        if now >= deadline
+            || QUIT.load(Ordering::Acquire)
+            || (last && stats.received == stats.transmitted)
+        {
+            return;
+        }
+        socket
+            .set_read_timeout(Some((deadline - now).min(QUIT_CHECK_INTERVAL)))
+            .unwrap();
+
+        let (sz, from, seq) = match socket.recv_from(buf) {
+            Ok(reply) => reply,
+            Err(ErrorCode::TimedOut) => continue,
+            Err(err) => {
+                eprintln!("ping: recv: {:?}", err);
+                std::process::exit(2);
+            }
+        };
+        let Some((sent_at, replied)) = sent.get_mut(&seq) else {
+            continue; // Not ours, or from long ago.
+        };
+
+        let rtt_ms = sent_at.elapsed().as_secs_f64() * 1000.0;
+        let dup = *replied;
+        if dup {
+            stats.duplicates += 1;
+        } else {
+            *replied = true;
+            stats.received += 1;
+            stats.rtts_ms.push(rtt_ms);
+        }
+        println!(
+            "{} bytes from {}: icmp_seq={} time={:.3} ms{}",
+            sz + ICMP_HEADER_SIZE,
+            from,
+            seq,
+            rtt_ms,
+            if dup { " (DUP!)" } else { "" }
+        );
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "ping");
+
+    let mut count: Option<u32> = None;
+    let mut interval = Duration::from_secs(1);
+    let mut payload_size = DEFAULT_PAYLOAD_SIZE;
+    let mut linger = Duration::from_secs(2);
+
+    let mut idx = 1;
+    while idx < args.len() && args[idx].starts_with('-') {
+        let value = args.get(idx + 1);
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-c" => match value.and_then(|v| v.parse::<u32>().ok()) {
+                Some(c) if c > 0 => count = Some(c),
+                _ => print_usage_and_exit(1),
+            },
+            "-i" => match value.and_then(|v| v.parse::<f64>().ok()) {
+                Some(secs) if secs >= 0.01 => interval = Duration::from_secs_f64(secs),
+                _ => print_usage_and_exit(1),
+            },
+            "-s" => match value.and_then(|v| v.parse::<usize>().ok()) {
+                Some(sz) if sz <= moto_runtime::rt_api::net::PING_MAX_PAYLOAD => payload_size = sz,
+                _ => print_usage_and_exit(1),
+            },
+            "-W" => match value.and_then(|v| v.parse::<f64>().ok()) {
+                Some(secs) if secs >= 0.0 => linger = Duration::from_secs_f64(secs),
+                _ => print_usage_and_exit(1),
+            },
+            _ => print_usage_and_exit(1),
+        }
+        idx += 2;
+    }
+    if idx + 1 != args.len() {
+        print_usage_and_exit(1);
+    }
+    let host = args[idx].as_str();
+    let addr = resolve(host);
+
+    let socket = match PingSocket::open() {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("ping: cannot open a ping socket: {:?}", err);
+            std::process::exit(2);
+        }
+    };
+
+    std::thread::spawn(input_listener);
+
+    println!(
+        "PING {} ({}) {}({}) bytes of data.",
+        host,
+        addr,
+        payload_size,
+        payload_size + ICMP_HEADER_SIZE
+    );
+
+    let payload: Vec<u8> = (0..payload_size).map(|idx| idx as u8).collect();
+    let mut buf = vec![0_u8; moto_runtime::rt_api::net::PING_MAX_PAYLOAD];
+    let mut sent: HashMap<u16, (Instant, bool)> = HashMap::new();
+    let mut stats = Stats::default();
+    let started = Instant::now();
+
+    let mut seq: u16 = 1;
+    while !QUIT.load(Ordering::Acquire) {
+        // Replaces a request old enough to have its sequence number reused.
+        sent.insert(seq, (Instant::now(), false));
+        if let Err(err) = socket.send_to(&payload, &addr, seq) {
+            eprintln!("ping: send: {:?}", err);
+            std::process::exit(2);
+        }
+        stats.transmitted += 1;
+        seq = seq.wrapping_add(1);
+
+        let last = count.is_some_and(|count| stats.transmitted >= count);
+        let wait = if last { linger } else { interval };
+        receive_until(
+            &socket,
+            Instant::now() + wait,
+            last,
+            &mut sent,
+            &mut stats,
+            &mut buf,
+        );
+        if last {
+            break;
+        }
+    }
+
+    stats.print(host, started.elapsed());
+    std::process::exit(if stats.received > 0 { 0 } else { 1 });
+}
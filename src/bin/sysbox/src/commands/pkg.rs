@@ -0,0 +1,706 @@
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::{Component, Path};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!(
+        "Install, remove, and update signed packages from the repository in {}.",
+        CONFIG_PATH
+    );
+    eprintln!("usage:\n\tpkg list [-a]\n\tpkg install NAME|FILE...\n\tpkg remove NAME...");
+    eprintln!("\tpkg update [NAME...]\n\tpkg rollback\n");
+    eprintln!("\tlist: print installed packages (-a: packages available in the repository).");
+    eprintln!("\tinstall: install or reinstall packages from the repository, or local");
+    eprintln!("\t         package FILEs (FILE.sig must be next to FILE).");
+    eprintln!("\tremove: remove installed packages.");
+    eprintln!("\tupdate: install newer versions of installed packages (default: all).");
+    eprintln!("\trollback: undo the last install, remove, or update.");
+    eprintln!("\nConfig: 'repo = http://ADDR:PORT/PATH' and one or more 'key = HEX'");
+    eprintln!("(ed25519 public keys that packages must be signed with).");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const CONFIG_PATH: &str = "/sys/cfg/pkg.cfg";
+const DB_DIR: &str = "/sys/pkg/db";
+const ROLLBACK_DIR: &str = "/sys/pkg/rollback";
+const JOURNAL: &str = "journal";
+
+// Package archives are (optionally gzipped) tar files with a MANIFEST
+// and files under files/, which are installed relative to '/'.
+const MANIFEST: &str = "MANIFEST";
+const FILES_PREFIX: &str = "files/";
+
+const MAX_DOWNLOAD_SIZE: u64 = 64 << 20;
+const HTTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+struct Config {
+    repo: Option<String>,
+    keys: Vec<Vec<u8>>,
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(s.get(idx..(idx + 2))?, 16).ok())
+        .collect()
+}
+
+fn load_config() -> Result<Config, String> {
+    let contents = std::fs::read_to_string(CONFIG_PATH)
+        .map_err(|e| format!("{}: {:?}", CONFIG_PATH, e.kind()))?;
+
+    let mut config = Config {
+        repo: None,
+        keys: Vec::new(),
+    };
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bad_line = || format!("{}: bad line {}", CONFIG_PATH, idx + 1);
+        let (key, val) = line.split_once('=').ok_or_else(bad_line)?;
+        match key.trim() {
+            "repo" => config.repo = Some(val.trim().trim_end_matches('/').to_owned()),
+            "key" => match parse_hex(val.trim()) {
+                Some(key) if key.len() == 32 => config.keys.push(key),
+                _ => return Err(bad_line()),
+            },
+            _ => return Err(bad_line()),
+        }
+    }
+
+    if config.keys.is_empty() {
+        return Err(format!("{}: no signing keys", CONFIG_PATH));
+    }
+    Ok(config)
+}
+
+fn http_get(url: &str) -> Result<Vec<u8>, String> {
+    let err = |e: std::io::Error| format!("{}: {}", url, e);
+    let response = moto_http::Client::new()
+        .with_timeout(Some(HTTP_TIMEOUT))
+        .get(url)
+        .map_err(err)?;
+    if !response.is_success() {
+        return Err(format!("{}: HTTP {}", url, response.status()));
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_DOWNLOAD_SIZE)
+    {
+        return Err(format!("{}: too large", url));
+    }
+
+    let mut body = Vec::new();
+    response
+        .take(MAX_DOWNLOAD_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(err)?;
+    if body.len() as u64 > MAX_DOWNLOAD_SIZE {
+        return Err(format!("{}: too large", url));
+    }
+
+    Ok(body)
+}
+
+fn verify_signature(config: &Config, what: &str, data: &[u8], sig: &[u8]) -> Result<(), String> {
+    use ring::signature::{UnparsedPublicKey, ED25519};
+
+    if config.keys.iter().any(|key| {
+        UnparsedPublicKey::new(&ED25519, key)
+            .verify(data, sig)
+            .is_ok()
+    }) {
+        Ok(())
+    } else {
+        Err(format!("{}: bad signature", what))
+    }
+}
+
+// Compares dot-separated versions: numeric parts numerically, others as strings.
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        let ord = match (a_parts.next(), b_parts.next()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            },
+        };
+        if ord != std::cmp::Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+struct IndexEntry {
+    name: String,
+    version: String,
+    file: String, // Relative to the repo URL.
+}
+
+// The repository index: "NAME VERSION FILE" lines, signed like the packages
+// (index.sig next to it).
+fn fetch_index(config: &Config) -> Result<Vec<IndexEntry>, String> {
+    let repo = config
+        .repo
+        .as_ref()
+        .ok_or_else(|| format!("{}: no repo", CONFIG_PATH))?;
+    let url = format!("{}/index", repo);
+    let index = http_get(&url)?;
+    let sig = http_get(format!("{}.sig", url).as_str())?;
+    verify_signature(config, &url, &index, &sig)?;
+    let index = std::str::from_utf8(&index).map_err(|_| format!("{}: not UTF-8", url))?;
+
+    let mut entries = Vec::new();
+    for (idx, line) in index.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != 3 {
+            return Err(format!("{}: bad line {}", url, idx + 1));
+        }
+        entries.push(IndexEntry {
+            name: words[0].to_owned(),
+            version: words[1].to_owned(),
+            file: words[2].to_owned(),
+        });
+    }
+    Ok(entries)
+}
+
+struct Package {
+    name: String,
+    version: String,
+    files: Vec<(String, Vec<u8>)>, // Absolute path => contents.
+}
+
+fn parse_octal(field: &[u8]) -> Option<usize> {
+    let s = std::str::from_utf8(field).ok()?;
+    let s = s.trim_matches(|c| c == '\0' || c == ' ');
+    if s.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(s, 8).ok()
+}
+
+fn parse_str(field: &[u8]) -> Option<&str> {
+    let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[0..len]).ok()
+}
+
+// Returns the absolute path a package file is installed to.
+fn install_path(name: &str) -> Result<String, String> {
+    let mut path = String::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => {
+                path.push('/');
+                path.push_str(part.to_str().unwrap());
+            }
+            _ => return Err(format!("{}: unsafe path", name)),
+        }
+    }
+    if path.is_empty() || path.starts_with(DB_DIR) || path.starts_with(ROLLBACK_DIR) {
+        return Err(format!("{}: bad path", name));
+    }
+    Ok(path)
+}
+
+fn parse_package(archive: &[u8]) -> Result<Package, String> {
+    const BLOCK_SIZE: usize = 512;
+
+    let mut tar = Vec::new();
+    let tar = if archive.starts_with(&[0x1f, 0x8b]) {
+        flate2::read::GzDecoder::new(archive)
+            .take(MAX_DOWNLOAD_SIZE * 4)
+            .read_to_end(&mut tar)
+            .map_err(|_| "bad gzip data".to_owned())?;
+        &tar[..]
+    } else {
+        archive
+    };
+
+    let mut manifest = None;
+    let mut files = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut pos = 0;
+    while pos + BLOCK_SIZE <= tar.len() {
+        let header = &tar[pos..(pos + BLOCK_SIZE)];
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let size = parse_octal(&header[124..136]).ok_or("bad tar header")?;
+        let data_start = pos + BLOCK_SIZE;
+        let data = tar
+            .get(data_start..(data_start + size))
+            .ok_or("truncated tar archive")?;
+        pos = data_start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+
+        let name = match long_name.take() {
+            Some(name) => name,
+            None => {
+                let name = parse_str(&header[0..100]).ok_or("bad tar header")?;
+                let prefix = if &header[257..262] == b"ustar" {
+                    parse_str(&header[345..500]).ok_or("bad tar header")?
+                } else {
+                    ""
+                };
+                if prefix.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}/{}", prefix, name)
+                }
+            }
+        };
+        let name = name.trim_start_matches("./");
+
+        match header[156] {
+            b'L' => long_name = Some(parse_str(data).ok_or("bad tar header")?.to_owned()),
+            b'5' => {} // Directories are created as needed.
+            b'0' | 0 if name == MANIFEST => {
+                manifest = Some(String::from_utf8(data.to_vec()).map_err(|_| "bad MANIFEST")?)
+            }
+            b'0' | 0 => match name.strip_prefix(FILES_PREFIX) {
+                Some(file) => files.push((install_path(file)?, data.to_vec())),
+                None => return Err(format!("{}: not under {}", name, FILES_PREFIX)),
+            },
+            t => {
+                return Err(format!(
+                    "{}: unsupported tar entry type '{}'",
+                    name, t as char
+                ))
+            }
+        }
+    }
+
+    let manifest = manifest.ok_or("no MANIFEST")?;
+    let mut pkg_name = None;
+    let mut version = None;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+            Some(("name", val)) => pkg_name = Some(val.to_owned()),
+            Some(("version", val)) => version = Some(val.to_owned()),
+            Some(_) => {} // E.g. description.
+            None => return Err(format!("MANIFEST: bad line '{}'", line)),
+        }
+    }
+    let name = pkg_name.ok_or("MANIFEST: no name")?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("MANIFEST: bad name '{}'", name));
+    }
+
+    Ok(Package {
+        name,
+        version: version.ok_or("MANIFEST: no version")?,
+        files,
+    })
+}
+
+// An entry in the installed-package database: /sys/pkg/db/NAME.
+struct Installed {
+    name: String,
+    version: String,
+    files: Vec<String>,
+}
+
+impl Installed {
+    fn db_path(name: &str) -> String {
+        format!("{}/{}", DB_DIR, name)
+    }
+
+    fn load(name: &str) -> Result<Option<Self>, String> {
+        let path = Self::db_path(name);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(format!("{}: {:?}", path, err.kind())),
+        };
+
+        let mut installed = Self {
+            name: name.to_owned(),
+            version: String::new(),
+            files: Vec::new(),
+        };
+        for line in contents.lines() {
+            match line.split_once(' ') {
+                Some(("version", val)) => installed.version = val.to_owned(),
+                Some(("file", val)) => installed.files.push(val.to_owned()),
+                _ => return Err(format!("{}: bad line '{}'", path, line)),
+            }
+        }
+        Ok(Some(installed))
+    }
+
+    fn load_all() -> Result<Vec<Self>, String> {
+        let entries = match std::fs::read_dir(DB_DIR) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(format!("{}: {:?}", DB_DIR, err.kind())),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("{}: {:?}", DB_DIR, e.kind()))?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+
+        let mut all = Vec::new();
+        for name in names {
+            if let Some(installed) = Self::load(&name)? {
+                all.push(installed);
+            }
+        }
+        Ok(all)
+    }
+
+    fn contents(&self) -> String {
+        let mut contents = format!("version {}\n", self.version);
+        for file in &self.files {
+            contents.push_str(format!("file {}\n", file).as_str());
+        }
+        contents
+    }
+}
+
+fn write_file(path: &str, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("{}: {:?}", path, e.kind()))?;
+    }
+    std::fs::write(path, contents).map_err(|e| format!("{}: {:?}", path, e.kind()))
+}
+
+fn remove_file(path: &str) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("{}: {:?}", path, err.kind())),
+    }
+}
+
+// Records the previous state of every file a transaction touches, so that
+// `pkg rollback` can restore it. Only the last transaction is kept.
+//
+// The journal has "restore BACKUP PATH" and "delete PATH" lines and is
+// written before each change, so an interrupted transaction can be rolled
+// back as well.
+struct Transaction {
+    journal: String,
+    saved: BTreeSet<String>,
+}
+
+impl Transaction {
+    fn begin(what: &str) -> Result<Self, String> {
+        match std::fs::remove_dir_all(ROLLBACK_DIR) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("{}: {:?}", ROLLBACK_DIR, err.kind())),
+        }
+        std::fs::create_dir_all(ROLLBACK_DIR)
+            .map_err(|e| format!("{}: {:?}", ROLLBACK_DIR, e.kind()))?;
+
+        let mut txn = Self {
+            journal: format!("# {}\n", what),
+            saved: BTreeSet::new(),
+        };
+        txn.write_journal()?;
+        Ok(txn)
+    }
+
+    fn write_journal(&mut self) -> Result<(), String> {
+        write_file(
+            format!("{}/{}", ROLLBACK_DIR, JOURNAL).as_str(),
+            self.journal.as_bytes(),
+        )
+    }
+
+    // Must be called before @path is modified.
+    fn save(&mut self, path: &str) -> Result<(), String> {
+        if !self.saved.insert(path.to_owned()) {
+            return Ok(());
+        }
+
+        match std::fs::read(path) {
+            Ok(contents) => {
+                let backup = self.saved.len().to_string();
+                write_file(format!("{}/{}", ROLLBACK_DIR, backup).as_str(), &contents)?;
+                self.journal
+                    .push_str(format!("restore {} {}\n", backup, path).as_str());
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.journal.push_str(format!("delete {}\n", path).as_str());
+            }
+            Err(err) => return Err(format!("{}: {:?}", path, err.kind())),
+        }
+        self.write_journal()
+    }
+
+    fn install(&mut self, pkg: &Package) -> Result<(), String> {
+        let old = Installed::load(&pkg.name)?;
+
+        for (path, contents) in &pkg.files {
+            self.save(path)?;
+            write_file(path, contents)?;
+        }
+        if let Some(old) = &old {
+            for path in &old.files {
+                if !pkg.files.iter().any(|(new_path, _)| new_path == path) {
+                    self.save(path)?;
+                    remove_file(path)?;
+                }
+            }
+        }
+
+        let installed = Installed {
+            name: pkg.name.clone(),
+            version: pkg.version.clone(),
+            files: pkg.files.iter().map(|(path, _)| path.clone()).collect(),
+        };
+        let db_path = Installed::db_path(&pkg.name);
+        self.save(&db_path)?;
+        write_file(&db_path, installed.contents().as_bytes())
+    }
+
+    fn remove(&mut self, installed: &Installed) -> Result<(), String> {
+        for path in &installed.files {
+            self.save(path)?;
+            remove_file(path)?;
+        }
+        let db_path = Installed::db_path(&installed.name);
+        self.save(&db_path)?;
+        remove_file(&db_path)
+    }
+}
+
+fn rollback() -> Result<(), String> {
+    let journal_path = format!("{}/{}", ROLLBACK_DIR, JOURNAL);
+    let journal = match std::fs::read_to_string(&journal_path) {
+        Ok(journal) => journal,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err("nothing to roll back".to_owned())
+        }
+        Err(err) => return Err(format!("{}: {:?}", journal_path, err.kind())),
+    };
+
+    for line in journal.lines().rev() {
+        if let Some(what) = line.strip_prefix("# ") {
+            println!("rolled back: {}", what);
+        } else if let Some(path) = line.strip_prefix("delete ") {
+            remove_file(path)?;
+        } else if let Some((backup, path)) = line
+            .strip_prefix("restore ")
+            .and_then(|rest| rest.split_once(' '))
+        {
+            let backup = format!("{}/{}", ROLLBACK_DIR, backup);
+            let contents =
+                std::fs::read(&backup).map_err(|e| format!("{}: {:?}", backup, e.kind()))?;
+            write_file(path, &contents)?;
+        } else {
+            return Err(format!("{}: bad line '{}'", journal_path, line));
+        }
+    }
+
+    // A rollback cannot be rolled back.
+    std::fs::remove_dir_all(ROLLBACK_DIR).map_err(|e| format!("{}: {:?}", ROLLBACK_DIR, e.kind()))
+}
+
+// Downloads and verifies the package described by @entry.
+fn download(config: &Config, entry: &IndexEntry) -> Result<Package, String> {
+    let repo = config.repo.as_ref().unwrap();
+    let url = format!("{}/{}", repo, entry.file);
+    let archive = http_get(&url)?;
+    let sig = http_get(format!("{}.sig", url).as_str())?;
+    verify_signature(config, &url, &archive, &sig)?;
+
+    let pkg = parse_package(&archive).map_err(|err| format!("{}: {}", url, err))?;
+    if pkg.name != entry.name || pkg.version != entry.version {
+        return Err(format!(
+            "{}: contains {} {}, expected {} {}",
+            url, pkg.name, pkg.version, entry.name, entry.version
+        ));
+    }
+    Ok(pkg)
+}
+
+fn load_local(config: &Config, path: &str) -> Result<Package, String> {
+    let archive = std::fs::read(path).map_err(|e| format!("{}: {:?}", path, e.kind()))?;
+    let sig_path = format!("{}.sig", path);
+    let sig = std::fs::read(&sig_path).map_err(|e| format!("{}: {:?}", sig_path, e.kind()))?;
+    verify_signature(config, path, &archive, &sig)?;
+    parse_package(&archive).map_err(|err| format!("{}: {}", path, err))
+}
+
+// Fails if files of @pkgs belong to other installed packages.
+fn check_conflicts(pkgs: &[Package]) -> Result<(), String> {
+    let installed = Installed::load_all()?;
+    for pkg in pkgs {
+        for (path, _) in &pkg.files {
+            for other in &installed {
+                if other.name != pkg.name && other.files.contains(path) {
+                    return Err(format!("{}: {} belongs to {}", pkg.name, path, other.name));
+                }
+            }
+            for other in pkgs {
+                if other.name != pkg.name && other.files.iter().any(|(p, _)| p == path) {
+                    return Err(format!("{}: {} is also in {}", pkg.name, path, other.name));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn install_all(pkgs: Vec<Package>, what: &str) -> Result<(), String> {
+    if pkgs.is_empty() {
+        return Ok(());
+    }
+    check_conflicts(&pkgs)?;
+
+    let mut txn = Transaction::begin(what)?;
+    for pkg in &pkgs {
+        txn.install(pkg)
+            .map_err(|err| format!("{} (run `pkg rollback` to undo)", err))?;
+        println!("installed {} {}", pkg.name, pkg.version);
+    }
+    Ok(())
+}
+
+fn do_install(args: &[String]) -> Result<(), String> {
+    let config = load_config()?;
+    let mut index = None;
+
+    let mut pkgs = Vec::new();
+    for arg in args {
+        if arg.contains('/') || arg.ends_with(".pkg") {
+            pkgs.push(load_local(&config, arg)?);
+            continue;
+        }
+        if index.is_none() {
+            index = Some(fetch_index(&config)?);
+        }
+        let entry = index
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|entry| &entry.name == arg)
+            .max_by(|a, b| compare_versions(&a.version, &b.version))
+            .ok_or_else(|| format!("{}: no such package", arg))?;
+        pkgs.push(download(&config, entry)?);
+    }
+
+    install_all(pkgs, format!("install {}", args.join(" ")).as_str())
+}
+
+fn do_update(args: &[String]) -> Result<(), String> {
+    let config = load_config()?;
+    let index = fetch_index(&config)?;
+
+    for arg in args {
+        if Installed::load(arg)?.is_none() {
+            return Err(format!("{}: not installed", arg));
+        }
+    }
+
+    let mut pkgs = Vec::new();
+    for installed in Installed::load_all()? {
+        if !args.is_empty() && !args.contains(&installed.name) {
+            continue;
+        }
+        let newest = index
+            .iter()
+            .filter(|entry| entry.name == installed.name)
+            .max_by(|a, b| compare_versions(&a.version, &b.version));
+        match newest {
+            Some(entry) if compare_versions(&entry.version, &installed.version).is_gt() => {
+                pkgs.push(download(&config, entry)?)
+            }
+            _ => {}
+        }
+    }
+    if pkgs.is_empty() {
+        println!("all packages are up to date");
+        return Ok(());
+    }
+    let names: Vec<&str> = pkgs.iter().map(|pkg| pkg.name.as_str()).collect();
+    let what = format!("update {}", names.join(" "));
+    install_all(pkgs, &what)
+}
+
+fn do_remove(args: &[String]) -> Result<(), String> {
+    let mut all = Vec::new();
+    for arg in args {
+        all.push(Installed::load(arg)?.ok_or_else(|| format!("{}: not installed", arg))?);
+    }
+
+    let mut txn = Transaction::begin(format!("remove {}", args.join(" ")).as_str())?;
+    for installed in &all {
+        txn.remove(installed)
+            .map_err(|err| format!("{} (run `pkg rollback` to undo)", err))?;
+        println!("removed {} {}", installed.name, installed.version);
+    }
+    Ok(())
+}
+
+fn do_list(available: bool) -> Result<(), String> {
+    if available {
+        let config = load_config()?;
+        for entry in fetch_index(&config)? {
+            println!("{:<24} {}", entry.name, entry.version);
+        }
+    } else {
+        for installed in Installed::load_all()? {
+            println!(
+                "{:<24} {:<12} {} files",
+                installed.name,
+                installed.version,
+                installed.files.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "pkg");
+
+    if args.len() < 2 {
+        print_usage_and_exit(1);
+    }
+    let rest = &args[2..];
+
+    let result = match args[1].as_str() {
+        "--help" => print_usage_and_exit(0),
+        "list" if rest.is_empty() => do_list(false),
+        "list" if rest.len() == 1 && rest[0] == "-a" => do_list(true),
+        "install" if !rest.is_empty() => do_install(rest),
+        "remove" if !rest.is_empty() => do_remove(rest),
+        "update" => do_update(rest),
+        "rollback" if rest.is_empty() => rollback(),
+        _ => print_usage_and_exit(1),
+    };
+
+    if let Err(err) = result {
+        eprintln!("pkg: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -9,27 +9,164 @@ fn print_usage_and_exit(exit_code: i32) -> ! {
     eprintln!("        Process memory usage captures shared memory, meaning that total/cumulative");
     eprintln!("        virtual memory usage is higher than actual physical memory usage.");
     eprintln!("        Lazily mapped virtual memory (e.g. stacks) is included here, which also");
-    eprintln!("        leads to overstating virtual memory usage vs physical memory usage.\n");
-    eprintln!("usage:\n\tps [-H]\n");
+    eprintln!("        leads to overstating virtual memory usage vs physical memory usage.");
+    eprintln!("Note 4: P_HUGE = 2M pages mapped; these are also counted in P_USER as 4K pages.\n");
+    eprintln!("usage:\n\tps [-H] [-l | -o COLUMN[,COLUMN...]]\n");
+    eprintln!("\t-H: print the process tree.");
+    eprintln!("\t-l: print all columns.");
+    eprintln!("\t-o: print the selected columns; available columns:");
+    for col in COLUMNS {
+        eprintln!("\t\t{:8} {}", col.key, col.description);
+    }
+    eprintln!("\tdefault: -o {}\n", DEFAULT_COLUMNS);
     std::thread::sleep(std::time::Duration::new(0, 50_000_000));
     std::process::exit(exit_code);
 }
 
 const PS_BUF_SIZE: usize = 1024;
 
+struct Column {
+    key: &'static str,
+    header: &'static str,
+    description: &'static str,
+    value: fn(&ProcessStatsV1) -> String,
+}
+
+const COLUMNS: &[Column] = &[
+    Column {
+        key: "pid",
+        header: "PID",
+        description: "process ID ('*' marks system processes)",
+        value: |proc| {
+            format!(
+                "{}{}",
+                proc.pid,
+                if proc.system_process != 0 { "*" } else { " " }
+            )
+        },
+    },
+    Column {
+        key: "ppid",
+        header: "PPID",
+        description: "parent process ID",
+        value: |proc| proc.parent_pid.to_string(),
+    },
+    Column {
+        key: "state",
+        header: "ST",
+        description: "RUN or DEAD (a zombie)",
+        value: |proc| (if proc.active == 1 { "RUN" } else { "DEAD" }).to_owned(),
+    },
+    Column {
+        key: "thr",
+        header: "A_THR",
+        description: "active threads",
+        value: |proc| proc.active_threads.to_string(),
+    },
+    Column {
+        key: "tthr",
+        header: "T_THR",
+        description: "total threads created",
+        value: |proc| proc.total_threads.to_string(),
+    },
+    Column {
+        key: "chld",
+        header: "A_CHLD",
+        description: "active children",
+        value: |proc| proc.active_children.to_string(),
+    },
+    Column {
+        key: "tchld",
+        header: "T_CHLD",
+        description: "total children spawned",
+        value: |proc| proc.total_children.to_string(),
+    },
+    Column {
+        key: "puser",
+        header: "P_USER",
+        description: "user pages",
+        value: |proc| proc.pages_user.to_string(),
+    },
+    Column {
+        key: "pkern",
+        header: "P_KERN",
+        description: "kernel pages",
+        value: |proc| proc.pages_kernel.to_string(),
+    },
+    Column {
+        key: "phuge",
+        header: "P_HUGE",
+        description: "2M pages",
+        value: |proc| proc.pages_user_huge.to_string(),
+    },
+    Column {
+        key: "rss",
+        header: "RSS_KB",
+        description: "memory used, in KiB (see Note 3)",
+        value: |proc| (proc.total_bytes() >> 10).to_string(),
+    },
+    Column {
+        key: "time",
+        header: "CPU",
+        description: "CPU time, in seconds",
+        value: |proc| {
+            let tsc_in_sec = moto_sys::KernelStaticPage::get().tsc_in_sec as f64;
+            format!("{:.3}", (proc.cpu_usage as f64) / tsc_in_sec)
+        },
+    },
+    Column {
+        key: "name",
+        header: "Name",
+        description: "process name (short command line)",
+        value: |proc| proc.debug_name().to_owned(),
+    },
+    Column {
+        key: "cmd",
+        header: "Command",
+        description: "command line",
+        value: |proc| proc.cmdline().to_owned(),
+    },
+];
+
+const DEFAULT_COLUMNS: &str = "pid,ppid,state,thr,rss,time,cmd";
+const LONG_COLUMNS: &str = "pid,ppid,thr,tthr,chld,tchld,puser,pkern,phuge,rss,time,state,cmd";
+
+fn parse_columns(arg: &str) -> Vec<&'static Column> {
+    let mut columns = Vec::new();
+    for key in arg.split(',').filter(|s| !s.is_empty()) {
+        match COLUMNS.iter().find(|col| col.key == key) {
+            Some(col) => columns.push(col),
+            None => {
+                eprintln!("ps: unknown column '{}'", key);
+                print_usage_and_exit(1);
+            }
+        }
+    }
+    if columns.is_empty() {
+        print_usage_and_exit(1);
+    }
+    columns
+}
+
 pub fn do_command(args: &[String]) {
     let mut should_print_tree = false;
-    if args.len() > 2 {
-        print_usage_and_exit(1)
-    }
+    let mut columns = None;
 
-    if args.len() == 2 {
-        match args[1].as_str() {
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
             "-H" => should_print_tree = true,
+            "-l" if columns.is_none() => columns = Some(parse_columns(LONG_COLUMNS)),
+            "-o" if columns.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                columns = Some(parse_columns(args[idx].as_str()));
+            }
             "--help" => print_usage_and_exit(0),
             _ => print_usage_and_exit(1),
         }
+        idx += 1;
     }
+    let columns = columns.unwrap_or_else(|| parse_columns(DEFAULT_COLUMNS));
 
     let mut processes: Vec<ProcessStatsV1> = Vec::with_capacity(PS_BUF_SIZE);
     for _ in 0..PS_BUF_SIZE {
@@ -48,80 +185,72 @@ pub fn do_command(args: &[String]) {
         // Ask for more.
         eprintln!("\nsysbox ps: implement paging.\n");
     }
-
-    let mut max_num = 123456;
-    let mut total_cpu: u64 = 0;
-    for proc in &processes[0..cnt] {
-        max_num = max_num.max(proc.pid);
-        max_num = max_num.max(proc.parent_pid);
-        max_num = max_num.max(proc.total_threads);
-        max_num = max_num.max(proc.total_children);
-        max_num = max_num.max(proc.total_bytes() >> 10);
-        total_cpu += proc.cpu_usage;
-    }
+    let processes = &mut processes[0..cnt];
 
     // The first element has idle CPU; we show the sum of the rest.
-    total_cpu -= processes[0].cpu_usage;
+    let total_cpu: u64 = processes[1..].iter().map(|proc| proc.cpu_usage).sum();
     processes[0].cpu_usage = total_cpu;
 
-    let col_width = max_num.to_string().len();
-    let cpu_width = (total_cpu / moto_sys::KernelStaticPage::get().tsc_in_sec)
-        .to_string()
-        .len()
-        + 4;
-
-    println!(
-        "{:>w$}* {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:<w$} {:>wsec$}  ST   Name",
-        "PID",
-        "PPID",
-        "A_THR",
-        "T_THR",
-        "A_CHLD",
-        "T_CHLD",
-        "P_USER",
-        "P_KERN",
-        "KBYTES",
-        "CPU",
-        w = col_width,
-        wsec = cpu_width
-    );
-
+    // (process, tree depth) in the print order.
+    let mut ordered: Vec<(&ProcessStatsV1, usize)> = Vec::with_capacity(cnt);
     if should_print_tree {
-        print_tree(&processes[0..cnt], col_width, cpu_width);
-        return;
+        tree_order(processes, &mut ordered);
+    } else {
+        ordered.extend(processes.iter().map(|proc| (proc, 0)));
+    }
+
+    let rows: Vec<Vec<String>> = ordered
+        .iter()
+        .map(|(proc, depth)| {
+            columns
+                .iter()
+                .map(|col| {
+                    let value = (col.value)(proc);
+                    if col.key == "cmd" || col.key == "name" {
+                        format!("{:indent$}{}", "", value, indent = depth * 2)
+                    } else {
+                        value
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|col| col.header.len()).collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
     }
 
-    for proc in &processes[0..cnt] {
-        print_line(proc, col_width, cpu_width, 0);
+    let headers: Vec<String> = columns.iter().map(|col| col.header.to_owned()).collect();
+    print_row(&columns, &widths, &headers);
+    for row in &rows {
+        print_row(&columns, &widths, row);
     }
 }
 
-fn print_line(proc: &ProcessStatsV1, col_width: usize, cpu_width: usize, name_offset: usize) {
-    let tsc_f64 = moto_sys::KernelStaticPage::get().tsc_in_sec as f64;
-
-    println!(
-        "{:>w$}{} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>cpu_width$.3} {} {:off$} {}",
-        proc.pid,
-        if proc.system_process != 0 { "*" } else { " " },
-        proc.parent_pid,
-        proc.active_threads,
-        proc.total_threads,
-        proc.active_children,
-        proc.total_children,
-        proc.pages_user,
-        proc.pages_kernel,
-        proc.total_bytes() >> 10,
-        (proc.cpu_usage as f64) / tsc_f64,
-        if proc.active == 1 { "RUN " } else { "DEAD" },
-        "",
-        proc.debug_name(),
-        w = col_width,
-        cpu_width = cpu_width,
-        off = name_offset
-    );
+fn print_row(columns: &[&Column], widths: &[usize], values: &[String]) {
+    let mut line = String::new();
+    for (idx, value) in values.iter().enumerate() {
+        if idx > 0 {
+            line.push(' ');
+        }
+        let last = idx == values.len() - 1;
+        // Text columns are left-aligned; numbers are right-aligned.
+        let left = matches!(columns[idx].key, "cmd" | "name" | "state");
+        if left && last {
+            line.push_str(value);
+        } else if left {
+            line.push_str(format!("{:<w$}", value, w = widths[idx]).as_str());
+        } else {
+            line.push_str(format!("{:>w$}", value, w = widths[idx]).as_str());
+        }
+    }
+    println!("{}", line);
 }
 
-fn print_tree(processes: &[ProcessStatsV1], col_width: usize, cpu_width: usize) {
+fn tree_order<'a>(processes: &'a [ProcessStatsV1], ordered: &mut Vec<(&'a ProcessStatsV1, usize)>) {
     assert!(processes.len() > 2);
     // TODO: construct a proper tree for printing, instead of doing
     // the inefficient thing below.
@@ -130,25 +259,24 @@ fn print_tree(processes: &[ProcessStatsV1], col_width: usize, cpu_width: usize)
     assert_eq!(processes[0].pid, PID_SYSTEM);
     assert_eq!(processes[1].pid, PID_KERNEL);
 
-    print_line(&processes[0], col_width, cpu_width, 0);
-    print_line(&processes[1], col_width, cpu_width, 0);
+    ordered.push((&processes[0], 0));
+    ordered.push((&processes[1], 0));
 
-    print_subtree(processes, PID_KERNEL, col_width, cpu_width, 1);
+    add_subtree(processes, PID_KERNEL, 1, ordered);
 }
 
-fn print_subtree(
-    processes: &[ProcessStatsV1],
+fn add_subtree<'a>(
+    processes: &'a [ProcessStatsV1],
     parent_pid: u64,
-    col_width: usize,
-    cpu_width: usize,
     sublevel: usize,
+    ordered: &mut Vec<(&'a ProcessStatsV1, usize)>,
 ) {
     for proc in processes {
         if proc.parent_pid != parent_pid {
             continue;
         }
 
-        print_line(proc, col_width, cpu_width, sublevel * 2);
-        print_subtree(processes, proc.pid, col_width, cpu_width, sublevel + 1);
+        ordered.push((proc, sublevel));
+        add_subtree(processes, proc.pid, sublevel + 1, ordered);
     }
 }
@@ -0,0 +1,141 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Show or set storage quotas (srfs only).");
+    eprintln!("usage:\n\tquota -u UID [-b BYTES] [-i INODES] [PATH]");
+    eprintln!("\tquota -d DIR [-b BYTES] [-i INODES]\n");
+    eprintln!("\t-u: the quota of user UID on the filesystem PATH belongs to (default: /);");
+    eprintln!("\t-d: the quota of the tree under DIR;");
+    eprintln!("\t-b, -i: set the limit on bytes or on inodes (root only); 0 removes it.");
+    eprintln!("\nBYTES are in bytes, or in K, M, or G (1024-based).");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn parse_size(arg: &str) -> Option<u64> {
+    let (num, shift) = match arg.chars().last()? {
+        'K' | 'k' => (&arg[0..(arg.len() - 1)], 10),
+        'M' | 'm' => (&arg[0..(arg.len() - 1)], 20),
+        'G' | 'g' => (&arg[0..(arg.len() - 1)], 30),
+        _ => (arg, 0),
+    };
+    num.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn fmt_size(bytes: u64) -> String {
+    const SUFFIXES: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut val = bytes as f64;
+    let mut idx = 0;
+    while val >= 1024.0 && idx < SUFFIXES.len() - 1 {
+        val /= 1024.0;
+        idx += 1;
+    }
+    if idx == 0 {
+        format!("{}{}", bytes, SUFFIXES[0])
+    } else {
+        format!("{:.1}{}", val, SUFFIXES[idx])
+    }
+}
+
+fn fmt_limit(limit: u64, fmt: impl Fn(u64) -> String) -> String {
+    if limit == 0 {
+        "none".to_owned()
+    } else {
+        fmt(limit)
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "quota");
+
+    let mut uid = None;
+    let mut dir = None;
+    let mut max_bytes = None;
+    let mut max_inodes = None;
+    let mut path = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-u" | "-d" | "-b" | "-i" => {
+                idx += 1;
+                let Some(val) = args.get(idx).map(|s| s.as_str()) else {
+                    print_usage_and_exit(1);
+                };
+                match arg {
+                    "-u" => match val.parse::<u32>() {
+                        Ok(val) => uid = Some(val),
+                        Err(_) => print_usage_and_exit(1),
+                    },
+                    "-d" => dir = Some(val),
+                    "-b" => match parse_size(val) {
+                        Some(val) => max_bytes = Some(val),
+                        None => print_usage_and_exit(1),
+                    },
+                    _ => match val.parse::<u64>() {
+                        Ok(val) => max_inodes = Some(val),
+                        Err(_) => print_usage_and_exit(1),
+                    },
+                }
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let path = match (uid, dir, path) {
+        (Some(_), None, path) => path.unwrap_or("/"),
+        (None, Some(dir), None) => dir,
+        _ => print_usage_and_exit(1),
+    };
+
+    let abs_path = match std::fs::canonicalize(path) {
+        Ok(abs_path) => abs_path,
+        Err(err) => {
+            eprintln!("quota: {}: {:?}", path, err.kind());
+            std::process::exit(1);
+        }
+    };
+    let abs_path = abs_path.to_str().unwrap_or("");
+
+    let quota = match moto_sys_io::fs::get_quota(abs_path, uid) {
+        Ok(quota) => quota,
+        Err(err) => {
+            eprintln!("quota: {}: {:?}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    if max_bytes.is_some() || max_inodes.is_some() {
+        // A limit that is not given is kept.
+        if let Err(err) = moto_sys_io::fs::set_quota(
+            abs_path,
+            uid,
+            max_bytes.unwrap_or(quota.max_bytes),
+            max_inodes.unwrap_or(quota.max_inodes),
+        ) {
+            eprintln!("quota: {}: {:?}", path, err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    match uid {
+        Some(uid) => println!("user {} on {}:", uid, path),
+        None => println!("directory {}:", path),
+    }
+    println!("{:8} {:>12} {:>12}", "", "USED", "LIMIT");
+    println!(
+        "{:8} {:>12} {:>12}",
+        "bytes",
+        fmt_size(quota.used_bytes),
+        fmt_limit(quota.max_bytes, fmt_size)
+    );
+    println!(
+        "{:8} {:>12} {:>12}",
+        "inodes",
+        quota.used_inodes,
+        fmt_limit(quota.max_inodes, |val| val.to_string())
+    );
+}
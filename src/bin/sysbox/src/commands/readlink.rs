@@ -0,0 +1,32 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Print the target of a symbolic link.");
+    eprintln!("usage:\n\treadlink [-f] PATH\n");
+    eprintln!("\t-f: print the absolute path with all symlinks resolved.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "readlink");
+
+    let (canonicalize, path) = match args.len() {
+        2 if args[1] == "-h" || args[1] == "--help" => print_usage_and_exit(0),
+        2 => (false, args[1].as_str()),
+        3 if args[1] == "-f" => (true, args[2].as_str()),
+        _ => print_usage_and_exit(1),
+    };
+
+    let result = if canonicalize {
+        std::fs::canonicalize(path)
+    } else {
+        std::fs::read_link(path)
+    };
+
+    match result {
+        Ok(target) => println!("{}", target.display()),
+        Err(err) => {
+            eprintln!("readlink: {}: {:?}", path, err.kind());
+            std::process::exit(1);
+        }
+    }
+}
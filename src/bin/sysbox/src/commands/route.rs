@@ -0,0 +1,189 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use moto_sys::ErrorCode;
+use moto_sys_io::netcfg::*;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Show or change the routing table. Changes last until sys-io restarts;");
+    eprintln!("/sys/cfg/sys-net.toml has the startup configuration.");
+    eprintln!("usage:");
+    eprintln!("\troute                                          list routes");
+    eprintln!("\troute get ADDR                                 show the route to ADDR");
+    eprintln!("\troute add DEST [via GW] [dev IFACE] [metric N] add a route");
+    eprintln!("\troute del DEST [via GW] [dev IFACE]            remove a route\n");
+    eprintln!("DEST is ADDR/PREFIX or `default`. Routes with longer prefixes win,");
+    eprintln!("then those with lower metrics (default: 0).\n");
+    std::process::exit(exit_code);
+}
+
+fn exit_with_error(err: ErrorCode) -> ! {
+    let reason = match err {
+        ErrorCode::NotAllowed => "permission denied".to_owned(),
+        ErrorCode::NotFound => "no such route or interface".to_owned(),
+        ErrorCode::AlreadyInUse => "the route exists".to_owned(),
+        ErrorCode::OutOfMemory => "too many routes".to_owned(),
+        ErrorCode::InvalidArgument => "invalid argument (is the gateway on-link?)".to_owned(),
+        err => format!("{:?}", err),
+    };
+    eprintln!("route: {}", reason);
+    std::process::exit(1);
+}
+
+fn print_route(route: &RouteInfoV1) {
+    let mut line = if route.destination.prefix_len == 0 {
+        "default".to_owned()
+    } else {
+        format!("{:?}", route.destination)
+    };
+    if let Some(gateway) = route.gateway() {
+        line += &format!(" via {}", gateway);
+    }
+    line += &format!(" dev {}", route.ifname());
+
+    let proto = if (route.flags & RTF_CONNECTED) != 0 {
+        "kernel"
+    } else if (route.flags & RTF_CONFIG) != 0 {
+        "config"
+    } else if (route.flags & RTF_AUTOCONF) != 0 {
+        "ra"
+    } else if (route.flags & RTF_LOCAL) != 0 {
+        "local"
+    } else {
+        "static"
+    };
+    line += &format!(" proto {}", proto);
+
+    if route.source.prefix_len > 0 {
+        line += &format!(" src {}", route.source.addr());
+    }
+    line += &format!(" metric {}", route.metric);
+    println!("{}", line);
+}
+
+struct RouteArgs {
+    destination: Option<(IpAddr, u8)>, // None: default.
+    gateway: Option<IpAddr>,
+    ifname: Option<String>,
+    metric: u32,
+}
+
+fn parse_route_args(args: &[String]) -> RouteArgs {
+    let destination = if args[0] == "default" {
+        None
+    } else {
+        let Some((addr, prefix_len)) = args[0].split_once('/') else {
+            print_usage_and_exit(1);
+        };
+        match (addr.parse::<IpAddr>(), prefix_len.parse::<u8>()) {
+            (Ok(addr), Ok(prefix_len)) => Some((addr, prefix_len)),
+            _ => print_usage_and_exit(1),
+        }
+    };
+
+    let mut result = RouteArgs {
+        destination,
+        gateway: None,
+        ifname: None,
+        metric: 0,
+    };
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let Some(value) = args.get(idx + 1) else {
+            print_usage_and_exit(1);
+        };
+        match args[idx].as_str() {
+            "via" => match value.parse::<IpAddr>() {
+                Ok(gateway) => result.gateway = Some(gateway),
+                Err(_) => print_usage_and_exit(1),
+            },
+            "dev" => result.ifname = Some(value.clone()),
+            "metric" => match value.parse::<u32>() {
+                Ok(metric) => result.metric = metric,
+                Err(_) => print_usage_and_exit(1),
+            },
+            _ => print_usage_and_exit(1),
+        }
+        idx += 2;
+    }
+
+    result
+}
+
+impl RouteArgs {
+    fn to_route_info(&self) -> RouteInfoV1 {
+        // The default route's family is the gateway's.
+        let (addr, prefix_len) = match (self.destination, self.gateway) {
+            (Some(destination), _) => destination,
+            (None, Some(IpAddr::V6(_))) => (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+            (None, _) => (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        };
+        RouteInfoV1::new(
+            addr,
+            prefix_len,
+            self.gateway,
+            self.ifname.as_deref(),
+            self.metric,
+        )
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "route");
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print_usage_and_exit(0);
+    }
+
+    let mut svc = match NetCfgService::connect() {
+        Ok(svc) => svc,
+        Err(err) => {
+            eprintln!("route: cannot connect to sys-io: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.len() == 1 {
+        let routes = svc.list_routes().unwrap_or_else(|err| exit_with_error(err));
+        for route in &routes {
+            print_route(route);
+        }
+        return;
+    }
+
+    if args.len() < 3 {
+        print_usage_and_exit(1);
+    }
+    match args[1].as_str() {
+        "get" => {
+            if args.len() != 3 {
+                print_usage_and_exit(1);
+            }
+            let Ok(dst) = args[2].parse::<IpAddr>() else {
+                print_usage_and_exit(1);
+            };
+            match svc.get_route(dst) {
+                Ok(route) => print_route(&route),
+                Err(ErrorCode::NotFound) => {
+                    eprintln!("route: {}: network unreachable", dst);
+                    std::process::exit(1);
+                }
+                Err(err) => exit_with_error(err),
+            }
+        }
+        "add" => {
+            let route = parse_route_args(&args[2..]).to_route_info();
+            svc.add_route(&route)
+                .unwrap_or_else(|err| exit_with_error(err));
+        }
+        "del" => {
+            let route_args = parse_route_args(&args[2..]);
+            if route_args.metric != 0 {
+                print_usage_and_exit(1);
+            }
+            svc.del_route(&route_args.to_route_info())
+                .unwrap_or_else(|err| exit_with_error(err));
+        }
+        _ => print_usage_and_exit(1),
+    }
+}
@@ -0,0 +1,89 @@
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Set or remove extended attributes of files.");
+    eprintln!("usage:\n\tsetfattr -n NAME [-v VALUE] FILE...");
+    eprintln!("\tsetfattr -x NAME FILE...\n");
+    eprintln!("\t-n: set the attribute NAME to VALUE (default: empty);");
+    eprintln!("\t-x: remove the attribute NAME.");
+    eprintln!("\nVALUE is text, or hex if it starts with 0x.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn parse_value(arg: &str) -> Option<Vec<u8>> {
+    let Some(hex) = arg.strip_prefix("0x") else {
+        return Some(arg.as_bytes().to_vec());
+    };
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..(idx + 2))?, 16).ok())
+        .collect()
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "setfattr");
+
+    let mut name = None;
+    let mut remove = false;
+    let mut value = Vec::new();
+    let mut paths = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-n" | "-x" | "-v" => {
+                idx += 1;
+                let Some(val) = args.get(idx) else {
+                    print_usage_and_exit(1);
+                };
+                match arg {
+                    "-v" => match parse_value(val.as_str()) {
+                        Some(val) => value = val,
+                        None => print_usage_and_exit(1),
+                    },
+                    _ if name.is_some() => print_usage_and_exit(1),
+                    _ => {
+                        name = Some(val.as_str());
+                        remove = arg == "-x";
+                    }
+                }
+            }
+            _ if !arg.starts_with('-') => paths.push(arg),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let Some(name) = name else {
+        print_usage_and_exit(1);
+    };
+    if paths.is_empty() || (remove && !value.is_empty()) {
+        print_usage_and_exit(1);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let result = std::fs::canonicalize(path)
+            .map_err(|err| format!("{:?}", err.kind()))
+            .and_then(|abs_path| {
+                let abs_path = abs_path.to_str().unwrap_or("");
+                if remove {
+                    moto_sys_io::fs::remove_xattr(abs_path, name)
+                } else {
+                    moto_sys_io::fs::set_xattr(abs_path, name, value.as_slice(), 0)
+                }
+                .map_err(|err| format!("{:?}", err))
+            });
+        if let Err(err) = result {
+            eprintln!("setfattr: {}: {}", path, err);
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,129 @@
+use moto_sys::time::UtcDateTime;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("List, create, or delete snapshots of a volume (srfs only).");
+    eprintln!("usage:\n\tsnapshot [PATH]");
+    eprintln!("\tsnapshot -c NAME -s SIZE [PATH]");
+    eprintln!("\tsnapshot -d NAME [PATH]\n");
+    eprintln!("\tPATH: any path on the volume (default: /);");
+    eprintln!("\t-c: take snapshot NAME, setting SIZE of the volume aside for it;");
+    eprintln!("\t-s: in bytes, or in K, M, or G (1024-based);");
+    eprintln!("\t-d: delete snapshot NAME.");
+    eprintln!("\nSnapshots are mounted read-only with 'mount snapshot:NAME DIR'.");
+    eprintln!("A snapshot that runs out of space is invalidated. Root only.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn parse_size(arg: &str) -> Option<u64> {
+    let (num, shift) = match arg.chars().last()? {
+        'K' | 'k' => (&arg[0..(arg.len() - 1)], 10),
+        'M' | 'm' => (&arg[0..(arg.len() - 1)], 20),
+        'G' | 'g' => (&arg[0..(arg.len() - 1)], 30),
+        _ => (arg, 0),
+    };
+    num.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn fmt_size(bytes: u64) -> String {
+    const SUFFIXES: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut val = bytes as f64;
+    let mut idx = 0;
+    while val >= 1024.0 && idx < SUFFIXES.len() - 1 {
+        val /= 1024.0;
+        idx += 1;
+    }
+    if idx == 0 {
+        format!("{}{}", bytes, SUFFIXES[0])
+    } else {
+        format!("{:.1}{}", val, SUFFIXES[idx])
+    }
+}
+
+fn list_snapshots(abs_path: &str) {
+    let snapshots = match moto_sys_io::fs::snapshots(abs_path) {
+        Ok(snapshots) => snapshots,
+        Err(err) => {
+            eprintln!("snapshot: {}: {:?}", abs_path, err);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{:20} {:>10} {:>10} {:8} CREATED",
+        "NAME", "SIZE", "USED", "STATE"
+    );
+    for snapshot in &snapshots {
+        println!(
+            "{:20} {:>10} {:>10} {:8} {}",
+            snapshot.name,
+            fmt_size(snapshot.max_bytes),
+            fmt_size(snapshot.used_bytes),
+            if snapshot.valid { "ok" } else { "invalid" },
+            UtcDateTime::from_unix_nanos(snapshot.created as u128)
+        );
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "snapshot");
+
+    let mut create = None;
+    let mut delete = None;
+    let mut max_bytes = None;
+    let mut path = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        match arg {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-c" | "-d" | "-s" => {
+                idx += 1;
+                let Some(val) = args.get(idx).map(|s| s.as_str()) else {
+                    print_usage_and_exit(1);
+                };
+                match arg {
+                    "-c" => create = Some(val),
+                    "-d" => delete = Some(val),
+                    _ => match parse_size(val) {
+                        Some(val) => max_bytes = Some(val),
+                        None => print_usage_and_exit(1),
+                    },
+                }
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+
+    let path = path.unwrap_or("/");
+    let abs_path = match std::fs::canonicalize(path) {
+        Ok(abs_path) => abs_path,
+        Err(err) => {
+            eprintln!("snapshot: {}: {:?}", path, err.kind());
+            std::process::exit(1);
+        }
+    };
+    let abs_path = abs_path.to_str().unwrap_or("");
+
+    let result = match (create, delete, max_bytes) {
+        (None, None, None) => {
+            list_snapshots(abs_path);
+            return;
+        }
+        (Some(name), None, Some(max_bytes)) => {
+            moto_sys_io::fs::create_snapshot(name, abs_path, max_bytes).map_err(|err| (name, err))
+        }
+        (None, Some(name), None) => {
+            moto_sys_io::fs::delete_snapshot(name, abs_path).map_err(|err| (name, err))
+        }
+        _ => print_usage_and_exit(1),
+    };
+
+    if let Err((name, err)) = result {
+        eprintln!("snapshot: {}: {:?}", name, err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,522 @@
+use moto_sys::stats::{ProcessStatsV1, SyscallTraceEventV1};
+use moto_sys::syscalls::*;
+use moto_sys::time::Instant;
+use moto_sys::{ErrorCode, SysCpu, SysHandle, SysMem, SysObj, SysRay};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Trace syscalls of a process.\n");
+    eprintln!("usage:\n\tstrace [-T] [-c] (-p PID | COMMAND [ARG...])\n");
+    eprintln!("\t-T: print the time spent in each syscall.");
+    eprintln!("\t-c: print a per-syscall summary instead of individual syscalls.");
+    eprintln!("\t-p: trace a running process.");
+    eprintln!("Note: syscalls made by COMMAND before the trace is attached are not seen.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const TRACE_BUF_SIZE: usize = 64;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+enum Arg {
+    Handle(&'static str),
+    Hex(&'static str),
+    Dec(&'static str),
+}
+
+use Arg::*;
+
+// (syscall_nr, operation) => (name, args).
+const SYSCALLS: &[(u8, u8, &str, &[Arg])] = &[
+    (SYS_CPU, SysCpu::OP_EXIT, "SysCpu::exit", &[Dec("code")]),
+    (SYS_CPU, SysCpu::OP_WAIT, "SysCpu::wait", &[]), // See format_wait().
+    (
+        SYS_CPU,
+        SysCpu::OP_WAKE,
+        "SysCpu::wake",
+        &[Handle("target"), Handle("thread")],
+    ),
+    (SYS_CPU, SysCpu::OP_KILL, "SysCpu::kill", &[Dec("target")]),
+    (
+        SYS_CPU,
+        SysCpu::OP_SPAWN,
+        "SysCpu::spawn",
+        &[
+            Handle("process"),
+            Hex("stack"),
+            Hex("thread_fn"),
+            Hex("arg"),
+        ],
+    ),
+    (
+        SYS_CPU,
+        SysCpu::OP_USAGE,
+        "SysCpu::usage",
+        &[Hex("buf"), Dec("len")],
+    ),
+    (SYS_CPU, SysCpu::OP_AFFINE_CPU, "SysCpu::affine_cpu", &[]),
+    (
+        SYS_CPU,
+        SysCpu::OP_QUERY_PERCPU_STATS,
+        "SysCpu::query_percpu_stats",
+        &[Hex("page")],
+    ),
+    (
+        SYS_CPU,
+        SysCpu::OP_TIMER,
+        "SysCpu::timer",
+        &[Handle("timer"), Dec("deadline"), Dec("period_ns")],
+    ),
+    (SYS_CPU, SysCpu::OP_CPU_TIME, "SysCpu::cpu_time", &[]),
+    (SYS_CPU, SysCpu::OP_PERF, "SysCpu::perf", &[]),
+    (SYS_CPU, SysCpu::OP_WATCHDOG, "SysCpu::watchdog", &[]),
+    (
+        SYS_CPU,
+        SysCpu::OP_SCHED_POLICY,
+        "SysCpu::sched_policy",
+        &[],
+    ),
+    (
+        SYS_CPU,
+        SysCpu::OP_HOTPLUG,
+        "SysCpu::hotplug",
+        &[Dec("cpu")],
+    ),
+    (
+        SYS_CPU,
+        SysCpu::OP_EVENT_RING,
+        "SysCpu::event_ring",
+        &[Handle("ring"), Handle("source"), Hex("cookie")],
+    ),
+    (
+        SYS_CPU,
+        SysCpu::OP_WALL_CLOCK,
+        "SysCpu::wall_clock",
+        &[Dec("delta_nsec")],
+    ),
+    (
+        SYS_CPU,
+        SysCpu::OP_PRIORITY,
+        "SysCpu::priority",
+        &[Dec("priority")],
+    ),
+    (SYS_MEM, SysMem::OP_CREATE, "SysMem::create", &[]),
+    (SYS_MEM, SysMem::OP_GET, "SysMem::get", &[]),
+    (SYS_MEM, SysMem::OP_PUT, "SysMem::put", &[]),
+    (
+        SYS_MEM,
+        SysMem::OP_MAP,
+        "SysMem::map",
+        &[
+            Handle("address_space"),
+            Hex("phys_addr"),
+            Hex("virt_addr"),
+            Hex("page_size"),
+            Dec("num_pages"),
+        ],
+    ),
+    (
+        SYS_MEM,
+        SysMem::OP_UNMAP,
+        "SysMem::unmap",
+        &[Handle("address_space"), Hex("phys_addr"), Hex("virt_addr")],
+    ),
+    (SYS_MEM, SysMem::OP_REMAP, "SysMem::remap", &[]),
+    (SYS_MEM, SysMem::OP_QUERY, "SysMem::query", &[]),
+    (
+        SYS_MEM,
+        SysMem::OP_RECLAIM,
+        "SysMem::reclaim",
+        &[Handle("target")],
+    ),
+    (SYS_MEM, SysMem::OP_PAGER, "SysMem::pager", &[]),
+    (
+        SYS_MEM,
+        SysMem::OP_PROTECT,
+        "SysMem::protect",
+        &[Handle("address_space"), Hex("virt_addr"), Dec("num_pages")],
+    ),
+    (
+        SYS_MEM,
+        SysMem::OP_ADVISE,
+        "SysMem::advise",
+        &[Handle("address_space"), Hex("virt_addr"), Dec("num_pages")],
+    ),
+    (
+        SYS_OBJ,
+        SysObj::OP_GET,
+        "SysObj::get",
+        &[Handle("parent"), Hex("url"), Dec("url_len")],
+    ),
+    (
+        SYS_OBJ,
+        SysObj::OP_PUT,
+        "SysObj::put",
+        &[Handle("owner"), Handle("handle"), Hex("arg")],
+    ),
+    (
+        SYS_OBJ,
+        SysObj::OP_CREATE,
+        "SysObj::create",
+        &[
+            Handle("parent"),
+            Hex("url"),
+            Dec("url_len"),
+            Handle("process2"),
+        ],
+    ),
+    (
+        SYS_OBJ,
+        SysObj::OP_SET_LOG_LEVEL,
+        "SysObj::set_log_level",
+        &[Dec("level")],
+    ),
+    (
+        SYS_OBJ,
+        SysObj::OP_QUERY_HANDLE,
+        "SysObj::query_handle",
+        &[Handle("handle")],
+    ),
+    (
+        SYS_OBJ,
+        SysObj::OP_DUP,
+        "SysObj::dup",
+        &[
+            Handle("src_process"),
+            Handle("handle"),
+            Handle("dst_process"),
+            Handle("dst_handle"),
+        ],
+    ),
+    (
+        SYS_RAY,
+        SysRay::OP_QUERY_PROCESS,
+        "SysRay::query_process",
+        &[],
+    ),
+    (SYS_RAY, SysRay::OP_DBG, "SysRay::dbg", &[]),
+    (SYS_RAY, SysRay::OP_LOG, "SysRay::log", &[]),
+    (SYS_RAY, SysRay::OP_TRACE, "SysRay::trace", &[]),
+    (
+        SYS_RAY,
+        SysRay::OP_SYSCALL_TRACE,
+        "SysRay::syscall_trace",
+        &[],
+    ),
+];
+
+fn lookup(event: &SyscallTraceEventV1) -> Option<&'static (u8, u8, &'static str, &'static [Arg])> {
+    SYSCALLS
+        .iter()
+        .find(|(nr, op, _, _)| *nr == event.syscall_nr && *op == event.operation)
+}
+
+fn syscall_name(event: &SyscallTraceEventV1) -> String {
+    match lookup(event) {
+        Some((_, _, name, _)) => (*name).to_owned(),
+        None => format!("syscall_{}_{}", event.syscall_nr, event.operation),
+    }
+}
+
+fn format_handle(val: u64) -> String {
+    match SysHandle::from_u64(val) {
+        SysHandle::NONE => "NONE".to_owned(),
+        SysHandle::KERNEL => "KERNEL".to_owned(),
+        SysHandle::SELF => "SELF".to_owned(),
+        SysHandle::CURR => "CURR".to_owned(),
+        SysHandle::PARENT => "PARENT".to_owned(),
+        SysHandle::IO_MAN => "IO_MAN".to_owned(),
+        _ => val.to_string(),
+    }
+}
+
+fn format_wait(event: &SyscallTraceEventV1) -> Vec<String> {
+    let flags = event.flags;
+    let mut args = Vec::new();
+    let mut next_arg = 0;
+
+    if flags & SysCpu::F_DONTBLOCK != 0 {
+        args.push("dontblock".to_owned());
+    }
+    if flags & SysCpu::F_TIMEOUT != 0 {
+        let timeout = Instant::from_u64(event.args[next_arg]);
+        let started = Instant::from_u64(event.started);
+        let millis = timeout
+            .checked_sub_instant(&started)
+            .map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+        args.push(format!("timeout=+{:.3}ms", millis));
+        next_arg += 1;
+    }
+    if flags & SysCpu::F_SWAP_TARGET != 0 {
+        args.push(format!("swap={}", format_handle(event.args[next_arg])));
+        next_arg += 1;
+    } else if flags & SysCpu::F_WAKE_TARGET != 0 {
+        args.push(format!("wake={}", format_handle(event.args[next_arg])));
+        next_arg += 1;
+    }
+
+    if flags & SysCpu::F_HANDLE_ARRAY != 0 {
+        args.push(format!(
+            "handles=0x{:x}[{}]",
+            event.args[next_arg],
+            event.args[next_arg + 1]
+        ));
+    } else {
+        let handles: Vec<String> = event.args[next_arg..]
+            .iter()
+            .take_while(|h| **h != 0)
+            .map(|h| format_handle(*h))
+            .collect();
+        args.push(format!("handles=[{}]", handles.join(", ")));
+    }
+    args
+}
+
+fn format_args(event: &SyscallTraceEventV1) -> String {
+    if event.syscall_nr == SYS_CPU && event.operation == SysCpu::OP_WAIT {
+        return format_wait(event).join(", ");
+    }
+
+    let mut args = Vec::new();
+    if event.flags != 0 {
+        args.push(format!("flags=0x{:x}", event.flags));
+    }
+
+    match lookup(event) {
+        Some((_, _, _, schema)) if !schema.is_empty() => {
+            for (arg, val) in schema.iter().zip(event.args.iter()) {
+                args.push(match arg {
+                    Handle(name) => format!("{}={}", name, format_handle(*val)),
+                    Hex(name) => format!("{}=0x{:x}", name, val),
+                    Dec(name) => format!("{}={}", name, val),
+                });
+            }
+        }
+        _ => {
+            // Unknown layout: print the args up to the last non-zero one.
+            let len = event
+                .args
+                .iter()
+                .rposition(|arg| *arg != 0)
+                .map_or(0, |pos| pos + 1);
+            args.extend(event.args[0..len].iter().map(|arg| format!("0x{:x}", arg)));
+        }
+    }
+
+    args.join(", ")
+}
+
+fn error_code(event: &SyscallTraceEventV1) -> ErrorCode {
+    ErrorCode::from_u16((event.result & 0xFF_FF) as u16)
+}
+
+fn duration(event: &SyscallTraceEventV1) -> Duration {
+    Instant::from_u64(event.finished).duration_since(Instant::from_u64(event.started))
+}
+
+fn format_result(event: &SyscallTraceEventV1) -> String {
+    let mut result = match error_code(event) {
+        ErrorCode::Ok if event.data[0] != 0 => format!("Ok (0x{:x})", event.data[0]),
+        ErrorCode::Ok => "Ok".to_owned(),
+        err => format!("Err({:?})", err),
+    };
+    if event.result & SyscallResult::F_TIMED_OUT != 0 {
+        result.push_str(" [timed out]");
+    }
+    result
+}
+
+#[derive(Default)]
+struct Summary {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+}
+
+fn print_summary(summary: &BTreeMap<String, Summary>) {
+    let mut entries: Vec<_> = summary.iter().collect();
+    entries.sort_by(|a, b| b.1.total.cmp(&a.1.total));
+    let total_time: f64 = entries.iter().map(|(_, s)| s.total.as_secs_f64()).sum();
+
+    println!(
+        "{:>7} {:>12} {:>10} {:>10} {:>8} {:>8}  syscall",
+        "% time", "seconds", "usecs/call", "max usecs", "calls", "errors"
+    );
+    for (name, s) in &entries {
+        let pct = if total_time > 0.0 {
+            s.total.as_secs_f64() * 100.0 / total_time
+        } else {
+            0.0
+        };
+        println!(
+            "{:>7.2} {:>12.6} {:>10} {:>10} {:>8} {:>8}  {}",
+            pct,
+            s.total.as_secs_f64(),
+            s.total.as_micros() as u64 / s.calls,
+            s.max.as_micros(),
+            s.calls,
+            s.errors,
+            name
+        );
+    }
+    let calls: u64 = entries.iter().map(|(_, s)| s.calls).sum();
+    let errors: u64 = entries.iter().map(|(_, s)| s.errors).sum();
+    println!(
+        "{:>7} {:>12.6} {:>10} {:>10} {:>8} {:>8}  total",
+        "100.00", total_time, "", "", calls, errors
+    );
+}
+
+fn is_running(pid: u64) -> bool {
+    let mut stats = [ProcessStatsV1::default()];
+    match ProcessStatsV1::list(pid, &mut stats) {
+        Ok(1) => stats[0].pid == pid && stats[0].active == 1,
+        _ => false,
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "strace");
+
+    let mut timing = false;
+    let mut summarize = false;
+    let mut pid = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-T" => timing = true,
+            "-c" => summarize = true,
+            "-p" if pid.is_none() && idx + 1 < args.len() => {
+                idx += 1;
+                match args[idx].parse::<u64>() {
+                    Ok(val) => pid = Some(val),
+                    Err(_) => print_usage_and_exit(1),
+                }
+            }
+            arg if arg.starts_with('-') => print_usage_and_exit(1),
+            _ => break,
+        }
+        idx += 1;
+    }
+
+    let command = &args[idx..];
+    if pid.is_some() != command.is_empty() {
+        print_usage_and_exit(1);
+    }
+
+    let mut child = None;
+    let pid = match pid {
+        Some(pid) => pid,
+        None => match std::process::Command::new(command[0].as_str())
+            .args(&command[1..])
+            .spawn()
+        {
+            Ok(c) => {
+                let pid = c.id() as u64;
+                child = Some(c);
+                pid
+            }
+            Err(err) => {
+                eprintln!("strace: failed to start '{}': {:?}", command[0], err);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let handle = match SysRay::syscall_trace_attach(pid) {
+        Ok(handle) => handle,
+        Err(err) => {
+            eprintln!("strace: failed to trace process {}: {:?}", pid, err);
+            if let Some(mut child) = child {
+                let _ = child.kill();
+            }
+            std::process::exit(1);
+        }
+    };
+
+    let mut buf = vec![SyscallTraceEventV1::default(); TRACE_BUF_SIZE];
+    let mut summary: BTreeMap<String, Summary> = BTreeMap::new();
+    let mut next_seq = 0;
+    let mut exited = false;
+    let mut exit_status = None;
+
+    loop {
+        let cnt = match SysRay::syscall_trace_read_v1(handle, &mut buf[..]) {
+            Ok(cnt) => cnt,
+            Err(err) => {
+                eprintln!("strace: failed to read the trace: {:?}", err);
+                std::process::exit(1);
+            }
+        };
+
+        for event in &buf[0..cnt] {
+            if event.seq > next_seq {
+                eprintln!("strace: {} syscalls dropped", event.seq - next_seq);
+            }
+            next_seq = event.seq + 1;
+
+            let name = syscall_name(event);
+            if summarize {
+                let s = summary.entry(name).or_default();
+                s.calls += 1;
+                if error_code(event) != ErrorCode::Ok {
+                    s.errors += 1;
+                }
+                s.total += duration(event);
+                s.max = s.max.max(duration(event));
+                continue;
+            }
+
+            let mut line = format!(
+                "[{}] {}({}) = {}",
+                event.tid,
+                name,
+                format_args(event),
+                format_result(event)
+            );
+            if timing {
+                line.push_str(format!(" <{:.6}>", duration(event).as_secs_f64()).as_str());
+            }
+            eprintln!("{}", line);
+        }
+
+        if cnt == TRACE_BUF_SIZE {
+            continue;
+        }
+        if exited {
+            break; // Drained everything recorded before the exit.
+        }
+
+        let timeout = Instant::now() + POLL_INTERVAL;
+        let _ = SysCpu::wait(
+            &mut [handle],
+            SysHandle::NONE,
+            SysHandle::NONE,
+            Some(timeout),
+        );
+
+        exited = match child.as_mut() {
+            Some(child) => match child.try_wait() {
+                Ok(Some(status)) => {
+                    exit_status = status.code();
+                    true
+                }
+                Ok(None) => false,
+                Err(_) => true,
+            },
+            None => !is_running(pid),
+        };
+    }
+
+    if summarize {
+        print_summary(&summary);
+    }
+    if let Some(code) = exit_status {
+        eprintln!("+++ exited with {} +++", code);
+        std::process::exit(code);
+    }
+}
@@ -0,0 +1,98 @@
+use moto_svc::ServiceStatus;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Control services supervised by sys-init (declared in /sys/cfg/services.cfg).");
+    eprintln!("usage:\n\tsvc start|stop|restart NAME...\n\tsvc status [NAME]\n\tsvc logs NAME\n");
+    eprintln!("\tstart: start services, and the services they depend on.");
+    eprintln!("\tstop: stop services, and the services that depend on them.");
+    eprintln!("\trestart: kill services if running, and start them again.");
+    eprintln!("\tstatus: print the status of the service (default: all services).");
+    eprintln!("\tlogs: print the most recent output of the service.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+fn fmt_duration(dur: std::time::Duration) -> String {
+    let secs = dur.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else if secs < 86400 {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    } else {
+        format!("{}d{}h", secs / 86400, (secs % 86400) / 3600)
+    }
+}
+
+fn print_status(statuses: &[ServiceStatus]) {
+    println!(
+        "{:<24} {:<8} {:>10} {:>8} {:>9}",
+        "NAME", "STATE", "UPTIME", "RESTARTS", "LAST EXIT"
+    );
+    for status in statuses {
+        println!(
+            "{:<24} {:<8} {:>10} {:>8} {:>9}",
+            status.name,
+            status.state.as_str(),
+            status.uptime.map(fmt_duration).unwrap_or("-".to_owned()),
+            status.restarts,
+            status
+                .last_exit
+                .map(|code| code.to_string())
+                .unwrap_or("-".to_owned())
+        );
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "svc");
+
+    if args.len() < 2 {
+        print_usage_and_exit(1);
+    }
+    let names = &args[2..];
+
+    let mut failed = false;
+    match args[1].as_str() {
+        "--help" => print_usage_and_exit(0),
+        cmd @ ("start" | "stop" | "restart") => {
+            if names.is_empty() {
+                print_usage_and_exit(1);
+            }
+            for name in names {
+                let result = match cmd {
+                    "start" => moto_svc::start(name),
+                    "stop" => moto_svc::stop(name),
+                    _ => moto_svc::restart(name),
+                };
+                if let Err(err) = result {
+                    eprintln!("svc: {} {}: {:?}", cmd, name, err);
+                    failed = true;
+                }
+            }
+        }
+        "status" if names.len() < 2 => {
+            let name = names.first().map(|s| s.as_str()).unwrap_or("");
+            match moto_svc::status(name) {
+                Ok(statuses) => print_status(&statuses),
+                Err(err) => {
+                    eprintln!("svc: status: {:?}", err);
+                    failed = true;
+                }
+            }
+        }
+        "logs" if names.len() == 1 => match moto_svc::logs(&names[0]) {
+            Ok(logs) => print!("{}", logs),
+            Err(err) => {
+                eprintln!("svc: logs {}: {:?}", names[0], err);
+                failed = true;
+            }
+        },
+        _ => print_usage_and_exit(1),
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,543 @@
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Create, extract, or list tar archives (ustar, with GNU long names).");
+    eprintln!("usage:\n\ttar -c|-x|-t [-z] [-v] -f ARCHIVE [-C DIR] [PATH...]\n");
+    eprintln!("\t-c: create ARCHIVE from PATHs (directories are added recursively).");
+    eprintln!("\t-x: extract ARCHIVE (into DIR, if given).");
+    eprintln!("\t-t: list the contents of ARCHIVE.");
+    eprintln!("\t-z: (de)compress the archive with gzip.");
+    eprintln!("\t-v: print the names of files as they are processed (to stderr).");
+    eprintln!("\t-f: the archive file; '-' means stdin/stdout, e.g. for piping over the network.");
+    eprintln!("\t-C: create: PATHs are relative to DIR; extract: extract into DIR.");
+    eprintln!("\nFlags can be combined, e.g. tar -czf out.tgz DIR; tar -xzf out.tgz.");
+    eprintln!("Regular files, directories, and symlinks are supported; symlinks are stored");
+    eprintln!("as links rather than followed. File modes are not kept.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const BLOCK_SIZE: usize = 512;
+
+const TYPE_FILE: u8 = b'0';
+const TYPE_FILE_OLD: u8 = 0;
+const TYPE_SYMLINK: u8 = b'2';
+const TYPE_DIR: u8 = b'5';
+const TYPE_GNU_LONG_LINK: u8 = b'K';
+const TYPE_GNU_LONG_NAME: u8 = b'L';
+
+const GNU_LONG_NAME: &str = "././@LongLink";
+const USTAR_MAGIC: &[u8] = b"ustar";
+
+// Motor OS does not have file permissions yet.
+const MODE_FILE: u64 = 0o644;
+const MODE_DIR: u64 = 0o755;
+const MODE_SYMLINK: u64 = 0o777;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Create,
+    Extract,
+    List,
+}
+
+struct Options {
+    gzip: bool,
+    verbose: bool,
+    dir: Option<PathBuf>,
+}
+
+// Header fields are NUL-terminated octal numbers; @val must fit.
+fn put_octal(field: &mut [u8], val: u64) {
+    let digits = format!("{:0width$o}", val, width = field.len() - 1);
+    assert!(digits.len() < field.len());
+    field[0..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let s = std::str::from_utf8(field).ok()?;
+    let s = s.trim_matches(|c| c == '\0' || c == ' ');
+    if s.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(s, 8).ok()
+}
+
+fn parse_str(field: &[u8]) -> Result<&str, String> {
+    let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[0..len]).map_err(|_| "invalid file name in header".to_owned())
+}
+
+fn checksum(header: &[u8; BLOCK_SIZE]) -> u64 {
+    // The checksum field itself is counted as spaces.
+    header
+        .iter()
+        .enumerate()
+        .map(|(idx, b)| if (148..156).contains(&idx) { b' ' } else { *b } as u64)
+        .sum()
+}
+
+// @link is the target of symlinks, and empty for other entries.
+fn build_header(name: &str, link: &str, size: u64, mtime: u64, type_flag: u8) -> [u8; BLOCK_SIZE] {
+    let mut header = [0_u8; BLOCK_SIZE];
+
+    // Names and links that do not fit are preceded by GNU long name/link
+    // entries (see write_entry), so here they are just truncated.
+    let (prefix, name) = split_name(name).unwrap_or(("", truncate(name, 100)));
+    header[0..name.len()].copy_from_slice(name.as_bytes());
+    header[345..(345 + prefix.len())].copy_from_slice(prefix.as_bytes());
+    let link = truncate(link, 100);
+    header[157..(157 + link.len())].copy_from_slice(link.as_bytes());
+
+    let mode = match type_flag {
+        TYPE_DIR => MODE_DIR,
+        TYPE_SYMLINK => MODE_SYMLINK,
+        _ => MODE_FILE,
+    };
+    put_octal(&mut header[100..108], mode);
+    put_octal(&mut header[108..116], 0); // uid
+    put_octal(&mut header[116..124], 0); // gid
+    put_octal(&mut header[124..136], size);
+    put_octal(&mut header[136..148], mtime);
+    header[156] = type_flag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    let sum = checksum(&header);
+    put_octal(&mut header[148..155], sum);
+    header[155] = b' ';
+
+    header
+}
+
+fn truncate(s: &str, max_len: usize) -> &str {
+    let mut len = s.len().min(max_len);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    &s[0..len]
+}
+
+// Splits @name into ustar (prefix, name), if it fits.
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    // The prefix is at most 155 bytes, the name at most 100, and the
+    // separating '/' is not stored.
+    for (idx, _) in name.match_indices('/') {
+        if idx <= 155 && name.len() - idx - 1 <= 100 && idx > 0 {
+            return Some((&name[0..idx], &name[(idx + 1)..]));
+        }
+    }
+    None
+}
+
+fn write_padding(writer: &mut dyn Write, len: u64) -> std::io::Result<()> {
+    let rem = (len as usize) % BLOCK_SIZE;
+    if rem != 0 {
+        writer.write_all(&[0_u8; BLOCK_SIZE][rem..])?;
+    }
+    Ok(())
+}
+
+// A GNU long name or link entry: @value, NUL-terminated, as data.
+fn write_long_entry(writer: &mut dyn Write, value: &str, type_flag: u8) -> std::io::Result<()> {
+    let bytes = value.as_bytes();
+    let header = build_header(GNU_LONG_NAME, "", (bytes.len() + 1) as u64, 0, type_flag);
+    writer.write_all(&header)?;
+    writer.write_all(bytes)?;
+    writer.write_all(&[0])?;
+    write_padding(writer, (bytes.len() + 1) as u64)
+}
+
+fn write_entry(
+    writer: &mut dyn Write,
+    name: &str,
+    link: &str,
+    size: u64,
+    mtime: u64,
+    type_flag: u8,
+) -> std::io::Result<()> {
+    if split_name(name).is_none() {
+        write_long_entry(writer, name, TYPE_GNU_LONG_NAME)?;
+    }
+    if link.len() > 100 {
+        write_long_entry(writer, link, TYPE_GNU_LONG_LINK)?;
+    }
+
+    if size >= (1 << 33) {
+        // The size field has 11 octal digits.
+        return Err(std::io::Error::from(std::io::ErrorKind::FileTooLarge));
+    }
+    writer.write_all(&build_header(name, link, size, mtime, type_flag))
+}
+
+fn mtime_of(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Adds @path to the archive as @name, recursively. Symlinks are stored as
+// such, so links to parent directories or to each other are not followed.
+fn add_path(writer: &mut dyn Write, path: &Path, name: &str, opts: &Options) -> Result<(), String> {
+    let metadata =
+        std::fs::symlink_metadata(path).map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+    let mtime = mtime_of(&metadata);
+
+    if opts.verbose {
+        eprintln!("{}", name);
+    }
+
+    if metadata.is_symlink() {
+        let target = std::fs::read_link(path).map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+        let target = target
+            .to_str()
+            .ok_or_else(|| format!("{}: invalid symlink target", name))?;
+        return write_entry(writer, name, target, 0, mtime, TYPE_SYMLINK)
+            .map_err(|e| format!("{}: {:?}", name, e.kind()));
+    }
+
+    if metadata.is_dir() {
+        let dir_name = format!("{}/", name.trim_end_matches('/'));
+        write_entry(writer, &dir_name, "", 0, mtime, TYPE_DIR)
+            .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+
+        let mut children = Vec::new();
+        for entry in std::fs::read_dir(path).map_err(|e| format!("{}: {:?}", name, e.kind()))? {
+            let entry = entry.map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+            children.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        children.sort();
+
+        for child in children {
+            add_path(
+                writer,
+                &path.join(&child),
+                &format!("{}{}", dir_name, child),
+                opts,
+            )?;
+        }
+        return Ok(());
+    }
+
+    let size = metadata.len();
+    write_entry(writer, name, "", size, mtime, TYPE_FILE)
+        .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+    let copied = std::io::copy(&mut file.take(size), writer)
+        .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+    if copied < size {
+        // The file shrank while being archived: keep the header valid.
+        eprintln!("tar: {}: file changed as we read it", name);
+        let mut zeroes = std::io::repeat(0).take(size - copied);
+        std::io::copy(&mut zeroes, writer).map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+    }
+    write_padding(writer, size).map_err(|e| format!("{}: {:?}", name, e.kind()))
+}
+
+fn write_archive(writer: &mut dyn Write, paths: &[&String], opts: &Options) -> Result<(), String> {
+    let base = opts.dir.clone().unwrap_or_default();
+    for path in paths {
+        // Like other tars, store absolute paths as relative.
+        let name = match path.trim_start_matches('/') {
+            "" => ".",
+            name => name,
+        };
+        add_path(writer, &base.join(path.as_str()), name, opts)?;
+    }
+
+    // The end of the archive is marked by two zero blocks.
+    writer
+        .write_all(&[0_u8; 2 * BLOCK_SIZE])
+        .map_err(|e| format!("write failed: {:?}", e.kind()))
+}
+
+fn create(archive: &str, paths: &[&String], opts: &Options) -> Result<(), String> {
+    let output: Box<dyn Write> = if archive == "-" {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(
+            std::fs::File::create(archive).map_err(|e| format!("{}: {:?}", archive, e.kind()))?,
+        )
+    };
+    let mut output = std::io::BufWriter::new(output);
+
+    if opts.gzip {
+        let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+        write_archive(&mut encoder, paths, opts)?;
+        output = encoder
+            .finish()
+            .map_err(|e| format!("write failed: {:?}", e.kind()))?;
+    } else {
+        write_archive(&mut output, paths, opts)?;
+    }
+
+    output
+        .flush()
+        .map_err(|e| format!("write failed: {:?}", e.kind()))
+}
+
+// Strips leading '/' and rejects '..', so that archives cannot write
+// outside of the destination directory.
+fn sanitize(name: &str) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::RootDir | Component::CurDir => {}
+            _ => return Err(format!("{}: unsafe path", name)),
+        }
+    }
+    Ok(path)
+}
+
+// Where to extract @name to. Archives may have symlinks, e.g. "dir -> /",
+// so nothing is extracted through one: the parents of the path under @base
+// must not be symlinks, and a symlink at the path itself is replaced.
+fn extract_path(base: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = sanitize(name)?;
+    let mut path = base.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        path.push(component);
+        let is_symlink = std::fs::symlink_metadata(&path).is_ok_and(|m| m.is_symlink());
+        if !is_symlink {
+            continue;
+        }
+        if components.peek().is_some() {
+            return Err(format!("{}: path goes through a symlink", name));
+        }
+        std::fs::remove_file(&path).map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+    }
+    Ok(path)
+}
+
+fn read_block(reader: &mut dyn Read, block: &mut [u8; BLOCK_SIZE]) -> Result<bool, String> {
+    let mut done = 0;
+    while done < BLOCK_SIZE {
+        match reader.read(&mut block[done..]) {
+            Ok(0) if done == 0 => return Ok(false),
+            Ok(0) => return Err("unexpected end of archive".to_owned()),
+            Ok(n) => done += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("read failed: {:?}", e.kind())),
+        }
+    }
+    Ok(true)
+}
+
+fn extract_or_list(archive: &str, mode: Mode, opts: &Options) -> Result<(), String> {
+    let input: Box<dyn Read> = if archive == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::fs::File::open(archive).map_err(|e| format!("{}: {:?}", archive, e.kind()))?)
+    };
+    let input = std::io::BufReader::new(input);
+    let mut reader: Box<dyn Read> = if opts.gzip {
+        Box::new(flate2::read::GzDecoder::new(input))
+    } else {
+        Box::new(input)
+    };
+
+    let base = opts.dir.clone().unwrap_or_default();
+    let mut long_name: Option<String> = None;
+    let mut long_link: Option<String> = None;
+    let mut header = [0_u8; BLOCK_SIZE];
+    loop {
+        if !read_block(reader.as_mut(), &mut header)? || header.iter().all(|b| *b == 0) {
+            return Ok(());
+        }
+
+        if parse_octal(&header[148..156]) != Some(checksum(&header)) {
+            return Err("bad header checksum (not a tar archive?)".to_owned());
+        }
+        let size = parse_octal(&header[124..136]).ok_or("bad entry size")?;
+        let type_flag = header[156];
+
+        // GNU long names and links apply to the next entry that is not one.
+        let is_long_entry = type_flag == TYPE_GNU_LONG_NAME || type_flag == TYPE_GNU_LONG_LINK;
+        let (pending_name, pending_link) = if is_long_entry {
+            (None, None)
+        } else {
+            (long_name.take(), long_link.take())
+        };
+
+        let name = match pending_name {
+            Some(name) => name,
+            None => {
+                let name = parse_str(&header[0..100])?;
+                let prefix = if &header[257..262] == USTAR_MAGIC {
+                    parse_str(&header[345..500])?
+                } else {
+                    ""
+                };
+                if prefix.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}/{}", prefix, name)
+                }
+            }
+        };
+
+        let link = match pending_link {
+            Some(link) => link,
+            None => parse_str(&header[157..257])?.to_owned(),
+        };
+
+        let mut data = reader.as_mut().take(size);
+        let padding = (BLOCK_SIZE - (size as usize) % BLOCK_SIZE) % BLOCK_SIZE;
+
+        match type_flag {
+            TYPE_GNU_LONG_NAME | TYPE_GNU_LONG_LINK => {
+                let mut bytes = Vec::new();
+                data.read_to_end(&mut bytes)
+                    .map_err(|e| format!("read failed: {:?}", e.kind()))?;
+                let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                let value = String::from_utf8(bytes[0..len].to_vec())
+                    .map_err(|_| "invalid long file name".to_owned())?;
+                if type_flag == TYPE_GNU_LONG_NAME {
+                    long_name = Some(value);
+                } else {
+                    long_link = Some(value);
+                }
+            }
+            _ if mode == Mode::List => {
+                println!("{}", name);
+                std::io::copy(&mut data, &mut std::io::sink())
+                    .map_err(|e| format!("read failed: {:?}", e.kind()))?;
+            }
+            TYPE_DIR => {
+                if opts.verbose {
+                    eprintln!("{}", name);
+                }
+                let path = extract_path(&base, &name)?;
+                std::fs::create_dir_all(&path).map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+            }
+            TYPE_FILE | TYPE_FILE_OLD => {
+                if opts.verbose {
+                    eprintln!("{}", name);
+                }
+                let path = extract_path(&base, &name)?;
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+                }
+                let mut file = std::fs::File::create(&path)
+                    .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+                let copied = std::io::copy(&mut data, &mut file)
+                    .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+                if copied < size {
+                    return Err("unexpected end of archive".to_owned());
+                }
+            }
+            TYPE_SYMLINK => {
+                if opts.verbose {
+                    eprintln!("{} -> {}", name, link);
+                }
+                let path = extract_path(&base, &name)?;
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+                }
+                // std::fs::soft_link is the platform-independent way to create symlinks.
+                #[allow(deprecated)]
+                std::fs::soft_link(&link, &path)
+                    .map_err(|e| format!("{}: {:?}", name, e.kind()))?;
+            }
+            _ => {
+                eprintln!(
+                    "tar: {}: skipping unsupported entry type '{}'",
+                    name, type_flag as char
+                );
+                std::io::copy(&mut data, &mut std::io::sink())
+                    .map_err(|e| format!("read failed: {:?}", e.kind()))?;
+            }
+        }
+
+        std::io::copy(
+            &mut reader.as_mut().take(padding as u64),
+            &mut std::io::sink(),
+        )
+        .map_err(|e| format!("read failed: {:?}", e.kind()))?;
+    }
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "tar");
+
+    let mut mode = None;
+    let mut archive = None;
+    let mut opts = Options {
+        gzip: false,
+        verbose: false,
+        dir: None,
+    };
+    let mut paths = Vec::new();
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        if arg == "--help" {
+            print_usage_and_exit(0);
+        }
+        if arg == "-C" && opts.dir.is_none() && idx + 1 < args.len() {
+            idx += 1;
+            opts.dir = Some(PathBuf::from(&args[idx]));
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            for flag in arg[1..].chars() {
+                match flag {
+                    'c' | 'x' | 't' if mode.is_none() => {
+                        mode = Some(match flag {
+                            'c' => Mode::Create,
+                            'x' => Mode::Extract,
+                            _ => Mode::List,
+                        })
+                    }
+                    'z' => opts.gzip = true,
+                    'v' => opts.verbose = true,
+                    'f' if archive.is_none() && idx + 1 < args.len() => {
+                        idx += 1;
+                        archive = Some(args[idx].as_str());
+                    }
+                    _ => print_usage_and_exit(1),
+                }
+            }
+        } else {
+            paths.push(&args[idx]);
+        }
+        idx += 1;
+    }
+
+    let (mode, archive) = match (mode, archive) {
+        (Some(mode), Some(archive)) => (mode, archive),
+        _ => print_usage_and_exit(1),
+    };
+
+    let result = match mode {
+        Mode::Create => {
+            if paths.is_empty() {
+                print_usage_and_exit(1);
+            }
+            create(archive, &paths, &opts)
+        }
+        Mode::Extract | Mode::List => {
+            if !paths.is_empty() {
+                print_usage_and_exit(1);
+            }
+            extract_or_list(archive, mode, &opts)
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("tar: {}", err);
+        std::process::exit(1);
+    }
+}
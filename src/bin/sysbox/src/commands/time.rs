@@ -1,26 +1,62 @@
+use moto_sys::stats::ProcessAcctV1;
+use moto_sys::time::tsc_to_duration;
+
 fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Run a command and print its real, user, and kernel (sys) time,");
+    eprintln!("and its peak memory usage.");
     eprintln!("usage:\n\ttime $CMD [args]\n");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
     std::process::exit(exit_code);
 }
 
+const ACCT_BUF_SIZE: usize = 64;
+
+// Finds the accounting record the kernel added when process @pid exited.
+fn find_acct_record(pid: u64) -> Option<ProcessAcctV1> {
+    let mut buf: Vec<ProcessAcctV1> = Vec::with_capacity(ACCT_BUF_SIZE);
+    buf.resize_with(ACCT_BUF_SIZE, ProcessAcctV1::default);
+
+    let mut found = None;
+    let mut next_seq = 0;
+    loop {
+        let cnt = ProcessAcctV1::read(next_seq, &mut buf[..]).ok()?;
+        if cnt == 0 {
+            break;
+        }
+        next_seq = buf[cnt - 1].seq + 1;
+        // The newest record wins, should the PID have been reused.
+        if let Some(record) = buf[0..cnt].iter().rev().find(|r| r.pid == pid) {
+            found = Some(record.clone());
+        }
+    }
+    found
+}
+
+fn print_duration(name: &str, duration: std::time::Duration) {
+    let secs = duration.as_secs();
+    let millis = duration.as_millis() % 1000;
+    let minutes = secs / 60;
+    let secs = secs % 60;
+
+    eprintln!("{:<6}{}m{}.{:03}s", name, minutes, secs, millis);
+}
+
 pub fn do_command(args: &[String]) {
     assert_eq!(args[0], "time");
 
     if args.len() < 2 {
         print_usage_and_exit(1);
     }
+    if args[1] == "--help" {
+        print_usage_and_exit(0);
+    }
 
     let mut cmd = std::process::Command::new(args[1].as_str());
-
-    for idx in 2..args.len() {
-        cmd.arg(args[idx].as_str());
-    }
+    cmd.args(&args[2..]);
 
     let start = std::time::Instant::now();
-    match cmd.spawn() {
-        Ok(mut child) => {
-            child.wait().ok();
-        }
+    let (pid, status) = match cmd.spawn() {
+        Ok(mut child) => (child.id() as u64, child.wait()),
         Err(e) => match e.kind() {
             std::io::ErrorKind::InvalidFilename => {
                 println!("{}: command not found.", args[1]);
@@ -31,14 +67,28 @@ pub fn do_command(args: &[String]) {
                 return;
             }
         },
-    }
+    };
     let stop = std::time::Instant::now();
     let duration = stop.duration_since(start);
 
-    let secs = duration.as_secs();
-    let millis = duration.as_millis() % 1000;
-    let minutes = secs / 60;
-    let secs = secs % 60;
+    // Printed to stderr, so that the command's output can be redirected.
+    eprintln!();
+    print_duration("real", duration);
+    match find_acct_record(pid) {
+        Some(record) => {
+            let cpu_kernel = record.cpu_usage_kernel.min(record.cpu_usage);
+            print_duration("user", tsc_to_duration(record.cpu_usage - cpu_kernel));
+            print_duration("sys", tsc_to_duration(cpu_kernel));
+            eprintln!("{:<6}{} KB", "peak", record.peak_bytes_user() >> 10);
+        }
+        None => eprintln!("(resource usage is not available)"),
+    }
 
-    println!("\nreal  {}m{}.{:03}s", minutes, secs, millis);
+    if let Ok(status) = status {
+        if let Some(code) = status.code() {
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+    }
 }
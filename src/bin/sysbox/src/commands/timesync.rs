@@ -0,0 +1,71 @@
+use moto_sys_io::stats::IoStatsService;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Show the status of NTP time synchronization (see [ntp] in sys-net.toml).");
+    eprintln!("usage:\n\ttimesync\n");
+    std::process::exit(exit_code);
+}
+
+fn format_nanos(nanos: i64) -> String {
+    let abs = nanos.unsigned_abs();
+    let sign = if nanos < 0 { "-" } else { "+" };
+    format!("{}{}.{:03}ms", sign, abs / 1_000_000, (abs / 1_000) % 1_000)
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "timesync");
+    if args.len() > 1 {
+        print_usage_and_exit(1);
+    }
+
+    let status = match IoStatsService::connect().and_then(|mut svc| svc.get_time_sync_status()) {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("timesync: failed to get the status from sys-io: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if status.num_servers == 0 {
+        println!("NTP is not configured.");
+        return;
+    }
+
+    println!(
+        "Synchronized:  {}",
+        if status.synced { "yes" } else { "no" }
+    );
+    println!(
+        "Servers:       {} (polled every {}s)",
+        status.num_servers, status.poll_interval_secs
+    );
+
+    let Some(server) = status.server_addr() else {
+        println!("Last sync:     never");
+        return;
+    };
+    let last_sync =
+        time::OffsetDateTime::from_unix_timestamp_nanos(status.last_sync_nsec as i128).unwrap();
+    println!(
+        "Last sync:     {}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        last_sync.year(),
+        last_sync.month() as u8,
+        last_sync.day(),
+        last_sync.hour(),
+        last_sync.minute(),
+        last_sync.second(),
+    );
+    println!("Server:        {} (stratum {})", server, status.stratum);
+    println!("Offset:        {}", format_nanos(status.last_offset_nsec));
+    println!(
+        "Round trip:    {}",
+        format_nanos(status.round_trip_nsec as i64).trim_start_matches('+')
+    );
+    println!(
+        "Slewing:       {}",
+        format_nanos(status.slew_remaining_nsec)
+    );
+    if let Ok(offset) = moto_sys::SysCpu::wall_clock_offset() {
+        println!("Adjusted by:   {}", format_nanos(offset));
+    }
+}
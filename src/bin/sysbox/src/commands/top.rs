@@ -1,8 +1,8 @@
-use moto_sys::stats::{CpuStatsV1, ProcessStatsV1};
+use moto_sys::stats::{CpuStatsV1, ProcessStatsV1, ThreadStatsV1, ThreadStatus};
 use moto_sys::time::Instant;
 use std::{
     collections::HashMap,
-    sync::atomic::{AtomicU32, Ordering},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
     time::Duration,
 };
 
@@ -25,13 +25,40 @@ static MODE: AtomicU32 = AtomicU32::new(1);
 
 static SLEEP: AtomicU32 = AtomicU32::new(0);
 
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Pid = 0,
+    Cpu = 1,
+    Mem = 2,
+}
+
+impl From<u32> for Sort {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Sort::Cpu,
+            2 => Sort::Mem,
+            _ => Sort::Pid,
+        }
+    }
+}
+
+static SORT: AtomicU32 = AtomicU32::new(0);
+static SHOW_THREADS: AtomicBool = AtomicBool::new(false);
+
+// The max number of threads shown per process.
+const MAX_THREADS: usize = 64;
+
 struct Context {
     num_cpus: u32,
     stats_prev: CpuStatsV1,
     stats_now: CpuStatsV1,
-    cmd_cache: HashMap<u64, String>,
+    processes: HashMap<u64, ProcessStatsV1>,
+    thread_cpu_prev: HashMap<(u64, u64), u64>, // (pid, tid) => CPU usage (TSC).
     elapsed: Duration,
     mode: Mode,
+    sort: Sort,
+    show_threads: bool,
 }
 
 fn hide_cursor() {
@@ -86,24 +113,37 @@ fn print_preamble(ctx: &Context) -> u32 {
         Mode::Total => "sec",
         Mode::Diff => "msec",
     };
+    let sort = match ctx.sort {
+        Sort::Pid => "pid",
+        Sort::Cpu => "cpu",
+        Sort::Mem => "mem",
+    };
+
+    let cpus = match moto_sys::SysCpu::online_cpus() {
+        Ok(mask) if mask.count_ones() != ctx.num_cpus => {
+            format!("{} ({} online)", ctx.num_cpus, mask.count_ones())
+        }
+        _ => format!("{}", ctx.num_cpus),
+    };
 
     write_line(
         1,
         &format!(
-            "uptime: {:02}:{:02}:{:02}.{:03}  cpus: {}  processes: {}  mode: {}",
+            "uptime: {:02}:{:02}:{:02}.{:03}  cpus: {}  processes: {}  mode: {}  sort: {}",
             hours,
             minutes,
             secs,
             millis,
-            ctx.num_cpus,
+            cpus,
             ctx.stats_now.num_entries() - 2,
-            mode
+            mode,
+            sort
         ),
     );
 
     write_line(
         2,
-        &format!("    press [space] to toggle, 'q' or [esc] to exit"),
+        "    press [space] to toggle mode, 's' to sort, 't' to show threads, 'q' or [esc] to exit",
     );
 
     2
@@ -122,7 +162,7 @@ fn tsc_to_sec(tsc: u64) -> f64 {
     (tsc as f64) / tsc_in_sec
 }
 
-fn get_cmd_string(cmd_cache: &mut HashMap<u64, String>, pid: u64) -> String {
+fn get_cmd_string(processes: &HashMap<u64, ProcessStatsV1>, pid: u64) -> String {
     if pid == moto_sys::stats::PID_SYSTEM {
         return "(idle)".to_owned();
     }
@@ -133,33 +173,47 @@ fn get_cmd_string(cmd_cache: &mut HashMap<u64, String>, pid: u64) -> String {
         return "sys-io".to_owned();
     }
 
-    fn update_cache(cmd_cache: &mut HashMap<u64, String>, pid: u64) {
-        const MAX_PROCS: usize = 256;
-        let mut processes: Vec<ProcessStatsV1> = Vec::with_capacity(MAX_PROCS);
-        for _ in 0..MAX_PROCS {
-            processes.push(ProcessStatsV1::default());
-        }
-
-        ProcessStatsV1::list(pid, &mut processes[..]).unwrap();
-        for stats in &processes {
-            cmd_cache.insert(stats.pid, stats.debug_name().to_owned());
-        }
+    if let Some(stats) = processes.get(&pid) {
+        return stats.cmdline().to_owned();
     }
 
-    if cmd_cache.len() == 0 {
-        update_cache(cmd_cache, moto_sys::stats::PID_SYSTEM);
-    }
+    "(???)".to_owned()
+}
 
-    if let Some(cmd) = cmd_cache.get(&pid) {
-        return cmd.clone();
+fn update_processes(processes: &mut HashMap<u64, ProcessStatsV1>) {
+    const MAX_PROCS: usize = 256;
+    let mut buf: Vec<ProcessStatsV1> = Vec::with_capacity(MAX_PROCS);
+    for _ in 0..MAX_PROCS {
+        buf.push(ProcessStatsV1::default());
     }
 
-    update_cache(cmd_cache, pid);
-    if let Some(cmd) = cmd_cache.get(&pid) {
-        return cmd.clone();
+    let cnt = ProcessStatsV1::list(moto_sys::stats::PID_SYSTEM, &mut buf[..]).unwrap();
+    processes.clear();
+    for stats in buf.into_iter().take(cnt) {
+        processes.insert(stats.pid, stats);
     }
+}
 
-    "(???)".to_owned()
+fn mem_kb(processes: &HashMap<u64, ProcessStatsV1>, pid: u64) -> u64 {
+    processes
+        .get(&pid)
+        .map_or(0, |stats| stats.total_bytes() >> 10)
+}
+
+fn thread_status_str(stats: &ThreadStatsV1) -> &'static str {
+    if stats.paused_debuggee != 0 {
+        return "paused";
+    }
+    match stats.status {
+        ThreadStatus::Unknown => "?",
+        ThreadStatus::Created => "created",
+        ThreadStatus::LiveRunning => "running",
+        ThreadStatus::LivePreempted => "preempted",
+        ThreadStatus::LiveRunnable => "runnable",
+        ThreadStatus::LiveSyscall => "syscall",
+        ThreadStatus::LiveInWait => "waiting",
+        ThreadStatus::Dead => "dead",
+    }
 }
 
 fn input_listener() {
@@ -184,6 +238,16 @@ fn input_listener() {
                     SLEEP.store(1, Ordering::Release);
                         moto_runtime::futex_wake(&SLEEP);
                 }
+                b's' | b'S' => {
+                    SORT.store((SORT.load(Ordering::Acquire) + 1) % 3, Ordering::Release);
+                    SLEEP.store(1, Ordering::Release);
+                    moto_runtime::futex_wake(&SLEEP);
+                }
+                b't' | b'T' => {
+                    SHOW_THREADS.fetch_xor(true, Ordering::AcqRel);
+                    SLEEP.store(1, Ordering::Release);
+                    moto_runtime::futex_wake(&SLEEP);
+                }
                 _ => {}
             }
         }
@@ -337,21 +401,111 @@ fn format_values(
     values
 }
 
+// Sums per-CPU values of each process; the sums are appended to each line.
+fn add_totals(values: &mut HashMap<u64, Vec<(f64, f64)>>) {
+    for (_, line) in values.iter_mut() {
+        let total = line
+            .iter()
+            .fold((0.0, 0.0), |acc, (k, u)| (acc.0 + k, acc.1 + u));
+        line.push(total);
+    }
+}
+
+// Returns indices of stats_now entries in the display order.
+fn sort_entries(ctx: &Context, values: &HashMap<u64, Vec<(f64, f64)>>) -> Vec<usize> {
+    let num_entries = ctx.stats_now.num_entries() as usize;
+    let num_cpus = ctx.stats_now.num_cpus() as usize;
+    let mut order: Vec<usize> = (0..num_entries).collect();
+
+    // The idle and the kernel entries always go first.
+    let pinned =
+        |pid: u64| pid == moto_sys::stats::PID_SYSTEM || pid == moto_sys::stats::PID_KERNEL;
+    let cpu = |pid: u64| {
+        values
+            .get(&pid)
+            .map_or(0.0, |line| line[num_cpus].0 + line[num_cpus].1)
+    };
+
+    order.sort_by(|a, b| {
+        let a = ctx.stats_now.entry(*a).pid;
+        let b = ctx.stats_now.entry(*b).pid;
+        if pinned(a) || pinned(b) {
+            return (!pinned(a), a).cmp(&(!pinned(b), b));
+        }
+        match ctx.sort {
+            Sort::Pid => a.cmp(&b),
+            Sort::Cpu => cpu(b).total_cmp(&cpu(a)).then(a.cmp(&b)),
+            Sort::Mem => mem_kb(&ctx.processes, b)
+                .cmp(&mem_kb(&ctx.processes, a))
+                .then(a.cmp(&b)),
+        }
+    });
+
+    order
+}
+
+fn thread_lines(ctx: &mut Context, pid: u64, pid_width: usize, num_width: usize) -> Vec<String> {
+    let mut threads = vec![ThreadStatsV1::default(); MAX_THREADS];
+    let cnt = match ThreadStatsV1::list(pid, &mut threads) {
+        Ok(cnt) => cnt,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut lines = Vec::new();
+    for thread in &threads[0..cnt] {
+        let prev = ctx
+            .thread_cpu_prev
+            .insert((pid, thread.tid), thread.cpu_usage)
+            .unwrap_or(thread.cpu_usage);
+        let value = match ctx.mode {
+            Mode::Total => tsc_to_sec(thread.cpu_usage),
+            Mode::Diff => tsc_to_sec(thread.cpu_usage.saturating_sub(prev)),
+            Mode::Percent => {
+                tsc_to_sec(thread.cpu_usage.saturating_sub(prev)) / ctx.elapsed.as_secs_f64()
+            }
+        };
+
+        let mut line = format!("{:>w$}  t ", " ", w = pid_width);
+        for _ in 0..ctx.stats_now.num_cpus() {
+            line += &format!(" {:>w$}", "", w = num_width);
+        }
+        line += &format!(" {:>w$}", format_value(ctx, value), w = num_width);
+        line += &format!("  tid {} {}", thread.tid, thread_status_str(thread));
+        if thread.last_cpu != u16::MAX {
+            line += &format!(" (cpu {})", thread.last_cpu);
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
 fn tick(ctx: &mut Context) {
     hide_cursor();
     let mut row = print_preamble(ctx);
-    let values_f64 = calc_values(ctx);
+    update_processes(&mut ctx.processes);
+    let mut values_f64 = calc_values(ctx);
+    add_totals(&mut values_f64);
+    let order = sort_entries(ctx, &values_f64);
     let values = format_values(ctx, values_f64);
 
     let (pid_width, num_width, num_cpus_len) = calc_column_widths(ctx, &values);
 
     let num_cpus = ctx.stats_now.num_cpus();
-    let num_entries = ctx.stats_now.num_entries();
+    let mem_width = ctx
+        .processes
+        .values()
+        .map(|stats| (stats.total_bytes() >> 10).to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("MEM_KB".len());
 
     let mut header = format!("{:>w$}  * ", "PID", w = pid_width);
     for cpu in 0..num_cpus {
         header += &format!(" {:>w$}{}", "CPU", cpu, w = (num_width - num_cpus_len));
     }
+    header += &format!(" {:>w$}", "TOTAL", w = num_width);
+    header += &format!(" {:>w$}", "MEM_KB", w = mem_width);
     header += "  COMMAND";
 
     let mut border = String::new();
@@ -363,39 +517,64 @@ fn tick(ctx: &mut Context) {
     write_line(row, border.as_str());
     row += 1;
     write_line(row, header.as_str());
+
+    let mut queue_depths = vec![0_u32; num_cpus as usize];
+    if moto_sys::stats::get_cpu_queue_depths(&mut queue_depths).is_ok() {
+        let mut line_q = format!("{:>w$}  q ", " ", w = pid_width);
+        for depth in &queue_depths {
+            line_q += &format!(" {:>w$}", depth, w = num_width);
+        }
+        line_q += "  (run queue depth)";
+        row += 1;
+        write_line(row, line_q.as_str());
+    }
+
     row += 1;
     write_line(row, border.as_str());
 
-    for idx in 0..num_entries {
-        let entry_now = ctx.stats_now.entry(idx as usize);
+    let show_threads = ctx.show_threads;
+    if !show_threads {
+        ctx.thread_cpu_prev.clear();
+    }
+
+    for idx in order {
+        let pid = ctx.stats_now.entry(idx).pid;
 
-        let mut line_k = format!("{:>w$}  k ", entry_now.pid, w = pid_width);
+        let mut line_k = format!("{:>w$}  k ", pid, w = pid_width);
 
-        let line = values.get(&entry_now.pid).unwrap();
+        let line = values.get(&pid).unwrap();
 
-        for cpu in 0..num_cpus {
+        for cpu in 0..=num_cpus {
             line_k += &format!(" {:>w$}", line[cpu as usize].0, w = num_width);
         }
+        line_k += &format!(" {:>w$}", mem_kb(&ctx.processes, pid), w = mem_width);
 
-        line_k += &format!("  {}", get_cmd_string(&mut ctx.cmd_cache, entry_now.pid));
+        line_k += &format!("  {}", get_cmd_string(&ctx.processes, pid));
         row += 1;
         write_line(row, line_k.as_str());
 
-        if entry_now.pid == moto_sys::stats::PID_SYSTEM {
+        if pid == moto_sys::stats::PID_SYSTEM {
             continue;
         }
-        if entry_now.pid == moto_sys::stats::PID_KERNEL {
+        if pid == moto_sys::stats::PID_KERNEL {
             continue;
         }
 
         let mut line_u = format!("{:>w$}  u ", " ", w = pid_width);
 
-        for cpu in 0..num_cpus {
+        for cpu in 0..=num_cpus {
             line_u += &format!(" {:>w$}", line[cpu as usize].1, w = num_width);
         }
 
         row += 1;
         write_line(row, line_u.as_str());
+
+        if show_threads {
+            for line_t in thread_lines(ctx, pid, pid_width, num_width) {
+                row += 1;
+                write_line(row, line_t.as_str());
+            }
+        }
     }
 
     write_line(row + 1, "");
@@ -411,7 +590,6 @@ pub fn do_command(args: &[String]) {
     }
 
     std::thread::spawn(|| input_listener());
-    let cmd_cache: HashMap<u64, String> = HashMap::new();
 
     let stats_prev = CpuStatsV1::new();
     let mut tick_prev = Instant::now();
@@ -424,9 +602,12 @@ pub fn do_command(args: &[String]) {
         num_cpus: stats_now.num_cpus(),
         stats_prev,
         stats_now,
-        cmd_cache,
+        processes: HashMap::new(),
+        thread_cpu_prev: HashMap::new(),
         elapsed: tick_now.duration_since(tick_prev),
         mode: MODE.load(Ordering::Relaxed).into(),
+        sort: SORT.load(Ordering::Relaxed).into(),
+        show_threads: SHOW_THREADS.load(Ordering::Relaxed),
     };
 
     loop {
@@ -447,5 +628,7 @@ pub fn do_command(args: &[String]) {
         tick_now = Instant::now();
         ctx.elapsed = tick_now.duration_since(tick_prev);
         ctx.mode = MODE.load(Ordering::Acquire).into();
+        ctx.sort = SORT.load(Ordering::Acquire).into();
+        ctx.show_threads = SHOW_THREADS.load(Ordering::Acquire);
     }
 }
@@ -0,0 +1,156 @@
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Run a command repeatedly, showing its output full screen.");
+    eprintln!("usage:\n\twatch [-n SECS] [-t] CMD [ARG...]\n");
+    eprintln!("\t-n: seconds between runs (default: 2; may be fractional).");
+    eprintln!("\t-t: do not show the header.");
+    eprintln!("\tCMD: a path, a binary in /bin, or a sysbox command (e.g. watch -n 1 ps).");
+    eprintln!("\nPress 'q', Esc, or Ctrl-C to exit.");
+    std::thread::sleep(std::time::Duration::new(0, 50_000_000));
+    std::process::exit(exit_code);
+}
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+// 0: keep sleeping; 1: quit.
+static QUIT: AtomicU32 = AtomicU32::new(0);
+
+fn input_listener() {
+    loop {
+        let mut input = [0_u8; 16];
+        let sz = match std::io::stdin().read(&mut input) {
+            Ok(0) | Err(_) => return, // No console.
+            Ok(sz) => sz,
+        };
+        for b in &input[0..sz] {
+            if matches!(*b, 3 /* ^C */ | 27 /* esc */ | b'q' | b'Q') {
+                QUIT.store(1, Ordering::Release);
+                moto_runtime::futex_wake(&QUIT);
+            }
+        }
+    }
+}
+
+fn is_elf(path: &str) -> bool {
+    let mut magic = [0_u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && magic == *b"\x7fELF"
+}
+
+// Scripts (like the wrappers in /bin) cannot be spawned directly,
+// so commands without a path run as sysbox commands unless they
+// are binaries in /bin.
+fn build_command(cmd: &[String]) -> std::process::Command {
+    if cmd[0].contains('/') {
+        let mut command = std::process::Command::new(&cmd[0]);
+        command.args(&cmd[1..]);
+        return command;
+    }
+
+    let bin = format!("/bin/{}", cmd[0]);
+    if is_elf(&bin) {
+        let mut command = std::process::Command::new(bin);
+        command.args(&cmd[1..]);
+        command
+    } else {
+        let mut command = std::process::Command::new("/sys/sysbox");
+        command.args(cmd);
+        command
+    }
+}
+
+fn draw(header: Option<&str>, output: &[u8]) {
+    let mut screen = Vec::with_capacity(output.len() + 256);
+    screen.extend_from_slice(b"\x1b[H"); // Move the cursor to 1:1 pos.
+    if let Some(header) = header {
+        screen.extend_from_slice(header.as_bytes());
+        screen.extend_from_slice(b"\x1b[K\r\n\x1b[K\r\n");
+    }
+    for line in output.split_inclusive(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        screen.extend_from_slice(line);
+        screen.extend_from_slice(b"\x1b[K\r\n"); // Clear until the end of the line.
+    }
+    screen.extend_from_slice(b"\x1b[0J"); // Clear the rest of the screen.
+
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout.write_all(&screen);
+    let _ = stdout.flush();
+}
+
+fn exit(code: i32) -> ! {
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout.write_all(b"\x1b[?25h"); // Show the cursor.
+    let _ = stdout.flush();
+    std::process::exit(code);
+}
+
+pub fn do_command(args: &[String]) {
+    assert_eq!(args[0], "watch");
+
+    let mut interval = DEFAULT_INTERVAL;
+    let mut show_header = true;
+    let mut idx = 1;
+    while idx < args.len() && args[idx].starts_with('-') {
+        match args[idx].as_str() {
+            "--help" => print_usage_and_exit(0),
+            "-t" => show_header = false,
+            "-n" => {
+                idx += 1;
+                match args.get(idx).and_then(|arg| arg.parse::<f64>().ok()) {
+                    Some(secs) if secs >= 0.1 => interval = Duration::from_secs_f64(secs),
+                    _ => print_usage_and_exit(1),
+                }
+            }
+            _ => print_usage_and_exit(1),
+        }
+        idx += 1;
+    }
+    let cmd = &args[idx..];
+    if cmd.is_empty() {
+        print_usage_and_exit(1);
+    }
+
+    std::thread::spawn(input_listener);
+
+    let cmdline = cmd.join(" ");
+    let _ = std::io::stdout().write_all(b"\x1b[?25l\x1b[2J"); // Hide the cursor, clear.
+    loop {
+        let output = match build_command(cmd)
+            .stdin(std::process::Stdio::null())
+            .output()
+        {
+            Ok(output) => {
+                let mut bytes = output.stdout;
+                bytes.extend_from_slice(&output.stderr);
+                bytes
+            }
+            Err(err) => format!("watch: {}: {:?}\n", cmd[0], err.kind()).into_bytes(),
+        };
+
+        let header = if show_header {
+            let now = time::OffsetDateTime::now_utc();
+            Some(format!(
+                "Every {:.1}s: {}    {:02}:{:02}:{:02} UTC",
+                interval.as_secs_f64(),
+                cmdline,
+                now.hour(),
+                now.minute(),
+                now.second()
+            ))
+        } else {
+            None
+        };
+        draw(header.as_deref(), &output);
+
+        moto_runtime::futex_wait(&QUIT, 0, Some(interval));
+        if QUIT.load(Ordering::Acquire) != 0 {
+            exit(0);
+        }
+    }
+}
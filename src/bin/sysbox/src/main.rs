@@ -4,25 +4,59 @@ mod commands;
 
 fn print_usage_and_exit(exit_code: i32) -> ! {
     println!("sysbox commands:");
+    println!("\tsysbox acct");
     println!("\tsysbox cat");
+    println!("\tsysbox chmod");
+    println!("\tsysbox chown");
+    println!("\tsysbox crond");
+    println!("\tsysbox crontab");
     println!("\tdate");
+    println!("\tsysbox df");
+    println!("\tsysbox display");
+    println!("\tsysbox dmesg");
+    println!("\tsysbox du");
     println!("\tsysbox echo");
+    println!("\tsysbox fallocate");
+    println!("\tsysbox find");
     println!("\tsysbox free");
+    println!("\tsysbox fswatch");
+    println!("\tsysbox getfattr");
+    println!("\tsysbox grep");
     println!("\tsysbox help");
+    println!("\tsysbox hexdump");
+    println!("\tsysbox ifconfig");
     println!("\tsysbox kill");
+    println!("\tsysbox ln");
     println!("\tsysbox loop");
     println!("\tsysbox ls");
+    println!("\tsysbox lsof");
+    println!("\tsysbox mfw");
     println!("\tsysbox mkdir");
+    println!("\tsysbox mount");
     println!("\tsysbox mv");
+    println!("\tsysbox netstat");
+    println!("\tsysbox ping");
+    println!("\tsysbox pkg");
     println!("\tsysbox ps");
     println!("\tsysbox pwd");
+    println!("\tsysbox quota");
+    println!("\tsysbox readlink");
     println!("\tsysbox rm");
     println!("\tsysbox rmdir");
+    println!("\tsysbox route");
+    println!("\tsysbox setfattr");
     println!("\tsysbox sleep");
+    println!("\tsysbox snapshot");
     println!("\tsysbox ss");
+    println!("\tsysbox strace");
+    println!("\tsysbox svc");
+    println!("\tsysbox tar");
     println!("\tsysbox time");
+    println!("\tsysbox timesync");
     println!("\tsysbox top");
     println!("\tsysbox uptime");
+    println!("\tsysbox watch");
+    println!("\tsysbox xxd");
     std::process::exit(exit_code);
 }
 
@@ -55,25 +89,58 @@ fn main() {
     }
 
     match args[1].as_str() {
+        "acct" => commands::acct::do_command(&args[1..]),
         "cat" => commands::cat::do_command(&args[1..]),
+        "chmod" => commands::chmod::do_command(&args[1..]),
+        "chown" => commands::chown::do_command(&args[1..]),
+        "crond" => commands::crond::do_command(&args[1..]),
+        "crontab" => commands::crontab::do_command(&args[1..]),
         "date" => commands::date::do_command(&args[1..]),
+        "df" => commands::df::do_command(&args[1..]),
+        "display" => commands::display::do_command(&args[1..]),
+        "dmesg" => commands::dmesg::do_command(&args[1..]),
+        "du" => commands::du::do_command(&args[1..]),
         "echo" => commands::echo::do_command(&args[1..]),
+        "fallocate" => commands::fallocate::do_command(&args[1..]),
+        "find" => commands::find::do_command(&args[1..]),
         "free" => commands::free::do_command(&args[1..]),
+        "fswatch" => commands::fswatch::do_command(&args[1..]),
+        "getfattr" => commands::getfattr::do_command(&args[1..]),
+        "grep" => commands::grep::do_command(&args[1..]),
         "help" => print_usage_and_exit(0),
+        "hexdump" | "xxd" => commands::hexdump::do_command(&args[1..]),
+        "ifconfig" => commands::ifconfig::do_command(&args[1..]),
         "kill" => commands::kill::do_command(&args[1..]),
+        "ln" => commands::ln::do_command(&args[1..]),
         "loop" => commands::loop_cmd::do_command(&args[1..]),
         "ls" => commands::ls::do_command(&args[1..]),
+        "lsof" => commands::lsof::do_command(&args[1..]),
+        "mfw" => commands::mfw::do_command(&args[1..]),
         "mkdir" => commands::mkdir::do_command(&args[1..]),
+        "mount" => commands::mount::do_command(&args[1..]),
         "mv" => commands::mv::do_command(&args[1..]),
+        "netstat" => commands::netstat::do_command(&args[1..]),
+        "ping" => commands::ping::do_command(&args[1..]),
+        "pkg" => commands::pkg::do_command(&args[1..]),
         "ps" => commands::ps::do_command(&args[1..]),
         "pwd" => commands::pwd::do_command(&args[1..]),
+        "quota" => commands::quota::do_command(&args[1..]),
+        "readlink" => commands::readlink::do_command(&args[1..]),
         "rm" => commands::rm::do_command(&args[1..]),
         "rmdir" => commands::rmdir::do_command(&args[1..]),
+        "route" => commands::route::do_command(&args[1..]),
+        "setfattr" => commands::setfattr::do_command(&args[1..]),
         "sleep" => commands::sleep::do_command(&args[1..]),
+        "snapshot" => commands::snapshot::do_command(&args[1..]),
         "ss" => commands::ss::do_command(&args[1..]),
+        "strace" => commands::strace::do_command(&args[1..]),
+        "svc" => commands::svc::do_command(&args[1..]),
+        "tar" => commands::tar::do_command(&args[1..]),
         "time" => commands::time::do_command(&args[1..]),
+        "timesync" => commands::timesync::do_command(&args[1..]),
         "top" => commands::top::do_command(&args[1..]),
         "uptime" => commands::uptime::do_command(&args[1..]),
+        "watch" => commands::watch::do_command(&args[1..]),
         _ => print_usage_and_exit(1),
     }
 
@@ -1,20 +1,55 @@
 // mod channel_test;
 mod mpmc;
+mod poll;
 mod spawn_wait_kill;
 mod subcommand;
 mod tcp;
 mod tls;
+mod udp;
 mod xor_server;
 
 use std::{
     io::{Read, Write},
     sync::{
-        atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering},
         Arc,
     },
     time::Duration,
 };
 
+fn test_clocks() {
+    use moto_runtime::time::{clock_now, clock_resolution, ClockId};
+
+    for clock in [
+        ClockId::Monotonic,
+        ClockId::Realtime,
+        ClockId::ProcessCpu,
+        ClockId::ThreadCpu,
+    ] {
+        assert!(clock_resolution(clock) > Duration::ZERO);
+        assert!(clock_now(clock).unwrap() > Duration::ZERO);
+    }
+
+    let monotonic = clock_now(ClockId::Monotonic).unwrap();
+    let thread_cpu = clock_now(ClockId::ThreadCpu).unwrap();
+    let started = std::time::Instant::now();
+    while started.elapsed() < Duration::from_millis(20) {
+        core::hint::spin_loop();
+    }
+    assert!(clock_now(ClockId::Monotonic).unwrap() >= monotonic + Duration::from_millis(20));
+    let thread_cpu = clock_now(ClockId::ThreadCpu).unwrap() - thread_cpu;
+    assert!(thread_cpu >= Duration::from_millis(10));
+    // The process has consumed at least what this thread has.
+    assert!(clock_now(ClockId::ProcessCpu).unwrap() >= clock_now(ClockId::ThreadCpu).unwrap());
+
+    // A sleeping thread consumes (almost) no CPU time.
+    let thread_cpu = clock_now(ClockId::ThreadCpu).unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(clock_now(ClockId::ThreadCpu).unwrap() - thread_cpu < Duration::from_millis(10));
+
+    println!("test_clocks PASS");
+}
+
 fn test_syscall() {
     const ITERS: usize = 1_000_000;
     let start = std::time::Instant::now();
@@ -70,6 +105,147 @@ fn test_futex() {
     println!("test_futex PASS");
 }
 
+fn test_futex_timeout() {
+    use moto_runtime::futex::*;
+
+    static FUTEX: AtomicU32 = AtomicU32::new(0);
+
+    // The value differs: returns right away.
+    assert!(futex_wait_until(&FUTEX, 1, None));
+
+    // A deadline in the past.
+    let past = moto_sys::time::Instant::now();
+    assert!(!futex_wait_until(&FUTEX, 0, Some(past)));
+
+    // Relative and absolute timeouts expire, and not early.
+    let timeout = Duration::from_millis(20);
+    let start = std::time::Instant::now();
+    assert!(!futex_wait(&FUTEX, 0, Some(timeout)));
+    assert!(start.elapsed() >= timeout);
+
+    let start = std::time::Instant::now();
+    assert!(!futex_wait_until(
+        &FUTEX,
+        0,
+        Some(moto_sys::time::Instant::now() + timeout)
+    ));
+    assert!(start.elapsed() >= timeout);
+
+    // A wake before the deadline.
+    let waker = std::thread::spawn(|| {
+        std::thread::sleep(Duration::from_millis(10));
+        FUTEX.store(1, Ordering::Release);
+        futex_wake(&FUTEX);
+    });
+    let deadline = moto_sys::time::Instant::now() + Duration::from_secs(5);
+    while FUTEX.load(Ordering::Acquire) == 0 {
+        assert!(futex_wait_until(&FUTEX, 0, Some(deadline)));
+    }
+    assert!(moto_sys::time::Instant::now() < deadline);
+    waker.join().unwrap();
+
+    println!("test_futex_timeout PASS");
+}
+
+fn test_futex_requeue() {
+    use moto_runtime::futex::*;
+
+    static SOURCE: AtomicU32 = AtomicU32::new(0);
+    static TARGET: AtomicU32 = AtomicU32::new(0);
+    static WOKEN: AtomicU16 = AtomicU16::new(0);
+    const THREADS: u16 = 8;
+
+    let mut threads = vec![];
+    for _idx in 0..THREADS {
+        threads.push(std::thread::spawn(|| {
+            // Spurious wakeups are possible; a requeued waiter returns
+            // once SOURCE changes.
+            while SOURCE.load(Ordering::Acquire) == 0 {
+                futex_wait(&SOURCE, 0, None);
+            }
+            WOKEN.fetch_add(1, Ordering::AcqRel);
+        }));
+    }
+
+    // Nothing to requeue from an unused futex, or to the same futex.
+    assert_eq!(0, futex_requeue(&TARGET, 1, &SOURCE, 1));
+    assert_eq!(0, futex_requeue(&SOURCE, 1, &SOURCE, 1));
+
+    // Move the waiters to TARGET as they block, without waking them.
+    let mut requeued = 0;
+    let start = std::time::Instant::now();
+    while requeued < THREADS as usize {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(1));
+        requeued += futex_requeue(&SOURCE, 0, &TARGET, usize::MAX);
+    }
+
+    // Waking SOURCE does not wake requeued waiters (and they would go back
+    // to waiting anyway).
+    futex_wake_all(&SOURCE);
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(0, WOKEN.load(Ordering::Acquire));
+
+    // Waking TARGET does.
+    SOURCE.store(1, Ordering::Release);
+    assert!(futex_wake(&TARGET));
+    let start = std::time::Instant::now();
+    while WOKEN.load(Ordering::Acquire) < THREADS {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        futex_wake_all(&TARGET);
+        futex_wake_all(&SOURCE);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    println!("test_futex_requeue PASS");
+}
+
+fn test_rt_condvar() {
+    use moto_runtime::mutex::{Condvar, Mutex};
+
+    static STATE: Mutex<(bool, u16)> = Mutex::new((false, 0));
+    static CONDVAR: Condvar = Condvar::new();
+    const THREADS: u16 = 20;
+
+    // A timeout.
+    let (guard, notified) = CONDVAR.wait_until(
+        STATE.lock(),
+        Some(moto_sys::time::Instant::now() + Duration::from_millis(10)),
+    );
+    assert!(!notified);
+    core::mem::drop(guard);
+
+    let mut threads = vec![];
+    for _idx in 0..THREADS {
+        threads.push(std::thread::spawn(|| {
+            let mut state = STATE.lock();
+            state.1 += 1;
+            while !state.0 {
+                state = CONDVAR.wait(state);
+            }
+            state.1 -= 1;
+        }));
+    }
+
+    // Wait for the threads to start waiting, then wake them all.
+    let start = std::time::Instant::now();
+    while STATE.lock().1 < THREADS {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    STATE.lock().0 = true;
+    CONDVAR.notify_all();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(0, STATE.lock().1);
+    println!("test_rt_condvar PASS");
+}
+
 fn test_rt_mutex() {
     use moto_runtime::mutex::Mutex;
 
@@ -94,6 +270,92 @@ fn test_rt_mutex() {
     println!("test_rt_mutex PASS");
 }
 
+// A high priority thread waiting for a mutex held by a normal priority
+// thread lends it its priority, so that busy normal priority threads
+// don't delay the owner (and thus the high priority thread).
+fn test_rt_mutex_priority_inversion() {
+    use moto_runtime::mutex::Mutex;
+    use moto_sys::SysCpu;
+
+    if moto_sys::ProcessStaticPage::get().capabilities & moto_sys::caps::CAP_REALTIME == 0 {
+        println!("test_rt_mutex_priority_inversion SKIPPED (no CAP_REALTIME)");
+        return;
+    }
+
+    static LOCK: Mutex<u64> = Mutex::new(0);
+    static HIGH_WAITING: AtomicBool = AtomicBool::new(false);
+    static STOP: AtomicBool = AtomicBool::new(false);
+
+    // The work the owner does under the lock: about 20ms on an idle CPU.
+    fn work(iterations: u64) -> u64 {
+        let mut val = 0_u64;
+        for idx in 0..iterations {
+            val = std::hint::black_box(val.wrapping_mul(31).wrapping_add(idx));
+        }
+        val
+    }
+    let started = std::time::Instant::now();
+    let mut iterations = 0;
+    while started.elapsed() < Duration::from_millis(20) {
+        work(1000);
+        iterations += 1000;
+    }
+    let started = std::time::Instant::now();
+    work(iterations);
+    let baseline = started.elapsed();
+
+    HIGH_WAITING.store(false, Ordering::Release);
+    STOP.store(false, Ordering::Release);
+
+    // Low: takes the lock, then works once the high priority thread waits.
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let low = std::thread::spawn(move || {
+        let mut val = LOCK.lock();
+        locked_tx.send(()).unwrap();
+        while !HIGH_WAITING.load(Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+        *val = work(iterations);
+    });
+    locked_rx.recv().unwrap();
+
+    // Medium: keep all CPUs busy, several times over.
+    let mut medium = vec![];
+    for _ in 0..(moto_sys::num_cpus() * 8) {
+        medium.push(std::thread::spawn(|| {
+            while !STOP.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }));
+    }
+
+    // High: waits for the lock.
+    let high = std::thread::spawn(|| {
+        SysCpu::set_priority(SysCpu::PRIORITY_HIGH).unwrap();
+        HIGH_WAITING.store(true, Ordering::Release);
+        let started = std::time::Instant::now();
+        let _val = LOCK.lock();
+        started.elapsed()
+    });
+
+    let waited = high.join().unwrap();
+    STOP.store(true, Ordering::Release);
+    low.join().unwrap();
+    for thread in medium {
+        thread.join().unwrap();
+    }
+
+    // Without the donation, the owner would share the CPUs with
+    // the medium priority threads, and take ~8x longer.
+    assert!(
+        waited < baseline * 4 + Duration::from_millis(50),
+        "waited {:?} vs baseline {:?}",
+        waited,
+        baseline
+    );
+    println!("test_rt_mutex_priority_inversion PASS");
+}
+
 fn test_reentrant_mutex() {
     let _lock1 = std::io::stdout().lock();
     let mut lock2 = std::io::stdout().lock();
@@ -140,6 +402,61 @@ fn test_cpus() {
     println!("test_cpus PASS");
 }
 
+// Takes the last CPU offline and brings it back: threads must stay off it
+// while it is offline, and run on it again once it is back.
+fn test_cpu_hotplug() {
+    use moto_sys::SysCpu;
+
+    let num_cpus = moto_sys::num_cpus();
+    let all_cpus = u64::MAX >> (64 - num_cpus);
+    assert_eq!(all_cpus, SysCpu::online_cpus().unwrap());
+
+    let cpu = num_cpus - 1;
+    if moto_sys::ProcessStaticPage::get().capabilities & moto_sys::caps::CAP_SYS == 0 {
+        assert_eq!(
+            Err(moto_sys::ErrorCode::NotAllowed),
+            SysCpu::set_cpu_online(cpu, false)
+        );
+        println!("test_cpu_hotplug SKIPPED (no CAP_SYS)");
+        return;
+    }
+    if num_cpus < 2 {
+        println!("test_cpu_hotplug SKIPPED (one CPU)");
+        return;
+    }
+
+    // Spins on every CPU for a while; returns the CPUs the threads ran on.
+    fn cpus_used(num_threads: u32) -> u64 {
+        let used = Arc::new(AtomicU64::new(0));
+        let threads: Vec<_> = (0..num_threads)
+            .map(|_| {
+                let used = used.clone();
+                std::thread::spawn(move || {
+                    let start = std::time::Instant::now();
+                    while start.elapsed() < Duration::from_millis(100) {
+                        used.fetch_or(1 << moto_sys::current_cpu(), Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        used.load(Ordering::Relaxed)
+    }
+
+    SysCpu::set_cpu_online(cpu, false).unwrap();
+    assert_eq!(all_cpus & !(1 << cpu), SysCpu::online_cpus().unwrap());
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(0, cpus_used(num_cpus * 2) & (1 << cpu));
+
+    SysCpu::set_cpu_online(cpu, true).unwrap();
+    assert_eq!(all_cpus, SysCpu::online_cpus().unwrap());
+    assert_ne!(0, cpus_used(num_cpus * 2) & (1 << cpu));
+
+    println!("test_cpu_hotplug PASS");
+}
+
 fn test_ipc() {
     use moto_ipc::sync::*;
 
@@ -478,6 +795,135 @@ fn test_caps() {
     println!("test_caps() PASS");
 }
 
+// Symlinks that a namespaced process follows are resolved against its FS
+// root, even if they were created outside of the namespace.
+#[allow(deprecated)] // std::fs::soft_link is platform-independent.
+fn test_namespace_symlinks() {
+    let mut dir = std::env::temp_dir();
+    dir.push("ns_symlinks");
+    let _ = std::fs::remove_dir_all(dir.as_path());
+
+    let root = dir.join("root");
+    std::fs::create_dir_all(root.as_path()).unwrap();
+    std::fs::write(dir.join("outside"), "outside").unwrap();
+    std::fs::write(root.join("inside"), "inside").unwrap();
+
+    let outside = dir.join("outside");
+    std::fs::soft_link(outside.as_path(), root.join("absolute")).unwrap();
+    std::fs::soft_link("../outside", root.join("relative")).unwrap();
+    std::fs::soft_link("../../../..", root.join("up")).unwrap();
+    std::fs::soft_link("/", root.join("self")).unwrap();
+
+    // Outside of the namespace, the links lead out of the root.
+    assert_eq!(
+        "outside",
+        std::fs::read_to_string(root.join("absolute")).unwrap()
+    );
+    assert_eq!(
+        "outside",
+        std::fs::read_to_string(root.join("relative")).unwrap()
+    );
+
+    let mut child = subcommand::spawn_with_env(&[(
+        moto_sys::caps::MOTURUS_FS_ROOT_ENV_KEY,
+        root.to_str().unwrap(),
+    )]);
+    assert_eq!("ok inside", child.cat("/inside"));
+    assert_eq!("ok inside", child.cat("/self/inside"));
+    assert_eq!("ok inside", child.cat("/up/inside"));
+    assert_eq!("err NotFound", child.cat("/absolute"));
+    assert_eq!("err NotFound", child.cat("/relative"));
+    assert_eq!(
+        "err NotFound",
+        child.cat(format!("/up{}", outside.display()).as_str())
+    );
+    child.do_exit(0);
+    assert!(child.wait().unwrap().success());
+
+    std::fs::remove_dir_all(dir.as_path()).unwrap();
+    println!("test_namespace_symlinks() PASS");
+}
+
+// tar stores symlinks as links, so a loop or a link to a parent directory
+// is archived as is instead of being followed.
+#[allow(deprecated)] // std::fs::soft_link is platform-independent.
+fn test_tar_symlinks() {
+    let mut dir = std::env::temp_dir();
+    dir.push("tar_symlinks");
+    let _ = std::fs::remove_dir_all(dir.as_path());
+
+    let src = dir.join("src");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("sub/file"), "file").unwrap();
+    std::fs::soft_link("b", src.join("a")).unwrap();
+    std::fs::soft_link("a", src.join("b")).unwrap();
+    std::fs::soft_link("..", src.join("sub/up")).unwrap();
+
+    let archive = dir.join("archive.tar");
+    let out = dir.join("out");
+    let tar = |args: &[&str]| {
+        std::process::Command::new("/sys/sysbox")
+            .arg("tar")
+            .args(args)
+            .status()
+            .unwrap()
+            .success()
+    };
+    assert!(tar(&[
+        "-cf",
+        archive.to_str().unwrap(),
+        "-C",
+        src.to_str().unwrap(),
+        "."
+    ]));
+    std::fs::create_dir_all(out.as_path()).unwrap();
+    assert!(tar(&[
+        "-xf",
+        archive.to_str().unwrap(),
+        "-C",
+        out.to_str().unwrap()
+    ]));
+
+    let link = |path: &str| std::fs::read_link(out.join(path)).unwrap();
+    assert_eq!("b", link("a").to_str().unwrap());
+    assert_eq!("a", link("b").to_str().unwrap());
+    assert_eq!("..", link("sub/up").to_str().unwrap());
+    let file = std::fs::read_to_string(out.join("sub/file")).unwrap();
+    assert_eq!("file", file);
+
+    std::fs::remove_dir_all(dir.as_path()).unwrap();
+    println!("test_tar_symlinks() PASS");
+}
+
+// A process can trace syscalls only of processes visible in its namespace,
+// and of processes running under another user ID only with CAP_LOG.
+fn test_syscall_trace_permissions() {
+    let mut target = subcommand::spawn();
+    let target_pid = target.pid();
+    let mut other_user =
+        subcommand::spawn_with_env(&[(moto_sys::caps::MOTURUS_UID_ENV_KEY, "1000")]);
+    let other_user_pid = other_user.pid();
+
+    let mut tracer = subcommand::spawn_with_env(&[(moto_sys::caps::MOTURUS_CAPS_ENV_KEY, "0x0")]);
+    assert_eq!("ok", tracer.trace(target_pid));
+    assert_eq!("err NotAllowed", tracer.trace(other_user_pid));
+
+    let root = std::env::temp_dir().join("syscall_trace_ns");
+    std::fs::create_dir_all(root.as_path()).unwrap();
+    let mut namespaced = subcommand::spawn_with_env(&[(
+        moto_sys::caps::MOTURUS_FS_ROOT_ENV_KEY,
+        root.to_str().unwrap(),
+    )]);
+    assert_eq!("err NotFound", namespaced.trace(target_pid));
+
+    for child in [&mut target, &mut other_user, &mut tracer, &mut namespaced] {
+        child.do_exit(0);
+        assert!(child.wait().unwrap().success());
+    }
+    std::fs::remove_dir_all(root.as_path()).unwrap();
+    println!("test_syscall_trace_permissions() PASS");
+}
+
 fn input_listener() {
     loop {
         let mut input = [0_u8; 16];
@@ -505,12 +951,15 @@ fn main() {
     std::thread::spawn(|| input_listener());
 
     test_cpus();
+    test_cpu_hotplug();
     tls::test_tls();
     test_caps();
     spawn_wait_kill::test_pid_kill();
     test_oom();
 
     tcp::test_tcp_loopback();
+    udp::test_udp_loopback();
+    poll::test_poll();
     spawn_wait_kill::test();
     mpmc::test_mpmc();
     mpmc::test_array_queue();
@@ -521,15 +970,23 @@ fn main() {
     // tcp::test_wget();
     // test_stdio();
     test_file_write();
+    test_namespace_symlinks();
+    test_syscall_trace_permissions();
+    test_tar_symlinks();
 
     test_lazy_memory_map();
     test_syscall();
+    test_clocks();
     stress_test_threads();
     test_thread();
     test_ipc();
     test_pipes();
     test_futex();
+    test_futex_timeout();
+    test_futex_requeue();
     test_rt_mutex();
+    test_rt_mutex_priority_inversion();
+    test_rt_condvar();
 
     println!("PASS");
 
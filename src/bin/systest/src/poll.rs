@@ -0,0 +1,128 @@
+// Readiness of nonblocking sockets, via moto_runtime::poll, which std doesn't expose.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use moto_runtime::net::{TcpListener, TcpStream, UdpSocket};
+use moto_runtime::poll::{Event, Poll, READABLE, WRITABLE};
+use moto_sys::ErrorCode;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Waits for an event for token; the others don't matter here.
+fn wait_for(poll: &Poll, token: u64) -> Event {
+    let mut events = Vec::new();
+    let deadline = std::time::Instant::now() + TIMEOUT;
+    loop {
+        let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+        assert_ne!(0, poll.wait(&mut events, 8, Some(timeout)).unwrap());
+        if let Some(event) = events.iter().find(|event| event.token == token) {
+            return *event;
+        }
+    }
+}
+
+fn tcp_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.socket_addr().unwrap();
+    let server = std::thread::spawn(move || listener.accept().unwrap().0);
+    let client = TcpStream::connect(&addr).unwrap();
+    (client, server.join().unwrap())
+}
+
+fn test_tcp_readiness() {
+    let poll = Poll::new().unwrap();
+    let (client, server) = tcp_pair();
+    client.set_nonblocking(true).unwrap();
+    client.register(&poll, 1, READABLE | WRITABLE).unwrap();
+
+    let event = wait_for(&poll, 1);
+    assert!(event.is_writable());
+    assert!(!event.is_readable());
+    let mut buf = [0_u8; 16];
+    assert_eq!(client.read(&mut buf), Err(ErrorCode::NotReady));
+
+    server.write(b"hello").unwrap();
+    client.reregister(&poll, 1, READABLE).unwrap();
+    assert!(wait_for(&poll, 1).is_readable());
+    assert_eq!(5, client.read(&mut buf).unwrap());
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(client.read(&mut buf), Err(ErrorCode::NotReady));
+
+    server.shutdown(false, true).unwrap();
+    let event = wait_for(&poll, 1);
+    assert!(event.is_readable() && event.is_read_closed());
+    assert_eq!(0, client.read(&mut buf).unwrap());
+}
+
+// Writes block once the reader falls behind, and become possible again when
+// sys-io frees the socket's IO pages.
+fn test_tcp_blocked_writes() {
+    const TOTAL: usize = 1 << 20;
+
+    let poll = Poll::new().unwrap();
+    let (client, server) = tcp_pair();
+    client.set_nonblocking(true).unwrap();
+    client.register(&poll, 1, WRITABLE).unwrap();
+
+    let reader = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        let mut buf = [0_u8; 4096];
+        let mut total = 0;
+        loop {
+            match server.read(&mut buf).unwrap() {
+                0 => return total,
+                sz => total += sz,
+            }
+        }
+    });
+
+    let chunk = [7_u8; 4096];
+    let mut written = 0;
+    let mut blocked = 0;
+    while written < TOTAL {
+        match client.write(&chunk[..(TOTAL - written).min(chunk.len())]) {
+            Ok(sz) => written += sz,
+            Err(ErrorCode::NotReady) => {
+                blocked += 1;
+                assert!(wait_for(&poll, 1).is_writable());
+            }
+            Err(err) => panic!("write failed: {err:?}"),
+        }
+    }
+    assert_ne!(0, blocked);
+
+    client.set_nonblocking(false).unwrap();
+    client.shutdown(false, true).unwrap();
+    assert_eq!(TOTAL, reader.join().unwrap());
+}
+
+fn test_udp_readiness() {
+    let poll = Poll::new().unwrap();
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let a = UdpSocket::bind(&addr).unwrap();
+    let b = UdpSocket::bind(&addr).unwrap();
+    b.set_nonblocking(true).unwrap();
+    b.register(&poll, 2, READABLE).unwrap();
+
+    let mut buf = [0_u8; 16];
+    assert_eq!(b.recv_from(&mut buf), Err(ErrorCode::NotReady));
+    a.send_to(&[1, 2, 3], &b.socket_addr().unwrap()).unwrap();
+    assert!(wait_for(&poll, 2).is_readable());
+    assert_eq!(
+        (3, a.socket_addr().unwrap()),
+        b.recv_from(&mut buf).unwrap()
+    );
+    assert_eq!(b.recv_from(&mut buf), Err(ErrorCode::NotReady));
+}
+
+pub fn test_poll() {
+    test_tcp_readiness();
+    test_tcp_blocked_writes();
+    test_udp_readiness();
+
+    // Wrap the output in sleeps to avoid debug console output mangling.
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    println!("test_poll() PASS");
+    std::thread::sleep(std::time::Duration::from_millis(10));
+}
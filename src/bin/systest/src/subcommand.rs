@@ -7,11 +7,17 @@ use std::time::Duration;
 pub struct Subcommand {
     inst: std::process::Child,
     stdin: std::process::ChildStdin,
+    stdout: std::io::BufReader<std::process::ChildStdout>,
 }
 
 pub fn spawn() -> Subcommand {
+    spawn_with_env(&[])
+}
+
+pub fn spawn_with_env(env: &[(&str, &str)]) -> Subcommand {
     let mut inst = std::process::Command::new(std::env::args().next().unwrap())
         .arg("subcommand")
+        .envs(env.iter().copied())
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -19,7 +25,12 @@ pub fn spawn() -> Subcommand {
         .unwrap();
 
     let stdin = inst.stdin.take().unwrap();
-    Subcommand { inst, stdin }
+    let stdout = std::io::BufReader::new(inst.stdout.take().unwrap());
+    Subcommand {
+        inst,
+        stdin,
+        stdout,
+    }
 }
 
 impl Subcommand {
@@ -57,6 +68,44 @@ impl Subcommand {
         self.inst.kill().unwrap()
     }
 
+    // Reads the file at @path in the child: "ok <contents>" or "err <kind>".
+    pub fn cat(&mut self, path: &str) -> String {
+        use std::io::{BufRead, Write};
+        self.stdin
+            .write(format!("cat {}\n", path).as_bytes())
+            .unwrap();
+        self.stdin.flush().unwrap();
+
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        line.trim_end().to_owned()
+    }
+
+    // The PID of the child, as the child sees it.
+    pub fn pid(&mut self) -> u64 {
+        use std::io::{BufRead, Write};
+        self.stdin.write(b"pid\n").unwrap();
+        self.stdin.flush().unwrap();
+
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        line.trim_end().parse::<u64>().unwrap()
+    }
+
+    // Starts and stops tracing syscalls of process @pid in the child:
+    // "ok" or "err <ErrorCode>".
+    pub fn trace(&mut self, pid: u64) -> String {
+        use std::io::{BufRead, Write};
+        self.stdin
+            .write(format!("trace {}\n", pid).as_bytes())
+            .unwrap();
+        self.stdin.flush().unwrap();
+
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        line.trim_end().to_owned()
+    }
+
     pub fn start_xor_service(&mut self) {
         use std::io::Write;
         self.stdin
@@ -107,6 +156,35 @@ fn do_command(cmd: String) {
             std::process::exit(code)
         }
         "xor_service" => crate::xor_server::start(),
+        "cat" => {
+            use std::io::Write;
+            assert_eq!(2, words.len());
+            match std::fs::read_to_string(words[1]) {
+                Ok(contents) => println!("ok {}", contents.trim_end()),
+                Err(err) => println!("err {:?}", err.kind()),
+            }
+            std::io::stdout().flush().unwrap();
+        }
+        "pid" => {
+            use std::io::Write;
+            assert_eq!(1, words.len());
+            let pid = moto_sys::SysObj::get_pid(moto_sys::SysHandle::SELF).unwrap();
+            println!("{}", pid);
+            std::io::stdout().flush().unwrap();
+        }
+        "trace" => {
+            use std::io::Write;
+            assert_eq!(2, words.len());
+            let pid = words[1].parse::<u64>().unwrap();
+            match moto_sys::SysRay::syscall_trace_attach(pid) {
+                Ok(handle) => {
+                    moto_sys::SysObj::put(handle).unwrap();
+                    println!("ok");
+                }
+                Err(err) => println!("err {:?}", err),
+            }
+            std::io::stdout().flush().unwrap();
+        }
         _ => panic!("unknown command: {:?}", words),
     }
 }
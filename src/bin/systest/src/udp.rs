@@ -0,0 +1,133 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+fn bind() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_millis(1000)))
+        .unwrap();
+    socket
+}
+
+fn test_send_recv() {
+    let a = bind();
+    let b = bind();
+    let a_addr = a.local_addr().unwrap();
+    let b_addr = b.local_addr().unwrap();
+    assert_ne!(a_addr.port(), 0);
+    assert_ne!(a_addr, b_addr);
+
+    let mut buf = [0_u8; 64];
+    for len in [0, 1, 17, 64] {
+        let tx: Vec<u8> = (0..len).map(|idx| idx as u8).collect();
+        assert_eq!(len, a.send_to(&tx, b_addr).unwrap());
+        let (sz, from) = b.recv_from(&mut buf).unwrap();
+        assert_eq!(from, a_addr);
+        assert_eq!(&buf[..sz], tx.as_slice());
+    }
+
+    // Datagrams don't merge, and what doesn't fit is dropped.
+    a.send_to(&[1, 2, 3], b_addr).unwrap();
+    a.send_to(&[4, 5], b_addr).unwrap();
+    let mut small = [0_u8; 2];
+    assert_eq!(2, b.peek_from(&mut small).unwrap().0);
+    assert_eq!(2, b.recv_from(&mut small).unwrap().0);
+    assert_eq!(small, [1, 2]);
+    assert_eq!(2, b.recv_from(&mut buf).unwrap().0);
+    assert_eq!(&buf[..2], &[4, 5]);
+
+    // Nothing more: the read times out.
+    b.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    assert!(b.recv_from(&mut buf).is_err());
+}
+
+fn test_connect() {
+    let a = bind();
+    let b = bind();
+    let c = bind();
+    let a_addr = a.local_addr().unwrap();
+    let b_addr = b.local_addr().unwrap();
+
+    assert!(a.peer_addr().is_err());
+    assert!(a.send(&[1]).is_err());
+    a.connect(b_addr).unwrap();
+    assert_eq!(a.peer_addr().unwrap(), b_addr);
+
+    // A connected socket sends to its peer, and only receives from it.
+    assert_eq!(3, a.send(&[1, 2, 3]).unwrap());
+    let mut buf = [0_u8; 8];
+    assert_eq!(b.recv_from(&mut buf).unwrap(), (3, a_addr));
+    c.send_to(&[9], a_addr).unwrap();
+    b.send_to(&[7, 7], a_addr).unwrap();
+    assert_eq!(2, a.recv(&mut buf).unwrap());
+    assert_eq!(&buf[..2], &[7, 7]);
+}
+
+// Sends fail in sys-io after send_to() returns; the error is reported
+// by take_error(), or by the next send.
+fn wait_for_send_error(socket: &UdpSocket) -> std::io::Error {
+    for _ in 0..100 {
+        if let Some(err) = socket.take_error().unwrap() {
+            return err;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("a failed UDP send was not reported");
+}
+
+fn test_send_errors() {
+    let a = bind();
+    let b = bind();
+    let b_addr = b.local_addr().unwrap();
+    let mut buf = [0_u8; 8];
+
+    // Rejected right away.
+    assert!(a.send_to(&[0_u8; 65536], b_addr).is_err());
+
+    // No port, no address: std may reject these before sys-io does.
+    for addr in ["127.0.0.1:0", "0.0.0.0:1234"] {
+        if a.send_to(&[1], addr).is_ok() {
+            let _ = wait_for_send_error(&a);
+        }
+    }
+    assert!(a.take_error().unwrap().is_none());
+
+    // A socket bound to the loopback address can't send elsewhere: there is
+    // either no route, or the route is not on loopback.
+    let elsewhere: SocketAddr = "203.0.113.1:9".parse().unwrap();
+    a.send_to(&[1], elsewhere).unwrap();
+    let _ = wait_for_send_error(&a);
+
+    // The error fails the next send, and only it.
+    a.send_to(&[1], elsewhere).unwrap();
+    let mut reported = false;
+    for _ in 0..100 {
+        if a.send_to(&[2], b_addr).is_err() {
+            reported = true;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(reported);
+    assert!(a.take_error().unwrap().is_none());
+    a.send_to(&[3], b_addr).unwrap();
+    loop {
+        let (sz, _) = b.recv_from(&mut buf).unwrap();
+        assert_eq!(sz, 1);
+        if buf[0] == 3 {
+            break;
+        }
+        assert_eq!(buf[0], 2); // Sent before the error arrived.
+    }
+}
+
+pub fn test_udp_loopback() {
+    test_send_recv();
+    test_connect();
+    test_send_errors();
+
+    // Wrap the output in sleeps to avoid debug console output mangling.
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    println!("test_udp() PASS");
+    std::thread::sleep(std::time::Duration::from_millis(10));
+}
@@ -30,8 +30,9 @@ static KERNEL_ENTRY_POINT: AtomicU64 = AtomicU64::new(0);
 struct KernelBootupInfo {
     pvh_addr: u64, // *const PvhStartInfo
     start_tsc: u64,
-    max_ram_offset: u64, // Max in use memory offset above 34M phys
+    max_ram_offset: u64, // Max in use memory offset above the kernel start.
     num_cpus: u32,
+    kernel_slide: u64, // The kernel is loaded at 34M phys + kernel_slide.
 }
 
 pub fn load_kernel_bsp(pvh: &'static crate::pvh::PvhStartInfo, num_cpus: u32, start_tsc: u64) -> ! {
@@ -61,12 +62,12 @@ pub fn load_kernel_bsp(pvh: &'static crate::pvh::PvhStartInfo, num_cpus: u32, st
     #[cfg(debug_assertions)]
     crate::raw_log!("will load kernel\n");
 
-    let (entry_point_raw, max_ram_offset) = load_kernel(kernel_bytes);
+    let (entry_point_raw, max_ram_offset, kernel_slide) = load_kernel(pvh, kernel_bytes);
 
     #[cfg(debug_assertions)]
     crate::raw_log!("kernel loaded\n");
 
-    let entry_point = entry_point_raw + crate::mm::kernel_offset();
+    let entry_point = entry_point_raw + crate::mm::kernel_offset() + kernel_slide;
     KERNEL_ENTRY_POINT.store(entry_point, Ordering::Release);
 
     let bootup_info = alloc::boxed::Box::new(KernelBootupInfo {
@@ -74,6 +75,7 @@ pub fn load_kernel_bsp(pvh: &'static crate::pvh::PvhStartInfo, num_cpus: u32, st
         start_tsc,
         max_ram_offset,
         num_cpus,
+        kernel_slide,
     });
 
     let bootup_info_addr = alloc::boxed::Box::leak(bootup_info) as *mut _ as usize as u64;
@@ -111,8 +113,8 @@ use core::sync::atomic::{AtomicU64, Ordering};
 
 use elfloader::*;
 
-// Returns the entry point and the max ram offset.
-fn load_kernel(bytes: &'static [u8]) -> (u64, u64) {
+// Returns the entry point, the max ram offset, and the slide.
+fn load_kernel(pvh: &'static crate::pvh::PvhStartInfo, bytes: &'static [u8]) -> (u64, u64, u64) {
     // crate::raw_log!("parsing kernel: {} bytes\n", bytes.len());
     let elf_binary = ElfBinary::new(bytes).expect("ELF parsing failed.");
 
@@ -125,8 +127,10 @@ fn load_kernel(bytes: &'static [u8]) -> (u64, u64) {
     }
 
     let mut kernel_loader = KernelLoader {
+        pvh,
         relocated: false,
         max_offset: 0,
+        slide: 0,
     };
 
     // crate::raw_log!("loading kernel\n");
@@ -134,23 +138,80 @@ fn load_kernel(bytes: &'static [u8]) -> (u64, u64) {
         .load(&mut kernel_loader)
         .expect("ELF loading failed.");
 
-    (elf_binary.entry_point(), kernel_loader.max_offset)
+    (
+        elf_binary.entry_point(),
+        kernel_loader.max_offset,
+        kernel_loader.slide,
+    )
+}
+
+// KASLR: the kernel is moved up from KERNEL_PHYS_START (both physically and
+// virtually, as the kernel lives in the direct map) by a random multiple of 2M.
+const KERNEL_SLIDE_ALIGN: u64 = 1 << 21;
+const KERNEL_SLIDE_SLOTS: u64 = 64; // The max slide is 126M.
+
+// The kernel puts its bootup heap right after its image; see
+// mm::init_mm_bsp_stage1() in the kernel.
+const KERNEL_BOOTUP_HEAP_MAX: u64 = 2 * KERNEL_SLIDE_ALIGN;
+
+// Returns a random slide for a kernel image of @size bytes such that
+// the image (and the bootup heap after it) are in available RAM and
+// do not overlap with initrd. Falls back to zero (no slide).
+fn choose_kernel_slide(pvh: &crate::pvh::PvhStartInfo, size: u64) -> u64 {
+    let initrd_start = pvh.initrd().0 as u64;
+
+    let fits = |slide: u64| -> bool {
+        let start = crate::mm::KERNEL_PHYS_START as u64 + slide;
+        let end = start + size + KERNEL_BOOTUP_HEAP_MAX;
+
+        // If initrd is above the kernel, it must stay above (see the kernel's
+        // init_mm_bsp_stage1()).
+        if initrd_start >= crate::mm::KERNEL_PHYS_START as u64 && initrd_start < end {
+            return false;
+        }
+
+        pvh.mem_map().iter().any(|entry| {
+            entry.entry_type == 1 /* available */
+                && entry.addr <= start
+                && end <= entry.addr + entry.size
+        })
+    };
+
+    for _ in 0..8 {
+        let slide = (crate::util::random_u64() % KERNEL_SLIDE_SLOTS) * KERNEL_SLIDE_ALIGN;
+        if fits(slide) {
+            return slide;
+        }
+    }
+
+    0
 }
 
 struct KernelLoader {
+    pvh: &'static crate::pvh::PvhStartInfo,
     relocated: bool,
     max_offset: u64,
+    slide: u64,
+}
+
+impl KernelLoader {
+    fn offset(&self) -> u64 {
+        crate::mm::kernel_offset() + self.slide
+    }
 }
 
 impl ElfLoader for KernelLoader {
     fn allocate(&mut self, load_headers: LoadableHeaders) -> Result<(), ElfLoaderErr> {
-        // We load the kernel at fixed physical/virtual addressess, as KASLR
-        // is mostly security theader: https://grsecurity.net/kaslr_an_exercise_in_cargo_cult_security
         for header in load_headers {
             self.max_offset = self
                 .max_offset
                 .max(header.virtual_addr() + header.mem_size());
         }
+        self.slide = choose_kernel_slide(self.pvh, self.max_offset);
+
+        #[cfg(debug_assertions)]
+        crate::raw_log!("kernel slide: 0x{:x}\n", self.slide);
+
         Ok(())
     }
 
@@ -158,7 +219,7 @@ impl ElfLoader for KernelLoader {
         unsafe {
             core::ptr::copy_nonoverlapping(
                 region.as_ptr(),
-                (crate::mm::kernel_offset() + base) as usize as *mut u8,
+                (self.offset() + base) as usize as *mut u8,
                 region.len(),
             );
         }
@@ -169,7 +230,7 @@ impl ElfLoader for KernelLoader {
         use elfloader::arch::x86_64::RelocationTypes::*;
         use RelocationType::x86_64;
 
-        let addr: u64 = crate::mm::kernel_offset() + entry.offset;
+        let addr: u64 = self.offset() + entry.offset;
 
         match entry.rtype {
             x86_64(R_AMD64_RELATIVE) => {
@@ -180,7 +241,7 @@ impl ElfLoader for KernelLoader {
 
                 // Need to write (addend + base) into addr.
                 unsafe {
-                    *(addr as usize as *mut u64) = crate::mm::kernel_offset() + addend;
+                    *(addr as usize as *mut u64) = self.offset() + addend;
                 }
 
                 self.relocated = true;
@@ -53,9 +53,7 @@ pub const PAGE_SIZE_SMALL: u64 = 4096;
 // The full physical memory is mapped to [DIRECT_MAP_OFFSET, *)
 pub const PAGING_DIRECT_MAP_OFFSET: u64 = 1_u64 << 46;
 
-// Where to load the kernel.
-// Note that we don't do kaslr, as it is mostly pointless:
-// https://grsecurity.net/kaslr_an_exercise_in_cargo_cult_security
+// Where to load the kernel, if not randomized (see loader::choose_kernel_slide()).
 pub const fn kernel_offset() -> u64 {
     PAGING_DIRECT_MAP_OFFSET + (KERNEL_PHYS_START as u64)
 }
@@ -69,6 +69,31 @@ pub fn vmm_exit() -> ! {
     loop {}
 }
 
+// Returns a random number from RDRAND, if available, or the TSC otherwise.
+pub fn random_u64() -> u64 {
+    const CPUID_ECX_RDRAND: u32 = 1 << 30;
+    if crate::cpuid::Leaf::new(1).ecx & CPUID_ECX_RDRAND != 0 {
+        for _ in 0..10 {
+            let mut val: u64;
+            let ok: u8;
+            unsafe {
+                core::arch::asm!(
+                    "rdrand {}",
+                    "setc {}",
+                    out(reg) val,
+                    out(reg_byte) ok,
+                    options(nomem, nostack)
+                );
+            }
+            if ok == 1 {
+                return val;
+            }
+        }
+    }
+
+    rdtsc()
+}
+
 pub fn rdtsc() -> u64 {
     let mut eax: u32;
     let mut edx: u32;
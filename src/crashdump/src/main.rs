@@ -0,0 +1,247 @@
+// Extracts and prints Motor OS kernel crash dumps (see moto_sys::crash)
+// from the file connected to the crash console port, or from captured serial
+// console output, e.g.:
+//
+//     ./run-qemu.sh | tee console.log
+//     cargo run -- console.log [--kernel path/to/kernel]
+//
+// If the path to the (unstripped) kernel binary is given, backtraces are
+// printed as addr2line commands.
+
+use moto_sys::crash::*;
+use moto_sys::stats::KLogEntryV1;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("usage:\n\tcrashdump <console log> [--kernel <kernel binary>]\n");
+    std::process::exit(exit_code);
+}
+
+// Returns the bytes of each crash dump found in the log.
+fn extract(log: &str) -> Vec<Vec<u8>> {
+    let mut dumps = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for line in log.lines() {
+        // Serial output has CRLF line endings, and may be interleaved with
+        // output from other sources, so be lenient.
+        let line = line.trim();
+        if line.ends_with(CRASH_BEGIN_MARKER) {
+            current = Some(Vec::new());
+            continue;
+        }
+        let bytes = match current.as_mut() {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+        if line.starts_with(CRASH_END_MARKER) {
+            dumps.push(current.take().unwrap());
+            continue;
+        }
+        if line.len() % 2 != 0 || line.len() > CRASH_BYTES_PER_LINE * 2 {
+            eprintln!("crashdump: skipping a malformed line: '{line}'");
+            continue;
+        }
+        for idx in (0..line.len()).step_by(2) {
+            match u8::from_str_radix(&line[idx..(idx + 2)], 16) {
+                Ok(byte) => bytes.push(byte),
+                Err(_) => {
+                    eprintln!("crashdump: skipping a malformed line: '{line}'");
+                    break;
+                }
+            }
+        }
+    }
+
+    if current.is_some() {
+        eprintln!("crashdump: the last crash dump is truncated.");
+    }
+    dumps
+}
+
+fn read_struct<T: Sized>(bytes: &[u8], offset: &mut usize) -> Result<T, String> {
+    let size = std::mem::size_of::<T>();
+    if *offset + size > bytes.len() {
+        return Err(format!(
+            "the crash dump is too short: {} bytes",
+            bytes.len()
+        ));
+    }
+    let val = unsafe { std::ptr::read_unaligned(bytes[*offset..].as_ptr() as *const T) };
+    *offset += size;
+    Ok(val)
+}
+
+fn cpu_state(state: u32) -> &'static str {
+    match state {
+        CRASH_CPU_PANIC => "panicked",
+        CRASH_CPU_STOPPED => "stopped",
+        _ => "did not respond",
+    }
+}
+
+fn log_level(level: u8) -> &'static str {
+    match level {
+        1 => "ERROR",
+        2 => "WARN",
+        3 => "INFO",
+        4 => "DEBUG",
+        5 => "TRACE",
+        _ => "?",
+    }
+}
+
+fn print_dump(bytes: &[u8], kernel: Option<&str>) -> Result<(), String> {
+    if bytes.len() < 8 {
+        return Err("the crash dump is too short".to_owned());
+    }
+    let (data, checksum) = bytes.split_at(bytes.len() - 8);
+    let checksum = u64::from_le_bytes(checksum.try_into().unwrap());
+    if crash_checksum(CRASH_CHECKSUM_INIT, data) != checksum {
+        return Err("checksum mismatch".to_owned());
+    }
+
+    let mut offset = 0;
+    let header: CrashHeaderV1 = read_struct(data, &mut offset)?;
+    if header.magic != CRASH_MAGIC {
+        return Err(format!("bad magic: 0x{:x}", header.magic));
+    }
+    if header.version != CRASH_VERSION {
+        return Err(format!("unsupported version: {}", header.version));
+    }
+
+    let msg_len = (header.msg_len as usize).min(CRASH_MAX_MSG_LEN);
+    let uptime = std::time::Duration::from_nanos(header.uptime_nanos);
+    println!("Kernel panic on CPU {}:", header.panic_cpu);
+    println!(
+        "    {}",
+        String::from_utf8_lossy(&header.msg_bytes[..msg_len])
+    );
+    println!(
+        "uptime: {:.3} sec; CPUs: {}",
+        uptime.as_secs_f64(),
+        header.num_cpus
+    );
+
+    for _ in 0..header.num_cpus {
+        let cpu: CrashCpuV1 = read_struct(data, &mut offset)?;
+        println!("\nCPU {} ({}):", cpu.cpu, cpu_state(cpu.state));
+        if cpu.state == CRASH_CPU_UNKNOWN {
+            continue;
+        }
+
+        let r = &cpu.regs;
+        println!(
+            "    rip: 0x{:016x}  rsp: 0x{:016x}  rflags: 0x{:x}  cs: 0x{:x}  ss: 0x{:x}",
+            r.rip, r.rsp, r.rflags, r.cs, r.ss
+        );
+        println!(
+            "    rax: 0x{:016x}  rbx: 0x{:016x}  rcx: 0x{:016x}  rdx: 0x{:016x}",
+            r.rax, r.rbx, r.rcx, r.rdx
+        );
+        println!(
+            "    rsi: 0x{:016x}  rdi: 0x{:016x}  rbp: 0x{:016x}  r8:  0x{:016x}",
+            r.rsi, r.rdi, r.rbp, r.r8
+        );
+        println!(
+            "    r9:  0x{:016x}  r10: 0x{:016x}  r11: 0x{:016x}  r12: 0x{:016x}",
+            r.r9, r.r10, r.r11, r.r12
+        );
+        println!(
+            "    r13: 0x{:016x}  r14: 0x{:016x}  r15: 0x{:016x}",
+            r.r13, r.r14, r.r15
+        );
+        println!("    cr2: 0x{:016x}  cr3: 0x{:016x}", r.cr2, r.cr3);
+
+        let backtrace: Vec<String> = cpu
+            .backtrace
+            .iter()
+            .take_while(|addr| **addr != 0)
+            .map(|addr| format!("0x{addr:x}"))
+            .collect();
+        if backtrace.is_empty() {
+            continue;
+        }
+        match kernel {
+            Some(kernel) => println!(
+                "    backtrace: addr2line -e {} {}",
+                kernel,
+                backtrace.join(" ")
+            ),
+            None => println!("    backtrace: {}", backtrace.join(" ")),
+        }
+    }
+
+    if header.num_log_entries > 0 {
+        println!("\nRecent kernel log:");
+    }
+    for _ in 0..header.num_log_entries {
+        let entry: KLogEntryV1 = read_struct(data, &mut offset)?;
+        let subsystem_len = (entry.subsystem_len as usize).min(entry.subsystem_bytes.len());
+        let msg_len = (entry.msg_len as usize).min(entry.msg_bytes.len());
+        let ago = if header.tsc_in_sec > 0 && entry.timestamp <= header.timestamp {
+            (header.timestamp - entry.timestamp) as f64 / header.tsc_in_sec as f64
+        } else {
+            0.0
+        };
+        println!(
+            "    -{:.3}s {:2}: {:5} {}: {}",
+            ago,
+            entry.cpu,
+            if entry.user != 0 {
+                "USER"
+            } else {
+                log_level(entry.level)
+            },
+            String::from_utf8_lossy(&entry.subsystem_bytes[..subsystem_len]),
+            String::from_utf8_lossy(&entry.msg_bytes[..msg_len])
+        );
+    }
+
+    if offset != data.len() {
+        return Err(format!("{} unexpected trailing bytes", data.len() - offset));
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (log_path, kernel) = match args.len() {
+        2 => (args[1].as_str(), None),
+        4 if args[2] == "--kernel" => (args[1].as_str(), Some(args[3].as_str())),
+        _ => print_usage_and_exit(1),
+    };
+
+    let log = match std::fs::read(log_path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(err) => {
+            eprintln!("crashdump: failed to read '{log_path}': {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let dumps = extract(&log);
+    if dumps.is_empty() {
+        eprintln!("crashdump: no crash dumps found in '{log_path}'.");
+        std::process::exit(1);
+    }
+
+    let mut failed = false;
+    for (idx, dump) in dumps.iter().enumerate() {
+        if dumps.len() > 1 {
+            println!(
+                "======== crash dump {} of {} ========\n",
+                idx + 1,
+                dumps.len()
+            );
+        }
+        if let Err(err) = print_dump(dump, kernel) {
+            eprintln!("crashdump: bad crash dump: {err}");
+            failed = true;
+        }
+        println!();
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -20,10 +20,12 @@ use std::io::{self, Seek, SeekFrom};
 const SECTOR_SIZE: u32 = 512;
 
 // For the "full" image.
-static BIN_FULL: [&'static str; 10] = [
+static BIN_FULL: [&str; 12] = [
     "bin/httpd",
     "bin/kibim",
+    "bin/mcp",
     "bin/rush",
+    "bin/sshd",
     "sys/mdbg",
     "sys/rnetbench",
     "sys/sys-init",
@@ -34,7 +36,7 @@ static BIN_FULL: [&'static str; 10] = [
 ];
 
 // For the "web" image.
-static BIN_WEB: [&'static str; 4] = ["bin/httpd", "sys/sys-init", "sys/sys-log", "sys/sys-tty"];
+static BIN_WEB: [&str; 4] = ["bin/httpd", "sys/sys-init", "sys/sys-log", "sys/sys-tty"];
 
 fn create_srfs_partition(result_path: &Path, files: &BTreeMap<PathBuf, String>) {
     const MB: usize = 1024 * 1024;
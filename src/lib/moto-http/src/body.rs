@@ -0,0 +1,242 @@
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result};
+use std::net::TcpStream;
+
+// Chunk size lines and trailers longer than this are rejected.
+const MAX_LINE_LEN: usize = 4096;
+
+pub(crate) enum Framing {
+    Empty,
+    Length(u64), // Bytes left.
+    Chunked,
+    UntilClose,
+}
+
+enum State {
+    Length(u64),
+    // Expecting a chunk size line; unless it is the first one, it is preceded
+    // by the CRLF that ends the previous chunk.
+    ChunkSize { first: bool },
+    Chunk(u64), // Bytes left in the current chunk.
+    UntilClose,
+    Done,
+}
+
+/// The body of a response, read as it arrives from the server.
+/// Content-Length, chunked and close-delimited bodies are supported.
+pub struct Body {
+    reader: BufReader<TcpStream>,
+    state: State,
+}
+
+fn truncated() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "truncated HTTP response body")
+}
+
+fn bad_chunk() -> Error {
+    Error::new(ErrorKind::InvalidData, "bad HTTP chunk")
+}
+
+// Reads a CRLF (or LF) terminated line, without the terminator.
+pub(crate) fn read_line(reader: &mut BufReader<TcpStream>, limit: usize) -> Result<String> {
+    let mut line = Vec::new();
+    let read = reader
+        .by_ref()
+        .take(limit as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "the HTTP server closed the connection",
+        ));
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(Error::new(ErrorKind::InvalidData, "HTTP line too long"));
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| Error::new(ErrorKind::InvalidData, "non-UTF-8 HTTP line"))
+}
+
+impl Body {
+    pub(crate) fn new(reader: BufReader<TcpStream>, framing: Framing) -> Self {
+        let state = match framing {
+            Framing::Empty | Framing::Length(0) => State::Done,
+            Framing::Length(len) => State::Length(len),
+            Framing::Chunked => State::ChunkSize { first: true },
+            Framing::UntilClose => State::UntilClose,
+        };
+        Self { reader, state }
+    }
+
+    /// Whether the whole body has been read.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    // Moves to the next chunk; returns false after the last one.
+    fn next_chunk(&mut self, first: bool) -> Result<bool> {
+        if !first && !read_line(&mut self.reader, MAX_LINE_LEN)?.is_empty() {
+            return Err(bad_chunk()); // Chunk data must be followed by CRLF.
+        }
+
+        let line = read_line(&mut self.reader, MAX_LINE_LEN)?;
+        let size = line.split(';').next().unwrap().trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| bad_chunk())?;
+        if size > 0 {
+            self.state = State::Chunk(size);
+            return Ok(true);
+        }
+
+        // Trailers are ignored, but limited like headers.
+        let mut trailers = 0;
+        while !read_line(&mut self.reader, MAX_LINE_LEN)?.is_empty() {
+            trailers += 1;
+            if trailers > crate::MAX_HEADERS {
+                return Err(Error::new(ErrorKind::InvalidData, "too many HTTP trailers"));
+            }
+        }
+        self.state = State::Done;
+        Ok(false)
+    }
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.state {
+                State::Done => return Ok(0),
+                State::UntilClose => {
+                    let read = self.reader.read(buf)?;
+                    if read == 0 {
+                        self.state = State::Done;
+                    }
+                    return Ok(read);
+                }
+                State::Length(left) => {
+                    let len = buf.len().min(left.min(usize::MAX as u64) as usize);
+                    let read = self.reader.read(&mut buf[..len])?;
+                    if read == 0 {
+                        return Err(truncated());
+                    }
+                    self.state = if left == read as u64 {
+                        State::Done
+                    } else {
+                        State::Length(left - read as u64)
+                    };
+                    return Ok(read);
+                }
+                State::ChunkSize { first } => {
+                    if !self.next_chunk(first)? {
+                        return Ok(0);
+                    }
+                }
+                State::Chunk(left) => {
+                    let len = buf.len().min(left.min(usize::MAX as u64) as usize);
+                    let read = self.reader.read(&mut buf[..len])?;
+                    if read == 0 {
+                        return Err(truncated());
+                    }
+                    self.state = if left == read as u64 {
+                        State::ChunkSize { first: false }
+                    } else {
+                        State::Chunk(left - read as u64)
+                    };
+                    return Ok(read);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    // A body that reads what the server side of a local connection sends.
+    fn serve(framing: Framing, sent: &'static [u8]) -> Body {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        std::thread::spawn(move || {
+            server.write_all(sent).unwrap();
+        });
+        Body::new(BufReader::new(client), framing)
+    }
+
+    fn read_all(mut body: Body) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        body.read_to_end(&mut bytes)?;
+        assert!(body.is_done());
+        Ok(bytes)
+    }
+
+    #[test]
+    fn content_length() {
+        let body = serve(Framing::Length(5), b"helloEXTRA");
+        assert_eq!(read_all(body).unwrap(), b"hello");
+
+        let body = serve(Framing::Length(10), b"short");
+        let err = read_all(body).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn empty() {
+        let body = serve(Framing::Empty, b"ignored");
+        assert!(body.is_done());
+        assert!(read_all(body).unwrap().is_empty());
+
+        let body = serve(Framing::Length(0), b"ignored");
+        assert!(read_all(body).unwrap().is_empty());
+    }
+
+    #[test]
+    fn until_close() {
+        let body = serve(Framing::UntilClose, b"all of it");
+        assert_eq!(read_all(body).unwrap(), b"all of it");
+    }
+
+    #[test]
+    fn chunked() {
+        let body = serve(
+            Framing::Chunked,
+            b"5\r\nhello\r\n7;ext=1\r\n, world\r\nA\nAAAAAAAAAA\n0\r\nX-Trailer: 1\r\n\r\nEXTRA",
+        );
+        assert_eq!(read_all(body).unwrap(), b"hello, worldAAAAAAAAAA");
+
+        let body = serve(Framing::Chunked, b"0\r\n\r\n");
+        assert!(read_all(body).unwrap().is_empty());
+    }
+
+    #[test]
+    fn chunked_errors() {
+        let err = read_all(serve(Framing::Chunked, b"zz\r\n")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Chunk data not followed by CRLF.
+        let err = read_all(serve(Framing::Chunked, b"2\r\nabc\r\n0\r\n\r\n")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = read_all(serve(Framing::Chunked, b"5\r\nab")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let err = read_all(serve(Framing::Chunked, b"5\r\nhello\r\n")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let mut sent = b"0\r\n".to_vec();
+        for _ in 0..=crate::MAX_HEADERS {
+            sent.extend_from_slice(b"X: y\r\n");
+        }
+        sent.extend_from_slice(b"\r\n");
+        let err = read_all(serve(Framing::Chunked, sent.leak())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,471 @@
+// A small HTTP/1.1 client for Motor OS tools (the package manager, metrics
+// pushers and the like).
+//
+// Requests are sent over a new connection each (Connection: close); response
+// bodies are streamed (Content-Length, chunked, or delimited by the server
+// closing the connection); redirects are followed. Client::send() blocks;
+// Client::send_async() runs the request on one of the client's worker threads
+// and returns a Pending future, like the file I/O in moto_sys_io::aio.
+//
+// Only http:// is supported for now.
+
+mod body;
+mod pending;
+mod url;
+
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use body::Body;
+pub use pending::Pending;
+pub use url::Url;
+
+use body::Framing;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_REDIRECTS: u32 = 8;
+const DEFAULT_MAX_WORKERS: usize = 4;
+
+// Limits on the response head (the status line and headers).
+const MAX_HEAD_LINE_LEN: usize = 8192;
+const MAX_HEADERS: usize = 128;
+
+const USER_AGENT: &str = concat!("moto-http/", env!("CARGO_PKG_VERSION"));
+
+/// An HTTP request. Headers the client manages (Host, Connection,
+/// Content-Length, Transfer-Encoding) are ignored.
+#[derive(Clone, Debug)]
+pub struct Request {
+    method: String,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    pub fn new(method: &str, url: &str) -> Result<Self> {
+        if method.is_empty() || !method.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("bad HTTP method '{}'", method),
+            ));
+        }
+
+        Ok(Self {
+            method: method.to_ascii_uppercase(),
+            url: Url::parse(url)?,
+            headers: Vec::new(),
+            body: Vec::new(),
+        })
+    }
+
+    pub fn get(url: &str) -> Result<Self> {
+        Self::new("GET", url)
+    }
+
+    pub fn post(url: &str, body: impl Into<Vec<u8>>) -> Result<Self> {
+        Ok(Self::new("POST", url)?.with_body(body))
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn is_managed_header(name: &str) -> bool {
+        ["host", "connection", "content-length", "transfer-encoding"]
+            .iter()
+            .any(|managed| name.eq_ignore_ascii_case(managed))
+    }
+
+    fn write_to(&self, stream: &mut TcpStream) -> Result<()> {
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+            self.method,
+            self.url.path(),
+            self.url.authority()
+        );
+        if !self
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("user-agent"))
+        {
+            head.push_str(&format!("User-Agent: {}\r\n", USER_AGENT));
+        }
+        for (name, value) in &self.headers {
+            if Self::is_managed_header(name) {
+                continue;
+            }
+            if name.contains([':', '\r', '\n']) || value.contains(['\r', '\n']) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("bad HTTP header '{}'", name),
+                ));
+            }
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !self.body.is_empty() || matches!(self.method.as_str(), "POST" | "PUT" | "PATCH") {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// A response: the status and headers, and the body to be read.
+pub struct Response {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    url: Url, // After redirects.
+    body: Body,
+    pool: Arc<pending::Pool>,
+}
+
+impl Response {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// The URL the response came from, which differs from the requested one
+    /// if the request was redirected.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// The value of the first header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The length of the body, if the server sent Content-Length.
+    pub fn content_length(&self) -> Option<u64> {
+        self.header("content-length")?.parse().ok()
+    }
+
+    /// Turns a non-2xx status into an error.
+    pub fn error_for_status(self) -> Result<Self> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(Error::other(format!(
+                "{}: HTTP {} {}",
+                self.url, self.status, self.reason
+            )))
+        }
+    }
+
+    pub fn body(&mut self) -> &mut Body {
+        &mut self.body
+    }
+
+    /// Reads the rest of the body.
+    pub fn bytes(mut self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.body.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads the rest of the body on a worker thread.
+    pub fn bytes_async(self) -> Pending<Vec<u8>> {
+        let pool = self.pool.clone();
+        pool.run(move || self.bytes())
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+impl std::fmt::Debug for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("reason", &self.reason)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+/// Sends requests. Cheap to clone: clones share the worker threads.
+#[derive(Clone)]
+pub struct Client {
+    timeout: Option<Duration>,
+    max_redirects: u32,
+    pool: Arc<pending::Pool>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            timeout: Some(DEFAULT_TIMEOUT),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            pool: pending::Pool::new(DEFAULT_MAX_WORKERS),
+        }
+    }
+
+    /// The timeout for connecting and for each read and write (None: wait forever).
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many redirects to follow; with zero, redirects are returned as is.
+    pub fn with_max_redirects(mut self, max_redirects: u32) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// How many async requests can be in flight; more are queued.
+    pub fn with_max_concurrency(mut self, max_workers: usize) -> Self {
+        self.pool = pending::Pool::new(max_workers);
+        self
+    }
+
+    pub fn get(&self, url: &str) -> Result<Response> {
+        self.send(Request::get(url)?)
+    }
+
+    /// Sends the request, following redirects, and returns once the response
+    /// headers have arrived.
+    pub fn send(&self, mut request: Request) -> Result<Response> {
+        let mut redirects = 0;
+        loop {
+            let response = self.send_once(&request)?;
+            let location = match response.status {
+                301 | 302 | 303 | 307 | 308 => response.header("location"),
+                _ => None,
+            };
+            let Some(location) = location else {
+                return Ok(response);
+            };
+            if self.max_redirects == 0 {
+                return Ok(response);
+            }
+            if redirects == self.max_redirects {
+                return Err(Error::other(format!(
+                    "{}: too many redirects (to {})",
+                    response.url, location
+                )));
+            }
+
+            let url = request.url.join(location)?;
+            if url.host() != request.url.host() || url.port() != request.url.port() {
+                // Don't leak credentials to another server.
+                request.headers.retain(|(name, _)| {
+                    !name.eq_ignore_ascii_case("authorization")
+                        && !name.eq_ignore_ascii_case("cookie")
+                });
+            }
+            if response.status == 303
+                || (matches!(response.status, 301 | 302) && request.method == "POST")
+            {
+                request.method = "GET".to_owned();
+                request.body.clear();
+                request
+                    .headers
+                    .retain(|(name, _)| !name.to_ascii_lowercase().starts_with("content-"));
+            }
+            request.url = url;
+            redirects += 1;
+        }
+    }
+
+    /// Like send(), but runs on a worker thread.
+    pub fn send_async(&self, request: Request) -> Pending<Response> {
+        let client = self.clone();
+        self.pool.run(move || client.send(request))
+    }
+
+    fn connect(&self, url: &Url) -> Result<TcpStream> {
+        let mut last_err = None;
+        for addr in url.socket_addr().to_socket_addrs()? {
+            let result = match self.timeout {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            match result {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("{}: no addresses for {}", url, url.host()),
+            )
+        }))
+    }
+
+    fn send_once(&self, request: &Request) -> Result<Response> {
+        let mut stream = self.connect(&request.url)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        let _ = stream.set_nodelay(true);
+        request.write_to(&mut stream)?;
+
+        let mut reader = BufReader::new(stream);
+        loop {
+            let head = read_head(&mut reader)?;
+            // Skip interim responses (e.g. 100 Continue).
+            if (100..200).contains(&head.status) && head.status != 101 {
+                continue;
+            }
+
+            let framing = framing(request, head.status, &head.headers)?;
+            return Ok(Response {
+                status: head.status,
+                reason: head.reason,
+                headers: head.headers,
+                url: request.url.clone(),
+                body: Body::new(reader, framing),
+                pool: self.pool.clone(),
+            });
+        }
+    }
+}
+
+fn bad_response(what: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("bad HTTP response: {}", what),
+    )
+}
+
+// The status line and the headers.
+struct Head {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+}
+
+fn read_head(reader: &mut BufReader<TcpStream>) -> Result<Head> {
+    let status_line = body::read_line(reader, MAX_HEAD_LINE_LEN)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap();
+    if !version.starts_with("HTTP/1.") {
+        return Err(bad_response(&status_line));
+    }
+    let status = parts
+        .next()
+        .and_then(|status| status.parse::<u16>().ok())
+        .filter(|status| (100..1000).contains(status))
+        .ok_or_else(|| bad_response(&status_line))?;
+    let reason = parts.next().unwrap_or("").to_owned();
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    loop {
+        let line = body::read_line(reader, MAX_HEAD_LINE_LEN)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            // An obsolete folded continuation line.
+            let Some((_, value)) = headers.last_mut() else {
+                return Err(bad_response(&line));
+            };
+            value.push(' ');
+            value.push_str(line.trim());
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(bad_response(&line));
+        };
+        if headers.len() == MAX_HEADERS {
+            return Err(bad_response("too many headers"));
+        }
+        headers.push((name.trim().to_owned(), value.trim().to_owned()));
+    }
+
+    Ok(Head {
+        status,
+        reason,
+        headers,
+    })
+}
+
+fn header_values<'a>(
+    headers: &'a [(String, String)],
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    headers
+        .iter()
+        .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+// How the end of the body is delimited (RFC 9112, section 6.3).
+fn framing(request: &Request, status: u16, headers: &[(String, String)]) -> Result<Framing> {
+    if request.method == "HEAD" || status == 204 || status == 304 || status < 200 {
+        return Ok(Framing::Empty);
+    }
+
+    if let Some(encoding) = header_values(headers, "transfer-encoding").last() {
+        let last = encoding.rsplit(',').next().unwrap().trim();
+        return if last.eq_ignore_ascii_case("chunked") {
+            Ok(Framing::Chunked)
+        } else if last.eq_ignore_ascii_case("identity") {
+            Ok(Framing::UntilClose)
+        } else {
+            Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported Transfer-Encoding '{}'", encoding),
+            ))
+        };
+    }
+
+    let mut lengths = header_values(headers, "content-length");
+    if let Some(length) = lengths.next() {
+        let length = length
+            .parse::<u64>()
+            .map_err(|_| bad_response("bad Content-Length"))?;
+        if lengths.any(|other| other.parse::<u64>().ok() != Some(length)) {
+            return Err(bad_response("conflicting Content-Length"));
+        }
+        return Ok(Framing::Length(length));
+    }
+
+    Ok(Framing::UntilClose)
+}
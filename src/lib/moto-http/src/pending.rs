@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{Error, Result};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+// Idle workers exit after this long.
+const WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Slot<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+struct Completion<T> {
+    slot: Mutex<Slot<T>>,
+    done: Condvar,
+}
+
+impl<T> Completion<T> {
+    fn complete(&self, result: Result<T>) {
+        let waker = {
+            let mut slot = self.slot.lock().unwrap();
+            slot.result = Some(result);
+            slot.waker.take()
+        };
+        self.done.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// An HTTP operation running in the background: block on it via wait(),
+/// or .await it from any executor (the worker wakes the task's waker when
+/// the operation completes).
+pub struct Pending<T> {
+    completion: Arc<Completion<T>>,
+}
+
+impl<T> Pending<T> {
+    pub fn is_done(&self) -> bool {
+        self.completion.slot.lock().unwrap().result.is_some()
+    }
+
+    pub fn wait(self) -> Result<T> {
+        let mut slot = self.completion.slot.lock().unwrap();
+        loop {
+            if let Some(result) = slot.result.take() {
+                return result;
+            }
+            slot = self.completion.done.wait(slot).unwrap();
+        }
+    }
+}
+
+impl<T> Future for Pending<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.completion.slot.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    workers: usize,
+    idle: usize,
+}
+
+// Worker threads that run pending operations. Operations do blocking socket
+// I/O (they are not built on the runtime's nonblocking sockets and Poll), so
+// each operation in flight ties up a worker, i.e. an OS thread, and at most
+// max_workers run at a time; the rest wait in the queue. Workers are started
+// on demand and exit when idle.
+pub(crate) struct Pool {
+    max_workers: usize,
+    state: Mutex<PoolState>,
+    have_jobs: Condvar,
+}
+
+impl Pool {
+    pub fn new(max_workers: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_workers: max_workers.max(1),
+            state: Mutex::new(PoolState {
+                jobs: VecDeque::new(),
+                workers: 0,
+                idle: 0,
+            }),
+            have_jobs: Condvar::new(),
+        })
+    }
+
+    pub fn run<T, F>(self: &Arc<Self>, op: F) -> Pending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let completion = Arc::new(Completion {
+            slot: Mutex::new(Slot {
+                result: None,
+                waker: None,
+            }),
+            done: Condvar::new(),
+        });
+
+        let job_completion = completion.clone();
+        let job: Job = Box::new(move || job_completion.complete(op()));
+
+        let mut state = self.state.lock().unwrap();
+        state.jobs.push_back(job);
+        if state.idle == 0 && state.workers < self.max_workers {
+            state.workers += 1;
+            let pool = self.clone();
+            let spawned = std::thread::Builder::new()
+                .name("moto-http".to_owned())
+                .spawn(move || pool.worker());
+            if let Err(err) = spawned {
+                state.workers -= 1;
+                // The queued job will run on an existing worker, if any.
+                if state.workers == 0 {
+                    let job = state.jobs.pop_back();
+                    drop(state);
+                    drop(job);
+                    completion.complete(Err(Error::other(format!(
+                        "moto-http: failed to start a worker: {}",
+                        err
+                    ))));
+                }
+            }
+        } else {
+            self.have_jobs.notify_one();
+        }
+
+        Pending { completion }
+    }
+
+    fn worker(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            state.idle += 1;
+            let (new_state, timeout) = self
+                .have_jobs
+                .wait_timeout(state, WORKER_IDLE_TIMEOUT)
+                .unwrap();
+            state = new_state;
+            state.idle -= 1;
+            if timeout.timed_out() && state.jobs.is_empty() {
+                state.workers -= 1;
+                return;
+            }
+        }
+    }
+}
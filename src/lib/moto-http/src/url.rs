@@ -0,0 +1,245 @@
+use std::io::{Error, ErrorKind, Result};
+
+/// An http:// URL: the only kind the client can fetch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url {
+    host: String, // IPv6 literals keep their brackets.
+    port: u16,
+    path: String, // With the query, if any; always starts with '/'.
+}
+
+fn bad_url(url: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("bad URL '{}'", url))
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self> {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return Err(bad_url(url));
+        };
+        if !scheme.eq_ignore_ascii_case("http") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported URL scheme '{}' (only http is)", scheme),
+            ));
+        }
+
+        // The fragment is never sent.
+        let rest = rest.split('#').next().unwrap();
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, "/"),
+        };
+        if authority.contains('@') {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "credentials in URLs are not supported",
+            ));
+        }
+
+        let (host, port) = match authority.rfind(':') {
+            // A colon inside brackets belongs to an IPv6 address.
+            Some(pos) if !authority[pos..].contains(']') => (
+                &authority[..pos],
+                authority[(pos + 1)..]
+                    .parse::<u16>()
+                    .map_err(|_| bad_url(url))?,
+            ),
+            _ => (authority, 80),
+        };
+        if host.is_empty() || (host.starts_with('[') != host.ends_with(']')) {
+            return Err(bad_url(url));
+        }
+
+        let path = if path.starts_with('?') {
+            format!("/{}", path)
+        } else {
+            path.to_owned()
+        };
+
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            port,
+            path,
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The path and the query.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The value of the Host header.
+    pub fn authority(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    // What to pass to TcpStream::connect().
+    pub(crate) fn socket_addr(&self) -> (&str, u16) {
+        (
+            self.host.trim_start_matches('[').trim_end_matches(']'),
+            self.port,
+        )
+    }
+
+    /// Resolves a reference (e.g. a Location header) against this URL.
+    pub fn join(&self, reference: &str) -> Result<Self> {
+        if reference.contains("://") {
+            return Self::parse(reference);
+        }
+        if let Some(rest) = reference.strip_prefix("//") {
+            return Self::parse(&format!("http://{}", rest));
+        }
+
+        let reference = reference.split('#').next().unwrap();
+        let path = if reference.starts_with('/') {
+            reference.to_owned()
+        } else if reference.starts_with('?') {
+            let base = self.path.split('?').next().unwrap();
+            format!("{}{}", base, reference)
+        } else if reference.is_empty() {
+            self.path.clone()
+        } else {
+            let base = self.path.split('?').next().unwrap();
+            let dir = &base[..(base.rfind('/').unwrap() + 1)];
+            format!("{}{}", dir, reference)
+        };
+
+        Ok(Self {
+            host: self.host.clone(),
+            port: self.port,
+            path: remove_dot_segments(&path),
+        })
+    }
+}
+
+// RFC 3986, section 5.2.4; the query is left as is.
+fn remove_dot_segments(path: &str) -> String {
+    let (path, query) = match path.find('?') {
+        Some(pos) => path.split_at(pos),
+        None => (path, ""),
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/').skip(1) {
+        trailing_slash = false;
+        match segment {
+            "." => trailing_slash = true,
+            ".." => {
+                segments.pop();
+                trailing_slash = true;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut result = String::with_capacity(path.len() + query.len());
+    for segment in &segments {
+        result.push('/');
+        result.push_str(segment);
+    }
+    if trailing_slash || result.is_empty() {
+        result.push('/');
+    }
+    result.push_str(query);
+    result
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}{}", self.authority(), self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let url = Url::parse("HTTP://Example.COM/a/b?x=1#frag").unwrap();
+        assert_eq!(url.host(), "example.com");
+        assert_eq!(url.port(), 80);
+        assert_eq!(url.path(), "/a/b?x=1");
+        assert_eq!(url.authority(), "example.com");
+
+        let url = Url::parse("http://host:8080").unwrap();
+        assert_eq!(url.port(), 8080);
+        assert_eq!(url.path(), "/");
+        assert_eq!(url.authority(), "host:8080");
+
+        let url = Url::parse("http://host?q").unwrap();
+        assert_eq!(url.path(), "/?q");
+
+        assert!(Url::parse("https://host/").is_err());
+        assert!(Url::parse("host/").is_err());
+        assert!(Url::parse("http://user@host/").is_err());
+        assert!(Url::parse("http:///path").is_err());
+        assert!(Url::parse("http://host:99999/").is_err());
+        assert!(Url::parse("http://host:/").is_err());
+    }
+
+    #[test]
+    fn parse_ipv6() {
+        let url = Url::parse("http://[::1]/").unwrap();
+        assert_eq!(url.host(), "[::1]");
+        assert_eq!(url.port(), 80);
+        assert_eq!(url.socket_addr(), ("::1", 80));
+
+        let url = Url::parse("http://[fe80::1]:8080/x").unwrap();
+        assert_eq!(url.host(), "[fe80::1]");
+        assert_eq!(url.port(), 8080);
+        assert_eq!(url.authority(), "[fe80::1]:8080");
+        assert_eq!(url.socket_addr(), ("fe80::1", 8080));
+        assert_eq!(url.to_string(), "http://[fe80::1]:8080/x");
+
+        assert!(Url::parse("http://[::1/").is_err());
+        assert!(Url::parse("http://::1]/").is_err());
+    }
+
+    #[test]
+    fn join() {
+        let base = Url::parse("http://host:81/a/b/c?q=1").unwrap();
+        let join = |reference: &str| base.join(reference).unwrap().to_string();
+
+        assert_eq!(join("d"), "http://host:81/a/b/d");
+        assert_eq!(join("./d"), "http://host:81/a/b/d");
+        assert_eq!(join("../d"), "http://host:81/a/d");
+        assert_eq!(join("../../../../d"), "http://host:81/d");
+        assert_eq!(join("."), "http://host:81/a/b/");
+        assert_eq!(join(".."), "http://host:81/a/");
+        assert_eq!(join("/x/./y/../z"), "http://host:81/x/z");
+        assert_eq!(join("?r=2"), "http://host:81/a/b/c?r=2");
+        assert_eq!(join(""), "http://host:81/a/b/c?q=1");
+        assert_eq!(join("#frag"), "http://host:81/a/b/c?q=1");
+        assert_eq!(join("d/../e?s=/../"), "http://host:81/a/b/e?s=/../");
+        assert_eq!(join("//other/p"), "http://other/p");
+        assert_eq!(join("http://[::1]:82/p"), "http://[::1]:82/p");
+        assert!(base.join("https://other/").is_err());
+    }
+
+    #[test]
+    fn dot_segments() {
+        assert_eq!(remove_dot_segments("/"), "/");
+        assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(remove_dot_segments("/mid/content=5/../6"), "/mid/6");
+        assert_eq!(remove_dot_segments("/a/.."), "/");
+        assert_eq!(remove_dot_segments("/.."), "/");
+        assert_eq!(remove_dot_segments("/a/./"), "/a/");
+        assert_eq!(remove_dot_segments("/a//b"), "/a//b");
+        assert_eq!(remove_dot_segments("/a/../b?c/../d"), "/b?c/../d");
+    }
+}
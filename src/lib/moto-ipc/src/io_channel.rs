@@ -284,6 +284,10 @@ impl RawChannel {
         }
     }
 
+    fn has_free_client_page(&self, subchannel_mask: u64) -> bool {
+        (self.client_pages_in_use.load(Ordering::Acquire) | !subchannel_mask) != u64::MAX
+    }
+
     fn dump_state(&self) {
         crate::moto_log!(
             "RawChannel: sqh: {} sqt: {} cqh: {} cqt: {} client pages: 0x{:x} server pages: 0x{:x}",
@@ -740,6 +744,13 @@ impl ServerConnection {
         }
     }
 
+    /// Whether the client can allocate a page in the subchannel: client pages
+    /// are freed when the server drops them, so this is how the server learns
+    /// that a client whose writes were blocked can write again.
+    pub fn has_free_client_page(&self, subchannel_mask: u64) -> bool {
+        self.raw_channel().has_free_client_page(subchannel_mask)
+    }
+
     fn raw_channel(&self) -> &'static mut RawChannel {
         #[cfg(debug_assertions)]
         unsafe {
@@ -238,6 +238,10 @@ impl ClientConnection {
         self.status == ClientConnectionStatus::CONNECTED
     }
 
+    pub fn handle(&self) -> SysHandle {
+        self.handle
+    }
+
     pub fn data(&self) -> &[u8] {
         unsafe {
             slice::from_raw_parts(
@@ -288,6 +292,40 @@ impl ClientConnection {
         }
     }
 
+    // Split-phase RPC: start_rpc() sends the request and returns without waiting;
+    // the server wakes handle() when the response is ready, which poll_rpc() checks.
+    pub fn start_rpc(&mut self) -> Result<(), ErrorCode> {
+        if !self.connected() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        fence(core::sync::atomic::Ordering::SeqCst);
+        let seq = self
+            .req::<RequestHeader>()
+            .seq
+            .fetch_add(1, Ordering::AcqRel);
+        assert_eq!(seq, self.seq);
+        assert_eq!(seq & 1, 0);
+        self.seq = seq + 1;
+
+        let res = SysCpu::wake(self.handle);
+        if let Err(ErrorCode::BadHandle) = res {
+            self.disconnect();
+        }
+        res
+    }
+
+    // Returns true once the response to the request sent via start_rpc() is in.
+    pub fn poll_rpc(&mut self) -> bool {
+        let seq = self.resp::<ResponseHeader>().seq.load(Ordering::SeqCst);
+        if self.seq == seq {
+            return false;
+        }
+        assert_eq!(self.seq + 1, seq);
+        self.seq += 1;
+        true
+    }
+
     pub fn req<T: Sized>(&mut self) -> &mut T {
         assert!(core::mem::size_of::<T>() <= self.channel_size.size());
         unsafe {
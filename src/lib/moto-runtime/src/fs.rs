@@ -19,6 +19,9 @@ pub struct FileAttr {
     modified: u64,
     accessed: u64,
     created: u64,
+    uid: u32,
+    gid: u32,
+    mode: u32,
 }
 
 impl FileAttr {
@@ -30,6 +33,9 @@ impl FileAttr {
             modified: raw_data.modified,
             accessed: raw_data.accessed,
             created: raw_data.created,
+            uid: raw_data.uid,
+            gid: raw_data.gid,
+            mode: raw_data.mode,
         }
     }
 
@@ -61,12 +67,28 @@ impl FileAttr {
     pub fn created(&self) -> Result<u64, ErrorCode> {
         Ok(self.created)
     }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    // See FILE_MODE_MASK.
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
 }
 
 #[derive(Debug)]
 pub struct ReadDir {
     path: String,
     fd: u64,
+    cursor: u64,                                   // Where the next batch starts.
+    batch: alloc::collections::VecDeque<DirEntry>, // Fetched, not yet returned.
+    done: bool,
 }
 
 impl Drop for ReadDir {
@@ -77,7 +99,13 @@ impl Drop for ReadDir {
 
 impl ReadDir {
     fn from(path: String, resp: &ReadDirResponse) -> Result<ReadDir, ErrorCode> {
-        Ok(ReadDir { path, fd: resp.fd })
+        Ok(ReadDir {
+            path,
+            fd: resp.fd,
+            cursor: 0,
+            batch: alloc::collections::VecDeque::new(),
+            done: false,
+        })
     }
 }
 
@@ -85,13 +113,13 @@ impl Iterator for ReadDir {
     type Item = Result<DirEntry, ErrorCode>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match FsClient::readdir_next(self) {
-            Ok(result) => match result {
-                Some(entry) => Some(Ok(entry)),
-                None => None,
-            },
-            Err(err) => Some(Err(err)),
+        if self.batch.is_empty() && !self.done {
+            // A failed batch can be retried: the cursor has not moved.
+            if let Err(err) = FsClient::readdir_next(self) {
+                return Some(Err(err));
+            }
         }
+        self.batch.pop_front().map(Ok)
     }
 }
 
@@ -250,6 +278,7 @@ impl OpenOptions {
     const F_TRUNCATE: u32 = FileOpenRequest::F_TRUNCATE;
     const F_CREATE: u32 = FileOpenRequest::F_CREATE;
     const F_CREATE_NEW: u32 = FileOpenRequest::F_CREATE_NEW;
+    const F_DIRECT: u32 = FileOpenRequest::F_DIRECT;
 
     pub const fn new() -> OpenOptions {
         Self { flags: 0 }
@@ -286,6 +315,12 @@ impl OpenOptions {
     pub fn create_new(&mut self, create_new: bool) {
         self.set_flag(Self::F_CREATE_NEW, create_new);
     }
+
+    // Bypass the FS caches: reads and writes must use offsets and sizes
+    // aligned to FileOpenRequest::DIRECT_IO_ALIGN.
+    pub fn direct(&mut self, direct: bool) {
+        self.set_flag(Self::F_DIRECT, direct);
+    }
 }
 
 pub struct File {
@@ -293,6 +328,7 @@ pub struct File {
     fd: u64,
     pos: AtomicU64, // Atomic because read operations take &File, but change pos.
     size: u64,
+    direct: bool, // Opened with F_DIRECT.
 }
 
 impl Drop for File {
@@ -319,17 +355,66 @@ impl File {
     }
 
     pub fn fsync(&self) -> Result<(), ErrorCode> {
-        Ok(())
+        FsClient::sync(self.fd, 0)
     }
 
     pub fn datasync(&self) -> Result<(), ErrorCode> {
-        Ok(())
+        FsClient::sync(self.fd, FileSyncRequest::F_DATA_ONLY)
+    }
+
+    // Allocate the blocks in [offset, offset + len), extending the file if needed.
+    pub fn allocate(&self, offset: u64, len: u64) -> Result<(), ErrorCode> {
+        FsClient::allocate(self.fd, offset, len, 0)
+    }
+
+    // Free the blocks in [offset, offset + len): the range reads as zeroes.
+    pub fn punch_hole(&self, offset: u64, len: u64) -> Result<(), ErrorCode> {
+        FsClient::allocate(self.fd, offset, len, FileAllocateRequest::F_PUNCH_HOLE)
     }
 
     pub fn truncate(&self, _size: u64) -> Result<(), ErrorCode> {
         todo!()
     }
 
+    // Advisory locks (see FlockRequest): lock() and lock_shared() wait
+    // for the lock, try_lock() and try_lock_shared() fail with NotReady.
+    pub fn lock(&self) -> Result<(), ErrorCode> {
+        self.lock_wait(FlockRequest::F_LOCK_EXCLUSIVE)
+    }
+
+    pub fn lock_shared(&self) -> Result<(), ErrorCode> {
+        self.lock_wait(FlockRequest::F_LOCK_SHARED)
+    }
+
+    pub fn try_lock(&self) -> Result<(), ErrorCode> {
+        FsClient::flock(self.fd, FlockRequest::F_LOCK_EXCLUSIVE)
+    }
+
+    pub fn try_lock_shared(&self) -> Result<(), ErrorCode> {
+        FsClient::flock(self.fd, FlockRequest::F_LOCK_SHARED)
+    }
+
+    pub fn unlock(&self) -> Result<(), ErrorCode> {
+        FsClient::flock(self.fd, FlockRequest::F_UNLOCK)
+    }
+
+    // The connection to sys-io is shared by all threads of the process,
+    // so waiting is done here, by polling, and not in sys-io.
+    fn lock_wait(&self, flags: u32) -> Result<(), ErrorCode> {
+        const MAX_BACKOFF: core::time::Duration = core::time::Duration::from_millis(50);
+
+        let mut backoff = core::time::Duration::from_micros(100);
+        loop {
+            match FsClient::flock(self.fd, flags) {
+                Err(ErrorCode::NotReady) => {
+                    super::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                res => return res,
+            }
+        }
+    }
+
     pub fn read(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
         FsClient::read(self, buf)
     }
@@ -353,6 +438,22 @@ impl File {
         Ok(done)
     }
 
+    // Maps @len bytes of the file starting at @offset (must be page-aligned)
+    // into memory. Pages are read from the file on first access; bytes past
+    // the end of the file read as zeroes. If @writable, the mapping is private:
+    // writes are not propagated back to the file. The mapping is of the open
+    // file, not of the path: it survives the file being renamed over or unlinked.
+    pub fn mmap(&self, offset: u64, len: usize, writable: bool) -> Result<FileMapping, ErrorCode> {
+        let pager = FilePager::get()?;
+        let token = rpc_file_dup(
+            &mut FsClient::get()?.conn.lock(),
+            FileDupRequest::F_EXPORT,
+            self.fd,
+        )?
+        .0;
+        pager.map(token, offset, len, writable)
+    }
+
     pub fn write(&self, buf: &[u8]) -> Result<usize, ErrorCode> {
         let mut written = 0;
         loop {
@@ -380,6 +481,37 @@ impl core::fmt::Debug for File {
     }
 }
 
+pub struct FileMapping {
+    addr: u64,
+    len: usize,
+}
+
+impl Drop for FileMapping {
+    fn drop(&mut self) {
+        if let Ok(pager) = FilePager::get() {
+            pager.unmap(self.addr);
+        }
+    }
+}
+
+impl FileMapping {
+    pub fn as_ptr(&self) -> *const u8 {
+        self.addr as usize as *const u8
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.addr as usize as *mut u8
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
 pub fn readdir(dir: &str) -> Result<ReadDir, ErrorCode> {
     FsClient::readdir(dir)
 }
@@ -388,8 +520,16 @@ pub fn rename(old: &str, new: &str) -> Result<(), ErrorCode> {
     FsClient::rename(old, new)
 }
 
-pub fn set_perm(_pathname: &str, _perm: FilePermissions) -> Result<(), ErrorCode> {
-    todo!()
+// Only the "readonly" bit is meaningful: it clears all write bits of the
+// file mode, or sets the write bit for the owner.
+pub fn set_perm(pathname: &str, perm: FilePermissions) -> Result<(), ErrorCode> {
+    let mode = FsClient::stat(pathname)?.mode;
+    let mode = if perm.readonly() {
+        mode & !0o222
+    } else {
+        mode | 0o200
+    };
+    FsClient::chmod(pathname, mode)
 }
 
 pub fn stat(path: &str) -> Result<FileAttr, ErrorCode> {
@@ -397,13 +537,96 @@ pub fn stat(path: &str) -> Result<FileAttr, ErrorCode> {
 }
 
 pub fn lstat(path: &str) -> Result<FileAttr, ErrorCode> {
-    SysRay::log(alloc::format!("fs.rs: lstat: {}", path).as_str()).ok();
-    todo!()
+    FsClient::lstat(path)
+}
+
+pub fn symlink(original: &str, link: &str) -> Result<(), ErrorCode> {
+    FsClient::symlink(original, link)
+}
+
+pub fn readlink(path: &str) -> Result<String, ErrorCode> {
+    FsClient::readlink(path)
+}
+
+// A hard link: both paths name the same file. Directories cannot be linked.
+pub fn link(original: &str, link: &str) -> Result<(), ErrorCode> {
+    FsClient::link(original, link)
+}
+
+// Extended attributes (see XattrRequest). Follow symlinks.
+pub fn get_xattr(path: &str, name: &str) -> Result<alloc::vec::Vec<u8>, ErrorCode> {
+    FsClient::xattr(CMD_XATTR_GET, 0, path, name, &[])
+}
+
+// flags: XattrRequest::F_CREATE, XattrRequest::F_REPLACE, or zero.
+pub fn set_xattr(path: &str, name: &str, value: &[u8], flags: u32) -> Result<(), ErrorCode> {
+    FsClient::xattr(CMD_XATTR_SET, flags, path, name, value).map(|_| ())
+}
+
+pub fn list_xattrs(path: &str) -> Result<alloc::vec::Vec<String>, ErrorCode> {
+    let names = FsClient::xattr(CMD_XATTR_LIST, 0, path, "", &[])?;
+    names
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            core::str::from_utf8(name)
+                .map(|name| name.to_owned())
+                .map_err(|_| ErrorCode::InternalError)
+        })
+        .collect()
+}
+
+pub fn remove_xattr(path: &str, name: &str) -> Result<(), ErrorCode> {
+    FsClient::xattr(CMD_XATTR_REMOVE, 0, path, name, &[]).map(|_| ())
 }
 
+// The same limit as in srfs.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
 pub fn canonicalize(path: &str) -> Result<String, ErrorCode> {
+    // Resolve symlinks one component at a time, so that the result
+    // has none.
     let c_path = CanonicalPath::parse(path)?;
-    FsClient::stat(c_path.abs_path.as_str()).map(|_| c_path.abs_path)
+    let mut resolved = String::new();
+    let mut rest = c_path.abs_path;
+    let mut follows = 0;
+
+    while !rest.is_empty() {
+        let (component, tail) = match rest.trim_start_matches('/').split_once('/') {
+            Some((component, tail)) => (component.to_owned(), tail.to_owned()),
+            None => (rest.trim_start_matches('/').to_owned(), String::new()),
+        };
+        rest = tail;
+        if component.is_empty() {
+            continue;
+        }
+
+        let candidate = alloc::format!("{}/{}", resolved, component);
+        let attr = FsClient::lstat(candidate.as_str())?;
+        if !attr.file_type().is_symlink() {
+            resolved = candidate;
+            continue;
+        }
+
+        follows += 1;
+        if follows > MAX_SYMLINK_FOLLOWS {
+            return Err(ErrorCode::FilesystemLoop);
+        }
+        let target = FsClient::readlink(candidate.as_str())?;
+        let base = if target.starts_with('/') {
+            ""
+        } else {
+            resolved.as_str()
+        };
+        let spliced = alloc::format!("/{}/{}/{}", base, target, rest);
+        rest = CanonicalPath::normalize(spliced.as_str())?.abs_path;
+        resolved = String::new();
+    }
+
+    if resolved.is_empty() {
+        resolved.push('/');
+    }
+    FsClient::stat(resolved.as_str()).map(|_| resolved)
 }
 
 pub fn getcwd() -> Result<String, ErrorCode> {
@@ -538,7 +761,7 @@ impl CanonicalPath {
 }
 
 struct FsClient {
-    _driver_url: String,
+    driver_url: String,
     conn: super::mutex::Mutex<moto_ipc::sync::ClientConnection>,
     cwd: super::mutex::Mutex<Option<DirEntry>>,
 }
@@ -556,7 +779,7 @@ impl FsClient {
         }
 
         let fs_client = Box::leak(Box::new(FsClient {
-            _driver_url: url,
+            driver_url: url,
             conn: super::mutex::Mutex::new(conn),
             cwd: super::mutex::Mutex::new(None),
         }));
@@ -653,16 +876,71 @@ impl FsClient {
         Ok(())
     }
 
-    fn unlink(path: &str, flags: u32) -> Result<(), ErrorCode> {
+    fn link(original: &str, link: &str) -> Result<(), ErrorCode> {
+        let mut conn = Self::get()?.conn.lock();
+        let raw_channel = conn.raw_channel();
+        unsafe {
+            let req = raw_channel.get_mut::<LinkRequest>();
+            let original_path = CanonicalPath::parse(original)?;
+            let link_path = CanonicalPath::parse(link)?;
+
+            req.build_cmd(
+                CMD_LINK,
+                original_path.abs_path.as_str(),
+                link_path.abs_path.as_str(),
+                &raw_channel,
+            )?;
+        }
+
+        conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<LinkResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from(resp.header.result));
+        }
+
+        Ok(())
+    }
+
+    // The target is sent as is, without being made absolute: relative
+    // targets are resolved against the directory of the link.
+    fn symlink(original: &str, link: &str) -> Result<(), ErrorCode> {
+        if original.is_empty() || original.len() >= MAX_PATH {
+            return Err(ErrorCode::InvalidFilename);
+        }
+        let link_path = CanonicalPath::parse(link)?;
+        let mut conn = Self::get()?.conn.lock();
+        let raw_channel = conn.raw_channel();
+        unsafe {
+            let req = raw_channel.get_mut::<SymlinkRequest>();
+            req.build_cmd(
+                CMD_SYMLINK,
+                original,
+                link_path.abs_path.as_str(),
+                &raw_channel,
+            )?;
+        }
+
+        conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<SymlinkResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from(resp.header.result));
+        }
+
+        Ok(())
+    }
+
+    fn readlink(path: &str) -> Result<String, ErrorCode> {
         let c_path = CanonicalPath::parse(path)?;
         let mut conn = Self::get()?.conn.lock();
         let raw_channel = conn.raw_channel();
 
         unsafe {
-            let req = raw_channel.get_mut::<UnlinkRequest>();
-            req.header.cmd = CMD_UNLINK;
+            let req = raw_channel.get_mut::<ReadLinkRequest>();
+            req.header.cmd = CMD_READLINK;
             req.header.ver = 0;
-            req.header.flags = flags;
+            req.header.flags = 0;
             req.parent_fd = 0;
 
             req.fname_size = c_path.abs_path.as_bytes().len() as u16;
@@ -671,24 +949,57 @@ impl FsClient {
 
         conn.do_rpc(None)?;
 
-        let resp = unsafe { raw_channel.get::<UnlinkResponse>() };
+        let resp = unsafe { raw_channel.get::<ReadLinkResponse>() };
         if resp.header.result != 0 {
             return Err(ErrorCode::from(resp.header.result));
         }
 
-        Ok(())
+        unsafe { resp.target(&raw_channel).map(|target| target.to_owned()) }
     }
 
-    fn file_open(path: &str, opts: &OpenOptions) -> Result<File, ErrorCode> {
+    fn xattr(
+        cmd: u16,
+        flags: u32,
+        path: &str,
+        name: &str,
+        value: &[u8],
+    ) -> Result<alloc::vec::Vec<u8>, ErrorCode> {
+        let c_path = CanonicalPath::parse(path)?;
+        let mut conn = Self::get()?.conn.lock();
+        let raw_channel = conn.raw_channel();
+
+        unsafe {
+            let req = raw_channel.get_mut::<XattrRequest>();
+            req.build(
+                cmd,
+                flags,
+                c_path.abs_path.as_str(),
+                name,
+                value,
+                &raw_channel,
+            )?;
+        }
+
+        conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<XattrResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from(resp.header.result));
+        }
+
+        unsafe { resp.data(&raw_channel).map(|data| data.to_vec()) }
+    }
+
+    fn unlink(path: &str, flags: u32) -> Result<(), ErrorCode> {
         let c_path = CanonicalPath::parse(path)?;
         let mut conn = Self::get()?.conn.lock();
         let raw_channel = conn.raw_channel();
 
         unsafe {
-            let req = raw_channel.get_mut::<FileOpenRequest>();
-            req.header.cmd = CMD_FILE_OPEN;
+            let req = raw_channel.get_mut::<UnlinkRequest>();
+            req.header.cmd = CMD_UNLINK;
             req.header.ver = 0;
-            req.header.flags = opts.flags;
+            req.header.flags = flags;
             req.parent_fd = 0;
 
             req.fname_size = c_path.abs_path.as_bytes().len() as u16;
@@ -697,20 +1008,27 @@ impl FsClient {
 
         conn.do_rpc(None)?;
 
-        let resp = unsafe { raw_channel.get::<FileOpenResponse>() };
+        let resp = unsafe { raw_channel.get::<UnlinkResponse>() };
         if resp.header.result != 0 {
             return Err(ErrorCode::from(resp.header.result));
         }
 
-        if resp.fd == 0 {
-            return Err(ErrorCode::InternalError);
-        }
+        Ok(())
+    }
+
+    fn file_open(path: &str, opts: &OpenOptions) -> Result<File, ErrorCode> {
+        let c_path = CanonicalPath::parse(path)?;
+        let (fd, size) = {
+            let mut conn = Self::get()?.conn.lock();
+            rpc_file_open(&mut conn, c_path.abs_path.as_str(), opts.flags)?
+        };
 
         Ok(File {
             path: c_path.abs_path,
-            fd: resp.fd,
+            fd,
             pos: AtomicU64::new(0),
-            size: resp.size,
+            size,
+            direct: opts.flags & FileOpenRequest::F_DIRECT != 0,
         })
     }
 
@@ -774,38 +1092,9 @@ impl FsClient {
 
     fn read(file: &File, buf: &mut [u8]) -> Result<usize, ErrorCode> {
         let mut conn = Self::get()?.conn.lock();
-        let raw_channel = conn.raw_channel();
-        unsafe {
-            let req = raw_channel.get_mut::<FileReadRequest>();
-            req.header.cmd = CMD_FILE_READ;
-            req.header.ver = 0;
-            req.fd = file.fd;
-            req.offset = file.pos.load(Ordering::Relaxed);
-            req.max_bytes = {
-                if buf.len() > raw_channel.size() {
-                    raw_channel.size()
-                } else {
-                    buf.len()
-                }
-            } as u32;
-        }
-
-        conn.do_rpc(None)?;
-
-        let resp = unsafe { raw_channel.get::<FileReadResponse>() };
-        if resp.header.result != 0 {
-            return Err(ErrorCode::from(resp.header.result));
-        }
-
-        // resp.size may be BLOCK_SIZE if buf is too small.
-        let result_sz = buf.len().min(resp.size as usize);
-
-        unsafe {
-            let bytes = raw_channel.get_bytes(&resp.data, result_sz)?;
-            core::intrinsics::copy_nonoverlapping(bytes.as_ptr(), buf.as_mut_ptr(), result_sz);
-            file.pos.fetch_add(result_sz as u64, Ordering::Relaxed);
-            Ok(result_sz)
-        }
+        let result_sz = rpc_read(&mut conn, file.fd, file.pos.load(Ordering::Relaxed), buf)?;
+        file.pos.fetch_add(result_sz as u64, Ordering::Relaxed);
+        Ok(result_sz)
     }
 
     fn write(file: &File, buf: &[u8]) -> Result<usize, ErrorCode> {
@@ -823,8 +1112,12 @@ impl FsClient {
             req.fd = file.fd;
             req.offset = file.pos.load(Ordering::Relaxed);
 
-            let size =
+            let mut size =
                 (raw_channel.size() - core::mem::size_of::<FileWriteRequest>()).min(buf.len());
+            if file.direct && size < buf.len() {
+                // Keep the rest of buf aligned for the next write.
+                size &= !(FileOpenRequest::DIRECT_IO_ALIGN as usize - 1);
+            }
             req.size = size as u32;
 
             raw_channel.put_bytes(&buf[0..size], &mut req.data).unwrap();
@@ -867,7 +1160,8 @@ impl FsClient {
         ReadDir::from(c_path.abs_path, &resp)
     }
 
-    fn readdir_next(readdir: &ReadDir) -> Result<Option<DirEntry>, ErrorCode> {
+    // Fetches the next batch of entries into readdir.
+    fn readdir_next(readdir: &mut ReadDir) -> Result<(), ErrorCode> {
         let mut conn = Self::get()?.conn.lock();
         let raw_channel = conn.raw_channel();
         unsafe {
@@ -876,6 +1170,7 @@ impl FsClient {
             req.header.ver = 0;
             req.header.flags = 0;
             req.readdir_fd = readdir.fd;
+            req.cursor = readdir.cursor;
         }
 
         conn.do_rpc(None)?;
@@ -886,23 +1181,81 @@ impl FsClient {
         }
 
         if resp.entries == 0 {
-            return Ok(None);
+            readdir.done = true;
+            return Ok(());
         }
 
-        if resp.entries != 1 {
-            panic!("Batched entries not supported yet.");
+        let mut addr = resp.dir_entries.as_ptr() as usize;
+        let mut batch = alloc::collections::VecDeque::with_capacity(resp.entries as usize);
+        for _ in 0..resp.entries {
+            let dentry =
+                unsafe { &raw_channel.get_at(&*(addr as *const [DirEntryData; 0]), 1)?[0] };
+            batch.push_back(DirEntry::from(readdir, &raw_channel, dentry)?);
+            addr += DirEntryData::packed_size(dentry.fname_size as usize);
         }
-
-        let dentry = unsafe { raw_channel.get_at(&resp.dir_entries, 1)? };
-        Ok(Some(DirEntry::from(readdir, &raw_channel, &dentry[0])?))
+        readdir.batch = batch;
+        readdir.cursor = resp.cursor;
+        Ok(())
     }
 
     fn close_fd(fd: u64, flags: u32) -> Result<(), ErrorCode> {
+        let mut conn = Self::get()?.conn.lock();
+        rpc_close_fd(&mut conn, fd, flags)
+    }
+
+    fn sync(fd: u64, flags: u32) -> Result<(), ErrorCode> {
+        let mut conn = Self::get()?.conn.lock();
+        let raw_channel = conn.raw_channel();
+
+        unsafe {
+            let req = raw_channel.get_mut::<FileSyncRequest>();
+            req.header.cmd = CMD_FILE_SYNC;
+            req.header.ver = 0;
+            req.header.flags = flags;
+            req.fd = fd;
+        }
+
+        conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<FileSyncResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from_u16(resp.header.result));
+        }
+
+        Ok(())
+    }
+
+    fn allocate(fd: u64, offset: u64, len: u64, flags: u32) -> Result<(), ErrorCode> {
+        let mut conn = Self::get()?.conn.lock();
+        let raw_channel = conn.raw_channel();
+
+        unsafe {
+            let req = raw_channel.get_mut::<FileAllocateRequest>();
+            req.header.cmd = CMD_FILE_ALLOCATE;
+            req.header.ver = 0;
+            req.header.flags = flags;
+            req.fd = fd;
+            req.offset = offset;
+            req.len = len;
+        }
+
+        conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<FileAllocateResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from_u16(resp.header.result));
+        }
+
+        Ok(())
+    }
+
+    fn flock(fd: u64, flags: u32) -> Result<(), ErrorCode> {
         let mut conn = Self::get()?.conn.lock();
         let raw_channel = conn.raw_channel();
+
         unsafe {
-            let req = raw_channel.get_mut::<CloseFdRequest>();
-            req.header.cmd = CMD_CLOSE_FD;
+            let req = raw_channel.get_mut::<FlockRequest>();
+            req.header.cmd = CMD_FLOCK;
             req.header.ver = 0;
             req.header.flags = flags;
             req.fd = fd;
@@ -910,15 +1263,23 @@ impl FsClient {
 
         conn.do_rpc(None)?;
 
-        let resp = unsafe { raw_channel.get::<CloseFdResponse>() };
+        let resp = unsafe { raw_channel.get::<FlockResponse>() };
         if resp.header.result != 0 {
-            SysRay::log("close_fd: RPC failed.").ok();
+            return Err(ErrorCode::from_u16(resp.header.result));
         }
 
         Ok(())
     }
 
     fn stat(path: &str) -> Result<FileAttr, ErrorCode> {
+        Self::stat_flags(path, 0)
+    }
+
+    fn lstat(path: &str) -> Result<FileAttr, ErrorCode> {
+        Self::stat_flags(path, F_STAT_NOFOLLOW)
+    }
+
+    fn stat_flags(path: &str, flags: u32) -> Result<FileAttr, ErrorCode> {
         let c_path = CanonicalPath::parse(path)?;
         let mut conn = Self::get()?.conn.lock();
         let raw_channel = conn.raw_channel();
@@ -927,7 +1288,7 @@ impl FsClient {
             let req = raw_channel.get_mut::<StatRequest>();
             req.header.cmd = CMD_STAT;
             req.header.ver = 0;
-            req.header.flags = 0;
+            req.header.flags = flags;
             req.parent_fd = 0;
 
             req.fname_size = c_path.abs_path.as_bytes().len() as u16;
@@ -946,6 +1307,32 @@ impl FsClient {
         Ok(file_attr)
     }
 
+    fn chmod(path: &str, mode: u32) -> Result<(), ErrorCode> {
+        let c_path = CanonicalPath::parse(path)?;
+        let mut conn = Self::get()?.conn.lock();
+        let raw_channel = conn.raw_channel();
+
+        unsafe {
+            let req = raw_channel.get_mut::<ChmodRequest>();
+            req.header.cmd = CMD_CHMOD;
+            req.header.ver = 0;
+            req.header.flags = mode;
+            req.parent_fd = 0;
+
+            req.fname_size = c_path.abs_path.as_bytes().len() as u16;
+            raw_channel.put_bytes(c_path.abs_path.as_bytes(), &mut req.fname)?;
+        }
+
+        conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<ChmodResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from_u16(resp.header.result));
+        }
+
+        Ok(())
+    }
+
     fn mkdir(path: &str) -> Result<(), ErrorCode> {
         let c_path = CanonicalPath::parse(path)?;
         let mut conn = Self::get()?.conn.lock();
@@ -973,6 +1360,268 @@ impl FsClient {
     }
 }
 
+// Memory-mapped files are backed by a kernel pager object (see SysMem::OP_PAGER):
+// File::mmap() reserves a paged region, and the pager thread below reads pages
+// from the file as they are first touched. The pager thread has its own
+// connection to the FS driver, as a thread faulting on a mapped page may hold
+// FsClient's connection lock (e.g. when reading a file into a private mapping).
+struct MappedFile {
+    fd: u64, // Imported into FilePager's connection.
+    offset: u64,
+}
+
+struct FilePager {
+    pager: moto_sys::SysHandle,
+    conn: super::mutex::Mutex<moto_ipc::sync::ClientConnection>,
+    mappings: super::mutex::Mutex<alloc::collections::BTreeMap<u64, MappedFile>>,
+}
+
+static FILE_PAGER: super::mutex::Mutex<usize> = super::mutex::Mutex::new(0);
+
+impl FilePager {
+    fn get() -> Result<&'static FilePager, ErrorCode> {
+        use alloc::boxed::Box;
+
+        let mut file_pager = FILE_PAGER.lock();
+        if *file_pager != 0 {
+            return unsafe {
+                Ok((*file_pager as *const FilePager)
+                    .as_ref()
+                    .unwrap_unchecked())
+            };
+        }
+
+        let mut conn = moto_ipc::sync::ClientConnection::new(moto_ipc::sync::ChannelSize::Small)?;
+        conn.connect(FsClient::get()?.driver_url.as_str())?;
+        let pager = moto_sys::SysObj::create(moto_sys::SysHandle::NONE, 0, "pager")?;
+
+        let self_ = Box::leak(Box::new(FilePager {
+            pager,
+            conn: super::mutex::Mutex::new(conn),
+            mappings: super::mutex::Mutex::new(alloc::collections::BTreeMap::new()),
+        }));
+
+        super::thread::spawn(
+            4096 * 16,
+            Self::pager_thread as usize,
+            self_ as *const _ as usize,
+        )?;
+
+        *file_pager = self_ as *const _ as usize;
+        Ok(self_)
+    }
+
+    // @token: the file's fd, exported for the pager's connection (see FileDupRequest).
+    fn map(
+        &self,
+        token: u64,
+        offset: u64,
+        len: usize,
+        writable: bool,
+    ) -> Result<FileMapping, ErrorCode> {
+        use moto_sys::sys_mem::{PAGE_SIZE_SMALL, PAGE_SIZE_SMALL_LOG2};
+
+        if len == 0 || (offset & (PAGE_SIZE_SMALL - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let fd = rpc_file_dup(&mut self.conn.lock(), FileDupRequest::F_IMPORT, token)?.0;
+        let num_pages = moto_sys::align_up(len as u64, PAGE_SIZE_SMALL) >> PAGE_SIZE_SMALL_LOG2;
+        let addr = match moto_sys::SysMem::pager_map(self.pager, num_pages, writable) {
+            Ok(addr) => addr,
+            Err(err) => {
+                rpc_close_fd(&mut self.conn.lock(), fd, CloseFdRequest::F_FILE).ok();
+                return Err(err);
+            }
+        };
+
+        self.mappings.lock().insert(addr, MappedFile { fd, offset });
+        Ok(FileMapping { addr, len })
+    }
+
+    fn unmap(&self, addr: u64) {
+        let mapped = self.mappings.lock().remove(&addr);
+        moto_sys::SysMem::free(addr).unwrap();
+        if let Some(mapped) = mapped {
+            rpc_close_fd(&mut self.conn.lock(), mapped.fd, CloseFdRequest::F_FILE).ok();
+        }
+    }
+
+    // Reads the page at @page_addr of the mapping at @region_start.
+    fn read_page(&self, page_addr: u64, region_start: u64, page: &mut [u8]) {
+        page.fill(0);
+
+        let (fd, mut offset) = match self.mappings.lock().get(&region_start) {
+            Some(mapped) => (mapped.fd, mapped.offset + (page_addr - region_start)),
+            None => return, // Being unmapped.
+        };
+
+        let mut conn = self.conn.lock();
+        let mut done = 0;
+        while done < page.len() {
+            match rpc_read(&mut conn, fd, offset, &mut page[done..]) {
+                Ok(0) => break, // EOF.
+                Ok(sz) => {
+                    done += sz;
+                    offset += sz as u64;
+                }
+                Err(err) => {
+                    SysRay::log(
+                        alloc::format!("FilePager: read failed at 0x{:x}: {:?}", page_addr, err)
+                            .as_str(),
+                    )
+                    .ok();
+                    break;
+                }
+            }
+        }
+    }
+
+    extern "C" fn pager_thread(self_addr: usize) {
+        let self_ = unsafe { (self_addr as *const FilePager).as_ref().unwrap_unchecked() };
+        let mut page = alloc::vec![0_u8; moto_sys::sys_mem::PAGE_SIZE_SMALL as usize];
+
+        loop {
+            while let Ok((page_addr, region_start)) =
+                moto_sys::SysMem::pager_next_fault(self_.pager)
+            {
+                self_.read_page(page_addr, region_start, &mut page);
+                // Fails only if the region has been unmapped concurrently.
+                let _ = moto_sys::SysMem::pager_supply(self_.pager, page_addr, &page);
+            }
+
+            let mut handles = [self_.pager];
+            let _ = moto_sys::SysCpu::wait(
+                &mut handles,
+                moto_sys::SysHandle::NONE,
+                moto_sys::SysHandle::NONE,
+                None,
+            );
+        }
+    }
+}
+
+// RPCs that are used both by FsClient and by FilePager, which has its own connection.
+fn rpc_file_open(
+    conn: &mut moto_ipc::sync::ClientConnection,
+    abs_path: &str,
+    flags: u32,
+) -> Result<(u64, u64), ErrorCode> {
+    let raw_channel = conn.raw_channel();
+
+    unsafe {
+        let req = raw_channel.get_mut::<FileOpenRequest>();
+        req.header.cmd = CMD_FILE_OPEN;
+        req.header.ver = 0;
+        req.header.flags = flags;
+        req.parent_fd = 0;
+
+        req.fname_size = abs_path.as_bytes().len() as u16;
+        raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<FileOpenResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    if resp.fd == 0 {
+        return Err(ErrorCode::InternalError);
+    }
+
+    Ok((resp.fd, resp.size))
+}
+
+// Returns (token, 0) for F_EXPORT, (fd, size) for F_IMPORT.
+fn rpc_file_dup(
+    conn: &mut moto_ipc::sync::ClientConnection,
+    flags: u32,
+    fd: u64,
+) -> Result<(u64, u64), ErrorCode> {
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<FileDupRequest>();
+        req.header.cmd = CMD_FILE_DUP;
+        req.header.ver = 0;
+        req.header.flags = flags;
+        req.fd = fd;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<FileDupResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    Ok((resp.fd, resp.size))
+}
+
+fn rpc_read(
+    conn: &mut moto_ipc::sync::ClientConnection,
+    fd: u64,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<usize, ErrorCode> {
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<FileReadRequest>();
+        req.header.cmd = CMD_FILE_READ;
+        req.header.ver = 0;
+        req.fd = fd;
+        req.offset = offset;
+        req.max_bytes = {
+            if buf.len() > raw_channel.size() {
+                raw_channel.size()
+            } else {
+                buf.len()
+            }
+        } as u32;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<FileReadResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    // resp.size may be BLOCK_SIZE if buf is too small.
+    let result_sz = buf.len().min(resp.size as usize);
+
+    unsafe {
+        let bytes = raw_channel.get_bytes(&resp.data, result_sz)?;
+        core::intrinsics::copy_nonoverlapping(bytes.as_ptr(), buf.as_mut_ptr(), result_sz);
+    }
+    Ok(result_sz)
+}
+
+fn rpc_close_fd(
+    conn: &mut moto_ipc::sync::ClientConnection,
+    fd: u64,
+    flags: u32,
+) -> Result<(), ErrorCode> {
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<CloseFdRequest>();
+        req.header.cmd = CMD_CLOSE_FD;
+        req.header.ver = 0;
+        req.header.flags = flags;
+        req.fd = fd;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<CloseFdResponse>() };
+    if resp.header.result != 0 {
+        SysRay::log("close_fd: RPC failed.").ok();
+    }
+
+    Ok(())
+}
+
 fn get_fileserver_url() -> Result<String, ErrorCode> {
     let mut conn = moto_ipc::sync::ClientConnection::new(moto_ipc::sync::ChannelSize::Small)?;
     conn.connect(FS_URL)?;
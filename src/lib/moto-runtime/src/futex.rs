@@ -14,6 +14,9 @@ use moto_sys::SysHandle;
 // standard List does not have this functionality.
 struct WaitQueueEntry {
     wake_handle: u64,
+    // The futex (key) the entry is currently queued on; changes on requeue.
+    // Protected by FUTEX_WAIT_QUEUES lock.
+    key: usize,
     prev: AtomicUsize,
     next: AtomicUsize,
     _pin: PhantomPinned,
@@ -27,6 +30,7 @@ impl WaitQueueEntry {
     fn new() -> Self {
         Self {
             wake_handle: 0,
+            key: 0,
             prev: AtomicUsize::new(0),
             next: AtomicUsize::new(0),
             _pin: PhantomPinned::default(),
@@ -96,10 +100,15 @@ impl WaitQueue {
         self_
     }
 
-    // Returns true if timed out.
-    fn wait(&self, timeout: &Option<moto_sys::time::Instant>) -> bool {
-        let mut entry = WaitQueueEntry::new();
-
+    // Returns true if timed out. The entry may still be queued on return
+    // (on timeout), and it may be requeued to a different queue while
+    // waiting, so the caller must remove it: see remove_waiter().
+    fn wait(
+        &self,
+        entry: &mut WaitQueueEntry,
+        timeout: &Option<moto_sys::time::Instant>,
+        owner: SysHandle,
+    ) -> bool {
         let tcb = moto_sys::UserThreadControlBlock::get();
         entry.wake_handle = tcb.self_handle;
 
@@ -112,25 +121,49 @@ impl WaitQueue {
             }
         }
 
-        let timed_out = match SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, *timeout) {
+        let mut result = Err(ErrorCode::BadHandle);
+        if owner != SysHandle::NONE {
+            result = SysCpu::wait_donating(owner, *timeout);
+        }
+        if result == Err(ErrorCode::BadHandle) {
+            // No owner, or it has exited.
+            result = SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, *timeout);
+        }
+
+        match result {
             Ok(()) => false,
             Err(err) => {
                 assert_eq!(err, ErrorCode::TimedOut);
                 true
             }
-        };
+        }
+    }
 
-        {
-            let _entries = self.entries.lock();
-            // Safe because under the mutex lock.
+    // Moves up to @max waiters from self to @target. Returns the number
+    // of waiters moved. Must be called with FUTEX_WAIT_QUEUES locked.
+    fn requeue_to(&self, target: &WaitQueue, target_key: usize, max: usize) -> usize {
+        let mut moved = 0;
+        let head = self.entries.lock();
+        let mut target_head = target.entries.lock();
+        while moved < max {
+            let p_first = head.next.load(Ordering::Relaxed);
+            if p_first == self.p_head {
+                break;
+            }
+
+            // Safe because under both mutex locks.
             unsafe {
-                // Entries are removed from the list in wake_one().
-                if entry.next.load(Ordering::Relaxed) != 0 {
-                    entry.remove(); // Timed out.
-                }
+                let first = (p_first as *mut WaitQueueEntry).as_mut().unwrap();
+                first.remove();
+                first.key = target_key;
+                first.insert_before(&mut *target_head);
             }
+            self.num_waiters.fetch_sub(1, Ordering::Relaxed);
+            target.num_waiters.fetch_add(1, Ordering::Relaxed);
+            moved += 1;
         }
-        timed_out
+
+        moved
     }
 
     fn wake_one(&self) -> bool {
@@ -163,8 +196,31 @@ static FUTEX_WAIT_QUEUES: spin::Mutex<BTreeMap<usize, Arc<WaitQueue>>> =
 
 // Returns false on timeout.
 pub fn futex_wait(futex: &AtomicU32, expected: u32, timeout: Option<core::time::Duration>) -> bool {
-    let timeout = timeout.map(|dur| moto_sys::time::Instant::now() + dur);
+    futex_wait_until(
+        futex,
+        expected,
+        timeout.map(|dur| moto_sys::time::Instant::now() + dur),
+    )
+}
+
+// Same as futex_wait(), but with an absolute timeout. Returns false on timeout.
+pub fn futex_wait_until(
+    futex: &AtomicU32,
+    expected: u32,
+    timeout: Option<moto_sys::time::Instant>,
+) -> bool {
+    futex_wait_on_owner(futex, expected, timeout, SysHandle::NONE)
+}
 
+// Same as futex_wait_until(), but the current thread lends its priority to
+// @owner, a thread of this process (e.g. the one holding the lock the futex
+// guards), while it waits; see SysCpu::F_DONATE_TARGET.
+pub fn futex_wait_on_owner(
+    futex: &AtomicU32,
+    expected: u32,
+    timeout: Option<moto_sys::time::Instant>,
+    owner: SysHandle,
+) -> bool {
     let key = futex as *const _ as usize;
     loop {
         if futex.load(Ordering::Relaxed) != expected {
@@ -176,39 +232,49 @@ pub fn futex_wait(futex: &AtomicU32, expected: u32, timeout: Option<core::time::
             }
         }
 
+        // Note: num_waiters must be incremented under FUTEX_WAIT_QUEUES lock,
+        // as a queue is removed from the map when num_waiters drops to zero.
         let queue = {
             let mut lock = FUTEX_WAIT_QUEUES.lock();
-            match lock.get(&key) {
+            let queue = match lock.get(&key) {
                 Some(q) => q.clone(),
                 None => {
                     let q = WaitQueue::new();
                     lock.insert(key, q.clone());
                     q
                 }
-            }
+            };
+            queue.num_waiters.fetch_add(1, Ordering::Relaxed);
+            queue
         };
 
-        queue.num_waiters.fetch_add(1, Ordering::Relaxed);
+        let mut entry = WaitQueueEntry::new();
+        entry.key = key;
         let timed_out = if futex.load(Ordering::Relaxed) == expected {
-            queue.wait(&timeout)
+            queue.wait(&mut entry, &timeout, owner)
         } else {
             false
         };
+        core::mem::drop(queue);
 
         {
             let mut lock = FUTEX_WAIT_QUEUES.lock();
-            if 1 == queue.num_waiters.fetch_sub(1, Ordering::Relaxed) {
-                if let Some(q) = lock.get(&key) {
-                    // It is really hard to get a pointer out of Pin<Arc<_>>.
-                    let ref1: &WaitQueue = &*q;
-                    let ref2: &WaitQueue = &*queue;
-                    let ptr1 = ref1 as *const WaitQueue;
-                    let ptr2 = ref2 as *const WaitQueue;
-                    if ptr1 == ptr2 {
-                        lock.remove(&key);
+            // The entry could have been requeued, so use entry.key, not key.
+            let curr_key = entry.key;
+            let queue = lock.get(&curr_key).unwrap().clone();
+            {
+                let _entries = queue.entries.lock();
+                // Safe because under the mutex lock.
+                unsafe {
+                    // Entries are removed from the list in wake_one().
+                    if entry.next.load(Ordering::Relaxed) != 0 {
+                        entry.remove(); // Timed out.
                     }
                 }
             }
+            if 1 == queue.num_waiters.fetch_sub(1, Ordering::Relaxed) {
+                lock.remove(&curr_key);
+            }
         }
 
         if timed_out {
@@ -233,13 +299,77 @@ pub fn futex_wake(futex: &AtomicU32) -> bool {
 pub fn futex_wake_all(futex: &AtomicU32) {
     let key = futex as *const _ as usize;
     let queue = {
-        let mut lock = FUTEX_WAIT_QUEUES.lock();
-        if let Some(q) = lock.remove(&key) {
-            q
-        } else {
-            return;
+        // Note: the queue is removed from the map by the last waiter.
+        let lock = FUTEX_WAIT_QUEUES.lock();
+        match lock.get(&key) {
+            Some(q) => q.clone(),
+            None => return,
         }
     };
 
     queue.wake_all()
 }
+
+// Wakes up to @num_wake waiters on @futex, and moves up to @num_requeue
+// remaining waiters to wait on @target, without waking them. This is used
+// e.g. by condvars to avoid thundering herds on notify_all. Returns the
+// number of waiters woken and requeued.
+pub fn futex_requeue(
+    futex: &AtomicU32,
+    num_wake: usize,
+    target: &AtomicU32,
+    num_requeue: usize,
+) -> usize {
+    futex_requeue_to_key(futex, num_wake, target as *const _ as usize, num_requeue)
+}
+
+// Same as futex_requeue(), with the target futex given by its address, so
+// that the caller does not need a reference to it (see Condvar).
+pub(crate) fn futex_requeue_to_key(
+    futex: &AtomicU32,
+    num_wake: usize,
+    target_key: usize,
+    num_requeue: usize,
+) -> usize {
+    let key = futex as *const _ as usize;
+    if key == target_key {
+        return 0;
+    }
+
+    let queue = {
+        let lock = FUTEX_WAIT_QUEUES.lock();
+        match lock.get(&key) {
+            Some(q) => q.clone(),
+            None => return 0,
+        }
+    };
+
+    let mut woken = 0;
+    while woken < num_wake && queue.wake_one() {
+        woken += 1;
+    }
+
+    if num_requeue == 0 {
+        return woken;
+    }
+
+    let mut lock = FUTEX_WAIT_QUEUES.lock();
+    let target_queue = match lock.get(&target_key) {
+        Some(q) => q.clone(),
+        None => {
+            let q = WaitQueue::new();
+            lock.insert(target_key, q.clone());
+            q
+        }
+    };
+
+    let requeued = queue.requeue_to(&target_queue, target_key, num_requeue);
+    if target_queue.num_waiters.load(Ordering::Relaxed) == 0 {
+        lock.remove(&target_key);
+    }
+    if queue.num_waiters.load(Ordering::Relaxed) == 0 {
+        lock.remove(&key);
+    }
+
+    woken + requeued
+}
@@ -14,6 +14,8 @@ pub mod fs;
 
 #[cfg(any(feature = "rustc-dep-of-std", feature = "rt-api"))]
 pub mod net;
+#[cfg(any(feature = "rustc-dep-of-std", feature = "rt-api"))]
+pub mod poll;
 
 #[cfg(feature = "rustc-dep-of-std")]
 pub mod process;
@@ -23,7 +25,7 @@ pub mod std_rt;
 pub mod stdio;
 #[cfg(any(feature = "rustc-dep-of-std", feature = "rt-api"))]
 pub mod thread;
-#[cfg(feature = "rustc-dep-of-std")]
+#[cfg(any(feature = "rustc-dep-of-std", feature = "rt-api"))]
 pub mod time;
 #[cfg(feature = "rustc-dep-of-std")]
 pub mod tls;
@@ -63,17 +65,14 @@ pub extern crate core as _core;
 
 #[cfg(feature = "rustc-dep-of-std")]
 pub fn hashmap_random_keys() -> (u64, u64) {
-    let mut val1 = 0_u64;
-    let mut val2 = 0_u64;
-    unsafe {
-        let _ = core::arch::x86_64::_rdrand64_step(&mut val1);
-        let _ = core::arch::x86_64::_rdrand64_step(&mut val2);
-    }
-    if val1 != 0 && val2 != 0 {
-        (val1, val2)
-    } else {
-        (7, 13)
+    let mut bytes = [0_u8; 16];
+    if moto_sys::fill_random(&mut bytes).is_err() {
+        return (7, 13);
     }
+    (
+        u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+        u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+    )
 }
 
 pub fn print_stacktace() {
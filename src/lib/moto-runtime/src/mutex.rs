@@ -1,18 +1,20 @@
-// A futex-based mutex, to be used in no-std environments.
+// A futex-based mutex and condvar, to be used in no-std environments.
 // Inspired by spin::Mutex.
 
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
 
 pub struct Mutex<T: ?Sized> {
     lock: AtomicU32,
+    owner: AtomicU64, // The handle of the thread holding the lock; waiters lend it their priority.
     data: UnsafeCell<T>,
 }
 
 #[derive(Debug)]
 pub struct MutexGuard<'a, T: ?Sized> {
     lock: &'a AtomicU32,
+    owner: &'a AtomicU64,
     data: &'a mut T,
 }
 
@@ -26,33 +28,40 @@ impl<T> Mutex<T> {
     pub const fn new(user_data: T) -> Mutex<T> {
         Mutex {
             lock: AtomicU32::new(UNLOCKED),
+            owner: AtomicU64::new(0),
             data: UnsafeCell::new(user_data),
         }
     }
 }
 
-impl<T: ?Sized> Mutex<T> {
-    fn obtain_lock(&self) {
-        const BUSY_LOOP_ITERS: i32 = 128;
-        let mut busy_loop_counter = 0;
-        while self
-            .lock
-            .compare_exchange_weak(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
-            .is_err()
-        {
-            if busy_loop_counter < BUSY_LOOP_ITERS {
-                busy_loop_counter += 1;
-                core::hint::spin_loop();
-                continue;
-            }
-            crate::futex_wait(&self.lock, LOCKED, None);
+fn obtain_lock(lock: &AtomicU32, owner: &AtomicU64) {
+    const BUSY_LOOP_ITERS: i32 = 128;
+    let mut busy_loop_counter = 0;
+    while lock
+        .compare_exchange_weak(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        if busy_loop_counter < BUSY_LOOP_ITERS {
+            busy_loop_counter += 1;
+            core::hint::spin_loop();
+            continue;
         }
+        let owner = moto_sys::SysHandle::from_u64(owner.load(Ordering::Relaxed));
+        crate::futex_wait_on_owner(lock, LOCKED, None, owner);
     }
 
+    owner.store(
+        moto_sys::UserThreadControlBlock::get().self_handle,
+        Ordering::Relaxed,
+    );
+}
+
+impl<T: ?Sized> Mutex<T> {
     pub fn lock(&self) -> MutexGuard<'_, T> {
-        self.obtain_lock();
+        obtain_lock(&self.lock, &self.owner);
         MutexGuard {
             lock: &self.lock,
+            owner: &self.owner,
             data: unsafe { &mut *self.data.get() },
         }
     }
@@ -79,7 +88,84 @@ impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
 
 impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
     fn drop(&mut self) {
+        self.owner.store(0, Ordering::Relaxed);
         self.lock.store(UNLOCKED, Ordering::Release);
         crate::futex_wake(self.lock);
     }
 }
+
+// A condition variable to be used with a single Mutex. notify_all() wakes
+// one waiter and moves the rest to wait on the mutex, so that they are
+// woken one at a time as the mutex is released, rather than all at once
+// only to contend for the mutex.
+pub struct Condvar {
+    seq: AtomicU32,
+    mutex: AtomicUsize, // The address of the lock word of the mutex used with the condvar.
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            mutex: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.wait_until(guard, None).0
+    }
+
+    // Same as wait(), but with an absolute timeout. Returns false on timeout.
+    pub fn wait_until<'a, T: ?Sized>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Option<moto_sys::time::Instant>,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let lock = guard.lock;
+        let owner = guard.owner;
+        let data = guard.data as *mut T;
+
+        let mutex = lock as *const _ as usize;
+        let prev = self.mutex.swap(mutex, Ordering::Relaxed);
+        assert!(
+            prev == 0 || prev == mutex,
+            "Condvar used with different mutexes"
+        );
+
+        // Read the sequence number before unlocking, so that a notification
+        // sent after the unlock is not missed.
+        let seq = self.seq.load(Ordering::Relaxed);
+        core::mem::drop(guard);
+        let notified = crate::futex_wait_until(&self.seq, seq, timeout);
+
+        obtain_lock(lock, owner);
+        let guard = MutexGuard {
+            lock,
+            owner,
+            // Safe because we hold the lock again.
+            data: unsafe { &mut *data },
+        };
+        (guard, notified)
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        crate::futex_wake(&self.seq);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        let mutex = self.mutex.load(Ordering::Relaxed);
+        if mutex == 0 {
+            crate::futex_wake_all(&self.seq);
+        } else {
+            crate::futex::futex_requeue_to_key(&self.seq, 1, mutex, usize::MAX);
+        }
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
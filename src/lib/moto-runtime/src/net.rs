@@ -18,6 +18,8 @@
 //       for performance and robustness.
 
 use super::util::moturus_log;
+use crate::poll::Poll;
+use crate::poll::Token;
 use crate::rt_api;
 use crate::rt_api::net::IO_SUBCHANNELS;
 use crate::util::CachePadded;
@@ -26,10 +28,12 @@ use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::sync::Weak;
 use alloc::vec::Vec;
+use core::net::IpAddr;
 use core::net::Ipv4Addr;
 use core::net::Ipv6Addr;
 use core::net::SocketAddr;
-use core::net::SocketAddrV4;
+use core::ops::Deref;
+use core::ops::DerefMut;
 use core::sync::atomic::*;
 use core::time::Duration;
 use moto_ipc::io_channel;
@@ -68,6 +72,37 @@ impl NetRuntime {
         }
     }
 
+    // Two reservations on the same channel, for socket pairs.
+    fn reserve_channel_pair(&mut self) -> Arc<NetChannel> {
+        let found = self
+            .channels
+            .values()
+            .find(|channel| channel.reservations.load(Ordering::Relaxed) + 2 <= IO_SUBCHANNELS)
+            .cloned();
+        let channel = match found {
+            Some(channel) => channel,
+            None => {
+                let channel = NetChannel::new();
+                self.channels.insert(channel.id(), channel.clone());
+                channel
+            }
+        };
+
+        let reservations = 2 + channel.reservations.fetch_add(2, Ordering::Relaxed);
+        if reservations == IO_SUBCHANNELS {
+            self.channels.remove(&channel.id());
+            self.full_channels.insert(channel.id(), channel.clone());
+        }
+        channel
+    }
+
+    fn channel(&self, id: u64) -> Option<Arc<NetChannel>> {
+        self.channels
+            .get(&id)
+            .or_else(|| self.full_channels.get(&id))
+            .cloned()
+    }
+
     fn release_channel(&mut self, channel: Arc<NetChannel>) {
         channel.reservations.fetch_sub(1, Ordering::Relaxed);
         if let Some(channel) = self.full_channels.remove(&channel.id()) {
@@ -90,6 +125,9 @@ struct NetChannel {
     // owns tcp streams, and we want to clear things away when the user drops them.
     tcp_streams: crate::util::SpinLock<BTreeMap<u64, Weak<TcpStreamImpl>>>,
     tcp_listeners: crate::util::SpinLock<BTreeMap<u64, Weak<TcpListenerImpl>>>,
+    udp_sockets: crate::util::SpinLock<BTreeMap<u64, Weak<UdpSocketImpl>>>,
+    local_sockets: crate::util::SpinLock<BTreeMap<u64, Weak<LocalSocketImpl>>>,
+    ping_sockets: crate::util::SpinLock<BTreeMap<u64, Weak<PingSocketImpl>>>,
 
     next_msg_id: CachePadded<AtomicU64>, // A counter.
 
@@ -102,6 +140,10 @@ struct NetChannel {
     // Threads waiting for specific resp_id: map resp_id => (thread handle, resp).
     resp_waiters: crate::util::SpinLock<BTreeMap<u64, (SysHandle, Option<io_channel::Msg>)>>,
 
+    // Accepts nobody waits for: map resp_id => (listener, subchannel_idx).
+    // See TcpListener::accept().
+    accept_waiters: crate::util::SpinLock<BTreeMap<u64, (Weak<TcpListenerImpl>, usize)>>,
+
     io_thread_join_handle: AtomicU64,
     io_thread_wake_handle: AtomicU64,
 
@@ -181,36 +223,116 @@ impl NetChannel {
             //     msg.command
             // );
 
-            let wait_handle: Option<SysHandle> = if msg.id == 0 {
-                // This is an incoming packet, or similar, without a dedicated waiter.
-                let stream_handle = msg.handle;
-                let stream = {
-                    let mut tcp_streams = self.tcp_streams.lock(line!());
-                    if let Some(stream) = tcp_streams.get_mut(&stream_handle) {
-                        stream.upgrade()
+            let wait_handle: Option<SysHandle> =
+                if msg.id == 0 && msg.command == rt_api::net::CMD_UDP_SOCKET_RX {
+                    let socket = self
+                        .udp_sockets
+                        .lock(line!())
+                        .get(&msg.handle)
+                        .and_then(|socket| socket.upgrade());
+                    if let Some(socket) = socket {
+                        // Hold the lock while processing the message, as with TCP streams below.
+                        let wait_handle = {
+                            let mut rx_lock = socket.rx_waiter.lock(line!());
+                            socket.process_incoming_msg(msg);
+                            rx_lock.take()
+                        };
+                        socket.notify_poll();
+                        wait_handle
                     } else {
+                        self.on_orphan_message(msg);
                         None
                     }
-                };
-                if let Some(stream) = stream {
-                    // Note: we must hold the lock while processing the message, otherwise the wait handle might get updated
-                    //       and we will lose the wakeup. Sad story, don't ask...
-                    let mut rx_lock = stream.rx_waiter.lock(line!());
-                    stream.process_incoming_msg(msg);
-                    rx_lock.take()
-                } else {
-                    self.on_orphan_message(msg);
+                } else if msg.id == 0 && msg.command == rt_api::net::CMD_UDP_SOCKET_TX {
+                    // A send failed in sys-io.
+                    let socket = self
+                        .udp_sockets
+                        .lock(line!())
+                        .get(&msg.handle)
+                        .and_then(|socket| socket.upgrade());
+                    if let Some(socket) = socket {
+                        socket.on_tx_error(msg.status());
+                        socket.notify_poll();
+                    }
+                    None
+                } else if msg.id == 0 && msg.command == rt_api::net::CMD_SOCKET_TX_WAIT {
+                    self.on_tx_page_free(msg);
+                    None
+                } else if msg.id == 0 && msg.command == rt_api::net::CMD_LOCAL_SOCKET_RX {
+                    let socket = self
+                        .local_sockets
+                        .lock(line!())
+                        .get(&msg.handle)
+                        .and_then(|socket| socket.upgrade());
+                    if let Some(socket) = socket {
+                        let wait_handle = {
+                            let mut rx_lock = socket.rx_waiter.lock(line!());
+                            socket.process_incoming_msg(msg);
+                            rx_lock.take()
+                        };
+                        socket.notify_poll();
+                        wait_handle
+                    } else {
+                        self.on_orphan_message(msg);
+                        None
+                    }
+                } else if msg.id == 0 && msg.command == rt_api::net::CMD_PING_SOCKET_RX {
+                    let socket = self
+                        .ping_sockets
+                        .lock(line!())
+                        .get(&msg.handle)
+                        .and_then(|socket| socket.upgrade());
+                    if let Some(socket) = socket {
+                        let mut rx_lock = socket.rx_waiter.lock(line!());
+                        socket.process_incoming_msg(msg);
+                        rx_lock.take()
+                    } else {
+                        self.on_orphan_message(msg);
+                        None
+                    }
+                } else if msg.id == 0 {
+                    // This is an incoming packet, or similar, without a dedicated waiter.
+                    let stream_handle = msg.handle;
+                    let stream = {
+                        let mut tcp_streams = self.tcp_streams.lock(line!());
+                        if let Some(stream) = tcp_streams.get_mut(&stream_handle) {
+                            stream.upgrade()
+                        } else {
+                            None
+                        }
+                    };
+                    if let Some(stream) = stream {
+                        // Note: we must hold the lock while processing the message, otherwise the wait handle might get updated
+                        //       and we will lose the wakeup. Sad story, don't ask...
+                        let wait_handle = {
+                            let mut rx_lock = stream.rx_waiter.lock(line!());
+                            stream.process_incoming_msg(msg);
+                            rx_lock.take()
+                        };
+                        stream.notify_poll();
+                        wait_handle
+                    } else {
+                        self.on_orphan_message(msg);
+                        None
+                    }
+                } else if let Some((listener, subchannel_idx)) =
+                    self.accept_waiters.lock(line!()).remove(&msg.id)
+                {
+                    if let Some(listener) = listener.upgrade() {
+                        listener.on_async_accept(msg);
+                    } else {
+                        self.on_orphan_accept(msg, subchannel_idx);
+                    }
                     None
-                }
-            } else {
-                let mut resp_waiters = self.resp_waiters.lock(line!());
-                if let Some((handle, resp)) = resp_waiters.get_mut(&msg.id) {
-                    *resp = Some(msg);
-                    Some(*handle)
                 } else {
-                    panic!("unexpected msg");
-                }
-            };
+                    let mut resp_waiters = self.resp_waiters.lock(line!());
+                    if let Some((handle, resp)) = resp_waiters.get_mut(&msg.id) {
+                        *resp = Some(msg);
+                        Some(*handle)
+                    } else {
+                        panic!("unexpected msg");
+                    }
+                };
 
             if let Some(wait_handle) = wait_handle {
                 if wait_handle.as_u64() != moto_sys::UserThreadControlBlock::get().self_handle {
@@ -300,11 +422,15 @@ impl NetChannel {
             subchannels_in_use,
             tcp_streams: crate::util::SpinLock::new(BTreeMap::new()),
             tcp_listeners: crate::util::SpinLock::new(BTreeMap::new()),
+            udp_sockets: crate::util::SpinLock::new(BTreeMap::new()),
+            local_sockets: crate::util::SpinLock::new(BTreeMap::new()),
+            ping_sockets: crate::util::SpinLock::new(BTreeMap::new()),
             reservations: AtomicUsize::new(0),
             next_msg_id: CachePadded::new(AtomicU64::new(1)),
             send_queue: crate::util::ArrayQueue::new(io_channel::CHANNEL_PAGE_COUNT),
             send_waiters: crate::util::SpinLock::new(VecDeque::new()),
             resp_waiters: crate::util::SpinLock::new(BTreeMap::new()),
+            accept_waiters: crate::util::SpinLock::new(BTreeMap::new()),
             io_thread_join_handle: AtomicU64::new(SysHandle::NONE.into()),
             io_thread_wake_handle: AtomicU64::new(SysHandle::NONE.into()),
             io_thread_running: CachePadded::new(AtomicBool::new(false)),
@@ -378,6 +504,102 @@ impl NetChannel {
         NET.lock(line!()).release_channel(self.clone());
     }
 
+    fn udp_socket_created(self: &Arc<Self>, socket: &Arc<UdpSocketImpl>) {
+        assert!(self
+            .udp_sockets
+            .lock(line!())
+            .insert(socket.handle, Arc::downgrade(socket))
+            .is_none());
+    }
+
+    fn udp_socket_dropped(self: &Arc<Self>, handle: u64, subchannel_idx: usize) {
+        let socket = self.udp_sockets.lock(line!()).remove(&handle).unwrap();
+        assert_eq!(0, socket.strong_count());
+
+        self.release_subchannel(subchannel_idx);
+        NET.lock(line!()).release_channel(self.clone());
+    }
+
+    fn local_socket_created(self: &Arc<Self>, socket: &Arc<LocalSocketImpl>) {
+        assert!(self
+            .local_sockets
+            .lock(line!())
+            .insert(socket.handle, Arc::downgrade(socket))
+            .is_none());
+    }
+
+    fn local_socket_dropped(self: &Arc<Self>, handle: u64, subchannel_idx: usize) {
+        let socket = self.local_sockets.lock(line!()).remove(&handle).unwrap();
+        assert_eq!(0, socket.strong_count());
+
+        self.release_subchannel(subchannel_idx);
+        NET.lock(line!()).release_channel(self.clone());
+    }
+
+    fn ping_socket_created(self: &Arc<Self>, socket: &Arc<PingSocketImpl>) {
+        assert!(self
+            .ping_sockets
+            .lock(line!())
+            .insert(socket.handle, Arc::downgrade(socket))
+            .is_none());
+    }
+
+    fn ping_socket_dropped(self: &Arc<Self>, handle: u64, subchannel_idx: usize) {
+        let socket = self.ping_sockets.lock(line!()).remove(&handle).unwrap();
+        assert_eq!(0, socket.strong_count());
+
+        self.release_subchannel(subchannel_idx);
+        NET.lock(line!()).release_channel(self.clone());
+    }
+
+    // A nonblocking write found no free TX page: sys-io tells the socket when
+    // there is one, so that it can notify its Poll. Asks once per wait.
+    fn wait_for_tx_page(self: &Arc<Self>, handle: u64, kind: u32, tx_waiting: &AtomicBool) {
+        if !tx_waiting.swap(true, Ordering::AcqRel) {
+            self.send_msg(rt_api::net::socket_tx_wait_msg(handle, kind));
+        }
+    }
+
+    // Note: this is called from the IO thread, so must not sleep.
+    fn on_tx_page_free(&self, msg: io_channel::Msg) {
+        match msg.flags {
+            rt_api::net::TX_WAIT_TCP => {
+                let stream = self
+                    .tcp_streams
+                    .lock(line!())
+                    .get(&msg.handle)
+                    .and_then(|stream| stream.upgrade());
+                if let Some(stream) = stream {
+                    stream.tx_waiting.store(false, Ordering::Release);
+                    stream.notify_poll();
+                }
+            }
+            rt_api::net::TX_WAIT_UDP => {
+                let socket = self
+                    .udp_sockets
+                    .lock(line!())
+                    .get(&msg.handle)
+                    .and_then(|socket| socket.upgrade());
+                if let Some(socket) = socket {
+                    socket.tx_waiting.store(false, Ordering::Release);
+                    socket.notify_poll();
+                }
+            }
+            rt_api::net::TX_WAIT_LOCAL => {
+                let socket = self
+                    .local_sockets
+                    .lock(line!())
+                    .get(&msg.handle)
+                    .and_then(|socket| socket.upgrade());
+                if let Some(socket) = socket {
+                    socket.tx_waiting.store(false, Ordering::Release);
+                    socket.notify_poll();
+                }
+            }
+            _ => {}
+        }
+    }
+
     fn send_msg(self: &Arc<Self>, msg: io_channel::Msg) {
         loop {
             if self.send_queue.push(msg).is_ok() {
@@ -439,6 +661,23 @@ impl NetChannel {
             }
             rt_api::net::EVT_TCP_STREAM_STATE_CHANGED => {}
             rt_api::net::CMD_TCP_STREAM_CLOSE => {}
+            rt_api::net::CMD_UDP_SOCKET_RX => {
+                // RX raced with the client dropping the socket.
+                let _ = self
+                    .conn
+                    .get_page(rt_api::net::udp_socket_datagram_page(&msg));
+            }
+            rt_api::net::CMD_UDP_SOCKET_DROP => {}
+            rt_api::net::CMD_LOCAL_SOCKET_RX => {
+                if let Some(page) = rt_api::net::local_socket_data_page(&msg) {
+                    let _ = self.conn.get_page(page);
+                }
+            }
+            rt_api::net::CMD_LOCAL_SOCKET_DROP => {}
+            rt_api::net::CMD_PING_SOCKET_RX => {
+                let _ = self.conn.get_page(rt_api::net::ping_socket_page(&msg));
+            }
+            rt_api::net::CMD_PING_SOCKET_DROP => {}
             _ => {
                 // #[cfg(debug_assertions)]
                 // This is logged always because if a new incoming message is added that
@@ -454,6 +693,23 @@ impl NetChannel {
         }
     }
 
+    // The listener was dropped while its accept was in flight.
+    // Note: this is called from the IO thread, so must not sleep/block.
+    fn on_orphan_accept(&self, msg: io_channel::Msg, subchannel_idx: usize) {
+        if msg.status().is_ok() {
+            let mut req = io_channel::Msg::new();
+            req.command = rt_api::net::CMD_TCP_STREAM_CLOSE;
+            req.handle = msg.handle;
+            self.send_queue.push(req).unwrap(); // TODO: don't panic on failure.
+        }
+
+        self.release_subchannel(subchannel_idx);
+        let mut net = NET.lock(line!());
+        if let Some(channel) = net.channel(self.id()) {
+            net.release_channel(channel);
+        }
+    }
+
     #[inline]
     fn wake_driver(&self) {
         let _ = moto_sys::SysCpu::wake(self.conn.server_handle());
@@ -479,10 +735,6 @@ impl RxBuf {
     fn is_consumed(&self) -> bool {
         self.consumed == self.len
     }
-
-    fn available(&self) -> usize {
-        self.len - self.consumed
-    }
 }
 
 pub struct TcpStreamImpl {
@@ -505,12 +757,17 @@ pub struct TcpStreamImpl {
 
     rx_timeout_ns: AtomicU64,
     tx_timeout_ns: AtomicU64,
+    nonblocking: AtomicBool,
+    // Set while sys-io is to report a free TX page (see CMD_SOCKET_TX_WAIT).
+    tx_waiting: AtomicBool,
 
     subchannel_idx: usize, // Never changes.
     subchannel_mask: u64,  // Never changes.
 
     stats_rx_bytes: AtomicU64,
     stats_tx_bytes: AtomicU64,
+
+    poll_registration: crate::util::SpinLock<Option<crate::poll::Registration>>,
 }
 
 impl Drop for TcpStreamImpl {
@@ -546,6 +803,40 @@ impl Drop for TcpStreamImpl {
 }
 
 impl TcpStreamImpl {
+    fn new(
+        channel: &Arc<NetChannel>,
+        handle: u64,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        subchannel_idx: usize,
+    ) -> Arc<Self> {
+        let inner = Arc::new(TcpStreamImpl {
+            local_addr,
+            remote_addr,
+            handle,
+            channel: channel.clone(),
+            recv_queue: crate::util::SpinLock::new(VecDeque::new()),
+            next_rx_seq: AtomicU64::new(1),
+            rx_buf: crate::util::SpinLock::new(None),
+            rx_waiter: crate::util::SpinLock::new(None),
+            tcp_state: AtomicU32::new(rt_api::net::TcpState::ReadWrite.into()),
+            rx_done: AtomicBool::new(false),
+            rx_timeout_ns: AtomicU64::new(u64::MAX),
+            tx_timeout_ns: AtomicU64::new(u64::MAX),
+            nonblocking: AtomicBool::new(false),
+            tx_waiting: AtomicBool::new(false),
+            subchannel_idx,
+            subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+            stats_rx_bytes: AtomicU64::new(0),
+            stats_tx_bytes: AtomicU64::new(0),
+            poll_registration: crate::util::SpinLock::new(None),
+        });
+
+        channel.tcp_stream_created(&inner);
+        inner.ack_rx();
+        inner
+    }
+
     fn ack_rx(&self) {
         let mut req = io_channel::Msg::new();
         req.command = rt_api::net::CMD_TCP_STREAM_RX_ACK;
@@ -577,12 +868,43 @@ impl TcpStreamImpl {
             ),
         }
     }
+
+    fn readiness(&self) -> u32 {
+        let mut readiness = 0;
+        if self.rx_buf.lock(line!()).is_some() || !self.recv_queue.lock(line!()).is_empty() {
+            readiness |= crate::poll::READABLE;
+        }
+        if self.rx_done.load(Ordering::Relaxed) {
+            readiness |= crate::poll::READABLE | crate::poll::READ_CLOSED;
+        }
+        if !self.tcp_state().can_write() {
+            readiness |= crate::poll::WRITE_CLOSED;
+        } else if self.channel.conn.alloc_page(self.subchannel_mask).is_ok() {
+            // The page goes back right away: we only check that there is one.
+            readiness |= crate::poll::WRITABLE;
+        }
+        readiness
+    }
+
+    fn notify_poll(&self) {
+        crate::poll::notify(&self.poll_registration, || self.readiness());
+    }
 }
 
 pub struct TcpStream {
     inner: Arc<TcpStreamImpl>,
 }
 
+/// TCP keepalive parameters, as in socket2: after the connection has been
+/// idle for `time`, sys-io sends a probe, and drops the connection if the
+/// peer does not answer within `interval * retries`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    pub time: Duration,
+    pub interval: Duration,
+    pub retries: u32,
+}
+
 impl TcpStream {
     fn connect_impl(
         socket_addr: &SocketAddr,
@@ -617,27 +939,13 @@ impl TcpStream {
             return Err(resp.status());
         }
 
-        let inner = Arc::new(TcpStreamImpl {
-            local_addr: rt_api::net::get_socket_addr(&resp.payload).unwrap(),
-            remote_addr: *socket_addr,
-            handle: resp.handle,
-            channel: channel.clone(),
-            recv_queue: crate::util::SpinLock::new(VecDeque::new()),
-            next_rx_seq: AtomicU64::new(1),
-            rx_buf: crate::util::SpinLock::new(None),
-            rx_waiter: crate::util::SpinLock::new(None),
-            tcp_state: AtomicU32::new(rt_api::net::TcpState::ReadWrite.into()),
-            rx_done: AtomicBool::new(false),
-            rx_timeout_ns: AtomicU64::new(u64::MAX),
-            tx_timeout_ns: AtomicU64::new(u64::MAX),
+        let inner = TcpStreamImpl::new(
+            &channel,
+            resp.handle,
+            rt_api::net::get_socket_addr(&resp.payload).unwrap(),
+            *socket_addr,
             subchannel_idx,
-            subchannel_mask,
-            stats_rx_bytes: AtomicU64::new(0),
-            stats_tx_bytes: AtomicU64::new(0),
-        });
-
-        channel.tcp_stream_created(&inner);
-        inner.ack_rx();
+        );
 
         #[cfg(debug_assertions)]
         moturus_log!(
@@ -711,11 +1019,11 @@ impl TcpStream {
         }
     }
 
-    pub fn peek(&self, _buf: &mut [u8]) -> Result<usize, ErrorCode> {
-        todo!()
-    }
-
-    fn process_rx_message(&self, buf: &mut [u8], msg: io_channel::Msg) -> Result<usize, ErrorCode> {
+    fn process_rx_message<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+        msg: io_channel::Msg,
+    ) -> Result<usize, ErrorCode> {
         fence(Ordering::SeqCst);
         assert_eq!(msg.command, crate::rt_api::net::CMD_TCP_STREAM_RX);
         let sz_read = msg.payload.args_64()[1] as usize;
@@ -768,59 +1076,52 @@ impl TcpStream {
             msg.handle,
             self.inner.stats_rx_bytes.load(Ordering::Relaxed)
         );
-        let sz_read = if sz_read > buf.len() {
-            unsafe {
-                core::ptr::copy_nonoverlapping(
-                    io_page.bytes().as_ptr(),
-                    buf.as_mut_ptr(),
-                    buf.len(),
-                );
-            }
-
+        let copied = scatter(&io_page.bytes()[..sz_read], bufs);
+        if copied < sz_read {
             let mut buf_lock = self.inner.rx_buf.lock(line!());
             let rx_buf = &mut *buf_lock;
             assert!(rx_buf.is_none());
             *rx_buf = Some(RxBuf {
                 page: io_page,
                 len: sz_read,
-                consumed: buf.len(),
+                consumed: copied,
             });
-            buf.len()
-        } else {
-            unsafe {
-                core::ptr::copy_nonoverlapping(io_page.bytes().as_ptr(), buf.as_mut_ptr(), sz_read);
-            }
-            sz_read
-        };
+        }
 
-        return Ok(sz_read);
+        Ok(copied)
     }
 
-    pub fn poll_rx(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
-        {
-            let mut buf_lock = self.inner.rx_buf.lock(line!());
-            if let Some(rx_buf) = &mut *buf_lock {
-                let sz_read = buf.len().min(rx_buf.available());
-                unsafe {
-                    core::ptr::copy_nonoverlapping(
-                        rx_buf.bytes().as_ptr(),
-                        buf.as_mut_ptr(),
-                        sz_read,
-                    );
-                }
+    // When peeking, the bytes stay in rx_buf (an incoming message is moved there first).
+    fn poll_rx<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+        peek: bool,
+    ) -> Result<usize, ErrorCode> {
+        loop {
+            {
+                let mut buf_lock = self.inner.rx_buf.lock(line!());
+                if let Some(rx_buf) = &mut *buf_lock {
+                    let sz_read = scatter(rx_buf.bytes(), bufs);
+                    if !peek {
+                        rx_buf.consume(sz_read);
+                        if rx_buf.is_consumed() {
+                            *buf_lock = None;
+                        }
+                    }
 
-                rx_buf.consume(sz_read);
-                if rx_buf.is_consumed() {
-                    *buf_lock = None;
+                    return Ok(sz_read);
                 }
-
-                return Ok(sz_read);
             }
-        }
 
-        {
-            if let Some(msg) = self.inner.recv_queue.lock(line!()).pop_front() {
-                return self.process_rx_message(buf, msg);
+            let Some(msg) = self.inner.recv_queue.lock(line!()).pop_front() else {
+                break;
+            };
+            if !peek {
+                return self.process_rx_message(bufs, msg);
+            }
+            self.process_rx_message::<&mut [u8]>(&mut [], msg)?;
+            if self.inner.rx_done.load(Ordering::Relaxed) {
+                break;
             }
         }
 
@@ -832,10 +1133,33 @@ impl TcpStream {
     }
 
     pub fn read(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
-        match self.poll_rx(buf) {
+        self.read_impl(&mut [buf], false)
+    }
+
+    pub fn peek(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.read_impl(&mut [buf], true)
+    }
+
+    // Fills bufs in order with the bytes available, waiting for some as read() does.
+    pub fn read_vectored<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+    ) -> Result<usize, ErrorCode> {
+        self.read_impl(bufs, false)
+    }
+
+    fn read_impl<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+        peek: bool,
+    ) -> Result<usize, ErrorCode> {
+        match self.poll_rx(bufs, peek) {
             Ok(sz) => return Ok(sz),
             Err(err) => assert_eq!(err, ErrorCode::NotReady),
         }
+        if self.inner.nonblocking.load(Ordering::Relaxed) {
+            return Err(ErrorCode::NotReady);
+        }
 
         let rx_timeout_ns = self.inner.rx_timeout_ns.load(Ordering::Relaxed);
         let rx_timeout = if rx_timeout_ns == u64::MAX {
@@ -851,7 +1175,7 @@ impl TcpStream {
                 }
             }
 
-            match self.poll_rx(buf) {
+            match self.poll_rx(bufs, peek) {
                 Ok(sz) => return Ok(sz),
                 Err(err) => assert_eq!(err, ErrorCode::NotReady),
             }
@@ -862,7 +1186,7 @@ impl TcpStream {
             }
 
             // Re-check for incoming messages.
-            match self.poll_rx(buf) {
+            match self.poll_rx(bufs, peek) {
                 Ok(sz) => {
                     *self.inner.rx_waiter.lock(line!()) = None;
                     return Ok(sz);
@@ -916,7 +1240,12 @@ impl TcpStream {
     }
 
     pub fn write(&self, buf: &[u8]) -> Result<usize, ErrorCode> {
-        if buf.len() == 0 {
+        self.write_vectored(&[buf])
+    }
+
+    // Gathers bufs into a single TX message (of up to a page).
+    pub fn write_vectored<B: Deref<Target = [u8]>>(&self, bufs: &[B]) -> Result<usize, ErrorCode> {
+        if total_len(bufs) == 0 {
             return Ok(0);
         }
 
@@ -925,7 +1254,6 @@ impl TcpStream {
         }
 
         let timestamp = moto_sys::time::Instant::now();
-        let write_sz = buf.len().min(io_channel::PAGE_SIZE);
         let timo_ns = self.inner.tx_timeout_ns.load(Ordering::Relaxed);
         let abs_timeout = if timo_ns == u64::MAX {
             None
@@ -934,8 +1262,8 @@ impl TcpStream {
         };
 
         // TODO: now we sleep exponentially long (up to a limit) on stuck writes.
-        //       We should add a new msg to the driver so that it wakes us up
-        //       when writes are unstuck.
+        //       We should wait for CMD_SOCKET_TX_WAIT to come back instead,
+        //       as nonblocking writes do for Poll.
         let mut sleep_timo_usec = 1;
         let mut spin_loop_counter: u64 = 0;
         let mut yield_counter: u64 = 0;
@@ -957,6 +1285,14 @@ impl TcpStream {
                     if !self.inner.tcp_state().can_write() {
                         return Ok(0);
                     }
+                    if self.inner.nonblocking.load(Ordering::Relaxed) {
+                        self.inner.channel.wait_for_tx_page(
+                            self.inner.handle,
+                            rt_api::net::TX_WAIT_TCP,
+                            &self.inner.tx_waiting,
+                        );
+                        return Err(ErrorCode::NotReady);
+                    }
 
                     if spin_loop_counter < 100 {
                         spin_loop_counter += 1;
@@ -995,13 +1331,7 @@ impl TcpStream {
                 }
             }
         };
-        unsafe {
-            core::ptr::copy_nonoverlapping(
-                buf.as_ptr(),
-                io_page.bytes_mut().as_mut_ptr(),
-                write_sz,
-            );
-        }
+        let write_sz = gather(bufs, io_page.bytes_mut());
 
         let msg = rt_api::net::tcp_stream_tx_msg(
             self.inner.handle,
@@ -1064,22 +1394,143 @@ impl TcpStream {
         })
     }
 
+    fn set_option(&self, option: u64, args_32: &[u32]) -> Result<(), ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_TCP_STREAM_SET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = option;
+        req.payload.args_32_mut()[2..(2 + args_32.len())].copy_from_slice(args_32);
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(())
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    fn get_option(&self, option: u64) -> Result<io_channel::Msg, ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_TCP_STREAM_GET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = option;
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(resp)
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    // Streams start with SO_LINGER(0): drop discards unsent bytes. With a
+    // longer linger, or none, sys-io keeps sending them after the drop, and
+    // closes the connection gracefully; drop does not wait for that, but the
+    // process exiting aborts the connection.
     pub fn set_linger(&self, dur: Option<Duration>) -> Result<(), ErrorCode> {
-        if let Some(dur) = dur {
-            if dur == Duration::ZERO {
-                return Ok(());
+        match dur {
+            Some(dur) => self.set_option(
+                rt_api::net::TCP_OPTION_LINGER,
+                &[1, dur.as_secs().min(u32::MAX as u64) as u32],
+            ),
+            None => self.set_option(rt_api::net::TCP_OPTION_LINGER, &[0, 0]),
+        }
+    }
+
+    pub fn linger(&self) -> Result<Option<Duration>, ErrorCode> {
+        let resp = self.get_option(rt_api::net::TCP_OPTION_LINGER)?;
+        let args = resp.payload.args_32();
+        if args[0] == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Duration::from_secs(args[1] as u64)))
+        }
+    }
+
+    // Caps how fast sys-io sends the stream's bytes out, in bytes per second.
+    pub fn set_tx_rate_limit(&self, rate: Option<u64>) -> Result<(), ErrorCode> {
+        if rate == Some(0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_TCP_STREAM_SET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = rt_api::net::TCP_OPTION_TX_RATE_LIMIT;
+        req.payload.args_64_mut()[1] = rate.unwrap_or(0);
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(())
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    pub fn tx_rate_limit(&self) -> Result<Option<u64>, ErrorCode> {
+        let resp = self.get_option(rt_api::net::TCP_OPTION_TX_RATE_LIMIT)?;
+        Ok(Some(resp.payload.args_64()[0]).filter(|rate| *rate != 0))
+    }
+
+    pub fn set_keepalive(&self, keepalive: Option<&TcpKeepalive>) -> Result<(), ErrorCode> {
+        let to_ms = |dur: Duration| dur.as_millis().min(u32::MAX as u128) as u32;
+        match keepalive {
+            Some(keepalive) => {
+                if keepalive.time.is_zero() {
+                    return Err(ErrorCode::InvalidArgument);
+                }
+                self.set_option(
+                    rt_api::net::TCP_OPTION_KEEPALIVE,
+                    &[
+                        to_ms(keepalive.time),
+                        to_ms(keepalive.interval),
+                        keepalive.retries,
+                    ],
+                )
             }
+            None => self.set_option(rt_api::net::TCP_OPTION_KEEPALIVE, &[0, 0, 0]),
         }
+    }
 
-        // At the moment, socket shutdown or drop drops all unsent bytes, which
-        // corresponds to SO_LINGER(0). This may or may not be what the user
-        // wants, but anything different requires changing sys-io code/logic,
-        // at there are higher-priority work to do.
-        Err(ErrorCode::NotImplemented)
+    pub fn keepalive(&self) -> Result<Option<TcpKeepalive>, ErrorCode> {
+        let resp = self.get_option(rt_api::net::TCP_OPTION_KEEPALIVE)?;
+        let args = resp.payload.args_32();
+        if args[0] == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(TcpKeepalive {
+                time: Duration::from_millis(args[0] as u64),
+                interval: Duration::from_millis(args[1] as u64),
+                retries: args[2],
+            }))
+        }
     }
 
-    pub fn linger(&self) -> Result<Option<Duration>, ErrorCode> {
-        Ok(Some(Duration::ZERO)) // see set_linger() above.
+    // Buffers of connected streams cannot be resized: this fails unless
+    // the size does not change. Use TcpListener::set_send_buffer_size().
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<(), ErrorCode> {
+        if size == self.send_buffer_size()? {
+            return Ok(());
+        }
+        self.set_option(rt_api::net::TCP_OPTION_SEND_BUFFER, &[size as u32])
+    }
+
+    pub fn send_buffer_size(&self) -> Result<usize, ErrorCode> {
+        let resp = self.get_option(rt_api::net::TCP_OPTION_SEND_BUFFER)?;
+        Ok(resp.payload.args_32()[0] as usize)
+    }
+
+    // See set_send_buffer_size() above.
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<(), ErrorCode> {
+        if size == self.recv_buffer_size()? {
+            return Ok(());
+        }
+        self.set_option(rt_api::net::TCP_OPTION_RECV_BUFFER, &[size as u32])
+    }
+
+    pub fn recv_buffer_size(&self) -> Result<usize, ErrorCode> {
+        let resp = self.get_option(rt_api::net::TCP_OPTION_RECV_BUFFER)?;
+        Ok(resp.payload.args_32()[0] as usize)
     }
 
     pub fn set_nodelay(&self, nodelay: bool) -> Result<(), ErrorCode> {
@@ -1154,8 +1605,33 @@ impl TcpStream {
         Ok(None)
     }
 
-    pub fn set_nonblocking(&self, _nonblocking: bool) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
+        self.inner.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn register(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::register(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
+    }
+
+    pub fn reregister(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::reregister(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
+    }
+
+    pub fn deregister(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        crate::poll::deregister(&self.inner.poll_registration, poll)
     }
 }
 
@@ -1165,307 +1641,2231 @@ impl core::fmt::Debug for TcpStream {
     }
 }
 
-struct TcpListenerImpl {
-    socket_addr: SocketAddr,
+// An accept request sent without waiting for the response, which the IO
+// thread stores here.
+struct AsyncAccept {
+    channel: Arc<NetChannel>,
+    subchannel_idx: usize,
+    req_id: u64,
+    resp: Option<io_channel::Msg>,
+}
+
+struct TcpListenerImpl {
+    socket_addr: SocketAddr,
+    channel: Arc<NetChannel>,
+    handle: u64,
+    nonblocking: AtomicBool,
+
+    async_accept: crate::util::SpinLock<Option<AsyncAccept>>,
+    poll_registration: crate::util::SpinLock<Option<crate::poll::Registration>>,
+}
+
+impl Drop for TcpListenerImpl {
+    fn drop(&mut self) {
+        let mut msg = io_channel::Msg::new();
+        msg.command = rt_api::net::CMD_TCP_LISTENER_DROP;
+        msg.handle = self.handle;
+        self.channel.send_msg(msg);
+
+        // If the accept is still in flight, the IO thread cleans up after it.
+        if let Some(accept) = self.async_accept.lock(line!()).take() {
+            if let Some(resp) = accept.resp {
+                let _ = self.on_accepted(accept.channel, accept.subchannel_idx, resp);
+            }
+        }
+
+        self.channel.tcp_listener_dropped(self.handle)
+    }
+}
+
+impl TcpListenerImpl {
+    fn on_accepted(
+        &self,
+        channel: Arc<NetChannel>,
+        subchannel_idx: usize,
+        resp: io_channel::Msg,
+    ) -> Result<(TcpStream, SocketAddr), ErrorCode> {
+        if resp.status().is_err() {
+            channel.release_subchannel(subchannel_idx);
+            NET.lock(line!()).release_channel(channel);
+            return Err(resp.status());
+        }
+
+        let remote_addr = rt_api::net::get_socket_addr(&resp.payload).unwrap();
+        let inner = TcpStreamImpl::new(
+            &channel,
+            resp.handle,
+            self.socket_addr,
+            remote_addr,
+            subchannel_idx,
+        );
+
+        #[cfg(debug_assertions)]
+        moturus_log!(
+            "{}:{} new incoming TcpStream {:?} <- {:?} mask: 0x{:x}",
+            file!(),
+            line!(),
+            inner.local_addr,
+            inner.remote_addr,
+            inner.subchannel_mask
+        );
+
+        Ok((TcpStream { inner }, remote_addr))
+    }
+
+    // Makes sure an accept request is in flight, so that new connections
+    // are noticed without a blocked accept() call.
+    fn start_async_accept(self: &Arc<Self>) {
+        let (channel, req) = {
+            let mut async_accept = self.async_accept.lock(line!());
+            if async_accept.is_some() {
+                return;
+            }
+
+            let channel = NET.lock(line!()).reserve_channel();
+            let subchannel_idx = channel.reserve_subchannel();
+            let mut req = rt_api::net::accept_tcp_listener_request(
+                self.handle,
+                rt_api::net::io_subchannel_mask(subchannel_idx),
+            );
+            req.id = channel.next_msg_id.fetch_add(1, Ordering::Relaxed);
+
+            // Register the request before sending it, as with send_receive().
+            channel
+                .accept_waiters
+                .lock(line!())
+                .insert(req.id, (Arc::downgrade(self), subchannel_idx));
+            *async_accept = Some(AsyncAccept {
+                channel: channel.clone(),
+                subchannel_idx,
+                req_id: req.id,
+                resp: None,
+            });
+            (channel, req)
+        };
+
+        channel.send_msg(req);
+    }
+
+    // Returns the accepted connection, if the async accept has completed.
+    fn take_async_accept(&self) -> Option<(Arc<NetChannel>, usize, io_channel::Msg)> {
+        let mut async_accept = self.async_accept.lock(line!());
+        async_accept.as_ref()?.resp?;
+        let accept = async_accept.take().unwrap();
+        Some((accept.channel, accept.subchannel_idx, accept.resp.unwrap()))
+    }
+
+    // Note: this is called from the IO thread, so must not sleep.
+    fn on_async_accept(&self, msg: io_channel::Msg) {
+        if let Some(accept) = self.async_accept.lock(line!()).as_mut() {
+            assert_eq!(accept.req_id, msg.id);
+            accept.resp = Some(msg);
+        }
+        self.notify_poll();
+    }
+
+    fn readiness(&self) -> u32 {
+        match self.async_accept.lock(line!()).as_ref() {
+            Some(accept) if accept.resp.is_some() => crate::poll::READABLE,
+            _ => 0,
+        }
+    }
+
+    fn notify_poll(&self) {
+        crate::poll::notify(&self.poll_registration, || self.readiness());
+    }
+}
+
+pub struct TcpListener {
+    inner: Arc<TcpListenerImpl>,
+}
+
+impl TcpListener {
+    pub fn bind(socket_addr: &SocketAddr) -> Result<TcpListener, ErrorCode> {
+        let req = rt_api::net::bind_tcp_listener_request(socket_addr, None);
+        let channel = NET.lock(line!()).reserve_channel();
+        let resp = channel.send_receive(req);
+        if resp.status().is_err() {
+            NET.lock(line!()).release_channel(channel);
+            return Err(resp.status());
+        }
+
+        let inner = Arc::new(TcpListenerImpl {
+            socket_addr: *socket_addr,
+            channel: channel.clone(),
+            handle: resp.handle,
+            nonblocking: AtomicBool::new(false),
+            async_accept: crate::util::SpinLock::new(None),
+            poll_registration: crate::util::SpinLock::new(None),
+        });
+        channel.tcp_listener_created(&inner);
+
+        #[cfg(debug_assertions)]
+        moturus_log!(
+            "{}:{} new TcpListener {:?}",
+            file!(),
+            line!(),
+            inner.socket_addr
+        );
+
+        Ok(Self { inner })
+    }
+
+    pub fn socket_addr(&self) -> Result<SocketAddr, ErrorCode> {
+        Ok(self.inner.socket_addr)
+    }
+
+    pub fn accept(&self) -> Result<(TcpStream, SocketAddr), ErrorCode> {
+        // Because a listener can spawn thousands, millions of sockets
+        // (think a long-running web server), we cannot use the listener's
+        // channel for incoming connections.
+
+        if let Some((channel, subchannel_idx, resp)) = self.inner.take_async_accept() {
+            self.inner.start_async_accept();
+            return self.inner.on_accepted(channel, subchannel_idx, resp);
+        }
+        if self.inner.nonblocking.load(Ordering::Relaxed) {
+            // Nonblocking accepts don't wait: they pick up what an accept
+            // request sent earlier has brought in, and send the next one.
+            self.inner.start_async_accept();
+            return Err(ErrorCode::NotReady);
+        }
+
+        let channel = NET.lock(line!()).reserve_channel();
+        let subchannel_idx = channel.reserve_subchannel();
+        let subchannel_mask = rt_api::net::io_subchannel_mask(subchannel_idx);
+
+        let req = rt_api::net::accept_tcp_listener_request(self.inner.handle, subchannel_mask);
+        let resp = channel.send_receive(req);
+        self.inner.on_accepted(channel, subchannel_idx, resp)
+    }
+
+    pub fn duplicate(&self) -> Result<TcpListener, ErrorCode> {
+        Ok(TcpListener {
+            inner: self.inner.clone(),
+        })
+    }
+
+    pub fn set_ttl(&self, _ttl: u32) -> Result<(), ErrorCode> {
+        todo!()
+    }
+
+    pub fn ttl(&self) -> Result<u32, ErrorCode> {
+        todo!()
+    }
+
+    // sys-io never refuses to bind because of connections lingering in
+    // TIME-WAIT, so SO_REUSEADDR is always on.
+    pub fn set_reuse_address(&self, reuse: bool) -> Result<(), ErrorCode> {
+        if reuse {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotImplemented)
+        }
+    }
+
+    pub fn reuse_address(&self) -> Result<bool, ErrorCode> {
+        Ok(true)
+    }
+
+    fn set_buffer_size(&self, option: u64, size: usize) -> Result<(), ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_TCP_LISTENER_SET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = option;
+        req.payload.args_32_mut()[2] = size.min(u32::MAX as usize) as u32;
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(())
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    fn buffer_size(&self, option: u64) -> Result<usize, ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_TCP_LISTENER_GET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = option;
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(resp.payload.args_32()[0] as usize)
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    // Buffer sizes apply to streams accepted from now on; sys-io clamps them
+    // to the range it supports.
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<(), ErrorCode> {
+        self.set_buffer_size(rt_api::net::TCP_OPTION_SEND_BUFFER, size)
+    }
+
+    pub fn send_buffer_size(&self) -> Result<usize, ErrorCode> {
+        self.buffer_size(rt_api::net::TCP_OPTION_SEND_BUFFER)
+    }
+
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<(), ErrorCode> {
+        self.set_buffer_size(rt_api::net::TCP_OPTION_RECV_BUFFER, size)
+    }
+
+    pub fn recv_buffer_size(&self) -> Result<usize, ErrorCode> {
+        self.buffer_size(rt_api::net::TCP_OPTION_RECV_BUFFER)
+    }
+
+    // Listeners bound to [::] are dual-stack; there is no way to opt out.
+    pub fn set_only_v6(&self, only_v6: bool) -> Result<(), ErrorCode> {
+        if only_v6 {
+            Err(ErrorCode::NotImplemented)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn only_v6(&self) -> Result<bool, ErrorCode> {
+        Ok(false)
+    }
+
+    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
+        // We don't have this unixism.
+        Ok(None)
+    }
+
+    // accept()s blocked at the time keep waiting for a connection.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
+        self.inner.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn register(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        self.inner.start_async_accept();
+        crate::poll::register(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
+    }
+
+    pub fn reregister(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::reregister(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
+    }
+
+    pub fn deregister(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        crate::poll::deregister(&self.inner.poll_registration, poll)
+    }
+}
+
+impl core::fmt::Debug for TcpListener {
+    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        todo!()
+    }
+}
+
+// A datagram received from sys-io, waiting to be read.
+struct Datagram {
+    page: io_channel::IoPage,
+    len: usize,
+    addr: SocketAddr,
+}
+
+struct UdpSocketImpl {
+    channel: Arc<NetChannel>,
+    local_addr: SocketAddr,
+    handle: u64,
+
+    recv_queue: crate::util::SpinLock<VecDeque<Datagram>>,
+    rx_waiter: crate::util::SpinLock<Option<SysHandle>>,
+
+    // Set by connect(): the default destination, and the only source accepted.
+    peer_addr: crate::util::SpinLock<Option<SocketAddr>>,
+
+    // The error of a send that failed in sys-io (zero if none), reported
+    // by the next send or by take_error().
+    tx_error: AtomicU16,
+
+    rx_timeout_ns: AtomicU64,
+    tx_timeout_ns: AtomicU64,
+    nonblocking: AtomicBool,
+    tx_waiting: AtomicBool, // As in TcpStreamImpl.
+    broadcast: AtomicBool,
+
+    subchannel_idx: usize, // Never changes.
+    subchannel_mask: u64,  // Never changes.
+
+    poll_registration: crate::util::SpinLock<Option<crate::poll::Registration>>,
+}
+
+impl Drop for UdpSocketImpl {
+    fn drop(&mut self) {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_UDP_SOCKET_DROP;
+        req.handle = self.handle;
+
+        if moto_sys::UserThreadControlBlock::get().self_handle
+            == self.channel.io_thread_wake_handle.load(Ordering::Relaxed)
+        {
+            // We cannot do send_receive here because it will block the IO thread.
+            self.channel.send_queue.push(req).unwrap(); // TODO: don't panic on failure.
+        } else {
+            let _ = self.channel.send_receive(req);
+        }
+
+        // Free up server-allocated pages.
+        self.recv_queue.lock(line!()).clear();
+
+        self.channel
+            .udp_socket_dropped(self.handle, self.subchannel_idx);
+    }
+}
+
+impl UdpSocketImpl {
+    // Note: this is called from the IO thread, so must not sleep.
+    fn process_incoming_msg(&self, msg: io_channel::Msg) {
+        assert_eq!(msg.command, rt_api::net::CMD_UDP_SOCKET_RX);
+
+        let page = match self
+            .channel
+            .conn
+            .get_page(rt_api::net::udp_socket_datagram_page(&msg))
+        {
+            Ok(page) => page,
+            Err(err) => {
+                moturus_log!("{}:{} bad RX page: {:?}", file!(), line!(), err);
+                return;
+            }
+        };
+        let addr = rt_api::net::get_socket_addr(&msg.payload).unwrap();
+
+        self.recv_queue.lock(line!()).push_back(Datagram {
+            page,
+            len: (msg.flags as usize).min(rt_api::net::UDP_MAX_DATAGRAM),
+            addr,
+        });
+    }
+
+    // Copies the next datagram, if any, into buf, truncating it if buf is too small.
+    fn poll_rx<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+        peek: bool,
+    ) -> Option<(usize, SocketAddr)> {
+        let peer_addr = *self.peer_addr.lock(line!());
+        let mut recv_queue = self.recv_queue.lock(line!());
+
+        loop {
+            let datagram = recv_queue.front()?;
+            if let Some(peer) = peer_addr {
+                if datagram.addr != peer {
+                    recv_queue.pop_front();
+                    continue;
+                }
+            }
+
+            let sz = scatter(&datagram.page.bytes()[..datagram.len], bufs);
+            let addr = datagram.addr;
+            if !peek {
+                recv_queue.pop_front();
+            }
+
+            return Some((sz, addr));
+        }
+    }
+
+    // Note: this is called from the IO thread, so must not sleep.
+    fn on_tx_error(&self, err: ErrorCode) {
+        self.tx_error.store(err as u16, Ordering::Relaxed);
+    }
+
+    fn take_tx_error(&self) -> Option<ErrorCode> {
+        match self.tx_error.swap(0, Ordering::Relaxed) {
+            0 => None,
+            status => Some(ErrorCode::from_u16(status)),
+        }
+    }
+
+    fn readiness(&self) -> u32 {
+        let mut readiness = 0;
+        if !self.recv_queue.lock(line!()).is_empty() {
+            readiness |= crate::poll::READABLE;
+        }
+        if self.channel.conn.alloc_page(self.subchannel_mask).is_ok() {
+            readiness |= crate::poll::WRITABLE;
+        }
+        if self.tx_error.load(Ordering::Relaxed) != 0 {
+            readiness |= crate::poll::ERROR;
+        }
+        readiness
+    }
+
+    fn notify_poll(&self) {
+        crate::poll::notify(&self.poll_registration, || self.readiness());
+    }
+}
+
+// Sockets that can be registered with a Poll.
+trait PollSource: Send + Sync + 'static {
+    fn readiness(&self) -> u32;
+}
+
+impl PollSource for TcpStreamImpl {
+    fn readiness(&self) -> u32 {
+        TcpStreamImpl::readiness(self)
+    }
+}
+
+impl PollSource for TcpListenerImpl {
+    fn readiness(&self) -> u32 {
+        TcpListenerImpl::readiness(self)
+    }
+}
+
+impl PollSource for UdpSocketImpl {
+    fn readiness(&self) -> u32 {
+        UdpSocketImpl::readiness(self)
+    }
+}
+
+impl PollSource for LocalSocketImpl {
+    fn readiness(&self) -> u32 {
+        LocalSocketImpl::readiness(self)
+    }
+}
+
+// Poll holds sockets weakly: it must not keep them from being dropped.
+fn readiness_fn<T: PollSource>(source: &Arc<T>) -> crate::poll::ReadinessFn {
+    let source = Arc::downgrade(source);
+    Arc::new(move || source.upgrade().map(|source| source.readiness()))
+}
+
+fn timeout_to_ns(timeout: Option<Duration>) -> u64 {
+    match timeout {
+        Some(timo) => timo.as_nanos().min(u64::MAX as u128) as u64,
+        None => u64::MAX,
+    }
+}
+
+fn timeout_from_ns(timo_ns: u64) -> Option<Duration> {
+    if timo_ns == u64::MAX {
+        None
+    } else {
+        Some(Duration::from_nanos(timo_ns))
+    }
+}
+
+// Scatter/gather for the *_vectored calls. The buffers are anything that derefs
+// to a byte slice: plain slices, or std::io::IoSlice/IoSliceMut.
+
+fn total_len<B: Deref<Target = [u8]>>(bufs: &[B]) -> usize {
+    bufs.iter().map(|buf| buf.len()).sum()
+}
+
+// Copies as much of bufs as fits into dst; returns the number of bytes copied.
+fn gather<B: Deref<Target = [u8]>>(bufs: &[B], dst: &mut [u8]) -> usize {
+    let mut copied = 0;
+    for buf in bufs {
+        let sz = buf.len().min(dst.len() - copied);
+        dst[copied..(copied + sz)].copy_from_slice(&buf[..sz]);
+        copied += sz;
+        if copied == dst.len() {
+            break;
+        }
+    }
+    copied
+}
+
+// Copies as much of src as fits into bufs; returns the number of bytes copied.
+fn scatter<B: DerefMut<Target = [u8]>>(src: &[u8], bufs: &mut [B]) -> usize {
+    let mut copied = 0;
+    for buf in bufs {
+        let sz = buf.len().min(src.len() - copied);
+        buf[..sz].copy_from_slice(&src[copied..(copied + sz)]);
+        copied += sz;
+        if copied == src.len() {
+            break;
+        }
+    }
+    copied
+}
+
+pub struct UdpSocket {
+    inner: Arc<UdpSocketImpl>,
+}
+
+impl UdpSocket {
+    pub fn bind(socket_addr: &SocketAddr) -> Result<UdpSocket, ErrorCode> {
+        let channel = NET.lock(line!()).reserve_channel();
+        let subchannel_idx = channel.reserve_subchannel();
+
+        let resp = channel.send_receive(rt_api::net::udp_socket_bind_request(
+            socket_addr,
+            subchannel_idx,
+        ));
+        if resp.status().is_err() {
+            channel.release_subchannel(subchannel_idx);
+            NET.lock(line!()).release_channel(channel);
+            return Err(resp.status());
+        }
+
+        let inner = Arc::new(UdpSocketImpl {
+            channel: channel.clone(),
+            local_addr: rt_api::net::get_socket_addr(&resp.payload).unwrap(),
+            handle: resp.handle,
+            recv_queue: crate::util::SpinLock::new(VecDeque::new()),
+            rx_waiter: crate::util::SpinLock::new(None),
+            peer_addr: crate::util::SpinLock::new(None),
+            tx_error: AtomicU16::new(0),
+            rx_timeout_ns: AtomicU64::new(u64::MAX),
+            tx_timeout_ns: AtomicU64::new(u64::MAX),
+            nonblocking: AtomicBool::new(false),
+            tx_waiting: AtomicBool::new(false),
+            broadcast: AtomicBool::new(false),
+            subchannel_idx,
+            subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+            poll_registration: crate::util::SpinLock::new(None),
+        });
+        channel.udp_socket_created(&inner);
+
+        #[cfg(debug_assertions)]
+        moturus_log!(
+            "{}:{} new UdpSocket {:?} 0x{:x}",
+            file!(),
+            line!(),
+            inner.local_addr,
+            inner.handle
+        );
+
+        Ok(Self { inner })
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr, ErrorCode> {
+        (*self.inner.peer_addr.lock(line!())).ok_or(ErrorCode::NotFound)
+    }
+
+    pub fn socket_addr(&self) -> Result<SocketAddr, ErrorCode> {
+        Ok(self.inner.local_addr)
+    }
+
+    fn recv_impl<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+        peek: bool,
+    ) -> Result<(usize, SocketAddr), ErrorCode> {
+        if let Some(res) = self.inner.poll_rx(bufs, peek) {
+            return Ok(res);
+        }
+        if self.inner.nonblocking.load(Ordering::Relaxed) {
+            return Err(ErrorCode::NotReady);
+        }
+
+        let rx_timeout = timeout_from_ns(self.inner.rx_timeout_ns.load(Ordering::Relaxed))
+            .map(|timo| Instant::now() + timo);
+
+        loop {
+            if let Some(timeout) = rx_timeout {
+                if Instant::now() >= timeout {
+                    return Err(ErrorCode::TimedOut);
+                }
+            }
+
+            {
+                // Store this thread's handle so that it is woken when a datagram arrives.
+                *self.inner.rx_waiter.lock(line!()) =
+                    Some(moto_sys::UserThreadControlBlock::get().self_handle.into());
+            }
+
+            // Re-check for incoming datagrams.
+            if let Some(res) = self.inner.poll_rx(bufs, peek) {
+                *self.inner.rx_waiter.lock(line!()) = None;
+                return Ok(res);
+            }
+
+            self.inner.channel.maybe_wake_io_thread();
+            if let Err(err) =
+                moto_sys::SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, rx_timeout)
+            {
+                assert_eq!(err, ErrorCode::TimedOut);
+            }
+
+            if let Some(res) = self.inner.poll_rx(bufs, peek) {
+                return Ok(res);
+            }
+        }
+    }
+
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), ErrorCode> {
+        self.recv_impl(&mut [buf], false)
+    }
+
+    pub fn peek_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), ErrorCode> {
+        self.recv_impl(&mut [buf], true)
+    }
+
+    // Scatters a datagram over bufs; what does not fit is dropped, as with recv_from().
+    pub fn recv_from_vectored<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+    ) -> Result<(usize, SocketAddr), ErrorCode> {
+        self.recv_impl(bufs, false)
+    }
+
+    pub fn peek_from_vectored<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+    ) -> Result<(usize, SocketAddr), ErrorCode> {
+        self.recv_impl(bufs, true)
+    }
+
+    pub fn send_to(&self, buf: &[u8], addr: &SocketAddr) -> Result<usize, ErrorCode> {
+        self.send_to_vectored(&[buf], addr)
+    }
+
+    // Sends bufs, concatenated, as a single datagram.
+    pub fn send_to_vectored<B: Deref<Target = [u8]>>(
+        &self,
+        bufs: &[B],
+        addr: &SocketAddr,
+    ) -> Result<usize, ErrorCode> {
+        if let Some(err) = self.inner.take_tx_error() {
+            return Err(err);
+        }
+        let len = total_len(bufs);
+        if len > rt_api::net::UDP_MAX_DATAGRAM {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        if let SocketAddr::V4(addr) = addr {
+            if addr.ip().is_broadcast() && !self.inner.broadcast.load(Ordering::Relaxed) {
+                return Err(ErrorCode::NotAllowed);
+            }
+        }
+
+        let abs_timeout = timeout_from_ns(self.inner.tx_timeout_ns.load(Ordering::Relaxed))
+            .map(|timo| Instant::now() + timo);
+
+        // Pages are freed as sys-io sends datagrams out, so we wait for one,
+        // sleeping exponentially longer (up to a limit).
+        let mut sleep_timo_usec = 1;
+        let io_page = loop {
+            match self
+                .inner
+                .channel
+                .conn
+                .alloc_page(self.inner.subchannel_mask)
+            {
+                Ok(page) => break page,
+                Err(_) => {
+                    if self.inner.nonblocking.load(Ordering::Relaxed) {
+                        self.inner.channel.wait_for_tx_page(
+                            self.inner.handle,
+                            rt_api::net::TX_WAIT_UDP,
+                            &self.inner.tx_waiting,
+                        );
+                        return Err(ErrorCode::NotReady);
+                    }
+
+                    let now = Instant::now();
+                    if let Some(timo) = abs_timeout {
+                        if now >= timo {
+                            return Err(ErrorCode::TimedOut);
+                        }
+                    }
+
+                    let mut sleep_timo = now + Duration::from_micros(sleep_timo_usec);
+                    if let Some(timo) = abs_timeout {
+                        if timo < sleep_timo {
+                            sleep_timo = timo;
+                        }
+                    }
+                    sleep_timo_usec = (sleep_timo_usec * 2).min(100_000);
+
+                    let _ = moto_sys::SysCpu::wait(
+                        &mut [],
+                        SysHandle::NONE,
+                        SysHandle::NONE,
+                        Some(sleep_timo),
+                    );
+                }
+            }
+        };
+
+        gather(bufs, &mut io_page.bytes_mut()[..len]);
+        self.inner
+            .channel
+            .send_msg(rt_api::net::udp_socket_datagram_msg(
+                rt_api::net::CMD_UDP_SOCKET_TX,
+                self.inner.handle,
+                io_page,
+                len,
+                addr,
+            ));
+
+        Ok(len)
+    }
+
+    pub fn duplicate(&self) -> Result<UdpSocket, ErrorCode> {
+        Ok(UdpSocket {
+            inner: self.inner.clone(),
+        })
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner
+            .rx_timeout_ns
+            .store(timeout_to_ns(timeout), Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner
+            .tx_timeout_ns
+            .store(timeout_to_ns(timeout), Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn read_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(timeout_from_ns(
+            self.inner.rx_timeout_ns.load(Ordering::Relaxed),
+        ))
+    }
+
+    pub fn write_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(timeout_from_ns(
+            self.inner.tx_timeout_ns.load(Ordering::Relaxed),
+        ))
+    }
+
+    pub fn set_broadcast(&self, broadcast: bool) -> Result<(), ErrorCode> {
+        self.inner.broadcast.store(broadcast, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn broadcast(&self) -> Result<bool, ErrorCode> {
+        Ok(self.inner.broadcast.load(Ordering::Relaxed))
+    }
+
+    // sys-io sends with the unicast TTL and does not loop datagrams back; IPv6
+    // multicast (MLD) is not supported.
+
+    pub fn set_multicast_loop_v4(&self, _: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn multicast_loop_v4(&self) -> Result<bool, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn set_multicast_ttl_v4(&self, _: u32) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn multicast_ttl_v4(&self) -> Result<u32, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn set_multicast_loop_v6(&self, _: bool) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn multicast_loop_v6(&self) -> Result<bool, ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn join_multicast_v4(&self, group: &Ipv4Addr, iface: &Ipv4Addr) -> Result<(), ErrorCode> {
+        self.set_multicast_membership(rt_api::net::UDP_OPTION_JOIN_MULTICAST_V4, group, iface)
+    }
+
+    pub fn join_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    pub fn leave_multicast_v4(&self, group: &Ipv4Addr, iface: &Ipv4Addr) -> Result<(), ErrorCode> {
+        self.set_multicast_membership(rt_api::net::UDP_OPTION_LEAVE_MULTICAST_V4, group, iface)
+    }
+
+    pub fn leave_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotImplemented)
+    }
+
+    fn set_multicast_membership(
+        &self,
+        option: u64,
+        group: &Ipv4Addr,
+        iface: &Ipv4Addr,
+    ) -> Result<(), ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_UDP_SOCKET_SET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = option;
+        req.payload.args_32_mut()[2] = u32::from(*group);
+        req.payload.args_32_mut()[3] = u32::from(*iface);
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(())
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> Result<(), ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_UDP_SOCKET_SET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = rt_api::net::UDP_OPTION_TTL;
+        req.payload.args_32_mut()[2] = ttl;
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(())
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    pub fn ttl(&self) -> Result<u32, ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_UDP_SOCKET_GET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = rt_api::net::UDP_OPTION_TTL;
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(resp.payload.args_32()[0])
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    // Caps how fast sys-io sends the socket's datagrams out, in bytes per second.
+    pub fn set_tx_rate_limit(&self, rate: Option<u64>) -> Result<(), ErrorCode> {
+        if rate == Some(0) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_UDP_SOCKET_SET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = rt_api::net::UDP_OPTION_TX_RATE_LIMIT;
+        req.payload.args_64_mut()[1] = rate.unwrap_or(0);
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(())
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    pub fn tx_rate_limit(&self) -> Result<Option<u64>, ErrorCode> {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_UDP_SOCKET_GET_OPTION;
+        req.handle = self.inner.handle;
+        req.payload.args_64_mut()[0] = rt_api::net::UDP_OPTION_TX_RATE_LIMIT;
+        let resp = self.inner.channel.send_receive(req);
+
+        if resp.status().is_ok() {
+            Ok(Some(resp.payload.args_64()[0]).filter(|rate| *rate != 0))
+        } else {
+            Err(resp.status())
+        }
+    }
+
+    // The error of a send that failed after send_to() returned.
+    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
+        Ok(self.inner.take_tx_error())
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
+        self.inner.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn register(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::register(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
+    }
+
+    pub fn reregister(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::reregister(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
+    }
+
+    pub fn deregister(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        crate::poll::deregister(&self.inner.poll_registration, poll)
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.recv_from(buf).map(|(sz, _)| sz)
+    }
+
+    pub fn peek(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.peek_from(buf).map(|(sz, _)| sz)
+    }
+
+    pub fn send(&self, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let peer_addr = self.peer_addr()?;
+        self.send_to(buf, &peer_addr)
+    }
+
+    pub fn recv_vectored<B: DerefMut<Target = [u8]>>(
+        &self,
+        bufs: &mut [B],
+    ) -> Result<usize, ErrorCode> {
+        self.recv_from_vectored(bufs).map(|(sz, _)| sz)
+    }
+
+    pub fn send_vectored<B: Deref<Target = [u8]>>(&self, bufs: &[B]) -> Result<usize, ErrorCode> {
+        let peer_addr = self.peer_addr()?;
+        self.send_to_vectored(bufs, &peer_addr)
+    }
+
+    pub fn connect(&self, addr: &SocketAddr) -> Result<(), ErrorCode> {
+        // UDP "connections" are purely local: sys-io is not involved.
+        *self.inner.peer_addr.lock(line!()) = Some(*addr);
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("addr", &self.inner.local_addr)
+            .field("peer", &*self.inner.peer_addr.lock(line!()))
+            .field("handle", &self.inner.handle)
+            .finish()
+    }
+}
+
+// An echo reply received from sys-io, waiting to be read.
+struct EchoReply {
+    page: io_channel::IoPage,
+    len: usize,
+    addr: IpAddr,
+    seq: u16,
+}
+
+struct PingSocketImpl {
+    channel: Arc<NetChannel>,
+    handle: u64,
+    ident: u16,
+
+    recv_queue: crate::util::SpinLock<VecDeque<EchoReply>>,
+    rx_waiter: crate::util::SpinLock<Option<SysHandle>>,
+    rx_timeout_ns: AtomicU64,
+
+    subchannel_idx: usize, // Never changes.
+    subchannel_mask: u64,  // Never changes.
+}
+
+impl Drop for PingSocketImpl {
+    fn drop(&mut self) {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_PING_SOCKET_DROP;
+        req.handle = self.handle;
+
+        if moto_sys::UserThreadControlBlock::get().self_handle
+            == self.channel.io_thread_wake_handle.load(Ordering::Relaxed)
+        {
+            // We cannot do send_receive here because it will block the IO thread.
+            self.channel.send_queue.push(req).unwrap(); // TODO: don't panic on failure.
+        } else {
+            let _ = self.channel.send_receive(req);
+        }
+
+        // Free up server-allocated pages.
+        self.recv_queue.lock(line!()).clear();
+
+        self.channel
+            .ping_socket_dropped(self.handle, self.subchannel_idx);
+    }
+}
+
+impl PingSocketImpl {
+    // Note: this is called from the IO thread, so must not sleep.
+    fn process_incoming_msg(&self, msg: io_channel::Msg) {
+        assert_eq!(msg.command, rt_api::net::CMD_PING_SOCKET_RX);
+
+        let page = match self
+            .channel
+            .conn
+            .get_page(rt_api::net::ping_socket_page(&msg))
+        {
+            Ok(page) => page,
+            Err(err) => {
+                moturus_log!("{}:{} bad RX page: {:?}", file!(), line!(), err);
+                return;
+            }
+        };
+        let Ok(addr) = rt_api::net::ping_socket_addr(&msg) else {
+            return;
+        };
+
+        self.recv_queue.lock(line!()).push_back(EchoReply {
+            page,
+            len: (msg.flags as usize).min(rt_api::net::PING_MAX_PAYLOAD),
+            addr,
+            seq: rt_api::net::ping_socket_seq(&msg),
+        });
+    }
+
+    fn poll_rx(&self, buf: &mut [u8]) -> Option<(usize, IpAddr, u16)> {
+        let reply = self.recv_queue.lock(line!()).pop_front()?;
+        let sz = reply.len.min(buf.len());
+        buf[..sz].copy_from_slice(&reply.page.bytes()[..sz]);
+        Some((sz, reply.addr, reply.seq))
+    }
+}
+
+/// An unprivileged ICMP echo socket: sys-io picks the echo identifier and
+/// builds the packets; the socket sends requests and receives the replies.
+pub struct PingSocket {
+    inner: Arc<PingSocketImpl>,
+}
+
+impl PingSocket {
+    pub fn open() -> Result<PingSocket, ErrorCode> {
+        let channel = NET.lock(line!()).reserve_channel();
+        let subchannel_idx = channel.reserve_subchannel();
+
+        let resp = channel.send_receive(rt_api::net::ping_socket_open_request(subchannel_idx));
+        if resp.status().is_err() {
+            channel.release_subchannel(subchannel_idx);
+            NET.lock(line!()).release_channel(channel);
+            return Err(resp.status());
+        }
+
+        let inner = Arc::new(PingSocketImpl {
+            channel: channel.clone(),
+            handle: resp.handle,
+            ident: resp.payload.args_16()[0],
+            recv_queue: crate::util::SpinLock::new(VecDeque::new()),
+            rx_waiter: crate::util::SpinLock::new(None),
+            rx_timeout_ns: AtomicU64::new(u64::MAX),
+            subchannel_idx,
+            subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+        });
+        channel.ping_socket_created(&inner);
+
+        Ok(Self { inner })
+    }
+
+    /// The ICMP echo identifier of the socket's requests and replies.
+    pub fn ident(&self) -> u16 {
+        self.inner.ident
+    }
+
+    /// Sends an echo request with the payload; errors on the way (e.g. no route)
+    /// are not reported: the reply just never comes.
+    pub fn send_to(&self, payload: &[u8], addr: &IpAddr, seq: u16) -> Result<(), ErrorCode> {
+        if payload.len() > rt_api::net::PING_MAX_PAYLOAD {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        // Pages are freed as sys-io sends requests out.
+        let mut sleep_timo_usec = 1;
+        let io_page = loop {
+            match self
+                .inner
+                .channel
+                .conn
+                .alloc_page(self.inner.subchannel_mask)
+            {
+                Ok(page) => break page,
+                Err(_) => {
+                    let _ = moto_sys::SysCpu::wait(
+                        &mut [],
+                        SysHandle::NONE,
+                        SysHandle::NONE,
+                        Some(Instant::now() + Duration::from_micros(sleep_timo_usec)),
+                    );
+                    sleep_timo_usec = (sleep_timo_usec * 2).min(100_000);
+                }
+            }
+        };
+
+        io_page.bytes_mut()[..payload.len()].copy_from_slice(payload);
+        self.inner.channel.send_msg(rt_api::net::ping_socket_msg(
+            rt_api::net::CMD_PING_SOCKET_TX,
+            self.inner.handle,
+            io_page,
+            payload.len(),
+            addr,
+            seq,
+        ));
+
+        Ok(())
+    }
+
+    /// Receives the next echo reply: its payload (truncated to fit into buf),
+    /// where it came from, and its sequence number.
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, IpAddr, u16), ErrorCode> {
+        if let Some(res) = self.inner.poll_rx(buf) {
+            return Ok(res);
+        }
+
+        let rx_timeout = timeout_from_ns(self.inner.rx_timeout_ns.load(Ordering::Relaxed))
+            .map(|timo| Instant::now() + timo);
+
+        loop {
+            if let Some(timeout) = rx_timeout {
+                if Instant::now() >= timeout {
+                    return Err(ErrorCode::TimedOut);
+                }
+            }
+
+            {
+                // Store this thread's handle so that it is woken when a reply arrives.
+                *self.inner.rx_waiter.lock(line!()) =
+                    Some(moto_sys::UserThreadControlBlock::get().self_handle.into());
+            }
+
+            if let Some(res) = self.inner.poll_rx(buf) {
+                *self.inner.rx_waiter.lock(line!()) = None;
+                return Ok(res);
+            }
+
+            self.inner.channel.maybe_wake_io_thread();
+            if let Err(err) =
+                moto_sys::SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, rx_timeout)
+            {
+                assert_eq!(err, ErrorCode::TimedOut);
+            }
+
+            if let Some(res) = self.inner.poll_rx(buf) {
+                return Ok(res);
+            }
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner
+            .rx_timeout_ns
+            .store(timeout_to_ns(timeout), Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn read_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(timeout_from_ns(
+            self.inner.rx_timeout_ns.load(Ordering::Relaxed),
+        ))
+    }
+}
+
+impl core::fmt::Debug for PingSocket {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PingSocket")
+            .field("ident", &self.inner.ident)
+            .field("handle", &self.inner.handle)
+            .finish()
+    }
+}
+
+// Local (Unix-domain) sockets. sys-io moves the data between processes
+// (see sys-io/src/net/local_socket.rs); here they look like std::os::unix::net.
+
+/// The name of a local socket: an absolute path, an abstract name, or nothing.
+#[derive(Clone, PartialEq, Eq)]
+pub struct UnixSocketAddr {
+    name: Vec<u8>, // Abstract names start with a zero byte.
+}
+
+impl UnixSocketAddr {
+    pub fn from_pathname(path: &str) -> Result<Self, ErrorCode> {
+        if !path.starts_with('/')
+            || path.len() > rt_api::net::LOCAL_NAME_MAX
+            || path.as_bytes().contains(&0)
+        {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        Ok(Self {
+            name: path.as_bytes().to_vec(),
+        })
+    }
+
+    pub fn from_abstract_name(name: &[u8]) -> Result<Self, ErrorCode> {
+        if name.len() >= rt_api::net::LOCAL_NAME_MAX {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let mut bytes = Vec::with_capacity(name.len() + 1);
+        bytes.push(0);
+        bytes.extend_from_slice(name);
+        Ok(Self { name: bytes })
+    }
+
+    pub fn unnamed() -> Self {
+        Self { name: Vec::new() }
+    }
+
+    pub fn is_unnamed(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    pub fn as_pathname(&self) -> Option<&str> {
+        if self.name.first() == Some(&b'/') {
+            core::str::from_utf8(&self.name).ok()
+        } else {
+            None
+        }
+    }
+
+    pub fn as_abstract_name(&self) -> Option<&[u8]> {
+        if self.name.first() == Some(&0) {
+            Some(&self.name[1..])
+        } else {
+            None
+        }
+    }
+}
+
+impl core::fmt::Debug for UnixSocketAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(path) = self.as_pathname() {
+            write!(f, "{:?} (pathname)", path)
+        } else if let Some(name) = self.as_abstract_name() {
+            write!(
+                f,
+                "{:?} (abstract)",
+                alloc::string::String::from_utf8_lossy(name)
+            )
+        } else {
+            write!(f, "(unnamed)")
+        }
+    }
+}
+
+// Allocates a page in a fresh subchannel and puts the name into it.
+fn local_name_page(
+    channel: &NetChannel,
+    subchannel_idx: usize,
+    addr: &UnixSocketAddr,
+) -> Result<io_channel::IoPage, ErrorCode> {
+    let page = channel
+        .conn
+        .alloc_page(rt_api::net::io_subchannel_mask(subchannel_idx))?;
+    page.bytes_mut()[..addr.name.len()].copy_from_slice(&addr.name);
+    Ok(page)
+}
+
+// A chunk of a stream or a whole datagram received from sys-io.
+struct LocalRx {
+    page: Option<io_channel::IoPage>, // None marks the end of stream.
+    len: usize,
+    consumed: usize,
+    src_name: Vec<u8>, // Datagrams only.
+    creds: rt_api::net::LocalCreds,
+}
+
+struct LocalSocketImpl {
+    channel: Arc<NetChannel>,
+    handle: u64,
+
+    local_addr: UnixSocketAddr,
+    // Streams: the listener connected to. Datagrams: set by connect(), the default
+    // destination and the only source accepted.
+    peer_addr: crate::util::SpinLock<Option<UnixSocketAddr>>,
+    peer_creds: Option<rt_api::net::LocalCreds>, // Streams and socket pairs.
+
+    recv_queue: crate::util::SpinLock<VecDeque<LocalRx>>,
+    rx_waiter: crate::util::SpinLock<Option<SysHandle>>,
+    read_shut: AtomicBool,
+
+    rx_timeout_ns: AtomicU64,
+    tx_timeout_ns: AtomicU64,
+    nonblocking: AtomicBool,
+    tx_waiting: AtomicBool, // As in TcpStreamImpl.
+
+    subchannel_idx: usize, // Never changes.
+    subchannel_mask: u64,  // Never changes.
+
+    poll_registration: crate::util::SpinLock<Option<crate::poll::Registration>>,
+}
+
+impl Drop for LocalSocketImpl {
+    fn drop(&mut self) {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_LOCAL_SOCKET_DROP;
+        req.handle = self.handle;
+
+        if moto_sys::UserThreadControlBlock::get().self_handle
+            == self.channel.io_thread_wake_handle.load(Ordering::Relaxed)
+        {
+            // We cannot do send_receive here because it will block the IO thread.
+            self.channel.send_queue.push(req).unwrap(); // TODO: don't panic on failure.
+        } else {
+            let _ = self.channel.send_receive(req);
+        }
+
+        // Free up server-allocated pages.
+        self.recv_queue.lock(line!()).clear();
+
+        self.channel
+            .local_socket_dropped(self.handle, self.subchannel_idx);
+    }
+}
+
+impl LocalSocketImpl {
+    fn new(
+        channel: &Arc<NetChannel>,
+        handle: u64,
+        local_addr: UnixSocketAddr,
+        peer_addr: Option<UnixSocketAddr>,
+        peer_creds: Option<rt_api::net::LocalCreds>,
+        subchannel_idx: usize,
+    ) -> Arc<Self> {
+        let inner = Arc::new(Self {
+            channel: channel.clone(),
+            handle,
+            local_addr,
+            peer_addr: crate::util::SpinLock::new(peer_addr),
+            peer_creds,
+            recv_queue: crate::util::SpinLock::new(VecDeque::new()),
+            rx_waiter: crate::util::SpinLock::new(None),
+            read_shut: AtomicBool::new(false),
+            rx_timeout_ns: AtomicU64::new(u64::MAX),
+            tx_timeout_ns: AtomicU64::new(u64::MAX),
+            nonblocking: AtomicBool::new(false),
+            tx_waiting: AtomicBool::new(false),
+            subchannel_idx,
+            subchannel_mask: rt_api::net::io_subchannel_mask(subchannel_idx),
+            poll_registration: crate::util::SpinLock::new(None),
+        });
+        channel.local_socket_created(&inner);
+
+        // sys-io holds incoming data until the socket is registered above.
+        inner.ack_rx(rt_api::net::LOCAL_RX_MAX_INFLIGHT);
+
+        #[cfg(debug_assertions)]
+        moturus_log!(
+            "{}:{} new local socket {:?} 0x{:x}",
+            file!(),
+            line!(),
+            inner.local_addr,
+            inner.handle
+        );
+
+        inner
+    }
+
+    fn ack_rx(&self, count: usize) {
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_LOCAL_SOCKET_RX_ACK;
+        req.handle = self.handle;
+        req.payload.args_32_mut()[0] = count as u32;
+        self.channel.send_msg(req);
+    }
+
+    // Note: this is called from the IO thread, so must not sleep.
+    fn process_incoming_msg(&self, msg: io_channel::Msg) {
+        assert_eq!(msg.command, rt_api::net::CMD_LOCAL_SOCKET_RX);
+
+        let creds = rt_api::net::get_local_creds(&msg.payload);
+        let Some(page_idx) = rt_api::net::local_socket_data_page(&msg) else {
+            self.recv_queue.lock(line!()).push_back(LocalRx {
+                page: None,
+                len: 0,
+                consumed: 0,
+                src_name: Vec::new(),
+                creds,
+            });
+            return;
+        };
+
+        let page = match self.channel.conn.get_page(page_idx) {
+            Ok(page) => page,
+            Err(err) => {
+                moturus_log!("{}:{} bad RX page: {:?}", file!(), line!(), err);
+                return;
+            }
+        };
+        let len = (msg.flags as usize).min(io_channel::PAGE_SIZE);
+        let name_len =
+            rt_api::net::local_socket_data_name_len(&msg).min(io_channel::PAGE_SIZE - len);
+        let src_name = page.bytes()[len..(len + name_len)].to_vec();
+
+        self.recv_queue.lock(line!()).push_back(LocalRx {
+            page: Some(page),
+            len,
+            consumed: 0,
+            src_name,
+            creds,
+        });
+    }
+
+    // Copies whatever stream data there is into buf. Returns Some(0) at the end of stream.
+    fn poll_stream_rx(&self, buf: &mut [u8], peek: bool) -> Option<usize> {
+        if buf.is_empty() || self.read_shut.load(Ordering::Relaxed) {
+            return Some(0);
+        }
+
+        let mut recv_queue = self.recv_queue.lock(line!());
+        let mut copied = 0;
+        let mut done = 0; // Chunks fully read.
+        for rx in recv_queue.iter_mut() {
+            let Some(page) = &rx.page else {
+                break;
+            };
+            let sz = (rx.len - rx.consumed).min(buf.len() - copied);
+            buf[copied..(copied + sz)]
+                .copy_from_slice(&page.bytes()[rx.consumed..(rx.consumed + sz)]);
+            copied += sz;
+            if !peek {
+                rx.consumed += sz;
+                if rx.consumed == rx.len {
+                    done += 1;
+                }
+            }
+            if copied == buf.len() {
+                break;
+            }
+        }
+
+        recv_queue.drain(..done);
+        // The end of stream stays in the queue, so that further reads see it.
+        let eof = recv_queue.front().is_some_and(|rx| rx.page.is_none());
+        core::mem::drop(recv_queue);
+
+        if done > 0 {
+            self.ack_rx(done);
+        }
+        if copied > 0 || eof {
+            Some(copied)
+        } else {
+            None
+        }
+    }
+
+    // Copies the next datagram, if any, into buf, truncating it if buf is too small.
+    fn poll_datagram_rx(
+        &self,
+        buf: &mut [u8],
+        peek: bool,
+    ) -> Option<(usize, UnixSocketAddr, rt_api::net::LocalCreds)> {
+        let peer_addr = self.peer_addr.lock(line!()).clone();
+        let mut recv_queue = self.recv_queue.lock(line!());
+        let mut done = 0;
+
+        let res = loop {
+            let Some(rx) = recv_queue.front() else {
+                break None;
+            };
+            if let Some(peer) = &peer_addr {
+                if rx.src_name != peer.name {
+                    recv_queue.pop_front();
+                    done += 1;
+                    continue;
+                }
+            }
+
+            let sz = rx.len.min(buf.len());
+            if let Some(page) = &rx.page {
+                buf[..sz].copy_from_slice(&page.bytes()[..sz]);
+            }
+            let res = (
+                sz,
+                UnixSocketAddr {
+                    name: rx.src_name.clone(),
+                },
+                rx.creds,
+            );
+            if !peek {
+                recv_queue.pop_front();
+                done += 1;
+            }
+            break Some(res);
+        };
+        core::mem::drop(recv_queue);
+
+        if done > 0 {
+            self.ack_rx(done);
+        }
+        res
+    }
+
+    // Polls until there is something, the timeout expires, or, if nonblocking, right away.
+    fn wait_rx<T>(&self, mut poll: impl FnMut() -> Option<T>) -> Result<T, ErrorCode> {
+        if let Some(res) = poll() {
+            return Ok(res);
+        }
+        if self.nonblocking.load(Ordering::Relaxed) {
+            return Err(ErrorCode::NotReady);
+        }
+
+        let rx_timeout = timeout_from_ns(self.rx_timeout_ns.load(Ordering::Relaxed))
+            .map(|timo| Instant::now() + timo);
+
+        loop {
+            if let Some(timeout) = rx_timeout {
+                if Instant::now() >= timeout {
+                    return Err(ErrorCode::TimedOut);
+                }
+            }
+
+            {
+                // Store this thread's handle so that it is woken when data arrives.
+                *self.rx_waiter.lock(line!()) =
+                    Some(moto_sys::UserThreadControlBlock::get().self_handle.into());
+            }
+
+            if let Some(res) = poll() {
+                *self.rx_waiter.lock(line!()) = None;
+                return Ok(res);
+            }
+
+            self.channel.maybe_wake_io_thread();
+            if let Err(err) =
+                moto_sys::SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, rx_timeout)
+            {
+                assert_eq!(err, ErrorCode::TimedOut);
+            }
+
+            if let Some(res) = poll() {
+                return Ok(res);
+            }
+        }
+    }
+
+    // Pages are freed as sys-io delivers data, so we wait for one,
+    // sleeping exponentially longer (up to a limit).
+    fn alloc_tx_page(&self) -> Result<io_channel::IoPage, ErrorCode> {
+        let abs_timeout = timeout_from_ns(self.tx_timeout_ns.load(Ordering::Relaxed))
+            .map(|timo| Instant::now() + timo);
+
+        let mut sleep_timo_usec = 1;
+        loop {
+            if let Ok(page) = self.channel.conn.alloc_page(self.subchannel_mask) {
+                return Ok(page);
+            }
+            if self.nonblocking.load(Ordering::Relaxed) {
+                self.channel.wait_for_tx_page(
+                    self.handle,
+                    rt_api::net::TX_WAIT_LOCAL,
+                    &self.tx_waiting,
+                );
+                return Err(ErrorCode::NotReady);
+            }
+
+            let now = Instant::now();
+            if let Some(timo) = abs_timeout {
+                if now >= timo {
+                    return Err(ErrorCode::TimedOut);
+                }
+            }
+
+            let mut sleep_timo = now + Duration::from_micros(sleep_timo_usec);
+            if let Some(timo) = abs_timeout {
+                if timo < sleep_timo {
+                    sleep_timo = timo;
+                }
+            }
+            sleep_timo_usec = (sleep_timo_usec * 2).min(100_000);
+
+            let _ =
+                moto_sys::SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, Some(sleep_timo));
+        }
+    }
+
+    // Returns once sys-io has handed the data to the receiver.
+    fn tx(&self, buf: &[u8], dest: &[u8]) -> Result<usize, ErrorCode> {
+        debug_assert!(buf.len() + dest.len() <= io_channel::PAGE_SIZE);
+        let io_page = self.alloc_tx_page()?;
+        io_page.bytes_mut()[..buf.len()].copy_from_slice(buf);
+        io_page.bytes_mut()[buf.len()..(buf.len() + dest.len())].copy_from_slice(dest);
+
+        let resp = self
+            .channel
+            .send_receive(rt_api::net::local_socket_data_msg(
+                rt_api::net::CMD_LOCAL_SOCKET_TX,
+                self.handle,
+                Some(io_page),
+                buf.len(),
+                dest.len(),
+            ));
+        if resp.status().is_err() {
+            return Err(resp.status());
+        }
+        Ok(buf.len())
+    }
+
+    fn shutdown(&self, read: bool, write: bool) -> Result<(), ErrorCode> {
+        let mut how = 0;
+        if read {
+            how |= rt_api::net::LOCAL_SHUT_RD;
+            self.read_shut.store(true, Ordering::Relaxed);
+        }
+        if write {
+            how |= rt_api::net::LOCAL_SHUT_WR;
+        }
+
+        let mut req = io_channel::Msg::new();
+        req.command = rt_api::net::CMD_LOCAL_SOCKET_SHUTDOWN;
+        req.handle = self.handle;
+        req.payload.args_64_mut()[0] = how;
+        let resp = self.channel.send_receive(req);
+        if resp.status().is_err() {
+            return Err(resp.status());
+        }
+        Ok(())
+    }
+
+    // Two connected unnamed sockets of the kind.
+    fn pair(kind: u8) -> Result<(Arc<Self>, Arc<Self>), ErrorCode> {
+        let channel = NET.lock(line!()).reserve_channel_pair();
+        let subchannel_1 = channel.reserve_subchannel();
+        let subchannel_2 = channel.reserve_subchannel();
+
+        let resp = channel.send_receive(rt_api::net::local_socket_pair_request(
+            kind,
+            subchannel_1,
+            subchannel_2,
+        ));
+        if resp.status().is_err() {
+            channel.release_subchannel(subchannel_1);
+            channel.release_subchannel(subchannel_2);
+            let mut net = NET.lock(line!());
+            net.release_channel(channel.clone());
+            net.release_channel(channel);
+            return Err(resp.status());
+        }
+
+        let creds = rt_api::net::get_local_creds(&resp.payload);
+        let first = Self::new(
+            &channel,
+            resp.handle,
+            UnixSocketAddr::unnamed(),
+            None,
+            Some(creds),
+            subchannel_1,
+        );
+        let second = Self::new(
+            &channel,
+            resp.payload.args_64()[0],
+            UnixSocketAddr::unnamed(),
+            None,
+            Some(creds),
+            subchannel_2,
+        );
+        Ok((first, second))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) {
+        self.rx_timeout_ns
+            .store(timeout_to_ns(timeout), Ordering::Relaxed);
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) {
+        self.tx_timeout_ns
+            .store(timeout_to_ns(timeout), Ordering::Relaxed);
+    }
+
+    fn read_timeout(&self) -> Option<Duration> {
+        timeout_from_ns(self.rx_timeout_ns.load(Ordering::Relaxed))
+    }
+
+    fn write_timeout(&self) -> Option<Duration> {
+        timeout_from_ns(self.tx_timeout_ns.load(Ordering::Relaxed))
+    }
+
+    fn readiness(&self) -> u32 {
+        let mut readiness = 0;
+        if self.read_shut.load(Ordering::Relaxed) {
+            readiness |= crate::poll::READABLE | crate::poll::READ_CLOSED;
+        } else if let Some(rx) = self.recv_queue.lock(line!()).front() {
+            readiness |= crate::poll::READABLE;
+            if rx.page.is_none() {
+                readiness |= crate::poll::READ_CLOSED;
+            }
+        }
+        if self.channel.conn.alloc_page(self.subchannel_mask).is_ok() {
+            readiness |= crate::poll::WRITABLE;
+        }
+        readiness
+    }
+
+    fn notify_poll(&self) {
+        crate::poll::notify(&self.poll_registration, || self.readiness());
+    }
+}
+
+pub struct UnixListener {
     channel: Arc<NetChannel>,
     handle: u64,
-    nonblocking: AtomicBool,
+    local_addr: UnixSocketAddr,
 }
 
-impl Drop for TcpListenerImpl {
+impl Drop for UnixListener {
     fn drop(&mut self) {
         let mut msg = io_channel::Msg::new();
-        msg.command = rt_api::net::CMD_TCP_LISTENER_DROP;
+        msg.command = rt_api::net::CMD_LOCAL_SOCKET_DROP;
         msg.handle = self.handle;
         self.channel.send_msg(msg);
-        self.channel.tcp_listener_dropped(self.handle)
+        NET.lock(line!()).release_channel(self.channel.clone());
     }
 }
 
-pub struct TcpListener {
-    inner: Arc<TcpListenerImpl>,
-}
-
-impl TcpListener {
-    pub fn bind(socket_addr: &SocketAddr) -> Result<TcpListener, ErrorCode> {
-        let req = rt_api::net::bind_tcp_listener_request(socket_addr, None);
+impl UnixListener {
+    pub fn bind(addr: &UnixSocketAddr) -> Result<UnixListener, ErrorCode> {
         let channel = NET.lock(line!()).reserve_channel();
-        let resp = channel.send_receive(req);
-        if resp.status().is_err() {
-            NET.lock(line!()).release_channel(channel);
-            return Err(resp.status());
-        }
+        // Listeners don't receive anything, but the name needs a page to travel in.
+        let subchannel_idx = channel.reserve_subchannel();
 
-        let inner = Arc::new(TcpListenerImpl {
-            socket_addr: *socket_addr,
-            channel: channel.clone(),
-            handle: resp.handle,
-            nonblocking: AtomicBool::new(false),
+        let resp = local_name_page(&channel, subchannel_idx, addr).map(|page| {
+            channel.send_receive(rt_api::net::local_socket_bind_request(
+                rt_api::net::LOCAL_KIND_LISTENER,
+                page,
+                addr.name.len(),
+                subchannel_idx,
+            ))
         });
-        channel.tcp_listener_created(&inner);
+        channel.release_subchannel(subchannel_idx);
+
+        let handle = match resp {
+            Ok(resp) if resp.status().is_ok() => resp.handle,
+            Ok(resp) => {
+                NET.lock(line!()).release_channel(channel);
+                return Err(resp.status());
+            }
+            Err(err) => {
+                NET.lock(line!()).release_channel(channel);
+                return Err(err);
+            }
+        };
 
         #[cfg(debug_assertions)]
-        moturus_log!(
-            "{}:{} new TcpListener {:?}",
-            file!(),
-            line!(),
-            inner.socket_addr
-        );
+        moturus_log!("{}:{} new UnixListener {:?}", file!(), line!(), addr);
 
-        Ok(Self { inner })
+        Ok(Self {
+            channel,
+            handle,
+            local_addr: addr.clone(),
+        })
     }
 
-    pub fn socket_addr(&self) -> Result<SocketAddr, ErrorCode> {
-        Ok(self.inner.socket_addr)
+    pub fn local_addr(&self) -> Result<UnixSocketAddr, ErrorCode> {
+        Ok(self.local_addr.clone())
     }
 
-    pub fn accept(&self) -> Result<(TcpStream, SocketAddr), ErrorCode> {
-        // Because a listener can spawn thousands, millions of sockets
-        // (think a long-running web server), we cannot use the listener's
-        // channel for incoming connections.
-
-        if self.inner.nonblocking.load(Ordering::Relaxed) {
-            todo!()
-        }
+    pub fn accept(&self) -> Result<(UnixStream, UnixSocketAddr), ErrorCode> {
+        // As with TCP, accepted streams go to other channels.
         let channel = NET.lock(line!()).reserve_channel();
         let subchannel_idx = channel.reserve_subchannel();
-        let subchannel_mask = rt_api::net::io_subchannel_mask(subchannel_idx);
 
-        let req = rt_api::net::accept_tcp_listener_request(self.inner.handle, subchannel_mask);
-        let resp = channel.send_receive(req);
+        let resp = channel.send_receive(rt_api::net::local_listener_accept_request(
+            self.handle,
+            subchannel_idx,
+        ));
         if resp.status().is_err() {
             channel.release_subchannel(subchannel_idx);
             NET.lock(line!()).release_channel(channel);
             return Err(resp.status());
         }
 
-        let remote_addr = rt_api::net::get_socket_addr(&resp.payload).unwrap();
-
-        let inner = Arc::new(TcpStreamImpl {
-            local_addr: self.inner.socket_addr,
-            remote_addr: remote_addr.clone(),
-            handle: resp.handle,
-            channel: channel.clone(),
-            recv_queue: crate::util::SpinLock::new(VecDeque::new()),
-            next_rx_seq: AtomicU64::new(1),
-            rx_buf: crate::util::SpinLock::new(None),
-            rx_waiter: crate::util::SpinLock::new(None),
-            tcp_state: AtomicU32::new(rt_api::net::TcpState::ReadWrite.into()),
-            rx_done: AtomicBool::new(false),
-            rx_timeout_ns: AtomicU64::new(u64::MAX),
-            tx_timeout_ns: AtomicU64::new(u64::MAX),
+        let inner = LocalSocketImpl::new(
+            &channel,
+            resp.handle,
+            self.local_addr.clone(),
+            None, // Connecting streams are unnamed.
+            Some(rt_api::net::get_local_creds(&resp.payload)),
             subchannel_idx,
-            subchannel_mask,
-            stats_rx_bytes: AtomicU64::new(0),
-            stats_tx_bytes: AtomicU64::new(0),
-        });
+        );
 
-        channel.tcp_stream_created(&inner);
-        inner.ack_rx();
+        Ok((UnixStream { inner }, UnixSocketAddr::unnamed()))
+    }
 
-        #[cfg(debug_assertions)]
-        moturus_log!(
-            "{}:{} new incoming TcpStream {:?} <- {:?} mask: 0x{:x}",
-            file!(),
-            line!(),
-            inner.local_addr,
-            inner.remote_addr,
-            inner.subchannel_mask
-        );
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
+        if nonblocking {
+            Err(ErrorCode::NotImplemented)
+        } else {
+            Ok(())
+        }
+    }
 
-        Ok((TcpStream { inner }, remote_addr))
+    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
+        Ok(None)
     }
+}
 
-    pub fn duplicate(&self) -> Result<TcpListener, ErrorCode> {
-        Ok(TcpListener {
-            inner: self.inner.clone(),
-        })
+impl core::fmt::Debug for UnixListener {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UnixListener")
+            .field("addr", &self.local_addr)
+            .field("handle", &self.handle)
+            .finish()
     }
+}
 
-    pub fn set_ttl(&self, _ttl: u32) -> Result<(), ErrorCode> {
-        todo!()
+pub struct UnixStream {
+    inner: Arc<LocalSocketImpl>,
+}
+
+impl UnixStream {
+    pub fn connect(addr: &UnixSocketAddr) -> Result<UnixStream, ErrorCode> {
+        let channel = NET.lock(line!()).reserve_channel();
+        let subchannel_idx = channel.reserve_subchannel();
+
+        let resp = local_name_page(&channel, subchannel_idx, addr).map(|page| {
+            channel.send_receive(rt_api::net::local_stream_connect_request(
+                page,
+                addr.name.len(),
+                subchannel_idx,
+            ))
+        });
+        let resp = match resp {
+            Ok(resp) if resp.status().is_ok() => resp,
+            res => {
+                channel.release_subchannel(subchannel_idx);
+                NET.lock(line!()).release_channel(channel);
+                return Err(match res {
+                    Ok(resp) => resp.status(),
+                    Err(err) => err,
+                });
+            }
+        };
+
+        let inner = LocalSocketImpl::new(
+            &channel,
+            resp.handle,
+            UnixSocketAddr::unnamed(),
+            Some(addr.clone()),
+            Some(rt_api::net::get_local_creds(&resp.payload)),
+            subchannel_idx,
+        );
+        Ok(Self { inner })
     }
 
-    pub fn ttl(&self) -> Result<u32, ErrorCode> {
-        todo!()
+    pub fn pair() -> Result<(UnixStream, UnixStream), ErrorCode> {
+        let (first, second) = LocalSocketImpl::pair(rt_api::net::LOCAL_KIND_STREAM)?;
+        Ok((Self { inner: first }, Self { inner: second }))
     }
 
-    pub fn set_only_v6(&self, _: bool) -> Result<(), ErrorCode> {
-        Err(ErrorCode::NotImplemented) // This is deprected since Rust 1.16
+    pub fn local_addr(&self) -> Result<UnixSocketAddr, ErrorCode> {
+        Ok(self.inner.local_addr.clone())
     }
 
-    pub fn only_v6(&self) -> Result<bool, ErrorCode> {
-        Err(ErrorCode::NotImplemented) // This is deprected since Rust 1.16
+    pub fn peer_addr(&self) -> Result<UnixSocketAddr, ErrorCode> {
+        Ok(self
+            .inner
+            .peer_addr
+            .lock(line!())
+            .clone()
+            .unwrap_or_else(UnixSocketAddr::unnamed))
     }
 
-    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
-        // We don't have this unixism.
-        Ok(None)
+    /// The process on the other end, as of when the stream was connected.
+    pub fn peer_cred(&self) -> Result<rt_api::net::LocalCreds, ErrorCode> {
+        self.inner.peer_creds.ok_or(ErrorCode::NotFound)
     }
 
-    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
-        self.inner.nonblocking.store(nonblocking, Ordering::Relaxed);
-        if nonblocking {
-            todo!("Kick existing blocking accept()s.");
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.inner.wait_rx(|| self.inner.poll_stream_rx(buf, false))
+    }
+
+    pub fn peek(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.inner.wait_rx(|| self.inner.poll_stream_rx(buf, true))
+    }
+
+    /// Writes at most a page; returns once the data has reached the reader's process.
+    pub fn write(&self, buf: &[u8]) -> Result<usize, ErrorCode> {
+        if buf.is_empty() {
+            return Ok(0);
         }
+        let sz = buf.len().min(io_channel::PAGE_SIZE);
+        self.inner.tx(&buf[..sz], &[])
+    }
+
+    pub fn shutdown(&self, read: bool, write: bool) -> Result<(), ErrorCode> {
+        self.inner.shutdown(read, write)
+    }
+
+    pub fn duplicate(&self) -> Result<UnixStream, ErrorCode> {
+        Ok(UnixStream {
+            inner: self.inner.clone(),
+        })
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner.set_read_timeout(timeout);
         Ok(())
     }
-}
 
-impl core::fmt::Debug for TcpListener {
-    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        todo!()
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner.set_write_timeout(timeout);
+        Ok(())
     }
-}
 
-pub struct UdpSocket {}
+    pub fn read_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(self.inner.read_timeout())
+    }
 
-impl UdpSocket {
-    pub fn bind(_: &SocketAddr) -> Result<UdpSocket, ErrorCode> {
-        todo!()
+    pub fn write_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(self.inner.write_timeout())
     }
 
-    pub fn peer_addr(&self) -> Result<SocketAddr, ErrorCode> {
-        todo!()
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
+        self.inner.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
     }
 
-    pub fn socket_addr(&self) -> Result<SocketAddr, ErrorCode> {
-        todo!()
+    pub fn register(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::register(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
     }
 
-    pub fn recv_from(&self, _: &mut [u8]) -> Result<(usize, SocketAddr), ErrorCode> {
-        todo!()
+    pub fn reregister(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::reregister(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
     }
 
-    pub fn peek_from(&self, _: &mut [u8]) -> Result<(usize, SocketAddr), ErrorCode> {
-        todo!()
+    pub fn deregister(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        crate::poll::deregister(&self.inner.poll_registration, poll)
     }
 
-    pub fn send_to(&self, _: &[u8], _: &SocketAddr) -> Result<usize, ErrorCode> {
-        todo!()
+    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
+        Ok(None)
     }
+}
 
-    pub fn duplicate(&self) -> Result<UdpSocket, ErrorCode> {
-        todo!()
+impl core::fmt::Debug for UnixStream {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UnixStream")
+            .field("addr", &self.inner.local_addr)
+            .field("peer", &*self.inner.peer_addr.lock(line!()))
+            .field("handle", &self.inner.handle)
+            .finish()
     }
+}
 
-    pub fn set_read_timeout(&self, _: Option<Duration>) -> Result<(), ErrorCode> {
-        todo!()
+pub struct UnixDatagram {
+    inner: Arc<LocalSocketImpl>,
+}
+
+impl UnixDatagram {
+    pub fn bind(addr: &UnixSocketAddr) -> Result<UnixDatagram, ErrorCode> {
+        let channel = NET.lock(line!()).reserve_channel();
+        let subchannel_idx = channel.reserve_subchannel();
+
+        let resp = local_name_page(&channel, subchannel_idx, addr).map(|page| {
+            channel.send_receive(rt_api::net::local_socket_bind_request(
+                rt_api::net::LOCAL_KIND_DATAGRAM,
+                page,
+                addr.name.len(),
+                subchannel_idx,
+            ))
+        });
+        let resp = match resp {
+            Ok(resp) if resp.status().is_ok() => resp,
+            res => {
+                channel.release_subchannel(subchannel_idx);
+                NET.lock(line!()).release_channel(channel);
+                return Err(match res {
+                    Ok(resp) => resp.status(),
+                    Err(err) => err,
+                });
+            }
+        };
+
+        let inner = LocalSocketImpl::new(
+            &channel,
+            resp.handle,
+            addr.clone(),
+            None,
+            None,
+            subchannel_idx,
+        );
+        Ok(Self { inner })
     }
 
-    pub fn set_write_timeout(&self, _: Option<Duration>) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn unbound() -> Result<UnixDatagram, ErrorCode> {
+        Self::bind(&UnixSocketAddr::unnamed())
     }
 
-    pub fn read_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
-        todo!()
+    pub fn pair() -> Result<(UnixDatagram, UnixDatagram), ErrorCode> {
+        let (first, second) = LocalSocketImpl::pair(rt_api::net::LOCAL_KIND_DATAGRAM)?;
+        Ok((Self { inner: first }, Self { inner: second }))
     }
 
-    pub fn write_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
-        todo!()
+    pub fn connect(&self, addr: &UnixSocketAddr) -> Result<(), ErrorCode> {
+        // As with UDP, sys-io is not involved.
+        *self.inner.peer_addr.lock(line!()) = Some(addr.clone());
+        Ok(())
     }
 
-    pub fn set_broadcast(&self, _: bool) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn local_addr(&self) -> Result<UnixSocketAddr, ErrorCode> {
+        Ok(self.inner.local_addr.clone())
     }
 
-    pub fn broadcast(&self) -> Result<bool, ErrorCode> {
-        todo!()
+    pub fn peer_addr(&self) -> Result<UnixSocketAddr, ErrorCode> {
+        match &*self.inner.peer_addr.lock(line!()) {
+            Some(addr) => Ok(addr.clone()),
+            None if self.inner.peer_creds.is_some() => Ok(UnixSocketAddr::unnamed()),
+            None => Err(ErrorCode::NotFound),
+        }
     }
 
-    pub fn set_multicast_loop_v4(&self, _: bool) -> Result<(), ErrorCode> {
-        todo!()
+    /// For socket pairs only: datagrams from elsewhere carry their own credentials.
+    pub fn peer_cred(&self) -> Result<rt_api::net::LocalCreds, ErrorCode> {
+        self.inner.peer_creds.ok_or(ErrorCode::NotFound)
     }
 
-    pub fn multicast_loop_v4(&self) -> Result<bool, ErrorCode> {
-        todo!()
+    pub fn send_to(&self, buf: &[u8], addr: &UnixSocketAddr) -> Result<usize, ErrorCode> {
+        if buf.len() > rt_api::net::LOCAL_MAX_DATAGRAM {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.inner.tx(buf, &addr.name)
     }
 
-    pub fn set_multicast_ttl_v4(&self, _: u32) -> Result<(), ErrorCode> {
-        todo!()
+    /// To the connected address or, for socket pairs, to the other socket.
+    pub fn send(&self, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let peer_addr = self.inner.peer_addr.lock(line!()).clone();
+        self.send_to(buf, &peer_addr.unwrap_or_else(UnixSocketAddr::unnamed))
     }
 
-    pub fn multicast_ttl_v4(&self) -> Result<u32, ErrorCode> {
-        todo!()
+    /// Also returns the sender's credentials.
+    pub fn recv_from_with_creds(
+        &self,
+        buf: &mut [u8],
+    ) -> Result<(usize, UnixSocketAddr, rt_api::net::LocalCreds), ErrorCode> {
+        self.inner
+            .wait_rx(|| self.inner.poll_datagram_rx(buf, false))
     }
 
-    pub fn set_multicast_loop_v6(&self, _: bool) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, UnixSocketAddr), ErrorCode> {
+        self.recv_from_with_creds(buf)
+            .map(|(sz, addr, _)| (sz, addr))
     }
 
-    pub fn multicast_loop_v6(&self) -> Result<bool, ErrorCode> {
-        todo!()
+    pub fn peek_from(&self, buf: &mut [u8]) -> Result<(usize, UnixSocketAddr), ErrorCode> {
+        self.inner
+            .wait_rx(|| self.inner.poll_datagram_rx(buf, true))
+            .map(|(sz, addr, _)| (sz, addr))
     }
 
-    pub fn join_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.recv_from(buf).map(|(sz, _)| sz)
     }
 
-    pub fn join_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn peek(&self, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        self.peek_from(buf).map(|(sz, _)| sz)
     }
 
-    pub fn leave_multicast_v4(&self, _: &Ipv4Addr, _: &Ipv4Addr) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn shutdown(&self, read: bool, write: bool) -> Result<(), ErrorCode> {
+        self.inner.shutdown(read, write)
     }
 
-    pub fn leave_multicast_v6(&self, _: &Ipv6Addr, _: u32) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn duplicate(&self) -> Result<UnixDatagram, ErrorCode> {
+        Ok(UnixDatagram {
+            inner: self.inner.clone(),
+        })
     }
 
-    pub fn set_ttl(&self, _: u32) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner.set_read_timeout(timeout);
+        Ok(())
     }
 
-    pub fn ttl(&self) -> Result<u32, ErrorCode> {
-        todo!()
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), ErrorCode> {
+        self.inner.set_write_timeout(timeout);
+        Ok(())
     }
 
-    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
-        todo!()
+    pub fn read_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(self.inner.read_timeout())
     }
 
-    pub fn set_nonblocking(&self, _: bool) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn write_timeout(&self) -> Result<Option<Duration>, ErrorCode> {
+        Ok(self.inner.write_timeout())
     }
 
-    pub fn recv(&self, _: &mut [u8]) -> Result<usize, ErrorCode> {
-        todo!()
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), ErrorCode> {
+        self.inner.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
     }
 
-    pub fn peek(&self, _: &mut [u8]) -> Result<usize, ErrorCode> {
-        todo!()
+    pub fn register(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::register(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
     }
 
-    pub fn send(&self, _: &[u8]) -> Result<usize, ErrorCode> {
-        todo!()
+    pub fn reregister(&self, poll: &Poll, token: Token, interests: u32) -> Result<(), ErrorCode> {
+        crate::poll::reregister(
+            &self.inner.poll_registration,
+            poll,
+            token,
+            interests,
+            readiness_fn(&self.inner),
+        )
     }
 
-    pub fn connect(&self, _addr: &SocketAddr) -> Result<(), ErrorCode> {
-        todo!()
+    pub fn deregister(&self, poll: &Poll) -> Result<(), ErrorCode> {
+        crate::poll::deregister(&self.inner.poll_registration, poll)
+    }
+
+    pub fn take_error(&self) -> Result<Option<ErrorCode>, ErrorCode> {
+        Ok(None)
     }
 }
 
-impl core::fmt::Debug for UdpSocket {
-    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        todo!()
+impl core::fmt::Debug for UnixDatagram {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("UnixDatagram")
+            .field("addr", &self.inner.local_addr)
+            .field("peer", &*self.inner.peer_addr.lock(line!()))
+            .field("handle", &self.inner.handle)
+            .finish()
     }
 }
 
 pub struct LookupHost {
-    addr: SocketAddr,
-    next: Option<SocketAddr>,
+    port: u16,
+    addrs: alloc::vec::IntoIter<SocketAddr>,
 }
 
 impl LookupHost {
     pub fn port(&self) -> u16 {
-        self.addr.port()
+        self.port
     }
 
-    fn new(addr: SocketAddr) -> Self {
+    fn new(addrs: Vec<IpAddr>, port: u16) -> Self {
+        let addrs: Vec<SocketAddr> = addrs
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
         Self {
-            addr: addr.clone(),
-            next: Some(addr),
+            port,
+            addrs: addrs.into_iter(),
         }
     }
 }
@@ -1473,7 +3873,7 @@ impl LookupHost {
 impl Iterator for LookupHost {
     type Item = SocketAddr;
     fn next(&mut self) -> Option<SocketAddr> {
-        self.next.take()
+        self.addrs.next()
     }
 }
 
@@ -1497,22 +3897,69 @@ impl<'a> TryFrom<(&'a str, u16)> for LookupHost {
         let (host, port) = host_port;
 
         if host == "localhost" {
-            Ok(LookupHost::new(SocketAddr::V4(SocketAddrV4::new(
-                Ipv4Addr::new(127, 0, 0, 1),
+            Ok(LookupHost::new(
+                alloc::vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))],
                 port,
-            ))))
-        } else if let Ok(addr_v4) = Ipv4Addr::from_str(host) {
-            Ok(LookupHost::new(SocketAddr::V4(SocketAddrV4::new(
-                addr_v4, port,
-            ))))
+            ))
+        } else if let Ok(addr) = IpAddr::from_str(host) {
+            Ok(LookupHost::new(alloc::vec![addr], port))
         } else {
-            #[cfg(debug_assertions)]
-            crate::util::moturus_log!(
-                "LookupHost::try_from: {}:{}: DNS lookup not implemented",
-                host,
-                port
-            );
-            Err(ErrorCode::NotImplemented)
+            Ok(LookupHost::new(dns_lookup(host)?, port))
         }
     }
 }
+
+// Resolves the hostname via sys-io, which caches the answers.
+fn dns_lookup(host: &str) -> Result<Vec<IpAddr>, ErrorCode> {
+    if host.is_empty() || host.len() > rt_api::net::DNS_MAX_NAME_LEN {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let channel = NET.lock(line!()).reserve_channel();
+    let subchannel_idx = channel.reserve_subchannel();
+
+    let result = dns_lookup_on(&channel, subchannel_idx, host);
+
+    channel.release_subchannel(subchannel_idx);
+    NET.lock(line!()).release_channel(channel);
+
+    #[cfg(debug_assertions)]
+    moturus_log!("{}:{} DNS lookup {}: {:?}", file!(), line!(), host, result);
+
+    result
+}
+
+fn dns_lookup_on(
+    channel: &Arc<NetChannel>,
+    subchannel_idx: usize,
+    host: &str,
+) -> Result<Vec<IpAddr>, ErrorCode> {
+    let subchannel_mask = rt_api::net::io_subchannel_mask(subchannel_idx);
+
+    // The subchannel is ours alone, so a busy page will be freed soon.
+    let page = loop {
+        match channel.conn.alloc_page(subchannel_mask) {
+            Ok(page) => break page,
+            Err(_) => moto_sys::SysCpu::sched_yield(),
+        }
+    };
+    page.bytes_mut()[..host.len()].copy_from_slice(host.as_bytes());
+
+    let resp = channel.send_receive(rt_api::net::dns_lookup_request(
+        page,
+        host.len(),
+        subchannel_idx,
+    ));
+    if resp.status().is_err() {
+        return Err(resp.status());
+    }
+
+    let num_addrs = resp.payload.args_16()[1] as usize;
+    let page = channel.conn.get_page(resp.payload.shared_pages()[0])?;
+    let mut addrs = Vec::with_capacity(num_addrs);
+    for idx in 0..num_addrs.min(rt_api::net::DNS_MAX_ADDRS) {
+        addrs.push(rt_api::net::get_dns_addr(page.bytes(), idx)?);
+    }
+
+    Ok(addrs)
+}
@@ -0,0 +1,423 @@
+// Readiness notifications for sockets, similar to epoll/kqueue: sockets
+// are registered with a Poll under user-chosen tokens, and Poll::wait()
+// returns batches of (token, events) for sockets that became ready.
+//
+// Sources are notified by their channel's IO thread as messages arrive,
+// including the one sys-io sends when a socket whose write would have
+// blocked has an IO page to write into again.
+
+use crate::util::SpinLock;
+use alloc::collections::BTreeMap;
+use alloc::collections::BTreeSet;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::sync::atomic::*;
+use core::time::Duration;
+use moto_sys::time::Instant;
+use moto_sys::ErrorCode;
+use moto_sys::SysHandle;
+
+pub const READABLE: u32 = 1 << 0;
+pub const WRITABLE: u32 = 1 << 1;
+pub const READ_CLOSED: u32 = 1 << 2;
+pub const WRITE_CLOSED: u32 = 1 << 3;
+pub const ERROR: u32 = 1 << 4;
+
+/// An interest flag: report readiness once per change (as EPOLLET does)
+/// rather than on every wait() for as long as it lasts.
+pub const EDGE_TRIGGERED: u32 = 1 << 31;
+
+// Reported whether asked for or not.
+const ALWAYS_REPORTED: u32 = READ_CLOSED | WRITE_CLOSED | ERROR;
+
+pub type Token = u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub token: Token,
+    pub events: u32,
+}
+
+impl Event {
+    pub fn is_readable(&self) -> bool {
+        self.events & READABLE != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.events & WRITABLE != 0
+    }
+
+    pub fn is_read_closed(&self) -> bool {
+        self.events & READ_CLOSED != 0
+    }
+
+    pub fn is_write_closed(&self) -> bool {
+        self.events & WRITE_CLOSED != 0
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.events & ERROR != 0
+    }
+}
+
+// Returns the current readiness of a source, or None if it is gone.
+// Must not block.
+pub(crate) type ReadinessFn = Arc<dyn Fn() -> Option<u32> + Send + Sync>;
+
+struct Entry {
+    token: Token,
+    interests: u32,
+    readiness: ReadinessFn,
+    pending: u32, // Edge-triggered: events not reported yet.
+    queued: bool,
+}
+
+struct PollState {
+    entries: BTreeMap<u64, Entry>,
+    ready: VecDeque<u64>,
+    wakers: BTreeSet<Token>, // Tokens of PollWakers that fired.
+}
+
+pub(crate) struct PollInner {
+    state: SpinLock<PollState>,
+    next_id: AtomicU64,
+    waiter: AtomicU64, // The thread sleeping in wait(), if any.
+}
+
+impl PollInner {
+    fn wake_waiter(&self) {
+        let waiter = self.waiter.load(Ordering::Acquire);
+        if waiter != SysHandle::NONE.as_u64() {
+            let _ = moto_sys::SysCpu::wake(waiter.into());
+        }
+    }
+
+    fn notify(&self, id: u64, readiness: u32) {
+        {
+            let mut state = self.state.lock(line!());
+            let Some(entry) = state.entries.get_mut(&id) else {
+                return;
+            };
+            let ready = readiness & mask(entry.interests);
+            if ready == 0 {
+                return;
+            }
+            entry.pending |= ready;
+            if entry.queued {
+                return;
+            }
+            entry.queued = true;
+            state.ready.push_back(id);
+        }
+        self.wake_waiter();
+    }
+
+    // Moves up to max_events ready entries into events.
+    fn collect(&self, events: &mut Vec<Event>, max_events: usize) {
+        let mut batch: Vec<Candidate> = Vec::new();
+        {
+            let mut state = self.state.lock(line!());
+            while events.len() < max_events {
+                let Some(token) = state.wakers.pop_first() else {
+                    break;
+                };
+                events.push(Event {
+                    token,
+                    events: READABLE,
+                });
+            }
+
+            while events.len() + batch.len() < max_events {
+                let Some(id) = state.ready.pop_front() else {
+                    break;
+                };
+                let Some(entry) = state.entries.get_mut(&id) else {
+                    continue;
+                };
+                entry.queued = false;
+                batch.push(Candidate {
+                    id,
+                    token: entry.token,
+                    interests: entry.interests,
+                    pending: core::mem::take(&mut entry.pending),
+                    readiness: entry.readiness.clone(),
+                });
+            }
+        }
+
+        // Readiness is checked without holding the lock, as sources
+        // take their own locks.
+        let mut requeue = Vec::new();
+        for candidate in batch {
+            let Some(readiness) = (candidate.readiness)() else {
+                continue;
+            };
+            let edge = candidate.interests & EDGE_TRIGGERED != 0;
+            let ready = if edge { candidate.pending } else { readiness };
+            let ready = ready & mask(candidate.interests);
+            if ready != 0 {
+                events.push(Event {
+                    token: candidate.token,
+                    events: ready,
+                });
+                if !edge {
+                    requeue.push(candidate.id);
+                }
+            }
+        }
+
+        if requeue.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock(line!());
+        for id in requeue {
+            if let Some(entry) = state.entries.get_mut(&id) {
+                if !entry.queued {
+                    entry.queued = true;
+                    state.ready.push_back(id);
+                }
+            }
+        }
+    }
+}
+
+struct Candidate {
+    id: u64,
+    token: Token,
+    interests: u32,
+    pending: u32,
+    readiness: ReadinessFn,
+}
+
+fn mask(interests: u32) -> u32 {
+    (interests & !EDGE_TRIGGERED) | ALWAYS_REPORTED
+}
+
+/// A set of registered sockets, and the events they are ready for.
+pub struct Poll {
+    inner: Arc<PollInner>,
+}
+
+impl Poll {
+    pub fn new() -> Result<Self, ErrorCode> {
+        Ok(Self {
+            inner: Arc::new(PollInner {
+                state: SpinLock::new(PollState {
+                    entries: BTreeMap::new(),
+                    ready: VecDeque::new(),
+                    wakers: BTreeSet::new(),
+                }),
+                next_id: AtomicU64::new(1),
+                waiter: AtomicU64::new(SysHandle::NONE.as_u64()),
+            }),
+        })
+    }
+
+    /// Clears events and fills it with up to max_events ready sockets,
+    /// waiting for at least one until the timeout expires. Only one thread
+    /// may wait on a Poll at a time.
+    pub fn wait(
+        &self,
+        events: &mut Vec<Event>,
+        max_events: usize,
+        timeout: Option<Duration>,
+    ) -> Result<usize, ErrorCode> {
+        if max_events == 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        events.clear();
+        let deadline = timeout.map(|timo| Instant::now() + timo);
+        let this_thread = moto_sys::UserThreadControlBlock::get().self_handle;
+
+        loop {
+            self.inner.collect(events, max_events);
+            if !events.is_empty() {
+                return Ok(events.len());
+            }
+
+            let now = Instant::now();
+            if let Some(deadline) = deadline {
+                if now >= deadline {
+                    return Ok(0);
+                }
+            }
+
+            if self
+                .inner
+                .waiter
+                .compare_exchange(
+                    SysHandle::NONE.as_u64(),
+                    this_thread,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+            {
+                return Err(ErrorCode::AlreadyInUse);
+            }
+
+            // Re-check: a notification may have come before the store above.
+            let wake_at = {
+                let state = self.inner.state.lock(line!());
+                if !state.ready.is_empty() || !state.wakers.is_empty() {
+                    Some(now)
+                } else {
+                    deadline
+                }
+            };
+            if wake_at != Some(now) {
+                let _ = moto_sys::SysCpu::wait(&mut [], SysHandle::NONE, SysHandle::NONE, wake_at);
+            }
+            self.inner
+                .waiter
+                .store(SysHandle::NONE.as_u64(), Ordering::Release);
+        }
+    }
+
+    /// Returns a handle that other threads can use to make wait() return
+    /// a READABLE event for token.
+    pub fn waker(&self, token: Token) -> PollWaker {
+        PollWaker {
+            poll: Arc::downgrade(&self.inner),
+            token,
+        }
+    }
+
+    fn add(&self, token: Token, interests: u32, readiness: ReadinessFn) -> Registration {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.inner.state.lock(line!()).entries.insert(
+            id,
+            Entry {
+                token,
+                interests,
+                readiness,
+                pending: 0,
+                queued: false,
+            },
+        );
+
+        Registration {
+            poll: Arc::downgrade(&self.inner),
+            id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PollWaker {
+    poll: Weak<PollInner>,
+    token: Token,
+}
+
+impl PollWaker {
+    pub fn wake(&self) -> Result<(), ErrorCode> {
+        let Some(poll) = self.poll.upgrade() else {
+            return Err(ErrorCode::BadHandle);
+        };
+        poll.state.lock(line!()).wakers.insert(self.token);
+        poll.wake_waiter();
+        Ok(())
+    }
+}
+
+/// A socket's entry in a Poll; dropping it deregisters the socket.
+pub(crate) struct Registration {
+    poll: Weak<PollInner>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(poll) = self.poll.upgrade() {
+            let mut state = poll.state.lock(line!());
+            state.entries.remove(&self.id);
+        }
+    }
+}
+
+impl Registration {
+    fn is_in(&self, poll: &Poll) -> bool {
+        core::ptr::eq(self.poll.as_ptr(), Arc::as_ptr(&poll.inner))
+    }
+
+    // Called by sources when their readiness may have changed.
+    pub(crate) fn notify(&self, readiness: u32) {
+        if let Some(poll) = self.poll.upgrade() {
+            poll.notify(self.id, readiness);
+        }
+    }
+}
+
+// The socket side of register()/reregister()/deregister(): sockets keep
+// their registration in a slot, so a socket is in at most one Poll.
+pub(crate) fn register(
+    slot: &SpinLock<Option<Registration>>,
+    poll: &Poll,
+    token: Token,
+    interests: u32,
+    readiness: ReadinessFn,
+) -> Result<(), ErrorCode> {
+    if interests & !EDGE_TRIGGERED == 0 {
+        return Err(ErrorCode::InvalidArgument);
+    }
+    let current = readiness();
+    {
+        let mut slot = slot.lock(line!());
+        if slot.is_some() {
+            return Err(ErrorCode::AlreadyInUse);
+        }
+        *slot = Some(poll.add(token, interests, readiness));
+    }
+    notify(slot, || current.unwrap_or(0));
+    Ok(())
+}
+
+pub(crate) fn reregister(
+    slot: &SpinLock<Option<Registration>>,
+    poll: &Poll,
+    token: Token,
+    interests: u32,
+    readiness: ReadinessFn,
+) -> Result<(), ErrorCode> {
+    if interests & !EDGE_TRIGGERED == 0 {
+        return Err(ErrorCode::InvalidArgument);
+    }
+    let current = readiness();
+    {
+        let slot = slot.lock(line!());
+        let Some(registration) = slot.as_ref().filter(|r| r.is_in(poll)) else {
+            return Err(ErrorCode::NotFound);
+        };
+        let mut state = poll.inner.state.lock(line!());
+        let entry = state.entries.get_mut(&registration.id).unwrap();
+        entry.token = token;
+        entry.interests = interests;
+        entry.pending = 0;
+    }
+    notify(slot, || current.unwrap_or(0));
+    Ok(())
+}
+
+pub(crate) fn deregister(
+    slot: &SpinLock<Option<Registration>>,
+    poll: &Poll,
+) -> Result<(), ErrorCode> {
+    let mut slot = slot.lock(line!());
+    if !slot.as_ref().is_some_and(|r| r.is_in(poll)) {
+        return Err(ErrorCode::NotFound);
+    }
+    // Drop the registration after releasing the slot.
+    let registration = slot.take();
+    core::mem::drop(slot);
+    core::mem::drop(registration);
+    Ok(())
+}
+
+// Readiness is only computed for registered sockets.
+pub(crate) fn notify(slot: &SpinLock<Option<Registration>>, readiness: impl FnOnce() -> u32) {
+    if let Some(registration) = slot.lock(line!()).as_ref() {
+        registration.notify(readiness());
+    }
+}
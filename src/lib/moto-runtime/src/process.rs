@@ -14,6 +14,8 @@ pub struct CommandRt {
     stdin: Option<StdioRt>,
     stdout: Option<StdioRt>,
     stderr: Option<StdioRt>,
+    // Handles to pass to the child: (our handle, child handle value).
+    handles: Vec<(SysHandle, SysHandle)>,
 }
 
 impl CommandRt {
@@ -25,6 +27,7 @@ impl CommandRt {
             stdin: None,
             stdout: None,
             stderr: None,
+            handles: Vec::new(),
         }
     }
 
@@ -48,6 +51,12 @@ impl CommandRt {
         self.stderr = Some(stderr);
     }
 
+    // Passes a duplicate of @handle to the child, where it will be available
+    // at @child_handle (>= SysObj::MIN_INHERITED_HANDLE) from the start.
+    pub fn inherit_handle(&mut self, handle: SysHandle, child_handle: SysHandle) {
+        self.handles.push((handle, child_handle));
+    }
+
     pub fn get_program(&self) -> &str {
         &self.program
     }
@@ -86,6 +95,10 @@ impl Process {
             // Map u64 to i32.
             let status_u32: u32 = exit_status as u32;
             unsafe { core::mem::transmute::<u32, i32>(status_u32) }
+        } else if exit_status == moto_sys::stats::EXIT_CODE_STACK_OVERFLOW {
+            // The kernel has logged which thread overflowed its stack.
+            crate::util::moturus_log!("child process: a thread overflowed its stack");
+            -1
         } else {
             // The process exited not via Rust's std::process::exit, but
             // via a lower-level syscall. Don't try to second-guess what
@@ -115,8 +128,24 @@ impl Process {
     }
 }
 
-// Loads a binary; returns the entry point.
-fn load_binary(bytes: &[u8], address_space: SysHandle) -> Result<u64, ErrorCode> {
+// ASLR: position-independent binaries are loaded at a random multiple
+// of 2M in [2M, 64G]; other binaries are loaded at their link addresses.
+const IMAGE_SLIDE_ALIGN: u64 = sys_mem::PAGE_SIZE_MID;
+const IMAGE_SLIDE_SLOTS: u64 = 1 << 15;
+
+// The kernel limits URLs to 256 bytes.
+const MAX_DEBUG_NAME_URL_LEN: usize = 256;
+
+fn choose_image_slide() -> u64 {
+    let random = moto_sys::rdrand().unwrap_or_else(|_| moto_sys::time::Instant::now().as_u64());
+    (1 + random % IMAGE_SLIDE_SLOTS) * IMAGE_SLIDE_ALIGN
+}
+
+// Loads a binary; returns the entry point and where the image was loaded.
+fn load_binary(
+    bytes: &[u8],
+    address_space: SysHandle,
+) -> Result<(u64, moto_sys::stats::ModuleInfoV1), ErrorCode> {
     let elf_binary = match ElfBinary::new(bytes) {
         Err(_) => {
             return Err(ErrorCode::InvalidArgument);
@@ -132,14 +161,36 @@ fn load_binary(bytes: &[u8], address_space: SysHandle) -> Result<u64, ErrorCode>
         return Err(ErrorCode::InvalidArgument);
     }
 
+    let slide = if elf_binary.file.header.pt2.type_().as_type()
+        == crate::external::xmas_elf::header::Type::SharedObject
+    {
+        choose_image_slide()
+    } else {
+        0
+    };
+
     let mut elf_loader = Loader {
         address_space,
         relocated: false,
+        slide,
         mapped_regions: BTreeMap::default(),
     };
     match elf_binary.load(&mut elf_loader) {
         Err(_) => Err(ErrorCode::InvalidArgument),
-        Ok(()) => Ok(elf_binary.entry_point()),
+        Ok(()) => {
+            let mut image = moto_sys::stats::ModuleInfoV1 {
+                start: u64::MAX,
+                end: 0,
+                slide,
+            };
+            for (remote, (_, num_pages)) in &elf_loader.mapped_regions {
+                image.start = image.start.min(*remote);
+                image.end = image
+                    .end
+                    .max(*remote + (*num_pages << sys_mem::PAGE_SIZE_SMALL_LOG2));
+            }
+            Ok((elf_binary.entry_point() + slide, image))
+        }
     }
 }
 
@@ -269,23 +320,33 @@ fn run_elf(
         return Err(ErrorCode::UnexpectedEof);
     }
 
-    let debug_name = match command.args.len() {
-        0 => exe.clone(),
-        1 => alloc::format!("{} {}", exe, command.args[0]),
-        _ => alloc::format!("{} {} ...", exe, command.args[0]),
-    };
+    // The debug name is the command line, so that tools like `ps` can show it.
+    let mut debug_name = exe.clone();
+    for arg in &command.args {
+        debug_name.push(' ');
+        debug_name.push_str(arg.as_str());
+    }
+
+    // Only the first MAX_CMDLINE_LEN bytes are reported by the kernel.
+    while debug_name.len() > moto_sys::stats::MAX_CMDLINE_LEN {
+        debug_name.pop();
+    }
 
     // Create an address space for the new process.
-    let full_url = alloc::format!(
+    let mut full_url = alloc::format!(
         "address_space:debug_name={}",
         &moto_sys::url_encode(debug_name.as_str())
     );
-    let address_space = syscalls::RaiiHandle::from(SysObj::create(SysHandle::NONE, 0, &full_url)?);
-    let load_result = load_binary(buf, address_space.syshandle());
-
-    if let Err(err) = load_result {
-        return Err(err);
+    // Long command lines are truncated to fit into the URL.
+    while full_url.len() > MAX_DEBUG_NAME_URL_LEN {
+        debug_name.pop();
+        full_url = alloc::format!(
+            "address_space:debug_name={}",
+            &moto_sys::url_encode(debug_name.as_str())
+        );
     }
+    let address_space = syscalls::RaiiHandle::from(SysObj::create(SysHandle::NONE, 0, &full_url)?);
+    let (entry_point, image) = load_binary(buf, address_space.syshandle())?;
 
     // TODO: remove CAP_LOG when the runtime is stabilized.
     let mut caps = moto_sys::caps::CAP_SPAWN | moto_sys::caps::CAP_LOG;
@@ -301,12 +362,68 @@ fn run_elf(
         }
     }
 
+    // Find MOTURUS_SYSCALL_FILTER env var.
+    let mut syscall_filter = moto_sys::caps::SYSCALL_FILTER_ALLOW_ALL;
+    for (k, v) in &mut env {
+        if k.as_str() == moto_sys::caps::MOTURUS_SYSCALL_FILTER_ENV_KEY {
+            *k = "".to_owned(); // Clear the key: see env::create_remote_env().
+            if let Ok(filter) = u128::from_str_radix(v.as_str().trim_start_matches("0x"), 16) {
+                syscall_filter = filter;
+            } else {
+                crate::util::moturus_log!("could not parse syscall filter {}", v);
+            }
+        }
+    }
+
+    // Find MOTURUS_FS_ROOT env var: if present, the child starts a new namespace.
+    let mut fs_root = None;
+    for (k, v) in &mut env {
+        if k.as_str() == moto_sys::caps::MOTURUS_FS_ROOT_ENV_KEY {
+            *k = "".to_owned(); // Clear the key: see env::create_remote_env().
+            if v.contains(';') {
+                crate::util::moturus_log!("bad fs root {}", v);
+                return Err(ErrorCode::InvalidArgument);
+            }
+            fs_root = Some(v.clone());
+        }
+    }
+
+    // Find MOTURUS_UID and MOTURUS_GID env vars; by default the child
+    // inherits the credentials of this process.
+    let mut credentials = String::new();
+    for (k, v) in &mut env {
+        let param = if k.as_str() == moto_sys::caps::MOTURUS_UID_ENV_KEY {
+            "uid"
+        } else if k.as_str() == moto_sys::caps::MOTURUS_GID_ENV_KEY {
+            "gid"
+        } else {
+            continue;
+        };
+        *k = "".to_owned(); // Clear the key: see env::create_remote_env().
+        if v.parse::<u32>().is_err() {
+            crate::util::moturus_log!("bad {} {}", param, v);
+            return Err(ErrorCode::InvalidArgument);
+        }
+        credentials.push_str(alloc::format!(";{}={}", param, v).as_str());
+    }
+
     // Create the process from the address space.
-    let proc_url = alloc::format!(
-        "process:entry_point={};capabilities={}",
-        load_result.unwrap(),
-        caps
+    let mut proc_url = alloc::format!(
+        "process:entry_point={};capabilities={};image_start={};image_end={};image_slide={}",
+        entry_point,
+        caps,
+        image.start,
+        image.end,
+        image.slide
     );
+    if syscall_filter != moto_sys::caps::SYSCALL_FILTER_ALLOW_ALL {
+        proc_url.push_str(alloc::format!(";syscall_filter={}", syscall_filter).as_str());
+    }
+    if let Some(fs_root) = fs_root {
+        proc_url.push_str(";fs_root=");
+        proc_url.push_str(fs_root.as_str());
+    }
+    proc_url.push_str(credentials.as_str());
     let process =
         syscalls::RaiiHandle::from(SysObj::create(address_space.syshandle(), 0, &proc_url)?);
 
@@ -360,6 +477,16 @@ fn run_elf(
         needs_stdin,
     )?;
 
+    for (handle, child_handle) in &command.handles {
+        SysObj::dup(
+            SysHandle::SELF,
+            *handle,
+            process.syshandle(),
+            *child_handle,
+            0,
+        )?;
+    }
+
     let main_thread = SysObj::get(process.syshandle(), 0, "main_thread").unwrap();
     if SysCpu::wake(main_thread).is_ok() {
         // While thread objects extracted from TCB or returned from spawn()
@@ -539,6 +666,7 @@ fn create_stdio_pipes(
 struct Loader {
     address_space: SysHandle,
     relocated: bool,
+    slide: u64, // Added to all ELF virtual addresses.
 
     // Map of allocated pages: remote addr -> (local addr, num_pages).
     mapped_regions: BTreeMap<u64, (u64, u64)>,
@@ -590,11 +718,9 @@ impl Drop for Loader {
 impl ElfLoader for Loader {
     fn allocate(&mut self, load_headers: LoadableHeaders<'_, '_>) -> Result<(), ElfLoaderErr> {
         for header in load_headers {
-            let vaddr_start = header.virtual_addr() & !(sys_mem::PAGE_SIZE_SMALL - 1);
-            let vaddr_end = moto_sys::align_up(
-                header.virtual_addr() + header.mem_size(),
-                sys_mem::PAGE_SIZE_SMALL,
-            );
+            let vaddr = header.virtual_addr() + self.slide;
+            let vaddr_start = vaddr & !(sys_mem::PAGE_SIZE_SMALL - 1);
+            let vaddr_end = moto_sys::align_up(vaddr + header.mem_size(), sys_mem::PAGE_SIZE_SMALL);
 
             let mut flags = SysMem::F_SHARE_SELF;
             if header.flags().is_read() {
@@ -603,6 +729,9 @@ impl ElfLoader for Loader {
             if header.flags().is_write() {
                 flags |= SysMem::F_WRITABLE;
             }
+            if header.flags().is_execute() {
+                flags |= SysMem::F_EXECUTABLE;
+            }
 
             let num_pages = (vaddr_end - vaddr_start) >> sys_mem::PAGE_SIZE_SMALL_LOG2;
 
@@ -624,7 +753,7 @@ impl ElfLoader for Loader {
 
     fn load(&mut self, _flags: Flags, base: VAddr, region: &[u8]) -> Result<(), ElfLoaderErr> {
         unsafe {
-            self.write_remotely(base, region.as_ptr(), region.len() as u64);
+            self.write_remotely(base + self.slide, region.as_ptr(), region.len() as u64);
         }
 
         Ok(())
@@ -634,7 +763,7 @@ impl ElfLoader for Loader {
         use elfloader::arch::x86_64::RelocationTypes::*;
         use RelocationType::x86_64;
 
-        let remote_addr: u64 = entry.offset;
+        let remote_addr: u64 = entry.offset + self.slide;
 
         match entry.rtype {
             x86_64(R_AMD64_RELATIVE) => {
@@ -644,10 +773,11 @@ impl ElfLoader for Loader {
                     .ok_or(ElfLoaderErr::UnsupportedRelocationEntry)?;
 
                 // Need to write (addend + base) into addr.
+                let value = addend + self.slide;
                 unsafe {
                     self.write_remotely(
                         remote_addr,
-                        &addend as *const _ as *const u8,
+                        &value as *const _ as *const u8,
                         core::mem::size_of::<u64>() as u64,
                     );
                 }
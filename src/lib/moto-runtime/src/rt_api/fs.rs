@@ -16,24 +16,66 @@ pub const CMD_READDIR_NEXT: u16 = 4;
 pub const CMD_CLOSE_FD: u16 = 5;
 
 pub const CMD_MKDIR: u16 = 6;
+pub const CMD_STATFS: u16 = 7;
 
 pub const CMD_FILE_OPEN: u16 = 100;
 pub const CMD_FILE_READ: u16 = 101;
 pub const CMD_FILE_WRITE: u16 = 102;
 pub const CMD_UNLINK: u16 = 103;
 pub const CMD_RENAME: u16 = 104;
+pub const CMD_SYMLINK: u16 = 105;
+pub const CMD_READLINK: u16 = 106;
+pub const CMD_LINK: u16 = 107;
+pub const CMD_CHMOD: u16 = 108;
+pub const CMD_CHOWN: u16 = 109;
+pub const CMD_FLOCK: u16 = 110;
+pub const CMD_WATCH_ADD: u16 = 111;
+pub const CMD_WATCH_REMOVE: u16 = 112;
+pub const CMD_WATCH_NEXT: u16 = 113;
+pub const CMD_FILE_SYNC: u16 = 114;
+pub const CMD_FILE_ALLOCATE: u16 = 115;
+pub const CMD_XATTR_GET: u16 = 116;
+pub const CMD_XATTR_SET: u16 = 117;
+pub const CMD_XATTR_LIST: u16 = 118;
+pub const CMD_XATTR_REMOVE: u16 = 119;
+pub const CMD_QUOTA_GET: u16 = 120;
+pub const CMD_QUOTA_SET: u16 = 121;
+pub const CMD_MOUNT: u16 = 122;
+pub const CMD_UNMOUNT: u16 = 123;
+pub const CMD_SNAPSHOT_CREATE: u16 = 124;
+pub const CMD_SNAPSHOT_DELETE: u16 = 125;
+pub const CMD_SNAPSHOT_LIST: u16 = 126;
+pub const CMD_FILE_DUP: u16 = 127;
 
 pub const FILE_TYPE_FILE: u8 = 1;
 pub const FILE_TYPE_DIR: u8 = 2;
 pub const FILE_TYPE_SYMLINK: u8 = 4;
 
+// Access the caller has to the file.
 pub const FILE_PERM_READ: u16 = 1;
 pub const FILE_PERM_WRITE: u16 = 2;
 
+// Unix-style permission bits (FileAttrData::mode): rwx for the owner,
+// the group, and others. Processes running as root (see
+// moto_sys::caps::ROOT_UID) are not restricted by them.
+pub const FILE_MODE_MASK: u32 = 0o777;
+
+pub const FS_TYPE_FLATFS: u8 = 1;
+pub const FS_TYPE_SRFS: u8 = 2;
+pub const FS_TYPE_TMPFS: u8 = 3;
+pub const FS_TYPE_FAT: u8 = 4;
+pub const FS_TYPE_EXT2: u8 = 5;
+pub const FS_TYPE_VIRTIOFS: u8 = 6;
+pub const FS_TYPE_PROCFS: u8 = 7;
+pub const FS_TYPE_DEVFS: u8 = 8;
+
 pub const F_UNLINK_FILE: u32 = 1;
 pub const F_UNLINK_DIR: u32 = 2;
 pub const F_UNLINK_DIR_ALL: u32 = 3;
 
+// CMD_STAT flag: stat the symlink itself rather than its target.
+pub const F_STAT_NOFOLLOW: u32 = 1;
+
 pub const FS_URL: &str = "motor-os-fs";
 
 // The first request the client makes to FS_URL, which then
@@ -92,7 +134,7 @@ pub struct CloseFdResponse {
 
 #[repr(C, align(8))]
 pub struct StatRequest {
-    // pub command: u16, // CMD_STAT, CMD_READDIR, CMD_MKDIR, CMD_FILE_OPEN, or CMD_UNLINK.
+    // pub command: u16, // CMD_STAT, CMD_STATFS, CMD_READDIR, CMD_MKDIR, CMD_FILE_OPEN, CMD_UNLINK, CMD_READLINK, or CMD_CHMOD.
     // pub version: u16,
     // pub flags: u16,
     pub header: moto_ipc::sync::RequestHeader,
@@ -122,6 +164,25 @@ pub type MkdirResponse = CloseFdResponse;
 pub type UnlinkRequest = StatRequest;
 pub type UnlinkResponse = CloseFdResponse;
 
+pub type ReadLinkRequest = StatRequest;
+
+#[repr(C, align(8))]
+pub struct ReadLinkResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub target_size: u16,
+    pub target: [u8; 0],
+}
+
+impl ReadLinkResponse {
+    pub unsafe fn target<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<&'a str, ErrorCode> {
+        let bytes = raw_channel.get_bytes(&self.target, self.target_size as usize)?;
+        core::str::from_utf8(bytes).map_err(|_| ErrorCode::InvalidArgument)
+    }
+}
+
 impl FileOpenRequest {
     // FileOpen flags.
     pub const F_READ: u32 = 1;
@@ -130,6 +191,12 @@ impl FileOpenRequest {
     pub const F_TRUNCATE: u32 = 8;
     pub const F_CREATE: u32 = 0x10;
     pub const F_CREATE_NEW: u32 = 0x20;
+
+    // Direct I/O: reads and writes bypass the FS caches. The offset and the size
+    // of every read and write on the fd must be multiples of DIRECT_IO_ALIGN,
+    // or they fail with InvalidArgument; reads may end short at EOF.
+    pub const F_DIRECT: u32 = 0x40;
+    pub const DIRECT_IO_ALIGN: u64 = 512;
 }
 
 #[repr(C, align(8))]
@@ -143,6 +210,10 @@ pub struct FileAttrData {
     pub created: u64,
     pub accessed: u64,
     pub modified: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32, // FILE_MODE_MASK bits.
+    pub reserved_2: u32,
 }
 
 #[repr(C, align(8))]
@@ -152,6 +223,33 @@ pub struct StatResponse {
     pub attr: FileAttrData,
 }
 
+// Capacity and usage of the filesystem a path belongs to.
+#[repr(C, align(8))]
+#[derive(Clone, Copy)]
+pub struct FsStatsData {
+    pub version: u16,
+    pub self_size: u16, // The size of this struct.
+    pub fs_type: u8,    // FS_TYPE_*.
+    pub read_only: u8,  // 1 => read only.
+    pub reserved: u16,
+    pub block_size: u64, // In bytes.
+    pub blocks_total: u64,
+    pub blocks_free: u64,
+}
+
+pub type StatFsRequest = StatRequest; // Same struct, different command value.
+
+#[repr(C, align(8))]
+pub struct StatFsResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub stats: FsStatsData,
+}
+
+// Directories are listed in batches, in a stable order chosen by the
+// filesystem. Each batch ends with a cursor; passing it back continues after
+// the last entry of the batch, also if the directory has changed since:
+// entries that were neither added nor removed are not skipped. sys-io keeps
+// no listing state between batches, so huge directories are never read whole.
 #[repr(C, align(8))]
 pub struct ReadDirNextRequest {
     pub header: moto_ipc::sync::RequestHeader,
@@ -159,6 +257,7 @@ pub struct ReadDirNextRequest {
     // pub version: u16,
     // pub reserved: u32,
     pub readdir_fd: u64,
+    pub cursor: u64, // Zero: from the start.
 }
 
 #[repr(C, align(8))]
@@ -172,10 +271,18 @@ pub struct DirEntryData {
     pub fname: [u8; 0],
 }
 
+impl DirEntryData {
+    // Entries are packed: the next one starts at this offset from this one.
+    pub fn packed_size(fname_size: usize) -> usize {
+        (core::mem::size_of::<Self>() + fname_size).next_multiple_of(8)
+    }
+}
+
 #[repr(C, align(8))]
 pub struct ReadDirNextResponse {
     pub header: moto_ipc::sync::ResponseHeader,
-    pub entries: u16,
+    pub entries: u16, // Zero: the end of the directory.
+    pub cursor: u64,  // To continue after the last of the entries.
     pub dir_entries: [DirEntryData; 0],
 }
 
@@ -213,9 +320,11 @@ pub struct FileWriteResponse {
     pub written: u32,
 }
 
+// Also used for CMD_SYMLINK, with old being the target and new being the link,
+// and for CMD_LINK, with old being the existing file and new being the link.
 #[repr(C, align(8))]
 pub struct RenameRequest {
-    pub header: moto_ipc::sync::RequestHeader, // CMD_RENAME
+    pub header: moto_ipc::sync::RequestHeader, // CMD_RENAME, CMD_SYMLINK, or CMD_LINK
     pub parent_fd: u64,                        // if 0, fname should be absolute.
     pub old_fname_size: u16,
     pub new_fname_size: u16,
@@ -229,7 +338,17 @@ impl RenameRequest {
         new: &str,
         raw_channel: &moto_ipc::sync::RawChannel,
     ) -> Result<(), ErrorCode> {
-        self.header.cmd = CMD_RENAME;
+        self.build_cmd(CMD_RENAME, old, new, raw_channel)
+    }
+
+    pub fn build_cmd(
+        &mut self,
+        cmd: u16,
+        old: &str,
+        new: &str,
+        raw_channel: &moto_ipc::sync::RawChannel,
+    ) -> Result<(), ErrorCode> {
+        self.header.cmd = cmd;
         self.header.ver = 0;
         self.header.flags = 0;
         self.parent_fd = 0;
@@ -274,3 +393,411 @@ impl RenameRequest {
 }
 
 pub type RenameResponse = CloseFdResponse;
+
+pub type SymlinkRequest = RenameRequest;
+pub type SymlinkResponse = CloseFdResponse;
+
+pub type LinkRequest = RenameRequest;
+pub type LinkResponse = CloseFdResponse;
+
+// CMD_CHMOD: the new mode is in header.flags. Only the owner of the
+// file (or root) can change it.
+pub type ChmodRequest = StatRequest;
+pub type ChmodResponse = CloseFdResponse;
+
+// Only root can change the owner or the group of a file.
+#[repr(C, align(8))]
+pub struct ChownRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_CHOWN
+    pub parent_fd: u64,                        // if 0, fname should be absolute.
+    pub uid: u32,                              // CHOWN_UNCHANGED => keep.
+    pub gid: u32,                              // CHOWN_UNCHANGED => keep.
+    pub fname_size: u16,
+    pub fname: [u8; 0], // array of bytes with size of fname_size.
+}
+
+impl ChownRequest {
+    pub const CHOWN_UNCHANGED: u32 = u32::MAX;
+}
+
+pub type ChownResponse = CloseFdResponse;
+
+// Advisory (flock-style) locks on open files: one exclusive lock, or any
+// number of shared locks. A lock belongs to the fd it was taken through,
+// and is released when the fd is closed. The server never blocks: if the
+// lock is held through another fd, the request fails with NotReady, and
+// clients retry to wait.
+#[repr(C, align(8))]
+pub struct FlockRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_FLOCK; flags: F_*.
+    pub fd: u64,
+}
+
+impl FlockRequest {
+    pub const F_LOCK_SHARED: u32 = 1;
+    pub const F_LOCK_EXCLUSIVE: u32 = 2;
+    pub const F_UNLOCK: u32 = 3;
+}
+
+pub type FlockResponse = CloseFdResponse;
+
+// fsync/fdatasync: when the response arrives, the writes done through
+// any fd before the request are on stable storage.
+#[repr(C, align(8))]
+pub struct FileSyncRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_FILE_SYNC; flags: F_*.
+    pub fd: u64,
+}
+
+impl FileSyncRequest {
+    pub const F_DATA_ONLY: u32 = 1; // fdatasync: metadata only as needed to read the data.
+}
+
+pub type FileSyncResponse = CloseFdResponse;
+
+// fallocate: allocate the blocks in [offset, offset + len), extending
+// the file if needed; with F_PUNCH_HOLE, free them instead (the file
+// size does not change, and the range reads as zeroes).
+#[repr(C, align(8))]
+pub struct FileAllocateRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_FILE_ALLOCATE; flags: F_*.
+    pub fd: u64,
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl FileAllocateRequest {
+    pub const F_PUNCH_HOLE: u32 = 1;
+}
+
+pub type FileAllocateResponse = CloseFdResponse;
+
+// Shares an open file with another connection of the same process, e.g.
+// with the file pager's (see moto_runtime::fs::File::mmap()). F_EXPORT,
+// with fd, returns a one-time token; F_IMPORT, with the token sent over
+// the other connection, returns a new fd there for the same open file, so
+// that both fds see the same file even after it is renamed over or unlinked.
+#[repr(C, align(8))]
+pub struct FileDupRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_FILE_DUP; flags: F_*.
+    pub fd: u64,                               // F_IMPORT: the token.
+}
+
+impl FileDupRequest {
+    pub const F_EXPORT: u32 = 1;
+    pub const F_IMPORT: u32 = 2;
+}
+
+#[repr(C, align(8))]
+pub struct FileDupResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub fd: u64,   // F_EXPORT: the token.
+    pub size: u64, // F_IMPORT: the size of the file.
+}
+
+// Extended attributes: name/value pairs attached to files and directories.
+// Names are in a namespace: "user." (data and write access to the file
+// needed), "security." (changed by root only), or "trusted." (root only).
+// Limits are such that any request fits into a (small) channel.
+pub const XATTR_NAMESPACES: [&str; 3] = ["user.", "security.", "trusted."];
+pub const MAX_XATTR_NAME: usize = 255;
+pub const MAX_XATTR_VALUE: usize = 1024;
+
+#[repr(C, align(8))]
+pub struct XattrRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_XATTR_*; flags: F_* for CMD_XATTR_SET.
+    pub parent_fd: u64,                        // if 0, fname should be absolute.
+    pub fname_size: u16,
+    pub name_size: u16,  // Zero for CMD_XATTR_LIST.
+    pub value_size: u16, // Non-zero only for CMD_XATTR_SET.
+    pub data: [u8; 0],   // fname, then name, then value.
+}
+
+impl XattrRequest {
+    pub const F_CREATE: u32 = 1; // Fail with AlreadyInUse if the attribute exists.
+    pub const F_REPLACE: u32 = 2; // Fail with NotFound if the attribute does not exist.
+
+    pub fn build(
+        &mut self,
+        cmd: u16,
+        flags: u32,
+        fname: &str,
+        name: &str,
+        value: &[u8],
+        raw_channel: &moto_ipc::sync::RawChannel,
+    ) -> Result<(), ErrorCode> {
+        if name.len() > MAX_XATTR_NAME || value.len() > MAX_XATTR_VALUE {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.header.cmd = cmd;
+        self.header.ver = 0;
+        self.header.flags = flags;
+        self.parent_fd = 0;
+
+        self.fname_size = fname.len() as u16;
+        self.name_size = name.len() as u16;
+        self.value_size = value.len() as u16;
+        let start = &self.data as *const _ as usize;
+        unsafe {
+            raw_channel.put_bytes(fname.as_bytes(), &mut self.data)?;
+            raw_channel.put_bytes(
+                name.as_bytes(),
+                ((start + fname.len()) as *mut [u8; 0]).as_mut().unwrap(),
+            )?;
+            raw_channel.put_bytes(
+                value,
+                ((start + fname.len() + name.len()) as *mut [u8; 0])
+                    .as_mut()
+                    .unwrap(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn bytes<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+        offset: usize,
+        size: usize,
+    ) -> Result<&'a [u8], ErrorCode> {
+        let data = raw_channel.get_bytes(
+            &self.data,
+            self.fname_size as usize + self.name_size as usize + self.value_size as usize,
+        )?;
+        Ok(&data[offset..(offset + size)])
+    }
+
+    pub unsafe fn fname<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<&'a str, ErrorCode> {
+        let bytes = self.bytes(raw_channel, 0, self.fname_size as usize)?;
+        core::str::from_utf8(bytes).map_err(|_| ErrorCode::InvalidFilename)
+    }
+
+    pub unsafe fn name<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<&'a str, ErrorCode> {
+        let bytes = self.bytes(
+            raw_channel,
+            self.fname_size as usize,
+            self.name_size as usize,
+        )?;
+        core::str::from_utf8(bytes).map_err(|_| ErrorCode::InvalidArgument)
+    }
+
+    pub unsafe fn value<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<&'a [u8], ErrorCode> {
+        self.bytes(
+            raw_channel,
+            self.fname_size as usize + self.name_size as usize,
+            self.value_size as usize,
+        )
+    }
+}
+
+#[repr(C, align(8))]
+pub struct XattrResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    // CMD_XATTR_GET: the value; CMD_XATTR_LIST: the names, each followed by
+    // a NUL (BufferFull if they don't fit); zero for other commands.
+    pub data_size: u16,
+    pub data: [u8; 0],
+}
+
+impl XattrResponse {
+    pub unsafe fn data<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<&'a [u8], ErrorCode> {
+        raw_channel.get_bytes(&self.data, self.data_size as usize)
+    }
+}
+
+// Quotas (srfs only): limits on the bytes (file sizes) and inodes (files,
+// directories, symlinks) owned by a user, or under a directory; zero means
+// no limit. Only root sets quotas; users may query their own, and those of
+// directories they can read. Root (uid 0) has no user quota.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct QuotaData {
+    pub max_bytes: u64,
+    pub max_inodes: u64,
+    pub used_bytes: u64,
+    pub used_inodes: u64,
+}
+
+#[repr(C, align(8))]
+pub struct QuotaRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_QUOTA_*; flags: F_*.
+    pub parent_fd: u64,                        // if 0, fname should be absolute.
+    pub max_bytes: u64,                        // CMD_QUOTA_SET only.
+    pub max_inodes: u64,                       // CMD_QUOTA_SET only.
+    pub uid: u32,                              // With F_USER.
+    pub fname_size: u16, // The directory, or, with F_USER, any path on the filesystem.
+    pub fname: [u8; 0],  // array of bytes with size of fname_size.
+}
+
+impl QuotaRequest {
+    pub const F_USER: u32 = 1;
+}
+
+#[repr(C, align(8))]
+pub struct QuotaResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub quota: QuotaData, // CMD_QUOTA_GET only.
+}
+
+// Mounting (root only; in the root namespace or one with CAP_SYS). Sources are:
+// - "tmpfs": a new, empty tmpfs of up to max_bytes (zero: the default size);
+// - "/dev/vdX" or "/dev/vdXN": a FAT or ext2 volume on a virtio drive, or on
+//   its N-th MBR partition;
+// - "virtiofs:TAG": the host directory shared via virtio-fs as TAG;
+// - "snapshot:NAME": snapshot NAME (see SnapshotRequest), read-only, of the
+//   volume the mount point is on.
+// The mount point must be an existing directory. The mount table is listed
+// in /proc/mounts, a "source mount_point fs_type" line per mount.
+#[repr(C, align(8))]
+pub struct MountRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_MOUNT or CMD_UNMOUNT.
+    pub max_bytes: u64,
+    pub source_size: u16, // Zero for CMD_UNMOUNT.
+    pub target_size: u16,
+    pub data: [u8; 0], // The source, then the target (the mount point).
+}
+
+impl MountRequest {
+    pub fn build(
+        &mut self,
+        cmd: u16,
+        source: &str,
+        target: &str,
+        max_bytes: u64,
+        raw_channel: &moto_ipc::sync::RawChannel,
+    ) -> Result<(), ErrorCode> {
+        self.header.cmd = cmd;
+        self.header.ver = 0;
+        self.header.flags = 0;
+        self.max_bytes = max_bytes;
+        self.source_size = source.len() as u16;
+        self.target_size = target.len() as u16;
+
+        let bytes =
+            unsafe { raw_channel.get_bytes_mut(&mut self.data, source.len() + target.len())? };
+        bytes[0..source.len()].copy_from_slice(source.as_bytes());
+        bytes[source.len()..].copy_from_slice(target.as_bytes());
+        Ok(())
+    }
+
+    // (source, target).
+    pub unsafe fn paths<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<(&'a str, &'a str), ErrorCode> {
+        let source_size = self.source_size as usize;
+        let bytes = raw_channel.get_bytes(&self.data, source_size + self.target_size as usize)?;
+        let source =
+            core::str::from_utf8(&bytes[0..source_size]).map_err(|_| ErrorCode::InvalidArgument)?;
+        let target =
+            core::str::from_utf8(&bytes[source_size..]).map_err(|_| ErrorCode::InvalidFilename)?;
+        Ok((source, target))
+    }
+}
+
+#[repr(C, align(8))]
+pub struct MountResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+}
+
+// Snapshots (srfs only; allowed to whoever may mount): read-only views of a
+// volume as it was when the snapshot was taken. The source is the name of
+// the snapshot, the target any path on the volume. CMD_SNAPSHOT_CREATE sets
+// max_bytes of the volume aside for the snapshot to keep the old contents
+// of what changes later in; a snapshot that runs out of them is
+// invalidated, and can only be deleted. Snapshots are mounted with
+// MountRequest.
+pub type SnapshotRequest = MountRequest;
+
+pub const MAX_SNAPSHOT_NAME: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SnapshotData {
+    pub created: u64,    // Nanoseconds since the Unix epoch.
+    pub max_bytes: u64,  // Set aside for the snapshot.
+    pub used_bytes: u64, // Of max_bytes.
+    pub valid: u8,       // Zero if the snapshot ran out of space.
+    pub reserved: u8,
+    pub name_size: u16,
+    pub reserved_2: u32,
+    pub name: [u8; MAX_SNAPSHOT_NAME],
+}
+
+#[repr(C, align(8))]
+pub struct SnapshotResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub num_snapshots: u16, // CMD_SNAPSHOT_LIST only.
+    pub snapshots: [SnapshotData; 0],
+}
+
+// Change notification. Watches are added to a connection, which then
+// collects events from all of them; the server wakes the connection's
+// handle when an event is queued, so clients wait on it (SysCpu::wait)
+// and drain the events with CMD_WATCH_NEXT.
+//
+// Watches are on paths, not on files: a watch on a directory reports
+// changes of its direct entries (WatchNextResponse::name is the entry's
+// name), a watch on anything reports changes of the watched path itself
+// (name is empty).
+pub type WatchAddRequest = StatRequest;
+
+#[repr(C, align(8))]
+pub struct WatchAddResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub wd: u32, // Watch descriptor.
+}
+
+#[repr(C, align(8))]
+pub struct WatchRemoveRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_WATCH_REMOVE
+    pub wd: u32,
+}
+
+pub type WatchRemoveResponse = CloseFdResponse;
+
+// Fails with NotReady if there are no queued events.
+#[repr(C, align(8))]
+pub struct WatchNextRequest {
+    pub header: moto_ipc::sync::RequestHeader, // CMD_WATCH_NEXT
+}
+
+#[repr(C, align(8))]
+pub struct WatchNextResponse {
+    pub header: moto_ipc::sync::ResponseHeader,
+    pub wd: u32,
+    pub event: u16, // WATCH_EVENT_*.
+    pub name_size: u16,
+    pub name: [u8; 0], // array of bytes with size of name_size.
+}
+
+impl WatchNextResponse {
+    pub unsafe fn name<'a>(
+        &'a self,
+        raw_channel: &'a moto_ipc::sync::RawChannel,
+    ) -> Result<&'a str, ErrorCode> {
+        let bytes = raw_channel.get_bytes(&self.name, self.name_size as usize)?;
+        core::str::from_utf8(bytes).map_err(|_| ErrorCode::InvalidArgument)
+    }
+}
+
+pub const WATCH_EVENT_CREATE: u16 = 1;
+pub const WATCH_EVENT_MODIFY: u16 = 2; // Consecutive writes are reported once.
+pub const WATCH_EVENT_DELETE: u16 = 3;
+pub const WATCH_EVENT_RENAME_FROM: u16 = 4;
+pub const WATCH_EVENT_RENAME_TO: u16 = 5;
+pub const WATCH_EVENT_ATTRIB: u16 = 6; // chmod/chown.
+pub const WATCH_EVENT_OVERFLOW: u16 = 7; // Events were dropped; wd is zero.
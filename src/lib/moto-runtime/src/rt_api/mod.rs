@@ -2,7 +2,7 @@ pub mod fs;
 pub mod net;
 pub mod process;
 
-pub const TEMP_DIR: &str = "/sys/tmp";
+pub const TEMP_DIR: &str = "/tmp";
 
 #[repr(C, align(8))]
 pub struct RequestHeader {
@@ -23,7 +23,35 @@ pub const CMD_TCP_STREAM_SET_OPTION: u16 = CMD_MIN + 9;
 pub const CMD_TCP_STREAM_GET_OPTION: u16 = CMD_MIN + 10;
 pub const CMD_TCP_STREAM_CLOSE: u16 = CMD_MIN + 11;
 
-pub const CMD_MAX: u16 = CMD_TCP_STREAM_CLOSE;
+pub const CMD_UDP_SOCKET_BIND: u16 = CMD_MIN + 12;
+pub const CMD_UDP_SOCKET_TX: u16 = CMD_MIN + 13;
+pub const CMD_UDP_SOCKET_RX: u16 = CMD_MIN + 14;
+pub const CMD_UDP_SOCKET_SET_OPTION: u16 = CMD_MIN + 15;
+pub const CMD_UDP_SOCKET_GET_OPTION: u16 = CMD_MIN + 16;
+pub const CMD_UDP_SOCKET_DROP: u16 = CMD_MIN + 17;
+pub const CMD_DNS_LOOKUP: u16 = CMD_MIN + 18;
+
+pub const CMD_LOCAL_SOCKET_BIND: u16 = CMD_MIN + 19;
+pub const CMD_LOCAL_SOCKET_PAIR: u16 = CMD_MIN + 20;
+pub const CMD_LOCAL_LISTENER_ACCEPT: u16 = CMD_MIN + 21;
+pub const CMD_LOCAL_STREAM_CONNECT: u16 = CMD_MIN + 22;
+pub const CMD_LOCAL_SOCKET_TX: u16 = CMD_MIN + 23;
+pub const CMD_LOCAL_SOCKET_RX: u16 = CMD_MIN + 24;
+pub const CMD_LOCAL_SOCKET_RX_ACK: u16 = CMD_MIN + 25;
+pub const CMD_LOCAL_SOCKET_SHUTDOWN: u16 = CMD_MIN + 26;
+pub const CMD_LOCAL_SOCKET_DROP: u16 = CMD_MIN + 27;
+
+pub const CMD_PING_SOCKET_OPEN: u16 = CMD_MIN + 28;
+pub const CMD_PING_SOCKET_TX: u16 = CMD_MIN + 29;
+pub const CMD_PING_SOCKET_RX: u16 = CMD_MIN + 30;
+pub const CMD_PING_SOCKET_DROP: u16 = CMD_MIN + 31;
+
+/// Sent when a nonblocking write finds no free IO page in the socket's subchannel;
+/// sys-io sends it back, with id zero, once the client can allocate one. The socket
+/// kind (TX_WAIT_TCP etc.) is in flags, as local socket handles are numbered apart.
+pub const CMD_SOCKET_TX_WAIT: u16 = CMD_MIN + 32;
+
+pub const CMD_MAX: u16 = CMD_SOCKET_TX_WAIT;
 
 pub const EVT_TCP_STREAM_STATE_CHANGED: u16 = CMD_MIN;
 
@@ -31,9 +59,56 @@ pub const TCP_OPTION_SHUT_RD: u64 = 1 << 0;
 pub const TCP_OPTION_SHUT_WR: u64 = 1 << 1;
 pub const TCP_OPTION_NODELAY: u64 = 1 << 2;
 pub const TCP_OPTION_TTL: u64 = 1 << 3;
+// args_32[2]: idle time before the first probe, ms (zero: disabled);
+// args_32[3]: probe interval, ms; args_32[4]: probes before the connection is dropped.
+pub const TCP_OPTION_KEEPALIVE: u64 = 1 << 4;
+// args_32[2]: 1 if on, 0 if off; args_32[3]: seconds.
+pub const TCP_OPTION_LINGER: u64 = 1 << 5;
+// args_32[2]: bytes. Also valid for listeners, where they apply to accepted streams.
+pub const TCP_OPTION_SEND_BUFFER: u64 = 1 << 6;
+pub const TCP_OPTION_RECV_BUFFER: u64 = 1 << 7;
+// args_64[1]: bytes per second, zero for no limit. Queued bytes wait in sys-io.
+pub const TCP_OPTION_TX_RATE_LIMIT: u64 = 1 << 8;
 
 pub const TCP_RX_MAX_INFLIGHT: u64 = 8;
 
+pub const UDP_OPTION_TTL: u64 = 1 << 0;
+// IPv4 multicast group membership: the group is in args_32()[2], the local
+// (interface) address in args_32()[3]; 0.0.0.0 lets sys-io pick the interface.
+pub const UDP_OPTION_JOIN_MULTICAST_V4: u64 = 1 << 1;
+pub const UDP_OPTION_LEAVE_MULTICAST_V4: u64 = 1 << 2;
+// args_64[1]: bytes per second, as with TCP_OPTION_TX_RATE_LIMIT.
+pub const UDP_OPTION_TX_RATE_LIMIT: u64 = 1 << 3;
+
+// Socket kinds, for CMD_SOCKET_TX_WAIT.
+pub const TX_WAIT_TCP: u32 = 1;
+pub const TX_WAIT_UDP: u32 = 2;
+pub const TX_WAIT_LOCAL: u32 = 3;
+
+// Local (Unix-domain) socket kinds, for CMD_LOCAL_SOCKET_BIND and CMD_LOCAL_SOCKET_PAIR.
+pub const LOCAL_KIND_LISTENER: u8 = 1;
+pub const LOCAL_KIND_STREAM: u8 = 2;
+pub const LOCAL_KIND_DATAGRAM: u8 = 3;
+
+pub const LOCAL_SHUT_RD: u64 = 1 << 0;
+pub const LOCAL_SHUT_WR: u64 = 1 << 1;
+
+/// Local socket names are either absolute paths or, if they start with a zero
+/// byte, abstract names. As with sockaddr_un, they are at most this long.
+pub const LOCAL_NAME_MAX: usize = 108;
+
+/// A local datagram shares its page with the name of the destination (TX)
+/// or of the source (RX).
+pub const LOCAL_MAX_DATAGRAM: usize = io_channel::PAGE_SIZE - LOCAL_NAME_MAX;
+
+/// How many CMD_LOCAL_SOCKET_RX messages sys-io sends before waiting for
+/// CMD_LOCAL_SOCKET_RX_ACK; as each takes a server page, this is the subchannel size.
+pub const LOCAL_RX_MAX_INFLIGHT: usize = io_channel::CHANNEL_PAGE_COUNT / IO_SUBCHANNELS;
+
+/// A datagram travels in a single IO page, so larger ones can't be sent,
+/// and larger incoming ones are truncated.
+pub const UDP_MAX_DATAGRAM: usize = io_channel::PAGE_SIZE;
+
 /// Each IO Channel in moto_ipc::io_channel has 64 pages (for the server and for the client).
 /// Using the full channel per socket is wasteful, so channels are split into subchannels.
 /// A channel can be split into 2^0, 2^1, 2^2, ... 2^6 subchannels (technically, we
@@ -138,7 +213,8 @@ pub fn accept_tcp_listener_request(handle: u64, subchannel_mask: u64) -> io_chan
 pub fn tcp_stream_connect_request(addr: &SocketAddr, subchannel_mask: u64) -> io_channel::Msg {
     let mut msg = io_channel::Msg::new();
     msg.command = CMD_TCP_STREAM_CONNECT;
-    msg.payload.args_64_mut()[0] = subchannel_mask;
+    // The address takes the whole payload if it is IPv6.
+    msg.handle = subchannel_mask;
     msg.payload.args_32_mut()[5] = 0; // timeout
     put_socket_addr(&mut msg.payload, addr);
 
@@ -156,7 +232,7 @@ pub fn tcp_stream_connect_timeout_request(
     // We have only 32 bits for timeout. ~10ms granularity is fine.
     let timeout = timeout.as_u64() >> 27;
     assert!(timeout < (u32::MAX) as u64);
-    msg.payload.args_64_mut()[0] = subchannel_mask;
+    msg.handle = subchannel_mask;
     msg.payload.args_32_mut()[5] = timeout as u32;
     put_socket_addr(&mut msg.payload, addr);
 
@@ -189,6 +265,15 @@ pub fn tcp_stream_tx_msg(
     msg
 }
 
+pub fn socket_tx_wait_msg(handle: u64, kind: u32) -> io_channel::Msg {
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_SOCKET_TX_WAIT;
+    msg.handle = handle;
+    msg.flags = kind;
+
+    msg
+}
+
 pub fn tcp_stream_rx_msg(
     handle: u64,
     io_page: io_channel::IoPage,
@@ -205,6 +290,157 @@ pub fn tcp_stream_rx_msg(
     msg
 }
 
+/// Prepare CMD_UDP_SOCKET_BIND IO message. Port zero binds to an ephemeral port;
+/// the response carries the address bound to. Incoming datagrams arrive as
+/// CMD_UDP_SOCKET_RX messages in pages of the subchannel.
+pub fn udp_socket_bind_request(addr: &SocketAddr, subchannel_idx: usize) -> io_channel::Msg {
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_UDP_SOCKET_BIND;
+    msg.flags = subchannel_idx as u32;
+    put_socket_addr(&mut msg.payload, addr);
+
+    msg
+}
+
+/// CMD_UDP_SOCKET_TX and CMD_UDP_SOCKET_RX messages carry a datagram: its page,
+/// its length (in flags), and the remote address (the destination of TX, the
+/// source of RX). The address takes most of the payload; the page goes where
+/// neither an IPv4 nor an IPv6 address reaches.
+///
+/// TX gets no response unless it fails: sys-io then sends a CMD_UDP_SOCKET_TX
+/// message with the error in status (and no page) to the socket.
+pub fn udp_socket_datagram_msg(
+    command: u16,
+    handle: u64,
+    io_page: io_channel::IoPage,
+    sz: usize,
+    addr: &SocketAddr,
+) -> io_channel::Msg {
+    debug_assert!(command == CMD_UDP_SOCKET_TX || command == CMD_UDP_SOCKET_RX);
+    debug_assert!(sz <= UDP_MAX_DATAGRAM);
+    let mut msg = io_channel::Msg::new();
+    msg.command = command;
+    msg.handle = handle;
+    msg.flags = sz as u32;
+    put_socket_addr(&mut msg.payload, addr);
+    msg.payload.shared_pages_mut()[8] = io_channel::IoPage::into_u16(io_page);
+
+    msg
+}
+
+pub fn udp_socket_datagram_page(msg: &io_channel::Msg) -> u16 {
+    msg.payload.shared_pages()[8]
+}
+
+/// The largest echo payload a ping socket sends: what fits into an unfragmented
+/// IPv6 packet on a 1500-byte MTU link.
+pub const PING_MAX_PAYLOAD: usize = 1500 - 40 - 8;
+
+/// Prepare CMD_PING_SOCKET_OPEN IO message. The response carries the ICMP echo
+/// identifier sys-io allocated for the socket in args_16[0]. Echo replies arrive
+/// as CMD_PING_SOCKET_RX messages in pages of the subchannel.
+pub fn ping_socket_open_request(subchannel_idx: usize) -> io_channel::Msg {
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_PING_SOCKET_OPEN;
+    msg.flags = subchannel_idx as u32;
+
+    msg
+}
+
+/// CMD_PING_SOCKET_TX and CMD_PING_SOCKET_RX messages carry an echo request or
+/// reply: the payload in the page (its length in flags), the remote address,
+/// and the sequence number. sys-io builds the ICMP header; the identifier is
+/// the socket's. The layout is as with UDP datagrams, with the sequence number
+/// where the IPv6 port would be.
+pub fn ping_socket_msg(
+    command: u16,
+    handle: u64,
+    io_page: io_channel::IoPage,
+    sz: usize,
+    addr: &IpAddr,
+    seq: u16,
+) -> io_channel::Msg {
+    debug_assert!(command == CMD_PING_SOCKET_TX || command == CMD_PING_SOCKET_RX);
+    debug_assert!(sz <= PING_MAX_PAYLOAD);
+    let mut msg = io_channel::Msg::new();
+    msg.command = command;
+    msg.handle = handle;
+    msg.flags = sz as u32;
+    put_socket_addr(&mut msg.payload, &SocketAddr::new(*addr, 0));
+    msg.payload.args_16_mut()[9] = seq;
+    msg.payload.shared_pages_mut()[8] = io_channel::IoPage::into_u16(io_page);
+
+    msg
+}
+
+pub fn ping_socket_page(msg: &io_channel::Msg) -> u16 {
+    msg.payload.shared_pages()[8]
+}
+
+pub fn ping_socket_addr(msg: &io_channel::Msg) -> Result<IpAddr, ErrorCode> {
+    get_socket_addr(&msg.payload).map(|addr| addr.ip())
+}
+
+pub fn ping_socket_seq(msg: &io_channel::Msg) -> u16 {
+    msg.payload.args_16()[9]
+}
+
+/// The longest hostname CMD_DNS_LOOKUP accepts, as in RFC 1035.
+pub const DNS_MAX_NAME_LEN: usize = 253;
+
+/// At most this many addresses are returned for a hostname.
+pub const DNS_MAX_ADDRS: usize = 32;
+
+// In a CMD_DNS_LOOKUP response page each address takes a family byte (4 or 6),
+// followed by the address octets (IPv4 octets are followed by padding).
+const DNS_ADDR_ENTRY_SIZE: usize = 17;
+
+/// Prepare CMD_DNS_LOOKUP IO message. The hostname is in the page; the response
+/// carries the number of addresses found and, if there are any, a page
+/// in the subchannel with the addresses (see put_dns_addr()).
+pub fn dns_lookup_request(
+    io_page: io_channel::IoPage,
+    name_len: usize,
+    subchannel_idx: usize,
+) -> io_channel::Msg {
+    debug_assert!(name_len <= DNS_MAX_NAME_LEN);
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_DNS_LOOKUP;
+    msg.flags = subchannel_idx as u32;
+    msg.payload.shared_pages_mut()[0] = io_channel::IoPage::into_u16(io_page);
+    msg.payload.args_16_mut()[1] = name_len as u16;
+
+    msg
+}
+
+pub fn put_dns_addr(page_bytes: &mut [u8], idx: usize, addr: &IpAddr) {
+    let entry = &mut page_bytes[(idx * DNS_ADDR_ENTRY_SIZE)..((idx + 1) * DNS_ADDR_ENTRY_SIZE)];
+    match addr {
+        IpAddr::V4(addr) => {
+            entry[0] = 4;
+            entry[1..5].copy_from_slice(&addr.octets());
+        }
+        IpAddr::V6(addr) => {
+            entry[0] = 6;
+            entry[1..17].copy_from_slice(&addr.octets());
+        }
+    }
+}
+
+pub fn get_dns_addr(page_bytes: &[u8], idx: usize) -> Result<IpAddr, ErrorCode> {
+    let entry = &page_bytes[(idx * DNS_ADDR_ENTRY_SIZE)..((idx + 1) * DNS_ADDR_ENTRY_SIZE)];
+    match entry[0] {
+        4 => Ok(IpAddr::V4(Ipv4Addr::new(
+            entry[1], entry[2], entry[3], entry[4],
+        ))),
+        6 => {
+            let octets: [u8; 16] = entry[1..17].try_into().unwrap();
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => Err(ErrorCode::InvalidArgument),
+    }
+}
+
 pub fn get_socket_addr(payload: &io_channel::Payload) -> Result<SocketAddr, ErrorCode> {
     match payload.args_32()[5] & 1 {
         0 => {
@@ -236,3 +472,133 @@ pub fn put_socket_addr(payload: &mut io_channel::Payload, addr: &SocketAddr) {
         }
     }
 }
+
+/// The process on the other end of a local socket, as sys-io sees it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalCreds {
+    pub pid: u64,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Prepare CMD_LOCAL_SOCKET_BIND IO message: a listener or a datagram socket
+/// named by the page's first `name_len` bytes (datagram sockets can be unnamed).
+/// Datagrams arrive as CMD_LOCAL_SOCKET_RX messages in pages of the subchannel.
+pub fn local_socket_bind_request(
+    kind: u8,
+    io_page: io_channel::IoPage,
+    name_len: usize,
+    subchannel_idx: usize,
+) -> io_channel::Msg {
+    debug_assert!(kind == LOCAL_KIND_LISTENER || kind == LOCAL_KIND_DATAGRAM);
+    debug_assert!(name_len <= LOCAL_NAME_MAX);
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_LOCAL_SOCKET_BIND;
+    msg.flags = subchannel_idx as u32;
+    msg.payload.shared_pages_mut()[0] = io_channel::IoPage::into_u16(io_page);
+    msg.payload.args_16_mut()[1] = name_len as u16;
+    msg.payload.args_8_mut()[4] = kind;
+
+    msg
+}
+
+/// Prepare CMD_LOCAL_SOCKET_PAIR IO message: two unnamed sockets connected to
+/// each other. The response carries the handle of the first one in `handle`,
+/// and of the second one in args_64[0].
+pub fn local_socket_pair_request(
+    kind: u8,
+    subchannel_idx_1: usize,
+    subchannel_idx_2: usize,
+) -> io_channel::Msg {
+    debug_assert!(kind == LOCAL_KIND_STREAM || kind == LOCAL_KIND_DATAGRAM);
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_LOCAL_SOCKET_PAIR;
+    msg.flags = subchannel_idx_1 as u32;
+    msg.payload.args_32_mut()[0] = subchannel_idx_2 as u32;
+    msg.payload.args_8_mut()[4] = kind;
+
+    msg
+}
+
+/// Prepare CMD_LOCAL_STREAM_CONNECT IO message; the listener's name is in the page.
+/// The response carries the credentials of the listening process.
+pub fn local_stream_connect_request(
+    io_page: io_channel::IoPage,
+    name_len: usize,
+    subchannel_idx: usize,
+) -> io_channel::Msg {
+    debug_assert!(name_len <= LOCAL_NAME_MAX);
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_LOCAL_STREAM_CONNECT;
+    msg.flags = subchannel_idx as u32;
+    msg.payload.shared_pages_mut()[0] = io_channel::IoPage::into_u16(io_page);
+    msg.payload.args_16_mut()[1] = name_len as u16;
+
+    msg
+}
+
+/// Prepare CMD_LOCAL_LISTENER_ACCEPT IO message. The response carries the handle
+/// of the new stream and the credentials of the connecting process.
+pub fn local_listener_accept_request(handle: u64, subchannel_idx: usize) -> io_channel::Msg {
+    let mut msg = io_channel::Msg::new();
+    msg.command = CMD_LOCAL_LISTENER_ACCEPT;
+    msg.handle = handle;
+    msg.flags = subchannel_idx as u32;
+
+    msg
+}
+
+/// CMD_LOCAL_SOCKET_TX and CMD_LOCAL_SOCKET_RX messages carry a page with `sz` bytes
+/// of data (in flags), followed by `name_len` bytes of the datagram's destination (TX)
+/// or source (RX) name; stream messages have no names. RX messages also carry the
+/// sender's credentials. A stream RX message without a page marks the end of stream.
+pub fn local_socket_data_msg(
+    command: u16,
+    handle: u64,
+    io_page: Option<io_channel::IoPage>,
+    sz: usize,
+    name_len: usize,
+) -> io_channel::Msg {
+    debug_assert!(command == CMD_LOCAL_SOCKET_TX || command == CMD_LOCAL_SOCKET_RX);
+    debug_assert!(sz + name_len <= io_channel::PAGE_SIZE);
+    let mut msg = io_channel::Msg::new();
+    msg.command = command;
+    msg.handle = handle;
+    msg.flags = sz as u32;
+    match io_page {
+        Some(page) => {
+            msg.payload.shared_pages_mut()[0] = io_channel::IoPage::into_u16(page);
+        }
+        None => msg.payload.args_8_mut()[4] = 1,
+    }
+    msg.payload.args_16_mut()[1] = name_len as u16;
+
+    msg
+}
+
+pub fn local_socket_data_page(msg: &io_channel::Msg) -> Option<u16> {
+    if msg.payload.args_8()[4] == 0 {
+        Some(msg.payload.shared_pages()[0])
+    } else {
+        None
+    }
+}
+
+pub fn local_socket_data_name_len(msg: &io_channel::Msg) -> usize {
+    (msg.payload.args_16()[1] as usize).min(LOCAL_NAME_MAX)
+}
+
+// Credentials go where neither the page nor the handle of a socket pair are.
+pub fn put_local_creds(payload: &mut io_channel::Payload, creds: &LocalCreds) {
+    payload.args_64_mut()[1] = creds.pid;
+    payload.args_32_mut()[4] = creds.uid;
+    payload.args_32_mut()[5] = creds.gid;
+}
+
+pub fn get_local_creds(payload: &io_channel::Payload) -> LocalCreds {
+    LocalCreds {
+        pid: payload.args_64()[1],
+        uid: payload.args_32()[4],
+        gid: payload.args_32()[5],
+    }
+}
@@ -17,6 +17,18 @@ unsafe impl GlobalAlloc for BackEndAllocator {
         const PAGE_4K: u64 = 1 << 12;
         assert_eq!(sys_mem::PAGE_SIZE_SMALL, PAGE_4K);
 
+        // Very large allocations are rounded up to 2M, so that they are
+        // backed by huge pages; the overhead is less than 1/16.
+        const HUGE_ALLOC_MIN_SIZE: u64 = 16 * sys_mem::PAGE_SIZE_MID;
+        if layout.size() as u64 >= HUGE_ALLOC_MIN_SIZE {
+            let alloc_size = align_up(layout.size() as u64, sys_mem::PAGE_SIZE_MID);
+            return if let Ok(start) = SysMem::alloc_huge(alloc_size) {
+                start as usize as *mut u8
+            } else {
+                core::ptr::null_mut()
+            };
+        }
+
         let alloc_size = align_up(layout.size() as u64, PAGE_4K);
         if let Ok(start) = SysMem::alloc(PAGE_4K, alloc_size >> 12) {
             start as usize as *mut u8
@@ -152,11 +164,14 @@ pub fn log_backtrace(binary: &str) {
     use core::fmt::Write;
     let mut writer = alloc::string::String::with_capacity(256);
     let backtrace = get_backtrace();
+    // Print link-time addresses, so that they can be symbolized with the binary.
+    let slide = moto_sys::ProcessStaticPage::get().image_slide;
     write!(&mut writer, "backtrace: {}", binary).ok();
     for addr in backtrace {
         if addr == 0 {
             break;
         }
+        let addr = addr.saturating_sub(slide);
 
         if addr > (1_u64 << 40) {
             break;
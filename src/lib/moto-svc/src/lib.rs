@@ -0,0 +1,294 @@
+// Client side of service supervision: sys-init starts the services declared
+// in /sys/cfg/services.cfg, restarts them when they exit, and serves
+// start/stop/status/logs requests at SERVER_URL.
+
+use moto_ipc::sync::{ChannelSize, ClientConnection};
+use moto_sys::ErrorCode;
+
+pub const SERVER_URL: &str = "sys-svc";
+pub const MAX_NAME_LEN: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ServiceState {
+    Stopped,
+    Waiting, // For dependencies to start.
+    Running,
+    Backoff, // Waiting to be restarted.
+    Exited,  // Will not be restarted (see `restart` in services.cfg).
+}
+
+impl ServiceState {
+    fn from_u8(val: u8) -> Result<Self, ErrorCode> {
+        match val {
+            0 => Ok(Self::Stopped),
+            1 => Ok(Self::Waiting),
+            2 => Ok(Self::Running),
+            3 => Ok(Self::Backoff),
+            4 => Ok(Self::Exited),
+            _ => Err(ErrorCode::InternalError),
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::Stopped => 0,
+            Self::Waiting => 1,
+            Self::Running => 2,
+            Self::Backoff => 3,
+            Self::Exited => 4,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stopped => "stopped",
+            Self::Waiting => "waiting",
+            Self::Running => "running",
+            Self::Backoff => "backoff",
+            Self::Exited => "exited",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ServiceState,
+    pub uptime: Option<std::time::Duration>, // If running.
+    pub restarts: u32,
+    pub last_exit: Option<i32>,
+}
+
+fn connect() -> Result<ClientConnection, ErrorCode> {
+    let mut conn = ClientConnection::new(ChannelSize::Small)?;
+    conn.connect(SERVER_URL)?;
+    Ok(conn)
+}
+
+fn do_rpc(cmd: u16, name: &str) -> Result<ClientConnection, ErrorCode> {
+    if name.len() > MAX_NAME_LEN {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let mut conn = connect()?;
+    implementation::Request::prepare(conn.data_mut(), cmd, name);
+    conn.do_rpc(None)?;
+    implementation::Response::parse(conn.data())?;
+    Ok(conn)
+}
+
+/// Start the service and, first, the services it depends on.
+pub fn start(name: &str) -> Result<(), ErrorCode> {
+    do_rpc(implementation::CMD_START, name).map(|_| ())
+}
+
+/// Stop the service and the services that depend on it.
+pub fn stop(name: &str) -> Result<(), ErrorCode> {
+    do_rpc(implementation::CMD_STOP, name).map(|_| ())
+}
+
+/// Kill the service if it is running, and start it again right away.
+pub fn restart(name: &str) -> Result<(), ErrorCode> {
+    do_rpc(implementation::CMD_RESTART, name).map(|_| ())
+}
+
+/// The status of the service, or of all services if @name is empty.
+pub fn status(name: &str) -> Result<Vec<ServiceStatus>, ErrorCode> {
+    let conn = do_rpc(implementation::CMD_STATUS, name)?;
+    implementation::StatusEntry::parse_all(conn.data())
+}
+
+/// The most recent output (stdout and stderr) of the service.
+pub fn logs(name: &str) -> Result<String, ErrorCode> {
+    let conn = do_rpc(implementation::CMD_LOGS, name)?;
+    let bytes = implementation::Response::payload(conn.data())?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// Implementation details.
+#[doc(hidden)]
+pub mod implementation {
+    use super::{ServiceState, ServiceStatus, MAX_NAME_LEN};
+    use moto_ipc::sync::{RequestHeader, ResponseHeader};
+    use moto_sys::ErrorCode;
+    use std::mem::size_of;
+
+    pub const CMD_START: u16 = 1;
+    pub const CMD_STOP: u16 = 2;
+    pub const CMD_RESTART: u16 = 3;
+    pub const CMD_STATUS: u16 = 4;
+    pub const CMD_LOGS: u16 = 5;
+
+    #[repr(C, align(8))]
+    pub struct Request {
+        pub header: RequestHeader,
+        pub name_len: u8, // The service name is the payload.
+    }
+
+    impl Request {
+        pub fn prepare(buffer: &mut [u8], cmd: u16, name: &str) {
+            assert!(name.len() <= MAX_NAME_LEN);
+            assert!(buffer.len() >= size_of::<Self>() + name.len());
+
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { buffer.align_to_mut::<Self>() };
+            assert_eq!(prefix.len(), 0);
+            assert!(!data.is_empty());
+
+            let req = &mut data[0];
+            req.header.cmd = cmd;
+            req.header.ver = 0;
+            req.header.flags = 0;
+            req.name_len = name.len() as u8;
+
+            buffer[size_of::<Self>()..(size_of::<Self>() + name.len())]
+                .copy_from_slice(name.as_bytes());
+        }
+
+        pub fn parse(buffer: &[u8]) -> Result<(u16, &str), ErrorCode> {
+            assert!(buffer.len() >= size_of::<Self>());
+
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { buffer.align_to::<Self>() };
+            assert_eq!(prefix.len(), 0);
+
+            let req = &data[0];
+            if req.header.ver != 0 || req.name_len as usize > MAX_NAME_LEN {
+                return Err(ErrorCode::InvalidArgument);
+            }
+            let name = &buffer[size_of::<Self>()..(size_of::<Self>() + req.name_len as usize)];
+            let name = core::str::from_utf8(name).map_err(|_| ErrorCode::InvalidArgument)?;
+            Ok((req.header.cmd, name))
+        }
+    }
+
+    #[repr(C, align(8))]
+    pub struct Response {
+        pub header: ResponseHeader,
+        // CMD_STATUS: the number of StatusEntry structs that follow;
+        // CMD_LOGS: the number of bytes that follow.
+        pub payload_size: u32,
+    }
+
+    impl Response {
+        // Returns the payload area of the response buffer.
+        pub fn prepare(buffer: &mut [u8], payload_size: u32) -> &mut [u8] {
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { buffer.align_to_mut::<Self>() };
+            assert_eq!(prefix.len(), 0);
+            assert!(!data.is_empty());
+
+            let resp = &mut data[0];
+            resp.header.result = 0;
+            resp.header.ver = 0;
+            resp.payload_size = payload_size;
+
+            &mut buffer[size_of::<Self>()..]
+        }
+
+        pub fn parse(buffer: &[u8]) -> Result<(), ErrorCode> {
+            assert!(buffer.len() >= size_of::<Self>());
+
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { buffer.align_to::<Self>() };
+            assert_eq!(prefix.len(), 0);
+
+            match data[0].header.result {
+                0 => Ok(()),
+                e => Err(e.into()),
+            }
+        }
+
+        pub fn payload(buffer: &[u8]) -> Result<&[u8], ErrorCode> {
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { buffer.align_to::<Self>() };
+            assert_eq!(prefix.len(), 0);
+
+            let size = data[0].payload_size as usize;
+            if size > buffer.len() - size_of::<Self>() {
+                return Err(ErrorCode::InternalError);
+            }
+            Ok(&buffer[size_of::<Self>()..(size_of::<Self>() + size)])
+        }
+    }
+
+    #[repr(C, align(8))]
+    #[derive(Clone, Copy)]
+    pub struct StatusEntry {
+        pub uptime_ms: u64, // Zero if not running.
+        pub restarts: u32,
+        pub last_exit: i32,
+        pub has_exited: u8,
+        pub state: u8,
+        pub name_len: u8,
+        pub reserved: u8,
+        pub name: [u8; MAX_NAME_LEN],
+    }
+
+    impl StatusEntry {
+        pub const fn max_entries(buffer_size: usize) -> usize {
+            (buffer_size - size_of::<Response>()) / size_of::<Self>()
+        }
+
+        pub fn new(status: &ServiceStatus) -> Self {
+            let mut name = [0_u8; MAX_NAME_LEN];
+            name[0..status.name.len()].copy_from_slice(status.name.as_bytes());
+            Self {
+                uptime_ms: status.uptime.map(|d| d.as_millis() as u64).unwrap_or(0),
+                restarts: status.restarts,
+                last_exit: status.last_exit.unwrap_or(0),
+                has_exited: status.last_exit.is_some() as u8,
+                state: status.state.as_u8(),
+                name_len: status.name.len() as u8,
+                reserved: 0,
+                name,
+            }
+        }
+
+        pub fn write_all(payload: &mut [u8], entries: &[Self]) {
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { payload.align_to_mut::<Self>() };
+            assert_eq!(prefix.len(), 0);
+            data[0..entries.len()].copy_from_slice(entries);
+        }
+
+        pub fn parse_all(buffer: &[u8]) -> Result<Vec<ServiceStatus>, ErrorCode> {
+            // Safe because Response is POD, so transmuting into it is safe.
+            let (prefix, data, _) = unsafe { buffer.align_to::<Response>() };
+            assert_eq!(prefix.len(), 0);
+            let num_entries = data[0].payload_size as usize;
+            if num_entries > Self::max_entries(buffer.len()) {
+                return Err(ErrorCode::InternalError);
+            }
+
+            // Safe because Self is POD, so transmuting into it is safe.
+            let (prefix, entries, _) =
+                unsafe { buffer[size_of::<Response>()..].align_to::<Self>() };
+            assert_eq!(prefix.len(), 0);
+
+            let mut result = Vec::with_capacity(num_entries);
+            for entry in &entries[0..num_entries] {
+                let name = entry
+                    .name
+                    .get(0..entry.name_len as usize)
+                    .ok_or(ErrorCode::InternalError)?;
+                result.push(ServiceStatus {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    state: ServiceState::from_u8(entry.state)?,
+                    uptime: if entry.state == ServiceState::Running.as_u8() {
+                        Some(std::time::Duration::from_millis(entry.uptime_ms))
+                    } else {
+                        None
+                    },
+                    restarts: entry.restarts,
+                    last_exit: if entry.has_exited != 0 {
+                        Some(entry.last_exit)
+                    } else {
+                        None
+                    },
+                });
+            }
+            Ok(result)
+        }
+    }
+}
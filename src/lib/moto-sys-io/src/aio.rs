@@ -0,0 +1,624 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+use moto_ipc::sync::{ClientConnection, RawChannel};
+use moto_runtime::rt_api::fs::*;
+use moto_sys::{ErrorCode, SysCpu, SysHandle};
+
+// Completion-based file I/O.
+//
+// An IoQueue has a few connections to the FS driver (its depth) and a single
+// I/O thread. Reads, writes and syncs are submitted without blocking and
+// return an IoOp, which completes when the I/O thread sees the driver's
+// response: block on it via IoOp::wait(), or .await it from any executor
+// (the I/O thread wakes the task's waker). So a server with many outstanding
+// disk operations needs neither a thread per operation nor an executor that
+// knows about Motor OS.
+//
+// Each file is pinned to one of the connections: operations on a file run
+// in submission order, one at a time; operations on files pinned to
+// different connections run concurrently.
+
+pub const MAX_DEPTH: usize = 8;
+
+/// A finished read or write: the buffer passed to read_at() or write_at(),
+/// and the number of bytes read into it or written from it. A read returns
+/// fewer bytes than the buffer's length only at EOF.
+#[derive(Debug)]
+pub struct IoDone {
+    pub bytes: usize,
+    pub buf: Vec<u8>,
+}
+
+struct Completion<T> {
+    done: AtomicU32, // A futex: 0 => pending, 1 => done.
+    result: Mutex<Option<Result<T, ErrorCode>>>,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl<T> Completion<T> {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            done: AtomicU32::new(0),
+            result: Mutex::new(None),
+            waker: Mutex::new(None),
+        })
+    }
+
+    fn complete(&self, result: Result<T, ErrorCode>) {
+        *self.result.lock().unwrap() = Some(result);
+        self.done.store(1, Ordering::Release);
+        moto_runtime::futex_wake(&self.done);
+
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire) == 1
+    }
+
+    fn take(&self) -> Result<T, ErrorCode> {
+        self.result
+            .lock()
+            .unwrap()
+            .take()
+            .expect("aio: the result of an IoOp has already been taken")
+    }
+
+    fn wait(&self) -> Result<T, ErrorCode> {
+        while !self.is_done() {
+            moto_runtime::futex_wait(&self.done, 0, None);
+        }
+        self.take()
+    }
+
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<Result<T, ErrorCode>> {
+        if self.is_done() {
+            return Poll::Ready(self.take());
+        }
+
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        // complete() may have run before the waker was stored.
+        if self.is_done() {
+            return Poll::Ready(self.take());
+        }
+        Poll::Pending
+    }
+}
+
+/// A submitted operation: a future that resolves when the operation
+/// completes. Dropping it does not cancel the operation.
+pub struct IoOp {
+    completion: Arc<Completion<IoDone>>,
+}
+
+impl IoOp {
+    pub fn is_done(&self) -> bool {
+        self.completion.is_done()
+    }
+
+    /// Blocks until the operation completes.
+    pub fn wait(self) -> Result<IoDone, ErrorCode> {
+        self.completion.wait()
+    }
+}
+
+impl Future for IoOp {
+    type Output = Result<IoDone, ErrorCode>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.completion.poll(cx)
+    }
+}
+
+enum OpKind {
+    Open {
+        path: String,
+        flags: u32,
+        completion: Arc<Completion<(u64, u64)>>, // (fd, size).
+    },
+    Read {
+        fd: u64,
+        offset: u64,
+        buf: Vec<u8>,
+        done: usize,
+        completion: Arc<Completion<IoDone>>,
+    },
+    Write {
+        fd: u64,
+        offset: u64,
+        buf: Vec<u8>,
+        done: usize,
+        completion: Arc<Completion<IoDone>>,
+    },
+    Sync {
+        fd: u64,
+        flags: u32, // FileSyncRequest::F_*.
+        completion: Arc<Completion<IoDone>>,
+    },
+    Close {
+        fd: u64,
+    },
+}
+
+impl OpKind {
+    // Fills in the request for the next step of the operation.
+    unsafe fn prepare(&mut self, raw_channel: &RawChannel) -> Result<(), ErrorCode> {
+        match self {
+            OpKind::Open { path, flags, .. } => {
+                let req = raw_channel.get_mut::<FileOpenRequest>();
+                req.header.cmd = CMD_FILE_OPEN;
+                req.header.ver = 0;
+                req.header.flags = *flags;
+                req.parent_fd = 0;
+
+                req.fname_size = path.len() as u16;
+                raw_channel.put_bytes(path.as_bytes(), &mut req.fname)
+            }
+            OpKind::Read {
+                fd,
+                offset,
+                buf,
+                done,
+                ..
+            } => {
+                let req = raw_channel.get_mut::<FileReadRequest>();
+                req.header.cmd = CMD_FILE_READ;
+                req.header.ver = 0;
+                req.header.flags = 0;
+                req.fd = *fd;
+                req.offset = *offset + *done as u64;
+                req.max_bytes = (buf.len() - *done)
+                    .min(raw_channel.size() - core::mem::size_of::<FileReadResponse>())
+                    as u32;
+                Ok(())
+            }
+            OpKind::Write {
+                fd,
+                offset,
+                buf,
+                done,
+                ..
+            } => {
+                let req = raw_channel.get_mut::<FileWriteRequest>();
+                req.header.cmd = CMD_FILE_WRITE;
+                req.header.ver = 0;
+                req.header.flags = 0;
+                req.fd = *fd;
+                req.offset = *offset + *done as u64;
+
+                let size = (buf.len() - *done)
+                    .min(raw_channel.size() - core::mem::size_of::<FileWriteRequest>());
+                req.size = size as u32;
+                raw_channel.put_bytes(&buf[*done..(*done + size)], &mut req.data)
+            }
+            OpKind::Sync { fd, flags, .. } => {
+                let req = raw_channel.get_mut::<FileSyncRequest>();
+                req.header.cmd = CMD_FILE_SYNC;
+                req.header.ver = 0;
+                req.header.flags = *flags;
+                req.fd = *fd;
+                Ok(())
+            }
+            OpKind::Close { fd } => {
+                let req = raw_channel.get_mut::<CloseFdRequest>();
+                req.header.cmd = CMD_CLOSE_FD;
+                req.header.ver = 0;
+                req.header.flags = CloseFdRequest::F_FILE;
+                req.fd = *fd;
+                Ok(())
+            }
+        }
+    }
+
+    // Processes the response to the request from prepare(). Returns true
+    // if the operation is finished (and its completion is completed).
+    unsafe fn on_response(&mut self, raw_channel: &RawChannel) -> bool {
+        let result = raw_channel.get::<moto_ipc::sync::ResponseHeader>().result;
+        if result != 0 {
+            self.fail(ErrorCode::from(result));
+            return true;
+        }
+
+        match self {
+            OpKind::Open { completion, .. } => {
+                let resp = raw_channel.get::<FileOpenResponse>();
+                if resp.fd == 0 {
+                    completion.complete(Err(ErrorCode::InternalError));
+                } else {
+                    completion.complete(Ok((resp.fd, resp.size)));
+                }
+                true
+            }
+            OpKind::Read { buf, done, .. } => {
+                let resp = raw_channel.get::<FileReadResponse>();
+                let size = (resp.size as usize).min(buf.len() - *done);
+                match raw_channel.get_bytes(&resp.data, size) {
+                    Ok(bytes) => buf[*done..(*done + size)].copy_from_slice(bytes),
+                    Err(err) => {
+                        self.fail(err);
+                        return true;
+                    }
+                }
+                *done += size;
+
+                if size > 0 && *done < buf.len() {
+                    return false;
+                }
+                self.finish();
+                true
+            }
+            OpKind::Write { buf, done, .. } => {
+                let written = raw_channel.get::<FileWriteResponse>().written as usize;
+                *done += written.min(buf.len() - *done);
+
+                if written > 0 && *done < buf.len() {
+                    return false;
+                }
+                self.finish();
+                true
+            }
+            OpKind::Sync { completion, .. } => {
+                completion.complete(Ok(IoDone {
+                    bytes: 0,
+                    buf: Vec::new(),
+                }));
+                true
+            }
+            OpKind::Close { .. } => true,
+        }
+    }
+
+    fn finish(&mut self) {
+        match self {
+            OpKind::Read {
+                buf,
+                done,
+                completion,
+                ..
+            }
+            | OpKind::Write {
+                buf,
+                done,
+                completion,
+                ..
+            } => completion.complete(Ok(IoDone {
+                bytes: *done,
+                buf: core::mem::take(buf),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    fn fail(&mut self, err: ErrorCode) {
+        match self {
+            OpKind::Open { completion, .. } => completion.complete(Err(err)),
+            OpKind::Read { completion, .. }
+            | OpKind::Write { completion, .. }
+            | OpKind::Sync { completion, .. } => completion.complete(Err(err)),
+            OpKind::Close { .. } => {}
+        }
+    }
+}
+
+struct Slot {
+    conn: ClientConnection,
+    ops: VecDeque<OpKind>, // The front one is in flight if @busy.
+    busy: bool,
+    files: usize, // Open files pinned to this slot.
+}
+
+impl Slot {
+    // Processes the response to the in-flight request, if it is in,
+    // and sends the next request, if any.
+    fn poll(&mut self) {
+        let raw_channel = self.conn.raw_channel();
+        loop {
+            if self.busy {
+                if !self.conn.poll_rpc() {
+                    return;
+                }
+                self.busy = false;
+
+                let op = self.ops.front_mut().unwrap();
+                if !unsafe { op.on_response(&raw_channel) } {
+                    // More to read or write.
+                    self.start();
+                    continue;
+                }
+                self.ops.pop_front();
+            }
+
+            if self.ops.is_empty() {
+                return;
+            }
+            self.start();
+        }
+    }
+
+    fn start(&mut self) {
+        let raw_channel = self.conn.raw_channel();
+        let op = self.ops.front_mut().unwrap();
+        let res = unsafe { op.prepare(&raw_channel) }.and_then(|_| self.conn.start_rpc());
+        match res {
+            Ok(()) => self.busy = true,
+            Err(err) => self.ops.pop_front().unwrap().fail(err),
+        }
+    }
+}
+
+struct Inner {
+    slots: Mutex<Vec<Slot>>,
+    io_thread: AtomicU64, // The wake handle of the I/O thread.
+}
+
+// Reached when the IoQueue and all of its files are gone: operations still
+// queued fail, and the I/O thread exits.
+impl Drop for Inner {
+    fn drop(&mut self) {
+        for slot in self.slots.get_mut().unwrap().iter_mut() {
+            for mut op in slot.ops.drain(..) {
+                op.fail(ErrorCode::BadHandle);
+            }
+        }
+
+        let _ = SysCpu::wake(SysHandle::from(self.io_thread.load(Ordering::Acquire)));
+    }
+}
+
+impl Inner {
+    fn submit(&self, slot: usize, op: OpKind) {
+        {
+            let mut slots = self.slots.lock().unwrap();
+            let slot = &mut slots[slot];
+            slot.ops.push_back(op);
+            if slot.busy {
+                return; // The I/O thread will get to it.
+            }
+            slot.poll();
+        }
+
+        // Have the I/O thread wait on the slot's connection.
+        let _ = SysCpu::wake(SysHandle::from(self.io_thread.load(Ordering::Acquire)));
+    }
+
+    // Returns the handles to wait on.
+    fn poll(&self) -> Vec<SysHandle> {
+        let mut slots = self.slots.lock().unwrap();
+        let mut handles = Vec::with_capacity(slots.len());
+        for slot in slots.iter_mut() {
+            slot.poll();
+            if slot.busy {
+                handles.push(slot.conn.handle());
+            }
+        }
+        handles
+    }
+
+    // The driver is gone: nothing sent to it will ever complete.
+    fn on_bad_handles(&self, bad_handles: &[SysHandle]) {
+        let mut slots = self.slots.lock().unwrap();
+        for slot in slots.iter_mut() {
+            if slot.busy && bad_handles.contains(&slot.conn.handle()) {
+                slot.conn.disconnect();
+                slot.busy = false;
+                for mut op in slot.ops.drain(..) {
+                    op.fail(ErrorCode::BadHandle);
+                }
+            }
+        }
+    }
+
+    fn io_thread(inner: Weak<Inner>) {
+        loop {
+            let mut handles = match inner.upgrade() {
+                Some(inner) => inner.poll(),
+                None => return,
+            };
+
+            // Woken by the driver's responses, and by Inner::submit() and
+            // Inner::drop(). Wakes are not lost: one that came after poll()
+            // above makes this return immediately.
+            if let Err(ErrorCode::BadHandle) =
+                SysCpu::wait(&mut handles, SysHandle::NONE, SysHandle::NONE, None)
+            {
+                match inner.upgrade() {
+                    Some(inner) => inner.on_bad_handles(&handles),
+                    None => return,
+                }
+            }
+        }
+    }
+}
+
+/// A queue for completion-based file I/O; see the comment at the top of the
+/// file. Clones share the queue.
+#[derive(Clone)]
+pub struct IoQueue {
+    inner: Arc<Inner>,
+}
+
+impl IoQueue {
+    /// A queue with @depth (1..=MAX_DEPTH) connections to the FS driver.
+    pub fn new(depth: usize) -> Result<Self, ErrorCode> {
+        if depth == 0 || depth > MAX_DEPTH {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let mut slots = Vec::with_capacity(depth);
+        for _ in 0..depth {
+            slots.push(Slot {
+                conn: crate::fs::connect_to_driver()?,
+                ops: VecDeque::new(),
+                busy: false,
+                files: 0,
+            });
+        }
+
+        let inner = Arc::new(Inner {
+            slots: Mutex::new(slots),
+            io_thread: AtomicU64::new(0),
+        });
+
+        let weak = Arc::downgrade(&inner);
+        let io_thread = Arc::new(AtomicU64::new(0));
+        let io_thread_handle = io_thread.clone();
+        std::thread::Builder::new()
+            .stack_size(4096 * 16)
+            .spawn(move || {
+                io_thread_handle.store(
+                    moto_sys::UserThreadControlBlock::get().self_handle,
+                    Ordering::Release,
+                );
+                Inner::io_thread(weak)
+            })
+            .map_err(|_| ErrorCode::OutOfMemory)?;
+
+        loop {
+            let handle = io_thread.load(Ordering::Acquire);
+            if handle != 0 {
+                inner.io_thread.store(handle, Ordering::Release);
+                break;
+            }
+            core::hint::spin_loop();
+        }
+
+        Ok(Self { inner })
+    }
+
+    /// Opens the file at @abs_path with FileOpenRequest::F_* @flags. Blocks
+    /// until the driver responds; only reads and writes are asynchronous.
+    pub fn open(&self, abs_path: &str, flags: u32) -> Result<AsyncFile, ErrorCode> {
+        if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+            return Err(ErrorCode::InvalidFilename);
+        }
+
+        // Pin the file to the slot with the fewest files.
+        let slot = {
+            let mut slots = self.inner.slots.lock().unwrap();
+            let (idx, slot) = slots
+                .iter_mut()
+                .enumerate()
+                .min_by_key(|(_, slot)| slot.files)
+                .unwrap();
+            slot.files += 1;
+            idx
+        };
+
+        let completion = Completion::new();
+        self.inner.submit(
+            slot,
+            OpKind::Open {
+                path: abs_path.to_owned(),
+                flags,
+                completion: completion.clone(),
+            },
+        );
+
+        match completion.wait() {
+            Ok((fd, size)) => Ok(AsyncFile {
+                inner: self.inner.clone(),
+                slot,
+                fd,
+                size,
+            }),
+            Err(err) => {
+                self.inner.slots.lock().unwrap()[slot].files -= 1;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// A file opened via IoQueue::open(). Reads and writes are positional: there
+/// is no file position. Dropping the file closes it once the operations
+/// submitted before have completed.
+pub struct AsyncFile {
+    inner: Arc<Inner>,
+    slot: usize,
+    fd: u64,
+    size: u64,
+}
+
+impl Drop for AsyncFile {
+    fn drop(&mut self) {
+        self.inner.slots.lock().unwrap()[self.slot].files -= 1;
+        self.inner.submit(self.slot, OpKind::Close { fd: self.fd });
+    }
+}
+
+impl AsyncFile {
+    /// The size of the file when it was opened.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Reads up to buf.len() bytes at @offset into @buf.
+    pub fn read_at(&self, offset: u64, buf: Vec<u8>) -> IoOp {
+        let completion = Completion::new();
+        if buf.is_empty() {
+            completion.complete(Ok(IoDone { bytes: 0, buf }));
+            return IoOp { completion };
+        }
+        self.inner.submit(
+            self.slot,
+            OpKind::Read {
+                fd: self.fd,
+                offset,
+                buf,
+                done: 0,
+                completion: completion.clone(),
+            },
+        );
+        IoOp { completion }
+    }
+
+    /// Writes @buf at @offset.
+    pub fn write_at(&self, offset: u64, buf: Vec<u8>) -> IoOp {
+        let completion = Completion::new();
+        if buf.is_empty() {
+            completion.complete(Ok(IoDone { bytes: 0, buf }));
+            return IoOp { completion };
+        }
+        self.inner.submit(
+            self.slot,
+            OpKind::Write {
+                fd: self.fd,
+                offset,
+                buf,
+                done: 0,
+                completion: completion.clone(),
+            },
+        );
+        IoOp { completion }
+    }
+
+    /// Flushes the file to storage (fsync), after the writes submitted before.
+    pub fn sync(&self) -> IoOp {
+        self.sync_flags(0)
+    }
+
+    /// Like sync(), but flushes metadata only as needed to read the data (fdatasync).
+    pub fn datasync(&self) -> IoOp {
+        self.sync_flags(FileSyncRequest::F_DATA_ONLY)
+    }
+
+    fn sync_flags(&self, flags: u32) -> IoOp {
+        let completion = Completion::new();
+        self.inner.submit(
+            self.slot,
+            OpKind::Sync {
+                fd: self.fd,
+                flags,
+                completion: completion.clone(),
+            },
+        );
+        IoOp { completion }
+    }
+}
@@ -0,0 +1,213 @@
+use moto_ipc::sync::RequestHeader;
+use moto_ipc::sync::ResponseHeader;
+use moto_sys::ErrorCode;
+use moto_sys::SysHandle;
+
+// The display (virtio-gpu), if there is one. sys-io draws a text console
+// on it, mirroring what sys-tty prints, until a client acquires the display;
+// clients map the framebuffer (a named shared memory segment) and draw
+// into it directly.
+
+pub const URL_DISPLAY: &str = "sys-io-display-service";
+pub const FRAMEBUFFER_SHM_NAME: &str = "sys-io-framebuffer";
+
+pub const CMD_GET_INFO: u16 = 1200;
+pub const CMD_ACQUIRE: u16 = 1201;
+pub const CMD_RELEASE: u16 = 1202;
+pub const CMD_FLUSH: u16 = 1203; // Only by the connection that acquired the display.
+pub const CMD_CONSOLE_WRITE: u16 = 1204;
+pub const CMD_CONSOLE_SCROLL: u16 = 1205;
+
+// 32-bit pixels, 0x00RRGGBB.
+pub const PIXEL_FORMAT_XRGB8888: u32 = 1;
+
+// Per CMD_CONSOLE_WRITE request; DisplayService::console_write() splits
+// longer writes.
+pub const CONSOLE_WRITE_MAX: usize = 4000;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisplayInfoV1 {
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32, // In bytes.
+    pub pixel_format: u32,
+    pub framebuffer_size: u64, // Of the shared memory segment, in bytes.
+    pub owner_pid: u64,        // Who acquired the display; zero if the console has it.
+}
+
+#[repr(C)]
+pub struct DisplayRequest {
+    pub header: RequestHeader,
+    // CMD_FLUSH: the rectangle to update.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // CMD_CONSOLE_SCROLL: positive values scroll back, towards older lines;
+    // i32::MIN scrolls to the bottom.
+    pub lines: i32,
+}
+
+#[repr(C)]
+pub struct DisplayResponse {
+    pub header: ResponseHeader,
+    pub info: DisplayInfoV1, // CMD_GET_INFO only.
+}
+
+#[repr(C)]
+pub struct ConsoleWriteRequest {
+    pub header: RequestHeader,
+    pub len: u32,
+    pub data: [u8; CONSOLE_WRITE_MAX],
+}
+
+pub struct DisplayService {
+    conn: moto_ipc::sync::ClientConnection,
+}
+
+impl DisplayService {
+    // Fails if there is no display.
+    pub fn connect() -> Result<Self, ErrorCode> {
+        let mut conn = moto_ipc::sync::ClientConnection::new(moto_ipc::sync::ChannelSize::Small)?;
+        conn.connect(URL_DISPLAY)?;
+        Ok(Self { conn })
+    }
+
+    fn rpc(&mut self, cmd: u16, f: impl FnOnce(&mut DisplayRequest)) -> Result<(), ErrorCode> {
+        let req = self.conn.req::<DisplayRequest>();
+        req.header.cmd = cmd;
+        req.header.ver = 0;
+        req.header.flags = 0;
+        req.x = 0;
+        req.y = 0;
+        req.width = 0;
+        req.height = 0;
+        req.lines = 0;
+        f(req);
+
+        self.conn.do_rpc(None)?;
+
+        let resp = self.conn.resp::<DisplayResponse>();
+        let res = ErrorCode::from(resp.header.result);
+        if res.is_err() {
+            return Err(res);
+        }
+        Ok(())
+    }
+
+    pub fn info(&mut self) -> Result<DisplayInfoV1, ErrorCode> {
+        self.rpc(CMD_GET_INFO, |_| {})?;
+        Ok(self.conn.resp::<DisplayResponse>().info)
+    }
+
+    /// Maps the framebuffer read-write. Drawing into it is visible only
+    /// after flush(), which needs the display acquired.
+    pub fn map_framebuffer(&mut self) -> Result<Framebuffer, ErrorCode> {
+        let info = self.info()?;
+        if info.pixel_format != PIXEL_FORMAT_XRGB8888 {
+            return Err(ErrorCode::NotImplemented);
+        }
+        let (handle, addr) =
+            moto_sys::SysMem::shm_open(FRAMEBUFFER_SHM_NAME, info.framebuffer_size, true)?;
+        Ok(Framebuffer { info, handle, addr })
+    }
+
+    /// Takes the display over from the console (and from nobody else),
+    /// until release() or until the connection is dropped.
+    pub fn acquire(&mut self) -> Result<(), ErrorCode> {
+        self.rpc(CMD_ACQUIRE, |_| {})
+    }
+
+    /// Gives the display back to the console, which redraws it.
+    pub fn release(&mut self) -> Result<(), ErrorCode> {
+        self.rpc(CMD_RELEASE, |_| {})
+    }
+
+    /// Shows what has been drawn in the rectangle.
+    pub fn flush(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<(), ErrorCode> {
+        self.rpc(CMD_FLUSH, |req| {
+            req.x = x;
+            req.y = y;
+            req.width = width;
+            req.height = height;
+        })
+    }
+
+    /// Prints to the console: bytes are interpreted as by a (dumb) terminal.
+    pub fn console_write(&mut self, bytes: &[u8]) -> Result<(), ErrorCode> {
+        for chunk in bytes.chunks(CONSOLE_WRITE_MAX) {
+            let req = self.conn.req::<ConsoleWriteRequest>();
+            req.header.cmd = CMD_CONSOLE_WRITE;
+            req.header.ver = 0;
+            req.header.flags = 0;
+            req.len = chunk.len() as u32;
+            req.data[0..chunk.len()].copy_from_slice(chunk);
+
+            self.conn.do_rpc(None)?;
+
+            let res = ErrorCode::from(self.conn.resp::<DisplayResponse>().header.result);
+            if res.is_err() {
+                return Err(res);
+            }
+        }
+        Ok(())
+    }
+
+    /// Scrolls the console back (positive @lines) or forward through
+    /// its history. Output scrolls it to the bottom.
+    pub fn console_scroll(&mut self, lines: i32) -> Result<(), ErrorCode> {
+        self.rpc(CMD_CONSOLE_SCROLL, |req| req.lines = lines)
+    }
+
+    pub fn console_scroll_to_bottom(&mut self) -> Result<(), ErrorCode> {
+        self.console_scroll(i32::MIN)
+    }
+}
+
+/// The framebuffer, mapped into this process: see DisplayService::map_framebuffer().
+pub struct Framebuffer {
+    info: DisplayInfoV1,
+    handle: SysHandle,
+    addr: u64,
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        moto_sys::SysMem::free(self.addr).unwrap();
+        moto_sys::SysObj::put(self.handle).unwrap();
+    }
+}
+
+impl Framebuffer {
+    pub fn info(&self) -> &DisplayInfoV1 {
+        &self.info
+    }
+
+    pub fn width(&self) -> u32 {
+        self.info.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.info.height
+    }
+
+    /// Rows of pixels, info().stride bytes apart.
+    pub fn pixels(&self) -> &[u32] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self.addr as usize as *const u32,
+                (self.info.stride as usize >> 2) * self.info.height as usize,
+            )
+        }
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                self.addr as usize as *mut u32,
+                (self.info.stride as usize >> 2) * self.info.height as usize,
+            )
+        }
+    }
+}
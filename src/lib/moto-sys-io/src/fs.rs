@@ -0,0 +1,541 @@
+use moto_ipc::sync::{ChannelSize, ClientConnection};
+use moto_runtime::rt_api::fs::*;
+use moto_sys::ErrorCode;
+
+// Filesystem operations not exposed via std::fs. Each call makes its own
+// connection to the FS driver, so these are not meant for hot paths.
+
+pub(crate) fn connect_to_driver() -> Result<ClientConnection, ErrorCode> {
+    let mut conn = ClientConnection::new(ChannelSize::Small)?;
+    conn.connect(FS_URL)?;
+
+    let req = conn.req::<GetServerUrlRequest>();
+    req.header.cmd = 1;
+    req.header.ver = 0;
+    req.header.flags = 0;
+    conn.do_rpc(Some(
+        moto_sys::time::Instant::now() + core::time::Duration::from_millis(1000),
+    ))?;
+
+    let resp = conn.resp::<GetServerUrlResponse>();
+    if resp.header.result != 0 || resp.header.ver != 0 {
+        return Err(ErrorCode::InternalError);
+    }
+    let driver_url = unsafe { resp.url() }
+        .map_err(|_| ErrorCode::InternalError)?
+        .to_owned();
+
+    let mut conn = ClientConnection::new(ChannelSize::Small)?;
+    conn.connect(driver_url.as_str())?;
+    Ok(conn)
+}
+
+/// Get capacity and usage of the filesystem @abs_path belongs to.
+/// The path must be absolute (see std::fs::canonicalize).
+pub fn statfs(abs_path: &str) -> Result<FsStatsData, ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<StatFsRequest>();
+        req.header.cmd = CMD_STATFS;
+        req.header.ver = 0;
+        req.header.flags = 0;
+        req.parent_fd = 0;
+
+        req.fname_size = abs_path.len() as u16;
+        raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<StatFsResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+    if (resp.stats.self_size as usize) < core::mem::size_of::<FsStatsData>() {
+        return Err(ErrorCode::InternalError);
+    }
+
+    Ok(resp.stats)
+}
+
+/// Set the permission bits (FILE_MODE_MASK) of the file or directory at
+/// @abs_path. Only the owner or root can do that.
+pub fn chmod(abs_path: &str, mode: u32) -> Result<(), ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+    if (mode & !FILE_MODE_MASK) != 0 {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<ChmodRequest>();
+        req.header.cmd = CMD_CHMOD;
+        req.header.ver = 0;
+        req.header.flags = mode;
+        req.parent_fd = 0;
+
+        req.fname_size = abs_path.len() as u16;
+        raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<ChmodResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    Ok(())
+}
+
+/// Change the owner and/or the group (None => keep) of the file or
+/// directory at @abs_path. Only root can do that.
+pub fn chown(abs_path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<(), ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<ChownRequest>();
+        req.header.cmd = CMD_CHOWN;
+        req.header.ver = 0;
+        req.header.flags = 0;
+        req.parent_fd = 0;
+        req.uid = uid.unwrap_or(ChownRequest::CHOWN_UNCHANGED);
+        req.gid = gid.unwrap_or(ChownRequest::CHOWN_UNCHANGED);
+
+        req.fname_size = abs_path.len() as u16;
+        raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<ChownResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    Ok(())
+}
+
+/// Allocate the blocks of @abs_path in [@offset, @offset + @len), extending
+/// the file if needed, or, if @punch_hole, free them: the range then reads
+/// as zeroes, and the file size does not change.
+pub fn fallocate(abs_path: &str, offset: u64, len: u64, punch_hole: bool) -> Result<(), ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    let fd = unsafe {
+        let req = raw_channel.get_mut::<FileOpenRequest>();
+        req.header.cmd = CMD_FILE_OPEN;
+        req.header.ver = 0;
+        req.header.flags = FileOpenRequest::F_WRITE;
+        req.parent_fd = 0;
+
+        req.fname_size = abs_path.len() as u16;
+        raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+
+        conn.do_rpc(None)?;
+
+        let resp = raw_channel.get::<FileOpenResponse>();
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from(resp.header.result));
+        }
+        resp.fd
+    };
+
+    unsafe {
+        let req = raw_channel.get_mut::<FileAllocateRequest>();
+        req.header.cmd = CMD_FILE_ALLOCATE;
+        req.header.ver = 0;
+        req.header.flags = if punch_hole {
+            FileAllocateRequest::F_PUNCH_HOLE
+        } else {
+            0
+        };
+        req.fd = fd;
+        req.offset = offset;
+        req.len = len;
+    }
+    conn.do_rpc(None)?;
+    let result = unsafe { raw_channel.get::<FileAllocateResponse>() }
+        .header
+        .result;
+
+    unsafe {
+        let req = raw_channel.get_mut::<CloseFdRequest>();
+        req.header.cmd = CMD_CLOSE_FD;
+        req.header.ver = 0;
+        req.header.flags = CloseFdRequest::F_FILE;
+        req.fd = fd;
+    }
+    conn.do_rpc(None)?;
+
+    if result != 0 {
+        return Err(ErrorCode::from(result));
+    }
+
+    Ok(())
+}
+
+fn xattr(
+    cmd: u16,
+    flags: u32,
+    abs_path: &str,
+    name: &str,
+    value: &[u8],
+) -> Result<Vec<u8>, ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<XattrRequest>();
+        req.build(cmd, flags, abs_path, name, value, &raw_channel)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<XattrResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    Ok(unsafe { resp.data(&raw_channel)? }.to_vec())
+}
+
+/// The value of the extended attribute @name (e.g. "user.mime_type") of
+/// @abs_path; symlinks are followed.
+pub fn get_xattr(abs_path: &str, name: &str) -> Result<Vec<u8>, ErrorCode> {
+    xattr(CMD_XATTR_GET, 0, abs_path, name, &[])
+}
+
+/// Sets the extended attribute @name of @abs_path; @flags are
+/// XattrRequest::F_CREATE or F_REPLACE, or zero.
+pub fn set_xattr(abs_path: &str, name: &str, value: &[u8], flags: u32) -> Result<(), ErrorCode> {
+    xattr(CMD_XATTR_SET, flags, abs_path, name, value).map(|_| ())
+}
+
+/// The names of the extended attributes of @abs_path that the caller can see.
+pub fn list_xattrs(abs_path: &str) -> Result<Vec<String>, ErrorCode> {
+    let data = xattr(CMD_XATTR_LIST, 0, abs_path, "", &[])?;
+    let mut names = Vec::new();
+    for name in data.split(|b| *b == 0).filter(|n| !n.is_empty()) {
+        names.push(String::from_utf8(name.to_vec()).map_err(|_| ErrorCode::InternalError)?);
+    }
+    Ok(names)
+}
+
+pub fn remove_xattr(abs_path: &str, name: &str) -> Result<(), ErrorCode> {
+    xattr(CMD_XATTR_REMOVE, 0, abs_path, name, &[]).map(|_| ())
+}
+
+fn quota(
+    cmd: u16,
+    abs_path: &str,
+    uid: Option<u32>,
+    max_bytes: u64,
+    max_inodes: u64,
+) -> Result<QuotaData, ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<QuotaRequest>();
+        req.header.cmd = cmd;
+        req.header.ver = 0;
+        req.header.flags = if uid.is_some() {
+            QuotaRequest::F_USER
+        } else {
+            0
+        };
+        req.parent_fd = 0;
+        req.max_bytes = max_bytes;
+        req.max_inodes = max_inodes;
+        req.uid = uid.unwrap_or(0);
+
+        req.fname_size = abs_path.len() as u16;
+        raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<QuotaResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+
+    Ok(resp.quota)
+}
+
+/// The quota of user @uid on the filesystem @abs_path belongs to, or, if
+/// @uid is None, of the directory at @abs_path: limits (zero if none) and
+/// usage.
+pub fn get_quota(abs_path: &str, uid: Option<u32>) -> Result<QuotaData, ErrorCode> {
+    quota(CMD_QUOTA_GET, abs_path, uid, 0, 0)
+}
+
+/// Set the limits of a quota (see get_quota()); zero means no limit, and
+/// zero limits remove the quota. Root only.
+pub fn set_quota(
+    abs_path: &str,
+    uid: Option<u32>,
+    max_bytes: u64,
+    max_inodes: u64,
+) -> Result<(), ErrorCode> {
+    quota(CMD_QUOTA_SET, abs_path, uid, max_bytes, max_inodes).map(|_| ())
+}
+
+fn mount_rpc(cmd: u16, source: &str, abs_target: &str, max_bytes: u64) -> Result<(), ErrorCode> {
+    if !abs_target.starts_with('/') || abs_target.len() > MAX_PATH || source.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<MountRequest>();
+        req.build(cmd, source, abs_target, max_bytes, &raw_channel)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<MountResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+    Ok(())
+}
+
+/// Mount @source (see MountRequest) at the directory @abs_target. For tmpfs,
+/// @max_bytes limits its size (zero: the default). Root only.
+pub fn mount(source: &str, abs_target: &str, max_bytes: u64) -> Result<(), ErrorCode> {
+    mount_rpc(CMD_MOUNT, source, abs_target, max_bytes)
+}
+
+/// Unmount the filesystem mounted at @abs_target. Files open on it remain
+/// usable until closed. Root only.
+pub fn unmount(abs_target: &str) -> Result<(), ErrorCode> {
+    mount_rpc(CMD_UNMOUNT, "", abs_target, 0)
+}
+
+/// A snapshot of a volume (see SnapshotRequest).
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    pub created: u64, // Nanoseconds since the Unix epoch.
+    pub max_bytes: u64,
+    pub used_bytes: u64,
+    pub valid: bool, // False if the snapshot ran out of space.
+}
+
+fn snapshot_rpc(
+    cmd: u16,
+    name: &str,
+    abs_path: &str,
+    max_bytes: u64,
+) -> Result<Vec<Snapshot>, ErrorCode> {
+    if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+        return Err(ErrorCode::InvalidFilename);
+    }
+    if name.len() > MAX_SNAPSHOT_NAME {
+        return Err(ErrorCode::InvalidArgument);
+    }
+
+    let mut conn = connect_to_driver()?;
+    let raw_channel = conn.raw_channel();
+    unsafe {
+        let req = raw_channel.get_mut::<SnapshotRequest>();
+        req.build(cmd, name, abs_path, max_bytes, &raw_channel)?;
+    }
+
+    conn.do_rpc(None)?;
+
+    let resp = unsafe { raw_channel.get::<SnapshotResponse>() };
+    if resp.header.result != 0 {
+        return Err(ErrorCode::from(resp.header.result));
+    }
+    let snapshots = unsafe { raw_channel.get_at(&resp.snapshots, resp.num_snapshots as usize)? };
+    snapshots
+        .iter()
+        .map(|snapshot| {
+            let name = snapshot
+                .name
+                .get(0..(snapshot.name_size as usize))
+                .ok_or(ErrorCode::InternalError)?;
+            Ok(Snapshot {
+                name: core::str::from_utf8(name)
+                    .map_err(|_| ErrorCode::InternalError)?
+                    .to_owned(),
+                created: snapshot.created,
+                max_bytes: snapshot.max_bytes,
+                used_bytes: snapshot.used_bytes,
+                valid: snapshot.valid != 0,
+            })
+        })
+        .collect()
+}
+
+/// Take snapshot @name of the volume @abs_path is on, setting @max_bytes
+/// of the volume aside for it (see SnapshotRequest). Root only.
+pub fn create_snapshot(name: &str, abs_path: &str, max_bytes: u64) -> Result<(), ErrorCode> {
+    snapshot_rpc(CMD_SNAPSHOT_CREATE, name, abs_path, max_bytes).map(|_| ())
+}
+
+/// Delete snapshot @name of the volume @abs_path is on. Mounted snapshots
+/// cannot be deleted. Root only.
+pub fn delete_snapshot(name: &str, abs_path: &str) -> Result<(), ErrorCode> {
+    snapshot_rpc(CMD_SNAPSHOT_DELETE, name, abs_path, 0).map(|_| ())
+}
+
+/// The snapshots of the volume @abs_path is on. Root only.
+pub fn snapshots(abs_path: &str) -> Result<Vec<Snapshot>, ErrorCode> {
+    snapshot_rpc(CMD_SNAPSHOT_LIST, "", abs_path, 0)
+}
+
+/// A change event from a Watcher: @name is the changed entry of the
+/// watched directory, or empty if the watched path itself changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub wd: u32,
+    pub event: u16, // WATCH_EVENT_*.
+    pub name: String,
+}
+
+/// Change notification: collects events from watches on files and directories.
+/// Watches are on paths, and are not recursive: see WatchAddRequest.
+///
+/// Unlike the calls above, a Watcher keeps its connection to the FS driver,
+/// so that the driver can wake it: wait on handle() (e.g. together with other
+/// handles), or call wait_event().
+pub struct Watcher {
+    conn: ClientConnection,
+}
+
+impl Watcher {
+    pub fn new() -> Result<Self, ErrorCode> {
+        Ok(Self {
+            conn: connect_to_driver()?,
+        })
+    }
+
+    /// Woken when events are available.
+    pub fn handle(&self) -> moto_sys::SysHandle {
+        self.conn.handle()
+    }
+
+    /// Watch the file or directory at @abs_path; returns the watch descriptor.
+    pub fn add(&mut self, abs_path: &str) -> Result<u32, ErrorCode> {
+        if !abs_path.starts_with('/') || abs_path.len() > MAX_PATH {
+            return Err(ErrorCode::InvalidFilename);
+        }
+
+        let raw_channel = self.conn.raw_channel();
+        unsafe {
+            let req = raw_channel.get_mut::<WatchAddRequest>();
+            req.header.cmd = CMD_WATCH_ADD;
+            req.header.ver = 0;
+            req.header.flags = 0;
+            req.parent_fd = 0;
+
+            req.fname_size = abs_path.len() as u16;
+            raw_channel.put_bytes(abs_path.as_bytes(), &mut req.fname)?;
+        }
+
+        self.conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<WatchAddResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from(resp.header.result));
+        }
+
+        Ok(resp.wd)
+    }
+
+    /// Stop watching; events of @wd that have not been read are dropped.
+    pub fn remove(&mut self, wd: u32) -> Result<(), ErrorCode> {
+        let raw_channel = self.conn.raw_channel();
+        unsafe {
+            let req = raw_channel.get_mut::<WatchRemoveRequest>();
+            req.header.cmd = CMD_WATCH_REMOVE;
+            req.header.ver = 0;
+            req.header.flags = 0;
+            req.wd = wd;
+        }
+
+        self.conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<WatchRemoveResponse>() };
+        if resp.header.result != 0 {
+            return Err(ErrorCode::from(resp.header.result));
+        }
+
+        Ok(())
+    }
+
+    /// The next queued event, if any; does not block.
+    pub fn next_event(&mut self) -> Result<Option<WatchEvent>, ErrorCode> {
+        let raw_channel = self.conn.raw_channel();
+        unsafe {
+            let req = raw_channel.get_mut::<WatchNextRequest>();
+            req.header.cmd = CMD_WATCH_NEXT;
+            req.header.ver = 0;
+            req.header.flags = 0;
+        }
+
+        self.conn.do_rpc(None)?;
+
+        let resp = unsafe { raw_channel.get::<WatchNextResponse>() };
+        match resp.header.result {
+            0 => {}
+            r if r == ErrorCode::NotReady as u16 => return Ok(None),
+            r => return Err(ErrorCode::from(r)),
+        }
+
+        Ok(Some(WatchEvent {
+            wd: resp.wd,
+            event: resp.event,
+            name: unsafe { resp.name(&raw_channel) }?.to_owned(),
+        }))
+    }
+
+    /// Wait for the next event; fails with TimedOut if @timeout passes first.
+    pub fn wait_event(
+        &mut self,
+        timeout: Option<moto_sys::time::Instant>,
+    ) -> Result<WatchEvent, ErrorCode> {
+        loop {
+            if let Some(event) = self.next_event()? {
+                return Ok(event);
+            }
+
+            // Wakeups are not lost: one that came after next_event() above
+            // makes this return immediately.
+            let mut handles = [self.handle()];
+            moto_sys::SysCpu::wait(
+                &mut handles,
+                moto_sys::SysHandle::NONE,
+                moto_sys::SysHandle::NONE,
+                timeout,
+            )?;
+        }
+    }
+}
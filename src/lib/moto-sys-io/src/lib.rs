@@ -1 +1,5 @@
+pub mod aio;
+pub mod display;
+pub mod fs;
+pub mod netcfg;
 pub mod stats;
@@ -0,0 +1,781 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use moto_ipc::sync::RequestHeader;
+use moto_ipc::sync::ResponseHeader;
+use moto_sys::ErrorCode;
+
+// Network interface configuration at runtime. Changes are not persisted:
+// /sys/cfg/sys-net.toml is what sys-io starts with.
+
+pub const URL_NET_CFG: &str = "sys-io-net-cfg-service";
+
+pub const CMD_LIST_INTERFACES: u16 = 1100;
+pub const CMD_ADD_ADDR: u16 = 1101; // Root-only.
+pub const CMD_DEL_ADDR: u16 = 1102; // Root-only.
+pub const CMD_SET_MTU: u16 = 1103; // Root-only.
+pub const CMD_SET_LINK: u16 = 1104; // Root-only.
+pub const CMD_LIST_ROUTES: u16 = 1105;
+pub const CMD_ADD_ROUTE: u16 = 1106; // Root-only.
+pub const CMD_DEL_ROUTE: u16 = 1107; // Root-only.
+pub const CMD_GET_ROUTE: u16 = 1108;
+pub const CMD_FW_LIST: u16 = 1109;
+pub const CMD_FW_ADD: u16 = 1110; // Root-only.
+pub const CMD_FW_DEL: u16 = 1111; // Root-only.
+pub const CMD_FW_SET_POLICY: u16 = 1112; // Root-only.
+pub const CMD_FW_FLUSH: u16 = 1113; // Root-only.
+
+pub const IFNAME_MAX: usize = 15;
+pub const MAX_INTERFACES: usize = 16;
+pub const MAX_INTERFACE_ADDRS: usize = 8; // What sys-io configures smoltcp with.
+
+pub const MAX_ROUTE_INFOS: usize = 48; // Per response.
+pub const MAX_FW_RULES: usize = 40; // Per response.
+
+pub const IFF_UP: u32 = 1 << 0;
+pub const IFF_LOOPBACK: u32 = 1 << 1;
+
+pub const RTF_GATEWAY: u32 = 1 << 0;
+// Where the route comes from; routes added via add_route() have none of these.
+pub const RTF_CONNECTED: u32 = 1 << 1; // An address on the interface.
+pub const RTF_CONFIG: u32 = 1 << 2; // sys-net.toml.
+pub const RTF_AUTOCONF: u32 = 1 << 3; // An IPv6 router advertisement.
+pub const RTF_LOCAL: u32 = 1 << 4; // From get_route(): the destination is a local address.
+
+// Firewall chains: packets received by, or sent from, an interface.
+pub const FW_CHAIN_INPUT: u8 = 0;
+pub const FW_CHAIN_OUTPUT: u8 = 1;
+
+pub const FW_ALLOW: u8 = 0;
+pub const FW_DENY: u8 = 1;
+
+// FwRuleV1::protocol is an IP protocol number; FW_PROTO_ICMP also matches ICMPv6.
+pub const FW_PROTO_ANY: u8 = 0;
+pub const FW_PROTO_ICMP: u8 = 1;
+pub const FW_PROTO_TCP: u8 = 6;
+pub const FW_PROTO_UDP: u8 = 17;
+pub const FW_PROTO_ICMPV6: u8 = 58;
+
+// Which of the optional FwRuleV1 fields a rule matches on.
+pub const FW_MATCH_IFACE: u8 = 1 << 0;
+pub const FW_MATCH_SRC: u8 = 1 << 1;
+pub const FW_MATCH_DST: u8 = 1 << 2;
+pub const FW_MATCH_SRC_PORT: u8 = 1 << 3;
+pub const FW_MATCH_DST_PORT: u8 = 1 << 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct IpCidrV1 {
+    pub octets: [u8; 16], // IPv4 addresses take the first four bytes.
+    pub prefix_len: u8,
+    pub is_ipv6: u8,
+}
+
+impl IpCidrV1 {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Self {
+        let mut octets = [0_u8; 16];
+        let is_ipv6 = match addr {
+            IpAddr::V4(addr) => {
+                octets[0..4].copy_from_slice(&addr.octets());
+                0
+            }
+            IpAddr::V6(addr) => {
+                octets = addr.octets();
+                1
+            }
+        };
+
+        Self {
+            octets,
+            prefix_len,
+            is_ipv6,
+        }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        if self.is_ipv6 == 0 {
+            let o = &self.octets;
+            IpAddr::V4(Ipv4Addr::new(o[0], o[1], o[2], o[3]))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(self.octets))
+        }
+    }
+}
+
+impl core::fmt::Debug for IpCidrV1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr(), self.prefix_len)
+    }
+}
+
+// Counters since sys-io started.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InterfaceStatsV1 {
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    pub rx_dropped: u64, // Received while the link was down.
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InterfaceInfoV1 {
+    pub name: [u8; IFNAME_MAX],
+    pub name_len: u8,
+    pub mac: [u8; 6],
+    pub mtu: u16,
+    pub flags: u32, // IFF_*.
+    pub num_addrs: u32,
+    pub addrs: [IpCidrV1; MAX_INTERFACE_ADDRS],
+    pub stats: InterfaceStatsV1,
+}
+
+impl Default for InterfaceInfoV1 {
+    fn default() -> Self {
+        Self {
+            name: [0; IFNAME_MAX],
+            name_len: 0,
+            mac: [0; 6],
+            mtu: 0,
+            flags: 0,
+            num_addrs: 0,
+            addrs: [IpCidrV1::default(); MAX_INTERFACE_ADDRS],
+            stats: InterfaceStatsV1::default(),
+        }
+    }
+}
+
+impl core::fmt::Debug for InterfaceInfoV1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: flags: {:#x} mtu: {} addrs: {:?}",
+            self.name(),
+            self.flags,
+            self.mtu,
+            self.addrs()
+        )
+    }
+}
+
+impl InterfaceInfoV1 {
+    pub fn name(&self) -> &str {
+        let len = (self.name_len as usize).min(IFNAME_MAX);
+        core::str::from_utf8(&self.name[0..len]).unwrap_or("<invalid>")
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        let len = name.len().min(IFNAME_MAX);
+        self.name[0..len].copy_from_slice(&name.as_bytes()[0..len]);
+        self.name_len = len as u8;
+    }
+
+    pub fn addrs(&self) -> &[IpCidrV1] {
+        &self.addrs[0..(self.num_addrs as usize).min(MAX_INTERFACE_ADDRS)]
+    }
+
+    pub fn is_up(&self) -> bool {
+        (self.flags & IFF_UP) != 0
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        (self.flags & IFF_LOOPBACK) != 0
+    }
+}
+
+/// An interface address, as getifaddrs(3) lists them: one per address,
+/// plus one without an address for each interface that has none.
+#[derive(Clone, Debug)]
+pub struct IfAddr {
+    pub ifname: String,
+    pub flags: u32, // IFF_*.
+    pub mac: [u8; 6],
+    pub mtu: u16,
+    pub addr: Option<IpAddr>,
+    pub prefix_len: u8,
+    pub stats: InterfaceStatsV1, // Of the interface.
+}
+
+impl IfAddr {
+    pub fn is_up(&self) -> bool {
+        (self.flags & IFF_UP) != 0
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        (self.flags & IFF_LOOPBACK) != 0
+    }
+
+    pub fn netmask(&self) -> Option<IpAddr> {
+        match self.addr? {
+            IpAddr::V4(_) => {
+                let bits = u32::MAX
+                    .checked_shl(32_u32.saturating_sub(self.prefix_len as u32))
+                    .unwrap_or(0);
+                Some(IpAddr::V4(Ipv4Addr::from(bits)))
+            }
+            IpAddr::V6(_) => {
+                let bits = u128::MAX
+                    .checked_shl(128_u32.saturating_sub(self.prefix_len as u32))
+                    .unwrap_or(0);
+                Some(IpAddr::V6(Ipv6Addr::from(bits)))
+            }
+        }
+    }
+
+    /// IPv4 networks with room for a broadcast address have one.
+    pub fn broadcast(&self) -> Option<Ipv4Addr> {
+        let (Some(IpAddr::V4(addr)), Some(IpAddr::V4(netmask))) = (self.addr, self.netmask())
+        else {
+            return None;
+        };
+        if self.prefix_len >= 31 || self.is_loopback() {
+            return None;
+        }
+        Some(Ipv4Addr::from(u32::from(addr) | !u32::from(netmask)))
+    }
+}
+
+/// All addresses of all interfaces.
+pub fn getifaddrs() -> Result<Vec<IfAddr>, ErrorCode> {
+    let interfaces = NetCfgService::connect()?.list_interfaces()?;
+
+    let mut result = Vec::new();
+    for info in &interfaces {
+        let entry = IfAddr {
+            ifname: info.name().to_owned(),
+            flags: info.flags,
+            mac: info.mac,
+            mtu: info.mtu,
+            addr: None,
+            prefix_len: 0,
+            stats: info.stats,
+        };
+        if info.addrs().is_empty() {
+            result.push(entry);
+            continue;
+        }
+        for cidr in info.addrs() {
+            result.push(IfAddr {
+                addr: Some(cidr.addr()),
+                prefix_len: cidr.prefix_len,
+                ..entry.clone()
+            });
+        }
+    }
+    Ok(result)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RouteInfoV1 {
+    pub destination: IpCidrV1,
+    pub gateway: IpCidrV1, // Only if RTF_GATEWAY; prefix_len is unused.
+    // The local address packets are sent from; only from get_route().
+    pub source: IpCidrV1,
+    pub ifname: [u8; IFNAME_MAX],
+    pub ifname_len: u8, // Zero in add_route() and del_route(): any interface.
+    pub metric: u32,
+    pub flags: u32, // RTF_*.
+}
+
+impl core::fmt::Debug for RouteInfoV1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.destination)?;
+        if let Some(gateway) = self.gateway() {
+            write!(f, " via {}", gateway)?;
+        }
+        write!(f, " dev {} metric {}", self.ifname(), self.metric)
+    }
+}
+
+impl RouteInfoV1 {
+    pub fn new(
+        destination: IpAddr,
+        prefix_len: u8,
+        gateway: Option<IpAddr>,
+        ifname: Option<&str>,
+        metric: u32,
+    ) -> Self {
+        let mut route = Self {
+            destination: IpCidrV1::new(destination, prefix_len),
+            metric,
+            ..Default::default()
+        };
+        if let Some(gateway) = gateway {
+            route.gateway = IpCidrV1::new(gateway, 0);
+            route.flags |= RTF_GATEWAY;
+        }
+        if let Some(ifname) = ifname {
+            route.set_ifname(ifname);
+        }
+        route
+    }
+
+    pub fn gateway(&self) -> Option<IpAddr> {
+        if (self.flags & RTF_GATEWAY) != 0 {
+            Some(self.gateway.addr())
+        } else {
+            None
+        }
+    }
+
+    pub fn ifname(&self) -> &str {
+        let len = (self.ifname_len as usize).min(IFNAME_MAX);
+        core::str::from_utf8(&self.ifname[0..len]).unwrap_or("<invalid>")
+    }
+
+    pub fn set_ifname(&mut self, ifname: &str) {
+        let len = ifname.len().min(IFNAME_MAX);
+        self.ifname[0..len].copy_from_slice(&ifname.as_bytes()[0..len]);
+        self.ifname_len = len as u8;
+    }
+}
+
+/// A packet filter rule. Rules are stateless: each packet is checked on its own
+/// against its chain's rules in order, and the first match decides; packets
+/// no rule matches get the chain's policy.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FwRuleV1 {
+    pub chain: u8,       // FW_CHAIN_*.
+    pub action: u8,      // FW_ALLOW or FW_DENY.
+    pub protocol: u8,    // FW_PROTO_*, or any other IP protocol number.
+    pub match_flags: u8, // FW_MATCH_*.
+    pub ifname: [u8; IFNAME_MAX],
+    pub ifname_len: u8,
+    pub src: IpCidrV1,
+    pub dst: IpCidrV1,
+    pub src_ports: [u16; 2], // Inclusive.
+    pub dst_ports: [u16; 2], // Inclusive.
+
+    // Packets and bytes the rule matched; only in list_fw_rules().
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl core::fmt::Debug for FwRuleV1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.action == FW_ALLOW {
+            "allow"
+        } else {
+            "deny"
+        })?;
+        if let Some(ifname) = self.ifname() {
+            write!(f, " dev {}", ifname)?;
+        }
+        match self.protocol {
+            FW_PROTO_ANY => {}
+            FW_PROTO_ICMP => f.write_str(" proto icmp")?,
+            FW_PROTO_TCP => f.write_str(" proto tcp")?,
+            FW_PROTO_UDP => f.write_str(" proto udp")?,
+            proto => write!(f, " proto {}", proto)?,
+        }
+        if let Some(src) = self.src() {
+            write!(f, " from {:?}", src)?;
+        }
+        if let Some((first, last)) = self.src_ports() {
+            Self::fmt_ports(f, " sport", first, last)?;
+        }
+        if let Some(dst) = self.dst() {
+            write!(f, " to {:?}", dst)?;
+        }
+        if let Some((first, last)) = self.dst_ports() {
+            Self::fmt_ports(f, " dport", first, last)?;
+        }
+        Ok(())
+    }
+}
+
+impl FwRuleV1 {
+    /// A rule matching all packets in the chain.
+    pub fn new(chain: u8, action: u8) -> Self {
+        Self {
+            chain,
+            action,
+            ..Default::default()
+        }
+    }
+
+    pub fn ifname(&self) -> Option<&str> {
+        if (self.match_flags & FW_MATCH_IFACE) == 0 {
+            return None;
+        }
+        let len = (self.ifname_len as usize).min(IFNAME_MAX);
+        core::str::from_utf8(&self.ifname[0..len]).ok()
+    }
+
+    pub fn set_ifname(&mut self, ifname: &str) {
+        let len = ifname.len().min(IFNAME_MAX);
+        self.ifname[0..len].copy_from_slice(&ifname.as_bytes()[0..len]);
+        self.ifname_len = len as u8;
+        self.match_flags |= FW_MATCH_IFACE;
+    }
+
+    pub fn src(&self) -> Option<IpCidrV1> {
+        ((self.match_flags & FW_MATCH_SRC) != 0).then_some(self.src)
+    }
+
+    pub fn set_src(&mut self, addr: IpAddr, prefix_len: u8) {
+        self.src = IpCidrV1::new(addr, prefix_len);
+        self.match_flags |= FW_MATCH_SRC;
+    }
+
+    pub fn dst(&self) -> Option<IpCidrV1> {
+        ((self.match_flags & FW_MATCH_DST) != 0).then_some(self.dst)
+    }
+
+    pub fn set_dst(&mut self, addr: IpAddr, prefix_len: u8) {
+        self.dst = IpCidrV1::new(addr, prefix_len);
+        self.match_flags |= FW_MATCH_DST;
+    }
+
+    pub fn src_ports(&self) -> Option<(u16, u16)> {
+        ((self.match_flags & FW_MATCH_SRC_PORT) != 0)
+            .then_some((self.src_ports[0], self.src_ports[1]))
+    }
+
+    pub fn set_src_ports(&mut self, first: u16, last: u16) {
+        self.src_ports = [first, last];
+        self.match_flags |= FW_MATCH_SRC_PORT;
+    }
+
+    pub fn dst_ports(&self) -> Option<(u16, u16)> {
+        ((self.match_flags & FW_MATCH_DST_PORT) != 0)
+            .then_some((self.dst_ports[0], self.dst_ports[1]))
+    }
+
+    pub fn set_dst_ports(&mut self, first: u16, last: u16) {
+        self.dst_ports = [first, last];
+        self.match_flags |= FW_MATCH_DST_PORT;
+    }
+
+    fn fmt_ports(
+        f: &mut std::fmt::Formatter<'_>,
+        name: &str,
+        first: u16,
+        last: u16,
+    ) -> std::fmt::Result {
+        if first == last {
+            write!(f, "{} {}", name, first)
+        } else {
+            write!(f, "{} {}-{}", name, first, last)
+        }
+    }
+}
+
+/// Both chains of the packet filter.
+#[derive(Clone, Debug)]
+pub struct FwRules {
+    pub input_policy: u8,     // FW_ALLOW or FW_DENY.
+    pub output_policy: u8,    // FW_ALLOW or FW_DENY.
+    pub rules: Vec<FwRuleV1>, // Input rules first, each chain in order.
+}
+
+pub struct NetCfgService {
+    conn: moto_ipc::sync::ClientConnection,
+}
+
+impl NetCfgService {
+    pub fn connect() -> Result<Self, ErrorCode> {
+        let mut conn = moto_ipc::sync::ClientConnection::new(moto_ipc::sync::ChannelSize::Small)?;
+        conn.connect(URL_NET_CFG)?;
+        Ok(Self { conn })
+    }
+
+    pub fn list_interfaces(&mut self) -> Result<Vec<InterfaceInfoV1>, ErrorCode> {
+        let req = self.conn.req::<InterfaceRequest>();
+        req.header.cmd = CMD_LIST_INTERFACES;
+        req.header.ver = 0;
+        req.header.flags = 0;
+
+        self.conn.do_rpc(None)?;
+
+        let resp = self.conn.resp::<ListInterfacesResponse<MAX_INTERFACES>>();
+        let res = ErrorCode::from(resp.header.result);
+        if res.is_err() {
+            return Err(res);
+        }
+        if resp.num_results as usize > MAX_INTERFACES {
+            return Err(ErrorCode::InternalError);
+        }
+        Ok(resp.interfaces[0..(resp.num_results as usize)].to_vec())
+    }
+
+    pub fn add_addr(
+        &mut self,
+        ifname: &str,
+        addr: IpAddr,
+        prefix_len: u8,
+    ) -> Result<(), ErrorCode> {
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_prefix_len {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        self.interface_rpc(CMD_ADD_ADDR, ifname, |req| {
+            req.cidr = IpCidrV1::new(addr, prefix_len)
+        })
+    }
+
+    pub fn del_addr(&mut self, ifname: &str, addr: IpAddr) -> Result<(), ErrorCode> {
+        self.interface_rpc(CMD_DEL_ADDR, ifname, |req| {
+            req.cidr = IpCidrV1::new(addr, 0)
+        })
+    }
+
+    pub fn set_mtu(&mut self, ifname: &str, mtu: u16) -> Result<(), ErrorCode> {
+        self.interface_rpc(CMD_SET_MTU, ifname, |req| req.mtu = mtu)
+    }
+
+    pub fn set_link(&mut self, ifname: &str, up: bool) -> Result<(), ErrorCode> {
+        self.interface_rpc(CMD_SET_LINK, ifname, |req| req.up = up as u8)
+    }
+
+    /// All routes, including "connected" ones (networks of interface addresses).
+    pub fn list_routes(&mut self) -> Result<Vec<RouteInfoV1>, ErrorCode> {
+        let mut result = Vec::new();
+        loop {
+            let page = self.route_rpc(CMD_LIST_ROUTES, &RouteInfoV1::default(), result.len())?;
+            let done = page.len() < MAX_ROUTE_INFOS;
+            result.extend_from_slice(&page);
+            if done {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// The route packets to `dst` would take, with the local address they'd be sent from.
+    pub fn get_route(&mut self, dst: IpAddr) -> Result<RouteInfoV1, ErrorCode> {
+        let prefix_len = if dst.is_ipv4() { 32 } else { 128 };
+        let route = RouteInfoV1::new(dst, prefix_len, None, None, 0);
+        let result = self.route_rpc(CMD_GET_ROUTE, &route, 0)?;
+        result.first().copied().ok_or(ErrorCode::InternalError)
+    }
+
+    /// Without an interface name, the interface is the one the gateway is on.
+    pub fn add_route(&mut self, route: &RouteInfoV1) -> Result<(), ErrorCode> {
+        self.route_rpc(CMD_ADD_ROUTE, route, 0).map(|_| ())
+    }
+
+    /// Removes the first route to the destination, matching the gateway and
+    /// the interface if they are given.
+    pub fn del_route(&mut self, route: &RouteInfoV1) -> Result<(), ErrorCode> {
+        self.route_rpc(CMD_DEL_ROUTE, route, 0).map(|_| ())
+    }
+
+    fn route_rpc(
+        &mut self,
+        cmd: u16,
+        route: &RouteInfoV1,
+        start_idx: usize,
+    ) -> Result<Vec<RouteInfoV1>, ErrorCode> {
+        let req = self.conn.req::<RouteRequest>();
+        req.header.cmd = cmd;
+        req.header.ver = 0;
+        req.header.flags = 0;
+        req.route = *route;
+        req.start_idx = start_idx as u32;
+
+        self.conn.do_rpc(None)?;
+
+        let resp = self.conn.resp::<ListRoutesResponse<MAX_ROUTE_INFOS>>();
+        let res = ErrorCode::from(resp.header.result);
+        if res.is_err() {
+            return Err(res);
+        }
+        if resp.num_results as usize > MAX_ROUTE_INFOS {
+            return Err(ErrorCode::InternalError);
+        }
+        Ok(resp.routes[0..(resp.num_results as usize)].to_vec())
+    }
+
+    pub fn list_fw_rules(&mut self) -> Result<FwRules, ErrorCode> {
+        let mut result = FwRules {
+            input_policy: FW_ALLOW,
+            output_policy: FW_ALLOW,
+            rules: Vec::new(),
+        };
+        loop {
+            let req = self.conn.req::<FwRequest>();
+            req.header.cmd = CMD_FW_LIST;
+            req.header.ver = 0;
+            req.header.flags = 0;
+            req.rule = FwRuleV1::default();
+            req.idx = result.rules.len() as u32;
+
+            self.conn.do_rpc(None)?;
+
+            let resp = self.conn.resp::<ListFwRulesResponse<MAX_FW_RULES>>();
+            let res = ErrorCode::from(resp.header.result);
+            if res.is_err() {
+                return Err(res);
+            }
+            let num_results = resp.num_results as usize;
+            if num_results > MAX_FW_RULES {
+                return Err(ErrorCode::InternalError);
+            }
+            result.input_policy = resp.input_policy;
+            result.output_policy = resp.output_policy;
+            result.rules.extend_from_slice(&resp.rules[0..num_results]);
+            if num_results < MAX_FW_RULES {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Inserts the rule at `position` in its chain, or appends it.
+    pub fn add_fw_rule(
+        &mut self,
+        rule: &FwRuleV1,
+        position: Option<usize>,
+    ) -> Result<(), ErrorCode> {
+        let idx = position.map_or(u32::MAX, |pos| pos.min(u32::MAX as usize) as u32);
+        self.fw_rpc(CMD_FW_ADD, rule, idx)
+    }
+
+    /// Removes the rule at `idx` (zero-based) in the chain.
+    pub fn del_fw_rule(&mut self, chain: u8, idx: usize) -> Result<(), ErrorCode> {
+        let idx = idx.min(u32::MAX as usize) as u32;
+        self.fw_rpc(CMD_FW_DEL, &FwRuleV1::new(chain, FW_ALLOW), idx)
+    }
+
+    pub fn set_fw_policy(&mut self, chain: u8, action: u8) -> Result<(), ErrorCode> {
+        self.fw_rpc(CMD_FW_SET_POLICY, &FwRuleV1::new(chain, action), 0)
+    }
+
+    /// Removes all rules from the chain; its policy stays.
+    pub fn flush_fw_rules(&mut self, chain: u8) -> Result<(), ErrorCode> {
+        self.fw_rpc(CMD_FW_FLUSH, &FwRuleV1::new(chain, FW_ALLOW), 0)
+    }
+
+    fn fw_rpc(&mut self, cmd: u16, rule: &FwRuleV1, idx: u32) -> Result<(), ErrorCode> {
+        let req = self.conn.req::<FwRequest>();
+        req.header.cmd = cmd;
+        req.header.ver = 0;
+        req.header.flags = 0;
+        req.rule = *rule;
+        req.idx = idx;
+
+        self.conn.do_rpc(None)?;
+
+        let res = ErrorCode::from(
+            self.conn
+                .resp::<ListFwRulesResponse<MAX_FW_RULES>>()
+                .header
+                .result,
+        );
+        if res.is_err() {
+            Err(res)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn interface_rpc<F: FnOnce(&mut InterfaceRequest)>(
+        &mut self,
+        cmd: u16,
+        ifname: &str,
+        f: F,
+    ) -> Result<(), ErrorCode> {
+        if ifname.is_empty() || ifname.len() > IFNAME_MAX {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let req = self.conn.req::<InterfaceRequest>();
+        req.header.cmd = cmd;
+        req.header.ver = 0;
+        req.header.flags = 0;
+        req.name[0..ifname.len()].copy_from_slice(ifname.as_bytes());
+        req.name_len = ifname.len() as u8;
+        req.cidr = IpCidrV1::default();
+        req.mtu = 0;
+        req.up = 0;
+        f(req);
+
+        self.conn.do_rpc(None)?;
+
+        let res = ErrorCode::from(self.conn.resp::<InterfaceResponse>().header.result);
+        if res.is_err() {
+            Err(res)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Which fields matter depends on the command.
+#[repr(C)]
+pub struct InterfaceRequest {
+    pub header: RequestHeader,
+    pub name: [u8; IFNAME_MAX],
+    pub name_len: u8,
+    pub cidr: IpCidrV1,
+    pub mtu: u16,
+    pub up: u8,
+}
+
+impl InterfaceRequest {
+    pub fn name(&self) -> Option<&str> {
+        let len = self.name_len as usize;
+        if len == 0 || len > IFNAME_MAX {
+            return None;
+        }
+        core::str::from_utf8(&self.name[0..len]).ok()
+    }
+}
+
+#[repr(C)]
+pub struct InterfaceResponse {
+    pub header: ResponseHeader,
+}
+
+#[repr(C)]
+pub struct ListInterfacesResponse<const N: usize> {
+    pub header: ResponseHeader,
+    pub num_results: u64,
+    pub interfaces: [InterfaceInfoV1; N],
+}
+
+const _SZ: () = assert!(
+    size_of::<ListInterfacesResponse<MAX_INTERFACES>>()
+        <= moto_sys::sys_mem::PAGE_SIZE_SMALL as usize
+);
+
+#[repr(C)]
+pub struct RouteRequest {
+    pub header: RequestHeader,
+    pub route: RouteInfoV1,
+    pub start_idx: u32, // For CMD_LIST_ROUTES.
+}
+
+#[repr(C)]
+pub struct ListRoutesResponse<const N: usize> {
+    pub header: ResponseHeader,
+    pub num_results: u64,
+    pub routes: [RouteInfoV1; N],
+}
+
+const _SZ_ROUTES: () = assert!(
+    size_of::<ListRoutesResponse<MAX_ROUTE_INFOS>>() <= moto_sys::sys_mem::PAGE_SIZE_SMALL as usize
+);
+
+// For CMD_FW_SET_POLICY, the rule's chain and action are the policy.
+#[repr(C)]
+pub struct FwRequest {
+    pub header: RequestHeader,
+    pub rule: FwRuleV1,
+    pub idx: u32, // Where to add or delete; the first rule to list.
+}
+
+#[repr(C)]
+pub struct ListFwRulesResponse<const N: usize> {
+    pub header: ResponseHeader,
+    pub num_results: u64,
+    pub input_policy: u8,
+    pub output_policy: u8,
+    pub rules: [FwRuleV1; N],
+}
+
+const _SZ_FW_RULES: () = assert!(
+    size_of::<ListFwRulesResponse<MAX_FW_RULES>>() <= moto_sys::sys_mem::PAGE_SIZE_SMALL as usize
+);
@@ -21,6 +21,14 @@ pub struct TcpSocketStatsV1 {
 
     pub tcp_state: moto_runtime::rt_api::net::TcpState,
     pub smoltcp_state: smoltcp::socket::tcp::State,
+    pub rx_queue: u32,    // Bytes received but not yet consumed by the owner.
+    pub tx_queue: u32,    // Bytes sent by the owner but not yet acknowledged by the peer.
+    pub rx_bytes: u64,    // Total bytes delivered to the owner.
+    pub tx_bytes: u64,    // Total bytes accepted from the owner.
+    pub rtt_ms: u32,      // Smoothed round-trip time estimate.
+    pub rtt_var_ms: u32,  // Mean deviation of the round-trip time.
+    pub cwnd: u32,        // Congestion window, in bytes; u32::MAX if there is none.
+    pub retransmits: u32, // Segments sent again.
 }
 
 impl Default for TcpSocketStatsV1 {
@@ -35,6 +43,14 @@ impl Default for TcpSocketStatsV1 {
             remote_port: 0,
             tcp_state: moto_runtime::rt_api::net::TcpState::Closed,
             smoltcp_state: smoltcp::socket::tcp::State::Closed,
+            rx_queue: 0,
+            tx_queue: 0,
+            rx_bytes: 0,
+            tx_bytes: 0,
+            rtt_ms: 0,
+            rtt_var_ms: 0,
+            cwnd: u32::MAX,
+            retransmits: 0,
         }
     }
 }
@@ -43,14 +59,22 @@ impl core::fmt::Debug for TcpSocketStatsV1 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "TCP: pid: {} dev: {} id: {} local_addr: {:?} remote_addr: {:?} state: {:?} ({:?})",
+            "TCP: pid: {} dev: {} id: {} local_addr: {:?} remote_addr: {:?} state: {:?} ({:?}) rx_queue: {} tx_queue: {} rx_bytes: {} tx_bytes: {} rtt: {}ms (var {}ms) cwnd: {} retransmits: {}",
             self.pid,
             self.device_id,
             self.id,
             self.local_addr(),
             self.remote_addr(),
             self.tcp_state,
-            self.smoltcp_state
+            self.smoltcp_state,
+            self.rx_queue,
+            self.tx_queue,
+            self.rx_bytes,
+            self.tx_bytes,
+            self.rtt_ms,
+            self.rtt_var_ms,
+            self.cwnd,
+            self.retransmits
         )
     }
 }
@@ -87,7 +111,48 @@ impl TcpSocketStatsV1 {
     }
 }
 
+/// Protocol-wide counters, accumulated since sys-io started (see `netstat -s`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetStatsV1 {
+    pub tcp_active_opens: u64,  // Outgoing connection attempts.
+    pub tcp_passive_opens: u64, // Incoming connections handed to listeners.
+    pub tcp_failed_connects: u64,
+    pub tcp_established: u64, // Not a counter: connections currently open.
+    pub tcp_rx_bytes: u64,    // Delivered to applications.
+    pub tcp_tx_bytes: u64,    // Accepted from applications.
+    pub udp_rx_datagrams: u64,
+    pub udp_tx_datagrams: u64,
+    pub udp_tx_errors: u64,
+}
+
+/// The state of NTP time synchronization (see `timesync`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeSyncStatusV1 {
+    pub synced: bool, // Whether a server answered recently.
+    pub stratum: u8,  // Of the server last synced with.
+    pub num_servers: u32,
+    pub poll_interval_secs: u32,
+    pub server_addr: [u8; 16], // Last server synced with; IPv4 is mapped to IPv6.
+    pub last_sync_nsec: u64,   // Wall clock time of the last sync; zero if never.
+    pub last_offset_nsec: i64, // How far off the clock was at the last sync.
+    pub round_trip_nsec: u64,  // Of the last sync.
+    pub slew_remaining_nsec: i64,
+}
+
+impl TimeSyncStatusV1 {
+    pub fn server_addr(&self) -> Option<std::net::IpAddr> {
+        if self.last_sync_nsec == 0 {
+            return None;
+        }
+        Some(Ipv6Addr::from(self.server_addr).to_canonical())
+    }
+}
+
 pub const CMD_TCP_STATS: u16 = 1000;
+pub const CMD_NET_STATS: u16 = 1001;
+pub const CMD_TIME_SYNC_STATUS: u16 = 1002;
 
 pub struct IoStatsService {
     conn: moto_ipc::sync::ClientConnection,
@@ -118,6 +183,38 @@ impl IoStatsService {
             .resp::<GetTcpSocketStatsResponse<1>>()
             .socket_stats()
     }
+
+    pub fn get_net_stats(&mut self) -> Result<NetStatsV1, ErrorCode> {
+        let req = self.conn.req::<GetNetStatsRequest>();
+        req.header.cmd = CMD_NET_STATS;
+        req.header.ver = 0;
+        req.header.flags = 0;
+
+        self.conn.do_rpc(None)?;
+
+        let resp = self.conn.resp::<GetNetStatsResponse>();
+        let res = ErrorCode::from(resp.header.result);
+        if res.is_err() {
+            return Err(res);
+        }
+        Ok(resp.stats)
+    }
+
+    pub fn get_time_sync_status(&mut self) -> Result<TimeSyncStatusV1, ErrorCode> {
+        let req = self.conn.req::<GetTimeSyncStatusRequest>();
+        req.header.cmd = CMD_TIME_SYNC_STATUS;
+        req.header.ver = 0;
+        req.header.flags = 0;
+
+        self.conn.do_rpc(None)?;
+
+        let resp = self.conn.resp::<GetTimeSyncStatusResponse>();
+        let res = ErrorCode::from(resp.header.result);
+        if res.is_err() {
+            return Err(res);
+        }
+        Ok(resp.status)
+    }
 }
 
 #[repr(C)]
@@ -133,7 +230,7 @@ pub struct GetTcpSocketStatsResponse<const N: usize> {
     pub socket_stats: [TcpSocketStatsV1; N],
 }
 
-pub const MAX_TCP_SOCKET_STATS: usize = 56;
+pub const MAX_TCP_SOCKET_STATS: usize = 36;
 
 const _SZ: () = assert!(
     size_of::<GetTcpSocketStatsResponse<MAX_TCP_SOCKET_STATS>>()
@@ -166,3 +263,25 @@ impl<const N: usize> GetTcpSocketStatsResponse<N> {
         }
     }
 }
+
+#[repr(C)]
+pub struct GetNetStatsRequest {
+    pub header: RequestHeader,
+}
+
+#[repr(C)]
+pub struct GetNetStatsResponse {
+    pub header: ResponseHeader,
+    pub stats: NetStatsV1,
+}
+
+#[repr(C)]
+pub struct GetTimeSyncStatusRequest {
+    pub header: RequestHeader,
+}
+
+#[repr(C)]
+pub struct GetTimeSyncStatusResponse {
+    pub header: ResponseHeader,
+    pub status: TimeSyncStatusV1,
+}
@@ -13,7 +13,135 @@ pub const CAP_SPAWN: u64 = 1 << 2;
 // The process can use SysMem::OP_DEBUG and SysCtl::OP_SET_LOG_LEVEL.
 pub const CAP_LOG: u64 = 1 << 3;
 
+// The process can make memory in its own address space executable
+// (see SysMem::protect()), e.g. to run JIT-compiled code.
+pub const CAP_EXEC_MEM: u64 = 1 << 4;
+
+// The process can use real-time scheduling policies (see SysCpu::set_sched_policy()).
+pub const CAP_REALTIME: u64 = 1 << 5;
+
 // This ENV key can be used to specify caps for the
 // process being created. The value must be formated in hex.
 // Currently works with Rust's std::process::Command.
 pub const MOTURUS_CAPS_ENV_KEY: &str = "MOTURUS_CAPS";
+
+// This ENV key can be used to restrict the syscalls the process being
+// created can make. The value is a syscall filter (see below) formatted
+// in hex. The filter of a child is always a subset of the parent's filter.
+pub const MOTURUS_SYSCALL_FILTER_ENV_KEY: &str = "MOTURUS_SYSCALL_FILTER";
+
+// Syscall filter: bit (syscall_nr - 1) * 32 + operation is set if the
+// operation is allowed. SysCpu::OP_EXIT is always allowed.
+pub const SYSCALL_FILTER_ALLOW_ALL: u128 = u128::MAX;
+
+// Operations per syscall the filter has bits for.
+pub const SYSCALL_FILTER_OPS: u8 = 32;
+
+pub const fn syscall_filter_bit(syscall_nr: u8, operation: u8) -> u128 {
+    if syscall_nr == 0 || syscall_nr > crate::syscalls::SYS_RAY || operation >= SYSCALL_FILTER_OPS {
+        return 0;
+    }
+    1 << (((syscall_nr - 1) as u32) * (SYSCALL_FILTER_OPS as u32) + (operation as u32))
+}
+
+pub const fn syscall_allowed(filter: u128, syscall_nr: u8, operation: u8) -> bool {
+    if filter == SYSCALL_FILTER_ALLOW_ALL {
+        return true;
+    }
+    if syscall_nr == crate::syscalls::SYS_CPU && operation == crate::SysCpu::OP_EXIT {
+        return true;
+    }
+    filter & syscall_filter_bit(syscall_nr, operation) != 0
+}
+
+// Every operation must have a bit in the filter: an operation without one
+// could never be allowed by a filter other than SYSCALL_FILTER_ALLOW_ALL.
+const _: () = {
+    assert!((crate::syscalls::SYS_RAY as u32) * (SYSCALL_FILTER_OPS as u32) <= u128::BITS);
+
+    const fn check_ops(syscall_nr: u8, ops: &[u8]) {
+        let mut idx = 0;
+        while idx < ops.len() {
+            assert!(syscall_filter_bit(syscall_nr, ops[idx]) != 0);
+            idx += 1;
+        }
+    }
+
+    use crate::syscalls::*;
+    use crate::{SysCpu, SysMem, SysObj, SysRay};
+    check_ops(
+        SYS_CPU,
+        &[
+            SysCpu::OP_EXIT,
+            SysCpu::OP_WAIT,
+            SysCpu::OP_WAKE,
+            SysCpu::OP_KILL,
+            SysCpu::OP_SPAWN,
+            SysCpu::OP_USAGE,
+            SysCpu::OP_AFFINE_CPU,
+            SysCpu::OP_QUERY_PERCPU_STATS,
+            SysCpu::OP_TIMER,
+            SysCpu::OP_CPU_TIME,
+            SysCpu::OP_PERF,
+            SysCpu::OP_WATCHDOG,
+            SysCpu::OP_SCHED_POLICY,
+            SysCpu::OP_HOTPLUG,
+            SysCpu::OP_EVENT_RING,
+            SysCpu::OP_WALL_CLOCK,
+            SysCpu::OP_PRIORITY,
+        ],
+    );
+    check_ops(
+        SYS_MEM,
+        &[
+            SysMem::OP_CREATE,
+            SysMem::OP_GET,
+            SysMem::OP_PUT,
+            SysMem::OP_MAP,
+            SysMem::OP_UNMAP,
+            SysMem::OP_REMAP,
+            SysMem::OP_QUERY,
+            SysMem::OP_RECLAIM,
+            SysMem::OP_PAGER,
+            SysMem::OP_PROTECT,
+            SysMem::OP_ADVISE,
+        ],
+    );
+    check_ops(
+        SYS_OBJ,
+        &[
+            SysObj::OP_GET,
+            SysObj::OP_PUT,
+            SysObj::OP_CREATE,
+            SysObj::OP_SET_LOG_LEVEL,
+            SysObj::OP_QUERY_HANDLE,
+            SysObj::OP_DUP,
+        ],
+    );
+    check_ops(
+        SYS_RAY,
+        &[
+            SysRay::OP_QUERY_PROCESS,
+            SysRay::OP_DBG,
+            SysRay::OP_LOG,
+            SysRay::OP_TRACE,
+            SysRay::OP_SYSCALL_TRACE,
+        ],
+    );
+};
+
+// This ENV key can be used to spawn the process in a new namespace
+// (a lightweight container) with the filesystem root at the specified
+// directory. The new namespace has its own PID numbering, and its
+// capabilities are limited to those of the spawned process.
+pub const MOTURUS_FS_ROOT_ENV_KEY: &str = "MOTURUS_FS_ROOT";
+
+// The user ID of the superuser, which bypasses file permission checks.
+// The first process (sys-io) runs as root, and children inherit the
+// user and group IDs of their parents.
+pub const ROOT_UID: u32 = 0;
+
+// These ENV keys can be used to set the user and group IDs (decimal) of
+// the process being created. Only processes running as root can do that.
+pub const MOTURUS_UID_ENV_KEY: &str = "MOTURUS_UID";
+pub const MOTURUS_GID_ENV_KEY: &str = "MOTURUS_GID";
@@ -0,0 +1,106 @@
+// Kernel crash dumps.
+//
+// On a panic, the kernel writes a crash image to the serial console (which
+// the host captures, e.g. via `-nographic`), hex-encoded in lines of
+// CRASH_BYTES_PER_LINE bytes between CRASH_BEGIN_MARKER and CRASH_END_MARKER.
+// The image is:
+//   - CrashHeaderV1;
+//   - CrashCpuV1 x header.num_cpus;
+//   - stats::KLogEntryV1 x header.num_log_entries (oldest first);
+//   - u64: crash_checksum() of all of the above.
+//
+// Use the `crashdump` host tool (src/crashdump) to extract and print it.
+
+pub const CRASH_MAGIC: u64 = 0x4853_5243_4f54_4f4d; // "MOTOCRSH" in little endian.
+pub const CRASH_VERSION: u32 = 1;
+
+pub const CRASH_BEGIN_MARKER: &str = "-----BEGIN MOTOR OS CRASH DUMP-----";
+pub const CRASH_END_MARKER: &str = "-----END MOTOR OS CRASH DUMP-----";
+pub const CRASH_BYTES_PER_LINE: usize = 32;
+
+pub const CRASH_MAX_MSG_LEN: usize = 512;
+pub const CRASH_MAX_BACKTRACE: usize = 32;
+pub const CRASH_MAX_LOG_ENTRIES: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CrashHeaderV1 {
+    pub magic: u64,
+    pub version: u32,
+    pub panic_cpu: u32,
+    pub num_cpus: u32,
+    pub num_log_entries: u32,
+    pub timestamp: u64,     // TSC.
+    pub tsc_in_sec: u64,    // To convert log entry timestamps.
+    pub uptime_nanos: u64,  // Since the system start.
+    pub kernel_offset: u64, // Subtract from kernel addresses to symbolize them.
+    pub msg_len: u32,
+    pub _reserved: u32,
+    pub msg_bytes: [u8; CRASH_MAX_MSG_LEN],
+}
+
+// Did not respond to the crash NMI (e.g. hung with NMIs blocked).
+pub const CRASH_CPU_UNKNOWN: u32 = 0;
+// The CPU that panicked: registers other than rsp/rbp/cr2/cr3 are not captured.
+pub const CRASH_CPU_PANIC: u32 = 1;
+// Stopped by the crash NMI: registers are those at the time of the NMI.
+pub const CRASH_CPU_STOPPED: u32 = 2;
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+pub struct CrashRegsV1 {
+    pub rip: u64,
+    pub rsp: u64,
+    pub rflags: u64,
+    pub cs: u64,
+    pub ss: u64,
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rbp: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub cr2: u64,
+    pub cr3: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CrashCpuV1 {
+    pub cpu: u32,
+    pub state: u32, // CRASH_CPU_*.
+    pub regs: CrashRegsV1,
+    // Kernel return addresses, relative to header.kernel_offset; zero-terminated.
+    pub backtrace: [u64; CRASH_MAX_BACKTRACE],
+}
+
+impl Default for CrashCpuV1 {
+    fn default() -> Self {
+        Self {
+            cpu: 0,
+            state: CRASH_CPU_UNKNOWN,
+            regs: CrashRegsV1::default(),
+            backtrace: [0; CRASH_MAX_BACKTRACE],
+        }
+    }
+}
+
+// FNV-1a. Call with CRASH_CHECKSUM_INIT for the first chunk.
+pub const CRASH_CHECKSUM_INIT: u64 = 0xcbf2_9ce4_8422_2325;
+
+pub fn crash_checksum(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
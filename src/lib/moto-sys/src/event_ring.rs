@@ -0,0 +1,159 @@
+// Event rings: low-latency delivery of wake events to the userspace.
+//
+// An event ring is a page shared between the kernel (the producer) and a
+// process (the consumer). Once a wait object (an IPC channel, an irq_wait
+// handle, etc.) is attached to a ring, its wakes no longer go through the
+// generic wait path (SysCpu::wait): instead, the kernel appends an entry
+// with the cookie given at attach time to the ring, directly from the waking
+// context (including IRQs). The consumer polls the ring without syscalls,
+// and waits on the ring handle only when the ring is empty: the kernel wakes
+// the ring handle only if the consumer has set EVENT_RING_F_NEED_WAKE
+// (or if an event was dropped because the ring was full).
+//
+// Rings are created via SysObj::create(url = "event_ring:address=$addr"),
+// where $addr is a reserved (unmapped) small page; sources are attached and
+// detached via SysCpu::OP_EVENT_RING. See EventRing below for the userspace
+// side.
+
+use core::sync::atomic::*;
+
+#[cfg(feature = "userspace")]
+use crate::{ErrorCode, SysHandle};
+
+// The number of entries in the ring. One entry is always kept empty
+// to distinguish a full ring from an empty one.
+pub const EVENT_RING_CAPACITY: u32 = 252;
+
+// EventRingHeader::flags: set by the consumer before waiting on the ring
+// handle; cleared by the kernel when it wakes the ring handle.
+pub const EVENT_RING_F_NEED_WAKE: u32 = 1;
+
+// EventRingEntry::flags: the peer of the source (an IPC channel) is gone.
+pub const EVENT_F_PEER_DROPPED: u32 = 1;
+
+#[repr(C)]
+pub struct EventRingHeader {
+    pub head: AtomicU32,    // The next entry to produce; written by the kernel only.
+    pub tail: AtomicU32,    // The next entry to consume; written by the consumer only.
+    pub flags: AtomicU32,   // EVENT_RING_F_*.
+    pub dropped: AtomicU32, // Events dropped because the ring was full.
+    pub _reserved: [u32; 12],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EventRingEntry {
+    pub cookie: u64,
+    pub flags: u32, // EVENT_F_*.
+    pub _reserved: u32,
+}
+
+#[repr(C)]
+pub struct EventRingPage {
+    pub header: EventRingHeader,
+    pub entries: [EventRingEntry; EVENT_RING_CAPACITY as usize],
+}
+
+impl EventRingPage {
+    pub const PAGE_SIZE: u64 = 4096;
+}
+const _: () = assert!(core::mem::size_of::<EventRingPage>() as u64 == EventRingPage::PAGE_SIZE);
+
+/// The consumer side of an event ring.
+#[cfg(feature = "userspace")]
+pub struct EventRing {
+    handle: SysHandle,
+    page: &'static EventRingPage,
+}
+
+#[cfg(feature = "userspace")]
+impl Drop for EventRing {
+    fn drop(&mut self) {
+        crate::SysObj::put(self.handle).unwrap();
+        crate::SysMem::free(self.page as *const _ as usize as u64).unwrap();
+    }
+}
+
+#[cfg(feature = "userspace")]
+impl EventRing {
+    pub fn new() -> Result<Self, ErrorCode> {
+        use crate::sys_mem::PAGE_SIZE_SMALL;
+
+        // Reserve an unmapped page for the kernel to map the ring into.
+        let addr = crate::SysMem::map(SysHandle::SELF, 0, u64::MAX, u64::MAX, PAGE_SIZE_SMALL, 1)?;
+        let url = alloc::format!("event_ring:address={}", addr);
+        match crate::SysObj::create(SysHandle::NONE, 0, url.as_str()) {
+            Ok(handle) => Ok(Self {
+                handle,
+                page: unsafe { (addr as usize as *const EventRingPage).as_ref().unwrap() },
+            }),
+            Err(err) => {
+                crate::SysMem::free(addr).unwrap();
+                Err(err)
+            }
+        }
+    }
+
+    /// The handle to wait on (see Self::wait()).
+    pub fn handle(&self) -> SysHandle {
+        self.handle
+    }
+
+    /// Delivers wakes of @source into this ring, tagged with @cookie.
+    pub fn attach(&self, source: SysHandle, cookie: u64) -> Result<(), ErrorCode> {
+        crate::SysCpu::event_ring_attach(self.handle, source, cookie)
+    }
+
+    /// Restores the normal wake delivery for @source.
+    pub fn detach(&self, source: SysHandle) -> Result<(), ErrorCode> {
+        crate::SysCpu::event_ring_detach(source)
+    }
+
+    pub fn pop(&self) -> Option<EventRingEntry> {
+        let header = &self.page.header;
+        let tail = header.tail.load(Ordering::Relaxed);
+        if tail == header.head.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let entry = unsafe { core::ptr::read_volatile(&self.page.entries[tail as usize]) };
+        header
+            .tail
+            .store((tail + 1) % EVENT_RING_CAPACITY, Ordering::Release);
+        Some(entry)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let header = &self.page.header;
+        header.tail.load(Ordering::Relaxed) == header.head.load(Ordering::Acquire)
+    }
+
+    /// The number of events dropped because the ring was full.
+    pub fn dropped(&self) -> u32 {
+        self.page.header.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Blocks until the ring is not empty, or the timeout expires.
+    pub fn wait(&self, timeout: Option<crate::time::Instant>) -> Result<(), ErrorCode> {
+        let header = &self.page.header;
+        while self.is_empty() {
+            header
+                .flags
+                .fetch_or(EVENT_RING_F_NEED_WAKE, Ordering::SeqCst);
+            // Re-check: an event may have been added before the flag was set.
+            if !self.is_empty() {
+                header
+                    .flags
+                    .fetch_and(!EVENT_RING_F_NEED_WAKE, Ordering::Relaxed);
+                break;
+            }
+            crate::SysCpu::wait(
+                &mut [self.handle],
+                SysHandle::NONE,
+                SysHandle::NONE,
+                timeout,
+            )?;
+        }
+        Ok(())
+    }
+}
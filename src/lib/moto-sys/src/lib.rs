@@ -5,6 +5,8 @@
 
 // Syscalls.
 pub mod caps;
+pub mod crash;
+pub mod event_ring;
 pub mod stats;
 pub mod sys_cpu;
 pub mod sys_mem;
@@ -99,6 +101,10 @@ pub enum ErrorCode {
     BadHandle = 18,
     FileTooLarge = 19,
     BufferFull = 20,
+    FilesystemLoop = 21, // Too many levels of symbolic links.
+    StorageFull = 22,
+    CrossesDevices = 23, // E.g. renaming across mounts.
+    QuotaExceeded = 24,  // A per-user or per-directory limit on storage.
 
     MaxKernelError, // Must be last, so that from_u16() below works.
 }
@@ -145,6 +151,28 @@ pub fn rdrand() -> Result<u64, ErrorCode> {
     }
 }
 
+/// Fills @buf with random bytes from the CPU's DRNG, suitable for seeding
+/// cryptographic RNGs (e.g. for TLS). RDRAND may transiently fail under load,
+/// so each word is retried a few times (as Intel recommends); a persistent
+/// failure is reported rather than papered over with weak randomness.
+pub fn fill_random(buf: &mut [u8]) -> Result<(), ErrorCode> {
+    const RDRAND_RETRIES: usize = 10;
+
+    for chunk in buf.chunks_mut(8) {
+        let mut result = rdrand();
+        for _ in 1..RDRAND_RETRIES {
+            if result.is_ok() {
+                break;
+            }
+            result = rdrand();
+        }
+        let random = result?.to_le_bytes();
+        chunk.copy_from_slice(&random[0..chunk.len()]);
+    }
+
+    Ok(())
+}
+
 pub fn rdseed() -> Result<u64, ErrorCode> {
     let mut val = 0_u64;
     unsafe {
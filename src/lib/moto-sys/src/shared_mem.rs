@@ -29,6 +29,13 @@ pub struct KernelStaticPage {
     pub system_start_time_tsc: u64,
 
     pub num_cpus: u32,
+
+    // The wall clock slew in progress (see SysCpu::F_WALL_CLOCK_SLEW): from
+    // wall_clock_slew_tsc on, base_nsec moves by one nanosecond every
+    // SysCpu::WALL_CLOCK_SLEW_PERIOD_NSEC, until it has moved by
+    // wall_clock_slew_nsec.
+    pub wall_clock_slew_tsc: u64,
+    pub wall_clock_slew_nsec: i64,
 }
 
 impl KernelStaticPage {
@@ -59,6 +66,10 @@ pub struct ProcessStaticPage {
     // The capabilities of the process.
     pub capabilities: u64,
     pub active_threads: AtomicU64, // Includes stdio relay threads.
+
+    // How far the executable image was moved from its link-time address
+    // when loaded (ASLR). Subtract from code addresses to symbolize them.
+    pub image_slide: u64,
 }
 
 impl ProcessStaticPage {
@@ -11,10 +11,15 @@ pub const PID_SYSTEM: u64 = 0; // Used for aggregate (System) stats.
 pub const PID_KERNEL: u64 = 1;
 pub const PID_SYS_IO: u64 = 2;
 
+// Process exit codes set by the kernel (see SysRay::process_status()).
+// Processes killed for any other reason exit with u64::MAX.
+pub const EXIT_CODE_STACK_OVERFLOW: u64 = u64::MAX - 1; // A thread hit its stack guard page.
+
 // Instead of having a version field and mutate the struct,
 // which is unsafe/brittle, we will just be adding new structs.
+pub const MAX_CMDLINE_LEN: usize = 128;
+
 #[repr(C)]
-#[derive(Default)]
 pub struct ProcessStatsV1 {
     pub pid: u64, // PID_SYSTEM, PID_KERNEL, or actual process ID.
     pub parent_pid: u64,
@@ -25,10 +30,21 @@ pub struct ProcessStatsV1 {
     pub active_threads: u64,  // Threads still running.
     pub active_children: u64, // Children still running.
     pub cpu_usage: u64,       // Total, in TSC.
+    pub pages_user_huge: u64, // 2M pages mapped; included in pages_user (as 4K pages).
+    pub bytes_reclaimed: u64, // Memory returned to the OS via SysMem::advise().
     pub debug_name_bytes: [u8; 32],
     pub debug_name_len: u8,
     pub active: u8,         // 0 => zombie; 1 => active.
     pub system_process: u8, // 1 => system; 0 => normal.
+    pub cmdline_len: u8,
+    pub cmdline_bytes: [u8; MAX_CMDLINE_LEN], // Possibly truncated.
+}
+
+impl Default for ProcessStatsV1 {
+    fn default() -> Self {
+        // Safety: all fields are plain integers.
+        unsafe { core::mem::zeroed() }
+    }
 }
 
 #[cfg(feature = "userspace")]
@@ -49,11 +65,277 @@ impl ProcessStatsV1 {
             .unwrap_or("~")
     }
 
+    // The command line the process was started with (the executable
+    // and its arguments), if known; otherwise the debug name.
+    pub fn cmdline(&self) -> &str {
+        if self.cmdline_len == 0 {
+            return self.debug_name();
+        }
+        let bytes = &self.cmdline_bytes[0..(self.cmdline_len as usize)];
+        match core::str::from_utf8(bytes) {
+            Ok(s) => s,
+            // Truncated in the middle of a char.
+            Err(err) => core::str::from_utf8(&bytes[0..err.valid_up_to()]).unwrap(),
+        }
+    }
+
     pub fn total_bytes(&self) -> u64 {
         (self.pages_user + self.pages_kernel) << sys_mem::PAGE_SIZE_SMALL_LOG2
     }
 }
 
+// A process accounting record, added by the kernel when a process exits.
+// See SysRay::read_acct_log_v1().
+#[repr(C)]
+#[derive(Default, Clone)]
+pub struct ProcessAcctV1 {
+    pub seq: u64, // Record sequence number; gaps mean dropped records.
+    pub pid: u64,
+    pub parent_pid: u64,
+    pub started: u64,          // Instant as u64.
+    pub exited: u64,           // Instant as u64.
+    pub cpu_usage: u64,        // Total, in TSC.
+    pub cpu_usage_kernel: u64, // The part of cpu_usage spent in the kernel.
+    pub peak_pages_user: u64,  // The high watermark of user pages.
+    pub exit_code: u64,        // Valid if exit_status == EXITED.
+    pub debug_name_bytes: [u8; 32],
+    pub debug_name_len: u8,
+    pub exit_status: u8, // EXITED, KILLED, or ERROR.
+    pub _pad: [u8; 6],
+}
+
+impl ProcessAcctV1 {
+    pub const EXITED: u8 = 0;
+    pub const KILLED: u8 = 1;
+    pub const ERROR: u8 = 2;
+
+    pub fn debug_name(&self) -> &str {
+        core::str::from_utf8(&self.debug_name_bytes[0..(self.debug_name_len as usize)])
+            .unwrap_or("~")
+    }
+}
+
+#[cfg(feature = "userspace")]
+impl ProcessAcctV1 {
+    // Reads accounting records with seq >= @start_seq, oldest first.
+    pub fn read(start_seq: u64, buf: &mut [ProcessAcctV1]) -> Result<usize, ErrorCode> {
+        crate::SysRay::read_acct_log_v1(start_seq, buf)
+    }
+
+    pub fn peak_bytes_user(&self) -> u64 {
+        self.peak_pages_user << sys_mem::PAGE_SIZE_SMALL_LOG2
+    }
+}
+
+// A kernel log entry. See SysRay::read_log_v1().
+#[repr(C)]
+#[derive(Clone)]
+pub struct KLogEntryV1 {
+    pub seq: u64,       // Entry sequence number; gaps mean dropped entries.
+    pub timestamp: u64, // Instant as u64.
+    pub level: u8,      // log::Level as u8: 1 => Error, ..., 5 => Trace.
+    pub cpu: u8,
+    pub user: u8, // 1 => logged by a userspace process via SysRay::log().
+    pub subsystem_len: u8,
+    pub msg_len: u8,
+    pub subsystem_bytes: [u8; 32], // Module path or thread name.
+    pub msg_bytes: [u8; 200],
+}
+
+impl Default for KLogEntryV1 {
+    fn default() -> Self {
+        Self {
+            seq: 0,
+            timestamp: 0,
+            level: 0,
+            cpu: 0,
+            user: 0,
+            subsystem_len: 0,
+            msg_len: 0,
+            subsystem_bytes: [0; 32],
+            msg_bytes: [0; 200],
+        }
+    }
+}
+
+impl KLogEntryV1 {
+    pub fn subsystem(&self) -> &str {
+        core::str::from_utf8(&self.subsystem_bytes[0..(self.subsystem_len as usize)]).unwrap_or("~")
+    }
+
+    pub fn msg(&self) -> &str {
+        core::str::from_utf8(&self.msg_bytes[0..(self.msg_len as usize)]).unwrap_or("~")
+    }
+
+    pub fn level_str(&self) -> &'static str {
+        if self.user != 0 {
+            return "USER";
+        }
+        match self.level {
+            1 => "ERROR",
+            2 => "WARN",
+            3 => "INFO",
+            4 => "DEBUG",
+            5 => "TRACE",
+            _ => "?",
+        }
+    }
+}
+
+#[cfg(feature = "userspace")]
+impl KLogEntryV1 {
+    // Reads kernel log entries with seq >= @start_seq, oldest first.
+    pub fn read(start_seq: u64, buf: &mut [KLogEntryV1]) -> Result<usize, ErrorCode> {
+        crate::SysRay::read_log_v1(start_seq, buf)
+    }
+}
+
+// Kernel tracepoints. Each can be enabled individually (via a bit
+// in the mask passed to SysRay::trace_enable()).
+pub const TRACE_SCHED_IN: u32 = 0; // arg: 0.
+pub const TRACE_SCHED_OUT: u32 = 1; // arg: 0.
+pub const TRACE_SYSCALL_ENTER: u32 = 2; // arg: (syscall_nr << 8) | operation.
+pub const TRACE_SYSCALL_EXIT: u32 = 3; // arg: (syscall_nr << 8) | operation.
+pub const TRACE_PAGE_FAULT: u32 = 4; // arg: the faulting address.
+pub const TRACE_IPC_WAKE: u32 = 5; // arg: the wake target handle.
+pub const TRACE_NUM_EVENTS: u32 = 6;
+
+// Hardware performance counters. See SysCpu::perf_counters().
+#[derive(Default, Clone, Copy, Debug)]
+pub struct PerfCounters {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub cache_misses: u64,  // Last level cache misses.
+    pub branch_misses: u64, // Mispredicted branches.
+    pub available: u32,     // PERF_* bits: counters supported by the hardware.
+}
+
+pub const PERF_CYCLES: u32 = 1 << 0;
+pub const PERF_INSTRUCTIONS: u32 = 1 << 1;
+pub const PERF_CACHE_MISSES: u32 = 1 << 2;
+pub const PERF_BRANCH_MISSES: u32 = 1 << 3;
+
+impl PerfCounters {
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            cycles: self.cycles.wrapping_sub(earlier.cycles),
+            instructions: self.instructions.wrapping_sub(earlier.instructions),
+            cache_misses: self.cache_misses.wrapping_sub(earlier.cache_misses),
+            branch_misses: self.branch_misses.wrapping_sub(earlier.branch_misses),
+            available: self.available,
+        }
+    }
+}
+
+// A group of counters read together, either of the current thread (which
+// follows the thread across CPUs), or of the current CPU (which counts
+// everything running on it; affine the thread to the CPU to measure it).
+#[cfg(feature = "userspace")]
+pub struct PerfCounterGroup {
+    thread: bool,
+    start: PerfCounters,
+}
+
+#[cfg(feature = "userspace")]
+impl PerfCounterGroup {
+    pub fn new_thread() -> Result<Self, ErrorCode> {
+        Self::new(true)
+    }
+
+    pub fn new_cpu() -> Result<Self, ErrorCode> {
+        Self::new(false)
+    }
+
+    fn new(thread: bool) -> Result<Self, ErrorCode> {
+        Ok(Self {
+            thread,
+            start: crate::SysCpu::perf_counters(thread)?,
+        })
+    }
+
+    pub fn reset(&mut self) -> Result<(), ErrorCode> {
+        self.start = crate::SysCpu::perf_counters(self.thread)?;
+        Ok(())
+    }
+
+    // Counter values since the group was created or last reset.
+    pub fn read(&self) -> Result<PerfCounters, ErrorCode> {
+        Ok(crate::SysCpu::perf_counters(self.thread)?.since(&self.start))
+    }
+}
+
+// A kernel trace event. See SysRay::trace_drain_v1().
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+pub struct TraceEventV1 {
+    pub seq: u64,       // Per-CPU sequence number; gaps mean dropped events.
+    pub timestamp: u64, // Instant as u64.
+    pub event: u32,     // TRACE_*.
+    pub cpu: u32,
+    pub pid: u64,
+    pub tid: u64,
+    pub arg: u64, // Event-specific, see TRACE_* above.
+}
+
+impl TraceEventV1 {
+    pub fn event_name(&self) -> &'static str {
+        match self.event {
+            TRACE_SCHED_IN => "sched_in",
+            TRACE_SCHED_OUT => "sched_out",
+            TRACE_SYSCALL_ENTER => "syscall_enter",
+            TRACE_SYSCALL_EXIT => "syscall_exit",
+            TRACE_PAGE_FAULT => "page_fault",
+            TRACE_IPC_WAKE => "ipc_wake",
+            _ => "?",
+        }
+    }
+}
+
+// A completed syscall of a traced process. See SysRay::syscall_trace_attach().
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SyscallTraceEventV1 {
+    pub seq: u64, // Sequence number; gaps mean dropped events.
+    pub tid: u64,
+    pub started: u64,  // Instant as u64.
+    pub finished: u64, // Instant as u64.
+    pub syscall_nr: u8,
+    pub operation: u8,
+    pub version: u16,
+    pub flags: u32,
+    pub args: [u64; 6],
+    pub result: u64, // ErrorCode as u64.
+    pub data: [u64; 6],
+}
+
+// The namespace a process belongs to. See SysRay::query_namespace_v1().
+#[repr(C)]
+pub struct NamespaceInfoV1 {
+    pub id: u64,        // Zero for the root namespace.
+    pub local_pid: u64, // The PID of the process inside its namespace.
+    pub max_caps: u64,
+    pub fs_root_bytes: [u8; 128],
+    pub fs_root_len: u8,
+}
+
+impl Default for NamespaceInfoV1 {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            local_pid: 0,
+            max_caps: 0,
+            fs_root_bytes: [0; 128],
+            fs_root_len: 0,
+        }
+    }
+}
+
+impl NamespaceInfoV1 {
+    pub fn fs_root(&self) -> &str {
+        core::str::from_utf8(&self.fs_root_bytes[0..(self.fs_root_len as usize)]).unwrap_or("/")
+    }
+}
+
 #[repr(C)]
 pub struct CpuStatsPerCpuEntryV1 {
     pub kernel: u64,
@@ -133,9 +415,24 @@ impl CpuStatsV1 {
 #[repr(C)]
 #[derive(Default)]
 pub struct MemoryStats {
-    pub available: u64,  // Total physical memory.
-    pub used_pages: u64, // Physical pages mapped.
-    pub heap_total: u64, // Total memory in the kernel heap.
+    pub available: u64,        // Total physical memory.
+    pub used_pages: u64,       // Physical pages mapped.
+    pub heap_total: u64,       // Total memory in the kernel heap.
+    pub huge_pages_total: u64, // 2M pages in the huge page pool (included in used_pages).
+    pub huge_pages_used: u64,  // 2M pages from the pool mapped to userspace.
+
+    // Physical memory by zone (page size): 4K pages back almost everything;
+    // 2M (mid) pages are reserved for the kernel and sys-io; huge pages
+    // are above.
+    pub small_pages_total: u64,
+    pub mid_pages_total: u64,
+    pub mid_pages_used: u64,
+
+    // Who uses the memory, in 4K pages. These are mapped (virtual) pages,
+    // so shared pages are counted more than once.
+    pub user_pages: u64,   // Mapped into userspace.
+    pub kernel_pages: u64, // Kernel stacks, page tables, etc. (the heap is separate).
+    pub cached_pages: u64, // Stacks of exited threads kept for reuse (included above).
 }
 
 #[cfg(feature = "userspace")]
@@ -147,6 +444,18 @@ impl MemoryStats {
     pub fn used(&self) -> u64 {
         self.used_pages << sys_mem::PAGE_SIZE_SMALL_LOG2
     }
+
+    pub fn free(&self) -> u64 {
+        self.available.saturating_sub(self.used())
+    }
+
+    pub fn cached(&self) -> u64 {
+        self.cached_pages << sys_mem::PAGE_SIZE_SMALL_LOG2
+    }
+
+    pub fn kernel(&self) -> u64 {
+        (self.kernel_pages << sys_mem::PAGE_SIZE_SMALL_LOG2) + self.heap_total
+    }
 }
 
 #[cfg(feature = "userspace")]
@@ -154,6 +463,28 @@ pub fn get_cpu_usage(buf: &mut [f32]) -> Result<(), ErrorCode> {
     crate::SysCpu::query_stats(buf)
 }
 
+// The number of jobs (e.g. runnable threads) queued on each CPU.
+#[cfg(feature = "userspace")]
+pub fn get_cpu_queue_depths(buf: &mut [u32]) -> Result<(), ErrorCode> {
+    crate::SysCpu::query_queue_depths(buf)
+}
+
+// Per-CPU timer state, useful for diagnosing spurious wakeups of idle CPUs.
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct CpuTimerStatsV1 {
+    pub pending_timers: u64,
+    pub next_deadline: u64, // TSC; zero if there are no pending timers.
+    pub idle_wakeups: u64,
+    pub spurious_wakeups: u64, // Idle wakeups that found nothing to do.
+    pub ticks: u64,            // Scheduler ticks (these are stopped when idle).
+}
+
+#[cfg(feature = "userspace")]
+pub fn get_cpu_timer_stats(buf: &mut [CpuTimerStatsV1]) -> Result<(), ErrorCode> {
+    crate::SysCpu::query_timer_stats(buf)
+}
+
 #[repr(u16)]
 #[derive(Debug, Clone, Copy)]
 pub enum ThreadStatus {
@@ -181,7 +512,136 @@ pub struct ThreadDataV1 {
     pub syscall_num: u8,
     pub syscall_op: u8,
     pub paused_debuggee: u8,
-    pub _pad: [u8; 3],
+    pub fault: u8, // FAULT_NONE, FAULT_PAGE, or FAULT_STACK_OVERFLOW.
+    pub _pad: [u8; 2],
     pub ip: u64,  // Instruction pointer.
     pub rbp: u64, // The value of the RBP register.
 }
+
+impl ThreadDataV1 {
+    pub const FAULT_NONE: u8 = 0;
+    pub const FAULT_PAGE: u8 = 1; // An unresolvable page fault.
+    pub const FAULT_STACK_OVERFLOW: u8 = 2; // A fault on the stack guard page.
+}
+
+// Per-thread stats: see SysRay::list_threads_v1(). Unlike ThreadDataV1,
+// does not require attaching a debugger.
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ThreadStatsV1 {
+    pub tid: u64,
+    pub cpu_usage: u64,       // Total, in TSC.
+    pub status: ThreadStatus, // u16
+    pub last_cpu: u16,        // The CPU the thread last ran on; u16::MAX if none.
+    pub sched_policy: u8,     // SysCpu::SCHED_*.
+    pub paused_debuggee: u8,
+    pub _pad: [u8; 2],
+}
+
+#[cfg(feature = "userspace")]
+impl ThreadStatsV1 {
+    // List threads of the process, in TID order.
+    pub fn list(pid: u64, buf: &mut [ThreadStatsV1]) -> Result<usize, ErrorCode> {
+        crate::SysRay::list_threads_v1(pid, buf)
+    }
+}
+
+// HandleInfoV1::kind.
+pub const HANDLE_KIND_OTHER: u8 = 0;
+pub const HANDLE_KIND_PROCESS: u8 = 1;
+pub const HANDLE_KIND_THREAD: u8 = 2;
+pub const HANDLE_KIND_CHANNEL: u8 = 3; // IPC: "shared:" URLs and ipc pairs.
+pub const HANDLE_KIND_SHM: u8 = 4;
+pub const HANDLE_KIND_TIMER: u8 = 5;
+pub const HANDLE_KIND_EVENT_RING: u8 = 6;
+pub const HANDLE_KIND_ADDRESS_SPACE: u8 = 7;
+pub const HANDLE_KIND_PAGER: u8 = 8;
+pub const HANDLE_KIND_WATCHDOG: u8 = 9;
+pub const HANDLE_KIND_DEBUG: u8 = 10; // Debug sessions and syscall traces.
+
+pub const MAX_HANDLE_URL_LEN: usize = 64;
+
+// An open handle of a process. See SysRay::list_handles_v1().
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct HandleInfoV1 {
+    pub handle: u64,
+    pub peer_pid: u64, // The process at the other end (of a channel, etc.); 0 if none.
+    pub wake_count: u64,
+    pub kind: u8,         // HANDLE_KIND_*.
+    pub peer_dropped: u8, // 1 => the other end of the channel is gone.
+    pub url_len: u8,
+    pub _pad: [u8; 5],
+    pub url_bytes: [u8; MAX_HANDLE_URL_LEN], // Truncated.
+}
+
+impl Default for HandleInfoV1 {
+    fn default() -> Self {
+        Self {
+            handle: 0,
+            peer_pid: 0,
+            wake_count: 0,
+            kind: HANDLE_KIND_OTHER,
+            peer_dropped: 0,
+            url_len: 0,
+            _pad: [0; 5],
+            url_bytes: [0; MAX_HANDLE_URL_LEN],
+        }
+    }
+}
+
+impl HandleInfoV1 {
+    pub fn url(&self) -> &str {
+        core::str::from_utf8(&self.url_bytes[0..(self.url_len as usize)]).unwrap_or("~")
+    }
+
+    pub fn kind_str(&self) -> &'static str {
+        match self.kind {
+            HANDLE_KIND_PROCESS => "process",
+            HANDLE_KIND_THREAD => "thread",
+            HANDLE_KIND_CHANNEL => "channel",
+            HANDLE_KIND_SHM => "shm",
+            HANDLE_KIND_TIMER => "timer",
+            HANDLE_KIND_EVENT_RING => "event_ring",
+            HANDLE_KIND_ADDRESS_SPACE => "addr_space",
+            HANDLE_KIND_PAGER => "pager",
+            HANDLE_KIND_WATCHDOG => "watchdog",
+            HANDLE_KIND_DEBUG => "debug",
+            _ => "other",
+        }
+    }
+}
+
+#[cfg(feature = "userspace")]
+impl HandleInfoV1 {
+    // List handles of the process with values >= @start_handle, in handle order.
+    pub fn list(pid: u64, start_handle: u64, buf: &mut [HandleInfoV1]) -> Result<usize, ErrorCode> {
+        crate::SysRay::list_handles_v1(pid, start_handle, buf)
+    }
+}
+
+// A loaded ELF image of a process. Processes are statically linked, so
+// there is currently a single module per process: the executable.
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ModuleInfoV1 {
+    pub start: u64, // The first byte of the image, as loaded.
+    pub end: u64,   // One past the last byte of the image, as loaded.
+    pub slide: u64, // Load address minus link-time address (ASLR).
+}
+
+impl ModuleInfoV1 {
+    pub fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    // Converts a runtime address into the link-time one, which can be
+    // symbolized with the unmodified binary.
+    pub fn unslide(&self, addr: u64) -> u64 {
+        if self.contains(addr) {
+            addr - self.slide
+        } else {
+            addr
+        }
+    }
+}
@@ -17,6 +17,15 @@ impl SysCpu {
     pub const OP_USAGE: u8 = 6;
     pub const OP_AFFINE_CPU: u8 = 7;
     pub const OP_QUERY_PERCPU_STATS: u8 = 8;
+    pub const OP_TIMER: u8 = 9;
+    pub const OP_CPU_TIME: u8 = 10;
+    pub const OP_PERF: u8 = 11;
+    pub const OP_WATCHDOG: u8 = 12;
+    pub const OP_SCHED_POLICY: u8 = 13;
+    pub const OP_HOTPLUG: u8 = 14;
+    pub const OP_EVENT_RING: u8 = 15;
+    pub const OP_WALL_CLOCK: u8 = 16;
+    pub const OP_PRIORITY: u8 = 17;
 
     // Controls whether hanles to wait for are passed via registers or as an array in memory.
     pub const F_HANDLE_ARRAY: u32 = 1;
@@ -31,12 +40,95 @@ impl SysCpu {
     // If present, args[0] (if no timeout) or args[1] (if timeout) contains wake target.
     pub const F_WAKE_TARGET: u32 = 16;
 
+    // If present, the next arg (after the timeout and the swap/wake target)
+    // contains a thread of the current process that the waiting thread lends
+    // its priority to, e.g. the owner of the lock it waits for. The thread
+    // keeps the priority for as long as the waiting thread waits.
+    pub const F_DONATE_TARGET: u32 = 32;
+
     // If present, OP_KILL kills the peer of the share handle.
     pub const F_KILL_PEER: u32 = 1;
 
     // If present, OP_KILL's arg is the PID.
     pub const F_KILL_PID: u32 = 2;
 
+    // Together with F_KILL_PID: ask the process to terminate rather than
+    // kill it, if the process listens for termination requests (see
+    // termination_request_handle()); otherwise the process is killed.
+    pub const F_KILL_GRACEFUL: u32 = 4;
+
+    // If present, OP_USAGE returns run-queue depths (u32 per CPU)
+    // instead of CPU usage.
+    pub const F_USAGE_QUEUE_DEPTHS: u32 = 1;
+    // If present, OP_USAGE returns stats::CpuTimerStatsV1 per CPU.
+    pub const F_USAGE_TIMERS: u32 = 2;
+
+    // OP_TIMER flags: either (re)arm/disarm the timer, or take the expirations count.
+    pub const F_TIMER_SET: u32 = 1;
+    pub const F_TIMER_TAKE: u32 = 2;
+
+    // OP_CPU_TIME flags: which CPU time to return (as TSC).
+    pub const F_CPU_TIME_PROCESS: u32 = 1;
+    pub const F_CPU_TIME_THREAD: u32 = 2;
+
+    // OP_PERF flags: read hardware performance counters of the current
+    // thread, or of the current CPU.
+    pub const F_PERF_THREAD: u32 = 1;
+    pub const F_PERF_CPU: u32 = 2;
+
+    // OP_WATCHDOG flags: pet (and arm) the watchdog, disarm it, or query
+    // the number of missed deadlines. All return the number of misses
+    // since the last pet; pet and disarm also reset it.
+    pub const F_WATCHDOG_PET: u32 = 1;
+    pub const F_WATCHDOG_DISARM: u32 = 2;
+    pub const F_WATCHDOG_QUERY: u32 = 3;
+
+    // Scheduling policies (OP_SCHED_POLICY). Real-time threads (FIFO and RR)
+    // preempt normal threads as soon as they become runnable, and are never
+    // preempted by normal threads. A FIFO thread runs until it blocks; RR
+    // threads share the CPU with other real-time threads in 10ms slices.
+    // Real-time policies require CAP_REALTIME.
+    pub const SCHED_NORMAL: u64 = 0;
+    pub const SCHED_FIFO: u64 = 1;
+    pub const SCHED_RR: u64 = 2;
+
+    // Thread priorities (OP_PRIORITY): the base priority of a thread, which
+    // it may temporarily raise by inheriting a higher one (see
+    // F_DONATE_TARGET). High priority threads run ahead of normal ones;
+    // PRIORITY_HIGH requires CAP_REALTIME. Setting a scheduling policy
+    // resets the base priority: real-time threads run at PRIORITY_HIGH.
+    pub const PRIORITY_HIGH: u64 = 0;
+    pub const PRIORITY_NORMAL: u64 = 1;
+
+    // OP_HOTPLUG flags: take a CPU offline or bring it back online (both
+    // require CAP_SYS), or query the mask of online CPUs. An offline CPU
+    // runs no threads: its queued threads are migrated to other CPUs, and
+    // threads affined to it run elsewhere until it is back online.
+    // The bootstrap CPU (zero) cannot be taken offline.
+    pub const F_HOTPLUG_QUERY: u32 = 0;
+    pub const F_HOTPLUG_OFFLINE: u32 = 1;
+    pub const F_HOTPLUG_ONLINE: u32 = 2;
+
+    // OP_EVENT_RING flags: attach a wait object (args[1]) to an event ring
+    // (args[0]) with a cookie (args[2]), or detach a wait object (args[0])
+    // from its ring. See event_ring.rs.
+    pub const F_EVENT_RING_ATTACH: u32 = 1;
+    pub const F_EVENT_RING_DETACH: u32 = 2;
+
+    // OP_WALL_CLOCK flags: query the offset (in nanoseconds) the kernel adds
+    // to the hypervisor-provided wall clock and what is left to slew, shift
+    // the offset by args[0] (an i64; requires CAP_SYS), or slew it by args[0]
+    // (requires CAP_SYS). A slew moves the wall clock gradually, by one
+    // nanosecond every WALL_CLOCK_SLEW_PERIOD_NSEC (500 ppm), so that it
+    // never goes backward; a new slew replaces the one in progress.
+    // A shifted offset becomes visible in KernelStaticPage at the next
+    // system time update, within a second. Monotonic time is not affected.
+    pub const F_WALL_CLOCK_QUERY: u32 = 0;
+    pub const F_WALL_CLOCK_ADJUST: u32 = 1;
+    pub const F_WALL_CLOCK_SLEW: u32 = 2;
+
+    pub const WALL_CLOCK_SLEW_PERIOD_NSEC: u64 = 2000;
+
     #[cfg(feature = "userspace")]
     pub fn exit(code: u64) -> ! {
         do_syscall(
@@ -108,6 +200,42 @@ impl SysCpu {
         }
     }
 
+    /// Asks the process to terminate. Returns true if the process has been
+    /// notified, false if it does not listen for termination requests and
+    /// has been killed.
+    #[cfg(feature = "userspace")]
+    pub fn terminate_pid(target: u64) -> Result<bool, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(
+                SYS_CPU,
+                Self::OP_KILL,
+                Self::F_KILL_PID | Self::F_KILL_GRACEFUL,
+                0,
+            ),
+            target,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] != 0)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Returns a handle that is woken when another process asks this process
+    /// to terminate (see terminate_pid()). Once a process has obtained it,
+    /// termination requests no longer kill the process: it is expected to
+    /// wait on the handle and exit when woken.
+    #[cfg(feature = "userspace")]
+    pub fn termination_request_handle() -> Result<SysHandle, ErrorCode> {
+        crate::SysObj::get(SysHandle::SELF, 0, "terminate_request")
+    }
+
     #[cfg(feature = "userspace")]
     pub fn wake(target: SysHandle) -> Result<(), ErrorCode> {
         let result = do_syscall(
@@ -221,6 +349,37 @@ impl SysCpu {
         Self::process_result(&result, wait_handles)
     }
 
+    /// Waits to be woken (or until the timeout), lending the current thread's
+    /// priority to @donate_target (see F_DONATE_TARGET).
+    #[cfg(feature = "userspace")]
+    pub fn wait_donating(
+        donate_target: SysHandle,
+        timeout: Option<crate::time::Instant>,
+    ) -> Result<(), ErrorCode> {
+        let mut flags: u32 = Self::F_DONATE_TARGET;
+        let mut args = [0_u64; 2];
+        let mut next_arg: usize = 0;
+
+        if let Some(timeout) = timeout {
+            flags |= Self::F_TIMEOUT;
+            args[next_arg] = timeout.as_u64();
+            next_arg += 1;
+        }
+        args[next_arg] = donate_target.as_u64();
+
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_WAIT, flags, 1),
+            args[0],
+            args[1],
+            0,
+            0,
+            0,
+            0,
+        );
+
+        Self::process_result(&result, &mut [])
+    }
+
     #[cfg(feature = "userspace")]
     fn process_result(result: &SyscallResult, handles: &mut [SysHandle]) -> Result<(), ErrorCode> {
         // If the condition below is false, the kernel has properly put data in @handles.
@@ -296,6 +455,44 @@ impl SysCpu {
         }
     }
 
+    #[cfg(feature = "userspace")]
+    pub fn query_queue_depths(buf: &mut [u32]) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_USAGE, Self::F_USAGE_QUEUE_DEPTHS, 0),
+            buf.as_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    #[cfg(feature = "userspace")]
+    pub fn query_timer_stats(buf: &mut [crate::stats::CpuTimerStatsV1]) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_USAGE, Self::F_USAGE_TIMERS, 0),
+            buf.as_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     #[cfg(feature = "userspace")]
     pub fn get_percpu_stats_v1(page_addr: u64) -> Result<u32, ErrorCode> {
         let res = do_syscall(
@@ -315,8 +512,393 @@ impl SysCpu {
         }
     }
 
+    /// Create a timer object. The timer handle can be waited on
+    /// with SysCpu::wait(), same as any other handle.
+    #[cfg(feature = "userspace")]
+    pub fn timer_create() -> Result<SysHandle, ErrorCode> {
+        crate::SysObj::create(SysHandle::NONE, 0, "timer")
+    }
+
+    /// Arm (if @deadline is some) or disarm (if @deadline is none) the timer.
+    /// If @period is some, the timer fires every @period after @deadline.
+    /// Returns the number of expirations since the last set/take.
+    #[cfg(feature = "userspace")]
+    pub fn timer_set(
+        timer: SysHandle,
+        deadline: Option<crate::time::Instant>,
+        period: Option<core::time::Duration>,
+    ) -> Result<u64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_TIMER, Self::F_TIMER_SET, 0),
+            timer.as_u64(),
+            deadline.map_or(0, |d| d.as_u64()),
+            period.map_or(0, |p| p.as_nanos() as u64),
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Returns the number of times the timer has fired since the last set/take.
+    #[cfg(feature = "userspace")]
+    pub fn timer_take_expirations(timer: SysHandle) -> Result<u64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_TIMER, Self::F_TIMER_TAKE, 0),
+            timer.as_u64(),
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// CPU time (kernel + userspace) consumed by the current process (if @thread is false)
+    /// or by the current thread (if @thread is true), as TSC.
+    #[cfg(feature = "userspace")]
+    pub fn cpu_time_tsc(thread: bool) -> Result<u64, ErrorCode> {
+        let flags = if thread {
+            Self::F_CPU_TIME_THREAD
+        } else {
+            Self::F_CPU_TIME_PROCESS
+        };
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_CPU_TIME, flags, 0),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Hardware performance counters accumulated by the current thread
+    /// (if @thread is true) or by the current CPU. Counting starts when
+    /// the counters are first requested; use stats::PerfCounterGroup to
+    /// measure a region of code.
+    #[cfg(feature = "userspace")]
+    pub fn perf_counters(thread: bool) -> Result<crate::stats::PerfCounters, ErrorCode> {
+        let flags = if thread {
+            Self::F_PERF_THREAD
+        } else {
+            Self::F_PERF_CPU
+        };
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_PERF, flags, 0),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(crate::stats::PerfCounters {
+                cycles: result.data[0],
+                instructions: result.data[1],
+                cache_misses: result.data[2],
+                branch_misses: result.data[3],
+                available: result.data[4] as u32,
+            })
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Create a watchdog object. The process that pets the watchdog is watched:
+    /// if it does not pet the watchdog within @timeout, the kernel logs an error,
+    /// wakes the watchdog handle, and, if @action is "dump" or "pause", dumps
+    /// the backtraces of the process's threads or pauses it for a debugger.
+    #[cfg(feature = "userspace")]
+    pub fn watchdog_create(
+        timeout: core::time::Duration,
+        action: &str,
+    ) -> Result<SysHandle, ErrorCode> {
+        let url = alloc::format!(
+            "watchdog:timeout_ms={};action={}",
+            timeout.as_millis(),
+            action
+        );
+        crate::SysObj::create(SysHandle::NONE, 0, url.as_str())
+    }
+
+    /// Pet (and arm, if not armed) the watchdog.
+    /// Returns the number of missed deadlines since the last pet.
+    #[cfg(feature = "userspace")]
+    pub fn watchdog_pet(watchdog: SysHandle) -> Result<u64, ErrorCode> {
+        Self::watchdog_op(watchdog, Self::F_WATCHDOG_PET)
+    }
+
+    /// Disarm the watchdog. Returns the number of missed deadlines since the last pet.
+    #[cfg(feature = "userspace")]
+    pub fn watchdog_disarm(watchdog: SysHandle) -> Result<u64, ErrorCode> {
+        Self::watchdog_op(watchdog, Self::F_WATCHDOG_DISARM)
+    }
+
+    /// The number of missed deadlines since the last pet.
+    #[cfg(feature = "userspace")]
+    pub fn watchdog_misses(watchdog: SysHandle) -> Result<u64, ErrorCode> {
+        Self::watchdog_op(watchdog, Self::F_WATCHDOG_QUERY)
+    }
+
+    #[cfg(feature = "userspace")]
+    fn watchdog_op(watchdog: SysHandle, flags: u32) -> Result<u64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_WATCHDOG, flags, 0),
+            watchdog.as_u64(),
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Returns the bitmask of online CPUs (bit N is set if CPU N is online).
+    #[cfg(feature = "userspace")]
+    pub fn online_cpus() -> Result<u64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_HOTPLUG, Self::F_HOTPLUG_QUERY, 0),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Takes the CPU offline (online == false) or brings it back online.
+    /// Requires CAP_SYS.
+    #[cfg(feature = "userspace")]
+    pub fn set_cpu_online(cpu: u32, online: bool) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(
+                SYS_CPU,
+                Self::OP_HOTPLUG,
+                if online {
+                    Self::F_HOTPLUG_ONLINE
+                } else {
+                    Self::F_HOTPLUG_OFFLINE
+                },
+                0,
+            ),
+            cpu as u64,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Delivers wakes of @source into the event @ring (see event_ring.rs).
+    #[cfg(feature = "userspace")]
+    pub fn event_ring_attach(
+        ring: SysHandle,
+        source: SysHandle,
+        cookie: u64,
+    ) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_EVENT_RING, Self::F_EVENT_RING_ATTACH, 0),
+            ring.as_u64(),
+            source.as_u64(),
+            cookie,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Detaches @source from its event ring.
+    #[cfg(feature = "userspace")]
+    pub fn event_ring_detach(source: SysHandle) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_EVENT_RING, Self::F_EVENT_RING_DETACH, 0),
+            source.as_u64(),
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Returns the offset, in nanoseconds, applied to the wall clock.
+    #[cfg(feature = "userspace")]
+    pub fn wall_clock_offset() -> Result<i64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_WALL_CLOCK, Self::F_WALL_CLOCK_QUERY, 0),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as i64)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Moves the wall clock by delta_nsec (forward if positive).
+    /// Returns the new offset. Requires CAP_SYS.
+    #[cfg(feature = "userspace")]
+    pub fn adjust_wall_clock(delta_nsec: i64) -> Result<i64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_WALL_CLOCK, Self::F_WALL_CLOCK_ADJUST, 0),
+            delta_nsec as u64,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as i64)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Starts moving the wall clock by delta_nsec gradually (see
+    /// F_WALL_CLOCK_SLEW). Requires CAP_SYS.
+    #[cfg(feature = "userspace")]
+    pub fn slew_wall_clock(delta_nsec: i64) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_WALL_CLOCK, Self::F_WALL_CLOCK_SLEW, 0),
+            delta_nsec as u64,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Returns the nanoseconds the wall clock is yet to be slewed by.
+    #[cfg(feature = "userspace")]
+    pub fn wall_clock_slew_remaining() -> Result<i64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_WALL_CLOCK, Self::F_WALL_CLOCK_QUERY, 0),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[1] as i64)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Sets the scheduling policy of the current thread: one of SCHED_*.
+    #[cfg(feature = "userspace")]
+    pub fn set_sched_policy(policy: u64) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_SCHED_POLICY, 0, 0),
+            policy,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Sets the base priority of the current thread: one of PRIORITY_*.
+    #[cfg(feature = "userspace")]
+    pub fn set_priority(priority: u64) -> Result<(), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_CPU, Self::OP_PRIORITY, 0, 0),
+            priority,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     /// Affine the current thread to the specified CPU (or remove the affinity).
-    /// Only the IO_MANAGER can affine to CPU 0.
+    /// Only the IO_MANAGER can affine to CPU 0. Offline CPUs are rejected
+    /// with ErrorCode::NotReady.
     #[cfg(feature = "userspace")]
     pub fn affine_to_cpu(cpu: Option<u32>) -> Result<(), ErrorCode> {
         let result = do_syscall(
@@ -33,6 +33,9 @@ impl SysMem {
     pub const OP_REMAP: u8 = 6;
     pub const OP_QUERY: u8 = 7;
     pub const OP_RECLAIM: u8 = 9;
+    pub const OP_PAGER: u8 = 10;
+    pub const OP_PROTECT: u8 = 11;
+    pub const OP_ADVISE: u8 = 12;
 
     // Bit flags for create/map operations.
     pub const F_READABLE: u32 = 1;
@@ -46,9 +49,57 @@ impl SysMem {
     // is OK with lazy mapping.
     pub const F_LAZY: u32 = 0x20;
 
+    // F_HUGE is a hint that the (small) pages being allocated should be
+    // backed by 2M pages, to reduce TLB pressure. The kernel falls back
+    // to small pages if no huge pages are available. The size must be
+    // a multiple of PAGE_SIZE_MID. With F_READABLE | F_WRITABLE, allocates
+    // memory; alone, reserves a 2M-aligned unmapped region to share
+    // huge pages into (see "shared:" SysObj URLs).
+    //
+    // Note: normal (F_READABLE | F_WRITABLE) allocations that are large
+    // enough and a multiple of PAGE_SIZE_MID are backed by huge pages
+    // even without F_HUGE.
+    pub const F_HUGE: u32 = 0x40;
+
+    // Executable mappings. W^X is enforced: F_EXECUTABLE can't be combined
+    // with F_WRITABLE. Code can be freely loaded into other address spaces
+    // (F_SHARE_SELF); in the caller's own address space F_EXECUTABLE
+    // requires CAP_EXEC_MEM.
+    pub const F_EXECUTABLE: u32 = 0x80;
+
+    // OP_ADVISE hints (not bit flags). The range must be within a single
+    // region allocated by SysMem::alloc() or similar (with small pages).
+    //
+    // The contents are not needed: the pages are reclaimed if the system
+    // is low on memory, unless written to before that. Reads may return
+    // either the old contents or zeroes.
+    pub const F_ADVISE_FREE: u32 = 1;
+    // The pages will be accessed soon: map lazily allocated pages now.
+    pub const F_ADVISE_WILLNEED: u32 = 2;
+    // The contents are not needed: the pages are reclaimed now,
+    // and will be zero-filled on next access.
+    pub const F_ADVISE_DONTNEED: u32 = 3;
+    // The pages are unlikely to be accessed soon. Currently a no-op,
+    // as the kernel does not swap.
+    pub const F_ADVISE_COLD: u32 = 4;
+
     // Bit flags for query.
     pub const F_QUERY_STATS: u32 = 1;
 
+    // OP_PAGER flags. Pagers back memory regions with data provided by
+    // userspace (e.g. memory-mapped files): a page fault in a paged region
+    // blocks the faulting thread and wakes the pager handle; the pager
+    // thread then gets the faulting page via F_PAGER_NEXT_FAULT, and supplies
+    // its content via F_PAGER_SUPPLY, which resumes the blocked threads.
+    // Pagers are created via SysObj::create(SysHandle::NONE, 0, "pager").
+    //
+    // F_PAGER_MAP reserves a paged region in the pager owner's address space:
+    // read-only (F_READABLE) or private writable (F_READABLE | F_WRITABLE),
+    // i.e. writes are not propagated back to the pager.
+    pub const F_PAGER_MAP: u32 = 1;
+    pub const F_PAGER_NEXT_FAULT: u32 = 2;
+    pub const F_PAGER_SUPPLY: u32 = 3;
+
     #[cfg(feature = "userspace")]
     pub fn map(
         address_space: SysHandle,
@@ -124,6 +175,56 @@ impl SysMem {
         }
     }
 
+    // Changes the protection of [addr, addr + size) to @flags, which must be
+    // F_READABLE, F_READABLE | F_WRITABLE, or F_READABLE | F_EXECUTABLE
+    // (see F_EXECUTABLE). The range must be page-aligned and be within
+    // a single region allocated by SysMem::alloc() or similar.
+    #[cfg(feature = "userspace")]
+    pub fn protect(addr: u64, size: u64, flags: u32) -> Result<(), ErrorCode> {
+        if size == 0 || (size & (PAGE_SIZE_SMALL - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let result = do_syscall(
+            pack_nr_ver(SYS_MEM, Self::OP_PROTECT, flags, 0),
+            SysHandle::SELF.as_u64(),
+            addr,
+            size >> PAGE_SIZE_SMALL_LOG2,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    // Gives the kernel a hint about [addr, addr + size); see F_ADVISE_*.
+    // Returns the number of bytes reclaimed.
+    #[cfg(feature = "userspace")]
+    pub fn advise(addr: u64, size: u64, advice: u32) -> Result<u64, ErrorCode> {
+        if size == 0 || (size & (PAGE_SIZE_SMALL - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let result = do_syscall(
+            pack_nr_ver(SYS_MEM, Self::OP_ADVISE, advice, 0),
+            SysHandle::SELF.as_u64(),
+            addr,
+            size >> PAGE_SIZE_SMALL_LOG2,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     #[cfg(feature = "userspace")]
     pub fn virt_to_phys(virt_addr: u64) -> Result<u64, ErrorCode> {
         let result = do_syscall(
@@ -161,6 +262,23 @@ impl SysMem {
         )
     }
 
+    // Allocates @size bytes (a multiple of PAGE_SIZE_MID), preferably
+    // backed by huge pages. See F_HUGE.
+    #[cfg(feature = "userspace")]
+    pub fn alloc_huge(size: u64) -> Result<u64, ErrorCode> {
+        if size == 0 || (size & (PAGE_SIZE_MID - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        Self::map(
+            SysHandle::SELF,
+            Self::F_READABLE | Self::F_WRITABLE | Self::F_HUGE,
+            u64::MAX,
+            u64::MAX,
+            PAGE_SIZE_SMALL,
+            size >> PAGE_SIZE_SMALL_LOG2,
+        )
+    }
+
     // Note: the calling process must have CAP_IO_MANAGER.
     #[cfg(feature = "userspace")]
     pub fn alloc_contiguous_pages(size: u64) -> Result<u64, ErrorCode> {
@@ -226,6 +344,151 @@ impl SysMem {
         }
     }
 
+    // Creates a named shared memory segment of @size bytes (a multiple of
+    // PAGE_SIZE_SMALL) and maps it read-write. Other processes can map the segment
+    // via shm_open() if @others_access allows it: "none", "ro", or "rw".
+    // The name is unregistered when the returned handle is dropped; the mapping
+    // stays valid until unmapped via SysMem::free().
+    // Returns (handle, address).
+    #[cfg(feature = "userspace")]
+    pub fn shm_create(
+        name: &str,
+        size: u64,
+        others_access: &str,
+    ) -> Result<(SysHandle, u64), ErrorCode> {
+        Self::shm_op(
+            crate::SysObj::OP_CREATE,
+            name,
+            size,
+            "others",
+            others_access,
+        )
+    }
+
+    // Maps an existing named shared memory segment of @size bytes, read-only
+    // or read-write. Returns (handle, address).
+    #[cfg(feature = "userspace")]
+    pub fn shm_open(name: &str, size: u64, writable: bool) -> Result<(SysHandle, u64), ErrorCode> {
+        let access = if writable { "rw" } else { "ro" };
+        Self::shm_op(crate::SysObj::OP_GET, name, size, "access", access)
+    }
+
+    #[cfg(feature = "userspace")]
+    fn shm_op(
+        op: u8,
+        name: &str,
+        size: u64,
+        access_key: &str,
+        access: &str,
+    ) -> Result<(SysHandle, u64), ErrorCode> {
+        if size == 0 || (size & (PAGE_SIZE_SMALL - 1)) != 0 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        // The name goes into the URL as is.
+        if name.is_empty() || name.contains([';', '=']) {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let page_num = size >> PAGE_SIZE_SMALL_LOG2;
+
+        // Reserve an unmapped region for the kernel to map the segment into.
+        let addr = Self::map(
+            SysHandle::SELF,
+            0,
+            u64::MAX,
+            u64::MAX,
+            PAGE_SIZE_SMALL,
+            page_num,
+        )?;
+        let url = alloc::format!(
+            "shm:name={};address={};page_num={};{}={}",
+            name,
+            addr,
+            page_num,
+            access_key,
+            access
+        );
+
+        let result = if op == crate::SysObj::OP_CREATE {
+            crate::SysObj::create(SysHandle::NONE, 0, url.as_str())
+        } else {
+            crate::SysObj::get(SysHandle::NONE, 0, url.as_str())
+        };
+        match result {
+            Ok(handle) => Ok((handle, addr)),
+            Err(err) => {
+                Self::free(addr).unwrap();
+                Err(err)
+            }
+        }
+    }
+
+    // Reserves a paged region of @num_pages small pages. See F_PAGER_MAP.
+    #[cfg(feature = "userspace")]
+    pub fn pager_map(pager: SysHandle, num_pages: u64, writable: bool) -> Result<u64, ErrorCode> {
+        let flags = if writable {
+            Self::F_READABLE | Self::F_WRITABLE
+        } else {
+            Self::F_READABLE
+        };
+        let result = do_syscall(
+            pack_nr_ver(SYS_MEM, Self::OP_PAGER, Self::F_PAGER_MAP, 0),
+            pager.as_u64(),
+            num_pages,
+            flags as u64,
+            0,
+            0,
+            0,
+        );
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    // Returns (page address, region start) of the next page to supply,
+    // or ErrorCode::NotReady if there are no pending page faults.
+    #[cfg(feature = "userspace")]
+    pub fn pager_next_fault(pager: SysHandle) -> Result<(u64, u64), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_MEM, Self::OP_PAGER, Self::F_PAGER_NEXT_FAULT, 0),
+            pager.as_u64(),
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+        if result.is_ok() {
+            Ok((result.data[0], result.data[1]))
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    // Maps a copy of @bytes (exactly one small page) at @page_addr
+    // in a paged region, and resumes threads waiting on it.
+    #[cfg(feature = "userspace")]
+    pub fn pager_supply(pager: SysHandle, page_addr: u64, bytes: &[u8]) -> Result<(), ErrorCode> {
+        if bytes.len() as u64 != PAGE_SIZE_SMALL {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        let result = do_syscall(
+            pack_nr_ver(SYS_MEM, Self::OP_PAGER, Self::F_PAGER_SUPPLY, 0),
+            pager.as_u64(),
+            page_addr,
+            bytes.as_ptr() as usize as u64,
+            0,
+            0,
+            0,
+        );
+        if result.is_ok() {
+            Ok(())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     #[cfg(feature = "userspace")]
     pub fn reclaim(handle: SysHandle) -> Result<(), ErrorCode> {
         let res = do_syscall(
@@ -14,17 +14,28 @@ impl SysObj {
     pub const OP_CREATE: u8 = 3;
     pub const OP_SET_LOG_LEVEL: u8 = 5;
     pub const OP_QUERY_HANDLE: u8 = 6;
+    pub const OP_DUP: u8 = 7;
 
     pub const F_QUERY_PID: u32 = 4;
 
     // When connecting to ("getting") a shared URL, wake the counterpart.
     pub const F_WAKE_PEER: u32 = 1;
 
+    // When duplicating a handle, remove it from the source process.
+    pub const F_DUP_MOVE: u32 = 1;
+
+    // Handle values in [MIN_INHERITED_HANDLE, 65536) can be chosen by the parent
+    // when passing handles to a child process that has not started yet.
+    pub const MIN_INHERITED_HANDLE: u64 = 16;
+
     // URLS:
     //     - "address_space:$URL"
     //                  Creates a new address space that can be identified by the $URL;
     //     - "capabilities"
+    //     - "event_ring:address=$addr" (CREATE only; parent = NONE): see event_ring.rs
     //     - "irq_wait:$NUM"
+    //     - "klog" (GET only; parent = KERNEL): woken on new kernel log entries
+    //     - "pager" (CREATE only; parent = NONE): see SysMem::OP_PAGER
     //     - "process:entry_point=$NUM;capabilities=$NUM"
     //     - "serial_console"
     //     - "shared:url=$URL;address=$addr;page_type=[small|mid];page_num=$num"
@@ -35,6 +46,12 @@ impl SysObj {
     //              is up to the userspace.
     //            - For now, only 1:1 connections are supported.
     //            - Later "multicast" connections will be added (server writes), multiple clients read.
+    //     - "shm:name=$NAME;address=$addr;page_num=$num;others=[none|ro|rw]" (CREATE; parent = NONE)
+    //       "shm:name=$NAME;address=$addr;page_num=$num;access=[ro|rw]" (GET; parent = NONE)
+    //            - Named shared memory segments: see SysMem::shm_create() and SysMem::shm_open().
+    //     - "terminate_request" (GET only; parent = SELF): see SysCpu::termination_request_handle()
+    //     - "timer" (CREATE only; parent = NONE)
+    //     - "watchdog:timeout_ms=$NUM;action=[log|dump|pause]" (CREATE only; parent = NONE)
     #[cfg(feature = "userspace")]
     pub fn create(parent: SysHandle, flags: u32, url: &str) -> Result<SysHandle, ErrorCode> {
         let bytes = url.as_bytes();
@@ -155,6 +172,35 @@ impl SysObj {
         }
     }
 
+    /// Duplicates @handle owned by @src_process into @dst_process, which must
+    /// be the caller, or a process that has not started yet. If @dst_handle
+    /// is not NONE, the new handle gets this value (see MIN_INHERITED_HANDLE),
+    /// which is only allowed before @dst_process starts.
+    /// Returns the handle valid in @dst_process.
+    #[cfg(feature = "userspace")]
+    pub fn dup(
+        src_process: SysHandle,
+        handle: SysHandle,
+        dst_process: SysHandle,
+        dst_handle: SysHandle,
+        flags: u32,
+    ) -> Result<SysHandle, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_OBJ, Self::OP_DUP, flags, 0),
+            src_process.as_u64(),
+            handle.as_u64(),
+            dst_process.as_u64(),
+            dst_handle.as_u64(),
+            0,
+            0,
+        );
+        if result.is_ok() {
+            Ok(SysHandle::from(result.data[0]))
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     // Level: log::LevelFilter; 3 => Info, 4 => Debug, etc.
     #[cfg(feature = "userspace")]
     pub fn set_log_level(level: u8) -> Result<u8, ErrorCode> {
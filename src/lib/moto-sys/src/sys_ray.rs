@@ -12,10 +12,30 @@ impl SysRay {
     pub const OP_QUERY_PROCESS: u8 = 1;
     pub const OP_DBG: u8 = 2;
     pub const OP_LOG: u8 = 3;
+    pub const OP_TRACE: u8 = 4;
+    pub const OP_SYSCALL_TRACE: u8 = 5;
 
     pub const F_QUERY_STATUS: u32 = 1;
     pub const F_QUERY_LIST: u32 = 2;
     pub const F_QUERY_LIST_CHILDREN: u32 = 3;
+    pub const F_QUERY_NAMESPACE: u32 = 4;
+    pub const F_QUERY_ACCT: u32 = 5;
+    pub const F_QUERY_THREADS: u32 = 6;
+    pub const F_QUERY_HANDLES: u32 = 7;
+    pub const F_QUERY_CREDENTIALS: u32 = 8;
+
+    /// Read the kernel log (OP_LOG).
+    pub const F_LOG_READ: u32 = 1;
+
+    /// Set the mask of enabled tracepoints (OP_TRACE).
+    pub const F_TRACE_ENABLE: u32 = 1;
+    /// Drain trace events of a CPU (OP_TRACE).
+    pub const F_TRACE_DRAIN: u32 = 2;
+
+    /// Start tracing syscalls of a process (OP_SYSCALL_TRACE).
+    pub const F_SYSCALL_TRACE_ATTACH: u32 = 1;
+    /// Read syscalls recorded by a syscall trace (OP_SYSCALL_TRACE).
+    pub const F_SYSCALL_TRACE_READ: u32 = 2;
 
     /// Attach to a running process.
     pub const F_DBG_ATTACH: u32 = 1;
@@ -36,6 +56,8 @@ impl SysRay {
     pub const F_DBG_GET_MEM: u32 = 7;
     /// Detach the debugger. Note that just putting the handle is not enough.
     pub const F_DBG_DETACH: u32 = 8;
+    /// List loaded modules (ELF images) of the attached process.
+    pub const F_DBG_LIST_MODULES: u32 = 9;
 
     #[cfg(feature = "userspace")]
     pub fn process_status(handle: SysHandle) -> Result<Option<u64>, ErrorCode> {
@@ -92,6 +114,279 @@ impl SysRay {
         }
     }
 
+    /// List threads (up to buf.len()) of the process with the given PID.
+    #[cfg(feature = "userspace")]
+    pub fn list_threads_v1(
+        pid: u64,
+        buf: &mut [super::stats::ThreadStatsV1],
+    ) -> Result<usize, ErrorCode> {
+        if buf.len() < 1 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_QUERY_PROCESS, Self::F_QUERY_THREADS, 0),
+            pid,
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// List open handles (up to buf.len()) of the process with the given PID,
+    /// starting with @start_handle.
+    #[cfg(feature = "userspace")]
+    pub fn list_handles_v1(
+        pid: u64,
+        start_handle: u64,
+        buf: &mut [super::stats::HandleInfoV1],
+    ) -> Result<usize, ErrorCode> {
+        if buf.len() < 1 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_QUERY_PROCESS, Self::F_QUERY_HANDLES, 0),
+            pid,
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            start_handle,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Get the namespace info of the process with the given PID.
+    /// The process must be visible from the caller's namespace.
+    #[cfg(feature = "userspace")]
+    pub fn query_namespace_v1(pid: u64) -> Result<super::stats::NamespaceInfoV1, ErrorCode> {
+        let mut info = super::stats::NamespaceInfoV1::default();
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_QUERY_PROCESS, Self::F_QUERY_NAMESPACE, 0),
+            pid,
+            (&mut info) as *mut _ as usize as u64,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(info)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Get the (uid, gid) of the process with the given PID.
+    /// The process must be visible from the caller's namespace.
+    #[cfg(feature = "userspace")]
+    pub fn query_credentials(pid: u64) -> Result<(u32, u32), ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(
+                SYS_RAY,
+                Self::OP_QUERY_PROCESS,
+                Self::F_QUERY_CREDENTIALS,
+                0,
+            ),
+            pid,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok((result.data[0] as u32, result.data[1] as u32))
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Read process accounting records with seq >= @start_seq.
+    /// Returns the number of records read.
+    #[cfg(feature = "userspace")]
+    pub fn read_acct_log_v1(
+        start_seq: u64,
+        buf: &mut [super::stats::ProcessAcctV1],
+    ) -> Result<usize, ErrorCode> {
+        if buf.len() < 1 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_QUERY_PROCESS, Self::F_QUERY_ACCT, 0),
+            start_seq,
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Read kernel log entries with seq >= @start_seq.
+    /// Returns the number of entries read. To stream the log, wait on
+    /// the handle returned by SysObj::get(SysHandle::KERNEL, 0, "klog"),
+    /// which is woken when new entries are added.
+    #[cfg(feature = "userspace")]
+    pub fn read_log_v1(
+        start_seq: u64,
+        buf: &mut [super::stats::KLogEntryV1],
+    ) -> Result<usize, ErrorCode> {
+        if buf.len() < 1 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_LOG, Self::F_LOG_READ, 0),
+            start_seq,
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Enable tracepoints: bit N in @mask enables stats::TRACE_* event N;
+    /// zero mask disables tracing. Returns the previous mask.
+    #[cfg(feature = "userspace")]
+    pub fn trace_enable(mask: u64) -> Result<u64, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_TRACE, Self::F_TRACE_ENABLE, 0),
+            mask,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0])
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Move trace events recorded on @cpu into @buf, oldest first.
+    /// Returns the number of events drained.
+    #[cfg(feature = "userspace")]
+    pub fn trace_drain_v1(
+        cpu: u32,
+        buf: &mut [super::stats::TraceEventV1],
+    ) -> Result<usize, ErrorCode> {
+        if buf.len() < 1 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_TRACE, Self::F_TRACE_DRAIN, 0),
+            cpu as u64,
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Start tracing syscalls of process @pid: syscalls completed by the
+    /// process are recorded, and the returned handle is woken. Tracing stops
+    /// when the handle is put. Only one trace per process is allowed.
+    /// The process must be visible in the caller's namespace; tracing a
+    /// process running under another user ID requires CAP_LOG.
+    #[cfg(feature = "userspace")]
+    pub fn syscall_trace_attach(pid: u64) -> Result<SysHandle, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(
+                SYS_RAY,
+                Self::OP_SYSCALL_TRACE,
+                Self::F_SYSCALL_TRACE_ATTACH,
+                0,
+            ),
+            pid,
+            0,
+            0,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0].into())
+        } else {
+            Err(result.error_code())
+        }
+    }
+
+    /// Move syscalls recorded by the trace @handle into @buf, oldest first.
+    /// Returns the number of events read.
+    #[cfg(feature = "userspace")]
+    pub fn syscall_trace_read_v1(
+        handle: SysHandle,
+        buf: &mut [super::stats::SyscallTraceEventV1],
+    ) -> Result<usize, ErrorCode> {
+        if buf.len() < 1 {
+            return Err(ErrorCode::InvalidArgument);
+        }
+
+        let result = do_syscall(
+            pack_nr_ver(
+                SYS_RAY,
+                Self::OP_SYSCALL_TRACE,
+                Self::F_SYSCALL_TRACE_READ,
+                0,
+            ),
+            handle.as_u64(),
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     #[cfg(feature = "userspace")]
     pub fn dbg_attach(pid: u64) -> Result<SysHandle, ErrorCode> {
         let result = do_syscall(
@@ -242,6 +537,30 @@ impl SysRay {
         }
     }
 
+    /// Fill buf with the modules loaded into the debugged process.
+    /// Upon success, returns the number of modules populated into buf.
+    #[cfg(feature = "userspace")]
+    pub fn dbg_list_modules_v1(
+        dbg_handle: SysHandle,
+        buf: &mut [crate::stats::ModuleInfoV1],
+    ) -> Result<usize, ErrorCode> {
+        let result = do_syscall(
+            pack_nr_ver(SYS_RAY, Self::OP_DBG, Self::F_DBG_LIST_MODULES, 1),
+            dbg_handle.into(),
+            buf.as_mut_ptr() as usize as u64,
+            buf.len() as u64,
+            0,
+            0,
+            0,
+        );
+
+        if result.is_ok() {
+            Ok(result.data[0] as usize)
+        } else {
+            Err(result.error_code())
+        }
+    }
+
     #[cfg(feature = "userspace")]
     pub fn dbg_detach(dbg_handle: SysHandle) -> Result<(), ErrorCode> {
         let result = do_syscall(
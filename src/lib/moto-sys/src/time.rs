@@ -3,6 +3,7 @@ use core::sync::atomic::*;
 use core::time::Duration;
 
 use super::KernelStaticPage;
+use crate::ErrorCode;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Instant {
@@ -14,6 +15,58 @@ pub struct SystemTime {
     nanos: u128, // Note that SystemTime uses nanos vs Instant which uses tsc.
 }
 
+/// Clocks that can be queried via clock_now().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockId {
+    /// Never goes back; starts at system start.
+    Monotonic,
+    /// Wall clock time since UNIX_EPOCH; may jump.
+    Realtime,
+    /// CPU time consumed by the current process.
+    ProcessCpu,
+    /// CPU time consumed by the current thread.
+    ThreadCpu,
+}
+
+/// Returns the current value of @clock: the duration since system start
+/// for Monotonic, since UNIX_EPOCH for Realtime, and the CPU time consumed
+/// for CPU clocks (which query the kernel, and so can fail).
+pub fn clock_now(clock: ClockId) -> Result<Duration, ErrorCode> {
+    match clock {
+        ClockId::Monotonic => Ok(since_system_start()),
+        ClockId::Realtime => {
+            let nanos = SystemTime::now().nanos;
+            Ok(Duration::new(
+                (nanos / (NANOS_IN_SEC as u128)) as u64,
+                (nanos % (NANOS_IN_SEC as u128)) as u32,
+            ))
+        }
+        ClockId::ProcessCpu => crate::SysCpu::cpu_time_tsc(false).map(tsc_to_duration),
+        ClockId::ThreadCpu => crate::SysCpu::cpu_time_tsc(true).map(tsc_to_duration),
+    }
+}
+
+/// The resolution of @clock. All clocks are TSC-based.
+pub fn clock_resolution(_clock: ClockId) -> Duration {
+    let tsc_in_sec = KernelStaticPage::get().tsc_in_sec;
+    if tsc_in_sec >= NANOS_IN_SEC {
+        Duration::from_nanos(1)
+    } else {
+        Duration::from_nanos(NANOS_IN_SEC / tsc_in_sec.max(1))
+    }
+}
+
+pub fn tsc_to_duration(tsc: u64) -> Duration {
+    let tsc_in_sec = KernelStaticPage::get().tsc_in_sec;
+    if core::intrinsics::unlikely(tsc_in_sec == 0) {
+        return Duration::ZERO;
+    }
+    let secs = tsc / tsc_in_sec;
+    let nanos = tsc_to_nanos_128(tsc % tsc_in_sec);
+
+    Duration::new(secs, nanos as u32)
+}
+
 #[allow(unused)]
 pub const UNIX_EPOCH: SystemTime = SystemTime { nanos: 0u128 };
 pub const NANOS_IN_SEC: u64 = 1_000_000_000;
@@ -72,14 +125,7 @@ impl Instant {
             return Duration::ZERO;
         }
 
-        let tsc_in_sec = KernelStaticPage::get().tsc_in_sec;
-        if core::intrinsics::unlikely(tsc_in_sec == 0) {
-            return Duration::ZERO;
-        }
-        let secs = tsc_diff / tsc_in_sec;
-        let nanos = tsc_to_nanos_128(tsc_diff % tsc_in_sec);
-
-        Duration::new(secs, nanos as u32)
+        tsc_to_duration(tsc_diff)
     }
 
     pub fn elapsed(&self) -> Duration {
@@ -242,7 +288,28 @@ fn abs_nanos_from_tsc(tsc_val: u64) -> u128 {
     let mut time = mul >> 32;
     time += page.system_time as u128;
 
-    page.base_nsec as u128 + time
+    let slew = wall_clock_slew(page, tsc_val);
+    (page.base_nsec as u128 + time).wrapping_add_signed(slew as i128)
+}
+
+// How far the slew in progress has moved the wall clock by @tsc_val.
+fn wall_clock_slew(page: &KernelStaticPage, tsc_val: u64) -> i64 {
+    if page.wall_clock_slew_nsec == 0 || tsc_val <= page.wall_clock_slew_tsc {
+        return 0;
+    }
+
+    let mut elapsed = tsc_val - page.wall_clock_slew_tsc;
+    let tsc_shift = page.tsc_shift;
+    if tsc_shift >= 0 {
+        elapsed <<= tsc_shift;
+    } else {
+        elapsed >>= -tsc_shift;
+    }
+    let elapsed = ((elapsed as u128) * (page.tsc_mul as u128)) >> 32;
+
+    let slewed = (elapsed / (crate::SysCpu::WALL_CLOCK_SLEW_PERIOD_NSEC as u128))
+        .min(page.wall_clock_slew_nsec.unsigned_abs() as u128) as i64;
+    slewed * page.wall_clock_slew_nsec.signum()
 }
 
 fn rdtsc() -> u64 {
@@ -0,0 +1,169 @@
+// TLS for Motor OS apps, built on rustls (with ring).
+//
+// Servers get the usual PEM loading for certificates and private keys.
+// Clients validate servers against the system trust store: the Mozilla root
+// certificates (from webpki-roots) plus any bundles dropped into /sys/certs.
+
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::RootCertStore;
+
+/// Extra trusted root certificates: every *.pem/*.crt file here is loaded.
+pub const CERTS_DIR: &str = "/sys/certs";
+
+/// Loads all certificates from a PEM file.
+pub fn load_certs<P: AsRef<Path>>(path: P) -> Result<Vec<CertificateDer<'static>>> {
+    rustls_pemfile::certs(&mut BufReader::new(File::open(path)?)).collect()
+}
+
+/// Loads the first private key from a PEM file.
+pub fn load_private_key<P: AsRef<Path>>(path: P) -> Result<PrivateKeyDer<'static>> {
+    let path = path.as_ref();
+    rustls_pemfile::private_key(&mut BufReader::new(File::open(path)?))?.ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("no private key in {}", path.display()),
+        )
+    })
+}
+
+/// Adds the certificates from every *.pem/*.crt file in `dir` to `store`, and
+/// returns how many were added and ignored, like
+/// `RootCertStore::add_parsable_certificates`. A missing directory adds
+/// nothing; files that can't be read and certificates that do not parse are
+/// skipped, and counted as ignored, so that one bad bundle does not take the
+/// others down with it.
+pub fn add_certs_from_dir<P: AsRef<Path>>(
+    store: &mut RootCertStore,
+    dir: P,
+) -> Result<(usize, usize)> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok((0, 0)),
+        Err(err) => return Err(err),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pem") | Some("crt") => paths.push(path),
+            _ => {}
+        }
+    }
+    paths.sort();
+
+    let (mut added, mut ignored) = (0, 0);
+    for path in &paths {
+        match load_certs(path) {
+            Ok(certs) => {
+                let (file_added, file_ignored) = store.add_parsable_certificates(certs);
+                added += file_added;
+                ignored += file_ignored;
+            }
+            Err(_) => ignored += 1,
+        }
+    }
+
+    Ok((added, ignored))
+}
+
+/// The system trust store: the Mozilla roots, plus the certificates in CERTS_DIR.
+pub fn root_cert_store() -> Result<RootCertStore> {
+    let mut store = RootCertStore::empty();
+    store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    add_certs_from_dir(&mut store, CERTS_DIR)?;
+    Ok(store)
+}
+
+/// A client config that validates servers against the system trust store.
+pub fn client_config() -> Result<Arc<rustls::ClientConfig>> {
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(root_cert_store()?)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// A server config with the certificate chain and the private key from PEM files.
+pub fn server_config<P: AsRef<Path>>(
+    cert_file: P,
+    key_file: P,
+) -> Result<Arc<rustls::ServerConfig>> {
+    let certs = load_certs(cert_file)?;
+    let private_key = load_private_key(key_file)?;
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    Ok(Arc::new(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A per-test scratch directory, removed when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("moto-tls-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn certs_dir_bundles() {
+        let dir = TempDir::new("certs");
+        std::fs::write(dir.0.join("ca.pem"), include_str!("../testdata/ca.pem")).unwrap();
+        std::fs::write(dir.0.join("ca.crt"), include_str!("../testdata/ca.pem")).unwrap();
+        std::fs::write(dir.0.join("README"), "not a certificate").unwrap();
+
+        let mut store = RootCertStore::empty();
+        assert_eq!(add_certs_from_dir(&mut store, &dir.0).unwrap(), (2, 0));
+        assert_eq!(store.len(), 2);
+
+        // Bad files are skipped: one that is not PEM, and one with a
+        // certificate that is not DER.
+        std::fs::write(
+            dir.0.join("bad.pem"),
+            "-----BEGIN CERTIFICATE-----\n!!!\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.0.join("junk.crt"),
+            "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let mut store = RootCertStore::empty();
+        assert_eq!(add_certs_from_dir(&mut store, &dir.0).unwrap(), (2, 2));
+        assert_eq!(store.len(), 2);
+
+        let mut store = RootCertStore::empty();
+        assert_eq!(
+            add_certs_from_dir(&mut store, dir.0.join("missing")).unwrap(),
+            (0, 0)
+        );
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn system_trust_store() {
+        let store = root_cert_store().unwrap();
+        assert!(store.len() >= webpki_roots::TLS_SERVER_ROOTS.len());
+        client_config().unwrap();
+    }
+}
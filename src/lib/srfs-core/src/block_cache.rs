@@ -1,5 +1,9 @@
+use crate::journal::Journal;
+use crate::snapshot::{Snapshot, Snapshots};
 use crate::*;
 use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
 use core::pin::Pin;
 
 const CACHE_SIZE: usize = 16;
@@ -32,16 +36,26 @@ impl CachedBlock {
 
 pub(crate) struct BlockCache {
     blocks: [CachedBlock; CACHE_SIZE],
+    scratch: Pin<Box<Block>>, // For direct I/O.
     block_device: Box<dyn SyncBlockDevice>,
+    snapshots: Snapshots,     // Preserve blocks before they are written to.
+    journal: Option<Journal>, // Holds the writes of a TXN until it commits.
     block_reads: u64,
     block_writes: u64,
 }
 
 impl BlockCache {
-    pub(crate) fn new(block_device: Box<dyn SyncBlockDevice>) -> Self {
+    pub(crate) fn new(
+        block_device: Box<dyn SyncBlockDevice>,
+        snapshots: Snapshots,
+        journal: Option<Journal>,
+    ) -> Self {
         Self {
             blocks: Default::default(),
+            scratch: Box::pin(Block::new_uninit()),
             block_device,
+            snapshots,
+            journal,
             block_reads: 0,
             block_writes: 0,
         }
@@ -61,8 +75,12 @@ impl BlockCache {
         block.block_no = block_no;
 
         self.block_reads += 1;
-        self.block_device
-            .read_block(block_no, block.block.as_bytes_mut())?;
+        Self::read_block(
+            &mut *self.block_device,
+            &self.journal,
+            block_no,
+            block.block.as_bytes_mut(),
+        )?;
         self.push_top(CACHE_SIZE - 1);
         Ok(&self.blocks[0])
     }
@@ -82,8 +100,12 @@ impl BlockCache {
         block.block_no = block_no;
 
         self.block_reads += 1;
-        self.block_device
-            .read_block(block_no, block.block.as_bytes_mut())?;
+        Self::read_block(
+            &mut *self.block_device,
+            &self.journal,
+            block_no,
+            block.block.as_bytes_mut(),
+        )?;
         self.push_top(CACHE_SIZE - 1);
         self.blocks[0].dirty = true;
         Ok(&mut self.blocks[0])
@@ -127,8 +149,13 @@ impl BlockCache {
                 self.push_top(idx);
                 debug_assert!(self.blocks[0].dirty);
                 self.block_writes += 1;
-                self.block_device
-                    .write_block(block_no, self.blocks[0].block.as_bytes())?;
+                Self::write_block(
+                    &mut *self.block_device,
+                    &mut self.snapshots,
+                    &mut self.journal,
+                    block_no,
+                    self.blocks[0].block.as_bytes(),
+                )?;
                 self.blocks[0].dirty = false;
                 return Ok(());
             }
@@ -143,7 +170,12 @@ impl BlockCache {
         block: &mut Block,
     ) -> Result<(), FsError> {
         self.block_reads += 1;
-        self.block_device.read_block(block_no, block.as_bytes_mut())
+        Self::read_block(
+            &mut *self.block_device,
+            &self.journal,
+            block_no,
+            block.as_bytes_mut(),
+        )
     }
 
     pub(crate) fn write_uncached_block(
@@ -152,7 +184,187 @@ impl BlockCache {
         block: &Block,
     ) -> Result<(), FsError> {
         self.block_writes += 1;
-        self.block_device.write_block(block_no, block.as_bytes())
+        Self::write_block(
+            &mut *self.block_device,
+            &mut self.snapshots,
+            &mut self.journal,
+            block_no,
+            block.as_bytes(),
+        )
+    }
+
+    // Direct I/O: read bytes at @offset of the block without caching the block.
+    // The cache is write-through, so the device (or the journal) has the
+    // latest bytes.
+    pub(crate) fn read_direct(
+        &mut self,
+        block_no: u64,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<(), FsError> {
+        debug_assert!(offset + buf.len() <= BLOCK_SIZE as usize);
+        self.block_reads += 1;
+        Self::read_block(
+            &mut *self.block_device,
+            &self.journal,
+            block_no,
+            self.scratch.as_bytes_mut(),
+        )?;
+        buf.copy_from_slice(&self.scratch.as_bytes()[offset..(offset + buf.len())]);
+        Ok(())
+    }
+
+    // Direct I/O: write bytes at @offset of the block without caching the block.
+    // Partial writes read the block first. A cached copy, if any, is updated.
+    pub(crate) fn write_direct(
+        &mut self,
+        block_no: u64,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<(), FsError> {
+        debug_assert!(offset + buf.len() <= BLOCK_SIZE as usize);
+        if buf.len() < BLOCK_SIZE as usize {
+            self.block_reads += 1;
+            Self::read_block(
+                &mut *self.block_device,
+                &self.journal,
+                block_no,
+                self.scratch.as_bytes_mut(),
+            )?;
+        }
+        self.scratch.as_bytes_mut()[offset..(offset + buf.len())].copy_from_slice(buf);
+        self.block_writes += 1;
+        Self::write_block(
+            &mut *self.block_device,
+            &mut self.snapshots,
+            &mut self.journal,
+            block_no,
+            self.scratch.as_bytes(),
+        )?;
+
+        for cached in &mut self.blocks {
+            if cached.block_no == block_no {
+                debug_assert!(!cached.dirty);
+                cached.block.as_bytes_mut()[offset..(offset + buf.len())].copy_from_slice(buf);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn snapshots(&self) -> &Snapshots {
+        &self.snapshots
+    }
+
+    pub(crate) fn create_snapshot(
+        &mut self,
+        name: &str,
+        area_start: u64,
+        area_blocks: u64,
+        watermark: u64,
+    ) -> Result<(), FsError> {
+        self.snapshots.create(
+            &mut *self.block_device,
+            name,
+            area_start,
+            area_blocks,
+            watermark,
+        )
+    }
+
+    pub(crate) fn remove_snapshot(&mut self, name: &str) -> Result<Rc<RefCell<Snapshot>>, FsError> {
+        self.snapshots.remove(name)
+    }
+
+    pub(crate) fn has_journal(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    // Set up an empty journal in the area.
+    pub(crate) fn add_journal(&mut self, start: u64, blocks: u64) -> Result<(), FsError> {
+        debug_assert!(self.journal.is_none());
+        let mut journal = Journal::new(start, blocks);
+        journal.clear(&mut *self.block_device, &mut self.scratch)?;
+        self.journal = Some(journal);
+        Ok(())
+    }
+
+    pub(crate) fn replay_journal(&mut self) -> Result<bool, FsError> {
+        match self.journal.as_mut() {
+            Some(journal) => journal.replay(
+                &mut *self.block_device,
+                &mut self.snapshots,
+                &mut self.scratch,
+            ),
+            None => Ok(false),
+        }
+    }
+
+    // With a journal, the writes of the TXN stay in memory until it commits.
+    pub(crate) fn begin_txn(&mut self) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.begin();
+        }
+    }
+
+    pub(crate) fn commit_txn(&mut self) -> Result<(), FsError> {
+        match self.journal.as_mut() {
+            Some(journal) if journal.is_active() => journal.commit(
+                &mut *self.block_device,
+                &mut self.snapshots,
+                &mut self.scratch,
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    // Drop the writes of a failed TXN, if the journal holds them, and the
+    // cached blocks they came from. Returns true if it did.
+    pub(crate) fn abort_txn(&mut self) -> bool {
+        let Some(journal) = self.journal.as_mut() else {
+            return false;
+        };
+        if !journal.abort() {
+            return false;
+        }
+        for cached in &mut self.blocks {
+            cached.block_no = u64::MAX;
+            cached.dirty = false;
+        }
+        true
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), FsError> {
+        if self.journal.as_ref().is_some_and(|j| j.is_active()) {
+            return Ok(()); // Nothing has reached the device yet.
+        }
+        self.block_device.flush()
+    }
+
+    fn read_block(
+        block_device: &mut dyn SyncBlockDevice,
+        journal: &Option<Journal>,
+        block_no: u64,
+        buf: &mut [u8],
+    ) -> Result<(), FsError> {
+        if journal.as_ref().is_some_and(|j| j.read(block_no, buf)) {
+            return Ok(());
+        }
+        block_device.read_block(block_no, buf)
+    }
+
+    fn write_block(
+        block_device: &mut dyn SyncBlockDevice,
+        snapshots: &mut Snapshots,
+        journal: &mut Option<Journal>,
+        block_no: u64,
+        buf: &[u8],
+    ) -> Result<(), FsError> {
+        if let Some(journal) = journal.as_mut().filter(|j| j.is_active()) {
+            return journal.write(block_no, buf);
+        }
+        snapshots.before_write(block_device, block_no)?;
+        block_device.write_block(block_no, buf)
     }
 
     fn push_top(&mut self, idx: usize) {
@@ -87,4 +87,8 @@ impl crate::SyncBlockDevice for FileBlockDevice {
 
         Ok(())
     }
+
+    fn flush(&mut self) -> Result<(), FsError> {
+        self.file.sync_data().map_err(|_| FsError::IoError)
+    }
 }
@@ -7,12 +7,20 @@ use core::ptr::copy_nonoverlapping;
 
 use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 
 use crate::block_cache::BlockCache;
+use crate::journal::Journal;
+use crate::snapshot::Snapshots;
 
 use super::*;
 
+// See SyncFileSystem::read_xattrs().
+type XattrsResult = Result<(u64, Vec<(String, Vec<u8>)>), FsError>;
+
 pub(crate) fn format(block_device: &mut dyn SyncBlockDevice) -> Result<(), FsError> {
     let num_blocks = block_device.num_blocks();
     if num_blocks < 2 {
@@ -56,11 +64,89 @@ enum BlockType {
     ListOfLinks,
 }
 
+// What check() finds: the issues, with how to fix them (if possible), and
+// the blocks in use.
+struct Scan {
+    issues: Vec<(CheckIssue, Option<Fix>)>,
+    used: BlockMap,
+    end: u64, // Blocks from here on are in the empty area.
+}
+
+impl Scan {
+    // Mark a block as used by entry id; false if it is bad or used already.
+    fn use_block(&mut self, id: EntryId, block_no: u64, fix: Option<Fix>) -> bool {
+        let issue = if block_no < 2 || block_no >= self.end {
+            CheckIssue::BadBlock { id, block_no }
+        } else if !self.used.insert(block_no) {
+            CheckIssue::CrossLinked { id, block_no }
+        } else {
+            return true;
+        };
+        self.issues.push((issue, fix));
+        false
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Fix {
+    RemoveEntry {
+        parent: EntryId,
+        id: EntryId,
+    },
+    SetLinks {
+        id: EntryId,
+        references: u32,
+    },
+    SetParent {
+        id: EntryId,
+        parent_id: EntryId,
+    },
+    ClearXattrs {
+        id: EntryId,
+    },
+    // Makes a hole in a file.
+    ClearLink {
+        block_no: u64,
+        idx: u64,
+        in_meta: bool,
+    },
+    Generation {
+        generation: u64,
+    },
+    // Rebuild the freelist.
+    FreeSpace,
+}
+
+// One bit per block.
+struct BlockMap {
+    bits: Vec<u64>,
+}
+
+impl BlockMap {
+    fn new(num_blocks: u64) -> Self {
+        Self {
+            bits: vec![0; num_blocks.div_ceil(64) as usize],
+        }
+    }
+
+    fn contains(&self, block_no: u64) -> bool {
+        (self.bits[(block_no >> 6) as usize] & (1 << (block_no & 63))) != 0
+    }
+
+    // False if already there.
+    fn insert(&mut self, block_no: u64) -> bool {
+        let contains = self.contains(block_no);
+        self.bits[(block_no >> 6) as usize] |= 1 << (block_no & 63);
+        !contains
+    }
+}
+
 pub struct SyncFileSystem {
     superblock: Superblock,
     blockcache: BlockCache,
     num_blocks: u64,
     error: Result<(), FsError>, // If set, the FS is corrupted and cannot be used.
+    unclean: bool,              // A TXN was in progress when the FS was opened.
 }
 
 impl SyncFileSystem {
@@ -79,38 +165,97 @@ impl SyncFileSystem {
     pub fn open_fs(mut block_device: Box<dyn SyncBlockDevice>) -> Result<Self, FsError> {
         let mut block = Box::new(Block::new_uninit());
         block_device.read_block(0, block.as_bytes_mut())?;
-        let superblock = Superblock::from(block)?;
+        let mut superblock = Superblock::from(block)?;
 
         let num_blocks = superblock.header().num_blocks;
         if num_blocks < 3 || num_blocks == u64::MAX || num_blocks > block_device.num_blocks() {
             return Err(FsError::InvalidArgument);
         }
+        let jh = superblock.journal_header();
+        let journal = if jh.start == 0 {
+            None
+        } else if jh.start < num_blocks || jh.start + jh.blocks > block_device.num_blocks() {
+            return Err(FsError::ValidationFailed);
+        } else {
+            Some(Journal::new(jh.start, jh.blocks))
+        };
+
+        let snapshots = Snapshots::load(&mut *block_device, superblock.snapshot_table())?;
+        let mut blockcache = BlockCache::new(block_device, snapshots, journal);
+
+        // The last TXN may have been committed to the journal, but not fully
+        // written in place: finish it. It likely changed the superblock.
+        if blockcache.replay_journal()? {
+            let mut block = Box::new(Block::new_uninit());
+            blockcache.__read_uncached_block(0, &mut block)?;
+            superblock = Superblock::from(block)?;
+        }
 
+        // A transaction in progress means that the volume was not unmounted
+        // cleanly: it cannot be used until repaired (see repair()).
         let fbh = superblock.header();
-        if fbh.txn_meta_block != 0
+        let unclean = fbh.txn_type != TXN_TYPE_NONE
+            || fbh.txn_meta_block != 0
             || fbh.txn_data_block != 0
             || fbh.txn_link_block != 0
-            || fbh.txn_list_of_links_block != 0
-        {
-            todo!("roll back or commit the TXN")
-        }
+            || fbh.txn_list_of_links_block != 0;
 
         Ok(Self {
             num_blocks,
             superblock,
-            blockcache: BlockCache::new(block_device),
-            error: Ok(()),
+            blockcache,
+            error: if unclean {
+                Err(FsError::ValidationFailed)
+            } else {
+                Ok(())
+            },
+            unclean,
         })
     }
 
+    /// The volume was not unmounted cleanly, and must be repaired before use.
+    pub fn needs_repair(&self) -> bool {
+        self.unclean
+    }
+
     /// Create a new file.
     pub fn add_file(&mut self, parent_id: EntryId, name: &str) -> Result<EntryId, FsError> {
-        self.add_directory_entry(parent_id, name, true)
+        self.add_directory_entry(parent_id, name, true, None)
     }
 
     /// Create a new directory.
     pub fn add_directory(&mut self, parent_id: EntryId, name: &str) -> Result<EntryId, FsError> {
-        self.add_directory_entry(parent_id, name, false)
+        self.add_directory_entry(parent_id, name, false, None)
+    }
+
+    /// Create a new symbolic link pointing at target. The target is not
+    /// validated or resolved: it may be relative, and may not exist.
+    pub fn add_symlink(
+        &mut self,
+        parent_id: EntryId,
+        name: &str,
+        target: &str,
+    ) -> Result<EntryId, FsError> {
+        if target.is_empty() || target.len() as u64 > MAX_SYMLINK_LEN {
+            return Err(FsError::InvalidArgument);
+        }
+        self.add_directory_entry(parent_id, name, true, Some(target))
+    }
+
+    /// Get the target of a symbolic link.
+    pub fn read_symlink(&mut self, link_id: EntryId) -> Result<String, FsError> {
+        self.error?;
+        let block = self.blockcache.read(link_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate_file(link_id)?;
+        if (meta.flags & ENTRY_FLAG_SYMLINK) == 0 || meta.size > MAX_SYMLINK_LEN {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let bytes = &block.block().as_data_bytes_in_meta()[0..(meta.size as usize)];
+        Ok(core::str::from_utf8(bytes)
+            .map_err(|_| FsError::Utf8Error)?
+            .to_owned())
     }
 
     // Returns parent size.
@@ -314,6 +459,7 @@ impl SyncFileSystem {
         parent_id: EntryId,
         name: &str,
         is_file: bool,
+        symlink_target: Option<&str>,
     ) -> Result<EntryId, FsError> {
         self.error?;
         self.validate_new_entry(parent_id, name)?;
@@ -347,13 +493,22 @@ impl SyncFileSystem {
         let meta = unsafe { meta_block.block_mut().get_mut::<EntryMetadata>() };
         *meta = EntryMetadata::new(new_id, parent_id);
         meta.size = 0;
+        if let Some(target) = symlink_target {
+            meta.flags = ENTRY_FLAG_SYMLINK;
+            meta.size = target.len() as u64;
+        }
         meta.set_crc32();
+        if let Some(target) = symlink_target {
+            // The target goes into the same block, so the link is never seen
+            // without it.
+            meta_block.block_mut().as_data_bytes_in_meta_mut()[0..target.len()]
+                .copy_from_slice(target.as_bytes());
+        }
         self.blockcache.write(block_no)?;
 
         self.add_directory_entry_inner(parent_id, new_id, name)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })?;
 
         // Commit.
@@ -451,6 +606,225 @@ impl SyncFileSystem {
         Ok(meta.into())
     }
 
+    /// Replace the user data of an entry (see Attr::user_data).
+    pub fn set_user_data(&mut self, entry: EntryId, user_data: [u64; 4]) -> Result<(), FsError> {
+        self.error?;
+        let block = self.blockcache.read_mut(entry.block_no)?;
+        let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+        meta.validate(entry)?;
+        meta.user_data = user_data;
+        meta.set_crc32();
+        self.blockcache.write(entry.block_no)
+    }
+
+    /// Get the value of the extended attribute @name of the entry.
+    pub fn get_xattr(&mut self, entry: EntryId, name: &str) -> Result<Vec<u8>, FsError> {
+        self.error?;
+        let (_, xattrs) = self.read_xattrs(entry)?;
+        xattrs
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+            .ok_or(FsError::NotFound)
+    }
+
+    /// The names of the extended attributes of the entry.
+    pub fn list_xattrs(&mut self, entry: EntryId) -> Result<Vec<String>, FsError> {
+        self.error?;
+        let (_, xattrs) = self.read_xattrs(entry)?;
+        Ok(xattrs.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Add or replace the extended attribute @name of the entry.
+    pub fn set_xattr(&mut self, entry: EntryId, name: &str, value: &[u8]) -> Result<(), FsError> {
+        self.error?;
+        if name.is_empty() || name.len() as u64 > MAX_XATTR_NAME_LEN {
+            return Err(FsError::InvalidArgument);
+        }
+        let (block_no, mut xattrs) = self.read_xattrs(entry)?;
+        match xattrs.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_vec(),
+            None => xattrs.push((name.to_owned(), value.to_vec())),
+        }
+
+        // Check that the attributes fit before allocating the block.
+        let mut block = Box::new(Block::new_zeroed());
+        XattrHeader::encode(&mut block, entry, &xattrs)?;
+
+        if block_no != 0 {
+            // A single block write: no TXN is necessary.
+            let xattr_block = self.blockcache.read_mut(block_no)?;
+            *xattr_block.block_mut() = *block;
+            return self.blockcache.write(block_no);
+        }
+
+        self.start_txn(TXN_TYPE_ADD_BYTES, entry)?;
+        let result = self.add_xattr_block(entry, &block);
+        self.txn_result(result)?;
+        self.commit_txn()
+    }
+
+    /// Remove the extended attribute @name of the entry.
+    pub fn remove_xattr(&mut self, entry: EntryId, name: &str) -> Result<(), FsError> {
+        self.error?;
+        let (block_no, mut xattrs) = self.read_xattrs(entry)?;
+        let idx = xattrs
+            .iter()
+            .position(|(n, _)| n == name)
+            .ok_or(FsError::NotFound)?;
+        xattrs.remove(idx);
+        if xattrs.is_empty() {
+            return self.free_xattrs(entry);
+        }
+
+        let xattr_block = self.blockcache.read_mut(block_no)?;
+        XattrHeader::encode(xattr_block.block_mut(), entry, &xattrs)?;
+        self.blockcache.write(block_no)
+    }
+
+    /// Make all changes so far durable. Changes reach the device in the
+    /// order they are made, but may stay in its cache until flushed.
+    pub fn flush(&mut self) -> Result<(), FsError> {
+        self.error?;
+        self.blockcache.flush()
+    }
+
+    /// Take snapshot @name of the volume: see SnapshotDevice. The snapshot
+    /// gets an area of @blocks blocks, taken from the end of the volume,
+    /// to hold the old contents of the blocks that change later; if the
+    /// area fills up, the snapshot is invalidated.
+    pub fn create_snapshot(&mut self, name: &str, blocks: u64) -> Result<(), FsError> {
+        self.error?;
+        if name.is_empty() || name.len() as u64 > MAX_SNAPSHOT_NAME_LEN || blocks < 3 {
+            return Err(FsError::InvalidArgument);
+        }
+        let snapshots = self.blockcache.snapshots();
+        if snapshots.find(name).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        if snapshots.count() as u64 == MAX_SNAPSHOTS {
+            return Err(FsError::TooLarge);
+        }
+        let sbh = self.superblock.header();
+        if sbh.num_blocks - sbh.empty_area_start < blocks {
+            return Err(FsError::FsFull);
+        }
+
+        let area_start = sbh.num_blocks - blocks;
+        let watermark = sbh.empty_area_start;
+        self.blockcache
+            .create_snapshot(name, area_start, blocks, watermark)?;
+
+        let sbh = self.superblock.header_mut();
+        sbh.num_blocks = area_start;
+        sbh.free_blocks -= blocks;
+        self.num_blocks = area_start;
+        let table = self.superblock.snapshot_table_mut();
+        if table.total_blocks == 0 {
+            table.total_blocks = area_start + blocks;
+        }
+        *table.areas.iter_mut().find(|a| **a == 0).unwrap() = area_start;
+        table.set_crc32();
+
+        // This preserves the first block, and with it the whole snapshot.
+        self.save_superblock()?;
+        self.blockcache.flush()
+    }
+
+    /// Delete snapshot @name. Fails with FsError::Busy while a SnapshotDevice
+    /// of the snapshot exists.
+    pub fn delete_snapshot(&mut self, name: &str) -> Result<(), FsError> {
+        self.error?;
+        let snapshot = self.blockcache.remove_snapshot(name)?;
+        let area_start = snapshot.borrow().area_start();
+
+        let table = self.superblock.snapshot_table_mut();
+        *table.areas.iter_mut().find(|a| **a == area_start).unwrap() = 0;
+
+        // The volume grows back up to the area of the newest snapshot left.
+        let new_num_blocks = table
+            .areas
+            .iter()
+            .copied()
+            .filter(|a| *a != 0)
+            .min()
+            .unwrap_or(table.total_blocks);
+        if self.blockcache.snapshots().count() == 0 {
+            table.total_blocks = 0;
+        }
+        table.set_crc32();
+
+        let sbh = self.superblock.header_mut();
+        debug_assert!(new_num_blocks >= sbh.num_blocks);
+        sbh.free_blocks += new_num_blocks - sbh.num_blocks;
+        sbh.num_blocks = new_num_blocks;
+        self.num_blocks = new_num_blocks;
+        self.save_superblock()
+    }
+
+    /// Set @blocks blocks at the end of the volume aside for a journal: with
+    /// one, a volume that was not unmounted cleanly never needs repair(), as
+    /// the last transaction, if it was committed, is replayed by open_fs().
+    /// Volumes are formatted without a journal; it must be added before any
+    /// snapshots are taken.
+    pub fn add_journal(&mut self, blocks: u64) -> Result<(), FsError> {
+        self.error?;
+        if !(MIN_JOURNAL_BLOCKS..=MAX_JOURNAL_BLOCKS).contains(&blocks) {
+            return Err(FsError::InvalidArgument);
+        }
+        if self.blockcache.has_journal() {
+            return Err(FsError::AlreadyExists);
+        }
+        if self.blockcache.snapshots().count() > 0 {
+            return Err(FsError::Busy);
+        }
+        let sbh = self.superblock.header();
+        if sbh.num_blocks - sbh.empty_area_start < blocks {
+            return Err(FsError::FsFull);
+        }
+
+        let start = sbh.num_blocks - blocks;
+        self.blockcache.add_journal(start, blocks)?;
+
+        let sbh = self.superblock.header_mut();
+        sbh.num_blocks = start;
+        sbh.free_blocks -= blocks;
+        self.num_blocks = start;
+        let jh = self.superblock.journal_header_mut();
+        jh.start = start;
+        jh.blocks = blocks;
+        jh.set_crc32();
+
+        self.save_superblock()?;
+        self.blockcache.flush()
+    }
+
+    pub fn has_journal(&self) -> bool {
+        self.blockcache.has_journal()
+    }
+
+    /// The snapshots of the volume.
+    pub fn snapshots(&self) -> Vec<SnapshotInfo> {
+        self.blockcache.snapshots().list()
+    }
+
+    /// A read-only block device presenting snapshot @name of the volume:
+    /// @block_device is a second handle to the device of the volume.
+    pub fn snapshot_device(
+        &self,
+        name: &str,
+        block_device: Box<dyn SyncBlockDevice>,
+    ) -> Result<SnapshotDevice, FsError> {
+        let snapshot = self
+            .blockcache
+            .snapshots()
+            .find(name)
+            .ok_or(FsError::NotFound)?;
+        Ok(SnapshotDevice::new(block_device, snapshot))
+    }
+
+    /// Truncate or extend the file. Extending leaves a hole: the new bytes
+    /// read as zeros, and get data blocks only when written to.
     pub fn set_file_size(&mut self, file_id: EntryId, new_size: u64) -> Result<(), FsError> {
         self.error?;
 
@@ -461,7 +835,7 @@ impl SyncFileSystem {
             meta.validate_file(file_id)?;
             let prev_size = meta.size;
             if new_size > prev_size {
-                return Err(FsError::InvalidArgument);
+                return self.grow(file_id, new_size);
             }
             if new_size == prev_size {
                 return Ok(());
@@ -502,6 +876,30 @@ impl SyncFileSystem {
                 let meta_block = self.blockcache.get(file_id.block_no);
                 let data_block_no = meta_block.block().get_datablock_no_in_meta(block_idx);
 
+                if data_block_no == 0 {
+                    // A hole: nothing to free, and the blocks before it may be holes, too.
+                    let mut mid_size = mid_size;
+                    while mid_size > BLOCK_SIZE
+                        && mid_size - BLOCK_SIZE >= new_size
+                        && meta_block
+                            .block()
+                            .get_datablock_no_in_meta((mid_size >> BLOCK_SIZE.ilog2()) - 1)
+                            == 0
+                    {
+                        mid_size -= BLOCK_SIZE;
+                    }
+
+                    if mid_size <= MAX_BYTES_IN_META_BLOCK {
+                        let metadata_block = self.blockcache.get_mut(file_id.block_no);
+                        metadata_block
+                            .block_mut()
+                            .as_data_bytes_in_meta_mut()
+                            .fill(0);
+                    }
+                    self.write_file_size(file_id, mid_size)?;
+                    continue;
+                }
+
                 // TODO: we probably don't need a TXN here: will save a couple of block writes?
                 self.start_txn(TXN_TYPE_REMOVE_BYTES, file_id)?;
                 self.superblock.header_mut().txn_data_block = data_block_no;
@@ -540,13 +938,52 @@ impl SyncFileSystem {
                 let meta_block = self.blockcache.get(file_id.block_no);
                 let link_block_no = meta_block.block().get_datablock_no_in_meta(link_block_idx);
 
+                if link_block_no == 0 {
+                    // The whole link block is a hole.
+                    let mid_size = new_size
+                        .max(link_block_idx << BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2())
+                        .max(MAX_BYTES_ONLY_DATA_BLOCKS);
+                    if mid_size == MAX_BYTES_ONLY_DATA_BLOCKS {
+                        // The meta block lists data blocks again, all holes.
+                        let metadata_block = self.blockcache.get_mut(file_id.block_no);
+                        metadata_block
+                            .block_mut()
+                            .as_data_bytes_in_meta_mut()
+                            .fill(0);
+                    }
+                    self.write_file_size(file_id, mid_size)?;
+                    continue;
+                }
+
                 let link_block = self.blockcache.read(link_block_no)?;
+                let data_block_no = link_block.block().get_datablock_no_in_link(
+                    (mid_size & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2(),
+                );
+                let mut mid_size = mid_size;
+                if data_block_no == 0 {
+                    // A hole: the blocks before it may be holes, too.
+                    let link_block_start =
+                        link_block_idx << BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
+                    while mid_size > link_block_start
+                        && mid_size - BLOCK_SIZE >= new_size.max(MAX_BYTES_ONLY_DATA_BLOCKS)
+                        && link_block.block().get_datablock_no_in_link(
+                            ((mid_size - BLOCK_SIZE)
+                                & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1))
+                                >> BLOCK_SIZE.ilog2(),
+                        ) == 0
+                    {
+                        mid_size -= BLOCK_SIZE;
+                    }
+                }
                 let data_block_idx =
                     (mid_size & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
-                let data_block_no = link_block.block().get_datablock_no_in_link(data_block_idx);
 
                 let need_to_free_link_block =
                     (data_block_idx == 0) || (mid_size == MAX_BYTES_ONLY_DATA_BLOCKS);
+                if data_block_no == 0 && !need_to_free_link_block {
+                    self.write_file_size(file_id, mid_size)?;
+                    continue;
+                }
 
                 self.start_txn(TXN_TYPE_REMOVE_BYTES, file_id)?;
                 self.superblock.header_mut().txn_data_block = data_block_no;
@@ -572,7 +1009,9 @@ impl SyncFileSystem {
                     self.blockcache.write(file_id.block_no)?;
                 }
 
-                self.free_txn_block(BlockType::Data)?;
+                if data_block_no != 0 {
+                    self.free_txn_block(BlockType::Data)?;
+                }
                 if need_to_free_link_block {
                     self.free_txn_block(BlockType::Links)?;
                 }
@@ -588,6 +1027,23 @@ impl SyncFileSystem {
                 .block()
                 .get_datablock_no_in_meta(list_of_links_block_idx);
 
+            if list_of_links_block_no == 0 {
+                // The whole list-of-links block is a hole.
+                let mid_size = new_size
+                    .max(list_of_links_block_idx << BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST.ilog2())
+                    .max(MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS);
+                if mid_size == MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS {
+                    // The meta block lists link blocks again, all holes.
+                    let metadata_block = self.blockcache.get_mut(file_id.block_no);
+                    metadata_block
+                        .block_mut()
+                        .as_data_bytes_in_meta_mut()
+                        .fill(0);
+                }
+                self.write_file_size(file_id, mid_size)?;
+                continue;
+            }
+
             let link_block_idx = (mid_size & (BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST - 1))
                 >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
             let list_of_links_block = self.blockcache.read(list_of_links_block_no)?;
@@ -595,14 +1051,43 @@ impl SyncFileSystem {
                 .block()
                 .get_datablock_no_in_link(link_block_idx);
 
-            let link_block = self.blockcache.read(link_block_no)?;
-            let data_block_idx =
-                (mid_size & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
-            let data_block_no = link_block.block().get_datablock_no_in_link(data_block_idx);
+            let link_block_start = mid_size & !(BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1);
+            let mut mid_size = mid_size;
+            let mut data_block_no = 0;
+            if link_block_no == 0 {
+                // The whole link block is a hole.
+                mid_size = new_size
+                    .max(link_block_start)
+                    .max(MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS);
+            } else {
+                let link_block = self.blockcache.read(link_block_no)?;
+                data_block_no = link_block.block().get_datablock_no_in_link(
+                    (mid_size & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2(),
+                );
+                if data_block_no == 0 {
+                    // A hole: the blocks before it may be holes, too.
+                    while mid_size > link_block_start
+                        && mid_size - BLOCK_SIZE >= new_size.max(MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS)
+                        && link_block.block().get_datablock_no_in_link(
+                            ((mid_size - BLOCK_SIZE)
+                                & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1))
+                                >> BLOCK_SIZE.ilog2(),
+                        ) == 0
+                    {
+                        mid_size -= BLOCK_SIZE;
+                    }
+                }
+            }
 
-            let need_to_free_link_block = data_block_idx == 0;
-            let need_to_free_list_of_links_block = need_to_free_link_block
-                && ((link_block_idx == 0) || (mid_size == MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS));
+            let need_to_free_link_block = link_block_no != 0
+                && (mid_size & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) == 0;
+            let need_to_free_list_of_links_block =
+                (mid_size & (BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST - 1)) == 0
+                    || (mid_size == MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS);
+            if data_block_no == 0 && !need_to_free_link_block && !need_to_free_list_of_links_block {
+                self.write_file_size(file_id, mid_size)?;
+                continue;
+            }
 
             self.start_txn(TXN_TYPE_REMOVE_BYTES, file_id)?;
             self.superblock.header_mut().txn_data_block = data_block_no;
@@ -631,7 +1116,9 @@ impl SyncFileSystem {
                 self.blockcache.write(file_id.block_no)?;
             }
 
-            self.free_txn_block(BlockType::Data)?;
+            if data_block_no != 0 {
+                self.free_txn_block(BlockType::Data)?;
+            }
             if need_to_free_link_block {
                 self.free_txn_block(BlockType::Links)?;
             }
@@ -643,6 +1130,75 @@ impl SyncFileSystem {
         }
     }
 
+    /// Make sure the bytes in [offset, offset + len) have data blocks, so that
+    /// writing them does not fail with FsFull. The file grows to cover them.
+    pub fn allocate(&mut self, file_id: EntryId, offset: u64, len: u64) -> Result<(), FsError> {
+        let file_size = self.get_file_size(file_id)?;
+        let end = offset.checked_add(len).ok_or(FsError::InvalidArgument)?;
+        if end > file_size {
+            self.grow(file_id, end)?;
+        }
+        if file_size.max(end) <= MAX_BYTES_IN_META_BLOCK {
+            return Ok(()); // The bytes are in the meta block.
+        }
+
+        let mut pos = offset & !(BLOCK_SIZE - 1);
+        while pos < end {
+            self.blockcache.read(file_id.block_no)?;
+            if self.find_data_block(file_id, pos)? == 0 {
+                self.fill_hole(file_id, pos)?;
+            }
+            pos += BLOCK_SIZE;
+        }
+
+        Ok(())
+    }
+
+    /// Deallocate the bytes in [offset, offset + len): they then read as zeros.
+    /// The file size does not change. Data blocks in the range are freed, and
+    /// the bytes of blocks only partially in it are zeroed; link blocks stay.
+    pub fn punch_hole(&mut self, file_id: EntryId, offset: u64, len: u64) -> Result<(), FsError> {
+        let file_size = self.get_file_size(file_id)?;
+        let end = file_size.min(offset.saturating_add(len));
+        if offset >= end {
+            return Ok(());
+        }
+
+        if file_size <= MAX_BYTES_IN_META_BLOCK {
+            let metadata_block = self.blockcache.get_mut(file_id.block_no);
+            metadata_block.block_mut().as_data_bytes_in_meta_mut()
+                [(offset as usize)..(end as usize)]
+                .fill(0);
+            return self.blockcache.write(file_id.block_no);
+        }
+
+        let mut pos = offset;
+        while pos < end {
+            let block_start = pos & !(BLOCK_SIZE - 1);
+            let block_end = (block_start + BLOCK_SIZE).min(file_size);
+            let hole_end = end.min(block_end);
+
+            self.blockcache.read(file_id.block_no)?;
+            let data_block_no = self.find_data_block(file_id, pos)?;
+            if data_block_no == 0 {
+                // Already a hole.
+            } else if pos == block_start && hole_end == block_end {
+                self.free_data_block(file_id, pos, data_block_no)?;
+            } else {
+                let data_block = self.blockcache.read_mut(data_block_no)?;
+                data_block.block_mut().as_bytes_mut()
+                    [((pos - block_start) as usize)..((hole_end - block_start) as usize)]
+                    .fill(0);
+                self.blockcache.write(data_block_no)?;
+            }
+
+            pos = hole_end;
+        }
+
+        Ok(())
+    }
+
+    /// Hard-linked files have no single parent: InvalidArgument.
     pub fn get_parent(&mut self, entry_id: EntryId) -> Result<Option<EntryId>, FsError> {
         if entry_id == ROOT_DIR_ID {
             return Ok(None);
@@ -651,10 +1207,14 @@ impl SyncFileSystem {
         let block = self.blockcache.read(entry_id.block_no)?;
         let meta = unsafe { block.block().get::<EntryMetadata>() };
         meta.validate(entry_id)?;
+        if (meta.flags & ENTRY_FLAG_HARDLINKED) != 0 {
+            return Err(FsError::InvalidArgument);
+        }
 
         Ok(Some(meta.parent_id))
     }
 
+    /// Hard-linked files have no single name: InvalidArgument.
     pub fn get_name(&mut self, entry_id: EntryId) -> Result<String, FsError> {
         if entry_id == ROOT_DIR_ID {
             return Ok("/".to_owned());
@@ -664,6 +1224,9 @@ impl SyncFileSystem {
         let meta = unsafe { block.block().get::<EntryMetadata>() };
 
         meta.validate(entry_id)?;
+        if (meta.flags & ENTRY_FLAG_HARDLINKED) != 0 {
+            return Err(FsError::InvalidArgument);
+        }
 
         let parent_id = meta.parent_id;
         let (block_no, entry_pos) = self.find_entry_by_id(parent_id, entry_id)?;
@@ -672,65 +1235,573 @@ impl SyncFileSystem {
         Ok(dir_entry.to_owned()?.name)
     }
 
-    pub fn move_rename(
-        &mut self,
-        entry_id: EntryId,
-        new_parent: EntryId,
-        new_name: &str,
-    ) -> Result<(), FsError> {
-        self.error?;
-        #[cfg(debug_assertions)]
-        {
-            let block = self.blockcache.read(entry_id.block_no)?;
-            let meta = unsafe { block.block().get::<EntryMetadata>() };
-            meta.validate(entry_id).unwrap();
-            let block = self.blockcache.read(new_parent.block_no)?;
-            let meta = unsafe { block.block().get::<EntryMetadata>() };
-            meta.validate(new_parent).unwrap();
+    /// Walk the tree from the root and the freelist, verifying the metadata
+    /// of entries, link counts, parent pointers, and that every block is
+    /// either used once or free. Returns the inconsistencies found; I/O
+    /// errors stop the walk. Works on volumes that need repair.
+    pub fn check(&mut self) -> Result<Vec<CheckIssue>, FsError> {
+        if !self.unclean {
+            self.error?;
         }
-        self.validate_new_entry(new_parent, new_name)?;
-        if entry_id.block_no == ROOT_DIR_ID.block_no {
-            return Err(FsError::InvalidArgument);
-        }
-        let block = self.blockcache.read(entry_id.block_no)?;
-        let meta = unsafe { block.block().get::<EntryMetadata>() };
-        meta.validate(entry_id)?;
-        let old_parent = meta.parent_id;
+        Ok(self
+            .scan()?
+            .issues
+            .into_iter()
+            .map(|(issue, _)| issue)
+            .collect())
+    }
 
-        if old_parent == new_parent {
-            let (block_no, entry_pos) = self.find_entry_by_id(old_parent, entry_id)?;
-            let block = self.blockcache.get_mut(block_no);
-            block
-                .block_mut()
-                .get_dir_entry_mut(entry_pos)
-                .set_name(new_name);
-            self.blockcache.write(block_no)?;
-            return Ok(());
+    /// Fix what check() finds, where possible, and return the issues fixed:
+    /// bad entries are dropped from their directories, bad or cross-linked
+    /// blocks of files become holes, and blocks not in use are freed.
+    /// Bad blocks of directories are not fixed: check() again to see what
+    /// is left.
+    ///
+    /// The repair is a transaction: if interrupted, the volume needs repair
+    /// when opened again.
+    pub fn repair(&mut self) -> Result<Vec<CheckIssue>, FsError> {
+        if !self.unclean {
+            self.error?;
         }
-
-        self.start_txn(TXN_TYPE_MOVE, old_parent)?;
-        self.superblock.header_mut().txn_meta_block = entry_id.block_no;
-        self.save_superblock()?;
-
-        let block = self.blockcache.get_mut(entry_id.block_no);
-        let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
-        meta.parent_id = new_parent;
-        meta.set_crc32();
-        self.blockcache.write(entry_id.block_no)?;
-
-        #[cfg(debug_assertions)]
-        {
-            let block = self.blockcache.read(entry_id.block_no)?;
-            let meta = unsafe { block.block().get::<EntryMetadata>() };
-            meta.validate(entry_id).unwrap();
+        let scan = self.scan()?;
+        if scan.issues.iter().all(|(_, fix)| fix.is_none()) {
+            return Ok(Vec::new());
         }
 
-        // Move to the new parent.
-        self.add_directory_entry_inner(new_parent, entry_id, new_name)
-            .map_err(|e| {
-                let _ = self.make_error();
-                e
-            })?;
+        self.error = Ok(());
+        self.unclean = false;
+        let result = self.repair_inner(scan);
+        if result.is_err() {
+            let _ = self.make_error();
+        }
+        result
+    }
+
+    fn repair_inner(&mut self, scan: Scan) -> Result<Vec<CheckIssue>, FsError> {
+        // The blocks of an interrupted TXN, if any, are orphaned, and freed
+        // below with the others.
+        let sbh = self.superblock.header_mut();
+        sbh.txn_meta_block = 0;
+        sbh.txn_data_block = 0;
+        sbh.txn_link_block = 0;
+        sbh.txn_list_of_links_block = 0;
+        sbh.txn_blocks_owner = 0;
+        sbh.txn_type = TXN_TYPE_NONE;
+        self.start_txn(TXN_TYPE_REPAIR, ROOT_DIR_ID)?;
+
+        let mut repaired = Vec::new();
+        for (issue, fix) in scan.issues {
+            let Some(fix) = fix else {
+                continue;
+            };
+            match fix {
+                Fix::RemoveEntry { parent, id } => {
+                    self.remove_directory_entry_inner(parent, id)?;
+                    if self.superblock.header().txn_link_block != 0 {
+                        self.free_txn_block(BlockType::Links)?;
+                    }
+                    if self.superblock.header().txn_data_block != 0 {
+                        self.free_txn_block(BlockType::Data)?;
+                    }
+                }
+                Fix::SetLinks { id, references } => self.update_meta(id, |meta| {
+                    meta.links = (references - 1) as u16;
+                    if references > 1 {
+                        meta.flags |= ENTRY_FLAG_HARDLINKED;
+                    }
+                })?,
+                Fix::SetParent { id, parent_id } => {
+                    self.update_meta(id, |meta| meta.parent_id = parent_id)?
+                }
+                Fix::ClearXattrs { id } => self.update_meta(id, |meta| meta.xattrs = 0)?,
+                Fix::ClearLink {
+                    block_no,
+                    idx,
+                    in_meta,
+                } => {
+                    let block = self.blockcache.read_mut(block_no)?;
+                    if in_meta {
+                        block.block_mut().set_datablock_no_in_meta(idx, 0);
+                    } else {
+                        block.block_mut().set_datablock_no_in_link(idx, 0);
+                    }
+                    self.blockcache.write(block_no)?;
+                }
+                Fix::Generation { generation } => {
+                    let sbh = self.superblock.header_mut();
+                    sbh.generation = sbh.generation.max(generation);
+                    self.save_superblock()?;
+                }
+                Fix::FreeSpace => {} // See below.
+            }
+            log::warn!("srfs-core: repaired {:?}", issue);
+            repaired.push(issue);
+        }
+
+        // The fixes above change which blocks are in use, so look again.
+        let scan = self.scan()?;
+        if scan
+            .issues
+            .iter()
+            .any(|(_, fix)| matches!(fix, Some(Fix::FreeSpace)))
+        {
+            self.rebuild_freelist(&scan.used)?;
+        }
+
+        self.commit_txn()?;
+        Ok(repaired)
+    }
+
+    fn update_meta<F>(&mut self, id: EntryId, f: F) -> Result<(), FsError>
+    where
+        F: FnOnce(&mut EntryMetadata),
+    {
+        let block = self.blockcache.read_mut(id.block_no)?;
+        let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+        f(meta);
+        meta.set_crc32();
+        self.blockcache.write(id.block_no)
+    }
+
+    // Free all blocks not in use: those at the end go back to the empty area,
+    // the rest onto the freelist, lowest first. Must be inside a TXN.
+    fn rebuild_freelist(&mut self, used: &BlockMap) -> Result<(), FsError> {
+        let mut empty_area_start = self
+            .superblock
+            .header()
+            .empty_area_start
+            .min(self.num_blocks);
+        while empty_area_start > 2 && !used.contains(empty_area_start - 1) {
+            empty_area_start -= 1;
+        }
+
+        let mut freelist_head = 0;
+        let mut free_blocks = self.num_blocks - empty_area_start;
+        for block_no in (2..empty_area_start).rev() {
+            if used.contains(block_no) {
+                continue;
+            }
+            let block = self.blockcache.get_block_uninit(block_no);
+            unsafe { *block.block_mut().get_mut::<u64>() = freelist_head };
+            self.blockcache.write(block_no)?;
+            freelist_head = block_no;
+            free_blocks += 1;
+        }
+
+        let sbh = self.superblock.header_mut();
+        sbh.empty_area_start = empty_area_start;
+        sbh.freelist_head = freelist_head;
+        sbh.free_blocks = free_blocks;
+        self.save_superblock()
+    }
+
+    // Walk the tree, then the freelist. Does not look at self.error.
+    fn scan(&mut self) -> Result<Scan, FsError> {
+        let sbh = *self.superblock.header();
+        let mut scan = Scan {
+            issues: Vec::new(),
+            used: BlockMap::new(self.num_blocks),
+            end: sbh.empty_area_start.min(self.num_blocks),
+        };
+        scan.used.insert(0);
+        scan.used.insert(ROOT_DIR_ID.block_no);
+        if self.unclean {
+            let issue = CheckIssue::Unclean {
+                txn_type: sbh.txn_type,
+            };
+            scan.issues.push((issue, Some(Fix::FreeSpace)));
+        }
+
+        // Nothing can be done without the root.
+        let root_block = *self.blockcache.read(ROOT_DIR_ID.block_no)?.block();
+        let root_meta = unsafe { root_block.get::<EntryMetadata>() };
+        root_meta.validate_dir(ROOT_DIR_ID)?;
+        self.scan_xattrs(&mut scan, ROOT_DIR_ID, root_meta.xattrs)?;
+
+        let mut files: BTreeMap<u64, (EntryId, u32, u32)> = BTreeMap::new(); // id, links, refs.
+        let mut dirs: BTreeSet<u64> = BTreeSet::new();
+        dirs.insert(ROOT_DIR_ID.block_no);
+
+        let mut stack = vec![ROOT_DIR_ID];
+        while let Some(dir) = stack.pop() {
+            let (entries, dir_ok) = self.scan_dir(&mut scan, dir)?;
+            for (id, name_ok) in entries {
+                // Entries are removed only from directories with good blocks.
+                let remove = dir_ok.then_some(Fix::RemoveEntry { parent: dir, id });
+                if let Some(file) = files.get_mut(&id.block_no) {
+                    if file.0 == id && name_ok {
+                        file.2 += 1; // Another hard link.
+                        continue;
+                    }
+                }
+                if !name_ok || id.block_no < 2 || id.block_no >= scan.end {
+                    scan.issues
+                        .push((CheckIssue::BadEntry { id, parent: dir }, remove));
+                    continue;
+                }
+
+                let block = *self.blockcache.read(id.block_no)?.block();
+                let meta = unsafe { block.get::<EntryMetadata>() };
+                if meta.validate(id).is_err()
+                    || (id.kind() == EntryKind::File && meta.size > MAX_FILE_SIZE)
+                {
+                    scan.issues
+                        .push((CheckIssue::BadEntry { id, parent: dir }, remove));
+                    continue;
+                }
+                if id.kind() == EntryKind::Directory && dirs.contains(&id.block_no) {
+                    // Don't walk it twice (or forever).
+                    scan.issues
+                        .push((CheckIssue::DirectoryLinked { id, parent: dir }, remove));
+                    continue;
+                }
+                if !scan.use_block(id, id.block_no, None) {
+                    continue;
+                }
+
+                if id.generation > sbh.generation {
+                    let issue = CheckIssue::Generation {
+                        id,
+                        generation: id.generation,
+                    };
+                    let fix = Fix::Generation {
+                        generation: id.generation,
+                    };
+                    scan.issues.push((issue, Some(fix)));
+                }
+                let hardlinked = (meta.flags & ENTRY_FLAG_HARDLINKED) != 0;
+                if !hardlinked && meta.parent_id != dir {
+                    let issue = CheckIssue::WrongParent {
+                        id,
+                        parent_id: meta.parent_id,
+                        actual: dir,
+                    };
+                    let fix = Fix::SetParent { id, parent_id: dir };
+                    scan.issues.push((issue, Some(fix)));
+                }
+                self.scan_xattrs(&mut scan, id, meta.xattrs)?;
+
+                if id.kind() == EntryKind::Directory {
+                    dirs.insert(id.block_no);
+                    stack.push(id);
+                } else {
+                    files.insert(id.block_no, (id, meta.links as u32 + 1, 1));
+                    self.scan_file(&mut scan, id, &block)?;
+                }
+            }
+        }
+
+        for (id, links, references) in files.into_values() {
+            if links != references {
+                let issue = CheckIssue::LinkCount {
+                    id,
+                    links,
+                    references,
+                };
+                let fix = (references <= (u16::MAX as u32) + 1)
+                    .then_some(Fix::SetLinks { id, references });
+                scan.issues.push((issue, fix));
+            }
+        }
+
+        self.scan_freelist(&mut scan)?;
+        Ok(scan)
+    }
+
+    // The entries of a directory, with whether their names are valid, and
+    // whether all the blocks listing them are good.
+    fn scan_dir(
+        &mut self,
+        scan: &mut Scan,
+        dir: EntryId,
+    ) -> Result<(Vec<(EntryId, bool)>, bool), FsError> {
+        let meta_block = *self.blockcache.read(dir.block_no)?.block();
+        let num_entries = unsafe { meta_block.get::<EntryMetadata>() }.size;
+        let mut entries = Vec::new();
+        let add_entries = |entries: &mut Vec<(EntryId, bool)>, block: &Block, range| {
+            for pos in range {
+                let entry = block.get_dir_entry(pos);
+                entries.push((entry.id, entry.to_owned().is_ok()));
+            }
+        };
+
+        if num_entries <= MAX_ENTRIES_IN_META_BLOCK {
+            add_entries(&mut entries, &meta_block, 1..(num_entries as usize + 1));
+            return Ok((entries, true));
+        }
+
+        // None => a block listing data blocks is bad.
+        let mut data_blocks: Vec<Option<u64>> = Vec::new();
+        let num_data_blocks = num_entries.div_ceil(MAX_ENTRIES_IN_DATA_BLOCK);
+        if num_entries <= MAX_ENTRIES_ONLY_DATA_BLOCKS {
+            for idx in 0..num_data_blocks {
+                data_blocks.push(Some(meta_block.get_datablock_no_in_meta(idx)));
+            }
+        } else {
+            let num_link_blocks =
+                num_entries.div_ceil(MAX_ENTRIES_COVERED_BY_FIRST_LEVEL_BLOCKLIST);
+            for link_idx in 0..num_link_blocks {
+                let link_block_no = meta_block.get_datablock_no_in_meta(link_idx);
+                let num_links = (num_data_blocks - link_idx * 512).min(512);
+                if !scan.use_block(dir, link_block_no, None) {
+                    data_blocks.extend((0..num_links).map(|_| None));
+                    continue;
+                }
+                let link_block = *self.blockcache.read(link_block_no)?.block();
+                for idx in 0..num_links {
+                    data_blocks.push(Some(link_block.get_datablock_no_in_link(idx)));
+                }
+            }
+        }
+
+        let mut dir_ok = true;
+        for (idx, data_block_no) in data_blocks.into_iter().enumerate() {
+            let Some(data_block_no) = data_block_no else {
+                dir_ok = false;
+                continue;
+            };
+            if !scan.use_block(dir, data_block_no, None) {
+                dir_ok = false;
+                continue;
+            }
+            let first = idx as u64 * MAX_ENTRIES_IN_DATA_BLOCK;
+            let num = (num_entries - first).min(MAX_ENTRIES_IN_DATA_BLOCK);
+            let data_block = *self.blockcache.read(data_block_no)?.block();
+            add_entries(&mut entries, &data_block, 0..(num as usize));
+        }
+
+        Ok((entries, dir_ok))
+    }
+
+    // Zeroes in the lists of blocks of a file are holes; bad blocks become
+    // holes when repaired.
+    fn scan_file(
+        &mut self,
+        scan: &mut Scan,
+        id: EntryId,
+        meta_block: &Block,
+    ) -> Result<(), FsError> {
+        let file_size = unsafe { meta_block.get::<EntryMetadata>() }.size;
+        if file_size <= MAX_BYTES_IN_META_BLOCK {
+            return Ok(());
+        }
+
+        let num_data_blocks = file_size.div_ceil(BLOCK_SIZE);
+        let use_link = |scan: &mut Scan, block_no: u64, container: u64, idx, in_meta| {
+            let fix = Fix::ClearLink {
+                block_no: container,
+                idx,
+                in_meta,
+            };
+            block_no != 0 && scan.use_block(id, block_no, Some(fix))
+        };
+
+        if file_size <= MAX_BYTES_ONLY_DATA_BLOCKS {
+            for idx in 0..num_data_blocks {
+                let block_no = meta_block.get_datablock_no_in_meta(idx);
+                use_link(scan, block_no, id.block_no, idx, true);
+            }
+            return Ok(());
+        }
+
+        // (block_no, the block listing it, idx there, whether that's the meta block).
+        let mut link_blocks = Vec::new();
+        let num_link_blocks = num_data_blocks.div_ceil(512);
+        if file_size <= MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS {
+            for idx in 0..num_link_blocks {
+                let block_no = meta_block.get_datablock_no_in_meta(idx);
+                link_blocks.push((block_no, id.block_no, idx, true));
+            }
+        } else {
+            for list_idx in 0..num_link_blocks.div_ceil(512) {
+                let list_block_no = meta_block.get_datablock_no_in_meta(list_idx);
+                let num_links = (num_link_blocks - list_idx * 512).min(512);
+                if !use_link(scan, list_block_no, id.block_no, list_idx, true) {
+                    link_blocks.extend((0..num_links).map(|_| (0, 0, 0, false)));
+                    continue;
+                }
+                let list_block = *self.blockcache.read(list_block_no)?.block();
+                for idx in 0..num_links {
+                    let block_no = list_block.get_datablock_no_in_link(idx);
+                    link_blocks.push((block_no, list_block_no, idx, false));
+                }
+            }
+        }
+
+        for (link_idx, (link_block_no, container, idx, in_meta)) in
+            link_blocks.into_iter().enumerate()
+        {
+            if !use_link(scan, link_block_no, container, idx, in_meta) {
+                continue;
+            }
+            let link_block = *self.blockcache.read(link_block_no)?.block();
+            let num_links = (num_data_blocks - link_idx as u64 * 512).min(512);
+            for idx in 0..num_links {
+                let block_no = link_block.get_datablock_no_in_link(idx);
+                use_link(scan, block_no, link_block_no, idx, false);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn scan_xattrs(&mut self, scan: &mut Scan, id: EntryId, block_no: u64) -> Result<(), FsError> {
+        if block_no == 0 || !scan.use_block(id, block_no, Some(Fix::ClearXattrs { id })) {
+            return Ok(());
+        }
+        let block = self.blockcache.read(block_no)?;
+        if XattrHeader::decode(block.block(), id).is_err() {
+            let issue = CheckIssue::BadXattrs { id };
+            scan.issues.push((issue, Some(Fix::ClearXattrs { id })));
+        }
+        Ok(())
+    }
+
+    // Blocks must be either in use (see scan()), or free: on the freelist,
+    // or in the empty area.
+    fn scan_freelist(&mut self, scan: &mut Scan) -> Result<(), FsError> {
+        let sbh = *self.superblock.header();
+        let mut free = BlockMap::new(self.num_blocks);
+        let mut free_blocks = self.num_blocks - scan.end;
+        let mut freelist_ok = true;
+
+        let mut block_no = sbh.freelist_head;
+        while block_no != 0 {
+            let issue = if block_no < 2 || block_no >= scan.end || !free.insert(block_no) {
+                CheckIssue::BadFreeList { block_no }
+            } else if scan.used.contains(block_no) {
+                // Its link to the next free block is gone.
+                CheckIssue::FreeBlockInUse { block_no }
+            } else {
+                free_blocks += 1;
+                block_no = unsafe { *self.blockcache.read(block_no)?.block().get::<u64>() };
+                continue;
+            };
+            scan.issues.push((issue, Some(Fix::FreeSpace)));
+            freelist_ok = false;
+            break;
+        }
+
+        let orphans = (2..scan.end)
+            .filter(|block_no| !scan.used.contains(*block_no) && !free.contains(*block_no))
+            .count() as u64;
+        if orphans > 0 {
+            let issue = CheckIssue::OrphanedBlocks { count: orphans };
+            scan.issues.push((issue, Some(Fix::FreeSpace)));
+        } else if freelist_ok && free_blocks != sbh.free_blocks {
+            let issue = CheckIssue::FreeCount {
+                free_blocks: sbh.free_blocks,
+                actual: free_blocks,
+            };
+            scan.issues.push((issue, Some(Fix::FreeSpace)));
+        }
+
+        Ok(())
+    }
+
+    /// Move/rename an entry that is not hard-linked, using its parent_id.
+    pub fn move_rename(
+        &mut self,
+        entry_id: EntryId,
+        new_parent: EntryId,
+        new_name: &str,
+    ) -> Result<(), FsError> {
+        self.error?;
+        let block = self.blockcache.read(entry_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(entry_id)?;
+        if (meta.flags & ENTRY_FLAG_HARDLINKED) != 0 {
+            return Err(FsError::InvalidArgument);
+        }
+        let old_parent = meta.parent_id;
+        self.move_rename_from(old_parent, entry_id, new_parent, new_name)
+    }
+
+    // A directory can't be moved into itself or its subdirectories:
+    // that would detach them from the tree.
+    fn validate_move_target(
+        &mut self,
+        entry_id: EntryId,
+        new_parent: EntryId,
+    ) -> Result<(), FsError> {
+        if entry_id.kind() != EntryKind::Directory {
+            return Ok(());
+        }
+        let mut ancestor = new_parent;
+        loop {
+            if ancestor == entry_id {
+                return Err(FsError::InvalidArgument);
+            }
+            match self.get_parent(ancestor)? {
+                Some(parent) => ancestor = parent,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Move/rename the link to entry_id in old_parent.
+    pub fn move_rename_from(
+        &mut self,
+        old_parent: EntryId,
+        entry_id: EntryId,
+        new_parent: EntryId,
+        new_name: &str,
+    ) -> Result<(), FsError> {
+        self.error?;
+        #[cfg(debug_assertions)]
+        {
+            let block = self.blockcache.read(entry_id.block_no)?;
+            let meta = unsafe { block.block().get::<EntryMetadata>() };
+            meta.validate(entry_id).unwrap();
+            let block = self.blockcache.read(new_parent.block_no)?;
+            let meta = unsafe { block.block().get::<EntryMetadata>() };
+            meta.validate(new_parent).unwrap();
+        }
+        if entry_id.block_no == ROOT_DIR_ID.block_no {
+            return Err(FsError::InvalidArgument);
+        }
+        self.validate_move_target(entry_id, new_parent)?;
+        // Find the entry first: the finds below may flush the cache, and
+        // new_parent must stay cached for add_directory_entry_inner().
+        let (block_no, entry_pos) = self.find_entry_by_id(old_parent, entry_id)?;
+        self.validate_new_entry(new_parent, new_name)?;
+        let block = self.blockcache.read(entry_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(entry_id)?;
+
+        if old_parent == new_parent {
+            let block = self.blockcache.read_mut(block_no)?;
+            block
+                .block_mut()
+                .get_dir_entry_mut(entry_pos)
+                .set_name(new_name);
+            self.blockcache.write(block_no)?;
+            return Ok(());
+        }
+
+        self.start_txn(TXN_TYPE_MOVE, old_parent)?;
+        self.superblock.header_mut().txn_meta_block = entry_id.block_no;
+        self.save_superblock()?;
+
+        // Need to read instead of get because the find above may flush the cache.
+        let block = self.blockcache.read_mut(entry_id.block_no)?;
+        let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+        meta.parent_id = new_parent;
+        meta.set_crc32();
+        self.blockcache.write(entry_id.block_no)?;
+
+        #[cfg(debug_assertions)]
+        {
+            let block = self.blockcache.read(entry_id.block_no)?;
+            let meta = unsafe { block.block().get::<EntryMetadata>() };
+            meta.validate(entry_id).unwrap();
+        }
+
+        // Move to the new parent.
+        self.add_directory_entry_inner(new_parent, entry_id, new_name)
+            .inspect_err(|_| {
+                let _ = self.make_error();
+            })?;
 
         #[cfg(debug_assertions)]
         {
@@ -759,9 +1830,8 @@ impl SyncFileSystem {
         }
 
         self.remove_directory_entry_inner(old_parent, entry_id)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })?;
         if self.superblock.header().txn_link_block != 0 {
             self.free_txn_block(BlockType::Links)?;
@@ -794,7 +1864,175 @@ impl SyncFileSystem {
         Ok(())
     }
 
+    /// Move/rename the link to entry_id in old_parent over new_name in
+    /// new_parent, which may exist: the entry it points at then loses a link,
+    /// and is removed with its last link. new_name points at either the old
+    /// or the new entry at all times, including after a crash. Only a file
+    /// can replace a file, and only an empty directory can be replaced.
+    pub fn move_replace(
+        &mut self,
+        old_parent: EntryId,
+        entry_id: EntryId,
+        new_parent: EntryId,
+        new_name: &str,
+    ) -> Result<(), FsError> {
+        self.error?;
+        if entry_id.block_no == ROOT_DIR_ID.block_no {
+            return Err(FsError::InvalidArgument);
+        }
+        self.validate_move_target(entry_id, new_parent)?;
+        let (target_block_no, target_pos) = match self.find_entry_by_name(new_parent, new_name) {
+            Ok(found) => found,
+            Err(FsError::NotFound) => {
+                return self.move_rename_from(old_parent, entry_id, new_parent, new_name)
+            }
+            Err(err) => return Err(err),
+        };
+        let block = self.blockcache.read(target_block_no)?;
+        let target_id = block.block().get_dir_entry(target_pos).id;
+        if target_id == entry_id {
+            return Ok(()); // Both names are links to the same file.
+        }
+        if target_id.kind() != entry_id.kind() {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let block = self.blockcache.read(target_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(target_id)?;
+        let (target_links, target_size, target_xattrs) = (meta.links, meta.size, meta.xattrs);
+        if target_id.kind() == EntryKind::Directory && target_size > 0 {
+            return Err(FsError::TooLarge); // Same as in remove_link().
+        }
+
+        let block = self.blockcache.read(entry_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(entry_id)?;
+        let hardlinked = (meta.flags & ENTRY_FLAG_HARDLINKED) != 0;
+        // Validate before starting the transaction: failures inside it
+        // poison the FS.
+        let (entry_block_no, entry_pos) = self.find_entry_by_id(old_parent, entry_id)?;
+
+        // Entries without data or xattr blocks (directories, small files, symlinks)
+        // are freed in the transaction; others are emptied after it.
+        let free_target =
+            target_links == 0 && target_size <= MAX_BYTES_IN_META_BLOCK && target_xattrs == 0;
+
+        self.start_txn(TXN_TYPE_REPLACE, new_parent)?;
+        if free_target {
+            self.superblock.header_mut().txn_meta_block = target_id.block_no;
+        }
+        self.save_superblock()?;
+
+        if !hardlinked && old_parent != new_parent {
+            let block = self.blockcache.read_mut(entry_id.block_no)?;
+            let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+            meta.parent_id = new_parent;
+            meta.set_crc32();
+            self.blockcache.write(entry_id.block_no)?;
+        }
+
+        // The replacement itself: a single block write. The name is kept.
+        let block = self.blockcache.read_mut(target_block_no)?;
+        block.block_mut().get_dir_entry_mut(target_pos).id = entry_id;
+        self.blockcache.write(target_block_no)?;
+
+        // Positions in old_parent are still valid: the write above changed
+        // no layout. Note that in the same directory, the entry is now found
+        // by its id twice.
+        self.remove_directory_entry_at(old_parent, entry_block_no, entry_pos)
+            .inspect_err(|_| {
+                let _ = self.make_error();
+            })?;
+        if self.superblock.header().txn_link_block != 0 {
+            self.free_txn_block(BlockType::Links)?;
+        }
+        if self.superblock.header().txn_data_block != 0 {
+            self.free_txn_block(BlockType::Data)?;
+        }
+
+        if target_links > 0 {
+            let block = self.blockcache.read_mut(target_id.block_no)?;
+            let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+            meta.links -= 1;
+            meta.set_crc32();
+            self.blockcache.write(target_id.block_no)?;
+        } else if free_target {
+            self.free_txn_block(BlockType::Metadata)?;
+        }
+        self.commit_txn()?;
+
+        if target_links > 0 || free_target {
+            return Ok(());
+        }
+
+        // The replaced entry is no longer reachable: free its blocks, then
+        // the entry itself. A crash before that leaks its blocks.
+        if target_id.kind() == EntryKind::File {
+            self.set_file_size(target_id, 0)?;
+        }
+        self.free_xattrs(target_id)?;
+        self.start_txn(TXN_TYPE_REMOVE_NODE, new_parent)?;
+        self.superblock.header_mut().txn_meta_block = target_id.block_no;
+        self.free_txn_block(BlockType::Metadata)?;
+        self.commit_txn()
+    }
+
+    /// Remove an entry that is not hard-linked, using its parent_id.
     pub fn remove(&mut self, entry_id: EntryId) -> Result<(), FsError> {
+        self.error?;
+        let block = self.blockcache.read(entry_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(entry_id)?;
+        if (meta.flags & ENTRY_FLAG_HARDLINKED) != 0 {
+            return Err(FsError::InvalidArgument);
+        }
+        let parent_id = meta.parent_id;
+        self.remove_link(parent_id, entry_id)
+    }
+
+    /// Add a hard link to a file: a new directory entry pointing at it.
+    pub fn add_link(
+        &mut self,
+        entry_id: EntryId,
+        parent_id: EntryId,
+        name: &str,
+    ) -> Result<(), FsError> {
+        self.error?;
+        if entry_id.kind() != EntryKind::File {
+            return Err(FsError::InvalidArgument);
+        }
+        self.validate_new_entry(parent_id, name)?;
+        let block = self.blockcache.read(entry_id.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate_file(entry_id)?;
+        if meta.links == u16::MAX {
+            return Err(FsError::TooLarge);
+        }
+
+        // The link count goes up before the new entry is added (and down after
+        // an entry is removed), so that a crash in between leaves the count too
+        // high (a leak that fsck can fix), never too low.
+        self.start_txn(TXN_TYPE_ADD_LINK, parent_id)?;
+        let block = self.blockcache.get_mut(entry_id.block_no);
+        let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+        meta.links += 1;
+        meta.flags |= ENTRY_FLAG_HARDLINKED;
+        meta.set_crc32();
+        self.blockcache.write(entry_id.block_no)?;
+
+        self.add_directory_entry_inner(parent_id, entry_id, name)
+            .inspect_err(|_| {
+                let _ = self.make_error();
+            })?;
+
+        self.commit_txn()
+    }
+
+    /// Remove the directory entry pointing at entry_id from parent_id.
+    /// The entry itself is removed with its last link, and so must be
+    /// empty then (see remove()).
+    pub fn remove_link(&mut self, parent_id: EntryId, entry_id: EntryId) -> Result<(), FsError> {
         self.error?;
         if entry_id.block_no == ROOT_DIR_ID.block_no {
             return Err(FsError::InvalidArgument);
@@ -802,14 +2040,43 @@ impl SyncFileSystem {
         let block = self.blockcache.read(entry_id.block_no)?;
         let meta = unsafe { block.block().get::<EntryMetadata>() };
         meta.validate(entry_id)?;
-        if meta.size > 0 {
+        let links = meta.links;
+        if links == 0 && meta.size > 0 {
             // Cannot delete a non-empty directory or a file with data.
             // First clean the directory or truncate the file.
             #[cfg(debug_assertions)]
             log::debug!("srfs-core: remove failed: non-empty entry");
             return Err(FsError::TooLarge);
         }
-        let parent_id = meta.parent_id;
+        // Validate before starting the transaction: failures inside it
+        // poison the FS.
+        self.find_entry_by_id(parent_id, entry_id)?;
+
+        if links > 0 {
+            self.start_txn(TXN_TYPE_REMOVE_LINK, parent_id)?;
+            self.remove_directory_entry_inner(parent_id, entry_id)
+                .inspect_err(|_| {
+                    let _ = self.make_error();
+                })?;
+            if self.superblock.header().txn_link_block != 0 {
+                self.free_txn_block(BlockType::Links)?;
+            }
+            if self.superblock.header().txn_data_block != 0 {
+                self.free_txn_block(BlockType::Data)?;
+            }
+
+            // Need to read instead of get because the removal may flush the cache.
+            let block = self.blockcache.read_mut(entry_id.block_no)?;
+            let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
+            meta.links -= 1;
+            meta.set_crc32();
+            self.blockcache.write(entry_id.block_no)?;
+            return self.commit_txn();
+        }
+
+        // Extended attributes go first: a crash before the entry is removed
+        // leaves it without them.
+        self.free_xattrs(entry_id)?;
 
         // Pre-commit: mark the block we are removing as dirty.
         self.start_txn(TXN_TYPE_REMOVE_NODE, parent_id)?;
@@ -818,9 +2085,8 @@ impl SyncFileSystem {
         fbh.txn_meta_block = entry_id.block_no;
 
         self.remove_directory_entry_inner(parent_id, entry_id)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })?;
 
         // Commit.
@@ -839,13 +2105,22 @@ impl SyncFileSystem {
         &mut self,
         parent_id: EntryId,
         child: EntryId,
+    ) -> Result<(), FsError> {
+        let (entry_block_no, entry_pos) = self.find_entry_by_id(parent_id, child)?;
+        self.remove_directory_entry_at(parent_id, entry_block_no, entry_pos)
+    }
+
+    // Must be inside a transaction. Inner => no need to poison self.
+    fn remove_directory_entry_at(
+        &mut self,
+        parent_id: EntryId,
+        entry_block_no: u64,
+        entry_pos: usize,
     ) -> Result<(), FsError> {
         let sbh = self.superblock.header();
         assert_ne!(TXN_TYPE_NONE, sbh.txn_type);
 
         // Need to put the last entry into the place occupied by this entry.
-        let (entry_block_no, entry_pos) = self.find_entry_by_id(parent_id, child)?;
-
         // Need to read instead of get because the find above may flush the cache.
         let parent_block = self.blockcache.read(parent_id.block_no)?;
         let parent_meta = unsafe { parent_block.block().get::<EntryMetadata>() };
@@ -980,7 +2255,9 @@ impl SyncFileSystem {
         meta.validate_file(file_id)?;
         let prev_size = meta.size;
         if offset > prev_size {
-            return Err(FsError::InvalidArgument);
+            // Writing past the end leaves a hole.
+            self.grow(file_id, offset)?;
+            return self.write(file_id, offset, buf);
         }
         let new_end = if offset == prev_size {
             offset + (buf.len() as u64)
@@ -1006,25 +2283,118 @@ impl SyncFileSystem {
                     (new_end - offset) as usize,
                 );
             }
-            self.blockcache.write(file_id.block_no).map_err(|e| {
+            self.blockcache.write(file_id.block_no).inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })?;
             return Ok((new_end - offset) as usize);
         }
 
-        if new_size == prev_size {
-            return self.update_file(file_id, offset, buf);
+        if new_size == prev_size {
+            // Don't write past the end: the rest of buf is appended later.
+            self.update_file(file_id, offset, &buf[..((new_end - offset) as usize)])
+        } else {
+            self.append(file_id, offset, buf)
+        }
+    }
+
+    pub fn read(
+        &mut self,
+        file_id: EntryId,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, FsError> {
+        self.error?;
+        if file_id.kind() != EntryKind::File {
+            return Err(FsError::InvalidArgument);
+        }
+        let meta_block = self.blockcache.read(file_id.block_no)?;
+        let meta = unsafe { meta_block.block().get::<EntryMetadata>() };
+        meta.validate_file(file_id)?;
+        let file_size = meta.size;
+        if offset >= file_size {
+            return Ok(0);
+        }
+        let end = file_size.min(offset + (buf.len() as u64));
+        if file_size <= (MAX_BYTES_IN_META_BLOCK as u64) {
+            // We are still in the main block.
+            unsafe {
+                copy_nonoverlapping(
+                    (meta_block.block().as_data_bytes_in_meta().as_ptr() as usize
+                        + (offset as usize)) as *const u8,
+                    buf.as_mut_ptr(),
+                    (end - offset) as usize,
+                );
+            }
+            return Ok((end - offset) as usize);
+        }
+
+        let data_block_no = self.find_data_block(file_id, offset)?;
+        let block_end = align_up(offset + 1, BLOCK_SIZE);
+        let new_end = end.min(block_end);
+        if data_block_no == 0 {
+            // A hole.
+            buf[0..((new_end - offset) as usize)].fill(0);
+            return Ok((new_end - offset) as usize);
+        }
+        let data_block = self.blockcache.read(data_block_no)?;
+        unsafe {
+            copy_nonoverlapping(
+                (data_block.block().as_bytes().as_ptr() as usize
+                    + ((offset & (BLOCK_SIZE - 1)) as usize)) as *const u8,
+                buf.as_mut_ptr(),
+                (new_end - offset) as usize,
+            );
+        }
+        return Ok((new_end - offset) as usize);
+    }
+
+    /// Like read(), but data blocks bypass the block cache, so that large
+    /// sequential reads don't evict the metadata cached for other files.
+    pub fn read_direct(
+        &mut self,
+        file_id: EntryId,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, FsError> {
+        self.error?;
+        if file_id.kind() != EntryKind::File {
+            return Err(FsError::InvalidArgument);
+        }
+        let meta_block = self.blockcache.read(file_id.block_no)?;
+        let meta = unsafe { meta_block.block().get::<EntryMetadata>() };
+        meta.validate_file(file_id)?;
+        let file_size = meta.size;
+        if offset >= file_size || file_size <= MAX_BYTES_IN_META_BLOCK {
+            // The bytes, if any, are in the metadata block.
+            return self.read(file_id, offset, buf);
+        }
+
+        let data_block_no = self.find_data_block(file_id, offset)?;
+        let end = file_size
+            .min(offset + (buf.len() as u64))
+            .min(align_up(offset + 1, BLOCK_SIZE));
+        let len = (end - offset) as usize;
+        if data_block_no == 0 {
+            // A hole.
+            buf[0..len].fill(0);
         } else {
-            return self.append(file_id, offset, buf);
+            self.blockcache.read_direct(
+                data_block_no,
+                (offset & (BLOCK_SIZE - 1)) as usize,
+                &mut buf[0..len],
+            )?;
         }
+        Ok(len)
     }
 
-    pub fn read(
+    /// Like write(), but overwriting existing data blocks bypasses the block
+    /// cache. Writes that allocate blocks (appends, filling holes) and writes
+    /// to small files change metadata, and go through write().
+    pub fn write_direct(
         &mut self,
         file_id: EntryId,
         offset: u64,
-        buf: &mut [u8],
+        buf: &[u8],
     ) -> Result<usize, FsError> {
         self.error?;
         if file_id.kind() != EntryKind::File {
@@ -1034,36 +2404,26 @@ impl SyncFileSystem {
         let meta = unsafe { meta_block.block().get::<EntryMetadata>() };
         meta.validate_file(file_id)?;
         let file_size = meta.size;
-        if offset >= file_size {
-            return Ok(0);
-        }
-        let end = file_size.min(offset + (buf.len() as u64));
-        if file_size <= (MAX_BYTES_IN_META_BLOCK as u64) {
-            // We are still in the main block.
-            unsafe {
-                copy_nonoverlapping(
-                    (meta_block.block().as_data_bytes_in_meta().as_ptr() as usize
-                        + (offset as usize)) as *const u8,
-                    buf.as_mut_ptr(),
-                    (end - offset) as usize,
-                );
-            }
-            return Ok((end - offset) as usize);
+        if offset >= file_size || file_size <= MAX_BYTES_IN_META_BLOCK {
+            return self.write(file_id, offset, buf);
         }
 
         let data_block_no = self.find_data_block(file_id, offset)?;
-        let block_end = align_up(offset + 1, BLOCK_SIZE);
-        let new_end = (offset + (buf.len() as u64)).min(block_end);
-        let data_block = self.blockcache.read(data_block_no)?;
-        unsafe {
-            copy_nonoverlapping(
-                (data_block.block().as_bytes().as_ptr() as usize
-                    + ((offset & (BLOCK_SIZE - 1)) as usize)) as *const u8,
-                buf.as_mut_ptr(),
-                (new_end - offset) as usize,
-            );
+        if data_block_no == 0 {
+            return self.write(file_id, offset, buf);
         }
-        return Ok((new_end - offset) as usize);
+
+        // Don't write past the end: the rest of buf is appended later.
+        let end = file_size
+            .min(offset + (buf.len() as u64))
+            .min(align_up(offset + 1, BLOCK_SIZE));
+        let len = (end - offset) as usize;
+        self.blockcache.write_direct(
+            data_block_no,
+            (offset & (BLOCK_SIZE - 1)) as usize,
+            &buf[0..len],
+        )?;
+        Ok(len)
     }
 
     fn append(&mut self, file_id: EntryId, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
@@ -1110,9 +2470,8 @@ impl SyncFileSystem {
                     (new_size - prev_size) as usize,
                 );
             }
-            self.blockcache.write(data_block_no).map_err(|e| {
+            self.blockcache.write(data_block_no).inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })?;
 
             // Update the meta.
@@ -1123,9 +2482,8 @@ impl SyncFileSystem {
             let meta = unsafe { meta_block.block_mut().get_mut::<EntryMetadata>() };
             meta.size = new_size;
             meta.set_crc32();
-            self.blockcache.write(file_id.block_no).map_err(|e| {
+            self.blockcache.write(file_id.block_no).inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })?;
 
             // Commit the txn.
@@ -1133,6 +2491,13 @@ impl SyncFileSystem {
             return Ok((new_size - prev_size) as usize);
         }
 
+        // The file may end with a hole, which may also miss the link blocks
+        // the new block would go to: grow the file, then fill the hole.
+        if self.find_data_block(file_id, prev_size - 1)? == 0 {
+            self.grow(file_id, new_size)?;
+            return self.update_file(file_id, offset, buf);
+        }
+
         if align_up(prev_size, BLOCK_SIZE) >= new_size {
             // The write goes to an existing data block - no TXN is necessary.
             let data_block_no = self.find_data_block(file_id, prev_size - 1)?;
@@ -1173,9 +2538,8 @@ impl SyncFileSystem {
                 (new_size - prev_size) as usize,
             );
         }
-        self.blockcache.write(data_block_no).map_err(|e| {
+        self.blockcache.write(data_block_no).inspect_err(|_| {
             let _ = self.make_error();
-            e
         })?;
 
         if new_size <= MAX_BYTES_ONLY_DATA_BLOCKS {
@@ -1190,10 +2554,10 @@ impl SyncFileSystem {
                 || (prev_size == MAX_BYTES_ONLY_DATA_BLOCKS);
 
             if need_new_link_block {
-                let link_block_no = self.allocate_txn_block(BlockType::Links).map_err(|e| {
-                    let _ = self.make_error();
-                    e
-                })?;
+                let link_block_no =
+                    self.allocate_txn_block(BlockType::Links).inspect_err(|_| {
+                        let _ = self.make_error();
+                    })?;
 
                 let (link_block, data_block_idx) = if prev_size == MAX_BYTES_ONLY_DATA_BLOCKS {
                     let meta_block = *self.blockcache.get(file_id.block_no).block();
@@ -1216,9 +2580,8 @@ impl SyncFileSystem {
                 link_block
                     .block_mut()
                     .set_datablock_no_in_link(data_block_idx, data_block_no);
-                self.blockcache.write(link_block_no).map_err(|e| {
+                self.blockcache.write(link_block_no).inspect_err(|_| {
                     let _ = self.make_error();
-                    e
                 })?;
 
                 let link_block_idx = prev_size >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
@@ -1244,9 +2607,8 @@ impl SyncFileSystem {
                     .unwrap()
                     .block_mut()
                     .set_datablock_no_in_link(data_block_idx, data_block_no);
-                self.blockcache.write(link_block_no).map_err(|e| {
+                self.blockcache.write(link_block_no).inspect_err(|_| {
                     let _ = self.make_error();
-                    e
                 })?;
             }
         } else {
@@ -1257,25 +2619,23 @@ impl SyncFileSystem {
 
             if need_new_list_of_links_block {
                 // Always a new link block here.
-                let link_block_no = self.allocate_txn_block(BlockType::Links).map_err(|e| {
-                    let _ = self.make_error();
-                    e
-                })?;
+                let link_block_no =
+                    self.allocate_txn_block(BlockType::Links).inspect_err(|_| {
+                        let _ = self.make_error();
+                    })?;
                 let link_block = self.blockcache.get_block_uninit(link_block_no);
 
                 link_block
                     .block_mut()
                     .set_datablock_no_in_link(0, data_block_no);
-                self.blockcache.write(link_block_no).map_err(|e| {
+                self.blockcache.write(link_block_no).inspect_err(|_| {
                     let _ = self.make_error();
-                    e
                 })?;
 
                 let list_of_links_block_no = self
                     .allocate_txn_block(BlockType::ListOfLinks)
-                    .map_err(|e| {
+                    .inspect_err(|_| {
                         let _ = self.make_error();
-                        e
                     })?;
                 let (list_of_links_block, link_block_idx) =
                     if prev_size == MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS {
@@ -1300,10 +2660,11 @@ impl SyncFileSystem {
                 list_of_links_block
                     .block_mut()
                     .set_datablock_no_in_link(link_block_idx, link_block_no);
-                self.blockcache.write(list_of_links_block_no).map_err(|e| {
-                    let _ = self.make_error();
-                    e
-                })?;
+                self.blockcache
+                    .write(list_of_links_block_no)
+                    .inspect_err(|_| {
+                        let _ = self.make_error();
+                    })?;
 
                 let list_of_links_block_idx =
                     prev_size >> BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST.ilog2();
@@ -1330,28 +2691,28 @@ impl SyncFileSystem {
                     >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
 
                 if need_new_link_block {
-                    let link_block_no = self.allocate_txn_block(BlockType::Links).map_err(|e| {
-                        let _ = self.make_error();
-                        e
-                    })?;
+                    let link_block_no =
+                        self.allocate_txn_block(BlockType::Links).inspect_err(|_| {
+                            let _ = self.make_error();
+                        })?;
                     let link_block = self.blockcache.get_block_uninit(link_block_no);
 
                     link_block
                         .block_mut()
                         .set_datablock_no_in_link(0, data_block_no);
-                    self.blockcache.write(link_block_no).map_err(|e| {
+                    self.blockcache.write(link_block_no).inspect_err(|_| {
                         let _ = self.make_error();
-                        e
                     })?;
 
                     let list_of_links_block = self.blockcache.get_mut(list_of_links_block_no);
                     list_of_links_block
                         .block_mut()
                         .set_datablock_no_in_link(link_block_idx, link_block_no);
-                    self.blockcache.write(list_of_links_block_no).map_err(|e| {
-                        let _ = self.make_error();
-                        e
-                    })?;
+                    self.blockcache
+                        .write(list_of_links_block_no)
+                        .inspect_err(|_| {
+                            let _ = self.make_error();
+                        })?;
                 } else {
                     let link_block_no = self
                         .blockcache
@@ -1369,9 +2730,8 @@ impl SyncFileSystem {
                         .unwrap()
                         .block_mut()
                         .set_datablock_no_in_link(data_block_idx, data_block_no);
-                    self.blockcache.write(link_block_no).map_err(|e| {
+                    self.blockcache.write(link_block_no).inspect_err(|_| {
                         let _ = self.make_error();
-                        e
                     })?;
                 }
             }
@@ -1382,9 +2742,8 @@ impl SyncFileSystem {
         let meta = unsafe { meta_block.block_mut().get_mut::<EntryMetadata>() };
         meta.size = new_size;
         meta.set_crc32();
-        self.blockcache.write(file_id.block_no).map_err(|e| {
+        self.blockcache.write(file_id.block_no).inspect_err(|_| {
             let _ = self.make_error();
-            e
         })?;
 
         self.commit_txn()?;
@@ -1392,7 +2751,10 @@ impl SyncFileSystem {
     }
 
     fn update_file(&mut self, file_id: EntryId, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
-        let data_block_no = self.find_data_block(file_id, offset)?;
+        let data_block_no = match self.find_data_block(file_id, offset)? {
+            0 => self.fill_hole(file_id, offset)?,
+            data_block_no => data_block_no,
+        };
         let block_end = align_up(offset + 1, BLOCK_SIZE);
         let new_end = (offset + (buf.len() as u64)).min(block_end);
         let data_block = self.blockcache.read_mut(data_block_no)?;
@@ -1408,6 +2770,405 @@ impl SyncFileSystem {
         return Ok((new_end - offset) as usize);
     }
 
+    // Grow the file to new_size, leaving a hole: data blocks are not allocated,
+    // but the layout changes as the size crosses the thresholds.
+    fn grow(&mut self, file_id: EntryId, new_size: u64) -> Result<(), FsError> {
+        if new_size > MAX_FILE_SIZE {
+            return Err(FsError::TooLarge);
+        }
+
+        loop {
+            let metadata_block = self.blockcache.read(file_id.block_no)?;
+            let meta = unsafe { metadata_block.block().get::<EntryMetadata>() };
+            meta.validate_file(file_id)?;
+            let prev_size = meta.size;
+            if prev_size >= new_size {
+                return Ok(());
+            }
+
+            if prev_size <= MAX_BYTES_IN_META_BLOCK {
+                if new_size <= MAX_BYTES_IN_META_BLOCK {
+                    let metadata_block = self.blockcache.get_mut(file_id.block_no);
+                    metadata_block.block_mut().as_data_bytes_in_meta_mut()
+                        [(prev_size as usize)..(new_size as usize)]
+                        .fill(0);
+                    return self.write_file_size(file_id, new_size);
+                }
+
+                self.move_out_of_meta(
+                    file_id,
+                    BlockType::Data,
+                    prev_size as usize,
+                    new_size.min(MAX_BYTES_ONLY_DATA_BLOCKS),
+                )?;
+                continue;
+            }
+
+            self.clear_tail(file_id, prev_size)?;
+            if prev_size <= MAX_BYTES_ONLY_DATA_BLOCKS && new_size > MAX_BYTES_ONLY_DATA_BLOCKS {
+                self.move_out_of_meta(
+                    file_id,
+                    BlockType::Links,
+                    MAX_BYTES_IN_META_BLOCK as usize,
+                    new_size.min(MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS),
+                )?;
+            } else if prev_size <= MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS
+                && new_size > MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS
+            {
+                self.move_out_of_meta(
+                    file_id,
+                    BlockType::ListOfLinks,
+                    MAX_BYTES_IN_META_BLOCK as usize,
+                    new_size,
+                )?;
+            } else {
+                return self.write_file_size(file_id, new_size);
+            }
+        }
+    }
+
+    // Move the first len bytes after EntryMetadata (file bytes or links) into
+    // a new block, the only one the meta block then lists, and set the file
+    // size. If the bytes are all zeros, the new block is a hole instead.
+    fn move_out_of_meta(
+        &mut self,
+        file_id: EntryId,
+        block_type: BlockType,
+        len: usize,
+        new_size: u64,
+    ) -> Result<(), FsError> {
+        let metadata_block = *self.blockcache.get(file_id.block_no).block();
+        let bytes = &metadata_block.as_data_bytes_in_meta()[0..len];
+        if bytes.iter().all(|b| *b == 0) {
+            let metadata_block = self.blockcache.get_mut(file_id.block_no);
+            metadata_block
+                .block_mut()
+                .as_data_bytes_in_meta_mut()
+                .fill(0);
+            return self.write_file_size(file_id, new_size);
+        }
+        if self.superblock.header().free_blocks == 0 {
+            return Err(FsError::FsFull);
+        }
+
+        self.start_txn(TXN_TYPE_ADD_BYTES, file_id)?;
+        let result = self.move_out_of_meta_inner(file_id, block_type, bytes, new_size);
+        self.txn_result(result)?;
+        self.commit_txn()
+    }
+
+    // Must be inside a transaction.
+    fn move_out_of_meta_inner(
+        &mut self,
+        file_id: EntryId,
+        block_type: BlockType,
+        bytes: &[u8],
+        new_size: u64,
+    ) -> Result<(), FsError> {
+        let block_no = self.allocate_txn_block(block_type)?;
+        let block = self.blockcache.get_block_uninit(block_no);
+        *block.block_mut() = Block::new_zeroed();
+        block.block_mut().as_bytes_mut()[0..bytes.len()].copy_from_slice(bytes);
+        self.blockcache.write(block_no)?;
+
+        let metadata_block = self.blockcache.read_mut(file_id.block_no)?;
+        metadata_block
+            .block_mut()
+            .as_data_bytes_in_meta_mut()
+            .fill(0);
+        metadata_block
+            .block_mut()
+            .set_datablock_no_in_meta(0, block_no);
+        let meta = unsafe { metadata_block.block_mut().get_mut::<EntryMetadata>() };
+        meta.size = new_size;
+        meta.set_crc32();
+        self.blockcache.write(file_id.block_no)
+    }
+
+    // Zero the bytes and the links past the end of the file in its last blocks:
+    // they may be left from writes or truncations, and the file is about to
+    // grow over them.
+    fn clear_tail(&mut self, file_id: EntryId, size: u64) -> Result<(), FsError> {
+        debug_assert!(size > MAX_BYTES_IN_META_BLOCK);
+        let last = size - 1;
+
+        if (size & (BLOCK_SIZE - 1)) != 0 {
+            self.blockcache.read(file_id.block_no)?;
+            let data_block_no = self.find_data_block(file_id, last)?;
+            if data_block_no != 0 {
+                let data_block = self.blockcache.read_mut(data_block_no)?;
+                data_block.block_mut().as_bytes_mut()[((size & (BLOCK_SIZE - 1)) as usize)..]
+                    .fill(0);
+                self.blockcache.write(data_block_no)?;
+            }
+        }
+
+        let data_block_idx =
+            (last & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
+        let link_block_idx = (last & (BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST - 1))
+            >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
+        let metadata_block = self.blockcache.read(file_id.block_no)?;
+        let last_idx_in_meta = if size <= MAX_BYTES_ONLY_DATA_BLOCKS {
+            last >> BLOCK_SIZE.ilog2()
+        } else if size <= MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS {
+            let last_idx_in_meta = last >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
+            let link_block_no = metadata_block
+                .block()
+                .get_datablock_no_in_meta(last_idx_in_meta);
+            self.clear_links_after(link_block_no, data_block_idx)?;
+            last_idx_in_meta
+        } else {
+            let last_idx_in_meta = last >> BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST.ilog2();
+            let list_of_links_block_no = metadata_block
+                .block()
+                .get_datablock_no_in_meta(last_idx_in_meta);
+            if list_of_links_block_no != 0 {
+                let link_block_no = self
+                    .blockcache
+                    .read(list_of_links_block_no)?
+                    .block()
+                    .get_datablock_no_in_link(link_block_idx);
+                self.clear_links_after(link_block_no, data_block_idx)?;
+                self.clear_links_after(list_of_links_block_no, link_block_idx)?;
+            }
+            last_idx_in_meta
+        };
+
+        let metadata_block = self.blockcache.read_mut(file_id.block_no)?;
+        for idx in (last_idx_in_meta + 1)..MAX_LINKS_IN_META_BLOCK {
+            metadata_block.block_mut().set_datablock_no_in_meta(idx, 0);
+        }
+        self.blockcache.write(file_id.block_no)
+    }
+
+    // Zero the links after idx in (link or list-of-links) block_no, unless it is a hole.
+    fn clear_links_after(&mut self, block_no: u64, idx: u64) -> Result<(), FsError> {
+        if block_no == 0 || idx == 511 {
+            return Ok(());
+        }
+
+        let block = self.blockcache.read_mut(block_no)?;
+        block.block_mut().as_bytes_mut()[(((idx + 1) << 3) as usize)..].fill(0);
+        self.blockcache.write(block_no)
+    }
+
+    // Allocate a zeroed data block for the hole at offset, with the link blocks
+    // that are missing on the way to it. Returns the new data block.
+    fn fill_hole(&mut self, file_id: EntryId, offset: u64) -> Result<u64, FsError> {
+        let metadata_block = self.blockcache.read(file_id.block_no)?;
+        let file_size = unsafe { metadata_block.block().get::<EntryMetadata>() }.size;
+        debug_assert!(file_size > MAX_BYTES_IN_META_BLOCK);
+        debug_assert!(offset < file_size);
+
+        // The levels of link blocks between the meta block and the data blocks.
+        let (levels, idx_in_meta) = if file_size <= MAX_BYTES_ONLY_DATA_BLOCKS {
+            (0, offset >> BLOCK_SIZE.ilog2())
+        } else if file_size <= MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS {
+            (1, offset >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2())
+        } else {
+            (2, offset >> BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST.ilog2())
+        };
+        let data_block_idx =
+            (offset & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
+        let link_block_idx = (offset & (BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST - 1))
+            >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
+
+        let block_no_in_meta = metadata_block.block().get_datablock_no_in_meta(idx_in_meta);
+        let (list_of_links_block_no, link_block_no) = match levels {
+            0 => (0, 0),
+            1 => (0, block_no_in_meta),
+            _ if block_no_in_meta == 0 => (0, 0),
+            _ => (
+                block_no_in_meta,
+                self.blockcache
+                    .read(block_no_in_meta)?
+                    .block()
+                    .get_datablock_no_in_link(link_block_idx),
+            ),
+        };
+        let new_link_block = levels > 0 && link_block_no == 0;
+        let new_list_of_links_block = levels > 1 && list_of_links_block_no == 0;
+        if self.superblock.header().free_blocks
+            < 1 + (new_link_block as u64) + (new_list_of_links_block as u64)
+        {
+            return Err(FsError::FsFull);
+        }
+
+        // Write the new blocks before the blocks linking to them.
+        self.start_txn(TXN_TYPE_ADD_BYTES, file_id)?;
+        let result = (|| {
+            let data_block_no = self.allocate_txn_block(BlockType::Data)?;
+            let data_block = self.blockcache.get_block_uninit(data_block_no);
+            *data_block.block_mut() = Block::new_zeroed();
+            self.blockcache.write(data_block_no)?;
+            let mut new_block_no_in_meta = data_block_no;
+
+            if levels > 0 {
+                let link_block_no = if new_link_block {
+                    self.allocate_txn_block(BlockType::Links)?
+                } else {
+                    link_block_no
+                };
+                self.set_link(link_block_no, new_link_block, data_block_idx, data_block_no)?;
+                new_block_no_in_meta = link_block_no;
+
+                if levels > 1 {
+                    let list_of_links_block_no = if new_list_of_links_block {
+                        self.allocate_txn_block(BlockType::ListOfLinks)?
+                    } else {
+                        list_of_links_block_no
+                    };
+                    if new_link_block {
+                        self.set_link(
+                            list_of_links_block_no,
+                            new_list_of_links_block,
+                            link_block_idx,
+                            link_block_no,
+                        )?;
+                    }
+                    new_block_no_in_meta = list_of_links_block_no;
+                }
+            }
+
+            if new_block_no_in_meta != block_no_in_meta {
+                let metadata_block = self.blockcache.read_mut(file_id.block_no)?;
+                metadata_block
+                    .block_mut()
+                    .set_datablock_no_in_meta(idx_in_meta, new_block_no_in_meta);
+                self.blockcache.write(file_id.block_no)?;
+            }
+            Ok(data_block_no)
+        })();
+        let data_block_no = self.txn_result(result)?;
+        self.commit_txn()?;
+        Ok(data_block_no)
+    }
+
+    // Set link idx in (link or list-of-links) block_no, which is all zeros if new.
+    fn set_link(&mut self, block_no: u64, new: bool, idx: u64, link: u64) -> Result<(), FsError> {
+        let block = if new {
+            let block = self.blockcache.get_block_uninit(block_no);
+            *block.block_mut() = Block::new_zeroed();
+            block
+        } else {
+            self.blockcache.read_mut(block_no)?
+        };
+        block.block_mut().set_datablock_no_in_link(idx, link);
+        self.blockcache.write(block_no)
+    }
+
+    // Free the data block at offset, leaving a hole.
+    fn free_data_block(
+        &mut self,
+        file_id: EntryId,
+        offset: u64,
+        data_block_no: u64,
+    ) -> Result<(), FsError> {
+        let metadata_block = self.blockcache.get(file_id.block_no);
+        let file_size = unsafe { metadata_block.block().get::<EntryMetadata>() }.size;
+        let link_block_no = if file_size <= MAX_BYTES_ONLY_DATA_BLOCKS {
+            file_id.block_no // The meta block lists data blocks.
+        } else {
+            self.find_link_block(file_id, offset)?
+        };
+
+        self.start_txn(TXN_TYPE_REMOVE_BYTES, file_id)?;
+        self.superblock.header_mut().txn_data_block = data_block_no;
+        self.save_superblock()?;
+
+        let result = (|| {
+            let block = self.blockcache.read_mut(link_block_no)?;
+            if link_block_no == file_id.block_no {
+                block
+                    .block_mut()
+                    .set_datablock_no_in_meta(offset >> BLOCK_SIZE.ilog2(), 0);
+            } else {
+                block.block_mut().set_datablock_no_in_link(
+                    (offset & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2(),
+                    0,
+                );
+            }
+            self.blockcache.write(link_block_no)
+        })();
+        self.txn_result(result)?;
+
+        self.free_txn_block(BlockType::Data)?;
+        self.commit_txn()
+    }
+
+    // The xattr block of the entry (zero if none) and the attributes in it.
+    fn read_xattrs(&mut self, entry: EntryId) -> XattrsResult {
+        let block = self.blockcache.read(entry.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(entry)?;
+        let block_no = meta.xattrs;
+        if block_no == 0 {
+            return Ok((0, Vec::new()));
+        }
+
+        let xattr_block = self.blockcache.read(block_no)?;
+        let xattrs = XattrHeader::decode(xattr_block.block(), entry)?;
+        Ok((block_no, xattrs))
+    }
+
+    // Must be inside a transaction.
+    fn add_xattr_block(&mut self, entry: EntryId, block: &Block) -> Result<(), FsError> {
+        let block_no = self.allocate_txn_block(BlockType::Data)?;
+        let xattr_block = self.blockcache.get_block_uninit(block_no);
+        *xattr_block.block_mut() = *block;
+        self.blockcache.write(block_no)?;
+
+        let meta_block = self.blockcache.read_mut(entry.block_no)?;
+        let meta = unsafe { meta_block.block_mut().get_mut::<EntryMetadata>() };
+        meta.xattrs = block_no;
+        meta.set_crc32();
+        self.blockcache.write(entry.block_no)
+    }
+
+    // Remove all extended attributes of the entry, freeing their block.
+    fn free_xattrs(&mut self, entry: EntryId) -> Result<(), FsError> {
+        let block = self.blockcache.read(entry.block_no)?;
+        let meta = unsafe { block.block().get::<EntryMetadata>() };
+        meta.validate(entry)?;
+        let block_no = meta.xattrs;
+        if block_no == 0 {
+            return Ok(());
+        }
+
+        self.start_txn(TXN_TYPE_REMOVE_BYTES, entry)?;
+        self.superblock.header_mut().txn_data_block = block_no;
+        self.save_superblock()?;
+
+        let result = (|| {
+            let meta_block = self.blockcache.read_mut(entry.block_no)?;
+            let meta = unsafe { meta_block.block_mut().get_mut::<EntryMetadata>() };
+            meta.xattrs = 0;
+            meta.set_crc32();
+            self.blockcache.write(entry.block_no)
+        })();
+        self.txn_result(result)?;
+
+        self.free_txn_block(BlockType::Data)?;
+        self.commit_txn()
+    }
+
+    fn write_file_size(&mut self, file_id: EntryId, size: u64) -> Result<(), FsError> {
+        let metadata_block = self.blockcache.get_mut(file_id.block_no);
+        let meta = unsafe { metadata_block.block_mut().get_mut::<EntryMetadata>() };
+        meta.size = size;
+        meta.set_crc32();
+        self.blockcache.write(file_id.block_no)
+    }
+
+    // Failures inside a TXN poison the FS (if not poisoned already).
+    fn txn_result<T>(&mut self, result: Result<T, FsError>) -> Result<T, FsError> {
+        if result.is_err() && self.error.is_ok() {
+            let _ = self.make_error();
+        }
+        result
+    }
+
+    // Zero => a hole.
     fn find_data_block(&mut self, file_id: EntryId, offset: u64) -> Result<u64, FsError> {
         let meta_block = self.blockcache.get(file_id.block_no);
         let meta = unsafe { meta_block.block().get::<EntryMetadata>() };
@@ -1423,36 +3184,47 @@ impl SyncFileSystem {
             return Ok(meta_block.block().get_datablock_no_in_meta(block_idx));
         }
 
+        let link_block_no = self.find_link_block(file_id, offset)?;
+        if link_block_no == 0 {
+            return Ok(0);
+        }
+
+        let link_block = self.blockcache.read(link_block_no)?;
+        let data_block_idx =
+            (offset & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
+        Ok(link_block.block().get_datablock_no_in_link(data_block_idx))
+    }
+
+    // The link block listing the data block at offset in files larger than ~2M.
+    // Zero => a hole.
+    fn find_link_block(&mut self, file_id: EntryId, offset: u64) -> Result<u64, FsError> {
+        let meta_block = self.blockcache.get(file_id.block_no);
+        let meta = unsafe { meta_block.block().get::<EntryMetadata>() };
+        let file_size = meta.size;
+        debug_assert!(file_size > MAX_BYTES_ONLY_DATA_BLOCKS);
+        debug_assert!(offset < file_size);
+
         if file_size <= MAX_BYTES_SINGLE_LEVEL_LIST_BLOCKS {
             // Files smaller than ~1G.
             let link_block_idx = offset >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
-            let meta_block = self.blockcache.get(file_id.block_no);
-            let link_block_no = meta_block.block().get_datablock_no_in_meta(link_block_idx);
-
-            let link_block = self.blockcache.read(link_block_no)?;
-            let data_block_idx =
-                (offset & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
-            return Ok(link_block.block().get_datablock_no_in_link(data_block_idx));
+            return Ok(meta_block.block().get_datablock_no_in_meta(link_block_idx));
         }
 
         // Files larger than ~1G.
         let list_of_links_block_idx = offset >> BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST.ilog2();
-        let meta_block = self.blockcache.get(file_id.block_no);
         let list_of_links_block_no = meta_block
             .block()
             .get_datablock_no_in_meta(list_of_links_block_idx);
+        if list_of_links_block_no == 0 {
+            return Ok(0);
+        }
 
         let list_of_links_block = self.blockcache.read(list_of_links_block_no)?;
         let link_block_idx = (offset & (BYTES_COVERED_BY_SECOND_LEVEL_BLOCKLIST - 1))
             >> BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST.ilog2();
-        let link_block_no = list_of_links_block
+        Ok(list_of_links_block
             .block()
-            .get_datablock_no_in_link(link_block_idx);
-
-        let link_block = self.blockcache.read(link_block_no)?;
-        let data_block_idx =
-            (offset & (BYTES_COVERED_BY_FIRST_LEVEL_BLOCKLIST - 1)) >> BLOCK_SIZE.ilog2();
-        return Ok(link_block.block().get_datablock_no_in_link(data_block_idx));
+            .get_datablock_no_in_link(link_block_idx))
     }
 
     fn find_entry_by_id(
@@ -1571,6 +3343,17 @@ impl SyncFileSystem {
     fn make_error(&mut self) -> Result<(), FsError> {
         assert!(self.error.is_ok());
         self.error = Err(FsError::ValidationFailed);
+
+        // If the journal held the failed TXN, the volume is as it was before
+        // the TXN: so should be the superblock, for repair() to see.
+        if self.blockcache.abort_txn() {
+            let mut block = Box::new(Block::new_uninit());
+            if self.blockcache.__read_uncached_block(0, &mut block).is_ok() {
+                if let Ok(superblock) = Superblock::from(block) {
+                    self.superblock = superblock;
+                }
+            }
+        }
         return self.error;
     }
 
@@ -1579,9 +3362,8 @@ impl SyncFileSystem {
         self.superblock.header_mut().set_crc32();
         self.blockcache
             .write_uncached_block(0, self.superblock.block())
-            .map_err(|e| {
+            .inspect_err(|_| {
                 let _ = self.make_error();
-                e
             })
     }
 
@@ -1590,9 +3372,8 @@ impl SyncFileSystem {
         let meta = unsafe { block.block_mut().get_mut::<EntryMetadata>() };
         meta.size += 1;
         meta.set_crc32();
-        self.blockcache.write(block_no).map_err(|e| {
+        self.blockcache.write(block_no).inspect_err(|_| {
             let _ = self.make_error(); // We cannot recover from this.
-            e
         })
     }
 
@@ -1664,6 +3445,7 @@ impl SyncFileSystem {
             let block = self.blockcache.get_block_uninit(block_no);
             unsafe { *block.block_mut().get_mut::<u64>() = prev_head };
             self.blockcache.write(block_no)?;
+            sbh.freelist_head = block_no;
         }
 
         match block_type {
@@ -1686,11 +3468,19 @@ impl SyncFileSystem {
         assert_eq!(0, sbh.txn_list_of_links_block);
         sbh.txn_blocks_owner = owner.block_no;
         sbh.txn_type = txn_type;
+        if txn_type != TXN_TYPE_REPAIR {
+            self.blockcache.begin_txn();
+        }
 
-        self.save_superblock()
+        // The device may reorder cached writes: flushes order the TXN record
+        // before the blocks it covers, and these before the commit below.
+        // With a journal, none of this reaches the device before the commit.
+        self.save_superblock()?;
+        self.blockcache.flush()
     }
 
     fn commit_txn(&mut self) -> Result<(), FsError> {
+        self.blockcache.flush()?;
         let sbh = self.superblock.header_mut();
         sbh.txn_meta_block = 0;
         sbh.txn_data_block = 0;
@@ -1698,6 +3488,9 @@ impl SyncFileSystem {
         sbh.txn_list_of_links_block = 0;
         sbh.txn_blocks_owner = 0;
         sbh.txn_type = TXN_TYPE_NONE;
-        self.save_superblock()
+        self.save_superblock()?;
+        self.blockcache.commit_txn().inspect_err(|_| {
+            let _ = self.make_error();
+        })
     }
 }
@@ -0,0 +1,176 @@
+// The journal: transactions that survive power loss.
+//
+// Without a journal, a TXN changes blocks in place, marked in the superblock
+// as in progress, and a volume found with a TXN in progress must be repaired
+// (scanned in full) before use. With a journal, the blocks a TXN writes are
+// held in memory until the TXN commits; then they are written to the journal
+// area, then the record listing them, and only then to their places. The
+// record is the commit point: when the volume is opened, a valid record is
+// replayed (its blocks are written to their places again), and without one
+// the TXN never happened. Either way the volume is consistent.
+//
+// The journal area is carved from the end of the volume (see JournalHeader
+// and JournalRecord). Repair TXNs, which may change any number of blocks,
+// bypass the journal.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+
+use super::*;
+use crate::snapshot::Snapshots;
+
+pub(crate) struct Journal {
+    start: u64,
+    blocks: u64,
+    active: bool,                       // Inside a TXN.
+    pending: BTreeMap<u64, Box<Block>>, // Written in the TXN: block_no => its contents.
+}
+
+impl Journal {
+    pub(crate) fn new(start: u64, blocks: u64) -> Self {
+        debug_assert!((MIN_JOURNAL_BLOCKS..=MAX_JOURNAL_BLOCKS).contains(&blocks));
+        Self {
+            start,
+            blocks,
+            active: false,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    // The number of blocks a TXN can write.
+    fn capacity(&self) -> u64 {
+        self.blocks - 1
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub(crate) fn begin(&mut self) {
+        debug_assert!(!self.active);
+        debug_assert!(self.pending.is_empty());
+        self.active = true;
+    }
+
+    // Drop the TXN in progress, if any: nothing of it reached the device.
+    pub(crate) fn abort(&mut self) -> bool {
+        self.pending.clear();
+        core::mem::replace(&mut self.active, false)
+    }
+
+    // Read the block as written in the TXN, if it was.
+    pub(crate) fn read(&self, block_no: u64, buf: &mut [u8]) -> bool {
+        match self.pending.get(&block_no) {
+            Some(block) => {
+                buf.copy_from_slice(block.as_bytes());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn write(&mut self, block_no: u64, buf: &[u8]) -> Result<(), FsError> {
+        debug_assert!(self.active);
+        if let Some(block) = self.pending.get_mut(&block_no) {
+            block.as_bytes_mut().copy_from_slice(buf);
+            return Ok(());
+        }
+
+        if self.pending.len() as u64 == self.capacity() {
+            log::error!("srfs-core: the TXN does not fit into the journal");
+            return Err(FsError::TooLarge);
+        }
+        let mut block = Box::new(Block::new_uninit());
+        block.as_bytes_mut().copy_from_slice(buf);
+        self.pending.insert(block_no, block);
+        Ok(())
+    }
+
+    pub(crate) fn commit(
+        &mut self,
+        block_device: &mut dyn SyncBlockDevice,
+        snapshots: &mut Snapshots,
+        scratch: &mut Block,
+    ) -> Result<(), FsError> {
+        debug_assert!(self.active);
+        self.active = false;
+        let pending = core::mem::take(&mut self.pending);
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        // Every flush below orders the writes before it ahead of those after
+        // it: first the blocks go to the journal...
+        *scratch = Block::new_zeroed();
+        for (idx, (block_no, block)) in pending.iter().enumerate() {
+            let idx = idx as u64;
+            block_device.write_block(self.start + 1 + idx, block.as_bytes())?;
+            let entry = scratch.get_journal_entry_mut(idx);
+            entry.block_no = *block_no;
+            entry.crc32 = crc32_hash(block.as_bytes());
+        }
+        block_device.flush()?;
+
+        // ... then the record, which commits the TXN...
+        let record = unsafe { scratch.get_mut::<JournalRecord>() };
+        record.magic = JOURNAL_MAGIC;
+        record.num_entries = pending.len() as u64;
+        JournalRecord::set_crc32(scratch);
+        block_device.write_block(self.start, scratch.as_bytes())?;
+        block_device.flush()?;
+
+        // ... then the blocks go to their places.
+        for (block_no, block) in &pending {
+            snapshots.before_write(block_device, *block_no)?;
+            block_device.write_block(*block_no, block.as_bytes())?;
+        }
+        block_device.flush()?;
+        self.clear(block_device, scratch)
+    }
+
+    // Called when the volume is opened: finish the TXN committed last, if
+    // it may not have reached its places. Returns true if there was one.
+    pub(crate) fn replay(
+        &mut self,
+        block_device: &mut dyn SyncBlockDevice,
+        snapshots: &mut Snapshots,
+        scratch: &mut Block,
+    ) -> Result<bool, FsError> {
+        debug_assert!(!self.active);
+        block_device.read_block(self.start, scratch.as_bytes_mut())?;
+        if !JournalRecord::validate(scratch, self.capacity()) {
+            // Nothing committed, or the record never made it in full; either
+            // way the TXN did not touch the blocks in place.
+            return Ok(false);
+        }
+
+        let num_entries = unsafe { scratch.get::<JournalRecord>() }.num_entries;
+        let mut block = Box::new(Block::new_uninit());
+        for idx in 0..num_entries {
+            let entry = *scratch.get_journal_entry(idx);
+            block_device.read_block(self.start + 1 + idx, block.as_bytes_mut())?;
+            if entry.block_no >= self.start || crc32_hash(block.as_bytes()) != entry.crc32 {
+                log::error!("srfs-core: bad journal block {}", self.start + 1 + idx);
+                return Err(FsError::ValidationFailed);
+            }
+            snapshots.before_write(block_device, entry.block_no)?;
+            block_device.write_block(entry.block_no, block.as_bytes())?;
+        }
+        block_device.flush()?;
+        self.clear(block_device, scratch)?;
+
+        log::warn!("srfs-core: replayed {} journal block(s)", num_entries);
+        Ok(true)
+    }
+
+    // The TXN is in place: the record must not be replayed again.
+    pub(crate) fn clear(
+        &mut self,
+        block_device: &mut dyn SyncBlockDevice,
+        scratch: &mut Block,
+    ) -> Result<(), FsError> {
+        *scratch = Block::new_zeroed();
+        block_device.write_block(self.start, scratch.as_bytes())?;
+        block_device.flush()
+    }
+}
@@ -27,6 +27,11 @@
 ///     blocks which list data blocks;
 ///   - file sizes above that are currently not supported, but it will be easy to continue with
 ///     the same approach.
+/// - files may be sparse: a zero block number, at any level, is a hole, which reads as zeroes;
+///   the block numbers listed past the end of the file are undefined.
+///
+/// Extended attributes of files and directories, if any, are in a separate block
+/// (see XattrHeader).
 use core::{mem::MaybeUninit, ptr::copy_nonoverlapping};
 
 #[cfg(feature = "std")]
@@ -34,9 +39,10 @@ extern crate std;
 
 use super::*;
 
+use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
 use alloc::string::String;
-use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
 
 use crc::CRC_32_ISO_HDLC;
 
@@ -87,6 +93,11 @@ impl EntryId {
         }
     }
 
+    /// Identifies the entry: unlike block_no, never re-used.
+    pub fn unique_id(&self) -> u64 {
+        self.generation
+    }
+
     pub fn kind(&self) -> EntryKind {
         if (self.generation & 1) == 1 {
             EntryKind::Directory
@@ -187,12 +198,20 @@ pub(crate) struct EntryMetadata {
     pub size: u64, // File size in bytes, or the number of directory entries.
     pub created: Timestamp,
     pub modified: Timestamp,
-    pub accessed: Timestamp, // Usually not tracked.
+    pub xattrs: u64,         // The block with extended attributes, or zero.
+    _reserved: u64,          // Was the (never set) access time.
     pub user_data: [u64; 4], // Whatever, e.g. permissions, uid/gid, etc.
-    pub _reserved_2: u32,
-    pub crc32: u32, // CRC32 of this data structure.
+    pub flags: u16,          // ENTRY_FLAG_*.
+    pub links: u16,          // Hard links in addition to the first one.
+    pub crc32: u32,          // CRC32 of this data structure.
 }
 
+/// The entry is a symbolic link: a file whose content is the target path.
+pub const ENTRY_FLAG_SYMLINK: u16 = 1;
+/// The file has (or had) hard links, so its parent_id is not maintained:
+/// a file with several links has no single parent.
+pub const ENTRY_FLAG_HARDLINKED: u16 = 2;
+
 const _: () = assert!(core::mem::size_of::<EntryMetadata>() == 128);
 const _: () = assert!(core::mem::size_of::<EntryMetadata>() < (BLOCK_SIZE as usize));
 
@@ -258,9 +277,11 @@ impl EntryMetadata {
             size: 0,
             created: now,
             modified: now,
-            accessed: Timestamp::zero(),
+            xattrs: 0,
+            _reserved: 0,
             user_data: [0; 4],
-            _reserved_2: 0,
+            flags: 0,
+            links: 0,
             crc32: 0,
         }
     }
@@ -331,6 +352,14 @@ pub struct Attr {
     pub modified: Timestamp,
     pub accessed: Timestamp, // Usually not tracked.
     pub user_data: [u64; 4], // Whatever, e.g. permissions, uid/gid, etc.
+    pub flags: u16,          // ENTRY_FLAG_*.
+    pub links: u32,          // The number of hard links (directory entries).
+}
+
+impl Attr {
+    pub fn is_symlink(&self) -> bool {
+        (self.flags & ENTRY_FLAG_SYMLINK) != 0
+    }
 }
 
 impl From<&EntryMetadata> for Attr {
@@ -340,12 +369,103 @@ impl From<&EntryMetadata> for Attr {
             size: meta.size,
             created: meta.created,
             modified: meta.modified,
-            accessed: meta.accessed,
+            accessed: Timestamp::zero(),
             user_data: meta.user_data,
+            flags: meta.flags,
+            links: meta.links as u32 + 1,
         }
     }
 }
 
+/// The header of the block with the extended attributes of an entry. Records
+/// follow it: the name length (u8), the value length (u16, little-endian),
+/// the name (UTF-8), and the value, without padding.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub(crate) struct XattrHeader {
+    pub owner: EntryId, // The entry the attributes belong to.
+    pub used: u32,      // Bytes of records after the header.
+    pub crc32: u32,     // CRC32 of this header (but the crc32) and the records.
+}
+
+const XATTR_RECORD_HEADER: usize = 3;
+pub(crate) const MAX_XATTR_BYTES: usize = BLOCK_SIZE as usize - core::mem::size_of::<XattrHeader>();
+
+impl XattrHeader {
+    fn crc32(block: &Block, used: usize) -> u32 {
+        let bytes = block.as_bytes();
+        let header_len = core::mem::size_of::<Self>();
+        let mut digest = CRC32.digest();
+        digest.update(&bytes[0..(header_len - 4)]);
+        digest.update(&bytes[header_len..(header_len + used)]);
+        digest.finalize()
+    }
+
+    /// Parses the extended attributes of @owner in @block.
+    pub fn decode(block: &Block, owner: EntryId) -> Result<Vec<(String, Vec<u8>)>, FsError> {
+        let header = unsafe { block.get::<Self>() };
+        let used = header.used as usize;
+        if header.owner != owner || used > MAX_XATTR_BYTES {
+            return Err(FsError::ValidationFailed);
+        }
+        if header.crc32 != Self::crc32(block, used) {
+            return Err(FsError::ValidationFailed);
+        }
+
+        let mut records = &block.as_bytes()[core::mem::size_of::<Self>()..][..used];
+        let mut result = Vec::new();
+        while !records.is_empty() {
+            if records.len() < XATTR_RECORD_HEADER {
+                return Err(FsError::ValidationFailed);
+            }
+            let name_len = records[0] as usize;
+            let value_len = u16::from_le_bytes([records[1], records[2]]) as usize;
+            let records_left = &records[XATTR_RECORD_HEADER..];
+            if records_left.len() < name_len + value_len {
+                return Err(FsError::ValidationFailed);
+            }
+            let name =
+                core::str::from_utf8(&records_left[0..name_len]).map_err(|_| FsError::Utf8Error)?;
+            let value = &records_left[name_len..(name_len + value_len)];
+            result.push((name.to_owned(), value.to_vec()));
+            records = &records_left[(name_len + value_len)..];
+        }
+
+        Ok(result)
+    }
+
+    /// Writes @xattrs of @owner into @block; TooLarge if they don't fit.
+    pub fn encode(
+        block: &mut Block,
+        owner: EntryId,
+        xattrs: &[(String, Vec<u8>)],
+    ) -> Result<(), FsError> {
+        let header_len = core::mem::size_of::<Self>();
+        let mut used = 0;
+        for (name, value) in xattrs {
+            let name = name.as_bytes();
+            let record_len = XATTR_RECORD_HEADER + name.len() + value.len();
+            if used + record_len > MAX_XATTR_BYTES {
+                return Err(FsError::TooLarge);
+            }
+            let record = &mut block.as_bytes_mut()[(header_len + used)..][..record_len];
+            let (record_header, record) = record.split_at_mut(XATTR_RECORD_HEADER);
+            record_header[0] = name.len() as u8;
+            record_header[1..].copy_from_slice(&(value.len() as u16).to_le_bytes());
+            record[0..name.len()].copy_from_slice(name);
+            record[name.len()..].copy_from_slice(value);
+            used += record_len;
+        }
+
+        let header = unsafe { block.get_mut::<Self>() };
+        header.owner = owner;
+        header.used = used as u32;
+        let crc32 = Self::crc32(block, used);
+        unsafe { block.get_mut::<Self>() }.crc32 = crc32;
+        Ok(())
+    }
+}
+
 pub(crate) const MAGIC: u64 = 0x0c51_a0bb_b108_3d14; // Just a random number.
 
 // The type of transaction type in process.
@@ -355,11 +475,16 @@ pub(crate) const TXN_TYPE_ADD_BYTES: u32 = 2;
 pub(crate) const TXN_TYPE_REMOVE_NODE: u32 = 3;
 pub(crate) const TXN_TYPE_REMOVE_BYTES: u32 = 4;
 pub(crate) const TXN_TYPE_MOVE: u32 = 5;
+pub(crate) const TXN_TYPE_ADD_LINK: u32 = 6;
+pub(crate) const TXN_TYPE_REMOVE_LINK: u32 = 7;
+pub(crate) const TXN_TYPE_REPLACE: u32 = 8;
+pub(crate) const TXN_TYPE_REPAIR: u32 = 9;
 
 // The partition:
 // - the first block
 // - root_dir block
 // - either Entry blocks, data blocks, or empty blocks
+// - the journal, then snapshot areas, if any
 
 // The first block header. Duplicated at [0..) and [2048..) of the first block
 // of the partition.
@@ -416,6 +541,228 @@ impl SuperblockHeader {
     }
 }
 
+// The snapshot table, at SNAPSHOT_TABLE_OFFSET of the first block. All
+// zeroes if the volume has no snapshots (as volumes formatted before
+// snapshots were added don't).
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct SnapshotTable {
+    pub total_blocks: u64, // num_blocks plus snapshot areas. 0 => empty.
+    pub areas: [u64; MAX_SNAPSHOTS as usize], // The first blocks of snapshot areas. 0 => unused.
+    pub _reserved: u32,
+    pub crc32: u32, // CRC32 of this data structure.
+}
+
+pub(crate) const SNAPSHOT_TABLE_OFFSET: usize = 1024;
+const _: () = assert!(
+    SNAPSHOT_TABLE_OFFSET >= core::mem::size_of::<SuperblockHeader>()
+        && SNAPSHOT_TABLE_OFFSET + core::mem::size_of::<SnapshotTable>() <= 2048
+);
+
+impl SnapshotTable {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as usize as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    pub fn set_crc32(&mut self) {
+        if self.total_blocks == 0 {
+            *self = unsafe { core::mem::zeroed() };
+            return;
+        }
+        let bytes = &self.as_bytes()[0..(core::mem::size_of::<Self>() - 4)];
+        self.crc32 = crc32_hash(bytes);
+    }
+
+    pub fn validate(&self) -> Result<(), FsError> {
+        if self.total_blocks == 0 {
+            if self.as_bytes().iter().all(|b| *b == 0) {
+                return Ok(());
+            }
+            return Err(FsError::ValidationFailed);
+        }
+        crc32_verify(self.as_bytes())
+    }
+}
+
+pub(crate) const SNAPSHOT_MAGIC: u64 = 0x5a3c_19e7_d2b6_0f41; // Just a random number.
+
+// The snapshot has run out of space: it no longer matches the volume as it
+// was when the snapshot was taken.
+pub(crate) const SNAPSHOT_FLAG_INVALID: u32 = 1;
+
+// A snapshot area:
+// - this header block
+// - map blocks: the origins (block_no + 1) of the copies, in order; 0 => end
+// - copies of the blocks of the volume overwritten since the snapshot was taken
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct SnapshotHeader {
+    pub magic: u64,         // SNAPSHOT_MAGIC.
+    pub created: Timestamp, // When the snapshot was taken.
+    pub watermark: u64,     // empty_area_start of the volume when the snapshot was taken.
+    pub area_blocks: u64,   // The size of the area, this block included.
+    pub map_blocks: u64,    // The number of map blocks.
+    pub name: [u8; MAX_SNAPSHOT_NAME_LEN as usize],
+    pub name_len: u32,
+    pub flags: u32, // SNAPSHOT_FLAG_***.
+    pub _reserved: u32,
+    pub crc32: u32, // CRC32 of this data structure.
+}
+
+impl SnapshotHeader {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as usize as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    pub fn set_crc32(&mut self) {
+        let bytes = &self.as_bytes()[0..(core::mem::size_of::<Self>() - 4)];
+        self.crc32 = crc32_hash(bytes);
+    }
+
+    pub fn validate(&self) -> Result<(), FsError> {
+        crc32_verify(self.as_bytes())?;
+        if self.magic != SNAPSHOT_MAGIC
+            || self.name_len as u64 > MAX_SNAPSHOT_NAME_LEN
+            || self.map_blocks == 0
+            || self.area_blocks <= self.map_blocks + 1
+        {
+            return Err(FsError::ValidationFailed);
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[0..(self.name_len as usize)]).unwrap_or("")
+    }
+}
+
+// The journal header, at JOURNAL_HEADER_OFFSET of the first block. All
+// zeroes if the volume has no journal (see SyncFileSystem::add_journal()).
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct JournalHeader {
+    pub start: u64,  // The first block of the journal area. 0 => no journal.
+    pub blocks: u64, // The size of the area.
+    pub _reserved: u64,
+    pub _reserved_2: u32,
+    pub crc32: u32, // CRC32 of this data structure.
+}
+
+pub(crate) const JOURNAL_HEADER_OFFSET: usize =
+    SNAPSHOT_TABLE_OFFSET + core::mem::size_of::<SnapshotTable>();
+const _: () = assert!(JOURNAL_HEADER_OFFSET + core::mem::size_of::<JournalHeader>() <= 2048);
+
+impl JournalHeader {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as usize as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    pub fn set_crc32(&mut self) {
+        if self.start == 0 {
+            *self = unsafe { core::mem::zeroed() };
+            return;
+        }
+        let bytes = &self.as_bytes()[0..(core::mem::size_of::<Self>() - 4)];
+        self.crc32 = crc32_hash(bytes);
+    }
+
+    pub fn validate(&self) -> Result<(), FsError> {
+        if self.start == 0 {
+            if self.as_bytes().iter().all(|b| *b == 0) {
+                return Ok(());
+            }
+            return Err(FsError::ValidationFailed);
+        }
+        crc32_verify(self.as_bytes())?;
+        if self.blocks < MIN_JOURNAL_BLOCKS || self.blocks > MAX_JOURNAL_BLOCKS {
+            return Err(FsError::ValidationFailed);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) const JOURNAL_MAGIC: u64 = 0x7e21_c4d8_36a9_5b0f; // Just a random number.
+
+// The journal area:
+// - the record block: a JournalRecord followed by JournalEntries
+// - the new contents of the blocks listed in the record, in order
+//
+// A valid record means a committed transaction, not yet (fully) written
+// to the blocks in place; all zeroes means there is nothing to replay.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct JournalRecord {
+    pub magic: u64,       // JOURNAL_MAGIC.
+    pub num_entries: u64, // The number of JournalEntries.
+    pub entries_crc32: u32,
+    pub _reserved: u32,
+    pub _reserved_2: u32,
+    pub crc32: u32, // CRC32 of this data structure.
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct JournalEntry {
+    pub block_no: u64, // Where the block goes.
+    pub crc32: u32,    // CRC32 of the block.
+    pub _reserved: u32,
+}
+
+pub(crate) const MAX_JOURNAL_ENTRIES: u64 = (BLOCK_SIZE
+    - core::mem::size_of::<JournalRecord>() as u64)
+    / core::mem::size_of::<JournalEntry>() as u64;
+
+impl JournalRecord {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const _ as usize as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+
+    fn entries_bytes(block: &Block, num_entries: u64) -> &[u8] {
+        let start = core::mem::size_of::<Self>();
+        let len = (num_entries as usize) * core::mem::size_of::<JournalEntry>();
+        &block.as_bytes()[start..(start + len)]
+    }
+
+    /// Seals the record in @block, which has its entries set.
+    pub fn set_crc32(block: &mut Block) {
+        let num_entries = unsafe { block.get::<Self>() }.num_entries;
+        let entries_crc32 = crc32_hash(Self::entries_bytes(block, num_entries));
+        let record = unsafe { block.get_mut::<Self>() };
+        record.entries_crc32 = entries_crc32;
+        let bytes = &record.as_bytes()[0..(core::mem::size_of::<Self>() - 4)];
+        record.crc32 = crc32_hash(bytes);
+    }
+
+    /// Whether @block has a valid record with at most @max_entries entries.
+    pub fn validate(block: &Block, max_entries: u64) -> bool {
+        let record = unsafe { block.get::<Self>() };
+        record.magic == JOURNAL_MAGIC
+            && crc32_verify(record.as_bytes()).is_ok()
+            && record.num_entries <= max_entries
+            && record.entries_crc32 == crc32_hash(Self::entries_bytes(block, record.num_entries))
+    }
+}
+
 #[derive(Clone, Copy)]
 #[repr(C, align(4096))]
 pub(crate) struct Block {
@@ -523,6 +870,28 @@ impl Block {
         }
     }
 
+    pub fn get_journal_entry(&self, idx: u64) -> &JournalEntry {
+        assert!(idx < MAX_JOURNAL_ENTRIES);
+        let offset = core::mem::size_of::<JournalRecord>()
+            + (idx as usize) * core::mem::size_of::<JournalEntry>();
+        unsafe {
+            ((self.bytes.as_ptr() as usize + offset) as *const JournalEntry)
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    pub fn get_journal_entry_mut(&mut self, idx: u64) -> &mut JournalEntry {
+        assert!(idx < MAX_JOURNAL_ENTRIES);
+        let offset = core::mem::size_of::<JournalRecord>()
+            + (idx as usize) * core::mem::size_of::<JournalEntry>();
+        unsafe {
+            ((self.bytes.as_ptr() as usize + offset) as *mut JournalEntry)
+                .as_mut()
+                .unwrap_unchecked()
+        }
+    }
+
     pub fn get_datablock_no_in_meta(&self, data_block_idx: u64) -> u64 {
         assert!(data_block_idx <= MAX_LINKS_IN_META_BLOCK);
         let offset = core::mem::size_of::<EntryMetadata>() + ((data_block_idx as usize) << 3);
@@ -565,7 +934,10 @@ impl Superblock {
             fbh.validate()?;
         }
 
-        Ok(Self { block })
+        let superblock = Self { block };
+        superblock.snapshot_table().validate()?;
+        superblock.journal_header().validate()?;
+        Ok(superblock)
     }
 
     pub fn header(&self) -> &SuperblockHeader {
@@ -576,6 +948,42 @@ impl Superblock {
         unsafe { self.block.get_mut::<SuperblockHeader>() }
     }
 
+    pub fn snapshot_table(&self) -> &SnapshotTable {
+        unsafe {
+            ((self.block.as_bytes().as_ptr() as usize + SNAPSHOT_TABLE_OFFSET)
+                as *const SnapshotTable)
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    pub fn snapshot_table_mut(&mut self) -> &mut SnapshotTable {
+        unsafe {
+            ((self.block.as_bytes().as_ptr() as usize + SNAPSHOT_TABLE_OFFSET)
+                as *mut SnapshotTable)
+                .as_mut()
+                .unwrap_unchecked()
+        }
+    }
+
+    pub fn journal_header(&self) -> &JournalHeader {
+        unsafe {
+            ((self.block.as_bytes().as_ptr() as usize + JOURNAL_HEADER_OFFSET)
+                as *const JournalHeader)
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    pub fn journal_header_mut(&mut self) -> &mut JournalHeader {
+        unsafe {
+            ((self.block.as_bytes().as_ptr() as usize + JOURNAL_HEADER_OFFSET)
+                as *mut JournalHeader)
+                .as_mut()
+                .unwrap_unchecked()
+        }
+    }
+
     pub fn ___as_bytes(&self) -> &[u8] {
         self.block.as_bytes()
     }
@@ -15,7 +15,6 @@
 //! 
 //! TODO:
 //! 
-//! * crash recovery
 //! * timestamps
 //! * async API
 //! 
@@ -30,7 +29,9 @@ pub mod file_block_device;
 
 mod block_cache;
 mod fs_sync;
+mod journal;
 mod layout;
+mod snapshot;
 
 #[cfg(test)]
 extern crate std;
@@ -40,6 +41,7 @@ mod tests;
 
 pub use fs_sync::*;
 pub use layout::*;
+pub use snapshot::{SnapshotDevice, SnapshotInfo};
 
 pub const BLOCK_SIZE: u64 = 4096;
 
@@ -49,6 +51,24 @@ pub const BLOCK_SIZE: u64 = 4096;
 pub const MAX_DIR_ENTRIES: u64 = 65536;
 pub const MAX_FILE_SIZE: u64 = MAX_BYTES_LIST_OF_LISTS_BLOCKS; // ~500G.
 
+// Symlink targets are stored in the metadata block of the link.
+pub const MAX_SYMLINK_LEN: u64 = 2048;
+const _: () = assert!(MAX_SYMLINK_LEN <= MAX_BYTES_IN_META_BLOCK);
+
+// Extended attributes of an entry share a single block, so the total
+// size of their names and values is limited too.
+pub const MAX_XATTR_NAME_LEN: u64 = 255;
+
+// Snapshots are listed in the first block of the volume.
+pub const MAX_SNAPSHOTS: u64 = 14;
+pub const MAX_SNAPSHOT_NAME_LEN: u64 = 64;
+
+// The journal holds a single transaction: the blocks it changes (at most
+// MAX_JOURNAL_BLOCKS - 1), and the record of them.
+pub const MIN_JOURNAL_BLOCKS: u64 = 16;
+pub const MAX_JOURNAL_BLOCKS: u64 = MAX_JOURNAL_ENTRIES + 1;
+pub const DEFAULT_JOURNAL_BLOCKS: u64 = 64;
+
 /// See <https://en.wikipedia.org/wiki/Partition_type>.
 /// We use an arbitrary unused number here.
 pub const PARTITION_ID: u8 = 0x2d;
@@ -64,6 +84,12 @@ pub trait SyncBlockDevice {
 
     /// Write a single block. Same alignment requirements as in read_block.
     fn write_block(&mut self, block_no: u64, buf: &[u8]) -> Result<(), FsError>;
+
+    /// Make all completed writes durable. Devices that don't cache
+    /// writes have nothing to do.
+    fn flush(&mut self) -> Result<(), FsError> {
+        Ok(())
+    }
 }
 
 /// Initializes the block device so that it has an SFFS with a single/empty root dir.
@@ -74,6 +100,7 @@ pub fn format(block_device: &mut dyn SyncBlockDevice) -> Result<(), FsError> {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FsError {
     AlreadyExists,
+    Busy,
     FsFull,
     InvalidArgument,
     IoError,
@@ -83,3 +110,45 @@ pub enum FsError {
     Utf8Error,
     ValidationFailed,
 }
+
+/// An inconsistency found by SyncFileSystem::check().
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckIssue {
+    /// A transaction was in progress: the volume was not unmounted cleanly.
+    Unclean { txn_type: u32 },
+    /// The metadata of an entry in directory parent is corrupted (or the
+    /// directory entry itself is).
+    BadEntry { id: EntryId, parent: EntryId },
+    /// The link count of a file does not match the number of directory
+    /// entries pointing at it.
+    LinkCount {
+        id: EntryId,
+        links: u32,
+        references: u32,
+    },
+    /// The parent_id of an entry is not the directory it is in.
+    WrongParent {
+        id: EntryId,
+        parent_id: EntryId,
+        actual: EntryId,
+    },
+    /// A directory is pointed at by more than one directory entry; the
+    /// one in parent is extra.
+    DirectoryLinked { id: EntryId, parent: EntryId },
+    /// An entry refers to a block outside of the used area of the volume.
+    BadBlock { id: EntryId, block_no: u64 },
+    /// An entry refers to a block already used elsewhere.
+    CrossLinked { id: EntryId, block_no: u64 },
+    /// The extended attributes of an entry are corrupted.
+    BadXattrs { id: EntryId },
+    /// An entry has a generation the superblock has not reached yet.
+    Generation { id: EntryId, generation: u64 },
+    /// The freelist has a bad or repeated block.
+    BadFreeList { block_no: u64 },
+    /// A block on the freelist is in use.
+    FreeBlockInUse { block_no: u64 },
+    /// Blocks neither in use nor free.
+    OrphanedBlocks { count: u64 },
+    /// The number of free blocks in the superblock is wrong.
+    FreeCount { free_blocks: u64, actual: u64 },
+}
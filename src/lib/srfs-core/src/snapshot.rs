@@ -0,0 +1,306 @@
+// Snapshots: read-only views of the volume as it was at some point.
+//
+// Snapshots are copy-before-write: the volume keeps changing its blocks in
+// place, but before a block the snapshot can see (one below the snapshot's
+// watermark, i.e. in use when the snapshot was taken) is first overwritten,
+// its old contents are copied into the snapshot's area, and the copy is
+// recorded in the snapshot's map. A snapshot reads the copy of a block if
+// there is one, and the block itself otherwise.
+//
+// Snapshot areas are carved from the end of the volume, which shrinks
+// accordingly, and are listed in the snapshot table in the first block of
+// the volume (see SnapshotTable and SnapshotHeader). A snapshot whose area
+// fills up is invalidated: it can only be deleted. The space of a deleted
+// snapshot returns to the volume once the snapshots taken after it (which
+// are below it) are deleted as well.
+
+use core::cell::RefCell;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::*;
+
+const MAP_ENTRIES_PER_BLOCK: u64 = BLOCK_SIZE / 8;
+
+/// A snapshot of the volume, as listed by SyncFileSystem::snapshots().
+#[derive(Clone, Debug)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created: Timestamp,
+    /// The size of the snapshot's area, in blocks.
+    pub blocks: u64,
+    /// The number of blocks of the area in use.
+    pub used_blocks: u64,
+    /// False if the snapshot ran out of space: it can only be deleted.
+    pub valid: bool,
+}
+
+pub(crate) struct Snapshot {
+    area_start: u64,
+    header: SnapshotHeader,
+    map: BTreeMap<u64, u64>, // block_no => its copy.
+}
+
+impl Snapshot {
+    pub(crate) fn area_start(&self) -> u64 {
+        self.area_start
+    }
+
+    fn valid(&self) -> bool {
+        (self.header.flags & SNAPSHOT_FLAG_INVALID) == 0
+    }
+
+    // The number of copies the area can hold.
+    fn capacity(&self) -> u64 {
+        let copy_blocks = self.header.area_blocks - 1 - self.header.map_blocks;
+        copy_blocks.min(self.header.map_blocks * MAP_ENTRIES_PER_BLOCK)
+    }
+
+    fn map_block_no(&self, idx: u64) -> u64 {
+        self.area_start + 1 + idx / MAP_ENTRIES_PER_BLOCK
+    }
+
+    fn copy_block_no(&self, idx: u64) -> u64 {
+        self.area_start + 1 + self.header.map_blocks + idx
+    }
+
+    fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            name: self.header.name().into(),
+            created: self.header.created,
+            blocks: self.header.area_blocks,
+            used_blocks: 1 + self.header.map_blocks + self.map.len() as u64,
+            valid: self.valid(),
+        }
+    }
+
+    fn write_header(
+        &self,
+        block_device: &mut dyn SyncBlockDevice,
+        scratch: &mut Block,
+    ) -> Result<(), FsError> {
+        *scratch = Block::new_zeroed();
+        *unsafe { scratch.get_mut::<SnapshotHeader>() } = self.header;
+        block_device.write_block(self.area_start, scratch.as_bytes())
+    }
+}
+
+// The snapshots of a volume, shared with their SnapshotDevices.
+pub(crate) struct Snapshots {
+    snapshots: Vec<Rc<RefCell<Snapshot>>>,
+    scratch: Box<Block>,
+}
+
+impl Snapshots {
+    pub(crate) fn load(
+        block_device: &mut dyn SyncBlockDevice,
+        table: &SnapshotTable,
+    ) -> Result<Self, FsError> {
+        let mut scratch = Box::new(Block::new_uninit());
+        let mut snapshots = Vec::new();
+
+        for area_start in table.areas.iter().copied().filter(|a| *a != 0) {
+            block_device.read_block(area_start, scratch.as_bytes_mut())?;
+            let header = *unsafe { scratch.get::<SnapshotHeader>() };
+            header.validate()?;
+            if area_start + header.area_blocks > table.total_blocks {
+                return Err(FsError::ValidationFailed);
+            }
+
+            let mut snapshot = Snapshot {
+                area_start,
+                header,
+                map: BTreeMap::new(),
+            };
+            'map: for map_idx in 0..header.map_blocks {
+                block_device.read_block(area_start + 1 + map_idx, scratch.as_bytes_mut())?;
+                for pos in 0..MAP_ENTRIES_PER_BLOCK {
+                    let origin = scratch.get_datablock_no_in_link(pos);
+                    if origin == 0 {
+                        break 'map;
+                    }
+                    let idx = snapshot.map.len() as u64;
+                    if idx == snapshot.capacity() || origin > header.watermark {
+                        return Err(FsError::ValidationFailed);
+                    }
+                    let copy = snapshot.copy_block_no(idx);
+                    snapshot.map.insert(origin - 1, copy);
+                }
+            }
+
+            snapshots.push(Rc::new(RefCell::new(snapshot)));
+        }
+
+        Ok(Self { snapshots, scratch })
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub(crate) fn list(&self) -> Vec<SnapshotInfo> {
+        self.snapshots.iter().map(|s| s.borrow().info()).collect()
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<Rc<RefCell<Snapshot>>> {
+        self.snapshots
+            .iter()
+            .find(|s| s.borrow().header.name() == name)
+            .cloned()
+    }
+
+    // Set up a new snapshot in the area. The caller then records the area
+    // in the snapshot table: saving the first block preserves it.
+    pub(crate) fn create(
+        &mut self,
+        block_device: &mut dyn SyncBlockDevice,
+        name: &str,
+        area_start: u64,
+        area_blocks: u64,
+        watermark: u64,
+    ) -> Result<(), FsError> {
+        let map_blocks = (area_blocks - 1).div_ceil(MAP_ENTRIES_PER_BLOCK + 1);
+        let mut header = SnapshotHeader {
+            magic: SNAPSHOT_MAGIC,
+            created: Timestamp::now(),
+            watermark,
+            area_blocks,
+            map_blocks,
+            name: [0; MAX_SNAPSHOT_NAME_LEN as usize],
+            name_len: name.len() as u32,
+            flags: 0,
+            _reserved: 0,
+            crc32: 0,
+        };
+        header.name[0..name.len()].copy_from_slice(name.as_bytes());
+        header.set_crc32();
+
+        *self.scratch = Block::new_zeroed();
+        for map_idx in 0..map_blocks {
+            block_device.write_block(area_start + 1 + map_idx, self.scratch.as_bytes())?;
+        }
+        let snapshot = Snapshot {
+            area_start,
+            header,
+            map: BTreeMap::new(),
+        };
+        snapshot.write_header(block_device, &mut self.scratch)?;
+        block_device.flush()?;
+
+        self.snapshots.push(Rc::new(RefCell::new(snapshot)));
+        Ok(())
+    }
+
+    // Forget the snapshot, unless it is in use by a SnapshotDevice.
+    pub(crate) fn remove(&mut self, name: &str) -> Result<Rc<RefCell<Snapshot>>, FsError> {
+        let idx = self
+            .snapshots
+            .iter()
+            .position(|s| s.borrow().header.name() == name)
+            .ok_or(FsError::NotFound)?;
+        if Rc::strong_count(&self.snapshots[idx]) > 1 {
+            return Err(FsError::Busy);
+        }
+        Ok(self.snapshots.remove(idx))
+    }
+
+    // Called before block_no is written to: preserves the current contents
+    // of the block in the snapshots that see it and don't have a copy yet.
+    pub(crate) fn before_write(
+        &mut self,
+        block_device: &mut dyn SyncBlockDevice,
+        block_no: u64,
+    ) -> Result<(), FsError> {
+        for snapshot in &self.snapshots {
+            let mut snapshot = snapshot.borrow_mut();
+            if !snapshot.valid()
+                || block_no >= snapshot.header.watermark
+                || snapshot.map.contains_key(&block_no)
+            {
+                continue;
+            }
+
+            let idx = snapshot.map.len() as u64;
+            if idx == snapshot.capacity() {
+                log::error!(
+                    "snapshot '{}' is out of space: invalidating",
+                    snapshot.header.name()
+                );
+                snapshot.header.flags |= SNAPSHOT_FLAG_INVALID;
+                snapshot.header.set_crc32();
+                snapshot.write_header(block_device, &mut self.scratch)?;
+                block_device.flush()?;
+                continue;
+            }
+
+            // The copy must be durable before the map points at it, and the
+            // map before the block is overwritten.
+            let copy = snapshot.copy_block_no(idx);
+            block_device.read_block(block_no, self.scratch.as_bytes_mut())?;
+            block_device.write_block(copy, self.scratch.as_bytes())?;
+            block_device.flush()?;
+
+            let map_block_no = snapshot.map_block_no(idx);
+            block_device.read_block(map_block_no, self.scratch.as_bytes_mut())?;
+            self.scratch
+                .set_datablock_no_in_link(idx % MAP_ENTRIES_PER_BLOCK, block_no + 1);
+            block_device.write_block(map_block_no, self.scratch.as_bytes())?;
+            block_device.flush()?;
+
+            snapshot.map.insert(block_no, copy);
+        }
+
+        Ok(())
+    }
+}
+
+/// A read-only block device presenting a snapshot: open it with
+/// SyncFileSystem::open_fs(). See SyncFileSystem::snapshot_device().
+///
+/// Reads fail with FsError::IoError if the snapshot is invalidated.
+pub struct SnapshotDevice {
+    block_device: Box<dyn SyncBlockDevice>,
+    snapshot: Rc<RefCell<Snapshot>>,
+}
+
+impl SnapshotDevice {
+    pub(crate) fn new(
+        block_device: Box<dyn SyncBlockDevice>,
+        snapshot: Rc<RefCell<Snapshot>>,
+    ) -> Self {
+        Self {
+            block_device,
+            snapshot,
+        }
+    }
+}
+
+impl SyncBlockDevice for SnapshotDevice {
+    fn num_blocks(&self) -> u64 {
+        self.block_device.num_blocks()
+    }
+
+    fn read_block(&mut self, block_no: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        let snapshot = self.snapshot.borrow();
+        if !snapshot.valid() {
+            return Err(FsError::IoError);
+        }
+
+        let from = snapshot.map.get(&block_no).copied().unwrap_or(block_no);
+        self.block_device.read_block(from, buf)?;
+        if block_no == 0 {
+            // Snapshots have neither snapshots nor a journal.
+            let end = JOURNAL_HEADER_OFFSET + core::mem::size_of::<JournalHeader>();
+            buf[SNAPSHOT_TABLE_OFFSET..end].fill(0);
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, _block_no: u64, _buf: &[u8]) -> Result<(), FsError> {
+        Err(FsError::InvalidArgument)
+    }
+}
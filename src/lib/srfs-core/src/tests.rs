@@ -1,6 +1,8 @@
 extern crate std;
 
 use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
 use std::println;
 use std::format;
 
@@ -109,10 +111,6 @@ fn basic() {
     for idx in 0..10000_u64 {
         let buf =
             unsafe { core::slice::from_raw_parts(&idx as *const u64 as usize as *const u8, 8) };
-        assert_eq!(
-            fs.write(file, idx * 8 + 2, buf).err().unwrap(),
-            FsError::InvalidArgument
-        );
         assert_eq!(8, fs.write(file, idx * 8, buf).unwrap());
 
         // Read it back.
@@ -133,6 +131,664 @@ fn basic() {
         assert_eq!(idx, out);
     }
 
+    // Writing past EOF leaves a hole that reads as zeros.
+    assert_eq!(80000, fs.get_file_size(file).unwrap());
+    assert_eq!(5, fs.write(file, 80002, b"hello").unwrap());
+    assert_eq!(80007, fs.get_file_size(file).unwrap());
+    let mut gap = [0xff_u8; 7];
+    assert_eq!(7, fs.read(file, 80000, &mut gap).unwrap());
+    assert_eq!(&gap, b"\0\0hello");
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn symlinks() {
+    const NUM_BLOCKS: u64 = 64;
+    let path = std::env::temp_dir().join("fs_dev_symlinks");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    let dir = fs.add_directory(root, "dir").unwrap();
+    let file = fs.add_file(dir, "file").unwrap();
+    assert!(!fs.stat(file).unwrap().is_symlink());
+    assert_eq!(
+        fs.read_symlink(file).err().unwrap(),
+        FsError::InvalidArgument
+    );
+
+    assert_eq!(
+        fs.add_symlink(root, "empty", "").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    let long = "x".repeat(crate::MAX_SYMLINK_LEN as usize + 1);
+    assert_eq!(
+        fs.add_symlink(root, "long", &long).err().unwrap(),
+        FsError::InvalidArgument
+    );
+    assert_eq!(
+        fs.add_symlink(dir, "file", "/dir").err().unwrap(),
+        FsError::AlreadyExists
+    );
+
+    // Symlinks are files with a flag; a dangling target is fine.
+    let link = fs.add_symlink(root, "link", "dir/file").unwrap();
+    let dangling = fs.add_symlink(dir, "dangling", "../nowhere").unwrap();
+    assert_eq!(crate::EntryKind::File, link.kind());
+    let attr = fs.stat(link).unwrap();
+    assert!(attr.is_symlink());
+    assert_eq!(8, attr.size);
+    assert_eq!("dir/file", fs.read_symlink(link).unwrap().as_str());
+    assert_eq!("../nowhere", fs.read_symlink(dangling).unwrap().as_str());
+
+    // The target survives reopening the volume.
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert_eq!("dir/file", fs.read_symlink(link).unwrap().as_str());
+
+    // Links are removed like files.
+    fs.set_file_size(dangling, 0).unwrap();
+    fs.remove(dangling).unwrap();
+    fs.set_file_size(link, 0).unwrap();
+    fs.remove(link).unwrap();
+    assert_eq!(1, fs.get_num_entries(root).unwrap());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn hard_links() {
+    const NUM_BLOCKS: u64 = 64;
+    let path = std::env::temp_dir().join("fs_dev_hard_links");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    let dir = fs.add_directory(root, "dir").unwrap();
+    let file = fs.add_file(root, "file").unwrap();
+    assert_eq!(1, fs.stat(file).unwrap().links);
+    assert_eq!(5, fs.write(file, 0, "hello".as_bytes()).unwrap());
+
+    // Directories cannot be linked; names must be new.
+    assert_eq!(
+        fs.add_link(dir, root, "dir2").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    assert_eq!(
+        fs.add_link(file, root, "dir").err().unwrap(),
+        FsError::AlreadyExists
+    );
+
+    fs.add_link(file, dir, "link").unwrap();
+    fs.add_link(file, root, "link").unwrap();
+    assert_eq!(3, fs.stat(file).unwrap().links);
+    let entry = fs.get_directory_entry_by_name(dir, "link").unwrap();
+    assert_eq!(file, entry.id);
+    assert!(fs.check().unwrap().is_empty());
+
+    // A hard-linked file has no single parent.
+    assert_eq!(fs.get_parent(file).err().unwrap(), FsError::InvalidArgument);
+    assert_eq!(fs.remove(file).err().unwrap(), FsError::InvalidArgument);
+
+    // Removing a link keeps the data, until the last link goes.
+    fs.remove_link(root, file).unwrap();
+    assert_eq!(2, fs.stat(file).unwrap().links);
+    assert_eq!(
+        fs.get_directory_entry_by_name(root, "file").err().unwrap(),
+        FsError::NotFound
+    );
+    assert_eq!(fs.remove_link(root, dir).err().unwrap(), FsError::TooLarge);
+    fs.move_rename_from(dir, file, root, "moved").unwrap();
+    fs.remove_link(root, file).unwrap();
+    assert_eq!(1, fs.stat(file).unwrap().links);
+
+    // The count survives reopening the volume.
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert_eq!(1, fs.stat(file).unwrap().links);
+    assert!(fs.check().unwrap().is_empty());
+    let mut buf = [0_u8; 8];
+    assert_eq!(5, fs.read(file, 0, &mut buf).unwrap());
+    assert_eq!("hello".as_bytes(), &buf[0..5]);
+
+    assert_eq!(fs.remove_link(root, file).err().unwrap(), FsError::TooLarge);
+    fs.set_file_size(file, 0).unwrap();
+    assert_eq!(fs.remove_link(dir, file).err().unwrap(), FsError::NotFound);
+    fs.remove_link(root, file).unwrap();
+    assert_eq!(1, fs.get_num_entries(root).unwrap());
+    assert!(fs.check().unwrap().is_empty());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn replace() {
+    const NUM_BLOCKS: u64 = 256;
+    let path = std::env::temp_dir().join("fs_dev_replace");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    let dir = fs.add_directory(root, "dir").unwrap();
+    let empty_blocks = fs.empty_blocks();
+
+    // A file with data blocks, replaced across directories.
+    let big = fs.add_file(dir, "config").unwrap();
+    let buf = [7_u8; 4096];
+    for idx in 0..8 {
+        assert_eq!(4096, fs.write(big, idx * 4096, &buf).unwrap());
+    }
+    let file = fs.add_file(root, "config.new").unwrap();
+    assert_eq!(5, fs.write(file, 0, "hello".as_bytes()).unwrap());
+    fs.move_replace(root, file, dir, "config").unwrap();
+    assert_eq!(
+        file,
+        fs.get_directory_entry_by_name(dir, "config").unwrap().id
+    );
+    assert_eq!(Some(dir), fs.get_parent(file).unwrap());
+    assert_eq!(1, fs.get_num_entries(dir).unwrap());
+    assert_eq!(1, fs.get_num_entries(root).unwrap());
+    assert_eq!(empty_blocks - 1, fs.empty_blocks());
+    assert!(fs.check().unwrap().is_empty());
+
+    // Within a directory with entries in data blocks.
+    let mut files = Vec::new();
+    for idx in 0..40 {
+        files.push(fs.add_file(dir, format!("f{}", idx).as_str()).unwrap());
+    }
+    fs.move_replace(dir, files[3], dir, "f39").unwrap();
+    fs.move_replace(dir, files[38], dir, "f20").unwrap();
+    assert_eq!(39, fs.get_num_entries(dir).unwrap());
+    assert_eq!(
+        files[3],
+        fs.get_directory_entry_by_name(dir, "f39").unwrap().id
+    );
+    assert_eq!(
+        files[38],
+        fs.get_directory_entry_by_name(dir, "f20").unwrap().id
+    );
+    assert_eq!(
+        fs.get_directory_entry_by_name(dir, "f3").err().unwrap(),
+        FsError::NotFound
+    );
+    assert!(fs.check().unwrap().is_empty());
+
+    // A missing target is a plain rename; the same file is a no-op.
+    fs.move_replace(dir, files[0], root, "f0").unwrap();
+    fs.add_link(files[0], dir, "f0link").unwrap();
+    fs.move_replace(dir, files[0], root, "f0").unwrap();
+    assert_eq!(2, fs.stat(files[0]).unwrap().links);
+
+    // A hard-linked target only loses a link.
+    fs.move_replace(dir, files[1], dir, "f0link").unwrap();
+    assert_eq!(1, fs.stat(files[0]).unwrap().links);
+    assert!(fs.check().unwrap().is_empty());
+
+    // Directories replace empty directories only, and not files.
+    let sub = fs.add_directory(root, "sub").unwrap();
+    let sub2 = fs.add_directory(root, "sub2").unwrap();
+    fs.add_file(sub2, "file").unwrap();
+    assert_eq!(
+        fs.move_replace(root, sub, root, "sub2").err().unwrap(),
+        FsError::TooLarge
+    );
+    assert_eq!(
+        fs.move_replace(root, sub, root, "f0").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    assert_eq!(
+        fs.move_replace(root, files[0], root, "sub").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    fs.add_directory(dir, "empty").unwrap();
+    fs.move_replace(root, sub2, dir, "empty").unwrap();
+    assert_eq!(
+        sub2,
+        fs.get_directory_entry_by_name(dir, "empty").unwrap().id
+    );
+    assert_eq!(Some(dir), fs.get_parent(sub2).unwrap());
+    assert!(fs.check().unwrap().is_empty());
+
+    // A directory can't be moved into itself or its subdirectories.
+    let inner = fs.add_directory(sub, "inner").unwrap();
+    fs.add_directory(inner, "empty").unwrap();
+    assert_eq!(
+        fs.move_replace(root, sub, inner, "empty").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    assert_eq!(
+        fs.move_replace(root, sub, sub, "empty").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    assert_eq!(
+        fs.move_rename_from(root, sub, inner, "sub").err().unwrap(),
+        FsError::InvalidArgument
+    );
+    assert_eq!(Some(root), fs.get_parent(sub).unwrap());
+    assert_eq!(sub, fs.get_directory_entry_by_name(root, "sub").unwrap().id);
+    assert!(fs.check().unwrap().is_empty());
+
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert!(fs.check().unwrap().is_empty());
+    let mut buf = [0_u8; 8];
+    assert_eq!(5, fs.read(file, 0, &mut buf).unwrap());
+    assert_eq!("hello".as_bytes(), &buf[0..5]);
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn user_data() {
+    const NUM_BLOCKS: u64 = 16;
+    let path = std::env::temp_dir().join("fs_dev_user_data");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    let file = fs.add_file(root, "file").unwrap();
+    assert_eq!([0; 4], fs.stat(file).unwrap().user_data);
+    fs.set_user_data(file, [1, 2, 3, 4]).unwrap();
+    fs.set_user_data(root, [5, 6, 7, 8]).unwrap();
+
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert_eq!([1, 2, 3, 4], fs.stat(file).unwrap().user_data);
+    assert_eq!([5, 6, 7, 8], fs.stat(root).unwrap().user_data);
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn xattrs() {
+    const NUM_BLOCKS: u64 = 16;
+    let path = std::env::temp_dir().join("fs_dev_xattrs");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+    let empty_blocks = fs.empty_blocks();
+
+    let file = fs.add_file(root, "file").unwrap();
+    let dir = fs.add_directory(root, "dir").unwrap();
+    assert!(fs.list_xattrs(file).unwrap().is_empty());
+    assert_eq!(
+        fs.get_xattr(file, "user.foo").err().unwrap(),
+        FsError::NotFound
+    );
+    assert_eq!(
+        fs.remove_xattr(file, "user.foo").err().unwrap(),
+        FsError::NotFound
+    );
+
+    fs.set_xattr(file, "user.foo", b"foo").unwrap();
+    fs.set_xattr(file, "user.bar", b"").unwrap();
+    fs.set_xattr(dir, "security.label", b"label").unwrap();
+    assert_eq!(empty_blocks - 4, fs.empty_blocks());
+    fs.set_xattr(file, "user.foo", b"foo2").unwrap();
+    assert_eq!(empty_blocks - 4, fs.empty_blocks());
+
+    // The attributes share a single block.
+    assert_eq!(
+        fs.set_xattr(file, "user.big", &[0; 4096]).err().unwrap(),
+        FsError::TooLarge
+    );
+    assert_eq!(
+        fs.set_xattr(file, "", b"").err().unwrap(),
+        FsError::InvalidArgument
+    );
+
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert_eq!(
+        alloc::vec!["user.foo", "user.bar"],
+        fs.list_xattrs(file).unwrap()
+    );
+    assert_eq!(b"foo2".to_vec(), fs.get_xattr(file, "user.foo").unwrap());
+    assert!(fs.get_xattr(file, "user.bar").unwrap().is_empty());
+    assert_eq!(
+        b"label".to_vec(),
+        fs.get_xattr(dir, "security.label").unwrap()
+    );
+
+    // Removing the last attribute frees the block.
+    fs.remove_xattr(file, "user.foo").unwrap();
+    assert_eq!(empty_blocks - 4, fs.empty_blocks());
+    fs.remove_xattr(file, "user.bar").unwrap();
+    assert_eq!(empty_blocks - 3, fs.empty_blocks());
+
+    // Removing or replacing an entry frees its attributes.
+    fs.set_xattr(file, "user.foo", b"foo").unwrap();
+    let other = fs.add_file(root, "other").unwrap();
+    fs.move_replace(root, other, root, "file").unwrap();
+    assert_eq!(empty_blocks - 3, fs.empty_blocks());
+    fs.remove(dir).unwrap();
+    assert_eq!(empty_blocks - 1, fs.empty_blocks());
+    fs.remove(other).unwrap();
+    assert_eq!(empty_blocks, fs.empty_blocks());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DeviceOp {
+    Write(u64),
+    Flush,
+}
+
+// Records writes and flushes, to check their order.
+struct LoggingBlockDevice {
+    inner: FileBlockDevice,
+    log: std::sync::Arc<std::sync::Mutex<Vec<DeviceOp>>>,
+}
+
+impl crate::SyncBlockDevice for LoggingBlockDevice {
+    fn num_blocks(&self) -> u64 {
+        self.inner.num_blocks()
+    }
+
+    fn read_block(&mut self, block_no: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        self.inner.read_block(block_no, buf)
+    }
+
+    fn write_block(&mut self, block_no: u64, buf: &[u8]) -> Result<(), FsError> {
+        self.log.lock().unwrap().push(DeviceOp::Write(block_no));
+        self.inner.write_block(block_no, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), FsError> {
+        self.log.lock().unwrap().push(DeviceOp::Flush);
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn flush_ordering() {
+    const NUM_BLOCKS: u64 = 16;
+    let path = std::env::temp_dir().join("fs_dev_flush_ordering");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = FileBlockDevice::create(&path, NUM_BLOCKS).unwrap();
+    crate::fs_sync::format(&mut bd).unwrap();
+    let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let bd = Box::new(LoggingBlockDevice {
+        inner: bd,
+        log: log.clone(),
+    });
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    // A TXN: the superblock marks it, then the changes, then the commit.
+    let file = fs.add_file(root, "file").unwrap();
+    let ops = core::mem::take(&mut *log.lock().unwrap());
+    assert_eq!(&[DeviceOp::Write(0), DeviceOp::Flush], &ops[..2]);
+    assert_eq!(
+        &[DeviceOp::Flush, DeviceOp::Write(0)],
+        &ops[(ops.len() - 2)..]
+    );
+    assert!(ops[2..(ops.len() - 2)].contains(&DeviceOp::Write(file.block_no)));
+
+    // Not a TXN: no flushes until asked.
+    assert_eq!(5, fs.write(file, 0, b"hello").unwrap());
+    assert!(!log.lock().unwrap().contains(&DeviceOp::Flush));
+    fs.flush().unwrap();
+    assert_eq!(Some(&DeviceOp::Flush), log.lock().unwrap().last());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+fn read_all(fs: &mut SyncFileSystem, file: crate::EntryId, offset: u64, len: usize) -> Vec<u8> {
+    let mut buf = std::vec![0xff_u8; len];
+    let mut done = 0;
+    while done < len {
+        let read = fs
+            .read(file, offset + done as u64, &mut buf[done..])
+            .unwrap();
+        assert!(read > 0);
+        done += read;
+    }
+    buf
+}
+
+fn write_all(fs: &mut SyncFileSystem, file: crate::EntryId, offset: u64, bytes: &[u8]) {
+    let mut done = 0;
+    while done < bytes.len() {
+        done += fs
+            .write(file, offset + done as u64, &bytes[done..])
+            .unwrap();
+    }
+}
+
+#[test]
+fn sparse_files() {
+    const NUM_BLOCKS: u64 = 1024;
+    const ONE_GB: u64 = 1024 * 1024 * 1024;
+    let path = std::env::temp_dir().join("fs_dev_sparse_files");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+    let file = fs.add_file(root, "file").unwrap();
+    let empty_blocks = fs.empty_blocks();
+
+    // A hole in the meta block.
+    assert_eq!(5, fs.write(file, 100, b"hello").unwrap());
+    assert_eq!(105, fs.get_file_size(file).unwrap());
+    let bytes = read_all(&mut fs, file, 0, 105);
+    assert!(bytes[0..100].iter().all(|b| *b == 0));
+    assert_eq!(b"hello", &bytes[100..]);
+
+    // Writes far apart, through all the layouts: only the written blocks
+    // and the link blocks listing them are allocated.
+    const OFFSETS: [u64; 4] = [10_000, 3 << 20, ONE_GB + 12345, 3 * ONE_GB];
+    for offset in OFFSETS {
+        write_all(&mut fs, file, offset, &offset.to_ne_bytes());
+    }
+    assert_eq!(3 * ONE_GB + 8, fs.get_file_size(file).unwrap());
+    // Data: 5 + 1 moved out of the meta block. Links: 3. Lists of links: 3.
+    assert_eq!(empty_blocks - 12, fs.empty_blocks());
+    for offset in OFFSETS {
+        assert_eq!(
+            &offset.to_ne_bytes(),
+            &read_all(&mut fs, file, offset, 8)[..]
+        );
+    }
+    assert_eq!(b"hello", &read_all(&mut fs, file, 100, 5)[..]);
+    for offset in [200, 16384, 2 << 20, 2 * ONE_GB, 3 * ONE_GB - 8192] {
+        assert!(read_all(&mut fs, file, offset, 8192)
+            .iter()
+            .all(|b| *b == 0));
+    }
+    assert_eq!(empty_blocks - 12, fs.empty_blocks()); // Reads allocate nothing.
+
+    // Punching holes.
+    fs.punch_hole(file, 101, 2).unwrap();
+    assert_eq!(b"h\0\0lo", &read_all(&mut fs, file, 100, 5)[..]);
+    fs.punch_hole(file, 3 << 20, 4096).unwrap();
+    assert_eq!(empty_blocks - 11, fs.empty_blocks());
+    assert_eq!([0; 8], &read_all(&mut fs, file, 3 << 20, 8)[..]);
+    fs.punch_hole(file, 3 * ONE_GB, 100).unwrap(); // To the end of the file.
+    assert_eq!(empty_blocks - 10, fs.empty_blocks());
+    assert_eq!(3 * ONE_GB + 8, fs.get_file_size(file).unwrap());
+
+    // Preallocation.
+    fs.allocate(file, (3 << 20) + 100, 8192).unwrap();
+    assert_eq!(empty_blocks - 13, fs.empty_blocks());
+    assert!(read_all(&mut fs, file, 3 << 20, 16384)
+        .iter()
+        .all(|b| *b == 0));
+    fs.allocate(file, 3 * ONE_GB + 4096, 4096).unwrap();
+    assert_eq!(3 * ONE_GB + 8192, fs.get_file_size(file).unwrap());
+    assert_eq!(empty_blocks - 14, fs.empty_blocks());
+
+    // Truncation frees the blocks; growing again does not bring back old bytes.
+    fs.set_file_size(file, 5000).unwrap();
+    assert_eq!(empty_blocks - 1, fs.empty_blocks());
+    fs.set_file_size(file, 12000).unwrap();
+    assert!(read_all(&mut fs, file, 200, 11800).iter().all(|b| *b == 0));
+    write_all(&mut fs, file, 0, &[0xaa; 12000]);
+    fs.set_file_size(file, 5000).unwrap();
+    fs.set_file_size(file, 12000).unwrap();
+    assert!(read_all(&mut fs, file, 0, 5000).iter().all(|b| *b == 0xaa));
+    assert!(read_all(&mut fs, file, 5000, 7000).iter().all(|b| *b == 0));
+    write_all(&mut fs, file, 0, &[0xaa; 12000]);
+    fs.set_file_size(file, 100).unwrap();
+    fs.set_file_size(file, 12000).unwrap();
+    assert!(read_all(&mut fs, file, 100, 11900).iter().all(|b| *b == 0));
+
+    assert_eq!(
+        fs.set_file_size(file, crate::MAX_FILE_SIZE + 1)
+            .err()
+            .unwrap(),
+        FsError::TooLarge
+    );
+    fs.set_file_size(file, 0).unwrap();
+    fs.remove(file).unwrap();
+    assert_eq!(NUM_BLOCKS - 2, fs.empty_blocks());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn sparse_files_random() {
+    const NUM_BLOCKS: u64 = 2048;
+    const MAX_SIZE: u64 = 3 << 20; // Crosses MAX_BYTES_ONLY_DATA_BLOCKS.
+    let path = std::env::temp_dir().join("fs_dev_sparse_files_random");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+    let file = fs.add_file(root, "file").unwrap();
+
+    let mut rng = rand::thread_rng();
+    let mut model: Vec<u8> = Vec::new();
+    for step in 0..300 {
+        let offset = rng.gen_range(0..MAX_SIZE);
+        let len = rng.gen_range(1..=(MAX_SIZE - offset).min(20_000));
+        match rng.gen_range(0..4) {
+            0 => {
+                let bytes: Vec<u8> = (0..len).map(|_| rng.gen_range(1..=255)).collect();
+                write_all(&mut fs, file, offset, &bytes);
+                if model.len() < (offset + len) as usize {
+                    model.resize((offset + len) as usize, 0);
+                }
+                model[(offset as usize)..((offset + len) as usize)].copy_from_slice(&bytes);
+            }
+            1 => {
+                fs.set_file_size(file, offset).unwrap();
+                model.resize(offset as usize, 0);
+            }
+            2 => {
+                fs.punch_hole(file, offset, len).unwrap();
+                let end = model.len().min((offset + len) as usize);
+                if (offset as usize) < end {
+                    model[(offset as usize)..end].fill(0);
+                }
+            }
+            _ => {
+                fs.allocate(file, offset, len).unwrap();
+                if model.len() < (offset + len) as usize {
+                    model.resize((offset + len) as usize, 0);
+                }
+            }
+        }
+
+        assert_eq!(model.len() as u64, fs.get_file_size(file).unwrap());
+        let bytes = read_all(&mut fs, file, 0, model.len());
+        assert!(bytes == model, "step {}", step);
+    }
+
+    fs.set_file_size(file, 0).unwrap();
+    fs.remove(file).unwrap();
+    assert_eq!(NUM_BLOCKS - 2, fs.empty_blocks());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn direct_io() {
+    const NUM_BLOCKS: u64 = 256;
+    let path = std::env::temp_dir().join("fs_dev_direct_io");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+    let file = fs.add_file(root, "file").unwrap();
+
+    // Small files live in the meta block.
+    assert_eq!(5, fs.write_direct(file, 0, b"hello").unwrap());
+    let mut buf = [0_u8; 16];
+    assert_eq!(5, fs.read_direct(file, 0, &mut buf).unwrap());
+    assert_eq!(b"hello", &buf[..5]);
+
+    // Appends and holes go through write(), overwrites don't.
+    let mut model: Vec<u8> = (0..40_000_u32).map(|x| (x % 251) as u8).collect();
+    let mut done = 0;
+    while done < model.len() {
+        done += fs.write_direct(file, done as u64, &model[done..]).unwrap();
+    }
+    fs.set_file_size(file, 60_000).unwrap();
+    model.resize(60_000, 0);
+    assert_eq!(read_all(&mut fs, file, 0, 100), &model[..100]); // Cache a block.
+    for (offset, len) in [(0, 8192), (100, 10), (4000, 5000), (50_000, 10_000)] {
+        let bytes = std::vec![(offset % 7) as u8 + 1; len];
+        let mut done = 0;
+        while done < len {
+            done += fs
+                .write_direct(file, (offset + done) as u64, &bytes[done..])
+                .unwrap();
+        }
+        model[offset..(offset + len)].copy_from_slice(&bytes);
+    }
+    assert_eq!(60_000, fs.get_file_size(file).unwrap());
+
+    // Cached and direct reads see the same bytes.
+    assert!(read_all(&mut fs, file, 0, model.len()) == model);
+    let mut bytes = std::vec![0xff_u8; model.len()];
+    let mut done = 0;
+    while done < bytes.len() {
+        done += fs
+            .read_direct(file, done as u64, &mut bytes[done..])
+            .unwrap();
+    }
+    assert!(bytes == model);
+    assert_eq!(0, fs.read_direct(file, 60_000, &mut buf).unwrap());
+
     drop(fs);
     std::fs::remove_file(path.clone()).unwrap();
 }
@@ -416,3 +1072,497 @@ fn large_file() {
     drop(fs);
     std::fs::remove_file(path.clone()).unwrap();
 }
+
+fn read_raw_block(path: &std::path::Path, block_no: u64) -> crate::Block {
+    use crate::SyncBlockDevice;
+    let mut bd = FileBlockDevice::open(path).unwrap();
+    let mut block = crate::Block::new_zeroed();
+    bd.read_block(block_no, block.as_bytes_mut()).unwrap();
+    block
+}
+
+fn write_raw_block(path: &std::path::Path, block_no: u64, block: &crate::Block) {
+    use crate::SyncBlockDevice;
+    let mut bd = FileBlockDevice::open(path).unwrap();
+    bd.write_block(block_no, block.as_bytes()).unwrap();
+}
+
+#[test]
+fn check_and_repair() {
+    use crate::{CheckIssue, EntryMetadata, SuperblockHeader};
+
+    const NUM_BLOCKS: u64 = 256;
+    let path = std::env::temp_dir().join("fs_dev_check_and_repair");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    let dir = fs.add_directory(root, "dir").unwrap();
+    let file_a = fs.add_file(dir, "a").unwrap();
+    let file_b = fs.add_file(dir, "b").unwrap();
+    let file_c = fs.add_file(root, "c").unwrap();
+    write_all(&mut fs, file_a, 0, &[1_u8; 3 * BLOCK_SIZE as usize]);
+    write_all(&mut fs, file_b, 0, &[2_u8; 3 * BLOCK_SIZE as usize]);
+    fs.add_link(file_c, dir, "c2").unwrap();
+    assert!(fs.check().unwrap().is_empty());
+    assert!(fs.repair().unwrap().is_empty());
+    let empty_blocks = fs.empty_blocks();
+    drop(fs);
+
+    // Cross-link the second block of b to the first block of a.
+    let a_block_no = read_raw_block(&path, file_a.block_no).get_datablock_no_in_meta(0);
+    let mut block = read_raw_block(&path, file_b.block_no);
+    block.set_datablock_no_in_meta(1, a_block_no);
+    write_raw_block(&path, file_b.block_no, &block);
+
+    // One link too many for c.
+    let mut block = read_raw_block(&path, file_c.block_no);
+    let meta = unsafe { block.get_mut::<EntryMetadata>() };
+    meta.links += 1;
+    meta.set_crc32();
+    write_raw_block(&path, file_c.block_no, &block);
+
+    // Crash while adding a node: the new block is allocated, but not linked.
+    let mut block = read_raw_block(&path, 0);
+    let sbh = unsafe { block.get_mut::<SuperblockHeader>() };
+    sbh.txn_type = crate::TXN_TYPE_ADD_NODE;
+    sbh.txn_blocks_owner = root.block_no;
+    sbh.txn_meta_block = sbh.empty_area_start;
+    sbh.empty_area_start += 1;
+    sbh.free_blocks -= 1;
+    sbh.set_crc32();
+    write_raw_block(&path, 0, &block);
+
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert!(fs.needs_repair());
+    assert_eq!(
+        fs.get_num_entries(root).err().unwrap(),
+        FsError::ValidationFailed
+    );
+    let issues = std::vec![
+        CheckIssue::Unclean {
+            txn_type: crate::TXN_TYPE_ADD_NODE
+        },
+        CheckIssue::CrossLinked {
+            id: file_b,
+            block_no: a_block_no
+        },
+        CheckIssue::LinkCount {
+            id: file_c,
+            links: 3,
+            references: 2
+        },
+        CheckIssue::OrphanedBlocks { count: 2 },
+    ];
+    assert_eq!(issues, fs.check().unwrap());
+    assert_eq!(issues, fs.repair().unwrap());
+    assert!(!fs.needs_repair());
+    assert!(fs.check().unwrap().is_empty());
+
+    // The cross-linked block of b is now a hole; its own block is free.
+    assert_eq!(empty_blocks + 1, fs.empty_blocks());
+    assert_eq!(2, fs.stat(file_c).unwrap().links);
+    let bytes = read_all(&mut fs, file_b, 0, 3 * BLOCK_SIZE as usize);
+    assert!(bytes[0..(BLOCK_SIZE as usize)].iter().all(|b| *b == 2));
+    assert!(bytes[(BLOCK_SIZE as usize)..(2 * BLOCK_SIZE as usize)]
+        .iter()
+        .all(|b| *b == 0));
+    let bytes = read_all(&mut fs, file_a, 0, 3 * BLOCK_SIZE as usize);
+    assert!(bytes.iter().all(|b| *b == 1));
+    drop(fs);
+
+    // A corrupted entry is dropped, and its blocks freed.
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert!(!fs.needs_repair());
+    assert!(fs.check().unwrap().is_empty());
+    drop(fs);
+
+    let mut block = read_raw_block(&path, file_a.block_no);
+    unsafe { block.get_mut::<EntryMetadata>() }.size += 1;
+    write_raw_block(&path, file_a.block_no, &block);
+
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let issues = std::vec![
+        CheckIssue::BadEntry {
+            id: file_a,
+            parent: dir
+        },
+        CheckIssue::OrphanedBlocks { count: 4 },
+    ];
+    assert_eq!(issues, fs.check().unwrap());
+    assert_eq!(issues, fs.repair().unwrap());
+    assert!(fs.check().unwrap().is_empty());
+    assert_eq!(2, fs.get_num_entries(dir).unwrap());
+    assert_eq!(
+        fs.get_directory_entry_by_name(dir, "a").err().unwrap(),
+        FsError::NotFound
+    );
+    assert_eq!(empty_blocks + 5, fs.empty_blocks());
+
+    // The freed blocks can be used again.
+    let file_d = fs.add_file(dir, "d").unwrap();
+    write_all(&mut fs, file_d, 0, &[4_u8; 3 * BLOCK_SIZE as usize]);
+    assert!(fs.check().unwrap().is_empty());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn snapshots() {
+    const NUM_BLOCKS: u64 = 256;
+    const SNAPSHOT_BLOCKS: u64 = 16;
+    let path = std::env::temp_dir().join("fs_dev_snapshots");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let root = SyncFileSystem::root_dir_id();
+
+    let file = fs.add_file(root, "file").unwrap();
+    write_all(&mut fs, file, 0, &[1_u8; 2 * BLOCK_SIZE as usize]);
+    let empty_blocks = fs.empty_blocks();
+
+    fs.create_snapshot("snap", SNAPSHOT_BLOCKS).unwrap();
+    assert_eq!(
+        fs.create_snapshot("snap", SNAPSHOT_BLOCKS).err().unwrap(),
+        FsError::AlreadyExists
+    );
+    assert_eq!(NUM_BLOCKS - SNAPSHOT_BLOCKS, fs.num_blocks());
+    assert_eq!(empty_blocks - SNAPSHOT_BLOCKS, fs.empty_blocks());
+
+    // Change the volume after the snapshot.
+    write_all(&mut fs, file, 0, &[2_u8; BLOCK_SIZE as usize]);
+    fs.add_file(root, "new").unwrap();
+    fs.set_file_size(file, 0).unwrap();
+    fs.remove(file).unwrap();
+    assert!(fs.check().unwrap().is_empty());
+
+    // The snapshot survives reopening the volume.
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    let snapshots = fs.snapshots();
+    assert_eq!(1, snapshots.len());
+    assert_eq!("snap", snapshots[0].name);
+    assert!(snapshots[0].valid);
+
+    // The snapshot has the volume as it was.
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let snapshot_bd = Box::new(fs.snapshot_device("snap", bd).unwrap());
+    let mut snapshot = SyncFileSystem::open_fs(snapshot_bd).unwrap();
+    assert!(snapshot.check().unwrap().is_empty());
+    assert!(snapshot.snapshots().is_empty());
+    assert_eq!(1, snapshot.get_num_entries(root).unwrap());
+    let snapshot_file = snapshot
+        .get_directory_entry_by_name(root, "file")
+        .unwrap()
+        .id;
+    let bytes = read_all(&mut snapshot, snapshot_file, 0, 2 * BLOCK_SIZE as usize);
+    assert!(bytes.iter().all(|b| *b == 1));
+    assert_eq!(
+        snapshot.add_file(root, "foo").err().unwrap(),
+        FsError::InvalidArgument
+    );
+
+    // Snapshots in use cannot be deleted.
+    assert_eq!(fs.delete_snapshot("snap").err().unwrap(), FsError::Busy);
+    drop(snapshot);
+    fs.delete_snapshot("snap").unwrap();
+    assert!(fs.snapshots().is_empty());
+    assert_eq!(NUM_BLOCKS, fs.num_blocks());
+    assert!(fs.check().unwrap().is_empty());
+
+    // A snapshot that runs out of space is invalidated.
+    fs.create_snapshot("small", 3).unwrap();
+    let big = fs.add_file(root, "big").unwrap();
+    write_all(&mut fs, big, 0, &[3_u8; 4 * BLOCK_SIZE as usize]);
+    fs.set_file_size(big, 0).unwrap();
+    fs.remove(big).unwrap();
+    assert!(!fs.snapshots()[0].valid);
+    fs.delete_snapshot("small").unwrap();
+    assert_eq!(NUM_BLOCKS, fs.num_blocks());
+    assert!(fs.check().unwrap().is_empty());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+#[test]
+fn journal() {
+    use crate::{DEFAULT_JOURNAL_BLOCKS, MIN_JOURNAL_BLOCKS};
+
+    const NUM_BLOCKS: u64 = 256;
+    let path = std::env::temp_dir().join("fs_dev_journal");
+    std::fs::remove_file(path.clone()).ok();
+
+    let mut bd = Box::new(FileBlockDevice::create(&path, NUM_BLOCKS).unwrap());
+    crate::fs_sync::format(bd.as_mut()).unwrap();
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert!(!fs.has_journal());
+    let root = SyncFileSystem::root_dir_id();
+
+    // The journal must come before snapshots.
+    fs.create_snapshot("snap", 16).unwrap();
+    assert_eq!(
+        fs.add_journal(DEFAULT_JOURNAL_BLOCKS).err().unwrap(),
+        FsError::Busy
+    );
+    fs.delete_snapshot("snap").unwrap();
+
+    assert_eq!(
+        fs.add_journal(MIN_JOURNAL_BLOCKS - 1).err().unwrap(),
+        FsError::InvalidArgument
+    );
+    fs.add_journal(DEFAULT_JOURNAL_BLOCKS).unwrap();
+    assert!(fs.has_journal());
+    assert_eq!(NUM_BLOCKS - DEFAULT_JOURNAL_BLOCKS, fs.num_blocks());
+    assert_eq!(NUM_BLOCKS - 2 - DEFAULT_JOURNAL_BLOCKS, fs.empty_blocks());
+    assert_eq!(
+        fs.add_journal(DEFAULT_JOURNAL_BLOCKS).err().unwrap(),
+        FsError::AlreadyExists
+    );
+
+    let file = fs.add_file(root, "file").unwrap();
+    write_all(&mut fs, file, 0, &[1_u8; 3 * BLOCK_SIZE as usize]);
+
+    // Snapshots go below the journal, and give the space back.
+    fs.create_snapshot("snap", 16).unwrap();
+    assert_eq!(NUM_BLOCKS - DEFAULT_JOURNAL_BLOCKS - 16, fs.num_blocks());
+    fs.set_file_size(file, 0).unwrap();
+    fs.remove(file).unwrap();
+    fs.delete_snapshot("snap").unwrap();
+    assert_eq!(NUM_BLOCKS - DEFAULT_JOURNAL_BLOCKS, fs.num_blocks());
+    assert_eq!(NUM_BLOCKS - 2 - DEFAULT_JOURNAL_BLOCKS, fs.empty_blocks());
+    assert!(fs.check().unwrap().is_empty());
+
+    drop(fs);
+    let bd = Box::new(FileBlockDevice::open(&path).unwrap());
+    let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+    assert!(fs.has_journal());
+    assert!(!fs.needs_repair());
+    assert_eq!(0, fs.get_num_entries(root).unwrap());
+    assert!(fs.check().unwrap().is_empty());
+
+    drop(fs);
+    std::fs::remove_file(path.clone()).unwrap();
+}
+
+// A RAM disk that loses power after a number of writes. Like real devices,
+// it caches writes until flushed: when the power goes, each write since the
+// last flush may or may not have made it to the disk.
+struct CrashingBlockDevice {
+    disk: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    cached: Vec<(u64, Vec<u8>)>,
+    writes_left: u64,
+    crashed: bool,
+}
+
+impl CrashingBlockDevice {
+    fn new(disk: &std::sync::Arc<std::sync::Mutex<Vec<u8>>>, writes_left: u64) -> Self {
+        Self {
+            disk: disk.clone(),
+            cached: Vec::new(),
+            writes_left,
+            crashed: false,
+        }
+    }
+
+    fn crash(&mut self) {
+        let mut rng = rand::thread_rng();
+        let mut disk = self.disk.lock().unwrap();
+        for (block_no, bytes) in self.cached.drain(..) {
+            if rng.gen_bool(0.5) {
+                let offset = (block_no * BLOCK_SIZE) as usize;
+                disk[offset..(offset + bytes.len())].copy_from_slice(&bytes);
+            }
+        }
+        self.crashed = true;
+    }
+}
+
+impl crate::SyncBlockDevice for CrashingBlockDevice {
+    fn num_blocks(&self) -> u64 {
+        self.disk.lock().unwrap().len() as u64 / BLOCK_SIZE
+    }
+
+    fn read_block(&mut self, block_no: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        if self.crashed {
+            return Err(FsError::IoError);
+        }
+        if let Some((_, bytes)) = self.cached.iter().rev().find(|(no, _)| *no == block_no) {
+            buf.copy_from_slice(bytes);
+            return Ok(());
+        }
+        let offset = (block_no * BLOCK_SIZE) as usize;
+        buf.copy_from_slice(&self.disk.lock().unwrap()[offset..(offset + buf.len())]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_no: u64, buf: &[u8]) -> Result<(), FsError> {
+        if self.crashed {
+            return Err(FsError::IoError);
+        }
+        if self.writes_left == 0 {
+            self.crash();
+            return Err(FsError::IoError);
+        }
+        self.writes_left -= 1;
+        self.cached.push((block_no, buf.to_vec()));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FsError> {
+        if self.crashed {
+            return Err(FsError::IoError);
+        }
+        let mut disk = self.disk.lock().unwrap();
+        for (block_no, bytes) in self.cached.drain(..) {
+            let offset = (block_no * BLOCK_SIZE) as usize;
+            disk[offset..(offset + bytes.len())].copy_from_slice(&bytes);
+        }
+        Ok(())
+    }
+}
+
+// A step of journal_torture(): a single TXN.
+fn journal_step(fs: &mut SyncFileSystem, step: u64) -> Result<(), FsError> {
+    fn find(fs: &mut SyncFileSystem, parent: crate::EntryId, name: &str) -> crate::EntryId {
+        fs.get_directory_entry_by_name(parent, name).unwrap().id
+    }
+
+    let root = SyncFileSystem::root_dir_id();
+    let file_name = format!("file_{}", step / 8);
+    let dir_name = format!("dir_{}", step / 8);
+    let block = [step as u8; BLOCK_SIZE as usize];
+    match step % 8 {
+        0 => fs.add_file(root, &file_name).map(|_| ()),
+        1 => {
+            let file = find(fs, root, &file_name);
+            fs.write(file, 0, &block).map(|_| ())
+        }
+        2 => {
+            let file = find(fs, root, &file_name);
+            fs.write(file, BLOCK_SIZE, &block).map(|_| ())
+        }
+        3 => fs.add_directory(root, &dir_name).map(|_| ()),
+        4 => {
+            let file = find(fs, root, &file_name);
+            let dir = find(fs, root, &dir_name);
+            fs.move_rename(file, dir, "file")
+        }
+        5 => {
+            let dir = find(fs, root, &dir_name);
+            let file = find(fs, dir, "file");
+            fs.set_xattr(file, "step", b"5")
+        }
+        6 => {
+            let dir = find(fs, root, &dir_name);
+            fs.add_file(dir, "empty").map(|_| ())
+        }
+        _ => {
+            let dir = find(fs, root, &dir_name);
+            let empty = find(fs, dir, "empty");
+            fs.remove(empty)
+        }
+    }
+}
+
+// Everything visible on the volume.
+fn fingerprint(fs: &mut SyncFileSystem, dir: crate::EntryId, path: &str, out: &mut Vec<String>) {
+    for pos in 0..fs.get_num_entries(dir).unwrap() {
+        let entry = fs.get_directory_entry(dir, pos).unwrap();
+        let entry_path = format!("{}/{}", path, entry.name);
+        if entry.id.kind() == crate::EntryKind::Directory {
+            out.push(format!("{}/", entry_path));
+            fingerprint(fs, entry.id, &entry_path, out);
+            continue;
+        }
+        let size = fs.get_file_size(entry.id).unwrap();
+        let bytes = read_all(fs, entry.id, 0, size as usize);
+        out.push(format!(
+            "{} {:x} {:?}",
+            entry_path,
+            crate::layout::crc32_hash(&bytes),
+            fs.list_xattrs(entry.id).unwrap()
+        ));
+    }
+}
+
+#[test]
+fn journal_torture() {
+    use crate::SyncBlockDevice;
+
+    const NUM_BLOCKS: u64 = 128;
+    const STEPS: u64 = 32;
+
+    let disk_bytes = alloc::vec![0_u8; (NUM_BLOCKS * BLOCK_SIZE) as usize];
+    let disk = std::sync::Arc::new(std::sync::Mutex::new(disk_bytes));
+    let mut bd = CrashingBlockDevice::new(&disk, u64::MAX);
+    crate::fs_sync::format(&mut bd).unwrap();
+    bd.flush().unwrap();
+    let mut fs = SyncFileSystem::open_fs(Box::new(bd)).unwrap();
+    fs.add_journal(crate::MIN_JOURNAL_BLOCKS).unwrap();
+    drop(fs);
+    let formatted = disk.lock().unwrap().clone();
+
+    // What the volume looks like after each step.
+    let mut fs =
+        SyncFileSystem::open_fs(Box::new(CrashingBlockDevice::new(&disk, u64::MAX))).unwrap();
+    let mut fingerprints = Vec::new();
+    for step in 0..=STEPS {
+        let mut fp = Vec::new();
+        fingerprint(&mut fs, SyncFileSystem::root_dir_id(), "", &mut fp);
+        fingerprints.push(fp);
+        if step < STEPS {
+            journal_step(&mut fs, step).unwrap();
+        }
+    }
+    drop(fs);
+
+    // Lose power after each write in turn, until the steps all complete.
+    let mut rng = rand::thread_rng();
+    for writes in 0.. {
+        *disk.lock().unwrap() = formatted.clone();
+        let bd = Box::new(CrashingBlockDevice::new(&disk, writes));
+        let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+        let mut done = 0;
+        while done < STEPS && journal_step(&mut fs, done).is_ok() {
+            done += 1;
+        }
+        drop(fs);
+
+        // The power may go again while the journal is replayed.
+        if rng.gen_bool(0.5) {
+            let bd = Box::new(CrashingBlockDevice::new(&disk, rng.gen_range(0..4)));
+            let _ = SyncFileSystem::open_fs(bd);
+        }
+
+        // Replaying the journal is all the volume needs; every step is
+        // either done, or not at all.
+        let bd = Box::new(CrashingBlockDevice::new(&disk, u64::MAX));
+        let mut fs = SyncFileSystem::open_fs(bd).unwrap();
+        assert!(!fs.needs_repair());
+        assert!(fs.check().unwrap().is_empty(), "writes: {}", writes);
+        let mut fp = Vec::new();
+        fingerprint(&mut fs, SyncFileSystem::root_dir_id(), "", &mut fp);
+        assert!(
+            fp == fingerprints[done as usize] || fingerprints.get(done as usize + 1) == Some(&fp),
+            "writes: {}, steps done: {}",
+            writes,
+            done
+        );
+
+        if done == STEPS {
+            println!("journal_torture: {} crashes survived", writes);
+            break;
+        }
+    }
+}
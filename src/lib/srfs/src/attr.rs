@@ -12,22 +12,72 @@ bitflags! {
     }
 }
 
+// Ownership and permission bits are kept in srfs_core::Attr::user_data:
+// - user_data[0]: uid in the lower 32 bits, gid in the upper 32 bits;
+// - user_data[1]: the mode (Unix-style 0o777 bits) with MODE_VALID set.
+// Entries created before these were tracked have zeros there: they
+// belong to root and have the default mode of their kind.
+const USER_DATA_OWNER: usize = 0;
+const USER_DATA_MODE: usize = 1;
+const MODE_VALID: u64 = 1 << 63;
+
+/// The permission bits that can be set: rwx for the owner, group, and others.
+pub const MODE_MASK: u16 = 0o777;
+
+pub const DEFAULT_DIR_MODE: u16 = 0o755;
+pub const DEFAULT_FILE_MODE: u16 = 0o644;
+pub const DEFAULT_SYMLINK_MODE: u16 = 0o777;
+
+pub(crate) fn owner_to_user_data(user_data: &mut [u64; 4], uid: u32, gid: u32) {
+    user_data[USER_DATA_OWNER] = (uid as u64) | ((gid as u64) << 32);
+}
+
+pub(crate) fn user_data_to_uid(user_data: &[u64; 4]) -> u32 {
+    user_data[USER_DATA_OWNER] as u32
+}
+
+pub(crate) fn mode_to_user_data(user_data: &mut [u64; 4], mode: u16) {
+    user_data[USER_DATA_MODE] = MODE_VALID | ((mode & MODE_MASK) as u64);
+}
+
 #[derive(Clone, Copy)]
 pub struct Attr {
     pub created: SystemTime,
     pub modified: SystemTime,
     pub size: u64,
-    pub kind: EntryKind,
+    pub kind: EntryKind, // Symlinks are files.
+    pub is_symlink: bool,
+    pub links: u32, // Hard links; always 1 for directories.
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u16, // See MODE_MASK.
     pub permissions: Permissions,
 }
 
 impl From<srfs_core::Attr> for Attr {
     fn from(raw_attr: srfs_core::Attr) -> Self {
+        let owner = raw_attr.user_data[USER_DATA_OWNER];
+        let mode = raw_attr.user_data[USER_DATA_MODE];
+        let mode = if (mode & MODE_VALID) != 0 {
+            (mode as u16) & MODE_MASK
+        } else if raw_attr.is_symlink() {
+            DEFAULT_SYMLINK_MODE
+        } else if raw_attr.id.kind() == EntryKind::Directory {
+            DEFAULT_DIR_MODE
+        } else {
+            DEFAULT_FILE_MODE
+        };
+
         Self {
             created: raw_attr.created.into(),
             modified: raw_attr.modified.into(),
             size: raw_attr.size,
             kind: raw_attr.id.kind(),
+            is_symlink: raw_attr.is_symlink(),
+            links: raw_attr.links,
+            uid: owner as u32,
+            gid: (owner >> 32) as u32,
+            mode,
             permissions: Permissions::all(),
         }
     }
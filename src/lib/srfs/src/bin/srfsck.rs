@@ -0,0 +1,81 @@
+// srfsck: check (and repair) an SRFS volume image offline.
+//
+// Exit codes, as in e2fsck: 0 - no issues; 1 - issues fixed; 4 - issues
+// left unfixed; 8 - operational error.
+
+use std::path::Path;
+
+use srfs_core::file_block_device::FileBlockDevice;
+use srfs_core::SyncFileSystem;
+
+fn print_usage_and_exit(exit_code: i32) -> ! {
+    eprintln!("Check an SRFS volume image for inconsistencies.");
+    eprintln!("usage:\n\tsrfsck [-n | -y] VOLUME\n");
+    eprintln!("\t-n: check only (the default);");
+    eprintln!("\t-y: repair what can be repaired.");
+    std::process::exit(exit_code);
+}
+
+fn fail(what: &str, err: impl std::fmt::Debug) -> ! {
+    eprintln!("srfsck: {}: {:?}", what, err);
+    std::process::exit(8);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut repair = false;
+    let mut volume = None;
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-h" | "--help" => print_usage_and_exit(0),
+            "-n" => repair = false,
+            "-y" => repair = true,
+            _ if volume.is_none() && !arg.starts_with('-') => volume = Some(arg.as_str()),
+            _ => print_usage_and_exit(8),
+        }
+    }
+    let Some(volume) = volume else {
+        print_usage_and_exit(8);
+    };
+
+    let bd = match FileBlockDevice::open(Path::new(volume)) {
+        Ok(bd) => Box::new(bd),
+        Err(err) => fail(volume, err),
+    };
+    let mut fs = match SyncFileSystem::open_fs(bd) {
+        Ok(fs) => fs,
+        Err(err) => fail(volume, err),
+    };
+
+    let mut fixed = 0;
+    if repair {
+        let repaired = fs.repair().unwrap_or_else(|err| fail("repair", err));
+        for issue in &repaired {
+            println!("fixed: {:?}", issue);
+        }
+        fixed = repaired.len();
+        if let Err(err) = fs.flush() {
+            fail("flush", err);
+        }
+    }
+
+    let issues = fs.check().unwrap_or_else(|err| fail("check", err));
+    for issue in &issues {
+        println!("{:?}", issue);
+    }
+
+    println!(
+        "{}: {} blocks, {} free; {} issues fixed, {} left.",
+        volume,
+        fs.num_blocks(),
+        fs.empty_blocks(),
+        fixed,
+        issues.len()
+    );
+    if !issues.is_empty() {
+        std::process::exit(4);
+    }
+    if fixed > 0 {
+        std::process::exit(1);
+    }
+}
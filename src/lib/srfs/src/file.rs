@@ -8,6 +8,7 @@ use crate::{error, FileSystemInner};
 pub struct File {
     curr_pos: u64,
     id: EntryId,
+    direct: bool, // Data blocks bypass the block cache.
     fs: Rc<RefCell<FileSystemInner>>,
 }
 
@@ -16,10 +17,21 @@ impl File {
         Self {
             curr_pos: 0,
             id,
+            direct: false,
             fs,
         }
     }
 
+    // Identifies the file, not this handle to it.
+    pub fn unique_id(&self) -> u64 {
+        self.id.unique_id()
+    }
+
+    // Direct I/O: large sequential transfers don't evict cached metadata.
+    pub fn set_direct(&mut self, direct: bool) {
+        self.direct = direct;
+    }
+
     pub fn size(&mut self) -> Result<u64> {
         self.fs
             .borrow_mut()
@@ -29,47 +41,80 @@ impl File {
     }
 
     pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let written = self
-            .fs
-            .borrow_mut()
-            .fs_core()
-            .write(self.id, self.curr_pos, buf)
-            .map_err(error::to_ioerror)?;
+        let written = self.write_offset(self.curr_pos, buf)?;
         self.curr_pos += written as u64;
         Ok(written)
     }
 
+    // Growing the file is subject to quotas.
     pub fn write_offset(&mut self, offset: u64, buf: &[u8]) -> Result<usize> {
-        let written = self
-            .fs
-            .borrow_mut()
-            .fs_core()
-            .write(self.id, offset, buf)
-            .map_err(error::to_ioerror)?;
-        Ok(written)
+        let id = self.id;
+        let end = offset.saturating_add(buf.len() as u64);
+        let direct = self.direct;
+        self.fs.borrow_mut().resize_file(id, end, |fs_core| {
+            if direct {
+                fs_core.write_direct(id, offset, buf)
+            } else {
+                fs_core.write(id, offset, buf)
+            }
+        })
     }
 
     pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let read = self
-            .fs
-            .borrow_mut()
-            .fs_core()
-            .read(self.id, self.curr_pos, buf)
-            .map_err(error::to_ioerror)?;
+        let read = self.read_offset(self.curr_pos, buf)?;
         self.curr_pos += read as u64;
         Ok(read)
     }
 
     pub fn read_offset(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut fs = self.fs.borrow_mut();
+        let result = if self.direct {
+            fs.fs_core().read_direct(self.id, offset, buf)
+        } else {
+            fs.fs_core().read(self.id, offset, buf)
+        };
+        result.map_err(error::to_ioerror)
+    }
+
+    // Makes the file durable. srfs doesn't cache writes, so this flushes
+    // the device, and with it all files and their metadata.
+    pub fn sync(&mut self) -> Result<()> {
         self.fs
             .borrow_mut()
             .fs_core()
-            .read(self.id, offset, buf)
+            .flush()
             .map_err(error::to_ioerror)
     }
 
+    // Allocate the blocks in [offset, offset + len), extending the file if needed.
+    pub fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        let id = self.id;
+        let end = offset.saturating_add(len);
+        self.fs
+            .borrow_mut()
+            .resize_file(id, end, |fs_core| fs_core.allocate(id, offset, len))
+    }
+
+    // Free the blocks in [offset, offset + len), keeping the file size.
+    pub fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.fs
+            .borrow_mut()
+            .fs_core()
+            .punch_hole(self.id, offset, len)
+            .map_err(error::to_ioerror)
+    }
+
+    // Truncate or extend the file; extending is subject to quotas.
+    pub fn set_len(&mut self, size: u64) -> Result<()> {
+        let id = self.id;
+        self.fs
+            .borrow_mut()
+            .resize_file(id, size, |fs_core| fs_core.set_file_size(id, size))
+    }
+
+    // Truncate the file at the current position.
     pub fn truncate(&mut self) -> Result<()> {
-        todo!()
+        self.set_len(self.curr_pos)
     }
 
     pub fn seek(&mut self, pos: std::io::SeekFrom) -> Result<u64> {
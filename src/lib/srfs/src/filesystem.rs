@@ -1,15 +1,21 @@
 use srfs_core::{EntryId, EntryKind, FsError, SyncFileSystem};
 
 use crate::error;
+use crate::quota::{EntryUsage, Quota, QuotaLimits, Quotas};
 use std::{
     cell::RefCell,
     io::{Error, ErrorKind, Result},
     rc::Rc,
 };
 
+// The max number of symlinks followed while resolving a single path.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
 pub(crate) struct FileSystemInner {
     fs_core: srfs_core::SyncFileSystem,
     cache: lru::LruCache<String, srfs_core::EntryId>, // path => entry
+    // None until quotas are set or queried.
+    quotas: Option<Quotas>,
 }
 
 impl FileSystemInner {
@@ -18,9 +24,7 @@ impl FileSystemInner {
     }
 
     fn create_child_dir(&mut self, parent: EntryId, child: &str) -> Result<EntryId> {
-        self.fs_core
-            .add_directory(parent, child)
-            .map_err(error::to_ioerror)
+        self.add_entry(parent, 0, |fs_core| fs_core.add_directory(parent, child))
     }
 
     fn create_dir(&mut self, path: &str) -> Result<()> {
@@ -46,20 +50,20 @@ impl FileSystemInner {
             return Ok(());
         }
 
-        let ancestors = path.split('/');
+        // Go through get_entry() so that symlinks to directories are followed.
         let mut curr_dir = srfs_core::SyncFileSystem::root_dir_id();
-        for ancestor in ancestors {
-            let entry = self.fs_core.get_directory_entry_by_name(curr_dir, ancestor);
-            if let Err(err) = entry {
-                if err == FsError::NotFound {
+        let mut prefix_len = 0;
+        for ancestor in path.split('/') {
+            prefix_len += ancestor.len();
+            match self.get_entry(&path[0..prefix_len]) {
+                Ok(entry) if entry.kind() == EntryKind::Directory => curr_dir = entry,
+                Ok(_) => return Err(ErrorKind::NotADirectory.into()),
+                Err(err) if err.kind() == ErrorKind::NotFound => {
                     curr_dir = self.create_child_dir(curr_dir, ancestor)?;
-                    continue;
-                } else {
-                    return Err(error::to_ioerror(err));
                 }
+                Err(err) => return Err(err),
             }
-
-            curr_dir = entry.unwrap().id;
+            prefix_len += 1; // The slash.
         }
 
         self.cache.push(path.to_owned(), curr_dir);
@@ -69,9 +73,53 @@ impl FileSystemInner {
     fn create_file(&mut self, path: &str) -> Result<EntryId> {
         let (parent, child) = self.split_parent_child(path)?;
 
+        self.add_entry(parent, 0, |fs_core| fs_core.add_file(parent, child))
+    }
+
+    fn create_symlink(&mut self, path: &str, target: &str) -> Result<()> {
+        let (parent, child) = self.split_parent_child(path)?;
+
+        self.add_entry(parent, target.len() as u64, |fs_core| {
+            fs_core.add_symlink(parent, child, target).map(|_| ())
+        })
+    }
+
+    fn hard_link(&mut self, existing: &str, path: &str) -> Result<()> {
+        let entry = self.get_entry_nofollow(existing)?;
+        if entry.kind() != EntryKind::File {
+            return Err(ErrorKind::PermissionDenied.into());
+        }
+        let (parent, child) = self.split_parent_child(path)?;
+
+        // Hard-linked files are not charged to directory quotas.
+        let uncharge = match self.quotas {
+            Some(_) => {
+                let usage = EntryUsage::of(&mut self.fs_core, entry)?;
+                match usage.hardlinked {
+                    true => None,
+                    false => Some((self.quota_parent(entry, &usage)?, usage.bytes)),
+                }
+            }
+            None => None,
+        };
+
         self.fs_core
-            .add_file(parent, child)
-            .map_err(error::to_ioerror)
+            .add_link(entry, parent, child)
+            .map_err(error::to_ioerror)?;
+
+        if let Some((old_parent, bytes)) = uncharge {
+            self.quota_charge(None, old_parent, -(bytes as i64), -1)?;
+        }
+        Ok(())
+    }
+
+    fn read_link(&mut self, path: &str) -> Result<String> {
+        let entry = self.get_entry_nofollow(path)?;
+        if entry.kind() != EntryKind::File {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        self.fs_core.read_symlink(entry).map_err(error::to_ioerror)
     }
 
     fn split_parent_child<'a, 'b>(&'a mut self, path: &'b str) -> Result<(EntryId, &'b str)> {
@@ -122,25 +170,118 @@ impl FileSystemInner {
         Ok(raw_attr.into())
     }
 
-    fn unlink(&mut self, path: &str) -> Result<()> {
+    fn set_mode(&mut self, path: &str, mode: u16) -> Result<()> {
+        if (mode & !crate::MODE_MASK) != 0 {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        let entry = self.get_entry(path)?;
+        let raw_attr = self.fs_core.stat(entry).map_err(error::to_ioerror)?;
+        let mut user_data = raw_attr.user_data;
+        crate::attr::mode_to_user_data(&mut user_data, mode);
+        self.fs_core
+            .set_user_data(entry, user_data)
+            .map_err(error::to_ioerror)
+    }
+
+    fn set_owner(&mut self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        let entry = self.get_entry(path)?;
+        let raw_attr = self.fs_core.stat(entry).map_err(error::to_ioerror)?;
+
+        // The entry (not the tree under it) is charged to the new owner.
+        let prev_uid = crate::attr::user_data_to_uid(&raw_attr.user_data);
+        let bytes = match entry.kind() {
+            EntryKind::File => raw_attr.size as i64,
+            EntryKind::Directory => 0,
+        };
+        if prev_uid != uid {
+            self.quota_charge(Some(uid), None, bytes, 1)?;
+        }
+
+        let mut user_data = raw_attr.user_data;
+        crate::attr::owner_to_user_data(&mut user_data, uid, gid);
+        let result = self
+            .fs_core
+            .set_user_data(entry, user_data)
+            .map_err(error::to_ioerror);
+
+        if prev_uid != uid {
+            let uncharged = if result.is_ok() { prev_uid } else { uid };
+            self.quota_charge(Some(uncharged), None, -bytes, -1)?;
+        }
+        result
+    }
+
+    fn get_xattr(&mut self, path: &str, name: &str) -> Result<Vec<u8>> {
+        let entry = self.get_entry(path)?;
+        self.fs_core
+            .get_xattr(entry, name)
+            .map_err(error::to_ioerror)
+    }
+
+    fn list_xattrs(&mut self, path: &str) -> Result<Vec<String>> {
+        let entry = self.get_entry(path)?;
+        self.fs_core.list_xattrs(entry).map_err(error::to_ioerror)
+    }
+
+    fn set_xattr(&mut self, path: &str, name: &str, value: &[u8]) -> Result<()> {
+        if name.starts_with(crate::quota::QUOTA_XATTR_PREFIX) {
+            return Err(ErrorKind::PermissionDenied.into()); // See set_user_quota().
+        }
         let entry = self.get_entry(path)?;
+        self.fs_core
+            .set_xattr(entry, name, value)
+            .map_err(error::to_ioerror)
+    }
+
+    fn remove_xattr(&mut self, path: &str, name: &str) -> Result<()> {
+        if name.starts_with(crate::quota::QUOTA_XATTR_PREFIX) {
+            return Err(ErrorKind::PermissionDenied.into());
+        }
+        let entry = self.get_entry(path)?;
+        self.fs_core
+            .remove_xattr(entry, name)
+            .map_err(error::to_ioerror)
+    }
+
+    fn lstat(&mut self, path: &str) -> Result<crate::Attr> {
+        let entry = self.get_entry_nofollow(path)?;
+        let raw_attr = self.fs_core.stat(entry).map_err(error::to_ioerror)?;
+        Ok(raw_attr.into())
+    }
+
+    // Removes the symlink itself, not its target.
+    fn unlink(&mut self, path: &str) -> Result<()> {
+        let entry = self.get_entry_nofollow(path)?;
+        let (parent, _) = self.split_parent_child(path.trim_end_matches('/'))?;
+        let freed = match self.quotas {
+            Some(_) => self.quota_freed(entry, parent)?,
+            None => None,
+        };
         if entry.kind() == EntryKind::File {
-            // Need to truncate files first.
-            self.fs_core
-                .set_file_size(entry, 0)
-                .map_err(error::to_ioerror)?;
+            let raw_attr = self.fs_core.stat(entry).map_err(error::to_ioerror)?;
+            if raw_attr.links == 1 {
+                // Need to truncate files first.
+                self.fs_core
+                    .set_file_size(entry, 0)
+                    .map_err(error::to_ioerror)?;
+            }
         }
-        self.fs_core.remove(entry).map_err(error::to_ioerror)?;
+        self.fs_core
+            .remove_link(parent, entry)
+            .map_err(error::to_ioerror)?;
         self.pop_cache(path);
+        if let Some((usage, dir)) = freed {
+            self.quota_uncharge_freed(entry, usage, dir);
+        }
         Ok(())
     }
 
     fn rename(&mut self, old: &str, new: &str) -> Result<()> {
         log::debug!("rename 0: {} -> {}", old, new);
 
-        let entry = self.get_entry(old)?;
+        let entry = self.get_entry_nofollow(old)?;
 
-        let (_, old_child) = self.split_parent_child(old)?;
+        let (old_parent, old_child) = self.split_parent_child(old.trim_end_matches('/'))?;
         let (new_parent, new_child) = self.split_parent_child(new)?;
         let new_child = if new_child.is_empty() {
             old_child
@@ -150,14 +291,260 @@ impl FileSystemInner {
 
         log::debug!("rename: {} -> {:?} {}", old, new_parent, new_child);
 
+        // An existing destination is replaced atomically.
+        let mut freed = None;
+        if let Ok(target) = self
+            .fs_core
+            .get_directory_entry_by_name(new_parent, new_child)
+        {
+            match (entry.kind(), target.id.kind()) {
+                (EntryKind::Directory, EntryKind::File) => {
+                    return Err(ErrorKind::NotADirectory.into())
+                }
+                (EntryKind::File, EntryKind::Directory) => {
+                    return Err(ErrorKind::IsADirectory.into())
+                }
+                _ => {}
+            }
+            if self.quotas.is_some() && target.id != entry {
+                freed = self
+                    .quota_freed(target.id, new_parent)?
+                    .map(|freed| (target.id, freed));
+            }
+        }
+        // The replaced entry makes room in the quotas above new_parent.
+        let replaced = freed
+            .as_ref()
+            .and_then(|(_, (usage, dir))| dir.map(|_| usage.bytes));
+        let moved = match self.quotas {
+            Some(_) => self.quota_check_move(entry, old_parent, new_parent, replaced)?,
+            None => None,
+        };
         self.fs_core
-            .move_rename(entry, new_parent, new_child)
+            .move_replace(old_parent, entry, new_parent, new_child)
             .map_err(error::to_ioerror)?;
         self.pop_cache(old);
-        self.cache.push(new.to_owned(), entry);
+        self.pop_cache(new);
+
+        if let Some((target, (usage, dir))) = freed {
+            self.quota_uncharge_freed(target, usage, dir);
+        }
+        if let (Some(quotas), Some((added, removed, usage))) = (self.quotas.as_mut(), moved) {
+            quotas.charge(None, &added, usage.bytes as i64, usage.inodes as i64);
+            quotas.charge(
+                None,
+                &removed,
+                -(usage.bytes as i64),
+                -(usage.inodes as i64),
+            );
+        }
+        Ok(())
+    }
+
+    // Adds an entry to @parent via @f, charging it to the quotas; new entries
+    // belong to root until chowned.
+    fn add_entry<T>(
+        &mut self,
+        parent: EntryId,
+        bytes: u64,
+        f: impl FnOnce(&mut SyncFileSystem) -> std::result::Result<T, FsError>,
+    ) -> Result<T> {
+        self.quota_charge(Some(0), Some(parent), bytes as i64, 1)?;
+        let result = f(&mut self.fs_core).map_err(error::to_ioerror);
+        if result.is_err() {
+            self.quota_charge(Some(0), Some(parent), -(bytes as i64), -1)?;
+        }
+        result
+    }
+
+    // Runs @f, which changes the size of @file to at most @max_size, with
+    // the growth charged to the owner and the directory quotas above.
+    pub(crate) fn resize_file<T>(
+        &mut self,
+        file: EntryId,
+        max_size: u64,
+        f: impl FnOnce(&mut SyncFileSystem) -> std::result::Result<T, FsError>,
+    ) -> Result<T> {
+        if self.quotas.is_none() {
+            return f(&mut self.fs_core).map_err(error::to_ioerror);
+        }
+
+        let usage = EntryUsage::of(&mut self.fs_core, file)?;
+        let parent = self.quota_parent(file, &usage)?;
+        let growth = max_size.saturating_sub(usage.bytes) as i64;
+        self.quota_charge(Some(usage.uid), parent, growth, 0)?;
+
+        let result = f(&mut self.fs_core).map_err(error::to_ioerror);
+
+        // Settle the charge with the actual size.
+        let new_size = self
+            .fs_core
+            .get_file_size(file)
+            .map_err(error::to_ioerror)?;
+        let quotas = self.quotas.as_mut().unwrap();
+        let dirs = match parent {
+            Some(parent) => quotas.dirs_of(&mut self.fs_core, parent)?,
+            None => Vec::new(),
+        };
+        let actual = new_size as i64 - usage.bytes as i64;
+        quotas.charge(Some(usage.uid), &dirs, actual - growth, 0);
+        result
+    }
+
+    // Charges @uid and, if @dir is not None, the directory quotas at and
+    // above @dir; growth is checked against the limits first.
+    fn quota_charge(
+        &mut self,
+        uid: Option<u32>,
+        dir: Option<EntryId>,
+        bytes: i64,
+        inodes: i64,
+    ) -> Result<()> {
+        let Some(quotas) = self.quotas.as_mut() else {
+            return Ok(());
+        };
+        let dirs = match dir {
+            Some(dir) => quotas.dirs_of(&mut self.fs_core, dir)?,
+            None => Vec::new(),
+        };
+        quotas.check(uid, &dirs, bytes.max(0) as u64, inodes.max(0) as u64)?;
+        quotas.charge(uid, &dirs, bytes, inodes);
         Ok(())
     }
 
+    // The directory whose quotas @entry is charged to, if any.
+    fn quota_parent(&mut self, entry: EntryId, usage: &EntryUsage) -> Result<Option<EntryId>> {
+        if usage.hardlinked {
+            return Ok(None);
+        }
+        self.fs_core.get_parent(entry).map_err(error::to_ioerror)
+    }
+
+    // What removing the link to @entry from @parent frees, if anything.
+    fn quota_freed(
+        &mut self,
+        entry: EntryId,
+        parent: EntryId,
+    ) -> Result<Option<(EntryUsage, Option<EntryId>)>> {
+        let usage = EntryUsage::of(&mut self.fs_core, entry)?;
+        if usage.links > 1 {
+            return Ok(None);
+        }
+        let dir = if usage.hardlinked { None } else { Some(parent) };
+        Ok(Some((usage, dir)))
+    }
+
+    fn quota_uncharge_freed(&mut self, entry: EntryId, usage: EntryUsage, dir: Option<EntryId>) {
+        let Some(quotas) = self.quotas.as_mut() else {
+            return;
+        };
+        // @dir exists, so its ancestors can be found.
+        let dirs = match dir {
+            Some(dir) => quotas.dirs_of(&mut self.fs_core, dir).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        quotas.charge(Some(usage.uid), &dirs, -(usage.bytes as i64), -1);
+        if entry.kind() == EntryKind::Directory {
+            quotas.remove_dir(entry);
+        }
+    }
+
+    // Checks that moving @entry between directories fits into the directory
+    // quotas it moves into, less a @replaced entry of that many bytes;
+    // returns the quotas it enters and leaves, and what is moved.
+    fn quota_check_move(
+        &mut self,
+        entry: EntryId,
+        old_parent: EntryId,
+        new_parent: EntryId,
+        replaced: Option<u64>,
+    ) -> Result<Option<(Vec<u64>, Vec<u64>, crate::QuotaUsage)>> {
+        let quotas = self.quotas.as_mut().unwrap();
+        let old_dirs = quotas.dirs_of(&mut self.fs_core, old_parent)?;
+        let new_dirs = quotas.dirs_of(&mut self.fs_core, new_parent)?;
+        let added: Vec<u64> = new_dirs
+            .iter()
+            .filter(|dir| !old_dirs.contains(dir))
+            .copied()
+            .collect();
+        let removed: Vec<u64> = old_dirs
+            .iter()
+            .filter(|dir| !new_dirs.contains(dir))
+            .copied()
+            .collect();
+        if added.is_empty() && removed.is_empty() {
+            return Ok(None);
+        }
+
+        let entry_usage = EntryUsage::of(&mut self.fs_core, entry)?;
+        if entry_usage.hardlinked {
+            return Ok(None);
+        }
+        let mut usage = crate::QuotaUsage {
+            bytes: entry_usage.bytes,
+            inodes: 1,
+        };
+        if entry.kind() == EntryKind::Directory {
+            let tree = Quotas::tree_usage(&mut self.fs_core, entry)?;
+            usage.bytes += tree.bytes;
+            usage.inodes += tree.inodes;
+        }
+
+        let (bytes, inodes) = match replaced {
+            Some(bytes) => (usage.bytes.saturating_sub(bytes), usage.inodes - 1),
+            None => (usage.bytes, usage.inodes),
+        };
+        let quotas = self.quotas.as_ref().unwrap();
+        quotas.check(None, &added, bytes, inodes)?;
+        Ok(Some((added, removed, usage)))
+    }
+
+    // Quotas are counted when first needed.
+    fn quotas(&mut self) -> Result<&mut Quotas> {
+        if self.quotas.is_none() {
+            self.quotas = Some(Quotas::count(&mut self.fs_core)?);
+        }
+        Ok(self.quotas.as_mut().unwrap())
+    }
+
+    fn user_quota(&mut self, uid: u32) -> Result<Quota> {
+        Ok(self.quotas()?.user_quota(uid))
+    }
+
+    fn set_user_quota(&mut self, uid: u32, limits: QuotaLimits) -> Result<()> {
+        self.quotas()?;
+        let quotas = self.quotas.as_mut().unwrap();
+        quotas.set_user_limits(&mut self.fs_core, uid, limits)
+    }
+
+    fn dir_quota(&mut self, path: &str) -> Result<Quota> {
+        let dir = self.get_entry(path)?;
+        if dir.kind() != EntryKind::Directory {
+            return Err(ErrorKind::NotADirectory.into());
+        }
+        if let Some(quota) = self
+            .quotas
+            .as_ref()
+            .and_then(|quotas| quotas.dir_quota(dir))
+        {
+            return Ok(quota);
+        }
+        Ok(Quota {
+            limits: QuotaLimits::default(),
+            usage: Quotas::tree_usage(&mut self.fs_core, dir)?,
+        })
+    }
+
+    fn set_dir_quota(&mut self, path: &str, limits: QuotaLimits) -> Result<()> {
+        let dir = self.get_entry(path)?;
+        if dir.kind() != EntryKind::Directory {
+            return Err(ErrorKind::NotADirectory.into());
+        }
+        self.quotas()?;
+        let quotas = self.quotas.as_mut().unwrap();
+        quotas.set_dir_limits(&mut self.fs_core, dir, limits)
+    }
+
     fn pop_cache(&mut self, path: &str) {
         let path = if path.starts_with('/') {
             path.strip_prefix('/').unwrap()
@@ -167,38 +554,103 @@ impl FileSystemInner {
         self.cache.pop(path);
     }
 
+    // Symlinks are followed, including the last path component.
     pub(crate) fn get_entry(&mut self, path: &str) -> Result<EntryId> {
+        self.lookup(path, true)
+    }
+
+    // Symlinks are followed, except for the last path component.
+    fn get_entry_nofollow(&mut self, path: &str) -> Result<EntryId> {
+        self.lookup(path, false)
+    }
+
+    fn is_symlink(&mut self, entry: EntryId) -> Result<bool> {
+        if entry.kind() != EntryKind::File {
+            return Ok(false);
+        }
+        let raw_attr = self.fs_core.stat(entry).map_err(error::to_ioerror)?;
+        Ok(raw_attr.is_symlink())
+    }
+
+    fn lookup(&mut self, path: &str, follow_last: bool) -> Result<EntryId> {
         let path = if path.starts_with('/') {
             path.strip_prefix('/').unwrap()
         } else {
             path
         };
 
+        // The cache only has paths that don't go through or end with symlinks,
+        // so cached entries are the same whether following or not.
         if let Some(entry) = self.cache.get(path) {
             return Ok(*entry);
         }
 
-        let ancestors = path.split('/');
-        let mut curr_dir = srfs_core::SyncFileSystem::root_dir_id();
-        for ancestor in ancestors {
-            if ancestor.is_empty() {
+        // Components yet to be resolved, in reverse order; symlink targets
+        // are spliced in as they are encountered.
+        let mut components: Vec<String> = path.split('/').rev().map(|c| c.to_owned()).collect();
+        // The directories resolved so far, starting with the root.
+        let mut dirs = vec![srfs_core::SyncFileSystem::root_dir_id()];
+        let mut last = *dirs.last().unwrap();
+        let mut follows = 0;
+        let mut cacheable = true;
+
+        while let Some(component) = components.pop() {
+            if last.kind() != EntryKind::Directory {
+                return Err(ErrorKind::NotADirectory.into());
+            }
+            if component.is_empty() || component == "." {
                 continue;
             }
-            let entry = self.fs_core.get_directory_entry_by_name(curr_dir, ancestor);
-            if let Err(err) = entry {
-                if err == FsError::NotFound {
-                    return Err(Error::from(ErrorKind::NotFound));
-                } else {
-                    return Err(error::to_ioerror(err));
+            if component == ".." {
+                if dirs.len() > 1 {
+                    dirs.pop();
                 }
+                last = *dirs.last().unwrap();
+                continue;
+            }
+
+            let entry = match self.fs_core.get_directory_entry_by_name(last, &component) {
+                Ok(entry) => entry.id,
+                Err(FsError::NotFound) => return Err(Error::from(ErrorKind::NotFound)),
+                Err(err) => return Err(error::to_ioerror(err)),
+            };
+
+            let is_last = components.iter().all(|c| c.is_empty() || c == ".");
+            if is_last && !follow_last {
+                // Might be a symlink, which must not be cached.
+                cacheable &= entry.kind() == EntryKind::Directory;
+                last = entry;
+                break;
             }
 
-            curr_dir = entry.unwrap().id;
+            if self.is_symlink(entry)? {
+                follows += 1;
+                if follows > MAX_SYMLINK_FOLLOWS {
+                    return Err(ErrorKind::FilesystemLoop.into());
+                }
+                cacheable = false;
+
+                let target = self
+                    .fs_core
+                    .read_symlink(entry)
+                    .map_err(error::to_ioerror)?;
+                if target.starts_with('/') {
+                    dirs.truncate(1);
+                }
+                last = *dirs.last().unwrap();
+                components.extend(target.split('/').rev().map(|c| c.to_owned()));
+                continue;
+            }
+
+            dirs.push(entry);
+            last = entry;
         }
 
-        self.cache.push(path.to_owned(), curr_dir);
+        if cacheable {
+            self.cache.push(path.to_owned(), last);
+        }
 
-        Ok(curr_dir)
+        Ok(last)
     }
 }
 
@@ -208,10 +660,19 @@ pub struct FileSystem {
 
 impl FileSystem {
     const CACHE_SIZE: std::num::NonZeroUsize = std::num::NonZeroUsize::new(4096).unwrap();
+    const MIN_JOURNALED_BLOCKS: u64 = 16 * srfs_core::DEFAULT_JOURNAL_BLOCKS;
 
+    /// Volumes large enough get a journal: see SyncFileSystem::add_journal().
     pub fn create_volume(path: &std::path::Path, num_blocks: u64) -> Result<()> {
         let mut bd = srfs_core::file_block_device::FileBlockDevice::create(path, num_blocks)?;
-        srfs_core::format(&mut bd).map_err(error::to_ioerror)
+        srfs_core::format(&mut bd).map_err(error::to_ioerror)?;
+        if num_blocks < Self::MIN_JOURNALED_BLOCKS {
+            return Ok(());
+        }
+
+        let mut fs = srfs_core::SyncFileSystem::open_fs(Box::new(bd)).map_err(error::to_ioerror)?;
+        fs.add_journal(srfs_core::DEFAULT_JOURNAL_BLOCKS)
+            .map_err(error::to_ioerror)
     }
 
     pub fn open_volume(path: &std::path::Path) -> Result<Self> {
@@ -220,12 +681,22 @@ impl FileSystem {
     }
 
     pub fn open_device(block_device: Box<dyn srfs_core::SyncBlockDevice>) -> Result<Self> {
-        let fs = srfs_core::SyncFileSystem::open_fs(block_device).map_err(error::to_ioerror)?;
+        let mut fs = srfs_core::SyncFileSystem::open_fs(block_device).map_err(error::to_ioerror)?;
+        if fs.needs_repair() {
+            // Not unmounted cleanly; srfs-core logs what it fixes.
+            log::warn!("srfs: repairing the volume after an unclean shutdown");
+            fs.repair().map_err(error::to_ioerror)?;
+        }
+        let quotas = match Quotas::enabled(&mut fs) {
+            true => Some(Quotas::count(&mut fs)?),
+            false => None,
+        };
 
         Ok(Self {
             inner: Rc::new(RefCell::new(FileSystemInner {
                 fs_core: fs,
                 cache: lru::LruCache::new(Self::CACHE_SIZE),
+                quotas,
             })),
         })
     }
@@ -252,6 +723,24 @@ impl FileSystem {
         Ok(crate::File::from(file_id, self.inner.clone()))
     }
 
+    /// Create a symbolic link at path pointing at target. The target
+    /// is not required to exist; relative targets are resolved against
+    /// the directory containing the link.
+    pub fn create_symlink(&mut self, path: &str, target: &str) -> Result<()> {
+        self.inner.borrow_mut().create_symlink(path, target)
+    }
+
+    /// The target of the symbolic link at path.
+    pub fn read_link(&mut self, path: &str) -> Result<String> {
+        self.inner.borrow_mut().read_link(path)
+    }
+
+    /// Create a hard link at path to the existing file (not directory).
+    /// A symlink is linked itself, rather than its target.
+    pub fn hard_link(&mut self, existing: &str, path: &str) -> Result<()> {
+        self.inner.borrow_mut().hard_link(existing, path)
+    }
+
     pub fn exists(&mut self, path: &str) -> Result<bool> {
         self.inner.borrow_mut().exists(path)
     }
@@ -260,15 +749,128 @@ impl FileSystem {
         self.inner.borrow_mut().stat(path)
     }
 
+    /// Like stat(), but doesn't follow the last path component if it is a symlink.
+    pub fn lstat(&mut self, path: &str) -> Result<crate::Attr> {
+        self.inner.borrow_mut().lstat(path)
+    }
+
+    /// Set the permission bits (see MODE_MASK). Follows symlinks.
+    pub fn set_mode(&mut self, path: &str, mode: u16) -> Result<()> {
+        self.inner.borrow_mut().set_mode(path, mode)
+    }
+
+    /// Set the owner and group. Follows symlinks.
+    pub fn set_owner(&mut self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        self.inner.borrow_mut().set_owner(path, uid, gid)
+    }
+
+    /// Get the value of an extended attribute. Follows symlinks.
+    pub fn get_xattr(&mut self, path: &str, name: &str) -> Result<Vec<u8>> {
+        self.inner.borrow_mut().get_xattr(path, name)
+    }
+
+    /// The names of the extended attributes. Follows symlinks.
+    pub fn list_xattrs(&mut self, path: &str) -> Result<Vec<String>> {
+        self.inner.borrow_mut().list_xattrs(path)
+    }
+
+    /// Add or replace an extended attribute. Follows symlinks.
+    pub fn set_xattr(&mut self, path: &str, name: &str, value: &[u8]) -> Result<()> {
+        self.inner.borrow_mut().set_xattr(path, name, value)
+    }
+
+    /// Remove an extended attribute. Follows symlinks.
+    pub fn remove_xattr(&mut self, path: &str, name: &str) -> Result<()> {
+        self.inner.borrow_mut().remove_xattr(path, name)
+    }
+
+    /// The quota of user @uid: its limits (zero if none) and usage.
+    pub fn user_quota(&mut self, uid: u32) -> Result<Quota> {
+        self.inner.borrow_mut().user_quota(uid)
+    }
+
+    /// Set the limits of user @uid; zero limits remove the quota. Root
+    /// (uid 0) is not limited. The limits may be below the current usage.
+    pub fn set_user_quota(&mut self, uid: u32, limits: QuotaLimits) -> Result<()> {
+        self.inner.borrow_mut().set_user_quota(uid, limits)
+    }
+
+    /// The quota of a directory: its limits (zero if none) and the usage
+    /// of the tree under it. Follows symlinks.
+    pub fn dir_quota(&mut self, path: &str) -> Result<Quota> {
+        self.inner.borrow_mut().dir_quota(path)
+    }
+
+    /// Set the limits of the tree under a directory; zero limits remove
+    /// the quota. Follows symlinks.
+    pub fn set_dir_quota(&mut self, path: &str, limits: QuotaLimits) -> Result<()> {
+        self.inner.borrow_mut().set_dir_quota(path, limits)
+    }
+
     pub fn read_dir(&mut self, path: &str) -> Result<crate::ReadDir> {
-        crate::readdir::ReadDir::read_dir(path, self.inner.clone())
+        self.read_dir_from(path, 0)
+    }
+
+    // Continues listing after the entry with @cursor (see DirEntry::cursor()).
+    pub fn read_dir_from(&mut self, path: &str, cursor: u64) -> Result<crate::ReadDir> {
+        crate::readdir::ReadDir::read_dir(path, cursor, self.inner.clone())
     }
 
     pub fn unlink(&mut self, path: &str) -> Result<()> {
         self.inner.borrow_mut().unlink(path)
     }
 
+    // Replaces new atomically if it exists, like rename(2).
     pub fn rename(&mut self, old: &str, new: &str) -> Result<()> {
         self.inner.borrow_mut().rename(old, new)
     }
+
+    // The size of the volume, in blocks of BLOCK_SIZE.
+    pub fn num_blocks(&self) -> u64 {
+        self.inner.borrow().fs_core.num_blocks()
+    }
+
+    pub fn empty_blocks(&self) -> u64 {
+        self.inner.borrow().fs_core.empty_blocks()
+    }
+
+    /// Take snapshot @name of the volume, with room for @blocks blocks
+    /// changed after it; the room is taken from the volume until the
+    /// snapshot is deleted.
+    pub fn create_snapshot(&mut self, name: &str, blocks: u64) -> Result<()> {
+        self.inner
+            .borrow_mut()
+            .fs_core
+            .create_snapshot(name, blocks)
+            .map_err(error::to_ioerror)
+    }
+
+    /// Delete snapshot @name. Fails while the snapshot is open.
+    pub fn delete_snapshot(&mut self, name: &str) -> Result<()> {
+        self.inner
+            .borrow_mut()
+            .fs_core
+            .delete_snapshot(name)
+            .map_err(error::to_ioerror)
+    }
+
+    pub fn snapshots(&self) -> Vec<crate::SnapshotInfo> {
+        self.inner.borrow().fs_core.snapshots()
+    }
+
+    /// Open snapshot @name, read-only; @block_device is another handle to
+    /// the device of this volume.
+    pub fn open_snapshot(
+        &self,
+        name: &str,
+        block_device: Box<dyn srfs_core::SyncBlockDevice>,
+    ) -> Result<Self> {
+        let snapshot_device = self
+            .inner
+            .borrow()
+            .fs_core
+            .snapshot_device(name, block_device)
+            .map_err(error::to_ioerror)?;
+        Self::open_device(Box::new(snapshot_device))
+    }
 }
@@ -29,15 +29,22 @@ pub use srfs_core::SyncBlockDevice;
 pub use srfs_core::BLOCK_SIZE;
 pub use srfs_core::MAX_DIR_ENTRIES;
 pub use srfs_core::MAX_FILE_SIZE;
+pub use srfs_core::MAX_SYMLINK_LEN;
 pub use srfs_core::PARTITION_ID;
+pub use srfs_core::SnapshotInfo;
 
 mod attr;
 mod error;
 mod file;
 mod filesystem;
+mod quota;
 mod readdir;
 
+#[cfg(test)]
+mod tests;
+
 pub use attr::*;
 pub use file::*;
 pub use filesystem::*;
+pub use quota::{is_quota_exceeded, Quota, QuotaExceeded, QuotaLimits, QuotaUsage};
 pub use readdir::*;
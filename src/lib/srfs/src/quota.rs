@@ -0,0 +1,340 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Result};
+
+use srfs_core::{EntryId, EntryKind, FsError, SyncFileSystem, ENTRY_FLAG_HARDLINKED};
+
+use crate::error;
+
+// Quota limits are kept in extended attributes, so the layout of srfs-core
+// is unchanged:
+// - QUOTA_USER_XATTR_PREFIX + uid, on the root directory: per-user limits;
+// - QUOTA_DIR_XATTR, on a directory: the limits of its tree;
+// - QUOTA_ON_XATTR, on the root directory: there are (or were) limits, so
+//   usage is counted, by walking the tree, when the volume is opened.
+// Values are max_bytes and max_inodes, as u64 LE.
+//
+// Usage is not stored: it is counted in memory and kept up to date by
+// FileSystemInner. Hard-linked files have no single parent, so they are
+// charged to their owner only, not to directory quotas.
+pub(crate) const QUOTA_XATTR_PREFIX: &str = "trusted.srfs.quota.";
+const QUOTA_ON_XATTR: &str = "trusted.srfs.quota.on";
+const QUOTA_DIR_XATTR: &str = "trusted.srfs.quota.dir";
+const QUOTA_USER_XATTR_PREFIX: &str = "trusted.srfs.quota.user.";
+
+/// Limits of a quota; zero means no limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    pub max_bytes: u64,
+    pub max_inodes: u64,
+}
+
+impl QuotaLimits {
+    pub fn is_none(&self) -> bool {
+        self.max_bytes == 0 && self.max_inodes == 0
+    }
+
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0_u8; 16];
+        bytes[0..8].copy_from_slice(&self.max_bytes.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.max_inodes.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != 16 {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        Ok(Self {
+            max_bytes: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            max_inodes: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+        })
+    }
+}
+
+/// What is charged to a quota. Bytes are file sizes, so sparse files count
+/// in full; files, directories, and symlinks are inodes. A directory quota
+/// covers the entries below the directory, not the directory itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub bytes: u64,
+    pub inodes: u64,
+}
+
+/// The error payload of an operation refused because it would exceed a
+/// quota; the error kind is ErrorKind::Other. See [is_quota_exceeded].
+#[derive(Debug)]
+pub struct QuotaExceeded;
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("quota exceeded")
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Whether @err was returned because an operation would exceed a quota.
+pub fn is_quota_exceeded(err: &std::io::Error) -> bool {
+    err.get_ref()
+        .is_some_and(|inner| inner.is::<QuotaExceeded>())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub limits: QuotaLimits,
+    pub usage: QuotaUsage,
+}
+
+impl Quota {
+    fn check(&self, bytes: u64, inodes: u64) -> Result<()> {
+        let exceeds = |limit: u64, used: u64, add: u64| {
+            limit != 0 && add != 0 && used.saturating_add(add) > limit
+        };
+        if exceeds(self.limits.max_bytes, self.usage.bytes, bytes)
+            || exceeds(self.limits.max_inodes, self.usage.inodes, inodes)
+        {
+            return Err(std::io::Error::other(QuotaExceeded));
+        }
+        Ok(())
+    }
+
+    fn charge(&mut self, bytes: i64, inodes: i64) {
+        self.usage.bytes = self.usage.bytes.saturating_add_signed(bytes);
+        self.usage.inodes = self.usage.inodes.saturating_add_signed(inodes);
+    }
+}
+
+// What an entry is charged: to @uid, and, unless hard-linked, to the
+// directory quotas above it.
+pub(crate) struct EntryUsage {
+    pub uid: u32,
+    pub bytes: u64,
+    pub links: u32,
+    pub hardlinked: bool,
+}
+
+impl EntryUsage {
+    pub fn of(fs_core: &mut SyncFileSystem, entry: EntryId) -> Result<Self> {
+        let raw_attr = fs_core.stat(entry).map_err(error::to_ioerror)?;
+        Ok(Self {
+            uid: crate::attr::user_data_to_uid(&raw_attr.user_data),
+            bytes: match entry.kind() {
+                EntryKind::File => raw_attr.size,
+                EntryKind::Directory => 0,
+            },
+            links: raw_attr.links,
+            hardlinked: (raw_attr.flags & ENTRY_FLAG_HARDLINKED) != 0,
+        })
+    }
+}
+
+pub(crate) struct Quotas {
+    users: HashMap<u32, Quota>, // All owners, with or without limits.
+    dirs: HashMap<u64, Quota>,  // Directories with limits, by unique_id.
+}
+
+impl Quotas {
+    pub fn enabled(fs_core: &mut SyncFileSystem) -> bool {
+        fs_core
+            .get_xattr(SyncFileSystem::root_dir_id(), QUOTA_ON_XATTR)
+            .is_ok()
+    }
+
+    // Reads the limits and walks the tree to count the usage.
+    pub fn count(fs_core: &mut SyncFileSystem) -> Result<Self> {
+        let root = SyncFileSystem::root_dir_id();
+        let mut quotas = Self {
+            users: HashMap::new(),
+            dirs: HashMap::new(),
+        };
+
+        for name in fs_core.list_xattrs(root).map_err(error::to_ioerror)? {
+            let Some(uid) = name.strip_prefix(QUOTA_USER_XATTR_PREFIX) else {
+                continue;
+            };
+            let uid = uid.parse::<u32>().map_err(|_| ErrorKind::InvalidData)?;
+            let value = fs_core.get_xattr(root, &name).map_err(error::to_ioerror)?;
+            quotas.users.entry(uid).or_default().limits = QuotaLimits::from_bytes(&value)?;
+        }
+
+        let mut dirs = Vec::new();
+        if let Some(limits) = Self::read_dir_limits(fs_core, root)? {
+            quotas.dirs.entry(root.unique_id()).or_default().limits = limits;
+            dirs.push(root.unique_id());
+        }
+
+        // Hard-linked files are charged to their owner once.
+        let mut hardlinked = HashSet::new();
+        let mut stack = vec![(root, dirs)];
+        while let Some((dir, dirs)) = stack.pop() {
+            let num_entries = fs_core.get_num_entries(dir).map_err(error::to_ioerror)?;
+            for pos in 0..num_entries {
+                let entry = fs_core
+                    .get_directory_entry(dir, pos)
+                    .map_err(error::to_ioerror)?
+                    .id;
+                let usage = EntryUsage::of(fs_core, entry)?;
+                if !usage.hardlinked || hardlinked.insert(entry.unique_id()) {
+                    quotas.charge(Some(usage.uid), &[], usage.bytes as i64, 1);
+                }
+                if !usage.hardlinked {
+                    quotas.charge(None, &dirs, usage.bytes as i64, 1);
+                }
+
+                if entry.kind() == EntryKind::Directory {
+                    let mut dirs = dirs.clone();
+                    if let Some(limits) = Self::read_dir_limits(fs_core, entry)? {
+                        quotas.dirs.entry(entry.unique_id()).or_default().limits = limits;
+                        dirs.push(entry.unique_id());
+                    }
+                    stack.push((entry, dirs));
+                }
+            }
+        }
+
+        Ok(quotas)
+    }
+
+    fn read_dir_limits(fs_core: &mut SyncFileSystem, dir: EntryId) -> Result<Option<QuotaLimits>> {
+        match fs_core.get_xattr(dir, QUOTA_DIR_XATTR) {
+            Ok(value) => Ok(Some(QuotaLimits::from_bytes(&value)?)),
+            Err(FsError::NotFound) => Ok(None),
+            Err(err) => Err(error::to_ioerror(err)),
+        }
+    }
+
+    // The usage of the tree at @dir, as charged to directory quotas.
+    pub fn tree_usage(fs_core: &mut SyncFileSystem, dir: EntryId) -> Result<QuotaUsage> {
+        let mut usage = QuotaUsage::default();
+        let mut stack = vec![dir];
+        while let Some(dir) = stack.pop() {
+            let num_entries = fs_core.get_num_entries(dir).map_err(error::to_ioerror)?;
+            for pos in 0..num_entries {
+                let entry = fs_core
+                    .get_directory_entry(dir, pos)
+                    .map_err(error::to_ioerror)?
+                    .id;
+                let entry_usage = EntryUsage::of(fs_core, entry)?;
+                if entry_usage.hardlinked {
+                    continue;
+                }
+                usage.bytes += entry_usage.bytes;
+                usage.inodes += 1;
+                if entry.kind() == EntryKind::Directory {
+                    stack.push(entry);
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    // The directories with quotas at or above @dir.
+    pub fn dirs_of(&self, fs_core: &mut SyncFileSystem, dir: EntryId) -> Result<Vec<u64>> {
+        let mut dirs = Vec::new();
+        if self.dirs.is_empty() {
+            return Ok(dirs);
+        }
+
+        let mut dir = Some(dir);
+        while let Some(curr) = dir {
+            if self.dirs.contains_key(&curr.unique_id()) {
+                dirs.push(curr.unique_id());
+            }
+            dir = fs_core.get_parent(curr).map_err(error::to_ioerror)?;
+        }
+        Ok(dirs)
+    }
+
+    // Root is not limited by user quotas.
+    pub fn check(&self, uid: Option<u32>, dirs: &[u64], bytes: u64, inodes: u64) -> Result<()> {
+        if let Some(uid) = uid.filter(|uid| *uid != 0) {
+            if let Some(quota) = self.users.get(&uid) {
+                quota.check(bytes, inodes)?;
+            }
+        }
+        for dir in dirs {
+            self.dirs[dir].check(bytes, inodes)?;
+        }
+        Ok(())
+    }
+
+    pub fn charge(&mut self, uid: Option<u32>, dirs: &[u64], bytes: i64, inodes: i64) {
+        if let Some(uid) = uid {
+            self.users.entry(uid).or_default().charge(bytes, inodes);
+        }
+        for dir in dirs {
+            self.dirs.get_mut(dir).unwrap().charge(bytes, inodes);
+        }
+    }
+
+    pub fn user_quota(&self, uid: u32) -> Quota {
+        self.users.get(&uid).copied().unwrap_or_default()
+    }
+
+    pub fn dir_quota(&self, dir: EntryId) -> Option<Quota> {
+        self.dirs.get(&dir.unique_id()).copied()
+    }
+
+    pub fn set_user_limits(
+        &mut self,
+        fs_core: &mut SyncFileSystem,
+        uid: u32,
+        limits: QuotaLimits,
+    ) -> Result<()> {
+        if uid == 0 {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        let root = SyncFileSystem::root_dir_id();
+        let name = format!("{}{}", QUOTA_USER_XATTR_PREFIX, uid);
+        Self::write_limits(fs_core, root, &name, limits)?;
+        self.users.entry(uid).or_default().limits = limits;
+        Ok(())
+    }
+
+    pub fn set_dir_limits(
+        &mut self,
+        fs_core: &mut SyncFileSystem,
+        dir: EntryId,
+        limits: QuotaLimits,
+    ) -> Result<()> {
+        Self::write_limits(fs_core, dir, QUOTA_DIR_XATTR, limits)?;
+        if limits.is_none() {
+            self.dirs.remove(&dir.unique_id());
+        } else if let Some(quota) = self.dirs.get_mut(&dir.unique_id()) {
+            quota.limits = limits;
+        } else {
+            let usage = Self::tree_usage(fs_core, dir)?;
+            self.dirs.insert(dir.unique_id(), Quota { limits, usage });
+        }
+        Ok(())
+    }
+
+    // Forgets the quota of a removed directory.
+    pub fn remove_dir(&mut self, dir: EntryId) {
+        self.dirs.remove(&dir.unique_id());
+    }
+
+    fn write_limits(
+        fs_core: &mut SyncFileSystem,
+        entry: EntryId,
+        name: &str,
+        limits: QuotaLimits,
+    ) -> Result<()> {
+        if limits.is_none() {
+            return match fs_core.remove_xattr(entry, name) {
+                Ok(()) | Err(FsError::NotFound) => Ok(()),
+                Err(err) => Err(error::to_ioerror(err)),
+            };
+        }
+
+        let root = SyncFileSystem::root_dir_id();
+        if !Self::enabled(fs_core) {
+            fs_core
+                .set_xattr(root, QUOTA_ON_XATTR, &[])
+                .map_err(error::to_ioerror)?;
+        }
+        fs_core
+            .set_xattr(entry, name, &limits.to_bytes())
+            .map_err(error::to_ioerror)
+    }
+}
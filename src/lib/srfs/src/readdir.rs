@@ -8,6 +8,7 @@ pub struct DirEntry {
     name: String, // The leaf name.
     path: String, // The full path.
     id: EntryId,
+    cursor: u64,
     fs: Rc<RefCell<FileSystemInner>>,
 }
 
@@ -33,34 +34,39 @@ impl DirEntry {
     pub fn file_type(&self) -> crate::EntryKind {
         self.id.kind()
     }
+
+    // Pass to FileSystem::read_dir_from() to continue after this entry.
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
 }
 
+// Entries are listed from the last one to the first. Removing an entry moves
+// the last entry into its place, so when entries are removed during listing,
+// the moved entries have either been listed already (and may be listed again)
+// or are still ahead: entries present throughout are never skipped.
 pub struct ReadDir {
     path: String,
     parent: EntryId,
-    cur_pos: u64,
-    num_entries: u64,
+    end: u64, // The entries before this position are yet to be listed.
     fs: Rc<RefCell<FileSystemInner>>,
 }
 
 impl ReadDir {
-    pub(crate) fn read_dir(path: &str, fs: Rc<RefCell<FileSystemInner>>) -> Result<Self> {
+    pub(crate) fn read_dir(
+        path: &str,
+        cursor: u64,
+        fs: Rc<RefCell<FileSystemInner>>,
+    ) -> Result<Self> {
         let parent = fs.borrow_mut().get_entry(path)?;
         if parent.kind() != EntryKind::Directory {
             return Err(ErrorKind::NotADirectory.into());
         }
 
-        let num_entries = fs
-            .borrow_mut()
-            .fs_core()
-            .get_num_entries(parent)
-            .map_err(error::to_ioerror)?;
-
         Ok(Self {
             path: path.to_owned(),
             parent,
-            cur_pos: 0,
-            num_entries,
+            end: if cursor == 0 { u64::MAX } else { cursor - 1 },
             fs,
         })
     }
@@ -70,23 +76,28 @@ impl Iterator for ReadDir {
     type Item = Result<DirEntry>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur_pos == self.num_entries {
+        let mut fs = self.fs.borrow_mut();
+        let num_entries = match fs.fs_core().get_num_entries(self.parent) {
+            Ok(num_entries) => num_entries,
+            Err(err) => return Some(Err(error::to_ioerror(err))),
+        };
+        self.end = self.end.min(num_entries);
+        if self.end == 0 {
             return None;
         }
+        self.end -= 1;
 
-        let entry = self
-            .fs
-            .borrow_mut()
+        let entry = fs
             .fs_core()
-            .get_directory_entry(self.parent, self.cur_pos)
+            .get_directory_entry(self.parent, self.end)
             .map_err(error::to_ioerror);
-        self.cur_pos += 1;
 
         match entry {
             Ok(entry) => Some(Ok(DirEntry {
                 name: entry.name.clone(),
                 path: self.path.clone(),
                 id: entry.id,
+                cursor: self.end + 1,
                 fs: self.fs.clone(),
             })),
             Err(err) => Some(Err(err)),
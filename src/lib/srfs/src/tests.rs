@@ -0,0 +1,301 @@
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+
+use crate::{File, FileSystem, Quota, QuotaLimits, QuotaUsage};
+
+const NUM_BLOCKS: u64 = 512;
+const UID: u32 = 1000;
+
+fn create_volume(name: &str) -> (FileSystem, PathBuf) {
+    let path = std::env::temp_dir().join(name);
+    std::fs::remove_file(&path).ok();
+    FileSystem::create_volume(&path, NUM_BLOCKS).unwrap();
+    (FileSystem::open_volume(&path).unwrap(), path)
+}
+
+fn limits(max_bytes: u64, max_inodes: u64) -> QuotaLimits {
+    QuotaLimits {
+        max_bytes,
+        max_inodes,
+    }
+}
+
+fn usage(bytes: u64, inodes: u64) -> QuotaUsage {
+    QuotaUsage { bytes, inodes }
+}
+
+// File::write() stops at block boundaries.
+fn write_all(file: &mut File, mut buf: &[u8]) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        let written = file.write(buf)?;
+        buf = &buf[written..];
+    }
+    Ok(())
+}
+
+fn assert_exceeded<T>(result: std::io::Result<T>) {
+    assert!(crate::is_quota_exceeded(&result.err().unwrap()));
+}
+
+#[test]
+fn user_quota() {
+    let (mut fs, path) = create_volume("srfs_user_quota");
+    fs.set_user_quota(UID, limits(10_000, 3)).unwrap();
+    assert_eq!(
+        ErrorKind::InvalidInput,
+        fs.set_user_quota(0, limits(1, 1)).unwrap_err().kind()
+    );
+
+    // New entries belong to root, which is not limited.
+    let mut file = fs.create_file("/a").unwrap();
+    write_all(&mut file, &[1_u8; 12_000]).unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(0, 0));
+    assert_exceeded(fs.set_owner("/a", UID, UID));
+    file.set_len(0).unwrap();
+    fs.set_owner("/a", UID, UID).unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(0, 1));
+
+    // Writes.
+    file.seek(SeekFrom::Start(0)).unwrap();
+    write_all(&mut file, &[1_u8; 8_000]).unwrap();
+    assert_exceeded(file.write(&[1_u8; 4_000]));
+    assert_eq!(8_000, file.size().unwrap());
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(8_000, 1));
+    // Overwriting doesn't grow the file.
+    file.write_offset(0, &[2_u8; 8_000]).unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(8_000, 1));
+
+    // Truncation.
+    assert_exceeded(file.set_len(12_000));
+    assert_exceeded(file.allocate(8_000, 4_000));
+    file.set_len(10_000).unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(10_000, 1));
+    file.seek(SeekFrom::Start(1_000)).unwrap();
+    file.truncate().unwrap();
+    assert_eq!(1_000, file.size().unwrap());
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(1_000, 1));
+    write_all(&mut file, &[1_u8; 9_000]).unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(10_000, 1));
+
+    // Inodes.
+    fs.create_dir("/b").unwrap();
+    fs.set_owner("/b", UID, UID).unwrap();
+    fs.create_symlink("/c", "/a").unwrap();
+    fs.create_file("/d").unwrap();
+    fs.set_owner("/d", UID, UID).unwrap();
+    fs.set_owner("/c", UID, UID).unwrap(); // Follows /c to /a.
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(10_000, 3));
+    fs.create_file("/e").unwrap();
+    assert_exceeded(fs.set_owner("/e", UID, UID));
+    assert_eq!(0, fs.stat("/e").unwrap().uid);
+
+    // Unlinking.
+    drop(file);
+    fs.unlink("/a").unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(0, 2));
+    fs.set_owner("/e", UID, UID).unwrap();
+    fs.set_owner("/e", 0, 0).unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(0, 2));
+    // Symlinks are charged the length of the target: /c and /e.
+    assert_eq!(fs.user_quota(0).unwrap().usage, usage(2, 2));
+
+    // Removing the limits keeps counting the usage.
+    fs.set_user_quota(UID, QuotaLimits::default()).unwrap();
+    assert_eq!(
+        fs.user_quota(UID).unwrap(),
+        Quota {
+            limits: QuotaLimits::default(),
+            usage: usage(0, 2),
+        }
+    );
+
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn dir_quota() {
+    let (mut fs, path) = create_volume("srfs_dir_quota");
+    fs.create_dir_all("/d/s").unwrap();
+    let mut file = fs.create_file("/d/a").unwrap();
+    write_all(&mut file, &[1_u8; 1_000]).unwrap();
+
+    // The existing tree is counted when the limits are set.
+    fs.set_dir_quota("/d", limits(8_192, 3)).unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(1_000, 2));
+    assert_eq!(fs.dir_quota("/d/s").unwrap(), Quota::default());
+
+    // Creation, anywhere under the directory.
+    fs.create_file("/d/s/b").unwrap();
+    assert_exceeded(fs.create_file("/d/s/c"));
+    assert_exceeded(fs.create_dir("/d/c"));
+    assert_exceeded(fs.create_symlink("/d/c", "/d/a"));
+    assert!(!fs.exists("/d/c").unwrap());
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(1_000, 3));
+    fs.create_file("/c").unwrap(); // Not under /d.
+
+    // Writes and truncation, by root too.
+    write_all(&mut file, &[1_u8; 7_192]).unwrap();
+    assert_exceeded(file.write(&[1_u8; 1]));
+    let mut other = fs.open_file("/d/s/b").unwrap();
+    assert_exceeded(other.set_len(1));
+    file.set_len(4_096).unwrap();
+    other.set_len(4_096).unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(8_192, 3));
+
+    // Unlinking.
+    drop(other);
+    fs.unlink("/d/s/b").unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(4_096, 2));
+    fs.create_file("/d/s/c").unwrap();
+
+    // Nested quotas are both charged.
+    fs.set_dir_quota("/d/s", limits(0, 2)).unwrap();
+    assert_eq!(fs.dir_quota("/d/s").unwrap().usage, usage(0, 1));
+    fs.unlink("/d/s/c").unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(4_096, 2));
+    assert_eq!(fs.dir_quota("/d/s").unwrap().usage, usage(0, 0));
+
+    // Removing a directory with a quota forgets it.
+    fs.unlink("/d/s").unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(4_096, 1));
+    fs.create_dir("/d/s").unwrap();
+    assert_eq!(fs.dir_quota("/d/s").unwrap(), Quota::default());
+
+    drop(file);
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rename_across_quotas() {
+    let (mut fs, path) = create_volume("srfs_rename_quota");
+    fs.create_dir_all("/q1/inner").unwrap();
+    fs.create_dir("/q2").unwrap();
+    fs.create_dir_all("/src/sub").unwrap();
+    write_all(&mut fs.create_file("/src/sub/x").unwrap(), &[1_u8; 5_000]).unwrap();
+    write_all(&mut fs.create_file("/src/y").unwrap(), &[1_u8; 100]).unwrap();
+
+    fs.set_dir_quota("/q1", limits(10_000, 10)).unwrap();
+    fs.set_dir_quota("/q1/inner", limits(6_000, 10)).unwrap();
+    fs.set_dir_quota("/q2", limits(0, 2)).unwrap();
+    assert_eq!(fs.dir_quota("/q1").unwrap().usage, usage(0, 1));
+
+    // The whole tree moves: /src, /src/sub, /src/sub/x, /src/y.
+    assert_exceeded(fs.rename("/src", "/q2/src"));
+    assert!(fs.exists("/src/sub/x").unwrap());
+    assert_eq!(fs.dir_quota("/q2").unwrap().usage, usage(0, 0));
+    fs.rename("/src", "/q1/inner/src").unwrap();
+    assert_eq!(fs.dir_quota("/q1").unwrap().usage, usage(5_100, 5));
+    assert_eq!(fs.dir_quota("/q1/inner").unwrap().usage, usage(5_100, 4));
+
+    // Out of the inner quota only.
+    fs.rename("/q1/inner/src/sub", "/q1/sub").unwrap();
+    assert_eq!(fs.dir_quota("/q1").unwrap().usage, usage(5_100, 5));
+    assert_eq!(fs.dir_quota("/q1/inner").unwrap().usage, usage(100, 2));
+
+    // Between unrelated quotas.
+    fs.rename("/q1/sub/x", "/q2/x").unwrap();
+    assert_eq!(fs.dir_quota("/q1").unwrap().usage, usage(100, 4));
+    assert_eq!(fs.dir_quota("/q2").unwrap().usage, usage(5_000, 1));
+
+    // Replacing a file frees it.
+    write_all(&mut fs.create_file("/q2/z").unwrap(), &[1_u8; 10]).unwrap();
+    assert_exceeded(fs.rename("/q1/inner/src/y", "/q2/w"));
+    fs.rename("/q1/inner/src/y", "/q2/z").unwrap();
+    assert_eq!(fs.dir_quota("/q1").unwrap().usage, usage(0, 3));
+    assert_eq!(fs.dir_quota("/q2").unwrap().usage, usage(5_100, 2));
+
+    // Out of all quotas.
+    fs.rename("/q2/x", "/x").unwrap();
+    assert_eq!(fs.dir_quota("/q2").unwrap().usage, usage(100, 1));
+
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn hard_link_quota() {
+    let (mut fs, path) = create_volume("srfs_link_quota");
+    fs.create_dir("/d").unwrap();
+    fs.create_dir("/e").unwrap();
+    fs.set_dir_quota("/d", limits(0, 1)).unwrap();
+    fs.set_dir_quota("/e", limits(0, 1)).unwrap();
+    fs.set_user_quota(UID, limits(4_000, 0)).unwrap();
+
+    let mut file = fs.create_file("/d/f").unwrap();
+    fs.set_owner("/d/f", UID, UID).unwrap();
+    write_all(&mut file, &[1_u8; 3_000]).unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(3_000, 1));
+
+    // Hard-linked files are charged to the owner only, so linking into a
+    // full directory quota works, and moves the file out of /d's usage.
+    fs.hard_link("/d/f", "/e/g").unwrap();
+    fs.hard_link("/d/f", "/e/h").unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(0, 0));
+    assert_eq!(fs.dir_quota("/e").unwrap().usage, usage(0, 0));
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(3_000, 1));
+
+    write_all(&mut file, &[1_u8; 1_000]).unwrap();
+    assert_exceeded(file.write(&[1_u8; 1]));
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(0, 0));
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(4_000, 1));
+
+    // The owner is uncharged when the last link goes.
+    drop(file);
+    fs.unlink("/d/f").unwrap();
+    fs.rename("/e/g", "/d/g").unwrap();
+    assert_eq!(fs.dir_quota("/d").unwrap().usage, usage(0, 0));
+    fs.unlink("/e/h").unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(4_000, 1));
+    fs.unlink("/d/g").unwrap();
+    assert_eq!(fs.user_quota(UID).unwrap().usage, usage(0, 0));
+
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn quota_recount() {
+    let (mut fs, path) = create_volume("srfs_quota_recount");
+    fs.create_dir_all("/d/s").unwrap();
+    fs.set_dir_quota("/d", limits(100_000, 100)).unwrap();
+    fs.set_dir_quota("/d/s", limits(0, 3)).unwrap();
+    fs.set_user_quota(UID, limits(50_000, 0)).unwrap();
+
+    for (name, size) in [("/d/a", 1_000), ("/d/s/b", 20_000), ("/c", 300)] {
+        write_all(&mut fs.create_file(name).unwrap(), &vec![1_u8; size]).unwrap();
+        fs.set_owner(name, UID, UID).unwrap();
+    }
+    fs.hard_link("/d/a", "/d/s/a").unwrap();
+    fs.create_symlink("/d/l", "/c").unwrap();
+
+    let quotas = |fs: &mut FileSystem| {
+        (
+            fs.dir_quota("/d").unwrap(),
+            fs.dir_quota("/d/s").unwrap(),
+            fs.user_quota(UID).unwrap(),
+            fs.user_quota(0).unwrap(),
+        )
+    };
+    let before = quotas(&mut fs);
+    assert_eq!(before.0.usage, usage(20_002, 3));
+    assert_eq!(before.1.usage, usage(20_000, 1));
+    assert_eq!(before.2.usage, usage(21_300, 3));
+    assert_eq!(before.3.usage, usage(2, 3));
+
+    drop(fs);
+    let mut fs = FileSystem::open_volume(&path).unwrap();
+    assert_eq!(quotas(&mut fs), before);
+
+    // The recounted usage is enforced.
+    fs.create_file("/d/s/c").unwrap();
+    fs.create_file("/d/s/d").unwrap();
+    assert_exceeded(fs.create_file("/d/s/e"));
+    let mut file = fs.open_file("/d/s/b").unwrap();
+    assert_exceeded(file.set_len(50_000));
+
+    drop(file);
+    drop(fs);
+    std::fs::remove_file(path).unwrap();
+}
@@ -7,6 +7,8 @@ extern crate alloc;
 mod pci;
 mod virtio_blk;
 mod virtio_device;
+mod virtio_fs;
+mod virtio_gpu;
 pub mod virtio_net;
 mod virtio_queue;
 mod virtio_rng;
@@ -17,6 +19,8 @@ pub use pci::le64;
 
 pub use virtio_blk::lsblk;
 pub use virtio_device::init_virtio_devices;
+pub use virtio_fs::lsfs;
+pub use virtio_gpu::lsgpu;
 
 pub(crate) use virtio_device::mapper;
 
@@ -59,10 +63,38 @@ pub const BLOCK_SIZE_LOG2: usize = 9;
 pub trait BlockDevice {
     // buf must be aligned at BLOCK_SIZE.
     fn read(&self, buf: &mut [u8], address: u64, number_of_blocks: usize) -> Result<(), ()>;
+    // Writes may stay in the device's cache until flushed.
     fn write(&self, buf: &[u8], address: u64, number_of_blocks: usize) -> Result<(), ()>;
+    fn flush(&self) -> Result<(), ()>;
     fn capacity(&self) -> u64; // In blocks.
 }
 
+// Requests and replies to FuseDevice can't be larger than this.
+pub const FUSE_MAX_MESSAGE: usize = 132 * 1024;
+
+// This is the shared directory (virtio-fs) interface exposed by the library:
+// see crate::lsfs(). Messages are FUSE requests and replies, including
+// their headers (fuse_in_header/fuse_out_header).
+pub trait FuseDevice {
+    fn tag(&self) -> alloc::string::String; // Names the directory, as set up on the host.
+                                            // Returns the length of the reply.
+    fn request(&self, request: &[u8], reply: &mut [u8]) -> Result<usize, ()>;
+    // For requests that have no reply, e.g. FUSE_FORGET.
+    fn send(&self, request: &[u8]) -> Result<(), ()>;
+}
+
+// This is the display (virtio-gpu) interface exposed by the library: see crate::lsgpu().
+// Pixels are 32 bits, 0x00RRGGBB (i.e. B8G8R8X8 in memory); rows are not padded.
+pub trait GpuDevice {
+    // The (width, height) preferred by the host.
+    fn display_size(&self) -> (u32, u32);
+    // Makes the @width x @height framebuffer at @virt_addr (page aligned) what
+    // the display shows. The memory must stay mapped; can be done only once.
+    fn attach_framebuffer(&self, virt_addr: u64, width: u32, height: u32) -> Result<(), ()>;
+    // Updates the display from the framebuffer in the rectangle given.
+    fn flush(&self, x: u32, y: u32, width: u32, height: u32) -> Result<(), ()>;
+}
+
 pub type WaitHandle = u64;
 
 // This is the kernel/syscall interface consumed by the library:
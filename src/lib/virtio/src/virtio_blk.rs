@@ -447,10 +447,15 @@ impl super::BlockDevice for VirtioDrive {
             offset += BLOCK_SIZE;
         }
 
-        blk.flush()?;
         Ok(())
     }
 
+    fn flush(&self) -> Result<(), ()> {
+        let mut guard = BLK.lock();
+        let blk = guard.get_mut(self.blk_idx as usize).unwrap();
+        blk.flush()
+    }
+
     fn capacity(&self) -> u64 {
         BLK.lock().get(self.blk_idx as usize).unwrap().capacity
     }
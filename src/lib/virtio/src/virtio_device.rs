@@ -20,6 +20,8 @@ pub enum VirtioDeviceKind {
     MEM,
     CONSOLE,
     RNG,
+    FS,
+    GPU,
 }
 
 impl VirtioDeviceKind {
@@ -31,6 +33,8 @@ impl VirtioDeviceKind {
             0x1045 => VirtioDeviceKind::MEM,
             0x1043 => VirtioDeviceKind::CONSOLE,
             0x1044 => VirtioDeviceKind::RNG,
+            0x105a => VirtioDeviceKind::FS,
+            0x1050 => VirtioDeviceKind::GPU,
             x => VirtioDeviceKind::UNKNOWN(x),
         }
     }
@@ -182,6 +186,12 @@ impl VirtioDevice {
             return Err(());
         }
 
+        if let VirtioDeviceKind::CONSOLE = kind {
+            // The kernel owns virtio-console devices: it writes crash dumps there.
+            log::debug!("Skipping VirtIO console device_id {:?}", device_id);
+            return Err(());
+        }
+
         let reg_1 = device_id.read_config_u32(0x04);
         let status = ((reg_1 >> 16) & 0xFFFF) as u16;
         if status & pci::PCI_STATUS_CAP_LIST == 0 {
@@ -754,6 +764,12 @@ pub fn init_virtio_devices(mapper: &'static dyn super::KernelAdapter) {
                 VirtioDeviceKind::RNG => {
                     super::virtio_rng::Rng::init(device);
                 }
+                VirtioDeviceKind::FS => {
+                    super::virtio_fs::Fs::init(device);
+                }
+                VirtioDeviceKind::GPU => {
+                    super::virtio_gpu::Gpu::init(device);
+                }
                 _ => {}
             }
         }
@@ -0,0 +1,246 @@
+// Virtio-fs: shared host directories. The device carries FUSE messages
+// to a host daemon (e.g. virtiofsd); building and parsing them is left
+// to the user of the device (see super::FuseDevice).
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::pci::PciBar;
+use super::virtio_device::VirtioDevice;
+use super::virtio_queue::UserData;
+use super::FUSE_MAX_MESSAGE;
+use spin::Mutex;
+
+// Device configuration.
+const TAG_OFFSET: u64 = 0;
+const TAG_SIZE: u64 = 36;
+const NUM_REQUEST_QUEUES_OFFSET: u64 = 36;
+
+// FUSE_FORGET and the like go to the high priority queue, which is
+// the first one; everything else goes to the (first) request queue.
+const VIRTQ_HIPRIO: usize = 0;
+const VIRTQ_REQUEST: usize = 1;
+
+pub(super) struct Fs {
+    dev: alloc::boxed::Box<VirtioDevice>,
+    tag: String,
+    // Messages are copied via these, as virtqueue descriptors
+    // need physically contiguous memory.
+    request_buf: *mut u8,
+    reply_buf: *mut u8,
+}
+
+unsafe impl Send for Fs {}
+
+impl Fs {
+    fn self_init(&mut self) -> Result<(), ()> {
+        self.dev.acknowledge_driver(); // Step 3
+        self.negotiate_features()?; // Steps 4, 5, 6
+        self.dev.init_virtqueues(2, 2)?; // Step 7
+        self.dev.driver_ok(); // Step 8
+        Ok(())
+    }
+
+    pub(super) fn init(dev: alloc::boxed::Box<VirtioDevice>) {
+        if dev.device_cfg.is_none() {
+            log::warn!("Skiping Virtio FS device without device configuration.");
+            return;
+        }
+
+        let request_buf = crate::mapper()
+            .alloc_contiguous_pages(FUSE_MAX_MESSAGE as u64)
+            .expect("Failed to allocate FUSE buffers.");
+        let reply_buf = crate::mapper()
+            .alloc_contiguous_pages(FUSE_MAX_MESSAGE as u64)
+            .expect("Failed to allocate FUSE buffers.");
+
+        let mut fs = Fs {
+            dev,
+            tag: String::new(),
+            request_buf: request_buf as usize as *mut u8,
+            reply_buf: reply_buf as usize as *mut u8,
+        };
+
+        if fs.self_init().is_ok() {
+            log::debug!(
+                "Initialized Virtio FS device {:?}: tag: {}.",
+                fs.dev.pci_device.id,
+                fs.tag
+            );
+            FS.lock().push(fs);
+        } else {
+            moto_sys::SysRay::log("Failed to initialize Virtio FS device.").ok();
+            fs.dev.mark_failed();
+        }
+    }
+
+    // Step 4
+    fn negotiate_features(&mut self) -> Result<(), ()> {
+        let features_available = self.dev.get_available_features();
+        log::debug!("FS device features: 0x{:x}", features_available);
+
+        if (features_available & super::virtio_device::VIRTIO_F_VERSION_1) == 0 {
+            log::warn!("Virtio FS device {:?}: VIRTIO_F_VERSION_1 feature not available; features: 0x{:x}.",
+                self.dev.pci_device.id, features_available);
+            return Err(());
+        }
+
+        // VIRTIO_FS_F_NOTIFICATION is not used.
+        let features_acked = super::virtio_device::VIRTIO_F_VERSION_1;
+        self.dev.write_enabled_features(features_acked);
+        self.dev.confirm_features()?;
+
+        let device_cfg = self.dev.device_cfg.as_ref().unwrap();
+        let cfg_bar: &PciBar = self.dev.pci_device.bars[device_cfg.bar as usize]
+            .as_ref()
+            .unwrap();
+
+        if cfg_bar.read_u32(device_cfg.offset as u64 + NUM_REQUEST_QUEUES_OFFSET) == 0 {
+            log::warn!(
+                "Virtio FS device {:?}: no request queues.",
+                self.dev.pci_device.id
+            );
+            return Err(());
+        }
+
+        // The tag is UTF-8, NUL-padded unless it takes all TAG_SIZE bytes.
+        let mut tag = Vec::new();
+        for idx in 0..TAG_SIZE {
+            match cfg_bar.readb(device_cfg.offset as u64 + TAG_OFFSET + idx) {
+                0 => break,
+                b => tag.push(b),
+            }
+        }
+        self.tag = String::from_utf8(tag).map_err(|_| {
+            log::warn!("Virtio FS device {:?}: bad tag.", self.dev.pci_device.id);
+        })?;
+        if self.tag.is_empty() {
+            log::warn!("Virtio FS device {:?}: empty tag.", self.dev.pci_device.id);
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    // Adds the buffers to the virtqueue, notifies the device and waits
+    // until it is done with them; returns the number of bytes written.
+    #[inline(never)]
+    fn submit(&mut self, queue: usize, sg: &[UserData], outgoing: u16, incoming: u16) -> u32 {
+        let virtqueue = &mut self.dev.virtqueues[queue];
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        virtqueue.add_buf(sg, outgoing, incoming);
+
+        // Notify
+        let notify_cap = self.dev.notify_cfg.unwrap();
+        let cfg_bar: &PciBar = self.dev.pci_device.bars[notify_cap.bar as usize]
+            .as_ref()
+            .unwrap();
+        let notify_offset = notify_cap.offset as u64
+            + (notify_cap.notify_off_multiplier as u64 * virtqueue.queue_notify_off as u64);
+
+        cfg_bar.write_u16(notify_offset, virtqueue.queue_num);
+
+        let mut wait_failed = false;
+        while !virtqueue.more_used_deprecated() {
+            core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            if wait_failed {
+                super::nop(); // Don't spam the kernel if something is wrong here.
+            } else {
+                wait_failed = virtqueue.wait_deprecated().is_err();
+                if wait_failed {
+                    log::error!("virtqueue.wait() failed: switching to spinning.");
+                }
+            }
+        }
+        let written = virtqueue.consume_used_deprecated();
+
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::Acquire);
+        written
+    }
+
+    fn request(&mut self, request: &[u8], reply: &mut [u8]) -> Result<usize, ()> {
+        if request.len() > FUSE_MAX_MESSAGE || reply.is_empty() {
+            return Err(());
+        }
+        let reply_len = reply.len().min(FUSE_MAX_MESSAGE);
+        unsafe {
+            core::ptr::copy_nonoverlapping(request.as_ptr(), self.request_buf, request.len());
+        }
+
+        let sg: [UserData; 2] = [
+            UserData {
+                addr: self.request_buf as usize as u64,
+                len: request.len() as u32,
+            },
+            UserData {
+                addr: self.reply_buf as usize as u64,
+                len: reply_len as u32,
+            },
+        ];
+        let written = self.submit(VIRTQ_REQUEST, &sg, 1, 1) as usize;
+        if written > reply_len {
+            log::error!("VirtioFs: bad reply length {}.", written);
+            return Err(());
+        }
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.reply_buf, reply.as_mut_ptr(), written);
+        }
+        Ok(written)
+    }
+
+    fn send(&mut self, request: &[u8]) -> Result<(), ()> {
+        if request.len() > FUSE_MAX_MESSAGE {
+            return Err(());
+        }
+        unsafe {
+            core::ptr::copy_nonoverlapping(request.as_ptr(), self.request_buf, request.len());
+        }
+
+        let sg: [UserData; 1] = [UserData {
+            addr: self.request_buf as usize as u64,
+            len: request.len() as u32,
+        }];
+        self.submit(VIRTQ_HIPRIO, &sg, 1, 0);
+        Ok(())
+    }
+}
+
+static FS: Mutex<Vec<Fs>> = Mutex::new(vec![]);
+
+pub fn lsfs() -> Vec<Arc<dyn super::FuseDevice>> {
+    let mut result: Vec<Arc<dyn super::FuseDevice>> = alloc::vec![];
+    let cnt = FS.lock().len();
+
+    for idx in 0..cnt {
+        result.push(Arc::new(VirtioFsDevice { fs_idx: idx }));
+    }
+
+    result
+}
+
+pub(super) struct VirtioFsDevice {
+    fs_idx: usize, // index into FS
+}
+
+impl super::FuseDevice for VirtioFsDevice {
+    fn tag(&self) -> String {
+        FS.lock().get(self.fs_idx).unwrap().tag.clone()
+    }
+
+    fn request(&self, request: &[u8], reply: &mut [u8]) -> Result<usize, ()> {
+        FS.lock()
+            .get_mut(self.fs_idx)
+            .unwrap()
+            .request(request, reply)
+    }
+
+    fn send(&self, request: &[u8]) -> Result<(), ()> {
+        FS.lock().get_mut(self.fs_idx).unwrap().send(request)
+    }
+}
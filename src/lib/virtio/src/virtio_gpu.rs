@@ -0,0 +1,482 @@
+// Virtio-gpu: 2D only (no virgl/3D). The driver sets up a single resource
+// that covers the whole (first) display; the memory backing it, i.e. the
+// framebuffer, is given by the user of the device (see super::GpuDevice).
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::pci::PciBar;
+use super::virtio_device::VirtioDevice;
+use super::virtio_queue::UserData;
+use spin::Mutex;
+
+// Device configuration.
+const NUM_SCANOUTS_OFFSET: u64 = 8;
+
+const VIRTQ_CONTROL: usize = 0;
+// The cursor queue (1) is not used.
+
+// Commands.
+const CMD_GET_DISPLAY_INFO: u32 = 0x0100;
+const CMD_RESOURCE_CREATE_2D: u32 = 0x0101;
+const CMD_SET_SCANOUT: u32 = 0x0103;
+const CMD_RESOURCE_FLUSH: u32 = 0x0104;
+const CMD_TRANSFER_TO_HOST_2D: u32 = 0x0105;
+const CMD_RESOURCE_ATTACH_BACKING: u32 = 0x0106;
+
+// Responses.
+const RESP_OK_NODATA: u32 = 0x1100;
+const RESP_OK_DISPLAY_INFO: u32 = 0x1101;
+
+const FORMAT_B8G8R8X8_UNORM: u32 = 2;
+const MAX_SCANOUTS: usize = 16;
+const RESOURCE_ID: u32 = 1; // Zero is "no resource".
+
+// QEMU's default; used if the host does not say what it prefers.
+const DEFAULT_DISPLAY_SIZE: (u32, u32) = (1280, 800);
+
+// Requests, except for the memory entries of RESOURCE_ATTACH_BACKING, and
+// replies are small.
+const MSG_BUF_SIZE: u64 = 4096;
+
+// Backing memory is described by (phys_addr, len) entries, one per physically
+// contiguous run of pages; the entries are passed to the device in a buffer
+// of at most this size.
+const MAX_BACKING_ENTRIES_SIZE: u64 = 64 * 1024;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CtrlHeader {
+    type_: u32,
+    flags: u32,
+    fence_id: u64,
+    ctx_id: u32,
+    ring_idx: u8,
+    _padding: [u8; 3],
+}
+
+impl CtrlHeader {
+    fn new(type_: u32) -> Self {
+        Self {
+            type_,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DisplayOne {
+    rect: Rect,
+    enabled: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+struct RespDisplayInfo {
+    header: CtrlHeader,
+    pmodes: [DisplayOne; MAX_SCANOUTS],
+}
+
+#[repr(C)]
+struct ResourceCreate2d {
+    header: CtrlHeader,
+    resource_id: u32,
+    format: u32,
+    width: u32,
+    height: u32,
+}
+
+#[repr(C)]
+struct ResourceAttachBacking {
+    header: CtrlHeader,
+    resource_id: u32,
+    nr_entries: u32,
+    // Followed by nr_entries of MemEntry.
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MemEntry {
+    addr: u64,
+    length: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+struct SetScanout {
+    header: CtrlHeader,
+    rect: Rect,
+    scanout_id: u32,
+    resource_id: u32,
+}
+
+#[repr(C)]
+struct TransferToHost2d {
+    header: CtrlHeader,
+    rect: Rect,
+    offset: u64,
+    resource_id: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+struct ResourceFlush {
+    header: CtrlHeader,
+    rect: Rect,
+    resource_id: u32,
+    _padding: u32,
+}
+
+pub(super) struct Gpu {
+    dev: alloc::boxed::Box<VirtioDevice>,
+    display_size: (u32, u32),
+    // (width, height) of the resource, once a framebuffer is attached.
+    framebuffer: Option<(u32, u32)>,
+    // Requests and replies are copied via these, as virtqueue descriptors
+    // need physically contiguous memory.
+    request_buf: *mut u8,
+    reply_buf: *mut u8,
+}
+
+unsafe impl Send for Gpu {}
+
+impl Gpu {
+    fn self_init(&mut self) -> Result<(), ()> {
+        self.dev.acknowledge_driver(); // Step 3
+        self.negotiate_features()?; // Steps 4, 5, 6
+        self.dev.init_virtqueues(2, 2)?; // Step 7
+        self.dev.driver_ok(); // Step 8
+
+        self.get_display_info()
+    }
+
+    pub(super) fn init(dev: alloc::boxed::Box<VirtioDevice>) {
+        if dev.device_cfg.is_none() {
+            log::warn!("Skipping Virtio GPU device without device configuration.");
+            return;
+        }
+        if GPU.lock().is_some() {
+            log::info!("Skipping extra Virtio GPU device {:?}.", dev.pci_device.id);
+            return;
+        }
+
+        let request_buf = crate::mapper()
+            .alloc_contiguous_pages(MSG_BUF_SIZE)
+            .expect("Failed to allocate GPU buffers.");
+        let reply_buf = crate::mapper()
+            .alloc_contiguous_pages(MSG_BUF_SIZE)
+            .expect("Failed to allocate GPU buffers.");
+
+        let mut gpu = Gpu {
+            dev,
+            display_size: DEFAULT_DISPLAY_SIZE,
+            framebuffer: None,
+            request_buf: request_buf as usize as *mut u8,
+            reply_buf: reply_buf as usize as *mut u8,
+        };
+
+        if gpu.self_init().is_ok() {
+            log::debug!(
+                "Initialized Virtio GPU device {:?}: display: {}x{}.",
+                gpu.dev.pci_device.id,
+                gpu.display_size.0,
+                gpu.display_size.1
+            );
+            *GPU.lock() = Some(gpu);
+        } else {
+            moto_sys::SysRay::log("Failed to initialize Virtio GPU device.").ok();
+            gpu.dev.mark_failed();
+        }
+    }
+
+    // Step 4
+    fn negotiate_features(&mut self) -> Result<(), ()> {
+        let features_available = self.dev.get_available_features();
+        log::debug!("GPU device features: 0x{:x}", features_available);
+
+        if (features_available & super::virtio_device::VIRTIO_F_VERSION_1) == 0 {
+            log::warn!("Virtio GPU device {:?}: VIRTIO_F_VERSION_1 feature not available; features: 0x{:x}.",
+                self.dev.pci_device.id, features_available);
+            return Err(());
+        }
+
+        // VIRTIO_GPU_F_VIRGL, VIRTIO_GPU_F_EDID, etc. are not used.
+        let features_acked = super::virtio_device::VIRTIO_F_VERSION_1;
+        self.dev.write_enabled_features(features_acked);
+        self.dev.confirm_features()?;
+
+        let device_cfg = self.dev.device_cfg.as_ref().unwrap();
+        let cfg_bar: &PciBar = self.dev.pci_device.bars[device_cfg.bar as usize]
+            .as_ref()
+            .unwrap();
+
+        if cfg_bar.read_u32(device_cfg.offset as u64 + NUM_SCANOUTS_OFFSET) == 0 {
+            log::warn!(
+                "Virtio GPU device {:?}: no scanouts.",
+                self.dev.pci_device.id
+            );
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    // Adds the buffers to the virtqueue, notifies the device and waits
+    // until it is done with them; returns the number of bytes written.
+    #[inline(never)]
+    fn submit(&mut self, sg: &[UserData], outgoing: u16, incoming: u16) -> u32 {
+        let virtqueue = &mut self.dev.virtqueues[VIRTQ_CONTROL];
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        virtqueue.add_buf(sg, outgoing, incoming);
+
+        // Notify
+        let notify_cap = self.dev.notify_cfg.unwrap();
+        let cfg_bar: &PciBar = self.dev.pci_device.bars[notify_cap.bar as usize]
+            .as_ref()
+            .unwrap();
+        let notify_offset = notify_cap.offset as u64
+            + (notify_cap.notify_off_multiplier as u64 * virtqueue.queue_notify_off as u64);
+
+        cfg_bar.write_u16(notify_offset, virtqueue.queue_num);
+
+        let mut wait_failed = false;
+        while !virtqueue.more_used_deprecated() {
+            core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            if wait_failed {
+                super::nop(); // Don't spam the kernel if something is wrong here.
+            } else {
+                wait_failed = virtqueue.wait_deprecated().is_err();
+                if wait_failed {
+                    log::error!("virtqueue.wait() failed: switching to spinning.");
+                }
+            }
+        }
+        let written = virtqueue.consume_used_deprecated();
+
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::Acquire);
+        written
+    }
+
+    // Sends @request, followed by the bytes at @extra (physically contiguous),
+    // if any; returns the type of the reply, which is left in reply_buf.
+    fn command<T: Sized>(&mut self, request: T, extra: Option<UserData>) -> Result<u32, ()> {
+        let request_len = core::mem::size_of::<T>();
+        assert!(request_len as u64 <= MSG_BUF_SIZE);
+        unsafe {
+            (self.request_buf as *mut T).write_volatile(request);
+            (self.reply_buf as *mut CtrlHeader).write_volatile(CtrlHeader::default());
+        }
+
+        let request = UserData {
+            addr: self.request_buf as usize as u64,
+            len: request_len as u32,
+        };
+        let reply = UserData {
+            addr: self.reply_buf as usize as u64,
+            len: MSG_BUF_SIZE as u32,
+        };
+        let written = match extra {
+            Some(extra) => self.submit(&[request, extra, reply], 2, 1),
+            None => self.submit(&[request, reply], 1, 1),
+        };
+        if (written as usize) < core::mem::size_of::<CtrlHeader>() {
+            log::error!("VirtioGpu: bad reply length {}.", written);
+            return Err(());
+        }
+
+        Ok(unsafe { (self.reply_buf as *const CtrlHeader).read_volatile() }.type_)
+    }
+
+    // For commands that have no data in the reply.
+    fn command_nodata<T: Sized>(&mut self, request: T, extra: Option<UserData>) -> Result<(), ()> {
+        let cmd = unsafe { (&request as *const T as *const CtrlHeader).read() }.type_;
+        match self.command(request, extra)? {
+            RESP_OK_NODATA => Ok(()),
+            resp => {
+                log::warn!("VirtioGpu: command 0x{:x} failed: 0x{:x}.", cmd, resp);
+                Err(())
+            }
+        }
+    }
+
+    fn get_display_info(&mut self) -> Result<(), ()> {
+        if self.command(CtrlHeader::new(CMD_GET_DISPLAY_INFO), None)? != RESP_OK_DISPLAY_INFO {
+            log::warn!(
+                "Virtio GPU device {:?}: GET_DISPLAY_INFO failed.",
+                self.dev.pci_device.id
+            );
+            return Err(());
+        }
+
+        let info = unsafe { &*(self.reply_buf as *const RespDisplayInfo) };
+        let mode = &info.pmodes[0]; // Only the first scanout is used.
+        if mode.enabled != 0 && mode.rect.width > 0 && mode.rect.height > 0 {
+            self.display_size = (mode.rect.width, mode.rect.height);
+        }
+        Ok(())
+    }
+
+    fn attach_framebuffer(&mut self, virt_addr: u64, width: u32, height: u32) -> Result<(), ()> {
+        if self.framebuffer.is_some() || width == 0 || height == 0 {
+            return Err(());
+        }
+        let page_size = moto_sys::sys_mem::PAGE_SIZE_SMALL;
+        if virt_addr & (page_size - 1) != 0 {
+            return Err(());
+        }
+        let size = (width as u64) * (height as u64) * 4;
+
+        let mut entries: Vec<MemEntry> = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let len = (size - offset).min(page_size);
+            let phys_addr = crate::mapper().virt_to_phys(virt_addr + offset)?;
+            match entries.last_mut() {
+                Some(last) if last.addr + (last.length as u64) == phys_addr => {
+                    last.length += len as u32;
+                }
+                _ => entries.push(MemEntry {
+                    addr: phys_addr,
+                    length: len as u32,
+                    _padding: 0,
+                }),
+            }
+            offset += len;
+        }
+
+        let entries_size = (entries.len() * core::mem::size_of::<MemEntry>()) as u64;
+        if entries_size > MAX_BACKING_ENTRIES_SIZE {
+            log::warn!("VirtioGpu: framebuffer too fragmented.");
+            return Err(());
+        }
+        // The buffer is never freed, but there is only one framebuffer.
+        let entries_buf = crate::mapper().alloc_contiguous_pages(entries_size)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                entries.as_ptr(),
+                entries_buf as usize as *mut MemEntry,
+                entries.len(),
+            );
+        }
+
+        self.command_nodata(
+            ResourceCreate2d {
+                header: CtrlHeader::new(CMD_RESOURCE_CREATE_2D),
+                resource_id: RESOURCE_ID,
+                format: FORMAT_B8G8R8X8_UNORM,
+                width,
+                height,
+            },
+            None,
+        )?;
+        self.command_nodata(
+            ResourceAttachBacking {
+                header: CtrlHeader::new(CMD_RESOURCE_ATTACH_BACKING),
+                resource_id: RESOURCE_ID,
+                nr_entries: entries.len() as u32,
+            },
+            Some(UserData {
+                addr: entries_buf,
+                len: entries_size as u32,
+            }),
+        )?;
+        self.command_nodata(
+            SetScanout {
+                header: CtrlHeader::new(CMD_SET_SCANOUT),
+                rect: Rect {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                },
+                scanout_id: 0,
+                resource_id: RESOURCE_ID,
+            },
+            None,
+        )?;
+
+        self.framebuffer = Some((width, height));
+        self.flush(0, 0, width, height)
+    }
+
+    fn flush(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<(), ()> {
+        let Some((fb_width, fb_height)) = self.framebuffer else {
+            return Err(());
+        };
+        if x >= fb_width || y >= fb_height {
+            return Ok(());
+        }
+        let rect = Rect {
+            x,
+            y,
+            width: width.min(fb_width - x),
+            height: height.min(fb_height - y),
+        };
+        if rect.width == 0 || rect.height == 0 {
+            return Ok(());
+        }
+
+        self.command_nodata(
+            TransferToHost2d {
+                header: CtrlHeader::new(CMD_TRANSFER_TO_HOST_2D),
+                rect,
+                offset: (y as u64) * (fb_width as u64) * 4 + (x as u64) * 4,
+                resource_id: RESOURCE_ID,
+                _padding: 0,
+            },
+            None,
+        )?;
+        self.command_nodata(
+            ResourceFlush {
+                header: CtrlHeader::new(CMD_RESOURCE_FLUSH),
+                rect,
+                resource_id: RESOURCE_ID,
+                _padding: 0,
+            },
+            None,
+        )
+    }
+}
+
+static GPU: Mutex<Option<Gpu>> = Mutex::new(None);
+
+pub fn lsgpu() -> Vec<Arc<dyn super::GpuDevice>> {
+    if GPU.lock().is_some() {
+        vec![Arc::new(VirtioGpuDevice {})]
+    } else {
+        vec![]
+    }
+}
+
+pub(super) struct VirtioGpuDevice {}
+
+impl super::GpuDevice for VirtioGpuDevice {
+    fn display_size(&self) -> (u32, u32) {
+        GPU.lock().as_ref().unwrap().display_size
+    }
+
+    fn attach_framebuffer(&self, virt_addr: u64, width: u32, height: u32) -> Result<(), ()> {
+        GPU.lock()
+            .as_mut()
+            .unwrap()
+            .attach_framebuffer(virt_addr, width, height)
+    }
+
+    fn flush(&self, x: u32, y: u32, width: u32, height: u32) -> Result<(), ()> {
+        GPU.lock().as_mut().unwrap().flush(x, y, width, height)
+    }
+}
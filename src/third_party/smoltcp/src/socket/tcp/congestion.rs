@@ -0,0 +1,134 @@
+// Congestion control: how much unacknowledged data a socket may have in flight,
+// in addition to what the remote window allows. See RFC 5681 for the common
+// framework (slow start, congestion avoidance, fast retransmit).
+
+use crate::time::{Duration, Instant};
+
+mod cubic;
+mod reno;
+
+/// A congestion control algorithm.
+///
+/// See also the [set_congestion_control](super::Socket::set_congestion_control) method.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CongestionControl {
+    /// No congestion control: only the remote window limits sending.
+    #[default]
+    None,
+    /// NewReno-style AIMD, as described in RFC 5681.
+    Reno,
+    /// CUBIC, as described in RFC 9438; better suited than Reno to paths with
+    /// a large bandwidth-delay product.
+    Cubic,
+}
+
+/// The initial window, see RFC 6928.
+fn initial_window(mss: usize) -> usize {
+    (10 * mss).min((2 * mss).max(14600))
+}
+
+#[allow(unused_variables)]
+trait Controller {
+    /// The number of bytes that may be in flight.
+    fn window(&self) -> usize;
+
+    /// The slow start threshold.
+    fn ssthresh(&self) -> usize;
+
+    /// `len` previously unacknowledged bytes were acknowledged; `rtt` is the
+    /// current smoothed round-trip time.
+    fn on_ack(&mut self, now: Instant, len: usize, rtt: Duration);
+
+    /// The third duplicate ACK was received: a segment is about to be
+    /// fast-retransmitted.
+    fn on_duplicate_ack(&mut self, now: Instant);
+
+    /// The retransmission timer expired.
+    fn on_retransmit(&mut self, now: Instant);
+
+    fn set_mss(&mut self, mss: usize);
+
+    /// Called with each window the remote side advertises; there is no point
+    /// in growing the congestion window past what the remote lets us send.
+    fn set_remote_window(&mut self, remote_window: usize) {}
+}
+
+#[derive(Debug)]
+pub(super) enum AnyController {
+    None,
+    Reno(reno::Reno),
+    Cubic(cubic::Cubic),
+}
+
+impl AnyController {
+    pub(super) fn new(algorithm: CongestionControl, mss: usize) -> Self {
+        match algorithm {
+            CongestionControl::None => AnyController::None,
+            CongestionControl::Reno => AnyController::Reno(reno::Reno::new(mss)),
+            CongestionControl::Cubic => AnyController::Cubic(cubic::Cubic::new(mss)),
+        }
+    }
+
+    pub(super) fn algorithm(&self) -> CongestionControl {
+        match self {
+            AnyController::None => CongestionControl::None,
+            AnyController::Reno(_) => CongestionControl::Reno,
+            AnyController::Cubic(_) => CongestionControl::Cubic,
+        }
+    }
+
+    fn inner(&self) -> Option<&dyn Controller> {
+        match self {
+            AnyController::None => None,
+            AnyController::Reno(reno) => Some(reno),
+            AnyController::Cubic(cubic) => Some(cubic),
+        }
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Controller> {
+        match self {
+            AnyController::None => None,
+            AnyController::Reno(reno) => Some(reno),
+            AnyController::Cubic(cubic) => Some(cubic),
+        }
+    }
+
+    pub(super) fn window(&self) -> usize {
+        self.inner().map_or(usize::MAX, |c| c.window())
+    }
+
+    pub(super) fn ssthresh(&self) -> usize {
+        self.inner().map_or(usize::MAX, |c| c.ssthresh())
+    }
+
+    pub(super) fn on_ack(&mut self, now: Instant, len: usize, rtt: Duration) {
+        if let Some(c) = self.inner_mut() {
+            c.on_ack(now, len, rtt)
+        }
+    }
+
+    pub(super) fn on_duplicate_ack(&mut self, now: Instant) {
+        if let Some(c) = self.inner_mut() {
+            c.on_duplicate_ack(now)
+        }
+    }
+
+    pub(super) fn on_retransmit(&mut self, now: Instant) {
+        if let Some(c) = self.inner_mut() {
+            c.on_retransmit(now)
+        }
+    }
+
+    pub(super) fn set_mss(&mut self, mss: usize) {
+        if let Some(c) = self.inner_mut() {
+            c.set_mss(mss)
+        }
+    }
+
+    pub(super) fn set_remote_window(&mut self, remote_window: usize) {
+        if let Some(c) = self.inner_mut() {
+            c.set_remote_window(remote_window)
+        }
+    }
+}
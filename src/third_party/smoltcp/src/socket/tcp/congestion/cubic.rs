@@ -0,0 +1,220 @@
+use super::{initial_window, Controller};
+use crate::time::{Duration, Instant};
+
+// Constants from RFC 9438.
+const C: f64 = 0.4;
+const BETA: f64 = 0.7;
+// The Reno-friendly additive increase: 3 * (1 - BETA) / (1 + BETA).
+const ALPHA: f64 = 3.0 * (1.0 - BETA) / (1.0 + BETA);
+
+#[derive(Debug)]
+pub(crate) struct Cubic {
+    // Window sizes are in bytes; f64 to accumulate fractional growth.
+    cwnd: f64,
+    ssthresh: usize,
+    mss: usize,
+    max_cwnd: usize,
+    // The window before the last congestion event.
+    w_max: f64,
+    // The Reno-friendly window estimate.
+    w_est: f64,
+    // When the current congestion avoidance epoch started, and the time (in seconds)
+    // from then until the window function reaches w_max again.
+    epoch_start: Option<Instant>,
+    k: f64,
+}
+
+// core has no cbrt(); Newton's method converges quickly enough for the
+// magnitudes here (window sizes in segments).
+fn cube_root(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut y = if x > 1.0 { x } else { 1.0 };
+    for _ in 0..100 {
+        let next = (2.0 * y + x / (y * y)) / 3.0;
+        let delta = if next > y { next - y } else { y - next };
+        y = next;
+        if delta < 1e-9 * y {
+            break;
+        }
+    }
+    y
+}
+
+impl Cubic {
+    pub(crate) fn new(mss: usize) -> Self {
+        Cubic {
+            cwnd: initial_window(mss) as f64,
+            ssthresh: usize::MAX,
+            mss,
+            max_cwnd: usize::MAX,
+            w_max: 0.0,
+            w_est: 0.0,
+            epoch_start: None,
+            k: 0.0,
+        }
+    }
+
+    // W_cubic(t), in bytes.
+    fn w_cubic(&self, t: f64) -> f64 {
+        let d = t - self.k;
+        C * d * d * d * (self.mss as f64) + self.w_max
+    }
+
+    fn congestion_event(&mut self) {
+        // Fast convergence: release bandwidth for new flows if the window
+        // did not reach its previous maximum.
+        self.w_max = if self.cwnd < self.w_max {
+            self.cwnd * (1.0 + BETA) / 2.0
+        } else {
+            self.cwnd
+        };
+        self.ssthresh = ((self.cwnd * BETA) as usize).max(2 * self.mss);
+        self.epoch_start = None;
+    }
+}
+
+impl Controller for Cubic {
+    fn window(&self) -> usize {
+        self.cwnd as usize
+    }
+
+    fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+
+    fn on_ack(&mut self, now: Instant, len: usize, rtt: Duration) {
+        let mss = self.mss as f64;
+
+        if (self.cwnd as usize) < self.ssthresh {
+            // Slow start, with Appropriate Byte Counting (RFC 3465, L = 2).
+            self.cwnd += len.min(2 * self.mss) as f64;
+        } else {
+            let epoch_start = match self.epoch_start {
+                Some(epoch_start) => epoch_start,
+                None => {
+                    self.epoch_start = Some(now);
+                    self.k = if self.cwnd < self.w_max {
+                        cube_root((self.w_max - self.cwnd) / (mss * C))
+                    } else {
+                        self.w_max = self.cwnd;
+                        0.0
+                    };
+                    self.w_est = self.cwnd;
+                    now
+                }
+            };
+
+            let t = (now - epoch_start).total_micros() as f64 / 1_000_000.0;
+            let rtt = rtt.total_micros() as f64 / 1_000_000.0;
+
+            self.w_est += ALPHA * mss * (len as f64) / self.cwnd;
+
+            let w_cubic = self.w_cubic(t);
+            if w_cubic < self.w_est {
+                // The Reno-friendly region.
+                self.cwnd = self.w_est;
+            } else {
+                let target = self.w_cubic(t + rtt).max(self.cwnd).min(self.cwnd * 1.5);
+                self.cwnd += (target - self.cwnd) * (len as f64) / self.cwnd;
+            }
+        }
+
+        if self.cwnd > self.max_cwnd as f64 {
+            self.cwnd = self.max_cwnd as f64;
+        }
+    }
+
+    fn on_duplicate_ack(&mut self, _now: Instant) {
+        self.congestion_event();
+        self.cwnd = self.ssthresh as f64;
+    }
+
+    fn on_retransmit(&mut self, _now: Instant) {
+        self.congestion_event();
+        self.cwnd = self.mss as f64;
+    }
+
+    fn set_mss(&mut self, mss: usize) {
+        if self.cwnd as usize == initial_window(self.mss) {
+            self.cwnd = initial_window(mss) as f64;
+        }
+        self.mss = mss;
+    }
+
+    fn set_remote_window(&mut self, remote_window: usize) {
+        if self.max_cwnd == usize::MAX || self.max_cwnd < remote_window {
+            self.max_cwnd = remote_window.max(2 * self.mss);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MSS: usize = 1000;
+
+    #[test]
+    fn test_cube_root() {
+        for x in [0.001, 1.0, 8.0, 1000.0, 123456.0] {
+            let y = cube_root(x);
+            assert!((y * y * y - x).abs() < 1e-6 * x);
+        }
+        assert_eq!(cube_root(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_decrease() {
+        let now = Instant::from_millis(0);
+        let mut cubic = Cubic::new(MSS);
+        cubic.on_duplicate_ack(now);
+        assert_eq!(cubic.window(), 7 * MSS);
+        assert_eq!(cubic.ssthresh(), 7 * MSS);
+
+        cubic.on_retransmit(now);
+        assert_eq!(cubic.window(), MSS);
+        assert_eq!(cubic.ssthresh(), (7 * MSS * 7) / 10);
+    }
+
+    #[test]
+    fn test_concave_then_convex_growth() {
+        let rtt = Duration::from_millis(100);
+        let mut cubic = Cubic::new(MSS);
+        cubic.cwnd = (100 * MSS) as f64;
+        cubic.on_duplicate_ack(Instant::from_millis(0));
+        assert_eq!(cubic.window(), 70 * MSS);
+
+        // Feed a window of ACKs per RTT; the window approaches w_max,
+        // flattens out around it, and then grows past it.
+        let mut now = Instant::from_millis(0);
+        let mut windows = std::vec::Vec::new();
+        for _ in 0..100 {
+            let acks = cubic.window() / MSS;
+            for _ in 0..acks {
+                cubic.on_ack(now, MSS, rtt);
+            }
+            windows.push(cubic.window());
+            now += rtt;
+        }
+
+        assert!(windows.windows(2).all(|w| w[0] <= w[1]));
+        let k = cubic_k(100 * MSS, 70 * MSS);
+        let at_k = windows[(k * 10.0) as usize];
+        assert!(at_k > 95 * MSS && at_k < 105 * MSS, "{at_k}");
+        assert!(*windows.last().unwrap() > 150 * MSS);
+    }
+
+    fn cubic_k(w_max: usize, cwnd: usize) -> f64 {
+        cube_root(((w_max - cwnd) / MSS) as f64 / C)
+    }
+
+    #[test]
+    fn test_slow_start() {
+        let now = Instant::from_millis(0);
+        let mut cubic = Cubic::new(MSS);
+        cubic.on_ack(now, 4 * MSS, Duration::from_millis(10));
+        assert_eq!(cubic.window(), 12 * MSS);
+    }
+}
@@ -0,0 +1,126 @@
+use super::{initial_window, Controller};
+use crate::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub(crate) struct Reno {
+    cwnd: usize,
+    ssthresh: usize,
+    mss: usize,
+    max_cwnd: usize,
+    // Bytes acknowledged since cwnd last grew, in congestion avoidance.
+    acked: usize,
+}
+
+impl Reno {
+    pub(crate) fn new(mss: usize) -> Self {
+        Reno {
+            cwnd: initial_window(mss),
+            ssthresh: usize::MAX,
+            mss,
+            max_cwnd: usize::MAX,
+            acked: 0,
+        }
+    }
+
+    fn congestion_event(&mut self) {
+        self.ssthresh = (self.cwnd / 2).max(2 * self.mss);
+        self.acked = 0;
+    }
+}
+
+impl Controller for Reno {
+    fn window(&self) -> usize {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+
+    fn on_ack(&mut self, _now: Instant, len: usize, _rtt: Duration) {
+        if self.cwnd < self.ssthresh {
+            // Slow start, with Appropriate Byte Counting (RFC 3465, L = 2).
+            self.cwnd += len.min(2 * self.mss);
+        } else {
+            // Congestion avoidance: one MSS per window acknowledged.
+            self.acked += len;
+            if self.acked >= self.cwnd {
+                self.acked -= self.cwnd;
+                self.cwnd += self.mss;
+            }
+        }
+        self.cwnd = self.cwnd.min(self.max_cwnd);
+    }
+
+    fn on_duplicate_ack(&mut self, _now: Instant) {
+        self.congestion_event();
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_retransmit(&mut self, _now: Instant) {
+        self.congestion_event();
+        self.cwnd = self.mss;
+    }
+
+    fn set_mss(&mut self, mss: usize) {
+        if self.cwnd == initial_window(self.mss) {
+            self.cwnd = initial_window(mss);
+        }
+        self.mss = mss;
+    }
+
+    fn set_remote_window(&mut self, remote_window: usize) {
+        if self.max_cwnd == usize::MAX || self.max_cwnd < remote_window {
+            self.max_cwnd = remote_window.max(2 * self.mss);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MSS: usize = 1000;
+
+    #[test]
+    fn test_slow_start_then_avoidance() {
+        let now = Instant::from_millis(0);
+        let rtt = Duration::from_millis(10);
+        let mut reno = Reno::new(MSS);
+        assert_eq!(reno.window(), 10 * MSS);
+
+        reno.on_ack(now, 2 * MSS, rtt);
+        assert_eq!(reno.window(), 12 * MSS);
+
+        reno.on_duplicate_ack(now);
+        assert_eq!(reno.window(), 6 * MSS);
+        assert_eq!(reno.ssthresh(), 6 * MSS);
+
+        // A full window of ACKs grows cwnd by one MSS.
+        for _ in 0..5 {
+            reno.on_ack(now, MSS, rtt);
+        }
+        assert_eq!(reno.window(), 6 * MSS);
+        reno.on_ack(now, MSS, rtt);
+        assert_eq!(reno.window(), 7 * MSS);
+    }
+
+    #[test]
+    fn test_retransmit_timeout() {
+        let now = Instant::from_millis(0);
+        let mut reno = Reno::new(MSS);
+        reno.on_retransmit(now);
+        assert_eq!(reno.window(), MSS);
+        assert_eq!(reno.ssthresh(), 5 * MSS);
+    }
+
+    #[test]
+    fn test_remote_window_limit() {
+        let now = Instant::from_millis(0);
+        let rtt = Duration::from_millis(10);
+        let mut reno = Reno::new(MSS);
+        reno.set_remote_window(11 * MSS);
+        reno.on_ack(now, 2 * MSS, rtt);
+        assert_eq!(reno.window(), 11 * MSS);
+    }
+}
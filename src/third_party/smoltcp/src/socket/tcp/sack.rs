@@ -0,0 +1,176 @@
+// The sender's record of data the remote side has selectively acknowledged
+// (RFC 2018), so that retransmissions can skip it.
+
+use crate::wire::TcpSeqNumber;
+
+const MAX_BLOCKS: usize = 4;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Scoreboard {
+    // Disjoint, non-adjacent [start, end) ranges above the cumulative ACK,
+    // sorted by start. Only the lowest ones are kept: those are the ones
+    // retransmissions run into first.
+    blocks: [Option<(TcpSeqNumber, TcpSeqNumber)>; MAX_BLOCKS],
+}
+
+impl Scoreboard {
+    pub(super) fn clear(&mut self) {
+        self.blocks = [None; MAX_BLOCKS];
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.blocks[0].is_none()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (TcpSeqNumber, TcpSeqNumber)> + '_ {
+        self.blocks.iter().map_while(|block| *block)
+    }
+
+    /// Records a SACK block. Blocks outside of `snd_una..snd_max` (the
+    /// unacknowledged data that was sent) are clipped or ignored.
+    pub(super) fn add(
+        &mut self,
+        start: TcpSeqNumber,
+        end: TcpSeqNumber,
+        snd_una: TcpSeqNumber,
+        snd_max: TcpSeqNumber,
+    ) {
+        let start = start.max(snd_una);
+        let end = end.min(snd_max);
+        if start >= end {
+            return;
+        }
+
+        let mut merged: [Option<(TcpSeqNumber, TcpSeqNumber)>; MAX_BLOCKS + 1] =
+            [None; MAX_BLOCKS + 1];
+        let mut len = 0;
+        let mut new_block = Some((start, end));
+        for (block_start, block_end) in self.iter() {
+            if let Some((start, end)) = new_block {
+                if end < block_start {
+                    merged[len] = Some((start, end));
+                    len += 1;
+                    new_block = None;
+                } else if start <= block_end {
+                    // Overlapping or adjacent: fold the existing block in.
+                    new_block = Some((start.min(block_start), end.max(block_end)));
+                    continue;
+                }
+            }
+            merged[len] = Some((block_start, block_end));
+            len += 1;
+        }
+        if let Some(block) = new_block {
+            merged[len] = Some(block);
+        }
+
+        self.blocks.copy_from_slice(&merged[..MAX_BLOCKS]);
+    }
+
+    /// Drops what the cumulative ACK now covers.
+    pub(super) fn ack(&mut self, snd_una: TcpSeqNumber) {
+        let mut kept = [None; MAX_BLOCKS];
+        let mut len = 0;
+        for (start, end) in self.iter() {
+            if end > snd_una {
+                kept[len] = Some((start.max(snd_una), end));
+                len += 1;
+            }
+        }
+        self.blocks = kept;
+    }
+
+    /// Returns the end of the SACKed block that `seq` is in, or `seq`.
+    pub(super) fn skip(&self, seq: TcpSeqNumber) -> TcpSeqNumber {
+        self.iter()
+            .find(|(start, end)| *start <= seq && seq < *end)
+            .map_or(seq, |(_, end)| end)
+    }
+
+    /// Returns the start of the first SACKed block above `seq`.
+    pub(super) fn next_start(&self, seq: TcpSeqNumber) -> Option<TcpSeqNumber> {
+        self.iter()
+            .map(|(start, _)| start)
+            .find(|start| *start >= seq)
+    }
+
+    /// Returns how many bytes below `seq` were SACKed.
+    pub(super) fn sacked_below(&self, seq: TcpSeqNumber) -> usize {
+        self.iter()
+            .filter(|(start, _)| *start < seq)
+            .map(|(start, end)| end.min(seq) - start)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seq(n: i32) -> TcpSeqNumber {
+        TcpSeqNumber(n)
+    }
+
+    fn blocks(sb: &Scoreboard) -> Vec<(i32, i32)> {
+        sb.iter().map(|(start, end)| (start.0, end.0)).collect()
+    }
+
+    #[test]
+    fn test_add_merge() {
+        let mut sb = Scoreboard::default();
+        assert!(sb.is_empty());
+        sb.add(seq(300), seq(400), seq(100), seq(1000));
+        sb.add(seq(150), seq(200), seq(100), seq(1000));
+        sb.add(seq(500), seq(600), seq(100), seq(1000));
+        assert_eq!(blocks(&sb), vec![(150, 200), (300, 400), (500, 600)]);
+
+        // Adjacent and overlapping blocks merge.
+        sb.add(seq(200), seq(310), seq(100), seq(1000));
+        assert_eq!(blocks(&sb), vec![(150, 400), (500, 600)]);
+
+        // Out of range parts are clipped.
+        sb.add(seq(50), seq(120), seq(100), seq(1000));
+        sb.add(seq(900), seq(2000), seq(100), seq(1000));
+        assert_eq!(
+            blocks(&sb),
+            vec![(100, 120), (150, 400), (500, 600), (900, 1000)]
+        );
+
+        // Only the lowest blocks are kept.
+        sb.add(seq(700), seq(800), seq(100), seq(1000));
+        assert_eq!(
+            blocks(&sb),
+            vec![(100, 120), (150, 400), (500, 600), (700, 800)]
+        );
+    }
+
+    #[test]
+    fn test_ack() {
+        let mut sb = Scoreboard::default();
+        sb.add(seq(300), seq(400), seq(100), seq(1000));
+        sb.add(seq(500), seq(600), seq(100), seq(1000));
+        sb.ack(seq(350));
+        assert_eq!(blocks(&sb), vec![(350, 400), (500, 600)]);
+        sb.ack(seq(600));
+        assert!(sb.is_empty());
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut sb = Scoreboard::default();
+        sb.add(seq(300), seq(400), seq(100), seq(1000));
+        sb.add(seq(500), seq(600), seq(100), seq(1000));
+
+        assert_eq!(sb.skip(seq(200)), seq(200));
+        assert_eq!(sb.skip(seq(300)), seq(400));
+        assert_eq!(sb.skip(seq(550)), seq(600));
+
+        assert_eq!(sb.next_start(seq(100)), Some(seq(300)));
+        assert_eq!(sb.next_start(seq(400)), Some(seq(500)));
+        assert_eq!(sb.next_start(seq(600)), None);
+
+        assert_eq!(sb.sacked_below(seq(300)), 0);
+        assert_eq!(sb.sacked_below(seq(350)), 50);
+        assert_eq!(sb.sacked_below(seq(1000)), 200);
+    }
+}
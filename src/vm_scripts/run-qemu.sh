@@ -6,9 +6,14 @@ qemu-system-x86_64 -m 256M -enable-kvm -cpu host -smp 4 \
   -drive file=moturus.full.img,if=none,id=drive0,format=raw \
   -netdev tap,ifname=moto-tap,script=no,downscript=no,id=nic0 \
   -device virtio-net-pci,disable-legacy=on,mac=a4:a1:c2:00:00:01,netdev=nic0 \
+  -chardev file,id=crash,path=crash.log,append=on \
+  -device virtio-serial-pci,disable-legacy=on -device virtconsole,chardev=crash \
   -no-reboot -nographic
 
 #  -netdev user,host=10.0.2.10,hostfwd=tcp:127.0.0.1:10023-:5542,id=nic0 \
 
 #  -netdev tap,ifname=moto-tap-2,script=no,downscript=no,id=nic1 \
 #  -device virtio-net-pci,disable-legacy=on,mac=a4:a1:c2:00:00:02,netdev=nic1 \
+
+# To use the graphical display (see `sysbox display`), replace -nographic with:
+#  -device virtio-gpu-pci -serial stdio \